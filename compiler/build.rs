@@ -1,6 +1,6 @@
 use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 fn main() {
     // Re-run if the grammar changes
@@ -26,4 +26,72 @@ fn main() {
     );
     
     fs::write(&generated_file, pest_code).expect("Failed to write generated parser");
+
+    embed_templates(Path::new(&out_dir));
+    embed_runtime(Path::new(&out_dir));
+}
+
+/// Embed the built-in templates, so the compiler finds them wherever it runs
+///
+/// Writes `builtin_templates.rs`: every `.tera` file below `templates`, by its
+/// path below it, sorted so the list is the same on every build.
+fn embed_templates(out_dir: &Path) {
+    println!("cargo:rerun-if-changed=templates");
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("templates");
+    let mut files = Vec::new();
+    collect_files(&root, &["tera"], &mut files);
+    files.sort();
+    assert!(!files.is_empty(), "No templates found in {}", root.display());
+
+    let mut code = String::from("&[\n");
+    for path in &files {
+        let name = path.strip_prefix(&root).unwrap().components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        code.push_str(&format!("    ({:?}, include_str!({:?})),\n", name, path.to_string_lossy()));
+    }
+    code.push(']');
+    fs::write(out_dir.join("builtin_templates.rs"), code).expect("Failed to write the built-in templates");
+}
+
+/// Embed the sources of the `kumeo-runtime` crate, vendored next to every Rust agent
+///
+/// Writes `runtime_sources.rs`: its manifest, build script, protocol and
+/// sources, by their path below `runtime`, sorted like the templates.
+fn embed_runtime(out_dir: &Path) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../runtime");
+    for dir in ["Cargo.toml", "build.rs", "proto", "src"] {
+        println!("cargo:rerun-if-changed={}", root.join(dir).display());
+    }
+
+    let mut files = vec![root.join("Cargo.toml"), root.join("build.rs")];
+    collect_files(&root.join("proto"), &["proto"], &mut files);
+    collect_files(&root.join("src"), &["rs"], &mut files);
+    files.sort();
+
+    let mut code = String::from("&[\n");
+    for path in &files {
+        let name = path.strip_prefix(&root).unwrap().components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let path = path.canonicalize().expect("Failed to read the runtime");
+        code.push_str(&format!("    ({:?}, include_str!({:?})),\n", name, path.to_string_lossy()));
+    }
+    code.push(']');
+    fs::write(out_dir.join("runtime_sources.rs"), code).expect("Failed to write the runtime sources");
+}
+
+/// Files below a directory with one of some extensions
+fn collect_files(dir: &Path, extensions: &[&str], files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).expect("Failed to read the directory") {
+        let path = entry.expect("Failed to read the directory").path();
+        if path.is_dir() {
+            collect_files(&path, extensions, files);
+        } else if path.extension().is_some_and(|ext| extensions.iter().any(|wanted| ext == *wanted)) {
+            files.push(path);
+        }
+    }
 }
@@ -4,6 +4,14 @@ pub mod types;
 
 // Re-exportar los tipos principales para facilitar el acceso
 pub use types::{
-    Program, Workflow, Subworkflow, Source, Target, Context, Model, Schema, Agent, AgentType,
-    Deployment, ResourceRequirements, Argument, Value
+    Program, Constant, Span, TopicSchema, Workflow, WorkflowMode, WorkflowTest, Subworkflow, SubworkflowCall, Source, Target, Encoding, Context, Model, Schema, Agent, AgentType,
+    Deployment, ResourceRequirements, Storage, Infrastructure, CloudProvider, Platform, Scaling, Monitor, MonitorAlerts, RolloutStrategy, CanaryStrategy, AnalysisCondition, RetryPolicy, FallbackConfig, CompensationConfig,
+    QualityMetric, QualityMonitorConfig, Reducer, Reduction, AggregationWindow, AggregatorConfig, RuleAction, EngineRule, RuleEngineConfig, NetworkFormat, BayesianNetworkConfig, CustomAgentConfig, Scaler, FieldMapping, DataNormalizerConfig, Imputation, MissingValueHandlerConfig, DriftMethod, ModelDriftConfig, FeatureStoreConfig, InferenceBackend, InferenceServerConfig, FailoverTrigger, ChainedProvider, ProviderChain, LLMProviderKind, LLMProvider,
+    GuardrailAction, GuardrailCheck, GuardrailRule, GuardrailsConfig, MemoryStore, MemoryConfig, LlmBudgetConfig, HashRoutingConfig, RouteTableConfig, SlaBreachAction, EscalationStep, HumanReviewConfig, ReviewAuditConfig, OidcAuthConfig,
+    Argument, Value, Expr, CompareOp, parse_duration_secs, parse_duration_millis, UNTIL_SEQUENCE_OPTION, WEBHOOK_METHOD_OPTION,
+    FILE_WATCH_OPTION, PRELOAD_OPTION, IMAGE_OPTION, WHEN_OPTION, ASSERT_OPTION, RETRY_OPTION, FALLBACK_OPTION, COMPENSATE_OPTION, INPUT_OPTION, OUTPUT_OPTION, SOURCE_TOPIC, OUTPUT_SCHEMA_OPTION, INPUT_SCHEMA_OPTION, SCHEMA_VERSION_OPTION,
+    DRIFT_OPTION, FEATURES_OPTION, PROVIDER_OPTION, PROVIDERS_OPTION, ENGINE_OPTION, GUARDRAILS_OPTION, MEMORY_OPTION, BUDGET_OPTION, STRATEGY_OPTION, RULES_OPTION, SAMPLE_RATE_OPTION, METRICS_OPTION, THRESHOLDS_OPTION, WINDOW_OPTION, FIELDS_OPTION, GROUP_BY_OPTION, REDUCE_OPTION, MAPPINGS_OPTION, SCALE_OPTION, DEFAULTS_OPTION, IMPUTE_OPTION, NETWORK_PATH_OPTION, QUERY_OPTION, LANGUAGE_OPTION,
+    SLA_OPTION, ESCALATION_OPTION, DELEGATION_OPTION, SLA_BREACH_OPTION, REVIEW_TIMEOUT_OPTION, ON_TIMEOUT_OPTION, NOTIFICATIONS_OPTION, UI_OPTION, AUDIT_OPTION, AUTH_OPTION,
+    INPUT_TOPIC_OPTION, OUTPUT_TOPIC_OPTION, ENCODING_OPTION, AgentTopics, AgentEncodings, SIGNATURE_PARAMS, declared_signature, Placeholder, placeholders, secret_variable, validate_namespace, validate_label, validate_annotation, ACTIVE_SLOT_ANNOTATION, validate_registry, validate_image_tag, validate_image, split_image, interpolate,
+    EXPECT_TOPIC, EXPECT_OUTCOME,
 };
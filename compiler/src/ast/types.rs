@@ -1,12 +1,24 @@
 //! Abstract Syntax Tree (AST) for the Kumeo DSL.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt;
 
+use crate::i18n::Message;
+use crate::message;
+
 /// Represents a Kumeo program, which is a collection of workflows and subworkflows.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Program {
+    /// The files imported by the program, relative to its own file.
+    #[serde(default)]
+    pub imports: Vec<String>,
+    /// The constants declared at program scope, in declaration order.
+    #[serde(default)]
+    pub constants: Vec<Constant>,
+    /// The message schemas declared per topic, in declaration order.
+    #[serde(default)]
+    pub schemas: Vec<TopicSchema>,
     /// The workflows defined in the program.
     pub workflows: Vec<Workflow>,
     /// The subworkflows defined in the program.
@@ -17,10 +29,273 @@ impl Program {
     /// Create a new, empty program.
     pub fn new() -> Self {
         Self {
+            imports: Vec::new(),
+            constants: Vec::new(),
+            schemas: Vec::new(),
             workflows: Vec::new(),
             subworkflows: Vec::new(),
         }
     }
+
+    /// Record the file the constants, workflows, subworkflows, agents and options of the program were parsed from.
+    pub fn set_file(&mut self, file: &str) {
+        let spans = self
+            .constants
+            .iter_mut()
+            .map(|constant| &mut constant.span)
+            .chain(self.workflows.iter_mut().flat_map(|workflow| {
+                std::iter::once(&mut workflow.span)
+                    .chain(
+                        workflow
+                            .agents
+                            .iter_mut()
+                            .chain(workflow.preprocessors.iter_mut().flatten())
+                            .flat_map(Agent::spans_mut),
+                    )
+                    .chain(workflow.tests.iter_mut().map(|test| &mut test.span))
+            }))
+            .chain(self.subworkflows.iter_mut().flat_map(|subworkflow| {
+                std::iter::once(&mut subworkflow.span).chain(subworkflow.agents.iter_mut().flat_map(Agent::spans_mut))
+            }));
+        for span in spans {
+            span.file = Some(file.to_string());
+        }
+    }
+
+    /// Append the constants, workflows and subworkflows of another program.
+    pub fn merge(&mut self, other: Program) {
+        self.constants.extend(other.constants);
+        self.schemas.extend(other.schemas);
+        self.workflows.extend(other.workflows);
+        self.subworkflows.extend(other.subworkflows);
+    }
+
+    /// The valid schemas of the `schemas:` block, by topic; the last declaration of a topic wins.
+    pub fn topic_schemas(&self) -> HashMap<String, Schema> {
+        self.schemas
+            .iter()
+            .filter_map(|declared| Some((declared.topic.clone(), Schema::from_value(&declared.fields).ok()?)))
+            .collect()
+    }
+
+    /// Interpolate the `{NAME}` references to constants in the strings of the program.
+    ///
+    /// Covers the constants themselves, the `schemas:` topics, the workflow
+    /// sources and targets, the subjects bound to subworkflow invocations and
+    /// the agent configurations. In subworkflow agents the names of the
+    /// subworkflow inputs and outputs are kept for the invocations to bind.
+    /// Only strings, numbers and booleans can be interpolated. Fails with a
+    /// description of the first unresolved reference.
+    pub fn interpolate_constants(&mut self) -> std::result::Result<(), Message> {
+        let substituted = self
+            .constant_values()
+            .map_err(|name| message!("undefined constant ${}", name))?;
+
+        // A constant may interpolate the constants declared before it
+        let mut values = HashMap::new();
+        for constant in &self.constants {
+            let mut value = substituted[&constant.name].clone();
+            Interpolation { constants: &values, kept: &[] }.value(&mut value)?;
+            values.insert(constant.name.clone(), value);
+        }
+
+        let program = Interpolation { constants: &values, kept: &[] };
+        for schema in &mut self.schemas {
+            program.text(&mut schema.topic)?;
+        }
+        for workflow in &mut self.workflows {
+            if let Some(Source::NATS(topic, options)
+            | Source::Kafka(topic, options)
+            | Source::MQTT(topic, options)
+            | Source::HTTP(topic, options)
+            | Source::File(topic, options)) = &mut workflow.source
+            {
+                program.text(topic)?;
+                for option in options.iter_mut().flat_map(|options| options.values_mut()) {
+                    program.text(option)?;
+                }
+            }
+            if let Some(Target::NATS(topic, options)
+            | Target::Kafka(topic, options)
+            | Target::MQTT(topic, options)
+            | Target::File(topic, options)) = &mut workflow.target
+            {
+                program.text(topic)?;
+                for option in options.iter_mut().flat_map(|options| options.values_mut()) {
+                    program.text(option)?;
+                }
+            }
+            for subject in workflow.calls.iter_mut().flat_map(|call| call.input.iter_mut().chain(call.output.iter_mut())) {
+                program.text(subject)?;
+            }
+            for agent in workflow.agents.iter_mut().chain(workflow.preprocessors.iter_mut().flatten()) {
+                program.agent(agent)?;
+            }
+        }
+        for subworkflow in &mut self.subworkflows {
+            let parameters: Vec<String> = subworkflow
+                .input
+                .iter()
+                .chain(subworkflow.output.iter())
+                .flatten()
+                .cloned()
+                .collect();
+            let scope = Interpolation { constants: &values, kept: &parameters };
+            for agent in &mut subworkflow.agents {
+                scope.agent(agent)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve the constants in declaration order.
+    ///
+    /// A constant may reference the constants declared before it. Fails with
+    /// the name of the first undefined reference.
+    pub fn constant_values(&self) -> std::result::Result<HashMap<String, Value>, String> {
+        let mut values = HashMap::new();
+        for constant in &self.constants {
+            let mut value = constant.value.clone();
+            value.substitute(&values)?;
+            values.insert(constant.name.clone(), value);
+        }
+        Ok(values)
+    }
+
+    /// Replace the `$NAME` references in agent configurations with the values of the constants.
+    ///
+    /// Fails with the name of the first undefined constant.
+    pub fn substitute_constants(&mut self) -> std::result::Result<(), String> {
+        let values = self.constant_values()?;
+        let workflow_agents = self.workflows.iter_mut().flat_map(|workflow| {
+            workflow
+                .agents
+                .iter_mut()
+                .chain(workflow.preprocessors.iter_mut().flatten())
+        });
+        let subworkflow_agents = self.subworkflows.iter_mut().flat_map(|subworkflow| subworkflow.agents.iter_mut());
+
+        for agent in workflow_agents.chain(subworkflow_agents) {
+            for argument in &mut agent.config {
+                let (Argument::Named(_, value, _) | Argument::Positional(value, _)) = argument;
+                value.substitute(&values)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Interpolation of program constants into the strings of a scope.
+struct Interpolation<'a> {
+    /// The constant values by name.
+    constants: &'a HashMap<String, Value>,
+    /// Names kept as written, such as the parameters of a subworkflow.
+    kept: &'a [String],
+}
+
+impl Interpolation<'_> {
+    fn lookup(&self, name: &str) -> Option<String> {
+        if self.kept.iter().any(|kept| kept == name) {
+            return Some(format!("{{{}}}", name));
+        }
+        self.constants.get(name)?.interpolated()
+    }
+
+    fn error(&self, name: String) -> Message {
+        match self.constants.get(&name) {
+            Some(_) => message!("constant {} can't be interpolated: only strings, numbers and booleans can", name),
+            None => message!("undefined name {{{}}}", name),
+        }
+    }
+
+    fn text(&self, text: &mut String) -> std::result::Result<(), Message> {
+        *text = interpolate(text, &|name| self.lookup(name)).map_err(|name| self.error(name))?;
+        Ok(())
+    }
+
+    fn value(&self, value: &mut Value) -> std::result::Result<(), Message> {
+        value.interpolate(&|name| self.lookup(name)).map_err(|name| self.error(name))
+    }
+
+    fn agent(&self, agent: &mut Agent) -> std::result::Result<(), Message> {
+        for argument in &mut agent.config {
+            let (Argument::Named(_, value, _) | Argument::Positional(value, _)) = argument;
+            self.value(value)?;
+        }
+        Ok(())
+    }
+}
+
+/// The schema of the messages on a topic, declared in the program's
+/// `schemas:` block (`"orders": { id: "string", total: "number" }`).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TopicSchema {
+    /// The topic the schema applies to.
+    pub topic: String,
+    /// The field types, as written; see [`Schema::from_value`].
+    pub fields: Value,
+}
+
+/// A constant declared at program scope (`const MODEL = "ollama/llama3"`).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Constant {
+    /// The name the constant is referenced by, without the `$`.
+    pub name: String,
+    /// The value of the constant.
+    pub value: Value,
+    /// Where the constant is declared.
+    #[serde(skip)]
+    pub span: Span,
+}
+
+/// The position of a node in the source, for error messages.
+///
+/// Nodes built in code rather than parsed have an unknown position.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Span {
+    /// The file the node was parsed from, if parsed from a file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// The line the node starts on, from 1; 0 when unknown.
+    pub line: usize,
+    /// The column the node starts at, from 1.
+    pub column: usize,
+    /// The source line the node starts on, without surrounding whitespace.
+    pub snippet: String,
+    /// The whitespace trimmed from the start of the snippet.
+    pub indent: usize,
+    /// The characters of the node on the line it starts on.
+    pub length: usize,
+}
+
+impl Span {
+    /// The span of `length` characters at a column of a source line.
+    pub fn in_line(line: usize, column: usize, length: usize, text: &str) -> Self {
+        let text = text.trim_end();
+        let snippet = text.trim_start();
+        Self {
+            file: None,
+            line,
+            column,
+            snippet: snippet.to_string(),
+            indent: text.chars().count() - snippet.chars().count(),
+            length,
+        }
+    }
+
+    /// Whether the position is known.
+    pub fn is_known(&self) -> bool {
+        self.line > 0
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+        }
+        write!(f, "{}:{}", self.line, self.column)
+    }
 }
 
 impl Default for Program {
@@ -45,11 +320,296 @@ pub struct Workflow {
     /// The agents in the workflow.
     pub agents: Vec<Agent>,
     /// Monitoring configuration for the workflow.
-    pub monitor: Option<HashMap<String, String>>,
+    pub monitor: Option<Monitor>,
     /// Deployment configuration for the workflow.
     pub deployment: Option<Deployment>,
+    /// The semantic version of the workflow, as written in `version: "2.1.0"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// The documentation of the workflow, from the `///` comments above it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+    /// Whether the workflow runs continuously or over a bounded input.
+    #[serde(default)]
+    pub mode: WorkflowMode,
+    /// The subworkflows invoked from the agent list.
+    #[serde(default)]
+    pub calls: Vec<SubworkflowCall>,
+    /// The unit tests of the workflow.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tests: Vec<WorkflowTest>,
+    /// Where the workflow is defined; not part of the definition.
+    #[serde(skip)]
+    pub span: Span,
+}
+
+impl Workflow {
+    /// The orchestrator the workflow's agents are deployed on.
+    pub fn platform(&self) -> Platform {
+        self.deployment.as_ref().map(|deployment| deployment.platform).unwrap_or_default()
+    }
+
+    /// Resolve a `models.<name>` reference to the path of that context model.
+    pub fn context_model_path(&self, reference: &str) -> Option<&str> {
+        let name = reference.strip_prefix("models.")?;
+        let model = self.context.as_ref()?.models.get(name)?;
+        Some(model.path.as_str())
+    }
+
+    /// The models an agent declares for warm-up, resolved to URIs.
+    ///
+    /// `preload: true` preloads the agent's own model, its `model_path` or the
+    /// context model its `model` names; a list names the models explicitly,
+    /// as URIs or `models.<name>` references. `None` when nothing is declared.
+    pub fn preload_uris(&self, agent: &Agent) -> Option<Vec<String>> {
+        let resolve = |value: &Value| match value {
+            Value::String(reference) | Value::Path(reference) => Some(
+                self.context_model_path(reference)
+                    .map_or_else(|| reference.clone(), str::to_string),
+            ),
+            _ => None,
+        };
+
+        match agent.config_value(PRELOAD_OPTION)? {
+            Value::Boolean(true) => {
+                let own_model = agent
+                    .config_value("model_path")
+                    .and_then(resolve)
+                    .or_else(|| agent.config_value("model").and_then(resolve))
+                    .filter(|uri| uri.contains("://"));
+                Some(own_model.into_iter().collect())
+            }
+            Value::Array(models) => Some(models.iter().filter_map(resolve).collect()),
+            _ => None,
+        }
+    }
+
+    /// Whether agents declare `input`/`output` topics, wiring the pipeline explicitly.
+    pub fn is_wired(&self) -> bool {
+        self.agents
+            .iter()
+            .any(|agent| agent.config_value(INPUT_OPTION).is_some() || agent.config_value(OUTPUT_OPTION).is_some())
+    }
+
+    /// The topics each agent of the workflow consumes and produces, in agent order.
+    ///
+    /// An agent without `output` publishes on `<workflow>.<agent>`, or on the
+    /// workflow target when it is the last one. An agent without `input`
+    /// consumes what the previous step produces, or the workflow source when
+    /// it is the first one. `input: "<agent>.output"` names the output of
+    /// another agent and `input: "source"` the workflow source.
+    pub fn topic_graph(&self) -> std::result::Result<Vec<AgentTopics>, Message> {
+        let source_topic = self.source.as_ref().map(|source| source.topic());
+        let target_topic = self.target.as_ref().map(|target| target.topic());
+        let last = self.agents.len().saturating_sub(1);
+
+        let mut outputs = Vec::with_capacity(self.agents.len());
+        for (index, agent) in self.agents.iter().enumerate() {
+            let output = match topic_option(agent, OUTPUT_OPTION)? {
+                Some(topic) => Some(topic),
+                None => match topic_option(agent, OUTPUT_TOPIC_OPTION)? {
+                    Some(topic) => Some(topic),
+                    None if index == last => None,
+                    None => Some(format!("{}.{}", self.name.to_lowercase(), agent_name(agent, index))),
+                },
+            };
+            // Publishing on the target topic is publishing on the workflow target
+            outputs.push(output.filter(|topic| Some(topic.as_str()) != target_topic));
+        }
+
+        let mut calls = self.calls.iter().peekable();
+        let mut previous: Option<Option<String>> = None;
+        let mut graph = Vec::with_capacity(self.agents.len());
+        for (index, agent) in self.agents.iter().enumerate() {
+            // A subworkflow invoked before the agent is the previous step
+            while let Some(call) = calls.next_if(|call| call.position <= index) {
+                previous = Some(call.output.first().cloned());
+            }
+
+            let produced_by = |topic: &str| {
+                let producer = topic.strip_suffix(".output")?;
+                let position = (0..self.agents.len()).find(|&i| agent_name(&self.agents[i], i) == producer)?;
+                Some(outputs[position].clone().or_else(|| target_topic.map(str::to_string)))
+            };
+            let input = match topic_option(agent, INPUT_OPTION)? {
+                Some(topic) if topic == SOURCE_TOPIC => None,
+                Some(topic) => produced_by(&topic).unwrap_or(Some(topic)),
+                None => match topic_option(agent, INPUT_TOPIC_OPTION)? {
+                    Some(topic) => Some(topic),
+                    None => previous.clone().flatten(),
+                },
+            };
+            graph.push(AgentTopics {
+                input: input.filter(|topic| Some(topic.as_str()) != source_topic),
+                output: outputs[index].clone(),
+            });
+            previous = Some(outputs[index].clone().or_else(|| target_topic.map(str::to_string)));
+        }
+        Ok(graph)
+    }
+
+    /// The topics each agent consumes and produces once deployed, in agent order.
+    ///
+    /// Unlike [`Workflow::topic_graph`], the workflow source and target are
+    /// named, so `None` only stands for a missing source or target. Agents of
+    /// workflows without `input`/`output` topics read the source and write
+    /// the target unless their subjects were already resolved.
+    pub fn deployed_topics(&self) -> Vec<AgentTopics> {
+        let source_topic = self.source.as_ref().map(|source| source.topic().to_string());
+        let target_topic = self.target.as_ref().map(|target| target.topic().to_string());
+        let graph = match self.topic_graph() {
+            Ok(graph) if self.is_wired() => graph,
+            _ => self
+                .agents
+                .iter()
+                .map(|agent| AgentTopics {
+                    input: topic_option(agent, INPUT_TOPIC_OPTION).ok().flatten(),
+                    output: topic_option(agent, OUTPUT_TOPIC_OPTION).ok().flatten(),
+                })
+                .collect(),
+        };
+        graph
+            .into_iter()
+            .map(|topics| AgentTopics {
+                input: topics.input.or_else(|| source_topic.clone()),
+                output: topics.output.or_else(|| target_topic.clone()),
+            })
+            .collect()
+    }
+
+    /// The encodings of the topics each agent consumes and produces once deployed, in agent order.
+    ///
+    /// An agent publishes in its `encoding`, else in the encoding of the
+    /// workflow target when it writes the target, else in JSON. It reads the
+    /// workflow source in the source's encoding and the topics of other agents
+    /// in the encoding they publish in; other topics are JSON.
+    pub fn agent_encodings(&self) -> std::result::Result<Vec<AgentEncodings>, Message> {
+        let source = self.source.as_ref().map(Source::encoding).transpose()?.unwrap_or_default();
+        let target = self.target.as_ref().map(Target::encoding).transpose()?.unwrap_or_default();
+        let source_topic = self.source.as_ref().map(|source| source.topic());
+        let target_topic = self.target.as_ref().map(|target| target.topic());
+        let topics = self.deployed_topics();
+
+        let mut outputs = Vec::with_capacity(self.agents.len());
+        for (agent, topics) in self.agents.iter().zip(&topics) {
+            outputs.push(match agent.encoding()? {
+                Some(encoding) => encoding,
+                None if topics.output.is_some() && topics.output.as_deref() == target_topic => target,
+                None => Encoding::Json,
+            });
+        }
+        Ok(topics
+            .iter()
+            .zip(&outputs)
+            .map(|(agent_topics, output)| {
+                let input = match agent_topics.input.as_deref() {
+                    Some(topic) if Some(topic) == source_topic => source,
+                    Some(topic) => topics
+                        .iter()
+                        .position(|producer| producer.output.as_deref() == Some(topic))
+                        .map_or(Encoding::Json, |producer| outputs[producer]),
+                    None => Encoding::Json,
+                };
+                AgentEncodings { input, output: *output }
+            })
+            .collect())
+    }
+
+    /// The declared schemas of the messages published on each topic of the workflow.
+    pub fn message_schemas(&self) -> HashMap<String, Schema> {
+        let mut schemas = HashMap::new();
+        for (agent, topics) in self.agents.iter().zip(self.deployed_topics()) {
+            if let (Ok(Some((schema, _))), Some(topic)) = (agent.output_schema(), topics.output) {
+                schemas.insert(topic, schema);
+            }
+        }
+        schemas
+    }
+}
+
+/// Topics an agent consumes and produces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentTopics {
+    /// The topic consumed; `None` for the workflow source in [`Workflow::topic_graph`].
+    pub input: Option<String>,
+    /// The topic produced; `None` for the workflow target in [`Workflow::topic_graph`].
+    pub output: Option<String>,
+}
+
+/// Encodings of the topics an agent consumes and produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AgentEncodings {
+    /// The encoding of the topic consumed.
+    pub input: Encoding,
+    /// The encoding of the topic produced.
+    pub output: Encoding,
+}
+
+/// Name of an agent in topics, its ID or its position.
+fn agent_name(agent: &Agent, index: usize) -> String {
+    agent.id.clone().unwrap_or_else(|| format!("agent{}", index + 1))
+}
+
+/// Read a topic option; bound subworkflow subjects may be a list, of which the first is used.
+fn topic_option(agent: &Agent, name: &str) -> std::result::Result<Option<String>, Message> {
+    match agent.config_value(name) {
+        None => Ok(None),
+        Some(Value::String(topic)) if !topic.trim().is_empty() => Ok(Some(topic.clone())),
+        Some(Value::Array(topics)) if name == INPUT_TOPIC_OPTION || name == OUTPUT_TOPIC_OPTION => match topics.first() {
+            Some(Value::String(topic)) => Ok(Some(topic.clone())),
+            _ => Ok(None),
+        },
+        Some(other) => Err(message!("{} must be a topic name, found {}", name, other)),
+    }
+}
+
+/// Represents how a workflow is run.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowMode {
+    /// Agents run as long-lived Deployments consuming an unbounded stream.
+    #[default]
+    Stream,
+    /// Agents run as Jobs that process a bounded input and exit.
+    Batch,
+}
+
+/// A subworkflow invoked from a workflow's agent list (`use Enrich(input: ..., output: ...)`).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SubworkflowCall {
+    /// The name of the invoked subworkflow.
+    pub name: String,
+    /// The subjects bound to the subworkflow inputs, in declaration order.
+    pub input: Vec<String>,
+    /// The subjects bound to the subworkflow outputs, in declaration order.
+    pub output: Vec<String>,
+    /// The number of workflow agents listed before the call.
+    pub position: usize,
 }
 
+/// A unit test of a workflow
+/// (`test "name" { given: {...}, when agent: "router1", expect: { topic: "alerts" } }`).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WorkflowTest {
+    /// The name of the test.
+    pub name: String,
+    /// The message delivered to the agent.
+    pub given: Value,
+    /// The ID of the agent receiving the message.
+    pub agent: String,
+    /// What should become of the message: `topic` and `outcome`.
+    pub expect: HashMap<String, Value>,
+    /// Where the test is defined; not part of the definition.
+    #[serde(skip)]
+    pub span: Span,
+}
+
+/// Expected property of a test: the topic the message ends up published on.
+pub const EXPECT_TOPIC: &str = "topic";
+
+/// Expected property of a test: whether the agent processes, skips or rejects the message.
+pub const EXPECT_OUTCOME: &str = "outcome";
+
 /// Represents a subworkflow in the Kumeo DSL.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Subworkflow {
@@ -63,6 +623,9 @@ pub struct Subworkflow {
     pub context: Option<Context>,
     /// The agents in the subworkflow.
     pub agents: Vec<Agent>,
+    /// Where the subworkflow is defined; not part of the definition.
+    #[serde(skip)]
+    pub span: Span,
 }
 
 /// Represents a data source in the Kumeo DSL.
@@ -70,6 +633,217 @@ pub struct Subworkflow {
 pub enum Source {
     /// A NATS message broker source.
     NATS(String, Option<HashMap<String, String>>),
+    /// A Kafka topic source.
+    Kafka(String, Option<HashMap<String, String>>),
+    /// An MQTT topic filter source (may contain `+` and `#` wildcards).
+    MQTT(String, Option<HashMap<String, String>>),
+    /// An HTTP webhook source, identified by its request path.
+    HTTP(String, Option<HashMap<String, String>>),
+    /// Files matching a glob (e.g. `/data/in/*.json`).
+    File(String, Option<HashMap<String, String>>),
+}
+
+/// Source option bounding the input of a batch workflow to a stream sequence.
+pub const UNTIL_SEQUENCE_OPTION: &str = "until_sequence";
+
+/// HTTP source option naming the accepted request method (`POST` by default).
+pub const WEBHOOK_METHOD_OPTION: &str = "method";
+
+/// Agent option declaring the models to load before the agent reports ready.
+pub const PRELOAD_OPTION: &str = "preload";
+
+/// Agent option naming the image it is built and deployed as, instead of
+/// `<registry>/<id>:<tag>`.
+pub const IMAGE_OPTION: &str = "image";
+
+/// Agent option holding the condition a message must meet to be processed.
+pub const WHEN_OPTION: &str = "when";
+
+/// Agent option holding an invariant every processed message must satisfy; may repeat.
+pub const ASSERT_OPTION: &str = "assert";
+
+/// File source option that keeps watching the directory for new files.
+pub const FILE_WATCH_OPTION: &str = "watch";
+
+/// Agent option configuring how failed messages are retried.
+pub const RETRY_OPTION: &str = "retry";
+
+/// Agent option configuring what happens once the retries are exhausted.
+pub const FALLBACK_OPTION: &str = "fallback";
+
+/// Agent option naming the subject that undoes the agent's work when its saga fails downstream.
+pub const COMPENSATE_OPTION: &str = "compensate";
+
+/// Agent option naming the topic the agent consumes.
+pub const INPUT_OPTION: &str = "input";
+
+/// Agent option naming the topic the agent produces.
+pub const OUTPUT_OPTION: &str = "output";
+
+/// Agent option declaring the schema of the messages the agent produces.
+pub const OUTPUT_SCHEMA_OPTION: &str = "output_schema";
+
+/// Agent option declaring the schema of the messages the agent expects.
+pub const INPUT_SCHEMA_OPTION: &str = "input_schema";
+
+/// Agent option numbering the version of the agent's output schema.
+pub const SCHEMA_VERSION_OPTION: &str = "schema_version";
+
+/// MLModel option tracking the drift of the input features from a reference profile.
+pub const DRIFT_OPTION: &str = "drift";
+
+/// MLModel option naming the feature store the inputs are enriched from.
+pub const FEATURES_OPTION: &str = "features";
+
+/// LLM option naming the provider the prompts are sent to, or the inference server serving the model.
+pub const PROVIDER_OPTION: &str = "provider";
+
+/// LLM option listing the providers a request fails over to, in order.
+pub const PROVIDERS_OPTION: &str = "providers";
+
+/// Former LLM option naming the provider and model as one string, such as `"ollama/llama3"`; ignored.
+pub const ENGINE_OPTION: &str = "engine";
+
+/// Name of the agent option holding the guardrails of an LLM agent's prompts and responses.
+pub const GUARDRAILS_OPTION: &str = "guardrails";
+
+/// Name of the agent option keeping the chat history of an LLM agent's sessions.
+pub const MEMORY_OPTION: &str = "memory";
+
+/// LLM option declaring the tokens the agent is expected to use per month, for cost estimates.
+pub const BUDGET_OPTION: &str = "budget";
+
+/// Router option picking the topic of every message, as in `strategy: hash(key: data.customer_id)`.
+pub const STRATEGY_OPTION: &str = "strategy";
+
+/// Router option reading the routing table from a resource, as in `rules: resource("s3://config/routes.yaml")`;
+/// RuleEngine option listing its rules, written as a `rules:` clause.
+pub const RULES_OPTION: &str = "rules";
+
+/// Quality monitor option giving the fraction of the messages sampled.
+pub const SAMPLE_RATE_OPTION: &str = "sample_rate";
+
+/// Quality monitor option listing the statistics computed.
+pub const METRICS_OPTION: &str = "metrics";
+
+/// Quality monitor option giving the drift allowed per statistic.
+pub const THRESHOLDS_OPTION: &str = "thresholds";
+
+/// Quality monitor option giving the sampled messages per report;
+/// MissingValueHandler option giving the recent values of a field its imputations are computed over.
+pub const WINDOW_OPTION: &str = "window";
+
+/// Quality monitor option listing the fields monitored.
+pub const FIELDS_OPTION: &str = "fields";
+
+/// Aggregator option listing the fields messages are grouped by.
+pub const GROUP_BY_OPTION: &str = "group_by";
+
+/// Aggregator option giving the fields computed over each window, written as a `reduce:` clause.
+pub const REDUCE_OPTION: &str = "reduce";
+
+/// DataNormalizer option moving fields of the messages, as in `mappings: { amount: data.payment.amount }`.
+pub const MAPPINGS_OPTION: &str = "mappings";
+
+/// DataNormalizer option rescaling numeric fields, as in `scale: { amount: minmax(min: 0, max: 10000) }`.
+pub const SCALE_OPTION: &str = "scale";
+
+/// MissingValueHandler option filling missing fields with literal values, as in `defaults: { country: "US" }`.
+pub const DEFAULTS_OPTION: &str = "defaults";
+
+/// MissingValueHandler option filling missing fields from the values seen, as in `impute: { amount: mean() }`.
+pub const IMPUTE_OPTION: &str = "impute";
+
+/// BayesianNetwork option giving the file of the network, such as `network_path: "models/risk.bif"`.
+pub const NETWORK_PATH_OPTION: &str = "network_path";
+
+/// BayesianNetwork option listing the variables whose posteriors are published.
+pub const QUERY_OPTION: &str = "query";
+
+/// Custom option giving the language of the agent's templates, `rust` or `python`.
+pub const LANGUAGE_OPTION: &str = "language";
+
+/// HumanReview option giving how long a review may stay pending.
+pub const SLA_OPTION: &str = "sla";
+
+/// HumanReview option listing who is notified as a review stays pending.
+pub const ESCALATION_OPTION: &str = "escalation";
+
+/// HumanReview option naming groups of reviewers who decide for each other.
+pub const DELEGATION_OPTION: &str = "delegation";
+
+/// HumanReview option giving the decision taken when the SLA lapses.
+pub const SLA_BREACH_OPTION: &str = "on_sla_breach";
+
+/// HumanReview option giving how long a message waits for its review.
+pub const REVIEW_TIMEOUT_OPTION: &str = "timeout";
+
+/// HumanReview option naming the subject the messages whose review timed out go to.
+pub const ON_TIMEOUT_OPTION: &str = "on_timeout";
+
+/// HumanReview option listing the channels notified as soon as a review is submitted.
+pub const NOTIFICATIONS_OPTION: &str = "notifications";
+
+/// HumanReview option saying whether the review UI is generated.
+pub const UI_OPTION: &str = "ui";
+
+/// HumanReview option saying how reviewers are identified and decisions signed.
+pub const AUDIT_OPTION: &str = "audit";
+
+/// HumanReview and HTTP source option requiring callers to authenticate, as in `auth: OIDC(...)`.
+pub const AUTH_OPTION: &str = "auth";
+
+/// `input` topic standing for the workflow source.
+pub const SOURCE_TOPIC: &str = "source";
+
+/// Agent option carrying the subject an agent reads once topics are resolved.
+pub const INPUT_TOPIC_OPTION: &str = "input_topic";
+
+/// Agent option carrying the subject an agent writes once topics are resolved.
+pub const OUTPUT_TOPIC_OPTION: &str = "output_topic";
+
+impl Source {
+    /// Get the topic (or subject) the source reads from; the request path for webhooks.
+    pub fn topic(&self) -> &str {
+        match self {
+            Source::NATS(topic, _)
+            | Source::Kafka(topic, _)
+            | Source::MQTT(topic, _)
+            | Source::HTTP(topic, _)
+            | Source::File(topic, _) => topic,
+        }
+    }
+
+    /// Get an option of the source by name.
+    pub fn option(&self, name: &str) -> Option<&str> {
+        match self {
+            Source::NATS(_, options)
+            | Source::Kafka(_, options)
+            | Source::MQTT(_, options)
+            | Source::HTTP(_, options)
+            | Source::File(_, options) => options.as_ref()?.get(name).map(String::as_str),
+        }
+    }
+
+    /// Get the encoding of the messages read from the source, JSON unless an `encoding` option is given.
+    pub fn encoding(&self) -> std::result::Result<Encoding, Message> {
+        self.option(ENCODING_OPTION).map(Encoding::parse).transpose().map(Option::unwrap_or_default)
+    }
+
+    /// Get the options of the source written inside another one, as `name.option`, by option.
+    pub fn nested_options(&self, name: &str) -> BTreeMap<&str, &str> {
+        let (Source::NATS(_, options)
+        | Source::Kafka(_, options)
+        | Source::MQTT(_, options)
+        | Source::HTTP(_, options)
+        | Source::File(_, options)) = self;
+        let prefix = format!("{}.", name);
+        options
+            .iter()
+            .flatten()
+            .filter_map(|(key, value)| Some((key.strip_prefix(&prefix)?, value.as_str())))
+            .collect()
+    }
 }
 
 /// Represents a data target in the Kumeo DSL.
@@ -77,6 +851,106 @@ pub enum Source {
 pub enum Target {
     /// A NATS message broker target.
     NATS(String, Option<HashMap<String, String>>),
+    /// A Kafka topic target.
+    Kafka(String, Option<HashMap<String, String>>),
+    /// An MQTT topic target.
+    MQTT(String, Option<HashMap<String, String>>),
+    /// A directory each output message is written to as a file.
+    File(String, Option<HashMap<String, String>>),
+}
+
+impl Target {
+    /// Get the topic (or subject) the target writes to.
+    pub fn topic(&self) -> &str {
+        match self {
+            Target::NATS(topic, _)
+            | Target::Kafka(topic, _)
+            | Target::MQTT(topic, _)
+            | Target::File(topic, _) => topic,
+        }
+    }
+
+    /// Get an option of the target by name.
+    pub fn option(&self, name: &str) -> Option<&str> {
+        match self {
+            Target::NATS(_, options)
+            | Target::Kafka(_, options)
+            | Target::MQTT(_, options)
+            | Target::File(_, options) => options.as_ref()?.get(name).map(String::as_str),
+        }
+    }
+
+    /// Get the encoding of the messages written to the target, JSON unless an `encoding` option is given.
+    pub fn encoding(&self) -> std::result::Result<Encoding, Message> {
+        self.option(ENCODING_OPTION).map(Encoding::parse).transpose().map(Option::unwrap_or_default)
+    }
+}
+
+/// Source, target and agent option naming how the messages of a topic are serialized.
+///
+/// On an agent, it names the encoding of the topic the agent publishes on.
+pub const ENCODING_OPTION: &str = "encoding";
+
+/// How the messages of a topic are serialized.
+///
+/// Agents handle JSON; the runtime encodes what they publish and decodes what
+/// they receive, and tells the encoding of every message in its content type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// JSON text.
+    #[default]
+    Json,
+    /// MessagePack, a binary form of JSON.
+    Msgpack,
+    /// Protocol Buffers, with a message generated from the schema of the topic.
+    Protobuf,
+    /// Avro binary records, with a record schema generated from the schema of the topic.
+    Avro,
+}
+
+impl Encoding {
+    /// Every encoding.
+    pub const ALL: [Encoding; 4] = [Encoding::Json, Encoding::Msgpack, Encoding::Protobuf, Encoding::Avro];
+
+    /// The encoding as written in `encoding: ...`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Json => "json",
+            Encoding::Msgpack => "msgpack",
+            Encoding::Protobuf => "protobuf",
+            Encoding::Avro => "avro",
+        }
+    }
+
+    /// The content type messages in the encoding are published with.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Encoding::Json => "application/json",
+            Encoding::Msgpack => "application/msgpack",
+            Encoding::Protobuf => "application/x-protobuf",
+            Encoding::Avro => "application/avro",
+        }
+    }
+
+    /// Whether messages can only be read with the schema of their topic.
+    pub fn needs_schema(self) -> bool {
+        matches!(self, Encoding::Protobuf | Encoding::Avro)
+    }
+
+    /// Read an encoding as written in `encoding: ...`.
+    pub fn parse(name: &str) -> std::result::Result<Self, Message> {
+        Self::ALL
+            .into_iter()
+            .find(|encoding| encoding.as_str() == name)
+            .ok_or_else(|| message!("unknown encoding \"{}\", expected json, msgpack, protobuf or avro", name))
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 /// Represents context for a workflow or subworkflow.
@@ -110,6 +984,104 @@ pub struct Schema {
     pub strict: bool,
 }
 
+impl Schema {
+    /// Field types a schema may declare; a trailing `?` marks an optional field.
+    pub const FIELD_TYPES: [&'static str; 6] = ["string", "number", "integer", "boolean", "object", "array"];
+
+    /// Resolve the declared type of a message field path such as `data.order.total`.
+    ///
+    /// `data` is the message itself. Fields below an `object` or `array`
+    /// field are not declared, so their type is `None`. A field declared
+    /// optional (`"object?"`) may only be looked into with `?.`, whose
+    /// segments `optional` flags.
+    pub fn field_type(&self, path: &[String], optional: &[bool]) -> std::result::Result<Option<&str>, Message> {
+        let (field, rest) = match path {
+            [root] if root == "data" => return Ok(Some("object")),
+            [root, field, rest @ ..] if root == "data" => (field, rest),
+            _ => return Err(message!("{} is not a path below data", path.join("."))),
+        };
+        let Some(field_type) = self.fields.get(field) else {
+            return Err(message!("unknown field '{}'", field));
+        };
+        let Some(next) = rest.first() else {
+            return Ok(Some(field_type));
+        };
+        let (base_type, nullable) = split_optional(field_type);
+        if !matches!(base_type, "object" | "array") {
+            return Err(message!("field '{}' is a {} and has no field '{}'", field, field_type, next));
+        }
+        if nullable && !optional.get(2).copied().unwrap_or(false) {
+            return Err(message!("field '{}' is optional, read '{}' with ?.", field, next));
+        }
+        Ok(None)
+    }
+
+    /// What a reader expecting `expected` may not find in messages of this schema.
+    ///
+    /// Every field the reader requires must be present and not optional, and
+    /// every field it reads must have the same type; an `integer` is a
+    /// `number`. Extra fields are fine.
+    pub fn incompatibilities(&self, expected: &Schema) -> Vec<Message> {
+        let mut names: Vec<&String> = expected.fields.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let wanted = &expected.fields[name];
+                let (wanted_type, wanted_optional) = split_optional(wanted);
+                let Some(found) = self.fields.get(name) else {
+                    return (!wanted_optional).then(|| message!("field '{}' is missing", name));
+                };
+                let (found_type, found_optional) = split_optional(found);
+                if found_type != wanted_type && !(found_type == "integer" && wanted_type == "number") {
+                    Some(message!("field '{}' is a {}, not a {}", name, found, wanted))
+                } else if found_optional && !wanted_optional {
+                    Some(message!("field '{}' is optional", name))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Read an `output_schema: { id: "string", score: "number" }` option.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let Value::Object(fields) = value else {
+            return Err(message!("expected an object of field types, found {}", value));
+        };
+        let mut schema = Self {
+            fields: HashMap::new(),
+            strict: false,
+        };
+        for (name, field_type) in fields {
+            match field_type {
+                Value::String(field_type)
+                    if Self::FIELD_TYPES.contains(&field_type.strip_suffix('?').unwrap_or(field_type)) =>
+                {
+                    schema.fields.insert(name.clone(), field_type.clone());
+                }
+                other => {
+                    return Err(message!(
+                        "field '{}' has type {} (expected {})",
+                        name,
+                        other,
+                        Self::FIELD_TYPES.join(", ")
+                    ))
+                }
+            }
+        }
+        Ok(schema)
+    }
+}
+
+/// A schema field type without its optional marker, and whether it had one.
+fn split_optional(field_type: &str) -> (&str, bool) {
+    match field_type.strip_suffix('?') {
+        Some(field_type) => (field_type, true),
+        None => (field_type, false),
+    }
+}
+
 /// Represents an agent in the Kumeo DSL.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Agent {
@@ -119,34 +1091,443 @@ pub struct Agent {
     pub agent_type: AgentType,
     /// The configuration for the agent.
     pub config: Vec<Argument>,
+    /// The documentation of the agent, from the `///` comments above it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+    /// Where the agent is declared; not part of the definition.
+    #[serde(skip)]
+    pub span: Span,
 }
 
-/// Represents the type of an agent.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
-pub enum AgentType {
-    /// A large language model agent.
-    LLM,
-    /// A machine learning model agent.
-    MLModel,
-    /// A data processing agent.
-    DataProcessor,
-    /// A router agent for directing data flows.
-    Router,
-    /// A decision matrix for complex decision making.
-    DecisionMatrix,
-    /// A human review step in the workflow.
-    HumanReview,
-}
+impl Agent {
+    /// Look up a named configuration value.
+    pub fn config_value(&self, name: &str) -> Option<&Value> {
+        self.config.iter().find_map(|arg| match arg {
+            Argument::Named(key, value, _) if key == name => Some(value),
+            _ => None,
+        })
+    }
 
-impl std::fmt::Display for AgentType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            AgentType::LLM => write!(f, "llm"),
-            AgentType::MLModel => write!(f, "mlmodel"),
-            AgentType::DataProcessor => write!(f, "dataprocessor"),
+    /// The span of the agent and those of its options.
+    fn spans_mut(&mut self) -> impl Iterator<Item = &mut Span> {
+        std::iter::once(&mut self.span).chain(self.config.iter_mut().map(|arg| match arg {
+            Argument::Named(_, _, span) | Argument::Positional(_, span) => span,
+        }))
+    }
+
+    /// Where a named configuration value is written, if the agent was parsed.
+    pub fn option_span(&self, name: &str) -> Option<&Span> {
+        self.config.iter().find_map(|arg| match arg {
+            Argument::Named(key, _, span) if key == name && span.is_known() => Some(span),
+            _ => None,
+        })
+    }
+
+    /// The `assert` invariants of the agent, in declaration order.
+    pub fn assertions(&self) -> Vec<&Expr> {
+        self.config
+            .iter()
+            .filter_map(|arg| match arg {
+                Argument::Named(key, Value::Condition(expr), _) if key == ASSERT_OPTION => Some(expr.as_ref()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The `${...}` placeholders in the string values of the configuration.
+    pub fn placeholders(&self) -> Vec<Placeholder<'_>> {
+        let mut found = Vec::new();
+        for argument in &self.config {
+            let (Argument::Named(_, value, _) | Argument::Positional(value, _)) = argument;
+            value.visit_strings(&mut |text| found.extend(placeholders(text)));
+        }
+        found
+    }
+
+    /// The environment variables the configuration references with `${env.NAME}`.
+    pub fn env_references(&self) -> BTreeSet<&str> {
+        self.placeholders()
+            .into_iter()
+            .filter_map(|placeholder| match placeholder {
+                Placeholder::Env(name) => Some(name),
+                Placeholder::Unknown(_) => None,
+            })
+            .collect()
+    }
+
+    /// The encoding of the topic the agent publishes on, if declared.
+    pub fn encoding(&self) -> std::result::Result<Option<Encoding>, Message> {
+        match self.config_value(ENCODING_OPTION) {
+            None => Ok(None),
+            Some(Value::String(name)) => Encoding::parse(name).map(Some),
+            Some(other) => Err(message!("{} must be a string, found {}", ENCODING_OPTION, other)),
+        }
+    }
+
+    /// The schema of the messages the agent expects, if declared.
+    pub fn input_schema(&self) -> std::result::Result<Option<Schema>, Message> {
+        self.config_value(INPUT_SCHEMA_OPTION).map(Schema::from_value).transpose()
+    }
+
+    /// The schema and schema version of the messages the agent produces, if declared.
+    ///
+    /// The version defaults to 1.
+    pub fn output_schema(&self) -> std::result::Result<Option<(Schema, u32)>, Message> {
+        let Some(value) = self.config_value(OUTPUT_SCHEMA_OPTION) else {
+            return match self.config_value(SCHEMA_VERSION_OPTION) {
+                Some(_) => Err(message!("{} requires an {}", SCHEMA_VERSION_OPTION, OUTPUT_SCHEMA_OPTION)),
+                None => Ok(None),
+            };
+        };
+        let version = match self.config_value(SCHEMA_VERSION_OPTION) {
+            None => 1,
+            Some(Value::Number(n)) if *n >= 1.0 && n.fract() == 0.0 && *n <= u32::MAX as f64 => *n as u32,
+            Some(other) => {
+                return Err(message!(
+                    "{} must be a whole number of at least 1, found {}",
+                    SCHEMA_VERSION_OPTION, other
+                ))
+            }
+        };
+        Ok(Some((Schema::from_value(value)?, version)))
+    }
+}
+
+/// A `${...}` placeholder inside a string value.
+///
+/// Placeholders are kept verbatim by the compiler and resolved by the
+/// agent when it loads its configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placeholder<'a> {
+    /// A deferred environment variable reference, `${env.NAME}`.
+    Env(&'a str),
+    /// Any other placeholder, which is left as is.
+    Unknown(&'a str),
+}
+
+/// Find the `${...}` placeholders in a string.
+pub fn placeholders(text: &str) -> Vec<Placeholder<'_>> {
+    let mut found = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let placeholder = &rest[start..start + len + 1];
+        let name = placeholder[2..placeholder.len() - 1].strip_prefix("env.").filter(|name| {
+            name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+        found.push(name.map_or(Placeholder::Unknown(placeholder), Placeholder::Env));
+        rest = &rest[start + len + 1..];
+    }
+    found
+}
+
+/// The environment variable a `secret("name")` is read from, which is also
+/// its key in the Secret: the name in upper snake case, such as
+/// `OPENAI_API_KEY` for `secret("openai-api-key")`.
+pub fn secret_variable(name: &str) -> std::result::Result<String, Message> {
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid {
+        return Err(message!(
+            "Invalid secret name '{}', expected a letter followed by letters, digits, '-', '.' or '_'",
+            name
+        ));
+    }
+    Ok(name.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' }).collect())
+}
+
+/// Check that a namespace is a DNS label, as Kubernetes requires.
+pub fn validate_namespace(name: &str) -> std::result::Result<(), Message> {
+    if is_dns_label(name) {
+        Ok(())
+    } else {
+        Err(message!(
+            "Invalid namespace '{}', expected up to 63 lowercase letters, digits or '-', starting and ending with a letter or digit",
+            name
+        ))
+    }
+}
+
+/// Check a label of the `deployment` block: its key is a Kubernetes
+/// qualified name outside the `app` and `kumeo.io/` ones Kumeo sets, and its
+/// value up to 63 letters, digits, `-`, `_` or `.`.
+pub fn validate_label(key: &str, value: &str) -> std::result::Result<(), Message> {
+    if !is_qualified_name(key) {
+        return Err(message!(
+            "Invalid label key '{}', expected a name of up to 63 letters, digits, '-', '_' or '.', with an optional DNS prefix such as example.com/",
+            key
+        ));
+    }
+    if is_reserved_key(key) {
+        return Err(message!("Label {} is reserved for Kumeo", key));
+    }
+    if !value.is_empty() && !is_label_name(value) {
+        return Err(message!(
+            "Invalid value '{}' of label {}, expected up to 63 letters, digits, '-', '_' or '.', starting and ending with a letter or digit",
+            value, key
+        ));
+    }
+    Ok(())
+}
+
+/// Annotation of the `deployment` block naming the blue/green slot serving
+/// traffic, the one `kumeo.io/` key a workflow sets itself.
+pub const ACTIVE_SLOT_ANNOTATION: &str = "kumeo.io/active-slot";
+
+/// Check the key of an annotation of the `deployment` block; its value may be any text.
+pub fn validate_annotation(key: &str) -> std::result::Result<(), Message> {
+    if !is_qualified_name(key) {
+        return Err(message!(
+            "Invalid annotation key '{}', expected a name of up to 63 letters, digits, '-', '_' or '.', with an optional DNS prefix such as example.com/",
+            key
+        ));
+    }
+    if is_reserved_key(key) && key != ACTIVE_SLOT_ANNOTATION {
+        return Err(message!("Annotation {} is reserved for Kumeo", key));
+    }
+    Ok(())
+}
+
+/// Check a registry agent images are pushed to: a host, with an optional
+/// port, and optional path components, such as `ghcr.io/acme`.
+pub fn validate_registry(registry: &str) -> std::result::Result<(), Message> {
+    if is_repository(registry) {
+        Ok(())
+    } else {
+        Err(message!("Invalid registry '{}', expected a host and optional path such as ghcr.io/acme", registry))
+    }
+}
+
+/// Check an image tag: up to 128 letters, digits, `_`, `.` or `-`, not
+/// starting with `.` or `-`.
+pub fn validate_image_tag(tag: &str) -> std::result::Result<(), Message> {
+    if is_image_tag(tag) {
+        Ok(())
+    } else {
+        Err(message!(
+            "Invalid image tag '{}', expected up to 128 letters, digits, '_', '.' or '-', not starting with '.' or '-'",
+            tag
+        ))
+    }
+}
+
+/// Check the `image` of an agent: a repository with an optional tag or
+/// digest, such as `ghcr.io/acme/scorer:1.2`.
+pub fn validate_image(image: &str) -> std::result::Result<(), Message> {
+    let (repository, tag, digest) = split_image(image);
+    let valid = is_repository(repository)
+        && tag.is_none_or(is_image_tag)
+        && digest.is_none_or(|digest| {
+            digest.split_once(':').is_some_and(|(algorithm, hex)| {
+                !algorithm.is_empty() && !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit())
+            })
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(message!(
+            "Invalid image '{}', expected a repository with an optional tag or digest such as ghcr.io/acme/scorer:1.2",
+            image
+        ))
+    }
+}
+
+/// The repository, tag and digest of an image reference.
+pub fn split_image(image: &str) -> (&str, Option<&str>, Option<&str>) {
+    let (name, digest) = match image.split_once('@') {
+        Some((name, digest)) => (name, Some(digest)),
+        None => (image, None),
+    };
+    // A colon before the last slash separates the port of the registry
+    match name.rsplit_once(':') {
+        Some((repository, tag)) if !tag.contains('/') => (repository, Some(tag), digest),
+        _ => (name, None, digest),
+    }
+}
+
+/// Whether a name is an image repository: a registry host, with an optional
+/// port, or a path component, followed by path components.
+fn is_repository(name: &str) -> bool {
+    let mut components = name.split('/');
+    let first = components.next().unwrap_or_default();
+    let host = match first.split_once(':') {
+        Some((host, port)) => !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()) && is_host(host),
+        None => is_host(first) || is_path_component(first),
+    };
+    host && components.all(is_path_component)
+}
+
+/// Whether a name is a registry host: DNS labels of any case separated by dots.
+fn is_host(name: &str) -> bool {
+    name.split('.').all(|label| {
+        !label.is_empty()
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+    })
+}
+
+/// Whether a name is a path component of a repository: lowercase letters
+/// and digits, separated by `.`, `_`, `__` or dashes.
+fn is_path_component(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-'))
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.ends_with(|c: char| c.is_ascii_alphanumeric())
+}
+
+/// Whether a name is an image tag.
+fn is_image_tag(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag.len() <= 128
+        && tag.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+        && !tag.starts_with(['.', '-'])
+}
+
+/// Whether a name is a DNS label: lowercase letters, digits and inner dashes.
+fn is_dns_label(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 63
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+}
+
+/// Whether a name is the name part of a label key, or a label value.
+fn is_label_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 63
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.ends_with(|c: char| c.is_ascii_alphanumeric())
+}
+
+/// Whether a key is a qualified name: a name with an optional DNS subdomain prefix.
+fn is_qualified_name(key: &str) -> bool {
+    match key.split_once('/') {
+        Some((prefix, name)) => prefix.len() <= 253 && prefix.split('.').all(is_dns_label) && is_label_name(name),
+        None => is_label_name(key),
+    }
+}
+
+/// Whether a key is one of those Kumeo sets on the manifests.
+fn is_reserved_key(key: &str) -> bool {
+    key == "app" || key.split_once('/').is_some_and(|(prefix, _)| prefix == "kumeo.io" || prefix.ends_with(".kumeo.io"))
+}
+
+/// Replace the `{NAME}` references of a string with the text `lookup` gives for them.
+///
+/// Only braces around an identifier are references: `{{...}}` prompt
+/// placeholders, which agents fill at runtime, `${env.NAME}` references and
+/// any other brace are kept as written. Fails with the first name `lookup`
+/// doesn't resolve.
+pub fn interpolate(text: &str, lookup: &impl Fn(&str) -> Option<String>) -> std::result::Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(['{', '$']) {
+        result.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let reference = tail
+            .strip_prefix('{')
+            .and_then(|inner| inner.split_once('}'))
+            .map(|(name, _)| name)
+            .filter(|name| is_identifier(name));
+
+        // Prompt placeholders and environment references are kept whole
+        let kept = if tail.starts_with("{{") {
+            tail.find("}}").map_or(2, |end| end + 2)
+        } else if tail.starts_with("${") {
+            tail.find('}').map_or(2, |end| end + 1)
+        } else if let Some(name) = reference {
+            result.push_str(&lookup(name).ok_or_else(|| name.to_string())?);
+            rest = &tail[name.len() + 2..];
+            continue;
+        } else {
+            1
+        };
+        result.push_str(&tail[..kept]);
+        rest = &tail[kept..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Whether a name is a valid interpolation reference (`[A-Za-z_][A-Za-z0-9_]*`).
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Represents the type of an agent.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum AgentType {
+    /// A large language model agent.
+    LLM,
+    /// A machine learning model agent.
+    MLModel,
+    /// A data processing agent.
+    DataProcessor,
+    /// A router agent for directing data flows.
+    Router,
+    /// A decision matrix for complex decision making.
+    DecisionMatrix,
+    /// A human review step in the workflow.
+    HumanReview,
+    /// A data quality monitor sampling the messages of a topic.
+    QualityMonitor,
+    /// An aggregator reducing the messages of a window per group.
+    Aggregator,
+    /// A rule engine applying condition/action rules to every message.
+    RuleEngine,
+    /// A Bayesian network inferring the posteriors of its variables from every message.
+    BayesianNetwork,
+    /// An agent generated from templates supplied by the user, such as `Custom("scorer", ...)`.
+    Custom,
+    /// A preprocessor moving and rescaling the fields of every message.
+    DataNormalizer,
+    /// A preprocessor filling the missing fields of every message.
+    MissingValueHandler,
+}
+
+impl AgentType {
+    /// Every agent type.
+    pub const ALL: [AgentType; 13] = [
+        AgentType::LLM,
+        AgentType::MLModel,
+        AgentType::DataProcessor,
+        AgentType::Router,
+        AgentType::DecisionMatrix,
+        AgentType::HumanReview,
+        AgentType::QualityMonitor,
+        AgentType::Aggregator,
+        AgentType::RuleEngine,
+        AgentType::BayesianNetwork,
+        AgentType::Custom,
+        AgentType::DataNormalizer,
+        AgentType::MissingValueHandler,
+    ];
+}
+
+impl std::fmt::Display for AgentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AgentType::LLM => write!(f, "llm"),
+            AgentType::MLModel => write!(f, "mlmodel"),
+            AgentType::DataProcessor => write!(f, "dataprocessor"),
             AgentType::Router => write!(f, "router"),
             AgentType::DecisionMatrix => write!(f, "decisionmatrix"),
             AgentType::HumanReview => write!(f, "humanreview"),
+            AgentType::QualityMonitor => write!(f, "qualitymonitor"),
+            AgentType::Aggregator => write!(f, "aggregator"),
+            AgentType::RuleEngine => write!(f, "ruleengine"),
+            AgentType::BayesianNetwork => write!(f, "bayesiannetwork"),
+            AgentType::Custom => write!(f, "custom"),
+            AgentType::DataNormalizer => write!(f, "datanormalizer"),
+            AgentType::MissingValueHandler => write!(f, "missingvaluehandler"),
         }
     }
 }
@@ -154,10 +1535,10 @@ impl std::fmt::Display for AgentType {
 /// Represents an argument to an agent or function.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Argument {
-    /// A named argument.
-    Named(String, Value),
-    /// A positional argument.
-    Positional(Value),
+    /// A named argument, with where it is written.
+    Named(String, Value, #[serde(skip)] Span),
+    /// A positional argument, with where it is written.
+    Positional(Value, #[serde(skip)] Span),
 }
 
 /// Represents a value in the Kumeo DSL.
@@ -175,6 +1556,298 @@ pub enum Value {
     Array(Vec<Value>),
     /// A map of strings to values.
     Object(HashMap<String, Value>),
+    /// A bare, possibly dotted, reference (e.g. `blue_green`, `models.scorer`).
+    Path(String),
+    /// A named object (e.g. `canary { steps: [10%, 100%] }`).
+    Tagged(String, HashMap<String, Value>),
+    /// A reference to a program constant (e.g. `$MODEL`), without the `$`.
+    Variable(String),
+    /// A condition over the fields of a message (e.g. `data.score > 0.8`).
+    Condition(Box<Expr>),
+}
+
+/// A `when` condition (e.g. `data.score > 0.8 && data.lang == "es"`).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum Expr {
+    /// A field of the message, as its dotted path (`data.score`).
+    Field(Vec<String>),
+    /// A field reached through optional chaining (`data.customer?.email`),
+    /// with whether each segment of the path follows a `?.`.
+    OptionalField(Vec<String>, Vec<bool>),
+    /// The first operand, or the second one when it is null (`data.lang ?? "en"`).
+    Default(Box<Expr>, Box<Expr>),
+    /// A string, number, boolean or null literal.
+    Literal(Value),
+    /// Logical negation.
+    Not(Box<Expr>),
+    /// Both conditions hold.
+    And(Box<Expr>, Box<Expr>),
+    /// Either condition holds.
+    Or(Box<Expr>, Box<Expr>),
+    /// A comparison between two operands.
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+}
+
+/// A comparison operator of a `when` condition.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CompareOp {
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+impl CompareOp {
+    /// The operator as written in the DSL.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CompareOp::Eq => "==",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
+
+    /// Whether the operator orders its operands rather than testing equality.
+    pub fn is_ordering(self) -> bool {
+        !matches!(self, CompareOp::Eq | CompareOp::Ne)
+    }
+}
+
+impl Expr {
+    /// The message field paths the expression reads, in order of appearance,
+    /// with the segments reached through `?.` (none for plain fields).
+    pub fn fields(&self) -> Vec<(&[String], &[bool])> {
+        match self {
+            Expr::Field(path) => vec![(path.as_slice(), &[])],
+            Expr::OptionalField(path, optional) => vec![(path.as_slice(), optional.as_slice())],
+            Expr::Literal(_) => Vec::new(),
+            Expr::Not(inner) => inner.fields(),
+            Expr::And(left, right)
+            | Expr::Or(left, right)
+            | Expr::Compare(left, _, right)
+            | Expr::Default(left, right) => {
+                let mut fields = left.fields();
+                fields.extend(right.fields());
+                fields
+            }
+        }
+    }
+
+    /// Binding strength, used to parenthesize only where needed when printing.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Or(..) => 1,
+            Expr::And(..) => 2,
+            Expr::Not(_) => 3,
+            Expr::Compare(..) => 4,
+            Expr::Default(..) => 5,
+            Expr::Field(_) | Expr::OptionalField(..) | Expr::Literal(_) => 6,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operand = |f: &mut fmt::Formatter<'_>, expr: &Expr, min: u8| {
+            if expr.precedence() < min {
+                write!(f, "({})", expr)
+            } else {
+                write!(f, "{}", expr)
+            }
+        };
+        match self {
+            Expr::Field(path) => write!(f, "{}", path.join(".")),
+            Expr::OptionalField(path, optional) => {
+                for (index, segment) in path.iter().enumerate() {
+                    match (index, optional.get(index)) {
+                        (0, _) => {}
+                        (_, Some(true)) => write!(f, "?.")?,
+                        _ => write!(f, ".")?,
+                    }
+                    write!(f, "{}", segment)?;
+                }
+                Ok(())
+            }
+            Expr::Default(value, default) => {
+                operand(f, value, 5)?;
+                write!(f, " ?? ")?;
+                operand(f, default, 6)
+            }
+            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::Not(inner) => {
+                write!(f, "!")?;
+                operand(f, inner, 4)
+            }
+            Expr::And(left, right) => {
+                operand(f, left, 2)?;
+                write!(f, " && ")?;
+                operand(f, right, 3)
+            }
+            Expr::Or(left, right) => {
+                operand(f, left, 1)?;
+                write!(f, " || ")?;
+                operand(f, right, 2)
+            }
+            Expr::Compare(left, op, right) => {
+                operand(f, left, 5)?;
+                write!(f, " {} ", op.as_str())?;
+                operand(f, right, 5)
+            }
+        }
+    }
+}
+
+impl Value {
+    /// The value as plain JSON, for payloads generated from the DSL.
+    ///
+    /// References and conditions become their DSL text.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::String(s) => serde_json::Value::String(s.clone()),
+            // Whole numbers stay integers, as they were most likely written
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => serde_json::Value::from(*n as i64),
+            Value::Number(n) => serde_json::Number::from_f64(*n)
+                .map_or(serde_json::Value::Null, serde_json::Value::Number),
+            Value::Boolean(b) => serde_json::Value::Bool(*b),
+            Value::Null => serde_json::Value::Null,
+            Value::Array(items) => items.iter().map(Value::to_json).collect(),
+            Value::Object(map) => map.iter().map(|(key, value)| (key.clone(), value.to_json())).collect(),
+            Value::Tagged(name, map) => serde_json::json!({
+                name.clone(): map.iter().map(|(key, value)| (key.clone(), value.to_json())).collect::<serde_json::Map<_, _>>()
+            }),
+            Value::Path(_) | Value::Variable(_) | Value::Condition(_) => serde_json::Value::String(self.to_string()),
+        }
+    }
+
+    /// Call `f` with every string in the value, including nested ones.
+    pub fn visit_strings<'a>(&'a self, f: &mut impl FnMut(&'a str)) {
+        match self {
+            Value::String(text) => f(text),
+            Value::Array(items) => items.iter().for_each(|item| item.visit_strings(f)),
+            Value::Object(map) | Value::Tagged(_, map) => map.values().for_each(|value| value.visit_strings(f)),
+            Value::Number(_) | Value::Boolean(_) | Value::Null | Value::Path(_) | Value::Variable(_) | Value::Condition(_) => {}
+        }
+    }
+
+    /// Replace constant references, including nested ones, with their values.
+    ///
+    /// Fails with the name of the first undefined constant.
+    pub fn substitute(&mut self, constants: &HashMap<String, Value>) -> std::result::Result<(), String> {
+        match self {
+            Value::Variable(name) => {
+                *self = constants.get(name.as_str()).cloned().ok_or_else(|| name.clone())?;
+            }
+            Value::Array(items) => {
+                for item in items {
+                    item.substitute(constants)?;
+                }
+            }
+            Value::Object(map) | Value::Tagged(_, map) => {
+                for value in map.values_mut() {
+                    value.substitute(constants)?;
+                }
+            }
+            Value::String(_) | Value::Number(_) | Value::Boolean(_) | Value::Null | Value::Path(_) | Value::Condition(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Interpolate the `{NAME}` references in every string of the value, including nested ones.
+    ///
+    /// Fails with the first name `lookup` doesn't resolve; see [`interpolate`].
+    pub fn interpolate(&mut self, lookup: &impl Fn(&str) -> Option<String>) -> std::result::Result<(), String> {
+        match self {
+            Value::String(text) => *text = interpolate(text, lookup)?,
+            Value::Array(items) => {
+                for item in items {
+                    item.interpolate(lookup)?;
+                }
+            }
+            Value::Object(map) | Value::Tagged(_, map) => {
+                for value in map.values_mut() {
+                    value.interpolate(lookup)?;
+                }
+            }
+            Value::Number(_) | Value::Boolean(_) | Value::Null | Value::Path(_) | Value::Variable(_) | Value::Condition(_) => {}
+        }
+        Ok(())
+    }
+
+    /// The text a scalar value interpolates as; arrays, objects and references have none.
+    pub fn interpolated(&self) -> Option<String> {
+        match self {
+            Value::String(text) => Some(text.clone()),
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => Some((*n as i64).to_string()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Boolean(b) => Some(b.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Interpret the value as a duration in seconds.
+    ///
+    /// Numbers are taken as seconds; strings may carry an `ms`, `s`, `m` or
+    /// `h` suffix (e.g. `"30s"`, `"5m"`).
+    pub fn as_duration_secs(&self) -> Option<u64> {
+        match self {
+            Value::Number(n) if *n >= 0.0 => Some(n.ceil() as u64),
+            Value::String(s) => parse_duration_secs(s),
+            _ => None,
+        }
+    }
+}
+
+/// URI fragment parameters declaring a resource signature (`#minisign`, `#cosign=<uri>`).
+pub const SIGNATURE_PARAMS: [&str; 2] = ["minisign", "cosign"];
+
+/// The signature a resource URI declares in its fragment, with its explicit location if any.
+pub fn declared_signature(uri: &str) -> Option<(&'static str, Option<&str>)> {
+    let (_, fragment) = uri.split_once('#')?;
+    fragment.split('&').find_map(|param| {
+        let (key, location) = param.split_once('=').map_or((param, None), |(k, v)| (k, Some(v)));
+        SIGNATURE_PARAMS.into_iter().find(|kind| *kind == key).map(|kind| (kind, location))
+    })
+}
+
+/// Parse a duration string such as `"500ms"`, `"30s"`, `"5m"` or `"1h"` into whole seconds.
+pub fn parse_duration_secs(input: &str) -> Option<u64> {
+    parse_duration(input).map(|secs| secs.ceil() as u64)
+}
+
+/// Parse a duration string such as `"500ms"` or `"1s"` into whole milliseconds.
+pub fn parse_duration_millis(input: &str) -> Option<u64> {
+    parse_duration(input).map(|secs| (secs * 1000.0).ceil() as u64)
+}
+
+/// Parse a duration string into fractional seconds.
+fn parse_duration(input: &str) -> Option<f64> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(input.len());
+    let (amount, unit) = input.split_at(split);
+    let amount: f64 = amount.parse().ok()?;
+
+    let secs = match unit.trim() {
+        "ms" => amount / 1000.0,
+        "" | "s" => amount,
+        "m" => amount * 60.0,
+        "h" => amount * 3600.0,
+        _ => return None,
+    };
+
+    Some(secs)
 }
 
 impl fmt::Display for Value {
@@ -193,19 +1866,28 @@ impl fmt::Display for Value {
                 write!(f, "]")
             }
             Value::Object(obj) => {
+                // Keys in order, so an object always displays the same
+                let mut entries: Vec<_> = obj.iter().collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
                 write!(f, "{{")?;
-                for (i, (k, v)) in obj.iter().enumerate() {
+                for (i, (k, v)) in entries.into_iter().enumerate() {
                     if i > 0 { write!(f, ", ")?; }
                     write!(f, "\"{}\": {}", k, v)?;
                 }
                 write!(f, "}}")
             }
+            Value::Path(path) => write!(f, "{}", path),
+            Value::Variable(name) => write!(f, "${}", name),
+            Value::Condition(expr) => write!(f, "{}", expr),
+            Value::Tagged(name, obj) => {
+                write!(f, "{} {}", name, Value::Object(obj.clone()))
+            }
         }
     }
 }
 
 /// Represents a deployment configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Deployment {
     /// The name of the deployment.
     pub name: String,
@@ -217,6 +1899,2965 @@ pub struct Deployment {
     pub resources: Option<ResourceRequirements>,
     /// The environment variables for the deployment.
     pub env: Option<HashMap<String, String>>,
+    /// How new agent versions are rolled out.
+    pub rollout: Option<RolloutStrategy>,
+    /// Persistent volumes requested by agents, keyed by agent ID.
+    pub storage: Option<HashMap<String, Storage>>,
+    /// Whether agents refuse model artifacts without a valid signature.
+    pub require_signed: bool,
+    /// The Secret holding the public keys signatures are verified with.
+    pub trusted_keys: Option<String>,
+    /// The Secret `${env.NAME}` references are read from.
+    pub env_secret: Option<String>,
+    /// The cloud infrastructure Terraform is generated for.
+    pub infrastructure: Option<Infrastructure>,
+    /// The orchestrator the agents are deployed on.
+    pub platform: Platform,
+    /// How the replicas of each agent scale with its load.
+    pub scaling: Option<Scaling>,
+    /// The labels added to every generated manifest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labels: Option<HashMap<String, String>>,
+    /// The annotations added to every generated manifest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<HashMap<String, String>>,
+    /// The registry the agent images are pushed to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    /// The tag of the agent images.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+impl Deployment {
+    /// The variables of `env` read from the env Secret, such as `TOKEN: secret("api-token")`,
+    /// with the key each one is read from.
+    pub fn secret_env(&self) -> BTreeMap<&str, &str> {
+        self.env
+            .iter()
+            .flatten()
+            .filter_map(|(name, value)| match placeholders(value).as_slice() {
+                [Placeholder::Env(key)] if value.len() == key.len() + "${env.}".len() => Some((name.as_str(), *key)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The variables of `env` set to their value as written.
+    pub fn plain_env(&self) -> BTreeMap<&str, &str> {
+        let secret = self.secret_env();
+        self.env
+            .iter()
+            .flatten()
+            .filter(|(name, _)| !secret.contains_key(name.as_str()))
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect()
+    }
+}
+
+/// Represents the autoscaling of an agent's replicas.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Scaling {
+    /// The fewest replicas an agent runs.
+    pub min_replicas: u32,
+    /// The most replicas an agent runs.
+    pub max_replicas: u32,
+    /// Average CPU utilization to keep, in percent of the requests.
+    pub target_cpu: Option<u32>,
+    /// Average memory utilization to keep, in percent of the requests.
+    pub target_memory: Option<u32>,
+}
+
+/// Represents the monitoring of a workflow, from its `monitor` block.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Monitor {
+    /// Whether Prometheus scrapes the agents and inference servers of the workflow.
+    pub metrics_enabled: bool,
+    /// How often Prometheus scrapes them, as a duration such as `"30s"`.
+    pub scrape_interval: String,
+    /// The alerts raised on the metrics, from the `alert_*` settings.
+    pub alerts: MonitorAlerts,
+}
+
+/// Represents the alerts of a workflow's `monitor` block; each one is raised only when set.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MonitorAlerts {
+    /// Restarts of an agent's containers within the window that raise an alert, from `alert_threshold`.
+    pub restarts: Option<u32>,
+    /// Fraction of failed LLM requests within the window that raises an alert, from `alert_error_rate`.
+    pub error_rate: Option<f64>,
+    /// Messages blocked by guardrails within the window that raise an alert, from `alert_guardrail_blocks`.
+    pub guardrail_blocks: Option<u32>,
+    /// How long an agent may go without an available replica, such as `"5m"`, from `alert_unavailable`.
+    pub unavailable_for: Option<String>,
+    /// The end-to-end latency the 99th percentile of the workflow's messages must stay below, such as `"2s"`, from `alert_latency_p99`.
+    pub latency_p99: Option<String>,
+    /// The window rates and increases are computed over, from `alert_window`.
+    pub window: String,
+    /// The severity label of the alerts, from `alert_severity`.
+    pub severity: String,
+}
+
+impl MonitorAlerts {
+    /// Whether any alert is set.
+    pub fn any(&self) -> bool {
+        self.restarts.is_some()
+            || self.error_rate.is_some()
+            || self.guardrail_blocks.is_some()
+            || self.unavailable_for.is_some()
+            || self.latency_p99.is_some()
+    }
+}
+
+/// Represents the orchestrator a workflow's agents are deployed on.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    /// Kubernetes manifests, one Deployment (or Rollout, or Job) per agent.
+    #[default]
+    Kubernetes,
+    /// HashiCorp Nomad job specs, one job per agent.
+    Nomad,
+}
+
+/// Represents a persistent volume attached to an agent.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Storage {
+    /// The requested size as a Kubernetes quantity (e.g. `"10Gi"`).
+    pub size: String,
+    /// The path the volume is mounted at.
+    pub path: String,
+    /// The storage class to provision from, or the cluster default.
+    pub class: Option<String>,
+}
+
+/// Represents the cloud infrastructure a workflow is deployed on.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Infrastructure {
+    /// The cloud the supporting services are provisioned on.
+    pub provider: CloudProvider,
+    /// The region they are provisioned in.
+    pub region: String,
+    /// Whether Kafka is the cloud's managed service rather than an in-cluster broker.
+    pub managed_kafka: bool,
+}
+
+/// Represents a cloud Terraform can provision infrastructure on.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CloudProvider {
+    /// Amazon Web Services: S3 buckets and Amazon MSK.
+    Aws,
+    /// Google Cloud: Cloud Storage buckets and Managed Service for Apache Kafka.
+    Gcp,
+}
+
+impl CloudProvider {
+    /// The URI scheme of the cloud's object storage.
+    pub fn bucket_scheme(self) -> &'static str {
+        match self {
+            CloudProvider::Aws => "s3",
+            CloudProvider::Gcp => "gs",
+        }
+    }
+}
+
+/// Represents the strategy used to ship new agent versions.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RolloutStrategy {
+    /// Shift traffic progressively to the new version.
+    Canary(CanaryStrategy),
+    /// Run two slots side by side and switch between them at once.
+    BlueGreen,
+}
+
+/// Represents a canary rollout.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CanaryStrategy {
+    /// Traffic weights (in percent) of the successive steps.
+    pub steps: Vec<f64>,
+    /// Condition that must hold between steps (e.g. `"error_rate < 1%"`).
+    pub analysis: Option<String>,
+}
+
+/// Represents a parsed analysis condition such as `error_rate < 1%`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AnalysisCondition {
+    /// The metric being checked.
+    pub metric: String,
+    /// The comparison operator.
+    pub operator: String,
+    /// The threshold, with percentages converted to fractions.
+    pub threshold: f64,
+}
+
+impl AnalysisCondition {
+    /// Parse a condition of the form `<metric> <op> <number>[%]`.
+    pub fn parse(input: &str) -> std::result::Result<Self, Message> {
+        let input = input.trim();
+        let op_start = input
+            .find(['<', '>', '=', '!'])
+            .ok_or_else(|| message!("missing comparison operator in '{}'", input))?;
+        let op_len = if input[op_start + 1..].starts_with('=') { 2 } else { 1 };
+        let (metric, rest) = input.split_at(op_start);
+        let (operator, threshold) = rest.split_at(op_len);
+
+        let metric = metric.trim();
+        if metric.is_empty() || !metric.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(message!("invalid metric name '{}'", metric));
+        }
+        if !matches!(operator, "<" | "<=" | ">" | ">=" | "==" | "!=") {
+            return Err(message!("invalid operator '{}'", operator));
+        }
+
+        let threshold = threshold.trim();
+        let (number, scale) = match threshold.strip_suffix('%') {
+            Some(number) => (number, 0.01),
+            None => (threshold, 1.0),
+        };
+        let threshold = number
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| message!("invalid threshold '{}'", threshold))?
+            * scale;
+
+        Ok(Self {
+            metric: metric.to_string(),
+            operator: operator.to_string(),
+            threshold,
+        })
+    }
+}
+
+/// Represents how an agent retries a message whose processing failed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts in total, the first one included.
+    pub max_attempts: u32,
+    /// Delays (in milliseconds) before the successive retries; the last one repeats.
+    pub backoff_ms: Vec<u64>,
+}
+
+impl RetryPolicy {
+    /// Attempts when `max_attempts` is not given.
+    pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+    /// Delay between attempts when `backoff` is not given.
+    pub const DEFAULT_BACKOFF_MS: u64 = 1000;
+
+    /// Read a `retry: { max_attempts: 3, backoff: "1s,2s,5s" }` option.
+    ///
+    /// The backoff may also be a single duration or a list of durations.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let Value::Object(options) = value else {
+            return Err(message!("expected an object, found {}", value));
+        };
+
+        let mut policy = Self {
+            max_attempts: Self::DEFAULT_MAX_ATTEMPTS,
+            backoff_ms: vec![Self::DEFAULT_BACKOFF_MS],
+        };
+        for (key, value) in options {
+            match (key.as_str(), value) {
+                ("max_attempts", Value::Number(n)) if *n >= 1.0 && n.fract() == 0.0 && *n <= u32::MAX as f64 => {
+                    policy.max_attempts = *n as u32;
+                }
+                ("max_attempts", other) => {
+                    return Err(message!("max_attempts must be a whole number of at least 1, found {}", other));
+                }
+                ("backoff", value) => policy.backoff_ms = parse_backoff(value)?,
+                (key, _) => return Err(message!("unknown retry setting '{}'", key)),
+            }
+        }
+        Ok(policy)
+    }
+}
+
+/// Read a backoff written as `"1s,2s,5s"`, `"500ms"`, `2` or `["1s", "2s"]`.
+fn parse_backoff(value: &Value) -> std::result::Result<Vec<u64>, Message> {
+    let delay = |value: &Value| match value {
+        Value::Number(n) if *n >= 0.0 => Ok((n * 1000.0).ceil() as u64),
+        Value::String(s) => parse_duration_millis(s).ok_or_else(|| message!("invalid backoff duration '{}'", s.trim())),
+        other => Err(message!("invalid backoff duration {}", other)),
+    };
+    let delays = match value {
+        Value::String(s) => s
+            .split(',')
+            .map(|part| delay(&Value::String(part.to_string())))
+            .collect::<std::result::Result<Vec<_>, _>>()?,
+        Value::Array(items) => items.iter().map(delay).collect::<std::result::Result<Vec<_>, _>>()?,
+        other => vec![delay(other)?],
+    };
+    if delays.is_empty() {
+        return Err(message!("backoff needs at least one duration"));
+    }
+    Ok(delays)
+}
+
+/// Represents what an agent does with a message once its retries are exhausted.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum FallbackConfig {
+    /// Report the failure, as without a fallback.
+    Fail,
+    /// Acknowledge the message and drop it.
+    Skip,
+    /// Publish a default result in place of the failed one.
+    UseDefault {
+        /// The result published.
+        default: Value,
+    },
+    /// Publish the failed message to another subject.
+    DeadLetter {
+        /// The subject failed messages are published to.
+        subject: String,
+    },
+    /// Hand the message to the runtime, which delivers it to the agent again later.
+    RetryLater {
+        /// Milliseconds before the message comes back.
+        after_ms: u64,
+        /// Times a message is retried later before it fails.
+        max_delays: u32,
+    },
+}
+
+impl FallbackConfig {
+    /// Times a message is retried later when `max_delays` isn't given.
+    pub const DEFAULT_MAX_DELAYS: u32 = 3;
+
+    /// Read a `fallback: { action: "use_default", default: {...} }` option.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let Value::Object(options) = value else {
+            return Err(message!("expected an object, found {}", value));
+        };
+        let action = match options.get("action") {
+            Some(Value::String(action)) => action.as_str(),
+            Some(other) => return Err(message!("action must be a string, found {}", other)),
+            None => return Err(message!("missing action")),
+        };
+
+        let allowed: &[&str] = match action {
+            "use_default" => &["action", "default"],
+            "dead_letter" => &["action", "subject"],
+            "retry_later" => &["action", "after", "max_delays"],
+            "fail" | "skip" => &["action"],
+            other => {
+                return Err(message!(
+                    "unknown action '{}' (expected fail, skip, use_default, dead_letter or retry_later)",
+                    other
+                ))
+            }
+        };
+        if let Some(key) = options.keys().find(|key| !allowed.contains(&key.as_str())) {
+            return Err(message!("'{}' is not a setting of the {} action", key, action));
+        }
+
+        match action {
+            "use_default" => match options.get("default") {
+                Some(default) => Ok(Self::UseDefault { default: default.clone() }),
+                None => Err(message!("use_default requires a default")),
+            },
+            "dead_letter" => match options.get("subject") {
+                Some(Value::String(subject)) if !subject.trim().is_empty() => {
+                    Ok(Self::DeadLetter { subject: subject.clone() })
+                }
+                Some(other) => Err(message!("subject must be a non-empty string, found {}", other)),
+                None => Err(message!("dead_letter requires a subject")),
+            },
+            "retry_later" => {
+                let after_ms = match options.get("after") {
+                    Some(Value::String(after)) => parse_duration_millis(after)
+                        .filter(|ms| *ms > 0)
+                        .ok_or_else(|| message!("invalid after '{}'", after.trim()))?,
+                    Some(Value::Number(secs)) if *secs > 0.0 => (secs * 1000.0).ceil() as u64,
+                    Some(other) => return Err(message!("after must be a duration such as \"10m\", found {}", other)),
+                    None => return Err(message!("retry_later requires an after")),
+                };
+                let max_delays = match options.get("max_delays") {
+                    Some(Value::Number(n)) if *n >= 1.0 && n.fract() == 0.0 && *n <= u32::MAX as f64 => *n as u32,
+                    Some(other) => return Err(message!("max_delays must be a whole number of at least 1, found {}", other)),
+                    None => Self::DEFAULT_MAX_DELAYS,
+                };
+                Ok(Self::RetryLater { after_ms, max_delays })
+            }
+            "skip" => Ok(Self::Skip),
+            _ => Ok(Self::Fail),
+        }
+    }
+}
+
+/// A step of a saga, written `compensate: NATS("orders.cancel")` or
+/// `compensate: NATS("orders.cancel", correlation: data.order_id)`.
+///
+/// Every message an agent with a compensation processes is recorded under its
+/// correlation ID in the state store; when an agent of the workflow fails a
+/// message for good, the steps recorded for its correlation ID are undone by
+/// publishing the messages they processed on their compensation subjects, the
+/// latest first.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CompensationConfig {
+    /// The subject the compensating messages are published on.
+    pub subject: String,
+    /// Path of the correlation ID in the messages, starting with `data`.
+    pub correlation: Vec<String>,
+}
+
+impl CompensationConfig {
+    /// The name compensations are written with.
+    pub const NATS: &'static str = "NATS";
+    /// The key the subject is read under.
+    pub const SUBJECT: &'static str = "subject";
+    /// Field of the messages holding their correlation ID when none is given.
+    pub const DEFAULT_CORRELATION: &'static str = "correlation_id";
+    /// Seconds the steps of a saga are kept after the last one.
+    pub const TTL_SECS: u64 = 24 * 60 * 60;
+    /// Every setting of `NATS(...)`.
+    const SETTINGS: [&'static str; 2] = ["subject", "correlation"];
+
+    /// Read a `NATS("<subject>")` or `NATS("<subject>", correlation: data.<field>)` value.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let options = match value {
+            Value::Tagged(name, options) if name == Self::NATS => options,
+            other => return Err(message!("compensate must be a NATS(\"<subject>\") subject, found {}", other)),
+        };
+        let mut unknown: Vec<&String> = options.keys().filter(|key| !Self::SETTINGS.contains(&key.as_str())).collect();
+        unknown.sort();
+        if let Some(key) = unknown.first() {
+            return Err(message!("unknown compensation setting '{}'", key));
+        }
+
+        let subject = match options.get(Self::SUBJECT) {
+            Some(Value::String(subject)) if !subject.trim().is_empty() => subject.trim().to_string(),
+            _ => return Err(message!("the compensation subject is empty")),
+        };
+        if subject.contains(['*', '>', ' ']) {
+            return Err(message!("the compensation subject {} must not have wildcards or spaces", subject));
+        }
+
+        let correlation: Vec<String> = match options.get("correlation") {
+            Some(Value::Path(path) | Value::String(path)) => path.split('.').map(str::to_string).collect(),
+            Some(other) => return Err(message!("correlation must be a path below data such as data.order_id, found {}", other)),
+            None => vec!["data".to_string(), Self::DEFAULT_CORRELATION.to_string()],
+        };
+        if correlation.len() < 2 || correlation[0] != "data" || correlation.iter().any(|segment| segment.trim().is_empty()) {
+            return Err(message!("correlation must be a path below data such as data.order_id, found {}", correlation.join(".")));
+        }
+        Ok(Self { subject, correlation })
+    }
+
+    /// Read the `compensate` option of an agent, if it has one.
+    pub fn from_agent(agent: &Agent) -> std::result::Result<Option<Self>, Message> {
+        agent.config_value(COMPENSATE_OPTION).map(Self::from_value).transpose()
+    }
+}
+
+/// A statistic a quality monitor computes over a window of sampled messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityMetric {
+    /// Fraction of the messages where a field is missing or null.
+    NullRate,
+    /// Mean of the numeric values of a field.
+    Mean,
+    /// Standard deviation of the numeric values of a field.
+    Stddev,
+    /// Smallest numeric value of a field.
+    Min,
+    /// Largest numeric value of a field.
+    Max,
+    /// Number of distinct values of a field.
+    Distinct,
+    /// Fraction of the messages conforming to the monitor's input schema.
+    Conformity,
+}
+
+impl QualityMetric {
+    /// Every metric, in the order reports list them.
+    pub const ALL: [QualityMetric; 7] = [
+        QualityMetric::NullRate,
+        QualityMetric::Mean,
+        QualityMetric::Stddev,
+        QualityMetric::Min,
+        QualityMetric::Max,
+        QualityMetric::Distinct,
+        QualityMetric::Conformity,
+    ];
+
+    /// The metric as written in `metrics` and `thresholds`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QualityMetric::NullRate => "null_rate",
+            QualityMetric::Mean => "mean",
+            QualityMetric::Stddev => "stddev",
+            QualityMetric::Min => "min",
+            QualityMetric::Max => "max",
+            QualityMetric::Distinct => "distinct",
+            QualityMetric::Conformity => "conformity",
+        }
+    }
+
+    /// Read a metric name.
+    pub fn parse(name: &str) -> std::result::Result<Self, Message> {
+        Self::ALL.into_iter().find(|metric| metric.as_str() == name).ok_or_else(|| {
+            let names: Vec<&str> = Self::ALL.iter().map(|metric| metric.as_str()).collect();
+            message!("unknown metric '{}' (expected {})", name, names.join(", "))
+        })
+    }
+
+    /// Whether the metric is a fraction, whose drift is absolute rather than relative.
+    pub fn is_rate(self) -> bool {
+        matches!(self, QualityMetric::NullRate | QualityMetric::Conformity)
+    }
+}
+
+/// Represents what a `QualityMonitor` agent samples, measures and reports.
+///
+/// The first window of sampled messages is the baseline; every later window
+/// is compared with it, and a metric drifting further than its threshold is
+/// reported as drift. Rates drift by their difference with the baseline, the
+/// other metrics by that difference relative to the baseline.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QualityMonitorConfig {
+    /// Fraction of the messages sampled, above 0 and at most 1.
+    pub sample_rate: f64,
+    /// Sampled messages per report.
+    pub window: u32,
+    /// Metrics computed, without repetitions.
+    pub metrics: Vec<QualityMetric>,
+    /// Drift allowed per metric before it is reported.
+    pub thresholds: BTreeMap<QualityMetric, f64>,
+    /// Fields measured; empty for the fields of the input schema or, without one, of the messages.
+    pub fields: Vec<String>,
+}
+
+impl QualityMonitorConfig {
+    /// Fraction of the messages sampled when `sample_rate` is not given.
+    pub const DEFAULT_SAMPLE_RATE: f64 = 0.1;
+    /// Sampled messages per report when `window` is not given.
+    pub const DEFAULT_WINDOW: u32 = 500;
+
+    /// Read the `sample_rate`, `window`, `metrics`, `thresholds` and `fields` options of a monitor.
+    ///
+    /// Without `metrics` every metric is computed; without `thresholds` the
+    /// reports carry the metrics but no drift.
+    pub fn from_agent(agent: &Agent) -> std::result::Result<Self, Message> {
+        let mut config = Self {
+            sample_rate: Self::DEFAULT_SAMPLE_RATE,
+            window: Self::DEFAULT_WINDOW,
+            metrics: QualityMetric::ALL.to_vec(),
+            thresholds: BTreeMap::new(),
+            fields: Vec::new(),
+        };
+
+        match agent.config_value(SAMPLE_RATE_OPTION) {
+            Some(Value::Number(n)) if *n > 0.0 && *n <= 1.0 => config.sample_rate = *n,
+            Some(other) => return Err(message!("sample_rate must be a number above 0 and at most 1, found {}", other)),
+            None => {}
+        }
+        match agent.config_value(WINDOW_OPTION) {
+            Some(Value::Number(n)) if *n >= 1.0 && n.fract() == 0.0 && *n <= u32::MAX as f64 => config.window = *n as u32,
+            Some(other) => return Err(message!("window must be a whole number of at least 1, found {}", other)),
+            None => {}
+        }
+        match agent.config_value(METRICS_OPTION) {
+            Some(Value::Array(items)) if !items.is_empty() => {
+                config.metrics.clear();
+                for item in items {
+                    let Value::String(name) = item else {
+                        return Err(message!("metrics must be metric names, found {}", item));
+                    };
+                    let metric = QualityMetric::parse(name)?;
+                    if config.metrics.contains(&metric) {
+                        return Err(message!("metric '{}' is listed twice", name));
+                    }
+                    config.metrics.push(metric);
+                }
+            }
+            Some(other) => return Err(message!("metrics must be a non-empty list, found {}", other)),
+            None => {}
+        }
+        match agent.config_value(THRESHOLDS_OPTION) {
+            Some(Value::Object(thresholds)) => {
+                for (name, value) in thresholds {
+                    let metric = QualityMetric::parse(name)?;
+                    if !config.metrics.contains(&metric) {
+                        return Err(message!("threshold for '{}', which is not in metrics", name));
+                    }
+                    match value {
+                        Value::Number(n) if *n >= 0.0 => config.thresholds.insert(metric, *n),
+                        other => return Err(message!("the threshold of '{}' must be a number of at least 0, found {}", name, other)),
+                    };
+                }
+            }
+            Some(other) => return Err(message!("thresholds must be an object, found {}", other)),
+            None => {}
+        }
+        match agent.config_value(FIELDS_OPTION) {
+            Some(Value::Array(items)) => {
+                for item in items {
+                    match item {
+                        Value::String(field) if !field.trim().is_empty() => config.fields.push(field.clone()),
+                        other => return Err(message!("fields must be field names, found {}", other)),
+                    }
+                }
+            }
+            Some(other) => return Err(message!("fields must be a list, found {}", other)),
+            None => {}
+        }
+        Ok(config)
+    }
+}
+
+/// How a reduction of an `Aggregator` folds the values of a window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reducer {
+    /// Number of messages, or of non-null values with an argument.
+    Count,
+    /// Sum of the numeric values.
+    Sum,
+    /// Mean of the numeric values.
+    Avg,
+    /// Smallest number or string.
+    Min,
+    /// Largest number or string.
+    Max,
+    /// Value of the first message of the window.
+    First,
+    /// Value of the last message of the window.
+    Last,
+    /// Every value, in arrival order.
+    Collect,
+}
+
+impl Reducer {
+    /// Every reducer, in the order they are listed in messages.
+    pub const ALL: [Reducer; 8] = [
+        Reducer::Count,
+        Reducer::Sum,
+        Reducer::Avg,
+        Reducer::Min,
+        Reducer::Max,
+        Reducer::First,
+        Reducer::Last,
+        Reducer::Collect,
+    ];
+
+    /// The reducer as written in a `reduce:` clause.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Reducer::Count => "count",
+            Reducer::Sum => "sum",
+            Reducer::Avg => "avg",
+            Reducer::Min => "min",
+            Reducer::Max => "max",
+            Reducer::First => "first",
+            Reducer::Last => "last",
+            Reducer::Collect => "collect",
+        }
+    }
+
+    /// Read a reducer name.
+    pub fn parse(name: &str) -> std::result::Result<Self, Message> {
+        Self::ALL.into_iter().find(|reducer| reducer.as_str() == name).ok_or_else(|| {
+            let names: Vec<&str> = Self::ALL.iter().map(|reducer| reducer.as_str()).collect();
+            message!("unknown reducer '{}' (expected {})", name, names.join(", "))
+        })
+    }
+
+    /// Whether the reducer only folds numbers.
+    pub fn is_numeric(self) -> bool {
+        matches!(self, Reducer::Sum | Reducer::Avg)
+    }
+
+    /// Whether the reducer orders its values.
+    pub fn is_ordering(self) -> bool {
+        matches!(self, Reducer::Min | Reducer::Max)
+    }
+}
+
+/// A field an `Aggregator` computes over a window, such as `total: sum(data.amount)`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Reduction {
+    /// How the values are folded.
+    pub reducer: Reducer,
+    /// The value read from every message; only `count()` goes without.
+    pub of: Option<Expr>,
+}
+
+impl Reduction {
+    /// Setting of the reduction value holding its argument.
+    pub const ARGUMENT: &'static str = "of";
+
+    /// Read a reduction as parsed from a `reduce:` clause.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let (name, arguments) = match value {
+            Value::Tagged(name, arguments) => (name, arguments),
+            other => return Err(message!("expected a reduction such as sum(data.amount), found {}", other)),
+        };
+        let reducer = Reducer::parse(name)?;
+        let of = match arguments.get(Self::ARGUMENT) {
+            Some(Value::Condition(expr)) => Some((**expr).clone()),
+            Some(other) => return Err(message!("expected a reduction such as sum(data.amount), found {}", other)),
+            None => None,
+        };
+        if of.is_none() && reducer != Reducer::Count {
+            return Err(message!("{} needs a value such as {}(data.amount)", name, name));
+        }
+        Ok(Self { reducer, of })
+    }
+}
+
+impl fmt::Display for Reduction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.of {
+            Some(expr) => write!(f, "{}({})", self.reducer.as_str(), expr),
+            None => write!(f, "{}()", self.reducer.as_str()),
+        }
+    }
+}
+
+/// The window an `Aggregator` reduces messages over.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregationWindow {
+    /// Closes when a group has received this many messages, written `window: { count: 100 }`.
+    Count(u32),
+    /// Tumbling window of this many milliseconds, written `window: { time: "1m" }`.
+    Time(u64),
+}
+
+/// Represents how an `Aggregator` agent groups and reduces its messages.
+///
+/// Messages are grouped by the values of the `group_by` fields and folded
+/// into the reductions of their group. When the window of a group closes,
+/// one message with the group fields and the reductions is published and
+/// the group starts over.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AggregatorConfig {
+    /// When the window of a group closes.
+    pub window: AggregationWindow,
+    /// Paths of the fields messages are grouped by, starting with `data`; empty for a single group.
+    pub group_by: Vec<Vec<String>>,
+    /// Fields computed over each window, by name.
+    pub reductions: BTreeMap<String, Reduction>,
+}
+
+impl AggregatorConfig {
+    /// Field of the published messages describing their window.
+    pub const WINDOW_FIELD: &'static str = "window";
+
+    /// Read the `window`, `group_by` and `reduce` options of an aggregator.
+    ///
+    /// The published messages carry every group field under its last
+    /// segment, so those names and the reductions must not clash.
+    pub fn from_agent(agent: &Agent) -> std::result::Result<Self, Message> {
+        let window = match agent.config_value(WINDOW_OPTION) {
+            Some(Value::Object(window)) => match (window.get("count"), window.get("time")) {
+                (Some(_), Some(_)) => return Err(message!("give a count or a time window, not both")),
+                (Some(Value::Number(n)), None) if *n >= 1.0 && n.fract() == 0.0 && *n <= u32::MAX as f64 => {
+                    AggregationWindow::Count(*n as u32)
+                }
+                (Some(other), None) => return Err(message!("count must be a whole number of at least 1, found {}", other)),
+                (None, Some(Value::String(time))) => match parse_duration_millis(time) {
+                    Some(millis) if millis > 0 => AggregationWindow::Time(millis),
+                    _ => return Err(message!("time must be a duration such as \"1m\", found {}", Value::String(time.clone()))),
+                },
+                (None, Some(other)) => return Err(message!("time must be a duration such as \"1m\", found {}", other)),
+                (None, None) => return Err(message!("window needs a count or a time, such as {{ count: 100 }} or {{ time: \"1m\" }}")),
+            },
+            Some(other) => return Err(message!("window must be an object such as {{ count: 100 }} or {{ time: \"1m\" }}, found {}", other)),
+            None => return Err(message!("missing window, such as {{ count: 100 }} or {{ time: \"1m\" }}")),
+        };
+
+        let keys = match agent.config_value(GROUP_BY_OPTION) {
+            Some(Value::Array(items)) => items.iter().collect(),
+            Some(value) => vec![value],
+            None => Vec::new(),
+        };
+        let mut group_by: Vec<Vec<String>> = Vec::new();
+        let mut names = BTreeSet::new();
+        for key in keys {
+            let path: Vec<String> = match key {
+                Value::Path(path) | Value::String(path) => path.split('.').map(str::to_string).collect(),
+                other => return Err(message!("group_by must be paths below data such as data.customer_id, found {}", other)),
+            };
+            if path.len() < 2 || path[0] != "data" || path.iter().any(|segment| segment.trim().is_empty()) {
+                return Err(message!("group_by must be paths below data such as data.customer_id, found {}", path.join(".")));
+            }
+            if !names.insert(path[path.len() - 1].clone()) {
+                return Err(message!("two group_by fields are named '{}'", path[path.len() - 1]));
+            }
+            group_by.push(path);
+        }
+
+        let reductions = match agent.config_value(REDUCE_OPTION) {
+            Some(Value::Object(reductions)) if !reductions.is_empty() => reductions,
+            Some(other) => return Err(message!("reduce must list reductions such as {{ total: sum(data.amount) }}, found {}", other)),
+            None => return Err(message!("missing reduce, such as reduce: {{ total: sum(data.amount) }}")),
+        };
+        let mut config = Self { window, group_by, reductions: BTreeMap::new() };
+        for (name, value) in reductions {
+            if names.contains(name) || name == Self::WINDOW_FIELD {
+                return Err(message!("reduction '{}' clashes with a field of the published messages", name));
+            }
+            let reduction = Reduction::from_value(value).map_err(|e| message!("reduction '{}': {}", name, e))?;
+            config.reductions.insert(name.clone(), reduction);
+        }
+        Ok(config)
+    }
+
+    /// Names of the group fields in the published messages, in `group_by` order.
+    pub fn group_names(&self) -> Vec<&str> {
+        self.group_by.iter().filter_map(|path| path.last().map(String::as_str)).collect()
+    }
+}
+
+/// What a rule of a `RuleEngine` does to the messages matching it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    /// Set fields of the message, written `set(risk: "high", review: true)`.
+    Set(BTreeMap<String, Value>),
+    /// Publish a copy of the message on a topic, written `publish(topic: "orders.vip")`.
+    Publish(String),
+    /// Discard the message and skip the rules after it, written `drop()`.
+    Drop,
+}
+
+impl RuleAction {
+    /// Setting of the value of a rule holding its condition.
+    pub const WHEN: &'static str = "when";
+    /// Setting of the value of a rule holding its action.
+    pub const THEN: &'static str = "then";
+
+    /// Name of the action, as written in a rule.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RuleAction::Set(_) => "set",
+            RuleAction::Publish(_) => "publish",
+            RuleAction::Drop => "drop",
+        }
+    }
+
+    /// Read an action as parsed from a rule, such as `set(risk: "high")`.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let (name, arguments) = match value {
+            Value::Tagged(name, arguments) => (name.as_str(), arguments),
+            other => return Err(message!("expected an action such as set(risk: \"high\"), found {}", other)),
+        };
+        match name {
+            "set" => {
+                if arguments.is_empty() {
+                    return Err(message!("set needs the fields to set, such as set(risk: \"high\")"));
+                }
+                let mut fields = BTreeMap::new();
+                for (field, value) in arguments {
+                    if matches!(value, Value::Path(_) | Value::Tagged(..) | Value::Variable(_) | Value::Condition(_)) {
+                        return Err(message!("set writes literal values, found {}: {}", field, value));
+                    }
+                    fields.insert(field.clone(), value.clone());
+                }
+                Ok(RuleAction::Set(fields))
+            }
+            "publish" => match (arguments.get("topic"), arguments.len()) {
+                (Some(Value::String(topic)), 1) if !topic.trim().is_empty() => Ok(RuleAction::Publish(topic.clone())),
+                _ => Err(message!("publish needs a topic and nothing else, such as publish(topic: \"orders.vip\")")),
+            },
+            "drop" if arguments.is_empty() => Ok(RuleAction::Drop),
+            "drop" => Err(message!("drop takes no arguments")),
+            other => Err(message!("unknown action '{}' (expected set, publish or drop)", other)),
+        }
+    }
+}
+
+/// A rule of a `RuleEngine`, such as `high_risk: data.score > 0.9 => set(risk: "high")`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EngineRule {
+    /// Name of the rule, labelling its hits.
+    pub name: String,
+    /// Condition the messages matching the rule satisfy.
+    pub when: Expr,
+    /// What the rule does to the messages matching it.
+    pub then: RuleAction,
+}
+
+/// Represents the rules of a `RuleEngine` agent.
+///
+/// Every message is checked against the conditions of all the rules as it
+/// was received, and the rules it matches act on it in order: `set` fields,
+/// `publish` a copy of the message as it stands on another topic, or `drop`
+/// it, which skips the rules after it. The message is then published on the
+/// agent's output topic, unless it was dropped.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuleEngineConfig {
+    /// The rules, in evaluation order.
+    pub rules: Vec<EngineRule>,
+}
+
+impl RuleEngineConfig {
+    /// Read the `rules` option of a rule engine.
+    pub fn from_agent(agent: &Agent) -> std::result::Result<Self, Message> {
+        let items = match agent.config_value(RULES_OPTION) {
+            Some(Value::Array(items)) if !items.is_empty() => items,
+            Some(other) => {
+                return Err(message!(
+                    "rules must list rules such as [high_risk: data.score > 0.9 => set(risk: \"high\")], found {}",
+                    other
+                ))
+            }
+            None => return Err(message!("missing rules, such as rules: [high_risk: data.score > 0.9 => set(risk: \"high\")]")),
+        };
+        let mut names = BTreeSet::new();
+        let mut rules = Vec::new();
+        for item in items {
+            let (name, rule) = match item {
+                Value::Tagged(name, rule) => (name, rule),
+                other => {
+                    return Err(message!(
+                        "rules must list rules such as [high_risk: data.score > 0.9 => set(risk: \"high\")], found {}",
+                        other
+                    ))
+                }
+            };
+            if !names.insert(name.as_str()) {
+                return Err(message!("two rules are named '{}'", name));
+            }
+            let when = match rule.get(RuleAction::WHEN) {
+                Some(Value::Condition(expr)) => (**expr).clone(),
+                _ => return Err(message!("rule '{}' needs a condition such as data.score > 0.9", name)),
+            };
+            let then = match rule.get(RuleAction::THEN) {
+                Some(value) => RuleAction::from_value(value).map_err(|e| message!("rule '{}': {}", name, e))?,
+                None => return Err(message!("rule '{}' needs an action such as set(risk: \"high\")", name)),
+            };
+            rules.push(EngineRule { name: name.clone(), when, then });
+        }
+        Ok(Self { rules })
+    }
+}
+
+/// File format of the network of a `BayesianNetwork` agent, told by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkFormat {
+    /// Bayesian Interchange Format, `.bif`.
+    Bif,
+    /// XML Bayesian Interchange Format, `.xmlbif`.
+    XmlBif,
+    /// Hugin network, `.net`.
+    Net,
+    /// UAI model, `.uai`.
+    Uai,
+}
+
+impl NetworkFormat {
+    /// Every format, in the order they are listed in messages.
+    pub const ALL: [NetworkFormat; 4] = [NetworkFormat::Bif, NetworkFormat::XmlBif, NetworkFormat::Net, NetworkFormat::Uai];
+
+    /// Extension of the files of the format, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            NetworkFormat::Bif => "bif",
+            NetworkFormat::XmlBif => "xmlbif",
+            NetworkFormat::Net => "net",
+            NetworkFormat::Uai => "uai",
+        }
+    }
+}
+
+/// Represents the network a `BayesianNetwork` agent queries.
+///
+/// The fields of every message named after variables of the network are
+/// the evidence; the posteriors of the `query` variables, or of every
+/// variable not observed, are added to the message under `posteriors`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BayesianNetworkConfig {
+    /// Path or URI of the network file.
+    pub network_path: String,
+    /// Format of the network file.
+    pub format: NetworkFormat,
+    /// Variables whose posteriors are published; empty for every variable not observed.
+    pub query: Vec<String>,
+}
+
+impl BayesianNetworkConfig {
+    /// Field of the published messages holding the posteriors.
+    pub const POSTERIORS_FIELD: &'static str = "posteriors";
+
+    /// Read the `network_path` and `query` options of a Bayesian network agent.
+    pub fn from_agent(agent: &Agent) -> std::result::Result<Self, Message> {
+        let network_path = match agent.config_value(NETWORK_PATH_OPTION) {
+            Some(Value::String(path)) if !path.trim().is_empty() => path.trim().to_string(),
+            Some(Value::String(_)) => return Err(message!("the network_path is empty")),
+            Some(other) => return Err(message!("network_path must be the path of the network such as \"models/risk.bif\", found {}", other)),
+            None => return Err(message!("missing network_path, such as network_path: \"models/risk.bif\"")),
+        };
+
+        // The extension is that of the archive member, if any, without the fragment or query
+        let path = network_path.rsplit('!').next().unwrap_or(&network_path);
+        let path = path.split(['#', '?']).next().unwrap_or(path).to_ascii_lowercase();
+        let format = NetworkFormat::ALL
+            .into_iter()
+            .find(|format| path.ends_with(&format!(".{}", format.extension())))
+            .ok_or_else(|| message!("the network {} must be a .bif, .xmlbif, .net or .uai file", network_path))?;
+
+        let variables = match agent.config_value(QUERY_OPTION) {
+            Some(Value::Array(items)) => items.as_slice(),
+            Some(other) => return Err(message!("query must list variables of the network such as [\"fraud\"], found {}", other)),
+            None => &[],
+        };
+        let mut query = Vec::new();
+        for variable in variables {
+            match variable {
+                Value::String(name) if !name.trim().is_empty() => {
+                    if query.contains(name) {
+                        return Err(message!("query lists '{}' twice", name));
+                    }
+                    query.push(name.clone());
+                }
+                other => return Err(message!("query must list variables of the network such as [\"fraud\"], found {}", other)),
+            }
+        }
+        Ok(Self { network_path, format, query })
+    }
+}
+
+/// Represents the templates a `Custom` agent is generated from.
+///
+/// `Custom("scorer", language: "python", ...)` is generated from the
+/// templates under `agents/scorer/` of the project's or the user's
+/// templates, in the language they are written in. The rest of its options
+/// reach the templates as they are, through `agent.config`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CustomAgentConfig {
+    /// Name of the directory of the agent's templates, below `agents/`.
+    pub kind: String,
+    /// Language of the templates, `rust` by default.
+    pub language: String,
+}
+
+impl CustomAgentConfig {
+    /// Languages custom templates can be written in.
+    pub const LANGUAGES: [&'static str; 2] = ["rust", "python"];
+
+    /// Read the name of the templates and the `language` option of a custom agent.
+    pub fn from_agent(agent: &Agent) -> std::result::Result<Self, Message> {
+        let kind = match agent.config.iter().find_map(|arg| match arg {
+            Argument::Positional(value, _) => Some(value),
+            _ => None,
+        }) {
+            Some(Value::String(kind)) => kind.trim().to_string(),
+            Some(other) => return Err(message!("the name of the templates must be a string such as Custom(\"scorer\", ...), found {}", other)),
+            None => return Err(message!("missing the name of the templates, such as Custom(\"scorer\", ...)")),
+        };
+        let mut chars = kind.chars();
+        let valid = chars.next().is_some_and(|first| first.is_ascii_alphanumeric())
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(message!("the name of the templates '{}' must be letters, digits, '-' or '_', such as \"fraud-scorer\"", kind));
+        }
+        // The directories of the built-in agents and languages are not the user's to fill
+        let mut builtin = AgentType::ALL.iter().map(ToString::to_string).chain(["python", "rust", "typescript"].map(String::from));
+        if builtin.any(|name| name.eq_ignore_ascii_case(&kind)) {
+            return Err(message!("the name of the templates '{}' is reserved for the built-in templates", kind));
+        }
+
+        let language = match agent.config_value(LANGUAGE_OPTION) {
+            Some(Value::String(language)) if Self::LANGUAGES.contains(&language.as_str()) => language.clone(),
+            Some(other) => return Err(message!("language must be \"rust\" or \"python\", found {}", other)),
+            None => "rust".to_string(),
+        };
+        Ok(Self { kind, language })
+    }
+}
+
+/// How a `DataNormalizer` rescales a numeric field.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Scaler {
+    /// Map `[min, max]` onto `[0, 1]`, written `minmax(min: 0, max: 10000)`.
+    MinMax {
+        /// Value mapped to 0.
+        min: f64,
+        /// Value mapped to 1.
+        max: f64,
+    },
+    /// Standardize to a mean of 0 and a deviation of 1, written `zscore(mean: 50, std: 10)`.
+    ZScore {
+        /// Mean of the field.
+        mean: f64,
+        /// Standard deviation of the field.
+        std: f64,
+    },
+}
+
+impl Scaler {
+    /// Name of the scaler, as written in the DSL.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scaler::MinMax { .. } => "minmax",
+            Scaler::ZScore { .. } => "zscore",
+        }
+    }
+
+    /// The value subtracted from the field and what the difference is divided by.
+    pub fn offset_and_divisor(&self) -> (f64, f64) {
+        match *self {
+            Scaler::MinMax { min, max } => (min, max - min),
+            Scaler::ZScore { mean, std } => (mean, std),
+        }
+    }
+
+    /// Read a scaler, such as `minmax(min: 0, max: 10000)`.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let (name, arguments) = match value {
+            Value::Tagged(name, arguments) => (name.as_str(), arguments),
+            other => return Err(message!("expected a scaler such as minmax(min: 0, max: 100) or zscore(mean: 50, std: 10), found {}", other)),
+        };
+        let (expected, listed): (&[&str], Message) = match name {
+            "minmax" => (&["min", "max"], message!("min and max")),
+            "zscore" => (&["mean", "std"], message!("mean and std")),
+            other => return Err(message!("unknown scaler '{}' (expected minmax or zscore)", other)),
+        };
+        if let Some(unknown) = arguments.keys().find(|key| !expected.contains(&key.as_str())) {
+            return Err(message!("unknown {} setting '{}'", name, unknown));
+        }
+        let number = |key: &str| match arguments.get(key) {
+            Some(Value::Number(n)) => Ok(*n),
+            Some(other) => Err(message!("{} of {} must be a number, found {}", key, name, other)),
+            None => Err(message!("{} needs {}", name, listed)),
+        };
+        match name {
+            "minmax" => {
+                let (min, max) = (number("min")?, number("max")?);
+                if max <= min {
+                    return Err(message!("the max of minmax must be above its min, found {} and {}", min, max));
+                }
+                Ok(Scaler::MinMax { min, max })
+            }
+            _ => {
+                let (mean, std) = (number("mean")?, number("std")?);
+                if std <= 0.0 {
+                    return Err(message!("the std of zscore must be positive, found {}", std));
+                }
+                Ok(Scaler::ZScore { mean, std })
+            }
+        }
+    }
+}
+
+/// A field a `DataNormalizer` moves, such as `amount: data.payment.amount`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FieldMapping {
+    /// Top-level field of the normalized message.
+    pub field: String,
+    /// Path of the field it is read from, starting with `data`.
+    pub from: Vec<String>,
+}
+
+/// Represents the settings of a `DataNormalizer` agent.
+///
+/// Every message gets its `mappings` applied first: each value is read from
+/// its path, removed from there, and written to its top-level field, so
+/// mappings may swap fields. The `scale` fields of the result are then
+/// rescaled. Fields neither mapped nor scaled are kept as they are.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DataNormalizerConfig {
+    /// Fields moved, by name of their new field.
+    pub mappings: Vec<FieldMapping>,
+    /// Numeric top-level fields rescaled, by name.
+    pub scale: BTreeMap<String, Scaler>,
+}
+
+/// Whether a key names a top-level field, such as `amount`.
+fn is_field_name(key: &str) -> bool {
+    !key.trim().is_empty() && !key.contains('.')
+}
+
+/// Path of a field below the message written as a key, such as `"customer.tier"`.
+fn key_path(key: &str) -> Option<Vec<String>> {
+    let path: Vec<String> = key.split('.').map(str::to_string).collect();
+    path.iter().all(|segment| !segment.trim().is_empty()).then_some(path)
+}
+
+impl DataNormalizerConfig {
+    /// Read the `mappings` and `scale` options of a data normalizer.
+    pub fn from_agent(agent: &Agent) -> std::result::Result<Self, Message> {
+        let mut mappings = Vec::new();
+        match agent.config_value(MAPPINGS_OPTION) {
+            Some(Value::Object(items)) => {
+                let mut sources = BTreeSet::new();
+                let items: BTreeMap<&String, &Value> = items.iter().collect();
+                for (field, value) in items {
+                    if !is_field_name(field) {
+                        return Err(message!("mapping '{}' must name a top-level field such as amount", field));
+                    }
+                    let from: Vec<String> = match value {
+                        Value::Path(path) | Value::String(path) => path.split('.').map(str::to_string).collect(),
+                        other => return Err(message!("mapping '{}' must read a path below data such as data.payment.amount, found {}", field, other)),
+                    };
+                    if from.len() < 2 || from[0] != "data" || from.iter().any(|segment| segment.trim().is_empty()) {
+                        return Err(message!(
+                            "mapping '{}' must read a path below data such as data.payment.amount, found {}",
+                            field,
+                            from.join(".")
+                        ));
+                    }
+                    if !sources.insert(from.join(".")) {
+                        return Err(message!("two mappings read {}", from.join(".")));
+                    }
+                    mappings.push(FieldMapping { field: field.clone(), from });
+                }
+            }
+            Some(other) => return Err(message!("mappings must map fields to paths such as {{ amount: data.payment.amount }}, found {}", other)),
+            None => {}
+        }
+
+        let mut scale = BTreeMap::new();
+        match agent.config_value(SCALE_OPTION) {
+            Some(Value::Object(items)) => {
+                for (field, value) in items {
+                    if !is_field_name(field) {
+                        return Err(message!("scale '{}' must name a top-level field such as amount", field));
+                    }
+                    let scaler = Scaler::from_value(value).map_err(|e| message!("scale of {}: {}", field, e))?;
+                    scale.insert(field.clone(), scaler);
+                }
+            }
+            Some(other) => return Err(message!("scale must map fields to scalers such as {{ amount: minmax(min: 0, max: 100) }}, found {}", other)),
+            None => {}
+        }
+
+        if mappings.is_empty() && scale.is_empty() {
+            return Err(message!("missing mappings or scale, such as mappings: {{ amount: data.payment.amount }}"));
+        }
+        Ok(Self { mappings, scale })
+    }
+
+    /// Path below the message a top-level field of the normalized message is read from.
+    pub fn source_of(&self, field: &str) -> Vec<String> {
+        match self.mappings.iter().find(|mapping| mapping.field == field) {
+            Some(mapping) => mapping.from.clone(),
+            None => vec!["data".to_string(), field.to_string()],
+        }
+    }
+}
+
+/// How a `MissingValueHandler` fills a field from the values it has seen.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Imputation {
+    /// The mean of the recent values, written `mean()`.
+    Mean,
+    /// The median of the recent values, written `median()`.
+    Median,
+    /// The last value seen, written `last()`.
+    Last,
+}
+
+impl Imputation {
+    /// Name of the imputation, as written in the DSL.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Imputation::Mean => "mean",
+            Imputation::Median => "median",
+            Imputation::Last => "last",
+        }
+    }
+
+    /// Whether the imputation only applies to numbers.
+    pub fn is_numeric(self) -> bool {
+        matches!(self, Imputation::Mean | Imputation::Median)
+    }
+
+    /// Read an imputation, such as `mean()`.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let (name, arguments) = match value {
+            Value::Tagged(name, arguments) => (name.as_str(), arguments),
+            other => return Err(message!("expected an imputation such as mean(), found {}", other)),
+        };
+        let imputation = match name {
+            "mean" => Imputation::Mean,
+            "median" => Imputation::Median,
+            "last" => Imputation::Last,
+            other => return Err(message!("unknown imputation '{}' (expected mean, median or last)", other)),
+        };
+        if !arguments.is_empty() {
+            return Err(message!("{}() takes no arguments", name));
+        }
+        Ok(imputation)
+    }
+}
+
+/// Represents the settings of a `MissingValueHandler` agent.
+///
+/// A field is missing when it is absent or null. Missing `impute` fields are
+/// filled from the last `window` values the agent has seen of them, and
+/// missing `defaults` fields with their literal value; fields are written
+/// below the message, creating the objects on their path.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MissingValueHandlerConfig {
+    /// Literal values of the fields filled, by path below the message such as `customer.tier`.
+    pub defaults: BTreeMap<String, Value>,
+    /// Imputations of the fields filled from the values seen, by path below the message.
+    pub impute: BTreeMap<String, Imputation>,
+    /// Recent values of each field the imputations are computed over.
+    pub window: u32,
+}
+
+impl MissingValueHandlerConfig {
+    /// Recent values of each field the imputations are computed over, unless `window` says otherwise.
+    pub const DEFAULT_WINDOW: u32 = 1000;
+
+    /// Read the `defaults`, `impute` and `window` options of a missing value handler.
+    pub fn from_agent(agent: &Agent) -> std::result::Result<Self, Message> {
+        let mut defaults = BTreeMap::new();
+        match agent.config_value(DEFAULTS_OPTION) {
+            Some(Value::Object(items)) => {
+                for (field, value) in items {
+                    if key_path(field).is_none() {
+                        return Err(message!("default '{}' must name a field of the message such as country or \"customer.tier\"", field));
+                    }
+                    match value {
+                        Value::Null => return Err(message!("the default of {} can't be null", field)),
+                        Value::Path(_) | Value::Tagged(..) | Value::Variable(_) | Value::Condition(_) => {
+                            return Err(message!("defaults are literal values, found {}: {}", field, value))
+                        }
+                        _ => {}
+                    }
+                    defaults.insert(field.clone(), value.clone());
+                }
+            }
+            Some(other) => return Err(message!("defaults must map fields to values such as {{ country: \"US\" }}, found {}", other)),
+            None => {}
+        }
+
+        let mut impute = BTreeMap::new();
+        match agent.config_value(IMPUTE_OPTION) {
+            Some(Value::Object(items)) => {
+                for (field, value) in items {
+                    if key_path(field).is_none() {
+                        return Err(message!("impute '{}' must name a field of the message such as amount or \"customer.age\"", field));
+                    }
+                    if defaults.contains_key(field) {
+                        return Err(message!("{} has both a default and an imputation", field));
+                    }
+                    let imputation = Imputation::from_value(value).map_err(|e| message!("impute of {}: {}", field, e))?;
+                    impute.insert(field.clone(), imputation);
+                }
+            }
+            Some(other) => return Err(message!("impute must map fields to imputations such as {{ amount: mean() }}, found {}", other)),
+            None => {}
+        }
+
+        if defaults.is_empty() && impute.is_empty() {
+            return Err(message!("missing defaults or impute, such as defaults: {{ country: \"US\" }}"));
+        }
+        let window = match agent.config_value(WINDOW_OPTION) {
+            Some(Value::Number(n)) if *n >= 1.0 && n.fract() == 0.0 && *n <= u32::MAX as f64 => *n as u32,
+            Some(other) => return Err(message!("window must be a whole number of values of at least 1, found {}", other)),
+            None => Self::DEFAULT_WINDOW,
+        };
+        Ok(Self { defaults, impute, window })
+    }
+
+    /// Path of a field of the settings, starting with `data`.
+    pub fn path(field: &str) -> Vec<String> {
+        std::iter::once("data".to_string()).chain(field.split('.').map(str::to_string)).collect()
+    }
+}
+
+/// What happens to a review still pending when its SLA lapses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlaBreachAction {
+    /// The review is approved automatically.
+    Approve,
+    /// The review is rejected automatically.
+    Reject,
+    /// The review expires undecided.
+    #[default]
+    Expire,
+}
+
+impl SlaBreachAction {
+    /// Every action, in the order they are listed in messages.
+    pub const ALL: [SlaBreachAction; 3] = [SlaBreachAction::Approve, SlaBreachAction::Reject, SlaBreachAction::Expire];
+
+    /// The action as written in `on_sla_breach`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SlaBreachAction::Approve => "approve",
+            SlaBreachAction::Reject => "reject",
+            SlaBreachAction::Expire => "expire",
+        }
+    }
+}
+
+/// A step of the escalation chain of a `HumanReview` agent.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EscalationStep {
+    /// Seconds after the review is submitted when the step is taken.
+    pub after_secs: u64,
+    /// Reviewers or delegation groups notified.
+    pub notify: Vec<String>,
+    /// Notification channel they are notified through.
+    pub via: String,
+}
+
+/// Represents how the reviews of a `HumanReview` agent are handled.
+///
+/// Every message is submitted for review, which notifies the
+/// `notifications` channels, and waits for the decision up to `timeout`:
+/// approved messages go on to the agent's output, and those still pending
+/// when the wait is over go to the `on_timeout` subject. A review still
+/// pending after each escalation step's delay notifies that step's
+/// reviewers; once the SLA lapses the review is approved, rejected or
+/// expired as `on_sla_breach` says. A decision taken by a member of a
+/// delegation group counts as the group's, so several members of a group
+/// make a single approval.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HumanReviewConfig {
+    /// Seconds a review may stay pending, if it has an SLA.
+    pub sla_secs: Option<u64>,
+    /// Escalation steps, in increasing order of delay.
+    pub escalation: Vec<EscalationStep>,
+    /// Members of each delegation group.
+    pub delegation: BTreeMap<String, Vec<String>>,
+    /// Decision taken when the SLA lapses.
+    pub on_sla_breach: SlaBreachAction,
+    /// Seconds a message waits for its review, if not the SLA's.
+    pub timeout_secs: Option<u64>,
+    /// Subject the messages whose review timed out go to, if not the default one.
+    pub on_timeout: Option<String>,
+    /// Channels notified as soon as a review is submitted.
+    pub notifications: Vec<String>,
+    /// Whether the review UI is generated.
+    pub ui: bool,
+}
+
+impl Default for HumanReviewConfig {
+    fn default() -> Self {
+        Self {
+            sla_secs: None,
+            escalation: Vec::new(),
+            delegation: BTreeMap::new(),
+            on_sla_breach: SlaBreachAction::default(),
+            timeout_secs: None,
+            on_timeout: None,
+            notifications: Vec::new(),
+            ui: true,
+        }
+    }
+}
+
+impl HumanReviewConfig {
+    /// Every setting of an escalation step.
+    const STEP_SETTINGS: [&'static str; 3] = ["after", "notify", "via"];
+
+    /// Seconds a message waits for its review without a `timeout` or an `sla`.
+    pub const DEFAULT_TIMEOUT_SECS: u64 = 60 * 60;
+
+    /// Read every review option of a reviewer.
+    pub fn from_agent(agent: &Agent) -> std::result::Result<Self, Message> {
+        let mut config = Self::sla_of(agent)?;
+        config.read_wait(agent)?;
+        Ok(config)
+    }
+
+    /// Seconds a message waits for its review: the `timeout`, or else the SLA.
+    pub fn wait_secs(&self) -> u64 {
+        self.timeout_secs.or(self.sla_secs).unwrap_or(Self::DEFAULT_TIMEOUT_SECS)
+    }
+
+    /// Read the `timeout`, `on_timeout`, `notifications` and `ui` options of a
+    /// reviewer, which say how its messages wait for their review.
+    pub fn read_wait(&mut self, agent: &Agent) -> std::result::Result<(), Message> {
+        if let Some(timeout) = agent.config_value(REVIEW_TIMEOUT_OPTION) {
+            let secs = timeout
+                .as_duration_secs()
+                .filter(|secs| *secs > 0)
+                .ok_or_else(|| message!("timeout must be a duration such as \"30m\", found {}", timeout))?;
+            if let Some(sla) = self.sla_secs.filter(|sla| secs > *sla) {
+                return Err(message!("the timeout of {}s is longer than the sla of {}s, which decides the review first", secs, sla));
+            }
+            self.timeout_secs = Some(secs);
+        }
+        match agent.config_value(ON_TIMEOUT_OPTION) {
+            Some(Value::Tagged(name, options)) if name == CompensationConfig::NATS => {
+                let mut unknown: Vec<&String> = options.keys().filter(|key| key.as_str() != CompensationConfig::SUBJECT).collect();
+                unknown.sort();
+                if let Some(key) = unknown.first() {
+                    return Err(message!("unknown on_timeout setting '{}'", key));
+                }
+                let subject = match options.get(CompensationConfig::SUBJECT) {
+                    Some(Value::String(subject)) if !subject.trim().is_empty() => subject.trim().to_string(),
+                    _ => return Err(message!("the on_timeout subject is empty")),
+                };
+                if subject.contains(['*', '>', ' ']) {
+                    return Err(message!("the on_timeout subject {} must not have wildcards or spaces", subject));
+                }
+                self.on_timeout = Some(subject);
+            }
+            Some(other) => return Err(message!("on_timeout must be a NATS(\"<subject>\") subject, found {}", other)),
+            None => {}
+        }
+        if let Some(notifications) = agent.config_value(NOTIFICATIONS_OPTION) {
+            let channels: Option<Vec<String>> = match notifications {
+                Value::Array(channels) if !channels.is_empty() => channels.iter().map(Self::channel).collect(),
+                _ => None,
+            };
+            let mut channels = channels.ok_or_else(|| {
+                message!("notifications must be a non-empty list of notification channels such as [\"slack\"], found {}", notifications)
+            })?;
+            channels.sort();
+            channels.dedup();
+            self.notifications = channels;
+        }
+        match agent.config_value(UI_OPTION) {
+            Some(Value::Boolean(ui)) => self.ui = *ui,
+            Some(other) => return Err(message!("ui must be true or false, found {}", other)),
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Read the `sla`, `escalation`, `delegation` and `on_sla_breach` options of a reviewer.
+    pub fn sla_of(agent: &Agent) -> std::result::Result<Self, Message> {
+        let mut config = Self::default();
+
+        if let Some(sla) = agent.config_value(SLA_OPTION) {
+            let secs = sla
+                .as_duration_secs()
+                .filter(|secs| *secs > 0)
+                .ok_or_else(|| message!("sla must be a duration such as \"4h\", found {}", sla))?;
+            config.sla_secs = Some(secs);
+        }
+        match agent.config_value(ESCALATION_OPTION) {
+            Some(Value::Array(steps)) if !steps.is_empty() => {
+                for step in steps {
+                    let step = Self::escalation_step(step)?;
+                    if let Some(previous) = config.escalation.last() {
+                        if step.after_secs <= previous.after_secs {
+                            return Err(message!("escalation steps must come in increasing order of after"));
+                        }
+                    }
+                    if config.sla_secs.is_some_and(|sla| step.after_secs >= sla) {
+                        return Err(message!("the escalation step after {}s comes once the sla has lapsed", step.after_secs));
+                    }
+                    config.escalation.push(step);
+                }
+            }
+            Some(other) => return Err(message!("escalation must be a non-empty list of steps, found {}", other)),
+            None => {}
+        }
+        match agent.config_value(DELEGATION_OPTION) {
+            Some(Value::Object(groups)) => {
+                let mut groups_of: BTreeMap<&str, &str> = BTreeMap::new();
+                let mut groups: Vec<(&String, &Value)> = groups.iter().collect();
+                groups.sort_by_key(|(group, _)| *group);
+                for (group, members) in groups {
+                    let members = Self::names(members)
+                        .ok_or_else(|| message!("the members of delegation group '{}' must be a non-empty list of reviewers, found {}", group, members))?;
+                    for member in &members {
+                        if let Some(other) = groups_of.insert(member, group) {
+                            return Err(message!("reviewer '{}' belongs to delegation groups '{}' and '{}'", member, other, group));
+                        }
+                    }
+                    config.delegation.insert(group.clone(), members.into_iter().map(str::to_string).collect());
+                }
+            }
+            Some(other) => return Err(message!("delegation must be an object of groups, found {}", other)),
+            None => {}
+        }
+        match agent.config_value(SLA_BREACH_OPTION) {
+            Some(Value::String(action) | Value::Path(action)) => {
+                config.on_sla_breach = SlaBreachAction::ALL
+                    .into_iter()
+                    .find(|known| known.as_str() == action)
+                    .ok_or_else(|| message!("unknown on_sla_breach '{}' (expected approve, reject or expire)", action))?;
+            }
+            Some(other) => return Err(message!("on_sla_breach must be approve, reject or expire, found {}", other)),
+            None => {}
+        }
+        if config.sla_secs.is_none() && agent.config_value(SLA_BREACH_OPTION).is_some() {
+            return Err(message!("on_sla_breach needs an sla"));
+        }
+        Ok(config)
+    }
+
+    /// The delegation group a reviewer decides for, if any.
+    pub fn group_of(&self, reviewer: &str) -> Option<&str> {
+        self.delegation
+            .iter()
+            .find(|(_, members)| members.iter().any(|member| member == reviewer))
+            .map(|(group, _)| group.as_str())
+    }
+
+    /// Read a step such as `{ after: "2h", notify: ["leads"], via: "slack" }`.
+    fn escalation_step(value: &Value) -> std::result::Result<EscalationStep, Message> {
+        let Value::Object(options) = value else {
+            return Err(message!("escalation steps must be objects such as {{ after: \"2h\", notify: [\"leads\"], via: \"slack\" }}, found {}", value));
+        };
+        let mut unknown: Vec<&String> = options.keys().filter(|key| !Self::STEP_SETTINGS.contains(&key.as_str())).collect();
+        unknown.sort();
+        if let Some(key) = unknown.first() {
+            return Err(message!("unknown escalation setting '{}'", key));
+        }
+
+        let after_secs = match options.get("after") {
+            Some(after) => after
+                .as_duration_secs()
+                .filter(|secs| *secs > 0)
+                .ok_or_else(|| message!("after must be a duration such as \"2h\", found {}", after))?,
+            None => return Err(message!("missing after, the delay of the escalation step")),
+        };
+        let notify = match options.get("notify") {
+            Some(notify) => Self::names(notify)
+                .ok_or_else(|| message!("notify must be a non-empty list of reviewers or groups, found {}", notify))?,
+            None => return Err(message!("missing notify, who the escalation step notifies")),
+        };
+        let via = match options.get("via") {
+            Some(via) => Self::channel(via).ok_or_else(|| message!("via must name a notification channel such as \"slack\", found {}", via))?,
+            None => return Err(message!("missing via, the notification channel of the escalation step")),
+        };
+        Ok(EscalationStep { after_secs, notify: notify.into_iter().map(str::to_string).collect(), via })
+    }
+
+    /// The name of a notification channel, such as `slack`.
+    fn channel(value: &Value) -> Option<String> {
+        match value {
+            Value::String(channel) | Value::Path(channel)
+                if !channel.is_empty() && channel.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_') =>
+            {
+                Some(channel.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// The names of a non-empty list of strings.
+    fn names(value: &Value) -> Option<Vec<&str>> {
+        let Value::Array(items) = value else {
+            return None;
+        };
+        let names: Option<Vec<&str>> = items
+            .iter()
+            .map(|item| match item {
+                Value::String(name) if !name.trim().is_empty() => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        names.filter(|names| !names.is_empty())
+    }
+}
+
+/// How the decisions of a `HumanReview` agent are audited, as written in
+/// `audit: { issuer: "https://sso.example.com", audience: "reviews", signing_key: "review-audit-key" }`.
+///
+/// Reviewers are identified by an ID token of the OIDC issuer issued for the
+/// audience, and every decision is signed with the Ed25519 key of the
+/// Kubernetes Secret. Every setting is optional: the deployment may provide
+/// the issuer instead, the audience defaults to the agent ID and the Secret
+/// to a shared one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReviewAuditConfig {
+    /// URL of the OIDC issuer reviewers authenticate with.
+    pub issuer: Option<String>,
+    /// Audience the ID tokens must be issued for.
+    pub audience: Option<String>,
+    /// Secret holding the key decisions are signed with.
+    pub signing_key: Option<String>,
+}
+
+impl ReviewAuditConfig {
+    /// Every setting of `audit`.
+    const SETTINGS: [&'static str; 3] = ["issuer", "audience", "signing_key"];
+
+    /// Read the `audit` option of a reviewer; the defaults without one.
+    pub fn from_agent(agent: &Agent) -> std::result::Result<Self, Message> {
+        let options = match agent.config_value(AUDIT_OPTION) {
+            Some(Value::Object(options)) => options,
+            Some(other) => return Err(message!("audit must be an object such as {{ issuer: \"https://sso.example.com\" }}, found {}", other)),
+            None => return Ok(Self::default()),
+        };
+        let mut unknown: Vec<&String> = options.keys().filter(|key| !Self::SETTINGS.contains(&key.as_str())).collect();
+        unknown.sort();
+        if let Some(key) = unknown.first() {
+            return Err(message!("unknown audit setting '{}'", key));
+        }
+
+        let setting = |name: &str| match options.get(name) {
+            Some(Value::String(value)) if !value.trim().is_empty() => Ok(Some(value.clone())),
+            Some(other) => Err(message!("{} must be a non-empty string, found {}", name, other)),
+            None => Ok(None),
+        };
+        let config = Self { issuer: setting("issuer")?, audience: setting("audience")?, signing_key: setting("signing_key")? };
+        if let Some(issuer) = config.issuer.as_deref().filter(|issuer| !issuer.starts_with("https://")) {
+            return Err(message!("issuer must be an https URL, found '{}'", issuer));
+        }
+        if let Some(secret) = config.signing_key.as_deref() {
+            let valid = secret.len() <= 253
+                && secret.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.')
+                && secret.starts_with(|c: char| c.is_ascii_alphanumeric())
+                && secret.ends_with(|c: char| c.is_ascii_alphanumeric());
+            if !valid {
+                return Err(message!("signing_key must name a Kubernetes Secret, found '{}'", secret));
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// OIDC authentication of a human-facing endpoint, written
+/// `auth: OIDC("https://sso.example.com", "reviews", "groups")`.
+///
+/// Callers present an ID or access token of the issuer, issued for the
+/// client; the groups claim lists the groups they belong to. With `allow`,
+/// only members of those groups are let in.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OidcAuthConfig {
+    /// URL of the OIDC issuer.
+    pub issuer: String,
+    /// Client ID the tokens must be issued for.
+    pub client_id: String,
+    /// Claim of the tokens listing the caller's groups.
+    pub groups_claim: String,
+    /// Groups allowed in; every authenticated caller when empty.
+    pub allow: Vec<String>,
+}
+
+impl OidcAuthConfig {
+    /// The name OIDC authentication is written with.
+    pub const OIDC: &'static str = "OIDC";
+    /// The keys the positional arguments of `OIDC(...)` are read into.
+    pub const ARGUMENTS: [&'static str; 3] = ["issuer", "client_id", "groups_claim"];
+    /// The groups claim when none is given.
+    pub const DEFAULT_GROUPS_CLAIM: &'static str = "groups";
+    /// Every setting of `OIDC(...)`.
+    const SETTINGS: [&'static str; 4] = ["issuer", "client_id", "groups_claim", "allow"];
+
+    /// Read an `OIDC("<issuer>", "<client id>", "<groups claim>")` value.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let options = match value {
+            Value::Tagged(name, options) if name == Self::OIDC => options,
+            other => return Err(message!("expected OIDC(\"<issuer>\", \"<client id>\", \"<groups claim>\"), found {}", other)),
+        };
+        let mut unknown: Vec<&String> = options.keys().filter(|key| !Self::SETTINGS.contains(&key.as_str())).collect();
+        unknown.sort();
+        if let Some(key) = unknown.first() {
+            return Err(message!("unknown OIDC setting '{}'", key));
+        }
+
+        let text = |key: &str| match options.get(key) {
+            Some(Value::String(text)) if !text.trim().is_empty() => Ok(Some(text.clone())),
+            Some(other) => Err(message!("{} must be a non-empty string, found {}", key, other)),
+            None => Ok(None),
+        };
+        let issuer = text("issuer")?.ok_or("missing issuer")?;
+        if !issuer.starts_with("https://") {
+            return Err(message!("issuer must be an https URL, found '{}'", issuer));
+        }
+        let client_id = text("client_id")?.ok_or("missing client ID")?;
+        let groups_claim = text("groups_claim")?.unwrap_or_else(|| Self::DEFAULT_GROUPS_CLAIM.to_string());
+        let allow = match options.get("allow") {
+            Some(Value::Array(groups)) => groups
+                .iter()
+                .map(|group| match group {
+                    Value::String(group) if !group.trim().is_empty() && !group.contains(',') => Ok(group.clone()),
+                    other => Err(message!("allow must list group names, found {}", other)),
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?,
+            Some(other) => return Err(message!("allow must be a list of groups, found {}", other)),
+            None => Vec::new(),
+        };
+        Ok(Self { issuer, client_id, groups_claim, allow })
+    }
+
+    /// Read the `auth` option of an agent, if it has one.
+    pub fn from_agent(agent: &Agent) -> std::result::Result<Option<Self>, Message> {
+        agent.config_value(AUTH_OPTION).map(Self::from_value).transpose()
+    }
+
+    /// Read the `auth` option of a source, if it has one.
+    ///
+    /// Source options are flat, so `auth: OIDC(...)` reaches the source as
+    /// `auth: "OIDC"` and its settings as `auth.<setting>`, with the allowed
+    /// groups separated by commas.
+    pub fn from_source(source: &Source) -> std::result::Result<Option<Self>, Message> {
+        let Some(name) = source.option(AUTH_OPTION) else {
+            return Ok(None);
+        };
+        let settings = source
+            .nested_options(AUTH_OPTION)
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match key {
+                    "allow" => Value::Array(value.split(',').map(|group| Value::String(group.trim().to_string())).collect()),
+                    _ => Value::String(value.to_string()),
+                };
+                (key.to_string(), value)
+            })
+            .collect();
+        Self::from_value(&Value::Tagged(name.to_string(), settings)).map(Some)
+    }
+}
+
+/// How the distribution of a feature is compared with its reference.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftMethod {
+    /// Population stability index.
+    #[default]
+    Psi,
+    /// Kullback-Leibler divergence of the current distribution from the reference.
+    Kl,
+    /// Jensen-Shannon divergence.
+    Js,
+    /// Kolmogorov-Smirnov statistic over the bins of the reference.
+    Ks,
+}
+
+impl DriftMethod {
+    /// Every method.
+    pub const ALL: [DriftMethod; 4] = [DriftMethod::Psi, DriftMethod::Kl, DriftMethod::Js, DriftMethod::Ks];
+
+    /// The method as written in `drift: { method: ... }`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DriftMethod::Psi => "psi",
+            DriftMethod::Kl => "kl",
+            DriftMethod::Js => "js",
+            DriftMethod::Ks => "ks",
+        }
+    }
+
+    /// The score above which a feature has drifted, when no threshold is given.
+    pub fn default_threshold(self) -> f64 {
+        match self {
+            DriftMethod::Psi => 0.2,
+            DriftMethod::Kl | DriftMethod::Js | DriftMethod::Ks => 0.1,
+        }
+    }
+}
+
+/// Represents how an MLModel agent tracks the drift of its input features.
+///
+/// The reference profile is a JSON resource giving, for every feature, the
+/// bins of its reference distribution. The agent bins the features of every
+/// window of inputs the same way and reports the features whose score is
+/// above the threshold.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelDriftConfig {
+    /// URI of the reference profile.
+    pub reference: String,
+    /// How distributions are compared.
+    pub method: DriftMethod,
+    /// Score above which a feature has drifted.
+    pub threshold: f64,
+    /// Inputs per comparison.
+    pub window: u32,
+    /// Features tracked, as dotted paths in the input; empty for every feature of the profile.
+    pub features: Vec<String>,
+}
+
+impl ModelDriftConfig {
+    /// Inputs per comparison when `window` is not given.
+    pub const DEFAULT_WINDOW: u32 = 1000;
+
+    /// Read a `drift: { reference: resource("..."), method: "psi", threshold: 0.2 }` option.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let Value::Object(options) = value else {
+            return Err(message!("expected an object, found {}", value));
+        };
+        let reference = match options.get("reference") {
+            Some(Value::String(uri)) if !uri.trim().is_empty() => uri.clone(),
+            Some(other) => return Err(message!("reference must be a resource URI, found {}", other)),
+            None => return Err(message!("missing reference")),
+        };
+
+        let mut config = Self {
+            reference,
+            method: DriftMethod::default(),
+            threshold: f64::NAN,
+            window: Self::DEFAULT_WINDOW,
+            features: Vec::new(),
+        };
+        let mut keys: Vec<&String> = options.keys().collect();
+        keys.sort();
+        for key in keys {
+            match (key.as_str(), &options[key]) {
+                ("reference", _) => {}
+                ("method", Value::String(method)) => {
+                    config.method = DriftMethod::ALL.into_iter().find(|known| known.as_str() == method).ok_or_else(|| {
+                        message!("unknown method '{}' (expected psi, kl, js or ks)", method)
+                    })?;
+                }
+                ("threshold", Value::Number(n)) if *n > 0.0 => config.threshold = *n,
+                ("threshold", other) => return Err(message!("threshold must be a number above 0, found {}", other)),
+                ("window", Value::Number(n)) if *n >= 1.0 && n.fract() == 0.0 && *n <= u32::MAX as f64 => {
+                    config.window = *n as u32;
+                }
+                ("window", other) => return Err(message!("window must be a whole number of at least 1, found {}", other)),
+                ("method", other) => return Err(message!("method must be a string, found {}", other)),
+                ("features", Value::Array(items)) => {
+                    for item in items {
+                        match item {
+                            Value::String(feature) if !feature.trim().is_empty() => config.features.push(feature.clone()),
+                            other => return Err(message!("features must be field paths, found {}", other)),
+                        }
+                    }
+                }
+                ("features", other) => return Err(message!("features must be a list, found {}", other)),
+                (key, _) => return Err(message!("unknown drift setting '{}'", key)),
+            }
+        }
+        if config.threshold.is_nan() {
+            config.threshold = config.method.default_threshold();
+        }
+        Ok(config)
+    }
+}
+
+/// How an MLModel agent looks up online features for its inputs, as
+/// written in `features: Feast("driver_stats", keys: ["driver_id"])`.
+///
+/// Each input is enriched with the features of the feature view for the
+/// entity its key fields identify, fetched from the store's online store.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FeatureStoreConfig {
+    /// The feature view the features are read from.
+    pub feature_view: String,
+    /// Fields of the input identifying the entity, named as the entity's join keys.
+    pub keys: Vec<String>,
+    /// Features fetched; empty for every feature of the view.
+    pub features: Vec<String>,
+    /// Feast project; the workflow's name when not given.
+    pub project: Option<String>,
+    /// Location of the Feast registry.
+    pub registry: Option<String>,
+    /// Connection string of the Redis online store.
+    pub online_store: Option<String>,
+}
+
+impl FeatureStoreConfig {
+    /// The name feature stores are written with.
+    pub const FEAST: &'static str = "Feast";
+    /// The key the feature view, the first argument of `Feast(...)`, is read into.
+    pub const FEATURE_VIEW: &'static str = "feature_view";
+    /// Every setting of `Feast(...)`.
+    const SETTINGS: [&'static str; 6] = ["feature_view", "keys", "features", "project", "registry", "online_store"];
+
+    /// Read a `Feast("<feature view>", keys: [...])` option.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let options = match value {
+            Value::Tagged(name, options) if name == Self::FEAST => options,
+            other => return Err(message!("expected Feast(\"<feature view>\", keys: [...]), found {}", other)),
+        };
+        let feature_view = match options.get(Self::FEATURE_VIEW) {
+            Some(Value::String(view)) if !view.trim().is_empty() => view.clone(),
+            _ => return Err(message!("missing feature view")),
+        };
+        let names = |key: &str| -> std::result::Result<Vec<String>, Message> {
+            match options.get(key) {
+                Some(Value::Array(items)) => items
+                    .iter()
+                    .map(|item| match item {
+                        Value::String(name) if !name.trim().is_empty() => Ok(name.clone()),
+                        other => Err(message!("{} must be names, found {}", key, other)),
+                    })
+                    .collect(),
+                Some(other) => Err(message!("{} must be a list, found {}", key, other)),
+                None => Ok(Vec::new()),
+            }
+        };
+        let text = |key: &str| -> std::result::Result<Option<String>, Message> {
+            match options.get(key) {
+                Some(Value::String(text)) => Ok(Some(text.clone())),
+                Some(other) => Err(message!("{} must be a string, found {}", key, other)),
+                None => Ok(None),
+            }
+        };
+
+        let mut unknown: Vec<&String> = options.keys().filter(|key| !Self::SETTINGS.contains(&key.as_str())).collect();
+        unknown.sort();
+        if let Some(key) = unknown.first() {
+            return Err(message!("unknown feature store setting '{}'", key));
+        }
+        let keys = names("keys")?;
+        if keys.is_empty() {
+            return Err(message!("feature view '{}' needs the keys identifying its entities", feature_view));
+        }
+        Ok(Self {
+            feature_view,
+            keys,
+            features: names("features")?,
+            project: text("project")?,
+            registry: text("registry")?,
+            online_store: text("online_store")?,
+        })
+    }
+}
+
+/// A self-hosted inference server an LLM agent sends its prompts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InferenceBackend {
+    /// vLLM, through its OpenAI-compatible API.
+    Vllm,
+    /// Hugging Face Text Generation Inference.
+    Tgi,
+}
+
+impl InferenceBackend {
+    /// Every backend.
+    pub const ALL: [InferenceBackend; 2] = [InferenceBackend::Vllm, InferenceBackend::Tgi];
+
+    /// The name the backend is written with, as in `provider: VLLM(...)`.
+    pub fn tag(self) -> &'static str {
+        match self {
+            InferenceBackend::Vllm => "VLLM",
+            InferenceBackend::Tgi => "TGI",
+        }
+    }
+
+    /// The provider name of the backend in the agent's configuration.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InferenceBackend::Vllm => "vllm",
+            InferenceBackend::Tgi => "tgi",
+        }
+    }
+
+    /// Read a backend from the name it is written with.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|backend| backend.tag() == tag)
+    }
+}
+
+/// Represents the inference server of an LLM agent, written
+/// `provider: VLLM(endpoint: "http://vllm:8000", model: "...")` or `TGI(...)`.
+///
+/// Without an endpoint, the agents of a namespace serving the same model
+/// share one server deployed with the workflow instead of each loading it.
+/// Prompts arriving within the batch window are sent together, up to the
+/// batch size, so the server fills its GPUs.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InferenceServerConfig {
+    /// The server's implementation.
+    pub backend: InferenceBackend,
+    /// URL of an existing server; a shared server is deployed when not given.
+    pub endpoint: Option<String>,
+    /// The model the server serves; the agent's `model` when not given.
+    pub model: Option<String>,
+    /// GPUs of a deployed server, the model being sharded across them.
+    pub gpus: u32,
+    /// Prompts sent to the server at once.
+    pub max_batch_size: u32,
+    /// Milliseconds a prompt waits for others to fill its batch.
+    pub batch_window_ms: u32,
+}
+
+impl InferenceServerConfig {
+    /// GPUs of a deployed server when `gpus` is not given.
+    pub const DEFAULT_GPUS: u32 = 1;
+    /// Prompts per batch when `max_batch_size` is not given.
+    pub const DEFAULT_MAX_BATCH_SIZE: u32 = 16;
+    /// Batch window when `batch_window_ms` is not given.
+    pub const DEFAULT_BATCH_WINDOW_MS: u32 = 10;
+    /// Every setting of `VLLM(...)` and `TGI(...)`.
+    const SETTINGS: [&'static str; 5] = ["endpoint", "model", "gpus", "max_batch_size", "batch_window_ms"];
+
+    /// Whether a value names an inference server rather than a hosted provider.
+    pub fn is_server(value: &Value) -> bool {
+        matches!(value, Value::Tagged(name, _) if InferenceBackend::from_tag(name).is_some())
+    }
+
+    /// Read a `VLLM(...)` or `TGI(...)` provider.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let (backend, options) = match value {
+            Value::Tagged(name, options) => match InferenceBackend::from_tag(name) {
+                Some(backend) => (backend, options),
+                None => return Err(message!("unknown inference server '{}' (expected VLLM or TGI)", name)),
+            },
+            other => return Err(message!("expected VLLM(...) or TGI(...), found {}", other)),
+        };
+
+        let mut unknown: Vec<&String> = options.keys().filter(|key| !Self::SETTINGS.contains(&key.as_str())).collect();
+        unknown.sort();
+        if let Some(key) = unknown.first() {
+            return Err(message!("unknown {} setting '{}'", backend.tag(), key));
+        }
+        let text = |key: &str| -> std::result::Result<Option<String>, Message> {
+            match options.get(key) {
+                Some(Value::String(text)) if !text.trim().is_empty() => Ok(Some(text.clone())),
+                Some(other) => Err(message!("{} must be a non-empty string, found {}", key, other)),
+                None => Ok(None),
+            }
+        };
+        let count = |key: &str, default: u32| -> std::result::Result<u32, Message> {
+            match options.get(key) {
+                Some(Value::Number(n)) if *n >= 1.0 && n.fract() == 0.0 && *n <= u32::MAX as f64 => Ok(*n as u32),
+                Some(other) => Err(message!("{} must be a whole number of at least 1, found {}", key, other)),
+                None => Ok(default),
+            }
+        };
+
+        let endpoint = text("endpoint")?;
+        if let Some(endpoint) = &endpoint {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                return Err(message!("endpoint must be an http(s) URL, found '{}'", endpoint));
+            }
+        }
+        Ok(Self {
+            backend,
+            endpoint,
+            model: text("model")?,
+            gpus: count("gpus", Self::DEFAULT_GPUS)?,
+            max_batch_size: count("max_batch_size", Self::DEFAULT_MAX_BATCH_SIZE)?,
+            batch_window_ms: match options.get("batch_window_ms") {
+                Some(Value::Number(n)) if *n >= 0.0 && n.fract() == 0.0 && *n <= u32::MAX as f64 => *n as u32,
+                Some(other) => return Err(message!("batch_window_ms must be a whole number of milliseconds, found {}", other)),
+                None => Self::DEFAULT_BATCH_WINDOW_MS,
+            },
+        })
+    }
+}
+
+/// What makes an LLM agent fail over to the next provider of its chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailoverTrigger {
+    /// The provider didn't answer within its timeout.
+    Timeout,
+    /// The provider rejected the request for its rate limits (HTTP 429).
+    RateLimit,
+    /// The provider failed (HTTP 5xx) or couldn't be reached.
+    ServerError,
+    /// Any failure, including rejected requests.
+    Error,
+}
+
+impl FailoverTrigger {
+    /// Every trigger.
+    pub const ALL: [FailoverTrigger; 4] =
+        [FailoverTrigger::Timeout, FailoverTrigger::RateLimit, FailoverTrigger::ServerError, FailoverTrigger::Error];
+
+    /// The triggers of a provider that doesn't list its own.
+    pub const DEFAULT: [FailoverTrigger; 3] = [FailoverTrigger::Timeout, FailoverTrigger::RateLimit, FailoverTrigger::ServerError];
+
+    /// The trigger as written in `on: [...]`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FailoverTrigger::Timeout => "timeout",
+            FailoverTrigger::RateLimit => "rate_limit",
+            FailoverTrigger::ServerError => "server_error",
+            FailoverTrigger::Error => "error",
+        }
+    }
+
+    /// Read a trigger as written in `on: [...]`.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|trigger| trigger.as_str() == name)
+    }
+}
+
+/// A provider of an LLM agent's fallback chain, written `OpenAI(gpt-4o)`,
+/// `Anthropic(...)`, `Ollama(...)`, `VLLM(...)` or `TGI(...)`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChainedProvider {
+    /// The provider's name in the agent's configuration: openai, anthropic, ollama, vllm or tgi.
+    pub provider: String,
+    /// The model; the agent's `model` when not given.
+    pub model: Option<String>,
+    /// URL of the provider's API; the provider's own, or a shared server for vLLM and TGI, when not given.
+    pub endpoint: Option<String>,
+    /// Seconds a request may take before failing over; the agent's timeout when not given.
+    pub timeout_secs: Option<u64>,
+    /// What fails the request over to the next provider.
+    pub on: Vec<FailoverTrigger>,
+    /// The inference server of `VLLM(...)` and `TGI(...)`.
+    pub server: Option<InferenceServerConfig>,
+}
+
+/// Represents the ordered providers of an LLM agent, written
+/// `providers: [OpenAI(gpt-4o, timeout: "20s"), Ollama(llama3)]`.
+///
+/// Requests go to the first provider; a failure matching its `on` triggers
+/// sends them to the next one, and any other failure is the request's.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProviderChain {
+    /// The providers, in the order they are tried.
+    pub providers: Vec<ChainedProvider>,
+}
+
+impl ProviderChain {
+    /// Read a `providers: [...]` option.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let items = match value {
+            Value::Array(items) if !items.is_empty() => items,
+            other => return Err(message!("expected a list of providers such as [OpenAI(gpt-4o), Ollama(llama3)], found {}", other)),
+        };
+        let providers = items.iter().map(Self::provider).collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(Self { providers })
+    }
+
+    /// Read a provider of the chain.
+    fn provider(value: &Value) -> std::result::Result<ChainedProvider, Message> {
+        let (name, options) = match value {
+            Value::Tagged(name, options) => (name, options),
+            other => return Err(message!("expected a provider such as OpenAI(gpt-4o), found {}", other)),
+        };
+        let timeout_secs = match options.get("timeout") {
+            Some(timeout) => Some(
+                timeout
+                    .as_duration_secs()
+                    .filter(|secs| *secs > 0)
+                    .ok_or_else(|| message!("timeout of {} must be a duration, found {}", name, timeout))?,
+            ),
+            None => None,
+        };
+        let on = match options.get("on") {
+            Some(Value::Array(triggers)) => triggers
+                .iter()
+                .map(|trigger| match trigger {
+                    Value::String(trigger) | Value::Path(trigger) => FailoverTrigger::parse(trigger).ok_or_else(|| {
+                        message!("unknown failover trigger '{}' (expected timeout, rate_limit, server_error or error)", trigger)
+                    }),
+                    other => Err(message!("failover triggers must be names, found {}", other)),
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?,
+            Some(other) => return Err(message!("on must be a list of failover triggers, found {}", other)),
+            None => FailoverTrigger::DEFAULT.to_vec(),
+        };
+
+        // The rest are the settings of `provider: ...`
+        let mut provider_options = options.clone();
+        provider_options.remove("timeout");
+        provider_options.remove("on");
+        let provider = LLMProvider::tagged(name, &provider_options)?;
+        Ok(ChainedProvider {
+            provider: provider.kind.as_str().to_string(),
+            model: provider.model,
+            endpoint: provider.endpoint,
+            timeout_secs,
+            on,
+            server: provider.server,
+        })
+    }
+}
+
+/// The kinds of provider an LLM agent sends its prompts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LLMProviderKind {
+    /// OpenAI's chat completions API, or a compatible one at an `endpoint`.
+    OpenAI,
+    /// Anthropic's messages API.
+    Anthropic,
+    /// An Ollama server.
+    Ollama,
+    /// A self-hosted vLLM server.
+    Vllm,
+    /// A self-hosted Text Generation Inference server.
+    Tgi,
+}
+
+impl LLMProviderKind {
+    /// Every kind of provider.
+    pub const ALL: [LLMProviderKind; 5] = [
+        LLMProviderKind::OpenAI,
+        LLMProviderKind::Anthropic,
+        LLMProviderKind::Ollama,
+        LLMProviderKind::Vllm,
+        LLMProviderKind::Tgi,
+    ];
+
+    /// The name the provider is written with, as in `provider: OpenAI(gpt-4o)`.
+    pub fn tag(self) -> &'static str {
+        match self {
+            LLMProviderKind::OpenAI => "OpenAI",
+            LLMProviderKind::Anthropic => "Anthropic",
+            LLMProviderKind::Ollama => "Ollama",
+            LLMProviderKind::Vllm => "VLLM",
+            LLMProviderKind::Tgi => "TGI",
+        }
+    }
+
+    /// The provider name in the agent's configuration, as in `provider: "openai"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LLMProviderKind::OpenAI => "openai",
+            LLMProviderKind::Anthropic => "anthropic",
+            LLMProviderKind::Ollama => "ollama",
+            LLMProviderKind::Vllm => "vllm",
+            LLMProviderKind::Tgi => "tgi",
+        }
+    }
+
+    /// Read a provider from the name it is written with.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.tag() == tag)
+    }
+
+    /// Read a provider from its name in the agent's configuration, in any case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str().eq_ignore_ascii_case(name))
+    }
+
+    /// The inference server of a self-hosted provider.
+    pub fn backend(self) -> Option<InferenceBackend> {
+        match self {
+            LLMProviderKind::Vllm => Some(InferenceBackend::Vllm),
+            LLMProviderKind::Tgi => Some(InferenceBackend::Tgi),
+            _ => None,
+        }
+    }
+
+    /// Agent options the provider's API needs in every request.
+    pub fn required_options(self) -> &'static [&'static str] {
+        match self {
+            LLMProviderKind::Anthropic => &["max_tokens"],
+            _ => &[],
+        }
+    }
+
+    /// Agent options the provider's API has no equivalent of.
+    pub fn unsupported_options(self) -> &'static [&'static str] {
+        match self {
+            LLMProviderKind::Anthropic => &["frequency_penalty", "presence_penalty"],
+            LLMProviderKind::Ollama | LLMProviderKind::Tgi => &["api_key"],
+            _ => &[],
+        }
+    }
+
+    /// Check that an agent sets the options the provider requires and none it rejects.
+    ///
+    /// Generation settings may be given as agent options or in `options: { ... }`.
+    pub fn check_options(self, agent: &Agent) -> std::result::Result<(), Message> {
+        let has = |name: &str| {
+            agent.config_value(name).is_some()
+                || matches!(agent.config_value("options"), Some(Value::Object(options)) if options.contains_key(name))
+        };
+        if let Some(option) = self.required_options().iter().find(|option| !has(option)) {
+            return Err(message!("the {} provider requires {}, which its API needs in every request", self.tag(), option));
+        }
+        if let Some(option) = self.unsupported_options().iter().find(|option| has(option)) {
+            return Err(message!("the {} provider doesn't accept {}", self.tag(), option));
+        }
+        Ok(())
+    }
+}
+
+/// Represents the provider of an LLM agent, written `provider: "openai"`,
+/// `provider: OpenAI(gpt-4o)`, `Anthropic(...)`, `Ollama(...)`, `VLLM(...)`
+/// or `TGI(...)`, or as an object named after it, `{ ollama: { model: "llama3" } }`.
+///
+/// Every provider is reached through the same client interface in the
+/// generated agent, so switching providers only changes its configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LLMProvider {
+    /// The kind of provider.
+    pub kind: LLMProviderKind,
+    /// The model; the agent's `model` when not given.
+    pub model: Option<String>,
+    /// URL of the provider's API; the provider's own, or a shared server for vLLM and TGI, when not given.
+    pub endpoint: Option<String>,
+    /// The inference server of `VLLM(...)` and `TGI(...)`.
+    pub server: Option<InferenceServerConfig>,
+}
+
+impl LLMProvider {
+    /// The provider of LLM agents that don't name one.
+    pub const DEFAULT: LLMProviderKind = LLMProviderKind::OpenAI;
+    /// Settings of a hosted provider.
+    const SETTINGS: [&'static str; 2] = ["model", "endpoint"];
+
+    /// Read a `provider: ...` option.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        match value {
+            Value::String(name) => match LLMProviderKind::from_name(name) {
+                Some(kind) => Ok(Self { kind, model: None, endpoint: None, server: None }),
+                None => Err(message!("unknown provider '{}' (expected openai, anthropic, ollama, vllm or tgi)", name)),
+            },
+            Value::Tagged(name, options) => Self::tagged(name, options),
+            // `{ ollama: { model: "llama3" } }` reads as `Ollama(model: "llama3")`
+            Value::Object(fields) if fields.len() == 1 => {
+                let (name, settings) = fields.iter().next().expect("one field");
+                match (LLMProviderKind::from_name(name), settings) {
+                    (Some(kind), Value::Object(options)) => Self::tagged(kind.tag(), options),
+                    (Some(_), other) => Err(message!("the settings of {} must be an object, found {}", name, other)),
+                    (None, _) => Err(message!("unknown provider '{}' (expected openai, anthropic, ollama, vllm or tgi)", name)),
+                }
+            }
+            other => Err(message!("expected a provider such as \"openai\" or OpenAI(gpt-4o), found {}", other)),
+        }
+    }
+
+    /// Read a provider written with its name, as in `OpenAI(gpt-4o)`.
+    fn tagged(name: &str, options: &HashMap<String, Value>) -> std::result::Result<Self, Message> {
+        let Some(kind) = LLMProviderKind::from_tag(name) else {
+            return Err(message!("unknown provider '{}' (expected OpenAI, Anthropic, Ollama, VLLM or TGI)", name));
+        };
+        if kind.backend().is_some() {
+            let server = InferenceServerConfig::from_value(&Value::Tagged(name.to_string(), options.clone()))?;
+            return Ok(Self { kind, model: server.model.clone(), endpoint: server.endpoint.clone(), server: Some(server) });
+        }
+        let mut unknown: Vec<&String> = options.keys().filter(|key| !Self::SETTINGS.contains(&key.as_str())).collect();
+        unknown.sort();
+        if let Some(key) = unknown.first() {
+            return Err(message!("unknown {} setting '{}'", name, key));
+        }
+        let text = |key: &str| -> std::result::Result<Option<String>, Message> {
+            match options.get(key) {
+                Some(Value::String(text)) if !text.trim().is_empty() => Ok(Some(text.clone())),
+                Some(other) => Err(message!("{} of {} must be a non-empty string, found {}", key, name, other)),
+                None => Ok(None),
+            }
+        };
+        let endpoint = text("endpoint")?;
+        if let Some(endpoint) = &endpoint {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                return Err(message!("endpoint must be an http(s) URL, found '{}'", endpoint));
+            }
+        }
+        Ok(Self { kind, model: text("model")?, endpoint, server: None })
+    }
+
+    /// Read the provider of an LLM agent: its `provider`, or [`LLMProvider::DEFAULT`] without one.
+    ///
+    /// Agents chaining `providers` have none, and neither do other agents.
+    /// The provider's required and rejected options are checked against the agent's.
+    pub fn for_agent(agent: &Agent) -> std::result::Result<Option<Self>, Message> {
+        if agent.agent_type != AgentType::LLM || agent.config_value(PROVIDERS_OPTION).is_some() {
+            return Ok(None);
+        }
+        let provider = match agent.config_value(PROVIDER_OPTION) {
+            Some(value) => Self::from_value(value)?,
+            None => Self { kind: Self::DEFAULT, model: None, endpoint: None, server: None },
+        };
+        provider.kind.check_options(agent)?;
+        Ok(Some(provider))
+    }
+}
+
+/// What a guardrail does with a message breaking its rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardrailAction {
+    /// Drop the message and report it on the error topic.
+    Block,
+    /// Mask what the rule found and go on.
+    Redact,
+    /// Send the message to a HumanReview agent instead.
+    Review,
+}
+
+impl GuardrailAction {
+    /// Every action.
+    pub const ALL: [GuardrailAction; 3] = [GuardrailAction::Block, GuardrailAction::Redact, GuardrailAction::Review];
+
+    /// The action as written in `action: ...`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GuardrailAction::Block => "block",
+            GuardrailAction::Redact => "redact",
+            GuardrailAction::Review => "review",
+        }
+    }
+
+    /// Read an action as written in `action: ...`.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.as_str() == name)
+    }
+}
+
+/// What a guardrail rule looks for.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "check", rename_all = "snake_case")]
+pub enum GuardrailCheck {
+    /// Personal data: email addresses, phone numbers, card and social security numbers.
+    Pii,
+    /// Instructions trying to override the agent's own.
+    PromptInjection,
+    /// Toxic language, scored from 0 to 1.
+    Toxicity {
+        /// Score from which a message is toxic.
+        threshold: f64,
+        /// Classifier scoring the messages; a built-in lexicon when `None`.
+        endpoint: Option<String>,
+    },
+    /// Text matching a regular expression.
+    Regex {
+        /// The expression.
+        pattern: String,
+    },
+    /// Rules loaded from a resource when the agent starts.
+    Resource {
+        /// URI of a JSON list of `{"name": ..., "pattern": ..., "action": ...}` rules.
+        source: String,
+    },
+}
+
+/// A rule of an LLM agent's guardrails, written `pii_block`, `toxicity(threshold: 0.8)`, ...
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GuardrailRule {
+    /// The rule's name in metrics and reports.
+    pub name: String,
+    /// What the rule looks for.
+    pub check: GuardrailCheck,
+    /// What is done with a message breaking it; rules of a resource carry their own.
+    pub action: GuardrailAction,
+}
+
+/// The `guardrails: { input: [...], output: [...] }` of an LLM agent.
+///
+/// Input rules check the prompts before they reach the model, output rules
+/// the responses before they are published, in the order written.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GuardrailsConfig {
+    /// Rules of the prompts.
+    pub input: Vec<GuardrailRule>,
+    /// Rules of the responses.
+    pub output: Vec<GuardrailRule>,
+    /// ID of the HumanReview agent messages with the `review` action go to.
+    pub review: Option<String>,
+}
+
+impl GuardrailsConfig {
+    /// Score from which `toxicity` blocks a message when no threshold is given.
+    pub const DEFAULT_TOXICITY_THRESHOLD: f64 = 0.8;
+
+    /// The rules, as written.
+    pub const RULES: [&'static str; 7] = ["pii_block", "pii_redact", "pii", "prompt_injection", "toxicity", "regex", "rules"];
+
+    /// Read a `guardrails: { input: [...], output: [...], review: "moderator" }` option.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let Value::Object(options) = value else {
+            return Err(message!("expected an object such as {{ input: [pii_block], output: [toxicity] }}, found {}", value));
+        };
+        let mut unknown: Vec<&String> = options.keys().filter(|key| !["input", "output", "review"].contains(&key.as_str())).collect();
+        unknown.sort();
+        if let Some(key) = unknown.first() {
+            return Err(message!("unknown guardrails setting '{}'", key));
+        }
+
+        let rules = |stage: &str| match options.get(stage) {
+            Some(Value::Array(rules)) => rules.iter().map(Self::rule).collect::<std::result::Result<Vec<_>, _>>(),
+            Some(other) => Err(message!("{} must be a list of rules, found {}", stage, other)),
+            None => Ok(Vec::new()),
+        };
+        let config = Self {
+            input: rules("input")?,
+            output: rules("output")?,
+            review: match options.get("review") {
+                Some(Value::String(agent) | Value::Path(agent)) if !agent.trim().is_empty() => Some(agent.clone()),
+                Some(other) => return Err(message!("review must be the ID of a HumanReview agent, found {}", other)),
+                None => None,
+            },
+        };
+        if config.input.is_empty() && config.output.is_empty() {
+            return Err(message!("guardrails need at least one input or output rule"));
+        }
+        let reviewed = config.input.iter().chain(&config.output).find(|rule| rule.action == GuardrailAction::Review);
+        if let (Some(rule), None) = (reviewed, &config.review) {
+            return Err(message!("{} sends messages to review, which needs the HumanReview agent under review", rule.name));
+        }
+        Ok(config)
+    }
+
+    /// Read a rule of a stage.
+    fn rule(value: &Value) -> std::result::Result<GuardrailRule, Message> {
+        let no_options = HashMap::new();
+        let (name, options) = match value {
+            Value::Path(name) | Value::String(name) => (name.as_str(), &no_options),
+            Value::Tagged(name, options) => (name.as_str(), options),
+            other => return Err(message!("expected a rule such as pii_block or toxicity(threshold: 0.8), found {}", other)),
+        };
+        let allowed: &[&str] = match name {
+            "pii_block" | "pii_redact" => &[],
+            "pii" | "prompt_injection" => &["action"],
+            "toxicity" => &["threshold", "endpoint", "action"],
+            "regex" => &["pattern", "name", "action"],
+            "rules" => &["source"],
+            other => {
+                return Err(message!(
+                    "unknown guardrail '{}' (expected {})",
+                    other,
+                    Self::RULES.join(", ")
+                ))
+            }
+        };
+        let mut unknown: Vec<&String> = options.keys().filter(|key| !allowed.contains(&key.as_str())).collect();
+        unknown.sort();
+        if let Some(key) = unknown.first() {
+            return Err(message!("unknown {} setting '{}'", name, key));
+        }
+
+        let action = match options.get("action") {
+            Some(Value::String(action) | Value::Path(action)) => GuardrailAction::parse(action)
+                .ok_or_else(|| message!("unknown action '{}' of {} (expected block, redact or review)", action, name))?,
+            Some(other) => return Err(message!("action of {} must be block, redact or review, found {}", name, other)),
+            None if name == "pii_redact" => GuardrailAction::Redact,
+            None => GuardrailAction::Block,
+        };
+        if action == GuardrailAction::Redact && matches!(name, "prompt_injection" | "toxicity") {
+            return Err(message!("{} can't redact a message, its action must be block or review", name));
+        }
+        let text = |key: &str| -> std::result::Result<Option<String>, Message> {
+            match options.get(key) {
+                Some(Value::String(text)) if !text.trim().is_empty() => Ok(Some(text.clone())),
+                Some(other) => Err(message!("{} of {} must be a non-empty string, found {}", key, name, other)),
+                None => Ok(None),
+            }
+        };
+
+        let check = match name {
+            "pii_block" | "pii_redact" | "pii" => GuardrailCheck::Pii,
+            "prompt_injection" => GuardrailCheck::PromptInjection,
+            "toxicity" => GuardrailCheck::Toxicity {
+                threshold: match options.get("threshold") {
+                    Some(Value::Number(n)) if *n > 0.0 && *n <= 1.0 => *n,
+                    Some(other) => return Err(message!("threshold of toxicity must be a number in (0, 1], found {}", other)),
+                    None => Self::DEFAULT_TOXICITY_THRESHOLD,
+                },
+                endpoint: match text("endpoint")? {
+                    Some(endpoint) if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") => {
+                        return Err(message!("endpoint of toxicity must be an http(s) URL, found '{}'", endpoint));
+                    }
+                    endpoint => endpoint,
+                },
+            },
+            "regex" => {
+                let pattern = text("pattern")?.ok_or_else(|| "regex needs a pattern".to_string())?;
+                regex::Regex::new(&pattern).map_err(|e| message!("invalid pattern of regex: {}", e))?;
+                GuardrailCheck::Regex { pattern }
+            }
+            _ => GuardrailCheck::Resource {
+                source: text("source")?.ok_or_else(|| "rules needs a source, such as resource(\"s3://guardrails/rules.json\")".to_string())?,
+            },
+        };
+        let name = text("name")?.unwrap_or_else(|| name.to_string());
+        Ok(GuardrailRule { name, check, action })
+    }
+}
+
+/// Where an LLM agent keeps the chat history of its sessions.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryStore {
+    /// The state store of the agent's runtime.
+    State,
+}
+
+impl MemoryStore {
+    /// Every store, in the order they are documented.
+    pub const ALL: [MemoryStore; 1] = [MemoryStore::State];
+
+    /// The store as written in `store:`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MemoryStore::State => "state",
+        }
+    }
+}
+
+/// The chat history an LLM agent keeps per session, as written in
+/// `memory: { scope: "session_id", window: 10, store: state }`.
+///
+/// Messages carrying the same value in the scope field belong to the same
+/// session; the last exchanges of a session (a prompt and its response) come
+/// before every new prompt of that session. Sessions idle for longer than
+/// the TTL are forgotten.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MemoryConfig {
+    /// Field of the messages identifying their session, as a dotted path.
+    pub scope: String,
+    /// Exchanges kept per session.
+    pub window: u32,
+    /// Where the history is kept.
+    pub store: MemoryStore,
+    /// Seconds a session is kept after its last exchange.
+    pub ttl_secs: u64,
+}
+
+impl MemoryConfig {
+    /// Exchanges kept per session when `window` is not given.
+    pub const DEFAULT_WINDOW: u32 = 10;
+    /// Seconds a session is kept when `ttl` is not given (a day).
+    pub const DEFAULT_TTL_SECS: u64 = 86_400;
+    /// Every setting of `memory`.
+    const SETTINGS: [&'static str; 4] = ["scope", "window", "store", "ttl"];
+
+    /// Read a `memory: { scope: "session_id", window: 10, store: state, ttl: "1h" }` option.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let Value::Object(options) = value else {
+            return Err(message!("expected an object such as {{ scope: \"session_id\", window: 10 }}, found {}", value));
+        };
+        let mut unknown: Vec<&String> = options.keys().filter(|key| !Self::SETTINGS.contains(&key.as_str())).collect();
+        unknown.sort();
+        if let Some(key) = unknown.first() {
+            return Err(message!("unknown memory setting '{}'", key));
+        }
+
+        let scope = match options.get("scope") {
+            Some(Value::String(scope) | Value::Path(scope)) if scope.split('.').all(|segment| !segment.trim().is_empty()) => scope.clone(),
+            Some(other) => return Err(message!("scope must be the field identifying the session, found {}", other)),
+            None => return Err(message!("missing scope, the field identifying the session")),
+        };
+        let window = match options.get("window") {
+            Some(Value::Number(n)) if *n >= 1.0 && n.fract() == 0.0 && *n <= u32::MAX as f64 => *n as u32,
+            Some(other) => return Err(message!("window must be a whole number of at least 1, found {}", other)),
+            None => Self::DEFAULT_WINDOW,
+        };
+        let store = match options.get("store") {
+            Some(Value::String(store) | Value::Path(store)) => MemoryStore::ALL
+                .into_iter()
+                .find(|known| known.as_str() == store)
+                .ok_or_else(|| message!("unknown store '{}' (expected state)", store))?,
+            Some(other) => return Err(message!("store must be state, found {}", other)),
+            None => MemoryStore::State,
+        };
+        let ttl_secs = match options.get("ttl") {
+            Some(ttl) => ttl
+                .as_duration_secs()
+                .filter(|secs| *secs > 0)
+                .ok_or_else(|| message!("ttl must be a duration such as \"1h\", found {}", ttl))?,
+            None => Self::DEFAULT_TTL_SECS,
+        };
+        Ok(Self { scope, window, store, ttl_secs })
+    }
+}
+
+/// Monthly token usage an LLM agent is budgeted for, as written in
+/// `budget: { input_tokens: 20000000, output_tokens: 4000000 }`.
+///
+/// The budget is declarative: cost estimates price it, nothing enforces it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LlmBudgetConfig {
+    /// Prompt tokens per month.
+    pub input_tokens: u64,
+    /// Completion tokens per month.
+    pub output_tokens: u64,
+}
+
+impl LlmBudgetConfig {
+    /// Every setting of `budget`.
+    const SETTINGS: [&'static str; 2] = ["input_tokens", "output_tokens"];
+
+    /// Read a `budget: { input_tokens: 20000000, output_tokens: 4000000 }` option.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let Value::Object(options) = value else {
+            return Err(message!("expected an object such as {{ input_tokens: 20000000, output_tokens: 4000000 }}, found {}", value));
+        };
+        let mut unknown: Vec<&String> = options.keys().filter(|key| !Self::SETTINGS.contains(&key.as_str())).collect();
+        unknown.sort();
+        if let Some(key) = unknown.first() {
+            return Err(message!("unknown budget setting '{}'", key));
+        }
+
+        let tokens = |setting: &str| match options.get(setting) {
+            Some(Value::Number(n)) if *n >= 0.0 && n.fract() == 0.0 && *n <= u64::MAX as f64 => Ok(*n as u64),
+            Some(other) => Err(message!("{} must be a whole number of tokens per month, found {}", setting, other)),
+            None => Ok(0),
+        };
+        let budget = Self { input_tokens: tokens("input_tokens")?, output_tokens: tokens("output_tokens")? };
+        if budget.input_tokens == 0 && budget.output_tokens == 0 {
+            return Err(message!("a budget needs input_tokens or output_tokens"));
+        }
+        Ok(budget)
+    }
+
+    /// Read the `budget` option of an agent, if it has one.
+    pub fn from_agent(agent: &Agent) -> std::result::Result<Option<Self>, Message> {
+        agent.config_value(BUDGET_OPTION).map(Self::from_value).transpose()
+    }
+}
+
+/// Sticky routing of a Router agent, written
+/// `strategy: hash(key: data.customer_id, partitions: 8)`.
+///
+/// Messages are spread over the topics by a consistent hash of their key, so
+/// messages with the same key always go to the same topic. The topics are
+/// either the `targets` listed or `partitions` numbered subjects below the
+/// agent's output topic.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HashRoutingConfig {
+    /// Path of the key in the messages, starting with `data`.
+    pub key: Vec<String>,
+    /// Number of topics the keys are spread over: partitions of the output topic, or the targets.
+    pub partitions: u32,
+    /// Topics the keys are spread over, instead of partitions.
+    pub targets: Vec<String>,
+}
+
+impl HashRoutingConfig {
+    /// The name hash routing is written with.
+    pub const HASH: &'static str = "hash";
+    /// Partitions of the output topic when neither `partitions` nor `targets` is given.
+    pub const DEFAULT_PARTITIONS: u32 = 8;
+    /// Every setting of `hash(...)`.
+    const SETTINGS: [&'static str; 3] = ["key", "partitions", "targets"];
+
+    /// Read a `hash(key: data.<field>, partitions: 8)` or `hash(key: data.<field>, targets: [...])` value.
+    pub fn from_value(value: &Value) -> std::result::Result<Self, Message> {
+        let options = match value {
+            Value::Tagged(name, options) if name == Self::HASH => options,
+            Value::Tagged(name, _) => return Err(message!("unknown strategy '{}' (expected hash)", name)),
+            other => return Err(message!("expected hash(key: data.<field>), found {}", other)),
+        };
+        let mut unknown: Vec<&String> = options.keys().filter(|key| !Self::SETTINGS.contains(&key.as_str())).collect();
+        unknown.sort();
+        if let Some(key) = unknown.first() {
+            return Err(message!("unknown hash setting '{}'", key));
+        }
+
+        let key: Vec<String> = match options.get("key") {
+            Some(Value::Path(path) | Value::String(path)) => path.split('.').map(str::to_string).collect(),
+            Some(other) => return Err(message!("key must be a path below data such as data.customer_id, found {}", other)),
+            None => return Err(message!("missing key, the path of the field messages are routed by")),
+        };
+        if key.len() < 2 || key[0] != "data" || key.iter().any(|segment| segment.trim().is_empty()) {
+            return Err(message!("key must be a path below data such as data.customer_id, found {}", key.join(".")));
+        }
+
+        let targets = match options.get("targets") {
+            Some(Value::Array(targets)) if !targets.is_empty() => {
+                let mut listed = Vec::new();
+                for target in targets {
+                    match target {
+                        Value::String(topic) if !topic.trim().is_empty() && !listed.contains(topic) => listed.push(topic.clone()),
+                        Value::String(topic) if listed.contains(topic) => return Err(message!("target '{}' is listed twice", topic)),
+                        other => return Err(message!("targets must list topics, found {}", other)),
+                    }
+                }
+                listed
+            }
+            Some(other) => return Err(message!("targets must be a non-empty list of topics, found {}", other)),
+            None => Vec::new(),
+        };
+        let partitions = match options.get("partitions") {
+            Some(_) if !targets.is_empty() => return Err(message!("give partitions or targets, not both")),
+            Some(Value::Number(n)) if *n >= 1.0 && n.fract() == 0.0 && *n <= u32::MAX as f64 => *n as u32,
+            Some(other) => return Err(message!("partitions must be a whole number of at least 1, found {}", other)),
+            None if targets.is_empty() => Self::DEFAULT_PARTITIONS,
+            None => targets.len() as u32,
+        };
+        Ok(Self { key, partitions, targets })
+    }
+
+    /// Read the `strategy` option of an agent, if it has one.
+    pub fn from_agent(agent: &Agent) -> std::result::Result<Option<Self>, Message> {
+        agent.config_value(STRATEGY_OPTION).map(Self::from_value).transpose()
+    }
+}
+
+/// Routing table of a Router agent kept in a resource, written
+/// `rules: resource("s3://config/routes.yaml")`.
+///
+/// The agent loads the table when it starts and reloads it while it runs,
+/// so routes change without redeploying. The table is a YAML or JSON list of
+/// routes, told apart by the extension of the resource. An inline
+/// `rules: { ... }` map is not a table resource.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RouteTableConfig {
+    /// URI of the table.
+    pub source: String,
+    /// Whether the table is YAML rather than JSON.
+    pub yaml: bool,
+}
+
+/// A route of a routing table, as the Router agent reads it.
+#[derive(Debug, Deserialize)]
+struct TableRoute {
+    id: String,
+    pattern: String,
+    actions: Vec<TableAction>,
+}
+
+/// An action of a route, as the Router agent reads it.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum TableAction {
+    Publish { target: String },
+    Forward { target: String },
+    Log { message: String },
+    Transform { target: String },
+    Delay { target: String, after: String },
+}
+
+impl RouteTableConfig {
+    /// Seconds between reloads of the table, unless the agent's environment sets others.
+    pub const DEFAULT_RELOAD_SECS: u64 = 30;
+
+    /// Read a `rules: resource("...")` value; inline rules give `None`.
+    pub fn from_value(value: &Value) -> std::result::Result<Option<Self>, Message> {
+        let source = match value {
+            Value::String(source) => source.trim(),
+            Value::Object(_) => return Ok(None),
+            other => return Err(message!("rules must be a resource(\"...\") with the routing table, found {}", other)),
+        };
+        if source.is_empty() {
+            return Err(message!("the URI of the routing table is empty"));
+        }
+
+        // The extension is that of the archive member, if any, without the fragment or query
+        let path = source.rsplit('!').next().unwrap_or(source);
+        let path = path.split(['#', '?']).next().unwrap_or(path).to_ascii_lowercase();
+        let yaml = if path.ends_with(".yaml") || path.ends_with(".yml") {
+            true
+        } else if path.ends_with(".json") {
+            false
+        } else {
+            return Err(message!("the routing table {} must be a .yaml, .yml or .json file", source));
+        };
+        Ok(Some(Self { source: source.to_string(), yaml }))
+    }
+
+    /// Read the `rules` option of an agent, if it names a routing table.
+    pub fn from_agent(agent: &Agent) -> std::result::Result<Option<Self>, Message> {
+        match agent.config_value(RULES_OPTION) {
+            Some(value) => Self::from_value(value),
+            None => Ok(None),
+        }
+    }
+
+    /// Check the contents of the table as the agent would load them, returning the number of routes.
+    pub fn check_table(&self, contents: &[u8]) -> std::result::Result<usize, Message> {
+        let routes: Vec<TableRoute> = if self.yaml {
+            serde_yaml::from_slice(contents).map_err(|e| message!("invalid YAML routing table: {}", e))?
+        } else {
+            serde_json::from_slice(contents).map_err(|e| message!("invalid JSON routing table: {}", e))?
+        };
+        let mut ids = BTreeSet::new();
+        for route in &routes {
+            if !ids.insert(route.id.as_str()) {
+                return Err(message!("route '{}' is listed twice", route.id));
+            }
+            regex::Regex::new(&route.pattern).map_err(|e| message!("invalid pattern of route '{}': {}", route.id, e))?;
+            for action in &route.actions {
+                match action {
+                    TableAction::Publish { target } | TableAction::Forward { target } | TableAction::Transform { target }
+                        if target.trim().is_empty() =>
+                    {
+                        return Err(message!("route '{}' has an action without a target", route.id));
+                    }
+                    TableAction::Log { message } if message.is_empty() => {
+                        return Err(message!("route '{}' logs an empty message", route.id));
+                    }
+                    TableAction::Delay { target, .. } if target.trim().is_empty() => {
+                        return Err(message!("route '{}' has an action without a target", route.id));
+                    }
+                    TableAction::Delay { after, .. } if parse_duration_millis(after).is_none() => {
+                        return Err(message!("route '{}' delays by an invalid interval '{}'", route.id, after));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(routes.len())
+    }
 }
 
 /// Represents resource requirements for a deployment.
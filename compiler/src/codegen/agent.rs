@@ -1,128 +1,284 @@
 //! Agent code generation
 
 use anyhow::Result;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use tera::Tera;
 
-use crate::ast::{Agent, AgentType};
-use super::template_processor::{process_template_dir, create_base_context};
+use crate::ast::{Agent, AgentType, CustomAgentConfig, Platform, Workflow};
+use crate::message;
+use super::aggregator::AggregatorSettings;
+use super::auth::AuthSettings;
+use super::bayesian_network::BayesianNetworkSettings;
+use super::kubernetes::{
+    image_of_agent, workflow_registry, workflow_tag, BatchSettings, BlueGreenSettings, BrokerSettings, CanarySettings, DeploymentSettings, DrainSettings,
+    FileSettings, ManifestMetadata, PreloadSettings, SecretEnvSettings, SigningSettings, StorageSettings, WebhookSettings,
+};
+use super::condition::{AssertSettings, WhenSettings};
+use super::contracts::ValidationSettings;
+use super::custom::{generate_custom_agent, CustomSettings};
+use super::dependencies::{generate_requirements, generate_runtime_crate, CrateSettings, DependencySettings};
+use super::drift::workflow_hash;
+use super::encoding::EncodingSettings;
+use super::failover::{FailoverSettings, METRICS_PORT};
+use super::guardrails::GuardrailSettings;
+use super::feature_store::FeatureStoreSettings;
+use super::inference::InferenceSettings;
+use super::llm_provider::ProviderSettings;
+use super::memory::MemorySettings;
+use super::missing_values::MissingValueSettings;
+use super::nats::ExternalNats;
+use super::nomad::generate_nomad_job;
+use super::model_drift::ModelDriftSettings;
+use super::normalizer::NormalizerSettings;
+use super::quality::QualitySettings;
+use super::resilience::{FallbackSettings, RetrySettings};
+use super::review::ReviewSettings;
+use super::review_ui::{generate_review_ui, ReviewUiSettings};
+use super::routing::{RouteTableSettings, RoutingSettings};
+use super::rule_engine::RuleEngineSettings;
+use super::saga::SagaSettings;
+use super::scaffold::generate_test_scaffold;
+use super::sink::OutputSink;
+use super::template_processor::{create_base_context, render_template, render_templates};
 use anyhow::Context;
 
-/// Generate agent-specific files based on agent type
-pub fn generate_agent(agent: &Agent, output_dir: &Path, tera: &Tera) -> Result<()> {
-    // Get agent ID or return error if missing
-    let agent_id = agent.id.as_ref().ok_or_else(|| 
-        anyhow::anyhow!("Agent must have an ID")
-    )?;
+/// Language an agent's code is generated in
+pub fn agent_language(agent: &Agent) -> &'static str {
+    match agent.agent_type {
+        AgentType::MLModel | AgentType::QualityMonitor | AgentType::BayesianNetwork => "python",
+        // Custom agents are in the language of their templates
+        AgentType::Custom => match CustomAgentConfig::from_agent(agent) {
+            Ok(config) if config.language == "python" => "python",
+            _ => "rust",
+        },
+        _ => "rust",
+    }
+}
 
-    // Determine agent type and template directory
-    let (agent_type, template_dir) = match agent.agent_type {
-        AgentType::LLM => ("llm", "llm"),
-        AgentType::MLModel => ("mlmodel", "mlmodel"),
-        AgentType::DataProcessor => ("dataprocessor", "dataprocessor"),
-        AgentType::Router => ("router", "router"),
-        AgentType::DecisionMatrix => ("decisionmatrix", "decisionmatrix"),
-        AgentType::HumanReview => ("humanreview", "humanreview"),
+/// Port an agent serves its `/metrics` on, if it has any to serve
+///
+/// LLM agents chaining providers or with guardrails and RuleEngine agents
+/// export their own counters; canary agents export the message counters of
+/// their runtime, which the analysis of the rollout reads.
+pub fn metrics_port(workflow: &Workflow, agent: &Agent) -> Result<Option<u16>> {
+    let canary = match &agent.id {
+        Some(agent_id) => CanarySettings::for_agent(workflow, agent_id)?.is_some(),
+        None => false,
     };
+    let exported = FailoverSettings::for_agent(agent)?.is_some()
+        || GuardrailSettings::for_agent(workflow, agent)?.is_some()
+        || RuleEngineSettings::for_agent(agent)?.is_some();
+    Ok((canary || exported).then_some(METRICS_PORT))
+}
 
-    // Create agent context
-    let mut context = create_base_context(agent_id);
+/// Context the templates of an agent are rendered with
+///
+/// Shared by everything generated per agent: its code, Dockerfile and README,
+/// and its deployment, whether Kubernetes manifests or a Nomad job.
+pub fn agent_context(workflow: &Workflow, agent: &Agent, external_nats: Option<&ExternalNats>) -> Result<tera::Context> {
+    let agent_id = agent.id.as_ref().ok_or_else(|| anyhow::anyhow!(message!("Agent must have an ID")))?;
+
+    let mut context = create_base_context(&workflow.name);
     context.insert("agent", agent);
     context.insert("agent_type", &agent.agent_type);
     context.insert("agent_id", agent_id);
+    context.insert("deployment", &DeploymentSettings::for_agent(workflow, agent)?);
+    context.insert("metadata", &ManifestMetadata::for_workflow(workflow));
+    context.insert("drain", &DrainSettings::for_agent(agent));
+    context.insert("image", &image_of_agent(agent, workflow_registry(workflow), workflow_tag(workflow)));
+    context.insert("canary", &CanarySettings::for_agent(workflow, agent_id)?);
+    context.insert("blue_green", &BlueGreenSettings::for_workflow(workflow));
+    context.insert("storage", &StorageSettings::for_agent(workflow, agent_id));
+    context.insert("batch", &BatchSettings::for_agent(workflow, agent_id));
+    context.insert("broker", &BrokerSettings::for_agent(workflow, agent));
+    context.insert("webhook", &WebhookSettings::for_agent(workflow, agent_id));
+    context.insert("files", &FileSettings::for_agent(workflow, agent_id));
+    context.insert("signing", &SigningSettings::for_workflow(workflow));
+    context.insert("preload", &PreloadSettings::for_agent(workflow, agent));
+    context.insert("secret_env", &SecretEnvSettings::for_agent(workflow, agent));
+    context.insert("when", &WhenSettings::for_agent(workflow, agent)?);
+    context.insert("assertions", &AssertSettings::for_agent(workflow, agent)?);
+    context.insert("validation", &ValidationSettings::for_agent(agent)?);
+    context.insert("nats", &external_nats);
+    context.insert("retry", &RetrySettings::for_agent(agent)?);
+    context.insert("fallback", &FallbackSettings::for_agent(agent)?);
+    context.insert("saga", &SagaSettings::for_agent(workflow, agent)?);
+    context.insert("quality", &QualitySettings::for_agent(agent)?);
+    context.insert("review", &ReviewSettings::for_agent(workflow, agent)?);
+    context.insert("auth", &AuthSettings::for_agent(workflow, agent)?);
+    context.insert("model_drift", &ModelDriftSettings::for_agent(workflow, agent)?);
+    context.insert("feature_store", &FeatureStoreSettings::for_agent(workflow, agent)?);
+    context.insert("inference", &InferenceSettings::for_agent(agent)?);
+    context.insert("llm_provider", &ProviderSettings::for_agent(agent)?);
+    context.insert("dependencies", &DependencySettings::for_agent(workflow, agent)?);
+    context.insert("crates", &CrateSettings::for_agent(workflow, agent)?);
+    context.insert("metrics_port", &metrics_port(workflow, agent)?);
+    context.insert("failover", &FailoverSettings::for_agent(agent)?);
+    context.insert("guardrails", &GuardrailSettings::for_agent(workflow, agent)?);
+    context.insert("memory", &MemorySettings::for_agent(workflow, agent)?);
+    context.insert("routing", &RoutingSettings::for_agent(workflow, agent)?);
+    context.insert("route_table", &RouteTableSettings::for_agent(agent)?);
+    context.insert("aggregator", &AggregatorSettings::for_agent(agent)?);
+    context.insert("rule_engine", &RuleEngineSettings::for_agent(agent)?);
+    context.insert("bayesian_network", &BayesianNetworkSettings::for_agent(agent)?);
+    context.insert("custom", &CustomSettings::for_agent(agent)?);
+    context.insert("normalizer", &NormalizerSettings::for_agent(agent)?);
+    context.insert("missing_values", &MissingValueSettings::for_agent(agent)?);
+    context.insert("encoding", &EncodingSettings::for_agent(workflow, agent)?);
+    context.insert("workflow_hash", &workflow_hash(workflow)?);
     
     // Use agent ID as the name
     context.insert("agent_name", agent_id);
 
+    Ok(context)
+}
+
+/// Generate agent-specific files based on agent type
+pub fn generate_agent(
+    workflow: &Workflow,
+    agent: &Agent,
+    output_dir: &Path,
+    tera: &Tera,
+    external_nats: Option<&ExternalNats>,
+    sink: &mut dyn OutputSink,
+) -> Result<()> {
+    // Get agent ID or return error if missing
+    let agent_id = agent.id.as_ref().ok_or_else(|| 
+        anyhow::anyhow!(message!("Agent must have an ID"))
+    )?;
+
+    let context = agent_context(workflow, agent, external_nats)?;
+
     // Create agent directory based on type and name
     let agent_dir = output_dir.join(format!("agents/{}", agent_id));
-    std::fs::create_dir_all(&agent_dir)
-        .with_context(|| format!("Failed to create agent directory: {}", agent_dir.display()))?;
-
-    // Process template directory
-    let template_path = PathBuf::from("templates/agents").join(template_dir);
-    if template_path.exists() {
-        process_template_dir(&template_path, &agent_dir, &context, tera, &[])
-            .with_context(|| format!("Failed to process template for agent: {}", agent_id))?;
-    } else {
-        // Fallback to default agent template if specific template doesn't exist
-        let default_template_path = PathBuf::from("templates/agents/default");
-        if default_template_path.exists() {
-            process_template_dir(&default_template_path, &agent_dir, &context, tera, &[])
-                .with_context(|| format!("Failed to process default template for agent: {}", agent_id))?;
-        }
+    sink.create_dir(&agent_dir)
+        .with_context(|| message!("Failed to create agent directory: {}", agent_dir.display()))?;
+
+    // Render the agent's code from the templates of its type
+    let written = match CustomSettings::for_agent(agent)? {
+        Some(custom) => generate_custom_agent(agent_id, &custom, &agent_dir, &context, tera, sink)?,
+        None => generate_builtin_agent(agent, &agent_dir, &context, tera, sink)
+            .with_context(|| message!("Failed to generate agent: {}", agent_id))?,
+    };
+
+    // Generate the Dockerfile and the pinned dependencies it installs
+    if !written.iter().any(|path| path == "Dockerfile") {
+        generate_dockerfile(agent, &agent_dir, &context, tera, sink)?;
     }
+    generate_requirements(workflow, agent, &agent_dir, sink)?;
+    generate_runtime_crate(agent, &agent_dir, sink)?;
 
-    // Generate Dockerfile
-    generate_dockerfile(agent, &agent_dir, &context, tera)?;
+    // Generate the unit tests to start from
+    if agent.agent_type != AgentType::Custom {
+        generate_test_scaffold(workflow, agent, &agent_dir, &context, tera, sink)?;
+    }
     
-    // Generate Kubernetes manifests
-    generate_kubernetes_manifests(agent, &agent_dir, &context, tera).ok(); // Ignore errors for now
+    // Generate the manifests of the platform the agent is deployed on
+    match workflow.platform() {
+        Platform::Kubernetes => generate_kubernetes_manifests(&agent_dir, &context, tera, sink)
+            .with_context(|| message!("Failed to generate the manifests of agent: {}", agent_id))?,
+        Platform::Nomad => generate_nomad_job(workflow, agent, &agent_dir, &context, tera, external_nats, sink)?,
+    }
     
     // Generate README for the agent
-    generate_readme(agent, &agent_dir, &context, tera)?;
+    let readme = render_template(tera, "agents/README.md.tera", &context)?;
+    sink.write(&agent_dir.join("README.md"), readme.as_bytes())?;
+
+    // Generate the review UI of human reviewers
+    if let Some(review_ui) = ReviewUiSettings::for_agent(workflow, agent, external_nats)? {
+        let mut ui_context = context.clone();
+        ui_context.insert("review_ui", &review_ui);
+        generate_review_ui(&agent_dir.join("ui"), &ui_context, tera, sink)
+            .with_context(|| message!("Failed to generate the review UI of agent: {}", agent_id))?;
+    }
 
     Ok(())
 }
 
-/// Generate Dockerfile for the agent
-fn generate_dockerfile(
+/// Directory of the templates of a built-in agent type, below `agents/<language>/`
+fn builtin_template_dir(agent_type: &AgentType) -> Option<&'static str> {
+    Some(match agent_type {
+        AgentType::LLM => "LLM",
+        AgentType::MLModel => "MLModel",
+        AgentType::DataProcessor => "DataProcessor",
+        AgentType::Router => "Router",
+        AgentType::DecisionMatrix => "DecisionMatrix",
+        AgentType::HumanReview => "HumanReview",
+        AgentType::QualityMonitor => "QualityMonitor",
+        AgentType::Aggregator => "Aggregator",
+        AgentType::RuleEngine => "RuleEngine",
+        AgentType::BayesianNetwork => "BayesianNetwork",
+        AgentType::DataNormalizer => "DataNormalizer",
+        AgentType::MissingValueHandler => "MissingValueHandler",
+        // Rendered from the templates of the layers, see `custom`
+        AgentType::Custom => return None,
+    })
+}
+
+/// Render the templates of a built-in agent type, such as `agents/rust/LLM/`, into its directory
+///
+/// Agents also get the modules all of their language share, such as
+/// `agents/rust/src/`, but for the test scaffold, for `metrics` unless they
+/// have a metrics port, and for `kafka` unless their workflow reads or
+/// writes Kafka; a module of the type's own templates replaces
+/// the shared one of the same name. Returns the paths written, relative to
+/// `agent_dir`.
+fn generate_builtin_agent(
     agent: &Agent,
-    output_dir: &Path,
+    agent_dir: &Path,
     context: &tera::Context,
     tera: &Tera,
-) -> Result<()> {
-    let dockerfile_template = match agent.agent_type {
-        AgentType::MLModel => "agents/mlmodel/Dockerfile.tera",
-        _ => "agents/default/Dockerfile.tera",
-    };
-
-    let output_path = output_dir.join("Dockerfile");
-    
-    // Only generate if template exists
-    if let Some(template) = tera.get_template_names().find(|&name| name == dockerfile_template) {
-        if let Ok(rendered) = tera.render(template, context) {
-            std::fs::write(&output_path, rendered)
-                .with_context(|| format!("Failed to write Dockerfile: {}", output_path.display()))?;
-        }
+    sink: &mut dyn OutputSink,
+) -> Result<Vec<String>> {
+    let template_dir = builtin_template_dir(&agent.agent_type)
+        .ok_or_else(|| anyhow::anyhow!(message!("{} agents have no built-in templates", agent.agent_type)))?;
+    let language = agent_language(agent);
+    let mut exclude = vec!["tests.rs.tera"];
+    if context.get("metrics_port").is_none_or(|port| port.is_null()) {
+        exclude.extend(["metrics.rs.tera", "kumeo_agent_{{agent_name | lower}}/metrics.py.tera"]);
+    }
+    if context.get("broker").and_then(|broker| broker.get("kafka")).is_none_or(|kafka| kafka.is_null()) {
+        exclude.push("kumeo_agent_{{agent_name | lower}}/kafka.py.tera");
     }
+    let shared_prefix = format!("agents/{}/src/", language);
+    let shared = render_templates(tera, &shared_prefix, &agent_dir.join("src"), context, &exclude, sink)?;
+    let mut written: Vec<String> = shared.into_iter().map(|path| format!("src/{}", path)).collect();
 
-    Ok(())
+    let prefix = format!("agents/{}/{}/", language, template_dir);
+    let own = render_templates(tera, &prefix, agent_dir, context, &[], sink)?;
+    if own.is_empty() {
+        return Err(anyhow::anyhow!(message!("No templates found for {} agents in {}", agent.agent_type, prefix)));
+    }
+    written.extend(own);
+    Ok(written)
 }
 
-/// Generate Kubernetes manifests for an agent
-fn generate_kubernetes_manifests(
-    _agent: &Agent,
+/// Generate the Dockerfile of the agent's language, `agents/<language>/Dockerfile.tera`
+fn generate_dockerfile(
+    agent: &Agent,
     output_dir: &Path,
     context: &tera::Context,
     tera: &Tera,
+    sink: &mut dyn OutputSink,
 ) -> Result<()> {
-    let k8s_dir = output_dir.join("kubernetes");
-    std::fs::create_dir_all(&k8s_dir).ok();
-    
-    let template_dir = PathBuf::from("compiler/templates/kubernetes/agent");
-    if template_dir.exists() {
-        process_template_dir(&template_dir, &k8s_dir, context, tera, &[]).ok();
-    }
-    
-    Ok(())
+    let dockerfile_template = format!("agents/{}/Dockerfile.tera", agent_language(agent));
+    let rendered = render_template(tera, &dockerfile_template, context)?;
+
+    let output_path = output_dir.join("Dockerfile");
+    sink.write(&output_path, rendered.as_bytes())
+        .with_context(|| message!("Failed to write Dockerfile: {}", output_path.display()))
 }
 
-/// Generate a README file for the agent
-fn generate_readme(
-    _agent: &Agent,
-    output_dir: &Path,
+/// Generate the Kubernetes manifests of an agent from `kubernetes/agent/`
+fn generate_kubernetes_manifests(
+    agent_dir: &Path,
     context: &tera::Context,
     tera: &Tera,
+    sink: &mut dyn OutputSink,
 ) -> Result<()> {
-    let readme_path = output_dir.join("README.md");
-    
-    // Only generate if template exists
-    if let Some(template) = tera.get_template_names().find(|&name| name == "agents/README.md.tera") {
-        if let Ok(rendered) = tera.render(template, context) {
-            std::fs::write(&readme_path, rendered).ok();
-        }
-    }
-    
+    let k8s_dir = agent_dir.join("kubernetes");
+    sink.create_dir(&k8s_dir)
+        .with_context(|| message!("Failed to create kubernetes directory: {}", k8s_dir.display()))?;
+    render_templates(tera, "kubernetes/agent/", &k8s_dir, context, &[], sink)?;
     Ok(())
 }
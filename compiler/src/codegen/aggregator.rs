@@ -0,0 +1,98 @@
+//! Windowed aggregation for Aggregator agents
+//!
+//! An Aggregator with `window: { count: 100 }` or `window: { time: "1m" }`,
+//! `group_by: data.customer_id` and `reduce: { total: sum(data.amount) }`
+//! keeps one set of accumulators per group in memory. Every message is folded
+//! into the accumulators of its group; when the window of the group closes,
+//! the agent publishes one message with the group fields, the reductions and
+//! the bounds of the window under `window`, and the group starts over.
+//!
+//! Count windows close per group, on the message that fills them. Time
+//! windows are tumbling and aligned on the epoch, so every group closes at
+//! the same instants. Windows still open when the agent stops are published
+//! as they are.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ast::{Agent, AggregationWindow, AggregatorConfig, AgentType, Expr};
+use crate::message;
+use super::condition::rust_value;
+
+/// Windowed aggregation of an Aggregator agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AggregatorSettings {
+    /// Messages per group and window, for count windows
+    pub window_count: Option<u32>,
+    /// Length of the windows in milliseconds, for time windows
+    pub window_millis: Option<u64>,
+    /// Fields messages are grouped by, in `group_by` order
+    pub groups: Vec<GroupField>,
+    /// Reductions, by name
+    pub reductions: Vec<ReductionSettings>,
+}
+
+/// A field messages are grouped by
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GroupField {
+    /// Name of the field in the published messages
+    pub name: String,
+    /// Path of the field, as written
+    pub path: String,
+    /// Rust `&Value` expression reading the field from `data`
+    pub rust: String,
+}
+
+/// A field computed over each window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReductionSettings {
+    /// Name of the field in the published messages
+    pub name: String,
+    /// The reduction as written in the DSL
+    pub source: String,
+    /// Name of the reducer, such as `sum`
+    pub reducer: String,
+    /// Rust `&Value` expression reading the folded value from `data`; none for `count()`
+    pub rust: Option<String>,
+}
+
+impl AggregatorSettings {
+    /// Compute the aggregation of an Aggregator agent
+    pub fn for_agent(agent: &Agent) -> Result<Option<Self>> {
+        if agent.agent_type != AgentType::Aggregator {
+            return Ok(None);
+        }
+        let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+        let config = AggregatorConfig::from_agent(agent).map_err(|e| anyhow!(message!("Invalid aggregator {}: {}", agent_id, e)))?;
+
+        let (window_count, window_millis) = match config.window {
+            AggregationWindow::Count(count) => (Some(count), None),
+            AggregationWindow::Time(millis) => (None, Some(millis)),
+        };
+        let groups = config
+            .group_by
+            .iter()
+            .map(|path| GroupField {
+                name: path[path.len() - 1].clone(),
+                path: path.join("."),
+                rust: rust_value(&Expr::Field(path.clone())),
+            })
+            .collect();
+        let reductions = config
+            .reductions
+            .iter()
+            .map(|(name, reduction)| ReductionSettings {
+                name: name.clone(),
+                source: reduction.to_string(),
+                reducer: reduction.reducer.as_str().to_string(),
+                rust: reduction.of.as_ref().map(rust_value),
+            })
+            .collect();
+        Ok(Some(Self {
+            window_count,
+            window_millis,
+            groups,
+            reductions,
+        }))
+    }
+}
@@ -0,0 +1,68 @@
+//! OIDC authentication of human-facing endpoints
+//!
+//! HumanReview agents serve the review API their UI decides through, and the
+//! first agent of an `HTTP` source serves its webhook. With
+//! `auth: OIDC(issuer, client_id, groups_claim)` the generated Rust backend
+//! puts those endpoints behind a middleware that verifies the bearer token of
+//! every request against the issuer's published keys, and lets in only the
+//! `allow`ed groups when there are any. The settings reach the agent through
+//! a ConfigMap, so the issuer can be changed without rebuilding the image.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use super::kubernetes::WebhookSettings;
+use crate::ast::{Agent, AgentType, OidcAuthConfig, Workflow};
+use crate::message;
+
+/// OIDC settings of an agent's endpoints, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuthSettings {
+    /// URL of the OIDC issuer
+    pub issuer: String,
+    /// The issuer as a Rust string literal
+    pub rust_issuer: String,
+    /// Client ID the tokens must be issued for
+    pub client_id: String,
+    /// Claim listing the caller's groups
+    pub groups_claim: String,
+    /// Groups allowed in, separated by commas; every caller when empty
+    pub allowed_groups: String,
+    /// The allowed groups as a Rust slice literal
+    pub rust_allowed_groups: String,
+    /// ConfigMap the settings are read from
+    pub config_map: String,
+}
+
+impl AuthSettings {
+    /// Compute the OIDC settings of an agent, if it serves an authenticated endpoint
+    ///
+    /// That is a HumanReview agent with `auth`, or the agent serving the
+    /// webhook of an HTTP source with `auth`.
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Result<Option<Self>> {
+        let Some(agent_id) = agent.id.as_deref() else {
+            return Ok(None);
+        };
+        let config = if agent.agent_type == AgentType::HumanReview {
+            OidcAuthConfig::from_agent(agent)
+        } else if WebhookSettings::for_agent(workflow, agent_id).is_some() {
+            workflow.source.as_ref().map_or(Ok(None), OidcAuthConfig::from_source)
+        } else {
+            Ok(None)
+        };
+        let Some(config) = config.map_err(|e| anyhow!(message!("Invalid auth of {}: {}", agent_id, e)))? else {
+            return Ok(None);
+        };
+
+        // Debug-formatting strings and lists of strings gives valid Rust literals
+        Ok(Some(Self {
+            rust_issuer: format!("{:?}", config.issuer),
+            issuer: config.issuer,
+            client_id: config.client_id,
+            groups_claim: config.groups_claim,
+            allowed_groups: config.allow.join(","),
+            rust_allowed_groups: format!("&{:?}", config.allow),
+            config_map: format!("{}-oidc", agent_id),
+        }))
+    }
+}
@@ -0,0 +1,42 @@
+//! Inference for BayesianNetwork agents
+//!
+//! A BayesianNetwork with `network_path: "models/risk.bif"` loads the network
+//! with pgmpy when it starts, in the reader of the file's format. For every
+//! message, the fields named after variables of the network are the
+//! evidence, and the agent publishes the message with the posterior of each
+//! `query` variable, or of every variable not observed, under `posteriors`:
+//! the probability of each of its states.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ast::{Agent, AgentType, BayesianNetworkConfig, NetworkFormat};
+use crate::message;
+
+/// Network of a BayesianNetwork agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BayesianNetworkSettings {
+    /// Path or URI of the network file
+    pub network_path: String,
+    /// Format of the network file
+    pub format: NetworkFormat,
+    /// Variables whose posteriors are published, as a Python list; empty for every variable not observed
+    pub python_query: String,
+}
+
+impl BayesianNetworkSettings {
+    /// Compute the network of a BayesianNetwork agent
+    pub fn for_agent(agent: &Agent) -> Result<Option<Self>> {
+        if agent.agent_type != AgentType::BayesianNetwork {
+            return Ok(None);
+        }
+        let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+        let config = BayesianNetworkConfig::from_agent(agent).map_err(|e| anyhow!(message!("Invalid network of {}: {}", agent_id, e)))?;
+        Ok(Some(Self {
+            network_path: config.network_path,
+            format: config.format,
+            // JSON strings are valid Python strings
+            python_query: serde_json::to_string(&config.query)?,
+        }))
+    }
+}
@@ -0,0 +1,295 @@
+//! Combined cluster layout for several programs
+//!
+//! Generating programs one by one into the same cluster makes each of them
+//! deploy its own broker into the same namespace, and nothing stops two of
+//! them from reusing names or publishing to the same subjects. The combined
+//! layout gives every program its own namespace under `programs/<name>`,
+//! deploys a single NATS shared by all of them, and ties everything together
+//! with a root kustomization. Conflicts are detected before anything is
+//! written. A program generating several workflows gets one directory per
+//! workflow, `programs/<name>/<workflow>`, all in the program's namespace.
+
+use anyhow::{Context as _, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use tera::Tera;
+
+use crate::ast::{Source, Target, Workflow};
+use crate::message;
+use super::kubernetes::{webhook_subject, BrokerSettings, Endpoint, KafkaSettings, ManifestMetadata};
+use super::nats::ExternalNats;
+use super::sink::OutputSink;
+
+/// Namespace of the shared NATS
+pub const NATS_NAMESPACE: &str = "kumeo-system";
+
+/// Name of the shared NATS Service and StatefulSet
+const NATS_SERVICE: &str = "nats";
+
+/// Client port of the shared NATS
+const NATS_PORT: u16 = 4222;
+
+/// NATS server image of the shared NATS
+const NATS_IMAGE: &str = "nats:2.9-alpine";
+
+/// NATS server shared by every program of a layout
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NatsSettings {
+    /// Namespace the server runs in
+    pub namespace: &'static str,
+    /// Name of its Service and StatefulSet
+    pub service_name: &'static str,
+    /// Client port
+    pub port: u16,
+    /// Server image
+    pub image: &'static str,
+    /// URL agents connect to, passed as `NATS_URL`
+    pub url: String,
+    /// Whether the server runs outside the layout, which then doesn't deploy one
+    pub external: bool,
+}
+
+impl Default for NatsSettings {
+    fn default() -> Self {
+        Self {
+            namespace: NATS_NAMESPACE,
+            service_name: NATS_SERVICE,
+            port: NATS_PORT,
+            image: NATS_IMAGE,
+            url: format!("nats://{}.{}.svc.cluster.local:{}", NATS_SERVICE, NATS_NAMESPACE, NATS_PORT),
+            external: false,
+        }
+    }
+}
+
+impl NatsSettings {
+    /// Point the layout at an existing NATS instead of deploying one
+    ///
+    /// Agents generated with the same external NATS already carry its URL
+    /// and credentials, so the layout doesn't patch them.
+    pub fn external(nats: &ExternalNats) -> Self {
+        Self {
+            url: nats.url.clone(),
+            external: true,
+            ..Self::default()
+        }
+    }
+}
+
+/// A program of the combined layout
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClusterProgram {
+    /// Program name, from its input file
+    pub name: String,
+    /// Namespace its resources are deployed into
+    pub namespace: String,
+    /// Name of the workflow generated for it
+    pub workflow_name: String,
+    /// Directory of its generated output, relative to the layout root
+    pub dir: String,
+    /// Manifests of its kustomization, relative to its directory
+    pub resources: Vec<String>,
+    /// IDs of its agents
+    pub agents: Vec<String>,
+    /// NATS subjects its agents publish to
+    pub published_subjects: Vec<String>,
+    /// Labels of its `deployment` block, set on every resource by its kustomization
+    pub labels: BTreeMap<String, String>,
+    /// Annotations of its `deployment` block, set on every resource by its kustomization
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl ClusterProgram {
+    /// Describe a program of the layout
+    ///
+    /// The namespace comes from the workflow's `deployment` block and
+    /// defaults to the program name.
+    pub fn new(name: &str, workflow: &Workflow) -> Self {
+        let agents: Vec<String> = workflow.agents.iter().filter_map(|agent| agent.id.clone()).collect();
+
+        let mut resources = Vec::new();
+        if KafkaSettings::for_workflow(workflow).is_some_and(|kafka| kafka.in_cluster) {
+            resources.push("kubernetes/kafka.yaml".to_string());
+        }
+        resources.extend(agents.iter().map(|id| format!("agents/{}/kubernetes/deployment.yaml", id)));
+        if workflow.monitor.is_some() {
+            resources.push("kubernetes/monitoring/monitoring.yaml".to_string());
+        }
+
+        let mut published_subjects = Vec::new();
+        if let Some(Target::NATS(subject, _)) = &workflow.target {
+            published_subjects.push(subject.clone());
+        }
+        // Agents expanded from subworkflows publish on their own subjects
+        for agent in &workflow.agents {
+            let target = BrokerSettings::for_agent(workflow, agent).target;
+            if let Some(Endpoint { broker: "nats", topic }) = target {
+                if !published_subjects.contains(&topic) {
+                    published_subjects.push(topic);
+                }
+            }
+        }
+        if let Some(Source::HTTP(..)) = &workflow.source {
+            published_subjects.push(webhook_subject(workflow));
+        }
+
+        let metadata = ManifestMetadata::for_workflow(workflow);
+        Self {
+            name: name.to_string(),
+            namespace: workflow
+                .deployment
+                .as_ref()
+                .and_then(|deployment| deployment.namespace.clone())
+                .unwrap_or_else(|| name.to_string()),
+            workflow_name: workflow.name.clone(),
+            dir: format!("programs/{}", name),
+            resources,
+            agents,
+            published_subjects,
+            labels: metadata.labels,
+            annotations: metadata.annotations,
+        }
+    }
+
+    /// Describe one of the workflows of a program that generates several
+    ///
+    /// Each workflow gets its own directory under the program's. Its
+    /// namespace still defaults to the program name, so the workflows of a
+    /// program deploy together unless their `deployment` blocks say otherwise.
+    pub fn for_workflow(program: &str, workflow: &Workflow) -> Self {
+        let workflow_dir = dns_label(&workflow.name);
+        let mut member = Self::new(&format!("{}-{}", program, workflow_dir), workflow);
+        if workflow.deployment.as_ref().and_then(|deployment| deployment.namespace.as_ref()).is_none() {
+            member.namespace = program.to_string();
+        }
+        member.dir = format!("programs/{}/{}", program, workflow_dir);
+        member
+    }
+}
+
+/// Program name of an input file: its stem as a DNS label
+pub fn program_name(input: &Path) -> String {
+    let stem = input.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    dns_label(&stem)
+}
+
+/// A name lowercased, with anything but letters and digits turned into dashes
+fn dns_label(name: &str) -> String {
+    let label: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    label.trim_matches('-').to_string()
+}
+
+/// Whether two NATS subjects, possibly with wildcards, can match the same message
+pub fn subjects_overlap(a: &str, b: &str) -> bool {
+    let mut a = a.split('.');
+    let mut b = b.split('.');
+    loop {
+        match (a.next(), b.next()) {
+            (Some(">"), Some(_)) | (Some(_), Some(">")) => return true,
+            (Some(x), Some(y)) if x == y || x == "*" || y == "*" => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Names and subjects programs of a layout would step on each other with
+pub fn conflicts(programs: &[ClusterProgram]) -> Vec<String> {
+    let mut conflicts = Vec::new();
+
+    let mut names = BTreeSet::new();
+    let mut workflows = BTreeMap::new();
+    for program in programs {
+        if !names.insert(program.name.as_str()) {
+            conflicts.push(format!("Program name `{}` is used by more than one input", program.name));
+        }
+        if let Some(other) = workflows.insert(program.workflow_name.as_str(), program.name.as_str()) {
+            conflicts.push(format!(
+                "Programs `{}` and `{}` both define workflow `{}`",
+                other, program.name, program.workflow_name
+            ));
+        }
+    }
+
+    // Programs sharing a namespace must not reuse resource names
+    let mut agents: BTreeMap<(&str, &str), &str> = BTreeMap::new();
+    for program in programs {
+        for agent in &program.agents {
+            if let Some(other) = agents.insert((program.namespace.as_str(), agent.as_str()), program.name.as_str()) {
+                if other != program.name {
+                    conflicts.push(format!(
+                        "Programs `{}` and `{}` both deploy agent `{}` into namespace `{}`",
+                        other, program.name, agent, program.namespace
+                    ));
+                }
+            }
+        }
+    }
+
+    // With a shared NATS, overlapping subjects mix the output of both programs
+    for (index, program) in programs.iter().enumerate() {
+        for other in &programs[index + 1..] {
+            for subject in &program.published_subjects {
+                for other_subject in &other.published_subjects {
+                    if subjects_overlap(subject, other_subject) {
+                        conflicts.push(format!(
+                            "Programs `{}` and `{}` publish to overlapping subjects `{}` and `{}`",
+                            program.name, other.name, subject, other_subject
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    conflicts
+}
+
+/// Write the root kustomization, the shared NATS and each program's kustomization
+///
+/// `tera` holds the `kubernetes/cluster/` templates. Programs are
+/// expected to be generated into their directories already. An external NATS
+/// is only referenced, so `nats/` is not written.
+pub fn generate_cluster_layout(
+    programs: &[ClusterProgram],
+    output_dir: &Path,
+    tera: &Tera,
+    nats: &NatsSettings,
+    sink: &mut dyn OutputSink,
+) -> Result<()> {
+    let mut context = tera::Context::new();
+    context.insert("programs", programs);
+    context.insert("nats", nats);
+
+    let mut render = |template: &str, context: &tera::Context, path: &Path| -> Result<()> {
+        let rendered = tera
+            .render(template, context)
+            .with_context(|| message!("Failed to render {}", template))?;
+        sink.write(path, rendered.as_bytes())
+    };
+
+    // Programs may share a namespace, so namespaces are created once at the root
+    let namespaces: BTreeSet<&str> = programs.iter().map(|program| program.namespace.as_str()).collect();
+    context.insert("namespaces", &namespaces);
+
+    render("kubernetes/cluster/kustomization.yaml.tera", &context, &output_dir.join("kustomization.yaml"))?;
+    render("kubernetes/cluster/namespaces.yaml.tera", &context, &output_dir.join("namespaces.yaml"))?;
+    if !nats.external {
+        render("kubernetes/cluster/nats.yaml.tera", &context, &output_dir.join("nats/nats.yaml"))?;
+        render("kubernetes/cluster/nats-kustomization.yaml.tera", &context, &output_dir.join("nats/kustomization.yaml"))?;
+    }
+
+    for program in programs {
+        let program_dir = output_dir.join(&program.dir);
+        let mut program_context = context.clone();
+        program_context.insert("program", program);
+        render("kubernetes/cluster/program-kustomization.yaml.tera", &program_context, &program_dir.join("kustomization.yaml"))?;
+    }
+
+    Ok(())
+}
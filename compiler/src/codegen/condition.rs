@@ -0,0 +1,246 @@
+//! Translation of agent `when` conditions
+//!
+//! A `when:` expression is compiled into the language of the agent rather
+//! than interpreted at runtime. Rust agents evaluate it with the helpers of
+//! `kumeo_runtime::condition` and Python agents with the `_field`, `_equals`
+//! and `_compare` helpers of their template, so both read missing fields as
+//! null and treat comparisons between different types as false. Field access
+//! is null-safe either way, so `?.` only matters to type checking, and `??`
+//! falls back on its default with `coalesce`/`_coalesce`.
+//!
+//! When the agent consuming the messages declares their `output_schema`, the
+//! fields the condition reads are resolved against it and their declared
+//! types are documented in the generated code.
+//!
+//! `assert:` invariants are translated the same way. Agents check them on
+//! every message they would process, and publish the messages breaking one
+//! to their dead-letter subject, or to the audit subject of the workflow,
+//! along with the failed expression instead of processing them.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::ast::{Agent, Argument, CompareOp, Expr, FallbackConfig, Value, Workflow, FALLBACK_OPTION, WHEN_OPTION};
+use crate::message;
+
+/// Prefix of the subject the violations of a workflow's invariants are
+/// published to when the agent has no dead-letter subject
+pub const AUDIT_SUBJECT_PREFIX: &str = "kumeo.audit";
+
+/// `when` condition of an agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WhenSettings {
+    /// The condition as written in the DSL
+    pub source: String,
+    /// Rust expression over `data: &serde_json::Value`
+    pub rust: String,
+    /// Python expression over the decoded payload `data`
+    pub python: String,
+    /// Declared types of the fields the condition reads, by dotted path
+    pub fields: BTreeMap<String, String>,
+}
+
+impl WhenSettings {
+    /// Translate the condition of an agent that declares `when`
+    ///
+    /// Fails when the condition reads a field the schema of the agent's
+    /// input messages does not declare.
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Result<Option<Self>> {
+        let Some(expr) = agent.config.iter().find_map(|arg| match arg {
+            Argument::Named(name, Value::Condition(expr), _) if name == WHEN_OPTION => Some(expr),
+            _ => None,
+        }) else {
+            return Ok(None);
+        };
+
+        // The agent's own input schema wins over the one of the step producing its input
+        let mut fields = BTreeMap::new();
+        let mut schemas = workflow.message_schemas();
+        let input = workflow
+            .agents
+            .iter()
+            .zip(workflow.deployed_topics())
+            .find(|(other, _)| other.id == agent.id)
+            .and_then(|(_, topics)| topics.input);
+        let schema = match agent.input_schema().map_err(|e| anyhow!(message!("Invalid input schema: {}", e)))? {
+            Some(schema) => Some(schema),
+            None => input.and_then(|topic| schemas.remove(&topic)),
+        };
+        if let Some(schema) = schema {
+            for (path, optional) in expr.fields() {
+                let field_type = schema
+                    .field_type(path, optional)
+                    .map_err(|e| anyhow!(message!("Invalid when condition `{}`: {}", expr, e)))?;
+                if let Some(field_type) = field_type {
+                    fields.insert(path.join("."), field_type.to_string());
+                }
+            }
+        }
+
+        Ok(Some(Self {
+            source: expr.to_string(),
+            rust: rust_condition(expr),
+            python: python_condition(expr),
+            fields,
+        }))
+    }
+}
+
+/// `assert` invariants of an agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AssertSettings {
+    /// The invariants, in declaration order
+    pub checks: Vec<Assertion>,
+    /// Subject the messages breaking an invariant are published to
+    pub subject: String,
+}
+
+/// An `assert` invariant of an agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Assertion {
+    /// The invariant as written in the DSL
+    pub source: String,
+    /// Rust expression over `data: &serde_json::Value`
+    pub rust: String,
+    /// Python expression over the decoded payload `data`
+    pub python: String,
+    /// The invariant as a Rust string literal
+    pub rust_source: String,
+    /// The invariant as a Python string literal
+    pub python_source: String,
+}
+
+impl AssertSettings {
+    /// Translate the invariants of an agent that declares `assert`
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Result<Option<Self>> {
+        let assertions = agent.assertions();
+        if assertions.is_empty() {
+            return Ok(None);
+        }
+
+        let fallback = agent
+            .config_value(FALLBACK_OPTION)
+            .map(FallbackConfig::from_value)
+            .transpose()
+            .map_err(|e| anyhow!(message!("Invalid fallback: {}", e)))?;
+        let subject = match fallback {
+            Some(FallbackConfig::DeadLetter { subject }) => subject,
+            _ => audit_subject(workflow),
+        };
+        let checks = assertions
+            .into_iter()
+            .map(|expr| Assertion {
+                source: expr.to_string(),
+                rust: rust_condition(expr),
+                python: python_condition(expr),
+                rust_source: format!("{:?}", expr.to_string()),
+                python_source: python_string(&expr.to_string()),
+            })
+            .collect();
+        Ok(Some(Self { checks, subject }))
+    }
+}
+
+/// Subject the violations of a workflow's invariants are audited on
+pub fn audit_subject(workflow: &Workflow) -> String {
+    format!("{}.{}", AUDIT_SUBJECT_PREFIX, workflow.name.to_lowercase())
+}
+
+/// Path of a field below the payload; `data` itself is the payload
+fn field_path(path: &[String]) -> &[String] {
+    match path.split_first() {
+        Some((root, rest)) if root == "data" => rest,
+        _ => path,
+    }
+}
+
+/// Whether an operand has to be parenthesized inside a negation or a conjunction
+fn is_compound(expr: &Expr) -> bool {
+    matches!(expr, Expr::And(..) | Expr::Or(..))
+}
+
+/// Rust `bool` expression for a condition
+pub(crate) fn rust_condition(expr: &Expr) -> String {
+    let operand = |expr: &Expr| match is_compound(expr) {
+        true => format!("({})", rust_condition(expr)),
+        false => rust_condition(expr),
+    };
+    match expr {
+        Expr::Field(_) | Expr::OptionalField(..) | Expr::Default(..) => format!("truthy({})", rust_value(expr)),
+        Expr::Literal(Value::Boolean(value)) => value.to_string(),
+        Expr::Literal(_) => format!("truthy({})", rust_value(expr)),
+        Expr::Not(inner) => format!("!{}", operand(inner)),
+        Expr::And(left, right) => format!("{} && {}", operand(left), operand(right)),
+        Expr::Or(left, right) => format!("{} || {}", operand(left), operand(right)),
+        Expr::Compare(left, op, right) => {
+            let (left, right) = (rust_value(left), rust_value(right));
+            let ordering = match op {
+                CompareOp::Eq => return format!("equals({}, {})", left, right),
+                CompareOp::Ne => return format!("!equals({}, {})", left, right),
+                CompareOp::Lt => "is_lt",
+                CompareOp::Le => "is_le",
+                CompareOp::Gt => "is_gt",
+                CompareOp::Ge => "is_ge",
+            };
+            format!("compare({}, {}).is_some_and(std::cmp::Ordering::{})", left, right, ordering)
+        }
+    }
+}
+
+/// Rust `&Value` expression for an operand
+pub(crate) fn rust_value(expr: &Expr) -> String {
+    match expr {
+        Expr::Field(path) | Expr::OptionalField(path, _) => {
+            let keys: Vec<String> = field_path(path).iter().map(|key| format!("{:?}", key)).collect();
+            format!("field(data, &[{}])", keys.join(", "))
+        }
+        Expr::Default(value, default) => format!("coalesce({}, {})", rust_value(value), rust_value(default)),
+        Expr::Literal(Value::String(value)) => format!("&json!({:?})", value),
+        Expr::Literal(Value::Number(value)) => format!("&json!({})", value),
+        Expr::Literal(Value::Boolean(value)) => format!("&Value::Bool({})", value),
+        Expr::Literal(_) => "&Value::Null".to_string(),
+        _ => format!("&Value::Bool({})", rust_condition(expr)),
+    }
+}
+
+/// Python expression for a condition or an operand
+fn python_condition(expr: &Expr) -> String {
+    let operand = |expr: &Expr| match is_compound(expr) {
+        true => format!("({})", python_condition(expr)),
+        false => python_condition(expr),
+    };
+    match expr {
+        Expr::Field(path) | Expr::OptionalField(path, _) => {
+            let keys: Vec<String> = field_path(path).iter().map(|key| python_string(key)).collect();
+            match keys.as_slice() {
+                [key] => format!("_field(data, ({},))", key),
+                _ => format!("_field(data, ({}))", keys.join(", ")),
+            }
+        }
+        Expr::Literal(Value::String(value)) => python_string(value),
+        Expr::Literal(Value::Number(value)) => value.to_string(),
+        Expr::Literal(Value::Boolean(true)) => "True".to_string(),
+        Expr::Literal(Value::Boolean(false)) => "False".to_string(),
+        Expr::Literal(_) => "None".to_string(),
+        Expr::Default(value, default) => {
+            format!("_coalesce({}, {})", python_condition(value), python_condition(default))
+        }
+        Expr::Not(inner) => format!("not {}", operand(inner)),
+        Expr::And(left, right) => format!("{} and {}", operand(left), operand(right)),
+        Expr::Or(left, right) => format!("{} or {}", operand(left), operand(right)),
+        Expr::Compare(left, op, right) => {
+            let (left, right) = (operand(left), operand(right));
+            match op {
+                CompareOp::Eq => format!("_equals({}, {})", left, right),
+                CompareOp::Ne => format!("not _equals({}, {})", left, right),
+                _ => format!("_compare({}, \"{}\", {})", left, op.as_str(), right),
+            }
+        }
+    }
+}
+
+/// Python string literal; JSON escapes are valid Python escapes
+fn python_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
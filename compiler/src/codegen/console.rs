@@ -0,0 +1,208 @@
+//! Read-only web console of the generated workflows
+//!
+//! With `generate --console` (or `console = true` in `kumeo.toml`) the
+//! generation gets a `console` directory next to the workflows: a small Rust
+//! backend serving a static page for operators who don't live in terminals.
+//! It shows the topology of every workflow as compiled, the status of each
+//! agent from the registrations the agents publish when they start, the
+//! latest messages of the dead-letter subjects of agents with
+//! `fallback: { action: "dead_letter", ... }`, and what the resource cache of
+//! each replica holds, as the runtime reports it. The backend only follows
+//! NATS subjects, so nothing can be changed through the console. The app
+//! comes with its Dockerfile and Kubernetes manifests.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+use tera::Tera;
+
+use super::drift::workflow_hash;
+use super::kubernetes::{agent_image, workflow_registry, workflow_tag, ManifestMetadata};
+use super::nats::ExternalNats;
+use super::resilience::FallbackSettings;
+use super::sink::OutputSink;
+use crate::ast::{Source, Target, Workflow};
+use crate::message;
+
+/// Templates of the console, by their name prefix
+pub const CONSOLE_TEMPLATES: &str = "console/";
+
+/// Directory of the output the console is generated into
+pub const CONSOLE_DIR: &str = "console";
+
+/// Name of the Deployment, Service and image of the console
+pub const CONSOLE_NAME: &str = "kumeo-console";
+
+/// Container port of the console
+pub const CONSOLE_PORT: u16 = 8080;
+
+/// Dead-letter messages the console keeps, the latest first
+pub const DEAD_LETTER_HISTORY: usize = 50;
+
+/// A source or target of a workflow, as shown by the console
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConsoleEndpoint {
+    /// Broker or kind of endpoint, such as `NATS` or `HTTP`
+    pub kind: &'static str,
+    /// Topic, subject, path or directory
+    pub topic: String,
+}
+
+/// An agent of a workflow, as shown by the console
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConsoleAgent {
+    /// Agent ID
+    pub id: String,
+    /// Agent type, such as `llm`
+    pub agent_type: String,
+    /// Subject the agent consumes, if any
+    pub input: Option<String>,
+    /// Subject the agent publishes on, if any
+    pub output: Option<String>,
+    /// Subject the agent's failed messages go to, if it has one
+    pub dead_letter: Option<String>,
+}
+
+/// A workflow, as shown by the console
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConsoleWorkflow {
+    /// Workflow name
+    pub name: String,
+    /// Hash of the definition the agents are expected to register with
+    pub hash: String,
+    /// Source of the workflow, if any
+    pub source: Option<ConsoleEndpoint>,
+    /// Target of the workflow, if any
+    pub target: Option<ConsoleEndpoint>,
+    /// Agents, in pipeline order
+    pub agents: Vec<ConsoleAgent>,
+    /// Subject the agents register on when they start
+    pub registration_subject: String,
+    /// Subject the runtimes report their resource cache on
+    pub cache_subject: String,
+}
+
+/// Console of a generation, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConsoleSettings {
+    /// Name of the Deployment and Service of the console
+    pub name: String,
+    /// Image of the console
+    pub image: String,
+    /// NATS the agents publish on
+    pub nats_url: String,
+    /// Container port of the console
+    pub port: u16,
+    /// Dead-letter messages kept
+    pub dead_letter_history: usize,
+    /// Workflows shown, in generation order
+    pub workflows: Vec<ConsoleWorkflow>,
+    /// The workflows as JSON, embedded into the backend
+    pub topology: String,
+}
+
+impl ConsoleSettings {
+    /// Compute the console of some workflows, which follows them on `nats_url`
+    ///
+    /// The image is pushed to the registry and with the tag of the first workflow.
+    pub fn for_workflows(workflows: &[&Workflow], nats_url: &str) -> Result<Self> {
+        let shown = workflows.iter().map(|workflow| console_workflow(workflow)).collect::<Result<Vec<_>>>()?;
+        let (registry, tag) = workflows
+            .first()
+            .map_or((super::kubernetes::DEFAULT_REGISTRY, super::kubernetes::DEFAULT_TAG), |workflow| {
+                (workflow_registry(workflow), workflow_tag(workflow))
+            });
+        Ok(Self {
+            name: CONSOLE_NAME.to_string(),
+            image: agent_image(CONSOLE_NAME, registry, tag),
+            nats_url: nats_url.to_string(),
+            port: CONSOLE_PORT,
+            dead_letter_history: DEAD_LETTER_HISTORY,
+            topology: serde_json::to_string_pretty(&shown)?,
+            workflows: shown,
+        })
+    }
+}
+
+/// What the console shows of a workflow
+fn console_workflow(workflow: &Workflow) -> Result<ConsoleWorkflow> {
+    let agents = workflow
+        .agents
+        .iter()
+        .zip(workflow.deployed_topics())
+        .enumerate()
+        .map(|(index, (agent, topics))| {
+            let fallback = FallbackSettings::for_agent(agent)
+                .with_context(|| {
+                    message!("Invalid fallback of agent {} of {}", agent.id.as_deref().unwrap_or("<unnamed>"), workflow.name)
+                })?;
+            Ok(ConsoleAgent {
+                id: agent.id.clone().unwrap_or_else(|| format!("agent{}", index + 1)),
+                agent_type: agent.agent_type.to_string(),
+                input: topics.input,
+                output: topics.output,
+                dead_letter: fallback.subject.filter(|_| fallback.action == "dead_letter"),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(ConsoleWorkflow {
+        name: workflow.name.clone(),
+        hash: workflow_hash(workflow)?,
+        source: workflow.source.as_ref().map(|source| ConsoleEndpoint {
+            kind: match source {
+                Source::NATS(..) => "NATS",
+                Source::Kafka(..) => "Kafka",
+                Source::MQTT(..) => "MQTT",
+                Source::HTTP(..) => "HTTP",
+                Source::File(..) => "File",
+            },
+            topic: source.topic().to_string(),
+        }),
+        target: workflow.target.as_ref().map(|target| ConsoleEndpoint {
+            kind: match target {
+                Target::NATS(..) => "NATS",
+                Target::Kafka(..) => "Kafka",
+                Target::MQTT(..) => "MQTT",
+                Target::File(..) => "File",
+            },
+            topic: target.topic().to_string(),
+        }),
+        agents,
+        registration_subject: format!("kumeo.control.{}.registered", workflow.name),
+        cache_subject: format!("kumeo.control.{}.cache", workflow.name),
+    })
+}
+
+/// Generate the console of some workflows into `console_dir`
+///
+/// Its manifests go to the namespace of the first workflow, and it reads
+/// the credentials of an external NATS from its Secret.
+pub fn generate_console(
+    workflows: &[&Workflow],
+    console_dir: &Path,
+    settings: &ConsoleSettings,
+    external_nats: Option<&ExternalNats>,
+    tera: &Tera,
+    sink: &mut dyn OutputSink,
+) -> Result<()> {
+    let mut context = tera::Context::new();
+    context.insert("console", settings);
+    context.insert("metadata", &workflows.first().map(|workflow| ManifestMetadata::for_workflow(workflow)));
+    context.insert("nats", &external_nats);
+
+    let mut templates: Vec<&str> = tera.get_template_names().filter(|name| name.starts_with(CONSOLE_TEMPLATES)).collect();
+    templates.sort_unstable();
+    for template in templates {
+        let relative = template[CONSOLE_TEMPLATES.len()..].trim_end_matches(".tera");
+        let output_path = console_dir.join(relative);
+        if let Some(parent) = output_path.parent() {
+            sink.create_dir(parent)
+                .with_context(|| message!("Failed to create directory: {}", parent.display()))?;
+        }
+        let rendered = tera.render(template, &context)
+            .with_context(|| message!("Failed to render the console template {}", template))?;
+        sink.write(&output_path, rendered.as_bytes())
+            .with_context(|| message!("Failed to write {}", output_path.display()))?;
+    }
+    Ok(())
+}
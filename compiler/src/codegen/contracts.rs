@@ -0,0 +1,87 @@
+//! Message contracts of agents
+//!
+//! The schema of the messages an agent consumes comes from its own
+//! `input_schema:`, from the `schemas:` block of the program for its input
+//! topic, or from the `output_schema:` of the agent producing that topic, in
+//! that order. [`attach`] resolves it into the agent's `input_schema` option
+//! and generated agents check every incoming message against it before
+//! processing, handing messages that break the contract to their fallback.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::ast::{Agent, Argument, Schema, Span, Value, Workflow, INPUT_SCHEMA_OPTION};
+use crate::message;
+
+/// Resolve the input schema of every agent of a workflow into its `input_schema` option
+///
+/// `declared` holds the schemas of the program's `schemas:` block, by topic.
+pub fn attach(workflow: &Workflow, declared: &HashMap<String, Schema>) -> Workflow {
+    let mut attached = workflow.clone();
+    let produced = workflow.message_schemas();
+    for (agent, topics) in attached.agents.iter_mut().zip(workflow.deployed_topics()) {
+        if agent.config_value(INPUT_SCHEMA_OPTION).is_some() {
+            continue;
+        }
+        let Some(schema) = topics
+            .input
+            .and_then(|topic| declared.get(&topic).or_else(|| produced.get(&topic)))
+        else {
+            continue;
+        };
+        agent
+            .config
+            .push(Argument::Named(INPUT_SCHEMA_OPTION.to_string(), schema_value(schema), Span::default()));
+    }
+    attached
+}
+
+/// A schema as the object of an `input_schema:` option
+fn schema_value(schema: &Schema) -> Value {
+    Value::Object(
+        schema
+            .fields
+            .iter()
+            .map(|(name, field_type)| (name.clone(), Value::String(field_type.clone())))
+            .collect(),
+    )
+}
+
+/// Validation of the messages an agent consumes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationSettings {
+    /// Field types by name; a trailing `?` marks an optional field
+    pub fields: BTreeMap<String, String>,
+    /// The fields as a Rust `&[(&str, &str)]` literal
+    pub rust: String,
+    /// The fields as a Python dict literal
+    pub python: String,
+}
+
+impl ValidationSettings {
+    /// Compute the validation of an agent whose input schema is known
+    pub fn for_agent(agent: &Agent) -> Result<Option<Self>> {
+        let Some(schema) = agent.input_schema().map_err(|e| anyhow!(message!("Invalid input schema: {}", e)))? else {
+            return Ok(None);
+        };
+        let fields: BTreeMap<String, String> = schema.fields.into_iter().collect();
+
+        let rust = fields
+            .iter()
+            .map(|(name, field_type)| format!("({:?}, {:?})", name, field_type))
+            .collect::<Vec<_>>()
+            .join(", ");
+        // JSON strings are valid Python strings
+        let python = fields
+            .iter()
+            .map(|(name, field_type)| Ok(format!("{}: {}", serde_json::to_string(name)?, serde_json::to_string(field_type)?)))
+            .collect::<Result<Vec<_>>>()?
+            .join(", ");
+        Ok(Some(Self {
+            rust: format!("&[{}]", rust),
+            python: format!("{{{}}}", python),
+            fields,
+        }))
+    }
+}
@@ -0,0 +1,91 @@
+//! Agents generated from the user's templates
+//!
+//! `Custom("fraud-scorer", language: "python", threshold: 0.8)` is generated
+//! from the templates under `agents/fraud-scorer/` of the template layers,
+//! the project's or the user's: each `.tera` file there is rendered with the
+//! context of built-in agents into the agent's directory, without the
+//! extension. The agent's options, but `language`, reach the templates under
+//! `custom.options`.
+//!
+//! The rest of the agent is generated as for built-in agents of its
+//! language: the Dockerfile, unless the templates bring one, the pinned
+//! requirements of Python agents, the README and the manifests of the
+//! platform. Custom agents get no test scaffold, which would test the
+//! internals of the built-in templates.
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tera::Tera;
+
+use crate::ast::{Agent, AgentType, Argument, CustomAgentConfig, Value, LANGUAGE_OPTION};
+use crate::message;
+use super::sink::OutputSink;
+use super::template_processor::render_templates;
+
+/// Templates of a custom agent, ready to be injected into them
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CustomSettings {
+    /// Name of the directory of the templates, below `agents/`
+    pub kind: String,
+    /// Language of the templates, `rust` or `python`
+    pub language: String,
+    /// The agent's options but `language`, by name
+    pub options: BTreeMap<String, serde_json::Value>,
+}
+
+impl CustomSettings {
+    /// Compute the templates of a custom agent
+    pub fn for_agent(agent: &Agent) -> Result<Option<Self>> {
+        if agent.agent_type != AgentType::Custom {
+            return Ok(None);
+        }
+        let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+        let config = CustomAgentConfig::from_agent(agent).map_err(|e| anyhow!(message!("Invalid custom agent {}: {}", agent_id, e)))?;
+        let options = agent
+            .config
+            .iter()
+            .filter_map(|arg| match arg {
+                Argument::Named(name, value, _) if name != LANGUAGE_OPTION => Some((name.clone(), Value::to_json(value))),
+                _ => None,
+            })
+            .collect();
+        Ok(Some(Self {
+            kind: config.kind,
+            language: config.language,
+            options,
+        }))
+    }
+
+    /// Prefix of the names of the templates, such as `agents/fraud-scorer/`
+    pub fn template_prefix(&self) -> String {
+        format!("agents/{}/", self.kind)
+    }
+}
+
+/// Render the templates of a custom agent into its directory
+///
+/// Returns the paths written, relative to `agent_dir`, in name order.
+pub fn generate_custom_agent(
+    agent_id: &str,
+    custom: &CustomSettings,
+    agent_dir: &Path,
+    context: &tera::Context,
+    tera: &Tera,
+    sink: &mut dyn OutputSink,
+) -> Result<Vec<String>> {
+    let prefix = custom.template_prefix();
+    if !tera.get_template_names().any(|name| name.starts_with(&prefix)) {
+        return Err(anyhow!(message!(
+            "Custom agent {} has no templates: create {} in the project's templates directory, or in the user's, with a .tera file per file of the agent such as {}{}",
+            agent_id,
+            prefix,
+            prefix,
+            if custom.language == "python" { "src/agent.py.tera" } else { "src/main.rs.tera" }
+        )));
+    }
+
+    render_templates(tera, &prefix, agent_dir, context, &[], sink)
+        .with_context(|| message!("Failed to generate custom agent {}", agent_id))
+}
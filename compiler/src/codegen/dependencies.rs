@@ -0,0 +1,252 @@
+//! Pinned dependencies of the generated agents
+//!
+//! Every agent gets a Dockerfile of its language, and the Python agents a
+//! `requirements.txt` pinning exactly the packages their code imports, so
+//! rebuilding an image gives the same agent. What goes in depends on the
+//! agent:
+//!
+//! - every Python agent needs `kumeo-runtime`, released with the compiler,
+//!   and `pydantic`;
+//! - MLModel agents also need `numpy`, `tensorflow` and `aiohttp`, and the
+//!   runtime of their model's format: `onnxruntime` for `.onnx` files,
+//!   `scikit-learn` and `joblib` for pickled models, `torch` for `.pt`;
+//! - agents with a metrics port, such as canary ones, need `aiohttp` to
+//!   serve their `/metrics`;
+//! - BayesianNetwork agents also need `pgmpy`, which reads and queries
+//!   their network;
+//! - agents reading a feature store need `feast`;
+//! - agents of a workflow reading or writing Kafka need `aiokafka`, which
+//!   consumes and produces its topics.
+//!
+//! The `pyproject.toml` of the agent lists the same pins. The `Cargo.toml`
+//! of Rust agents lists the crates their code uses in the same way: those of
+//! the runtime and the shared modules for every agent, and those of its
+//! type's own modules, such as `axum` for the endpoints of RuleEngine and
+//! HumanReview agents and the `/metrics` of agents with a metrics port, plus
+//! the crates their unit tests need. Rust agents of a workflow reading or
+//! writing Kafka enable the `kafka` feature of the runtime, whose client
+//! consumes and produces its topics.
+//!
+//! The `kumeo-runtime` crate isn't published, so its sources, embedded in
+//! the compiler when it is built, are vendored into `kumeo-runtime/` of every
+//! Rust agent, which depends on it by path. The agent's directory is thus all
+//! its image needs to be built.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+
+use super::agent::{agent_language, metrics_port};
+use super::feature_store::FeatureStoreSettings;
+use super::kubernetes::BrokerSettings;
+use super::sink::OutputSink;
+use crate::ast::{Agent, AgentType, Value, Workflow};
+use crate::message;
+
+/// Name of the requirements file of Python agents
+pub const REQUIREMENTS_FILE: &str = "requirements.txt";
+
+/// Directory of a Rust agent the `kumeo-runtime` crate is vendored into
+pub const RUNTIME_DIR: &str = "kumeo-runtime";
+
+/// The sources of the `kumeo-runtime` crate, by their path below `runtime`
+pub const RUNTIME_SOURCES: &[(&str, &str)] = include!(concat!(env!("OUT_DIR"), "/runtime_sources.rs"));
+
+/// Pinned Python packages, as name and version
+const KUMEO_RUNTIME: (&str, &str) = ("kumeo-runtime", env!("CARGO_PKG_VERSION"));
+const PYDANTIC: (&str, &str) = ("pydantic", "2.8.2");
+const AIOHTTP: (&str, &str) = ("aiohttp", "3.9.5");
+const NUMPY: (&str, &str) = ("numpy", "1.26.4");
+const TENSORFLOW: (&str, &str) = ("tensorflow", "2.16.2");
+const ONNXRUNTIME: (&str, &str) = ("onnxruntime", "1.18.1");
+const SCIKIT_LEARN: (&str, &str) = ("scikit-learn", "1.5.1");
+const JOBLIB: (&str, &str) = ("joblib", "1.4.2");
+const TORCH: (&str, &str) = ("torch", "2.3.1");
+const FEAST: (&str, &str) = ("feast[redis]", "0.40.1");
+const PGMPY: (&str, &str) = ("pgmpy", "0.1.26");
+const AIOKAFKA: (&str, &str) = ("aiokafka", "0.11.0");
+
+/// Crates of Rust agents, as name and the value of their `Cargo.toml` line
+const KUMEO_RUNTIME_CRATE: (&str, &str) = ("kumeo-runtime", r#"{ path = "kumeo-runtime" }"#);
+const KUMEO_RUNTIME_KAFKA_CRATE: (&str, &str) = ("kumeo-runtime", r#"{ path = "kumeo-runtime", features = ["kafka"] }"#);
+const ANYHOW: (&str, &str) = ("anyhow", r#""1.0""#);
+const ASYNC_TRAIT: (&str, &str) = ("async-trait", r#""0.1""#);
+const AXUM: (&str, &str) = ("axum", r#""0.6""#);
+const BASE64: (&str, &str) = ("base64", r#""0.22""#);
+const CHRONO: (&str, &str) = ("chrono", r#"{ version = "0.4", features = ["serde"] }"#);
+const ED25519_DALEK: (&str, &str) = ("ed25519-dalek", r#""2""#);
+const FUTURES_UTIL: (&str, &str) = ("futures-util", r#"{ version = "0.3", features = ["std"] }"#);
+const HEX: (&str, &str) = ("hex", r#""0.4""#);
+const JSONWEBTOKEN: (&str, &str) = ("jsonwebtoken", r#""9""#);
+const MOCKITO: (&str, &str) = ("mockito", r#""1""#);
+const REGEX: (&str, &str) = ("regex", r#""1""#);
+const REQWEST: (&str, &str) = ("reqwest", r#"{ version = "0.11", features = ["json"] }"#);
+const REQWEST_STREAM: (&str, &str) = ("reqwest", r#"{ version = "0.11", features = ["json", "stream"] }"#);
+const SERDE: (&str, &str) = ("serde", r#"{ version = "1.0", features = ["derive"] }"#);
+const SERDE_JSON: (&str, &str) = ("serde_json", r#""1.0""#);
+const SERDE_YAML: (&str, &str) = ("serde_yaml", r#""0.9""#);
+const SHA2: (&str, &str) = ("sha2", r#""0.10""#);
+const TEMPFILE: (&str, &str) = ("tempfile", r#""3""#);
+const THISERROR: (&str, &str) = ("thiserror", r#""1.0""#);
+const TOKIO: (&str, &str) = ("tokio", r#"{ version = "1.0", features = ["full"] }"#);
+const TOKIO_TUNGSTENITE: (&str, &str) = ("tokio-tungstenite", r#"{ version = "0.20", features = ["native-tls"] }"#);
+const TRACING: (&str, &str) = ("tracing", r#""0.1""#);
+const TRACING_SUBSCRIBER: (&str, &str) = ("tracing-subscriber", r#"{ version = "0.3", features = ["env-filter"] }"#);
+const URL: (&str, &str) = ("url", r#"{ version = "2.0", features = ["serde"] }"#);
+const UUID: (&str, &str) = ("uuid", r#"{ version = "1.0", features = ["v4"] }"#);
+const VALIDATOR: (&str, &str) = ("validator", r#"{ version = "0.16", features = ["derive"] }"#);
+
+/// Dependencies of a Python agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DependencySettings {
+    /// Pinned requirements, as `name==version`, sorted by name
+    pub requirements: Vec<String>,
+}
+
+impl DependencySettings {
+    /// Compute the dependencies of an agent, if it is generated in Python
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Result<Option<Self>> {
+        if agent_language(agent) != "python" {
+            return Ok(None);
+        }
+        let mut packages = vec![KUMEO_RUNTIME, PYDANTIC];
+        if agent.agent_type == AgentType::MLModel {
+            packages.extend([AIOHTTP, NUMPY, TENSORFLOW]);
+            packages.extend(model_runtime(workflow, agent));
+        }
+        if agent.agent_type == AgentType::BayesianNetwork {
+            packages.push(PGMPY);
+        }
+        if FeatureStoreSettings::for_agent(workflow, agent)?.is_some() {
+            packages.push(FEAST);
+        }
+        if metrics_port(workflow, agent)?.is_some() {
+            packages.push(AIOHTTP);
+        }
+        if BrokerSettings::for_agent(workflow, agent).kafka.is_some() {
+            packages.push(AIOKAFKA);
+        }
+        packages.sort();
+        packages.dedup();
+        Ok(Some(Self {
+            requirements: packages.iter().map(|(name, version)| format!("{}=={}", name, version)).collect(),
+        }))
+    }
+
+    /// Contents of the agent's `requirements.txt`
+    pub fn requirements_txt(&self) -> String {
+        let mut contents = String::from("# Generated by Kumeo, do not edit\n");
+        for requirement in &self.requirements {
+            contents.push_str(requirement);
+            contents.push('\n');
+        }
+        contents
+    }
+}
+
+/// Crates of a Rust agent, ready to be injected into its `Cargo.toml`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CrateSettings {
+    /// Lines of `[dependencies]`, as `name = value`, sorted by name
+    pub dependencies: Vec<String>,
+    /// Lines of `[dev-dependencies]`, the crates of the unit tests, sorted by name
+    pub dev_dependencies: Vec<String>,
+}
+
+impl CrateSettings {
+    /// Compute the crates of an agent, if it is generated in Rust
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Result<Option<Self>> {
+        if agent_language(agent) != "rust" {
+            return Ok(None);
+        }
+        let runtime = if BrokerSettings::for_agent(workflow, agent).kafka.is_some() {
+            KUMEO_RUNTIME_KAFKA_CRATE
+        } else {
+            KUMEO_RUNTIME_CRATE
+        };
+        let mut crates = vec![runtime, ANYHOW, SERDE, SERDE_JSON, TOKIO, TRACING, TRACING_SUBSCRIBER];
+        let mut dev_crates = Vec::new();
+        match agent.agent_type {
+            AgentType::LLM => {
+                crates.extend([ASYNC_TRAIT, AXUM, CHRONO, FUTURES_UTIL, JSONWEBTOKEN, REGEX, REQWEST_STREAM, THISERROR, TOKIO_TUNGSTENITE, URL]);
+                dev_crates.extend([MOCKITO, TEMPFILE]);
+            }
+            AgentType::HumanReview => {
+                crates.extend([AXUM, BASE64, CHRONO, ED25519_DALEK, HEX, JSONWEBTOKEN, REQWEST, SHA2, UUID]);
+                dev_crates.push(TEMPFILE);
+            }
+            AgentType::DataProcessor => {
+                crates.extend([CHRONO, THISERROR, VALIDATOR]);
+                dev_crates.push(TEMPFILE);
+            }
+            AgentType::DecisionMatrix => {
+                crates.push(REGEX);
+                dev_crates.push(TEMPFILE);
+            }
+            AgentType::Router => {
+                crates.extend([REGEX, SERDE_YAML, URL]);
+                dev_crates.push(TEMPFILE);
+            }
+            AgentType::RuleEngine => crates.push(AXUM),
+            _ => {}
+        }
+        if metrics_port(workflow, agent)?.is_some() {
+            crates.push(AXUM);
+        }
+        Ok(Some(Self {
+            dependencies: cargo_lines(crates),
+            dev_dependencies: cargo_lines(dev_crates),
+        }))
+    }
+}
+
+/// `Cargo.toml` lines of some crates, sorted by name
+fn cargo_lines(mut crates: Vec<(&str, &str)>) -> Vec<String> {
+    crates.sort();
+    crates.dedup();
+    crates.iter().map(|(name, value)| format!("{} = {}", name, value)).collect()
+}
+
+/// Packages loading the model of an MLModel agent, by the extension of its file
+fn model_runtime(workflow: &Workflow, agent: &Agent) -> Vec<(&'static str, &'static str)> {
+    let path = ["model_path", "model"].iter().find_map(|name| match agent.config_value(name)? {
+        Value::String(reference) | Value::Path(reference) => {
+            Some(workflow.context_model_path(reference).unwrap_or(reference).to_lowercase())
+        }
+        _ => None,
+    });
+    let extension = path.as_deref().and_then(|path| path.rsplit_once('.')).map(|(_, extension)| extension);
+    match extension {
+        Some("onnx") => vec![ONNXRUNTIME],
+        Some("pkl" | "pickle" | "joblib") => vec![SCIKIT_LEARN, JOBLIB],
+        Some("pt" | "pth") => vec![TORCH],
+        _ => vec![],
+    }
+}
+
+/// Generate `agent_dir/requirements.txt` with the pinned dependencies of a Python agent
+pub fn generate_requirements(workflow: &Workflow, agent: &Agent, agent_dir: &Path, sink: &mut dyn OutputSink) -> Result<()> {
+    let Some(dependencies) = DependencySettings::for_agent(workflow, agent)? else {
+        return Ok(());
+    };
+    let output_path = agent_dir.join(REQUIREMENTS_FILE);
+    sink.write(&output_path, dependencies.requirements_txt().as_bytes())
+        .with_context(|| message!("Failed to write {}", output_path.display()))
+}
+
+/// Vendor the `kumeo-runtime` crate into `agent_dir/kumeo-runtime/` of a Rust agent
+pub fn generate_runtime_crate(agent: &Agent, agent_dir: &Path, sink: &mut dyn OutputSink) -> Result<()> {
+    if agent_language(agent) != "rust" {
+        return Ok(());
+    }
+    let runtime_dir = agent_dir.join(RUNTIME_DIR);
+    for (path, contents) in RUNTIME_SOURCES {
+        let output_path = runtime_dir.join(path);
+        if let Some(parent) = output_path.parent() {
+            sink.create_dir(parent)?;
+        }
+        sink.write(&output_path, contents.as_bytes())
+            .with_context(|| message!("Failed to write {}", output_path.display()))?;
+    }
+    Ok(())
+}
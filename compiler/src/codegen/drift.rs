@@ -0,0 +1,27 @@
+//! Workflow hashes for config drift detection
+//!
+//! Every agent is generated with a hash of the workflow definition it was
+//! compiled from. Agents announce it when they start and the runtime, which
+//! gets the same hash through `KUMEO_WORKFLOW_HASH`, flags agents reporting
+//! another one, such as a pod left over from a previous deploy.
+
+use anyhow::{Context as _, Result};
+use sha2::{Digest, Sha256};
+
+use crate::ast::Workflow;
+use crate::message;
+
+/// Hash of a workflow definition
+///
+/// The workflow is hashed as canonical JSON with sorted keys, so the hash
+/// only changes with the definition and not with formatting or map order.
+/// Its `test` blocks are left out, since they don't change what is deployed.
+pub fn workflow_hash(workflow: &Workflow) -> Result<String> {
+    let deployed = Workflow {
+        tests: Vec::new(),
+        ..workflow.clone()
+    };
+    let definition = serde_json::to_value(&deployed)
+        .with_context(|| message!("Failed to serialize workflow {}", workflow.name))?;
+    Ok(hex::encode(Sha256::digest(definition.to_string().as_bytes())))
+}
@@ -0,0 +1,233 @@
+//! Encodings of the topics agents consume and produce
+//!
+//! Agents handle JSON whatever the `encoding` of their topics: the runtime
+//! next to each agent encodes the messages published on its output topic and
+//! decodes the ones it receives, reading the `Content-Type` header of each
+//! message to know its encoding. [`EncodingSettings`] tells the runtime the
+//! encoding of both topics through its environment.
+//!
+//! Protobuf and Avro messages follow the schema of their topic: its fields in
+//! name order become the fields of a message or record, numbered from 1 in
+//! Protobuf. Fields of type `object` or `array` travel as JSON text. The
+//! `.proto` and `.avsc` files generated under `schemas/` let clients outside
+//! the workflow read and write these topics.
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::ast::{Agent, Encoding, Schema, Workflow};
+use crate::message;
+use super::sink::OutputSink;
+
+/// Directory of the output the schemas of the encoded topics are written to
+pub const SCHEMAS_DIR: &str = "schemas";
+
+/// A topic the runtime encodes or decodes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EncodedTopic {
+    /// The topic, as the agent names it
+    pub topic: String,
+    /// Encoding of the messages of the topic
+    pub encoding: Encoding,
+    /// Content type the messages of the topic are published with
+    pub content_type: &'static str,
+    /// Field types by name as a JSON object, for the encodings that need them
+    pub schema: Option<String>,
+}
+
+/// Encodings of the topics of an agent that aren't JSON
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EncodingSettings {
+    /// The topic the agent consumes, unless it is JSON
+    pub input: Option<EncodedTopic>,
+    /// The topic the agent publishes on, unless it is JSON
+    pub output: Option<EncodedTopic>,
+}
+
+impl EncodingSettings {
+    /// Compute the encodings of an agent, if either of its topics isn't JSON
+    ///
+    /// The input schema is the one [`super::contracts::attach`] resolved.
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Result<Option<Self>> {
+        let encodings = workflow.agent_encodings().map_err(|e| anyhow!(message!("Invalid encoding: {}", e)))?;
+        let Some((topics, encodings)) = workflow
+            .agents
+            .iter()
+            .zip(workflow.deployed_topics())
+            .zip(encodings)
+            .find(|((other, _), _)| other.id == agent.id)
+            .map(|((_, topics), encodings)| (topics, encodings))
+        else {
+            return Ok(None);
+        };
+
+        let input_schema = agent.input_schema().map_err(|e| anyhow!(message!("Invalid input schema: {}", e)))?;
+        let output_schema = agent
+            .output_schema()
+            .map_err(|e| anyhow!(message!("Invalid output schema: {}", e)))?
+            .map(|(schema, _)| schema);
+        let input = topics
+            .input
+            .map(|topic| encoded_topic(topic, encodings.input, input_schema.as_ref()))
+            .transpose()?
+            .flatten();
+        let output = topics
+            .output
+            .map(|topic| encoded_topic(topic, encodings.output, output_schema.as_ref()))
+            .transpose()?
+            .flatten();
+        Ok((input.is_some() || output.is_some()).then_some(Self { input, output }))
+    }
+}
+
+/// A topic in an encoding other than JSON
+fn encoded_topic(topic: String, encoding: Encoding, schema: Option<&Schema>) -> Result<Option<EncodedTopic>> {
+    if encoding == Encoding::Json {
+        return Ok(None);
+    }
+    let schema = match schema {
+        Some(schema) if encoding.needs_schema() => Some(serde_json::to_string(&sorted_fields(schema))?),
+        None if encoding.needs_schema() => return Err(anyhow!(message!("Topic '{}' is encoded in {} but has no schema", topic, encoding))),
+        _ => None,
+    };
+    Ok(Some(EncodedTopic {
+        topic,
+        encoding,
+        content_type: encoding.content_type(),
+        schema,
+    }))
+}
+
+/// The fields of a schema in the order they are encoded in
+fn sorted_fields(schema: &Schema) -> BTreeMap<&str, &str> {
+    schema.fields.iter().map(|(name, field_type)| (name.as_str(), field_type.as_str())).collect()
+}
+
+/// The topics of a workflow encoded in Protobuf or Avro, with their encoding and schema
+pub fn schema_topics(workflow: &Workflow) -> Result<BTreeMap<String, (Encoding, Schema)>> {
+    let encodings = workflow.agent_encodings().map_err(|e| anyhow!(message!("Invalid encoding: {}", e)))?;
+    let mut topics = BTreeMap::new();
+    for ((agent, agent_topics), encodings) in workflow.agents.iter().zip(workflow.deployed_topics()).zip(encodings) {
+        if let (Some(topic), true) = (agent_topics.input, encodings.input.needs_schema()) {
+            if let Some(schema) = agent.input_schema().map_err(|e| anyhow!(message!("Invalid input schema: {}", e)))? {
+                topics.entry(topic).or_insert((encodings.input, schema));
+            }
+        }
+        if let (Some(topic), true) = (agent_topics.output, encodings.output.needs_schema()) {
+            if let Some((schema, _)) = agent.output_schema().map_err(|e| anyhow!(message!("Invalid output schema: {}", e)))? {
+                topics.insert(topic, (encodings.output, schema));
+            }
+        }
+    }
+    Ok(topics)
+}
+
+/// Name of the message or record of a topic, such as `OrdersScored` for `orders.scored`
+pub fn message_name(topic: &str) -> String {
+    let name: String = topic
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or_else(String::new, |first| first.to_ascii_uppercase().to_string() + chars.as_str())
+        })
+        .collect();
+    match name.chars().next() {
+        Some(first) if first.is_ascii_alphabetic() => name,
+        _ => format!("Topic{}", name),
+    }
+}
+
+/// Package or namespace of the messages of a workflow
+fn package(workflow: &Workflow) -> String {
+    format!("kumeo.{}", workflow.name.to_lowercase())
+}
+
+/// The Protobuf definition of the messages of a topic
+pub fn proto_file(workflow: &Workflow, topic: &str, schema: &Schema) -> String {
+    let mut proto = format!(
+        "// Messages of topic '{}' in workflow {}, generated by Kumeo\nsyntax = \"proto3\";\n\npackage {};\n\nmessage {} {{\n",
+        topic,
+        workflow.name,
+        package(workflow),
+        message_name(topic)
+    );
+    for (number, (name, field_type)) in sorted_fields(schema).into_iter().enumerate() {
+        let (base, optional) = split_optional(field_type);
+        let proto_type = match base {
+            "number" => "double",
+            "integer" => "sint64",
+            "boolean" => "bool",
+            // Strings, and objects and arrays as JSON text
+            _ => "string",
+        };
+        let label = if optional { "optional " } else { "" };
+        proto.push_str(&format!("  {}{} {} = {};\n", label, proto_type, name, number + 1));
+    }
+    proto.push_str("}\n");
+    proto
+}
+
+/// The Avro record schema of the messages of a topic
+pub fn avro_schema(workflow: &Workflow, topic: &str, schema: &Schema) -> serde_json::Value {
+    let fields: Vec<serde_json::Value> = sorted_fields(schema)
+        .into_iter()
+        .map(|(name, field_type)| {
+            let (base, optional) = split_optional(field_type);
+            let avro_type = match base {
+                "number" => "double",
+                "integer" => "long",
+                "boolean" => "boolean",
+                // Strings, and objects and arrays as JSON text
+                _ => "string",
+            };
+            if optional {
+                json!({ "name": name, "type": ["null", avro_type], "default": null })
+            } else {
+                json!({ "name": name, "type": avro_type })
+            }
+        })
+        .collect();
+    json!({
+        "type": "record",
+        "name": message_name(topic),
+        "namespace": package(workflow),
+        "doc": format!("Messages of topic '{}' in workflow {}", topic, workflow.name),
+        "fields": fields,
+    })
+}
+
+/// A field type without its optional marker, and whether it had one
+fn split_optional(field_type: &str) -> (&str, bool) {
+    match field_type.strip_suffix('?') {
+        Some(base) => (base, true),
+        None => (field_type, false),
+    }
+}
+
+/// Generate `output_dir/schemas/` with the `.proto` or `.avsc` of every Protobuf or Avro topic of a workflow
+pub fn generate_schemas(workflow: &Workflow, output_dir: &Path, sink: &mut dyn OutputSink) -> Result<()> {
+    let topics = schema_topics(workflow)?;
+    if topics.is_empty() {
+        return Ok(());
+    }
+    let schemas_dir = output_dir.join(SCHEMAS_DIR);
+    sink.create_dir(&schemas_dir)
+        .with_context(|| message!("Failed to create {}", schemas_dir.display()))?;
+    for (topic, (encoding, schema)) in &topics {
+        let (file, contents) = match encoding {
+            Encoding::Protobuf => (format!("{}.proto", topic), proto_file(workflow, topic, schema)),
+            _ => (
+                format!("{}.avsc", topic),
+                serde_json::to_string_pretty(&avro_schema(workflow, topic, schema))? + "\n",
+            ),
+        };
+        let output_path = schemas_dir.join(file);
+        sink.write(&output_path, contents.as_bytes())
+            .with_context(|| message!("Failed to write {}", output_path.display()))?;
+    }
+    Ok(())
+}
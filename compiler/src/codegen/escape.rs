@@ -0,0 +1,63 @@
+//! Escaping filters of the templates
+//!
+//! Tera's autoescaping is HTML escaping, which would turn the quotes of a
+//! prompt into `&quot;` in Rust, Python and YAML files, so the templates are
+//! rendered without it. Values written inside a literal of the generated
+//! file go through the filter of its language instead, which quotes them as
+//! well:
+//!
+//! - `yaml_quote` makes a double-quoted YAML scalar;
+//! - `rust_str` makes a Rust string literal;
+//! - `py_str` makes a Python string literal.
+//!
+//! Numbers and booleans are quoted as their text and null as an empty
+//! string, so the filters also apply to settings that are strings in the
+//! generated file whatever their type.
+
+use std::collections::HashMap;
+use tera::{Tera, Value};
+
+/// Register the escaping filters in a Tera instance
+pub fn register_filters(tera: &mut Tera) {
+    tera.register_filter("yaml_quote", yaml_quote);
+    tera.register_filter("rust_str", rust_str);
+    tera.register_filter("py_str", py_str);
+}
+
+/// A double-quoted YAML scalar; JSON strings are valid ones
+pub fn yaml_quote_str(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+/// A Rust string literal
+pub fn rust_str_literal(value: &str) -> String {
+    format!("{:?}", value)
+}
+
+/// A Python string literal; JSON escapes are valid Python escapes
+pub fn py_str_literal(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+/// The text of a scalar, for the filters
+fn scalar<'a>(filter: &str, value: &'a Value) -> tera::Result<std::borrow::Cow<'a, str>> {
+    match value {
+        Value::String(text) => Ok(text.as_str().into()),
+        // As Tera renders a missing setting
+        Value::Null => Ok("".into()),
+        Value::Number(_) | Value::Bool(_) => Ok(value.to_string().into()),
+        _ => Err(tera::Error::msg(format!("Filter `{}` expects a string, a number or a boolean, got {}", filter, value))),
+    }
+}
+
+fn yaml_quote(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(Value::String(yaml_quote_str(&scalar("yaml_quote", value)?)))
+}
+
+fn rust_str(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(Value::String(rust_str_literal(&scalar("rust_str", value)?)))
+}
+
+fn py_str(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(Value::String(py_str_literal(&scalar("py_str", value)?)))
+}
@@ -0,0 +1,91 @@
+//! Provider fallback chains for LLM agents
+//!
+//! An LLM agent with `providers: [...]` sends each request to the first
+//! provider of the chain. When it fails for one of the provider's `on`
+//! triggers, the request goes to the next provider, and so on; any other
+//! failure is the request's. The generated agent tags every response with
+//! the provider and model that served it, in the `Kumeo-LLM-Provider` and
+//! `Kumeo-LLM-Model` message headers, and counts the requests each
+//! provider served, failed over or failed in Prometheus metrics.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ast::{Agent, AgentType, ProviderChain, Value, PROVIDERS_OPTION};
+use crate::message;
+use super::inference::{served_model, server_endpoint};
+
+/// Port the generated agents serve their `/metrics` on, see `agent::metrics_port`
+pub const METRICS_PORT: u16 = 9090;
+
+/// A provider of a chain, as the generated agent reads it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FailoverProvider {
+    /// openai, anthropic, ollama, vllm or tgi
+    pub provider: String,
+    /// The model asked for
+    pub model: String,
+    /// URL of its API; the provider's own when `None`
+    pub base_url: Option<String>,
+    /// Seconds a request may take before failing over; the agent's timeout when `None`
+    pub timeout_secs: Option<u64>,
+    /// What fails a request over to the next provider
+    pub on: Vec<&'static str>,
+}
+
+/// Provider chain of an LLM agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FailoverSettings {
+    /// The providers, in the order they are tried
+    pub providers: Vec<FailoverProvider>,
+    /// The providers as JSON, read by the generated configuration
+    pub json: String,
+}
+
+impl FailoverSettings {
+    /// Compute the provider chain of an LLM agent, if it has a `providers:` option
+    pub fn for_agent(agent: &Agent) -> Result<Option<Self>> {
+        let Some(value) = agent.config_value(PROVIDERS_OPTION) else {
+            return Ok(None);
+        };
+        let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+        if agent.agent_type != AgentType::LLM {
+            return Err(anyhow!(message!("Only LLM agents chain providers, {} is a {}", agent_id, agent.agent_type)));
+        }
+        let chain = ProviderChain::from_value(value).map_err(|e| anyhow!(message!("Invalid providers of {}: {}", agent_id, e)))?;
+
+        let providers = chain
+            .providers
+            .into_iter()
+            .map(|chained| {
+                let (model, base_url) = match &chained.server {
+                    Some(server) => {
+                        let model = served_model(agent, server)?;
+                        let (endpoint, _) = server_endpoint(server, &model);
+                        (model, Some(endpoint))
+                    }
+                    None => {
+                        let model = match (&chained.model, agent.config_value("model")) {
+                            (Some(model), _) => model.clone(),
+                            (None, Some(Value::String(model))) => model.clone(),
+                            _ => return Err(anyhow!(message!("The {} provider of {} needs a model", chained.provider, agent_id))),
+                        };
+                        (model, chained.endpoint.clone())
+                    }
+                };
+                Ok(FailoverProvider {
+                    provider: chained.provider,
+                    model,
+                    base_url,
+                    timeout_secs: chained.timeout_secs,
+                    on: chained.on.iter().map(|trigger| trigger.as_str()).collect(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Some(Self {
+            json: serde_json::to_string(&providers)?,
+            providers,
+        }))
+    }
+}
@@ -0,0 +1,62 @@
+//! Online features for MLModel agents
+//!
+//! An MLModel agent with a `features: Feast(...)` option enriches each batch
+//! of inputs with the online features of their entities before running the
+//! model. The generated agent builds a Feast client on a Redis online store;
+//! the entity of an input is given by its key fields, and the features land
+//! in the input's `features` field. The registry and the Redis connection
+//! default to the options of the DSL and can be overridden with
+//! `FEAST_REGISTRY` and `FEAST_REDIS_URL`.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ast::{Agent, AgentType, FeatureStoreConfig, Workflow, FEATURES_OPTION};
+use crate::message;
+
+/// Registry of the feature store when the DSL doesn't name one
+pub const DEFAULT_REGISTRY: &str = "data/registry.db";
+
+/// Redis online store when the DSL doesn't name one
+pub const DEFAULT_ONLINE_STORE: &str = "localhost:6379";
+
+/// Feature store lookup of an MLModel agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FeatureStoreSettings {
+    /// The feature view the features are read from
+    pub feature_view: String,
+    /// Feast project
+    pub project: String,
+    /// Input fields identifying the entity, as a Python list literal
+    pub python_keys: String,
+    /// Features fetched as a Python list literal; empty for every feature of the view
+    pub python_features: String,
+    /// Default registry as a Python string literal
+    pub python_registry: String,
+    /// Default Redis connection string as a Python string literal
+    pub python_online_store: String,
+}
+
+impl FeatureStoreSettings {
+    /// Compute the feature store settings of an MLModel agent, if it has a `features:` option
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Result<Option<Self>> {
+        let Some(value) = agent.config_value(FEATURES_OPTION) else {
+            return Ok(None);
+        };
+        if agent.agent_type != AgentType::MLModel {
+            let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+            return Err(anyhow!(message!("Only MLModel agents fetch features, {} is a {}", agent_id, agent.agent_type)));
+        }
+        let config = FeatureStoreConfig::from_value(value).map_err(|e| anyhow!(message!("Invalid feature store: {}", e)))?;
+
+        // JSON strings and lists of strings are valid Python literals
+        Ok(Some(Self {
+            project: config.project.unwrap_or_else(|| workflow.name.to_lowercase()),
+            python_keys: serde_json::to_string(&config.keys)?,
+            python_features: serde_json::to_string(&config.features)?,
+            python_registry: serde_json::to_string(config.registry.as_deref().unwrap_or(DEFAULT_REGISTRY))?,
+            python_online_store: serde_json::to_string(config.online_store.as_deref().unwrap_or(DEFAULT_ONLINE_STORE))?,
+            feature_view: config.feature_view,
+        }))
+    }
+}
@@ -0,0 +1,101 @@
+//! Guardrails of LLM agents
+//!
+//! An LLM agent with `guardrails: { input: [...], output: [...] }` checks
+//! every prompt before it reaches the model and every response before it is
+//! published, rule after rule. A rule breaking a message blocks it (it is
+//! reported on the agent's error topic), redacts what the rule found, or
+//! sends the message to the HumanReview agent named by `review`, on the
+//! topic that agent consumes. The agent counts the checks of each rule and
+//! their outcome in Prometheus metrics.
+//!
+//! Personal data, prompt injections and `regex` rules are matched with
+//! regular expressions, toxicity is scored by a classifier, and `rules`
+//! loads regular expressions from a resource when the agent starts.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ast::{Agent, AgentType, GuardrailCheck, GuardrailRule, GuardrailsConfig, Workflow, GUARDRAILS_OPTION};
+use crate::message;
+
+/// A rule of the guardrails, as the generated agent reads it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GuardrailRuleSettings {
+    /// The rule's name in metrics and reports
+    pub name: String,
+    /// What it looks for, with its settings
+    #[serde(flatten)]
+    pub check: GuardrailCheck,
+    /// block, redact or review
+    pub action: &'static str,
+}
+
+impl From<GuardrailRule> for GuardrailRuleSettings {
+    fn from(rule: GuardrailRule) -> Self {
+        Self {
+            name: rule.name,
+            check: rule.check,
+            action: rule.action.as_str(),
+        }
+    }
+}
+
+/// Guardrails of an LLM agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GuardrailSettings {
+    /// Rules of the prompts
+    pub input: Vec<GuardrailRuleSettings>,
+    /// Rules of the responses
+    pub output: Vec<GuardrailRuleSettings>,
+    /// The rules as JSON in a Rust string literal, read by the generated agent
+    pub rules_literal: String,
+    /// Subject of the HumanReview agent reviewed messages are published to
+    pub review_subject: Option<String>,
+}
+
+impl GuardrailSettings {
+    /// Compute the guardrails of an LLM agent, if it has a `guardrails:` option
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Result<Option<Self>> {
+        let Some(value) = agent.config_value(GUARDRAILS_OPTION) else {
+            return Ok(None);
+        };
+        let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+        if agent.agent_type != AgentType::LLM {
+            return Err(anyhow!(message!("Only LLM agents have guardrails, {} is a {}", agent_id, agent.agent_type)));
+        }
+        let config = GuardrailsConfig::from_value(value).map_err(|e| anyhow!(message!("Invalid guardrails of {}: {}", agent_id, e)))?;
+
+        let review_subject = match &config.review {
+            Some(reviewer) => Some(review_subject(workflow, reviewer).ok_or_else(|| {
+                anyhow!(message!(
+                    "{} sends messages to review to {}, which is not a HumanReview agent of {}",
+                    agent_id,
+                    reviewer,
+                    workflow.name
+                ))
+            })?),
+            None => None,
+        };
+        let input: Vec<GuardrailRuleSettings> = config.input.into_iter().map(Into::into).collect();
+        let output: Vec<GuardrailRuleSettings> = config.output.into_iter().map(Into::into).collect();
+
+        // Debug-formatting a string gives a valid Rust string literal, whatever the patterns contain
+        let json = serde_json::to_string(&serde_json::json!({ "input": input, "output": output }))?;
+        Ok(Some(Self {
+            input,
+            output,
+            rules_literal: format!("{:?}", json),
+            review_subject,
+        }))
+    }
+}
+
+/// The subject a HumanReview agent of a workflow consumes
+fn review_subject(workflow: &Workflow, reviewer: &str) -> Option<String> {
+    workflow
+        .agents
+        .iter()
+        .zip(workflow.deployed_topics())
+        .find(|(agent, _)| agent.id.as_deref() == Some(reviewer) && agent.agent_type == AgentType::HumanReview)
+        .and_then(|(_, topics)| topics.input)
+}
@@ -0,0 +1,95 @@
+//! Image build manifest of a workflow
+//!
+//! Every generation writes `images.json` next to the Taskfile, listing the
+//! images the deployment needs with the directory each one is built from,
+//! so CI can build and push them without knowing the layout:
+//!
+//! ```json
+//! {
+//!   "workflow": "Orders",
+//!   "images": [
+//!     { "name": "classify", "image": "ghcr.io/acme/classify:1.4.0", "context": "agents/classify", "dockerfile": "agents/classify/Dockerfile" }
+//!   ]
+//! }
+//! ```
+//!
+//! Paths are relative to the workflow's output directory. Images follow the
+//! `registry` and `tag` of the `deployment` block and the `image` setting of
+//! each agent, as the manifests do.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+
+use super::kubernetes::{image_of_agent, workflow_registry, workflow_tag};
+use super::review_ui::ReviewUiSettings;
+use super::sink::OutputSink;
+use crate::ast::Workflow;
+use crate::message;
+
+/// Name of the image build manifest in the output directory
+pub const IMAGES_FILE: &str = "images.json";
+
+/// An image to build
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImageBuild {
+    /// Agent, or review UI, the image runs
+    pub name: String,
+    /// Image reference it is pushed as
+    pub image: String,
+    /// Build context, relative to the output directory
+    pub context: String,
+    /// Dockerfile, relative to the output directory
+    pub dockerfile: String,
+}
+
+/// The images to build for a workflow
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImageManifest {
+    /// Name of the workflow
+    pub workflow: String,
+    /// Its images, agents first, in declaration order
+    pub images: Vec<ImageBuild>,
+}
+
+impl ImageManifest {
+    /// Compute the images of a workflow
+    pub fn for_workflow(workflow: &Workflow) -> Result<Self> {
+        let (registry, tag) = (workflow_registry(workflow), workflow_tag(workflow));
+        let mut images = Vec::new();
+        let mut review_uis = Vec::new();
+        for agent in &workflow.agents {
+            let Some(id) = &agent.id else {
+                continue;
+            };
+            let context = format!("agents/{}", id);
+            images.push(ImageBuild {
+                name: id.clone(),
+                image: image_of_agent(agent, registry, tag),
+                dockerfile: format!("{}/Dockerfile", context),
+                context,
+            });
+            if let Some(review_ui) = ReviewUiSettings::for_agent(workflow, agent, None)? {
+                let context = format!("agents/{}/ui", id);
+                review_uis.push(ImageBuild {
+                    name: review_ui.name,
+                    image: review_ui.image,
+                    dockerfile: format!("{}/Dockerfile", context),
+                    context,
+                });
+            }
+        }
+        images.extend(review_uis);
+        Ok(Self { workflow: workflow.name.clone(), images })
+    }
+}
+
+/// Generate `output_dir/images.json` with the images of a workflow
+pub fn generate_image_manifest(workflow: &Workflow, output_dir: &Path, sink: &mut dyn OutputSink) -> Result<()> {
+    let manifest = ImageManifest::for_workflow(workflow)?;
+    let output_path = output_dir.join(IMAGES_FILE);
+    let mut contents = serde_json::to_string_pretty(&manifest)?;
+    contents.push('\n');
+    sink.write(&output_path, contents.as_bytes())
+        .with_context(|| message!("Failed to write {}", output_path.display()))
+}
@@ -0,0 +1,229 @@
+//! Self-hosted inference servers for LLM agents
+//!
+//! An LLM agent with `provider: VLLM(...)` or `provider: TGI(...)` sends its
+//! prompts to a vLLM or Text Generation Inference server instead of a hosted
+//! API. Prompts arriving within the batch window are sent together: to
+//! vLLM's completions endpoint as one batch, to TGI as concurrent requests
+//! it batches on the GPU. Streaming uses the server-sent events of both.
+//!
+//! With an `endpoint`, the agent uses that server. Without one, the agents
+//! of the workflow's namespace serving the same model share a single server,
+//! deployed with the workflow as `kubernetes/inference/<server>.yaml`,
+//! instead of each loading the model. The servers of `providers:` chains
+//! are shared the same way.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::ast::{
+    Agent, AgentType, InferenceBackend, InferenceServerConfig, ProviderChain, Value, Workflow, PROVIDERS_OPTION,
+    PROVIDER_OPTION,
+};
+use crate::message;
+
+/// vLLM server image
+pub const VLLM_IMAGE: &str = "vllm/vllm-openai:v0.5.4";
+
+/// Port vLLM serves its API on
+pub const VLLM_PORT: u16 = 8000;
+
+/// Text Generation Inference server image
+pub const TGI_IMAGE: &str = "ghcr.io/huggingface/text-generation-inference:2.2.0";
+
+/// Port TGI serves its API on
+pub const TGI_PORT: u16 = 80;
+
+/// Secret holding the `HF_TOKEN` the servers download gated models with
+pub const HF_TOKEN_SECRET: &str = "huggingface";
+
+/// Longest Kubernetes name of a deployed server
+const MAX_NAME_LENGTH: usize = 63;
+
+/// Inference server of an LLM agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InferenceSettings {
+    /// Provider name of the generated client: vllm or tgi
+    pub provider: &'static str,
+    /// URL of the server
+    pub endpoint: String,
+    /// The model the server serves
+    pub model: String,
+    /// Prompts sent at once
+    pub max_batch_size: u32,
+    /// Milliseconds a prompt waits for others to fill its batch
+    pub batch_window_ms: u32,
+    /// Name of the shared server deployed with the workflow; `None` for an external endpoint
+    pub server: Option<String>,
+}
+
+impl InferenceSettings {
+    /// Compute the inference server of an LLM agent, if its provider is `VLLM(...)` or `TGI(...)`
+    pub fn for_agent(agent: &Agent) -> Result<Option<Self>> {
+        let Some(config) = server_config(agent)? else {
+            return Ok(None);
+        };
+        let model = served_model(agent, &config)?;
+        let (endpoint, server) = server_endpoint(&config, &model);
+        Ok(Some(Self {
+            provider: config.backend.as_str(),
+            endpoint,
+            model,
+            max_batch_size: config.max_batch_size,
+            batch_window_ms: config.batch_window_ms,
+            server,
+        }))
+    }
+}
+
+/// A shared inference server deployed with a workflow
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InferenceServer {
+    /// vllm or tgi
+    pub backend: &'static str,
+    /// Name of its Deployment and Service
+    pub name: String,
+    /// The model it serves
+    pub model: String,
+    /// Server image
+    pub image: &'static str,
+    /// Port of its API
+    pub port: u16,
+    /// GPUs the model is sharded across
+    pub gpus: u32,
+    /// Arguments of the server
+    pub args: Vec<String>,
+    /// The agents sending it prompts
+    pub agents: Vec<String>,
+}
+
+impl InferenceServer {
+    /// The shared servers of a workflow, one per backend and model
+    ///
+    /// A server gets the most GPUs and the largest batch any of its agents
+    /// asks for. TGI sizes its batches itself, from the requests in flight.
+    pub fn for_workflow(workflow: &Workflow) -> Result<Vec<Self>> {
+        let mut servers: BTreeMap<(InferenceBackend, String), (u32, u32, Vec<String>)> = BTreeMap::new();
+        let configs = workflow
+            .agents
+            .iter()
+            .map(|agent| Ok(server_configs(agent)?.into_iter().map(move |config| (agent, config))))
+            .collect::<Result<Vec<_>>>()?;
+        for (agent, config) in configs.into_iter().flatten().filter(|(_, config)| config.endpoint.is_none()) {
+            let model = served_model(agent, &config)?;
+            let entry = servers.entry((config.backend, model)).or_insert((0, 0, Vec::new()));
+            entry.0 = entry.0.max(config.gpus);
+            entry.1 = entry.1.max(config.max_batch_size);
+            entry.2.push(agent.id.clone().unwrap_or_default());
+        }
+
+        Ok(servers
+            .into_iter()
+            .map(|((backend, model), (gpus, max_batch_size, agents))| {
+                let (image, args) = match backend {
+                    InferenceBackend::Vllm => (VLLM_IMAGE, vec![
+                        "--model".to_string(),
+                        model.clone(),
+                        "--port".to_string(),
+                        VLLM_PORT.to_string(),
+                        "--tensor-parallel-size".to_string(),
+                        gpus.to_string(),
+                        "--max-num-seqs".to_string(),
+                        max_batch_size.to_string(),
+                    ]),
+                    InferenceBackend::Tgi => (TGI_IMAGE, vec![
+                        "--model-id".to_string(),
+                        model.clone(),
+                        "--port".to_string(),
+                        TGI_PORT.to_string(),
+                        "--num-shard".to_string(),
+                        gpus.to_string(),
+                    ]),
+                };
+                Self {
+                    backend: backend.as_str(),
+                    name: server_name(backend, &model),
+                    port: port(backend),
+                    model,
+                    image,
+                    gpus,
+                    args,
+                    agents,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Name of the shared server of a backend and model, a valid Kubernetes name
+pub fn server_name(backend: InferenceBackend, model: &str) -> String {
+    let mut name = format!("{}-", backend.as_str());
+    for c in model.to_lowercase().chars() {
+        match c {
+            'a'..='z' | '0'..='9' => name.push(c),
+            _ if !name.ends_with('-') => name.push('-'),
+            _ => {}
+        }
+    }
+    name.truncate(MAX_NAME_LENGTH);
+    name.trim_end_matches('-').to_string()
+}
+
+/// URL of the server of a backend serving a model, with the name of the shared server deployed for it, if any
+pub fn server_endpoint(config: &InferenceServerConfig, model: &str) -> (String, Option<String>) {
+    match &config.endpoint {
+        Some(endpoint) => (endpoint.trim_end_matches('/').to_string(), None),
+        None => {
+            let name = server_name(config.backend, model);
+            (format!("http://{}:{}", name, port(config.backend)), Some(name))
+        }
+    }
+}
+
+/// Port a backend serves its API on
+fn port(backend: InferenceBackend) -> u16 {
+    match backend {
+        InferenceBackend::Vllm => VLLM_PORT,
+        InferenceBackend::Tgi => TGI_PORT,
+    }
+}
+
+/// The inference server an agent's provider names, if any
+fn server_config(agent: &Agent) -> Result<Option<InferenceServerConfig>> {
+    let Some(value) = agent.config_value(PROVIDER_OPTION).filter(|value| InferenceServerConfig::is_server(value)) else {
+        return Ok(None);
+    };
+    let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+    if agent.agent_type != AgentType::LLM {
+        return Err(anyhow!(message!("Only LLM agents use inference servers, {} is a {}", agent_id, agent.agent_type)));
+    }
+    InferenceServerConfig::from_value(value)
+        .map(Some)
+        .map_err(|e| anyhow!(message!("Invalid provider of {}: {}", agent_id, e)))
+}
+
+/// Every inference server an agent sends prompts to: its provider's, or those of its provider chain
+fn server_configs(agent: &Agent) -> Result<Vec<InferenceServerConfig>> {
+    if let Some(config) = server_config(agent)? {
+        return Ok(vec![config]);
+    }
+    let Some(value) = agent.config_value(PROVIDERS_OPTION).filter(|_| agent.agent_type == AgentType::LLM) else {
+        return Ok(Vec::new());
+    };
+    let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+    let chain = ProviderChain::from_value(value).map_err(|e| anyhow!(message!("Invalid providers of {}: {}", agent_id, e)))?;
+    Ok(chain.providers.into_iter().filter_map(|provider| provider.server).collect())
+}
+
+/// The model a server serves for an agent: the server's own, or the agent's `model`
+pub fn served_model(agent: &Agent, config: &InferenceServerConfig) -> Result<String> {
+    match (&config.model, agent.config_value("model")) {
+        (Some(model), _) => Ok(model.clone()),
+        (None, Some(Value::String(model))) => Ok(model.clone()),
+        _ => Err(anyhow!(message!(
+            "The {} server of {} needs a model",
+            config.backend.tag(),
+            agent.id.as_deref().unwrap_or("<unnamed>")
+        ))),
+    }
+}
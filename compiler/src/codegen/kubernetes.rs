@@ -1,70 +1,96 @@
 //! Kubernetes configuration generation
 
 use anyhow::Result;
-use std::path::{Path, PathBuf};
+use serde::Serialize;
+use std::path::Path;
 use tera::Tera;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use crate::ast::{Workflow, AgentType};
-use super::template_processor::{process_template_dir, create_base_context};
+use crate::ast::{
+    split_image, Agent, AgentType, ACTIVE_SLOT_ANNOTATION, AnalysisCondition, RolloutStrategy, Source, Target, Value, Workflow, WorkflowMode,
+    FILE_WATCH_OPTION, IMAGE_OPTION, INPUT_TOPIC_OPTION, OUTPUT_TOPIC_OPTION, UNTIL_SEQUENCE_OPTION, WEBHOOK_METHOD_OPTION,
+};
+use super::inference::{InferenceServer, HF_TOKEN_SECRET};
+use crate::message;
+use crate::stats::agent_requests;
+use super::nats::ExternalNats;
+use super::sink::OutputSink;
+use super::template_processor::{create_base_context, render_templates};
 use anyhow::Context;
 
+/// Registry prefix for agent images when none is configured
+pub const DEFAULT_REGISTRY: &str = "";
+
+/// Tag for agent images when none is configured
+pub const DEFAULT_TAG: &str = "latest";
+
 /// Generate Kubernetes configuration files
 pub fn generate_kubernetes_config(
     workflow: &Workflow,
     output_dir: &Path,
     tera: &Tera,
+    external_nats: Option<&ExternalNats>,
+    sink: &mut dyn OutputSink,
 ) -> Result<()> {
     let kubernetes_dir = output_dir.join("kubernetes");
-    std::fs::create_dir_all(&kubernetes_dir)
-        .with_context(|| format!("Failed to create kubernetes directory: {}", kubernetes_dir.display()))?;
+    sink.create_dir(&kubernetes_dir)
+        .with_context(|| message!("Failed to create kubernetes directory: {}", kubernetes_dir.display()))?;
 
     // Create context with workflow information
     let mut context = create_base_context(&workflow.name);
     context.insert("workflow", workflow);
-    context.insert("namespace", "kumeo");
-    context.insert("registry", "");
-    context.insert("tag", "latest");
+    context.insert("namespace", workflow_namespace(workflow));
+    context.insert("metadata", &ManifestMetadata::for_workflow(workflow));
+    context.insert("registry", workflow_registry(workflow));
+    context.insert("tag", workflow_tag(workflow));
+    context.insert("external_nats", &external_nats);
     
     // Add agent type counts to context
     let agent_type_counts = count_agent_types(&workflow);
     context.insert("agent_type_counts", &agent_type_counts);
 
-    // Process kubernetes templates
-    let template_dir = PathBuf::from("compiler/templates/kubernetes");
-    if template_dir.exists() {
-        // Skip agent-specific templates as they are handled in agent.rs
-        process_template_dir(&template_dir, &kubernetes_dir, &context, tera, &["agent"]).ok();
+    // Deploy an in-cluster Kafka when the workflow uses Kafka without external brokers
+    if let Some(kafka) = KafkaSettings::for_workflow(workflow).filter(|kafka| kafka.in_cluster) {
+        let mut kafka_context = context.clone();
+        kafka_context.insert("kafka", &kafka);
+        let rendered = tera.render("kubernetes/brokers/kafka.yaml.tera", &kafka_context)
+            .context(message!("Failed to render the Kafka StatefulSet"))?;
+        sink.write(&kubernetes_dir.join("kafka.yaml"), rendered.as_bytes())
+            .context(message!("Failed to write the Kafka StatefulSet"))?;
     }
 
-    // Generate Helm chart if templates exist
-    let helm_dir = template_dir.join("helm");
-    if helm_dir.exists() {
-        let output_helm = kubernetes_dir.join("helm").join(&workflow.name);
-        std::fs::create_dir_all(&output_helm)?;
-        
-        // Process Helm templates
-        process_template_dir(&helm_dir, &output_helm, &context, tera, &[]).ok();
-            
-        // Generate values.yaml if it doesn't exist
-        let values_path = output_helm.join("values.yaml");
-        if !values_path.exists() {
-            let mut values_context = tera::Context::new();
-            values_context.insert("workflow", workflow);
-            values_context.insert("agent_type_counts", &agent_type_counts);
-            
-            if let Ok(rendered) = tera.render("kubernetes/helm/values.yaml.tera", &values_context) {
-                std::fs::write(values_path, rendered).ok();
-            }
+    // Deploy the inference servers shared by the LLM agents without an endpoint
+    let servers = InferenceServer::for_workflow(workflow)?;
+    if !servers.is_empty() {
+        let inference_dir = kubernetes_dir.join("inference");
+        sink.create_dir(&inference_dir)
+            .with_context(|| message!("Failed to create inference directory: {}", inference_dir.display()))?;
+        for server in &servers {
+            let mut server_context = context.clone();
+            server_context.insert("server", server);
+            server_context.insert("hf_token_secret", HF_TOKEN_SECRET);
+            let rendered = tera.render("kubernetes/inference/server.yaml.tera", &server_context)
+                .with_context(|| message!("Failed to render the inference server {}", server.name))?;
+            sink.write(&inference_dir.join(format!("{}.yaml", server.name)), rendered.as_bytes())
+                .with_context(|| message!("Failed to write the inference server {}", server.name))?;
         }
     }
 
+    // Scrape, alert on and chart the workflow when it has a `monitor` block
+    super::monitoring::generate_monitoring(workflow, &kubernetes_dir, tera, sink)?;
+
+    // Generate the Helm chart of the workflow; the values of a single agent are not part of it
+    let output_helm = kubernetes_dir.join("helm").join(&workflow.name);
+    sink.create_dir(&output_helm)?;
+    render_templates(tera, "kubernetes/helm/", &output_helm, &context, &["values-agent.yaml.tera"], sink)
+        .context(message!("Failed to generate the Helm chart"))?;
+
     Ok(())
 }
 
-/// Count the number of agents of each type
-pub fn count_agent_types(workflow: &Workflow) -> HashMap<String, usize> {
-    let mut counts = HashMap::new();
+/// Count the number of agents of each type, by type name
+pub fn count_agent_types(workflow: &Workflow) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
     
     for agent in &workflow.agents {
         let type_name = match agent.agent_type {
@@ -74,6 +100,13 @@ pub fn count_agent_types(workflow: &Workflow) -> HashMap<String, usize> {
             AgentType::Router => "router",
             AgentType::DecisionMatrix => "decisionmatrix",
             AgentType::HumanReview => "humanreview",
+            AgentType::QualityMonitor => "qualitymonitor",
+            AgentType::Aggregator => "aggregator",
+            AgentType::RuleEngine => "ruleengine",
+            AgentType::BayesianNetwork => "bayesiannetwork",
+            AgentType::Custom => "custom",
+            AgentType::DataNormalizer => "datanormalizer",
+            AgentType::MissingValueHandler => "missingvaluehandler",
         };
         
         *counts.entry(type_name.to_string()).or_insert(0) += 1;
@@ -81,3 +114,821 @@ pub fn count_agent_types(workflow: &Workflow) -> HashMap<String, usize> {
     
     counts
 }
+
+/// Image reference for an agent
+pub fn agent_image(agent_id: &str, registry: &str, tag: &str) -> String {
+    let registry = registry.trim_end_matches('/');
+    if registry.is_empty() {
+        format!("{}:{}", agent_id, tag)
+    } else {
+        format!("{}/{}:{}", registry, agent_id, tag)
+    }
+}
+
+/// Image reference for an agent, honouring its `image` setting
+///
+/// An `image` without a tag or digest gets `tag`, so a release retags
+/// every agent; without one the image is `<registry>/<id>:<tag>`.
+pub fn image_of_agent(agent: &Agent, registry: &str, tag: &str) -> String {
+    match agent.config_value(IMAGE_OPTION) {
+        Some(Value::String(image)) => match split_image(image) {
+            (_, None, None) => format!("{}:{}", image, tag),
+            _ => image.clone(),
+        },
+        _ => agent_image(agent.id.as_deref().unwrap_or("<unnamed>"), registry, tag),
+    }
+}
+
+/// Registry of a workflow's images, from its `deployment` block
+pub fn workflow_registry(workflow: &Workflow) -> &str {
+    workflow
+        .deployment
+        .as_ref()
+        .and_then(|deployment| deployment.registry.as_deref())
+        .unwrap_or(DEFAULT_REGISTRY)
+}
+
+/// Tag of a workflow's images, from its `deployment` block
+pub fn workflow_tag(workflow: &Workflow) -> &str {
+    workflow
+        .deployment
+        .as_ref()
+        .and_then(|deployment| deployment.tag.as_deref())
+        .unwrap_or(DEFAULT_TAG)
+}
+
+/// Default time an agent gets to finish a message when none is configured
+pub const DEFAULT_AGENT_TIMEOUT_SECS: u64 = 30;
+
+/// Time the `preStop` hook waits so endpoints stop routing to the pod
+pub const PRE_STOP_SLEEP_SECS: u64 = 5;
+
+/// Extra slack between the drain deadline and the kubelet's SIGKILL
+const TERMINATION_MARGIN_SECS: u64 = 5;
+
+/// Shutdown timings for an agent Deployment, derived from the agent timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DrainSettings {
+    /// Seconds the `preStop` hook sleeps before SIGTERM is delivered
+    pub pre_stop_sleep_seconds: u64,
+    /// Seconds in-flight messages get to finish before being nacked
+    pub drain_timeout_seconds: u64,
+    /// Value for the pod's `terminationGracePeriodSeconds`
+    pub termination_grace_period_seconds: u64,
+}
+
+impl DrainSettings {
+    /// Compute the drain settings for an agent from its `timeout` config
+    pub fn for_agent(agent: &Agent) -> Self {
+        let drain_timeout_seconds = agent
+            .config_value("timeout")
+            .and_then(|value| value.as_duration_secs())
+            .unwrap_or(DEFAULT_AGENT_TIMEOUT_SECS);
+
+        Self {
+            pre_stop_sleep_seconds: PRE_STOP_SLEEP_SECS,
+            drain_timeout_seconds,
+            termination_grace_period_seconds: PRE_STOP_SLEEP_SECS
+                + drain_timeout_seconds
+                + TERMINATION_MARGIN_SECS,
+        }
+    }
+}
+
+/// Prometheus the generated analysis templates query
+pub const DEFAULT_PROMETHEUS_ADDRESS: &str = "http://prometheus.monitoring.svc:9090";
+
+/// Time a canary step holds its weight when no analysis is configured
+const CANARY_PAUSE_DURATION: &str = "5m";
+
+/// Canary rollout of an agent, rendered as an Argo Rollout
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CanarySettings {
+    /// Traffic weights of the successive steps
+    pub steps: Vec<u32>,
+    /// Time each step holds its weight when there is no analysis
+    pub pause_duration: &'static str,
+    /// Analysis run between steps
+    pub analysis: Option<AnalysisSettings>,
+}
+
+/// An Argo `AnalysisTemplate` checking one Prometheus metric
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AnalysisSettings {
+    /// Name of the `AnalysisTemplate`
+    pub template_name: String,
+    /// Metric name as written in the DSL
+    pub metric: String,
+    /// Prometheus server address
+    pub prometheus_address: &'static str,
+    /// PromQL query producing the metric value
+    pub query: String,
+    /// Argo success condition on the query result
+    pub success_condition: String,
+}
+
+impl CanarySettings {
+    /// Compute the canary settings of an agent, if its workflow asks for one
+    pub fn for_agent(workflow: &Workflow, agent_id: &str) -> Result<Option<Self>> {
+        let Some(RolloutStrategy::Canary(canary)) = workflow
+            .deployment
+            .as_ref()
+            .and_then(|deployment| deployment.rollout.as_ref())
+        else {
+            return Ok(None);
+        };
+
+        let analysis = canary
+            .analysis
+            .as_deref()
+            .map(|expression| {
+                let condition = AnalysisCondition::parse(expression)
+                    .map_err(|e| anyhow::anyhow!(message!("Invalid canary analysis: {}", e)))?;
+                Ok::<_, anyhow::Error>(AnalysisSettings {
+                    template_name: format!("{}-analysis", agent_id),
+                    query: metric_query(&condition.metric, agent_id),
+                    success_condition: format!(
+                        "result[0] {} {}",
+                        condition.operator, condition.threshold
+                    ),
+                    metric: condition.metric,
+                    prometheus_address: DEFAULT_PROMETHEUS_ADDRESS,
+                })
+            })
+            .transpose()?;
+
+        Ok(Some(Self {
+            steps: canary.steps.iter().map(|&weight| weight as u32).collect(),
+            pause_duration: CANARY_PAUSE_DURATION,
+            analysis,
+        }))
+    }
+}
+
+/// Counter of the messages an agent's runtime received, served on its `/metrics`
+pub const MESSAGES_METRIC: &str = "kumeo_agent_messages_total";
+
+/// Counter of the received messages that failed to decode or to be handled
+pub const FAILED_MESSAGES_METRIC: &str = "kumeo_agent_messages_failed_total";
+
+/// PromQL query for a canary analysis metric of an agent
+///
+/// `error_rate` is the share of failed messages, from the counters of the
+/// runtime's message loop; any other name is taken as a metric exported by
+/// the agent pods.
+pub fn metric_query(metric: &str, agent_id: &str) -> String {
+    match metric {
+        "error_rate" => format!(
+            "sum(rate({1}{{app=\"{0}\"}}[1m])) / sum(rate({2}{{app=\"{0}\"}}[1m]))",
+            agent_id, FAILED_MESSAGES_METRIC, MESSAGES_METRIC
+        ),
+        _ => format!("avg({}{{app=\"{}\"}})", metric, agent_id),
+    }
+}
+
+/// Slots of a blue/green agent, the first one being active until promoted
+pub const BLUE_GREEN_SLOTS: [&str; 2] = ["blue", "green"];
+
+/// Label telling which blue/green slot a pod belongs to
+pub const SLOT_LABEL: &str = "kumeo.io/slot";
+
+/// Blue/green rollout of an agent: one Deployment per slot and a Service
+/// selecting the active one
+///
+/// Only the idle slot is rendered, with the new image and no replicas;
+/// `task promote` starts it and switches the Service to it. The active slot
+/// keeps the image and replicas it was promoted with, so applying the
+/// manifests again never touches the version serving traffic.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlueGreenSettings {
+    /// Slot names
+    pub slots: Vec<&'static str>,
+    /// Slot serving traffic, as the `kumeo.io/active-slot` annotation of the
+    /// deployment says; the first one when it says none
+    pub active: &'static str,
+    /// Slot the new version is rendered into
+    pub idle: &'static str,
+    /// Replicas of the active slot; idle slots run none so that two versions
+    /// never consume the same stream concurrently
+    pub replicas: u32,
+    /// Label selecting the slot
+    pub slot_label: &'static str,
+}
+
+impl BlueGreenSettings {
+    /// Compute the blue/green settings of a workflow's agents, if it asks for one
+    pub fn for_workflow(workflow: &Workflow) -> Option<Self> {
+        let deployment = workflow.deployment.as_ref()?;
+        if !matches!(deployment.rollout, Some(RolloutStrategy::BlueGreen)) {
+            return None;
+        }
+        let [first, second] = BLUE_GREEN_SLOTS;
+        let annotation = deployment.annotations.as_ref().and_then(|annotations| annotations.get(ACTIVE_SLOT_ANNOTATION));
+        let (active, idle) = match annotation {
+            Some(slot) if slot == second => (second, first),
+            _ => (first, second),
+        };
+        Some(Self {
+            slots: BLUE_GREEN_SLOTS.to_vec(),
+            active,
+            idle,
+            replicas: deployment.replicas.unwrap_or(1),
+            slot_label: SLOT_LABEL,
+        })
+    }
+}
+
+/// Namespace of the agents when the deployment names none
+pub const DEFAULT_NAMESPACE: &str = "kumeo";
+
+/// Namespace of a workflow's agents
+pub fn workflow_namespace(workflow: &Workflow) -> &str {
+    workflow
+        .deployment
+        .as_ref()
+        .and_then(|deployment| deployment.namespace.as_deref())
+        .unwrap_or(DEFAULT_NAMESPACE)
+}
+
+/// Namespace, labels and annotations shared by every manifest of a workflow
+///
+/// The labels and annotations of the `deployment` block go on the metadata
+/// of each generated resource, next to the `app` and `kumeo.io/` ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ManifestMetadata {
+    /// Namespace of the resources
+    pub namespace: String,
+    /// Labels added to every resource
+    pub labels: BTreeMap<String, String>,
+    /// Annotations added to every resource
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl ManifestMetadata {
+    /// Compute the metadata of a workflow's manifests
+    pub fn for_workflow(workflow: &Workflow) -> Self {
+        let deployment = workflow.deployment.as_ref();
+        let sorted = |map: Option<&HashMap<String, String>>| {
+            map.into_iter().flatten().map(|(key, value)| (key.clone(), value.clone())).collect()
+        };
+        Self {
+            namespace: workflow_namespace(workflow).to_string(),
+            labels: sorted(deployment.and_then(|deployment| deployment.labels.as_ref())),
+            annotations: sorted(deployment.and_then(|deployment| deployment.annotations.as_ref())),
+        }
+    }
+}
+
+/// Replicas, resources and environment of an agent's pods, from its workflow's `deployment`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeploymentSettings {
+    /// Namespace of the agent's objects
+    pub namespace: String,
+    /// Replicas the agent runs, or starts with when it autoscales
+    pub replicas: u32,
+    /// CPU request of its container
+    pub cpu: String,
+    /// Memory request of its container
+    pub memory: String,
+    /// GPUs its container is limited to, if any
+    pub gpu: Option<String>,
+    /// Environment variables of its container, sorted by name
+    pub env: BTreeMap<String, String>,
+    /// Autoscaling of its replicas
+    pub autoscaling: Option<AutoscalingSettings>,
+}
+
+/// A `HorizontalPodAutoscaler` of an agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AutoscalingSettings {
+    /// Fewest replicas
+    pub min_replicas: u32,
+    /// Most replicas
+    pub max_replicas: u32,
+    /// Average CPU utilization to keep, in percent
+    pub target_cpu: Option<u32>,
+    /// Average memory utilization to keep, in percent
+    pub target_memory: Option<u32>,
+}
+
+impl DeploymentSettings {
+    /// Compute the pod settings of an agent
+    ///
+    /// Requests default to those of the agent's language. Agents that run as a
+    /// batch `Job` don't autoscale.
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Result<Self> {
+        let deployment = workflow.deployment.as_ref();
+        let (cpu_millis, memory_mib) = agent_requests(workflow, agent)?;
+        let batch = agent.id.as_deref().is_some_and(|agent_id| BatchSettings::for_agent(workflow, agent_id).is_some());
+        Ok(Self {
+            namespace: workflow_namespace(workflow).to_string(),
+            replicas: deployment.and_then(|deployment| deployment.replicas).unwrap_or(1),
+            cpu: format!("{}m", cpu_millis),
+            memory: format!("{}Mi", memory_mib),
+            gpu: deployment
+                .and_then(|deployment| deployment.resources.as_ref())
+                .and_then(|resources| resources.gpu.clone()),
+            env: deployment
+                .map(|deployment| deployment.plain_env())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            autoscaling: deployment
+                .and_then(|deployment| deployment.scaling.as_ref())
+                .filter(|_| !batch)
+                .map(|scaling| AutoscalingSettings {
+                    min_replicas: scaling.min_replicas,
+                    max_replicas: scaling.max_replicas,
+                    target_cpu: scaling.target_cpu,
+                    target_memory: scaling.target_memory,
+                }),
+        })
+    }
+}
+
+/// Persistent volume of an agent: a PVC mounted into its pods
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StorageSettings {
+    /// Name of the `PersistentVolumeClaim`
+    pub claim_name: String,
+    /// Requested size
+    pub size: String,
+    /// Mount path inside the agent container
+    pub path: String,
+    /// Storage class, or the cluster default when unset
+    pub class: Option<String>,
+}
+
+impl StorageSettings {
+    /// Compute the storage settings of an agent, if its workflow declares any
+    pub fn for_agent(workflow: &Workflow, agent_id: &str) -> Option<Self> {
+        let storage = workflow.deployment.as_ref()?.storage.as_ref()?.get(agent_id)?;
+        Some(Self {
+            claim_name: format!("{}-data", agent_id),
+            size: storage.size.clone(),
+            path: storage.path.clone(),
+            class: storage.class.clone(),
+        })
+    }
+}
+
+/// Secret with the trusted signing keys, when the deployment does not name one
+pub const DEFAULT_TRUSTED_KEYS_SECRET: &str = "kumeo-trusted-keys";
+
+/// Where the trusted signing keys are mounted in agent containers
+pub const TRUSTED_KEYS_PATH: &str = "/etc/kumeo/trusted-keys";
+
+/// Signature verification of the resources an agent loads
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SigningSettings {
+    /// Secret holding the minisign and cosign public keys
+    pub secret: String,
+    /// Mount path of the keys inside the agent container
+    pub path: &'static str,
+}
+
+impl SigningSettings {
+    /// Compute the signing settings of a workflow that requires signed resources
+    pub fn for_workflow(workflow: &Workflow) -> Option<Self> {
+        let deployment = workflow.deployment.as_ref().filter(|d| d.require_signed)?;
+        Some(Self {
+            secret: deployment
+                .trusted_keys
+                .clone()
+                .unwrap_or_else(|| DEFAULT_TRUSTED_KEYS_SECRET.to_string()),
+            path: TRUSTED_KEYS_PATH,
+        })
+    }
+}
+
+/// Secret `${env.NAME}` references are read from, when the deployment does not name one
+pub const DEFAULT_ENV_SECRET: &str = "kumeo-env";
+
+/// Environment variables an agent references with `${env.NAME}` or `secret("name")`
+///
+/// Each one is sourced from the key of the same name in the Secret, so the
+/// values never appear in the DSL or the generated manifests. Variables of
+/// the deployment's `env` set to a secret are sourced from its key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SecretEnvSettings {
+    /// Secret holding the values
+    pub secret: String,
+    /// Variable names, which are also the Secret keys
+    pub vars: Vec<String>,
+    /// Variables of the deployment's `env` read from the Secret, with the key of each
+    pub deployment_vars: BTreeMap<String, String>,
+}
+
+impl SecretEnvSettings {
+    /// Compute the secret environment of an agent, if it reads any
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Option<Self> {
+        let vars: Vec<String> = agent.env_references().into_iter().map(str::to_string).collect();
+        let deployment_vars: BTreeMap<String, String> = workflow
+            .deployment
+            .as_ref()
+            .map(|deployment| deployment.secret_env())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(name, _)| !vars.iter().any(|var| var == name))
+            .map(|(name, key)| (name.to_string(), key.to_string()))
+            .collect();
+        if vars.is_empty() && deployment_vars.is_empty() {
+            return None;
+        }
+        Some(Self { secret: env_secret(workflow).to_string(), vars, deployment_vars })
+    }
+
+    /// Keys of the Secret the agents of a workflow read, with the agents reading each
+    pub fn keys_of_workflow(workflow: &Workflow) -> BTreeMap<String, Vec<String>> {
+        let mut keys: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for agent in &workflow.agents {
+            let Some(settings) = Self::for_agent(workflow, agent) else {
+                continue;
+            };
+            let id = agent.id.clone().unwrap_or_default();
+            for key in settings.vars.iter().chain(settings.deployment_vars.values()) {
+                let readers = keys.entry(key.clone()).or_default();
+                if !readers.contains(&id) {
+                    readers.push(id.clone());
+                }
+            }
+        }
+        keys
+    }
+}
+
+/// The Secret a workflow's `${env.NAME}` references and secrets are read from
+pub fn env_secret(workflow: &Workflow) -> &str {
+    workflow
+        .deployment
+        .as_ref()
+        .and_then(|deployment| deployment.env_secret.as_deref())
+        .unwrap_or(DEFAULT_ENV_SECRET)
+}
+
+/// Where the runtime writes its ready file once the declared models are loaded
+pub const READY_FILE_PATH: &str = "/tmp/kumeo/ready";
+
+/// Model warm-up of an agent: the pod is only ready once its models are pinned
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PreloadSettings {
+    /// Model URIs loaded at startup
+    pub uris: Vec<String>,
+    /// Directory on the agent's volume pinned models are kept in, instead of memory
+    pub dir: Option<String>,
+    /// Ready file checked by the readiness probe
+    pub ready_file: &'static str,
+}
+
+impl PreloadSettings {
+    /// Compute the warm-up settings of an agent that declares `preload`
+    ///
+    /// Agents with a persistent volume pin their models on it, so large
+    /// models don't have to stay in memory.
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Option<Self> {
+        let uris = workflow.preload_uris(agent).filter(|uris| !uris.is_empty())?;
+        let dir = agent
+            .id
+            .as_deref()
+            .and_then(|agent_id| StorageSettings::for_agent(workflow, agent_id))
+            .map(|storage| format!("{}/.kumeo-preload", storage.path.trim_end_matches('/')));
+        Some(Self {
+            uris,
+            dir,
+            ready_file: READY_FILE_PATH,
+        })
+    }
+}
+
+/// File source/target option naming a PVC to mount instead of a host directory
+pub const FILE_CLAIM_OPTION: &str = "claim";
+
+/// A directory of a File source or target mounted into an agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileMount {
+    /// Volume name
+    pub name: &'static str,
+    /// Directory, mounted at the same path in the container
+    pub path: String,
+    /// Sources are only read
+    pub read_only: bool,
+    /// PVC backing the directory, or a host directory when unset
+    pub claim: Option<String>,
+}
+
+/// File source and target volumes of an agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileSettings {
+    /// Directories to mount
+    pub mounts: Vec<FileMount>,
+    /// Whether the agent reading the source keeps watching for new files
+    pub watch: bool,
+}
+
+/// Directory to mount, with the filesystem root for an empty path
+fn mount_path(directory: &str) -> String {
+    if directory.is_empty() { "/".to_string() } else { directory.to_string() }
+}
+
+impl FileSettings {
+    /// Compute the file volumes of an agent
+    ///
+    /// The first agent reads the source directory and the last one writes the
+    /// target directory, mirroring how the chain is wired to the brokers.
+    pub fn for_agent(workflow: &Workflow, agent_id: &str) -> Option<Self> {
+        let ids: Vec<&str> = workflow.agents.iter().filter_map(|agent| agent.id.as_deref()).collect();
+        let mut mounts = Vec::new();
+        let mut watch = false;
+
+        if let Some(source @ Source::File(pattern, _)) = &workflow.source {
+            if ids.first() == Some(&agent_id) {
+                mounts.push(FileMount {
+                    name: "file-source",
+                    path: mount_path(pattern.rsplit_once('/').map_or("", |(directory, _)| directory)),
+                    read_only: true,
+                    claim: source.option(FILE_CLAIM_OPTION).map(str::to_string),
+                });
+                watch = source.option(FILE_WATCH_OPTION) == Some("true");
+            }
+        }
+
+        if let Some(target @ Target::File(directory, _)) = &workflow.target {
+            if ids.last() == Some(&agent_id) {
+                mounts.push(FileMount {
+                    name: "file-target",
+                    path: mount_path(directory.trim_end_matches('/')),
+                    read_only: false,
+                    claim: target.option(FILE_CLAIM_OPTION).map(str::to_string),
+                });
+            }
+        }
+
+        (!mounts.is_empty()).then_some(Self { mounts, watch })
+    }
+}
+
+/// Retries of a batch Job before it is marked as failed
+const BATCH_BACKOFF_LIMIT: u32 = 2;
+
+/// Seconds without messages after which a batch agent considers its input done
+const BATCH_IDLE_TIMEOUT_SECS: u64 = 30;
+
+/// Batch execution of an agent as a Kubernetes Job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BatchSettings {
+    /// Value for the Job's `backoffLimit`
+    pub backoff_limit: u32,
+    /// Stream sequence at which the agent stops, for the agent reading the source
+    pub until_sequence: Option<u64>,
+    /// Seconds without messages after which the input is considered consumed
+    pub idle_timeout_seconds: u64,
+}
+
+impl BatchSettings {
+    /// Compute the batch settings of an agent, if its workflow runs in batch mode
+    ///
+    /// Agents form a chain, so only the first one reads the workflow source and
+    /// gets its `until_sequence`; the others stop once they have caught up.
+    pub fn for_agent(workflow: &Workflow, agent_id: &str) -> Option<Self> {
+        if workflow.mode != WorkflowMode::Batch {
+            return None;
+        }
+
+        let reads_source = workflow
+            .agents
+            .first()
+            .and_then(|agent| agent.id.as_deref())
+            == Some(agent_id);
+        let until_sequence = workflow
+            .source
+            .as_ref()
+            .and_then(|source| source.option(UNTIL_SEQUENCE_OPTION))
+            .and_then(|until| until.parse().ok())
+            .filter(|_| reads_source);
+
+        Some(Self {
+            backoff_limit: BATCH_BACKOFF_LIMIT,
+            until_sequence,
+            idle_timeout_seconds: BATCH_IDLE_TIMEOUT_SECS,
+        })
+    }
+}
+
+/// Source/target option naming external Kafka bootstrap servers
+pub const KAFKA_BROKERS_OPTION: &str = "brokers";
+
+/// Source option naming the Kafka consumer group
+pub const KAFKA_GROUP_OPTION: &str = "group";
+
+/// Kafka image of the in-cluster broker
+const KAFKA_IMAGE: &str = "apache/kafka:3.7.0";
+
+/// Kafka client port
+const KAFKA_PORT: u16 = 9092;
+
+/// Kafka connection of a workflow
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KafkaSettings {
+    /// Comma-separated bootstrap servers
+    pub bootstrap_servers: String,
+    /// Consumer group shared by the agent replicas
+    pub group_id: String,
+    /// Whether the compiler deploys the broker itself
+    pub in_cluster: bool,
+    /// Secret the bootstrap servers of a managed cluster are read from, written by its Terraform
+    pub bootstrap_secret: Option<String>,
+    /// Name of the in-cluster StatefulSet and its Service
+    pub service_name: String,
+    /// Image of the in-cluster broker
+    pub image: &'static str,
+    /// Client port
+    pub port: u16,
+}
+
+impl KafkaSettings {
+    /// Compute the Kafka settings of a workflow, if its source or target is Kafka
+    pub fn for_workflow(workflow: &Workflow) -> Option<Self> {
+        let source = workflow.source.as_ref().filter(|s| matches!(s, Source::Kafka(..)));
+        let target = workflow.target.as_ref().filter(|t| matches!(t, Target::Kafka(..)));
+        if source.is_none() && target.is_none() {
+            return None;
+        }
+
+        let service_name = format!("{}-kafka", workflow.name.to_lowercase());
+        let external = source
+            .and_then(|s| s.option(KAFKA_BROKERS_OPTION))
+            .or_else(|| target.and_then(|t| t.option(KAFKA_BROKERS_OPTION)));
+
+        let managed = external.is_none()
+            && workflow
+                .deployment
+                .as_ref()
+                .and_then(|deployment| deployment.infrastructure.as_ref())
+                .is_some_and(|infrastructure| infrastructure.managed_kafka);
+
+        Some(Self {
+            bootstrap_servers: external
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("{}:{}", service_name, KAFKA_PORT)),
+            group_id: source
+                .and_then(|s| s.option(KAFKA_GROUP_OPTION))
+                .unwrap_or(&workflow.name)
+                .to_string(),
+            in_cluster: external.is_none() && !managed,
+            bootstrap_secret: managed.then(|| service_name.clone()),
+            service_name,
+            image: KAFKA_IMAGE,
+            port: KAFKA_PORT,
+        })
+    }
+}
+
+/// Source/target option naming the MQTT broker URL
+pub const MQTT_URL_OPTION: &str = "url";
+
+/// MQTT broker assumed when the workflow does not name one
+const DEFAULT_MQTT_URL: &str = "mqtt://mqtt:1883";
+
+/// HTTP source option naming the host the Ingress routes for the webhook
+pub const WEBHOOK_HOST_OPTION: &str = "host";
+
+/// Port the agent's webhook server listens on
+const WEBHOOK_PORT: u16 = 8088;
+
+/// NATS subject a webhook forwards its payloads to
+pub fn webhook_subject(workflow: &Workflow) -> String {
+    format!("{}.webhook", workflow.name.to_lowercase())
+}
+
+/// HTTP webhook served by the agent reading the workflow source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WebhookSettings {
+    /// Request path, e.g. `/ingest`
+    pub path: String,
+    /// Accepted request method
+    pub method: String,
+    /// Container port of the webhook server
+    pub port: u16,
+    /// Name of the Service and Ingress exposing the webhook
+    pub service_name: String,
+    /// Host the Ingress routes, or every host when absent
+    pub host: Option<String>,
+}
+
+impl WebhookSettings {
+    /// Compute the webhook of an agent, if it reads an HTTP workflow source
+    ///
+    /// Only the first agent receives requests; it forwards them into the
+    /// pipeline on [`webhook_subject`], where the chain continues as usual.
+    pub fn for_agent(workflow: &Workflow, agent_id: &str) -> Option<Self> {
+        let source @ Source::HTTP(path, _) = workflow.source.as_ref()? else {
+            return None;
+        };
+        if workflow.agents.first().and_then(|agent| agent.id.as_deref()) != Some(agent_id) {
+            return None;
+        }
+
+        Some(Self {
+            path: path.clone(),
+            method: source.option(WEBHOOK_METHOD_OPTION).unwrap_or("POST").to_ascii_uppercase(),
+            port: WEBHOOK_PORT,
+            service_name: format!("{}-webhook", agent_id),
+            host: source.option(WEBHOOK_HOST_OPTION).map(str::to_string),
+        })
+    }
+}
+
+/// Broker and topic of one end of a workflow
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Endpoint {
+    /// `nats`, `kafka`, `mqtt`, `http` or `file`
+    pub broker: &'static str,
+    /// Topic (Kafka, MQTT), subject (NATS), or glob/directory (file)
+    pub topic: String,
+}
+
+/// Messaging wiring of the agents of a workflow
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BrokerSettings {
+    /// Where the workflow reads from
+    pub source: Option<Endpoint>,
+    /// Where the workflow writes to
+    pub target: Option<Endpoint>,
+    /// Kafka connection, when either end uses Kafka
+    pub kafka: Option<KafkaSettings>,
+    /// MQTT broker URL, when either end uses MQTT
+    pub mqtt_url: Option<String>,
+}
+
+impl BrokerSettings {
+    /// Compute the messaging wiring of a workflow
+    pub fn for_workflow(workflow: &Workflow) -> Self {
+        let source = workflow.source.as_ref().map(|source| match source {
+            // Webhook payloads enter the pipeline through NATS
+            Source::HTTP(..) => Endpoint {
+                broker: "http",
+                topic: webhook_subject(workflow),
+            },
+            _ => Endpoint {
+                broker: match source {
+                    Source::Kafka(..) => "kafka",
+                    Source::MQTT(..) => "mqtt",
+                    Source::File(..) => "file",
+                    _ => "nats",
+                },
+                topic: source.topic().to_string(),
+            },
+        });
+        let target = workflow.target.as_ref().map(|target| Endpoint {
+            broker: match target {
+                Target::NATS(..) => "nats",
+                Target::Kafka(..) => "kafka",
+                Target::MQTT(..) => "mqtt",
+                Target::File(..) => "file",
+            },
+            topic: target.topic().to_string(),
+        });
+
+        let mqtt_source = workflow.source.as_ref().filter(|s| matches!(s, Source::MQTT(..)));
+        let mqtt_target = workflow.target.as_ref().filter(|t| matches!(t, Target::MQTT(..)));
+        let mqtt_url = (mqtt_source.is_some() || mqtt_target.is_some()).then(|| {
+            mqtt_source
+                .and_then(|s| s.option(MQTT_URL_OPTION))
+                .or_else(|| mqtt_target.and_then(|t| t.option(MQTT_URL_OPTION)))
+                .unwrap_or(DEFAULT_MQTT_URL)
+                .to_string()
+        });
+
+        Self {
+            source,
+            target,
+            kafka: KafkaSettings::for_workflow(workflow),
+            mqtt_url,
+        }
+    }
+
+    /// Compute the messaging wiring of an agent
+    ///
+    /// Agents expanded from a subworkflow name their own NATS subjects; with
+    /// several subjects bound, the agent consumes or produces the first one.
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Self {
+        let subject = |option| match agent.config_value(option)? {
+            Value::String(subject) => Some(subject.clone()),
+            Value::Array(subjects) => match subjects.first()? {
+                Value::String(subject) => Some(subject.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        let mut settings = Self::for_workflow(workflow);
+        if let Some(topic) = subject(INPUT_TOPIC_OPTION) {
+            settings.source = Some(Endpoint { broker: "nats", topic });
+        }
+        if let Some(topic) = subject(OUTPUT_TOPIC_OPTION) {
+            settings.target = Some(Endpoint { broker: "nats", topic });
+        }
+        settings
+    }
+}
@@ -0,0 +1,71 @@
+//! Providers of LLM agents
+//!
+//! Every generated LLM agent carries the clients of all providers behind the
+//! same `LLMClient` trait: OpenAI, Anthropic, Ollama, vLLM and TGI. The
+//! workflow's `provider` only sets the default of its configuration, so an
+//! agent switches providers without being regenerated, through its config
+//! file or the `KUMEO_LLM_PROVIDER`, `KUMEO_LLM_MODEL` and
+//! `KUMEO_LLM_ENDPOINT` variables. Hosted APIs read their key from
+//! `LLM_API_KEY`, or from the provider's own variable such as
+//! `ANTHROPIC_API_KEY`.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ast::{Agent, LLMProvider, Value, PROVIDER_OPTION};
+use crate::message;
+use super::inference::{served_model, server_endpoint};
+
+/// Provider of an LLM agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProviderSettings {
+    /// openai, anthropic, ollama, vllm or tgi
+    pub provider: &'static str,
+    /// The model asked for
+    pub model: String,
+    /// URL of its API; the provider's own when `None`
+    pub base_url: Option<String>,
+    /// Tokens a response may take, when the agent sets `max_tokens`
+    pub max_tokens: Option<u32>,
+}
+
+impl ProviderSettings {
+    /// Compute the provider of an LLM agent, unless it chains `providers` or names neither provider nor model
+    pub fn for_agent(agent: &Agent) -> Result<Option<Self>> {
+        let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+        let Some(provider) = LLMProvider::for_agent(agent).map_err(|e| anyhow!(message!("Invalid provider of {}: {}", agent_id, e)))? else {
+            return Ok(None);
+        };
+
+        let (model, base_url) = match &provider.server {
+            Some(server) => {
+                let model = served_model(agent, server)?;
+                let (endpoint, _) = server_endpoint(server, &model);
+                (model, Some(endpoint))
+            }
+            None => {
+                let model = match (&provider.model, agent.config_value("model")) {
+                    (Some(model), _) => model.clone(),
+                    (None, Some(Value::String(model))) => model.clone(),
+                    // Without either, the agent keeps the configuration's own defaults
+                    _ if agent.config_value(PROVIDER_OPTION).is_none() => return Ok(None),
+                    _ => return Err(anyhow!(message!("The {} provider of {} needs a model", provider.kind.tag(), agent_id))),
+                };
+                (model, provider.endpoint.clone())
+            }
+        };
+        Ok(Some(Self { provider: provider.kind.as_str(), model, base_url, max_tokens: max_tokens(agent) }))
+    }
+}
+
+/// The agent's `max_tokens`, given as an option or in `options: { ... }`
+fn max_tokens(agent: &Agent) -> Option<u32> {
+    let value = agent.config_value("max_tokens").or_else(|| match agent.config_value("options") {
+        Some(Value::Object(options)) => options.get("max_tokens"),
+        _ => None,
+    });
+    match value {
+        Some(Value::Number(tokens)) if *tokens >= 1.0 => Some(*tokens as u32),
+        _ => None,
+    }
+}
@@ -0,0 +1,54 @@
+//! Session memory for LLM agents
+//!
+//! An LLM agent with `memory: { scope: "session_id", window: 10 }` keeps the
+//! chat history of every session in the state store of its runtime. The
+//! session of a message is the value of its scope field; the last `window`
+//! exchanges of the session come before its prompt, and the new exchange is
+//! stored once the response is published. Sessions expire `ttl` after their
+//! last exchange. Messages without the scope field are processed without
+//! history.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ast::{Agent, AgentType, MemoryConfig, Workflow, MEMORY_OPTION};
+use crate::message;
+
+/// Session memory of an LLM agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MemorySettings {
+    /// Field of the messages identifying their session, as written
+    pub scope: String,
+    /// The scope's path segments as a Rust array literal
+    pub rust_scope: String,
+    /// Exchanges kept per session
+    pub window: u32,
+    /// Seconds a session is kept after its last exchange
+    pub ttl_secs: u64,
+    /// Prefix of the state keys of the agent's sessions
+    pub key_prefix: String,
+}
+
+impl MemorySettings {
+    /// Compute the session memory of an LLM agent, if it has a `memory:` option
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Result<Option<Self>> {
+        let Some(value) = agent.config_value(MEMORY_OPTION) else {
+            return Ok(None);
+        };
+        let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+        if agent.agent_type != AgentType::LLM {
+            return Err(anyhow!(message!("Only LLM agents remember sessions, {} is a {}", agent_id, agent.agent_type)));
+        }
+        let config = MemoryConfig::from_value(value).map_err(|e| anyhow!(message!("Invalid memory of {}: {}", agent_id, e)))?;
+
+        // Debug-formatting a list of strings gives a valid Rust array literal
+        let segments: Vec<&str> = config.scope.split('.').collect();
+        Ok(Some(Self {
+            rust_scope: format!("{:?}", segments),
+            scope: config.scope,
+            window: config.window,
+            ttl_secs: config.ttl_secs,
+            key_prefix: format!("memory.{}.{}", workflow.name, agent_id),
+        }))
+    }
+}
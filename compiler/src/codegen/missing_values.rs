@@ -0,0 +1,81 @@
+//! Defaults and imputations for MissingValueHandler agents
+//!
+//! A MissingValueHandler with `defaults: { country: "US" }` and
+//! `impute: { amount: mean() }` compiles both into constants of the agent.
+//! A field is missing when it is absent or null: `defaults` fields are filled
+//! with their literal value, and `impute` fields from the last `window`
+//! values the replica has seen of them, until it has seen one. Messages are
+//! filled before they are checked against the input schema, so the fields
+//! the schema requires may be the ones filled.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ast::{Agent, AgentType, MissingValueHandlerConfig, Value};
+use crate::message;
+use super::normalizer::rust_path;
+
+/// Defaults and imputations of a MissingValueHandler agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MissingValueSettings {
+    /// Fields filled with a literal value, by path
+    pub defaults: Vec<DefaultSettings>,
+    /// Fields filled from the values seen, by path
+    pub impute: Vec<ImputeSettings>,
+    /// Recent values of each field the imputations are computed over
+    pub window: u32,
+}
+
+/// A field filled with a literal value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DefaultSettings {
+    /// Path of the field below the message, as written in the DSL
+    pub field: String,
+    /// Rust `&[&str]` of the segments of the path
+    pub rust_path: String,
+    /// The value, as JSON
+    pub json: String,
+}
+
+/// A field filled from the values seen
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImputeSettings {
+    /// Path of the field below the message, as written in the DSL
+    pub field: String,
+    /// Rust `&[&str]` of the segments of the path
+    pub rust_path: String,
+    /// Name of the imputation, `mean`, `median` or `last`
+    pub imputation: String,
+}
+
+impl MissingValueSettings {
+    /// Compute the defaults and imputations of a MissingValueHandler agent
+    pub fn for_agent(agent: &Agent) -> Result<Option<Self>> {
+        if agent.agent_type != AgentType::MissingValueHandler {
+            return Ok(None);
+        }
+        let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+        let config = MissingValueHandlerConfig::from_agent(agent)
+            .map_err(|e| anyhow!(message!("Invalid missing value handler {}: {}", agent_id, e)))?;
+
+        let defaults = config
+            .defaults
+            .iter()
+            .map(|(field, value)| DefaultSettings {
+                field: field.clone(),
+                rust_path: rust_path(&MissingValueHandlerConfig::path(field)),
+                json: Value::to_json(value).to_string(),
+            })
+            .collect();
+        let impute = config
+            .impute
+            .iter()
+            .map(|(field, imputation)| ImputeSettings {
+                field: field.clone(),
+                rust_path: rust_path(&MissingValueHandlerConfig::path(field)),
+                imputation: imputation.as_str().to_string(),
+            })
+            .collect();
+        Ok(Some(Self { defaults, impute, window: config.window }))
+    }
+}
@@ -2,64 +2,218 @@
 //! 
 //! This module handles the generation of project files from templates
 //! based on the parsed DSL.
+//!
+//! Generation keeps no state between renders: every template gets a context
+//! built from its workflow and agent alone, and the Tera engine is only read.
+//! One engine can thus generate any number of workflows, one after the other
+//! or from several threads, without an agent of one showing up in another.
 
 use anyhow::Context;
 
 pub mod agent;
+pub mod aggregator;
+pub mod auth;
+pub mod bayesian_network;
+pub mod cluster;
+pub mod condition;
+pub mod console;
+pub mod contracts;
+pub mod custom;
+pub mod dependencies;
+pub mod drift;
+pub mod encoding;
+pub mod escape;
+pub mod failover;
+pub mod guardrails;
+pub mod feature_store;
+pub mod images;
+pub mod inference;
 pub mod kubernetes;
+pub mod llm_provider;
+pub mod memory;
+pub mod missing_values;
+pub mod model_drift;
+pub mod monitoring;
+pub mod nats;
+pub mod nomad;
+pub mod normalizer;
+pub mod output;
+pub mod quality;
+pub mod resilience;
+pub mod review;
+pub mod review_ui;
+pub mod routing;
+pub mod rule_engine;
+pub mod saga;
+pub mod scaffold;
+pub mod sink;
+pub mod subworkflow;
 pub mod taskfile;
+pub mod template_manager;
 pub mod template_processor;
+pub mod terraform;
+pub mod topics;
 
 use anyhow::Result;
+use std::collections::BTreeSet;
 use std::path::Path;
 use tera::Tera;
 
-use crate::ast::Workflow;
+use crate::ast::{Platform, Workflow};
+use crate::message;
+use self::nats::ExternalNats;
+use self::sink::OutputSink;
+use self::template_manager::TemplateManager;
+
+/// Template of the `.env.example` listing the values of a workflow's env Secret
+pub const ENV_EXAMPLE_TEMPLATE: &str = "workflow/env.example.tera";
 
 /// Generate all project files from templates
-pub fn generate_workflow(workflow: &Workflow, output_dir: &Path) -> Result<()> {
-    // Initialize template engine
-    let mut tera = Tera::new("compiler/templates/**/*.tera")?;
-    tera.autoescape_on(vec![".rs", ".toml", ".yaml", ".yml", ".py"]);
+///
+/// With an external NATS, agents connect to it and no NATS is deployed.
+/// A workflow whose deployment declares its `infrastructure` also gets the
+/// Terraform of it, and its agents connect to the NATS provisioned there.
+/// Templates are resolved through the layers of `templates`. Files are
+/// written through `sink`.
+pub fn generate_workflow(
+    workflow: &Workflow,
+    output_dir: &Path,
+    external_nats: Option<&ExternalNats>,
+    templates: &TemplateManager,
+    sink: &mut dyn OutputSink,
+) -> Result<()> {
+    let tera = templates.engine()?;
 
     // Create output directory if it doesn't exist
-    std::fs::create_dir_all(output_dir)
-        .with_context(|| format!("Failed to create output directory: {}", output_dir.display()))?;
+    sink.create_dir(output_dir)
+        .with_context(|| message!("Failed to create output directory: {}", output_dir.display()))?;
 
-    // Generate Kubernetes configuration
-    kubernetes::generate_kubernetes_config(workflow, output_dir, &tera)?;
+    // Generate the Terraform of the supporting infrastructure
+    let provisioned_nats = match terraform::TerraformSettings::for_workflow(workflow, external_nats) {
+        Some(settings) => {
+            terraform::generate_terraform(&output_dir.join("terraform"), &settings, &tera, sink)?;
+            settings.provisioned_nats()?
+        }
+        None => None,
+    };
+    let external_nats = external_nats.or(provisioned_nats.as_ref());
+
+    // Generate the configuration of the platform the agents are deployed on
+    match workflow.platform() {
+        Platform::Kubernetes => kubernetes::generate_kubernetes_config(workflow, output_dir, &tera, external_nats, sink)?,
+        Platform::Nomad => nomad::generate_nomad_config(workflow, output_dir, &tera, external_nats, sink)?,
+    }
 
     // Generate Taskfiles
-    taskfile::generate_taskfiles(workflow, output_dir, &tera)?;
+    taskfile::generate_taskfiles(workflow, output_dir, &tera, sink)?;
 
     // Generate agent-specific files
     for agent in &workflow.agents {
-        agent::generate_agent(agent, output_dir, &tera)?;
+        agent::generate_agent(workflow, agent, output_dir, &tera, external_nats, sink)?;
     }
 
     // Generate workflow-level files
-    generate_workflow_files(workflow, output_dir, &tera)?;
+    generate_workflow_files(workflow, output_dir, &tera, sink)?;
+    generate_env_example(workflow, output_dir, &tera, sink)?;
+    images::generate_image_manifest(workflow, output_dir, sink)?;
+    encoding::generate_schemas(workflow, output_dir, sink)?;
+
+    Ok(())
+}
+
+/// Generate `output_dir/.env.example` with the keys of the env Secret the agents read, if any
+///
+/// Filled in as `.env`, it runs the agents outside the platform or creates the Secret.
+pub fn generate_env_example(workflow: &Workflow, output_dir: &Path, tera: &Tera, sink: &mut dyn OutputSink) -> Result<()> {
+    let keys = kubernetes::SecretEnvSettings::keys_of_workflow(workflow);
+    if keys.is_empty() {
+        return Ok(());
+    }
+    let mut context = template_processor::create_base_context(&workflow.name);
+    context.insert("secret", kubernetes::env_secret(workflow));
+    context.insert("keys", &keys);
+    context.insert("platform", &workflow.platform());
+    context.insert("variables", nomad::VARIABLES_PREFIX);
+    let rendered = tera.render(ENV_EXAMPLE_TEMPLATE, &context)
+        .with_context(|| message!("Failed to render the .env.example of workflow {}", workflow.name))?;
+    let output_path = output_dir.join(".env.example");
+    sink.write(&output_path, rendered.as_bytes())
+        .with_context(|| message!("Failed to write {}", output_path.display()))
+}
 
+/// Regenerate the files of some agents of a workflow, leaving the rest of the output as is
+pub fn generate_agents(
+    workflow: &Workflow,
+    agent_ids: &BTreeSet<String>,
+    output_dir: &Path,
+    external_nats: Option<&ExternalNats>,
+    templates: &TemplateManager,
+    sink: &mut dyn OutputSink,
+) -> Result<()> {
+    let tera = templates.engine()?;
+    let provisioned_nats = terraform::TerraformSettings::for_workflow(workflow, external_nats)
+        .map(|settings| settings.provisioned_nats())
+        .transpose()?
+        .flatten();
+    let external_nats = external_nats.or(provisioned_nats.as_ref());
+    for agent in workflow.agents.iter().filter(|agent| agent.id.as_ref().is_some_and(|id| agent_ids.contains(id))) {
+        agent::generate_agent(workflow, agent, output_dir, &tera, external_nats, sink)?;
+    }
     Ok(())
 }
 
+/// Generate the combined cluster layout of several programs
+///
+/// Each program must already be generated into its directory of the layout.
+pub fn generate_cluster(
+    programs: &[cluster::ClusterProgram],
+    output_dir: &Path,
+    external_nats: Option<&ExternalNats>,
+    templates: &TemplateManager,
+    sink: &mut dyn OutputSink,
+) -> Result<()> {
+    let tera = templates.engine()?;
+    let nats = external_nats.map(cluster::NatsSettings::external).unwrap_or_default();
+    cluster::generate_cluster_layout(programs, output_dir, &tera, &nats, sink)
+}
+
+/// Generate the read-only web console of some workflows into `console_dir`
+///
+/// The console follows the external NATS when there is one, the shared NATS
+/// of the cluster layout when the workflows are deployed in namespaces, and
+/// the NATS deployed with the workflows otherwise.
+pub fn generate_console(
+    workflows: &[&Workflow],
+    console_dir: &Path,
+    cluster_layout: bool,
+    external_nats: Option<&ExternalNats>,
+    templates: &TemplateManager,
+    sink: &mut dyn OutputSink,
+) -> Result<()> {
+    let nats_url = match external_nats {
+        Some(nats) => nats.url.clone(),
+        None if cluster_layout => cluster::NatsSettings::default().url,
+        None => review_ui::DEFAULT_NATS_URL.to_string(),
+    };
+    let settings = console::ConsoleSettings::for_workflows(workflows, &nats_url)?;
+    let tera = templates.engine()?;
+    console::generate_console(workflows, console_dir, &settings, external_nats, &tera, sink)
+}
+
 /// Generate workflow-level files
-fn generate_workflow_files(workflow: &Workflow, output_dir: &Path, tera: &Tera) -> Result<()> {
+fn generate_workflow_files(workflow: &Workflow, output_dir: &Path, tera: &Tera, sink: &mut dyn OutputSink) -> Result<()> {
     let mut context = template_processor::create_base_context(&workflow.name);
     context.insert("workflow", workflow);
     
     // Generate README.md for the workflow
-    let readme_path = output_dir.join("README.md");
-    if let Ok(rendered) = tera.render("workflow/README.md.tera", &context) {
-        std::fs::write(readme_path, rendered)?;
-    }
+    let readme = template_processor::render_template(tera, "workflow/README.md.tera", &context)?;
+    sink.write(&output_dir.join("README.md"), readme.as_bytes())?;
     
-    // Generate .gitignore if it doesn't exist
+    // Generate .gitignore if it doesn't exist and the templates have one
     let gitignore_path = output_dir.join(".gitignore");
-    if !gitignore_path.exists() {
-        if let Ok(rendered) = tera.render("workflow/gitignore.tera", &context) {
-            std::fs::write(gitignore_path, rendered)?;
-        }
+    if !sink.exists(&gitignore_path) && tera.get_template_names().any(|name| name == "workflow/gitignore.tera") {
+        let rendered = template_processor::render_template(tera, "workflow/gitignore.tera", &context)?;
+        sink.write(&gitignore_path, rendered.as_bytes())?;
     }
     
     Ok(())
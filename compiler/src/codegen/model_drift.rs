@@ -0,0 +1,70 @@
+//! Drift detection for MLModel agents
+//!
+//! An MLModel agent with a `drift:` option loads a reference profile of its
+//! input features when it starts: for every feature, the bins of its
+//! reference distribution. The agent bins the features of the inputs it
+//! accepts the same way and, after every window of inputs, scores each
+//! feature against its reference. A report with the scores and the features
+//! above the threshold is published on the workflow's monitor subject.
+//!
+//! A profile is a JSON object with a `features` entry per feature, either
+//! numeric (`{"edges": [...], "frequencies": [...]}`, one frequency more
+//! than edges) or categorical (`{"categories": {"value": frequency}}`).
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ast::{Agent, AgentType, ModelDriftConfig, Workflow, DRIFT_OPTION};
+use crate::message;
+
+/// Prefix of the subject the drift reports of a workflow's models are published to
+pub const MONITOR_SUBJECT_PREFIX: &str = "kumeo.monitor";
+
+/// Drift detection of an MLModel agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelDriftSettings {
+    /// URI of the reference profile
+    pub reference: String,
+    /// The URI as a Python string literal
+    pub python_reference: String,
+    /// How distributions are compared: psi, kl, js or ks
+    pub method: &'static str,
+    /// Score above which a feature has drifted
+    pub threshold: f64,
+    /// Inputs per comparison
+    pub window: u32,
+    /// Features tracked as a Python list literal; empty for every feature of the profile
+    pub python_features: String,
+    /// Subject the reports are published to
+    pub subject: String,
+}
+
+impl ModelDriftSettings {
+    /// Compute the drift settings of an MLModel agent, if it has a `drift:` option
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Result<Option<Self>> {
+        let Some(value) = agent.config_value(DRIFT_OPTION) else {
+            return Ok(None);
+        };
+        if agent.agent_type != AgentType::MLModel {
+            let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+            return Err(anyhow!(message!("Only MLModel agents track drift, {} is a {}", agent_id, agent.agent_type)));
+        }
+        let config = ModelDriftConfig::from_value(value).map_err(|e| anyhow!(message!("Invalid drift: {}", e)))?;
+
+        // JSON strings and lists of strings are valid Python literals
+        Ok(Some(Self {
+            python_reference: serde_json::to_string(&config.reference)?,
+            reference: config.reference,
+            method: config.method.as_str(),
+            threshold: config.threshold,
+            window: config.window,
+            python_features: serde_json::to_string(&config.features)?,
+            subject: monitor_subject(workflow),
+        }))
+    }
+}
+
+/// Subject the drift reports of a workflow's models are published to
+pub fn monitor_subject(workflow: &Workflow) -> String {
+    format!("{}.{}", MONITOR_SUBJECT_PREFIX, workflow.name.to_lowercase())
+}
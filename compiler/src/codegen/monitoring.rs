@@ -0,0 +1,287 @@
+//! Prometheus and Grafana assets of monitored workflows
+//!
+//! A workflow with a `monitor` block, deployed on Kubernetes, gets in
+//! `kubernetes/monitoring/` the Prometheus Operator resources that scrape and
+//! alert on it, and a Grafana dashboard:
+//!
+//! - a PodMonitor scraping the `metrics` port of its agents, the ones
+//!   counting their provider requests and guardrail checks, and a
+//!   ServiceMonitor scraping its inference servers, unless
+//!   `metrics_enabled: false`;
+//! - a PrometheusRule with an alert per `alert_*` setting, on the agents'
+//!   metrics, the end-to-end latency the runtime observes as messages leave
+//!   the workflow, and the metrics of kube-state-metrics;
+//! - the dashboard, as `dashboard.json` to import and in a ConfigMap
+//!   labelled for the dashboard sidecar of the Grafana Helm chart.
+//!
+//! Series are matched by agent ID rather than namespace, so the assets work
+//! in the namespace a cluster layout gives the program.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::Path;
+use tera::Tera;
+
+use super::inference::InferenceServer;
+use super::kubernetes::{workflow_namespace, BatchSettings, CanarySettings, ManifestMetadata, BLUE_GREEN_SLOTS};
+use super::sink::OutputSink;
+use super::template_processor::create_base_context;
+use crate::ast::{parse_duration_millis, Monitor, Platform, Workflow};
+use crate::message;
+
+/// Template of the PodMonitor, ServiceMonitor, PrometheusRule and dashboard ConfigMap
+pub const MONITORING_TEMPLATE: &str = "kubernetes/monitoring/monitoring.yaml.tera";
+
+/// Label the Grafana sidecar imports dashboard ConfigMaps by
+pub const DASHBOARD_LABEL: &str = "grafana_dashboard";
+
+/// Histogram of the time from ingest until messages leave a workflow, labelled by workflow
+pub const LATENCY_METRIC: &str = "kumeo_e2e_latency_seconds";
+
+/// An alert of the PrometheusRule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AlertRule {
+    /// Name of the alert
+    pub name: &'static str,
+    /// PromQL expression raising it
+    pub expr: String,
+    /// How long the expression must hold, if not at once
+    pub for_duration: Option<String>,
+    /// Summary annotation, with Prometheus templating
+    pub summary: String,
+}
+
+/// What to scrape, alert on and chart for a monitored workflow, ready to be injected into its template
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MonitoringSettings {
+    /// Namespace of the resources
+    pub namespace: String,
+    /// Whether the agents and inference servers are scraped
+    pub metrics_enabled: bool,
+    /// Interval they are scraped at
+    pub scrape_interval: String,
+    /// Whether the workflow deploys inference servers, scraped through their Services
+    pub inference: bool,
+    /// Severity label of the alerts
+    pub severity: String,
+    /// Alerts of the PrometheusRule; none leaves it out
+    pub alerts: Vec<AlertRule>,
+    /// Name of the dashboard ConfigMap
+    pub dashboard_name: String,
+    /// The Grafana dashboard as JSON
+    pub dashboard: String,
+}
+
+impl MonitoringSettings {
+    /// Compute the monitoring of a workflow, if it has a `monitor` block and runs on Kubernetes
+    pub fn for_workflow(workflow: &Workflow) -> Result<Option<Self>> {
+        let Some(monitor) = &workflow.monitor else {
+            return Ok(None);
+        };
+        if workflow.platform() != Platform::Kubernetes {
+            return Ok(None);
+        }
+        let ids: Vec<&str> = workflow.agents.iter().filter_map(|agent| agent.id.as_deref()).collect();
+        let agents = id_pattern(&ids);
+
+        // kube-state-metrics only knows the Deployments: batch agents run as
+        // Jobs and canaries as Rollouts
+        let mut deployed = Vec::new();
+        for id in &ids {
+            if BatchSettings::for_agent(workflow, id).is_none() && CanarySettings::for_agent(workflow, id)?.is_none() {
+                deployed.push(*id);
+            }
+        }
+        let deployments = format!("{}(-({}))?", id_pattern(&deployed), BLUE_GREEN_SLOTS.join("|"));
+
+        Ok(Some(Self {
+            namespace: workflow_namespace(workflow).to_string(),
+            metrics_enabled: monitor.metrics_enabled,
+            scrape_interval: monitor.scrape_interval.clone(),
+            inference: !InferenceServer::for_workflow(workflow)?.is_empty(),
+            severity: monitor.alerts.severity.clone(),
+            alerts: alert_rules(workflow, monitor, &agents, (!deployed.is_empty()).then_some(&deployments)),
+            dashboard_name: format!("{}-dashboard", workflow.name.to_lowercase()),
+            dashboard: serde_json::to_string_pretty(&dashboard(workflow, &agents, &deployments))?,
+        }))
+    }
+}
+
+/// A regex alternation matching any of some agent IDs
+fn id_pattern(ids: &[&str]) -> String {
+    let escaped: Vec<String> = ids.iter().map(|id| regex::escape(id)).collect();
+    format!("({})", escaped.join("|"))
+}
+
+/// The alerts of the `alert_*` settings of a monitor
+fn alert_rules(workflow: &Workflow, monitor: &Monitor, agents: &str, deployments: Option<&String>) -> Vec<AlertRule> {
+    let alerts = &monitor.alerts;
+    let window = &alerts.window;
+    let mut rules = Vec::new();
+    if let Some(restarts) = alerts.restarts {
+        rules.push(AlertRule {
+            name: "KumeoAgentRestarting",
+            expr: format!(
+                "increase(kube_pod_container_status_restarts_total{{pod=~\"{}-.*\"}}[{}]) >= {}",
+                agents, window, restarts
+            ),
+            for_duration: None,
+            summary: format!("Pod {{{{ $labels.pod }}}} of workflow {} restarted {{{{ $value }}}} times in {}", workflow.name, window),
+        });
+    }
+    if let Some(rate) = alerts.error_rate {
+        let requests = format!("kumeo_llm_requests_total{{agent=~\"{}\"", agents);
+        rules.push(AlertRule {
+            name: "KumeoLLMErrorRate",
+            expr: format!(
+                "sum by (agent) (rate({0}, outcome=\"failed\"}}[{1}])) / sum by (agent) (rate({0}}}[{1}])) > {2}",
+                requests, window, rate
+            ),
+            for_duration: None,
+            summary: format!("Agent {{{{ $labels.agent }}}} of workflow {} fails {{{{ $value | humanizePercentage }}}} of its LLM requests", workflow.name),
+        });
+    }
+    if let Some(blocks) = alerts.guardrail_blocks {
+        rules.push(AlertRule {
+            name: "KumeoGuardrailBlocks",
+            expr: format!(
+                "sum by (agent) (increase(kumeo_guardrail_checks_total{{agent=~\"{}\", outcome=\"blocked\"}}[{}])) >= {}",
+                agents, window, blocks
+            ),
+            for_duration: None,
+            summary: format!("Guardrails of agent {{{{ $labels.agent }}}} of workflow {} blocked {{{{ $value }}}} messages in {}", workflow.name, window),
+        });
+    }
+    if let (Some(duration), Some(deployments)) = (&alerts.unavailable_for, deployments) {
+        // The slots of a blue/green agent count as one: the idle one runs no replicas
+        rules.push(AlertRule {
+            name: "KumeoAgentUnavailable",
+            expr: format!(
+                "max by (agent) (label_replace(kube_deployment_status_replicas_available{{deployment=~\"{0}\"}}, \"agent\", \"$1\", \"deployment\", \"{0}\")) == 0",
+                deployments
+            ),
+            for_duration: Some(duration.clone()),
+            summary: format!("Agent {{{{ $labels.agent }}}} of workflow {} has no available replica", workflow.name),
+        });
+    }
+    if let Some(latency) = &alerts.latency_p99 {
+        let seconds = parse_duration_millis(latency).unwrap_or_default() as f64 / 1000.0;
+        rules.push(AlertRule {
+            name: "KumeoEndToEndLatency",
+            expr: format!("{} > {}", latency_quantile(workflow, 0.99, window), seconds),
+            for_duration: None,
+            summary: format!(
+                "The p99 end-to-end latency of workflow {} is {{{{ $value | humanizeDuration }}}}, above {}",
+                workflow.name, latency
+            ),
+        });
+    }
+    rules
+}
+
+/// A quantile of the end-to-end latency of a workflow's messages over a window
+fn latency_quantile(workflow: &Workflow, quantile: f64, window: &str) -> String {
+    format!(
+        "histogram_quantile({}, sum by (le) (rate({}_bucket{{workflow=\"{}\"}}[{}])))",
+        quantile, LATENCY_METRIC, workflow.name, window
+    )
+}
+
+/// The Grafana dashboard of a workflow: its agents' requests, guardrail checks, restarts and
+/// replicas, and the end-to-end latency of its messages
+fn dashboard(workflow: &Workflow, agents: &str, deployments: &str) -> Value {
+    let panels = [
+        (
+            "LLM requests by outcome",
+            vec![(
+                format!("sum by (agent, outcome) (rate(kumeo_llm_requests_total{{agent=~\"{}\"}}[$__rate_interval]))", agents),
+                "{{agent}} {{outcome}}",
+            )],
+        ),
+        (
+            "Guardrail checks by outcome",
+            vec![(
+                format!("sum by (agent, outcome) (rate(kumeo_guardrail_checks_total{{agent=~\"{}\"}}[$__rate_interval]))", agents),
+                "{{agent}} {{outcome}}",
+            )],
+        ),
+        (
+            "Container restarts",
+            vec![(
+                format!("sum by (pod) (increase(kube_pod_container_status_restarts_total{{pod=~\"{}-.*\"}}[$__rate_interval]))", agents),
+                "{{pod}}",
+            )],
+        ),
+        (
+            "Available replicas",
+            vec![(
+                format!("sum by (deployment) (kube_deployment_status_replicas_available{{deployment=~\"{}\"}})", deployments),
+                "{{deployment}}",
+            )],
+        ),
+        (
+            "End-to-end latency",
+            vec![
+                (latency_quantile(workflow, 0.5, "$__rate_interval"), "p50"),
+                (latency_quantile(workflow, 0.9, "$__rate_interval"), "p90"),
+                (latency_quantile(workflow, 0.99, "$__rate_interval"), "p99"),
+            ],
+        ),
+    ];
+    let panels: Vec<Value> = panels
+        .iter()
+        .enumerate()
+        .map(|(index, (title, queries))| {
+            let targets: Vec<Value> = queries
+                .iter()
+                .zip('A'..)
+                .map(|((expr, legend), ref_id)| json!({ "refId": ref_id.to_string(), "expr": expr, "legendFormat": legend }))
+                .collect();
+            json!({
+                "id": index + 1,
+                "type": "timeseries",
+                "title": title,
+                "datasource": { "type": "prometheus", "uid": "${datasource}" },
+                "gridPos": { "h": 8, "w": 12, "x": (index % 2) * 12, "y": (index / 2) * 8 },
+                "targets": targets,
+            })
+        })
+        .collect();
+
+    json!({
+        "title": format!("Kumeo / {}", workflow.name),
+        "uid": format!("kumeo-{}", workflow.name.to_lowercase()),
+        "tags": ["kumeo"],
+        "schemaVersion": 39,
+        "time": { "from": "now-6h", "to": "now" },
+        "templating": {
+            "list": [{ "name": "datasource", "type": "datasource", "query": "prometheus", "label": "Data source" }]
+        },
+        "panels": panels,
+    })
+}
+
+/// Generate the monitoring of a workflow into `kubernetes_dir/monitoring`, if it has a `monitor` block
+pub fn generate_monitoring(workflow: &Workflow, kubernetes_dir: &Path, tera: &Tera, sink: &mut dyn OutputSink) -> Result<()> {
+    let Some(settings) = MonitoringSettings::for_workflow(workflow)? else {
+        return Ok(());
+    };
+    let monitoring_dir = kubernetes_dir.join("monitoring");
+    sink.create_dir(&monitoring_dir)
+        .with_context(|| message!("Failed to create directory: {}", monitoring_dir.display()))?;
+
+    let mut context = create_base_context(&workflow.name);
+    context.insert("monitoring", &settings);
+    context.insert("metadata", &ManifestMetadata::for_workflow(workflow));
+    context.insert("dashboard_label", DASHBOARD_LABEL);
+    let rendered = tera.render(MONITORING_TEMPLATE, &context)
+        .with_context(|| message!("Failed to render the monitoring of workflow {}", workflow.name))?;
+    let output_path = monitoring_dir.join("monitoring.yaml");
+    sink.write(&output_path, rendered.as_bytes())
+        .with_context(|| message!("Failed to write {}", output_path.display()))?;
+
+    let output_path = monitoring_dir.join("dashboard.json");
+    sink.write(&output_path, settings.dashboard.as_bytes())
+        .with_context(|| message!("Failed to write {}", output_path.display()))
+}
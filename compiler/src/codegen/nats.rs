@@ -0,0 +1,186 @@
+//! Reuse of an existing NATS
+//!
+//! By default every generated program brings its own NATS: the Helm chart
+//! pulls the NATS subchart and the combined cluster layout deploys a shared
+//! StatefulSet. With `--external-nats` no NATS is generated at all; agents are
+//! pointed at the given server instead, so several workflows can share one
+//! bus that is operated separately.
+
+use anyhow::{anyhow, Context as _, Result};
+use serde::Serialize;
+use std::fmt;
+use std::io::{BufRead, BufReader};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use url::Url;
+use crate::message;
+
+/// Default client port of a NATS server
+pub const DEFAULT_NATS_PORT: u16 = 4222;
+
+/// Credential variables agents read, with the Secret key they come from
+pub const NATS_CREDENTIAL_KEYS: [(&str, &str); 3] = [
+    ("NATS_USERNAME", "username"),
+    ("NATS_PASSWORD", "password"),
+    ("NATS_TOKEN", "token"),
+];
+
+/// A NATS server running outside of the generated manifests
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExternalNats {
+    /// URL agents connect to, passed as `NATS_URL`
+    pub url: String,
+    /// Secret holding the `username`/`password` or `token` keys, if the server requires them
+    pub credentials_secret: Option<String>,
+    /// Credential variables and their Secret keys, empty without a Secret
+    pub credentials: Vec<NatsCredential>,
+}
+
+/// A credential variable read from the NATS Secret
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NatsCredential {
+    /// Environment variable of the agent
+    pub var: &'static str,
+    /// Key of the Secret; missing keys are skipped
+    pub key: &'static str,
+}
+
+impl ExternalNats {
+    /// Validate the URL of an external NATS and its credentials Secret
+    ///
+    /// Credentials belong in the Secret rather than in the URL, which ends up
+    /// in plain text in every generated manifest.
+    pub fn new(url: &str, credentials_secret: Option<&str>) -> Result<Self> {
+        let parsed = Url::parse(url).with_context(|| message!("Invalid NATS URL: {}", url))?;
+        if !matches!(parsed.scheme(), "nats" | "tls" | "ws" | "wss") {
+            return Err(anyhow!(message!(
+                "Unsupported NATS URL scheme `{}` (expected nats, tls, ws or wss): {}",
+                parsed.scheme(),
+                url
+            )));
+        }
+        if parsed.host_str().is_none_or(str::is_empty) {
+            return Err(anyhow!(message!("NATS URL has no host: {}", url)));
+        }
+        if !parsed.username().is_empty() || parsed.password().is_some() {
+            return Err(anyhow!(message!(
+                "NATS URL must not embed credentials, pass a Secret with --nats-credentials instead: {}",
+                url
+            )));
+        }
+
+        if let Some(secret) = credentials_secret {
+            let valid = !secret.is_empty()
+                && secret.len() <= 253
+                && secret
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.')
+                && !secret.starts_with(['-', '.'])
+                && !secret.ends_with(['-', '.']);
+            if !valid {
+                return Err(anyhow!(message!("Invalid Secret name for NATS credentials: {}", secret)));
+            }
+        }
+
+        let credentials = match credentials_secret {
+            Some(_) => NATS_CREDENTIAL_KEYS
+                .iter()
+                .map(|&(var, key)| NatsCredential { var, key })
+                .collect(),
+            None => Vec::new(),
+        };
+        Ok(Self {
+            url: url.to_string(),
+            credentials_secret: credentials_secret.map(str::to_string),
+            credentials,
+        })
+    }
+
+    /// Host and port the server listens on
+    pub fn address(&self) -> Result<(String, u16)> {
+        let parsed = Url::parse(&self.url)?;
+        let host = parsed
+            .host_str()
+            .ok_or_else(|| anyhow!(message!("NATS URL has no host: {}", self.url)))?
+            .to_string();
+        let port = parsed.port().unwrap_or(match parsed.scheme() {
+            "ws" => 80,
+            "wss" => 443,
+            _ => DEFAULT_NATS_PORT,
+        });
+        Ok((host, port))
+    }
+
+    /// Check that the server is reachable from here
+    ///
+    /// NATS servers greet clients with an `INFO` line, so for `nats://` and
+    /// `tls://` URLs the greeting is read and described. WebSocket URLs are
+    /// only checked for a listening port and give `None`.
+    pub fn probe(&self, timeout: Duration) -> Result<Option<NatsServer>> {
+        let (host, port) = self.address()?;
+        let addresses: Vec<_> = (host.as_str(), port)
+            .to_socket_addrs()
+            .with_context(|| message!("Cannot resolve NATS host {}", host))?
+            .collect();
+
+        let mut last_error = None;
+        for address in addresses {
+            match TcpStream::connect_timeout(&address, timeout) {
+                Ok(stream) => {
+                    if self.url.starts_with("ws") {
+                        return Ok(None);
+                    }
+                    return read_server_info(stream, timeout).map(Some);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) => Err(anyhow!(message!("Cannot connect to NATS at {}:{}: {}", host, port, e))),
+            None => Err(anyhow!(message!("NATS host {} resolves to no address", host))),
+        }
+    }
+}
+
+/// A NATS server as described by its `INFO` greeting
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NatsServer {
+    /// Server name
+    pub name: String,
+    /// Server version
+    pub version: String,
+    /// Whether clients must authenticate
+    pub auth_required: bool,
+}
+
+impl fmt::Display for NatsServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (NATS {})", self.name, self.version)?;
+        if self.auth_required {
+            write!(f, ", requires authentication")?;
+        }
+        Ok(())
+    }
+}
+
+/// Read the `INFO` greeting of a NATS server
+fn read_server_info(stream: TcpStream, timeout: Duration) -> Result<NatsServer> {
+    stream.set_read_timeout(Some(timeout))?;
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .context(message!("NATS server did not send its INFO greeting"))?;
+
+    let info = line
+        .strip_prefix("INFO ")
+        .ok_or_else(|| anyhow!(message!("Not a NATS server, unexpected greeting: {}", line.trim())))?;
+    let info: serde_json::Value =
+        serde_json::from_str(info.trim()).context(message!("Invalid INFO greeting from the NATS server"))?;
+
+    let field = |name: &str| info.get(name).and_then(|value| value.as_str()).unwrap_or("unknown").to_string();
+    Ok(NatsServer {
+        name: field("server_name"),
+        version: field("version"),
+        auth_required: info.get("auth_required").and_then(|value| value.as_bool()) == Some(true),
+    })
+}
@@ -0,0 +1,151 @@
+//! Nomad job specs of the agents
+//!
+//! A workflow whose `deployment` sets `platform: "nomad"` is deployed on
+//! HashiCorp Nomad instead of Kubernetes: each agent gets a job spec in
+//! `agents/<id>/nomad/job.nomad.hcl`, rendered from the same context as its
+//! Kubernetes manifests, and the workflow gets a NATS job in `nomad/` unless
+//! an external NATS was given.
+//!
+//! What Kubernetes reads from Secrets is read from Nomad Variables under
+//! `kumeo/<secret>`, with the same keys, through `template` blocks. Canary and
+//! blue/green rollouts become canary deployments promoted with
+//! `nomad deployment promote`; the analysis of a canary is not run.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+use tera::Tera;
+
+use super::kubernetes::{BatchSettings, FileSettings};
+use super::nats::ExternalNats;
+use super::sink::OutputSink;
+use super::template_processor::create_base_context;
+use crate::ast::{Agent, Workflow};
+use crate::message;
+use crate::stats::agent_requests;
+
+/// Template of the job of an agent
+pub const JOB_TEMPLATE: &str = "nomad/job.nomad.hcl.tera";
+
+/// Template of the NATS job of a workflow
+pub const NATS_TEMPLATE: &str = "nomad/nats.nomad.hcl.tera";
+
+/// Nomad service agents look NATS up by when it is deployed with the workflow
+pub const NATS_SERVICE: &str = "nats";
+
+/// Namespace of the jobs when the deployment names none
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// Datacenters the jobs are placed in: any of the cluster's
+pub const DEFAULT_DATACENTERS: &[&str] = &["*"];
+
+/// Prefix of the Nomad Variables that stand in for Kubernetes Secrets
+pub const VARIABLES_PREFIX: &str = "kumeo";
+
+/// Items of the trusted keys variable, each written to its own key file
+pub const TRUSTED_KEY_ITEMS: &[&str] = &["minisign", "cosign"];
+
+/// Placement and resources of the job of an agent, ready to be injected into its template
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NomadSettings {
+    /// Job type: `service`, or `batch` for agents that exit once their input is consumed
+    pub job_type: &'static str,
+    /// Namespace of the job
+    pub namespace: String,
+    /// Datacenters the job is placed in
+    pub datacenters: Vec<String>,
+    /// Allocations of the agent's group
+    pub count: u32,
+    /// CPU of each allocation, in MHz: a Kubernetes core counts as 1000 MHz
+    pub cpu_mhz: u64,
+    /// Memory of each allocation, in MB
+    pub memory_mb: u64,
+    /// Host directories bind-mounted at the same path, as Docker `volumes`
+    pub host_volumes: Vec<String>,
+    /// Prefix of the variables the agent's secrets are read from
+    pub variables_prefix: &'static str,
+    /// Items of the trusted keys variable, when signed resources are required
+    pub trusted_key_items: &'static [&'static str],
+    /// Service NATS is looked up by, unless NATS is external
+    pub nats_service: Option<&'static str>,
+}
+
+impl NomadSettings {
+    /// Compute the job settings of an agent
+    pub fn for_agent(workflow: &Workflow, agent: &Agent, external_nats: Option<&ExternalNats>) -> Result<Self> {
+        let agent_id = agent.id.as_deref().unwrap_or_default();
+        let deployment = workflow.deployment.as_ref();
+        let batch = BatchSettings::for_agent(workflow, agent_id).is_some();
+        let (cpu_millis, memory_mib) = agent_requests(workflow, agent)?;
+        let host_volumes = FileSettings::for_agent(workflow, agent_id)
+            .map(|files| files.mounts)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|mount| mount.claim.is_none())
+            .map(|mount| format!("{0}:{0}{1}", mount.path, if mount.read_only { ":ro" } else { "" }))
+            .collect();
+        Ok(Self {
+            job_type: if batch { "batch" } else { "service" },
+            namespace: deployment
+                .and_then(|deployment| deployment.namespace.clone())
+                .unwrap_or_else(|| DEFAULT_NAMESPACE.to_string()),
+            datacenters: DEFAULT_DATACENTERS.iter().map(|datacenter| datacenter.to_string()).collect(),
+            count: if batch { 1 } else { deployment.and_then(|deployment| deployment.replicas).unwrap_or(1) },
+            cpu_mhz: cpu_millis,
+            memory_mb: memory_mib,
+            host_volumes,
+            variables_prefix: VARIABLES_PREFIX,
+            trusted_key_items: TRUSTED_KEY_ITEMS,
+            nats_service: external_nats.is_none().then_some(NATS_SERVICE),
+        })
+    }
+}
+
+/// Generate the Nomad job of an agent into `agent_dir`, from the agent's context
+pub fn generate_nomad_job(
+    workflow: &Workflow,
+    agent: &Agent,
+    agent_dir: &Path,
+    context: &tera::Context,
+    tera: &Tera,
+    external_nats: Option<&ExternalNats>,
+    sink: &mut dyn OutputSink,
+) -> Result<()> {
+    let mut context = context.clone();
+    context.insert("nomad", &NomadSettings::for_agent(workflow, agent, external_nats)?);
+    let nomad_dir = agent_dir.join("nomad");
+    sink.create_dir(&nomad_dir)
+        .with_context(|| message!("Failed to create directory: {}", nomad_dir.display()))?;
+
+    let output_path = nomad_dir.join("job.nomad.hcl");
+    let rendered = tera.render(JOB_TEMPLATE, &context)
+        .with_context(|| message!("Failed to render the Nomad job of agent {}", agent.id.as_deref().unwrap_or_default()))?;
+    sink.write(&output_path, rendered.as_bytes())
+        .with_context(|| message!("Failed to write {}", output_path.display()))
+}
+
+/// Generate the workflow-level Nomad jobs into `output_dir/nomad`: NATS, unless it is external
+pub fn generate_nomad_config(
+    workflow: &Workflow,
+    output_dir: &Path,
+    tera: &Tera,
+    external_nats: Option<&ExternalNats>,
+    sink: &mut dyn OutputSink,
+) -> Result<()> {
+    if external_nats.is_some() {
+        return Ok(());
+    }
+    let nomad_dir = output_dir.join("nomad");
+    sink.create_dir(&nomad_dir)
+        .with_context(|| message!("Failed to create directory: {}", nomad_dir.display()))?;
+
+    let namespace = workflow.deployment.as_ref().and_then(|deployment| deployment.namespace.as_deref());
+    let mut context = create_base_context(&workflow.name);
+    context.insert("namespace", namespace.unwrap_or(DEFAULT_NAMESPACE));
+    context.insert("datacenters", DEFAULT_DATACENTERS);
+    context.insert("service", NATS_SERVICE);
+    let output_path = nomad_dir.join("nats.nomad.hcl");
+    let rendered = tera.render(NATS_TEMPLATE, &context).context(message!("Failed to render the NATS job"))?;
+    sink.write(&output_path, rendered.as_bytes())
+        .with_context(|| message!("Failed to write {}", output_path.display()))
+}
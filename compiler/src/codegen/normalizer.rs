@@ -0,0 +1,90 @@
+//! Field mappings and scaling for DataNormalizer agents
+//!
+//! A DataNormalizer with `mappings: { amount: data.payment.amount }` and
+//! `scale: { amount: minmax(min: 0, max: 10000) }` compiles both into
+//! constants of the agent, so it moves and rescales the fields of every
+//! message without interpreting anything at runtime. Mapped values are read
+//! before any is moved; scaled fields that are missing or null are left as
+//! they are, and messages with a scaled field that isn't a number go to the
+//! fallback.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ast::{Agent, AgentType, DataNormalizerConfig};
+use crate::message;
+use super::escape::rust_str_literal;
+
+/// Mappings and scaling of a DataNormalizer agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NormalizerSettings {
+    /// Fields moved, by name of their new field
+    pub mappings: Vec<MappingSettings>,
+    /// Fields rescaled, by name
+    pub scale: Vec<ScaleSettings>,
+}
+
+/// A field a DataNormalizer moves
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MappingSettings {
+    /// Top-level field of the normalized message
+    pub field: String,
+    /// Path it is read from, as written in the DSL
+    pub source: String,
+    /// Rust `&[&str]` of the segments of the path below the message
+    pub rust_path: String,
+}
+
+/// A field a DataNormalizer rescales as `(value - offset) / divisor`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScaleSettings {
+    /// Top-level field of the normalized message
+    pub field: String,
+    /// Name of the scaler, `minmax` or `zscore`
+    pub scaler: String,
+    /// Rust `f64` literal subtracted from the value
+    pub offset: String,
+    /// Rust `f64` literal the difference is divided by
+    pub divisor: String,
+}
+
+/// Rust `&[&str]` of the segments of a path below the message, without its leading `data`
+pub(crate) fn rust_path(path: &[String]) -> String {
+    let segments: Vec<String> = path.iter().skip(1).map(|segment| rust_str_literal(segment)).collect();
+    format!("&[{}]", segments.join(", "))
+}
+
+impl NormalizerSettings {
+    /// Compute the mappings and scaling of a DataNormalizer agent
+    pub fn for_agent(agent: &Agent) -> Result<Option<Self>> {
+        if agent.agent_type != AgentType::DataNormalizer {
+            return Ok(None);
+        }
+        let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+        let config = DataNormalizerConfig::from_agent(agent).map_err(|e| anyhow!(message!("Invalid normalizer {}: {}", agent_id, e)))?;
+
+        let mappings = config
+            .mappings
+            .iter()
+            .map(|mapping| MappingSettings {
+                field: mapping.field.clone(),
+                source: mapping.from.join("."),
+                rust_path: rust_path(&mapping.from),
+            })
+            .collect();
+        let scale = config
+            .scale
+            .iter()
+            .map(|(field, scaler)| {
+                let (offset, divisor) = scaler.offset_and_divisor();
+                ScaleSettings {
+                    field: field.clone(),
+                    scaler: scaler.name().to_string(),
+                    offset: format!("{:?}", offset),
+                    divisor: format!("{:?}", divisor),
+                }
+            })
+            .collect();
+        Ok(Some(Self { mappings, scale }))
+    }
+}
@@ -0,0 +1,123 @@
+//! Tracking of generated output
+//!
+//! `kumeo generate` writes into the same output directory on every run, so an
+//! agent removed from the DSL would leave its project and manifests behind and
+//! a later `kubectl apply` would keep it running. The output manifest records
+//! the files generated for each agent; agents that disappear from the program
+//! are reported as orphans and their files can be pruned.
+
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::ast::Workflow;
+use crate::message;
+use super::sink::OutputSink;
+
+/// Manifest of generated files, stored in the output directory
+pub const MANIFEST_FILE: &str = "kumeo-output.json";
+
+/// Directory of an agent's generated files, relative to the output directory
+pub fn agent_dir(agent_id: &str) -> String {
+    format!("agents/{}", agent_id)
+}
+
+/// Files generated for each agent
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputManifest {
+    /// Generated files keyed by agent ID, relative to the output directory
+    pub agents: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl OutputManifest {
+    /// Read the manifest of an output directory; a missing manifest is empty
+    pub fn load(output_dir: &Path) -> Result<Self> {
+        let path = output_dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| message!("Failed to read output manifest: {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| message!("Invalid output manifest: {}", path.display()))
+    }
+
+    /// Write the manifest into an output directory
+    pub fn save(&self, output_dir: &Path, sink: &mut dyn OutputSink) -> Result<()> {
+        let path = output_dir.join(MANIFEST_FILE);
+        sink.write(&path, serde_json::to_string_pretty(self)?.as_bytes())
+            .with_context(|| message!("Failed to write output manifest: {}", path.display()))
+    }
+
+    /// Record the files just generated for the agents of a workflow
+    ///
+    /// Entries of other agents are kept, so orphans stay flagged until they
+    /// are pruned. The files are those of the sink they were generated through.
+    pub fn record(&mut self, workflow: &Workflow, output_dir: &Path, sink: &dyn OutputSink) -> Result<()> {
+        for agent_id in workflow.agents.iter().filter_map(|agent| agent.id.as_deref()) {
+            let files = sink
+                .files(&output_dir.join(agent_dir(agent_id)))?
+                .iter()
+                .filter_map(|path| path.strip_prefix(output_dir).ok())
+                .map(|relative| relative.to_string_lossy().replace('\\', "/"))
+                .collect();
+            self.agents.insert(agent_id.to_string(), files);
+        }
+        Ok(())
+    }
+
+    /// Recorded agents the workflow no longer declares, with their files
+    pub fn orphans(&self, workflow: &Workflow) -> BTreeMap<&str, &BTreeSet<String>> {
+        let current: BTreeSet<&str> = workflow.agents.iter().filter_map(|agent| agent.id.as_deref()).collect();
+        self.agents
+            .iter()
+            .filter(|(agent_id, _)| !current.contains(agent_id.as_str()))
+            .map(|(agent_id, files)| (agent_id.as_str(), files))
+            .collect()
+    }
+
+    /// Delete the files of orphaned agents and forget them
+    ///
+    /// Only recorded files are deleted, so anything added by hand next to
+    /// them survives along with its directory. Returns the deleted paths.
+    pub fn prune(&mut self, workflow: &Workflow, output_dir: &Path) -> Result<Vec<PathBuf>> {
+        let orphans: Vec<String> = self.orphans(workflow).into_keys().map(str::to_string).collect();
+        let mut deleted = Vec::new();
+
+        for agent_id in orphans {
+            for file in self.agents.remove(&agent_id).unwrap_or_default() {
+                let path = output_dir.join(&file);
+                match std::fs::remove_file(&path) {
+                    Ok(()) => deleted.push(path),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e).with_context(|| message!("Failed to delete {}", path.display())),
+                }
+            }
+            remove_empty_dirs(&output_dir.join(agent_dir(&agent_id)))?;
+        }
+
+        Ok(deleted)
+    }
+}
+
+/// Remove `dir` and its subdirectories if they are empty
+fn remove_empty_dirs(dir: &Path) -> Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| message!("Failed to list {}", dir.display())),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            remove_empty_dirs(&path)?;
+        }
+    }
+
+    if std::fs::read_dir(dir)?.next().is_none() {
+        std::fs::remove_dir(dir).with_context(|| message!("Failed to delete {}", dir.display()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! Data quality monitors
+//!
+//! A `QualityMonitor` agent samples the messages of its input topic, measures
+//! their fields and their conformity to the input schema over windows of
+//! sampled messages, and publishes a report per window on its output topic.
+//! The first window is the baseline the later ones are compared with; the
+//! metrics drifting further than their thresholds are listed in the report.
+//! Everything the monitor does is compiled from its options into constants of
+//! the generated Python agent.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ast::{Agent, AgentType, QualityMonitorConfig};
+use crate::message;
+
+/// Sampling, metrics and thresholds of a quality monitor
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QualitySettings {
+    /// Fraction of the messages sampled
+    pub sample_rate: f64,
+    /// Sampled messages per report
+    pub window: u32,
+    /// Names of the metrics computed
+    pub metrics: Vec<&'static str>,
+    /// The metrics as a Python list literal
+    pub python_metrics: String,
+    /// Drift allowed per metric as a Python dict literal
+    pub python_thresholds: String,
+    /// Fields measured as a Python list literal; empty for every field
+    pub python_fields: String,
+}
+
+impl QualitySettings {
+    /// Compute the settings of an agent that is a quality monitor
+    pub fn for_agent(agent: &Agent) -> Result<Option<Self>> {
+        if agent.agent_type != AgentType::QualityMonitor {
+            return Ok(None);
+        }
+        let config = QualityMonitorConfig::from_agent(agent).map_err(|e| anyhow!(message!("Invalid quality monitor: {}", e)))?;
+
+        let metrics: Vec<&'static str> = config.metrics.iter().map(|metric| metric.as_str()).collect();
+        let thresholds: serde_json::Map<String, serde_json::Value> = config
+            .thresholds
+            .iter()
+            .map(|(metric, threshold)| (metric.as_str().to_string(), serde_json::Value::from(*threshold)))
+            .collect();
+        // JSON lists of strings and objects of numbers are valid Python literals
+        Ok(Some(Self {
+            sample_rate: config.sample_rate,
+            window: config.window,
+            python_metrics: serde_json::to_string(&metrics)?,
+            python_thresholds: serde_json::to_string(&thresholds)?,
+            python_fields: serde_json::to_string(&config.fields)?,
+            metrics,
+        }))
+    }
+}
@@ -0,0 +1,101 @@
+//! Retry and fallback policies of agents
+//!
+//! An agent's `retry:` option is compiled into a retry loop around the
+//! processing of each message, and its `fallback:` option into the branch
+//! taken once every attempt failed. Agents without them make a single
+//! attempt and report the failure, as before.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ast::{Agent, FallbackConfig, RetryPolicy, FALLBACK_OPTION, RETRY_OPTION};
+use crate::message;
+
+/// Retry loop of an agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RetrySettings {
+    /// Attempts in total, the first one included
+    pub max_attempts: u32,
+    /// Delays in milliseconds before the successive retries; the last one repeats
+    pub backoff_ms: Vec<u64>,
+}
+
+impl RetrySettings {
+    /// Compute the retry loop of an agent that declares `retry`
+    pub fn for_agent(agent: &Agent) -> Result<Option<Self>> {
+        let Some(value) = agent.config_value(RETRY_OPTION) else {
+            return Ok(None);
+        };
+        let policy = RetryPolicy::from_value(value).map_err(|e| anyhow!(message!("Invalid retry policy: {}", e)))?;
+        Ok(Some(Self {
+            max_attempts: policy.max_attempts,
+            backoff_ms: policy.backoff_ms,
+        }))
+    }
+}
+
+/// Fallback branch of an agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FallbackSettings {
+    /// `fail`, `skip`, `use_default`, `dead_letter` or `retry_later`
+    pub action: &'static str,
+    /// JSON default result as a Rust string literal, for `use_default`
+    pub rust_default: Option<String>,
+    /// JSON default result as a Python string literal, for `use_default`
+    pub python_default: Option<String>,
+    /// Subject failed messages are published to, for `dead_letter`
+    pub subject: Option<String>,
+    /// Milliseconds before a failed message comes back, for `retry_later`
+    pub delay_ms: Option<u64>,
+    /// Times a message comes back before it fails, for `retry_later`
+    pub max_delays: Option<u32>,
+    /// Seconds the count of a message's delays is kept, for `retry_later`
+    pub delays_ttl_secs: Option<u64>,
+}
+
+/// Shortest time the count of a message's delays is kept, a day
+const MIN_DELAYS_TTL_SECS: u64 = 24 * 60 * 60;
+
+impl FallbackSettings {
+    /// Compute the fallback branch of an agent; without `fallback` failures are reported
+    pub fn for_agent(agent: &Agent) -> Result<Self> {
+        let config = match agent.config_value(FALLBACK_OPTION) {
+            Some(value) => FallbackConfig::from_value(value).map_err(|e| anyhow!(message!("Invalid fallback: {}", e)))?,
+            None => FallbackConfig::Fail,
+        };
+
+        let mut settings = Self {
+            action: "fail",
+            rust_default: None,
+            python_default: None,
+            subject: None,
+            delay_ms: None,
+            max_delays: None,
+            delays_ttl_secs: None,
+        };
+        match config {
+            FallbackConfig::Fail => {}
+            FallbackConfig::Skip => settings.action = "skip",
+            FallbackConfig::UseDefault { default } => {
+                let json = default.to_json().to_string();
+                settings.action = "use_default";
+                settings.rust_default = Some(format!("{:?}", json));
+                // JSON escapes are valid Python escapes
+                settings.python_default = Some(serde_json::to_string(&json)?);
+            }
+            FallbackConfig::DeadLetter { subject } => {
+                settings.action = "dead_letter";
+                settings.subject = Some(subject);
+            }
+            FallbackConfig::RetryLater { after_ms, max_delays } => {
+                // The count has to outlive every delay of the message
+                let delays_secs = after_ms.div_ceil(1000).saturating_mul(u64::from(max_delays) + 1);
+                settings.action = "retry_later";
+                settings.delay_ms = Some(after_ms);
+                settings.max_delays = Some(max_delays);
+                settings.delays_ttl_secs = Some(delays_secs.max(MIN_DELAYS_TTL_SECS));
+            }
+        }
+        Ok(settings)
+    }
+}
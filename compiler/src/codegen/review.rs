@@ -0,0 +1,133 @@
+//! SLAs and timeouts of human reviews
+//!
+//! A `HumanReview` agent submits every message it receives for review,
+//! notifying the `notifications` channels on `kumeo.notifications.<via>`, and
+//! waits up to `timeout` for the decision: approved messages are published
+//! on the agent's output, rejected ones are dropped, and those left without
+//! a decision go to the `on_timeout` subject (`reviews.<agent>.timeout` by
+//! default) as a timeout action.
+//!
+//! An agent with `sla: "4h"` gives every review that long to be
+//! decided. The generated agent hands its timers to the runtime scheduler
+//! when a review is submitted: one per `escalation` step, which notifies the
+//! step's reviewers on `kumeo.notifications.<via>`, and one for the SLA,
+//! which publishes the `on_sla_breach` decision. Deciding the review cancels
+//! the timers left. Members of a `delegation` group decide for the group.
+//!
+//! Reviewers decide through the agent's review API with a token of the
+//! `auth` issuer (or else the `audit` one), and each decision is recorded with their verified identity, the time and the
+//! SHA-256 of the reviewed item, signed with the Ed25519 key of the `audit`
+//! Secret and appended to the workflow's audit subject before it applies.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use super::condition::audit_subject;
+use crate::ast::{Agent, AgentType, HumanReviewConfig, OidcAuthConfig, ReviewAuditConfig, SlaBreachAction, Workflow};
+use crate::message;
+
+/// Secret holding the key decisions are signed with, when `audit` names none
+pub const DEFAULT_AUDIT_SIGNING_SECRET: &str = "kumeo-audit-signing-key";
+
+/// Container port of the review API
+pub const REVIEW_API_PORT: u16 = 8090;
+
+/// SLA, escalation chain and delegation groups of a reviewer, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReviewSettings {
+    /// Seconds a review may stay pending; 0 without an SLA
+    pub sla_secs: u64,
+    /// The `on_sla_breach` decision, as written
+    pub on_sla_breach: &'static str,
+    /// The decision as a `ReviewStatus` variant
+    pub rust_on_sla_breach: &'static str,
+    /// Escalation steps as a Rust slice literal of `Escalation`
+    pub rust_escalation: String,
+    /// Delegation groups as a Rust slice literal of `(group, members)`
+    pub rust_delegation: String,
+    /// Notification channels of the escalation steps and `notifications`, without repetitions
+    pub channels: Vec<String>,
+    /// Seconds a message waits for its review
+    pub timeout_secs: u64,
+    /// Subject the timeout actions are published on
+    pub timeout_subject: String,
+    /// Channels notified when a review is submitted, as a Rust slice literal
+    pub rust_notifications: String,
+    /// Whether the review UI is generated
+    pub ui: bool,
+    /// Subject the signed decisions are appended to
+    pub audit_subject: String,
+    /// OIDC issuer reviewers authenticate with, unless the deployment provides it
+    pub oidc_issuer: Option<String>,
+    /// The issuer as a Rust `Option<&str>` literal
+    pub rust_oidc_issuer: String,
+    /// Audience the reviewers' ID tokens are issued for
+    pub oidc_audience: String,
+    /// Secret holding the signing key
+    pub signing_secret: String,
+    /// Container port of the review API
+    pub api_port: u16,
+    /// Name of the Service exposing the review API
+    pub api_service: String,
+}
+
+impl ReviewSettings {
+    /// Compute the settings of an agent that is a human reviewer
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Result<Option<Self>> {
+        if agent.agent_type != AgentType::HumanReview {
+            return Ok(None);
+        }
+        let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+        let config = HumanReviewConfig::from_agent(agent).map_err(|e| anyhow!(message!("Invalid review settings of {}: {}", agent_id, e)))?;
+        let mut audit = ReviewAuditConfig::from_agent(agent).map_err(|e| anyhow!(message!("Invalid audit of {}: {}", agent_id, e)))?;
+        // Reviewers authenticate with the `auth` issuer and client when there is one
+        if let Some(auth) = OidcAuthConfig::from_agent(agent).map_err(|e| anyhow!(message!("Invalid auth of {}: {}", agent_id, e)))? {
+            audit.issuer = Some(auth.issuer);
+            audit.audience = Some(auth.client_id);
+        }
+
+        // Debug-formatting strings and lists of strings gives valid Rust literals
+        let steps: Vec<String> = config
+            .escalation
+            .iter()
+            .map(|step| {
+                format!(
+                    "Escalation {{ after: Duration::from_secs({}), via: {:?}, notify: &{:?} }}",
+                    step.after_secs, step.via, step.notify
+                )
+            })
+            .collect();
+        let groups: Vec<String> = config
+            .delegation
+            .iter()
+            .map(|(group, members)| format!("({:?}, &{:?})", group, members))
+            .collect();
+        let mut channels: Vec<String> = config.escalation.iter().map(|step| step.via.clone()).collect();
+        channels.extend(config.notifications.iter().cloned());
+        channels.sort();
+        channels.dedup();
+        Ok(Some(Self {
+            sla_secs: config.sla_secs.unwrap_or(0),
+            on_sla_breach: config.on_sla_breach.as_str(),
+            rust_on_sla_breach: match config.on_sla_breach {
+                SlaBreachAction::Approve => "ReviewStatus::Approved",
+                SlaBreachAction::Reject => "ReviewStatus::Rejected",
+                SlaBreachAction::Expire => "ReviewStatus::TimedOut",
+            },
+            rust_escalation: format!("&[{}]", steps.join(", ")),
+            rust_delegation: format!("&[{}]", groups.join(", ")),
+            channels,
+            timeout_secs: config.wait_secs(),
+            timeout_subject: config.on_timeout.clone().unwrap_or_else(|| format!("reviews.{}.timeout", agent_id)),
+            rust_notifications: format!("&{:?}", config.notifications),
+            ui: config.ui,
+            audit_subject: audit_subject(workflow),
+            rust_oidc_issuer: format!("{:?}", audit.issuer.as_deref()),
+            oidc_issuer: audit.issuer,
+            oidc_audience: audit.audience.unwrap_or_else(|| agent_id.to_string()),
+            signing_secret: audit.signing_key.unwrap_or_else(|| DEFAULT_AUDIT_SIGNING_SECRET.to_string()),
+            api_port: REVIEW_API_PORT,
+            api_service: format!("{}-reviews", agent_id),
+        }))
+    }
+}
@@ -0,0 +1,99 @@
+//! Review UIs of human reviewers
+//!
+//! Every `HumanReview` agent gets a small TypeScript web app next to its code,
+//! in `agents/<agent>/ui`: a Node backend that follows the agent's review
+//! subjects to list the pending reviews and proxies reads and decisions to
+//! the agent's review API, and a frontend served by it. Reviewers sign in
+//! with a token of the agent's OIDC issuer, which the backend forwards to the
+//! API untouched, so decisions are verified and audited by the agent as
+//! usual. The app comes with its Dockerfile and Kubernetes manifests.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+use tera::Tera;
+
+use super::kubernetes::{agent_image, workflow_registry, workflow_tag};
+use super::nats::ExternalNats;
+use super::review::ReviewSettings;
+use super::sink::OutputSink;
+use crate::ast::{Agent, Workflow};
+use crate::message;
+
+/// Templates of the review UI, by their name prefix
+pub const REVIEW_UI_TEMPLATES: &str = "agents/typescript/ReviewUI/";
+
+/// Container port of the review UI
+pub const REVIEW_UI_PORT: u16 = 3000;
+
+/// NATS the review UI connects to without an external one
+pub const DEFAULT_NATS_URL: &str = "nats://nats:4222";
+
+/// Review UI of a human reviewer, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReviewUiSettings {
+    /// Name of the Deployment and Service of the UI
+    pub name: String,
+    /// Image of the UI
+    pub image: String,
+    /// NATS the agent publishes its reviews on
+    pub nats_url: String,
+    /// Subjects the reviews are published on while pending
+    pub pending_subject: String,
+    /// Subjects the decisions are published on
+    pub completed_subject: String,
+    /// URL of the agent's review API inside the cluster
+    pub api_url: String,
+    /// Container port of the UI
+    pub port: u16,
+    /// OIDC issuer reviewers sign in with, unless the deployment provides it
+    pub oidc_issuer: Option<String>,
+    /// Client the reviewers' ID tokens are issued for
+    pub oidc_audience: String,
+}
+
+impl ReviewUiSettings {
+    /// Compute the review UI of an agent that is a human reviewer
+    ///
+    /// The UI follows the reviews on the external NATS when there is one.
+    /// Reviewers with `ui: false` have none.
+    pub fn for_agent(workflow: &Workflow, agent: &Agent, external_nats: Option<&ExternalNats>) -> Result<Option<Self>> {
+        let Some(review) = ReviewSettings::for_agent(workflow, agent)?.filter(|review| review.ui) else {
+            return Ok(None);
+        };
+        let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+        let name = format!("{}-ui", agent_id);
+        Ok(Some(Self {
+            image: agent_image(&name, workflow_registry(workflow), workflow_tag(workflow)),
+            name,
+            nats_url: external_nats.map_or_else(|| DEFAULT_NATS_URL.to_string(), |nats| nats.url.clone()),
+            pending_subject: "reviews.*.pending".to_string(),
+            completed_subject: "reviews.*.completed".to_string(),
+            api_url: format!("http://{}", review.api_service),
+            port: REVIEW_UI_PORT,
+            oidc_issuer: review.oidc_issuer,
+            oidc_audience: review.oidc_audience,
+        }))
+    }
+}
+
+/// Generate the review UI of an agent into `ui_dir`
+///
+/// `context` is the agent's context with `review_ui` inserted.
+pub fn generate_review_ui(ui_dir: &Path, context: &tera::Context, tera: &Tera, sink: &mut dyn OutputSink) -> Result<()> {
+    let mut templates: Vec<&str> = tera.get_template_names().filter(|name| name.starts_with(REVIEW_UI_TEMPLATES)).collect();
+    templates.sort_unstable();
+    for template in templates {
+        let relative = template[REVIEW_UI_TEMPLATES.len()..].trim_end_matches(".tera");
+        let output_path = ui_dir.join(relative);
+        if let Some(parent) = output_path.parent() {
+            sink.create_dir(parent)
+                .with_context(|| message!("Failed to create directory: {}", parent.display()))?;
+        }
+        let rendered = tera.render(template, context)
+            .with_context(|| message!("Failed to render the review UI template {}", template))?;
+        sink.write(&output_path, rendered.as_bytes())
+            .with_context(|| message!("Failed to write {}", output_path.display()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,100 @@
+//! Sticky routing for Router agents
+//!
+//! A Router with `strategy: hash(key: data.customer_id)` publishes every
+//! message on one of its topics, picked by a consistent hash of the key:
+//! messages with the same key always land on the same topic, and growing the
+//! topics only moves the keys the new ones take over. The topics are the
+//! `targets` listed, or `partitions` subjects numbered below the agent's
+//! output topic (`<output>.0`, `<output>.1`, ...).
+//!
+//! A Router with `rules: resource("s3://config/routes.yaml")` reads its
+//! routing table from the resource instead of a file baked into its image,
+//! and watches it through the runtime, so the routes change without
+//! redeploying.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ast::{Agent, AgentType, HashRoutingConfig, RouteTableConfig, Workflow, STRATEGY_OPTION};
+use crate::message;
+
+/// Sticky routing of a Router agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RoutingSettings {
+    /// Path of the key, as written
+    pub key: String,
+    /// The key's path segments below `data` as a Rust array literal
+    pub rust_key: String,
+    /// Topics the keys are spread over, in order
+    pub topics: Vec<String>,
+    /// The topics as a Rust slice literal
+    pub rust_topics: String,
+}
+
+impl RoutingSettings {
+    /// Compute the sticky routing of a Router agent, if it has a `strategy:` option
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Result<Option<Self>> {
+        let Some(value) = agent.config_value(STRATEGY_OPTION) else {
+            return Ok(None);
+        };
+        let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+        if agent.agent_type != AgentType::Router {
+            return Err(anyhow!(message!("Only Router agents have a routing strategy, {} is a {}", agent_id, agent.agent_type)));
+        }
+        let config = HashRoutingConfig::from_value(value).map_err(|e| anyhow!(message!("Invalid strategy of {}: {}", agent_id, e)))?;
+
+        let topics = if config.targets.is_empty() {
+            let output = workflow
+                .agents
+                .iter()
+                .zip(workflow.deployed_topics())
+                .find(|(other, _)| other.id == agent.id)
+                .and_then(|(_, topics)| topics.output)
+                .ok_or_else(|| anyhow!(message!("{} partitions its output topic, but has none", agent_id)))?;
+            (0..config.partitions).map(|partition| format!("{}.{}", output, partition)).collect()
+        } else {
+            config.targets
+        };
+
+        // Debug-formatting lists of strings gives valid Rust literals
+        let segments = &config.key[1..];
+        Ok(Some(Self {
+            key: config.key.join("."),
+            rust_key: format!("{:?}", segments),
+            rust_topics: format!("&{:?}", topics),
+            topics,
+        }))
+    }
+}
+
+/// Routing table resource of a Router agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RouteTableSettings {
+    /// URI of the table
+    pub source: String,
+    /// The URI as a Rust string literal
+    pub rust_source: String,
+    /// Whether the table is YAML rather than JSON
+    pub yaml: bool,
+    /// Seconds between reloads of the table
+    pub reload_secs: u64,
+}
+
+impl RouteTableSettings {
+    /// Compute the routing table of a Router agent, if its `rules:` name a resource
+    pub fn for_agent(agent: &Agent) -> Result<Option<Self>> {
+        if agent.agent_type != AgentType::Router {
+            return Ok(None);
+        }
+        let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+        let Some(config) = RouteTableConfig::from_agent(agent).map_err(|e| anyhow!(message!("Invalid rules of {}: {}", agent_id, e)))? else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            rust_source: format!("{:?}", config.source),
+            source: config.source,
+            yaml: config.yaml,
+            reload_secs: RouteTableConfig::DEFAULT_RELOAD_SECS,
+        }))
+    }
+}
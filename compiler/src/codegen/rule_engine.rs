@@ -0,0 +1,78 @@
+//! Rule evaluation for RuleEngine agents
+//!
+//! A RuleEngine with `rules: [high_risk: data.score > 0.9 => set(risk: "high")]`
+//! compiles every condition into Rust, like `when` conditions, so the agent
+//! checks each message against all of them without interpreting anything at
+//! runtime. The rules a message matches act on it in order; a `drop()` skips
+//! the ones after it.
+//!
+//! Every hit is counted per rule in `kumeo_rule_hits_total`, served on the
+//! agent's `/metrics` along with the messages evaluated, so the rules that
+//! never fire or fire on everything stand out.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ast::{Agent, AgentType, RuleAction, RuleEngineConfig, Value};
+use crate::message;
+use super::condition::rust_condition;
+
+/// Rules of a RuleEngine agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuleEngineSettings {
+    /// The rules, in evaluation order
+    pub rules: Vec<RuleSettings>,
+}
+
+/// A rule of a RuleEngine agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuleSettings {
+    /// Name of the rule, labelling its hits
+    pub name: String,
+    /// The condition as written in the DSL
+    pub source: String,
+    /// Rust `bool` expression over `data: &serde_json::Value`
+    pub rust: String,
+    /// Name of the action, `set`, `publish` or `drop`
+    pub action: String,
+    /// Fields a `set` writes, as a JSON object
+    pub fields: Option<String>,
+    /// Topic a `publish` sends a copy of the message to
+    pub topic: Option<String>,
+}
+
+impl RuleEngineSettings {
+    /// Compute the rules of a RuleEngine agent
+    pub fn for_agent(agent: &Agent) -> Result<Option<Self>> {
+        if agent.agent_type != AgentType::RuleEngine {
+            return Ok(None);
+        }
+        let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+        let config = RuleEngineConfig::from_agent(agent).map_err(|e| anyhow!(message!("Invalid rules of {}: {}", agent_id, e)))?;
+
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| {
+                let (fields, topic) = match &rule.then {
+                    RuleAction::Set(fields) => {
+                        let fields: serde_json::Map<String, serde_json::Value> =
+                            fields.iter().map(|(name, value)| (name.clone(), Value::to_json(value))).collect();
+                        (Some(serde_json::Value::Object(fields).to_string()), None)
+                    }
+                    RuleAction::Publish(topic) => (None, Some(topic.clone())),
+                    RuleAction::Drop => (None, None),
+                };
+                RuleSettings {
+                    name: rule.name.clone(),
+                    source: rule.when.to_string(),
+                    rust: rust_condition(&rule.when),
+                    action: rule.then.name().to_string(),
+                    fields,
+                    topic,
+                }
+            })
+            .collect();
+        Ok(Some(Self { rules }))
+    }
+}
@@ -0,0 +1,75 @@
+//! Sagas: undoing the steps of a workflow when a later one fails
+//!
+//! An agent with `compensate: NATS("orders.cancel")` is a step of its
+//! workflow's saga. The messages of a saga share a correlation ID, the
+//! `correlation` field of the messages (`data.correlation_id` unless given):
+//! every step records the messages it processes under it in the state store
+//! of its runtime, and any agent of the workflow that fails a message for good
+//! publishes the messages recorded before it on their compensation subjects,
+//! the latest first, before applying its fallback.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ast::{Agent, CompensationConfig, Workflow};
+use crate::message;
+use super::agent::agent_language;
+
+/// Saga of the workflow of an agent, ready to be injected into its templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SagaSettings {
+    /// Path of the correlation ID, as written
+    pub correlation: String,
+    /// The correlation ID's path segments below `data` as a Rust array literal
+    pub rust_correlation: String,
+    /// Subject undoing the agent's own work, if it is a step of the saga
+    pub subject: Option<String>,
+    /// Prefix of the state keys of the workflow's sagas
+    pub key_prefix: String,
+    /// Seconds the steps of a saga are kept after the last one
+    pub ttl_secs: u64,
+}
+
+impl SagaSettings {
+    /// Compute the saga an agent takes part in, if any agent of its workflow has a `compensate:` option
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Result<Option<Self>> {
+        let mut correlation: Option<Vec<String>> = None;
+        let mut subject = None;
+        for step in &workflow.agents {
+            let step_id = step.id.as_deref().unwrap_or("<unnamed>");
+            let Some(config) = CompensationConfig::from_agent(step).map_err(|e| anyhow!(message!("Invalid compensation of {}: {}", step_id, e)))? else {
+                continue;
+            };
+            if agent_language(step) != "rust" {
+                return Err(anyhow!(message!("{} can't compensate, {} agents don't take part in sagas", step_id, step.agent_type)));
+            }
+            match &correlation {
+                Some(path) if *path != config.correlation => {
+                    return Err(anyhow!(message!(
+                        "The saga steps of {} disagree on the correlation ID: {} and {}",
+                        workflow.name,
+                        path.join("."),
+                        config.correlation.join(".")
+                    )))
+                }
+                _ => correlation = Some(config.correlation),
+            }
+            if step.id == agent.id {
+                subject = Some(config.subject);
+            }
+        }
+        let Some(correlation) = correlation else {
+            return Ok(None);
+        };
+
+        // Debug-formatting a list of strings gives a valid Rust array literal
+        let segments = &correlation[1..];
+        Ok(Some(Self {
+            correlation: correlation.join("."),
+            rust_correlation: format!("{:?}", segments),
+            subject,
+            key_prefix: format!("saga.{}", workflow.name),
+            ttl_secs: CompensationConfig::TTL_SECS,
+        }))
+    }
+}
@@ -0,0 +1,144 @@
+//! Unit-test scaffolds of the generated agents
+//!
+//! Every agent comes with tests to start from: `src/tests.rs` for Rust
+//! agents, a `#[cfg(test)]` module of the crate, and `tests/test_agent.py`
+//! for Python agents, run with pytest. They create the agent with a runtime
+//! client that talks to no broker, the Python one recording what the agent
+//! publishes, and check a golden-path message.
+//!
+//! The message is the one of a `test` block of the workflow the agent
+//! processes, or else one with the required fields of its input schema. It
+//! is simulated as the generated code would handle it; if the agent's `when`
+//! condition or `assert` invariants turn it away, the golden-path test is
+//! generated skipped, with the reason, until the message is fixed.
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use serde_json::{json, Map, Value as Json};
+use std::path::Path;
+use tera::Tera;
+
+use super::agent::agent_language;
+use super::sink::OutputSink;
+use super::template_processor::render_template;
+use crate::ast::{Agent, Schema, Value, Workflow, EXPECT_OUTCOME};
+use crate::i18n::Message;
+use crate::message;
+use crate::simulator::{deliver, Outcome};
+
+/// Golden-path test of an agent, ready to be injected into its test template
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TestScaffoldSettings {
+    /// The message, as JSON
+    pub message: String,
+    /// The workflow test the message comes from, if any
+    pub test: Option<String>,
+    /// Whether the agent processes the message
+    pub processed: bool,
+    /// Why it doesn't, if it doesn't
+    pub reason: Option<String>,
+    /// Topic the agent consumes
+    pub input_topic: String,
+    /// Topic the agent publishes its results on
+    pub output_topic: String,
+}
+
+impl TestScaffoldSettings {
+    /// Compute the golden-path test of an agent
+    pub fn for_agent(workflow: &Workflow, agent: &Agent) -> Result<Self> {
+        let agent_id = agent.id.as_deref().ok_or_else(|| anyhow!(message!("Agent must have an ID")))?;
+        let topics = workflow
+            .agents
+            .iter()
+            .zip(workflow.deployed_topics())
+            .find_map(|(other, topics)| (other.id.as_deref() == Some(agent_id)).then_some(topics));
+        let schema = agent.input_schema().map_err(|e| anyhow!(message!("Invalid input schema: {}", e)))?;
+        let Some(topics) = topics else {
+            // Not part of the workflow, so it can't be simulated
+            return Ok(Self {
+                message: sample_message(schema.as_ref()).to_string(),
+                test: None,
+                processed: true,
+                reason: None,
+                input_topic: format!("{}.input", agent_id),
+                output_topic: format!("{}.output", agent_id),
+            });
+        };
+
+        // A workflow test the agent passes is the best golden path
+        let mut candidates = Vec::new();
+        for test in workflow.tests.iter().filter(|test| test.agent == agent_id) {
+            let processed = match test.expect.get(EXPECT_OUTCOME) {
+                Some(Value::String(outcome)) => outcome == Outcome::Processed.as_str(),
+                _ => true,
+            };
+            if processed {
+                candidates.push((Some(test.name.clone()), test.given.to_json()));
+            }
+        }
+        candidates.push((None, sample_message(schema.as_ref())));
+
+        let mut simulated = Vec::new();
+        for (test, message) in candidates {
+            let delivery = deliver(workflow, agent_id, &message)?;
+            simulated.push((test, message, delivery));
+        }
+        let index = simulated.iter().position(|(_, _, delivery)| delivery.outcome == Outcome::Processed);
+        let (test, message, delivery) = simulated.swap_remove(index.unwrap_or(simulated.len() - 1));
+
+        Ok(Self {
+            message: message.to_string(),
+            test,
+            processed: delivery.outcome == Outcome::Processed,
+            reason: delivery.reason.as_ref().map(Message::written),
+            input_topic: topics.input.unwrap_or_else(|| format!("{}.input", agent_id)),
+            output_topic: topics.output.unwrap_or_else(|| format!("{}.output", agent_id)),
+        })
+    }
+}
+
+/// A message with a value for every required field of a schema
+fn sample_message(schema: Option<&Schema>) -> Json {
+    let mut message = Map::new();
+    let Some(schema) = schema else {
+        return Json::Object(message);
+    };
+    for (field, field_type) in &schema.fields {
+        let value = match field_type.as_str() {
+            "string" => json!("example"),
+            "number" | "integer" => json!(1),
+            "boolean" => json!(true),
+            "object" => json!({}),
+            "array" => json!([]),
+            // Optional fields are left out
+            _ => continue,
+        };
+        message.insert(field.clone(), value);
+    }
+    Json::Object(message)
+}
+
+/// Generate the test scaffold of an agent into its directory
+pub fn generate_test_scaffold(
+    workflow: &Workflow,
+    agent: &Agent,
+    agent_dir: &Path,
+    context: &tera::Context,
+    tera: &Tera,
+    sink: &mut dyn OutputSink,
+) -> Result<()> {
+    let mut context = context.clone();
+    context.insert("scaffold", &TestScaffoldSettings::for_agent(workflow, agent)?);
+
+    let (template, output_path) = match agent_language(agent) {
+        "python" => ("agents/python/tests/test_agent.py.tera", agent_dir.join("tests/test_agent.py")),
+        _ => ("agents/rust/src/tests.rs.tera", agent_dir.join("src/tests.rs")),
+    };
+    let rendered = render_template(tera, template, &context)?;
+    if let Some(dir) = output_path.parent() {
+        sink.create_dir(dir)
+            .with_context(|| message!("Failed to create directory: {}", dir.display()))?;
+    }
+    sink.write(&output_path, rendered.as_bytes())
+        .with_context(|| message!("Failed to write {}", output_path.display()))
+}
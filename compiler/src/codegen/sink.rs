@@ -0,0 +1,168 @@
+//! Destinations of generated files
+//!
+//! Code generation writes through an [`OutputSink`] instead of `std::fs`, so
+//! the same run either writes the project ([`FsSink`]) or only plans it
+//! ([`PlanSink`]). A plan keeps every file in memory and compares it with the
+//! one already on disk, which is how `kumeo generate --dry-run` shows what a
+//! generation would create and overwrite without touching the filesystem.
+
+use anyhow::{Context as _, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::formatter;
+use crate::message;
+
+/// Where generated files are written
+pub trait OutputSink {
+    /// Create a directory and its parents
+    fn create_dir(&mut self, path: &Path) -> Result<()>;
+
+    /// Write a file, creating its directory
+    fn write(&mut self, path: &Path, contents: &[u8]) -> Result<()>;
+
+    /// Whether a file or directory exists, on disk or written through the sink
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Files below a directory, on disk or written through the sink
+    fn files(&self, dir: &Path) -> Result<BTreeSet<PathBuf>>;
+
+    /// Copy a file from disk, creating the directory of the copy
+    fn copy(&mut self, from: &Path, to: &Path) -> Result<()> {
+        let contents = std::fs::read(from).with_context(|| message!("Failed to read {}", from.display()))?;
+        self.write(to, &contents)
+    }
+}
+
+/// Sink writing to the filesystem
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsSink;
+
+impl OutputSink for FsSink {
+    fn create_dir(&mut self, path: &Path) -> Result<()> {
+        std::fs::create_dir_all(path).with_context(|| message!("Failed to create directory: {}", path.display()))
+    }
+
+    fn write(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            self.create_dir(parent)?;
+        }
+        std::fs::write(path, contents).with_context(|| message!("Failed to write {}", path.display()))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn files(&self, dir: &Path) -> Result<BTreeSet<PathBuf>> {
+        files_on_disk(dir)
+    }
+}
+
+/// What writing a planned file would do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// The file doesn't exist yet
+    Create,
+    /// The file exists with other contents; holds the diff from them
+    Overwrite(String),
+    /// The file exists with the same contents
+    Unchanged,
+}
+
+/// A file a plan would write
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedFile {
+    /// Path of the file
+    pub path: PathBuf,
+    /// What writing it would do
+    pub change: Change,
+}
+
+/// Sink recording the files a generation would write, without writing them
+#[derive(Debug, Clone, Default)]
+pub struct PlanSink {
+    /// Contents of each written file, the last write winning
+    files: BTreeMap<PathBuf, Vec<u8>>,
+    /// Directories created
+    dirs: BTreeSet<PathBuf>,
+}
+
+impl PlanSink {
+    /// An empty plan
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Contents planned for a file, if it is written
+    pub fn contents(&self, path: &Path) -> Option<&[u8]> {
+        self.files.get(path).map(Vec::as_slice)
+    }
+
+    /// The planned files, in path order, compared with the files on disk
+    pub fn plan(&self) -> Result<Vec<PlannedFile>> {
+        self.files
+            .iter()
+            .map(|(path, contents)| {
+                let change = match std::fs::read(path) {
+                    Ok(existing) if existing == *contents => Change::Unchanged,
+                    Ok(existing) => Change::Overwrite(formatter::diff(
+                        &String::from_utf8_lossy(&existing),
+                        &String::from_utf8_lossy(contents),
+                    )),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Change::Create,
+                    Err(e) => return Err(e).with_context(|| message!("Failed to read {}", path.display())),
+                };
+                Ok(PlannedFile { path: path.clone(), change })
+            })
+            .collect()
+    }
+}
+
+impl OutputSink for PlanSink {
+    fn create_dir(&mut self, path: &Path) -> Result<()> {
+        self.dirs.insert(path.to_path_buf());
+        Ok(())
+    }
+
+    fn write(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+        self.files.insert(path.to_path_buf(), contents.to_vec());
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+            || self.dirs.contains(path)
+            || self.files.keys().any(|file| file.starts_with(path))
+    }
+
+    fn files(&self, dir: &Path) -> Result<BTreeSet<PathBuf>> {
+        let mut files = files_on_disk(dir)?;
+        files.extend(self.files.keys().filter(|file| file.starts_with(dir)).cloned());
+        Ok(files)
+    }
+}
+
+/// Files below a directory on disk; none if it doesn't exist
+fn files_on_disk(dir: &Path) -> Result<BTreeSet<PathBuf>> {
+    let mut files = BTreeSet::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| message!("Failed to list {}", dir.display())),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                files.insert(path);
+            }
+        }
+    }
+
+    Ok(files)
+}
@@ -0,0 +1,179 @@
+//! Expansion of subworkflow invocations
+//!
+//! A `use Enrich(input: ..., output: ...)` step in a workflow's agent list is
+//! replaced by the agents of the subworkflow before anything is generated.
+//! The agents are renamed after the call and chained through subjects
+//! namespaced under the workflow and the call, so one subworkflow can be
+//! invoked several times, or by several workflows, without clashing. The
+//! `{name}` references to the subworkflow inputs and outputs in the agents'
+//! configuration are replaced with the subjects the invocation binds.
+//!
+//! A subworkflow no workflow invokes is generated on its own, as a workflow
+//! invoking it with subjects named after its inputs and outputs.
+
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{
+    Agent, Argument, Program, Source, Span, Subworkflow, SubworkflowCall, Target, Value, Workflow, WorkflowMode,
+    INPUT_TOPIC_OPTION, OUTPUT_TOPIC_OPTION,
+};
+use crate::message;
+
+/// Replace the subworkflow invocations of a workflow with the agents they run
+///
+/// The first agent of an invocation reads the bound inputs and the last one
+/// writes the bound outputs; agents only consume and produce one subject, so
+/// extra bound subjects are only passed along in their configuration. In
+/// between, each agent publishes on `<workflow>.<call>.<agent>`.
+pub fn expand(workflow: &Workflow, subworkflows: &[Subworkflow]) -> Result<Workflow> {
+    let mut expanded = workflow.clone();
+    if workflow.calls.is_empty() {
+        return Ok(expanded);
+    }
+    expanded.calls.clear();
+    expanded.agents.clear();
+
+    let mut occurrences: HashMap<&str, usize> = HashMap::new();
+    let mut calls = workflow.calls.iter().peekable();
+    for position in 0..=workflow.agents.len() {
+        while let Some(call) = calls.next_if(|call| call.position <= position) {
+            let occurrence = occurrences.entry(call.name.as_str()).or_default();
+            *occurrence += 1;
+            let subworkflow = subworkflows
+                .iter()
+                .find(|subworkflow| subworkflow.name == call.name)
+                .ok_or_else(|| anyhow!(message!("Subworkflow not found: {}", call.name)))?;
+            expanded.agents.extend(expand_call(workflow, call, *occurrence, subworkflow)?);
+        }
+        if let Some(agent) = workflow.agents.get(position) {
+            expanded.agents.push(agent.clone());
+        }
+    }
+
+    let mut ids = HashSet::new();
+    for id in expanded.agents.iter().filter_map(|agent| agent.id.as_deref()) {
+        if !ids.insert(id) {
+            return Err(anyhow!(message!(
+                "Agent ID {} of workflow {} clashes with an agent of an invoked subworkflow",
+                id,
+                workflow.name
+            )));
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// The subworkflows of a program no workflow invokes, as workflows of their own
+pub fn standalone(program: &Program) -> Vec<Workflow> {
+    let invoked: HashSet<&str> = program
+        .workflows
+        .iter()
+        .flat_map(|workflow| &workflow.calls)
+        .map(|call| call.name.as_str())
+        .collect();
+    program
+        .subworkflows
+        .iter()
+        .filter(|subworkflow| !invoked.contains(subworkflow.name.as_str()))
+        .map(standalone_workflow)
+        .collect()
+}
+
+/// A workflow invoking a subworkflow once
+///
+/// Each input and output is bound to the NATS subject of the same name; the
+/// workflow reads the first input and writes the first output.
+fn standalone_workflow(subworkflow: &Subworkflow) -> Workflow {
+    let input = subworkflow.input.clone().unwrap_or_default();
+    let output = subworkflow.output.clone().unwrap_or_default();
+    Workflow {
+        name: subworkflow.name.clone(),
+        source: input.first().map(|subject| Source::NATS(subject.clone(), None)),
+        target: output.first().map(|subject| Target::NATS(subject.clone(), None)),
+        context: subworkflow.context.clone(),
+        preprocessors: None,
+        agents: Vec::new(),
+        monitor: None,
+        deployment: None,
+        version: None,
+        doc: None,
+        mode: WorkflowMode::Stream,
+        calls: vec![SubworkflowCall { name: subworkflow.name.clone(), input, output, position: 0 }],
+        tests: Vec::new(),
+        span: subworkflow.span.clone(),
+    }
+}
+
+/// Agents of one invocation, renamed and chained
+fn expand_call(
+    workflow: &Workflow,
+    call: &SubworkflowCall,
+    occurrence: usize,
+    subworkflow: &Subworkflow,
+) -> Result<Vec<Agent>> {
+    // Later invocations of the same subworkflow are numbered from 2
+    let prefix = match occurrence {
+        1 => call.name.to_lowercase(),
+        n => format!("{}_{}", call.name.to_lowercase(), n),
+    };
+    let namespace = format!("{}.{}", workflow.name.to_lowercase(), prefix);
+
+    // `{name}` in the agents stands for the subject bound to an input or output
+    let parameters: HashMap<&str, &str> = [(&subworkflow.input, &call.input), (&subworkflow.output, &call.output)]
+        .into_iter()
+        .flat_map(|(names, bound)| names.iter().flatten().map(String::as_str).zip(bound.iter().map(String::as_str)))
+        .collect();
+
+    let last = subworkflow.agents.len().saturating_sub(1);
+    let mut previous = None;
+    let mut agents = Vec::new();
+    for (index, agent) in subworkflow.agents.iter().enumerate() {
+        let id = agent
+            .id
+            .as_deref()
+            .ok_or_else(|| anyhow!(message!("Agent {} of subworkflow {} must have an ID", index + 1, call.name)))?;
+
+        let input = match previous {
+            None => subjects(&call.input),
+            Some(previous) => Value::String(format!("{}.{}", namespace, previous)),
+        };
+        let output = if index == last {
+            subjects(&call.output)
+        } else {
+            Value::String(format!("{}.{}", namespace, id))
+        };
+
+        let mut agent = agent.clone();
+        for argument in &mut agent.config {
+            let (Argument::Named(_, value, _) | Argument::Positional(value, _)) = argument;
+            value.interpolate(&|name| parameters.get(name).map(|subject| subject.to_string())).map_err(|name| {
+                anyhow!(message!(
+                    "Parameter {{{}}} of subworkflow {} is not bound by its invocation in workflow {}",
+                    name,
+                    call.name,
+                    workflow.name
+                ))
+            })?;
+        }
+        agent.id = Some(format!("{}_{}", prefix, id));
+        agent.config.retain(|argument| {
+            !matches!(argument, Argument::Named(key, ..) if key == INPUT_TOPIC_OPTION || key == OUTPUT_TOPIC_OPTION)
+        });
+        agent.config.push(Argument::Named(INPUT_TOPIC_OPTION.to_string(), input, Span::default()));
+        agent.config.push(Argument::Named(OUTPUT_TOPIC_OPTION.to_string(), output, Span::default()));
+        agents.push(agent);
+        previous = Some(id);
+    }
+
+    Ok(agents)
+}
+
+/// A single subject, or the list when several are bound
+fn subjects(bound: &[String]) -> Value {
+    match bound {
+        [subject] => Value::String(subject.clone()),
+        _ => Value::Array(bound.iter().cloned().map(Value::String).collect()),
+    }
+}
@@ -3,9 +3,12 @@
 use anyhow::Result;
 use std::path::Path;
 use tera::Tera;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet};
 
 use crate::ast::{Workflow, Agent, AgentType};
+use super::agent::agent_language;
+use super::kubernetes::{workflow_namespace, BlueGreenSettings};
+use super::sink::OutputSink;
 use super::template_processor::create_base_context;
 
 /// Generate Taskfile and related task configurations
@@ -13,18 +16,25 @@ pub fn generate_taskfiles(
     workflow: &Workflow,
     output_dir: &Path,
     tera: &Tera,
+    sink: &mut dyn OutputSink,
 ) -> Result<()> {
     let tasks_dir = output_dir.join("tasks");
-    std::fs::create_dir_all(&tasks_dir)?;
+    sink.create_dir(&tasks_dir)?;
     
-    // Group agents by language and type
-    let mut agents_by_lang: HashMap<String, Vec<&Agent>> = HashMap::new();
-    let mut agent_types = HashSet::new();
+    // Group agents by language and type, in a stable order so the output doesn't change between runs
+    let mut agents_by_lang: BTreeMap<String, Vec<&Agent>> = BTreeMap::new();
+    let mut agent_types = BTreeSet::new();
     
     for agent in &workflow.agents {
         let lang = match agent.agent_type {
-            AgentType::LLM | AgentType::MLModel => "python",
-            AgentType::DataProcessor | AgentType::Router => "rust",
+            AgentType::LLM | AgentType::MLModel | AgentType::QualityMonitor | AgentType::BayesianNetwork => "python",
+            AgentType::DataProcessor
+            | AgentType::Router
+            | AgentType::Aggregator
+            | AgentType::RuleEngine
+            | AgentType::DataNormalizer
+            | AgentType::MissingValueHandler => "rust",
+            AgentType::Custom => agent_language(agent),
             _ => "other",
         }.to_string();
         
@@ -36,19 +46,28 @@ pub fn generate_taskfiles(
     let mut context = create_base_context(&workflow.name);
     context.insert("workflow", workflow);
     context.insert("agent_types", &agent_types);
+    context.insert("blue_green", &BlueGreenSettings::for_workflow(workflow));
+    context.insert("namespace", workflow_namespace(workflow));
+    for lang in ["rust", "python"] {
+        let ids: Vec<&String> = agents_by_lang
+            .get(lang)
+            .map(|agents| agents.iter().filter_map(|agent| agent.id.as_ref()).collect())
+            .unwrap_or_default();
+        context.insert(format!("{}_agents", lang), &ids);
+    }
     
     // Generate main Taskfile
     let taskfile_path = output_dir.join("Taskfile.yml");
     if let Some(template) = tera.get_template_names().find(|&name| name == "Taskfile.yml.tera") {
         if let Ok(rendered) = tera.render(template, &context) {
-            std::fs::write(&taskfile_path, rendered).ok();
+            sink.write(&taskfile_path, rendered.as_bytes()).ok();
         }
     }
     
     // Generate language-specific task files
     for (lang, agents) in agents_by_lang {
         let lang_dir = tasks_dir.join(&lang);
-        if std::fs::create_dir_all(&lang_dir).is_err() {
+        if sink.create_dir(&lang_dir).is_err() {
             continue;
         }
         
@@ -59,7 +78,7 @@ pub fn generate_taskfiles(
         let tasks_file = lang_dir.join("tasks.yml");
         if let Some(template) = tera.get_template_names().find(|&name| name == "tasks/tasks.yml.tera") {
             if let Ok(rendered) = tera.render(template, &lang_context) {
-                std::fs::write(&tasks_file, rendered).ok();
+                sink.write(&tasks_file, rendered.as_bytes()).ok();
             }
         }
     }
@@ -73,23 +92,24 @@ fn generate_language_tasks(
     tasks_dir: &Path,
     context: &tera::Context,
     tera: &Tera,
+    sink: &mut dyn OutputSink,
 ) -> Result<()> {
     // Generate Rust tasks if there are Rust agents
-    if workflow.agents.iter().any(|a| !matches!(a.agent_type, AgentType::MLModel)) {
+    if workflow.agents.iter().any(|a| !matches!(a.agent_type, AgentType::MLModel | AgentType::QualityMonitor)) {
         let rust_tasks_dir = tasks_dir.join("rust");
-        if std::fs::create_dir_all(&rust_tasks_dir).is_ok() {
+        if sink.create_dir(&rust_tasks_dir).is_ok() {
             if let Ok(rendered) = tera.render("tasks/rust/tasks.yml.tera", context) {
-                std::fs::write(rust_tasks_dir.join("tasks.yml"), rendered).ok();
+                sink.write(&rust_tasks_dir.join("tasks.yml"), rendered.as_bytes()).ok();
             }
         }
     }
     
     // Generate Python tasks if there are Python agents
-    if workflow.agents.iter().any(|a| matches!(a.agent_type, AgentType::MLModel)) {
+    if workflow.agents.iter().any(|a| matches!(a.agent_type, AgentType::MLModel | AgentType::QualityMonitor)) {
         let python_tasks_dir = tasks_dir.join("python");
-        if std::fs::create_dir_all(&python_tasks_dir).is_ok() {
+        if sink.create_dir(&python_tasks_dir).is_ok() {
             if let Ok(rendered) = tera.render("tasks/python/tasks.yml.tera", context) {
-                std::fs::write(python_tasks_dir.join("tasks.yml"), rendered).ok();
+                sink.write(&python_tasks_dir.join("tasks.yml"), rendered.as_bytes()).ok();
             }
         }
     }
@@ -0,0 +1,215 @@
+//! Layered lookup of the templates
+//!
+//! Templates are resolved through a chain of directories laid out like the
+//! built-in templates: the project's `templates` directory, then the user's
+//! `~/.config/kumeo/templates`, then the built-in templates, embedded in the
+//! compiler when it is built so it finds them wherever it runs.
+//! The first layer holding a template wins, so a project can replace a
+//! template for itself and a user for all their projects, while the rest keep
+//! coming from the layers below.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tera::Tera;
+
+use super::escape::register_filters;
+use super::template_processor::add_overrides;
+use crate::message;
+
+/// The built-in templates, by their path below `compiler/templates`
+pub const BUILTIN_TEMPLATES: &[(&str, &str)] = include!(concat!(env!("OUT_DIR"), "/builtin_templates.rs"));
+
+/// Directory of a project's templates when its manifest names none
+pub const PROJECT_TEMPLATES: &str = "templates";
+
+/// Where a layer of templates comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateSource {
+    /// The project's templates
+    Project,
+    /// The user's templates, shared by their projects
+    User,
+    /// The templates embedded in the compiler
+    Builtin,
+}
+
+impl fmt::Display for TemplateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Project => "project",
+            Self::User => "user",
+            Self::Builtin => "built-in",
+        })
+    }
+}
+
+/// A directory of templates in the chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateLayer {
+    /// Where the layer comes from
+    pub source: TemplateSource,
+    /// Directory of its templates, laid out like the built-in ones; `None`
+    /// for the templates embedded in the compiler
+    pub dir: Option<PathBuf>,
+}
+
+impl fmt::Display for TemplateLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.dir {
+            Some(dir) => write!(f, "{}", dir.display()),
+            None => f.write_str("(embedded)"),
+        }
+    }
+}
+
+/// A template name and the layer it is resolved from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResolvedTemplate {
+    /// Path of the template below its layer, such as `agents/rust/LLM/src/agent.rs.tera`
+    pub name: String,
+    /// The layer that wins
+    pub source: TemplateSource,
+    /// The file used; `None` for an embedded template
+    pub path: Option<PathBuf>,
+    /// The layers below with a template of the same name, which it replaces
+    pub shadows: Vec<TemplateSource>,
+}
+
+/// The chain of template directories, highest priority first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateManager {
+    layers: Vec<TemplateLayer>,
+}
+
+impl Default for TemplateManager {
+    /// A chain with only the embedded built-in templates
+    fn default() -> Self {
+        Self {
+            layers: vec![TemplateLayer { source: TemplateSource::Builtin, dir: None }],
+        }
+    }
+}
+
+impl TemplateManager {
+    /// A chain whose built-in templates are those of `builtin_dir` instead of the embedded ones
+    pub fn new(builtin_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            layers: vec![TemplateLayer { source: TemplateSource::Builtin, dir: Some(builtin_dir.into()) }],
+        }
+    }
+
+    /// The usual chain: the project's templates, if any, then the user's, if
+    /// the directory exists, then the built-in ones
+    pub fn layered(project_dir: Option<PathBuf>) -> Self {
+        let mut manager = Self::default();
+        if let Some(user_dir) = Self::user_dir().filter(|dir| dir.is_dir()) {
+            manager = manager.with_layer(TemplateSource::User, user_dir);
+        }
+        if let Some(project_dir) = project_dir {
+            manager = manager.with_layer(TemplateSource::Project, project_dir);
+        }
+        manager
+    }
+
+    /// Add a layer above all the others
+    pub fn with_layer(mut self, source: TemplateSource, dir: impl Into<PathBuf>) -> Self {
+        self.layers.insert(0, TemplateLayer { source, dir: Some(dir.into()) });
+        self
+    }
+
+    /// The user's templates: `$XDG_CONFIG_HOME/kumeo/templates`, or
+    /// `~/.config/kumeo/templates` without it
+    pub fn user_dir() -> Option<PathBuf> {
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| dirs::home_dir().map(|home| home.join(".config")))?;
+        Some(config.join("kumeo").join("templates"))
+    }
+
+    /// The layers, highest priority first
+    pub fn layers(&self) -> &[TemplateLayer] {
+        &self.layers
+    }
+
+    /// The directories of the layers, highest priority first, without the embedded one
+    pub fn dirs(&self) -> Vec<PathBuf> {
+        self.layers.iter().filter_map(|layer| layer.dir.clone()).collect()
+    }
+
+    /// Template engine with the built-in templates, each replaced by the
+    /// template of the same name in the highest layer holding one
+    ///
+    /// Fails when there are no built-in templates, since nothing could be generated.
+    pub fn engine(&self) -> Result<Tera> {
+        let mut tera = Tera::default();
+        // Lower layers are registered first, so higher ones replace their templates
+        for layer in self.layers.iter().rev() {
+            match (layer.source, &layer.dir) {
+                (TemplateSource::Builtin, None) => {
+                    tera.add_raw_templates(BUILTIN_TEMPLATES.iter().copied())
+                        .context(message!("Failed to parse the built-in templates"))?;
+                }
+                (TemplateSource::Builtin, Some(dir)) => {
+                    let glob = dir.join("**/*.tera");
+                    tera = Tera::new(&glob.to_string_lossy())
+                        .with_context(|| message!("Failed to parse the templates in {}", dir.display()))?;
+                    if tera.get_template_names().next().is_none() {
+                        return Err(anyhow::anyhow!(message!("No built-in templates found in {}", dir.display())));
+                    }
+                }
+                (_, Some(dir)) => {
+                    add_overrides(&mut tera, dir)?;
+                }
+                (_, None) => {}
+            }
+        }
+        // Values are escaped for the language of each file with the filters of `escape`
+        tera.autoescape_on(Vec::new());
+        register_filters(&mut tera);
+        Ok(tera)
+    }
+
+    /// Every template name of the chain, with the layer it is resolved from
+    pub fn resolve_all(&self) -> Result<Vec<ResolvedTemplate>> {
+        let mut resolved: BTreeMap<String, ResolvedTemplate> = BTreeMap::new();
+        for layer in &self.layers {
+            let templates = match &layer.dir {
+                Some(dir) => templates_in(dir)?.into_iter().map(|(name, path)| (name, Some(path))).collect(),
+                None => BUILTIN_TEMPLATES.iter().map(|(name, _)| (name.to_string(), None)).collect::<Vec<_>>(),
+            };
+            for (name, path) in templates {
+                match resolved.get_mut(&name) {
+                    Some(winner) => winner.shadows.push(layer.source),
+                    None => {
+                        let template = ResolvedTemplate { name: name.clone(), source: layer.source, path, shadows: Vec::new() };
+                        resolved.insert(name, template);
+                    }
+                }
+            }
+        }
+        Ok(resolved.into_values().collect())
+    }
+}
+
+/// The `.tera` files of a directory, by their path below it
+fn templates_in(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    if !dir.is_dir() {
+        return Err(anyhow::anyhow!(message!("Template directory not found: {}", dir.display())));
+    }
+    let walker = globwalk::GlobWalkerBuilder::from_patterns(dir, &["**/*.tera"])
+        .build()
+        .with_context(|| message!("Failed to list the templates in {}", dir.display()))?;
+    let mut templates = Vec::new();
+    for entry in walker {
+        let path = entry?.into_path();
+        let relative = path.strip_prefix(dir).unwrap_or(&path);
+        let name = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+        templates.push((name, path));
+    }
+    Ok(templates)
+}
@@ -2,12 +2,19 @@
 
 use anyhow::{Context, Result};
 use std::fs;
-use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use tera::Tera;
 use std::ffi::OsStr;
 use std::collections::HashSet;
 
+use super::escape::register_filters;
+use super::sink::OutputSink;
+use crate::error::KumeoError;
+use crate::message;
+
+/// Prefix of the names template overrides are registered under, besides their own
+pub const OVERRIDE_PREFIX: &str = "overrides/";
+
 /// Process a directory of templates and render them to the output directory
 /// 
 /// # Arguments
@@ -16,25 +23,27 @@ use std::collections::HashSet;
 /// * `context` - Tera context with variables for template rendering
 /// * `tera` - Tera template engine instance
 /// * `exclude_dirs` - List of directory names to exclude from processing
+/// * `sink` - Where the rendered files are written
 pub fn process_template_dir(
     template_dir: &Path,
     output_dir: &Path,
     context: &tera::Context,
     tera: &Tera,
     exclude_dirs: &[&str],
+    sink: &mut dyn OutputSink,
 ) -> Result<()> {
     // Create output directory if it doesn't exist
-    if !output_dir.exists() {
-        fs::create_dir_all(output_dir)
-            .with_context(|| format!("Failed to create directory: {}", output_dir.display()))?;
+    if !sink.exists(output_dir) {
+        sink.create_dir(output_dir)?;
     }
 
     // Convert exclude dirs to a set for faster lookups
     let exclude_set: HashSet<&str> = exclude_dirs.iter().cloned().collect();
 
     // Create a new Tera instance that knows about our template directory
-    let mut tera = Tera::new(template_dir.join("**/*").to_str().unwrap())
-        .with_context(|| format!("Failed to parse templates in {}", template_dir.display()))?;
+    let mut dir_tera = Tera::new(template_dir.join("**/*").to_str().unwrap())
+        .with_context(|| message!("Failed to parse templates in {}", template_dir.display()))?;
+    register_filters(&mut dir_tera);
 
     // Process each entry in the template directory
     for entry in fs::read_dir(template_dir)? {
@@ -44,7 +53,7 @@ pub fn process_template_dir(
         // Skip if the entry is in the exclude list
         let file_name = entry_path.file_name()
             .and_then(OsStr::to_str)
-            .ok_or_else(|| anyhow::anyhow!("Invalid file name"))?;
+            .ok_or_else(|| anyhow::anyhow!(message!("Invalid file name")))?;
             
         if exclude_set.contains(file_name) {
             continue;
@@ -58,49 +67,138 @@ pub fn process_template_dir(
 
         if entry_path.is_dir() {
             // Create the directory in the output path
-            fs::create_dir_all(&output_path)
-                .with_context(|| format!("Failed to create directory: {}", output_path.display()))?;
+            sink.create_dir(&output_path)?;
                 
             // Recursively process subdirectories
-            process_template_dir(&entry_path, &output_path, context, &tera, exclude_dirs)?;
+            process_template_dir(&entry_path, &output_path, context, tera, exclude_dirs, sink)?;
         } else if entry_path.extension().and_then(OsStr::to_str) == Some("tera") {
             // Process template file
             let template_content = fs::read_to_string(&entry_path)
-                .with_context(|| format!("Failed to read template: {}", entry_path.display()))?;
+                .with_context(|| message!("Failed to read template: {}", entry_path.display()))?;
                 
-            // Render the template
-            let rendered = match tera.render_str(&template_content, context) {
-                Ok(rendered) => rendered,
-                Err(e) => {
-                    eprintln!("Warning: Failed to render template {}: {}", entry_path.display(), e);
-                    continue;
-                }
+            // Render the template, or the project's override of it
+            let override_name = builtin_name(&entry_path)
+                .map(|name| format!("{}{}", OVERRIDE_PREFIX, name))
+                .filter(|name| tera.get_template_names().any(|registered| registered == name));
+            let rendered = match override_name {
+                Some(name) => tera.render(&name, context),
+                None => dir_tera.render_str(&template_content, context),
             };
+            let rendered = rendered.map_err(|e| template_error(&entry_path.display().to_string(), &e))?;
             
             // Remove .tera extension from output path
             let output_path = output_path.with_extension("");
-            
-            if let Some(parent) = output_path.parent() {
-                fs::create_dir_all(parent)
-                    .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
-            }
 
             // Write rendered content to file
-            let mut file = fs::File::create(&output_path)
-                .with_context(|| format!("Failed to create file: {}", output_path.display()))?;
-                
-            file.write_all(rendered.as_bytes())
-                .with_context(|| format!("Failed to write to file: {}", output_path.display()))?;
+            sink.write(&output_path, rendered.as_bytes())
+                .with_context(|| message!("Failed to write to file: {}", output_path.display()))?;
         } else {
             // Copy non-template files as-is
-            fs::copy(&entry_path, &output_path)
-                .with_context(|| format!("Failed to copy file from {} to {}", entry_path.display(), output_path.display()))?;
+            sink.copy(&entry_path, &output_path)
+                .with_context(|| message!("Failed to copy file from {} to {}", entry_path.display(), output_path.display()))?;
         }
     }
 
     Ok(())
 }
 
+/// Render every template whose name starts with `prefix` into `output_dir`
+///
+/// Each template is written to its name below the prefix, without the
+/// `.tera` extension. Names may hold Tera expressions, such as the
+/// `kumeo_agent_{{agent_name | lower}}` package of Python agents. Templates
+/// named in `exclude`, by their name below the prefix, are left out. Returns
+/// the paths written, relative to `output_dir`, in name order.
+pub fn render_templates(
+    tera: &Tera,
+    prefix: &str,
+    output_dir: &Path,
+    context: &tera::Context,
+    exclude: &[&str],
+    sink: &mut dyn OutputSink,
+) -> Result<Vec<String>> {
+    let mut templates: Vec<&str> = tera
+        .get_template_names()
+        .filter(|name| name.strip_prefix(prefix).is_some_and(|relative| !exclude.contains(&relative)))
+        .collect();
+    templates.sort_unstable();
+
+    let mut written = Vec::new();
+    for name in templates {
+        let relative = &name[prefix.len()..];
+        let relative = relative.strip_suffix(".tera").unwrap_or(relative);
+        let relative = if relative.contains("{{") {
+            Tera::one_off(relative, context, false).map_err(|e| template_error(name, &e))?
+        } else {
+            relative.to_string()
+        };
+        let rendered = tera.render(name, context).map_err(|e| template_error(name, &e))?;
+        let output_path = output_dir.join(&relative);
+        if let Some(dir) = output_path.parent() {
+            sink.create_dir(dir)
+                .with_context(|| message!("Failed to create directory: {}", dir.display()))?;
+        }
+        sink.write(&output_path, rendered.as_bytes())
+            .with_context(|| message!("Failed to write {}", output_path.display()))?;
+        written.push(relative);
+    }
+    Ok(written)
+}
+
+/// Render a template of the engine by name
+pub fn render_template(tera: &Tera, name: &str, context: &tera::Context) -> Result<String> {
+    Ok(tera.render(name, context).map_err(|e| template_error(name, &e))?)
+}
+
+/// The error of a template, with every cause Tera gives
+///
+/// Tera only names the template in its own message and leaves the reason,
+/// such as a missing variable, to its sources.
+fn template_error(template: &str, error: &tera::Error) -> KumeoError {
+    let mut causes = Vec::new();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        causes.push(cause.to_string());
+        source = cause.source();
+    }
+    let message = if causes.is_empty() { error.to_string() } else { causes.join(": ") };
+    KumeoError::TemplateError { template: template.to_string(), message }
+}
+
+/// Register the templates of an override directory in a Tera instance
+///
+/// The directory mirrors the layout of the built-in templates: each of its
+/// `.tera` files replaces the built-in template with the same relative path,
+/// both when it is rendered by name and when its directory is processed.
+/// Returns the number of templates overridden.
+pub fn add_overrides(tera: &mut Tera, overrides_dir: &Path) -> Result<usize> {
+    if !overrides_dir.is_dir() {
+        return Err(anyhow::anyhow!(message!("Template overrides directory not found: {}", overrides_dir.display())));
+    }
+    let walker = globwalk::GlobWalkerBuilder::from_patterns(overrides_dir, &["**/*.tera"])
+        .build()
+        .with_context(|| message!("Failed to list the templates in {}", overrides_dir.display()))?;
+    let mut files = Vec::new();
+    for entry in walker {
+        let path = entry?.into_path();
+        let relative = path.strip_prefix(overrides_dir).unwrap_or(&path);
+        let name = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+        files.push((path.clone(), Some(format!("{}{}", OVERRIDE_PREFIX, name))));
+        files.push((path, Some(name)));
+    }
+    tera.add_template_files(files.iter().map(|(path, name)| (path.as_path(), name.as_deref())).collect::<Vec<_>>())
+        .with_context(|| message!("Failed to parse the templates in {}", overrides_dir.display()))?;
+    Ok(files.len() / 2)
+}
+
+/// Name of a built-in template: its path below the `templates` directory
+fn builtin_name(path: &Path) -> Option<String> {
+    let mut components = path.components().map(|c| c.as_os_str().to_string_lossy());
+    components.by_ref().find(|component| component == "templates")?;
+    let name = components.collect::<Vec<_>>().join("/");
+    (!name.is_empty()).then_some(name)
+}
+
 /// Helper function to create a context with common variables
 pub fn create_base_context(workflow_name: &str) -> tera::Context {
     let mut context = tera::Context::new();
@@ -112,7 +210,7 @@ pub fn create_base_context(workflow_name: &str) -> tera::Context {
 /// Helper function to check if a file contains a specific pattern
 pub fn file_contains(file_path: &Path, pattern: &str) -> Result<bool> {
     let content = fs::read_to_string(file_path)
-        .with_context(|| format!("Failed to read file: {}", file_path.display()))?;
+        .with_context(|| message!("Failed to read file: {}", file_path.display()))?;
     Ok(content.contains(pattern))
 }
 
@@ -120,17 +218,17 @@ pub fn file_contains(file_path: &Path, pattern: &str) -> Result<bool> {
 pub fn render_template_string(template: &str, context: &tera::Context, _tera: &Tera) -> Result<String> {
     let mut tera = Tera::default();
     tera.add_raw_template("inline_template", template)
-        .context("Failed to parse inline template")?;
+        .context(message!("Failed to parse inline template"))?;
     
     tera.render("inline_template", context)
-        .context("Failed to render template string")
+        .context(message!("Failed to render template string"))
 }
 
 /// Helper function to create a directory if it doesn't exist
 pub fn ensure_dir(path: &Path) -> Result<()> {
     if !path.exists() {
         fs::create_dir_all(path)
-            .with_context(|| format!("Failed to create directory: {}", path.display()))?;
+            .with_context(|| message!("Failed to create directory: {}", path.display()))?;
     }
     Ok(())
 }
@@ -0,0 +1,134 @@
+//! Terraform of the supporting infrastructure
+//!
+//! A workflow whose `deployment` declares its `infrastructure` gets a
+//! Terraform module in `terraform/` that provisions what its agents need
+//! besides their own manifests, on the declared cloud and on the cluster:
+//!
+//! - NATS, as a Helm release the agents connect to, unless an external NATS
+//!   was given;
+//! - the cloud's managed Kafka when `kafka: managed` and the workflow reads
+//!   or writes Kafka without naming its brokers, with a Secret holding its
+//!   bootstrap servers for the agents;
+//! - the object storage buckets referenced by the workflow's resources and
+//!   `File` endpoints, for the URIs of the cloud (`s3://` on AWS, `gs://` on
+//!   Google Cloud); buckets of other schemes are left alone.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::Path;
+use tera::Tera;
+use url::Url;
+
+use super::kubernetes::KafkaSettings;
+use super::nats::ExternalNats;
+use super::sink::OutputSink;
+use crate::ast::{CloudProvider, Program, Source, Target, Workflow};
+use crate::message;
+use crate::vendor::resource_uris;
+
+/// Templates of the Terraform module, by their name prefix
+pub const TERRAFORM_TEMPLATES: &str = "terraform/";
+
+/// Name of the NATS Helm release, and so of its Service
+pub const NATS_RELEASE: &str = "nats";
+
+/// Namespace of the infrastructure when the deployment names none: that of the agents
+pub use super::kubernetes::DEFAULT_NAMESPACE;
+
+/// Supporting infrastructure of a workflow, ready to be injected into the Terraform templates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TerraformSettings {
+    /// Prefix of the names of the cloud resources
+    pub name: String,
+    /// Cloud the resources are provisioned on
+    pub provider: CloudProvider,
+    /// Region they are provisioned in
+    pub region: String,
+    /// Namespace of the agents and of NATS
+    pub namespace: String,
+    /// URL of the NATS release the agents connect to, unless NATS is external
+    pub nats_url: Option<String>,
+    /// Managed Kafka cluster, when the workflow uses one
+    pub kafka: Option<ManagedKafka>,
+    /// Names of the buckets to create, sorted
+    pub buckets: Vec<String>,
+}
+
+/// A managed Kafka cluster of a workflow
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ManagedKafka {
+    /// Name of the cluster
+    pub cluster_name: String,
+    /// Secret its bootstrap servers are written to for the agents
+    pub bootstrap_secret: String,
+}
+
+impl TerraformSettings {
+    /// Compute the infrastructure of a workflow, if its deployment declares one
+    pub fn for_workflow(workflow: &Workflow, external_nats: Option<&ExternalNats>) -> Option<Self> {
+        let deployment = workflow.deployment.as_ref()?;
+        let infrastructure = deployment.infrastructure.as_ref()?;
+        let namespace = deployment.namespace.clone().unwrap_or_else(|| DEFAULT_NAMESPACE.to_string());
+        let kafka = KafkaSettings::for_workflow(workflow)
+            .and_then(|kafka| Some((kafka.bootstrap_secret?, kafka.service_name)))
+            .map(|(bootstrap_secret, cluster_name)| ManagedKafka { cluster_name, bootstrap_secret });
+
+        Some(Self {
+            name: workflow.name.to_lowercase(),
+            provider: infrastructure.provider,
+            region: infrastructure.region.clone(),
+            nats_url: external_nats.is_none().then(|| format!("nats://{}.{}:4222", NATS_RELEASE, namespace)),
+            namespace,
+            kafka,
+            buckets: buckets(workflow, infrastructure.provider).into_iter().collect(),
+        })
+    }
+
+    /// The NATS the agents connect to when Terraform provisions it
+    pub fn provisioned_nats(&self) -> Result<Option<ExternalNats>> {
+        self.nats_url.as_deref().map(|url| ExternalNats::new(url, None)).transpose()
+    }
+}
+
+/// Buckets of a cloud referenced by the resources and `File` endpoints of a workflow
+fn buckets(workflow: &Workflow, provider: CloudProvider) -> BTreeSet<String> {
+    let mut program = Program::new();
+    program.workflows.push(workflow.clone());
+    let mut uris = resource_uris(&program);
+    if let Some(Source::File(path, _)) = &workflow.source {
+        uris.insert(path.clone());
+    }
+    if let Some(Target::File(path, _)) = &workflow.target {
+        uris.insert(path.clone());
+    }
+    uris.iter()
+        .filter_map(|uri| Url::parse(uri).ok())
+        .filter(|url| url.scheme() == provider.bucket_scheme())
+        .filter_map(|url| url.host_str().map(str::to_string))
+        .collect()
+}
+
+/// Generate the Terraform module of a workflow into `terraform_dir`
+pub fn generate_terraform(
+    terraform_dir: &Path,
+    settings: &TerraformSettings,
+    tera: &Tera,
+    sink: &mut dyn OutputSink,
+) -> Result<()> {
+    let mut context = tera::Context::new();
+    context.insert("terraform", settings);
+    sink.create_dir(terraform_dir)
+        .with_context(|| message!("Failed to create directory: {}", terraform_dir.display()))?;
+
+    let mut templates: Vec<&str> = tera.get_template_names().filter(|name| name.starts_with(TERRAFORM_TEMPLATES)).collect();
+    templates.sort_unstable();
+    for template in templates {
+        let output_path = terraform_dir.join(template[TERRAFORM_TEMPLATES.len()..].trim_end_matches(".tera"));
+        let rendered = tera.render(template, &context)
+            .with_context(|| message!("Failed to render the Terraform template {}", template))?;
+        sink.write(&output_path, rendered.as_bytes())
+            .with_context(|| message!("Failed to write {}", output_path.display()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+//! Topic wiring between agents
+//!
+//! Agents of a workflow read the workflow source and write the workflow
+//! target unless they declare `input:` or `output:` topics. Once any agent
+//! does, the workflow is wired as a pipeline: every agent consumes the topic
+//! it names or the output of the previous step, and publishes on the topic
+//! it names or on `<workflow>.<agent>`. The resolved subjects are stored in
+//! the agents' `input_topic`/`output_topic` options, which the broker
+//! settings of each agent are computed from.
+
+use anyhow::{anyhow, Result};
+
+use crate::ast::{Argument, Span, Value, Workflow, INPUT_OPTION, INPUT_TOPIC_OPTION, OUTPUT_OPTION, OUTPUT_TOPIC_OPTION};
+use crate::message;
+
+/// Resolve the `input`/`output` topics of a workflow into NATS subjects
+///
+/// Workflows whose agents declare no topics are returned unchanged. Agents
+/// that keep consuming the workflow source or producing the workflow target
+/// get no subject, so they stay on the source and target brokers.
+pub fn wire(workflow: &Workflow) -> Result<Workflow> {
+    let mut wired = workflow.clone();
+    if !workflow.is_wired() {
+        return Ok(wired);
+    }
+
+    let graph = workflow
+        .topic_graph()
+        .map_err(|e| anyhow!(message!("Invalid topics in workflow {}: {}", workflow.name, e)))?;
+    for (agent, topics) in wired.agents.iter_mut().zip(graph) {
+        // Subjects bound by a subworkflow invocation are kept unless the agent names its own
+        let rewired = [
+            (INPUT_OPTION, INPUT_TOPIC_OPTION, topics.input),
+            (OUTPUT_OPTION, OUTPUT_TOPIC_OPTION, topics.output),
+        ];
+        for (option, subject_option, subject) in rewired {
+            let bound = agent.config_value(subject_option).is_some() && agent.config_value(option).is_none();
+            agent.config.retain(|argument| !matches!(argument, Argument::Named(key, ..) if key == option));
+            if bound {
+                continue;
+            }
+            agent.config.retain(|argument| !matches!(argument, Argument::Named(key, ..) if key == subject_option));
+            if let Some(subject) = subject {
+                agent.config.push(Argument::Named(subject_option.to_string(), Value::String(subject), Span::default()));
+            }
+        }
+    }
+    Ok(wired)
+}
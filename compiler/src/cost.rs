@@ -0,0 +1,241 @@
+//! Cost estimates
+//!
+//! Prices what a program's deployment would cost per month from a pricing
+//! file: the CPU and memory its agents request, as estimated by
+//! [`crate::stats`], times the hours of a month, and the tokens budgeted by
+//! its LLM agents' `budget` option at the price of their model.
+//!
+//! ```yaml
+//! currency: USD
+//! hours_per_month: 730
+//! cpu_core_hour: 0.0316
+//! memory_gib_hour: 0.0042
+//! llm:
+//!   default: { input: 0.5, output: 1.5 }
+//!   models:
+//!     gpt-4o: { input: 2.5, output: 10.0 }
+//! ```
+//!
+//! Token prices are per million tokens. An agent's model is its `model`
+//! option, or else the model of the first provider of its chain; a model
+//! without a price of its own takes the `default` one, and budgets of models
+//! without any price are an error rather than a silent zero. Brokers,
+//! inference servers and other shared services are not priced.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::ast::{Agent, AgentType, LlmBudgetConfig, Program, Value};
+use crate::codegen::failover::FailoverSettings;
+use crate::message;
+use crate::stats::{Footprint, ProgramStats};
+
+/// Hours in a month when the pricing names none (365 × 24 / 12)
+pub const DEFAULT_HOURS_PER_MONTH: f64 = 730.0;
+
+/// Unit prices of a pricing file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pricing {
+    /// Currency of the prices, only used to label the estimate
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    /// Hours the agents run per month
+    #[serde(default = "default_hours_per_month")]
+    pub hours_per_month: f64,
+    /// Price of a requested CPU core per hour
+    #[serde(default)]
+    pub cpu_core_hour: f64,
+    /// Price of a requested GiB of memory per hour
+    #[serde(default)]
+    pub memory_gib_hour: f64,
+    /// Token prices of the LLM models
+    #[serde(default)]
+    pub llm: LlmPricing,
+}
+
+/// Token prices of the LLM models
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LlmPricing {
+    /// Price of the models not listed
+    pub default: Option<TokenPrice>,
+    /// Prices by model name
+    #[serde(default)]
+    pub models: BTreeMap<String, TokenPrice>,
+}
+
+/// Price of a million tokens
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenPrice {
+    /// A million prompt tokens
+    #[serde(default)]
+    pub input: f64,
+    /// A million completion tokens
+    #[serde(default)]
+    pub output: f64,
+}
+
+fn default_currency() -> String {
+    "USD".to_string()
+}
+
+fn default_hours_per_month() -> f64 {
+    DEFAULT_HOURS_PER_MONTH
+}
+
+impl Pricing {
+    /// Read a pricing file
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| message!("Failed to read the pricing file {}", path.display()))?;
+        Self::from_yaml(&source).with_context(|| message!("Invalid pricing file {}", path.display()))
+    }
+
+    /// Read the YAML of a pricing file
+    pub fn from_yaml(source: &str) -> Result<Self> {
+        let pricing: Self = serde_yaml::from_str(source)?;
+        let mut prices = vec![
+            ("hours_per_month".to_string(), pricing.hours_per_month),
+            ("cpu_core_hour".to_string(), pricing.cpu_core_hour),
+            ("memory_gib_hour".to_string(), pricing.memory_gib_hour),
+        ];
+        let models = pricing.llm.default.iter().map(|price| ("default", price))
+            .chain(pricing.llm.models.iter().map(|(model, price)| (model.as_str(), price)));
+        for (model, price) in models {
+            prices.push((format!("the input price of {}", model), price.input));
+            prices.push((format!("the output price of {}", model), price.output));
+        }
+        if let Some((name, _)) = prices.iter().find(|(_, price)| !price.is_finite() || *price < 0.0) {
+            return Err(anyhow!(message!("{} must be a non-negative number", name)));
+        }
+        Ok(pricing)
+    }
+
+    /// Price of the tokens of a model: its own, or else the default one
+    pub fn token_price(&self, model: Option<&str>) -> Option<TokenPrice> {
+        model.and_then(|model| self.llm.models.get(model)).or(self.llm.default.as_ref()).copied()
+    }
+}
+
+/// Estimated monthly cost of a program
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CostEstimate {
+    /// Currency of the amounts
+    pub currency: String,
+    /// Hours of the month priced
+    pub hours_per_month: f64,
+    /// Cost of all the workflows
+    pub total: Cost,
+    /// Cost of each workflow, in program order
+    pub by_workflow: Vec<WorkflowCost>,
+}
+
+/// Estimated monthly cost of a workflow
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WorkflowCost {
+    /// Name of the workflow
+    pub name: String,
+    /// Footprint its compute cost is priced from
+    pub footprint: Footprint,
+    /// Its cost
+    pub cost: Cost,
+    /// Token budgets of its LLM agents
+    pub llm: Vec<LlmCost>,
+}
+
+/// Estimated monthly cost of the token budget of an LLM agent
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LlmCost {
+    /// ID of the agent
+    pub agent: String,
+    /// Its model, if known
+    pub model: Option<String>,
+    /// Its budget
+    pub budget: LlmBudgetConfig,
+    /// Price of the budget
+    pub cost: f64,
+}
+
+/// A monthly cost, broken down
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Cost {
+    /// CPU requested by the agents
+    pub cpu: f64,
+    /// Memory requested by the agents
+    pub memory: f64,
+    /// Tokens budgeted by the LLM agents
+    pub llm: f64,
+    /// Sum of the above
+    pub total: f64,
+}
+
+impl std::ops::AddAssign for Cost {
+    fn add_assign(&mut self, other: Self) {
+        self.cpu += other.cpu;
+        self.memory += other.memory;
+        self.llm += other.llm;
+        self.total += other.total;
+    }
+}
+
+impl CostEstimate {
+    /// Estimate the monthly cost of a program, with its subworkflows already expanded
+    pub fn of(program: &Program, pricing: &Pricing) -> Result<Self> {
+        let stats = ProgramStats::of(program)?;
+        let mut total = Cost::default();
+        let mut by_workflow = Vec::with_capacity(program.workflows.len());
+
+        for (workflow, workflow_stats) in program.workflows.iter().zip(stats.by_workflow) {
+            let footprint = workflow_stats.footprint;
+            let hours = pricing.hours_per_month;
+            let mut cost = Cost {
+                cpu: footprint.cpu_millis as f64 / 1000.0 * pricing.cpu_core_hour * hours,
+                memory: footprint.memory_mib as f64 / 1024.0 * pricing.memory_gib_hour * hours,
+                ..Cost::default()
+            };
+            let llm = workflow
+                .agents
+                .iter()
+                .filter(|agent| agent.agent_type == AgentType::LLM)
+                .map(|agent| llm_cost(agent, pricing))
+                .filter_map(Result::transpose)
+                .collect::<Result<Vec<_>>>()?;
+            cost.llm = llm.iter().map(|agent| agent.cost).sum();
+            cost.total = cost.cpu + cost.memory + cost.llm;
+            total += cost;
+            by_workflow.push(WorkflowCost { name: workflow.name.clone(), footprint, cost, llm });
+        }
+
+        Ok(Self { currency: pricing.currency.clone(), hours_per_month: pricing.hours_per_month, total, by_workflow })
+    }
+}
+
+/// Cost of the token budget of an LLM agent, if it declares one
+fn llm_cost(agent: &Agent, pricing: &Pricing) -> Result<Option<LlmCost>> {
+    let agent_id = agent.id.as_deref().unwrap_or("<unnamed>");
+    let Some(budget) = LlmBudgetConfig::from_agent(agent).map_err(|e| anyhow!(message!("Invalid budget of {}: {}", agent_id, e)))? else {
+        return Ok(None);
+    };
+    let model = agent_model(agent)?;
+    let price = pricing.token_price(model.as_deref()).ok_or_else(|| {
+        anyhow!(message!(
+            "No token price for the model {} of {}; add it to llm.models or set llm.default",
+            model.as_deref().unwrap_or("<unknown>"),
+            agent_id
+        ))
+    })?;
+    let cost = (budget.input_tokens as f64 * price.input + budget.output_tokens as f64 * price.output) / 1e6;
+    Ok(Some(LlmCost { agent: agent_id.to_string(), model, budget, cost }))
+}
+
+/// Model an LLM agent sends its prompts to: its `model`, or else the first provider's
+fn agent_model(agent: &Agent) -> Result<Option<String>> {
+    if let Some(Value::String(model)) = agent.config_value("model") {
+        return Ok(Some(model.clone()));
+    }
+    Ok(FailoverSettings::for_agent(agent)?.and_then(|chain| chain.providers.into_iter().next()).map(|provider| provider.model))
+}
@@ -0,0 +1,376 @@
+//! Time-travel debugging of deployed agents
+//!
+//! In debug mode the runtime records every message received or published on
+//! the subjects in `KUMEO_DEBUG_SUBJECTS` into a local store, a directory of
+//! JSON Lines files, one per subject (see `kumeo_runtime::history`).
+//! `kumeo debug` reads the store back as the trace of an agent: the messages
+//! it received on its input subject and those it published on its output
+//! subject, in the order they happened. The trace is stepped through message
+//! by message in the terminal; a payload can be edited and re-injected on
+//! its subject, so the agent, or the ones after it, process it again.
+//!
+//! Re-injected messages carry the [`REINJECTED_HEADER`] with the time of the
+//! message they replay, and drop its ingest time so they don't skew the
+//! end-to-end latency of the workflow.
+
+use anyhow::{anyhow, Context, Result};
+use console::{Key, Term};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use crate::ast::Workflow;
+use crate::load::NatsConnection;
+use crate::message;
+
+/// Directory of the store unless the runtime's `KUMEO_DEBUG_STORE` says otherwise
+pub const DEFAULT_STORE: &str = ".kumeo/history";
+
+/// Header marking a re-injected message, with the time of the message it replays
+pub const REINJECTED_HEADER: &str = "Kumeo-Reinjected";
+
+/// Header the runtime stamps with the time a message entered the workflow
+const INGEST_HEADER: &str = "Kumeo-Ingest-Time";
+
+/// Whether a message was received or published by the agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Received on the agent's input subject
+    In,
+    /// Published on the agent's output subject
+    Out,
+}
+
+/// A message of the store
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// When it was received or published, in epoch milliseconds
+    pub time: u64,
+    /// Agent that received or published it, if the runtime knew
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    /// Whether it was received or published
+    pub direction: Direction,
+    /// Subject, without the channel prefix
+    pub subject: String,
+    /// Headers
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Payload: its JSON, or its text when it isn't JSON
+    pub payload: Json,
+}
+
+/// File of the store holding the messages of a subject
+pub fn subject_file(store: &Path, subject: &str) -> PathBuf {
+    store.join(format!("{}.jsonl", subject))
+}
+
+/// Read the messages of a subject from the store, in the order they were recorded
+///
+/// Subjects never recorded have no messages.
+pub fn read_subject(store: &Path, subject: &str) -> Result<Vec<Record>> {
+    let path = subject_file(store, subject);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let file = std::fs::File::open(&path).with_context(|| message!("Failed to read {}", path.display()))?;
+    let mut records = Vec::new();
+    for (number, line) in std::io::BufReader::new(file).lines().enumerate() {
+        let line = line.with_context(|| message!("Failed to read {}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .with_context(|| message!("Invalid message at {}:{}", path.display(), number + 1))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// The messages an agent received and published, in the order they happened
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Trace {
+    /// Workflow of the agent
+    pub workflow: String,
+    /// ID of the agent
+    pub agent: String,
+    /// Subject it consumes
+    pub input: Option<String>,
+    /// Subject it publishes on
+    pub output: Option<String>,
+    /// Its messages, by time; received before published at the same time
+    pub records: Vec<Record>,
+}
+
+impl Trace {
+    /// Read the trace of an agent of a workflow from the store
+    ///
+    /// Messages recorded by other agents on the same subjects are left out.
+    pub fn load(store: &Path, workflow: &Workflow, agent_id: &str) -> Result<Self> {
+        let index = workflow
+            .agents
+            .iter()
+            .position(|agent| agent.id.as_deref() == Some(agent_id))
+            .ok_or_else(|| anyhow!(message!("Workflow {} has no agent {}", workflow.name, agent_id)))?;
+        let topics = workflow.deployed_topics().swap_remove(index);
+
+        let mut records = Vec::new();
+        for (subject, direction) in [(&topics.input, Direction::In), (&topics.output, Direction::Out)] {
+            let Some(subject) = subject else {
+                continue;
+            };
+            records.extend(read_subject(store, subject)?.into_iter().filter(|record| {
+                record.direction == direction && record.agent.as_deref().is_none_or(|agent| agent == agent_id)
+            }));
+        }
+        records.sort_by_key(|record| (record.time, record.direction == Direction::Out));
+
+        Ok(Self {
+            workflow: workflow.name.clone(),
+            agent: agent_id.to_string(),
+            input: topics.input,
+            output: topics.output,
+            records,
+        })
+    }
+}
+
+/// Headers and payload of a message ready to be published
+pub type Message = (Vec<(String, String)>, Vec<u8>);
+
+/// Headers and payload of a message re-injected in place of a recorded one
+pub fn reinjection(record: &Record, payload: &Json) -> Result<Message> {
+    let mut headers: Vec<(String, String)> = record
+        .headers
+        .iter()
+        .filter(|(name, _)| name.as_str() != INGEST_HEADER && name.as_str() != REINJECTED_HEADER)
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    headers.push((REINJECTED_HEADER.to_string(), record.time.to_string()));
+    let payload = match payload {
+        // Payloads that weren't JSON are replayed as the text they were
+        Json::String(text) if serde_json::from_str::<Json>(text).is_err() => text.clone().into_bytes(),
+        payload => serde_json::to_vec(payload)?,
+    };
+    Ok((headers, payload))
+}
+
+/// Publish a message on a subject of the NATS server at `url`
+pub async fn reinject(url: &str, record: &Record, payload: &Json) -> Result<()> {
+    let (headers, payload) = reinjection(record, payload)?;
+    let headers: Vec<(&str, String)> = headers.iter().map(|(name, value)| (name.as_str(), value.clone())).collect();
+    let connection = NatsConnection::connect(url).await?;
+    connection.publish(&record.subject, &headers, &payload).await
+}
+
+/// Stepping through a trace, with the payloads edited so far
+#[derive(Debug, Clone)]
+pub struct Session {
+    trace: Trace,
+    cursor: usize,
+    edited: HashMap<usize, Json>,
+}
+
+impl Session {
+    /// Start at the first message of a trace
+    pub fn new(trace: Trace) -> Self {
+        Self { trace, cursor: 0, edited: HashMap::new() }
+    }
+
+    /// The trace stepped through
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    /// Position of the current message
+    pub fn position(&self) -> usize {
+        self.cursor
+    }
+
+    /// The current message, unless the trace is empty
+    pub fn current(&self) -> Option<&Record> {
+        self.trace.records.get(self.cursor)
+    }
+
+    /// Step to the next message; false at the last one
+    pub fn forward(&mut self) -> bool {
+        let moved = self.cursor + 1 < self.trace.records.len();
+        if moved {
+            self.cursor += 1;
+        }
+        moved
+    }
+
+    /// Step to the previous message; false at the first one
+    pub fn back(&mut self) -> bool {
+        let moved = self.cursor > 0;
+        if moved {
+            self.cursor -= 1;
+        }
+        moved
+    }
+
+    /// Jump to the first message
+    pub fn first(&mut self) {
+        self.cursor = 0;
+    }
+
+    /// Jump to the last message
+    pub fn last(&mut self) {
+        self.cursor = self.trace.records.len().saturating_sub(1);
+    }
+
+    /// Payload of the current message, as edited
+    pub fn payload(&self) -> Option<&Json> {
+        self.edited.get(&self.cursor).or_else(|| self.current().map(|record| &record.payload))
+    }
+
+    /// Whether the payload of the current message was edited
+    pub fn is_edited(&self) -> bool {
+        self.edited.contains_key(&self.cursor)
+    }
+
+    /// Replace the payload of the current message with edited JSON
+    pub fn edit(&mut self, text: &str) -> Result<()> {
+        if self.current().is_none() {
+            return Err(anyhow!(message!("There is no message to edit")));
+        }
+        let payload: Json = serde_json::from_str(text).context(message!("The edited payload is not JSON"))?;
+        if Some(&payload) == self.current().map(|record| &record.payload) {
+            self.edited.remove(&self.cursor);
+        } else {
+            self.edited.insert(self.cursor, payload);
+        }
+        Ok(())
+    }
+
+    /// The current message as a screen of at most `rows` lines
+    pub fn render(&self, rows: usize) -> String {
+        let trace = &self.trace;
+        let mut lines = Vec::new();
+        let Some(record) = self.current() else {
+            lines.push(format!("{} ({}): no messages recorded", trace.agent, trace.workflow));
+            lines.push(format!(
+                "Set KUMEO_DEBUG_SUBJECTS={} on the runtime to record them",
+                [&trace.input, &trace.output].into_iter().flatten().cloned().collect::<Vec<_>>().join(",")
+            ));
+            return lines.join("\n");
+        };
+
+        let arrow = match record.direction {
+            Direction::In => "<- received on",
+            Direction::Out => "-> published on",
+        };
+        lines.push(format!(
+            "{} ({}) · message {} of {} · {} {} · {}",
+            trace.agent,
+            trace.workflow,
+            self.cursor + 1,
+            trace.records.len(),
+            arrow,
+            record.subject,
+            format_time(record.time)
+        ));
+        for (name, value) in &record.headers {
+            lines.push(format!("  {}: {}", name, value));
+        }
+        lines.push(if self.is_edited() { "payload (edited):".to_string() } else { "payload:".to_string() });
+        let payload = self.payload().map(|payload| serde_json::to_string_pretty(payload).unwrap_or_default()).unwrap_or_default();
+        lines.extend(payload.lines().map(|line| format!("  {}", line)));
+
+        let footer = "[n/→] next  [p/←] previous  [g] first  [G] last  [e] edit  [r] re-inject  [q] quit";
+        // The footer stays visible; long payloads are cut
+        let room = rows.saturating_sub(2).max(1);
+        if lines.len() > room {
+            lines.truncate(room.saturating_sub(1));
+            lines.push("  ...".to_string());
+        }
+        lines.push(String::new());
+        lines.push(footer.to_string());
+        lines.join("\n")
+    }
+}
+
+/// An epoch time in milliseconds as UTC
+fn format_time(millis: u64) -> String {
+    chrono::DateTime::from_timestamp_millis(millis as i64)
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S%.3f UTC").to_string())
+        .unwrap_or_else(|| millis.to_string())
+}
+
+/// Edit a payload in `$VISUAL` or `$EDITOR`, `vi` by default
+fn edit_in_editor(payload: &Json) -> Result<String> {
+    let editor = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")).unwrap_or_else(|_| "vi".to_string());
+    let file = tempfile::Builder::new().suffix(".json").tempfile()?;
+    std::fs::write(file.path(), serde_json::to_string_pretty(payload)?)?;
+    let status = std::process::Command::new(&editor)
+        .arg(file.path())
+        .status()
+        .with_context(|| message!("Failed to run the editor {}", editor))?;
+    if !status.success() {
+        return Err(anyhow!(message!("The editor {} exited with {}", editor, status)));
+    }
+    Ok(std::fs::read_to_string(file.path())?)
+}
+
+/// Step through a session in the terminal until the user quits
+///
+/// Re-injected messages are published on the NATS server at `nats`.
+pub async fn run_tui(session: &mut Session, nats: &str) -> Result<()> {
+    let term = Term::stdout();
+    if !term.is_term() {
+        return Err(anyhow!(message!("The debugger needs a terminal")));
+    }
+    term.hide_cursor()?;
+    let mut status = String::new();
+    let result = loop {
+        let (rows, _) = term.size();
+        term.clear_screen()?;
+        term.write_line(&session.render(usize::from(rows).saturating_sub(1)))?;
+        term.write_str(&status)?;
+        status.clear();
+
+        let key = match term.read_key() {
+            Ok(key) => key,
+            Err(e) => break Err(e.into()),
+        };
+        match key {
+            Key::Char('q') | Key::Escape => break Ok(()),
+            Key::Char('n') | Key::ArrowRight | Key::ArrowDown if !session.forward() => {
+                status = "Last message".to_string();
+            }
+            Key::Char('p') | Key::ArrowLeft | Key::ArrowUp if !session.back() => {
+                status = "First message".to_string();
+            }
+            Key::Char('g') | Key::Home => session.first(),
+            Key::Char('G') | Key::End => session.last(),
+            Key::Char('e') => {
+                let Some(payload) = session.payload().cloned() else {
+                    continue;
+                };
+                term.show_cursor()?;
+                let edited = edit_in_editor(&payload);
+                term.hide_cursor()?;
+                if let Err(e) = edited.and_then(|text| session.edit(&text)) {
+                    status = format!("{:#}", e);
+                }
+            }
+            Key::Char('r') => {
+                let (Some(record), Some(payload)) = (session.current().cloned(), session.payload().cloned()) else {
+                    continue;
+                };
+                status = match reinject(nats, &record, &payload).await {
+                    Ok(()) => format!("Re-injected on {}", record.subject),
+                    Err(e) => format!("{:#}", e),
+                };
+            }
+            _ => {}
+        }
+    };
+    term.show_cursor()?;
+    term.clear_screen()?;
+    result
+}
@@ -0,0 +1,654 @@
+//! Diagnostics of the compiler
+//!
+//! Parse and semantic problems are reported as [`Diagnostic`]s: a severity,
+//! a stable code from [`codes`], a message and, when known, the position of
+//! the node the problem was found in, with an optional help text and a
+//! suggested replacement. Rendered, a diagnostic shows the source line with
+//! the node underlined:
+//!
+//! ```text
+//! error[KU0302]: Configuración inválida en el agente editor (LLM): 'temperature' debe estar entre 0 y 2
+//!  --> review.kumeo:5:9
+//!   |
+//! 5 | LLM(id: "editor", temperature: 3)
+//!   | ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! ```
+//!
+//! Once a locale is chosen with [`i18n::set_locale`], diagnostics are
+//! rendered in it, their messages translated by the catalog of [`i18n`].
+//!
+//! Tools read diagnostics as [`JsonDiagnostic`]s, whose schema is versioned
+//! by [`JSON_SCHEMA_VERSION`]: each has the file and range of its node and
+//! the fixes that can be applied automatically, as text edits. `kumeo fix`
+//! applies them with [`apply_edits`].
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::fmt;
+use std::path::Path;
+
+use crate::ast::Span;
+use crate::i18n::{self, Locale, Message};
+
+/// Codes of the diagnostics, grouped by the area they check
+///
+/// Codes are stable: a code is never reused for another problem, so tools
+/// and documentation can refer to them.
+pub mod codes {
+    /// Declare the codes, each with its doc comment, and [`ALL`] listing them with their descriptions
+    macro_rules! codes {
+        ($($(#[doc = $doc:literal])+ $name:ident = $code:literal;)+) => {
+            $($(#[doc = $doc])+ pub const $name: &str = $code;)+
+
+            /// Every code with its description, in order
+            pub const ALL: &[(&str, &str)] = &[$(($code, concat!($($doc),+).trim_ascii())),+];
+        };
+    }
+
+    codes! {
+        /// The input doesn't match the grammar
+        SYNTAX = "KU0001";
+        /// A construct the grammar accepts but the parser can't build
+        MALFORMED = "KU0002";
+
+        /// Two workflows, subworkflows, constants, agents, tests or schemas share a name
+        DUPLICATE_NAME = "KU0101";
+        /// A workflow or subworkflow name that isn't an identifier
+        INVALID_IDENTIFIER = "KU0102";
+        /// A `$NAME` reference to a constant that isn't declared
+        UNDEFINED_CONSTANT = "KU0103";
+        /// A `${...}` interpolation that can't be resolved
+        INVALID_INTERPOLATION = "KU0104";
+        /// A `use` of a subworkflow that isn't declared
+        UNDEFINED_SUBWORKFLOW = "KU0105";
+        /// A `use` binding other inputs or outputs than the subworkflow declares
+        SUBWORKFLOW_PORTS = "KU0106";
+        /// A workflow `version` that isn't a `MAJOR.MINOR.PATCH` semantic version
+        INVALID_VERSION = "KU0107";
+
+        /// A workflow without a source
+        MISSING_SOURCE = "KU0201";
+        /// A source whose options are invalid
+        INVALID_SOURCE = "KU0202";
+        /// A target whose options are invalid
+        INVALID_TARGET = "KU0203";
+        /// A NATS, Kafka or MQTT topic the broker rejects
+        INVALID_TOPIC = "KU0204";
+
+        /// An agent without an `id`
+        MISSING_AGENT_ID = "KU0301";
+        /// An agent option of the wrong type, out of range or missing
+        INVALID_CONFIG = "KU0302";
+        /// An invalid `retry` or `fallback` policy
+        INVALID_POLICY = "KU0303";
+        /// A `preload` without a remote model to load
+        INVALID_PRELOAD = "KU0304";
+        /// A resource glob that can't be expanded
+        INVALID_RESOURCE_GLOB = "KU0305";
+        /// A placeholder that can't be resolved in this environment
+        UNRESOLVED_PLACEHOLDER = "KU0306";
+        /// A credential written into the DSL instead of read with `secret(...)`
+        INLINE_SECRET = "KU0307";
+
+        /// A `when` or `assert` condition that isn't a boolean expression
+        INVALID_CONDITION = "KU0401";
+        /// A condition field outside the message or its schema
+        UNKNOWN_FIELD = "KU0402";
+        /// A condition combining operands of incompatible types
+        TYPE_MISMATCH = "KU0403";
+        /// An ordering comparison on an optional field without a default
+        OPTIONAL_FIELD = "KU0404";
+
+        /// A message schema that can't be read
+        INVALID_SCHEMA = "KU0501";
+        /// A producer or consumer breaking the schema of its topic
+        CONTRACT_VIOLATION = "KU0502";
+        /// An incompatible schema change without a new `schema_version`
+        BREAKING_SCHEMA_CHANGE = "KU0503";
+
+        /// Agent topics that can't be wired
+        INVALID_TOPICS = "KU0601";
+        /// A topic consumed in a workflow that nothing produces
+        UNPRODUCED_TOPIC = "KU0602";
+        /// A topic produced in a workflow that nothing consumes
+        UNUSED_TOPIC = "KU0603";
+        /// A step no message from the source reaches
+        UNREACHABLE_STEP = "KU0604";
+        /// A cycle in the dataflow of a workflow
+        DATAFLOW_CYCLE = "KU0605";
+        /// An unknown topic encoding, one producers and consumers disagree on, or one missing the schema it needs
+        INVALID_ENCODING = "KU0606";
+
+        /// A batch workflow whose input isn't bounded
+        INVALID_BATCH = "KU0701";
+        /// Invalid storage or rollout settings
+        INVALID_DEPLOYMENT = "KU0702";
+        /// A remote model without a signature where signatures are required
+        UNSIGNED_MODEL = "KU0703";
+
+        /// A workflow `test` block that can't be run
+        INVALID_TEST = "KU0801";
+
+        /// The external NATS given to `kumeo check` is invalid or doesn't answer
+        EXTERNAL_NATS = "KU0901";
+        /// A resource `kumeo check --check-resources` can't fetch, or whose content is invalid
+        INVALID_RESOURCE = "KU0902";
+        /// A workflow version bump smaller than its changes against the baseline of `kumeo check --baseline`
+        VERSION_BUMP = "KU0903";
+        /// A namespace whose workflows request more at their peak than its quota in the quotas file of `kumeo check --quotas`
+        QUOTA_EXCEEDED = "KU0904";
+    }
+
+    /// The description of a code
+    pub fn description(code: &str) -> Option<&'static str> {
+        ALL.iter().find(|(known, _)| *known == code).map(|(_, description)| *description)
+    }
+}
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The program is invalid
+    Error,
+    /// The program is valid but probably not what was meant
+    Warning,
+}
+
+impl Severity {
+    /// The label the diagnostic is rendered with in a locale
+    pub fn label(self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (Severity::Error, _) => "error",
+            (Severity::Warning, Locale::En) => "warning",
+            (Severity::Warning, Locale::Es) => "aviso",
+        }
+    }
+}
+
+/// The label of a help text in a locale
+fn help_label(locale: Locale) -> &'static str {
+    match locale {
+        Locale::En => "help",
+        Locale::Es => "ayuda",
+    }
+}
+
+/// The help text suggesting what was probably meant, in a locale
+fn suggestion_help(suggestion: &str, locale: Locale) -> String {
+    match locale {
+        Locale::En => format!("did you mean `{}`?", suggestion),
+        Locale::Es => format!("¿quisiste decir `{}`?", suggestion),
+    }
+}
+
+/// The title of a fix in a locale: an insertion when the replacement keeps the text
+fn fix_title(fix: &Fix, locale: Locale) -> String {
+    match (fix.with.strip_prefix(fix.replace.as_str()), locale) {
+        (Some(inserted), Locale::En) => format!("insert `{}`", inserted.trim_end_matches([',', ' '])),
+        (Some(inserted), Locale::Es) => format!("insertar `{}`", inserted.trim_end_matches([',', ' '])),
+        (None, Locale::En) => format!("replace `{}` with `{}`", fix.replace, fix.with),
+        (None, Locale::Es) => format!("reemplazar `{}` por `{}`", fix.replace, fix.with),
+    }
+}
+
+/// A problem found in a program
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// How serious the problem is
+    pub severity: Severity,
+    /// The code of the problem, one of [`codes`]
+    pub code: &'static str,
+    /// What is wrong
+    pub message: Message,
+    /// The node the problem was found in; unknown for program-wide problems
+    #[serde(skip_serializing_if = "is_unknown")]
+    pub span: Span,
+    /// How to fix the problem
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub help: Option<Message>,
+    /// What was probably meant instead of a misspelled name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+    /// The replacement that fixes the problem, when it can be fixed automatically
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<Fix>,
+}
+
+/// A replacement in the source of a node that fixes a problem found in it
+///
+/// The first occurrence of `replace` from the start of the node, on the line
+/// it starts on, is replaced with `with`. When the text isn't there, as in a
+/// node written over several lines, the fix has no edits and isn't applied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Fix {
+    /// The text replaced
+    pub replace: String,
+    /// The text it is replaced with
+    pub with: String,
+}
+
+impl Diagnostic {
+    /// An error, without position yet
+    pub fn error(code: &'static str, message: impl Into<Message>) -> Self {
+        Self::new(Severity::Error, code, message.into())
+    }
+
+    /// A warning, without position yet
+    pub fn warning(code: &'static str, message: impl Into<Message>) -> Self {
+        Self::new(Severity::Warning, code, message.into())
+    }
+
+    fn new(severity: Severity, code: &'static str, message: Message) -> Self {
+        Self {
+            severity,
+            code,
+            message,
+            span: Span::default(),
+            help: None,
+            suggestion: None,
+            fix: None,
+        }
+    }
+
+    /// Attach the position of the node the problem was found in.
+    ///
+    /// A diagnostic that already has a position keeps it, so the innermost
+    /// node wins.
+    pub fn at(mut self, span: &Span) -> Self {
+        if !self.span.is_known() {
+            self.span = span.clone();
+        }
+        self
+    }
+
+    /// Explain how to fix the problem
+    pub fn with_help(mut self, help: impl Into<Message>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Suggest what was probably meant, when there is a guess
+    pub fn with_suggestion(mut self, suggestion: Option<impl Into<String>>) -> Self {
+        self.suggestion = suggestion.map(Into::into);
+        self
+    }
+
+    /// Fix the problem by replacing text of the node it is found in; see [`Fix`]
+    pub fn with_fix(mut self, replace: impl Into<String>, with: impl Into<String>) -> Self {
+        self.fix = Some(Fix { replace: replace.into(), with: with.into() });
+        self
+    }
+
+    /// Whether the diagnostic makes the program invalid
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// The range of the node on the line it starts on, if its position is known
+    pub fn range(&self) -> Option<Range> {
+        self.span.is_known().then(|| Range::on_line(self.span.line, self.span.column, self.span.length))
+    }
+
+    /// The fixes of the problem as edits of its file, titled in a locale;
+    /// empty when it can't be fixed automatically
+    pub fn fixes(&self, locale: Locale) -> Vec<SuggestedFix> {
+        let Some(fix) = &self.fix else {
+            return Vec::new();
+        };
+        let Some(column) = fix_column(&self.span, &fix.replace) else {
+            return Vec::new();
+        };
+        // A replacement that keeps the text is an insertion after it
+        let length = fix.replace.chars().count();
+        let (range, new_text) = match fix.with.strip_prefix(fix.replace.as_str()) {
+            Some(inserted) => (Range::on_line(self.span.line, column + length, 0), inserted),
+            None => (Range::on_line(self.span.line, column, length), fix.with.as_str()),
+        };
+        vec![SuggestedFix {
+            title: fix_title(fix, locale),
+            edits: vec![TextEdit { file: self.span.file.clone(), range, new_text: new_text.to_string() }],
+        }]
+    }
+
+    /// The diagnostic in the JSON schema, in the locale of the process
+    pub fn to_json(&self) -> JsonDiagnostic {
+        let (diagnostic, locale) = self.in_current_locale();
+        JsonDiagnostic {
+            code: self.code.to_string(),
+            message: diagnostic.message.written(),
+            severity: self.severity,
+            file: self.span.is_known().then(|| self.span.file.clone()).flatten(),
+            range: self.range(),
+            help: diagnostic.help.as_ref().map(Message::written),
+            suggestion: self.suggestion.clone(),
+            fixes: self.fixes(locale),
+        }
+    }
+
+    /// The diagnostic in a locale, its message and help translated
+    pub fn localized(&self, locale: Locale) -> Self {
+        Self {
+            message: Message::Text(self.message.localized(locale)),
+            help: self.help.as_ref().map(|help| Message::Text(help.localized(locale))),
+            ..self.clone()
+        }
+    }
+
+    /// The diagnostic in the locale of the process, as written while none is chosen
+    fn in_current_locale(&self) -> (Cow<'_, Self>, Locale) {
+        match i18n::locale() {
+            Some(locale) => (Cow::Owned(self.localized(locale)), locale),
+            None => (Cow::Borrowed(self), Locale::default()),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (diagnostic, locale) = self.in_current_locale();
+        write!(f, "{}[{}]: {}", self.severity.label(locale), self.code, diagnostic.message)?;
+
+        // The gutter is as wide as the line number, as in rustc
+        let number = self.span.line.to_string();
+        let gutter = if self.span.is_known() { " ".repeat(number.len()) } else { String::new() };
+        if self.span.is_known() {
+            write!(f, "\n{}--> {}", gutter, self.span)?;
+            if !self.span.snippet.is_empty() {
+                let width = self.span.snippet.chars().count();
+                let offset = (self.span.column.saturating_sub(1 + self.span.indent)).min(width.saturating_sub(1));
+                let carets = self.span.length.clamp(1, (width - offset).max(1));
+                write!(f, "\n{} |\n{} | {}", gutter, number, self.span.snippet)?;
+                write!(f, "\n{} | {}{}", gutter, " ".repeat(offset), "^".repeat(carets))?;
+            }
+        }
+        if let Some(help) = &diagnostic.help {
+            write!(f, "\n{} = {}: {}", gutter, help_label(locale), help)?;
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n{} = {}: {}", gutter, help_label(locale), suggestion_help(suggestion, locale))?;
+        }
+        Ok(())
+    }
+}
+
+fn is_unknown(span: &Span) -> bool {
+    !span.is_known()
+}
+
+/// The column the text of a fix starts at, searched from the start of the node on its line
+fn fix_column(span: &Span, text: &str) -> Option<usize> {
+    if !span.is_known() || span.snippet.is_empty() || text.is_empty() {
+        return None;
+    }
+    let offset = span.column.saturating_sub(1 + span.indent);
+    let rest: String = span.snippet.chars().skip(offset).collect();
+    let found = rest.find(text)?;
+    Some(span.indent + offset + rest[..found].chars().count() + 1)
+}
+
+/// Version of the JSON schema of [`JsonDiagnostic`], raised on incompatible changes
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// A position in a file: line and column from 1, columns counted in characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Position {
+    /// The line, from 1
+    pub line: usize,
+    /// The column, from 1
+    pub column: usize,
+}
+
+/// A range of a file, from its start to just before its end
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Range {
+    /// The first character of the range
+    pub start: Position,
+    /// The character after the last one of the range
+    pub end: Position,
+}
+
+impl Range {
+    /// The range of `length` characters at a column of a line
+    pub fn on_line(line: usize, column: usize, length: usize) -> Self {
+        Self {
+            start: Position { line, column },
+            end: Position { line, column: column + length },
+        }
+    }
+}
+
+/// A replacement of a range of a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEdit {
+    /// The file edited; none for programs parsed from memory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// The range replaced; empty to insert
+    pub range: Range,
+    /// The text that replaces the range
+    pub new_text: String,
+}
+
+/// A fix of a diagnostic: the edits that make the problem go away
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SuggestedFix {
+    /// What applying the fix does
+    pub title: String,
+    /// The edits of the fix, none overlapping another
+    pub edits: Vec<TextEdit>,
+}
+
+/// A diagnostic as read by tools, in version [`JSON_SCHEMA_VERSION`] of its schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonDiagnostic {
+    /// The code of the problem, one of [`codes`]
+    pub code: String,
+    /// What is wrong
+    pub message: String,
+    /// How serious the problem is
+    pub severity: Severity,
+    /// The file the problem was found in, if parsed from a file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// The range of the node the problem was found in; none for program-wide problems
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<Range>,
+    /// How to fix the problem
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub help: Option<String>,
+    /// What was probably meant instead of a misspelled name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+    /// The fixes that can be applied automatically
+    #[serde(default)]
+    pub fixes: Vec<SuggestedFix>,
+}
+
+/// Apply edits to the text of a file, returning the new text and how many were applied
+///
+/// Ranges refer to the original text: edits are applied from the last to the
+/// first, and an edit overlapping one already applied, or out of the text, is
+/// skipped.
+pub fn apply_edits(text: &str, edits: &[&TextEdit]) -> (String, usize) {
+    let mut line_starts = vec![0];
+    line_starts.extend(text.match_indices('\n').map(|(index, _)| index + 1));
+    let offset = |position: Position| -> Option<usize> {
+        let start = *line_starts.get(position.line.checked_sub(1)?)?;
+        let line = text[start..].split('\n').next().unwrap_or_default();
+        let column = position.column.checked_sub(1)?;
+        match line.char_indices().nth(column) {
+            Some((index, _)) => Some(start + index),
+            None if column == line.chars().count() => Some(start + line.len()),
+            None => None,
+        }
+    };
+
+    let mut ranges: Vec<(usize, usize, &str)> = edits
+        .iter()
+        .filter_map(|edit| {
+            let (start, end) = (offset(edit.range.start)?, offset(edit.range.end)?);
+            (start <= end).then_some((start, end, edit.new_text.as_str()))
+        })
+        .collect();
+    ranges.sort_by(|left, right| right.0.cmp(&left.0).then(right.1.cmp(&left.1)));
+
+    let mut result = text.to_string();
+    let mut applied = 0;
+    let mut limit = text.len();
+    for (start, end, new_text) in ranges {
+        // Two insertions at the same place would be applied in no given order
+        if end > limit || (applied > 0 && start == end && end == limit) {
+            continue;
+        }
+        result.replace_range(start..end, new_text);
+        limit = start;
+        applied += 1;
+    }
+    (result, applied)
+}
+
+/// Version of the SARIF format of [`sarif`]
+pub const SARIF_VERSION: &str = "2.1.0";
+
+/// Base of the locations under the root given to [`sarif`]
+const SARIF_ROOT: &str = "%SRCROOT%";
+
+/// A SARIF log with one run of the compiler reporting the diagnostics
+///
+/// Every code reported becomes a rule described by its documentation, or by
+/// its title in the locale of the process once one is chosen. Help
+/// and suggestions are appended to the message, as SARIF has no place for
+/// them without a replacement to apply. Files under `root` are located
+/// relative to it, as `%SRCROOT%`, so code scanning services can match them
+/// with the files of the repository. Diagnostics without a position or
+/// parsed from memory have no location.
+pub fn sarif(diagnostics: &[Diagnostic], root: &Path) -> Value {
+    let mut rules: Vec<&'static str> = Vec::new();
+    for diagnostic in diagnostics {
+        if !rules.contains(&diagnostic.code) {
+            rules.push(diagnostic.code);
+        }
+    }
+
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let (diagnostic, locale) = diagnostic.in_current_locale();
+            let mut text = diagnostic.message.written();
+            if let Some(help) = &diagnostic.help {
+                text.push_str(&format!("\n{}: {}", help_label(locale), help));
+            }
+            if let Some(suggestion) = &diagnostic.suggestion {
+                text.push_str(&format!("\n{}: {}", help_label(locale), suggestion_help(suggestion, locale)));
+            }
+
+            let mut result = json!({
+                "ruleId": diagnostic.code,
+                "ruleIndex": rules.iter().position(|code| *code == diagnostic.code),
+                "level": match diagnostic.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                },
+                "message": { "text": text },
+            });
+            if let (true, Some(file)) = (diagnostic.span.is_known(), &diagnostic.span.file) {
+                let span = &diagnostic.span;
+                let mut region = json!({ "startLine": span.line, "startColumn": span.column });
+                if span.length > 0 {
+                    region["endColumn"] = json!(span.column + span.length);
+                }
+                if !span.snippet.is_empty() {
+                    region["snippet"] = json!({ "text": span.snippet });
+                }
+                let location = match Path::new(file).strip_prefix(root) {
+                    Ok(relative) => json!({ "uri": uri(&relative.to_string_lossy()), "uriBaseId": SARIF_ROOT }),
+                    Err(_) => json!({ "uri": uri(file) }),
+                };
+                result["locations"] = json!([{
+                    "physicalLocation": {
+                        "artifactLocation": location,
+                        "region": region,
+                    }
+                }]);
+            }
+            if let Some(suggestion) = &diagnostic.suggestion {
+                result["properties"] = json!({ "suggestion": suggestion });
+            }
+            result
+        })
+        .collect();
+
+    let rules: Vec<Value> = rules
+        .into_iter()
+        .map(|code| {
+            let mut rule = json!({ "id": code });
+            let description = match i18n::locale() {
+                Some(locale) => i18n::title(code, locale),
+                None => codes::description(code),
+            };
+            if let Some(description) = description {
+                rule["shortDescription"] = json!({ "text": description });
+            }
+            rule
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": SARIF_VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "kumeo",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "originalUriBaseIds": {
+                SARIF_ROOT: { "uri": format!("{}/", uri(&root.to_string_lossy()).trim_end_matches('/')) }
+            },
+            "results": results,
+        }]
+    })
+}
+
+/// The URI of a path in a SARIF log: a relative reference, or a `file:` URI for absolute paths
+fn uri(file: &str) -> String {
+    let path = file.replace('\\', "/");
+    if path.starts_with('/') {
+        format!("file://{}", path)
+    } else if path.as_bytes().get(1) == Some(&b':') {
+        // A Windows drive such as `C:/`
+        format!("file:///{}", path)
+    } else {
+        path
+    }
+}
+
+/// The candidate closest to a misspelled name, if any is close enough to be a typo
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let tolerance = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= tolerance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance between two strings
+fn edit_distance(left: &str, right: &str) -> usize {
+    let right: Vec<char> = right.chars().collect();
+    let mut previous: Vec<usize> = (0..=right.len()).collect();
+    for (i, l) in left.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, r) in right.iter().enumerate() {
+            let substitution = previous[j] + usize::from(l != *r);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[right.len()]
+}
@@ -0,0 +1,130 @@
+//! Documentation of a program
+//!
+//! Workflows and agents are documented with `///` comments above them, which
+//! the parser keeps as their `doc`. This module renders that documentation
+//! outside the generated agents: as the Markdown pages of a documentation
+//! site, one per workflow plus an index, with the front matter of the
+//! project's Jekyll site, and as the hover text an editor shows over a
+//! workflow or agent.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use crate::ast::{Agent, Program, Span, Workflow};
+
+/// A page of the documentation site
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocPage {
+    /// Path of the page, relative to the site directory
+    pub path: PathBuf,
+    /// Markdown of the page, front matter included
+    pub contents: String,
+}
+
+/// Pages documenting a program: an index and a page per workflow
+pub fn site_pages(program: &Program) -> Vec<DocPage> {
+    let mut index = front_matter("Workflows", None, 1);
+    index.push_str("# Workflows\n\n");
+    for workflow in &program.workflows {
+        let summary = workflow.doc.as_deref().and_then(|doc| doc.lines().next()).unwrap_or_default();
+        writeln!(index, "- [{}]({}.html) {}", workflow.name, page_name(&workflow.name), summary).ok();
+    }
+
+    let mut pages = vec![DocPage { path: PathBuf::from("index.md"), contents: index }];
+    pages.extend(program.workflows.iter().enumerate().map(|(position, workflow)| DocPage {
+        path: PathBuf::from(format!("{}.md", page_name(&workflow.name))),
+        contents: workflow_page(workflow, position + 1),
+    }));
+    pages
+}
+
+/// Markdown shown when hovering a position of a source, from 1
+///
+/// The position must fall on the first line of a workflow or agent, such as
+/// on `workflow Orders {` or on `LLM(id: "classify", ...)`. `program` is the
+/// source parsed on its own, so that every span is one of its positions.
+pub fn hover(program: &Program, line: usize, column: usize) -> Option<String> {
+    let covers = |span: &Span| span.line == line && (span.column..span.column + span.length.max(1)).contains(&column);
+
+    // An agent on the first line of its workflow is more specific than the workflow
+    let mut agents = program
+        .workflows
+        .iter()
+        .flat_map(|workflow| workflow.agents.iter().chain(workflow.preprocessors.iter().flatten()))
+        .chain(program.subworkflows.iter().flat_map(|subworkflow| &subworkflow.agents));
+    if let Some(agent) = agents.find(|agent| covers(&agent.span)) {
+        return Some(agent_hover(agent));
+    }
+    let workflow = program.workflows.iter().find(|workflow| covers(&workflow.span))?;
+    let mut hover = format!("**workflow** `{}`", workflow.name);
+    if let Some(version) = &workflow.version {
+        write!(hover, " {}", version).ok();
+    }
+    if let Some(doc) = &workflow.doc {
+        write!(hover, "\n\n{}", doc).ok();
+    }
+    Some(hover)
+}
+
+/// Hover text of an agent
+fn agent_hover(agent: &Agent) -> String {
+    let mut hover = format!("**{:?}**", agent.agent_type);
+    if let Some(id) = &agent.id {
+        write!(hover, " `{}`", id).ok();
+    }
+    if let Some(doc) = &agent.doc {
+        write!(hover, "\n\n{}", doc).ok();
+    }
+    hover
+}
+
+/// Page of a workflow
+fn workflow_page(workflow: &Workflow, nav_order: usize) -> String {
+    let mut page = front_matter(&workflow.name, Some("Workflows"), nav_order);
+    writeln!(page, "# {}\n", workflow.name).ok();
+    if let Some(version) = &workflow.version {
+        writeln!(page, "Version {}\n", version).ok();
+    }
+    if let Some(doc) = &workflow.doc {
+        writeln!(page, "{}\n", doc).ok();
+    }
+    if let Some(source) = &workflow.source {
+        writeln!(page, "- Source: `{}`", source.topic()).ok();
+    }
+    if let Some(target) = &workflow.target {
+        writeln!(page, "- Target: `{}`", target.topic()).ok();
+    }
+
+    page.push_str("\n## Agents\n");
+    for (agent, topics) in workflow.agents.iter().zip(workflow.deployed_topics()) {
+        writeln!(page, "\n### {}\n", agent.id.as_deref().unwrap_or("<unnamed>")).ok();
+        writeln!(page, "{:?} agent.\n", agent.agent_type).ok();
+        if let Some(doc) = &agent.doc {
+            writeln!(page, "{}\n", doc).ok();
+        }
+        if let Some(input) = topics.input {
+            writeln!(page, "- Input: `{}`", input).ok();
+        }
+        if let Some(output) = topics.output {
+            writeln!(page, "- Output: `{}`", output).ok();
+        }
+    }
+    page
+}
+
+/// Front matter of a page of the site
+fn front_matter(title: &str, parent: Option<&str>, nav_order: usize) -> String {
+    let mut front_matter = format!("---\nlayout: default\ntitle: {}\n", title);
+    if let Some(parent) = parent {
+        writeln!(front_matter, "parent: {}", parent).ok();
+    } else {
+        front_matter.push_str("has_children: true\n");
+    }
+    writeln!(front_matter, "nav_order: {}\n---\n", nav_order).ok();
+    front_matter
+}
+
+/// File name of the page of a workflow, without extension
+fn page_name(workflow: &str) -> String {
+    workflow.to_lowercase()
+}
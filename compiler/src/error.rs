@@ -1,67 +1,77 @@
-use thiserror::Error;
+use std::fmt;
 
-// Use thiserror's derive macro to implement Display automatically
-#[derive(Error, Debug, Clone)]
+use crate::i18n::Message;
+use crate::message;
+
+// Display is implemented by hand, so errors are rendered in the locale of the process
+#[derive(Debug, Clone)]
 pub enum KumeoError {
-    #[error("Lexer error: {0}")]
     LexerError(String),
     
-    #[error("Parser error at line {line}, column {column}: {message}")]
     ParserError {
         line: usize,
         column: usize,
-        message: String,
+        message: Message,
     },
     
-    #[error("Semantic error: {0}")]
     SemanticError(String),
     
-    #[error("Semantic errors: {0:?}")]
     SemanticErrors(Vec<String>),
     
-    #[error("Type error: {0}")]
-    TypeError(String),
+    TypeError(Message),
     
-    #[error("IO error: {0}")]
-    IoError(String),
+    IoError(Message),
+    
+    /// A template that failed to render, with the causes reported by Tera
+    TemplateError {
+        /// The name of the template, as registered in Tera
+        template: String,
+        /// The error Tera reported, followed by its causes
+        message: String,
+    },
     
-    #[error("Unknown error: {0}")]
     Unknown(String),
 }
 
+impl fmt::Display for KumeoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            KumeoError::LexerError(error) => message!("Lexer error: {}", error),
+            KumeoError::ParserError { line, column, message } => {
+                message!("Parser error at line {}, column {}: {}", line, column, message)
+            }
+            KumeoError::SemanticError(error) => message!("Semantic error: {}", error),
+            KumeoError::SemanticErrors(errors) => message!("Semantic errors:\n{}", errors.join("\n")),
+            KumeoError::TypeError(error) => message!("Type error: {}", error),
+            KumeoError::IoError(error) => message!("IO error: {}", error),
+            KumeoError::TemplateError { template, message } => message!("Failed to render template {}: {}", template, message),
+            KumeoError::Unknown(error) => message!("Unknown error: {}", error),
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for KumeoError {}
+
 // Implementation to convert std::io::Error to KumeoError
 impl From<std::io::Error> for KumeoError {
     fn from(err: std::io::Error) -> Self {
-        KumeoError::IoError(err.to_string())
+        KumeoError::IoError(Message::Text(err.to_string()))
     }
 }
 
 // Implementation to convert ParseError to KumeoError
 impl From<crate::parser::error::ParseError> for KumeoError {
     fn from(err: crate::parser::error::ParseError) -> Self {
-        match err {
-            crate::parser::error::ParseError::PestError(e) => {
-                KumeoError::ParserError {
-                    line: 0, // No tenemos acceso a la línea exacta desde Pest
-                    column: 0, // No tenemos acceso a la columna exacta desde Pest
-                    message: e.to_string(),
-                }
-            }
-            crate::parser::error::ParseError::SemanticError(msg) => {
-                KumeoError::ParserError {
-                    line: 0,
-                    column: 0,
-                    message: msg,
-                }
-            }
-            crate::parser::error::ParseError::Generic(msg) => {
-                KumeoError::ParserError {
-                    line: 0,
-                    column: 0,
-                    message: msg,
-                }
-            }
-        }
+        // Los errores sin posición (p. ej. al leer un archivo) quedan en 0:0
+        let (line, column) = err.line_col().unwrap_or_default();
+        // Con posición se muestra como un diagnóstico, con la línea en la que está
+        let diagnostic = err.diagnostic();
+        let message = match diagnostic.span.is_known() {
+            true => Message::Text(diagnostic.to_string()),
+            false => diagnostic.message,
+        };
+        KumeoError::ParserError { line, column, message }
     }
 }
 
@@ -0,0 +1,805 @@
+//! Source formatter for the Kumeo DSL.
+//!
+//! The formatter rewrites the whitespace between the tokens of a program,
+//! writes `key = value` as `key: value` and adds the commas and semicolons
+//! left out at line ends, so everything else the AST doesn't keep survives
+//! it: comments, the quotes of strings, `resource(...)` wrappers and the
+//! optional semicolons of tests and top-level items.
+//! Brackets that fit on a line stay on it; the others are broken after each
+//! `,` and `;`, with their contents indented. Workflow and subworkflow
+//! bodies, `agents: [...]` pipelines and the `schemas:` block are always
+//! broken. Blank lines between entries are kept, one at most, and top-level
+//! blocks are separated by one.
+//!
+//! The formatted source is parsed again and must give the same program, so
+//! a formatting bug surfaces as an error instead of a changed program.
+//!
+//! The indentation, the line length, the trailing commas of broken brackets
+//! and the order of object keys can be configured in a `.kumeofmt.toml`
+//! file, found next to the formatted file or in one of its parent
+//! directories:
+//!
+//! ```toml
+//! indent_width = 2
+//! max_line_length = 120
+//! trailing_commas = "always"  # or "never"
+//! key_order = "alphabetical"  # or "preserve"
+//! ```
+//!
+//! `kumeo format` takes files, directories (every `.kumeo` file under them)
+//! and globs; [`source_files`] expands them and [`diff`] shows what
+//! formatting a file would change.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::message;
+use crate::parser::{
+    self,
+    error::{ParseError, ParseResult},
+};
+
+/// Name of the formatter's configuration file
+pub const CONFIG_FILE: &str = ".kumeofmt.toml";
+
+/// Spaces per indentation level when not configured
+pub const DEFAULT_INDENT_WIDTH: usize = 4;
+
+/// Columns a line may take before the brackets on it are broken, when not configured
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 100;
+
+/// Extension of Kumeo sources
+pub const SOURCE_EXTENSION: &str = "kumeo";
+
+/// Lines of context around the changes of a diff
+const DIFF_CONTEXT: usize = 3;
+
+/// Keywords that start a top-level item
+const ITEM_KEYWORDS: [&str; 5] = ["import", "const", "schemas", "workflow", "subworkflow"];
+
+/// Top-level items separated from their neighbours by a blank line
+const BLOCK_KEYWORDS: [&str; 3] = ["schemas", "workflow", "subworkflow"];
+
+/// Two-character operators of conditions
+const OPERATORS: [&str; 7] = ["==", "!=", ">=", "<=", "&&", "||", "??"];
+
+/// Whether brackets broken over several lines end their last entry with a comma
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailingCommas {
+    /// No bracket ends with a comma
+    #[default]
+    Never,
+    /// Broken brackets end with a comma; those on one line don't
+    Always,
+}
+
+/// The order the keys of objects are written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyOrder {
+    /// As written
+    #[default]
+    Preserve,
+    /// Sorted by name, in objects without comments
+    Alphabetical,
+}
+
+/// Formatter options, as read from a `.kumeofmt.toml` file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormatOptions {
+    /// Spaces per indentation level
+    pub indent_width: usize,
+    /// Columns a line may take before the brackets on it are broken
+    pub max_line_length: usize,
+    /// Whether broken brackets end with a comma
+    pub trailing_commas: TrailingCommas,
+    /// The order of the keys of objects
+    pub key_order: KeyOrder,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent_width: DEFAULT_INDENT_WIDTH,
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+            trailing_commas: TrailingCommas::default(),
+            key_order: KeyOrder::default(),
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Read the options of a `.kumeofmt.toml` file's contents; missing options keep their default.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let options: Self = config::Config::builder()
+            .add_source(config::File::from_str(text, config::FileFormat::Toml))
+            .build()
+            .and_then(config::Config::try_deserialize)
+            .map_err(|e| anyhow!(message!("Invalid formatter options: {}", e)))?;
+        if options.indent_width == 0 || options.max_line_length == 0 {
+            return Err(anyhow!(message!("Invalid formatter options: indent_width and max_line_length must be above 0")));
+        }
+        Ok(options)
+    }
+
+    /// Read the options of a `.kumeofmt.toml` file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| message!("Failed to read {}", path.display()))?;
+        Self::from_toml(&text).with_context(|| message!("In {}", path.display()))
+    }
+
+    /// The options that apply to a file: those of the closest `.kumeofmt.toml`
+    /// in its directory or a parent, or the defaults without one
+    pub fn for_file(file: &Path) -> Result<Self> {
+        match find_config(file) {
+            Some(path) => Self::load(&path),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+/// The closest `.kumeofmt.toml` in the directory of a file or one of its parents
+pub fn find_config(file: &Path) -> Option<PathBuf> {
+    let file = std::fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+    file.ancestors().skip(1).map(|dir| dir.join(CONFIG_FILE)).find(|path| path.is_file())
+}
+
+/// The Kumeo sources a target names: the file itself, every `.kumeo` file
+/// under a directory, or the files a glob matches, sorted by path
+pub fn source_files(target: &Path) -> Result<Vec<PathBuf>> {
+    if target.is_file() {
+        return Ok(vec![target.to_path_buf()]);
+    }
+    let (base, pattern) = if target.is_dir() {
+        (target.to_path_buf(), format!("**/*.{}", SOURCE_EXTENSION))
+    } else {
+        let pattern = target.to_string_lossy();
+        if !pattern.contains(['*', '?', '[', '{']) {
+            return Err(anyhow!(message!("No such file or directory: {}", target.display())));
+        }
+        // The glob is matched from its longest directory without wildcards
+        let base: PathBuf = target
+            .components()
+            .take_while(|component| !component.as_os_str().to_string_lossy().contains(['*', '?', '[', '{']))
+            .collect();
+        let rest = target.strip_prefix(&base).unwrap_or(target).to_string_lossy().replace('\\', "/");
+        (if base.as_os_str().is_empty() { PathBuf::from(".") } else { base }, rest)
+    };
+
+    let mut files = globwalk::GlobWalkerBuilder::from_patterns(&base, &[pattern.as_str()])
+        .file_type(globwalk::FileType::FILE)
+        .build()
+        .with_context(|| message!("Invalid pattern: {}", target.display()))?
+        .map(|entry| entry.map(|entry| entry.into_path()))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .with_context(|| message!("Failed to read {}", target.display()))?;
+    files.sort();
+    Ok(files)
+}
+
+/// A line of a diff
+enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// The lines formatting changes in a source, as a unified diff
+///
+/// Empty when the lines are the same.
+pub fn diff(original: &str, formatted: &str) -> String {
+    let old: Vec<&str> = original.lines().collect();
+    let new: Vec<&str> = formatted.lines().collect();
+
+    // Longest common subsequence of the lines after each position
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(DiffLine::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j]));
+            j += 1;
+        }
+    }
+
+    // Changes closer than twice the context share a hunk
+    let changes: Vec<usize> = (0..lines.len()).filter(|k| !matches!(lines[*k], DiffLine::Same(_))).collect();
+    let mut out = String::new();
+    let mut k = 0;
+    while k < changes.len() {
+        let start = changes[k].saturating_sub(DIFF_CONTEXT);
+        while k + 1 < changes.len() && changes[k + 1] - changes[k] <= 2 * DIFF_CONTEXT + 1 {
+            k += 1;
+        }
+        let end = (changes[k] + DIFF_CONTEXT + 1).min(lines.len());
+        k += 1;
+
+        let old_lines = |lines: &[DiffLine]| lines.iter().filter(|line| !matches!(line, DiffLine::Added(_))).count();
+        let new_lines = |lines: &[DiffLine]| lines.iter().filter(|line| !matches!(line, DiffLine::Removed(_))).count();
+        let hunk = &lines[start..end];
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_lines(&lines[..start]) + 1,
+            old_lines(hunk),
+            new_lines(&lines[..start]) + 1,
+            new_lines(hunk)
+        ));
+        for line in hunk {
+            let (prefix, text) = match line {
+                DiffLine::Same(text) => (' ', text),
+                DiffLine::Removed(text) => ('-', text),
+                DiffLine::Added(text) => ('+', text),
+            };
+            out.push(prefix);
+            out.push_str(text);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Format a program with the default options, keeping its comments.
+///
+/// Fails when the source doesn't parse, or when the formatted source
+/// wouldn't parse to the same program.
+pub fn format(source: &str) -> ParseResult<String> {
+    format_with(source, &FormatOptions::default())
+}
+
+/// Format a program with some options, keeping its comments.
+pub fn format_with(source: &str, options: &FormatOptions) -> ParseResult<String> {
+    let program = parser::parse(source)?;
+
+    let mut nodes = tree(tokenize(source));
+    normalize(&mut nodes, options);
+    let mut printer = Printer { out: String::new(), options };
+    printer.sequence(&nodes, 0, Sequence::TopLevel, false);
+    let formatted = format!("{}\n", printer.out.trim());
+
+    let reparsed = parser::parse(&formatted)
+        .map_err(|e| ParseError::generic(format!("The formatted program doesn't parse: {}", e)))?;
+    if serde_json::to_value(&reparsed).ok() != serde_json::to_value(&program).ok() {
+        return Err(ParseError::generic(message!("Formatting would change the program")));
+    }
+    Ok(formatted)
+}
+
+/// What a token is, as far as layout goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// Identifiers, paths, fields and `$CONSTANTS`
+    Word,
+    /// Strings and numbers
+    Literal,
+    /// `(`, `[` or `{`
+    Open,
+    /// `)`, `]` or `}`
+    Close,
+    Comma,
+    Semicolon,
+    Colon,
+    /// Operators of conditions, and `=` of constants
+    Operator,
+    /// A `//` comment, without its line break
+    Comment,
+}
+
+/// A token of the source
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    kind: Kind,
+    text: &'a str,
+    /// Line breaks between the previous token and this one
+    newlines: usize,
+}
+
+/// Split a source into tokens, dropping the whitespace between them
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let bytes = source.as_bytes();
+    let is_word = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+    let digits = |mut i: usize| {
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        i
+    };
+
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut newlines = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let kind = match bytes[i] {
+            b'\n' => {
+                newlines += 1;
+                i += 1;
+                continue;
+            }
+            b' ' | b'\t' | b'\r' => {
+                i += 1;
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i = source[i..].find('\n').map_or(bytes.len(), |end| i + end);
+                Kind::Comment
+            }
+            quote @ (b'"' | b'\'') => {
+                i = source[i + 1..].find(quote as char).map_or(bytes.len(), |end| i + 1 + end + 1);
+                Kind::Literal
+            }
+            b'(' | b'[' | b'{' => {
+                i += 1;
+                Kind::Open
+            }
+            b')' | b']' | b'}' => {
+                i += 1;
+                Kind::Close
+            }
+            b',' => {
+                i += 1;
+                Kind::Comma
+            }
+            b';' => {
+                i += 1;
+                Kind::Semicolon
+            }
+            b':' => {
+                i += 1;
+                Kind::Colon
+            }
+            // `key = value` is written `key: value`; constants keep their `=`
+            b'=' if bytes.get(i + 1) != Some(&b'=') && tokens.iter().rev().nth(1).map(|token| token.text) != Some("const") => {
+                tokens.push(Token { kind: Kind::Colon, text: ":", newlines });
+                newlines = 0;
+                i += 1;
+                continue;
+            }
+            b'-' | b'0'..=b'9' if bytes[i].is_ascii_digit() || bytes.get(i + 1).is_some_and(u8::is_ascii_digit) => {
+                i = digits(i + 1);
+                if bytes.get(i) == Some(&b'.') && bytes.get(i + 1).is_some_and(u8::is_ascii_digit) {
+                    i = digits(i + 1);
+                }
+                if bytes.get(i) == Some(&b'%') {
+                    i += 1;
+                }
+                Kind::Literal
+            }
+            b if b == b'$' || b == b'_' || b.is_ascii_alphabetic() => {
+                // A word takes its dotted segments, so paths and fields stay whole
+                i += 1;
+                loop {
+                    while i < bytes.len() && is_word(bytes[i]) {
+                        i += 1;
+                    }
+                    let separator = if source[i..].starts_with("?.") {
+                        2
+                    } else {
+                        // Bare model names such as `gpt-4o` take their dashes
+                        usize::from(matches!(bytes.get(i), Some(b'.' | b'-')))
+                    };
+                    if separator == 0 || !bytes.get(i + separator).is_some_and(|&b| is_word(b)) {
+                        break;
+                    }
+                    i += separator;
+                }
+                Kind::Word
+            }
+            _ => {
+                let two = source.get(i..i + 2);
+                i += if two.is_some_and(|two| OPERATORS.contains(&two)) {
+                    2
+                } else {
+                    source[i..].chars().next().map_or(1, char::len_utf8)
+                };
+                Kind::Operator
+            }
+        };
+        tokens.push(Token { kind, text: &source[start..i], newlines });
+        newlines = 0;
+    }
+    tokens
+}
+
+/// How the contents of a bracket are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// On one line when they fit, one entry per line otherwise
+    Fit,
+    /// On one line, as the parentheses of a condition
+    Inline,
+    /// One entry per line
+    Broken,
+    /// One statement per line, as a workflow or subworkflow body
+    Block,
+}
+
+/// A token, or a bracket with everything up to its closing bracket
+#[derive(Debug)]
+enum Node<'a> {
+    Token(Token<'a>),
+    Group(Group<'a>),
+}
+
+/// A bracket and its contents
+#[derive(Debug)]
+struct Group<'a> {
+    open: Token<'a>,
+    nodes: Vec<Node<'a>>,
+    close: Option<Token<'a>>,
+    layout: Layout,
+}
+
+impl<'a> Node<'a> {
+    /// The first token of the node
+    fn first(&self) -> Token<'a> {
+        match self {
+            Node::Token(token) => *token,
+            Node::Group(group) => group.open,
+        }
+    }
+
+    /// The last token of the node
+    fn last(&self) -> Token<'a> {
+        match self {
+            Node::Token(token) => *token,
+            Node::Group(group) => group.close.or_else(|| group.nodes.last().map(Node::last)).unwrap_or(group.open),
+        }
+    }
+
+    /// The node's token, if it is a token of that kind
+    fn token(&self, kind: Kind) -> Option<Token<'a>> {
+        match self {
+            Node::Token(token) if token.kind == kind => Some(*token),
+            _ => None,
+        }
+    }
+}
+
+/// Nest the tokens inside their brackets
+fn tree(tokens: Vec<Token<'_>>) -> Vec<Node<'_>> {
+    let mut stack: Vec<(Token, Layout, Vec<Node>)> = Vec::new();
+    let mut nodes = Vec::new();
+    for token in tokens {
+        match token.kind {
+            Kind::Open => {
+                let layout = layout(&nodes, token);
+                stack.push((token, layout, std::mem::take(&mut nodes)));
+            }
+            Kind::Close if !stack.is_empty() => {
+                let (open, layout, parent) = stack.pop().expect("an open bracket");
+                let group = Group { open, nodes: std::mem::replace(&mut nodes, parent), close: Some(token), layout };
+                nodes.push(Node::Group(group));
+            }
+            _ => nodes.push(Node::Token(token)),
+        }
+    }
+    // Brackets left open stay open
+    while let Some((open, layout, parent)) = stack.pop() {
+        let group = Group { open, nodes: std::mem::replace(&mut nodes, parent), close: None, layout };
+        nodes.push(Node::Group(group));
+    }
+    nodes
+}
+
+/// Add the separators left out at line ends, drop the trailing commas of the
+/// brackets, and sort the keys of objects if configured
+///
+/// Trailing commas are written back when a bracket is broken and they are configured.
+fn normalize(nodes: &mut Vec<Node>, options: &FormatOptions) {
+    for node in nodes.iter_mut() {
+        let Node::Group(group) = node else {
+            continue;
+        };
+        normalize(&mut group.nodes, options);
+        separate(group);
+        if group.layout == Layout::Block {
+            continue;
+        }
+        if let Some(last) = group.nodes.iter().rposition(|node| node.token(Kind::Comment).is_none()) {
+            if group.nodes[last].token(Kind::Comma).is_some() {
+                group.nodes.remove(last);
+            }
+        }
+        if options.key_order == KeyOrder::Alphabetical && group.open.text == "{" && group.layout == Layout::Fit {
+            sort_keys(group);
+        }
+    }
+}
+
+/// Add the commas left out between the entries of a bracket, or the semicolons between the statements of a block
+///
+/// A separator is missing where an entry starts a line and neither it nor the
+/// previous token continues the one before. The statements of a block all end
+/// with one, but for tests, which need none.
+fn separate(group: &mut Group) {
+    let (kind, text) = if group.layout == Layout::Block { (Kind::Semicolon, ";") } else { (Kind::Comma, ",") };
+    let joins = |token: Token| matches!(token.kind, Kind::Comma | Kind::Semicolon | Kind::Colon | Kind::Operator);
+    let mut previous: Option<usize> = None;
+    let mut statement: Option<&str> = None;
+    let mut i = 0;
+    while i < group.nodes.len() {
+        let first = group.nodes[i].first();
+        if first.kind == Kind::Comment {
+            i += 1;
+            continue;
+        }
+        if let Some(previous) = previous {
+            let last = group.nodes[previous].last();
+            let missing = first.newlines > 0 && !joins(last) && !joins(first) && first.kind != Kind::Close;
+            if missing && (group.layout != Layout::Block || statement != Some("test")) {
+                group.nodes.insert(previous + 1, Node::Token(Token { kind, text, newlines: 0 }));
+                statement = None;
+                i += 1;
+            }
+        }
+        if group.nodes[i].token(kind).is_some() {
+            statement = None;
+        } else if statement.is_none() {
+            statement = Some(first.text);
+        }
+        previous = Some(i);
+        i += 1;
+    }
+    // The last statement of a block ends with its semicolon too
+    if let Some(previous) = previous.filter(|_| group.layout == Layout::Block && statement.is_some_and(|s| s != "test")) {
+        group.nodes.insert(previous + 1, Node::Token(Token { kind, text, newlines: 0 }));
+    }
+}
+
+/// Sort the pairs of an object by key; objects with comments or other entries are left as they are
+fn sort_keys(group: &mut Group) {
+    if group.nodes.iter().any(|node| node.token(Kind::Comment).is_some()) {
+        return;
+    }
+    let mut pairs: Vec<Vec<Node>> = vec![Vec::new()];
+    for node in group.nodes.drain(..) {
+        match node.token(Kind::Comma) {
+            Some(_) => pairs.push(Vec::new()),
+            None => pairs.last_mut().expect("a pair").push(node),
+        }
+    }
+    let key = |pair: &[Node]| match pair {
+        [key, colon, _, ..] if matches!(key.first().kind, Kind::Word | Kind::Literal) && colon.token(Kind::Colon).is_some() => {
+            Some(key.first().text.trim_matches(|c| c == '"' || c == '\'').to_string())
+        }
+        _ => None,
+    };
+    if pairs.iter().all(|pair| key(pair).is_some()) {
+        pairs.sort_by_key(|pair| key(pair));
+    }
+
+    let comma = |newlines| Node::Token(Token { kind: Kind::Comma, text: ",", newlines });
+    for (i, pair) in pairs.into_iter().enumerate() {
+        if i > 0 {
+            group.nodes.push(comma(0));
+        }
+        group.nodes.extend(pair);
+    }
+}
+
+/// Layout of a bracket opened after some nodes
+fn layout<'a>(before: &[Node<'a>], open: Token) -> Layout {
+    let word = |node: Option<&Node<'a>>| node.and_then(|node| node.token(Kind::Word)).map(|token| token.text);
+    let mut previous = before.iter().rev();
+    let (last, second) = (previous.next(), previous.next());
+    match (open.text, word(second)) {
+        ("{", Some("workflow" | "subworkflow")) if word(last).is_some() => Layout::Block,
+        ("[", Some("agents")) | ("{", Some("schemas")) if last.is_some_and(|node| node.token(Kind::Colon).is_some()) => {
+            Layout::Broken
+        }
+        ("(", _) if word(last).is_none() => Layout::Inline,
+        _ => Layout::Fit,
+    }
+}
+
+/// How the entries of a sequence are split
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sequence {
+    /// After `,` and `;`
+    List,
+    /// After `;` and after a braced block not followed by one
+    Statements,
+    /// As statements, and before the keyword of each item
+    TopLevel,
+}
+
+/// Writes the formatted source
+#[derive(Debug)]
+struct Printer<'o> {
+    out: String,
+    options: &'o FormatOptions,
+}
+
+impl Printer<'_> {
+    /// Column the next character is written at, from 0
+    fn column(&self) -> usize {
+        let line = self.out.rfind('\n').map_or(&self.out[..], |i| &self.out[i + 1..]);
+        line.chars().count()
+    }
+
+    /// Start a new line at an indentation level
+    fn newline(&mut self, level: usize) {
+        self.out.push('\n');
+        self.out.push_str(&" ".repeat(self.options.indent_width * level));
+    }
+
+    /// Write the entries of a sequence, each on a line of its own
+    ///
+    /// With `comma`, the last entry that isn't a comment ends with a comma.
+    fn sequence(&mut self, nodes: &[Node], level: usize, kind: Sequence, comma: bool) {
+        let entries = entries(nodes, kind);
+        let is_comment = |entry: &[&Node]| entry.len() == 1 && entry[0].token(Kind::Comment).is_some();
+        let is_trailing = |entry: &[&Node]| is_comment(entry) && entry[0].first().newlines == 0;
+        let is_block = |entry: &[&Node]| {
+            entry[0].token(Kind::Word).is_some_and(|token| BLOCK_KEYWORDS.contains(&token.text))
+        };
+
+        let last = entries.iter().rposition(|entry| !is_comment(entry));
+        let mut previous: Option<usize> = None;
+        for (i, entry) in entries.iter().enumerate() {
+            if previous.is_some() && is_trailing(entry) {
+                self.out.push(' ');
+                self.out.push_str(entry[0].first().text);
+                continue;
+            }
+            if let Some(previous) = previous {
+                // Comments go with the block they precede
+                let starts_block = entries[i..].iter().find(|entry| !is_comment(entry)).is_some_and(|next| is_block(next))
+                    && (is_block(entry) || is_comment(entry));
+                let separated = kind == Sequence::TopLevel
+                    && ((starts_block && !is_comment(&entries[previous])) || is_block(&entries[previous]));
+                if separated || entry[0].first().newlines >= 2 {
+                    self.out.push('\n');
+                }
+            }
+            self.newline(level);
+            self.entry(entry, level);
+            if comma && Some(i) == last {
+                self.out.push(',');
+            }
+            previous = Some(i);
+        }
+    }
+
+    /// Write an entry of a sequence on the current line, breaking the brackets that don't fit
+    fn entry(&mut self, entry: &[&Node], level: usize) {
+        let mut previous: Option<Token> = None;
+        for node in entry {
+            if let Some(previous) = previous {
+                self.out.push_str(space(previous, node.first()));
+            }
+            match node {
+                Node::Token(token) => self.out.push_str(token.text),
+                Node::Group(group) => match flat(node) {
+                    Some(flat)
+                        if group.layout == Layout::Inline
+                            || self.column() + flat.chars().count() < self.options.max_line_length =>
+                    {
+                        self.out.push_str(&flat)
+                    }
+                    _ => self.broken(group, level),
+                },
+            }
+            previous = Some(node.last());
+        }
+    }
+
+    /// Write a bracket with one entry of its contents per line
+    fn broken(&mut self, group: &Group, level: usize) {
+        self.out.push_str(group.open.text);
+        let mut nodes = &group.nodes[..];
+        if nodes.is_empty() {
+            self.out.push_str(group.close.map_or("", |close| close.text));
+            return;
+        }
+        // A comment right after the bracket stays on its line
+        if let Some(comment) = nodes[0].token(Kind::Comment).filter(|comment| comment.newlines == 0) {
+            self.out.push(' ');
+            self.out.push_str(comment.text);
+            nodes = &nodes[1..];
+        }
+        let kind = if group.layout == Layout::Block { Sequence::Statements } else { Sequence::List };
+        let comma = kind == Sequence::List && self.options.trailing_commas == TrailingCommas::Always;
+        self.sequence(nodes, level + 1, kind, comma);
+        if let Some(close) = group.close {
+            self.newline(level);
+            self.out.push_str(close.text);
+        }
+    }
+}
+
+/// Split a sequence into the entries written on lines of their own
+///
+/// Comments are entries of their own, so that a line break always follows them.
+fn entries<'n, 'a>(nodes: &'n [Node<'a>], kind: Sequence) -> Vec<Vec<&'n Node<'a>>> {
+    let mut entries = Vec::new();
+    let mut entry: Vec<&Node> = Vec::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let first = node.first();
+        let item_start = kind == Sequence::TopLevel
+            && first.kind == Kind::Word
+            && ITEM_KEYWORDS.contains(&first.text)
+            && entry.last().is_some_and(|last| {
+                let last = last.last();
+                matches!(last.kind, Kind::Word | Kind::Literal | Kind::Close) && !ITEM_KEYWORDS.contains(&last.text)
+            });
+        if (first.kind == Kind::Comment || item_start) && !entry.is_empty() {
+            entries.push(std::mem::take(&mut entry));
+        }
+        entry.push(node);
+
+        let next = nodes[i + 1..].iter().find(|next| next.token(Kind::Comment).is_none());
+        let ends = match node {
+            Node::Token(token) => matches!(token.kind, Kind::Comma | Kind::Semicolon | Kind::Comment),
+            // A block such as a workflow body or a test ends its statement when no `;` follows it
+            Node::Group(group) => {
+                kind != Sequence::List
+                    && group.open.text == "{"
+                    && !next.is_some_and(|next| matches!(next.first().kind, Kind::Semicolon | Kind::Comma))
+            }
+        };
+        if ends {
+            entries.push(std::mem::take(&mut entry));
+        }
+    }
+    if !entry.is_empty() {
+        entries.push(entry);
+    }
+    entries
+}
+
+/// A node on one line, if it can be written on one
+fn flat(node: &Node) -> Option<String> {
+    match node {
+        Node::Token(token) if token.kind == Kind::Comment || token.text.contains('\n') => None,
+        Node::Token(token) => Some(token.text.to_string()),
+        Node::Group(group) if !matches!(group.layout, Layout::Fit | Layout::Inline) => None,
+        Node::Group(group) => {
+            let mut inner = String::new();
+            let mut previous: Option<Token> = None;
+            for node in &group.nodes {
+                if let Some(previous) = previous {
+                    inner.push_str(space(previous, node.first()));
+                }
+                inner.push_str(&flat(node)?);
+                previous = Some(node.last());
+            }
+            let close = group.close.map_or("", |close| close.text);
+            Some(if group.open.text == "{" && !inner.is_empty() {
+                format!("{{ {} {}", inner, close)
+            } else {
+                format!("{}{}{}", group.open.text, inner, close)
+            })
+        }
+    }
+}
+
+/// Whitespace between two tokens written on the same line
+fn space(previous: Token, next: Token) -> &'static str {
+    match (previous.kind, next.kind) {
+        (_, Kind::Comma | Kind::Semicolon | Kind::Colon) => "",
+        (Kind::Operator, _) if previous.text == "!" => "",
+        (Kind::Word, Kind::Open) if next.text == "(" => "",
+        _ => " ",
+    }
+}
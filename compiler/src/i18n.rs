@@ -0,0 +1,1772 @@
+//! Localization of the messages
+//!
+//! The compiler reports its diagnostics, and the errors its commands fail
+//! with, in English or Spanish. A message is built where the problem is
+//! found with [`message!`](crate::message), which keeps the template it was
+//! written with apart from its arguments; the catalog of this module has
+//! every template in both languages, with `{}` standing for the arguments,
+//! and the message is rendered by looking its template up. The arguments are
+//! messages in turn, often the cause reported by the validation of an
+//! option, and are rendered in the same locale.
+//!
+//! The locale is chosen once per process with [`set_locale`], by `kumeo`
+//! from its `--locale` flag or the [`LOCALE_ENV`] variable. Until it is set,
+//! messages are rendered as they were written, as are templates missing from
+//! the catalog and text that isn't a template, such as names and values.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::diagnostics::codes;
+
+/// Variable with the locale of the messages, as `en` or `es`
+pub const LOCALE_ENV: &str = "KUMEO_LOCALE";
+
+/// A language the messages are rendered in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    /// English
+    En,
+    /// Spanish, the language of the compiler's own messages
+    #[default]
+    Es,
+}
+
+impl Locale {
+    /// Every supported locale
+    pub const ALL: [Locale; 2] = [Locale::En, Locale::Es];
+
+    /// The position of the locale in [`Locale::ALL`], and of its text in the catalog
+    fn position(self) -> usize {
+        match self {
+            Locale::En => 0,
+            Locale::Es => 1,
+        }
+    }
+
+    /// The code of the locale
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Es => "es",
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    /// Parse a locale such as `en`, `es-ES` or `en_US.UTF-8` by its language
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let language = s.split(['-', '_', '.']).next().unwrap_or_default().to_ascii_lowercase();
+        Locale::ALL
+            .into_iter()
+            .find(|locale| locale.code() == language)
+            .ok_or_else(|| format!("Unsupported locale {}, expected en or es", s))
+    }
+}
+
+/// The locale chosen for the process: 0 while unset, else 1 + its position in [`Locale::ALL`]
+static LOCALE: AtomicU8 = AtomicU8::new(0);
+
+/// Choose the locale the messages of the process are rendered in
+pub fn set_locale(locale: Locale) {
+    LOCALE.store(locale.position() as u8 + 1, Ordering::Relaxed);
+}
+
+/// The locale chosen for the process, if any
+pub fn locale() -> Option<Locale> {
+    match LOCALE.load(Ordering::Relaxed) {
+        0 => None,
+        position => Locale::ALL.get(usize::from(position) - 1).copied(),
+    }
+}
+
+/// The title of a code in a locale
+///
+/// English titles are the documentation of [`codes`].
+pub fn title(code: &str, locale: Locale) -> Option<&'static str> {
+    match locale {
+        Locale::En => codes::description(code),
+        Locale::Es => TITLES.iter().find(|(known, _)| *known == code).map(|(_, title)| *title),
+    }
+}
+
+/// A message, rendered in the locale of the process
+///
+/// Messages are built with [`message!`](crate::message), which takes the
+/// same arguments as `format!` but keeps them apart from the template, so
+/// the template can be looked up in the catalog when the message is
+/// rendered. Messages compare by their text as written.
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// Text that isn't a template, such as a name or a value, rendered as written
+    Text(String),
+    /// A template of the catalog, with the messages filling its `{}`
+    Template(&'static str, Vec<Message>),
+    /// Messages joined by a separator, such as the causes of a problem
+    Joined(Vec<Message>, &'static str),
+}
+
+impl Message {
+    /// Messages joined by a separator
+    pub fn join(messages: impl IntoIterator<Item = Message>, separator: &'static str) -> Self {
+        Message::Joined(messages.into_iter().collect(), separator)
+    }
+
+    /// The message in a locale
+    pub fn localized(&self, locale: Locale) -> String {
+        self.render(Some(locale))
+    }
+
+    /// The message as it was written
+    pub fn written(&self) -> String {
+        self.render(None)
+    }
+
+    fn render(&self, locale: Option<Locale>) -> String {
+        match self {
+            Message::Text(text) => text.clone(),
+            Message::Joined(messages, separator) => {
+                messages.iter().map(|message| message.render(locale)).collect::<Vec<_>>().join(separator)
+            }
+            Message::Template(template, arguments) => {
+                let text = match (locale, CATALOG.get(template)) {
+                    (Some(locale), Some(texts)) => texts[locale.position()],
+                    _ => template,
+                };
+                fill(text, arguments.iter().map(|argument| argument.render(locale)))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match locale() {
+            Some(locale) => f.write_str(&self.localized(locale)),
+            None => f.write_str(&self.written()),
+        }
+    }
+}
+
+impl std::error::Error for Message {}
+
+impl PartialEq for Message {
+    fn eq(&self, other: &Self) -> bool {
+        self.written() == other.written()
+    }
+}
+
+impl Eq for Message {}
+
+impl PartialEq<str> for Message {
+    fn eq(&self, other: &str) -> bool {
+        self.written() == other
+    }
+}
+
+impl PartialEq<&str> for Message {
+    fn eq(&self, other: &&str) -> bool {
+        self.written() == *other
+    }
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Self {
+        Message::Text(text)
+    }
+}
+
+impl From<&str> for Message {
+    fn from(text: &str) -> Self {
+        Message::Text(text.to_string())
+    }
+}
+
+impl Serialize for Message {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A message built from a template of the catalog and its arguments
+///
+/// Takes the arguments of `format!`, with only `{}` in the template, one for
+/// each argument, which is checked at compile time. An argument that is
+/// itself a [`Message`] is rendered in the same locale; any other is
+/// rendered as written, with its `Display`.
+#[macro_export]
+macro_rules! message {
+    ($template:literal $(, $argument:expr)* $(,)?) => {{
+        #[allow(unused_imports)]
+        use $crate::i18n::DisplayArgument as _;
+        const _: () = assert!(
+            $crate::i18n::placeholders($template) == 0 $(+ $crate::i18n::one(stringify!($argument)))*,
+            "the template of a message needs one `{{}}` for each of its arguments"
+        );
+        $crate::i18n::Message::Template($template, vec![$($crate::i18n::Argument(&$argument).into_message()),*])
+    }};
+}
+
+/// The `{}` of a template, which can have no other placeholders
+#[doc(hidden)]
+pub const fn placeholders(template: &str) -> usize {
+    let bytes = template.as_bytes();
+    let (mut count, mut position) = (0, 0);
+    while position < bytes.len() {
+        let next = if position + 1 < bytes.len() { bytes[position + 1] } else { 0 };
+        match (bytes[position], next) {
+            (b'{', b'}') => count += 1,
+            (b'{', b'{') | (b'}', b'}') => {}
+            (b'{', _) | (b'}', _) => panic!("the template of a message can only have `{{}}` placeholders"),
+            _ => {
+                position += 1;
+                continue;
+            }
+        }
+        position += 2;
+    }
+    count
+}
+
+/// One, for each argument of a message counted by [`message!`](crate::message)
+#[doc(hidden)]
+pub const fn one(_: &str) -> usize {
+    1
+}
+
+/// An argument of [`message!`](crate::message), kept as a message when it is one
+#[doc(hidden)]
+pub struct Argument<'a, T: ?Sized>(pub &'a T);
+
+impl Argument<'_, Message> {
+    #[doc(hidden)]
+    pub fn into_message(self) -> Message {
+        self.0.clone()
+    }
+}
+
+impl Argument<'_, &Message> {
+    #[doc(hidden)]
+    pub fn into_message(self) -> Message {
+        (*self.0).clone()
+    }
+}
+
+/// An argument of [`message!`](crate::message) that isn't a message, rendered as written
+#[doc(hidden)]
+pub trait DisplayArgument {
+    fn into_message(self) -> Message;
+}
+
+impl<T: fmt::Display + ?Sized> DisplayArgument for Argument<'_, T> {
+    fn into_message(self) -> Message {
+        Message::Text(self.0.to_string())
+    }
+}
+
+/// The template of the catalog with a text, in English and Spanish
+pub fn template(text: &str) -> Option<(&'static str, &'static str)> {
+    CATALOG.get(text).map(|[en, es]| (*en, *es))
+}
+
+/// Every template of the catalog, in English and Spanish
+pub fn templates() -> impl Iterator<Item = (&'static str, &'static str)> {
+    MESSAGES.iter().flat_map(|(_, templates)| templates.iter()).chain(CAUSES).chain(COMMANDS).copied()
+}
+
+/// The templates of the catalog by their text in either language
+static CATALOG: Lazy<HashMap<&'static str, [&'static str; 2]>> =
+    Lazy::new(|| templates().flat_map(|(en, es)| [(en, [en, es]), (es, [en, es])]).collect());
+
+/// A template filled with its arguments, `{{` and `}}` standing for braces
+fn fill(template: &str, mut arguments: impl Iterator<Item = String>) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('}')) => {
+                chars.next();
+                filled.push_str(&arguments.next().unwrap_or_default());
+            }
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                filled.push(c);
+            }
+            _ => filled.push(c),
+        }
+    }
+    filled
+}
+
+/// Spanish titles of the codes
+const TITLES: &[(&str, &str)] = &[
+    (codes::SYNTAX, "La entrada no sigue la gramática"),
+    (codes::MALFORMED, "Una construcción que la gramática acepta pero el parser no puede construir"),
+    (codes::DUPLICATE_NAME, "Dos workflows, subworkflows, constantes, agentes, tests o esquemas con el mismo nombre"),
+    (codes::INVALID_IDENTIFIER, "Un nombre de workflow o subworkflow que no es un identificador"),
+    (codes::UNDEFINED_CONSTANT, "Una referencia `$NOMBRE` a una constante no declarada"),
+    (codes::INVALID_INTERPOLATION, "Una interpolación `${...}` que no se puede resolver"),
+    (codes::UNDEFINED_SUBWORKFLOW, "Un `use` de un subworkflow no declarado"),
+    (codes::SUBWORKFLOW_PORTS, "Un `use` que enlaza otras entradas o salidas que las que declara el subworkflow"),
+    (codes::INVALID_VERSION, "Una `version` de workflow que no es una versión semántica `MAJOR.MINOR.PATCH`"),
+    (codes::MISSING_SOURCE, "Un workflow sin fuente"),
+    (codes::INVALID_SOURCE, "Una fuente con opciones inválidas"),
+    (codes::INVALID_TARGET, "Un destino con opciones inválidas"),
+    (codes::INVALID_TOPIC, "Un tema de NATS, Kafka o MQTT que el broker rechaza"),
+    (codes::MISSING_AGENT_ID, "Un agente sin `id`"),
+    (codes::INVALID_CONFIG, "Una opción de agente de otro tipo, fuera de rango u obligatoria que falta"),
+    (codes::INVALID_POLICY, "Una política `retry` o `fallback` inválida"),
+    (codes::INVALID_PRELOAD, "Un `preload` sin modelo remoto que cargar"),
+    (codes::INVALID_RESOURCE_GLOB, "Un glob de recursos que no se puede expandir"),
+    (codes::UNRESOLVED_PLACEHOLDER, "Un marcador que no se puede resolver en este entorno"),
+    (codes::INLINE_SECRET, "Una credencial escrita en el DSL en lugar de leída con `secret(...)`"),
+    (codes::INVALID_CONDITION, "Una condición `when` o `assert` que no es una expresión booleana"),
+    (codes::UNKNOWN_FIELD, "Un campo de una condición fuera del mensaje o de su esquema"),
+    (codes::TYPE_MISMATCH, "Una condición que combina operandos de tipos incompatibles"),
+    (codes::OPTIONAL_FIELD, "Una comparación de orden sobre un campo opcional sin valor por defecto"),
+    (codes::INVALID_SCHEMA, "Un esquema de mensajes que no se puede leer"),
+    (codes::CONTRACT_VIOLATION, "Un productor o consumidor que no respeta el esquema de su topic"),
+    (codes::BREAKING_SCHEMA_CHANGE, "Un cambio incompatible de esquema sin nueva `schema_version`"),
+    (codes::INVALID_TOPICS, "Topics de agentes que no se pueden conectar"),
+    (codes::UNPRODUCED_TOPIC, "Un topic consumido en un workflow que nada produce"),
+    (codes::UNUSED_TOPIC, "Un topic producido en un workflow que nada consume"),
+    (codes::UNREACHABLE_STEP, "Un paso al que no llega ningún mensaje de la fuente"),
+    (codes::DATAFLOW_CYCLE, "Un ciclo en el flujo de datos de un workflow"),
+    (codes::INVALID_ENCODING, "Una codificación de topic desconocida, en la que productores y consumidores no coinciden o sin el esquema que necesita"),
+    (codes::INVALID_BATCH, "Un workflow batch cuya entrada no está acotada"),
+    (codes::INVALID_DEPLOYMENT, "Opciones de almacenamiento o de rollout inválidas"),
+    (codes::UNSIGNED_MODEL, "Un modelo remoto sin firma donde se exigen firmas"),
+    (codes::INVALID_TEST, "Un bloque `test` de workflow que no se puede ejecutar"),
+    (codes::EXTERNAL_NATS, "El NATS externo indicado a `kumeo check` es inválido o no responde"),
+    (codes::INVALID_RESOURCE, "Un recurso que `kumeo check --check-resources` no puede descargar o cuyo contenido es inválido"),
+    (codes::VERSION_BUMP, "Una subida de versión de workflow menor que sus cambios respecto a la referencia de `kumeo check --baseline`"),
+    (codes::QUOTA_EXCEEDED, "Un namespace cuyos workflows piden en su pico más que su cuota en el archivo de cuotas de `kumeo check --quotas`"),
+];
+
+/// Templates of the messages of each code, in English and Spanish
+const MESSAGES: &[(&str, &[(&str, &str)])] = &[
+    (codes::SYNTAX, &[
+        ("expected a separator or a line break before {}", "se esperaba un separador o un salto de línea antes de {}"),
+        ("unexpected {}; expected {}", "no se esperaba {}; se esperaba {}"),
+        ("expected {}", "se esperaba {}"),
+        ("unexpected {}", "no se esperaba {}"),
+        ("unknown parsing error", "error de análisis desconocido"),
+        ("{} or {}", "{} o {}"),
+    ]),
+    (codes::MALFORMED, &[
+        ("Annotation {} is reserved for Kumeo", "La anotación {} está reservada para Kumeo"),
+        ("Canary analysis must be a string", "El análisis de canary debe ser un texto"),
+        ("Canary steps must be a list", "Los pasos de canary deben ser una lista"),
+        ("Cannot read {}: {}", "No se puede leer {}: {}"),
+        ("Expected a condition", "Se esperaba una condición"),
+        ("Expected a condition after !", "Se esperaba una condición después de !"),
+        ("Expected a condition after assert", "Se esperaba una condición después de assert"),
+        ("Expected a condition after when", "Se esperaba una condición después de when"),
+        ("Expected a rollout strategy, found {}", "Se esperaba una estrategia de rollout, no {}"),
+        ("Expected a scaling object, found {}", "Se esperaba un objeto de escalado, no {}"),
+        ("Expected a storage object, found {}", "Se esperaba un objeto de almacenamiento, no {}"),
+        ("Expected a subject in use {}, found {}", "Se esperaba un subject en use {}, no {}"),
+        (
+            "Expected a subject or a list of subjects for {} in use {}, found {}",
+            "Se esperaba un subject o una lista de subjects para {} en use {}, no {}",
+        ),
+        ("Expected a topic", "Se esperaba un topic"),
+        ("Expected a value for constant {}", "Se esperaba un valor para la constante {}"),
+        ("Expected action", "Se esperaba una acción"),
+        ("Expected agent type", "Se esperaba un tipo de agente"),
+        ("Expected an infrastructure object, found {}", "Se esperaba un objeto de infraestructura, no {}"),
+        ("Expected an operand", "Se esperaba un operando"),
+        ("Expected an operand after {}", "Se esperaba un operando después de {}"),
+        ("Expected constant name", "Se esperaba el nombre de la constante"),
+        ("Expected feature view", "Se esperaba una feature view"),
+        ("Expected inference server", "Se esperaba un servidor de inferencia"),
+        ("Expected key", "Se esperaba una clave"),
+        ("Expected model", "Se esperaba un modelo"),
+        ("Expected name", "Se esperaba un nombre"),
+        ("Expected provider", "Se esperaba un proveedor"),
+        ("Expected reducer", "Se esperaba un reductor"),
+        ("Expected reduction name", "Se esperaba el nombre de la reducción"),
+        ("Expected resource URI", "Se esperaba la URI del recurso"),
+        ("Expected rule", "Se esperaba una regla"),
+        ("Expected rule name", "Se esperaba el nombre de la regla"),
+        ("Expected secret name", "Se esperaba el nombre del secreto"),
+        ("Expected source type", "Se esperaba el tipo de fuente"),
+        ("Expected subject", "Se esperaba un subject"),
+        ("Expected subworkflow name", "Se esperaba el nombre del subworkflow"),
+        ("Expected target type", "Se esperaba el tipo de destino"),
+        ("Expected test expectations", "Se esperaban las expectativas del test"),
+        ("Expected test message", "Se esperaba el mensaje del test"),
+        ("Expected test name", "Se esperaba el nombre del test"),
+        ("Expected tested agent", "Se esperaba el agente probado"),
+        ("Expected the schema of topic {}", "Se esperaba el esquema del topic {}"),
+        ("Expected value", "Se esperaba un valor"),
+        ("Expected {} topic", "Se esperaba un topic de {}"),
+        ("Import cycle: {}", "Ciclo de imports: {}"),
+        ("Infrastructure requires a provider", "La infraestructura requiere un provider"),
+        ("Infrastructure requires a region", "La infraestructura requiere una region"),
+        ("Infrastructure {} must be a string, found {}", "El {} de la infraestructura debe ser un texto, no {}"),
+        (
+            "Invalid annotation key '{}', expected a name of up to 63 letters, digits, '-', '_' or '.', with an optional DNS prefix such as example.com/",
+            "Clave de anotación inválida '{}': se esperaba un nombre de hasta 63 letras, dígitos, '-', '_' o '.', con un prefijo DNS opcional como example.com/",
+        ),
+        ("Invalid blue_green setting: {}", "Opción de blue_green inválida: {}"),
+        ("Invalid deployment setting: {}", "Opción de despliegue inválida: {}"),
+        (
+            "Invalid image '{}', expected a repository with an optional tag or digest such as ghcr.io/acme/scorer:1.2",
+            "Imagen inválida '{}': se esperaba un repositorio con un tag o digest opcional como ghcr.io/acme/scorer:1.2",
+        ),
+        (
+            "Invalid image tag '{}', expected up to 128 letters, digits, '_', '.' or '-', not starting with '.' or '-'",
+            "Tag de imagen inválido '{}': se esperaban hasta 128 letras, dígitos, '_', '.' o '-', sin empezar por '.' ni '-'",
+        ),
+        ("Invalid infrastructure setting: {}", "Opción de infraestructura inválida: {}"),
+        (
+            "Invalid label key '{}', expected a name of up to 63 letters, digits, '-', '_' or '.', with an optional DNS prefix such as example.com/",
+            "Clave de etiqueta inválida '{}': se esperaba un nombre de hasta 63 letras, dígitos, '-', '_' o '.', con un prefijo DNS opcional como example.com/",
+        ),
+        ("Invalid monitor setting: {}", "Opción de monitorización inválida: {}"),
+        (
+            "Invalid namespace '{}', expected up to 63 lowercase letters, digits or '-', starting and ending with a letter or digit",
+            "Namespace inválido '{}': se esperaban hasta 63 letras minúsculas, dígitos o '-', empezando y terminando por letra o dígito",
+        ),
+        ("Invalid number: {}", "Número inválido: {}"),
+        (
+            "Invalid registry '{}', expected a host and optional path such as ghcr.io/acme",
+            "Registro inválido '{}': se esperaba un host y una ruta opcional como ghcr.io/acme",
+        ),
+        ("Invalid scaling setting: {}", "Opción de escalado inválida: {}"),
+        (
+            "Invalid secret name '{}', expected a letter followed by letters, digits, '-', '.' or '_'",
+            "Nombre de secreto inválido '{}': se esperaba una letra seguida de letras, dígitos, '-', '.' o '_'",
+        ),
+        ("Invalid traffic weight: {}", "Peso de tráfico inválido: {}"),
+        (
+            "Invalid value '{}' of label {}, expected up to 63 letters, digits, '-', '_' or '.', starting and ending with a letter or digit",
+            "Valor inválido '{}' de la etiqueta {}: se esperaban hasta 63 letras, dígitos, '-', '_' o '.', empezando y terminando por letra o dígito",
+        ),
+        ("Label {} is reserved for Kumeo", "La etiqueta {} está reservada para Kumeo"),
+        (
+            "Monitor alert_error_rate must be a fraction between 0 and 1, found {}",
+            "El alert_error_rate de la monitorización debe ser una fracción entre 0 y 1, no {}",
+        ),
+        (
+            "Monitor alert_latency_p99 must be a duration such as \"500ms\" or \"2s\", found {}",
+            "El alert_latency_p99 de la monitorización debe ser una duración como \"500ms\" o \"2s\", no {}",
+        ),
+        (
+            "Monitor metrics_enabled must be true or false, found {}",
+            "El metrics_enabled de la monitorización debe ser true o false, no {}",
+        ),
+        (
+            "Monitor {} must be a duration such as \"5m\", found {}",
+            "El {} de la monitorización debe ser una duración como \"5m\", no {}",
+        ),
+        (
+            "Monitor {} must be a whole number of at least 1, found {}",
+            "El {} de la monitorización debe ser un número entero de al menos 1, no {}",
+        ),
+        ("Only Custom agents take the name of their templates", "Solo los agentes Custom reciben el nombre de sus plantillas"),
+        ("Scaling max_replicas {} is below min_replicas {}", "El max_replicas {} del escalado es menor que su min_replicas {}"),
+        ("Scaling requires max_replicas", "El escalado requiere max_replicas"),
+        (
+            "Scaling {} must be a whole number of at least 1, found {}",
+            "El {} del escalado debe ser un número entero de al menos 1, no {}",
+        ),
+        ("Storage requires a path", "El almacenamiento requiere un path"),
+        ("Storage requires a size", "El almacenamiento requiere un size"),
+        ("Storage {} must be a string, found {}", "El {} del almacenamiento debe ser un texto, no {}"),
+        ("Unexpected rule: {}", "Regla inesperada: {}"),
+        ("Unexpected value type", "Tipo de valor inesperado"),
+        ("Unknown Kafka deployment {}, expected managed or in_cluster", "Despliegue de Kafka desconocido {}, se esperaba managed o in_cluster"),
+        ("Unknown agent type", "Tipo de agente desconocido"),
+        (
+            "Unknown alert severity {}, expected critical, warning or info",
+            "Severidad de alerta desconocida {}, se esperaba critical, warning o info",
+        ),
+        ("Unknown deployment platform {}, expected kubernetes or nomad", "Plataforma de despliegue desconocida {}, se esperaba kubernetes o nomad"),
+        ("Unknown infrastructure provider {}, expected aws or gcp", "Proveedor de infraestructura desconocido {}, se esperaba aws o gcp"),
+        ("Unknown option for use {}: {}", "Opción desconocida para use {}: {}"),
+        ("Unknown rollout strategy: {}", "Estrategia de rollout desconocida: {}"),
+        ("Unsupported source type", "Tipo de fuente no soportado"),
+        ("Unsupported target type", "Tipo de destino no soportado"),
+    ]),
+    (codes::DUPLICATE_NAME, &[
+        ("Duplicate workflow name: {}", "Nombre de workflow duplicado: {}"),
+        ("Duplicate subworkflow name: {}", "Nombre de subworkflow duplicado: {}"),
+        ("Duplicate constant: {}", "Constante duplicada: {}"),
+        ("Duplicate test: \"{}\"", "Test duplicado: \"{}\""),
+        ("Duplicate schema for topic '{}'", "Esquema duplicado para el topic '{}'"),
+        ("Duplicate agent ID: {}", "ID de agente duplicado: {}"),
+    ]),
+    (codes::INVALID_IDENTIFIER, &[
+        ("The {} can't have an empty name", "El {} no puede tener un nombre vacío"),
+        (
+            "The {} '{}' can only contain alphanumeric characters and underscores",
+            "El {} '{}' solo puede contener caracteres alfanuméricos y guiones bajos",
+        ),
+        ("The {} '{}' can't start with a digit", "El {} '{}' no puede empezar con un número"),
+    ]),
+    (codes::UNDEFINED_CONSTANT, &[("Undefined constant: ${}", "Constante no definida: ${}")]),
+    (codes::INVALID_INTERPOLATION, &[("Invalid interpolation: {}", "Interpolación inválida: {}")]),
+    (codes::UNDEFINED_SUBWORKFLOW, &[("Undefined subworkflow: {}", "Subworkflow no definido: {}")]),
+    (codes::SUBWORKFLOW_PORTS, &[
+        ("use {}: the subworkflow expects {} {} ({}) and receives {}", "use {}: el subworkflow espera {} {} ({}) y recibe {}"),
+    ]),
+    (codes::INVALID_VERSION, &[
+        (
+            "The version '{}' of workflow {} isn't a MAJOR.MINOR.PATCH semantic version",
+            "La versión '{}' del workflow {} no es una versión semántica MAJOR.MINOR.PATCH",
+        ),
+    ]),
+    (codes::MISSING_SOURCE, &[("The workflow must have a data source", "El workflow debe tener una fuente de datos")]),
+    (codes::INVALID_SOURCE, &[
+        ("Only HTTP sources support auth", "Solo las fuentes HTTP admiten auth"),
+        ("Invalid authentication of the HTTP source: {}", "Autenticación inválida en la fuente HTTP: {}"),
+        (
+            "The webhook of {} is served by agent {} ({}), which is generated in Python; auth is only available in Rust agents",
+            "El webhook de {} lo sirve el agente {} ({}), que se genera en Python; auth solo está disponible en los agentes Rust",
+        ),
+        (
+            "Invalid File pattern: '{}' (it must be absolute and only supports wildcards in the file name)",
+            "Patrón File inválido: '{}' (debe ser absoluto y solo admite comodines en el nombre del archivo)",
+        ),
+        ("The watch option must be true or false, not '{}'", "La opción watch debe ser true o false, no '{}'"),
+        (
+            "Invalid HTTP path: '{}' (it must start with '/' and have no spaces, '?' or '#')",
+            "Ruta HTTP inválida: '{}' (debe empezar por '/' y no llevar espacios, '?' ni '#')",
+        ),
+        (
+            "Unsupported HTTP method for webhooks: '{}' (use POST, PUT or PATCH)",
+            "Método HTTP no soportado para webhooks: '{}' (usa POST, PUT o PATCH)",
+        ),
+    ]),
+    (codes::INVALID_TARGET, &[
+        (
+            "The File target must be an absolute directory without wildcards: '{}'",
+            "El destino File debe ser un directorio absoluto sin comodines: '{}'",
+        ),
+    ]),
+    (codes::INVALID_TOPIC, &[
+        ("The NATS subject can't be empty", "El tema de NATS no puede estar vacío"),
+        ("The Kafka topic can't be empty", "El tema de Kafka no puede estar vacío"),
+        (
+            "Invalid Kafka topic: '{}' (only letters, digits, '.', '_' and '-', up to 249 characters)",
+            "Tema de Kafka inválido: '{}' (solo letras, números, '.', '_' y '-', hasta 249 caracteres)",
+        ),
+        ("The MQTT topic can't be empty", "El tema de MQTT no puede estar vacío"),
+        ("The MQTT target '{}' can't contain '+' or '#' wildcards", "El destino MQTT '{}' no puede contener comodines '+' ni '#'"),
+        (
+            "Misplaced MQTT wildcard in '{}': '+' must take a whole level and '#' can only be the last level",
+            "Comodín MQTT mal ubicado en '{}': '+' debe ocupar un nivel completo y '#' solo puede ser el último nivel",
+        ),
+    ]),
+    (codes::MISSING_AGENT_ID, &[("Every agent must have an ID", "Todos los agentes deben tener un ID")]),
+    (codes::INVALID_CONFIG, &[
+        (
+            "Agent {} sends messages to review to {}, which isn't a HumanReview agent of the workflow",
+            "El agente {} envía mensajes a revisión a {}, que no es un agente HumanReview del workflow",
+        ),
+        ("Invalid configuration of agent {} ({}): {}", "Configuración inválida en el agente {} ({}): {}"),
+        (
+            "Agent {} ({}) doesn't support drift; only MLModel agents track the drift of their inputs",
+            "El agente {} ({}) no admite drift; solo los agentes MLModel siguen la deriva de sus entradas",
+        ),
+        ("Invalid drift of agent {}: {}", "Drift inválido en el agente {}: {}"),
+        (
+            "Agent {} ({}) doesn't support features; only MLModel agents query a feature store",
+            "El agente {} ({}) no admite features; solo los agentes MLModel consultan un feature store",
+        ),
+        (
+            "Agent {} ({}) doesn't support an inference server; only LLM agents send prompts to vLLM or TGI",
+            "El agente {} ({}) no admite un servidor de inferencia; solo los agentes LLM envían prompts a vLLM o TGI",
+        ),
+        (
+            "Agent {} ({}) doesn't support providers; only LLM agents chain providers",
+            "El agente {} ({}) no admite providers; solo los agentes LLM encadenan proveedores",
+        ),
+        (
+            "Agent {} declares provider and providers; use only providers to chain providers",
+            "El agente {} declara provider y providers; usa solo providers para encadenar proveedores",
+        ),
+        ("Invalid provider chain of agent {}: {}", "Cadena de proveedores inválida en el agente {}: {}"),
+        (
+            "Agent {} ({}) doesn't support guardrails; only LLM agents filter prompts and responses",
+            "El agente {} ({}) no admite guardrails; solo los agentes LLM filtran prompts y respuestas",
+        ),
+        ("Invalid guardrails of agent {}: {}", "Guardrails inválidos en el agente {}: {}"),
+        ("Invalid image of agent {}: {}", "Imagen inválida en el agente {}: {}"),
+        (
+            "Agent {} ({}) doesn't support memory; only LLM agents remember sessions",
+            "El agente {} ({}) no admite memory; solo los agentes LLM recuerdan sesiones",
+        ),
+        (
+            "Agent {} ({}) doesn't support budget; only LLM agents consume tokens",
+            "El agente {} ({}) no admite budget; solo los agentes LLM consumen tokens",
+        ),
+        ("Invalid budget of agent {}: {}", "Presupuesto inválido en el agente {}: {}"),
+        (
+            "Agent {} ({}) doesn't support strategy; only Router agents distribute messages",
+            "El agente {} ({}) no admite strategy; solo los agentes Router reparten mensajes",
+        ),
+        ("Invalid routing table of agent {}: {}", "Tabla de rutas inválida en el agente {}: {}"),
+        (
+            "Agent {} declares rules and routes_path; use only rules to read the table from a resource",
+            "El agente {} declara rules y routes_path; usa solo rules para leer la tabla de un recurso",
+        ),
+        (
+            "Agent {} ({}) doesn't support {}; only HumanReview agents have an SLA",
+            "El agente {} ({}) no admite {}; solo los agentes HumanReview tienen SLA",
+        ),
+        (
+            "Agent {} ({}) doesn't support audit; only the decisions of HumanReview agents are audited",
+            "El agente {} ({}) no admite audit; solo se auditan las decisiones de los agentes HumanReview",
+        ),
+        (
+            "Agent {} ({}) doesn't support auth; only HumanReview agents and HTTP sources authenticate",
+            "El agente {} ({}) no admite auth; solo se autentican los agentes HumanReview y las fuentes HTTP",
+        ),
+        ("ML agents must have 'model_path' or 'model_name' configured", "Los agentes de ML deben tener 'model_path' o 'model_name' configurado"),
+        ("add model_path: \"<model URI>\" or model_name: \"<name>\"", "añade model_path: \"<uri del modelo>\" o model_name: \"<nombre>\""),
+        ("Invalid quality monitor of agent {}: {}", "Monitor de calidad inválido en el agente {}: {}"),
+        (
+            "Quality monitor {} measures conformance, but the messages it consumes have no schema; all of them will count as conforming",
+            "El monitor de calidad {} mide la conformidad, pero los mensajes que consume no tienen esquema; todos contarán como conformes",
+        ),
+        (
+            "declare input_schema in the monitor or the schema of its input topic",
+            "declara input_schema en el monitor o el esquema de su topic de entrada",
+        ),
+        ("Invalid aggregator of agent {}: {}", "Agregador inválido en el agente {}: {}"),
+        ("Invalid rule engine of agent {}: {}", "Motor de reglas inválido en el agente {}: {}"),
+        ("Invalid Bayesian network of agent {}: {}", "Red bayesiana inválida en el agente {}: {}"),
+        ("Invalid custom agent {}: {}", "Agente personalizado inválido {}: {}"),
+        ("Invalid normalizer of agent {}: {}", "Normalizador inválido en el agente {}: {}"),
+        ("Invalid missing value handler of agent {}: {}", "Manejador de valores ausentes inválido en el agente {}: {}"),
+        ("Invalid SLA of agent {}: {}", "SLA inválido en el agente {}: {}"),
+        ("Invalid review of agent {}: {}", "Revisión inválida en el agente {}: {}"),
+        ("Invalid audit of agent {}: {}", "Auditoría inválida en el agente {}: {}"),
+        (
+            "Agent {} defines the issuer and audience in auth; remove them from audit",
+            "El agente {} define el emisor y la audiencia en auth; quítalos de audit",
+        ),
+        ("Invalid authentication of agent {}: {}", "Autenticación inválida en el agente {}: {}"),
+        ("Invalid provider of agent {}: {}", "Proveedor inválido en el agente {}: {}"),
+        (
+            "Agent {} ignores engine: {}; declare the provider with provider and the model with model",
+            "El agente {} ignora engine: {}; declara el proveedor con provider y el modelo con model",
+        ),
+        ("Invalid feature store of agent {}: {}", "Feature store inválido en el agente {}: {}"),
+        (
+            "The keys of feature view '{}' of agent {} can't be checked: the messages it consumes have no schema",
+            "No se pueden comprobar las claves de la feature view '{}' del agente {}: los mensajes que consume no tienen esquema",
+        ),
+        (
+            "declare input_schema in the agent or the schema of its input topic",
+            "declara input_schema en el agente o el esquema de su topic de entrada",
+        ),
+        (
+            "Key '{}' of feature view '{}' of agent {} isn't a field of the messages it consumes",
+            "La clave '{}' de la feature view '{}' del agente {} no es un campo de los mensajes que consume",
+        ),
+        (
+            "Key '{}' of feature view '{}' of agent {} has type {}; entities are identified by string or integer fields",
+            "La clave '{}' de la feature view '{}' del agente {} es de tipo {}; las entidades se identifican con campos string o integer",
+        ),
+        (
+            "Key '{}' of feature view '{}' of agent {} is optional; messages without it will be processed without features",
+            "La clave '{}' de la feature view '{}' del agente {} es opcional; los mensajes sin ella se procesarán sin features",
+        ),
+        ("Invalid memory of agent {}: {}", "Memoria inválida en el agente {}: {}"),
+        (
+            "Scope '{}' of the memory of agent {} isn't a field of the messages it consumes",
+            "El scope '{}' de la memoria del agente {} no es un campo de los mensajes que consume",
+        ),
+        (
+            "Scope '{}' of the memory of agent {} has type {}; sessions are identified by string or integer fields",
+            "El scope '{}' de la memoria del agente {} es de tipo {}; las sesiones se identifican con campos string o integer",
+        ),
+        (
+            "Scope '{}' of the memory of agent {} is optional; messages without it will be processed without history",
+            "El scope '{}' de la memoria del agente {} es opcional; los mensajes sin él se procesarán sin historial",
+        ),
+        ("Invalid routing strategy of agent {}: {}", "Estrategia de enrutamiento inválida en el agente {}: {}"),
+        (
+            "Key {} of agent {} can't be checked: the messages it consumes have no schema",
+            "No se puede comprobar la clave {} del agente {}: los mensajes que consume no tienen esquema",
+        ),
+        ("Key {} of agent {} isn't in the schema of its messages: {}", "La clave {} del agente {} no está en el esquema de sus mensajes: {}"),
+        (
+            "Key {} of agent {} has type {}; messages are distributed by string or integer fields",
+            "La clave {} del agente {} es de tipo {}; los mensajes se reparten por campos string o integer",
+        ),
+        (
+            "Key {} of agent {} is optional; messages without it can't be routed",
+            "La clave {} del agente {} es opcional; los mensajes sin ella no se podrán enrutar",
+        ),
+    ]),
+    (codes::INVALID_POLICY, &[
+        (
+            "Agent {} correlates its saga by {}, but the previous steps by {}",
+            "El agente {} correlaciona su saga por {}, pero los pasos anteriores por {}",
+        ),
+        (
+            "every compensate of a workflow must use the same correlation",
+            "todos los compensate de un workflow deben usar el mismo correlation",
+        ),
+        ("Invalid retry policy of agent {}: {}", "Política retry inválida en el agente {}: {}"),
+        ("Invalid fallback of agent {}: {}", "Fallback inválido en el agente {}: {}"),
+        (
+            "Agent {} ({}) doesn't support the retry_later fallback; Python agents don't receive delayed messages",
+            "El agente {} ({}) no admite el fallback retry_later; los agentes Python no reciben mensajes diferidos",
+        ),
+        (
+            "Agent {} ({}) doesn't support compensate; Python agents don't take part in sagas",
+            "El agente {} ({}) no admite compensate; los agentes Python no toman parte en sagas",
+        ),
+        ("Invalid compensation of agent {}: {}", "Compensación inválida en el agente {}: {}"),
+    ]),
+    (codes::INVALID_PRELOAD, &[
+        (
+            "Agent {} declares preload but has no remote model (model_path or models.<name>)",
+            "El agente {} declara preload pero no tiene un modelo remoto (model_path o models.<nombre>)",
+        ),
+        (
+            "Agent {} preloads {}, which is neither a URI nor a model of the context",
+            "El agente {} precarga {}, que no es una URI ni un modelo del contexto",
+        ),
+        ("preload of agent {} must be true, false or a list of models", "preload del agente {} debe ser true, false o una lista de modelos"),
+    ]),
+    (codes::INVALID_RESOURCE_GLOB, &[
+        ("'{}' uses a glob over {}, which doesn't support listings: {}", "'{}' usa un glob sobre {}, que no admite listados: {}"),
+        ("Invalid glob in '{}': '**' must take a whole segment ({})", "Glob inválido en '{}': '**' debe ocupar un segmento completo ({})"),
+    ]),
+    (codes::UNRESOLVED_PLACEHOLDER, &[
+        (
+            "Agent {} uses ${{env.{}}}, which isn't defined in this environment; in the cluster it is read from the Secret of the deployment",
+            "El agente {} usa ${{env.{}}}, que no está definida en este entorno; en el clúster se lee del Secret del despliegue",
+        ),
+        (
+            "Agent {} uses {}, which isn't a ${{env.NAME}} reference and is left unresolved",
+            "El agente {} usa {}, que no es una referencia ${{env.NOMBRE}} y se deja sin resolver",
+        ),
+    ]),
+    (codes::INLINE_SECRET, &[
+        (
+            "Agent {} writes its {} into the DSL; it ends up in plain text in the generated manifests",
+            "El agente {} escribe su {} en el DSL; acaba en claro en los manifiestos generados",
+        ),
+        (
+            "The deployment writes {} into the DSL; it ends up in plain text in the generated manifests",
+            "El despliegue escribe {} en el DSL; acaba en claro en los manifiestos generados",
+        ),
+        ("read it from the Secret of the deployment: {}: secret(\"{}\")", "léelo del Secret del despliegue: {}: secret(\"{}\")"),
+    ]),
+    (codes::INVALID_CONDITION, &[
+        ("Condition {} of agent {} must be an expression, not {}", "La condición {} del agente {} debe ser una expresión, no {}"),
+        ("Condition {} of agent {} must be boolean: {}", "La condición {} del agente {} debe ser booleana: {}"),
+        ("Rule {} of agent {} needs a boolean condition: {}", "La regla {} del agente {} debe tener una condición booleana: {}"),
+    ]),
+    (codes::UNKNOWN_FIELD, &[
+        ("Field {} of agent {} must start with 'data'", "El campo {} del agente {} debe empezar por 'data'"),
+        ("Field {} of agent {} isn't in the schema of its messages: {}", "El campo {} del agente {} no está en el esquema de sus mensajes: {}"),
+    ]),
+    (codes::TYPE_MISMATCH, &[
+        ("The default of {} in agent {} is {} and the value {}: {}", "El valor por defecto de {} en el agente {} es {} y el valor {}: {}"),
+        ("Operator {} of agent {} only compares numbers or strings: {}", "El operador {} del agente {} solo compara números o textos: {}"),
+        ("Agent {} compares {} with {}: {}", "El agente {} compara {} con {}: {}"),
+        ("Operator {} of agent {} expects booleans and receives {}: {}", "El operador {} del agente {} espera booleanos y recibe {}: {}"),
+        ("Reduction {} of agent {} can't apply {} to {}: {}", "La reducción {} del agente {} no puede aplicar {} a {}: {}"),
+        ("Agent {} can't scale {} with {}: it is {}", "El agente {} no puede escalar {} con {}: es {}"),
+        ("Agent {} can't impute {} with {}(): it is {}", "El agente {} no puede imputar {} con {}(): es {}"),
+    ]),
+    (codes::OPTIONAL_FIELD, &[
+        (
+            "Field {} of agent {} is optional: use ?? to give it a default in {}",
+            "El campo {} del agente {} es opcional: usa ?? para darle un valor por defecto en {}",
+        ),
+    ]),
+    (codes::INVALID_SCHEMA, &[
+        ("Invalid schema for topic '{}': {}", "Esquema inválido para el topic '{}': {}"),
+        ("Invalid input schema of agent {}: {}", "Esquema de entrada inválido en el agente {}: {}"),
+        ("Invalid output schema of agent {}: {}", "Esquema de salida inválido en el agente {}: {}"),
+    ]),
+    (codes::CONTRACT_VIOLATION, &[
+        (
+            "Agent {} publishes messages to '{}' that don't comply with its schema: {}",
+            "El agente {} publica en '{}' mensajes que no cumplen su esquema: {}",
+        ),
+        (
+            "Agent {} expects messages on '{}' that its schema doesn't guarantee: {}",
+            "El agente {} espera en '{}' mensajes que su esquema no garantiza: {}",
+        ),
+    ]),
+    (codes::BREAKING_SCHEMA_CHANGE, &[
+        (
+            "Agent {} publishes to '{}' with schema_version {}, but version {} is already registered (consumed by {})",
+            "El agente {} publica en '{}' con schema_version {}, pero ya se registró la versión {} (consumido por {})",
+        ),
+        (
+            "Incompatible change in the schema of topic '{}', consumed by {}: {}; increase the schema_version of agent {}",
+            "Cambio incompatible en el esquema del topic '{}', consumido por {}: {}; incrementa schema_version del agente {}",
+        ),
+    ]),
+    (codes::INVALID_TOPICS, &[("Invalid topics in workflow {}: {}", "Topics inválidos en el workflow {}: {}")]),
+    (codes::UNPRODUCED_TOPIC, &[
+        ("Agent {} consumes topic '{}', which no agent produces", "El agente {} consume el topic '{}' que ningún agente produce"),
+        ("use {}: topic '{}' isn't produced by any agent", "use {}: el topic '{}' no lo produce ningún agente"),
+    ]),
+    (codes::UNUSED_TOPIC, &[
+        (
+            "Nothing consumes topic '{}', produced by agent {}, and it isn't the target of workflow {}",
+            "Nadie consume el topic '{}' que produce el agente {} y no es el destino del workflow {}",
+        ),
+    ]),
+    (codes::UNREACHABLE_STEP, &[
+        (
+            "Step {} of workflow {} never receives messages: no path from the source reaches it",
+            "El paso {} del workflow {} nunca recibe mensajes: ningún camino desde la fuente llega a él",
+        ),
+    ]),
+    (codes::DATAFLOW_CYCLE, &[
+        ("Cycle in the dataflow of workflow {} guarded by `when`: {}", "Ciclo en el flujo de datos del workflow {} condicionado por `when`: {}"),
+        ("Cycle in the dataflow of workflow {}: {}", "Ciclo en el flujo de datos del workflow {}: {}"),
+        (
+            "filter the messages with `when` in some step of the cycle so that it ends",
+            "filtra los mensajes con `when` en algún paso del ciclo para que termine",
+        ),
+    ]),
+    (codes::INVALID_ENCODING, &[
+        ("Invalid encoding in the source of workflow {}: {}", "Codificación inválida en la fuente del workflow {}: {}"),
+        ("Invalid encoding in the target of workflow {}: {}", "Codificación inválida en el destino del workflow {}: {}"),
+        ("Invalid encoding in agent {}: {}", "Codificación inválida en el agente {}: {}"),
+        (
+            "The source of workflow {} uses the {} encoding, which only NATS sources support",
+            "La fuente del workflow {} usa la codificación {}, que solo admiten las fuentes NATS",
+        ),
+        (
+            "The target of workflow {} uses the {} encoding, which only NATS targets support",
+            "El destino del workflow {} usa la codificación {}, que solo admiten los destinos NATS",
+        ),
+        (
+            "Agent {} publishes {} on the target of workflow {}, which uses {}",
+            "El agente {} publica en {} en el destino del workflow {}, que usa {}",
+        ),
+        ("Agent {} publishes '{}' in {}, which needs its output_schema", "El agente {} publica en '{}' en {}, que necesita su output_schema"),
+        (
+            "Source '{}' of workflow {} uses {}, which needs its schema in schemas: or the input_schema of the agents reading it",
+            "La fuente '{}' del workflow {} usa {}, que necesita su esquema en schemas: o el input_schema de los agentes que la leen",
+        ),
+        ("Topic '{}' is published by {} in {} and by {} in {}", "El topic '{}' lo publican {} en {} y {} en {}"),
+        ("Workflow {} reads '{}' in {}, but workflow {} publishes it in {}", "El workflow {} lee '{}' en {}, pero el workflow {} lo publica en {}"),
+    ]),
+    (codes::INVALID_BATCH, &[
+        ("until_sequence must be a positive integer: {}", "until_sequence debe ser un entero positivo: {}"),
+        ("Batch workflows don't support rollout strategies", "Los workflows batch no admiten estrategias de rollout"),
+        ("Batch workflows don't support HTTP sources", "Los workflows batch no admiten fuentes HTTP"),
+    ]),
+    (codes::INVALID_DEPLOYMENT, &[
+        ("Storage declared for a nonexistent agent: {}", "Almacenamiento declarado para un agente inexistente: {}"),
+        ("Invalid storage size for '{}': {} (example: 10Gi)", "Tamaño de almacenamiento inválido para '{}': {} (ejemplo: 10Gi)"),
+        ("The mount path of '{}' must be absolute: {}", "La ruta de montaje de '{}' debe ser absoluta: {}"),
+        ("The canary deployment must have at least one step", "El despliegue canary debe tener al menos un paso"),
+        ("Invalid canary weight: {}% (it must be an integer between 1 and 100)", "Peso de canary inválido: {}% (debe ser un entero entre 1 y 100)"),
+        ("Canary steps must increase: {}% after {}%", "Los pasos de canary deben ser crecientes: {}% después de {}%"),
+        ("Invalid canary analysis: {}", "Análisis de canary inválido: {}"),
+        ("Unknown active slot in {}: {} (it must be {} or {})", "Slot activo desconocido en {}: {} (debe ser {} o {})"),
+    ]),
+    (codes::UNSIGNED_MODEL, &[
+        (
+            "Model {} declares no signature (#minisign or #cosign) and the deployment requires require_signed",
+            "El modelo {} no declara firma (#minisign o #cosign) y el despliegue exige require_signed",
+        ),
+        ("add the signature to the URI, for example {}#minisign", "añade la firma a la URI, por ejemplo {}#minisign"),
+    ]),
+    (codes::INVALID_TEST, &[
+        ("Invalid test \"{}\" in workflow {}: {}", "Test \"{}\" inválido en el workflow {}: {}"),
+        ("agent {} isn't in the workflow", "el agente {} no existe en el workflow"),
+        ("the message must be an object, found {}", "el mensaje debe ser un objeto, no {}"),
+        ("it must expect a '{}' or an '{}'", "debe esperar un '{}' o un '{}'"),
+        ("'{}' must be a topic or null, found {}", "'{}' debe ser un topic o null, no {}"),
+        ("'{}' must be {} or {}, found {}", "'{}' debe ser {} o {}, no {}"),
+        ("unknown expectation '{}'", "expectativa desconocida '{}'"),
+    ]),
+    (codes::EXTERNAL_NATS, &[
+        ("Invalid external NATS: {}", "NATS externo inválido: {}"),
+        (
+            "The external NATS {} requires authentication and no --nats-credentials was given",
+            "El NATS externo {} requiere autenticación y no se indicó --nats-credentials",
+        ),
+        ("give the Secret with the credentials with --nats-credentials", "indica el Secret con las credenciales con --nats-credentials"),
+        ("The external NATS doesn't answer: {}", "El NATS externo no responde: {}"),
+    ]),
+    (codes::INVALID_RESOURCE, &[
+        ("The routing table of agent {} couldn't be downloaded: {}", "No se pudo descargar la tabla de rutas del agente {}: {}"),
+        (
+            "check the URI or add a mirror rule with --mirror or KUMEO_RESOURCE_MIRRORS",
+            "comprueba la URI o añade una regla de mirror con --mirror o KUMEO_RESOURCE_MIRRORS",
+        ),
+        ("The routing table {} of agent {} isn't valid: {}", "La tabla de rutas {} del agente {} no es válida: {}"),
+    ]),
+    (codes::VERSION_BUMP, &[
+        ("The version of workflow {} goes back from {} to {}", "La versión del workflow {} retrocede de {} a {}"),
+        (
+            "Workflow {} has {} changes ({}), but its version goes from {} to {}; it should be at least {}",
+            "El workflow {} tiene cambios {} ({}), pero su versión pasa de {} a {}; debería ser al menos {}",
+        ),
+    ]),
+    (codes::QUOTA_EXCEEDED, &[
+        (
+            "Namespace {} requests {}m of CPU at its peak, above its quota of {}m (workflows {})",
+            "El namespace {} pide {}m de CPU en su pico, por encima de su cuota de {}m (workflows {})",
+        ),
+        (
+            "Namespace {} requests {}Mi of memory at its peak, above its quota of {}Mi (workflows {})",
+            "El namespace {} pide {}Mi de memoria en su pico, por encima de su cuota de {}Mi (workflows {})",
+        ),
+        (
+            "Namespace {} runs up to {} pods, above its quota of {} replicas (workflows {})",
+            "El namespace {} ejecuta hasta {} pods, por encima de su cuota de {} réplicas (workflows {})",
+        ),
+        (
+            "lower the replicas, scaling or resources of their deployment blocks, or ask for a larger quota",
+            "reduce las réplicas, el escalado o los recursos de sus bloques deployment, o pide una cuota mayor",
+        ),
+    ]),
+];
+
+/// Templates of the causes shared by the messages of every code
+///
+/// These are the problems reported by the validation of options, schemas
+/// and conditions, which the messages of several codes quote.
+const CAUSES: &[(&str, &str)] = &[
+    // Names the analyzer fills its messages with
+    ("<no id>", "<sin id>"),
+    ("inputs", "entradas"),
+    ("outputs", "salidas"),
+    ("a boolean", "un booleano"),
+    ("a number", "un número"),
+    ("a string", "un texto"),
+    // Changes between two versions of a workflow
+    ("the source goes from {} to {}", "la fuente pasa de {} a {}"),
+    ("the options of the source change", "cambian las opciones de la fuente"),
+    ("the target goes from {} to {}", "el destino pasa de {} a {}"),
+    ("the options of the target change", "cambian las opciones del destino"),
+    ("the mode goes from {} to {}", "el modo pasa de {} a {}"),
+    ("agent {} is removed", "se elimina el agente {}"),
+    ("agent {} is added", "se añade el agente {}"),
+    ("subworkflow {} is no longer used", "deja de invocarse el subworkflow {}"),
+    ("the topics of subworkflow {} change", "cambian los topics del subworkflow {}"),
+    ("subworkflow {} is used", "se invoca el subworkflow {}"),
+    ("the context changes", "cambia el contexto"),
+    ("the preprocessors change", "cambian los preprocesadores"),
+    ("the monitoring changes", "cambia la monitorización"),
+    ("the deployment changes", "cambia el despliegue"),
+    ("agent {} goes from consuming {} to {}", "el agente {} pasa de consumir {} a {}"),
+    ("agent {} goes from publishing to {} to {}", "el agente {} pasa de publicar en {} a {}"),
+    ("agent {} goes from {} to {}", "el agente {} pasa de {} a {}"),
+    ("in the output schema of {} {}", "en el esquema de salida de {} {}"),
+    ("field '{}' is added to the output schema of {}", "se añade el campo '{}' al esquema de salida de {}"),
+    ("agent {} declares its output schema", "el agente {} declara su esquema de salida"),
+    ("agent {} no longer declares its output schema", "el agente {} deja de declarar su esquema de salida"),
+    ("option {} is removed from agent {}", "se elimina la opción {} del agente {}"),
+    ("option {} is added to agent {}", "se añade la opción {} al agente {}"),
+    ("option {} of agent {} changes", "cambia la opción {} del agente {}"),
+    ("the arguments of agent {} change", "cambian los argumentos del agente {}"),
+    ("none", "ninguno"),
+    // Configuration schemas of the agents
+    ("missing required option '{}'", "falta la opción obligatoria '{}'"),
+    ("'{}' must be of type {}, not {}", "'{}' debe ser de tipo {}, no {}"),
+    ("'{}' must be {}, not {}", "'{}' debe estar {}, no {}"),
+    ("between {} and {}", "entre {} y {}"),
+    ("at least {}", "al menos {}"),
+    ("at most {}", "como mucho {}"),
+    ("missing required field '{}'", "falta el campo obligatorio '{}'"),
+    // Schemas of the topics
+    ("field '{}' is removed", "se elimina el campo '{}'"),
+    ("field '{}' goes from {} to {}", "el campo '{}' pasa de {} a {}"),
+    ("field '{}' has type {} (expected {})", "el campo '{}' es de tipo {} (se esperaba {})"),
+    ("field '{}' is a {} and has no field '{}'", "el campo '{}' es un {} y no tiene el campo '{}'"),
+    ("field '{}' is a {}, not a {}", "el campo '{}' es un {}, no un {}"),
+    ("field '{}' is missing", "falta el campo '{}'"),
+    ("field '{}' is optional", "el campo '{}' es opcional"),
+    ("field '{}' is optional, read '{}' with ?.", "el campo '{}' es opcional, lee '{}' con ?."),
+    ("fields must be a list, found {}", "fields debe ser una lista, no {}"),
+    ("fields must be field names, found {}", "fields debe contener nombres de campos, no {}"),
+    ("expected an object of field types, found {}", "se esperaba un objeto de tipos de campos, no {}"),
+    ("unknown field '{}'", "campo desconocido '{}'"),
+    ("{} is not a path below data", "{} no es una ruta bajo data"),
+    ("{} requires an {}", "{} requiere un {}"),
+    // Constants
+    ("undefined constant ${}", "constante no definida ${}"),
+    ("undefined name {{{}}}", "nombre no definido {{{}}}"),
+    (
+        "constant {} can't be interpolated: only strings, numbers and booleans can",
+        "la constante {} no se puede interpolar: solo se pueden los textos, números y booleanos",
+    ),
+    // Generic values
+    ("expected an object, found {}", "se esperaba un objeto, no {}"),
+    ("{} must be a list, found {}", "{} debe ser una lista, no {}"),
+    ("{} must be a string, found {}", "{} debe ser un texto, no {}"),
+    ("{} must be a non-empty string, found {}", "{} debe ser un texto no vacío, no {}"),
+    ("{} of {} must be a non-empty string, found {}", "{} de {} debe ser un texto no vacío, no {}"),
+    ("{} must be a topic name, found {}", "{} debe ser el nombre de un topic, no {}"),
+    ("{} must be a whole number of at least 1, found {}", "{} debe ser un número entero de al menos 1, no {}"),
+    ("{} must be a whole number of tokens per month, found {}", "{} debe ser un número entero de tokens al mes, no {}"),
+    ("{} must be names, found {}", "{} debe contener nombres, no {}"),
+    ("{} must be a list of rules, found {}", "{} debe ser una lista de reglas, no {}"),
+    ("unknown {} setting '{}'", "opción de {} desconocida '{}'"),
+    // Encodings
+    ("unknown encoding \"{}\", expected json, msgpack, protobuf or avro", "codificación desconocida \"{}\", se esperaba json, msgpack, protobuf o avro"),
+    // Retry, fallback and compensation
+    ("max_attempts must be a whole number of at least 1, found {}", "max_attempts debe ser un número entero de al menos 1, no {}"),
+    ("unknown retry setting '{}'", "opción de retry desconocida '{}'"),
+    ("invalid backoff duration '{}'", "duración de backoff inválida '{}'"),
+    ("invalid backoff duration {}", "duración de backoff inválida {}"),
+    ("backoff needs at least one duration", "backoff necesita al menos una duración"),
+    ("action must be a string, found {}", "action debe ser un texto, no {}"),
+    ("missing action", "falta action"),
+    (
+        "unknown action '{}' (expected fail, skip, use_default, dead_letter or retry_later)",
+        "acción desconocida '{}' (se esperaba fail, skip, use_default, dead_letter o retry_later)",
+    ),
+    ("'{}' is not a setting of the {} action", "'{}' no es una opción de la acción {}"),
+    ("use_default requires a default", "use_default requiere un default"),
+    ("subject must be a non-empty string, found {}", "subject debe ser un texto no vacío, no {}"),
+    ("dead_letter requires a subject", "dead_letter requiere un subject"),
+    ("after must be a duration such as \"10m\", found {}", "after debe ser una duración como \"10m\", no {}"),
+    ("retry_later requires an after", "retry_later requiere un after"),
+    ("max_delays must be a whole number of at least 1, found {}", "max_delays debe ser un número entero de al menos 1, no {}"),
+    ("compensate must be a NATS(\"<subject>\") subject, found {}", "compensate debe ser un subject NATS(\"<subject>\"), no {}"),
+    ("unknown compensation setting '{}'", "opción de compensación desconocida '{}'"),
+    ("the compensation subject is empty", "el subject de compensación está vacío"),
+    ("the compensation subject {} must not have wildcards or spaces", "el subject de compensación {} no debe tener comodines ni espacios"),
+    (
+        "correlation must be a path below data such as data.order_id, found {}",
+        "correlation debe ser una ruta bajo data como data.order_id, no {}",
+    ),
+    // Drift and quality
+    ("unknown drift setting '{}'", "opción de drift desconocida '{}'"),
+    ("unknown method '{}' (expected psi, kl, js or ks)", "método desconocido '{}' (se esperaba psi, kl, js o ks)"),
+    ("threshold must be a number above 0, found {}", "threshold debe ser un número mayor que 0, no {}"),
+    ("features must be a list, found {}", "features debe ser una lista, no {}"),
+    ("features must be field paths, found {}", "features debe contener rutas de campos, no {}"),
+    ("sample_rate must be a number above 0 and at most 1, found {}", "sample_rate debe ser un número mayor que 0 y como mucho 1, no {}"),
+    ("invalid metric name '{}'", "nombre de métrica inválido '{}'"),
+    ("invalid operator '{}'", "operador inválido '{}'"),
+    ("invalid threshold '{}'", "umbral inválido '{}'"),
+    ("missing comparison operator in '{}'", "falta el operador de comparación en '{}'"),
+    ("unknown metric '{}' (expected {})", "métrica desconocida '{}' (se esperaba {})"),
+    ("metric '{}' is listed twice", "la métrica '{}' aparece dos veces"),
+    ("metrics must be a non-empty list, found {}", "metrics debe ser una lista no vacía, no {}"),
+    ("metrics must be metric names, found {}", "metrics debe contener nombres de métricas, no {}"),
+    ("thresholds must be an object, found {}", "thresholds debe ser un objeto, no {}"),
+    ("threshold for '{}', which is not in metrics", "umbral para '{}', que no está en metrics"),
+    ("the threshold of '{}' must be a number of at least 0, found {}", "el umbral de '{}' debe ser un número de al menos 0, no {}"),
+    ("batch_window_ms must be a whole number of milliseconds, found {}", "batch_window_ms debe ser un número entero de milisegundos, no {}"),
+    // Feature stores
+    ("expected Feast(\"<feature view>\", keys: [...]), found {}", "se esperaba Feast(\"<feature view>\", keys: [...]), no {}"),
+    ("missing feature view", "falta la feature view"),
+    ("feature view '{}' needs the keys identifying its entities", "la feature view '{}' necesita las claves que identifican sus entidades"),
+    ("unknown feature store setting '{}'", "opción de feature store desconocida '{}'"),
+    ("unknown store '{}' (expected state)", "store desconocido '{}' (se esperaba state)"),
+    ("store must be state, found {}", "store debe ser state, no {}"),
+    // Providers and inference servers
+    ("expected VLLM(...) or TGI(...), found {}", "se esperaba VLLM(...) o TGI(...), no {}"),
+    ("unknown inference server '{}' (expected VLLM or TGI)", "servidor de inferencia desconocido '{}' (se esperaba VLLM o TGI)"),
+    ("endpoint must be an http(s) URL, found '{}'", "endpoint debe ser una URL http(s), no '{}'"),
+    ("timeout of {} must be a duration, found {}", "timeout de {} debe ser una duración, no {}"),
+    (
+        "expected a list of providers such as [OpenAI(gpt-4o), Ollama(llama3)], found {}",
+        "se esperaba una lista de proveedores como [OpenAI(gpt-4o), Ollama(llama3)], no {}",
+    ),
+    ("expected a provider such as OpenAI(gpt-4o), found {}", "se esperaba un proveedor como OpenAI(gpt-4o), no {}"),
+    (
+        "unknown provider '{}' (expected OpenAI, Anthropic, Ollama, VLLM or TGI)",
+        "proveedor desconocido '{}' (se esperaba OpenAI, Anthropic, Ollama, VLLM o TGI)",
+    ),
+    (
+        "unknown provider '{}' (expected openai, anthropic, ollama, vllm or tgi)",
+        "proveedor desconocido '{}' (se esperaba openai, anthropic, ollama, vllm o tgi)",
+    ),
+    ("the settings of {} must be an object, found {}", "los ajustes de {} deben ser un objeto, no {}"),
+    (
+        "expected a provider such as \"openai\" or OpenAI(gpt-4o), found {}",
+        "se esperaba un proveedor como \"openai\" u OpenAI(gpt-4o), no {}",
+    ),
+    (
+        "the {} provider requires {}, which its API needs in every request",
+        "el proveedor {} requiere {}, que su API necesita en cada petición",
+    ),
+    ("the {} provider doesn't accept {}", "el proveedor {} no admite {}"),
+    ("on must be a list of failover triggers, found {}", "on debe ser una lista de disparadores de failover, no {}"),
+    ("failover triggers must be names, found {}", "los disparadores de failover deben ser nombres, no {}"),
+    (
+        "unknown failover trigger '{}' (expected timeout, rate_limit, server_error or error)",
+        "disparador de failover desconocido '{}' (se esperaba timeout, rate_limit, server_error o error)",
+    ),
+    // Guardrails
+    (
+        "expected an object such as {{ input: [pii_block], output: [toxicity] }}, found {}",
+        "se esperaba un objeto como {{ input: [pii_block], output: [toxicity] }}, no {}",
+    ),
+    ("unknown guardrails setting '{}'", "opción de guardrails desconocida '{}'"),
+    ("guardrails need at least one input or output rule", "los guardrails necesitan al menos una regla de input u output"),
+    (
+        "expected a rule such as pii_block or toxicity(threshold: 0.8), found {}",
+        "se esperaba una regla como pii_block o toxicity(threshold: 0.8), no {}",
+    ),
+    ("unknown guardrail '{}' (expected {})", "guardrail desconocido '{}' (se esperaba {})"),
+    ("action of {} must be block, redact or review, found {}", "action de {} debe ser block, redact o review, no {}"),
+    ("unknown action '{}' of {} (expected block, redact or review)", "acción desconocida '{}' de {} (se esperaba block, redact o review)"),
+    ("{} can't redact a message, its action must be block or review", "{} no puede redactar un mensaje, su action debe ser block o review"),
+    ("threshold of toxicity must be a number in (0, 1], found {}", "threshold de toxicity debe ser un número en (0, 1], no {}"),
+    ("endpoint of toxicity must be an http(s) URL, found '{}'", "endpoint de toxicity debe ser una URL http(s), no '{}'"),
+    ("invalid pattern of regex: {}", "patrón de regex inválido: {}"),
+    ("review must be the ID of a HumanReview agent, found {}", "review debe ser el ID de un agente HumanReview, no {}"),
+    (
+        "{} sends messages to review, which needs the HumanReview agent under review",
+        "{} envía mensajes a revisión, que necesita el agente HumanReview en review",
+    ),
+    // Memory and budgets
+    (
+        "expected an object such as {{ scope: \"session_id\", window: 10 }}, found {}",
+        "se esperaba un objeto como {{ scope: \"session_id\", window: 10 }}, no {}",
+    ),
+    ("unknown memory setting '{}'", "opción de memory desconocida '{}'"),
+    ("missing scope, the field identifying the session", "falta scope, el campo que identifica la sesión"),
+    ("scope must be the field identifying the session, found {}", "scope debe ser el campo que identifica la sesión, no {}"),
+    ("window must be a whole number of at least 1, found {}", "window debe ser un número entero de al menos 1, no {}"),
+    ("ttl must be a duration such as \"1h\", found {}", "ttl debe ser una duración como \"1h\", no {}"),
+    (
+        "expected an object such as {{ input_tokens: 20000000, output_tokens: 4000000 }}, found {}",
+        "se esperaba un objeto como {{ input_tokens: 20000000, output_tokens: 4000000 }}, no {}",
+    ),
+    ("unknown budget setting '{}'", "opción de budget desconocida '{}'"),
+    ("a budget needs input_tokens or output_tokens", "un presupuesto necesita input_tokens u output_tokens"),
+    // Routing
+    ("expected hash(key: data.<field>), found {}", "se esperaba hash(key: data.<campo>), no {}"),
+    ("unknown strategy '{}' (expected hash)", "estrategia desconocida '{}' (se esperaba hash)"),
+    ("unknown hash setting '{}'", "opción de hash desconocida '{}'"),
+    ("missing key, the path of the field messages are routed by", "falta key, la ruta del campo por el que se enrutan los mensajes"),
+    (
+        "key must be a path below data such as data.customer_id, found {}",
+        "key debe ser una ruta bajo data como data.customer_id, no {}",
+    ),
+    ("partitions must be a whole number of at least 1, found {}", "partitions debe ser un número entero de al menos 1, no {}"),
+    ("targets must be a non-empty list of topics, found {}", "targets debe ser una lista no vacía de topics, no {}"),
+    ("targets must list topics, found {}", "targets debe contener topics, no {}"),
+    ("target '{}' is listed twice", "el destino '{}' aparece dos veces"),
+    ("give partitions or targets, not both", "indica partitions o targets, no ambos"),
+    ("rules must be a resource(\"...\") with the routing table, found {}", "rules debe ser un resource(\"...\") con la tabla de rutas, no {}"),
+    ("missing reference", "falta la referencia"),
+    ("reference must be a resource URI, found {}", "reference debe ser la URI de un recurso, no {}"),
+    ("the URI of the routing table is empty", "la URI de la tabla de rutas está vacía"),
+    ("the routing table {} must be a .yaml, .yml or .json file", "la tabla de rutas {} debe ser un archivo .yaml, .yml o .json"),
+    ("invalid JSON routing table: {}", "tabla de rutas JSON inválida: {}"),
+    ("invalid YAML routing table: {}", "tabla de rutas YAML inválida: {}"),
+    ("invalid pattern of route '{}': {}", "patrón inválido de la ruta '{}': {}"),
+    ("route '{}' is listed twice", "la ruta '{}' aparece dos veces"),
+    ("route '{}' has an action without a target", "la ruta '{}' tiene una acción sin destino"),
+    ("route '{}' logs an empty message", "la ruta '{}' registra un mensaje vacío"),
+    ("route '{}' delays by an invalid interval '{}'", "la ruta '{}' retrasa con un intervalo inválido '{}'"),
+    // Aggregation
+    ("give a count or a time window, not both", "indica una ventana de count o de time, no ambas"),
+    ("count must be a whole number of at least 1, found {}", "count debe ser un número entero de al menos 1, no {}"),
+    ("time must be a duration such as \"1m\", found {}", "time debe ser una duración como \"1m\", no {}"),
+    (
+        "window needs a count or a time, such as {{ count: 100 }} or {{ time: \"1m\" }}",
+        "window necesita un count o un time, como {{ count: 100 }} o {{ time: \"1m\" }}",
+    ),
+    (
+        "window must be an object such as {{ count: 100 }} or {{ time: \"1m\" }}, found {}",
+        "window debe ser un objeto como {{ count: 100 }} o {{ time: \"1m\" }}, no {}",
+    ),
+    ("missing window, such as {{ count: 100 }} or {{ time: \"1m\" }}", "falta window, como {{ count: 100 }} o {{ time: \"1m\" }}"),
+    (
+        "group_by must be paths below data such as data.customer_id, found {}",
+        "group_by debe contener rutas bajo data como data.customer_id, no {}",
+    ),
+    ("two group_by fields are named '{}'", "dos campos de group_by se llaman '{}'"),
+    (
+        "reduce must list reductions such as {{ total: sum(data.amount) }}, found {}",
+        "reduce debe contener reducciones como {{ total: sum(data.amount) }}, no {}",
+    ),
+    ("missing reduce, such as reduce: {{ total: sum(data.amount) }}", "falta reduce, como reduce: {{ total: sum(data.amount) }}"),
+    ("reduction '{}' clashes with a field of the published messages", "la reducción '{}' choca con un campo de los mensajes publicados"),
+    ("reduction '{}': {}", "reducción '{}': {}"),
+    ("unknown reducer '{}' (expected {})", "reductor desconocido '{}' (se esperaba {})"),
+    ("expected a reduction such as sum(data.amount), found {}", "se esperaba una reducción como sum(data.amount), no {}"),
+    ("{} needs a value such as {}(data.amount)", "{} necesita un valor como {}(data.amount)"),
+    // Rules
+    (
+        "rules must list rules such as [high_risk: data.score > 0.9 => set(risk: \"high\")], found {}",
+        "rules debe contener reglas como [high_risk: data.score > 0.9 => set(risk: \"high\")], no {}",
+    ),
+    (
+        "missing rules, such as rules: [high_risk: data.score > 0.9 => set(risk: \"high\")]",
+        "faltan rules, como rules: [high_risk: data.score > 0.9 => set(risk: \"high\")]",
+    ),
+    ("two rules are named '{}'", "dos reglas se llaman '{}'"),
+    ("rule '{}' needs a condition such as data.score > 0.9", "la regla '{}' necesita una condición como data.score > 0.9"),
+    ("rule '{}' needs an action such as set(risk: \"high\")", "la regla '{}' necesita una acción como set(risk: \"high\")"),
+    ("rule '{}': {}", "regla '{}': {}"),
+    ("expected an action such as set(risk: \"high\"), found {}", "se esperaba una acción como set(risk: \"high\"), no {}"),
+    ("set needs the fields to set, such as set(risk: \"high\")", "set necesita los campos que asigna, como set(risk: \"high\")"),
+    ("set writes literal values, found {}: {}", "set asigna valores literales, no {}: {}"),
+    (
+        "publish needs a topic and nothing else, such as publish(topic: \"orders.vip\")",
+        "publish solo necesita un topic, como publish(topic: \"orders.vip\")",
+    ),
+    ("drop takes no arguments", "drop no admite argumentos"),
+    ("unknown action '{}' (expected set, publish or drop)", "acción desconocida '{}' (se esperaba set, publish o drop)"),
+    // Bayesian networks
+    ("the network_path is empty", "network_path está vacío"),
+    (
+        "network_path must be the path of the network such as \"models/risk.bif\", found {}",
+        "network_path debe ser la ruta de la red como \"models/risk.bif\", no {}",
+    ),
+    ("missing network_path, such as network_path: \"models/risk.bif\"", "falta network_path, como network_path: \"models/risk.bif\""),
+    ("the network {} must be a .bif, .xmlbif, .net or .uai file", "la red {} debe ser un fichero .bif, .xmlbif, .net o .uai"),
+    (
+        "query must list variables of the network such as [\"fraud\"], found {}",
+        "query debe contener variables de la red como [\"fraud\"], no {}",
+    ),
+    ("query lists '{}' twice", "query incluye '{}' dos veces"),
+    // Custom agents
+    (
+        "the name of the templates must be a string such as Custom(\"scorer\", ...), found {}",
+        "el nombre de las plantillas debe ser un texto como Custom(\"scorer\", ...), no {}",
+    ),
+    ("missing the name of the templates, such as Custom(\"scorer\", ...)", "falta el nombre de las plantillas, como Custom(\"scorer\", ...)"),
+    (
+        "the name of the templates '{}' must be letters, digits, '-' or '_', such as \"fraud-scorer\"",
+        "el nombre de las plantillas '{}' debe tener letras, dígitos, '-' o '_', como \"fraud-scorer\"",
+    ),
+    ("the name of the templates '{}' is reserved for the built-in templates", "el nombre de las plantillas '{}' está reservado para las plantillas incluidas"),
+    ("language must be \"rust\" or \"python\", found {}", "language debe ser \"rust\" o \"python\", no {}"),
+    // Data normalizers
+    (
+        "expected a scaler such as minmax(min: 0, max: 100) or zscore(mean: 50, std: 10), found {}",
+        "se esperaba un escalado como minmax(min: 0, max: 100) o zscore(mean: 50, std: 10), no {}",
+    ),
+    ("unknown scaler '{}' (expected minmax or zscore)", "escalado desconocido '{}' (se esperaba minmax o zscore)"),
+    ("{} of {} must be a number, found {}", "{} de {} debe ser un número, no {}"),
+    ("{} needs {}", "{} necesita {}"),
+    ("min and max", "min y max"),
+    ("mean and std", "mean y std"),
+    ("the max of minmax must be above its min, found {} and {}", "el max de minmax debe ser mayor que su min, no {} y {}"),
+    ("the std of zscore must be positive, found {}", "el std de zscore debe ser positivo, no {}"),
+    ("mapping '{}' must name a top-level field such as amount", "el mapeo '{}' debe nombrar un campo de primer nivel como amount"),
+    (
+        "mapping '{}' must read a path below data such as data.payment.amount, found {}",
+        "el mapeo '{}' debe leer una ruta bajo data como data.payment.amount, no {}",
+    ),
+    ("two mappings read {}", "dos mapeos leen {}"),
+    (
+        "mappings must map fields to paths such as {{ amount: data.payment.amount }}, found {}",
+        "mappings debe asociar campos a rutas como {{ amount: data.payment.amount }}, no {}",
+    ),
+    ("scale '{}' must name a top-level field such as amount", "scale '{}' debe nombrar un campo de primer nivel como amount"),
+    ("scale of {}: {}", "scale de {}: {}"),
+    (
+        "scale must map fields to scalers such as {{ amount: minmax(min: 0, max: 100) }}, found {}",
+        "scale debe asociar campos a escalados como {{ amount: minmax(min: 0, max: 100) }}, no {}",
+    ),
+    (
+        "missing mappings or scale, such as mappings: {{ amount: data.payment.amount }}",
+        "falta mappings o scale, como mappings: {{ amount: data.payment.amount }}",
+    ),
+    // Missing values
+    ("expected an imputation such as mean(), found {}", "se esperaba una imputación como mean(), no {}"),
+    ("unknown imputation '{}' (expected mean, median or last)", "imputación desconocida '{}' (se esperaba mean, median o last)"),
+    ("{}() takes no arguments", "{}() no admite argumentos"),
+    (
+        "default '{}' must name a field of the message such as country or \"customer.tier\"",
+        "el valor por defecto '{}' debe nombrar un campo del mensaje como country o \"customer.tier\"",
+    ),
+    ("the default of {} can't be null", "el valor por defecto de {} no puede ser null"),
+    ("defaults are literal values, found {}: {}", "los valores por defecto son literales, no {}: {}"),
+    (
+        "defaults must map fields to values such as {{ country: \"US\" }}, found {}",
+        "defaults debe asociar campos a valores como {{ country: \"US\" }}, no {}",
+    ),
+    (
+        "impute '{}' must name a field of the message such as amount or \"customer.age\"",
+        "impute '{}' debe nombrar un campo del mensaje como amount o \"customer.age\"",
+    ),
+    ("{} has both a default and an imputation", "{} tiene a la vez un valor por defecto y una imputación"),
+    ("impute of {}: {}", "impute de {}: {}"),
+    (
+        "impute must map fields to imputations such as {{ amount: mean() }}, found {}",
+        "impute debe asociar campos a imputaciones como {{ amount: mean() }}, no {}",
+    ),
+    ("missing defaults or impute, such as defaults: {{ country: \"US\" }}", "falta defaults o impute, como defaults: {{ country: \"US\" }}"),
+    (
+        "window must be a whole number of values of at least 1, found {}",
+        "window debe ser un número entero de valores de al menos 1, no {}",
+    ),
+    // Human review
+    ("sla must be a duration such as \"4h\", found {}", "sla debe ser una duración como \"4h\", no {}"),
+    ("on_sla_breach must be approve, reject or expire, found {}", "on_sla_breach debe ser approve, reject o expire, no {}"),
+    ("unknown on_sla_breach '{}' (expected approve, reject or expire)", "on_sla_breach desconocido '{}' (se esperaba approve, reject o expire)"),
+    ("on_sla_breach needs an sla", "on_sla_breach necesita un sla"),
+    ("escalation must be a non-empty list of steps, found {}", "escalation debe ser una lista no vacía de pasos, no {}"),
+    (
+        "escalation steps must be objects such as {{ after: \"2h\", notify: [\"leads\"], via: \"slack\" }}, found {}",
+        "los pasos de escalation deben ser objetos como {{ after: \"2h\", notify: [\"leads\"], via: \"slack\" }}, no {}",
+    ),
+    ("unknown escalation setting '{}'", "opción de escalation desconocida '{}'"),
+    ("missing after, the delay of the escalation step", "falta after, el retraso del paso de escalado"),
+    ("after must be a duration such as \"2h\", found {}", "after debe ser una duración como \"2h\", no {}"),
+    ("invalid after '{}'", "after inválido '{}'"),
+    ("missing notify, who the escalation step notifies", "falta notify, a quién notifica el paso de escalado"),
+    ("notify must be a non-empty list of reviewers or groups, found {}", "notify debe ser una lista no vacía de revisores o grupos, no {}"),
+    ("missing via, the notification channel of the escalation step", "falta via, el canal de notificación del paso de escalado"),
+    (
+        "via must name a notification channel such as \"slack\", found {}",
+        "via debe nombrar un canal de notificación como \"slack\", no {}",
+    ),
+    ("escalation steps must come in increasing order of after", "los pasos de escalation deben ir en orden creciente de after"),
+    ("the escalation step after {}s comes once the sla has lapsed", "el paso de escalado tras {}s llega cuando el sla ya ha vencido"),
+    ("timeout must be a duration such as \"30m\", found {}", "timeout debe ser una duración como \"30m\", no {}"),
+    (
+        "the timeout of {}s is longer than the sla of {}s, which decides the review first",
+        "el timeout de {}s es más largo que el sla de {}s, que decide la revisión antes",
+    ),
+    ("on_timeout must be a NATS(\"<subject>\") subject, found {}", "on_timeout debe ser un subject NATS(\"<subject>\"), no {}"),
+    ("unknown on_timeout setting '{}'", "opción de on_timeout desconocida '{}'"),
+    ("the on_timeout subject is empty", "el subject de on_timeout está vacío"),
+    ("the on_timeout subject {} must not have wildcards or spaces", "el subject de on_timeout {} no debe tener comodines ni espacios"),
+    (
+        "notifications must be a non-empty list of notification channels such as [\"slack\"], found {}",
+        "notifications debe ser una lista no vacía de canales de notificación como [\"slack\"], no {}",
+    ),
+    ("ui must be true or false, found {}", "ui debe ser true o false, no {}"),
+    ("delegation must be an object of groups, found {}", "delegation debe ser un objeto de grupos, no {}"),
+    (
+        "the members of delegation group '{}' must be a non-empty list of reviewers, found {}",
+        "los miembros del grupo de delegación '{}' deben ser una lista no vacía de revisores, no {}",
+    ),
+    ("reviewer '{}' belongs to delegation groups '{}' and '{}'", "el revisor '{}' pertenece a los grupos de delegación '{}' y '{}'"),
+    ("allow must be a list of groups, found {}", "allow debe ser una lista de grupos, no {}"),
+    ("allow must list group names, found {}", "allow debe contener nombres de grupos, no {}"),
+    (
+        "audit must be an object such as {{ issuer: \"https://sso.example.com\" }}, found {}",
+        "audit debe ser un objeto como {{ issuer: \"https://sso.example.com\" }}, no {}",
+    ),
+    ("unknown audit setting '{}'", "opción de audit desconocida '{}'"),
+    ("issuer must be an https URL, found '{}'", "issuer debe ser una URL https, no '{}'"),
+    ("signing_key must name a Kubernetes Secret, found '{}'", "signing_key debe nombrar un Secret de Kubernetes, no '{}'"),
+    (
+        "expected OIDC(\"<issuer>\", \"<client id>\", \"<groups claim>\"), found {}",
+        "se esperaba OIDC(\"<issuer>\", \"<client id>\", \"<groups claim>\"), no {}",
+    ),
+    ("unknown OIDC setting '{}'", "opción de OIDC desconocida '{}'"),
+    ("method must be a string, found {}", "method debe ser un texto, no {}"),
+];
+
+/// Templates of what `kumeo` reports while running its commands
+///
+/// These are the summaries of the commands and the errors they fail with,
+/// such as a file that can't be written or a template that can't be rendered.
+const COMMANDS: &[(&str, &str)] = &[
+    // Errors of the compiler
+    ("Lexer error: {}", "Error léxico: {}"),
+    ("Parser error at line {}, column {}: {}", "Error de análisis en la línea {}, columna {}: {}"),
+    ("Semantic error: {}", "Error semántico: {}"),
+    ("Semantic errors:\n{}", "Errores semánticos:\n{}"),
+    ("Type error: {}", "Error de tipos: {}"),
+    ("IO error: {}", "Error de E/S: {}"),
+    ("Failed to render template {}: {}", "No se pudo renderizar la plantilla {}: {}"),
+    ("Unknown error: {}", "Error desconocido: {}"),
+    ("Invalid schema catalog {}: {}", "Catálogo de esquemas inválido {}: {}"),
+    ("Invalid configuration schemas: {}", "Esquemas de configuración inválidos: {}"),
+    (
+        "The workflows exceed the quotas of the platform; nothing was generated:\n  {}",
+        "Los workflows exceden las cuotas de la plataforma; no se ha generado nada:\n  {}",
+    ),
+    // Errors of the kumeo commands
+    ("Invalid project manifest", "Manifiesto del proyecto inválido"),
+    ("Invalid external NATS", "NATS externo inválido"),
+    (
+        "No input file was given (use --input or set `entry` in {})",
+        "No se indicó ningún archivo de entrada (usa --input o define `entry` en {})",
+    ),
+    (
+        "{} sets {} input files; choose one with --input",
+        "{} define {} archivos de entrada; indica cuál usar con --input",
+    ),
+    ("Validation failed", "Validación fallida"),
+    ("Files with syntax errors can't be fixed", "No se pueden corregir archivos con errores de sintaxis"),
+    ("Couldn't list the files of {}", "No se pudieron listar los archivos de {}"),
+    ("No .kumeo files found in {}", "No se encontraron archivos .kumeo en {}"),
+    ("--output takes a single input file, found {}", "--output solo admite un archivo de entrada, se encontraron {}"),
+    ("A formatting task failed", "Falló una tarea de formateo"),
+    ("Couldn't format {} of {} files", "No se pudieron formatear {} de {} archivos"),
+    ("{} files need to be formatted", "{} archivos necesitan ser formateados"),
+    ("Invalid format options for {}", "Opciones de formato inválidas para {}"),
+    ("Workflow {} isn't defined in {}", "El workflow {} no está definido en {}"),
+    ("The workflows can't share the cluster:\n  {}", "Los workflows no pueden compartir el clúster:\n  {}"),
+    ("Couldn't generate the web console", "No se pudo generar la consola web"),
+    (
+        "Some programs have errors; they will be regenerated when they change",
+        "Hay programas con errores; se regenerarán cuando cambien",
+    ),
+    (
+        "Agent {} ({}) is generated on {}, which isn't one of the targets of {}",
+        "El agente {} ({}) se genera en {}, que no está entre los targets de {}",
+    ),
+    (
+        "Offline mode: some remote resources aren't vendored (run `kumeo vendor`):\n  {}",
+        "Modo offline: hay recursos remotos sin vendorizar (ejecuta `kumeo vendor`):\n  {}",
+    ),
+    ("No workflows found in the program: {}", "No se encontraron workflows en el programa: {}"),
+    ("Invalid namespace: {}", "Namespace inválido: {}"),
+    ("Invalid registry: {}", "Registro inválido: {}"),
+    ("Invalid tag: {}", "Tag inválido: {}"),
+    ("No tests found to run", "No se encontraron tests que ejecutar"),
+    ("{} tests failed", "{} tests fallidos"),
+    ("Workflow '{}' wasn't found", "No se encontró el workflow '{}'"),
+    ("No workflows found to test", "No se encontraron workflows que probar"),
+    (
+        "The program has several workflows; choose the one to test with --workflow",
+        "El programa tiene varios workflows, indica cuál probar con --workflow",
+    ),
+    ("No result reached {}", "No llegó ningún resultado a {}"),
+    ("No workflows found to debug", "No se encontraron workflows que depurar"),
+    (
+        "The program has several workflows; choose the one of the agent with --workflow",
+        "El programa tiene varios workflows, indica el del agente con --workflow",
+    ),
+    ("No workflows found to compare", "No se encontraron workflows para comparar"),
+    ("The cluster doesn't match the DSL", "El clúster no coincide con el DSL"),
+    (
+        "The versions of {} workflows don't match their changes",
+        "Las versiones de {} workflows no corresponden a sus cambios",
+    ),
+    ("Formatting would change the program", "El formateo cambiaría el programa"),
+    // Code generation
+    ("Agent must have an ID", "El agente debe tener un ID"),
+    ("Failed to create agent directory: {}", "No se pudo crear el directorio del agente: {}"),
+    ("Failed to generate agent: {}", "No se pudo generar el agente: {}"),
+    ("Failed to generate the manifests of agent: {}", "No se pudieron generar los manifiestos del agente: {}"),
+    ("Failed to generate the review UI of agent: {}", "No se pudo generar la interfaz de revisión del agente: {}"),
+    ("{} agents have no built-in templates", "Los agentes {} no tienen plantillas incluidas"),
+    ("No templates found for {} agents in {}", "No se encontraron plantillas para los agentes {} en {}"),
+    ("Failed to write Dockerfile: {}", "No se pudo escribir el Dockerfile: {}"),
+    ("Failed to create kubernetes directory: {}", "No se pudo crear el directorio de kubernetes: {}"),
+    ("Invalid aggregator {}: {}", "Agregador inválido {}: {}"),
+    ("Invalid auth of {}: {}", "Autenticación inválida de {}: {}"),
+    ("Invalid network of {}: {}", "Red inválida de {}: {}"),
+    ("Failed to render {}", "No se pudo renderizar {}"),
+    ("Invalid input schema: {}", "Esquema de entrada inválido: {}"),
+    ("Invalid when condition `{}`: {}", "Condición when inválida `{}`: {}"),
+    ("Invalid fallback: {}", "Fallback inválido: {}"),
+    ("Invalid fallback of agent {} of {}", "Fallback inválido del agente {} de {}"),
+    ("Failed to create directory: {}", "No se pudo crear el directorio: {}"),
+    ("Failed to render the console template {}", "No se pudo renderizar la plantilla de la consola {}"),
+    ("Failed to write {}", "No se pudo escribir {}"),
+    (
+        "Custom agent {} has no templates: create {} in the project's templates directory, or in the user's, with a .tera file per file of the agent such as {}{}",
+        "El agente Custom {} no tiene plantillas: crea {} en el directorio de plantillas del proyecto, o en el del usuario, con un archivo .tera por cada archivo del agente, como {}{}",
+    ),
+    ("Failed to generate custom agent {}", "No se pudo generar el agente Custom {}"),
+    ("Failed to serialize workflow {}", "No se pudo serializar el workflow {}"),
+    ("Invalid encoding: {}", "Codificación inválida: {}"),
+    ("Invalid output schema: {}", "Esquema de salida inválido: {}"),
+    ("Topic '{}' is encoded in {} but has no schema", "El topic '{}' se codifica en {} pero no tiene esquema"),
+    ("Failed to create {}", "No se pudo crear {}"),
+    ("Only LLM agents chain providers, {} is a {}", "Solo los agentes LLM encadenan proveedores, {} es un {}"),
+    ("Invalid providers of {}: {}", "Proveedores inválidos de {}: {}"),
+    ("The {} provider of {} needs a model", "El proveedor {} de {} necesita un modelo"),
+    ("Only MLModel agents fetch features, {} is a {}", "Solo los agentes MLModel leen features, {} es un {}"),
+    ("Invalid feature store: {}", "Feature store inválido: {}"),
+    ("Only LLM agents have guardrails, {} is a {}", "Solo los agentes LLM tienen guardrails, {} es un {}"),
+    ("Invalid guardrails of {}: {}", "Guardrails inválidos de {}: {}"),
+    (
+        "{} sends messages to review to {}, which is not a HumanReview agent of {}",
+        "{} envía mensajes a revisar a {}, que no es un agente HumanReview de {}",
+    ),
+    (
+        "Only LLM agents use inference servers, {} is a {}",
+        "Solo los agentes LLM usan servidores de inferencia, {} es un {}",
+    ),
+    ("Invalid provider of {}: {}", "Proveedor inválido de {}: {}"),
+    ("The {} server of {} needs a model", "El servidor {} de {} necesita un modelo"),
+    ("Failed to render the Kafka StatefulSet", "No se pudo renderizar el StatefulSet de Kafka"),
+    ("Failed to write the Kafka StatefulSet", "No se pudo escribir el StatefulSet de Kafka"),
+    ("Failed to create inference directory: {}", "No se pudo crear el directorio de inferencia: {}"),
+    ("Failed to render the inference server {}", "No se pudo renderizar el servidor de inferencia {}"),
+    ("Failed to write the inference server {}", "No se pudo escribir el servidor de inferencia {}"),
+    ("Failed to generate the Helm chart", "No se pudo generar el chart de Helm"),
+    ("Only LLM agents remember sessions, {} is a {}", "Solo los agentes LLM recuerdan sesiones, {} es un {}"),
+    ("Invalid memory of {}: {}", "Memoria inválida de {}: {}"),
+    ("Invalid missing value handler {}: {}", "Tratamiento de valores ausentes inválido {}: {}"),
+    ("Failed to create output directory: {}", "No se pudo crear el directorio de salida: {}"),
+    ("Failed to render the .env.example of workflow {}", "No se pudo renderizar el .env.example del workflow {}"),
+    ("Only MLModel agents track drift, {} is a {}", "Solo los agentes MLModel vigilan el drift, {} es un {}"),
+    ("Invalid drift: {}", "Drift inválido: {}"),
+    ("Failed to render the monitoring of workflow {}", "No se pudo renderizar la monitorización del workflow {}"),
+    ("Failed to render the Nomad job of agent {}", "No se pudo renderizar el job de Nomad del agente {}"),
+    ("Failed to render the NATS job", "No se pudo renderizar el job de NATS"),
+    ("Invalid normalizer {}: {}", "Normalizador inválido {}: {}"),
+    ("Failed to read output manifest: {}", "No se pudo leer el manifiesto de salida: {}"),
+    ("Invalid output manifest: {}", "Manifiesto de salida inválido: {}"),
+    ("Failed to write output manifest: {}", "No se pudo escribir el manifiesto de salida: {}"),
+    ("Failed to delete {}", "No se pudo borrar {}"),
+    ("Failed to list {}", "No se pudo listar {}"),
+    ("Invalid quality monitor: {}", "Monitor de calidad inválido: {}"),
+    ("Invalid retry policy: {}", "Política de reintentos inválida: {}"),
+    ("Invalid review settings of {}: {}", "Opciones de revisión inválidas de {}: {}"),
+    ("Invalid audit of {}: {}", "Auditoría inválida de {}: {}"),
+    ("Failed to render the review UI template {}", "No se pudo renderizar la plantilla de la interfaz de revisión {}"),
+    (
+        "Only Router agents have a routing strategy, {} is a {}",
+        "Solo los agentes Router tienen estrategia de enrutado, {} es un {}",
+    ),
+    ("Invalid strategy of {}: {}", "Estrategia inválida de {}: {}"),
+    ("{} partitions its output topic, but has none", "{} particiona su topic de salida, pero no tiene"),
+    ("Invalid rules of {}: {}", "Reglas inválidas de {}: {}"),
+    ("Invalid compensation of {}: {}", "Compensación inválida de {}: {}"),
+    (
+        "{} can't compensate, {} agents don't take part in sagas",
+        "{} no puede compensar, los agentes {} no participan en sagas",
+    ),
+    (
+        "The saga steps of {} disagree on the correlation ID: {} and {}",
+        "Los pasos de la saga de {} no coinciden en el ID de correlación: {} y {}",
+    ),
+    ("Failed to read {}", "No se pudo leer {}"),
+    ("Subworkflow not found: {}", "Subworkflow no encontrado: {}"),
+    (
+        "Agent ID {} of workflow {} clashes with an agent of an invoked subworkflow",
+        "El ID {} de un agente del workflow {} choca con un agente de un subworkflow invocado",
+    ),
+    ("Agent {} of subworkflow {} must have an ID", "El agente {} del subworkflow {} debe tener un ID"),
+    (
+        "Parameter {{{}}} of subworkflow {} is not bound by its invocation in workflow {}",
+        "El parámetro {{{}}} del subworkflow {} no se asigna en su invocación en el workflow {}",
+    ),
+    ("Failed to parse the built-in templates", "No se pudieron analizar las plantillas incluidas"),
+    ("Failed to parse the templates in {}", "No se pudieron analizar las plantillas de {}"),
+    ("No built-in templates found in {}", "No se encontraron plantillas incluidas en {}"),
+    ("Template directory not found: {}", "Directorio de plantillas no encontrado: {}"),
+    ("Failed to list the templates in {}", "No se pudieron listar las plantillas de {}"),
+    ("Failed to parse templates in {}", "No se pudieron analizar las plantillas en {}"),
+    ("Invalid file name", "Nombre de archivo inválido"),
+    ("Failed to read template: {}", "No se pudo leer la plantilla: {}"),
+    ("Failed to write to file: {}", "No se pudo escribir en el archivo: {}"),
+    ("Failed to copy file from {} to {}", "No se pudo copiar el archivo de {} a {}"),
+    ("Template overrides directory not found: {}", "Directorio de plantillas propias no encontrado: {}"),
+    ("Failed to read file: {}", "No se pudo leer el archivo: {}"),
+    ("Failed to parse inline template", "No se pudo analizar la plantilla en línea"),
+    ("Failed to render template string", "No se pudo renderizar la plantilla en línea"),
+    ("Failed to render the Terraform template {}", "No se pudo renderizar la plantilla de Terraform {}"),
+    // NATS
+    ("Invalid NATS URL: {}", "URL de NATS inválida: {}"),
+    (
+        "Unsupported NATS URL scheme `{}` (expected nats, tls, ws or wss): {}",
+        "Esquema de URL de NATS no soportado `{}` (se esperaba nats, tls, ws o wss): {}",
+    ),
+    ("NATS URL has no host: {}", "La URL de NATS no tiene host: {}"),
+    (
+        "NATS URL must not embed credentials, pass a Secret with --nats-credentials instead: {}",
+        "La URL de NATS no debe incluir credenciales, indica un Secret con --nats-credentials: {}",
+    ),
+    ("Invalid Secret name for NATS credentials: {}", "Nombre de Secret inválido para las credenciales de NATS: {}"),
+    ("Cannot resolve NATS host {}", "No se puede resolver el host de NATS {}"),
+    ("Cannot connect to NATS at {}:{}: {}", "No se puede conectar a NATS en {}:{}: {}"),
+    ("NATS host {} resolves to no address", "El host de NATS {} no se resuelve a ninguna dirección"),
+    ("NATS server did not send its INFO greeting", "El servidor de NATS no envió su saludo INFO"),
+    ("Not a NATS server, unexpected greeting: {}", "No es un servidor de NATS, saludo inesperado: {}"),
+    ("Invalid INFO greeting from the NATS server", "Saludo INFO inválido del servidor de NATS"),
+    ("Only nats:// URLs are supported: {}", "Solo se admiten URLs nats://: {}"),
+    ("Timed out connecting to NATS at {}:{}", "Se agotó el tiempo al conectar a NATS en {}:{}"),
+    ("Cannot connect to NATS at {}:{}", "No se puede conectar a NATS en {}:{}"),
+    ("NATS server did not answer the connection", "El servidor de NATS no respondió a la conexión"),
+    ("NATS server refused the connection: {}", "El servidor de NATS rechazó la conexión: {}"),
+    ("Unexpected reply from NATS: {}", "Respuesta inesperada de NATS: {}"),
+    ("Malformed {} frame from NATS: {}", "Trama {} mal formada de NATS: {}"),
+    ("NATS error: {}", "Error de NATS: {}"),
+    // Costs, quotas and statistics
+    ("Failed to read the pricing file {}", "No se pudo leer el archivo de precios {}"),
+    ("Invalid pricing file {}", "Archivo de precios inválido {}"),
+    ("{} must be a non-negative number", "{} debe ser un número no negativo"),
+    ("Invalid budget of {}: {}", "Presupuesto inválido de {}: {}"),
+    (
+        "No token price for the model {} of {}; add it to llm.models or set llm.default",
+        "No hay precio por token para el modelo {} de {}; añádelo a llm.models o define llm.default",
+    ),
+    ("Invalid CPU quota '{}' of {}", "Cuota de CPU inválida '{}' de {}"),
+    ("Invalid memory quota '{}' of {}", "Cuota de memoria inválida '{}' de {}"),
+    ("Failed to read the quotas file {}", "No se pudo leer el archivo de cuotas {}"),
+    ("Invalid quotas file {}", "Archivo de cuotas inválido {}"),
+    ("Invalid CPU request '{}' of workflow {}", "Petición de CPU inválida '{}' del workflow {}"),
+    ("Invalid memory request '{}' of workflow {}", "Petición de memoria inválida '{}' del workflow {}"),
+    // Debugger, live cluster and load tests
+    ("Invalid message at {}:{}", "Mensaje inválido en {}:{}"),
+    ("Workflow {} has no agent {}", "El workflow {} no tiene el agente {}"),
+    ("There is no message to edit", "No hay ningún mensaje que editar"),
+    ("The edited payload is not JSON", "El contenido editado no es JSON"),
+    ("Failed to run the editor {}", "No se pudo ejecutar el editor {}"),
+    ("The editor {} exited with {}", "El editor {} terminó con {}"),
+    ("The debugger needs a terminal", "El depurador necesita una terminal"),
+    ("Expected a Kubernetes list with an 'items' array", "Se esperaba una lista de Kubernetes con un array 'items'"),
+    ("Workload without metadata.name", "Workload sin metadata.name"),
+    (
+        "Failed to run kubectl; is it installed and on PATH?",
+        "No se pudo ejecutar kubectl; ¿está instalado y en el PATH?",
+    ),
+    ("kubectl failed: {}", "kubectl falló: {}"),
+    ("kubectl returned invalid JSON", "kubectl devolvió un JSON inválido"),
+    (
+        "Workflow '{}' does not read from NATS, load tests publish on a NATS subject",
+        "El workflow '{}' no lee de NATS, las pruebas de carga publican en un subject de NATS",
+    ),
+    ("Workflow '{}' has no input subject", "El workflow '{}' no tiene subject de entrada"),
+    (
+        "Workflow '{}' has no output subject to measure latency on",
+        "El workflow '{}' no tiene subject de salida en el que medir la latencia",
+    ),
+    ("Failed to read sample file: {}", "No se pudo leer el archivo de muestras: {}"),
+    ("Invalid JSON on line {} of {}", "JSON inválido en la línea {} de {}"),
+    ("Sample file has no messages: {}", "El archivo de muestras no tiene mensajes: {}"),
+    (
+        "Rate must be a positive number of messages per second",
+        "La tasa debe ser un número positivo de mensajes por segundo",
+    ),
+    ("Failed to publish on {}", "No se pudo publicar en {}"),
+    // Formatter, projects and simulator
+    ("Invalid formatter options: {}", "Opciones del formateador inválidas: {}"),
+    (
+        "Invalid formatter options: indent_width and max_line_length must be above 0",
+        "Opciones del formateador inválidas: indent_width y max_line_length deben ser mayores que 0",
+    ),
+    ("In {}", "En {}"),
+    ("No such file or directory: {}", "No existe el archivo o directorio: {}"),
+    ("Invalid pattern: {}", "Patrón inválido: {}"),
+    ("Invalid project manifest: {}", "Manifiesto del proyecto inválido: {}"),
+    (
+        "Invalid project manifest: unknown target '{}' (expected {})",
+        "Manifiesto del proyecto inválido: target desconocido '{}' (se esperaba {})",
+    ),
+    ("Test \"{}\" of workflow {}: {}", "Test \"{}\" del workflow {}: {}"),
+    ("Test \"{}\": unknown outcome '{}'", "Test \"{}\": resultado desconocido '{}'"),
+    ("Test \"{}\": invalid expectation {}: {}", "Test \"{}\": expectativa inválida {}: {}"),
+    ("Unknown agent {}", "Agente desconocido {}"),
+    ("Invalid aggregator: {}", "Agregador inválido: {}"),
+    ("Invalid rules: {}", "Reglas inválidas: {}"),
+    // Vendored resources and watching
+    ("Invalid mirror rule '{}': {}", "Regla de mirror inválida '{}': {}"),
+    ("Failed to read vendor manifest: {}", "No se pudo leer el manifiesto de vendor: {}"),
+    ("Invalid vendor manifest: {}", "Manifiesto de vendor inválido: {}"),
+    ("Failed to write vendor manifest: {}", "No se pudo escribir el manifiesto de vendor: {}"),
+    ("Invalid URI: {}", "URI inválida: {}"),
+    ("URI without host: {}", "URI sin host: {}"),
+    ("Refusing to vendor a URI with relative segments: {}", "No se vendoriza una URI con segmentos relativos: {}"),
+    ("Failed to create vendor directory: {}", "No se pudo crear el directorio de vendor: {}"),
+    (
+        "Vendored copy {} does not match its checksum; run `kumeo vendor` again",
+        "La copia vendorizada {} no coincide con su checksum; ejecuta `kumeo vendor` de nuevo",
+    ),
+    ("Failed to copy {}", "No se pudo copiar {}"),
+    ("Missing vendored copy: {}", "Falta la copia vendorizada: {}"),
+    (
+        "Archive members can't be fetched by the compiler: {}",
+        "El compilador no puede descargar miembros de archivos: {}",
+    ),
+    (
+        "The compiler can't fetch {}:// resources; add a mirror rule to HTTP or a file for {}",
+        "El compilador no puede descargar recursos {}://; añade una regla de mirror a HTTP o a un archivo para {}",
+    ),
+    ("Failed to download {}", "No se pudo descargar {}"),
+    ("Failed to download {}: HTTP {}", "No se pudo descargar {}: HTTP {}"),
+    ("Failed to start the file watcher", "No se pudo iniciar la vigilancia de archivos"),
+    ("Failed to watch {}", "No se pudo vigilar {}"),
+    // Summaries of the kumeo commands
+    ("External NATS reachable: {}", "NATS externo accesible: {}"),
+    ("The file is valid", "El archivo es válido"),
+    ("There are warnings and --deny-warnings was given", "Hay avisos y se indicó --deny-warnings"),
+    ("Validation errors were found", "Se encontraron errores de validación"),
+    ("There is nothing to fix", "No hay nada que corregir"),
+    ("{} fixes to apply", "{} correcciones por aplicar"),
+    ("{} fixes applied", "{} correcciones aplicadas"),
+    ("{} of {} files need to be formatted", "{} de {} archivos necesitan ser formateados"),
+    ("The {} files are already formatted", "Los {} archivos ya están correctamente formateados"),
+    ("{} files formatted, {} unchanged", "{} archivos formateados, {} sin cambios"),
+    ("Code generated in: {}", "Código generado correctamente en: {}"),
+    (
+        "{} workflows generated in: {} (apply them with `kubectl apply -k {}`)",
+        "{} workflows generados correctamente en: {} (aplica con `kubectl apply -k {}`)",
+    ),
+    (
+        "Dry run of the generation in {} (nothing was written):",
+        "Simulación de la generación en {} (no se ha escrito nada):",
+    ),
+    ("{} new files, {} changed and {} unchanged", "{} archivos nuevos, {} modificados y {} sin cambios"),
+    ("Change in {}", "Cambio en {}"),
+    ("Regenerated in {} ms; waiting for changes", "Regenerado en {} ms; esperando cambios"),
+    ("Regenerating {} of {}", "Regenerando {} de {}"),
+    ("Files --prune would delete:", "Archivos que --prune borraría:"),
+    ("{} files of removed agents deleted: {}", "{} archivos borrados de agentes eliminados: {}"),
+    (
+        "Their resources are still in the cluster: kubectl delete all -l app={}",
+        "Sus recursos siguen en el clúster: kubectl delete all -l app={}",
+    ),
+    (
+        "Agents removed from the program with generated files (use --prune to delete them):",
+        "Agentes eliminados del programa con archivos generados (usa --prune para borrarlos):",
+    ),
+    ("{} files in {}", "{} archivos en {}"),
+    ("{} resources vendored in: {}", "{} recursos vendorizados en: {}"),
+    ("{} tests, {} failed", "{} tests, {} fallidos"),
+    ("Publishing {} messages on {} ({}/s) from {}...", "Publicando {} mensajes en {} ({}/s) desde {}..."),
+    ("{} messages of {} ({}) in {}", "{} mensajes de {} ({}) en {}"),
+    ("the cluster matches the DSL", "el clúster coincide con el DSL"),
+    ("{} differences were found:", "se encontraron {} divergencias:"),
+    ("no version", "sin versión"),
+    ("no changes", "sin cambios"),
+    ("Workflows: {} ({} subworkflows)", "Workflows: {} ({} subworkflows)"),
+    ("Agents: {} ({})", "Agentes: {} ({})"),
+    ("Topics: {}", "Topics: {}"),
+    ("Schemas: {}", "Esquemas: {}"),
+    ("External resources: {}", "Recursos externos: {}"),
+    ("Estimated footprint on Kubernetes: {}", "Huella estimada en Kubernetes: {}"),
+    ("{} agents, {}", "{} agentes, {}"),
+    ("{} documentation pages in {}", "{} páginas de documentación en {}"),
+    ("Estimated monthly cost ({} hours, in {}):", "Coste mensual estimado ({} horas, en {}):"),
+    ("{} (CPU {}, memory {}, LLM {})", "{} (CPU {}, memoria {}, LLM {})"),
+    ("unknown model", "modelo desconocido"),
+    ("{} input tokens, {} output: {}", "{} tokens de entrada, {} de salida: {}"),
+    ("CPU {}, memory {}, LLM {}", "CPU {}, memoria {}, LLM {}"),
+    ("Template layers, from highest to lowest priority:", "Capas de plantillas, de mayor a menor prioridad:"),
+    ("overrides {}", "sustituye a {}"),
+    ("{} templates, {} overridden", "{} plantillas, {} sustituidas"),
+    ("Error in {}", "Error en {}"),
+    // Workflow tests
+    ("expected the message on '{}', it was published on '{}'", "se esperaba el mensaje en '{}', se publicó en '{}'"),
+    ("expected the message on '{}', nothing was published", "se esperaba el mensaje en '{}', no se publicó nada"),
+    (
+        "expected nothing to be published, the message went to '{}'",
+        "se esperaba que no se publicara nada, el mensaje fue a '{}'",
+    ),
+    ("expected the message to be {}, it was {}", "se esperaba que el mensaje fuera {}, fue {}"),
+    ("the message was {}: {}", "el mensaje fue {}: {}"),
+    ("when condition `{}` is false", "la condición when `{}` es falsa"),
+    ("invariant `{}` doesn't hold", "el invariante `{}` no se cumple"),
+    ("the message is not a JSON object", "el mensaje no es un objeto JSON"),
+    ("field '{}' should be of type {}, found {}", "el campo '{}' debería ser de tipo {}, no {}"),
+];
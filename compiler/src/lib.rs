@@ -8,19 +8,47 @@
 //! - `parser`: Análisis sintáctico del código fuente
 //! - `semantic`: Análisis semántico y validación
 //! - `codegen`: Generación de código
+//! - `cost`: Estimación del coste mensual de un despliegue a partir de unos precios
+//! - `debug`: Recorrido paso a paso del historial de mensajes de un agente y reinyección de mensajes
+//! - `diagnostics`: Errores y avisos con código, posición y ayuda
+//! - `docs`: Documentación de los workflows y agentes para el sitio de documentación y el editor
+//! - `i18n`: Catálogo de los mensajes de los diagnósticos en inglés y en español
+//! - `formatter`: Formateo del código fuente conservando los comentarios
+//! - `live`: Comparación del estado del clúster con el DSL
+//! - `load`: Pruebas de carga y de resistencia de los workflows desplegados
+//! - `project`: Manifiesto del proyecto (`kumeo.toml`) compartido por los subcomandos
+//! - `quota`: Cuotas de CPU, memoria y réplicas de cada namespace de la plataforma
+//! - `simulator`: Ejecución de los tests de los workflows sin desplegarlos
+//! - `stats`: Estadísticas de un programa y estimación de su huella en Kubernetes
+//! - `vendor`: Copias locales de recursos remotos para entornos sin red
+//! - `watch`: Regeneración incremental al cambiar las fuentes o las plantillas
 //! - `error`: Tipos de error y manejo de errores
 
 #![warn(missing_docs)]
 
 pub mod ast;
 pub mod codegen;
+pub mod cost;
+pub mod debug;
+pub mod diagnostics;
+pub mod docs;
 pub mod error;
+pub mod formatter;
+pub mod i18n;
+pub mod live;
+pub mod load;
 pub mod logging;
 pub mod parser;
+pub mod project;
+pub mod quota;
 pub mod semantic;
+pub mod simulator;
+pub mod stats;
+pub mod vendor;
+pub mod watch;
 
 // Re-export main functionality
-pub use parser::parse;
+pub use parser::{parse, parse_file};
 pub use crate::ast::*;
 pub use crate::error::{KumeoError, Result};
 pub use crate::semantic::SemanticAnalyzer;
@@ -0,0 +1,353 @@
+//! Drift detection between a deployed cluster and the DSL
+//!
+//! Reads the workloads that the cluster currently runs for a workflow, its
+//! Deployments, the Argo Rollouts of canary agents and the Jobs of batch
+//! workflows, and compares them with
+//! what the compiler would generate, so operators can spot missing agents,
+//! replica drift and image mismatches without adopting a full GitOps setup.
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::process::Command;
+
+use crate::ast::{RolloutStrategy, Workflow, WorkflowMode};
+use crate::codegen::kubernetes::{image_of_agent, BlueGreenSettings};
+use crate::message;
+
+/// Label the generated manifests put on every resource of a workflow
+pub const WORKFLOW_LABEL: &str = "kumeo.io/workflow";
+
+/// Default replica count of a generated agent Deployment
+const DEFAULT_REPLICAS: u32 = 1;
+
+/// Kind of workload an agent runs as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkloadKind {
+    /// An `apps/v1` Deployment
+    Deployment,
+    /// An Argo `Rollout`, for agents rolled out as a canary
+    Rollout,
+    /// A `batch/v1` Job, for the agents of batch workflows
+    Job,
+}
+
+impl WorkloadKind {
+    /// Resource `kubectl get` lists the workloads of this kind with
+    pub fn resource(self) -> &'static str {
+        match self {
+            WorkloadKind::Deployment => "deployments",
+            WorkloadKind::Rollout => "rollouts.argoproj.io",
+            WorkloadKind::Job => "jobs",
+        }
+    }
+
+    /// Kind of the workloads the compiler generates for a workflow's agents
+    pub fn of_workflow(workflow: &Workflow) -> Self {
+        if workflow.mode == WorkflowMode::Batch {
+            return WorkloadKind::Job;
+        }
+        let rollout = workflow.deployment.as_ref().and_then(|deployment| deployment.rollout.as_ref());
+        match rollout {
+            Some(RolloutStrategy::Canary(_)) => WorkloadKind::Rollout,
+            _ => WorkloadKind::Deployment,
+        }
+    }
+}
+
+/// Replicas an agent workload is expected to run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpectedReplicas {
+    /// Exactly this many, from the `replicas` of the deployment
+    Fixed(u32),
+    /// Any count its HorizontalPodAutoscaler may pick, from the `scaling` of the deployment
+    Autoscaled {
+        /// Fewest replicas
+        min: u32,
+        /// Most replicas
+        max: u32,
+    },
+    /// The pods of a Job, which run until the input is consumed and aren't checked
+    ToCompletion,
+}
+
+/// An agent workload as the compiler would generate it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExpectedAgent {
+    /// Workload name (the agent ID)
+    pub name: String,
+    /// Kind of the workload
+    pub kind: WorkloadKind,
+    /// Desired replica count
+    pub replicas: ExpectedReplicas,
+    /// Container image; `None` for the active slot of a blue/green agent,
+    /// which runs whatever version was promoted into it
+    pub image: Option<String>,
+}
+
+/// A workload as currently found in the cluster
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LiveDeployment {
+    /// Workload name
+    pub name: String,
+    /// Kind of the workload
+    pub kind: WorkloadKind,
+    /// Replica count in the workload spec
+    pub replicas: u32,
+    /// Images of the pod template containers
+    pub images: Vec<String>,
+}
+
+/// A divergence between the cluster and the DSL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Drift {
+    /// An agent declared in the DSL has no workload of the expected kind
+    MissingAgent {
+        /// Agent ID
+        agent: String,
+    },
+    /// A workload labelled with the workflow has no matching agent
+    UnexpectedDeployment {
+        /// Deployment name
+        deployment: String,
+    },
+    /// The replica count differs from the DSL
+    ReplicaDrift {
+        /// Agent ID
+        agent: String,
+        /// Replicas the DSL asks for
+        expected: u32,
+        /// Replicas found in the cluster
+        actual: u32,
+    },
+    /// The replica count of an autoscaled agent is outside its bounds
+    ReplicasOutOfRange {
+        /// Agent ID
+        agent: String,
+        /// Fewest replicas the DSL allows
+        min: u32,
+        /// Most replicas the DSL allows
+        max: u32,
+        /// Replicas found in the cluster
+        actual: u32,
+    },
+    /// The agent container runs a different image
+    ImageMismatch {
+        /// Agent ID
+        agent: String,
+        /// Image the DSL generates
+        expected: String,
+        /// Images found in the cluster
+        actual: Vec<String>,
+    },
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Drift::MissingAgent { agent } => {
+                write!(f, "agent '{}' is not deployed", agent)
+            }
+            Drift::UnexpectedDeployment { deployment } => {
+                write!(f, "deployment '{}' is not declared in the DSL", deployment)
+            }
+            Drift::ReplicaDrift { agent, expected, actual } => {
+                write!(f, "agent '{}' runs {} replicas, expected {}", agent, actual, expected)
+            }
+            Drift::ReplicasOutOfRange { agent, min, max, actual } => {
+                write!(f, "agent '{}' runs {} replicas, expected {} to {}", agent, actual, min, max)
+            }
+            Drift::ImageMismatch { agent, expected, actual } => {
+                write!(f, "agent '{}' runs image {}, expected {}", agent, actual.join(", "), expected)
+            }
+        }
+    }
+}
+
+/// Build the agent workloads the compiler generates for a workflow
+///
+/// A blue/green agent runs one Deployment per slot, `<id>-<slot>`: the active
+/// slot of the `kumeo.io/active-slot` annotation at the configured replicas,
+/// and the idle one, holding the generated image, at none. With `scaling`,
+/// the replicas of the running workloads are left to their autoscaler. The
+/// agents of a batch workflow run as Jobs, which neither scale nor idle.
+pub fn expected_agents(workflow: &Workflow, registry: &str, tag: &str) -> Vec<ExpectedAgent> {
+    let kind = WorkloadKind::of_workflow(workflow);
+    let deployment = workflow.deployment.as_ref();
+    let scaling = deployment.and_then(|deployment| deployment.scaling.as_ref());
+    let replicas = match scaling {
+        _ if kind == WorkloadKind::Job => ExpectedReplicas::ToCompletion,
+        Some(scaling) => ExpectedReplicas::Autoscaled { min: scaling.min_replicas, max: scaling.max_replicas },
+        None => ExpectedReplicas::Fixed(
+            deployment.and_then(|deployment| deployment.replicas).unwrap_or(DEFAULT_REPLICAS),
+        ),
+    };
+    // Slot suffix, replicas and whether the workload holds the generated image
+    let slots: Vec<(String, ExpectedReplicas, bool)> = match BlueGreenSettings::for_workflow(workflow) {
+        Some(blue_green) => blue_green
+            .slots
+            .iter()
+            .map(|&slot| {
+                let idle = slot == blue_green.idle;
+                let replicas = if idle { ExpectedReplicas::Fixed(0) } else { replicas };
+                (format!("-{}", slot), replicas, idle)
+            })
+            .collect(),
+        None => vec![(String::new(), replicas, true)],
+    };
+
+    workflow
+        .agents
+        .iter()
+        .filter_map(|agent| Some((agent, agent.id.as_ref()?)))
+        .flat_map(|(agent, id)| {
+            let image = image_of_agent(agent, registry, tag);
+            slots.iter().map(move |(suffix, replicas, generated)| ExpectedAgent {
+                name: format!("{}{}", id, suffix),
+                kind,
+                replicas: *replicas,
+                image: generated.then(|| image.clone()),
+            })
+        })
+        .collect()
+}
+
+/// Kinds of workload to read from the cluster for the expected agents
+///
+/// Deployments are always read so that stray ones labelled with the workflow
+/// are reported; other kinds only when an agent runs as one, since their
+/// resources may not be installed in the cluster.
+pub fn expected_kinds(expected: &[ExpectedAgent]) -> Vec<WorkloadKind> {
+    let mut kinds: BTreeSet<WorkloadKind> = expected.iter().map(|agent| agent.kind).collect();
+    kinds.insert(WorkloadKind::Deployment);
+    kinds.into_iter().collect()
+}
+
+/// Compare the expected agents with the live workloads
+///
+/// A workload matches an agent when both its name and its kind do.
+pub fn diff(expected: &[ExpectedAgent], live: &[LiveDeployment]) -> Vec<Drift> {
+    let live_by_name: BTreeMap<(WorkloadKind, &str), &LiveDeployment> =
+        live.iter().map(|deployment| ((deployment.kind, deployment.name.as_str()), deployment)).collect();
+    let mut drift = Vec::new();
+
+    for agent in expected {
+        let Some(deployment) = live_by_name.get(&(agent.kind, agent.name.as_str())) else {
+            drift.push(Drift::MissingAgent { agent: agent.name.clone() });
+            continue;
+        };
+
+        match agent.replicas {
+            ExpectedReplicas::Fixed(expected) if deployment.replicas != expected => {
+                drift.push(Drift::ReplicaDrift {
+                    agent: agent.name.clone(),
+                    expected,
+                    actual: deployment.replicas,
+                });
+            }
+            ExpectedReplicas::Autoscaled { min, max } if !(min..=max).contains(&deployment.replicas) => {
+                drift.push(Drift::ReplicasOutOfRange {
+                    agent: agent.name.clone(),
+                    min,
+                    max,
+                    actual: deployment.replicas,
+                });
+            }
+            _ => {}
+        }
+
+        if let Some(expected_image) = &agent.image {
+            if !deployment.images.iter().any(|image| image == expected_image) {
+                drift.push(Drift::ImageMismatch {
+                    agent: agent.name.clone(),
+                    expected: expected_image.clone(),
+                    actual: deployment.images.clone(),
+                });
+            }
+        }
+    }
+
+    for deployment in live {
+        if !expected.iter().any(|agent| agent.kind == deployment.kind && agent.name == deployment.name) {
+            drift.push(Drift::UnexpectedDeployment { deployment: deployment.name.clone() });
+        }
+    }
+
+    drift
+}
+
+/// Parse the output of `kubectl get <resource> -o json` for workloads of a kind
+pub fn parse_deployment_list(list: &serde_json::Value, kind: WorkloadKind) -> Result<Vec<LiveDeployment>> {
+    let items = list["items"]
+        .as_array()
+        .ok_or_else(|| anyhow!(message!("Expected a Kubernetes list with an 'items' array")))?;
+
+    items
+        .iter()
+        .map(|item| {
+            let name = item["metadata"]["name"]
+                .as_str()
+                .ok_or_else(|| anyhow!(message!("Workload without metadata.name")))?
+                .to_string();
+            // The API server defaults an omitted replica count to 1
+            let replicas = item["spec"]["replicas"].as_u64().unwrap_or(1) as u32;
+            let images = item["spec"]["template"]["spec"]["containers"]
+                .as_array()
+                .map(|containers| {
+                    containers
+                        .iter()
+                        .filter_map(|container| container["image"].as_str())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            Ok(LiveDeployment { name, kind, replicas, images })
+        })
+        .collect()
+}
+
+/// Read the workloads of the given kinds of a workflow from the cluster through `kubectl`
+pub fn fetch_deployments(
+    workflow_name: &str,
+    namespace: &str,
+    kinds: &[WorkloadKind],
+    kube_context: Option<&str>,
+) -> Result<Vec<LiveDeployment>> {
+    let mut deployments = Vec::new();
+    for &kind in kinds {
+        let mut command = Command::new("kubectl");
+        if let Some(kube_context) = kube_context {
+            command.args(["--context", kube_context]);
+        }
+        command.args([
+            "get",
+            kind.resource(),
+            "--namespace",
+            namespace,
+            "--selector",
+            &format!("{}={}", WORKFLOW_LABEL, workflow_name),
+            "--output",
+            "json",
+        ]);
+
+        let output = command
+            .output()
+            .context(message!("Failed to run kubectl; is it installed and on PATH?"))?;
+        if !output.status.success() {
+            return Err(anyhow!(message!(
+                "kubectl failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let list: serde_json::Value =
+            serde_json::from_slice(&output.stdout).context(message!("kubectl returned invalid JSON"))?;
+        deployments.extend(parse_deployment_list(&list, kind)?);
+    }
+    Ok(deployments)
+}
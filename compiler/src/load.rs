@@ -0,0 +1,545 @@
+//! Load and soak testing of deployed workflows
+//!
+//! `kumeo load` publishes synthetic messages at a target rate on the input
+//! subject of a workflow, listens on the subjects where it emits its results
+//! and reports the throughput reached and the latency percentiles, to
+//! validate the capacity of a deployment before it goes to production.
+//!
+//! Messages come from a sample file or are generated from the input schema
+//! of the first agent. Each one carries a `Kumeo-Load-Id` header; outputs
+//! that keep the header are matched with their message, and the rest with
+//! the oldest message still waiting for an output, which holds for workflows
+//! that emit one result per message in order.
+//!
+//! The broker is reached with a minimal client of the NATS core protocol
+//! over plain TCP, so only `nats://` URLs are supported.
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use serde_json::{json, Map, Value as Json};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::ast::{Schema, Source, Workflow};
+use crate::codegen::nats::ExternalNats;
+use crate::message;
+
+/// Header carrying the sequence number of a load message
+pub const LOAD_ID_HEADER: &str = "Kumeo-Load-Id";
+
+/// Time allowed to connect to the broker and for it to answer a `PING`
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Subjects a load test publishes on and listens to
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadPlan {
+    /// Workflow under test
+    pub workflow: String,
+    /// Subject the messages are published on
+    pub input_subject: String,
+    /// Subjects the results of the workflow are expected on
+    pub output_subjects: Vec<String>,
+    /// Schema of the messages the first agent consumes, if declared
+    #[serde(skip)]
+    pub input_schema: Option<Schema>,
+}
+
+impl LoadPlan {
+    /// Work out the subjects of a workflow
+    ///
+    /// The input is the NATS source of the workflow, or the subject the first
+    /// agent consumes. The outputs are the subjects some agent publishes on
+    /// and no agent consumes, which include the workflow target.
+    pub fn for_workflow(workflow: &Workflow) -> Result<Self> {
+        let topics = workflow.deployed_topics();
+        let input_subject = match &workflow.source {
+            Some(Source::NATS(subject, _)) => subject.clone(),
+            Some(_) => {
+                return Err(anyhow!(message!(
+                    "Workflow '{}' does not read from NATS, load tests publish on a NATS subject",
+                    workflow.name
+                )))
+            }
+            None => topics
+                .first()
+                .and_then(|topics| topics.input.clone())
+                .ok_or_else(|| anyhow!(message!("Workflow '{}' has no input subject", workflow.name)))?,
+        };
+
+        let consumed: BTreeSet<&str> = topics.iter().filter_map(|topics| topics.input.as_deref()).collect();
+        let mut output_subjects = Vec::new();
+        for output in topics.iter().filter_map(|topics| topics.output.as_deref()) {
+            if !consumed.contains(output) && !output_subjects.iter().any(|subject| subject == output) {
+                output_subjects.push(output.to_string());
+            }
+        }
+        if output_subjects.is_empty() {
+            return Err(anyhow!(message!("Workflow '{}' has no output subject to measure latency on", workflow.name)));
+        }
+
+        let input_schema = workflow
+            .agents
+            .iter()
+            .zip(&topics)
+            .find(|(_, topics)| topics.input.as_deref() == Some(input_subject.as_str()))
+            .and_then(|(agent, _)| agent.input_schema().ok().flatten());
+
+        Ok(Self {
+            workflow: workflow.name.clone(),
+            input_subject,
+            output_subjects,
+            input_schema,
+        })
+    }
+}
+
+/// Where the messages of a load test come from
+#[derive(Debug, Clone)]
+pub enum MessageSource {
+    /// Generated from a schema, varying with the sequence number
+    Generated(Option<Schema>),
+    /// Taken in turn from a list of samples
+    Samples(Vec<Json>),
+}
+
+impl MessageSource {
+    /// Read the samples of a file, a JSON array or one JSON value per line
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| message!("Failed to read sample file: {}", path.display()))?;
+        let samples = match serde_json::from_str::<Json>(&content) {
+            Ok(Json::Array(samples)) => samples,
+            Ok(sample) => vec![sample],
+            Err(_) => content
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(index, line)| {
+                    serde_json::from_str(line)
+                        .with_context(|| message!("Invalid JSON on line {} of {}", index + 1, path.display()))
+                })
+                .collect::<Result<_>>()?,
+        };
+        if samples.is_empty() {
+            return Err(anyhow!(message!("Sample file has no messages: {}", path.display())));
+        }
+        Ok(Self::Samples(samples))
+    }
+
+    /// The message with a given sequence number
+    pub fn message(&self, sequence: u64) -> Json {
+        match self {
+            Self::Samples(samples) => samples[(sequence % samples.len() as u64) as usize].clone(),
+            Self::Generated(schema) => generate_message(schema.as_ref(), sequence),
+        }
+    }
+}
+
+/// A message with a value for every required field of a schema
+///
+/// Values change with the sequence number so that messages are not all
+/// identical, which would hide caches and deduplication in the agents.
+fn generate_message(schema: Option<&Schema>, sequence: u64) -> Json {
+    let mut message = Map::new();
+    let Some(schema) = schema else {
+        return json!({ "sequence": sequence });
+    };
+    for (field, field_type) in &schema.fields {
+        let value = match field_type.as_str() {
+            "string" => json!(format!("{}-{}", field, sequence)),
+            "number" => json!(sequence as f64 + 0.5),
+            "integer" => json!(sequence),
+            "boolean" => json!(sequence.is_multiple_of(2)),
+            "object" => json!({}),
+            "array" => json!([]),
+            // Optional fields are left out
+            _ => continue,
+        };
+        message.insert(field.clone(), value);
+    }
+    Json::Object(message)
+}
+
+/// Settings of a load test run
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadSettings {
+    /// Messages published per second
+    pub rate: f64,
+    /// How long messages are published for
+    pub duration: Duration,
+    /// How long outputs are still awaited once publishing stops
+    pub drain: Duration,
+}
+
+impl LoadSettings {
+    /// Number of messages the run publishes
+    pub fn messages(&self) -> u64 {
+        (self.rate * self.duration.as_secs_f64()).round() as u64
+    }
+}
+
+/// Latency distribution of the matched outputs, in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencyStats {
+    /// Fastest output
+    pub min: f64,
+    /// Mean latency
+    pub mean: f64,
+    /// Median latency
+    pub p50: f64,
+    /// 90th percentile
+    pub p90: f64,
+    /// 99th percentile
+    pub p99: f64,
+    /// Slowest output
+    pub max: f64,
+}
+
+impl LatencyStats {
+    /// Summarize latency samples; `None` without samples
+    pub fn from_samples(samples: &[Duration]) -> Option<Self> {
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        let (first, last) = (sorted.first()?, sorted.last()?);
+        let millis = |duration: &Duration| duration.as_secs_f64() * 1000.0;
+        Some(Self {
+            min: millis(first),
+            mean: sorted.iter().map(millis).sum::<f64>() / sorted.len() as f64,
+            p50: millis(&percentile(&sorted, 50.0)),
+            p90: millis(&percentile(&sorted, 90.0)),
+            p99: millis(&percentile(&sorted, 99.0)),
+            max: millis(last),
+        })
+    }
+}
+
+/// Nearest-rank percentile of sorted, non-empty samples
+pub fn percentile(sorted: &[Duration], percent: f64) -> Duration {
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Outcome of a load test run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LoadReport {
+    /// Workflow under test
+    pub workflow: String,
+    /// Subject the messages were published on
+    pub input_subject: String,
+    /// Subjects the outputs were collected on
+    pub output_subjects: Vec<String>,
+    /// Messages per second asked for
+    pub target_rate: f64,
+    /// Messages per second actually published
+    pub achieved_rate: f64,
+    /// Seconds spent publishing
+    pub duration_secs: f64,
+    /// Messages published
+    pub sent: u64,
+    /// Outputs received on the output subjects
+    pub received: u64,
+    /// Messages no output was matched with
+    pub unanswered: u64,
+    /// Outputs received per second, from the first message to the last output
+    pub throughput: f64,
+    /// Latency from publishing a message to receiving its output
+    pub latency: Option<LatencyStats>,
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "workflow {}: {} -> {}", self.workflow, self.input_subject, self.output_subjects.join(", "))?;
+        writeln!(
+            f,
+            "  sent {} messages in {:.1}s ({:.1}/s, target {:.1}/s)",
+            self.sent, self.duration_secs, self.achieved_rate, self.target_rate
+        )?;
+        writeln!(
+            f,
+            "  received {} outputs ({:.1}/s), {} messages unanswered",
+            self.received, self.throughput, self.unanswered
+        )?;
+        match &self.latency {
+            Some(latency) => write!(
+                f,
+                "  latency ms: min {:.1}, mean {:.1}, p50 {:.1}, p90 {:.1}, p99 {:.1}, max {:.1}",
+                latency.min, latency.mean, latency.p50, latency.p90, latency.p99, latency.max
+            ),
+            None => write!(f, "  latency: no outputs received"),
+        }
+    }
+}
+
+/// A message delivered by the broker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    /// Subject it was published on
+    pub subject: String,
+    /// Headers, empty for messages published without them
+    pub headers: HashMap<String, String>,
+    /// Payload
+    pub payload: Vec<u8>,
+}
+
+/// A minimal client of the NATS core protocol
+///
+/// Publishes with headers and subscribes; deliveries arrive on a channel fed
+/// by a task that also answers the keep-alive `PING`s of the server.
+pub struct NatsConnection {
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    deliveries: mpsc::UnboundedReceiver<(Delivery, Instant)>,
+    subscriptions: u64,
+}
+
+impl NatsConnection {
+    /// Connect to the server of a `nats://` URL
+    pub async fn connect(url: &str) -> Result<Self> {
+        let nats = ExternalNats::new(url, None)?;
+        if !url.starts_with("nats://") {
+            return Err(anyhow!(message!("Only nats:// URLs are supported: {}", url)));
+        }
+        let (host, port) = nats.address()?;
+        let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect((host.as_str(), port)))
+            .await
+            .map_err(|_| anyhow!(message!("Timed out connecting to NATS at {}:{}", host, port)))?
+            .with_context(|| message!("Cannot connect to NATS at {}:{}", host, port))?;
+        stream.set_nodelay(true)?;
+        let (reader, writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        let mut greeting = String::new();
+        tokio::time::timeout(CONNECT_TIMEOUT, reader.read_line(&mut greeting))
+            .await
+            .map_err(|_| anyhow!(message!("NATS server did not send its INFO greeting")))??;
+        if !greeting.starts_with("INFO ") {
+            return Err(anyhow!(message!("Not a NATS server, unexpected greeting: {}", greeting.trim())));
+        }
+
+        let connect = json!({
+            "verbose": false,
+            "pedantic": false,
+            "headers": true,
+            "no_responders": false,
+            "name": "kumeo-load",
+            "lang": "rust",
+            "version": env!("CARGO_PKG_VERSION"),
+            "protocol": 1,
+        });
+        let writer = Arc::new(Mutex::new(writer));
+        writer
+            .lock()
+            .await
+            .write_all(format!("CONNECT {}\r\nPING\r\n", connect).as_bytes())
+            .await?;
+
+        // The server answers the PING once it has accepted the connection
+        let mut line = String::new();
+        loop {
+            line.clear();
+            tokio::time::timeout(CONNECT_TIMEOUT, reader.read_line(&mut line))
+                .await
+                .map_err(|_| anyhow!(message!("NATS server did not answer the connection")))??;
+            match line.trim_end() {
+                "PONG" => break,
+                "+OK" | "" => continue,
+                error if error.starts_with("-ERR") => {
+                    return Err(anyhow!(message!("NATS server refused the connection: {}", error)))
+                }
+                other => return Err(anyhow!(message!("Unexpected reply from NATS: {}", other))),
+            }
+        }
+
+        let (sender, deliveries) = mpsc::unbounded_channel();
+        tokio::spawn(read_deliveries(reader, writer.clone(), sender));
+        Ok(Self {
+            writer,
+            deliveries,
+            subscriptions: 0,
+        })
+    }
+
+    /// Subscribe to a subject
+    pub async fn subscribe(&mut self, subject: &str) -> Result<()> {
+        self.subscriptions += 1;
+        let command = format!("SUB {} {}\r\n", subject, self.subscriptions);
+        self.writer.lock().await.write_all(command.as_bytes()).await?;
+        Ok(())
+    }
+
+    /// Publish a message with headers
+    pub async fn publish(&self, subject: &str, headers: &[(&str, String)], payload: &[u8]) -> Result<()> {
+        let mut header_block = String::from("NATS/1.0\r\n");
+        for (name, value) in headers {
+            header_block.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        header_block.push_str("\r\n");
+
+        let mut frame = format!(
+            "HPUB {} {} {}\r\n{}",
+            subject,
+            header_block.len(),
+            header_block.len() + payload.len(),
+            header_block
+        )
+        .into_bytes();
+        frame.extend_from_slice(payload);
+        frame.extend_from_slice(b"\r\n");
+        self.writer.lock().await.write_all(&frame).await?;
+        Ok(())
+    }
+
+    /// Next delivery with the time it arrived, or `None` once the deadline passes
+    pub async fn next_delivery(&mut self, deadline: Instant) -> Option<(Delivery, Instant)> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        tokio::time::timeout(remaining, self.deliveries.recv()).await.ok().flatten()
+    }
+}
+
+/// Parse the frames the server sends until the connection closes
+async fn read_deliveries(
+    mut reader: BufReader<OwnedReadHalf>,
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    sender: mpsc::UnboundedSender<(Delivery, Instant)>,
+) -> Result<()> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("PING") => writer.lock().await.write_all(b"PONG\r\n").await?,
+            Some(kind @ ("MSG" | "HMSG")) => {
+                // MSG <subject> <sid> [reply] <size> and HMSG <subject> <sid> [reply] <header size> <size>
+                let fields: Vec<&str> = parts.collect();
+                let counts = if kind == "MSG" { 1 } else { 2 };
+                if fields.len() < 2 + counts {
+                    return Err(anyhow!(message!("Malformed {} frame from NATS: {}", kind, line.trim_end())));
+                }
+                let sizes: Vec<usize> = fields[fields.len() - counts..]
+                    .iter()
+                    .map(|size| size.parse())
+                    .collect::<std::result::Result<_, _>>()
+                    .with_context(|| message!("Malformed {} frame from NATS: {}", kind, line.trim_end()))?;
+                let (header_size, size) = if kind == "MSG" { (0, sizes[0]) } else { (sizes[0], sizes[1]) };
+
+                let mut body = vec![0; size + 2];
+                reader.read_exact(&mut body).await?;
+                let arrived = Instant::now();
+                body.truncate(size);
+                let payload = body.split_off(header_size.min(size));
+                let delivery = Delivery {
+                    subject: fields[0].to_string(),
+                    headers: parse_headers(&body),
+                    payload,
+                };
+                if sender.send((delivery, arrived)).is_err() {
+                    return Ok(());
+                }
+            }
+            Some(error) if error.starts_with("-ERR") => {
+                return Err(anyhow!(message!("NATS error: {}", line.trim_end())));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parse a `NATS/1.0` header block
+fn parse_headers(block: &[u8]) -> HashMap<String, String> {
+    String::from_utf8_lossy(block)
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// Run a load test against a deployed workflow
+///
+/// Messages are published on a fixed schedule, so a slow broker or network
+/// shows up as an achieved rate below the target rather than as latency.
+pub async fn run(plan: &LoadPlan, messages: &MessageSource, settings: &LoadSettings, url: &str) -> Result<LoadReport> {
+    if !(settings.rate > 0.0 && settings.rate.is_finite()) {
+        return Err(anyhow!(message!("Rate must be a positive number of messages per second")));
+    }
+    let mut connection = NatsConnection::connect(url).await?;
+    for subject in &plan.output_subjects {
+        connection.subscribe(subject).await?;
+    }
+
+    let total = settings.messages().max(1);
+    let interval = Duration::from_secs_f64(1.0 / settings.rate);
+    let started = Instant::now();
+    let mut pending: VecDeque<(u64, Instant)> = VecDeque::new();
+    let mut latencies = Vec::new();
+    let mut received = 0;
+    let mut last_output = started;
+
+    let mut record = |delivery: Delivery, arrived: Instant, pending: &mut VecDeque<(u64, Instant)>| {
+        received += 1;
+        last_output = arrived;
+        let sequence = delivery.headers.get(LOAD_ID_HEADER).and_then(|id| id.parse::<u64>().ok());
+        let index = match sequence {
+            Some(sequence) => pending.iter().position(|(pending, _)| *pending == sequence),
+            None => (!pending.is_empty()).then_some(0),
+        };
+        if let Some((_, sent)) = index.and_then(|index| pending.remove(index)) {
+            latencies.push(arrived.saturating_duration_since(sent));
+        }
+    };
+
+    let mut sent = 0;
+    while sent < total {
+        let due = started + interval.mul_f64(sent as f64);
+        // Collect the outputs that arrive while waiting for the next message
+        while let Some((delivery, arrived)) = connection.next_delivery(due).await {
+            record(delivery, arrived, &mut pending);
+        }
+        let payload = messages.message(sent).to_string();
+        let now = Instant::now();
+        connection
+            .publish(&plan.input_subject, &[(LOAD_ID_HEADER, sent.to_string())], payload.as_bytes())
+            .await
+            .with_context(|| message!("Failed to publish on {}", plan.input_subject))?;
+        pending.push_back((sent, now));
+        sent += 1;
+    }
+    // The last message takes up a whole interval too
+    let end = started + interval.mul_f64(total as f64);
+    while let Some((delivery, arrived)) = connection.next_delivery(end).await {
+        record(delivery, arrived, &mut pending);
+    }
+    let duration = started.elapsed();
+
+    let drained = Instant::now() + settings.drain;
+    while !pending.is_empty() {
+        let Some((delivery, arrived)) = connection.next_delivery(drained).await else {
+            break;
+        };
+        record(delivery, arrived, &mut pending);
+    }
+
+    let elapsed = last_output.saturating_duration_since(started).max(duration).as_secs_f64();
+    Ok(LoadReport {
+        workflow: plan.workflow.clone(),
+        input_subject: plan.input_subject.clone(),
+        output_subjects: plan.output_subjects.clone(),
+        target_rate: settings.rate,
+        achieved_rate: sent as f64 / duration.as_secs_f64().max(f64::EPSILON),
+        duration_secs: duration.as_secs_f64(),
+        sent,
+        received,
+        unanswered: pending.len() as u64,
+        throughput: received as f64 / elapsed.max(f64::EPSILON),
+        latency: LatencyStats::from_samples(&latencies),
+    })
+}
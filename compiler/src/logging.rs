@@ -6,7 +6,6 @@ use tracing_subscriber::{
     EnvFilter,
 };
 use tracing_error::ErrorLayer;
-use tracing_log::LogTracer;
 use tracing_appender::non_blocking::WorkerGuard;
 
 /// Configuración del formato de logs
@@ -36,9 +35,7 @@ pub fn init(app_name: &str, format: LogFormat, file_path: Option<&str>) -> Optio
         env::set_var("RUST_LOG", "info");
     }
 
-    // Integración con crate log
-    LogTracer::init().expect("Failed to set logger");
-
+    // La integración con el crate log la instala `init()` del subscriber
     let env_filter = EnvFilter::from_default_env();
     let mut file_guard = None;
 
@@ -53,7 +50,8 @@ pub fn init(app_name: &str, format: LogFormat, file_path: Option<&str>) -> Optio
                 .with_ansi(true)
                 .with_target(true)
                 .with_span_events(FmtSpan::CLOSE)
-                .with_writer(io::stdout);
+                // La salida estándar queda para la de los comandos, como el JSON de `check`
+                .with_writer(io::stderr);
             
             // Si se solicita salida a archivo, configurarla
             if let Some(path) = file_path {
@@ -84,7 +82,7 @@ pub fn init(app_name: &str, format: LogFormat, file_path: Option<&str>) -> Optio
                 .json()
                 .with_current_span(true)
                 .with_span_list(true)
-                .with_writer(io::stdout);
+                .with_writer(io::stderr);
 
             // Si se solicita salida a archivo, configurarla
             if let Some(path) = file_path {
@@ -1,19 +1,49 @@
 //! Punto de entrada principal para el compilador de Kumeo.
 
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
 use clap::{Parser, Subcommand};
 use kumeo_compiler::{
-    ast::{self, Agent, Argument, Program},
-    codegen,
+    ast::{self, Deployment, Program, Workflow},
+    codegen::{
+        self,
+        cluster::{self, ClusterProgram},
+        nats::ExternalNats,
+        output::OutputManifest,
+        sink::{Change, FsSink, OutputSink, PlanSink},
+        template_manager::{TemplateManager, PROJECT_TEMPLATES},
+    },
+    cost::{CostEstimate, Pricing},
+    debug,
+    diagnostics::{self, codes, Diagnostic, JsonDiagnostic, TextEdit, JSON_SCHEMA_VERSION},
+    docs,
     error::KumeoError,
+    formatter,
+    i18n::{self, Locale, Message, LOCALE_ENV},
+    live,
+    load,
+    message,
     logging::{self, LogFormat},
     parser,
-    semantic::SemanticAnalyzer,
+    project::{Project, MANIFEST_FILE},
+    quota::{self, NamespaceUsage, Quotas},
+    semantic::{catalog::{SchemaCatalog, SCHEMA_CATALOG_FILE}, resources, versioning, SemanticAnalyzer},
+    simulator,
+    stats::{self, ProgramStats},
+    vendor::{self, mirror::{self, MirrorRule}, VendorManifest},
+    watch::{DependencyGraph, Regeneration, SourceWatcher, DEFAULT_DEBOUNCE},
 };
 use tracing::metadata::LevelFilter;
 
+/// Variable de entorno con la URL del NATS externo
+const EXTERNAL_NATS_ENV: &str = "KUMEO_EXTERNAL_NATS";
+
+/// Tiempo máximo para comprobar que el NATS externo responde
+const NATS_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Formatos de salida soportados
 #[derive(Debug, Clone, Copy, clap::ValueEnum)]
 enum OutputFormat {
@@ -25,48 +55,368 @@ enum OutputFormat {
     Yaml,
 }
 
+/// Formatos de salida de `check`
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum CheckFormat {
+    /// Formato legible para humanos
+    Human,
+    /// Formato JSON, con los diagnósticos estructurados según la versión del esquema, sus rangos y correcciones
+    Json,
+    /// Formato YAML, con los diagnósticos estructurados
+    Yaml,
+    /// Log SARIF 2.1.0, para subirlo a herramientas de code scanning
+    Sarif,
+}
+
 /// Comandos disponibles
 #[derive(Debug, Subcommand)]
 enum Commands {
     /// Valida la sintaxis y semántica de un archivo Kumeo
     Check {
-        /// Archivo de entrada a validar
+        /// Archivo de entrada a validar (por defecto la entrada de kumeo.toml)
         #[arg(short, long)]
-        input: PathBuf,
+        input: Option<PathBuf>,
         
         /// Formato de salida
-        #[arg(short, long, value_enum, default_value_t = OutputFormat::Human)]
-        format: OutputFormat,
+        #[arg(short, long, value_enum, default_value_t = CheckFormat::Human)]
+        format: CheckFormat,
+        
+        /// NATS existente que usarán los agentes, para validar su URL
+        #[arg(long, value_name = "URL", env = EXTERNAL_NATS_ENV)]
+        external_nats: Option<String>,
+        
+        /// Secret con las credenciales del NATS externo (claves username/password o token)
+        #[arg(long, value_name = "SECRET", requires = "external_nats")]
+        nats_credentials: Option<String>,
+        
+        /// Comprobar además que el NATS externo responde desde esta máquina
+        #[arg(long, requires = "external_nats")]
+        probe_nats: bool,
+        
+        /// Descargar y validar los recursos que los agentes cargan al arrancar, como las tablas de rutas
+        #[arg(long)]
+        check_resources: bool,
+        
+        /// Reglas de mirror para descargar los recursos (FROM=TO, separadas por comas)
+        #[arg(long = "mirror", value_name = "FROM=TO", env = mirror::MIRRORS_ENV, value_delimiter = ',')]
+        mirrors: Vec<MirrorRule>,
+        
+        /// Tratar los avisos como errores
+        #[arg(long)]
+        deny_warnings: bool,
+        
+        /// Versión anterior del programa; la versión de cada workflow debe subir según sus cambios
+        #[arg(long, value_name = "FILE")]
+        baseline: Option<PathBuf>,
+        
+        /// Archivo de cuotas de la plataforma que deben respetar los workflows de cada namespace (por defecto el de kumeo.toml)
+        #[arg(long, value_name = "FILE")]
+        quotas: Option<PathBuf>,
     },
     
-    /// Formatea un archivo Kumeo
-    Format {
-        /// Archivo de entrada a formatear
+    /// Aplica las correcciones automáticas de los diagnósticos de un archivo Kumeo
+    Fix {
+        /// Archivo de entrada a corregir (por defecto la entrada de kumeo.toml)
         #[arg(short, long)]
-        input: PathBuf,
+        input: Option<PathBuf>,
+        
+        /// Mostrar las correcciones sin modificar los archivos
+        #[arg(long)]
+        dry_run: bool,
+    },
+    
+    /// Formatea archivos Kumeo
+    Format {
+        /// Archivos, directorios (todos sus .kumeo) o globs a formatear (por defecto las entradas de kumeo.toml)
+        #[arg(short, long, num_args = 1..)]
+        input: Vec<PathBuf>,
         
-        /// Archivo de salida (opcional, si no se especifica se sobrescribe el archivo de entrada)
+        /// Archivo de salida (opcional, si no se especifica se sobrescribe el archivo de entrada); solo con un archivo
         #[arg(short, long)]
         output: Option<PathBuf>,
         
-        /// Verificar formato sin modificar el archivo
+        /// Verificar formato sin modificar los archivos, mostrando los cambios como diff
         #[arg(long)]
         check: bool,
     },
     
-    /// Genera código a partir de un archivo Kumeo
+    /// Genera código a partir de uno o varios archivos Kumeo
+    #[command(visible_alias = "build")]
     Generate {
-        /// Archivo de entrada
+        /// Archivos de entrada; con varios se genera un layout de clúster común (por defecto las entradas de kumeo.toml)
+        #[arg(short, long, num_args = 1..)]
+        input: Vec<PathBuf>,
+        
+        /// Directorio de salida (por defecto el de kumeo.toml o ./output)
         #[arg(short, long)]
-        input: PathBuf,
+        output: Option<PathBuf>,
         
-        /// Directorio de salida
-        #[arg(short, long, default_value = "./output")]
-        output: PathBuf,
+        /// Workflow o subworkflow a generar (por defecto todos, cada uno en su directorio)
+        #[arg(short, long)]
+        workflow: Option<String>,
+        
+        /// Namespace de Kubernetes de todos los manifiestos, en lugar del de los bloques `deployment` (por defecto el de kumeo.toml)
+        #[arg(short, long)]
+        namespace: Option<String>,
+        
+        /// Registro de las imágenes de los agentes, en lugar del de los bloques `deployment` (por defecto el de kumeo.toml)
+        #[arg(long)]
+        registry: Option<String>,
+        
+        /// Tag de las imágenes de los agentes, en lugar del de los bloques `deployment` (por defecto el de kumeo.toml)
+        #[arg(long)]
+        tag: Option<String>,
         
         /// Validar el archivo antes de generar el código
         #[arg(long, default_value_t = true)]
         validate: bool,
+        
+        /// Prohibir el acceso a la red: todo recurso remoto debe estar vendorizado
+        #[arg(long)]
+        offline: bool,
+        
+        /// Directorio con los recursos vendorizados (por defecto el de kumeo.toml o ./vendor)
+        #[arg(long)]
+        vendor_dir: Option<PathBuf>,
+        
+        /// Reglas de mirror para recursos remotos (FROM=TO, separadas por comas)
+        #[arg(long = "mirror", value_name = "FROM=TO", env = mirror::MIRRORS_ENV, value_delimiter = ',')]
+        mirrors: Vec<MirrorRule>,
+        
+        /// Archivo de cuotas de la plataforma que deben respetar los workflows de cada namespace (por defecto el de kumeo.toml)
+        #[arg(long, value_name = "FILE")]
+        quotas: Option<PathBuf>,
+        
+        /// Borrar los archivos generados de agentes que ya no existen en el programa
+        #[arg(long)]
+        prune: bool,
+        
+        /// Mostrar los archivos que se crearían o sobrescribirían, con sus cambios, sin escribir nada (con --prune, también los que se borrarían)
+        #[arg(long, conflicts_with = "watch")]
+        dry_run: bool,
+        
+        /// Conectar los agentes a un NATS existente en lugar de desplegar uno
+        #[arg(long, value_name = "URL", env = EXTERNAL_NATS_ENV)]
+        external_nats: Option<String>,
+        
+        /// Secret con las credenciales del NATS externo (claves username/password o token)
+        #[arg(long, value_name = "SECRET", requires = "external_nats")]
+        nats_credentials: Option<String>,
+        
+        /// Seguir vigilando las fuentes y las plantillas y regenerar lo afectado por cada cambio
+        #[arg(long)]
+        watch: bool,
+        
+        /// Generar también una consola web de solo lectura con la topología, el estado de los agentes, las dead letters y las cachés (por defecto la de kumeo.toml)
+        #[arg(long)]
+        console: bool,
+        
+        /// Con --watch, milisegundos sin cambios antes de regenerar
+        #[arg(long, value_name = "MS", default_value_t = DEFAULT_DEBOUNCE.as_millis() as u64, requires = "watch")]
+        debounce: u64,
+    },
+    
+    /// Descarga los recursos remotos de un programa en un directorio local
+    Vendor {
+        /// Archivo de entrada (por defecto la entrada de kumeo.toml)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        
+        /// Directorio donde guardar los recursos (por defecto el de kumeo.toml o ./vendor)
+        #[arg(long)]
+        vendor_dir: Option<PathBuf>,
+        
+        /// Reglas de mirror para recursos remotos (FROM=TO, separadas por comas)
+        #[arg(long = "mirror", value_name = "FROM=TO", env = mirror::MIRRORS_ENV, value_delimiter = ',')]
+        mirrors: Vec<MirrorRule>,
+    },
+    
+    /// Ejecuta en el simulador los bloques `test` de los workflows
+    Test {
+        /// Archivo de entrada (por defecto la entrada de kumeo.toml)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        
+        /// Workflow cuyos tests ejecutar (por defecto todos)
+        #[arg(short, long)]
+        workflow: Option<String>,
+        
+        /// Formato de salida
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+    },
+    
+    /// Mide la capacidad de un workflow desplegado publicando mensajes a un ritmo fijo
+    Load {
+        /// Archivo de entrada (por defecto la entrada de kumeo.toml)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        
+        /// Workflow a probar (obligatorio si el programa tiene varios)
+        #[arg(short, long)]
+        workflow: Option<String>,
+        
+        /// Mensajes por segundo
+        #[arg(short, long, default_value_t = 10.0)]
+        rate: f64,
+        
+        /// Segundos durante los que se publican mensajes
+        #[arg(short, long, default_value_t = 60)]
+        duration: u64,
+        
+        /// Segundos que se siguen esperando resultados al dejar de publicar
+        #[arg(long, default_value_t = 5)]
+        drain: u64,
+        
+        /// Archivo con los mensajes a publicar, un array JSON o un JSON por línea (por defecto se generan a partir del esquema de entrada)
+        #[arg(long, value_name = "FILE")]
+        samples: Option<PathBuf>,
+        
+        /// NATS en el que está desplegado el workflow (por defecto el NATS externo de kumeo.toml o el local)
+        #[arg(long, value_name = "URL", env = EXTERNAL_NATS_ENV)]
+        nats: Option<String>,
+        
+        /// Formato de salida
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+    },
+    
+    /// Recorre paso a paso los mensajes que recibió y publicó un agente, grabados por el runtime en modo depuración
+    Debug {
+        /// Archivo de entrada (por defecto la entrada de kumeo.toml)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        
+        /// Workflow del agente (obligatorio si el programa tiene varios)
+        #[arg(short, long)]
+        workflow: Option<String>,
+        
+        /// Agente cuyos mensajes recorrer
+        #[arg(short, long)]
+        agent: String,
+        
+        /// Directorio del historial que graba el runtime con KUMEO_DEBUG_SUBJECTS
+        #[arg(long, value_name = "DIR", default_value = debug::DEFAULT_STORE)]
+        store: PathBuf,
+        
+        /// NATS en el que reinyectar los mensajes (por defecto el NATS externo de kumeo.toml o el local)
+        #[arg(long, value_name = "URL", env = EXTERNAL_NATS_ENV)]
+        nats: Option<String>,
+        
+        /// Listar los mensajes en lugar de recorrerlos
+        #[arg(long)]
+        list: bool,
+        
+        /// Formato de salida del listado
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+    },
+    
+    /// Compara el estado desplegado en el clúster con lo que generaría el DSL
+    ValidateLive {
+        /// Archivo de entrada (por defecto la entrada de kumeo.toml)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        
+        /// Namespace de Kubernetes a inspeccionar, en lugar del de los bloques `deployment` (por defecto el de kumeo.toml)
+        #[arg(short, long)]
+        namespace: Option<String>,
+        
+        /// Contexto de kubeconfig a usar (por defecto el actual)
+        #[arg(long)]
+        context: Option<String>,
+        
+        /// Workflow a comparar (por defecto todos)
+        #[arg(short, long)]
+        workflow: Option<String>,
+        
+        /// Formato de salida
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+    },
+    
+    /// Clasifica los cambios de cada workflow respecto a una versión anterior del programa
+    Diff {
+        /// Versión anterior del programa
+        #[arg(short, long, value_name = "FILE")]
+        base: PathBuf,
+        
+        /// Archivo de entrada (por defecto la entrada de kumeo.toml)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        
+        /// Formato de salida
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+    },
+    
+    /// Resume el tamaño de un programa y estima su huella en Kubernetes
+    Stats {
+        /// Archivo de entrada (por defecto la entrada de kumeo.toml)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        
+        /// Formato de salida
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+    },
+    
+    /// Estima el coste mensual del despliegue de un programa
+    Cost {
+        /// Archivo de entrada (por defecto la entrada de kumeo.toml)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        
+        /// Archivo YAML con los precios unitarios
+        #[arg(short, long)]
+        pricing: PathBuf,
+        
+        /// Formato de salida
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
+    },
+    
+    /// Genera las páginas del sitio de documentación a partir de los comentarios `///`
+    Docs {
+        /// Archivo de entrada (por defecto la entrada de kumeo.toml)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        
+        /// Directorio donde escribir las páginas
+        #[arg(short, long, default_value = "docs/workflows")]
+        output: PathBuf,
+    },
+    
+    /// Muestra la documentación del workflow o agente en una posición, para los editores
+    Hover {
+        /// Archivo de entrada
+        #[arg(short, long)]
+        input: PathBuf,
+        
+        /// Línea, desde 1
+        #[arg(short, long)]
+        line: usize,
+        
+        /// Columna, desde 1
+        #[arg(short, long)]
+        column: usize,
+    },
+    
+    /// Inspecciona las plantillas del proyecto, del usuario y las incluidas
+    Templates {
+        #[command(subcommand)]
+        command: TemplatesCommand,
+    },
+}
+
+/// Subcomandos de `templates`
+#[derive(Debug, Subcommand)]
+enum TemplatesCommand {
+    /// Lista las plantillas y la capa de la que sale cada una
+    Ls {
+        /// Formato de salida
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
     },
 }
 
@@ -82,6 +432,14 @@ struct Cli {
     #[arg(long, default_value = "auto")]
     log_format: String,
     
+    /// Manifiesto del proyecto (por defecto el kumeo.toml del directorio actual o de sus padres)
+    #[arg(long, global = true, value_name = "PATH")]
+    manifest: Option<PathBuf>,
+    
+    /// Idioma de los diagnósticos (en o es)
+    #[arg(long, global = true, value_name = "LOCALE", env = LOCALE_ENV, default_value_t = Locale::Es)]
+    locale: Locale,
+    
     /// Comando a ejecutar
     #[command(subcommand)]
     command: Commands,
@@ -108,234 +466,1574 @@ async fn main() -> Result<()> {
         _ => LogFormat::Human,
     };
     logging::init("kumeo-compiler", log_format, None);
+    i18n::set_locale(cli.locale);
+    
+    // Cargar el manifiesto del proyecto; los flags tienen prioridad sobre él
+    let project = Project::find(cli.manifest.as_deref()).context(message!("Manifiesto del proyecto inválido"))?;
+    let project = project.as_ref();
+    let deployment = project.map(|project| project.manifest.deployment.clone()).unwrap_or_default();
     
     // Ejecutar el comando correspondiente
     match cli.command {
-        Commands::Check { input, format } => check_command(&input, format).await,
-        Commands::Format { input, output, check } => format_command(&input, output, check).await,
-        Commands::Generate { input, output, validate } => generate_command(&input, &output, validate).await,
+        Commands::Check { input, format, external_nats, nats_credentials, probe_nats, check_resources, mirrors, deny_warnings, baseline, quotas } => {
+            let input = entry_file(input, project)?;
+            let nats = match external_nats {
+                Some(url) => Some((url, nats_credentials, probe_nats)),
+                None => deployment.external_nats.map(|url| (url, deployment.nats_credentials, probe_nats)),
+            };
+            let mirrors = if check_resources { Some(mirrors_of(mirrors, project)?) } else { None };
+            let quotas = quotas_of(quotas, project)?;
+            check_command(&input, format, nats, mirrors.as_deref(), quotas.as_ref(), deny_warnings, baseline.as_deref()).await
+        }
+        Commands::Fix { input, dry_run } => {
+            fix_command(&entry_file(input, project)?, dry_run)
+        }
+        Commands::Format { input, output, check } => {
+            format_command(&entry_files(input, project)?, output, check).await
+        }
+        Commands::Generate {
+            input,
+            output,
+            workflow,
+            namespace,
+            registry,
+            tag,
+            validate,
+            offline,
+            vendor_dir,
+            mirrors,
+            quotas,
+            prune,
+            dry_run,
+            external_nats,
+            nats_credentials,
+            watch,
+            console,
+            debounce,
+        } => {
+            let prune = match (prune, dry_run) {
+                (false, _) => Prune::Off,
+                (true, true) => Prune::DryRun,
+                (true, false) => Prune::Delete,
+            };
+            let external_nats = match external_nats {
+                Some(url) => Some((url, nats_credentials)),
+                None => deployment.external_nats.map(|url| (url, deployment.nats_credentials)),
+            };
+            let external_nats = external_nats
+                .map(|(url, credentials)| ExternalNats::new(&url, credentials.as_deref()))
+                .transpose()
+                .context(message!("NATS externo inválido"))?;
+            let output = output
+                .or_else(|| project.and_then(Project::output_dir))
+                .unwrap_or_else(|| PathBuf::from("./output"));
+            let vendor_dir = vendor_dir_of(vendor_dir, project);
+            let mirrors = mirrors_of(mirrors, project)?;
+            let offline = offline || project.is_some_and(|project| project.manifest.offline);
+            let console = console || project.is_some_and(|project| project.manifest.console);
+            let namespace = namespace.or(deployment.namespace);
+            let registry = registry.or(deployment.registry);
+            let tag = tag.or(deployment.tag);
+            let quotas = quotas_of(quotas, project)?;
+            let load = LoadOptions {
+                validate,
+                offline,
+                vendor_dir: &vendor_dir,
+                mirrors: &mirrors,
+                workflow: workflow.as_deref(),
+                namespace: namespace.as_deref(),
+                registry: registry.as_deref(),
+                tag: tag.as_deref(),
+                quotas: quotas.as_ref(),
+                console,
+            };
+            let inputs = entry_files(input, project)?;
+            if watch {
+                let debounce = Duration::from_millis(debounce);
+                watch_command(&inputs, &output, &load, prune, external_nats.as_ref(), project, debounce).await
+            } else {
+                generate_command(&inputs, &output, &load, prune, external_nats.as_ref(), project, dry_run).await
+            }
+        }
+        Commands::Vendor { input, vendor_dir, mirrors } => {
+            let input = entry_file(input, project)?;
+            vendor_command(&input, &vendor_dir_of(vendor_dir, project), &mirrors_of(mirrors, project)?).await
+        }
+        Commands::Test { input, workflow, format } => {
+            test_command(&entry_file(input, project)?, workflow.as_deref(), format).await
+        }
+        Commands::Load { input, workflow, rate, duration, drain, samples, nats, format } => {
+            let nats = nats
+                .or(deployment.external_nats)
+                .unwrap_or_else(|| format!("nats://localhost:{}", codegen::nats::DEFAULT_NATS_PORT));
+            let settings = load::LoadSettings {
+                rate,
+                duration: Duration::from_secs(duration),
+                drain: Duration::from_secs(drain),
+            };
+            load_command(&entry_file(input, project)?, workflow.as_deref(), &settings, samples.as_deref(), &nats, format).await
+        }
+        Commands::Debug { input, workflow, agent, store, nats, list, format } => {
+            let nats = nats
+                .or(deployment.external_nats)
+                .unwrap_or_else(|| format!("nats://localhost:{}", codegen::nats::DEFAULT_NATS_PORT));
+            debug_command(&entry_file(input, project)?, workflow.as_deref(), &agent, &store, &nats, list, format).await
+        }
+        Commands::ValidateLive { input, namespace, context, workflow, format } => {
+            let input = entry_file(input, project)?;
+            let namespace = namespace.or(deployment.namespace);
+            validate_live_command(
+                &input,
+                namespace.as_deref(),
+                deployment.registry.as_deref(),
+                deployment.tag.as_deref(),
+                context.as_deref(),
+                workflow.as_deref(),
+                format,
+            )
+            .await
+        }
+        Commands::Diff { base, input, format } => {
+            diff_command(&base, &entry_file(input, project)?, format)
+        }
+        Commands::Stats { input, format } => {
+            stats_command(&entry_file(input, project)?, format)
+        }
+        Commands::Cost { input, pricing, format } => {
+            cost_command(&entry_file(input, project)?, &pricing, format)
+        }
+        Commands::Docs { input, output } => {
+            docs_command(&entry_file(input, project)?, &output)
+        }
+        Commands::Hover { input, line, column } => {
+            hover_command(&input, line, column)
+        }
+        Commands::Templates { command: TemplatesCommand::Ls { format } } => {
+            templates_ls_command(&templates_of(project), format)
+        }
+    }
+}
+
+/// Archivos de entrada de un comando: los indicados o, si no, las entradas del proyecto
+fn entry_files(inputs: Vec<PathBuf>, project: Option<&Project>) -> Result<Vec<PathBuf>> {
+    if !inputs.is_empty() {
+        return Ok(inputs);
+    }
+    let entries = project.map(Project::entries).unwrap_or_default();
+    if entries.is_empty() {
+        return Err(anyhow!(message!(
+            "No se indicó ningún archivo de entrada (usa --input o define `entry` en {})",
+            MANIFEST_FILE
+        )));
+    }
+    Ok(entries)
+}
+
+/// Archivo de entrada de un comando que trabaja sobre un solo programa
+fn entry_file(input: Option<PathBuf>, project: Option<&Project>) -> Result<PathBuf> {
+    let mut entries = entry_files(input.into_iter().collect(), project)?;
+    if entries.len() > 1 {
+        return Err(anyhow!(message!(
+            "{} define {} archivos de entrada; indica cuál usar con --input",
+            MANIFEST_FILE,
+            entries.len()
+        )));
+    }
+    Ok(entries.remove(0))
+}
+
+/// Directorio de los recursos vendorizados: el indicado, el del proyecto o el de por defecto
+fn vendor_dir_of(vendor_dir: Option<PathBuf>, project: Option<&Project>) -> PathBuf {
+    vendor_dir
+        .or_else(|| project.and_then(Project::vendor_dir))
+        .unwrap_or_else(|| PathBuf::from(vendor::DEFAULT_VENDOR_DIR))
+}
+
+/// Capas de plantillas: las del proyecto (las de kumeo.toml o, si existe, su
+/// directorio `templates`), las del usuario y las incluidas
+fn templates_of(project: Option<&Project>) -> TemplateManager {
+    let default_dir = Path::new(PROJECT_TEMPLATES);
+    let project_dir = match project.and_then(Project::templates_dir) {
+        Some(dir) => Some(dir),
+        None => Some(project.map_or_else(|| default_dir.to_path_buf(), |project| project.resolve(default_dir))).filter(|dir| dir.is_dir()),
+    };
+    TemplateManager::layered(project_dir)
+}
+
+/// Cuotas de la plataforma: las del archivo indicado o, si no, las del proyecto
+fn quotas_of(quotas: Option<PathBuf>, project: Option<&Project>) -> Result<Option<Quotas>> {
+    quotas
+        .or_else(|| project.and_then(Project::quotas_file))
+        .map(|path| Quotas::from_file(&path))
+        .transpose()
+}
+
+/// Reglas de mirror: las indicadas o, si no, las del proyecto
+fn mirrors_of(mirrors: Vec<MirrorRule>, project: Option<&Project>) -> Result<Vec<MirrorRule>> {
+    match project {
+        Some(project) if mirrors.is_empty() => project.manifest.mirror_rules(),
+        _ => Ok(mirrors),
     }
 }
 
 /// Comando para validar un archivo Kumeo
-async fn check_command(input: &PathBuf, format: OutputFormat) -> Result<()> {
-    // Leer el archivo de entrada
-    let content = std::fs::read_to_string(input)
-        .with_context(|| format!("No se pudo leer el archivo: {}", input.display()))?;
-    
-    // Parsear el contenido
-    let program = parser::parse(&content)
-        .map_err(|e| KumeoError::ParserError {
-            line: 0,
-            column: 0,
-            message: e.to_string(),
-        })?;
-    
-    // Validar el programa
-    let mut analyzer = SemanticAnalyzer::new();
-    let validation_result = analyzer.analyze_program(&program);
+///
+/// `nats` es la URL del NATS externo, el Secret de sus credenciales y si hay
+/// que comprobar que responde. Con `resource_mirrors` se descargan además los
+/// recursos que los agentes cargan al arrancar, a través de esas reglas de
+/// mirror. Con `deny_warnings`, los avisos también hacen fallar la validación.
+async fn check_command(
+    input: &Path,
+    format: CheckFormat,
+    nats: Option<(String, Option<String>, bool)>,
+    resource_mirrors: Option<&[MirrorRule]>,
+    quotas: Option<&Quotas>,
+    deny_warnings: bool,
+    baseline: Option<&Path>,
+) -> Result<()> {
+    // Parsear el archivo y sus imports informando de todos los errores de sintaxis;
+    // el análisis semántico de un programa incompleto daría errores en cascada
+    let parsed = parser::parse_file_recovering(input);
+    let mut diagnostics = if parsed.is_complete() {
+        // Validar el programa contra los esquemas registrados junto a él; los
+        // errores del resultado son los de los diagnósticos
+        let mut analyzer = SemanticAnalyzer::new().with_schema_catalog(SchemaCatalog::load(program_dir(input))?);
+        if let Some(baseline) = baseline {
+            analyzer = analyzer.with_baseline(parser::parse_file(baseline).map_err(KumeoError::from)?);
+        }
+        let _ = analyzer.analyze_program(&parsed.program);
+        analyzer.diagnostics().to_vec()
+    } else {
+        parsed.errors.iter().map(|e| e.diagnostic()).collect()
+    };
     
-    // Mostrar resultados
-    match format {
-        OutputFormat::Human => {
-            match validation_result {
-                Ok(_) => {
-                    println!("✅ El archivo es válido");
-                    Ok(())
-                }
-                Err(e) => {
-                    println!("❌ Se encontraron errores de validación:");
-                    for error in e.to_string().lines() {
-                        println!("  - {}", error);
+    // Validar el NATS externo y, si se pide, comprobar que responde
+    let mut nats_server = None;
+    if let Some((url, credentials, probe)) = nats {
+        match ExternalNats::new(&url, credentials.as_deref()) {
+            Err(e) => diagnostics.push(Diagnostic::error(codes::EXTERNAL_NATS, message!("NATS externo inválido: {}", format!("{:#}", e)))),
+            Ok(nats) if probe => match nats.probe(NATS_PROBE_TIMEOUT) {
+                Ok(server) => {
+                    if server.as_ref().is_some_and(|server| server.auth_required) && nats.credentials_secret.is_none() {
+                        diagnostics.push(Diagnostic::warning(
+                            codes::EXTERNAL_NATS,
+                            message!("El NATS externo {} requiere autenticación y no se indicó --nats-credentials", nats.url),
+                        ).with_help(message!("indica el Secret con las credenciales con --nats-credentials")));
                     }
-                    Err(anyhow!("Validación fallida"))
+                    nats_server = Some(server.map_or_else(|| nats.url.clone(), |server| server.to_string()));
                 }
-            }
+                Err(e) => diagnostics.push(Diagnostic::error(
+                    codes::EXTERNAL_NATS,
+                    message!("El NATS externo no responde: {}", format!("{:#}", e)),
+                )),
+            },
+            Ok(_) => {}
         }
-        OutputFormat::Json => {
-            let errors = if let Err(e) = &validation_result {
-                e.to_string().lines().map(|s| s.to_string()).collect::<Vec<_>>()
+    }
+    
+    // Validar el contenido de los recursos que los agentes cargan al arrancar
+    if let (Some(mirrors), true) = (resource_mirrors, parsed.is_complete()) {
+        diagnostics.extend(resources::check_resources(&parsed.program, program_dir(input), mirrors).await);
+    }
+    
+    // Comprobar que los workflows de cada namespace caben en su cuota, una
+    // vez expandidos como se generarían
+    if let (Some(quotas), true) = (quotas, parsed.is_complete() && !diagnostics.iter().any(Diagnostic::is_error)) {
+        let mut program = parsed.program.clone();
+        resolve_constants(&mut program)?;
+        expand_workflows(&mut program)?;
+        diagnostics.extend(quota::check_quotas(&program.workflows, quotas)?);
+    }
+    
+    // Mostrar resultados: primero los avisos, después los errores
+    diagnostics.sort_by_key(Diagnostic::is_error);
+    if let Some(locale) = i18n::locale() {
+        diagnostics = diagnostics.iter().map(|diagnostic| diagnostic.localized(locale)).collect();
+    }
+    let errors: Vec<String> = diagnostics.iter().filter(|d| d.is_error()).map(ToString::to_string).collect();
+    let warnings: Vec<String> = diagnostics.iter().filter(|d| !d.is_error()).map(|d| d.message.to_string()).collect();
+    let valid = errors.is_empty() && (warnings.is_empty() || !deny_warnings);
+    match format {
+        CheckFormat::Human => {
+            for diagnostic in &diagnostics {
+                println!("{}\n", diagnostic);
+            }
+            if let Some(server) = &nats_server {
+                println!("🔌 {}", message!("NATS externo accesible: {}", server));
+            }
+            if valid {
+                println!("✅ {}", message!("El archivo es válido"));
+            } else if errors.is_empty() {
+                println!("❌ {}", message!("Hay avisos y se indicó --deny-warnings"));
             } else {
-                Vec::new()
-            };
+                println!("❌ {}", message!("Se encontraron errores de validación"));
+            }
+        }
+        CheckFormat::Json | CheckFormat::Yaml => {
+            let diagnostics: Vec<JsonDiagnostic> = diagnostics.iter().map(Diagnostic::to_json).collect();
             let result = serde_json::json!({
-                "valid": validation_result.is_ok(),
-                "errors": errors
+                "version": JSON_SCHEMA_VERSION,
+                "valid": valid,
+                "errors": errors,
+                "warnings": warnings,
+                "diagnostics": diagnostics,
+                "nats_server": nats_server
             });
-            println!("{}", serde_json::to_string_pretty(&result)?);
-            validation_result.map_err(|e| anyhow!(e))
+            match format {
+                CheckFormat::Json => println!("{}", serde_json::to_string_pretty(&result)?),
+                _ => println!("{}", serde_yaml::to_string(&result)?),
+            }
         }
-        OutputFormat::Yaml => {
-            let errors = if let Err(e) = &validation_result {
-                e.to_string().lines().map(|s| s.to_string()).collect::<Vec<_>>()
-            } else {
-                Vec::new()
-            };
-            let result = serde_yaml::to_string(&serde_json::json!({
-                "valid": validation_result.is_ok(),
-                "errors": errors
-            }))?;
-            println!("{}", result);
-            validation_result.map_err(|e| anyhow!(e))
+        CheckFormat::Sarif => {
+            // Las rutas se dan relativas al directorio actual, normalmente la raíz del repositorio
+            let root = std::env::current_dir()?;
+            let root = root.canonicalize().unwrap_or(root);
+            println!("{}", serde_json::to_string_pretty(&diagnostics::sarif(&diagnostics, &root))?);
         }
     }
+    check_outcome(valid)
 }
 
-/// Comando para formatear un archivo Kumeo
-async fn format_command(input: &PathBuf, output: Option<PathBuf>, check: bool) -> Result<()> {
-    // Leer el archivo de entrada
-    let content = std::fs::read_to_string(input)
-        .with_context(|| format!("No se pudo leer el archivo: {}", input.display()))?;
-    
-    // Parsear el contenido
-    let program = parser::parse(&content)
-        .map_err(|e| KumeoError::ParserError {
-            line: 0,
-            column: 0,
-            message: e.to_string(),
-        })?;
-    
-    // Formatear el programa
-    let formatted = format_program(&program);
-    
-    // Verificar si hay cambios
-    if content.trim() == formatted.trim() {
-        println!("✅ El archivo ya está correctamente formateado");
+/// Código de salida de `check` según el resultado de la validación
+fn check_outcome(valid: bool) -> Result<()> {
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!(message!("Validación fallida")))
+    }
+}
+
+/// Comando para aplicar las correcciones automáticas de los diagnósticos
+fn fix_command(input: &Path, dry_run: bool) -> Result<()> {
+    // Un programa con errores de sintaxis no llega al análisis que propone las correcciones
+    let parsed = parser::parse_file_recovering(input);
+    if !parsed.is_complete() {
+        for error in &parsed.errors {
+            println!("{}\n", error.diagnostic());
+        }
+        return Err(anyhow!(message!("No se pueden corregir archivos con errores de sintaxis")));
+    }
+    let mut analyzer = SemanticAnalyzer::new().with_schema_catalog(SchemaCatalog::load(program_dir(input))?);
+    let _ = analyzer.analyze_program(&parsed.program);
+    
+    // Agrupar las ediciones por archivo; los programas importados se corrigen también
+    let locale = i18n::locale().unwrap_or_default();
+    let mut fixes: BTreeMap<PathBuf, Vec<(String, TextEdit)>> = BTreeMap::new();
+    for diagnostic in analyzer.diagnostics() {
+        for fix in diagnostic.fixes(locale) {
+            for edit in fix.edits {
+                let file = edit.file.as_ref().map_or_else(|| input.to_path_buf(), PathBuf::from);
+                fixes.entry(file).or_default().push((fix.title.clone(), edit));
+            }
+        }
+    }
+    if fixes.is_empty() {
+        println!("✅ {}", message!("No hay nada que corregir"));
         return Ok(());
     }
     
-    if check {
-        println!("❌ El archivo necesita ser formateado");
-        return Err(anyhow!("El archivo necesita ser formateado"));
+    for (file, fixes) in fixes {
+        let source = std::fs::read_to_string(&file)
+            .with_context(|| message!("No se pudo leer {}", file.display()))?;
+        let edits: Vec<&TextEdit> = fixes.iter().map(|(_, edit)| edit).collect();
+        let (fixed, applied) = diagnostics::apply_edits(&source, &edits);
+        for (title, edit) in &fixes {
+            println!("🔧 {}:{}:{}: {}", file.display(), edit.range.start.line, edit.range.start.column, title);
+        }
+        if dry_run {
+            println!("📄 {}: {}", file.display(), message!("{} correcciones por aplicar", applied));
+        } else {
+            std::fs::write(&file, fixed).with_context(|| message!("No se pudo escribir {}", file.display()))?;
+            println!("✅ {}: {}", file.display(), message!("{} correcciones aplicadas", applied));
+        }
+    }
+    Ok(())
+}
+
+/// Comando para formatear un archivo Kumeo
+async fn format_command(inputs: &[PathBuf], output: Option<PathBuf>, check: bool) -> Result<()> {
+    // Expandir directorios y globs a los archivos .kumeo que contienen
+    let mut files = Vec::new();
+    for input in inputs {
+        let found = formatter::source_files(input)
+            .with_context(|| message!("No se pudieron listar los archivos de {}", input.display()))?;
+        if found.is_empty() {
+            return Err(anyhow!(message!("No se encontraron archivos .kumeo en {}", input.display())));
+        }
+        files.extend(found);
+    }
+    files.sort();
+    files.dedup();
+    if output.is_some() && files.len() > 1 {
+        return Err(anyhow!(message!("--output solo admite un archivo de entrada, se encontraron {}", files.len())));
+    }
+    
+    // Formatear los archivos en paralelo; el resultado es el contenido original y el formateado
+    let mut tasks = tokio::task::JoinSet::new();
+    for file in files.iter().cloned() {
+        tasks.spawn_blocking(move || {
+            let result = format_file(&file);
+            (file, result)
+        });
     }
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        results.push(joined.context(message!("Falló una tarea de formateo"))?);
+    }
+    results.sort_by(|a, b| a.0.cmp(&b.0));
     
-    // Escribir los cambios
-    let output_path = output.as_ref().unwrap_or(input);
-    std::fs::write(output_path, formatted)
-        .with_context(|| format!("No se pudo escribir en el archivo: {}", output_path.display()))?;
+    let mut changed = 0;
+    let mut failed = 0;
+    for (file, result) in results {
+        let (content, formatted) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("❌ {}: {:#}", file.display(), e);
+                failed += 1;
+                continue;
+            }
+        };
+        if content.trim() == formatted.trim() {
+            continue;
+        }
+        changed += 1;
+        
+        if check {
+            println!("❌ {}", file.display());
+            print!("{}", formatter::diff(&content, &formatted));
+            continue;
+        }
+        
+        // Escribir los cambios
+        let output_path = output.as_ref().unwrap_or(&file);
+        std::fs::write(output_path, formatted)
+            .with_context(|| message!("No se pudo escribir en el archivo: {}", output_path.display()))?;
+        println!("✏️  {}", output_path.display());
+    }
     
-    println!("✅ Archivo formateado correctamente: {}", output_path.display());
+    // Resumen
+    let total = files.len();
+    if check && changed > 0 {
+        println!("❌ {}", message!("{} de {} archivos necesitan ser formateados", changed, total));
+    } else if check {
+        println!("✅ {}", message!("Los {} archivos ya están correctamente formateados", total - failed));
+    } else {
+        println!("✅ {}", message!("{} archivos formateados, {} sin cambios", changed, total - changed - failed));
+    }
+    if failed > 0 {
+        return Err(anyhow!(message!("No se pudieron formatear {} de {} archivos", failed, total)));
+    }
+    if check && changed > 0 {
+        return Err(anyhow!(message!("{} archivos necesitan ser formateados", changed)));
+    }
     Ok(())
 }
 
-/// Comando para generar código a partir de un archivo Kumeo
-async fn generate_command(input: &PathBuf, output: &PathBuf, validate: bool) -> Result<()> {
-    // Leer el archivo de entrada
-    let content = std::fs::read_to_string(input)
-        .with_context(|| format!("No se pudo leer el archivo: {}", input.display()))?;
+/// Formatea un archivo con las opciones del .kumeofmt.toml más cercano;
+/// devuelve el contenido original y el formateado
+fn format_file(file: &Path) -> Result<(String, String)> {
+    let content = std::fs::read_to_string(file)
+        .with_context(|| message!("No se pudo leer el archivo: {}", file.display()))?;
+    
+    // Opciones del .kumeofmt.toml más cercano, o las de por defecto
+    let options = formatter::FormatOptions::for_file(file)
+        .with_context(|| message!("Opciones de formato inválidas para {}", file.display()))?;
     
-    // Parsear el contenido
-    let program = parser::parse(&content)
-        .map_err(|e| KumeoError::ParserError {
-            line: 0,
-            column: 0,
-            message: e.to_string(),
-        })?;
+    // Formatear conservando los comentarios; falla si el programa no se parsea
+    let formatted = formatter::format_with(&content, &options).map_err(KumeoError::from)?;
+    Ok((content, formatted))
+}
+
+/// Comando para generar código a partir de uno o varios archivos Kumeo
+///
+/// Se generan todos los workflows de cada programa, y los subworkflows que
+/// ningún workflow invoca como workflows propios, o solo el elegido con
+/// `--workflow`. Un único workflow se genera directamente en `output`; con
+/// varios, cada uno se genera en `programs/<programa>` (o en
+/// `programs/<programa>/<workflow>` si el programa genera varios) con su
+/// propio namespace y se añade un layout de clúster común (NATS compartido y
+/// kustomization raíz). Con un proyecto, sus plantillas sustituyen a las
+/// incluidas y los agentes solo pueden generarse en los lenguajes de sus targets.
+///
+/// Con `--dry-run` no se escribe nada: se muestran los archivos que se
+/// crearían o sobrescribirían y los cambios de los que ya existen.
+async fn generate_command(
+    inputs: &[PathBuf],
+    output: &Path,
+    load: &LoadOptions<'_>,
+    prune: Prune,
+    external_nats: Option<&ExternalNats>,
+    project: Option<&Project>,
+    dry_run: bool,
+) -> Result<()> {
+    let programs = inputs
+        .iter()
+        .map(|input| load_program(input, load, project))
+        .collect::<Result<Vec<_>>>()?;
+    let templates = templates_of(project);
+    let programs: Vec<&LoadedProgram> = programs.iter().collect();
+    check_selected(&programs, load.workflow)?;
+    check_quotas(&programs, load.quotas)?;
+    if dry_run {
+        let mut plan = PlanSink::new();
+        generate_programs(&programs, output, load, prune, external_nats, &templates, &mut plan)?;
+        return print_plan(&plan, output);
+    }
+    let generated = generate_programs(&programs, output, load, prune, external_nats, &templates, &mut FsSink)?;
+    
+    if generated == 1 {
+        println!("✅ {}", message!("Código generado correctamente en: {}", output.display()));
+    } else {
+        println!(
+            "✅ {}",
+            message!(
+                "{} workflows generados correctamente en: {} (aplica con `kubectl apply -k {}`)",
+                generated,
+                output.display(),
+                output.display()
+            )
+        );
+    }
+    Ok(())
+}
+
+/// Muestra los archivos que escribiría una generación: los nuevos (`+`), los
+/// que cambiarían (`~`, con sus cambios) y los que quedarían igual (`=`)
+fn print_plan(plan: &PlanSink, output: &Path) -> Result<()> {
+    let files = plan.plan()?;
+    println!("🔍 {}", message!("Simulación de la generación en {} (no se ha escrito nada):", output.display()));
+    let (mut created, mut changed, mut unchanged) = (0, 0, 0);
+    for file in &files {
+        let path = file.path.strip_prefix(output).unwrap_or(&file.path).display();
+        match &file.change {
+            Change::Create => {
+                created += 1;
+                println!("  + {}", path);
+            }
+            Change::Overwrite(diff) => {
+                changed += 1;
+                println!("  ~ {}", path);
+                for line in diff.lines() {
+                    println!("      {}", line);
+                }
+            }
+            Change::Unchanged => {
+                unchanged += 1;
+                println!("  = {}", path);
+            }
+        }
+    }
+    println!("{}", message!("{} archivos nuevos, {} modificados y {} sin cambios", created, changed, unchanged));
+    Ok(())
+}
+
+/// Comprueba que el workflow elegido con `--workflow` existe en algún programa
+fn check_selected(programs: &[&LoadedProgram], name: Option<&str>) -> Result<()> {
+    match name {
+        Some(name) if programs.iter().all(|loaded| loaded.program.workflows.is_empty()) => Err(anyhow!(message!(
+            "El workflow {} no está definido en {}",
+            name,
+            programs.iter().map(|loaded| loaded.input.display().to_string()).collect::<Vec<_>>().join(", ")
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Comprueba que los workflows de cada namespace, sumando los de todos los
+/// programas, caben en su cuota
+fn check_quotas(programs: &[&LoadedProgram], quotas: Option<&Quotas>) -> Result<()> {
+    let Some(quotas) = quotas else {
+        return Ok(());
+    };
+    let workflows = programs.iter().flat_map(|loaded| &loaded.program.workflows);
+    let violations: Vec<Message> = quota::namespace_usage(workflows, quotas)?.iter().flat_map(NamespaceUsage::violations).collect();
+    if violations.is_empty() {
+        return Ok(());
+    }
+    Err(anyhow!(message!(
+        "Los workflows exceden las cuotas de la plataforma; no se ha generado nada:\n  {}",
+        Message::join(violations, "\n  ")
+    )))
+}
+
+/// Un workflow a generar y el directorio en el que se genera
+struct GeneratedWorkflow<'a> {
+    /// Programa que lo define
+    loaded: &'a LoadedProgram,
+    /// Workflow expandido
+    workflow: &'a Workflow,
+    /// Directorio de salida
+    dir: PathBuf,
+}
+
+/// Reparte los workflows de los programas en `output`: uno solo
+/// directamente y varios en un layout de clúster común, comprobando que no
+/// se pisan antes de escribir nada
+///
+/// Devuelve los workflows con su directorio y el layout, vacío con un solo workflow.
+fn plan_layout<'a>(programs: &[&'a LoadedProgram], output: &Path) -> Result<(Vec<GeneratedWorkflow<'a>>, Vec<ClusterProgram>)> {
+    let workflows: Vec<(&LoadedProgram, &Workflow)> = programs
+        .iter()
+        .flat_map(|loaded| loaded.program.workflows.iter().map(move |workflow| (*loaded, workflow)))
+        .collect();
+    if let [(loaded, workflow)] = workflows.as_slice() {
+        return Ok((vec![GeneratedWorkflow { loaded, workflow, dir: output.to_path_buf() }], Vec::new()));
+    }
+    
+    let layout: Vec<ClusterProgram> = workflows
+        .iter()
+        .map(|(loaded, workflow)| {
+            let name = cluster::program_name(&loaded.input);
+            if loaded.program.workflows.len() == 1 {
+                ClusterProgram::new(&name, workflow)
+            } else {
+                ClusterProgram::for_workflow(&name, workflow)
+            }
+        })
+        .collect();
+    let conflicts = cluster::conflicts(&layout);
+    if !conflicts.is_empty() {
+        return Err(anyhow!(message!(
+            "Los workflows no pueden compartir el clúster:\n  {}",
+            conflicts.join("\n  ")
+        )));
+    }
+    let generated = workflows
+        .into_iter()
+        .zip(&layout)
+        .map(|((loaded, workflow), member)| GeneratedWorkflow { loaded, workflow, dir: output.join(&member.dir) })
+        .collect();
+    Ok((generated, layout))
+}
+
+/// Genera los workflows de los programas en `output` a través de `sink`;
+/// devuelve cuántos
+fn generate_programs(
+    programs: &[&LoadedProgram],
+    output: &Path,
+    load: &LoadOptions<'_>,
+    prune: Prune,
+    external_nats: Option<&ExternalNats>,
+    templates: &TemplateManager,
+    sink: &mut dyn OutputSink,
+) -> Result<usize> {
+    let (generated, layout) = plan_layout(programs, output)?;
+    for target in &generated {
+        generate_workflow(target, load.vendor_dir, prune, external_nats, templates, sink)?;
+    }
+    for loaded in programs {
+        save_schemas(&loaded.input, &loaded.schemas, sink)?;
+    }
+    if !layout.is_empty() {
+        codegen::generate_cluster(&layout, output, external_nats, templates, sink)?;
+    }
+    if load.console {
+        generate_console(&generated, &layout, output, external_nats, templates, sink)?;
+    }
+    Ok(generated.len())
+}
+
+/// Genera en `<output>/console` la consola web de todos los workflows generados
+///
+/// Sigue el NATS externo si lo hay, el NATS compartido de kumeo-system si los
+/// workflows se despliegan por namespaces, y si no el de los workflows.
+fn generate_console(
+    generated: &[GeneratedWorkflow<'_>],
+    layout: &[ClusterProgram],
+    output: &Path,
+    external_nats: Option<&ExternalNats>,
+    templates: &TemplateManager,
+    sink: &mut dyn OutputSink,
+) -> Result<()> {
+    let workflows: Vec<&Workflow> = generated.iter().map(|target| target.workflow).collect();
+    codegen::generate_console(&workflows, &output.join(codegen::console::CONSOLE_DIR), !layout.is_empty(), external_nats, templates, sink)
+        .context(message!("No se pudo generar la consola web"))
+}
+
+/// Comando para generar código y regenerarlo cada vez que cambian las fuentes
+/// o las plantillas
+///
+/// Tras una generación completa, cada lote de cambios regenera solo lo que
+/// depende de él: los programas cuyos archivos han cambiado o los agentes
+/// afectados por una plantilla. Los errores se muestran sin dejar de vigilar,
+/// y Ctrl+C termina el comando.
+async fn watch_command(
+    inputs: &[PathBuf],
+    output: &Path,
+    load: &LoadOptions<'_>,
+    prune: Prune,
+    external_nats: Option<&ExternalNats>,
+    project: Option<&Project>,
+    debounce: Duration,
+) -> Result<()> {
+    let templates = templates_of(project);
+    let graph = DependencyGraph::new(&templates.dirs());
+    let mut session = WatchSession {
+        inputs,
+        output,
+        load,
+        prune,
+        external_nats,
+        project,
+        templates,
+        graph,
+        programs: BTreeMap::new(),
+    };
+    let mut watcher = SourceWatcher::new()?;
+    
+    let everything = inputs.iter().map(|input| (input.clone(), Regeneration::Full)).collect();
+    session.rebuild(everything);
+    watcher.watch(&session.graph)?;
+    
+    loop {
+        let changed = tokio::select! {
+            changed = watcher.changes(debounce) => changed,
+            _ = tokio::signal::ctrl_c() => None,
+        };
+        let Some(changed) = changed else {
+            return Ok(());
+        };
+        let affected = session.graph.affected(&changed);
+        if affected.is_empty() {
+            continue;
+        }
+        for file in &changed {
+            println!("🔄 {}", message!("Cambio en {}", file.display()));
+        }
+        session.rebuild(affected);
+        watcher.watch(&session.graph)?;
+    }
+}
+
+/// Estado del modo watch: los programas cargados y de qué dependen
+struct WatchSession<'a> {
+    inputs: &'a [PathBuf],
+    output: &'a Path,
+    load: &'a LoadOptions<'a>,
+    prune: Prune,
+    external_nats: Option<&'a ExternalNats>,
+    project: Option<&'a Project>,
+    templates: TemplateManager,
+    graph: DependencyGraph,
+    /// Programas cargados correctamente, por archivo de entrada
+    programs: BTreeMap<PathBuf, LoadedProgram>,
+}
+
+impl WatchSession<'_> {
+    /// Regenera lo afectado y muestra el resultado
+    fn rebuild(&mut self, affected: BTreeMap<PathBuf, Regeneration>) {
+        let start = std::time::Instant::now();
+        match self.regenerate(affected) {
+            Ok(()) => println!("✅ {}", message!("Regenerado en {} ms; esperando cambios", start.elapsed().as_millis())),
+            Err(e) => println!("❌ {:#}", e),
+        }
+    }
+    
+    /// Recarga los programas afectados por completo y regenera los agentes
+    /// afectados por una plantilla
+    fn regenerate(&mut self, affected: BTreeMap<PathBuf, Regeneration>) -> Result<()> {
+        let mut full = Vec::new();
+        for (input, regeneration) in &affected {
+            if *regeneration == Regeneration::Full {
+                if let Err(e) = self.reload(input) {
+                    println!("❌ {:#}", e);
+                }
+                full.push(input.as_path());
+            }
+        }
+        
+        let loaded = self.inputs.iter().map(|input| self.programs.get(input)).collect::<Option<Vec<_>>>();
+        let Some(loaded) = loaded else {
+            return Err(anyhow!(message!("Hay programas con errores; se regenerarán cuando cambien")));
+        };
+        check_selected(&loaded, self.load.workflow)?;
+        check_quotas(&loaded, self.load.quotas)?;
+        let templates = &self.templates;
+        let (generated, layout) = plan_layout(&loaded, self.output)?;
+        for target in &generated {
+            let input = target.loaded.input.as_path();
+            if full.contains(&input) {
+                generate_workflow(target, self.load.vendor_dir, self.prune, self.external_nats, templates, &mut FsSink)?;
+            } else if let Some(Regeneration::Agents(agents)) = affected.get(input) {
+                let ids: Vec<&str> = target
+                    .workflow
+                    .agents
+                    .iter()
+                    .filter_map(|agent| agent.id.as_deref())
+                    .filter(|id| agents.contains(*id))
+                    .collect();
+                if ids.is_empty() {
+                    continue;
+                }
+                println!("🔄 {}", message!("Regenerando {} de {}", ids.join(", "), target.workflow.name));
+                let mut outputs = OutputManifest::load(&target.dir)?;
+                codegen::generate_agents(target.workflow, agents, &target.dir, self.external_nats, templates, &mut FsSink)?;
+                outputs.record(target.workflow, &target.dir, &FsSink)?;
+                outputs.save(&target.dir, &mut FsSink)?;
+            }
+        }
+        for loaded in loaded.iter().filter(|loaded| full.contains(&loaded.input.as_path())) {
+            save_schemas(&loaded.input, &loaded.schemas, &mut FsSink)?;
+        }
+        if !full.is_empty() && !layout.is_empty() {
+            codegen::generate_cluster(&layout, self.output, self.external_nats, templates, &mut FsSink)?;
+        }
+        if !full.is_empty() && self.load.console {
+            generate_console(&generated, &layout, self.output, self.external_nats, templates, &mut FsSink)?;
+        }
+        Ok(())
+    }
+    
+    /// Vuelve a cargar un programa y registra los archivos de los que depende
+    fn reload(&mut self, input: &Path) -> Result<()> {
+        match load_program(input, self.load, self.project) {
+            Ok(loaded) => {
+                self.graph.record_program(input, loaded.sources.clone(), &loaded.program);
+                self.programs.insert(input.to_path_buf(), loaded);
+                Ok(())
+            }
+            Err(e) => {
+                self.graph.record_failed(input);
+                self.programs.remove(input);
+                Err(e.context(message!("Error en {}", input.display())))
+            }
+        }
+    }
+}
+
+/// Comprueba que los agentes de los workflows generados se generan en
+/// lenguajes de los targets del proyecto
+fn check_targets(program: &Program, project: &Project) -> Result<()> {
+    for agent in program.workflows.iter().flat_map(|workflow| &workflow.agents) {
+        let language = codegen::agent::agent_language(agent);
+        if !project.manifest.targets_language(language) {
+            return Err(anyhow!(message!(
+                "El agente {} ({}) se genera en {}, que no está entre los targets de {}",
+                agent.id.clone().map_or_else(|| message!("<sin id>"), Message::Text),
+                format!("{:?}", agent.agent_type),
+                language,
+                MANIFEST_FILE
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Parsea, valida y prepara un programa para la generación
+///
+/// Devuelve el programa con las constantes sustituidas y los recursos
+/// remotos redirigidos a sus copias vendorizadas o mirrors, y el catálogo de
+/// esquemas con los del programa registrados. Con `--workflow`, el programa
+/// conserva solo ese workflow, si lo define. Con un proyecto, comprueba
+/// además sus targets.
+fn load_program(input: &Path, load: &LoadOptions<'_>, project: Option<&Project>) -> Result<LoadedProgram> {
+    // Parsear el archivo y sus imports
+    let (mut program, sources) = parser::parse_file_with_sources(input).map_err(KumeoError::from)?;
     
     // Validar el programa si es necesario
-    if validate {
-        let mut analyzer = SemanticAnalyzer::new();
+    let mut schemas = SchemaCatalog::load(program_dir(input))?;
+    if load.validate {
+        let mut analyzer = SemanticAnalyzer::new().with_schema_catalog(schemas.clone());
         analyzer.analyze_program(&program)?;
     }
     
+    resolve_constants(&mut program)?;
+    schemas.record(&program);
+    
+    // Apuntar los recursos vendorizados a sus copias locales
+    let manifest = VendorManifest::load(load.vendor_dir)?;
+    let missing = vendor::rewrite(&mut program, &manifest);
+    if load.offline && !missing.is_empty() {
+        return Err(anyhow!(message!(
+            "Modo offline: hay recursos remotos sin vendorizar (ejecuta `kumeo vendor`):\n  {}",
+            missing.into_iter().collect::<Vec<_>>().join("\n  ")
+        )));
+    }
+    
+    // Redirigir el resto de recursos remotos a sus mirrors
+    for uri in mirror::rewrite(&mut program, load.mirrors) {
+        tracing::debug!("Recurso redirigido a su mirror: {}", uri);
+    }
+    
+    expand_workflows(&mut program)?;
+    if program.workflows.is_empty() {
+        return Err(anyhow!(message!("No workflows found in the program: {}", input.display())));
+    }
+    if let Some(name) = load.workflow {
+        program.workflows.retain(|workflow| workflow.name == name);
+    }
+    override_deployments(&mut program, load)?;
+    if let Some(project) = project {
+        check_targets(&program, project)?;
+    }
+    Ok(LoadedProgram { input: input.to_path_buf(), program, manifest, schemas, sources })
+}
+
+/// Aplica a los bloques `deployment` de todos los workflows el namespace, el
+/// registro y el tag dados en la línea de comandos o en kumeo.toml
+fn override_deployments(program: &mut Program, load: &LoadOptions<'_>) -> Result<()> {
+    if let Some(namespace) = load.namespace {
+        ast::validate_namespace(namespace).map_err(|e| anyhow!(message!("Namespace inválido: {}", e)))?;
+    }
+    if let Some(registry) = load.registry {
+        ast::validate_registry(registry).map_err(|e| anyhow!(message!("Registro inválido: {}", e)))?;
+    }
+    if let Some(tag) = load.tag {
+        ast::validate_image_tag(tag).map_err(|e| anyhow!(message!("Tag inválido: {}", e)))?;
+    }
+    if load.namespace.is_none() && load.registry.is_none() && load.tag.is_none() {
+        return Ok(());
+    }
+    for workflow in &mut program.workflows {
+        let name = workflow.name.clone();
+        let deployment = workflow.deployment.get_or_insert_with(|| Deployment { name, ..Default::default() });
+        let replace = |setting: &mut Option<String>, value: Option<&str>| {
+            if let Some(value) = value {
+                *setting = Some(value.to_string());
+            }
+        };
+        replace(&mut deployment.namespace, load.namespace);
+        replace(&mut deployment.registry, load.registry);
+        replace(&mut deployment.tag, load.tag);
+    }
+    Ok(())
+}
+
+/// Un programa preparado para la generación
+struct LoadedProgram {
+    /// Archivo de entrada
+    input: PathBuf,
+    /// Programa con los workflows expandidos
+    program: Program,
+    /// Manifiesto de los recursos vendorizados
+    manifest: VendorManifest,
+    /// Catálogo de esquemas con los del programa
+    schemas: SchemaCatalog,
+    /// Archivos de los que se ha parseado, canónicos
+    sources: HashSet<PathBuf>,
+}
+
+/// Directorio de un programa, donde se guarda su catálogo de esquemas
+fn program_dir(input: &Path) -> &Path {
+    input.parent().unwrap_or(Path::new("."))
+}
+
+/// Guarda el catálogo de esquemas junto al programa generado
+///
+/// Los programas sin esquemas de mensajes no crean el catálogo.
+fn save_schemas(input: &Path, schemas: &SchemaCatalog, sink: &mut dyn OutputSink) -> Result<()> {
+    if schemas.has_schemas() {
+        sink.write(&program_dir(input).join(SCHEMA_CATALOG_FILE), schemas.to_json()?.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Genera el código de un workflow en su directorio a través de `sink`, con
+/// las plantillas resueltas por las capas de `templates`
+fn generate_workflow(
+    target: &GeneratedWorkflow<'_>,
+    vendor_dir: &Path,
+    prune: Prune,
+    external_nats: Option<&ExternalNats>,
+    templates: &TemplateManager,
+    sink: &mut dyn OutputSink,
+) -> Result<()> {
+    let (workflow, output) = (target.workflow, target.dir.as_path());
     // Crear el directorio de salida si no existe
-    if !output.exists() {
-        std::fs::create_dir_all(output)
-            .with_context(|| format!("No se pudo crear el directorio: {}", output.display()))?;
+    if !sink.exists(output) {
+        sink.create_dir(output)
+            .with_context(|| message!("No se pudo crear el directorio: {}", output.display()))?;
     }
     
     // Generar el código
-    // TODO: Handle multiple workflows or select the first one
-    if let Some(workflow) = program.workflows.first() {
-        codegen::generate_workflow(workflow, output)?;
+    let mut outputs = OutputManifest::load(output)?;
+    codegen::generate_workflow(workflow, output, external_nats, templates, sink)?;
+    outputs.record(workflow, output, sink)?;
+    
+    // Archivos de agentes eliminados del programa
+    let orphans = outputs.orphans(workflow);
+    if !orphans.is_empty() {
+        if prune == Prune::DryRun {
+            println!("🔍 {}", message!("Archivos que --prune borraría:"));
+            for file in orphans.values().flat_map(|files| files.iter()) {
+                println!("  {}", output.join(file).display());
+            }
+        } else if prune == Prune::Delete {
+            let agents: Vec<String> = orphans.keys().map(|agent| agent.to_string()).collect();
+            let deleted = outputs.prune(workflow, output)?;
+            println!("🗑️  {}", message!("{} archivos borrados de agentes eliminados: {}", deleted.len(), agents.join(", ")));
+            for agent in &agents {
+                println!("  {}", message!("Sus recursos siguen en el clúster: kubectl delete all -l app={}", agent));
+            }
+        } else {
+            println!("⚠️  {}", message!("Agentes eliminados del programa con archivos generados (usa --prune para borrarlos):"));
+            for (agent, files) in &orphans {
+                let directory = output.join(codegen::output::agent_dir(agent));
+                println!("  {} ({})", agent, message!("{} archivos en {}", files.len(), directory.display()));
+            }
+        }
+    }
+    outputs.save(output, sink)?;
+    
+    // Incluir las copias vendorizadas en el proyecto generado
+    vendor::copy_into(&target.loaded.manifest, vendor_dir, output, sink)?;
+    
+    Ok(())
+}
+
+/// Sustituye las invocaciones `use` de cada workflow por los agentes del
+/// subworkflow, resuelve los topics `input`/`output` entre agentes y el
+/// esquema de los mensajes que consume cada uno
+///
+/// Los subworkflows que ningún workflow invoca se añaden como workflows propios.
+fn expand_workflows(program: &mut Program) -> Result<()> {
+    let schemas = program.topic_schemas();
+    let standalone = codegen::subworkflow::standalone(program);
+    program.workflows.extend(standalone);
+    for workflow in &mut program.workflows {
+        let expanded = codegen::subworkflow::expand(workflow, &program.subworkflows)?;
+        let wired = codegen::topics::wire(&expanded)?;
+        *workflow = codegen::contracts::attach(&wired, &schemas);
+    }
+    Ok(())
+}
+
+/// Cómo cargar y generar los programas
+struct LoadOptions<'a> {
+    /// Validar el programa antes de generar
+    validate: bool,
+    /// Exigir que todo recurso remoto esté vendorizado
+    offline: bool,
+    /// Directorio con los recursos vendorizados
+    vendor_dir: &'a Path,
+    /// Reglas de mirror para recursos remotos
+    mirrors: &'a [MirrorRule],
+    /// Generar solo el workflow con este nombre
+    workflow: Option<&'a str>,
+    /// Namespace de todos los workflows, en lugar del de sus bloques `deployment`
+    namespace: Option<&'a str>,
+    /// Registro de las imágenes de todos los workflows, en lugar del de sus bloques `deployment`
+    registry: Option<&'a str>,
+    /// Tag de las imágenes de todos los workflows, en lugar del de sus bloques `deployment`
+    tag: Option<&'a str>,
+    /// Cuotas de la plataforma que deben respetar los workflows de cada namespace
+    quotas: Option<&'a Quotas>,
+    /// Generar también la consola web de los workflows
+    console: bool,
+}
+
+/// Qué hacer con los archivos de agentes eliminados del programa
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prune {
+    /// Solo avisar
+    Off,
+    /// Listar los archivos que se borrarían
+    DryRun,
+    /// Borrarlos
+    Delete,
+}
+
+/// Sustituye las referencias `$NOMBRE` por los valores de las constantes e
+/// interpola las referencias `{NOMBRE}` en los textos
+fn resolve_constants(program: &mut Program) -> Result<()> {
+    program
+        .substitute_constants()
+        .map_err(|name| anyhow!(message!("Constante no definida: ${}", name)))?;
+    program
+        .interpolate_constants()
+        .map_err(|e| anyhow!(message!("Interpolación inválida: {}", e)))
+}
+
+/// Comando para descargar los recursos remotos de un programa
+async fn vendor_command(input: &Path, vendor_dir: &Path, mirrors: &[MirrorRule]) -> Result<()> {
+    // Parsear el archivo y sus imports
+    let mut program = parser::parse_file(input).map_err(KumeoError::from)?;
+    resolve_constants(&mut program)?;
+    
+    let manifest = vendor::vendor(&program, vendor_dir, mirrors).await?;
+    println!("✅ {}", message!("{} recursos vendorizados en: {}", manifest.resources.len(), vendor_dir.display()));
+    Ok(())
+}
+
+/// Comando para ejecutar los tests de los workflows en el simulador
+///
+/// Los workflows se simulan tal como se generan: validados, con las
+/// constantes sustituidas y los subworkflows y topics resueltos.
+async fn test_command(input: &Path, workflow_name: Option<&str>, format: OutputFormat) -> Result<()> {
+    // Parsear y validar el archivo y sus imports
+    let mut program = parser::parse_file(input).map_err(KumeoError::from)?;
+    let mut analyzer = SemanticAnalyzer::new().with_schema_catalog(SchemaCatalog::load(program_dir(input))?);
+    analyzer.analyze_program(&program)?;
+    resolve_constants(&mut program)?;
+    expand_workflows(&mut program)?;
+    
+    // Ejecutar los tests de cada workflow
+    let mut reports = Vec::new();
+    for workflow in program.workflows.iter().filter(|w| workflow_name.is_none_or(|name| w.name == name)) {
+        reports.extend(simulator::run(workflow)?);
+    }
+    if reports.is_empty() {
+        return Err(anyhow!(message!("No se encontraron tests que ejecutar")));
+    }
+    let failed = reports.iter().filter(|report| !report.passed()).count();
+    
+    // Mostrar resultados
+    match format {
+        OutputFormat::Human => {
+            for report in &reports {
+                if report.passed() {
+                    println!("✅ {}: {}", report.workflow, report.name);
+                } else {
+                    println!("❌ {}: {}", report.workflow, report.name);
+                    for failure in &report.failures {
+                        println!("  - {}", failure);
+                    }
+                }
+            }
+            println!("{}", message!("{} tests, {} fallidos", reports.len(), failed));
+        }
+        OutputFormat::Json | OutputFormat::Yaml => {
+            let result = serde_json::json!({
+                "passed": failed == 0,
+                "tests": reports.iter()
+                    .map(|report| serde_json::json!({
+                        "workflow": report.workflow,
+                        "name": report.name,
+                        "failures": report.failures,
+                    }))
+                    .collect::<Vec<_>>(),
+            });
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&result)?),
+                _ => println!("{}", serde_yaml::to_string(&result)?),
+            }
+        }
+    }
+    
+    if failed == 0 {
+        Ok(())
     } else {
-        return Err(anyhow!("No workflows found in the program"));
+        Err(anyhow!(message!("{} tests fallidos", failed)))
+    }
+}
+
+/// Comando para las pruebas de carga de un workflow desplegado
+///
+/// Publica mensajes al ritmo pedido en el subject de entrada del workflow y
+/// mide la latencia hasta que aparecen sus resultados en los de salida.
+async fn load_command(
+    input: &Path,
+    workflow_name: Option<&str>,
+    settings: &load::LoadSettings,
+    samples: Option<&Path>,
+    nats: &str,
+    format: OutputFormat,
+) -> Result<()> {
+    // Parsear el archivo y resolver los topics como al generar
+    let mut program = parser::parse_file(input).map_err(KumeoError::from)?;
+    resolve_constants(&mut program)?;
+    expand_workflows(&mut program)?;
+    
+    let workflow = match workflow_name {
+        Some(name) => program.workflows.iter()
+            .find(|w| w.name == name)
+            .ok_or_else(|| anyhow!(message!("No se encontró el workflow '{}'", name)))?,
+        None => match program.workflows.as_slice() {
+            [workflow] => workflow,
+            [] => return Err(anyhow!(message!("No se encontraron workflows que probar"))),
+            _ => return Err(anyhow!(message!("El programa tiene varios workflows, indica cuál probar con --workflow"))),
+        },
+    };
+    let plan = load::LoadPlan::for_workflow(workflow)?;
+    let messages = match samples {
+        Some(path) => load::MessageSource::from_file(path)?,
+        None => load::MessageSource::Generated(plan.input_schema.clone()),
+    };
+    
+    if matches!(format, OutputFormat::Human) {
+        println!(
+            "{}",
+            message!(
+                "Publicando {} mensajes en {} ({}/s) desde {}...",
+                settings.messages(),
+                plan.input_subject,
+                settings.rate,
+                nats
+            )
+        );
     }
+    let report = load::run(&plan, &messages, settings, nats).await?;
     
-    println!("✅ Código generado correctamente en: {}", output.display());
+    // Mostrar resultados
+    match format {
+        OutputFormat::Human => println!("{}", report),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&report)?),
+    }
+    
+    if report.received == 0 {
+        return Err(anyhow!(message!("No llegó ningún resultado a {}", plan.output_subjects.join(", "))));
+    }
     Ok(())
 }
 
-/// Formatea un programa en una cadena de texto
-fn format_program(program: &Program) -> String {
-    let mut result = String::new();
+/// Comando para recorrer el historial de mensajes de un agente
+///
+/// Sin terminal, o con --list, se listan los mensajes en lugar de recorrerlos.
+async fn debug_command(
+    input: &Path,
+    workflow_name: Option<&str>,
+    agent: &str,
+    store: &Path,
+    nats: &str,
+    list: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    // Parsear el archivo y resolver los topics como al generar
+    let mut program = parser::parse_file(input).map_err(KumeoError::from)?;
+    resolve_constants(&mut program)?;
+    expand_workflows(&mut program)?;
     
-    // Formatear workflows
-    for workflow in &program.workflows {
-        result.push_str(&format!("workflow {} {{\n", workflow.name));
-        
-        // Agregar fuente si existe
-        if let Some(source) = &workflow.source {
-            result.push_str(&format!("  source: {}\n", format_source(source)));
+    let workflow = match workflow_name {
+        Some(name) => program.workflows.iter()
+            .find(|w| w.name == name)
+            .ok_or_else(|| anyhow!(message!("No se encontró el workflow '{}'", name)))?,
+        None => match program.workflows.as_slice() {
+            [workflow] => workflow,
+            [] => return Err(anyhow!(message!("No se encontraron workflows que depurar"))),
+            _ => return Err(anyhow!(message!("El programa tiene varios workflows, indica el del agente con --workflow"))),
+        },
+    };
+    let trace = debug::Trace::load(store, workflow, agent)?;
+    
+    let interactive = !list && matches!(format, OutputFormat::Human) && console::Term::stdout().is_term();
+    if interactive {
+        return debug::run_tui(&mut debug::Session::new(trace), nats).await;
+    }
+    match format {
+        OutputFormat::Human => {
+            println!(
+                "{}",
+                message!("{} mensajes de {} ({}) en {}", trace.records.len(), trace.agent, trace.workflow, store.display())
+            );
+            for (index, record) in trace.records.iter().enumerate() {
+                let arrow = match record.direction {
+                    debug::Direction::In => "<-",
+                    debug::Direction::Out => "->",
+                };
+                println!("  {:>4} {} {} {} {}", index + 1, record.time, arrow, record.subject, record.payload);
+            }
         }
-        
-        // Agregar destino si existe
-        if let Some(target) = &workflow.target {
-            result.push_str(&format!("  target: {}\n", format_target(target)));
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&trace)?),
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&trace)?),
+    }
+    Ok(())
+}
+
+/// Comando para detectar divergencias entre el clúster y el DSL
+///
+/// El namespace, las imágenes esperadas y su registro y tag siguen los de
+/// kumeo.toml, como al generar, o los del bloque `deployment` de cada workflow.
+async fn validate_live_command(
+    input: &Path,
+    namespace: Option<&str>,
+    registry: Option<&str>,
+    tag: Option<&str>,
+    kube_context: Option<&str>,
+    workflow_name: Option<&str>,
+    format: OutputFormat,
+) -> Result<()> {
+    // Parsear el archivo y sus imports
+    let mut program = parser::parse_file(input).map_err(KumeoError::from)?;
+    resolve_constants(&mut program)?;
+    expand_workflows(&mut program)?;
+    
+    let workflows: Vec<_> = program.workflows.iter()
+        .filter(|w| workflow_name.is_none_or(|name| w.name == name))
+        .collect();
+    if workflows.is_empty() {
+        return Err(anyhow!(message!("No se encontraron workflows para comparar")));
+    }
+    
+    // Calcular las divergencias de cada workflow
+    let mut report = Vec::new();
+    for workflow in workflows {
+        let expected = live::expected_agents(
+            workflow,
+            registry.unwrap_or(codegen::kubernetes::workflow_registry(workflow)),
+            tag.unwrap_or(codegen::kubernetes::workflow_tag(workflow)),
+        );
+        let namespace = namespace.unwrap_or(codegen::kubernetes::workflow_namespace(workflow));
+        let kinds = live::expected_kinds(&expected);
+        let deployed = live::fetch_deployments(&workflow.name, namespace, &kinds, kube_context)?;
+        report.push((workflow.name.clone(), namespace, live::diff(&expected, &deployed)));
+    }
+    let in_sync = report.iter().all(|(_, _, drift)| drift.is_empty());
+    
+    // Mostrar resultados
+    match format {
+        OutputFormat::Human => {
+            for (workflow, _, drift) in &report {
+                if drift.is_empty() {
+                    println!("✅ {}: {}", workflow, message!("el clúster coincide con el DSL"));
+                } else {
+                    println!("❌ {}: {}", workflow, message!("se encontraron {} divergencias:", drift.len()));
+                    for item in drift {
+                        println!("  - {}", item);
+                    }
+                }
+            }
         }
-        
-        // Agregar agentes
-        if !workflow.agents.is_empty() {
-            result.push_str("  agents: [\n");
-            for agent in &workflow.agents {
-                result.push_str(&format!("    {}(\n", format_agent(agent)));
+        OutputFormat::Json | OutputFormat::Yaml => {
+            let result = serde_json::json!({
+                "in_sync": in_sync,
+                "workflows": report.iter()
+                    .map(|(workflow, namespace, drift)| serde_json::json!({
+                        "name": workflow,
+                        "namespace": namespace,
+                        "drift": drift,
+                    }))
+                    .collect::<Vec<_>>(),
+            });
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&result)?),
+                _ => println!("{}", serde_yaml::to_string(&result)?),
             }
-            result.push_str("  ]\n");
         }
-        
-        result.push_str("}\n\n");
     }
     
-    result
+    if in_sync {
+        Ok(())
+    } else {
+        Err(anyhow!(message!("El clúster no coincide con el DSL")))
+    }
 }
 
-/// Formatea una fuente de datos
-fn format_source(source: &crate::ast::Source) -> String {
-    match source {
-        crate::ast::Source::NATS(topic, options) => {
-            if let Some(opts) = options {
-                format!("NATS(\"{}\", {:?})", topic, opts)
-            } else {
-                format!("NATS(\"{}\")", topic)
+/// Comando para clasificar los cambios de los workflows respecto a una versión anterior
+///
+/// Falla si la versión declarada de algún workflow no sube según sus cambios,
+/// para poder bloquear una publicación en CI.
+fn diff_command(base: &Path, input: &Path, format: OutputFormat) -> Result<()> {
+    let mut previous = parser::parse_file(base).map_err(KumeoError::from)?;
+    resolve_constants(&mut previous)?;
+    let mut program = parser::parse_file(input).map_err(KumeoError::from)?;
+    resolve_constants(&mut program)?;
+    let diffs = versioning::diff_programs(&previous, &program);
+    let errors: Vec<Message> = diffs.iter().filter_map(|diff| diff.bump_error()).collect();
+
+    match format {
+        OutputFormat::Human => {
+            let version = |version: &Option<String>| version.clone().map_or_else(|| message!("sin versión"), Message::Text);
+            for diff in &diffs {
+                let class = diff.class().map_or_else(|| message!("sin cambios"), |class| Message::Text(class.to_string()));
+                println!("{} ({} → {}): {}", diff.workflow, version(&diff.previous_version), version(&diff.version), class);
+                for change in &diff.changes {
+                    println!("  {:<5}  {}", change.class.to_string(), change.description);
+                }
+            }
+            for error in &errors {
+                println!("❌ {}", error);
+            }
+        }
+        OutputFormat::Json | OutputFormat::Yaml => {
+            let workflows: Vec<serde_json::Value> = diffs
+                .iter()
+                .map(|diff| {
+                    serde_json::json!({
+                        "workflow": diff.workflow,
+                        "previous_version": diff.previous_version,
+                        "version": diff.version,
+                        "class": diff.class(),
+                        "changes": diff.changes,
+                        "error": diff.bump_error(),
+                    })
+                })
+                .collect();
+            let result = serde_json::json!({ "valid": errors.is_empty(), "workflows": workflows });
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&result)?),
+                _ => println!("{}", serde_yaml::to_string(&result)?),
             }
         }
     }
+    if !errors.is_empty() {
+        return Err(anyhow!(message!("Las versiones de {} workflows no corresponden a sus cambios", errors.len())));
+    }
+    Ok(())
 }
 
-/// Formatea un destino de datos
-fn format_target(target: &crate::ast::Target) -> String {
-    match target {
-        crate::ast::Target::NATS(topic, options) => {
-            if let Some(opts) = options {
-                format!("NATS(\"{}\", {:?})", topic, opts)
-            } else {
-                format!("NATS(\"{}\")", topic)
+/// Comando para mostrar las estadísticas de un programa
+///
+/// Los workflows se cuentan tal como se generan, con las constantes
+/// sustituidas y los subworkflows expandidos.
+fn stats_command(input: &Path, format: OutputFormat) -> Result<()> {
+    let mut program = parser::parse_file(input).map_err(KumeoError::from)?;
+    resolve_constants(&mut program)?;
+    expand_workflows(&mut program)?;
+    let stats = ProgramStats::of(&program)?;
+    
+    match format {
+        OutputFormat::Human => {
+            println!("{}", message!("Workflows: {} ({} subworkflows)", stats.workflows, stats.subworkflows));
+            let languages = stats.agents_by_language.iter()
+                .map(|(language, count)| format!("{} {}", count, language))
+                .collect::<Vec<_>>();
+            println!("{}", message!("Agentes: {} ({})", stats.agents, languages.join(", ")));
+            for (agent_type, count) in &stats.agents_by_type {
+                println!("  {:<16} {}", agent_type, count);
+            }
+            println!("{}", message!("Topics: {}", stats.topics.len()));
+            println!("{}", message!("Esquemas: {}", stats.schemas));
+            println!("{}", message!("Recursos externos: {}", stats.resources.len()));
+            for resource in &stats.resources {
+                println!("  {}", resource);
+            }
+            let footprint = |footprint: &stats::Footprint| {
+                format!("{} pods, {}m de CPU, {}Mi de memoria", footprint.pods, footprint.cpu_millis, footprint.memory_mib)
+            };
+            println!("{}", message!("Huella estimada en Kubernetes: {}", footprint(&stats.footprint)));
+            for workflow in &stats.by_workflow {
+                println!("  {}: {}", workflow.name, message!("{} agentes, {}", workflow.agents, footprint(&workflow.footprint)));
             }
         }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&stats)?),
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&stats)?),
     }
+    Ok(())
 }
 
-/// Formatea un agente
-fn format_agent(agent: &Agent) -> String {
-    let mut result = String::new();
-    
-    // Agregar ID si existe
-    if let Some(id) = &agent.id {
-        result.push_str(&format!("id: \"{}\",\n", id));
+/// Comando para generar el sitio de documentación de un programa
+///
+/// Escribe un índice de los workflows y una página por workflow con la
+/// documentación de sus agentes.
+fn docs_command(input: &Path, output: &Path) -> Result<()> {
+    let program = parser::parse_file(input).map_err(KumeoError::from)?;
+    let pages = docs::site_pages(&program);
+    std::fs::create_dir_all(output)
+        .with_context(|| message!("No se pudo crear el directorio: {}", output.display()))?;
+    for page in &pages {
+        let path = output.join(&page.path);
+        std::fs::write(&path, &page.contents)
+            .with_context(|| message!("No se pudo escribir en el archivo: {}", path.display()))?;
     }
-    
-    // Agregar configuración específica del agente
-    for arg in &agent.config {
-        match arg {
-            Argument::Named(name, value) => {
-                result.push_str(&format!("    {}: {},\n", name, value));
+    println!("✅ {}", message!("{} páginas de documentación en {}", pages.len(), output.display()));
+    Ok(())
+}
+
+/// Comando para mostrar la documentación en una posición del código
+///
+/// El archivo se analiza por sí solo y pasando por alto sus errores, ya que
+/// los editores lo consultan mientras se escribe.
+fn hover_command(input: &Path, line: usize, column: usize) -> Result<()> {
+    let source = std::fs::read_to_string(input)
+        .with_context(|| message!("No se pudo leer el archivo: {}", input.display()))?;
+    let program = parser::parse_recovering(&source).program;
+    if let Some(hover) = docs::hover(&program, line, column) {
+        println!("{}", hover);
+    }
+    Ok(())
+}
+
+/// Comando para estimar el coste mensual de un programa
+///
+/// Los recursos pedidos por los agentes se valoran por horas y los
+/// presupuestos de tokens de los agentes LLM al precio de su modelo.
+fn cost_command(input: &Path, pricing: &Path, format: OutputFormat) -> Result<()> {
+    let pricing = Pricing::from_file(pricing)?;
+    let mut program = parser::parse_file(input).map_err(KumeoError::from)?;
+    resolve_constants(&mut program)?;
+    expand_workflows(&mut program)?;
+    let estimate = CostEstimate::of(&program, &pricing)?;
+
+    match format {
+        OutputFormat::Human => {
+            let currency = &estimate.currency;
+            println!("{}", message!("Coste mensual estimado ({} horas, en {}):", estimate.hours_per_month, currency));
+            for workflow in &estimate.by_workflow {
+                let cost = &workflow.cost;
+                println!(
+                    "  {}: {}",
+                    workflow.name,
+                    message!(
+                        "{} (CPU {}, memoria {}, LLM {})",
+                        format!("{:.2}", cost.total),
+                        format!("{:.2}", cost.cpu),
+                        format!("{:.2}", cost.memory),
+                        format!("{:.2}", cost.llm)
+                    )
+                );
+                for llm in &workflow.llm {
+                    println!(
+                        "    {} ({}): {}",
+                        llm.agent,
+                        llm.model.clone().map_or_else(|| message!("modelo desconocido"), Message::Text),
+                        message!(
+                            "{} tokens de entrada, {} de salida: {}",
+                            llm.budget.input_tokens,
+                            llm.budget.output_tokens,
+                            format!("{:.2}", llm.cost)
+                        )
+                    );
+                }
             }
-            Argument::Positional(value) => {
-                result.push_str(&format!("    {},\n", value));
+            let total = &estimate.total;
+            println!(
+                "Total: {:.2} {} ({})",
+                total.total,
+                currency,
+                message!(
+                    "CPU {}, memoria {}, LLM {}",
+                    format!("{:.2}", total.cpu),
+                    format!("{:.2}", total.memory),
+                    format!("{:.2}", total.llm)
+                )
+            );
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&estimate)?),
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&estimate)?),
+    }
+    Ok(())
+}
+
+/// Comando para listar las plantillas
+///
+/// Cada plantilla sale de la primera capa que la tiene: el proyecto, el
+/// usuario o las incluidas; se indican también las capas a las que sustituye.
+fn templates_ls_command(templates: &TemplateManager, format: OutputFormat) -> Result<()> {
+    let resolved = templates.resolve_all()?;
+    match format {
+        OutputFormat::Human => {
+            println!("{}", message!("Capas de plantillas, de mayor a menor prioridad:"));
+            for layer in templates.layers() {
+                println!("  {:<8}  {}", layer.source.to_string(), layer);
+            }
+            println!();
+            let width = resolved.iter().map(|template| template.name.len()).max().unwrap_or(0);
+            for template in &resolved {
+                let shadows = template.shadows.iter().map(ToString::to_string).collect::<Vec<_>>();
+                if shadows.is_empty() {
+                    println!("{:<width$}  {}", template.name, template.source, width = width);
+                } else {
+                    let shadowed = message!("sustituye a {}", shadows.join(", "));
+                    println!("{:<width$}  {} ({})", template.name, template.source, shadowed, width = width);
+                }
+            }
+            let overridden = resolved.iter().filter(|template| !template.shadows.is_empty()).count();
+            println!();
+            println!("{}", message!("{} plantillas, {} sustituidas", resolved.len(), overridden));
+        }
+        OutputFormat::Json | OutputFormat::Yaml => {
+            let result = serde_json::json!({
+                "layers": templates.layers().iter()
+                    .map(|layer| serde_json::json!({ "source": layer.source, "dir": layer.dir }))
+                    .collect::<Vec<_>>(),
+                "templates": resolved,
+            });
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&result)?),
+                _ => println!("{}", serde_yaml::to_string(&result)?),
             }
         }
     }
-    
-    result.push(')');
-    result
+    Ok(())
 }
@@ -1,42 +1,133 @@
 //! Error types for the Kumeo parser.
 
-use std::fmt;
+use pest::error::ErrorVariant;
 use thiserror::Error;
 
+use crate::ast::Span;
+use crate::diagnostics::{codes, Diagnostic};
+use crate::i18n::Message;
+use crate::message;
+use crate::parser::Rule;
+
 /// Error type for parsing Kumeo DSL.
 #[derive(Debug, Error)]
 pub enum ParseError {
     /// Error from the Pest parser.
     #[error("Parse error: {0}")]
-    PestError(#[from] Box<pest::error::Error<crate::parser::Rule>>),
+    PestError(#[from] Box<pest::error::Error<Rule>>),
+
+    /// Error of the grammar found by the parser itself, with its position.
+    #[error("{}", Diagnostic::error(codes::SYNTAX, message.clone()).at(span))]
+    Syntax {
+        /// The position of the error.
+        span: Box<Span>,
+        /// The error.
+        message: Message,
+    },
 
     /// Semantic error in the DSL.
     #[error("Semantic error: {0}")]
-    SemanticError(String),
+    SemanticError(Message),
 
 
     /// Generic error.
     #[error("Error: {0}")]
-    Generic(String),
+    Generic(Message),
+
+    /// Error in a node of the input, with its position.
+    #[error("{}", Diagnostic::error(codes::MALFORMED, message.clone()).at(span))]
+    Located {
+        /// The position of the node.
+        span: Box<Span>,
+        /// The error.
+        message: Message,
+    },
 }
 
 impl ParseError {
     /// Create a new semantic error.
-    pub fn semantic<S: Into<String>>(msg: S) -> Self {
+    pub fn semantic<S: Into<Message>>(msg: S) -> Self {
         Self::SemanticError(msg.into())
     }
 
     /// Create a new generic error.
-    pub fn generic<S: Into<String>>(msg: S) -> Self {
+    pub fn generic<S: Into<Message>>(msg: S) -> Self {
         Self::Generic(msg.into())
     }
+
+    /// Attach the position of the node the error was found in.
+    ///
+    /// Pest errors and errors that already have a position keep theirs, so
+    /// the innermost node wins.
+    pub fn at(self, span: &Span) -> Self {
+        match self {
+            Self::SemanticError(message) | Self::Generic(message) if span.is_known() => Self::Located {
+                span: Box::new(span.clone()),
+                message,
+            },
+            other => other,
+        }
+    }
+
+    /// The error as a diagnostic, with its code and position.
+    pub fn diagnostic(&self) -> Diagnostic {
+        match self {
+            Self::PestError(e) => {
+                let (position, length) = match e.line_col {
+                    pest::error::LineColLocation::Pos(position) => (position, 1),
+                    pest::error::LineColLocation::Span(start, end) if start.0 == end.0 => (start, end.1 - start.1),
+                    pest::error::LineColLocation::Span(start, _) => (start, e.line().chars().count() + 1 - start.1),
+                };
+                let mut span = Span::in_line(position.0, position.1, length, e.line());
+                span.file = e.path().map(str::to_string);
+                Diagnostic::error(codes::SYNTAX, grammar_message(&e.variant)).at(&span)
+            }
+            Self::Syntax { span, message } => Diagnostic::error(codes::SYNTAX, message.clone()).at(span),
+            Self::Located { span, message } => Diagnostic::error(codes::MALFORMED, message.clone()).at(span),
+            Self::SemanticError(message) | Self::Generic(message) => Diagnostic::error(codes::MALFORMED, message.clone()),
+        }
+    }
+
+    /// The line and column of the error, when known.
+    pub fn line_col(&self) -> Option<(usize, usize)> {
+        match self {
+            Self::PestError(e) => Some(match e.line_col {
+                pest::error::LineColLocation::Pos(position) | pest::error::LineColLocation::Span(position, _) => position,
+            }),
+            Self::Syntax { span, .. } | Self::Located { span, .. } => Some((span.line, span.column)),
+            Self::SemanticError(_) | Self::Generic(_) => None,
+        }
+    }
 }
 
-impl From<pest::error::Error<crate::parser::Rule>> for ParseError {
-    fn from(err: pest::error::Error<crate::parser::Rule>) -> Self {
+impl From<pest::error::Error<Rule>> for ParseError {
+    fn from(err: pest::error::Error<Rule>) -> Self {
         Self::PestError(Box::new(err))
     }
 }
 
+/// The message of an error of the grammar, worded as pest words it
+fn grammar_message(variant: &ErrorVariant<Rule>) -> Message {
+    match variant {
+        ErrorVariant::ParsingError { positives, negatives } => match (negatives.is_empty(), positives.is_empty()) {
+            (false, false) => message!("unexpected {}; expected {}", rules(negatives), rules(positives)),
+            (false, true) => message!("unexpected {}", rules(negatives)),
+            (true, false) => message!("expected {}", rules(positives)),
+            (true, true) => message!("unknown parsing error"),
+        },
+        ErrorVariant::CustomError { message } => Message::Text(message.clone()),
+    }
+}
+
+/// Rules of the grammar, listed as alternatives
+fn rules(rules: &[Rule]) -> Message {
+    let names: Vec<Message> = rules.iter().map(|rule| Message::Text(format!("{:?}", rule))).collect();
+    match names.as_slice() {
+        [] => Message::Text(String::new()),
+        [only] => only.clone(),
+        [listed @ .., last] => message!("{} or {}", Message::join(listed.to_vec(), ", "), last),
+    }
+}
+
 /// Result type for parsing operations.
 pub type ParseResult<T> = Result<T, ParseError>;
@@ -2,13 +2,17 @@
 
 pub mod error;
 pub mod parser;
+mod recovery;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
 use pest::iterators::Pair;
 
 use crate::{
     ast::*,
+    i18n::Message,
+    message,
     parser::parser::{Parser, Rule},
 };
 
@@ -20,17 +24,37 @@ pub fn parse(input: &str) -> ParseResult<Program> {
     let mut program = Program::new();
 
     for pair in pairs {
+        check_separators(&pair)?;
         match pair.as_rule() {
+            Rule::import => {
+                let path = pair
+                    .into_inner()
+                    .next()
+                    .map(|p| p.as_str().trim_matches(|c| c == '"' || c == '\'').to_string())
+                    .unwrap_or_default();
+                program.imports.push(path);
+            }
+            Rule::constant => {
+                let span = span_of(&pair);
+                program.constants.push(parse_constant(pair).map_err(|e| e.at(&span))?);
+            }
+            Rule::schemas => {
+                let span = span_of(&pair);
+                program.schemas.extend(parse_schemas(pair).map_err(|e| e.at(&span))?);
+            }
             Rule::workflow => {
-                program.workflows.push(parse_workflow(pair)?);
+                let span = span_of(&pair);
+                program.workflows.push(parse_workflow(pair).map_err(|e| e.at(&span))?);
             }
             Rule::subworkflow => {
-                program.subworkflows.push(parse_subworkflow(pair)?);
+                let span = span_of(&pair);
+                program.subworkflows.push(parse_subworkflow(pair).map_err(|e| e.at(&span))?);
             }
+            Rule::EOI => {}
             _ => {
-                return Err(ParseError::generic(format!(
-                    "Unexpected rule: {:?}",
-                    pair.as_rule()
+                return Err(ParseError::generic(message!(
+                    "Unexpected rule: {}",
+                    format!("{:?}", pair.as_rule())
                 )));
             }
         }
@@ -39,6 +63,284 @@ pub fn parse(input: &str) -> ParseResult<Program> {
     Ok(program)
 }
 
+/// A program parsed past its syntax errors.
+#[derive(Debug, Default)]
+pub struct PartialProgram {
+    /// The items that parsed, without the agents that didn't.
+    pub program: Program,
+    /// Every error found, in source order; empty when the whole input parsed.
+    pub errors: Vec<ParseError>,
+}
+
+impl PartialProgram {
+    /// Whether the whole input parsed.
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Parse a Kumeo DSL input string, reporting every syntax error instead of
+/// stopping at the first.
+///
+/// Workflows, subworkflows and the other top-level items are parsed one by
+/// one, and the agents of a workflow one by one, so the returned program
+/// has everything that parsed. An item with an error outside its agents is
+/// left out.
+pub fn parse_recovering(input: &str) -> PartialProgram {
+    match parse(input) {
+        Ok(program) => PartialProgram { program, errors: Vec::new() },
+        Err(_) => {
+            let (program, errors) = recovery::parse_items(input);
+            PartialProgram { program, errors }
+        }
+    }
+}
+
+/// Parse a Kumeo file together with everything it imports.
+///
+/// Imports are resolved relative to the importing file, so nested files can
+/// import their own neighbours. Each file is included once even when several
+/// files import it, and import cycles are reported with the chain of files.
+/// The returned program has no pending imports.
+pub fn parse_file(path: &Path) -> ParseResult<Program> {
+    let mut program = Program::new();
+    let mut loaded = HashSet::new();
+    let mut stack = Vec::new();
+    load_file(path, &mut program, &mut loaded, &mut stack, None)?;
+    Ok(program)
+}
+
+/// Parse a Kumeo file together with everything it imports, returning the
+/// canonical paths of the files read along with the program.
+pub fn parse_file_with_sources(path: &Path) -> ParseResult<(Program, HashSet<PathBuf>)> {
+    let mut program = Program::new();
+    let mut loaded = HashSet::new();
+    let mut stack = Vec::new();
+    load_file(path, &mut program, &mut loaded, &mut stack, None)?;
+    Ok((program, loaded))
+}
+
+/// Parse a Kumeo file together with everything it imports, reporting every
+/// syntax error of every file as [`parse_recovering`] does.
+pub fn parse_file_recovering(path: &Path) -> PartialProgram {
+    let mut partial = PartialProgram::default();
+    let mut loaded = HashSet::new();
+    let mut stack = Vec::new();
+    if let Err(e) = load_file(path, &mut partial.program, &mut loaded, &mut stack, Some(&mut partial.errors)) {
+        partial.errors.push(e);
+    }
+    partial
+}
+
+/// Load a file and its imports into a program.
+///
+/// With `errors`, syntax errors are collected there and loading goes on with
+/// what parsed; without it, the first error is returned.
+fn load_file(
+    path: &Path,
+    program: &mut Program,
+    loaded: &mut HashSet<PathBuf>,
+    stack: &mut Vec<PathBuf>,
+    mut errors: Option<&mut Vec<ParseError>>,
+) -> ParseResult<()> {
+    let path = path
+        .canonicalize()
+        .map_err(|e| ParseError::generic(message!("Cannot read {}: {}", path.display(), e)))?;
+
+    if let Some(start) = stack.iter().position(|file| *file == path) {
+        let chain: Vec<_> = stack[start..]
+            .iter()
+            .chain(std::iter::once(&path))
+            .map(|file| file.display().to_string())
+            .collect();
+        return Err(ParseError::generic(message!("Import cycle: {}", chain.join(" -> "))));
+    }
+    if !loaded.insert(path.clone()) {
+        return Ok(());
+    }
+
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| ParseError::generic(message!("Cannot read {}: {}", path.display(), e)))?;
+    let mut file = match errors.as_deref_mut() {
+        Some(errors) => {
+            let partial = parse_recovering(&content);
+            errors.extend(partial.errors.into_iter().map(|e| in_file(e, &path)));
+            partial.program
+        }
+        None => parse(&content).map_err(|e| in_file(e, &path))?,
+    };
+    file.set_file(&path.display().to_string());
+
+    // Imported definitions come first, so a file's dependencies precede it
+    stack.push(path.clone());
+    let directory = path.parent().unwrap_or(Path::new("."));
+    for import in &file.imports {
+        let loaded = load_file(&directory.join(import), program, loaded, stack, errors.as_deref_mut());
+        match (loaded, errors.as_deref_mut()) {
+            (Err(e), Some(errors)) => errors.push(e),
+            (result, _) => result?,
+        }
+    }
+    stack.pop();
+
+    program.merge(file);
+    Ok(())
+}
+
+/// Attach the file an error was found in
+fn in_file(error: ParseError, path: &Path) -> ParseError {
+    match error {
+        ParseError::PestError(e) => ParseError::PestError(Box::new(e.with_path(&path.to_string_lossy()))),
+        ParseError::Syntax { mut span, message } => {
+            span.file = Some(path.display().to_string());
+            ParseError::Syntax { span, message }
+        }
+        ParseError::Located { mut span, message } => {
+            span.file = Some(path.display().to_string());
+            ParseError::Located { span, message }
+        }
+        ParseError::SemanticError(message) | ParseError::Generic(message) => {
+            ParseError::generic(Message::join([Message::Text(path.display().to_string()), message], ": "))
+        }
+    }
+}
+
+/// The position of a node, with the source line it starts on
+///
+/// A node documented with `///` comments starts after them.
+fn span_of(pair: &Pair<Rule>) -> Span {
+    let input = pair.get_input();
+    let docs_end = pair
+        .clone()
+        .into_inner()
+        .take_while(|inner| inner.as_rule() == Rule::doc_comment)
+        .last()
+        .map(|doc| doc.as_span().end());
+    let mut offset = docs_end.unwrap_or(pair.as_span().start());
+    if docs_end.is_some() {
+        loop {
+            let rest = input[offset..].trim_start();
+            offset = input.len() - rest.len();
+            if !rest.starts_with("//") {
+                break;
+            }
+            offset += rest.find('\n').unwrap_or(rest.len());
+        }
+    }
+    let start = pest::Position::new(input, offset).unwrap_or(pair.as_span().start_pos());
+    let (line, column) = start.line_col();
+    let length = input[start.pos()..pair.as_span().end()].lines().next().unwrap_or_default().chars().count();
+    Span::in_line(line, column, length, start.line_of())
+}
+
+/// Nodes whose items are separated by `,` or, in workflows and subworkflows, `;`
+const SEPARATED: [Rule; 20] = [
+    Rule::array,
+    Rule::object,
+    Rule::feature_store,
+    Rule::inference_server,
+    Rule::hosted_model,
+    Rule::oidc_auth,
+    Rule::nats_subject,
+    Rule::rule_call,
+    Rule::agent,
+    Rule::reduce_clause,
+    Rule::rules_clause,
+    Rule::data_source,
+    Rule::data_target,
+    Rule::subworkflow_call,
+    Rule::workflow_test,
+    Rule::workflow,
+    Rule::subworkflow_input,
+    Rule::subworkflow_output,
+    Rule::subworkflow,
+    Rule::schemas,
+];
+
+/// Check that the items left without a separator start a new line
+///
+/// The grammar makes separators optional, as it can't see line breaks; a
+/// missing one is only allowed at the end of a line. Names and types that
+/// open a node aren't items, nor are workflow tests, which never needed one.
+fn check_separators(pair: &Pair<Rule>) -> ParseResult<()> {
+    if SEPARATED.contains(&pair.as_rule()) {
+        // The name of a test opens it, as a string
+        let items: Vec<_> = pair
+            .clone()
+            .into_inner()
+            .skip(usize::from(pair.as_rule() == Rule::workflow_test))
+            .filter(|item| {
+                pair.as_rule() == Rule::array
+                    || !matches!(
+                        item.as_rule(),
+                        Rule::doc_comment
+                            | Rule::ident
+                            | Rule::agent_type
+                            | Rule::source_type
+                            | Rule::target_type
+                            | Rule::inference_backend
+                            | Rule::hosted_provider
+                            | Rule::workflow_test
+                    )
+            })
+            .collect();
+        for (previous, next) in items.iter().zip(items.iter().skip(1)) {
+            let gap = &pair.get_input()[previous.as_span().end()..next.as_span().start()];
+            if !gap.contains([',', ';', '\n']) {
+                let word = next.as_str().split(|c: char| c.is_whitespace() || ":=(,".contains(c)).next().unwrap_or_default();
+                let start = next.as_span().start_pos();
+                let (line, column) = start.line_col();
+                return Err(ParseError::Syntax {
+                    span: Box::new(Span::in_line(line, column, 1, start.line_of())),
+                    message: message!("expected a separator or a line break before {}", word),
+                });
+            }
+        }
+    }
+    pair.clone().into_inner().try_for_each(|inner| check_separators(&inner))
+}
+
+fn parse_constant(pair: Pair<Rule>) -> ParseResult<Constant> {
+    let span = span_of(&pair);
+    let mut inner = pair.into_inner();
+    let name = inner
+        .next()
+        .ok_or_else(|| ParseError::generic(message!("Expected constant name")))?
+        .as_str()
+        .to_string();
+    let value = inner
+        .next()
+        .ok_or_else(|| ParseError::generic(message!("Expected a value for constant {}", name)))?;
+
+    Ok(Constant {
+        name,
+        value: parse_value(value)?,
+        span,
+    })
+}
+
+/// Parse a `schemas:` block, keeping the declarations in order
+fn parse_schemas(pair: Pair<Rule>) -> ParseResult<Vec<TopicSchema>> {
+    let mut schemas = Vec::new();
+    for declaration in pair.into_inner() {
+        let mut inner = declaration.into_inner();
+        let topic = inner
+            .next()
+            .ok_or_else(|| ParseError::generic(message!("Expected a topic")))?
+            .as_str()
+            .trim_matches(|c| c == '"' || c == '\'')
+            .to_string();
+        let fields = inner
+            .next()
+            .ok_or_else(|| ParseError::generic(message!("Expected the schema of topic {}", topic)))?;
+        schemas.push(TopicSchema {
+            topic,
+            fields: parse_value(fields)?,
+        });
+    }
+    Ok(schemas)
+}
+
 fn parse_workflow(pair: Pair<Rule>) -> ParseResult<Workflow> {
     let mut workflow = Workflow {
         name: String::new(),
@@ -49,13 +351,30 @@ fn parse_workflow(pair: Pair<Rule>) -> ParseResult<Workflow> {
         agents: Vec::new(),
         monitor: None,
         deployment: None,
+        version: None,
+        doc: None,
+        mode: WorkflowMode::Stream,
+        calls: Vec::new(),
+        tests: Vec::new(),
+        span: span_of(&pair),
     };
 
+    let mut doc = Vec::new();
     for pair in pair.into_inner() {
         match pair.as_rule() {
+            Rule::doc_comment => doc.push(pair),
             Rule::ident => {
                 workflow.name = pair.as_str().to_string();
             }
+            Rule::workflow_version => {
+                workflow.version = Some(pair.as_str().trim_matches(|c| c == '"' || c == '\'').to_string());
+            }
+            Rule::workflow_mode => {
+                workflow.mode = match pair.as_str() {
+                    "batch" => WorkflowMode::Batch,
+                    _ => WorkflowMode::Stream,
+                };
+            }
             Rule::data_source => {
                 workflow.source = Some(parse_data_source(pair)?);
             }
@@ -63,15 +382,64 @@ fn parse_workflow(pair: Pair<Rule>) -> ParseResult<Workflow> {
                 workflow.target = Some(parse_data_target(pair)?);
             }
             Rule::agent => {
-                workflow.agents.push(parse_agent(pair)?);
+                let span = span_of(&pair);
+                workflow.agents.push(parse_agent(pair).map_err(|e| e.at(&span))?);
+            }
+            Rule::subworkflow_call => {
+                workflow.calls.push(parse_subworkflow_call(pair, workflow.agents.len())?);
+            }
+            Rule::monitor => {
+                let span = span_of(&pair);
+                workflow.monitor = Some(parse_monitor(pair).map_err(|e| e.at(&span))?);
+            }
+            Rule::deployment => {
+                workflow.deployment = Some(parse_deployment(pair, &workflow.name)?);
+            }
+            Rule::workflow_test => {
+                let span = span_of(&pair);
+                workflow.tests.push(parse_workflow_test(pair).map_err(|e| e.at(&span))?);
             }
             _ => {}
         }
     }
+    workflow.doc = parse_doc(doc);
 
     Ok(workflow)
 }
 
+fn parse_workflow_test(pair: Pair<Rule>) -> ParseResult<WorkflowTest> {
+    let span = span_of(&pair);
+    let mut inner = pair.into_inner();
+    let name = inner
+        .next()
+        .ok_or_else(|| ParseError::generic(message!("Expected test name")))?
+        .as_str()
+        .trim_matches(|c| c == '"' || c == '\'')
+        .to_string();
+    let given = inner
+        .next()
+        .map(parse_value)
+        .ok_or_else(|| ParseError::generic(message!("Expected test message")))??;
+    let agent = inner
+        .next()
+        .ok_or_else(|| ParseError::generic(message!("Expected tested agent")))?
+        .as_str()
+        .trim_matches(|c| c == '"' || c == '\'')
+        .to_string();
+    let expect = inner
+        .next()
+        .map(parse_object)
+        .ok_or_else(|| ParseError::generic(message!("Expected test expectations")))??;
+
+    Ok(WorkflowTest {
+        name,
+        given,
+        agent,
+        expect,
+        span,
+    })
+}
+
 fn parse_subworkflow(pair: Pair<Rule>) -> ParseResult<Subworkflow> {
     let mut subworkflow = Subworkflow {
         name: String::new(),
@@ -79,6 +447,7 @@ fn parse_subworkflow(pair: Pair<Rule>) -> ParseResult<Subworkflow> {
         output: None,
         context: None,
         agents: Vec::new(),
+        span: span_of(&pair),
     };
 
     for pair in pair.into_inner() {
@@ -86,8 +455,15 @@ fn parse_subworkflow(pair: Pair<Rule>) -> ParseResult<Subworkflow> {
             Rule::ident => {
                 subworkflow.name = pair.as_str().to_string();
             }
+            Rule::subworkflow_input => {
+                subworkflow.input = Some(parse_string_list(pair));
+            }
+            Rule::subworkflow_output => {
+                subworkflow.output = Some(parse_string_list(pair));
+            }
             Rule::agent => {
-                subworkflow.agents.push(parse_agent(pair)?);
+                let span = span_of(&pair);
+                subworkflow.agents.push(parse_agent(pair).map_err(|e| e.at(&span))?);
             }
             _ => {}
         }
@@ -96,36 +472,104 @@ fn parse_subworkflow(pair: Pair<Rule>) -> ParseResult<Subworkflow> {
     Ok(subworkflow)
 }
 
+fn parse_string_list(pair: Pair<Rule>) -> Vec<String> {
+    pair.into_inner()
+        .map(|string| string.as_str().trim_matches(|c| c == '"' || c == '\'').to_string())
+        .collect()
+}
+
+/// Parse a `use Name(input: ..., output: ...)` step listed after `position` agents
+fn parse_subworkflow_call(pair: Pair<Rule>, position: usize) -> ParseResult<SubworkflowCall> {
+    let mut inner = pair.into_inner();
+    let name = inner
+        .next()
+        .ok_or_else(|| ParseError::generic(message!("Expected subworkflow name")))?
+        .as_str()
+        .to_string();
+    let mut call = SubworkflowCall {
+        name,
+        input: Vec::new(),
+        output: Vec::new(),
+        position,
+    };
+
+    for pair in inner {
+        let mut pair_inner = pair.into_inner();
+        let key = pair_inner
+            .next()
+            .ok_or_else(|| ParseError::generic(message!("Expected key")))?
+            .as_str()
+            .trim_matches(|c| c == '"' || c == '\'')
+            .to_string();
+        let value = parse_value(
+            pair_inner
+                .next()
+                .ok_or_else(|| ParseError::generic(message!("Expected value")))?,
+        )?;
+
+        // A single subject or a list of subjects, in declaration order
+        let subjects = match value {
+            Value::String(subject) => vec![subject],
+            Value::Array(values) => values
+                .into_iter()
+                .map(|value| match value {
+                    Value::String(subject) => Ok(subject),
+                    other => Err(ParseError::generic(message!(
+                        "Expected a subject in use {}, found {}",
+                        call.name, other
+                    ))),
+                })
+                .collect::<ParseResult<_>>()?,
+            other => {
+                return Err(ParseError::generic(message!(
+                    "Expected a subject or a list of subjects for {} in use {}, found {}",
+                    key, call.name, other
+                )))
+            }
+        };
+        match key.as_str() {
+            "input" => call.input = subjects,
+            "output" => call.output = subjects,
+            _ => {
+                return Err(ParseError::generic(message!(
+                    "Unknown option for use {}: {}",
+                    call.name, key
+                )))
+            }
+        }
+    }
+
+    Ok(call)
+}
+
 fn parse_data_source(pair: Pair<Rule>) -> ParseResult<Source> {
     let mut inner = pair.into_inner();
     let source_type = inner
         .next()
-        .ok_or_else(|| ParseError::generic("Expected source type"))?;
+        .ok_or_else(|| ParseError::generic(message!("Expected source type")))?;
 
     match source_type.as_str() {
         "NATS" => {
-            let topic = inner
-                .next()
-                .and_then(|p| p.into_inner().next())
-                .map(|p| p.as_str().trim_matches('"').to_string())
-                .ok_or_else(|| ParseError::generic("Expected NATS topic"))?;
-
-            let options = inner.next().map(parse_object).transpose()?;
-            // Convertir HashMap<String, Value> a HashMap<String, String>
-            let options = options.map(|opts| {
-                opts.into_iter()
-                    .filter_map(|(k, v)| {
-                        if let Value::String(s) = v {
-                            Some((k, s))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
-            });
+            let (topic, options) = parse_endpoint(inner, "NATS")?;
             Ok(Source::NATS(topic, options))
         }
-        _ => Err(ParseError::generic("Unsupported source type")),
+        "Kafka" => {
+            let (topic, options) = parse_endpoint(inner, "Kafka")?;
+            Ok(Source::Kafka(topic, options))
+        }
+        "MQTT" => {
+            let (topic, options) = parse_endpoint(inner, "MQTT")?;
+            Ok(Source::MQTT(topic, options))
+        }
+        "HTTP" => {
+            let (path, options) = parse_endpoint(inner, "HTTP")?;
+            Ok(Source::HTTP(path, options))
+        }
+        "File" => {
+            let (pattern, options) = parse_endpoint(inner, "File")?;
+            Ok(Source::File(pattern, options))
+        }
+        _ => Err(ParseError::generic(message!("Unsupported source type"))),
     }
 }
 
@@ -133,40 +577,77 @@ fn parse_data_target(pair: Pair<Rule>) -> ParseResult<Target> {
     let mut inner = pair.into_inner();
     let target_type = inner
         .next()
-        .ok_or_else(|| ParseError::generic("Expected target type"))?;
+        .ok_or_else(|| ParseError::generic(message!("Expected target type")))?;
 
     match target_type.as_str() {
         "NATS" => {
-            let topic = inner
-                .next()
-                .and_then(|p| p.into_inner().next())
-                .map(|p| p.as_str().trim_matches('"').to_string())
-                .ok_or_else(|| ParseError::generic("Expected NATS topic"))?;
-
-            let options = inner.next().map(parse_object).transpose()?;
-            // Convertir HashMap<String, Value> a HashMap<String, String>
-            let options = options.map(|opts| {
-                opts.into_iter()
-                    .filter_map(|(k, v)| {
-                        if let Value::String(s) = v {
-                            Some((k, s))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
-            });
+            let (topic, options) = parse_endpoint(inner, "NATS")?;
             Ok(Target::NATS(topic, options))
         }
-        _ => Err(ParseError::generic("Unsupported target type")),
+        "Kafka" => {
+            let (topic, options) = parse_endpoint(inner, "Kafka")?;
+            Ok(Target::Kafka(topic, options))
+        }
+        "MQTT" => {
+            let (topic, options) = parse_endpoint(inner, "MQTT")?;
+            Ok(Target::MQTT(topic, options))
+        }
+        "File" => {
+            let (directory, options) = parse_endpoint(inner, "File")?;
+            Ok(Target::File(directory, options))
+        }
+        _ => Err(ParseError::generic(message!("Unsupported target type"))),
     }
 }
 
+/// Parse the topic and options of a source or target.
+fn parse_endpoint(
+    mut inner: pest::iterators::Pairs<Rule>,
+    kind: &str,
+) -> ParseResult<(String, Option<HashMap<String, String>>)> {
+    let topic = inner
+        .next()
+        .map(|p| p.as_str().trim_matches('"').to_string())
+        .ok_or_else(|| ParseError::generic(message!("Expected {} topic", kind)))?;
+
+    let options = inner.next().map(parse_object).transpose()?;
+    // Convertir HashMap<String, Value> a HashMap<String, String>; los objetos
+    // con nombre se aplanan en su nombre y sus opciones `clave.opción`
+    let options = options.map(|opts| {
+        let mut flat = HashMap::new();
+        for (k, v) in opts {
+            match v {
+                Value::Tagged(name, fields) => {
+                    for (field, value) in fields {
+                        if let Some(value) = endpoint_option(value) {
+                            flat.insert(format!("{}.{}", k, field), value);
+                        }
+                    }
+                    flat.insert(k, name);
+                }
+                v => {
+                    if let Some(value) = endpoint_option(v) {
+                        flat.insert(k, value);
+                    }
+                }
+            }
+        }
+        flat
+    });
+
+    Ok((topic, options))
+}
+
 fn parse_agent(pair: Pair<Rule>) -> ParseResult<Agent> {
-    let mut inner = pair.into_inner();
+    let span = span_of(&pair);
+    let mut inner = pair.into_inner().peekable();
+    let mut doc = Vec::new();
+    while let Some(line) = inner.next_if(|pair| pair.as_rule() == Rule::doc_comment) {
+        doc.push(line);
+    }
     let agent_type = inner
         .next()
-        .ok_or_else(|| ParseError::generic("Expected agent type"))?;
+        .ok_or_else(|| ParseError::generic(message!("Expected agent type")))?;
 
     let agent_type = match agent_type.as_str() {
         "LLM" => AgentType::LLM,
@@ -175,7 +656,14 @@ fn parse_agent(pair: Pair<Rule>) -> ParseResult<Agent> {
         "Router" => AgentType::Router,
         "DecisionMatrix" => AgentType::DecisionMatrix,
         "HumanReview" => AgentType::HumanReview,
-        _ => return Err(ParseError::generic("Unknown agent type")),
+        "QualityMonitor" => AgentType::QualityMonitor,
+        "Aggregator" => AgentType::Aggregator,
+        "RuleEngine" => AgentType::RuleEngine,
+        "BayesianNetwork" => AgentType::BayesianNetwork,
+        "Custom" => AgentType::Custom,
+        "DataNormalizer" => AgentType::DataNormalizer,
+        "MissingValueHandler" => AgentType::MissingValueHandler,
+        _ => return Err(ParseError::generic(message!("Unknown agent type"))),
     };
 
     let mut id = None;
@@ -184,15 +672,17 @@ fn parse_agent(pair: Pair<Rule>) -> ParseResult<Agent> {
     for pair in inner {
         match pair.as_rule() {
             Rule::pair => {
+                let span = span_of(&pair);
                 let mut pair_inner = pair.into_inner();
                 let key = pair_inner
                     .next()
-                    .ok_or_else(|| ParseError::generic("Expected key"))?
+                    .ok_or_else(|| ParseError::generic(message!("Expected key")))?
                     .as_str()
+                    .trim_matches(|c| c == '"' || c == '\'')
                     .to_string();
                 let value = pair_inner
                     .next()
-                    .ok_or_else(|| ParseError::generic("Expected value"))?;
+                    .ok_or_else(|| ParseError::generic(message!("Expected value")))?;
 
                 if key == "id" {
                     id = Some(
@@ -202,9 +692,48 @@ fn parse_agent(pair: Pair<Rule>) -> ParseResult<Agent> {
                             .to_string(),
                     );
                 } else {
-                    config.push(Argument::Named(key, parse_value(value)?));
+                    config.push(Argument::Named(key, parse_value(value)?, span));
                 }
             }
+            Rule::when_clause => {
+                let span = span_of(&pair);
+                let expr = pair
+                    .into_inner()
+                    .next()
+                    .ok_or_else(|| ParseError::generic(message!("Expected a condition after when")))?;
+                config.push(Argument::Named(
+                    WHEN_OPTION.to_string(),
+                    Value::Condition(Box::new(parse_expr(expr)?)),
+                    span,
+                ));
+            }
+            Rule::assert_clause => {
+                let span = span_of(&pair);
+                let expr = pair
+                    .into_inner()
+                    .next()
+                    .ok_or_else(|| ParseError::generic(message!("Expected a condition after assert")))?;
+                config.push(Argument::Named(
+                    ASSERT_OPTION.to_string(),
+                    Value::Condition(Box::new(parse_expr(expr)?)),
+                    span,
+                ));
+            }
+            Rule::reduce_clause => {
+                let span = span_of(&pair);
+                config.push(Argument::Named(REDUCE_OPTION.to_string(), parse_reduce(pair)?, span));
+            }
+            Rule::rules_clause => {
+                let span = span_of(&pair);
+                config.push(Argument::Named(RULES_OPTION.to_string(), parse_rules(pair)?, span));
+            }
+            Rule::custom_kind => {
+                if agent_type != AgentType::Custom {
+                    return Err(ParseError::generic(message!("Only Custom agents take the name of their templates")));
+                }
+                let kind = pair.as_str().trim_matches('"').to_string();
+                config.push(Argument::Positional(Value::String(kind), span_of(&pair)));
+            }
             _ => {}
         }
     }
@@ -213,9 +742,175 @@ fn parse_agent(pair: Pair<Rule>) -> ParseResult<Agent> {
         id,
         agent_type,
         config,
+        doc: parse_doc(doc),
+        span,
     })
 }
 
+/// Read a `reduce:` clause as an object of reductions by field name
+///
+/// Each reduction is a value tagged with its reducer, with the expression it
+/// folds, if any, under `of`.
+fn parse_reduce(pair: Pair<Rule>) -> ParseResult<Value> {
+    let mut reductions = HashMap::new();
+    for reduction in pair.into_inner() {
+        let mut inner = reduction.into_inner();
+        let name = inner
+            .next()
+            .ok_or_else(|| ParseError::generic(message!("Expected reduction name")))?
+            .as_str()
+            .trim_matches(|c| c == '"' || c == '\'')
+            .to_string();
+        let reducer = inner
+            .next()
+            .ok_or_else(|| ParseError::generic(message!("Expected reducer")))?
+            .as_str()
+            .to_string();
+        let mut arguments = HashMap::new();
+        if let Some(expr) = inner.next() {
+            arguments.insert(Reduction::ARGUMENT.to_string(), Value::Condition(Box::new(parse_expr(expr)?)));
+        }
+        reductions.insert(name, Value::Tagged(reducer, arguments));
+    }
+    Ok(Value::Object(reductions))
+}
+
+/// Read a `rules:` clause as an array of rules in declaration order
+///
+/// Each rule is a value tagged with its name, with its condition under
+/// `when` and its action, tagged with the action's name, under `then`.
+fn parse_rules(pair: Pair<Rule>) -> ParseResult<Value> {
+    let mut rules = Vec::new();
+    for rule in pair.into_inner() {
+        let mut inner = rule.into_inner();
+        let name = inner
+            .next()
+            .ok_or_else(|| ParseError::generic(message!("Expected rule name")))?
+            .as_str()
+            .trim_matches(|c| c == '"' || c == '\'')
+            .to_string();
+        let condition = inner.next().ok_or_else(|| ParseError::generic(message!("Expected a condition")))?;
+        let action = inner.next().ok_or_else(|| ParseError::generic(message!("Expected action")))?;
+        let mut settings = HashMap::new();
+        settings.insert(RuleAction::WHEN.to_string(), Value::Condition(Box::new(parse_expr(condition)?)));
+        settings.insert(RuleAction::THEN.to_string(), parse_value(action)?);
+        rules.push(Value::Tagged(name, settings));
+    }
+    Ok(Value::Array(rules))
+}
+
+/// Join the `///` lines above a workflow or agent into its documentation
+///
+/// The slashes and the space after them are removed; documentation with only
+/// blank lines is no documentation.
+fn parse_doc(lines: Vec<Pair<Rule>>) -> Option<String> {
+    let lines: Vec<&str> = lines
+        .iter()
+        .map(|line| {
+            let text = line.as_str().trim_start_matches("///");
+            text.strip_prefix(' ').unwrap_or(text).trim_end()
+        })
+        .collect();
+    let doc = lines.join("\n").trim_matches('\n').to_string();
+    (!doc.is_empty()).then_some(doc)
+}
+
+/// Parse a `when` condition
+fn parse_expr(pair: Pair<Rule>) -> ParseResult<Expr> {
+    match pair.as_rule() {
+        Rule::or_expr | Rule::and_expr => {
+            let rule = pair.as_rule();
+            let mut operands = pair.into_inner().map(parse_expr);
+            let first = operands
+                .next()
+                .ok_or_else(|| ParseError::generic(message!("Expected a condition")))??;
+            operands.try_fold(first, |left, right| {
+                let (left, right) = (Box::new(left), Box::new(right?));
+                Ok(match rule {
+                    Rule::or_expr => Expr::Or(left, right),
+                    _ => Expr::And(left, right),
+                })
+            })
+        }
+        Rule::unary_expr => {
+            let mut negations = 0;
+            let mut comparison = None;
+            for inner in pair.into_inner() {
+                match inner.as_rule() {
+                    Rule::not_op => negations += 1,
+                    _ => comparison = Some(parse_expr(inner)?),
+                }
+            }
+            let comparison = comparison.ok_or_else(|| ParseError::generic(message!("Expected a condition after !")))?;
+            Ok((0..negations).fold(comparison, |expr, _| Expr::Not(Box::new(expr))))
+        }
+        Rule::comparison => {
+            let mut inner = pair.into_inner();
+            let left = parse_expr(
+                inner
+                    .next()
+                    .ok_or_else(|| ParseError::generic(message!("Expected an operand")))?,
+            )?;
+            let Some(op) = inner.next() else {
+                return Ok(left);
+            };
+            let op = match op.as_str() {
+                "==" => CompareOp::Eq,
+                "!=" => CompareOp::Ne,
+                "<" => CompareOp::Lt,
+                "<=" => CompareOp::Le,
+                ">" => CompareOp::Gt,
+                _ => CompareOp::Ge,
+            };
+            let right = parse_expr(
+                inner
+                    .next()
+                    .ok_or_else(|| ParseError::generic(message!("Expected an operand after {}", op.as_str())))?,
+            )?;
+            Ok(Expr::Compare(Box::new(left), op, Box::new(right)))
+        }
+        Rule::default_expr => {
+            let mut operands = pair.into_inner().map(parse_expr);
+            let first = operands
+                .next()
+                .ok_or_else(|| ParseError::generic(message!("Expected an operand")))??;
+            operands.try_fold(first, |value, default| Ok(Expr::Default(Box::new(value), Box::new(default?))))
+        }
+        Rule::field => {
+            // `a?.b` splits into `a?` and `b`: the `?` makes the next segment optional
+            let mut path = Vec::new();
+            let mut optional = Vec::new();
+            let mut chained = false;
+            for segment in pair.as_str().split('.') {
+                optional.push(chained);
+                chained = segment.ends_with('?');
+                path.push(segment.trim_end_matches('?').to_string());
+            }
+            Ok(if optional.contains(&true) {
+                Expr::OptionalField(path, optional)
+            } else {
+                Expr::Field(path)
+            })
+        }
+        _ => Ok(Expr::Literal(parse_value(pair)?)),
+    }
+}
+
+/// Text of a source or target option; lists of scalars are separated by commas.
+fn endpoint_option(value: Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Boolean(b) => Some(b.to_string()),
+        Value::Array(items) => items
+            .into_iter()
+            .map(endpoint_option)
+            .collect::<Option<Vec<_>>>()
+            .map(|items| items.join(",")),
+        _ => None,
+    }
+}
+
 fn parse_value(pair: Pair<Rule>) -> ParseResult<Value> {
     match pair.as_rule() {
         Rule::string => {
@@ -226,9 +921,10 @@ fn parse_value(pair: Pair<Rule>) -> ParseResult<Value> {
             let num = pair
                 .as_str()
                 .parse::<f64>()
-                .map_err(|e| ParseError::generic(format!("Invalid number: {}", e)))?;
+                .map_err(|e| ParseError::generic(message!("Invalid number: {}", e)))?;
             Ok(Value::Number(num))
         }
+        Rule::percent => Ok(Value::String(pair.as_str().to_string())),
         Rule::boolean => {
             let b = pair.as_str() == "true";
             Ok(Value::Boolean(b))
@@ -245,7 +941,102 @@ fn parse_value(pair: Pair<Rule>) -> ParseResult<Value> {
             let obj = parse_object(pair)?;
             Ok(Value::Object(obj))
         }
-        _ => Err(ParseError::generic("Unexpected value type")),
+        Rule::resource => {
+            let uri = pair
+                .into_inner()
+                .next()
+                .ok_or_else(|| ParseError::generic(message!("Expected resource URI")))?;
+            parse_value(uri)
+        }
+        Rule::secret => {
+            let name = pair
+                .into_inner()
+                .next()
+                .ok_or_else(|| ParseError::generic(message!("Expected secret name")))?;
+            let variable = secret_variable(name.as_str().trim_matches(|c| c == '"' || c == '\'')).map_err(ParseError::generic)?;
+            Ok(Value::String(format!("${{env.{}}}", variable)))
+        }
+        Rule::feature_store => {
+            let view = pair
+                .clone()
+                .into_inner()
+                .next()
+                .ok_or_else(|| ParseError::generic(message!("Expected feature view")))?;
+            let mut options = parse_object(pair)?;
+            options.insert(FeatureStoreConfig::FEATURE_VIEW.to_string(), parse_value(view)?);
+            Ok(Value::Tagged(FeatureStoreConfig::FEAST.to_string(), options))
+        }
+        Rule::inference_server => {
+            let backend = pair
+                .clone()
+                .into_inner()
+                .next()
+                .ok_or_else(|| ParseError::generic(message!("Expected inference server")))?
+                .as_str()
+                .to_string();
+            Ok(Value::Tagged(backend, parse_object(pair)?))
+        }
+        Rule::hosted_model => {
+            let mut inner = pair.clone().into_inner();
+            let provider = inner
+                .next()
+                .ok_or_else(|| ParseError::generic(message!("Expected provider")))?
+                .as_str()
+                .to_string();
+            let model = inner.next().ok_or_else(|| ParseError::generic(message!("Expected model")))?;
+            let model = match model.as_rule() {
+                Rule::model_id => Value::String(model.as_str().to_string()),
+                _ => parse_value(model)?,
+            };
+            let mut options = parse_object(pair)?;
+            options.insert("model".to_string(), model);
+            Ok(Value::Tagged(provider, options))
+        }
+        Rule::oidc_auth => {
+            let mut options = parse_object(pair.clone())?;
+            let arguments = pair.into_inner().filter(|inner| inner.as_rule() == Rule::string);
+            for (key, argument) in OidcAuthConfig::ARGUMENTS.iter().zip(arguments) {
+                options.insert(key.to_string(), parse_value(argument)?);
+            }
+            Ok(Value::Tagged(OidcAuthConfig::OIDC.to_string(), options))
+        }
+        Rule::nats_subject => {
+            let subject = pair
+                .clone()
+                .into_inner()
+                .next()
+                .ok_or_else(|| ParseError::generic(message!("Expected subject")))?;
+            let mut options = parse_object(pair)?;
+            options.insert(CompensationConfig::SUBJECT.to_string(), parse_value(subject)?);
+            Ok(Value::Tagged(CompensationConfig::NATS.to_string(), options))
+        }
+        Rule::rule_call => {
+            let name = pair
+                .clone()
+                .into_inner()
+                .next()
+                .ok_or_else(|| ParseError::generic(message!("Expected rule")))?
+                .as_str()
+                .to_string();
+            Ok(Value::Tagged(name, parse_object(pair)?))
+        }
+        Rule::tagged => {
+            let mut inner = pair.into_inner();
+            let name = inner
+                .next()
+                .ok_or_else(|| ParseError::generic(message!("Expected name")))?
+                .as_str()
+                .to_string();
+            let obj = inner
+                .next()
+                .map(parse_object)
+                .transpose()?
+                .unwrap_or_default();
+            Ok(Value::Tagged(name, obj))
+        }
+        Rule::path => Ok(Value::Path(pair.as_str().to_string())),
+        Rule::variable => Ok(Value::Variable(pair.as_str().trim_start_matches('$').to_string())),
+        _ => Err(ParseError::generic(message!("Unexpected value type"))),
     }
 }
 
@@ -257,15 +1048,435 @@ fn parse_object(pair: Pair<Rule>) -> ParseResult<HashMap<String, Value>> {
             let mut inner = pair.into_inner();
             let key = inner
                 .next()
-                .ok_or_else(|| ParseError::generic("Expected key"))?
+                .ok_or_else(|| ParseError::generic(message!("Expected key")))?
                 .as_str()
+                .trim_matches(|c| c == '"' || c == '\'')
                 .to_string();
             let value = inner
                 .next()
-                .ok_or_else(|| ParseError::generic("Expected value"))?;
+                .ok_or_else(|| ParseError::generic(message!("Expected value")))?;
             map.insert(key, parse_value(value)?);
         }
     }
 
     Ok(map)
 }
+
+fn parse_deployment(pair: Pair<Rule>, workflow_name: &str) -> ParseResult<Deployment> {
+    let object = pair
+        .into_inner()
+        .next()
+        .map(parse_object)
+        .transpose()?
+        .unwrap_or_default();
+
+    let mut deployment = Deployment {
+        name: workflow_name.to_string(),
+        namespace: None,
+        replicas: None,
+        resources: None,
+        env: None,
+        rollout: None,
+        storage: None,
+        require_signed: false,
+        trusted_keys: None,
+        env_secret: None,
+        infrastructure: None,
+        platform: Platform::default(),
+        scaling: None,
+        labels: None,
+        annotations: None,
+        registry: None,
+        tag: None,
+    };
+
+    for (key, value) in object {
+        match (key.as_str(), value) {
+            ("namespace", Value::String(namespace)) => {
+                validate_namespace(&namespace).map_err(ParseError::generic)?;
+                deployment.namespace = Some(namespace);
+            }
+            ("replicas", Value::Number(replicas)) if replicas >= 0.0 => {
+                deployment.replicas = Some(replicas as u32);
+            }
+            ("resources", Value::Object(resources)) => {
+                let text = |name: &str| match resources.get(name) {
+                    Some(Value::String(s)) => Some(s.clone()),
+                    Some(Value::Number(n)) => Some(n.to_string()),
+                    _ => None,
+                };
+                deployment.resources = Some(ResourceRequirements {
+                    cpu: text("cpu"),
+                    memory: text("memory"),
+                    gpu: text("gpu"),
+                });
+            }
+            ("env", Value::Object(env)) => {
+                deployment.env = Some(string_map(env));
+            }
+            ("labels", Value::Object(labels)) => {
+                let labels = string_map(labels);
+                for (key, value) in &labels {
+                    validate_label(key, value).map_err(ParseError::generic)?;
+                }
+                deployment.labels = Some(labels);
+            }
+            ("annotations", Value::Object(annotations)) => {
+                let annotations = string_map(annotations);
+                for key in annotations.keys() {
+                    validate_annotation(key).map_err(ParseError::generic)?;
+                }
+                deployment.annotations = Some(annotations);
+            }
+            ("registry", Value::String(registry)) => {
+                validate_registry(&registry).map_err(ParseError::generic)?;
+                deployment.registry = Some(registry);
+            }
+            ("tag", Value::String(tag)) => {
+                validate_image_tag(&tag).map_err(ParseError::generic)?;
+                deployment.tag = Some(tag);
+            }
+            ("rollout", value) => {
+                deployment.rollout = Some(parse_rollout(value)?);
+            }
+            ("storage", Value::Object(storage)) => {
+                deployment.storage = Some(
+                    storage
+                        .into_iter()
+                        .map(|(agent, value)| Ok((agent, parse_storage(value)?)))
+                        .collect::<ParseResult<_>>()?,
+                );
+            }
+            ("require_signed", Value::Boolean(require_signed)) => {
+                deployment.require_signed = require_signed;
+            }
+            ("trusted_keys", Value::String(secret)) => {
+                deployment.trusted_keys = Some(secret);
+            }
+            ("env_secret", Value::String(secret)) => {
+                deployment.env_secret = Some(secret);
+            }
+            ("infrastructure", value) => {
+                deployment.infrastructure = Some(parse_infrastructure(value)?);
+            }
+            ("scaling", value) => {
+                deployment.scaling = Some(parse_scaling(value)?);
+            }
+            ("platform", Value::String(platform)) | ("platform", Value::Path(platform)) => {
+                deployment.platform = match platform.as_str() {
+                    "kubernetes" => Platform::Kubernetes,
+                    "nomad" => Platform::Nomad,
+                    other => {
+                        return Err(ParseError::generic(message!(
+                            "Unknown deployment platform {}, expected kubernetes or nomad",
+                            other
+                        )))
+                    }
+                };
+            }
+            (key, _) => {
+                return Err(ParseError::generic(message!(
+                    "Invalid deployment setting: {}",
+                    key
+                )));
+            }
+        }
+    }
+
+    Ok(deployment)
+}
+
+/// The entries of an object as text, such as the `env` of a deployment.
+fn string_map(object: HashMap<String, Value>) -> HashMap<String, String> {
+    object
+        .into_iter()
+        .map(|(k, v)| match v {
+            Value::String(s) => (k, s),
+            other => (k, other.to_string()),
+        })
+        .collect()
+}
+
+fn parse_rollout(value: Value) -> ParseResult<RolloutStrategy> {
+    let (strategy, options) = match value {
+        Value::Tagged(name, options) => (name, options),
+        Value::Path(name) => (name, HashMap::new()),
+        other => {
+            return Err(ParseError::generic(message!(
+                "Expected a rollout strategy, found {}",
+                other
+            )));
+        }
+    };
+
+    match strategy.as_str() {
+        "canary" => {
+            let steps = match options.get("steps") {
+                Some(Value::Array(steps)) => steps
+                    .iter()
+                    .map(parse_weight)
+                    .collect::<ParseResult<Vec<_>>>()?,
+                Some(_) => return Err(ParseError::generic(message!("Canary steps must be a list"))),
+                None => vec![100.0],
+            };
+            let analysis = match options.get("analysis") {
+                Some(Value::String(analysis)) => Some(analysis.clone()),
+                Some(_) => {
+                    return Err(ParseError::generic(message!("Canary analysis must be a string")))
+                }
+                None => None,
+            };
+            Ok(RolloutStrategy::Canary(CanaryStrategy { steps, analysis }))
+        }
+        "blue_green" => match options.keys().next() {
+            Some(key) => Err(ParseError::generic(message!(
+                "Invalid blue_green setting: {}",
+                key
+            ))),
+            None => Ok(RolloutStrategy::BlueGreen),
+        },
+        other => Err(ParseError::generic(message!(
+            "Unknown rollout strategy: {}",
+            other
+        ))),
+    }
+}
+
+fn parse_storage(value: Value) -> ParseResult<Storage> {
+    let Value::Object(options) = value else {
+        return Err(ParseError::generic(message!(
+            "Expected a storage object, found {}",
+            value
+        )));
+    };
+
+    let text = |name: &str| match options.get(name) {
+        Some(Value::String(s)) => Ok(Some(s.clone())),
+        Some(other) => Err(ParseError::generic(message!(
+            "Storage {} must be a string, found {}",
+            name, other
+        ))),
+        None => Ok(None),
+    };
+
+    Ok(Storage {
+        size: text("size")?.ok_or_else(|| ParseError::generic(message!("Storage requires a size")))?,
+        path: text("path")?.ok_or_else(|| ParseError::generic(message!("Storage requires a path")))?,
+        class: text("class")?,
+    })
+}
+
+/// Default CPU utilization autoscaling keeps when no target is given
+const DEFAULT_TARGET_CPU: u32 = 80;
+
+fn parse_scaling(value: Value) -> ParseResult<Scaling> {
+    let Value::Object(options) = value else {
+        return Err(ParseError::generic(message!(
+            "Expected a scaling object, found {}",
+            value
+        )));
+    };
+    let keys = ["min_replicas", "max_replicas", "target_cpu", "target_memory"];
+    if let Some(key) = options.keys().find(|key| !keys.contains(&key.as_str())) {
+        return Err(ParseError::generic(message!("Invalid scaling setting: {}", key)));
+    }
+
+    let count = |name: &str| match options.get(name) {
+        Some(Value::Number(n)) if *n >= 1.0 && n.fract() == 0.0 => Ok(Some(*n as u32)),
+        Some(other) => Err(ParseError::generic(message!(
+            "Scaling {} must be a whole number of at least 1, found {}",
+            name, other
+        ))),
+        None => Ok(None),
+    };
+
+    let min_replicas = count("min_replicas")?.unwrap_or(1);
+    let max_replicas = count("max_replicas")?.ok_or_else(|| ParseError::generic(message!("Scaling requires max_replicas")))?;
+    if max_replicas < min_replicas {
+        return Err(ParseError::generic(message!(
+            "Scaling max_replicas {} is below min_replicas {}",
+            max_replicas, min_replicas
+        )));
+    }
+    let target_cpu = count("target_cpu")?;
+    let target_memory = count("target_memory")?;
+    Ok(Scaling {
+        min_replicas,
+        max_replicas,
+        target_cpu: target_cpu.or(target_memory.is_none().then_some(DEFAULT_TARGET_CPU)),
+        target_memory,
+    })
+}
+
+/// Default interval Prometheus scrapes a monitored workflow at
+const DEFAULT_SCRAPE_INTERVAL: &str = "30s";
+
+/// Default window the alerts of a monitored workflow are computed over
+const DEFAULT_ALERT_WINDOW: &str = "5m";
+
+/// Default severity label of the alerts
+const DEFAULT_ALERT_SEVERITY: &str = "warning";
+
+fn parse_monitor(pair: Pair<Rule>) -> ParseResult<Monitor> {
+    let options = pair
+        .into_inner()
+        .next()
+        .map(parse_object)
+        .transpose()?
+        .unwrap_or_default();
+    let keys = [
+        "metrics_enabled",
+        "scrape_interval",
+        "alert_threshold",
+        "alert_error_rate",
+        "alert_guardrail_blocks",
+        "alert_unavailable",
+        "alert_latency_p99",
+        "alert_window",
+        "alert_severity",
+    ];
+    if let Some(key) = options.keys().find(|key| !keys.contains(&key.as_str())) {
+        return Err(ParseError::generic(message!("Invalid monitor setting: {}", key)));
+    }
+
+    let metrics_enabled = match options.get("metrics_enabled") {
+        Some(Value::Boolean(enabled)) => *enabled,
+        Some(other) => {
+            return Err(ParseError::generic(message!(
+                "Monitor metrics_enabled must be true or false, found {}",
+                other
+            )))
+        }
+        None => true,
+    };
+    // Prometheus durations are whole amounts of a unit, such as `30s` or `5m`
+    let duration = |name: &str| match options.get(name) {
+        Some(Value::String(s))
+            if s.ends_with(['s', 'm', 'h'])
+                && !s.contains('.')
+                && parse_duration_secs(s).is_some_and(|secs| secs > 0) =>
+        {
+            Ok(Some(s.clone()))
+        }
+        Some(other) => Err(ParseError::generic(message!(
+            "Monitor {} must be a duration such as \"5m\", found {}",
+            name, other
+        ))),
+        None => Ok(None),
+    };
+    let count = |name: &str| match options.get(name) {
+        Some(Value::Number(n)) if *n >= 1.0 && n.fract() == 0.0 => Ok(Some(*n as u32)),
+        Some(other) => Err(ParseError::generic(message!(
+            "Monitor {} must be a whole number of at least 1, found {}",
+            name, other
+        ))),
+        None => Ok(None),
+    };
+
+    let error_rate = match options.get("alert_error_rate") {
+        Some(Value::Number(rate)) if *rate > 0.0 && *rate <= 1.0 => Some(*rate),
+        Some(other) => {
+            return Err(ParseError::generic(message!(
+                "Monitor alert_error_rate must be a fraction between 0 and 1, found {}",
+                other
+            )))
+        }
+        None => None,
+    };
+    // Latency targets are usually below a second, unlike the other durations
+    let latency_p99 = match options.get("alert_latency_p99") {
+        Some(Value::String(s)) if parse_duration_millis(s).is_some_and(|millis| millis > 0) => Some(s.clone()),
+        Some(other) => {
+            return Err(ParseError::generic(message!(
+                "Monitor alert_latency_p99 must be a duration such as \"500ms\" or \"2s\", found {}",
+                other
+            )))
+        }
+        None => None,
+    };
+    let severity = match options.get("alert_severity") {
+        Some(Value::String(severity)) if ["critical", "warning", "info"].contains(&severity.as_str()) => severity.clone(),
+        Some(other) => {
+            return Err(ParseError::generic(message!(
+                "Unknown alert severity {}, expected critical, warning or info",
+                other
+            )))
+        }
+        None => DEFAULT_ALERT_SEVERITY.to_string(),
+    };
+
+    Ok(Monitor {
+        metrics_enabled,
+        scrape_interval: duration("scrape_interval")?.unwrap_or_else(|| DEFAULT_SCRAPE_INTERVAL.to_string()),
+        alerts: MonitorAlerts {
+            restarts: count("alert_threshold")?,
+            error_rate,
+            guardrail_blocks: count("alert_guardrail_blocks")?,
+            unavailable_for: duration("alert_unavailable")?,
+            latency_p99,
+            window: duration("alert_window")?.unwrap_or_else(|| DEFAULT_ALERT_WINDOW.to_string()),
+            severity,
+        },
+    })
+}
+
+fn parse_infrastructure(value: Value) -> ParseResult<Infrastructure> {
+    let Value::Object(options) = value else {
+        return Err(ParseError::generic(message!(
+            "Expected an infrastructure object, found {}",
+            value
+        )));
+    };
+    if let Some(key) = options.keys().find(|key| !["provider", "region", "kafka"].contains(&key.as_str())) {
+        return Err(ParseError::generic(message!("Invalid infrastructure setting: {}", key)));
+    }
+
+    let text = |name: &str| match options.get(name) {
+        Some(Value::String(s)) | Some(Value::Path(s)) => Ok(Some(s.clone())),
+        Some(other) => Err(ParseError::generic(message!(
+            "Infrastructure {} must be a string, found {}",
+            name, other
+        ))),
+        None => Ok(None),
+    };
+
+    let provider = match text("provider")?.as_deref() {
+        Some("aws") => CloudProvider::Aws,
+        Some("gcp") => CloudProvider::Gcp,
+        Some(other) => {
+            return Err(ParseError::generic(message!(
+                "Unknown infrastructure provider {}, expected aws or gcp",
+                other
+            )))
+        }
+        None => return Err(ParseError::generic(message!("Infrastructure requires a provider"))),
+    };
+    let managed_kafka = match text("kafka")?.as_deref() {
+        Some("managed") => true,
+        Some("in_cluster") | None => false,
+        Some(other) => {
+            return Err(ParseError::generic(message!(
+                "Unknown Kafka deployment {}, expected managed or in_cluster",
+                other
+            )))
+        }
+    };
+
+    Ok(Infrastructure {
+        provider,
+        region: text("region")?.ok_or_else(|| ParseError::generic(message!("Infrastructure requires a region")))?,
+        managed_kafka,
+    })
+}
+
+/// Read a traffic weight written either as `10%` or `10`.
+fn parse_weight(value: &Value) -> ParseResult<f64> {
+    match value {
+        Value::Number(weight) => Ok(*weight),
+        Value::String(weight) => weight
+            .strip_suffix('%')
+            .and_then(|w| w.parse::<f64>().ok())
+            .ok_or_else(|| ParseError::generic(message!("Invalid traffic weight: {}", weight))),
+        other => Err(ParseError::generic(message!("Invalid traffic weight: {}", other))),
+    }
+}
@@ -0,0 +1,283 @@
+//! Parsing that keeps going after syntax errors.
+//!
+//! The source is split into its top-level items (imports, constants,
+//! `schemas:` blocks, workflows and subworkflows) and each item is parsed on
+//! its own, so an error in one item doesn't hide the errors of the next. An
+//! item is parsed with the text before it blanked out rather than cut, so
+//! the positions of its errors and nodes are those of the whole source.
+//!
+//! Inside a workflow or subworkflow, the steps of `agents: [...]` are a
+//! second recovery point: a step with an error is reported and blanked out,
+//! and the item is parsed again without it. An item with an error elsewhere
+//! is left out of the program.
+
+use std::ops::Range;
+
+use crate::ast::Program;
+
+use super::error::ParseError;
+
+/// Keywords that start a top-level item
+const ITEM_KEYWORDS: [&str; 5] = ["import", "const", "schemas", "workflow", "subworkflow"];
+
+/// Keywords that start a top-level item even when the previous item left a
+/// bracket open, since they can't appear inside one
+const HARD_KEYWORDS: [&str; 4] = ["import", "const", "workflow", "subworkflow"];
+
+/// Parse every item of the source, collecting the errors instead of stopping at the first.
+pub fn parse_items(input: &str) -> (Program, Vec<ParseError>) {
+    let mut program = Program::new();
+    let mut errors = Vec::new();
+    for item in items(input) {
+        if let Some(parsed) = parse_item(input, item, &mut errors) {
+            program.imports.extend(parsed.imports.iter().cloned());
+            program.merge(parsed);
+        }
+    }
+    (program, errors)
+}
+
+/// Parse an item, blanking out the steps with errors until the rest parses
+fn parse_item(input: &str, item: Range<usize>, errors: &mut Vec<ParseError>) -> Option<Program> {
+    let mut text = blank(input, 0..item.start);
+    text.push_str(&input[item.clone()]);
+    let steps = pipeline_steps(&text, item.start);
+    let mut remaining: Vec<usize> = (0..steps.as_ref().map_or(0, PipelineSteps::len)).collect();
+    let mut emptied = false;
+
+    loop {
+        let error = match super::parse(&text) {
+            Ok(program) => return Some(program),
+            // A subworkflow needs agents; its own were already reported
+            Err(_) if emptied => return None,
+            Err(error) => error,
+        };
+        let at = error.line_col().and_then(|(line, column)| offset(&text, line, column));
+        errors.push(error);
+
+        // Only an error in a step can be recovered from, by parsing the item without it
+        let (steps, at) = (steps.as_ref()?, at?);
+        let failing = remaining.iter().position(|&step| steps.contains(step, at))?;
+        let step = remaining.remove(failing);
+        let blanked = match (failing.checked_sub(1).map(|i| remaining[i]), remaining.get(failing)) {
+            // Workflows may omit their agents; a subworkflow without them fails and is dropped
+            (None, None) => {
+                emptied = true;
+                steps.clause.clone()
+            }
+            // The first step goes with the comma after it, the others with the comma before them
+            (None, Some(&next)) => steps.separators[step] + 1..steps.separators[next] + 1,
+            (Some(_), _) => steps.separators[step]..steps.separators[step + 1],
+        };
+        text = blank_range(&text, blanked);
+    }
+}
+
+/// The steps of the `agents: [...]` list of an item
+struct PipelineSteps {
+    /// Offsets of the `[`, the commas between steps and the `]`
+    separators: Vec<usize>,
+    /// The whole `agents: [...];` clause
+    clause: Range<usize>,
+}
+
+impl PipelineSteps {
+    /// Number of steps
+    fn len(&self) -> usize {
+        self.separators.len() - 1
+    }
+
+    /// Whether an offset falls in a step or on the separator that ends it
+    fn contains(&self, step: usize, at: usize) -> bool {
+        self.separators[step] < at && at <= self.separators[step + 1]
+    }
+}
+
+/// Find the steps of the `agents: [...]` list of the item starting at `start`
+///
+/// Returns `None` when the list isn't there or its brackets don't balance.
+fn pipeline_steps(text: &str, start: usize) -> Option<PipelineSteps> {
+    let tokens = scan(&text[start..]);
+    let agents = tokens
+        .iter()
+        .position(|token| token.text == 'a' && token.depth == 1 && is_clause(&text[start + token.offset..], "agents"))?;
+    let open = tokens[agents..].iter().position(|token| token.text == '[')? + agents;
+    let depth = tokens[open].depth;
+
+    let mut separators = vec![start + tokens[open].offset];
+    for token in &tokens[open + 1..] {
+        match token.text {
+            ',' if token.depth == depth + 1 => separators.push(start + token.offset),
+            ']' if token.depth == depth => {
+                let close = start + token.offset;
+                separators.push(close);
+                let rest = &text[close + 1..];
+                let after = rest.len() - rest.trim_start().len();
+                let end = close + 1 + if rest.trim_start().starts_with(';') { after + 1 } else { 0 };
+                return Some(PipelineSteps {
+                    separators,
+                    clause: start + tokens[agents].offset..end,
+                });
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// A structural character of the source, outside strings and comments
+struct Token {
+    /// Byte offset in the scanned text
+    offset: usize,
+    /// The character; `a` for the first letter of a word
+    text: char,
+    /// Bracket depth before the character
+    depth: usize,
+}
+
+/// The brackets, commas and word starts of a text, with their depth
+fn scan(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
+    let mut chars = text.char_indices().peekable();
+    let mut previous = ' ';
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                for (_, inner) in chars.by_ref() {
+                    if inner == c {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek().is_some_and(|(_, next)| *next == '/') => {
+                for (_, inner) in chars.by_ref() {
+                    if inner == '\n' {
+                        break;
+                    }
+                }
+            }
+            '{' | '[' | '(' => {
+                tokens.push(Token { offset, text: c, depth });
+                depth += 1;
+            }
+            '}' | ']' | ')' => {
+                depth = depth.saturating_sub(1);
+                tokens.push(Token { offset, text: c, depth });
+            }
+            ',' | ';' => tokens.push(Token { offset, text: c, depth }),
+            c if (c.is_ascii_alphabetic() || c == '_') && !(previous.is_ascii_alphanumeric() || previous == '_' || previous == '.') => {
+                tokens.push(Token { offset, text: 'a', depth });
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+    tokens
+}
+
+/// Split the source into its top-level items
+///
+/// An item starts with one of [`ITEM_KEYWORDS`] outside any bracket, or with
+/// one of [`HARD_KEYWORDS`] at the start of a line, which recovers from an
+/// item that left a bracket open. Each item ends at its last non-blank
+/// character, so an error at its end points right after it.
+fn items(input: &str) -> Vec<Range<usize>> {
+    let mut starts: Vec<usize> = Vec::new();
+    for token in scan(input).iter().filter(|token| token.text == 'a') {
+        let rest = &input[token.offset..];
+        let Some(keyword) = ITEM_KEYWORDS.iter().find(|keyword| is_keyword(rest, keyword)) else {
+            continue;
+        };
+        let line_start = input[..token.offset].rfind('\n').map_or(0, |i| i + 1);
+        let starts_line = input[line_start..token.offset].trim().is_empty();
+        if token.depth == 0 || (starts_line && HARD_KEYWORDS.contains(keyword)) {
+            starts.push(doc_start(input, token.offset));
+        }
+    }
+
+    // Text before the first item is an item too, so its errors are reported
+    let leading = starts.first().copied().unwrap_or(input.len());
+    if !is_blank(&input[..leading]) {
+        starts.insert(0, 0);
+    }
+
+    let mut items = Vec::new();
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(input.len());
+        let end = start + input[start..end].trim_end().len();
+        items.push(start..end);
+    }
+    items
+}
+
+/// Start of the `///` documentation lines right above an item, or of the item itself
+fn doc_start(input: &str, item: usize) -> usize {
+    let mut start = item;
+    while let Some(line_end) = input[..start].rfind('\n').filter(|&end| input[end..start].trim().is_empty()) {
+        let line_start = input[..line_end].rfind('\n').map_or(0, |i| i + 1);
+        let line = input[line_start..line_end].trim();
+        if !line.starts_with("///") || line.starts_with("////") {
+            break;
+        }
+        start = line_start + input[line_start..].len() - input[line_start..].trim_start().len();
+    }
+    start
+}
+
+/// Whether a text starts with a clause name followed by a `:` or `=`
+fn is_clause(text: &str, name: &str) -> bool {
+    text.strip_prefix(name).is_some_and(|rest| rest.trim_start().starts_with([':', '=']))
+}
+
+/// Whether a text starts with a keyword followed by a name, a string or an assignment
+fn is_keyword(text: &str, keyword: &str) -> bool {
+    let Some(rest) = text.strip_prefix(keyword) else {
+        return false;
+    };
+    if rest.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+        return false;
+    }
+    let next = rest.trim_start();
+    match keyword {
+        "import" => next.starts_with(['"', '\'']),
+        "schemas" => next.starts_with([':', '=']),
+        _ => next.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_'),
+    }
+}
+
+/// Whether a text has only whitespace and comments
+fn is_blank(text: &str) -> bool {
+    text.lines().all(|line| {
+        let line = line.trim();
+        line.is_empty() || line.starts_with("//")
+    })
+}
+
+/// A range of a text with everything but line breaks replaced by spaces
+fn blank(text: &str, range: Range<usize>) -> String {
+    text[range].chars().map(|c| if c == '\n' { '\n' } else { ' ' }).collect()
+}
+
+/// A text with a range blanked out, keeping every position
+fn blank_range(text: &str, range: Range<usize>) -> String {
+    let mut blanked = text[..range.start].to_string();
+    blanked.push_str(&blank(text, range.clone()));
+    blanked.push_str(&text[range.end..]);
+    blanked
+}
+
+/// Byte offset of a line and column, both from 1
+fn offset(text: &str, line: usize, column: usize) -> Option<usize> {
+    let line_start = if line == 1 {
+        0
+    } else {
+        text.match_indices('\n').nth(line - 2)?.0 + 1
+    };
+    let line_text = &text[line_start..];
+    let column = line_text
+        .char_indices()
+        .nth(column - 1)
+        .map_or(line_text.len(), |(i, _)| i);
+    Some(line_start + column)
+}
@@ -0,0 +1,179 @@
+//! Project manifests (`kumeo.toml`).
+//!
+//! A `kumeo.toml` at the root of a project says what the subcommands work
+//! on, so they can be run without flags:
+//!
+//! ```toml
+//! entry = ["workflows/fraud.kumeo"]
+//! output = "build"
+//! targets = ["rust", "python"]
+//! templates = "templates"
+//! vendor_dir = "vendor"
+//! offline = false
+//! mirrors = ["https://models.example.com=https://mirror.internal/models"]
+//! quotas = "platform/quotas.yaml"
+//! console = true
+//!
+//! [deployment]
+//! namespace = "fraud"
+//! external_nats = "nats://nats.shared:4222"
+//! nats_credentials = "nats-creds"
+//! registry = "ghcr.io/acme"
+//! tag = "1.4.0"
+//! ```
+//!
+//! Paths are relative to the manifest's directory. Every setting is
+//! optional, and the flags of a subcommand win over the manifest. The
+//! manifest is looked for in the current directory and its parents.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::message;
+use crate::vendor::mirror::MirrorRule;
+
+/// Name of the project manifest
+pub const MANIFEST_FILE: &str = "kumeo.toml";
+
+/// Languages agents are generated in
+pub const TARGET_LANGUAGES: [&str; 2] = ["rust", "python"];
+
+/// Where and how the generated project is deployed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeploymentSettings {
+    /// Kubernetes namespace the workflows are deployed to
+    pub namespace: Option<String>,
+    /// URL of an existing NATS the agents connect to instead of deploying one
+    pub external_nats: Option<String>,
+    /// Secret with the credentials of the external NATS
+    pub nats_credentials: Option<String>,
+    /// Registry the agent images are pushed to
+    pub registry: Option<String>,
+    /// Tag of the agent images
+    pub tag: Option<String>,
+}
+
+/// A project manifest, as read from a `kumeo.toml` file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectManifest {
+    /// Kumeo files compiled by the project
+    pub entry: Vec<PathBuf>,
+    /// Directory the code is generated in
+    pub output: Option<PathBuf>,
+    /// Languages agents may be generated in; every language when empty
+    pub targets: Vec<String>,
+    /// Directory of templates overriding the user's and the built-in ones, with the same layout; `templates` when it exists
+    pub templates: Option<PathBuf>,
+    /// Directory of the vendored resources
+    pub vendor_dir: Option<PathBuf>,
+    /// Whether every remote resource must be vendored
+    pub offline: bool,
+    /// Mirror rules of remote resources, as `FROM=TO`
+    pub mirrors: Vec<String>,
+    /// Quotas file of the platform the namespaces' workflows must fit in
+    pub quotas: Option<PathBuf>,
+    /// Whether `generate` also generates the read-only web console of the workflows
+    pub console: bool,
+    /// Deployment settings
+    pub deployment: DeploymentSettings,
+}
+
+impl ProjectManifest {
+    /// Read a manifest from the contents of a `kumeo.toml` file
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let manifest: Self = config::Config::builder()
+            .add_source(config::File::from_str(text, config::FileFormat::Toml))
+            .build()
+            .and_then(config::Config::try_deserialize)
+            .map_err(|e| anyhow!(message!("Invalid project manifest: {}", e)))?;
+        if let Some(target) = manifest.targets.iter().find(|target| !TARGET_LANGUAGES.contains(&target.as_str())) {
+            return Err(anyhow!(message!(
+                "Invalid project manifest: unknown target '{}' (expected {})",
+                target,
+                TARGET_LANGUAGES.join(" or ")
+            )));
+        }
+        manifest.mirror_rules().context(message!("Invalid project manifest"))?;
+        Ok(manifest)
+    }
+
+    /// The mirror rules of the manifest
+    pub fn mirror_rules(&self) -> Result<Vec<MirrorRule>> {
+        self.mirrors.iter().map(|rule| rule.parse()).collect()
+    }
+
+    /// Whether agents may be generated in a language
+    pub fn targets_language(&self, language: &str) -> bool {
+        self.targets.is_empty() || self.targets.iter().any(|target| target == language)
+    }
+}
+
+/// A project: its manifest and the directory its paths are relative to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Project {
+    /// Directory of the manifest
+    pub root: PathBuf,
+    /// The manifest
+    pub manifest: ProjectManifest,
+}
+
+impl Project {
+    /// Read the project of a `kumeo.toml` file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| message!("Failed to read {}", path.display()))?;
+        let manifest = ProjectManifest::from_toml(&text).with_context(|| message!("In {}", path.display()))?;
+        let root = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        Ok(Self { root: root.to_path_buf(), manifest })
+    }
+
+    /// The project of the closest `kumeo.toml` in a directory or one of its parents, if any
+    pub fn discover(dir: &Path) -> Result<Option<Self>> {
+        let dir = std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        match dir.ancestors().map(|dir| dir.join(MANIFEST_FILE)).find(|path| path.is_file()) {
+            Some(path) => Self::load(&path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The project of an explicit manifest, or else the one found from the current directory
+    pub fn find(manifest: Option<&Path>) -> Result<Option<Self>> {
+        match manifest {
+            Some(path) => Self::load(path).map(Some),
+            None => Self::discover(&std::env::current_dir()?),
+        }
+    }
+
+    /// A path of the manifest, relative to the project's root
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        self.root.join(path)
+    }
+
+    /// The entry files of the project
+    pub fn entries(&self) -> Vec<PathBuf> {
+        self.manifest.entry.iter().map(|entry| self.resolve(entry)).collect()
+    }
+
+    /// Directory the code is generated in, if the manifest sets one
+    pub fn output_dir(&self) -> Option<PathBuf> {
+        self.manifest.output.as_deref().map(|path| self.resolve(path))
+    }
+
+    /// Directory of the template overrides, if the manifest sets one
+    pub fn templates_dir(&self) -> Option<PathBuf> {
+        self.manifest.templates.as_deref().map(|path| self.resolve(path))
+    }
+
+    /// Directory of the vendored resources, if the manifest sets one
+    pub fn vendor_dir(&self) -> Option<PathBuf> {
+        self.manifest.vendor_dir.as_deref().map(|path| self.resolve(path))
+    }
+
+    /// Quotas file of the platform, if the manifest sets one
+    pub fn quotas_file(&self) -> Option<PathBuf> {
+        self.manifest.quotas.as_deref().map(|path| self.resolve(path))
+    }
+}
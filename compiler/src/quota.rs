@@ -0,0 +1,210 @@
+//! Platform quotas
+//!
+//! Caps what the workflows deployed to each Kubernetes namespace may request
+//! from the cluster, from a quotas file kept by the platform team:
+//!
+//! ```yaml
+//! default: { cpu: "8", memory: "16Gi", replicas: 40 }
+//! namespaces:
+//!   fraud: { cpu: "4", memory: "8Gi", replicas: 20 }
+//!   kumeo: { replicas: 10 }
+//! ```
+//!
+//! A namespace without quotas of its own takes the `default` ones, and is
+//! not capped without them; every limit is optional. The workflows of a
+//! namespace are summed at their peak: the CPU and memory estimated by
+//! [`crate::stats`] for each replica of their agents, with as many replicas
+//! as their `scaling` lets the autoscaler run, or else their `replicas`.
+//! `replicas` caps the pods of the namespace.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::ast::Workflow;
+use crate::codegen::kubernetes::workflow_namespace;
+use crate::diagnostics::{codes, Diagnostic};
+use crate::i18n::Message;
+use crate::message;
+use crate::stats::{agent_requests, parse_cpu_millis, parse_memory_mib, Footprint};
+
+/// Quotas of a quotas file, with their quantities read
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Quotas {
+    /// Quota of the namespaces not listed
+    pub default: Option<Quota>,
+    /// Quotas by namespace
+    pub namespaces: BTreeMap<String, Quota>,
+}
+
+/// Most a namespace may request; unlimited where `None`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Quota {
+    /// CPU, in millicores
+    pub cpu_millis: Option<u64>,
+    /// Memory, in MiB
+    pub memory_mib: Option<u64>,
+    /// Pods, one per replica of each agent
+    pub pods: Option<u64>,
+}
+
+/// A quota as written in a quotas file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuotaEntry {
+    cpu: Option<String>,
+    memory: Option<String>,
+    replicas: Option<u64>,
+}
+
+/// A quotas file as written
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuotasFile {
+    default: Option<QuotaEntry>,
+    #[serde(default)]
+    namespaces: BTreeMap<String, QuotaEntry>,
+}
+
+impl QuotaEntry {
+    /// Read the quantities of the quota of a namespace
+    fn read(self, namespace: &str) -> Result<Quota> {
+        let cpu_millis = self
+            .cpu
+            .map(|cpu| parse_cpu_millis(&cpu).ok_or_else(|| anyhow!(message!("Invalid CPU quota '{}' of {}", cpu, namespace))))
+            .transpose()?;
+        let memory_mib = self
+            .memory
+            .map(|memory| parse_memory_mib(&memory).ok_or_else(|| anyhow!(message!("Invalid memory quota '{}' of {}", memory, namespace))))
+            .transpose()?;
+        Ok(Quota { cpu_millis, memory_mib, pods: self.replicas })
+    }
+}
+
+impl Quotas {
+    /// Read a quotas file
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| message!("Failed to read the quotas file {}", path.display()))?;
+        Self::from_yaml(&source).with_context(|| message!("Invalid quotas file {}", path.display()))
+    }
+
+    /// Read the YAML of a quotas file
+    pub fn from_yaml(source: &str) -> Result<Self> {
+        let file: QuotasFile = serde_yaml::from_str(source)?;
+        let default = file.default.map(|quota| quota.read("the default")).transpose()?;
+        let namespaces = file
+            .namespaces
+            .into_iter()
+            .map(|(namespace, quota)| {
+                let quota = quota.read(&format!("namespace {}", namespace))?;
+                Ok((namespace, quota))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { default, namespaces })
+    }
+
+    /// Quota of a namespace: its own, or else the default one
+    pub fn quota(&self, namespace: &str) -> Option<Quota> {
+        self.namespaces.get(namespace).or(self.default.as_ref()).copied()
+    }
+}
+
+/// Peak footprint of the workflows of a namespace, against its quota
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NamespaceUsage {
+    /// Name of the namespace
+    pub namespace: String,
+    /// Workflows deployed to it, in program order
+    pub workflows: Vec<String>,
+    /// What they request at their peak
+    pub peak: Footprint,
+    /// Its quota, if capped
+    pub quota: Option<Quota>,
+}
+
+/// Peak footprint of each namespace of a set of workflows, by namespace
+pub fn namespace_usage<'a>(workflows: impl IntoIterator<Item = &'a Workflow>, quotas: &Quotas) -> Result<Vec<NamespaceUsage>> {
+    let mut usage: BTreeMap<&str, NamespaceUsage> = BTreeMap::new();
+    for workflow in workflows {
+        let namespace = workflow_namespace(workflow);
+        let entry = usage.entry(namespace).or_insert_with(|| NamespaceUsage {
+            namespace: namespace.to_string(),
+            workflows: Vec::new(),
+            peak: Footprint::default(),
+            quota: quotas.quota(namespace),
+        });
+        entry.workflows.push(workflow.name.clone());
+        entry.peak += peak_footprint(workflow)?;
+    }
+    Ok(usage.into_values().collect())
+}
+
+/// Footprint of the agents of a workflow with the most replicas it may run
+fn peak_footprint(workflow: &Workflow) -> Result<Footprint> {
+    let deployment = workflow.deployment.as_ref();
+    let replicas = deployment
+        .and_then(|deployment| deployment.scaling.as_ref())
+        .map(|scaling| scaling.max_replicas)
+        .or_else(|| deployment.and_then(|deployment| deployment.replicas))
+        .map_or(1, u64::from);
+    let mut footprint = Footprint::default();
+    for agent in &workflow.agents {
+        let (cpu_millis, memory_mib) = agent_requests(workflow, agent)?;
+        footprint += Footprint {
+            pods: replicas,
+            cpu_millis: replicas * cpu_millis,
+            memory_mib: replicas * memory_mib,
+        };
+    }
+    Ok(footprint)
+}
+
+impl NamespaceUsage {
+    /// The quotas the namespace exceeds, as messages
+    pub fn violations(&self) -> Vec<Message> {
+        let Some(quota) = self.quota else {
+            return Vec::new();
+        };
+        let workflows = self.workflows.join(", ");
+        let mut violations = Vec::new();
+        if let Some(limit) = quota.cpu_millis.filter(|limit| self.peak.cpu_millis > *limit) {
+            violations.push(message!(
+                "Namespace {} requests {}m of CPU at its peak, above its quota of {}m (workflows {})",
+                self.namespace, self.peak.cpu_millis, limit, workflows
+            ));
+        }
+        if let Some(limit) = quota.memory_mib.filter(|limit| self.peak.memory_mib > *limit) {
+            violations.push(message!(
+                "Namespace {} requests {}Mi of memory at its peak, above its quota of {}Mi (workflows {})",
+                self.namespace, self.peak.memory_mib, limit, workflows
+            ));
+        }
+        if let Some(limit) = quota.pods.filter(|limit| self.peak.pods > *limit) {
+            violations.push(message!(
+                "Namespace {} runs up to {} pods, above its quota of {} replicas (workflows {})",
+                self.namespace, self.peak.pods, limit, workflows
+            ));
+        }
+        violations
+    }
+}
+
+/// Diagnostics of the namespaces of a program exceeding their quotas, at their first workflow
+pub fn check_quotas(workflows: &[Workflow], quotas: &Quotas) -> Result<Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+    for usage in namespace_usage(workflows, quotas)? {
+        let span = workflows
+            .iter()
+            .find(|workflow| usage.workflows.first() == Some(&workflow.name))
+            .map(|workflow| workflow.span.clone())
+            .unwrap_or_default();
+        diagnostics.extend(usage.violations().into_iter().map(|message| {
+            Diagnostic::error(codes::QUOTA_EXCEEDED, message)
+                .at(&span)
+                .with_help(message!("lower the replicas, scaling or resources of their deployment blocks, or ask for a larger quota"))
+        }));
+    }
+    Ok(diagnostics)
+}
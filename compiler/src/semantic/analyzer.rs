@@ -1,10 +1,18 @@
 //! Implementación del analizador semántico para Kumeo.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
+use super::catalog::{self, SchemaCatalog};
+use super::config_schema::ConfigSchemas;
+use super::versioning;
 use crate::{
     ast::*,
+    codegen::kubernetes::BLUE_GREEN_SLOTS,
+    diagnostics::{closest, codes, Diagnostic},
+    i18n::Message,
+    message,
     error::{KumeoError, Result},
+    simulator::Outcome,
 };
 
 /// Analizador semántico para programas Kumeo.
@@ -12,12 +20,63 @@ use crate::{
 pub struct SemanticAnalyzer {
     /// IDs de agentes definidos (para detectar duplicados)
     agent_ids: HashSet<String>,
+    /// IDs de agentes del programa y los propuestos a los agentes sin ID, que no se repiten
+    declared_agent_ids: HashSet<String>,
     /// Nombres de workflows definidos
     workflow_names: HashSet<String>,
-    /// Nombres de subworkflows definidos
-    subworkflow_names: HashSet<String>,
-    /// Errores encontrados durante el análisis
-    errors: Vec<KumeoError>,
+    /// Subworkflows definidos, por nombre
+    subworkflows: HashMap<String, Subworkflow>,
+    /// Errores y avisos encontrados durante el análisis
+    diagnostics: Vec<Diagnostic>,
+    /// Versiones registradas de los esquemas de mensajes
+    schema_catalog: SchemaCatalog,
+    /// Esquemas declarados de los mensajes de cada topic
+    message_schemas: HashMap<String, Schema>,
+    /// Topics que consume algún workflow del programa o de otros programas registrados
+    consumed_topics: HashSet<String>,
+    /// Esquemas de configuración de cada tipo de agente
+    config_schemas: ConfigSchemas,
+    /// Versión anterior del programa, con la que se comprueban las versiones de los workflows
+    baseline: Option<Program>,
+}
+
+/// Paso del flujo de datos de un workflow: un agente o un subworkflow invocado.
+struct DataflowNode<'a> {
+    name: String,
+    consumes: Vec<&'a str>,
+    produces: Vec<&'a str>,
+    /// Si el paso filtra mensajes con `when`
+    guarded: bool,
+}
+
+/// Tipo conocido de una expresión de condición.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConditionType {
+    Boolean,
+    Number,
+    String,
+    Null,
+}
+
+impl ConditionType {
+    /// Tipo de un campo declarado en un esquema; los objetos y listas no se comparan.
+    fn of_field(field_type: &str) -> Option<Self> {
+        match field_type.trim_end_matches('?') {
+            "string" => Some(ConditionType::String),
+            "number" | "integer" => Some(ConditionType::Number),
+            "boolean" => Some(ConditionType::Boolean),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> Message {
+        match self {
+            ConditionType::Boolean => message!("un booleano"),
+            ConditionType::Number => message!("un número"),
+            ConditionType::String => message!("un texto"),
+            ConditionType::Null => Message::from("null"),
+        }
+    }
 }
 
 impl Default for SemanticAnalyzer {
@@ -31,72 +90,190 @@ impl SemanticAnalyzer {
     pub fn new() -> Self {
         Self {
             agent_ids: HashSet::new(),
+            declared_agent_ids: HashSet::new(),
             workflow_names: HashSet::new(),
-            subworkflow_names: HashSet::new(),
-            errors: Vec::new(),
+            subworkflows: HashMap::new(),
+            diagnostics: Vec::new(),
+            schema_catalog: SchemaCatalog::default(),
+            message_schemas: HashMap::new(),
+            consumed_topics: HashSet::new(),
+            config_schemas: ConfigSchemas::builtin(),
+            baseline: None,
         }
     }
 
+    /// Compara los esquemas de los agentes con las versiones de un catálogo.
+    pub fn with_schema_catalog(mut self, schema_catalog: SchemaCatalog) -> Self {
+        self.schema_catalog = schema_catalog;
+        self
+    }
+
+    /// Comprueba que la versión de cada workflow sube según sus cambios respecto a otro programa.
+    pub fn with_baseline(mut self, baseline: Program) -> Self {
+        self.baseline = Some(baseline);
+        self
+    }
+
+    /// Comprueba la configuración de los agentes con otros esquemas que los incluidos.
+    pub fn with_config_schemas(mut self, config_schemas: ConfigSchemas) -> Self {
+        self.config_schemas = config_schemas;
+        self
+    }
+
+    /// Avisos del último análisis, que no invalidan el programa.
+    pub fn warnings(&self) -> Vec<String> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| !diagnostic.is_error())
+            .map(|diagnostic| diagnostic.message.to_string())
+            .collect()
+    }
+
+    /// Errores y avisos del último análisis, con su código y posición.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
     /// Realiza el análisis semántico de un programa completo.
     pub fn analyze_program(&mut self, program: &Program) -> Result<()> {
         self.reset();
 
+        // Resolver constantes: el resto del análisis ve los valores sustituidos
+        let program = &self.resolve_constants(program);
+
+        // IDs de agentes del programa, que los propuestos para los agentes sin ID evitan
+        let agents = program.workflows.iter().flat_map(|workflow| {
+            workflow.agents.iter().chain(workflow.preprocessors.iter().flatten())
+        });
+        let ids = agents.chain(program.subworkflows.iter().flat_map(|subworkflow| &subworkflow.agents));
+        self.declared_agent_ids.extend(ids.filter_map(|agent| agent.id.clone()));
+
+        // Esquemas de los topics: los registrados y, por encima, los del programa
+        for (topic, schema) in &self.schema_catalog.topics {
+            if let Some((_, fields)) = schema.versions.last_key_value() {
+                self.message_schemas.insert(topic.clone(), catalog::schema_from_fields(fields));
+            }
+        }
+        for workflow in &program.workflows {
+            self.message_schemas.extend(workflow.message_schemas());
+        }
+        self.validate_topic_schemas(program);
+        self.message_schemas.extend(program.topic_schemas());
+
+        // Topics consumidos por este programa y por los registrados en el catálogo
+        let names: HashSet<&str> = program.workflows.iter().map(|workflow| workflow.name.as_str()).collect();
+        for (topic, schemas) in &self.schema_catalog.topics {
+            if schemas.consumers.iter().any(|consumer| !names.contains(consumer.as_str())) {
+                self.consumed_topics.insert(topic.clone());
+            }
+        }
+        for workflow in &program.workflows {
+            self.consumed_topics.extend(catalog::consumed_topics(workflow));
+        }
+
         // Validar nombres únicos de workflows y subworkflows
         let mut all_names = HashSet::new();
         
         for workflow in &program.workflows {
             if !all_names.insert(&workflow.name) {
-                self.errors.push(KumeoError::SemanticError(
-                    format!("Nombre de workflow duplicado: {}", workflow.name),
-                ));
+                self.report(
+                    Diagnostic::error(codes::DUPLICATE_NAME, message!("Nombre de workflow duplicado: {}", workflow.name))
+                        .at(&workflow.span),
+                );
             }
             self.workflow_names.insert(workflow.name.clone());
         }
 
         for subworkflow in &program.subworkflows {
             if !all_names.insert(&subworkflow.name) {
-                self.errors.push(KumeoError::SemanticError(
-                    format!("Nombre de subworkflow duplicado: {}", subworkflow.name),
-                ));
+                self.report(
+                    Diagnostic::error(codes::DUPLICATE_NAME, message!("Nombre de subworkflow duplicado: {}", subworkflow.name))
+                        .at(&subworkflow.span),
+                );
             }
-            self.subworkflow_names.insert(subworkflow.name.clone());
+            self.subworkflows.insert(subworkflow.name.clone(), subworkflow.clone());
         }
 
         // Validar cada workflow
         for workflow in &program.workflows {
+            let from = self.diagnostics.len();
             self.analyze_workflow(workflow)?;
+            self.locate_errors(from, &workflow.span);
         }
 
         // Validar cada subworkflow
         for subworkflow in &program.subworkflows {
+            let from = self.diagnostics.len();
             self.analyze_subworkflow(subworkflow)?;
+            self.locate_errors(from, &subworkflow.span);
         }
 
-        // Crear una copia de los errores para no mover self
-        let errors = self.errors.clone();
-        
+        self.validate_schema_evolution(program);
+        self.validate_message_contracts(program);
+        self.validate_encodings(program);
+        self.validate_version_bumps(program);
+
+        // Los errores se devuelven ya renderizados, con su código y su línea
+        let errors: Vec<String> = self
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.is_error())
+            .map(ToString::to_string)
+            .collect();
         if errors.is_empty() {
             Ok(())
         } else {
-            let error_messages: Vec<String> = errors.iter()
-                .map(|e| e.to_string())
-                .collect();
-            Err(KumeoError::SemanticErrors(error_messages))
+            Err(KumeoError::SemanticErrors(errors))
+        }
+    }
+
+    /// Valida las constantes del programa y devuelve una copia con sus
+    /// referencias sustituidas e interpoladas.
+    fn resolve_constants(&mut self, program: &Program) -> Program {
+        let mut names = HashSet::new();
+        for constant in &program.constants {
+            if !names.insert(&constant.name) {
+                self.report(
+                    Diagnostic::error(codes::DUPLICATE_NAME, message!("Constante duplicada: {}", constant.name))
+                        .at(&constant.span),
+                );
+            }
+        }
+
+        let mut resolved = program.clone();
+        if let Err(name) = resolved.substitute_constants() {
+            let declared = program.constants.iter().map(|constant| constant.name.as_str());
+            self.report(
+                Diagnostic::error(codes::UNDEFINED_CONSTANT, message!("Constante no definida: ${}", name))
+                    .with_suggestion(closest(&name, declared).map(|constant| format!("${}", constant))),
+            );
+        } else if let Err(e) = resolved.interpolate_constants() {
+            self.error(codes::INVALID_INTERPOLATION, message!("Interpolación inválida: {}", e));
         }
+        resolved
     }
 
     /// Analiza un workflow individual.
     pub fn analyze_workflow(&mut self, workflow: &Workflow) -> Result<()> {
         // Validar nombre
-        self.validate_identifier(&workflow.name, "workflow")?;
+        self.validate_identifier(&workflow.name, "workflow");
+
+        // Validar versión
+        if let Some(version) = &workflow.version {
+            if versioning::Version::parse(version).is_none() {
+                self.error(codes::INVALID_VERSION, message!(
+                    "La versión '{}' del workflow {} no es una versión semántica MAJOR.MINOR.PATCH",
+                    version, workflow.name
+                ));
+            }
+        }
 
         // Validar fuente
         if let Some(source) = &workflow.source {
             self.validate_source(source)?;
+            self.validate_source_auth(workflow, source);
         } else {
-            self.errors.push(KumeoError::SemanticError(
-                "El workflow debe tener una fuente de datos".to_string(),
-            ));
+            self.error(codes::MISSING_SOURCE, message!("El workflow debe tener una fuente de datos"));
         }
 
         // Validar destino
@@ -104,55 +281,266 @@ impl SemanticAnalyzer {
             self.validate_target(target)?;
         }
 
-        // Validar agentes
+        // Validar agentes con el esquema de los mensajes que consumen
+        for (agent, topics) in workflow.agents.iter().zip(workflow.deployed_topics()) {
+            let input_schema = match agent.input_schema() {
+                Ok(Some(schema)) => Some(schema),
+                _ => topics.input.and_then(|topic| self.message_schemas.get(&topic)).cloned(),
+            };
+            let from = self.diagnostics.len();
+            self.validate_agent(agent, input_schema.as_ref())?;
+            self.locate_errors(from, &agent.span);
+        }
+
+        // Los pasos de una saga comparten el campo con su ID de correlación
+        let mut correlation: Option<Vec<String>> = None;
         for agent in &workflow.agents {
-            self.validate_agent(agent)?;
+            let Ok(Some(config)) = CompensationConfig::from_agent(agent) else {
+                continue;
+            };
+            match &correlation {
+                Some(first) if *first != config.correlation => self.report(
+                    Diagnostic::error(
+                        codes::INVALID_POLICY,
+                        message!(
+                            "El agente {} correlaciona su saga por {}, pero los pasos anteriores por {}",
+                            agent_name(agent),
+                            config.correlation.join("."),
+                            first.join(".")
+                        ),
+                    )
+                    .at(&agent.span)
+                    .with_help(message!("todos los compensate de un workflow deben usar el mismo correlation")),
+                ),
+                Some(_) => {}
+                None => correlation = Some(config.correlation),
+            }
+        }
+
+        // Validar invocaciones de subworkflows
+        for call in &workflow.calls {
+            self.validate_call(call);
         }
 
         // Validar preprocesadores
         if let Some(preprocessors) = &workflow.preprocessors {
             for preprocessor in preprocessors {
-                self.validate_agent(preprocessor)?;
+                let from = self.diagnostics.len();
+                self.validate_agent(preprocessor, None)?;
+                self.locate_errors(from, &preprocessor.span);
+            }
+        }
+
+        // Validar el cableado de topics entre agentes
+        if workflow.is_wired() {
+            self.validate_topics(workflow);
+        }
+
+        // Validar precarga de modelos
+        for agent in &workflow.agents {
+            let from = self.diagnostics.len();
+            self.validate_preload(workflow, agent);
+            self.locate_option(from, agent, PRELOAD_OPTION);
+            self.locate_errors(from, &agent.span);
+        }
+
+        // Validar los revisores de los guardrails
+        for agent in &workflow.agents {
+            let from = self.diagnostics.len();
+            self.validate_guardrail_review(workflow, agent);
+            self.locate_option(from, agent, GUARDRAILS_OPTION);
+            self.locate_errors(from, &agent.span);
+        }
+
+        // Validar modo batch
+        if workflow.mode == WorkflowMode::Batch {
+            self.validate_batch(workflow);
+        }
+
+        // Validar despliegue
+        if let Some(deployment) = &workflow.deployment {
+            self.validate_deployment(workflow, deployment);
+        }
+
+        // Validar tests
+        let mut test_names = HashSet::new();
+        for test in &workflow.tests {
+            let from = self.diagnostics.len();
+            if !test_names.insert(test.name.as_str()) {
+                self.error(codes::DUPLICATE_NAME, message!("Test duplicado: \"{}\"", test.name));
             }
+            self.validate_test(workflow, test);
+            self.locate_errors(from, &test.span);
         }
 
         Ok(())
     }
 
+    /// Valida un bloque `test` de un workflow.
+    fn validate_test(&mut self, workflow: &Workflow, test: &WorkflowTest) {
+        // Cada problema con el nombre que probablemente se quiso escribir
+        let mut problems: Vec<(Message, Option<String>)> = Vec::new();
+
+        // Los agentes de los subworkflows invocados se nombran al expandirlos
+        let tested = workflow.agents.iter().any(|agent| agent.id.as_deref() == Some(test.agent.as_str()));
+        if !tested && workflow.calls.is_empty() {
+            let agents = workflow.agents.iter().filter_map(|agent| agent.id.as_deref());
+            problems.push((
+                message!("el agente {} no existe en el workflow", test.agent),
+                closest(&test.agent, agents).map(str::to_string),
+            ));
+        }
+        if !matches!(test.given, Value::Object(_)) {
+            problems.push((message!("el mensaje debe ser un objeto, no {}", test.given), None));
+        }
+
+        if test.expect.is_empty() {
+            problems.push((message!("debe esperar un '{}' o un '{}'", EXPECT_TOPIC, EXPECT_OUTCOME), None));
+        }
+        let mut expected: Vec<_> = test.expect.iter().collect();
+        expected.sort_by_key(|(key, _)| key.as_str());
+        for (key, value) in expected {
+            match (key.as_str(), value) {
+                (EXPECT_TOPIC, Value::String(_) | Value::Null) => {}
+                (EXPECT_TOPIC, _) => problems.push((message!("'{}' debe ser un topic o null, no {}", key, value), None)),
+                (EXPECT_OUTCOME, Value::String(outcome)) if Outcome::parse(outcome).is_some() => {}
+                (EXPECT_OUTCOME, _) => {
+                    let outcomes: Vec<String> = Outcome::ALL.iter().map(|outcome| format!("\"{}\"", outcome)).collect();
+                    let (last, others) = outcomes.split_last().expect("hay resultados posibles");
+                    let suggestion = match value {
+                        Value::String(outcome) => closest(outcome, Outcome::ALL.iter().map(|outcome| outcome.as_str())),
+                        _ => None,
+                    };
+                    problems.push((
+                        message!("'{}' debe ser {} o {}, no {}", key, others.join(", "), last, value),
+                        suggestion.map(|outcome| format!("\"{}\"", outcome)),
+                    ));
+                }
+                _ => problems.push((
+                    message!("expectativa desconocida '{}'", key),
+                    closest(key, [EXPECT_TOPIC, EXPECT_OUTCOME]).map(str::to_string),
+                )),
+            }
+        }
+
+        for (problem, suggestion) in problems {
+            self.report(
+                Diagnostic::error(
+                    codes::INVALID_TEST,
+                    message!("Test \"{}\" inválido en el workflow {}: {}", test.name, workflow.name, problem),
+                )
+                .with_suggestion(suggestion),
+            );
+        }
+    }
+
     /// Analiza un subworkflow individual.
     pub fn analyze_subworkflow(&mut self, subworkflow: &Subworkflow) -> Result<()> {
         // Validar nombre
-        self.validate_identifier(&subworkflow.name, "subworkflow")?;
+        self.validate_identifier(&subworkflow.name, "subworkflow");
 
         // Validar agentes
         for agent in &subworkflow.agents {
-            self.validate_agent(agent)?;
+            let from = self.diagnostics.len();
+            self.validate_agent(agent, None)?;
+            self.locate_errors(from, &agent.span);
         }
 
         Ok(())
     }
 
+    /// Valida que un `use` invoque un subworkflow definido con sus entradas y salidas.
+    fn validate_call(&mut self, call: &SubworkflowCall) {
+        let Some(subworkflow) = self.subworkflows.get(&call.name) else {
+            let declared = self.subworkflows.keys().map(String::as_str);
+            let suggestion = closest(&call.name, declared).map(str::to_string);
+            self.report(
+                Diagnostic::error(codes::UNDEFINED_SUBWORKFLOW, message!("Subworkflow no definido: {}", call.name))
+                    .with_suggestion(suggestion),
+            );
+            return;
+        };
+
+        let ports = [
+            (message!("entradas"), subworkflow.input.as_deref().unwrap_or_default(), &call.input),
+            (message!("salidas"), subworkflow.output.as_deref().unwrap_or_default(), &call.output),
+        ];
+        let mismatches: Vec<Diagnostic> = ports
+            .into_iter()
+            .filter(|(_, declared, bound)| declared.len() != bound.len())
+            .map(|(kind, declared, bound)| {
+                Diagnostic::error(
+                    codes::SUBWORKFLOW_PORTS,
+                    message!(
+                        "use {}: el subworkflow espera {} {} ({}) y recibe {}",
+                        call.name,
+                        declared.len(),
+                        kind,
+                        declared.join(", "),
+                        bound.len()
+                    ),
+                )
+            })
+            .collect();
+        self.diagnostics.extend(mismatches);
+    }
+
     /// Valida una fuente de datos.
     fn validate_source(&mut self, source: &Source) -> Result<()> {
         match source {
             Source::NATS(topic, _) => {
                 if topic.trim().is_empty() {
-                    self.errors.push(KumeoError::SemanticError(
-                        "El tema de NATS no puede estar vacío".to_string(),
-                    ));
+                    self.error(codes::INVALID_TOPIC, message!("El tema de NATS no puede estar vacío"));
                 }
             }
+            Source::Kafka(topic, _) => self.validate_kafka_topic(topic),
+            Source::MQTT(topic, _) => self.validate_mqtt_topic(topic, true),
+            Source::HTTP(path, _) => self.validate_webhook(path, source.option(WEBHOOK_METHOD_OPTION)),
+            Source::File(pattern, _) => self.validate_file_source(pattern, source.option(FILE_WATCH_OPTION)),
         }
         Ok(())
     }
 
+    /// Valida la autenticación de la fuente: solo la admiten las fuentes HTTP,
+    /// y solo si sirve el webhook un agente generado en Rust.
+    fn validate_source_auth(&mut self, workflow: &Workflow, source: &Source) {
+        if source.option(AUTH_OPTION).is_none() {
+            return;
+        }
+        if !matches!(source, Source::HTTP(..)) {
+            self.error(codes::INVALID_SOURCE, message!("Solo las fuentes HTTP admiten auth"));
+            return;
+        }
+        if let Err(e) = OidcAuthConfig::from_source(source) {
+            self.error(codes::INVALID_SOURCE, message!("Autenticación inválida en la fuente HTTP: {}", e));
+        }
+        if let Some(agent) = workflow.agents.first() {
+            if matches!(agent.agent_type, AgentType::MLModel | AgentType::QualityMonitor) {
+                self.error(codes::INVALID_SOURCE, message!(
+                    "El webhook de {} lo sirve el agente {} ({}), que se genera en Python; auth solo está disponible en los agentes Rust",
+                    source.topic(),
+                    agent_name(agent),
+                    agent.agent_type
+                ));
+            }
+        }
+    }
+
     /// Valida un destino de datos.
     fn validate_target(&mut self, target: &Target) -> Result<()> {
         match target {
             Target::NATS(topic, _) => {
                 if topic.trim().is_empty() {
-                    self.errors.push(KumeoError::SemanticError(
-                        "El tema de NATS no puede estar vacío".to_string(),
+                    self.error(codes::INVALID_TOPIC, message!("El tema de NATS no puede estar vacío"));
+                }
+            }
+            Target::Kafka(topic, _) => self.validate_kafka_topic(topic),
+            Target::MQTT(topic, _) => self.validate_mqtt_topic(topic, false),
+            Target::File(directory, _) => {
+                if !directory.starts_with('/') || directory.contains(['*', '?']) {
+                    self.error(codes::INVALID_TARGET, message!(
+                        "El destino File debe ser un directorio absoluto sin comodines: '{}'",
+                        directory
                     ));
                 }
             }
@@ -160,99 +548,2149 @@ impl SemanticAnalyzer {
         Ok(())
     }
 
-    /// Valida un agente.
-    fn validate_agent(&mut self, agent: &Agent) -> Result<()> {
-        // Validar ID único
-        if let Some(id) = &agent.id {
-            if !self.agent_ids.insert(id.clone()) {
-                self.errors.push(KumeoError::SemanticError(
-                    format!("ID de agente duplicado: {}", id),
+    /// Valida el nombre de un tema de Kafka.
+    fn validate_kafka_topic(&mut self, topic: &str) {
+        if topic.is_empty() {
+            self.error(codes::INVALID_TOPIC, message!("El tema de Kafka no puede estar vacío"));
+        } else if topic.len() > 249
+            || topic == "."
+            || topic == ".."
+            || !topic.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        {
+            self.error(codes::INVALID_TOPIC, message!(
+                "Tema de Kafka inválido: '{}' (solo letras, números, '.', '_' y '-', hasta 249 caracteres)",
+                topic
+            ));
+        }
+    }
+
+    /// Valida el patrón de una fuente File.
+    fn validate_file_source(&mut self, pattern: &str, watch: Option<&str>) {
+        // Se vigila un único directorio, así que los comodines solo pueden ir en el nombre
+        let directory = pattern.rsplit_once('/').map_or("", |(directory, _)| directory);
+        if !pattern.starts_with('/') || directory.contains(['*', '?']) {
+            self.error(codes::INVALID_SOURCE, message!(
+                "Patrón File inválido: '{}' (debe ser absoluto y solo admite comodines en el nombre del archivo)",
+                pattern
+            ));
+        }
+
+        if let Some(watch) = watch.filter(|watch| !matches!(*watch, "true" | "false")) {
+            self.error(codes::INVALID_SOURCE, message!(
+                "La opción watch debe ser true o false, no '{}'",
+                watch
+            ));
+        }
+    }
+
+    /// Valida la ruta y el método de una fuente HTTP.
+    fn validate_webhook(&mut self, path: &str, method: Option<&str>) {
+        if !path.starts_with('/') || path.contains(|c: char| c.is_whitespace() || c == '?' || c == '#') {
+            self.error(codes::INVALID_SOURCE, message!(
+                "Ruta HTTP inválida: '{}' (debe empezar por '/' y no llevar espacios, '?' ni '#')",
+                path
+            ));
+        }
+
+        if let Some(method) = method {
+            if !matches!(method.to_ascii_uppercase().as_str(), "POST" | "PUT" | "PATCH") {
+                self.error(codes::INVALID_SOURCE, message!(
+                    "Método HTTP no soportado para webhooks: '{}' (usa POST, PUT o PATCH)",
+                    method
                 ));
             }
-        } else {
-            self.errors.push(KumeoError::SemanticError(
-                "Todos los agentes deben tener un ID".to_string(),
-            ));
         }
+    }
 
-        // Validar configuración específica del tipo de agente
-        match agent.agent_type {
-            AgentType::LLM => self.validate_llm_agent(agent)?,
-            AgentType::MLModel => self.validate_ml_agent(agent)?,
-            _ => {}
+    /// Valida un tema de MQTT; los comodines solo se permiten al suscribirse.
+    fn validate_mqtt_topic(&mut self, topic: &str, allow_wildcards: bool) {
+        if topic.is_empty() {
+            self.error(codes::INVALID_TOPIC, message!("El tema de MQTT no puede estar vacío"));
+            return;
         }
 
-        Ok(())
+        let levels: Vec<&str> = topic.split('/').collect();
+        for (i, level) in levels.iter().enumerate() {
+            let has_wildcard = level.contains(['+', '#']);
+            if has_wildcard && !allow_wildcards {
+                self.error(codes::INVALID_TOPIC, message!(
+                    "El destino MQTT '{}' no puede contener comodines '+' ni '#'",
+                    topic
+                ));
+                return;
+            }
+
+            // '+' ocupa un nivel completo; '#' además debe ser el último nivel
+            let valid = !has_wildcard || *level == "+" || (*level == "#" && i == levels.len() - 1);
+            if !valid {
+                self.error(codes::INVALID_TOPIC, message!(
+                    "Comodín MQTT mal ubicado en '{}': '+' debe ocupar un nivel completo y '#' solo puede ser el último nivel",
+                    topic
+                ));
+                return;
+            }
+        }
     }
 
-    /// Valida un agente LLM.
-    fn validate_llm_agent(&self, agent: &Agent) -> Result<()> {
-        // Verificar que tenga el campo 'model' configurado
-        let has_model = agent.config.iter().any(|arg| match arg {
-            Argument::Named(name, _) => name == "model",
-            _ => false,
-        });
+    /// Valida la configuración de despliegue.
+    fn validate_deployment(&mut self, workflow: &Workflow, deployment: &Deployment) {
+        match &deployment.rollout {
+            Some(RolloutStrategy::Canary(canary)) => self.validate_canary(canary),
+            Some(RolloutStrategy::BlueGreen) => self.validate_active_slot(deployment),
+            None => {}
+        }
 
-        if !has_model {
-            return Err(KumeoError::SemanticError(
-                "Los agentes LLM deben tener un modelo configurado".to_string(),
-            ));
+        if let Some(storage) = &deployment.storage {
+            for (agent_id, volume) in storage {
+                self.validate_storage(workflow, agent_id, volume);
+            }
         }
 
-        Ok(())
+        if deployment.require_signed {
+            self.validate_signed_models(workflow);
+        }
+
+        // Las variables leídas del Secret ya no tienen valor en el DSL
+        let secret_env = deployment.secret_env();
+        for (name, value) in deployment.env.iter().flatten() {
+            if is_credential(&name.to_lowercase()) && !secret_env.contains_key(name.as_str()) && !value.contains("${") {
+                self.report(
+                    Diagnostic::warning(
+                        codes::INLINE_SECRET,
+                        message!("El despliegue escribe {} en el DSL; acaba en claro en los manifiestos generados", name),
+                    )
+                    .with_help(message!(
+                        "léelo del Secret del despliegue: {}: secret(\"{}\")",
+                        name,
+                        name.to_lowercase().replace('_', "-")
+                    )),
+                );
+            }
+        }
     }
 
-    /// Valida un agente de modelo de ML.
-    fn validate_ml_agent(&self, agent: &Agent) -> Result<()> {
-        // Verificar que tenga el campo 'model_path' o 'model_name' configurado
-        let has_model = agent.config.iter().any(|arg| match arg {
-            Argument::Named(name, _) => name == "model_path" || name == "model_name",
-            _ => false,
+    /// Valida que los modelos remotos declaren firma cuando el despliegue la exige.
+    fn validate_signed_models(&mut self, workflow: &Workflow) {
+        let context_models = workflow
+            .context
+            .iter()
+            .flat_map(|context| context.models.values())
+            .map(|model| model.path.as_str());
+        let agent_models = workflow.agents.iter().flat_map(|agent| &agent.config).filter_map(|arg| match arg {
+            Argument::Named(name, Value::String(path), _) if name == "model_path" => Some(path.as_str()),
+            _ => None,
         });
 
-        if !has_model {
-            return Err(KumeoError::SemanticError(
-                "Los agentes de ML deben tener 'model_path' o 'model_name' configurado".to_string(),
+        for path in context_models.chain(agent_models) {
+            if path.contains("://") && declared_signature(path).is_none() {
+                self.report(
+                    Diagnostic::error(
+                        codes::UNSIGNED_MODEL,
+                        message!(
+                            "El modelo {} no declara firma (#minisign o #cosign) y el despliegue exige require_signed",
+                            path
+                        ),
+                    )
+                    .with_help(message!("añade la firma a la URI, por ejemplo {}#minisign", path)),
+                );
+            }
+        }
+    }
+
+    /// Valida que los guardrails de un agente envíen a revisión a un agente
+    /// HumanReview de su mismo workflow.
+    fn validate_guardrail_review(&mut self, workflow: &Workflow, agent: &Agent) {
+        let Some(review) = agent
+            .config_value(GUARDRAILS_OPTION)
+            .and_then(|value| GuardrailsConfig::from_value(value).ok())
+            .and_then(|config| config.review)
+        else {
+            return;
+        };
+        let agent_id = agent_name(agent);
+
+        // Los mensajes en revisión se publican en el topic que consume el revisor
+        let reviewer = workflow.agents.iter().find(|other| other.id.as_deref() == Some(review.as_str()));
+        if !reviewer.is_some_and(|reviewer| reviewer.agent_type == AgentType::HumanReview) {
+            self.error(codes::INVALID_CONFIG, message!(
+                "El agente {} envía mensajes a revisión a {}, que no es un agente HumanReview del workflow",
+                agent_id, review
             ));
         }
+    }
 
-        Ok(())
+    /// Valida los modelos que un agente declara para precargar.
+    fn validate_preload(&mut self, workflow: &Workflow, agent: &Agent) {
+        let Some(value) = agent.config_value(PRELOAD_OPTION) else {
+            return;
+        };
+        let agent_id = agent_name(agent);
+
+        match value {
+            Value::Boolean(false) => {}
+            Value::Boolean(true) => {
+                if workflow.preload_uris(agent).is_none_or(|uris| uris.is_empty()) {
+                    self.error(codes::INVALID_PRELOAD, message!(
+                        "El agente {} declara preload pero no tiene un modelo remoto (model_path o models.<nombre>)",
+                        agent_id
+                    ));
+                }
+            }
+            Value::Array(models) => {
+                for model in models {
+                    let resolved = match model {
+                        Value::String(reference) | Value::Path(reference) => {
+                            reference.contains("://") || workflow.context_model_path(reference).is_some()
+                        }
+                        _ => false,
+                    };
+                    if !resolved {
+                        self.error(codes::INVALID_PRELOAD, message!(
+                            "El agente {} precarga {}, que no es una URI ni un modelo del contexto",
+                            agent_id,
+                            format!("{:?}", model)
+                        ));
+                    }
+                }
+            }
+            _ => self.error(codes::INVALID_PRELOAD, message!(
+                "preload del agente {} debe ser true, false o una lista de modelos",
+                agent_id
+            )),
+        }
     }
 
-    /// Valida un identificador (nombre de workflow, subworkflow, etc.).
-    fn validate_identifier(&self, id: &str, context: &str) -> Result<()> {
-        if id.trim().is_empty() {
-            return Err(KumeoError::SemanticError(
-                format!("El {} no puede tener un nombre vacío", context),
+    /// Valida un workflow batch: la entrada debe estar acotada.
+    fn validate_batch(&mut self, workflow: &Workflow) {
+        if let Some(until) = workflow.source.as_ref().and_then(|s| s.option(UNTIL_SEQUENCE_OPTION)) {
+            if !until.parse::<u64>().is_ok_and(|n| n > 0) {
+                self.error(codes::INVALID_BATCH, message!(
+                    "until_sequence debe ser un entero positivo: {}",
+                    until
+                ));
+            }
+        }
+
+        if workflow.deployment.as_ref().is_some_and(|d| d.rollout.is_some()) {
+            self.error(codes::INVALID_BATCH, message!("Los workflows batch no admiten estrategias de rollout"));
+        }
+
+        // Un webhook nunca agota su entrada, así que el Job no terminaría
+        if matches!(workflow.source, Some(Source::HTTP(..))) {
+            self.error(codes::INVALID_BATCH, message!("Los workflows batch no admiten fuentes HTTP"));
+        }
+    }
+
+    /// Valida que todo topic consumido lo produzca un agente, un subworkflow o la fuente.
+    fn validate_topics(&mut self, workflow: &Workflow) {
+        let graph = match workflow.topic_graph() {
+            Ok(graph) => graph,
+            Err(e) => {
+                self.error(codes::INVALID_TOPICS, message!(
+                    "Topics inválidos en el workflow {}: {}",
+                    workflow.name, e
+                ));
+                return;
+            }
+        };
+
+        // Agents without an output topic write the workflow target
+        let target_topic = workflow.target.as_ref().map(|target| target.topic());
+        let writes_target = graph.iter().any(|topics| topics.output.is_none());
+        let produced: HashSet<&str> = graph
+            .iter()
+            .filter_map(|topics| topics.output.as_deref())
+            .chain(workflow.calls.iter().flat_map(|call| call.output.iter().map(String::as_str)))
+            .chain(target_topic.filter(|_| writes_target))
+            .collect();
+        let source_topic = workflow.source.as_ref().map(|source| source.topic());
+        let is_produced = |topic: &str| produced.contains(topic) || Some(topic) == source_topic;
+
+        for (index, (agent, topics)) in workflow.agents.iter().zip(&graph).enumerate() {
+            if let Some(topic) = topics.input.as_deref().filter(|topic| !is_produced(topic)) {
+                let agent_id = agent.id.clone().unwrap_or_else(|| format!("#{}", index + 1));
+                let suggestion = closest(topic, produced.iter().copied().chain(source_topic)).map(str::to_string);
+                let mut diagnostic = Diagnostic::error(
+                    codes::UNPRODUCED_TOPIC,
+                    message!("El agente {} consume el topic '{}' que ningún agente produce", agent_id, topic),
+                )
+                .at(&agent.span);
+                if let Some(suggestion) = &suggestion {
+                    diagnostic = diagnostic.with_fix(format!("\"{}\"", topic), format!("\"{}\"", suggestion));
+                }
+                self.report(diagnostic.with_suggestion(suggestion));
+            }
+        }
+        for call in &workflow.calls {
+            for topic in call.input.iter().filter(|topic| !is_produced(topic)) {
+                self.error(codes::UNPRODUCED_TOPIC, message!(
+                    "use {}: el topic '{}' no lo produce ningún agente",
+                    call.name, topic
+                ));
+            }
+        }
+
+        // Avisar de los agentes cuya salida no consume nadie; los informes de
+        // los monitores de calidad son para quien los observe desde fuera
+        let consumed = catalog::consumed_topics(workflow);
+        for (index, (agent, topics)) in workflow.agents.iter().zip(&graph).enumerate() {
+            let Some(topic) = topics.output.as_deref() else {
+                continue;
+            };
+            if agent.agent_type == AgentType::QualityMonitor {
+                continue;
+            }
+            if !consumed.contains(topic) && !self.consumed_topics.contains(topic) {
+                let agent_id = agent.id.clone().unwrap_or_else(|| format!("#{}", index + 1));
+                self.report(
+                    Diagnostic::warning(
+                        codes::UNUSED_TOPIC,
+                        message!(
+                            "Nadie consume el topic '{}' que produce el agente {} y no es el destino del workflow {}",
+                            topic, agent_id, workflow.name
+                        ),
+                    )
+                    .at(&agent.span),
+                );
+            }
+        }
+
+        self.validate_dataflow(workflow, &graph);
+    }
+
+    /// Rechaza los ciclos del flujo de datos, que reenviarían mensajes sin fin.
+    ///
+    /// Un ciclo en el que algún agente filtra con `when` puede terminar, así
+    /// que solo genera un aviso.
+    fn validate_dataflow(&mut self, workflow: &Workflow, graph: &[AgentTopics]) {
+        let source_topic = workflow.source.as_ref().map(|source| source.topic());
+        let target_topic = workflow.target.as_ref().map(|target| target.topic());
+
+        // Nodos: agentes y subworkflows invocados, con los topics que consumen y producen
+        let mut nodes: Vec<DataflowNode> = Vec::new();
+        for (index, (agent, topics)) in workflow.agents.iter().zip(graph).enumerate() {
+            let name = agent.id.clone().unwrap_or_else(|| format!("#{}", index + 1));
+            let consumes = topics.input.as_deref().or(source_topic).into_iter().collect();
+            let produces = topics.output.as_deref().or(target_topic).into_iter().collect();
+            let guarded = agent.config_value(WHEN_OPTION).is_some();
+            nodes.push(DataflowNode { name, consumes, produces, guarded });
+        }
+        for call in &workflow.calls {
+            let consumes = call.input.iter().map(String::as_str).collect();
+            let produces = call.output.iter().map(String::as_str).collect();
+            nodes.push(DataflowNode { name: format!("use {}", call.name), consumes, produces, guarded: false });
+        }
+
+        let edges: Vec<Vec<usize>> = nodes
+            .iter()
+            .map(|producer| {
+                (0..nodes.len())
+                    .filter(|&consumer| nodes[consumer].consumes.iter().any(|topic| producer.produces.contains(topic)))
+                    .collect()
+            })
+            .collect();
+
+        // Los pasos que consumen la fuente u otro topic externo reciben mensajes, y con ellos sus sucesores
+        let mut reachable: Vec<bool> = nodes
+            .iter()
+            .map(|node| {
+                node.consumes.is_empty()
+                    || node
+                        .consumes
+                        .iter()
+                        .any(|topic| !nodes.iter().any(|producer| producer.produces.contains(topic)))
+            })
+            .collect();
+        let mut pending: Vec<usize> = (0..nodes.len()).filter(|&node| reachable[node]).collect();
+        while let Some(node) = pending.pop() {
+            for &next in &edges[node] {
+                if !reachable[next] {
+                    reachable[next] = true;
+                    pending.push(next);
+                }
+            }
+        }
+        for (node, _) in nodes.iter().zip(&reachable).filter(|(_, reachable)| !**reachable) {
+            self.warn(codes::UNREACHABLE_STEP, message!(
+                "El paso {} del workflow {} nunca recibe mensajes: ningún camino desde la fuente llega a él",
+                node.name, workflow.name
             ));
         }
 
-        // Validar que solo contenga caracteres alfanuméricos y guiones bajos
-        if !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
-            return Err(KumeoError::SemanticError(format!(
-                "El {} '{}' solo puede contener caracteres alfanuméricos y guiones bajos",
-                context, id
-            )));
+        let everyone = vec![true; nodes.len()];
+        for component in cyclic_components(&edges, &everyone) {
+            // Sin los pasos con `when`, lo que siga formando un ciclo no termina nunca
+            let mut unguarded = vec![false; nodes.len()];
+            for &node in &component {
+                unguarded[node] = !nodes[node].guarded;
+            }
+            let endless = cyclic_components(&edges, &unguarded);
+
+            let path = |cycle: Vec<usize>| -> String {
+                cycle
+                    .iter()
+                    .chain(cycle.first())
+                    .map(|&node| nodes[node].name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            };
+            if endless.is_empty() {
+                self.warn(codes::DATAFLOW_CYCLE, message!(
+                    "Ciclo en el flujo de datos del workflow {} condicionado por `when`: {}",
+                    workflow.name,
+                    path(shortest_cycle(&edges, &component))
+                ));
+            }
+            for members in endless {
+                self.report(
+                    Diagnostic::error(
+                        codes::DATAFLOW_CYCLE,
+                        message!("Ciclo en el flujo de datos del workflow {}: {}", workflow.name, path(shortest_cycle(&edges, &members))),
+                    )
+                    .with_help(message!("filtra los mensajes con `when` en algún paso del ciclo para que termine")),
+                );
+            }
         }
+    }
 
-        // Validar que no empiece con un número
-        if let Some(first_char) = id.chars().next() {
-            if first_char.is_ascii_digit() {
-                return Err(KumeoError::SemanticError(format!(
-                    "El {} '{}' no puede empezar con un número",
-                    context, id
-                )));
+    /// Valida las declaraciones del bloque `schemas:`.
+    fn validate_topic_schemas(&mut self, program: &Program) {
+        let mut topics = HashSet::new();
+        for declared in &program.schemas {
+            if !topics.insert(declared.topic.as_str()) {
+                self.error(codes::DUPLICATE_NAME, message!(
+                    "Esquema duplicado para el topic '{}'",
+                    declared.topic
+                ));
+            }
+            if let Err(e) = Schema::from_value(&declared.fields) {
+                self.error(codes::INVALID_SCHEMA, message!(
+                    "Esquema inválido para el topic '{}': {}",
+                    declared.topic, e
+                ));
             }
         }
+    }
 
-        Ok(())
+    /// Comprueba que la versión de cada workflow suba al menos en la clase de
+    /// sus cambios respecto al programa de referencia.
+    fn validate_version_bumps(&mut self, program: &Program) {
+        let Some(mut baseline) = self.baseline.clone() else {
+            return;
+        };
+        // Las constantes sin definir de la referencia se comparan tal cual
+        let _ = baseline.substitute_constants();
+        for changes in versioning::diff_programs(&baseline, program) {
+            if let Some(message) = changes.bump_error() {
+                let span = program
+                    .workflows
+                    .iter()
+                    .find(|workflow| workflow.name == changes.workflow)
+                    .map(|workflow| workflow.span.clone())
+                    .unwrap_or_default();
+                self.report(Diagnostic::error(codes::VERSION_BUMP, message).at(&span));
+            }
+        }
     }
 
-    /// Reinicia el estado del analizador.
-    fn reset(&mut self) {
-        self.agent_ids.clear();
-        self.workflow_names.clear();
-        self.subworkflow_names.clear();
-        self.errors.clear();
+    /// Comprueba que productores y consumidores respeten los esquemas de los topics.
+    ///
+    /// Un agente que publica en un topic con esquema declarado debe producir
+    /// mensajes que lo cumplan, y el `input_schema` de un consumidor no puede
+    /// exigir más de lo que garantiza el esquema de su topic.
+    fn validate_message_contracts(&mut self, program: &Program) {
+        let declared = program.topic_schemas();
+        for workflow in &program.workflows {
+            for (agent, topics) in workflow.agents.iter().zip(workflow.deployed_topics()) {
+                let agent_id = agent_name(agent);
+                let from = self.diagnostics.len();
+
+                if let (Some(topic), Ok(Some((produced, _)))) = (&topics.output, agent.output_schema()) {
+                    if let Some(contract) = declared.get(topic) {
+                        let problems = produced.incompatibilities(contract);
+                        if !problems.is_empty() {
+                            self.error(codes::CONTRACT_VIOLATION, message!(
+                                "El agente {} publica en '{}' mensajes que no cumplen su esquema: {}",
+                                agent_id,
+                                topic,
+                                Message::join(problems, ", ")
+                            ));
+                        }
+                    }
+                }
+
+                if let (Some(topic), Ok(Some(expected))) = (&topics.input, agent.input_schema()) {
+                    if let Some(provided) = self.message_schemas.get(topic) {
+                        let problems = provided.incompatibilities(&expected);
+                        if !problems.is_empty() {
+                            self.error(codes::CONTRACT_VIOLATION, message!(
+                                "El agente {} espera en '{}' mensajes que su esquema no garantiza: {}",
+                                agent_id,
+                                topic,
+                                Message::join(problems, ", ")
+                            ));
+                        }
+                    }
+                }
+                self.locate_errors(from, &agent.span);
+            }
+        }
     }
+
+    /// Comprueba que productores y consumidores de cada topic usen la misma codificación.
+    ///
+    /// Solo los topics de NATS llevan el content-type de cada mensaje, así que
+    /// las fuentes y destinos de otros brokers se quedan en JSON. Protobuf y
+    /// Avro se leen con el esquema del topic, que debe estar declarado.
+    fn validate_encodings(&mut self, program: &Program) {
+        let declared = program.topic_schemas();
+        // Agentes que publican en cada topic, con su workflow y su codificación
+        let mut producers: BTreeMap<String, Vec<(String, &str, Encoding)>> = BTreeMap::new();
+        let mut sources: Vec<(&Workflow, &str, Encoding)> = Vec::new();
+
+        for workflow in &program.workflows {
+            let from = self.diagnostics.len();
+            let source = workflow.source.as_ref().map(|source| (source, source.encoding()));
+            let target = workflow.target.as_ref().map(|target| (target, target.encoding()));
+            let mut valid = true;
+            if let Some((source, encoding)) = &source {
+                match encoding {
+                    Err(e) => self.error(codes::INVALID_ENCODING, message!(
+                        "Codificación inválida en la fuente del workflow {}: {}",
+                        workflow.name, e
+                    )),
+                    Ok(encoding) if *encoding != Encoding::Json && !matches!(source, Source::NATS(..)) => self.error(
+                        codes::INVALID_ENCODING,
+                        message!(
+                            "La fuente del workflow {} usa la codificación {}, que solo admiten las fuentes NATS",
+                            workflow.name, encoding
+                        ),
+                    ),
+                    Ok(_) => {}
+                }
+                valid &= encoding.is_ok();
+            }
+            if let Some((target, encoding)) = &target {
+                match encoding {
+                    Err(e) => self.error(codes::INVALID_ENCODING, message!(
+                        "Codificación inválida en el destino del workflow {}: {}",
+                        workflow.name, e
+                    )),
+                    Ok(encoding) if *encoding != Encoding::Json && !matches!(target, Target::NATS(..)) => self.error(
+                        codes::INVALID_ENCODING,
+                        message!(
+                            "El destino del workflow {} usa la codificación {}, que solo admiten los destinos NATS",
+                            workflow.name, encoding
+                        ),
+                    ),
+                    Ok(_) => {}
+                }
+                valid &= encoding.is_ok();
+            }
+            for agent in &workflow.agents {
+                if let Err(e) = agent.encoding() {
+                    valid = false;
+                    self.report(
+                        Diagnostic::error(
+                            codes::INVALID_ENCODING,
+                            message!("Codificación inválida en el agente {}: {}", agent_name(agent), e),
+                        )
+                        .at(&agent.span),
+                    );
+                }
+            }
+            let encodings = match workflow.agent_encodings() {
+                Ok(encodings) if valid => encodings,
+                _ => {
+                    self.locate_errors(from, &workflow.span);
+                    continue;
+                }
+            };
+
+            let target_topic = workflow.target.as_ref().map(|target| target.topic());
+            let target_encoding = target.and_then(|(_, encoding)| encoding.ok()).unwrap_or_default();
+            let topics = workflow.deployed_topics();
+            for ((agent, topics), encodings) in workflow.agents.iter().zip(&topics).zip(&encodings) {
+                let agent_id = agent_name(agent);
+                let Some(topic) = topics.output.as_deref() else {
+                    continue;
+                };
+                let mismatched = Some(topic) == target_topic && encodings.output != target_encoding;
+                if mismatched {
+                    self.report(
+                        Diagnostic::error(
+                            codes::INVALID_ENCODING,
+                            message!(
+                                "El agente {} publica en {} en el destino del workflow {}, que usa {}",
+                                agent_id, encodings.output, workflow.name, target_encoding
+                            ),
+                        )
+                        .at(&agent.span),
+                    );
+                }
+                if encodings.output.needs_schema() && !matches!(agent.output_schema(), Ok(Some(_))) {
+                    self.report(
+                        Diagnostic::error(
+                            codes::INVALID_ENCODING,
+                            message!(
+                                "El agente {} publica en '{}' en {}, que necesita su output_schema",
+                                agent_id, topic, encodings.output
+                            ),
+                        )
+                        .at(&agent.span),
+                    );
+                }
+                if !mismatched {
+                    producers
+                        .entry(topic.to_string())
+                        .or_default()
+                        .push((agent_id.to_string(), workflow.name.as_str(), encodings.output));
+                }
+            }
+
+            if let Some((source, Ok(encoding))) = &source {
+                let topic = source.topic();
+                // Los lectores de la fuente reciben el esquema declarado del topic o declaran el suyo
+                let mut readers = workflow
+                    .agents
+                    .iter()
+                    .zip(&topics)
+                    .filter(|(_, topics)| topics.input.as_deref() == Some(topic))
+                    .peekable();
+                let described = declared.contains_key(topic)
+                    || (readers.peek().is_some() && readers.all(|(agent, _)| matches!(agent.input_schema(), Ok(Some(_)))));
+                if encoding.needs_schema() && !described {
+                    self.error(codes::INVALID_ENCODING, message!(
+                        "La fuente '{}' del workflow {} usa {}, que necesita su esquema en schemas: o el input_schema de los agentes que la leen",
+                        topic, workflow.name, encoding
+                    ));
+                }
+                if matches!(source, Source::NATS(..)) {
+                    sources.push((workflow, topic, *encoding));
+                }
+            }
+            self.locate_errors(from, &workflow.span);
+        }
+
+        for (topic, publishers) in &producers {
+            let (agent, _, encoding) = &publishers[0];
+            if let Some((other, _, other_encoding)) = publishers.iter().find(|(_, _, other)| other != encoding) {
+                self.error(codes::INVALID_ENCODING, message!(
+                    "El topic '{}' lo publican {} en {} y {} en {}",
+                    topic, agent, encoding, other, other_encoding
+                ));
+            }
+        }
+        // Los workflows que leen lo que publica otro deben leerlo en su codificación
+        for (workflow, topic, encoding) in sources {
+            let mismatch = producers
+                .get(topic)
+                .into_iter()
+                .flatten()
+                .find(|(_, producer, published)| *producer != workflow.name && *published != encoding);
+            if let Some((_, producer, published)) = mismatch {
+                self.report(
+                    Diagnostic::error(
+                        codes::INVALID_ENCODING,
+                        message!(
+                            "El workflow {} lee '{}' en {}, pero el workflow {} lo publica en {}",
+                            workflow.name, topic, encoding, producer, published
+                        ),
+                    )
+                    .at(&workflow.span),
+                );
+            }
+        }
+    }
+
+    /// Exige incrementar `schema_version` ante cambios incompatibles en el
+    /// esquema de un topic que consume otro workflow.
+    fn validate_schema_evolution(&mut self, program: &Program) {
+        for workflow in &program.workflows {
+            let from = self.diagnostics.len();
+            for schema in catalog::published_schemas(workflow) {
+                let Some((recorded, previous)) = self.schema_catalog.latest(&schema.topic) else {
+                    continue;
+                };
+
+                // Consumidores del programa y los registrados por otros programas
+                let mut consumers: BTreeSet<&str> = program
+                    .workflows
+                    .iter()
+                    .filter(|other| catalog::consumed_topics(other).contains(&schema.topic))
+                    .map(|other| other.name.as_str())
+                    .collect();
+                consumers.extend(
+                    self.schema_catalog
+                        .consumers(&schema.topic)
+                        .filter(|consumer| !self.workflow_names.contains(*consumer)),
+                );
+                consumers.remove(workflow.name.as_str());
+                if consumers.is_empty() {
+                    continue;
+                }
+                let consumers = consumers.into_iter().collect::<Vec<_>>().join(", ");
+
+                if schema.version < recorded {
+                    self.error(codes::BREAKING_SCHEMA_CHANGE, message!(
+                        "El agente {} publica en '{}' con schema_version {}, pero ya se registró la versión {} (consumido por {})",
+                        schema.agent, schema.topic, schema.version, recorded, consumers
+                    ));
+                } else if schema.version == recorded {
+                    let changes = catalog::breaking_changes(previous, &schema.fields);
+                    if !changes.is_empty() {
+                        self.error(codes::BREAKING_SCHEMA_CHANGE, message!(
+                            "Cambio incompatible en el esquema del topic '{}', consumido por {}: {}; incrementa schema_version del agente {}",
+                            schema.topic,
+                            consumers,
+                            Message::join(changes, ", "),
+                            schema.agent
+                        ));
+                    }
+                }
+            }
+            self.locate_errors(from, &workflow.span);
+        }
+    }
+
+    /// Valida el volumen persistente de un agente.
+    fn validate_storage(&mut self, workflow: &Workflow, agent_id: &str, storage: &Storage) {
+        if !workflow.agents.iter().any(|agent| agent.id.as_deref() == Some(agent_id)) {
+            let agents = workflow.agents.iter().filter_map(|agent| agent.id.as_deref());
+            self.report(
+                Diagnostic::error(
+                    codes::INVALID_DEPLOYMENT,
+                    message!("Almacenamiento declarado para un agente inexistente: {}", agent_id),
+                )
+                .with_suggestion(closest(agent_id, agents)),
+            );
+        }
+
+        if !is_valid_quantity(&storage.size) {
+            self.error(codes::INVALID_DEPLOYMENT, message!(
+                "Tamaño de almacenamiento inválido para '{}': {} (ejemplo: 10Gi)",
+                agent_id, storage.size
+            ));
+        }
+
+        if !storage.path.starts_with('/') {
+            self.error(codes::INVALID_DEPLOYMENT, message!(
+                "La ruta de montaje de '{}' debe ser absoluta: {}",
+                agent_id, storage.path
+            ));
+        }
+    }
+
+    /// Valida el slot activo que declara un despliegue blue/green.
+    fn validate_active_slot(&mut self, deployment: &Deployment) {
+        let Some(slot) = deployment.annotations.as_ref().and_then(|annotations| annotations.get(ACTIVE_SLOT_ANNOTATION)) else {
+            return;
+        };
+        if !BLUE_GREEN_SLOTS.contains(&slot.as_str()) {
+            self.report(
+                Diagnostic::error(
+                    codes::INVALID_DEPLOYMENT,
+                    message!(
+                        "Slot activo desconocido en {}: {} (debe ser {} o {})",
+                        ACTIVE_SLOT_ANNOTATION, slot, BLUE_GREEN_SLOTS[0], BLUE_GREEN_SLOTS[1]
+                    ),
+                )
+                .with_suggestion(closest(slot, BLUE_GREEN_SLOTS.iter().copied())),
+            );
+        }
+    }
+
+    /// Valida una estrategia de despliegue canary.
+    fn validate_canary(&mut self, canary: &CanaryStrategy) {
+        if canary.steps.is_empty() {
+            self.error(codes::INVALID_DEPLOYMENT, message!("El despliegue canary debe tener al menos un paso"));
+        }
+
+        let mut previous = 0.0;
+        for &weight in &canary.steps {
+            if weight <= 0.0 || weight > 100.0 || weight.fract() != 0.0 {
+                self.error(codes::INVALID_DEPLOYMENT, message!(
+                    "Peso de canary inválido: {}% (debe ser un entero entre 1 y 100)",
+                    weight
+                ));
+            } else if weight <= previous {
+                self.error(codes::INVALID_DEPLOYMENT, message!(
+                    "Los pasos de canary deben ser crecientes: {}% después de {}%",
+                    weight, previous
+                ));
+            }
+            previous = weight;
+        }
+
+        if let Some(analysis) = &canary.analysis {
+            if let Err(e) = AnalysisCondition::parse(analysis) {
+                self.error(codes::INVALID_DEPLOYMENT, message!(
+                    "Análisis de canary inválido: {}",
+                    e
+                ));
+            }
+        }
+    }
+
+    /// Valida un agente; `input_schema` es el esquema declarado de los mensajes que consume.
+    fn validate_agent(&mut self, agent: &Agent, input_schema: Option<&Schema>) -> Result<()> {
+        // Validar ID único
+        if let Some(id) = &agent.id {
+            if !self.agent_ids.insert(id.clone()) {
+                self.error(codes::DUPLICATE_NAME, message!("ID de agente duplicado: {}", id));
+            }
+        } else {
+            // La corrección añade un ID propuesto como primer argumento
+            let id = format!("id: \"{}\"", self.propose_agent_id(agent.agent_type));
+            let fix = if agent.config.is_empty() { format!("({}", id) } else { format!("({}, ", id) };
+            self.report(
+                Diagnostic::error(codes::MISSING_AGENT_ID, message!("Todos los agentes deben tener un ID")).with_fix("(", fix),
+            );
+        }
+
+        // Validar contextos basados en directorios (globs de recursos)
+        for arg in &agent.config {
+            if let Argument::Named(name, Value::String(uri), span) = arg {
+                if uri.contains("://") && uri.contains(['*', '?']) {
+                    let from = self.diagnostics.len();
+                    self.validate_resource_glob(name, uri);
+                    self.locate_errors(from, span);
+                }
+            }
+        }
+
+        // Avisar de placeholders que no se pueden resolver en este entorno
+        self.check_placeholders(agent);
+        self.check_inline_secrets(agent);
+        self.validate_image(agent);
+
+        // Validar la condición `when` y los invariantes `assert`
+        for arg in &agent.config {
+            if let Argument::Named(name, value, span) = arg {
+                if name == WHEN_OPTION || name == ASSERT_OPTION {
+                    let from = self.diagnostics.len();
+                    self.validate_condition(agent, name, value, input_schema);
+                    self.locate_errors(from, span);
+                }
+            }
+        }
+
+        // Validar las políticas de reintento y fallback
+        let agent_id = agent_name(agent);
+        let from = self.diagnostics.len();
+        if let Some(Err(e)) = agent.config_value(RETRY_OPTION).map(RetryPolicy::from_value) {
+            self.error(codes::INVALID_POLICY, message!(
+                "Política retry inválida en el agente {}: {}",
+                agent_id, e
+            ));
+        }
+        self.locate_option(from, agent, RETRY_OPTION);
+        let from = self.diagnostics.len();
+        match agent.config_value(FALLBACK_OPTION).map(FallbackConfig::from_value) {
+            Some(Err(e)) => {
+                self.error(codes::INVALID_POLICY, message!(
+                    "Fallback inválido en el agente {}: {}",
+                    agent_id, e
+                ));
+            }
+            // La entrega diferida solo la usan los agentes Rust
+            Some(Ok(FallbackConfig::RetryLater { .. }))
+                if matches!(agent.agent_type, AgentType::MLModel | AgentType::QualityMonitor) =>
+            {
+                self.error(codes::INVALID_POLICY, message!(
+                    "El agente {} ({}) no admite el fallback retry_later; los agentes Python no reciben mensajes diferidos",
+                    agent_id, agent.agent_type
+                ));
+            }
+            _ => {}
+        }
+        self.locate_option(from, agent, FALLBACK_OPTION);
+
+        // Solo los agentes Rust toman parte en sagas
+        let from = self.diagnostics.len();
+        if let Some(compensate) = agent.config_value(COMPENSATE_OPTION) {
+            if matches!(agent.agent_type, AgentType::MLModel | AgentType::QualityMonitor) {
+                self.error(codes::INVALID_POLICY, message!(
+                    "El agente {} ({}) no admite compensate; los agentes Python no toman parte en sagas",
+                    agent_id, agent.agent_type
+                ));
+            } else if let Err(e) = CompensationConfig::from_value(compensate) {
+                self.error(codes::INVALID_POLICY, message!(
+                    "Compensación inválida en el agente {}: {}",
+                    agent_id, e
+                ));
+            }
+        }
+        self.locate_option(from, agent, COMPENSATE_OPTION);
+
+        // Validar los esquemas de los mensajes que consume y produce
+        let from = self.diagnostics.len();
+        if let Err(e) = agent.input_schema() {
+            self.error(codes::INVALID_SCHEMA, message!(
+                "Esquema de entrada inválido en el agente {}: {}",
+                agent_id, e
+            ));
+        }
+        self.locate_option(from, agent, INPUT_SCHEMA_OPTION);
+        let from = self.diagnostics.len();
+        if let Err(e) = agent.output_schema() {
+            self.error(codes::INVALID_SCHEMA, message!(
+                "Esquema de salida inválido en el agente {}: {}",
+                agent_id, e
+            ));
+        }
+        self.locate_option(from, agent, OUTPUT_SCHEMA_OPTION);
+
+        // Validar los tipos de la configuración con el esquema de su tipo de agente
+        let problems = self.config_schemas.check(agent);
+        let well_typed = problems.is_empty();
+        for (option, problem) in problems {
+            let from = self.diagnostics.len();
+            self.error(codes::INVALID_CONFIG, message!(
+                "Configuración inválida en el agente {} ({}): {}",
+                agent_id, agent.agent_type, problem
+            ));
+            self.locate_option(from, agent, &option);
+        }
+
+        // Solo los modelos ML siguen la deriva de sus entradas
+        let from = self.diagnostics.len();
+        if let Some(drift) = agent.config_value(DRIFT_OPTION) {
+            if agent.agent_type != AgentType::MLModel {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "El agente {} ({}) no admite drift; solo los agentes MLModel siguen la deriva de sus entradas",
+                    agent_id, agent.agent_type
+                ));
+            } else if let (true, Err(e)) = (well_typed, ModelDriftConfig::from_value(drift)) {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "Drift inválido en el agente {}: {}",
+                    agent_id, e
+                ));
+            }
+        }
+        self.locate_option(from, agent, DRIFT_OPTION);
+
+        // Solo los modelos ML se enriquecen con features, buscadas por campos de su entrada
+        let from = self.diagnostics.len();
+        if let Some(features) = agent.config_value(FEATURES_OPTION) {
+            if agent.agent_type != AgentType::MLModel {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "El agente {} ({}) no admite features; solo los agentes MLModel consultan un feature store",
+                    agent_id, agent.agent_type
+                ));
+            } else {
+                self.validate_feature_store(&agent_id, features, input_schema);
+            }
+        }
+        self.locate_option(from, agent, FEATURES_OPTION);
+
+        // Los servidores de inferencia propios (vLLM, TGI) solo sirven a agentes LLM
+        let from = self.diagnostics.len();
+        if let Some(provider) = agent.config_value(PROVIDER_OPTION) {
+            if InferenceServerConfig::is_server(provider) && agent.agent_type != AgentType::LLM {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "El agente {} ({}) no admite un servidor de inferencia; solo los agentes LLM envían prompts a vLLM o TGI",
+                    agent_id, agent.agent_type
+                ));
+            } else if agent.agent_type == AgentType::LLM {
+                self.validate_provider(&agent_id, agent, provider);
+            }
+        } else if agent.agent_type == AgentType::LLM && agent.config_value(PROVIDERS_OPTION).is_none() {
+            if let Err(e) = LLMProvider::DEFAULT.check_options(agent) {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "Proveedor inválido en el agente {}: {}",
+                    agent_id, e
+                ));
+            }
+        }
+        self.locate_option(from, agent, PROVIDER_OPTION);
+
+        // `engine` no elige el proveedor ni el modelo de los agentes LLM
+        let from = self.diagnostics.len();
+        if let Some(engine) = agent.config_value(ENGINE_OPTION).filter(|_| agent.agent_type == AgentType::LLM) {
+            self.warn(codes::INVALID_CONFIG, message!(
+                "El agente {} ignora engine: {}; declara el proveedor con provider y el modelo con model",
+                agent_id, engine
+            ));
+        }
+        self.locate_option(from, agent, ENGINE_OPTION);
+
+        // Las cadenas de proveedores solo existen en los agentes LLM y sustituyen a `provider`
+        let from = self.diagnostics.len();
+        if let Some(providers) = agent.config_value(PROVIDERS_OPTION) {
+            if agent.agent_type != AgentType::LLM {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "El agente {} ({}) no admite providers; solo los agentes LLM encadenan proveedores",
+                    agent_id, agent.agent_type
+                ));
+            } else if agent.config_value(PROVIDER_OPTION).is_some() {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "El agente {} declara provider y providers; usa solo providers para encadenar proveedores",
+                    agent_id
+                ));
+            } else {
+                // Cada proveedor de la cadena recibe las mismas opciones del agente
+                let checked = ProviderChain::from_value(providers).and_then(|chain| {
+                    chain
+                        .providers
+                        .iter()
+                        .filter_map(|provider| LLMProviderKind::from_name(&provider.provider))
+                        .try_for_each(|kind| kind.check_options(agent))
+                });
+                if let Err(e) = checked {
+                    self.error(codes::INVALID_CONFIG, message!(
+                        "Cadena de proveedores inválida en el agente {}: {}",
+                        agent_id, e
+                    ));
+                }
+            }
+        }
+        self.locate_option(from, agent, PROVIDERS_OPTION);
+
+        // Los guardrails filtran los prompts y las respuestas de los agentes LLM
+        let from = self.diagnostics.len();
+        if let Some(guardrails) = agent.config_value(GUARDRAILS_OPTION) {
+            if agent.agent_type != AgentType::LLM {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "El agente {} ({}) no admite guardrails; solo los agentes LLM filtran prompts y respuestas",
+                    agent_id, agent.agent_type
+                ));
+            } else if let Err(e) = GuardrailsConfig::from_value(guardrails) {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "Guardrails inválidos en el agente {}: {}",
+                    agent_id, e
+                ));
+            }
+        }
+        self.locate_option(from, agent, GUARDRAILS_OPTION);
+
+        // La memoria de sesiones solo existe en los agentes LLM
+        let from = self.diagnostics.len();
+        if let Some(memory) = agent.config_value(MEMORY_OPTION) {
+            if agent.agent_type != AgentType::LLM {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "El agente {} ({}) no admite memory; solo los agentes LLM recuerdan sesiones",
+                    agent_id, agent.agent_type
+                ));
+            } else if well_typed {
+                self.validate_memory(&agent_id, memory, input_schema);
+            }
+        }
+        self.locate_option(from, agent, MEMORY_OPTION);
+
+        // El presupuesto de tokens solo se declara en los agentes LLM
+        let from = self.diagnostics.len();
+        if let Some(budget) = agent.config_value(BUDGET_OPTION) {
+            if agent.agent_type != AgentType::LLM {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "El agente {} ({}) no admite budget; solo los agentes LLM consumen tokens",
+                    agent_id, agent.agent_type
+                ));
+            } else if let Err(e) = LlmBudgetConfig::from_value(budget) {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "Presupuesto inválido en el agente {}: {}",
+                    agent_id, e
+                ));
+            }
+        }
+        self.locate_option(from, agent, BUDGET_OPTION);
+
+        // El enrutamiento por clave solo existe en los agentes Router
+        let from = self.diagnostics.len();
+        if let Some(strategy) = agent.config_value(STRATEGY_OPTION) {
+            if agent.agent_type != AgentType::Router {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "El agente {} ({}) no admite strategy; solo los agentes Router reparten mensajes",
+                    agent_id, agent.agent_type
+                ));
+            } else if well_typed {
+                self.validate_routing(&agent_id, strategy, input_schema);
+            }
+        }
+        self.locate_option(from, agent, STRATEGY_OPTION);
+
+        // Los Router pueden leer su tabla de rutas de un recurso que se recarga en caliente
+        if agent.agent_type == AgentType::Router {
+            let from = self.diagnostics.len();
+            match RouteTableConfig::from_agent(agent) {
+                Err(e) => self.error(codes::INVALID_CONFIG, message!(
+                    "Tabla de rutas inválida en el agente {}: {}",
+                    agent_id, e
+                )),
+                Ok(Some(_)) if agent.config_value("routes_path").is_some() => self.error(codes::INVALID_CONFIG, message!(
+                    "El agente {} declara rules y routes_path; usa solo rules para leer la tabla de un recurso",
+                    agent_id
+                )),
+                Ok(_) => {}
+            }
+            self.locate_option(from, agent, RULES_OPTION);
+        }
+
+        // El SLA, las escalaciones y las delegaciones solo existen en las revisiones humanas
+        if agent.agent_type != AgentType::HumanReview {
+            for option in [SLA_OPTION, ESCALATION_OPTION, DELEGATION_OPTION, SLA_BREACH_OPTION] {
+                if agent.config_value(option).is_some() {
+                    let from = self.diagnostics.len();
+                    self.error(codes::INVALID_CONFIG, message!(
+                        "El agente {} ({}) no admite {}; solo los agentes HumanReview tienen SLA",
+                        agent_id, agent.agent_type, option
+                    ));
+                    self.locate_option(from, agent, option);
+                }
+            }
+            if agent.config_value(AUDIT_OPTION).is_some() {
+                let from = self.diagnostics.len();
+                self.error(codes::INVALID_CONFIG, message!(
+                    "El agente {} ({}) no admite audit; solo se auditan las decisiones de los agentes HumanReview",
+                    agent_id, agent.agent_type
+                ));
+                self.locate_option(from, agent, AUDIT_OPTION);
+            }
+            if agent.config_value(AUTH_OPTION).is_some() {
+                let from = self.diagnostics.len();
+                self.error(codes::INVALID_CONFIG, message!(
+                    "El agente {} ({}) no admite auth; solo se autentican los agentes HumanReview y las fuentes HTTP",
+                    agent_id, agent.agent_type
+                ));
+                self.locate_option(from, agent, AUTH_OPTION);
+            }
+        }
+
+        // Validar configuración específica del tipo de agente
+        match agent.agent_type {
+            AgentType::MLModel => self.validate_ml_agent(agent),
+            AgentType::HumanReview if well_typed => self.validate_human_review(agent),
+            // Los tipos incorrectos ya se han informado con el esquema de configuración
+            AgentType::QualityMonitor if well_typed => self.validate_quality_monitor(agent, input_schema),
+            AgentType::Aggregator if well_typed => self.validate_aggregator(agent, input_schema),
+            AgentType::RuleEngine if well_typed => self.validate_rule_engine(agent, input_schema),
+            AgentType::BayesianNetwork if well_typed => self.validate_bayesian_network(agent),
+            AgentType::Custom if well_typed => self.validate_custom_agent(agent),
+            AgentType::DataNormalizer if well_typed => self.validate_data_normalizer(agent, input_schema),
+            AgentType::MissingValueHandler if well_typed => self.validate_missing_value_handler(agent, input_schema),
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Avisa de las variables `${env.NOMBRE}` sin valor en este entorno y de
+    /// los placeholders que no son referencias a variables de entorno.
+    fn check_placeholders(&mut self, agent: &Agent) {
+        let agent_id = agent_name(agent);
+        for placeholder in agent.placeholders() {
+            match placeholder {
+                Placeholder::Env(name) if std::env::var_os(name).is_none() => self.warn(
+                    codes::UNRESOLVED_PLACEHOLDER,
+                    message!(
+                        "El agente {} usa ${{env.{}}}, que no está definida en este entorno; en el clúster se lee del Secret del despliegue",
+                        agent_id, name
+                    ),
+                ),
+                Placeholder::Env(_) => {}
+                Placeholder::Unknown(placeholder) => self.warn(
+                    codes::UNRESOLVED_PLACEHOLDER,
+                    message!(
+                        "El agente {} usa {}, que no es una referencia ${{env.NOMBRE}} y se deja sin resolver",
+                        agent_id, placeholder
+                    ),
+                ),
+            }
+        }
+    }
+
+    /// Valida la imagen con la que se construye y despliega un agente.
+    fn validate_image(&mut self, agent: &Agent) {
+        let Some(Value::String(image)) = agent.config_value(IMAGE_OPTION) else {
+            return;
+        };
+        if let Err(e) = validate_image(image) {
+            let agent_id = agent_name(agent);
+            let from = self.diagnostics.len();
+            self.error(codes::INVALID_CONFIG, message!("Imagen inválida en el agente {}: {}", agent_id, e));
+            self.locate_option(from, agent, IMAGE_OPTION);
+        }
+    }
+
+    /// Avisa de las credenciales escritas en claro en las opciones de un agente,
+    /// que deberían leerse con `secret("nombre")`.
+    fn check_inline_secrets(&mut self, agent: &Agent) {
+        let agent_id = agent_name(agent);
+        let mut options: Vec<(&String, &Value, &Span)> = agent
+            .config
+            .iter()
+            .filter_map(|arg| match arg {
+                Argument::Named(name, value, span) => Some((name, value, span)),
+                _ => None,
+            })
+            .rev()
+            .collect();
+        // Las credenciales suelen ir dentro de objetos como `options`; se
+        // recorren en orden para que los avisos salgan siempre igual, y se
+        // señalan en la opción de la que cuelgan
+        while let Some((name, value, span)) = options.pop() {
+            let value = match value {
+                Value::String(value) => value,
+                Value::Object(fields) => {
+                    let mut fields: Vec<_> = fields.iter().collect();
+                    fields.sort_by_key(|(name, _)| *name);
+                    options.extend(fields.into_iter().rev().map(|(name, value)| (name, value, span)));
+                    continue;
+                }
+                _ => continue,
+            };
+            if is_credential(name) && !value.contains("${") {
+                self.report(
+                    Diagnostic::warning(
+                        codes::INLINE_SECRET,
+                        message!(
+                            "El agente {} escribe su {} en el DSL; acaba en claro en los manifiestos generados",
+                            agent_id, name
+                        ),
+                    )
+                    .with_help(message!("léelo del Secret del despliegue: {}: secret(\"{}\")", name, name.replace('_', "-")))
+                    .at(span),
+                );
+            }
+        }
+    }
+
+    /// Valida una condición `when` o `assert` de un agente: debe ser booleana y
+    /// sus operandos de tipos compatibles.
+    fn validate_condition(&mut self, agent: &Agent, option: &str, value: &Value, schema: Option<&Schema>) {
+        let agent_id = agent_name(agent);
+        let Value::Condition(expr) = value else {
+            self.error(codes::INVALID_CONDITION, message!(
+                "La condición {} del agente {} debe ser una expresión, no {}",
+                option, agent_id, value
+            ));
+            return;
+        };
+
+        let condition_type = self.condition_type(&agent_id, expr, schema);
+        if condition_type.is_some_and(|condition_type| condition_type != ConditionType::Boolean) {
+            self.error(codes::INVALID_CONDITION, message!(
+                "La condición {} del agente {} debe ser booleana: {}",
+                option, agent_id, expr
+            ));
+        }
+    }
+
+    /// Tipo de una expresión de condición; `None` si depende del mensaje.
+    ///
+    /// Con el esquema de los mensajes, los campos se resuelven a su tipo declarado.
+    fn condition_type(&mut self, agent_id: &Message, expr: &Expr, schema: Option<&Schema>) -> Option<ConditionType> {
+        match expr {
+            Expr::Field(path) | Expr::OptionalField(path, _) => {
+                let optional = match expr {
+                    Expr::OptionalField(_, optional) => optional.as_slice(),
+                    _ => &[],
+                };
+                if path.first().map(String::as_str) != Some("data") {
+                    self.report(
+                        Diagnostic::error(
+                            codes::UNKNOWN_FIELD,
+                            message!("El campo {} del agente {} debe empezar por 'data'", path.join("."), agent_id),
+                        )
+                        .with_suggestion(Some(format!("data.{}", path.join(".")))),
+                    );
+                    return None;
+                }
+                match schema?.field_type(path, optional) {
+                    Ok(field_type) => ConditionType::of_field(field_type?),
+                    Err(e) => {
+                        self.error(codes::UNKNOWN_FIELD, message!(
+                            "El campo {} del agente {} no está en el esquema de sus mensajes: {}",
+                            path.join("."),
+                            agent_id,
+                            e
+                        ));
+                        None
+                    }
+                }
+            }
+            Expr::Literal(value) => match value {
+                Value::Boolean(_) => Some(ConditionType::Boolean),
+                Value::Number(_) => Some(ConditionType::Number),
+                Value::String(_) => Some(ConditionType::String),
+                _ => Some(ConditionType::Null),
+            },
+            Expr::Default(value, default) => {
+                let value_type = self.condition_type(agent_id, value, schema);
+                let default_type = self.condition_type(agent_id, default, schema);
+                if let (Some(value_type), Some(default_type)) = (value_type, default_type) {
+                    if value_type != default_type && default_type != ConditionType::Null {
+                        self.error(codes::TYPE_MISMATCH, message!(
+                            "El valor por defecto de {} en el agente {} es {} y el valor {}: {}",
+                            value,
+                            agent_id,
+                            default_type.name(),
+                            value_type.name(),
+                            expr
+                        ));
+                    }
+                }
+                value_type.or(default_type)
+            }
+            Expr::Not(operand) => {
+                self.check_boolean_operand(agent_id, "!", operand, schema);
+                Some(ConditionType::Boolean)
+            }
+            Expr::And(left, right) | Expr::Or(left, right) => {
+                let op = if matches!(expr, Expr::And(..)) { "&&" } else { "||" };
+                self.check_boolean_operand(agent_id, op, left, schema);
+                self.check_boolean_operand(agent_id, op, right, schema);
+                Some(ConditionType::Boolean)
+            }
+            Expr::Compare(left, op, right) => {
+                let left_type = self.condition_type(agent_id, left, schema);
+                let right_type = self.condition_type(agent_id, right, schema);
+                if op.is_ordering() {
+                    // Un campo opcional ausente no se puede ordenar
+                    for operand in [left, right] {
+                        if is_nullable(operand, schema) {
+                            self.error(codes::OPTIONAL_FIELD, message!(
+                                "El campo {} del agente {} es opcional: usa ?? para darle un valor por defecto en {}",
+                                operand, agent_id, expr
+                            ));
+                        }
+                    }
+                    for operand_type in [left_type, right_type].into_iter().flatten() {
+                        if !matches!(operand_type, ConditionType::Number | ConditionType::String) {
+                            self.error(codes::TYPE_MISMATCH, message!(
+                                "El operador {} del agente {} solo compara números o textos: {}",
+                                op.as_str(),
+                                agent_id,
+                                expr
+                            ));
+                            return Some(ConditionType::Boolean);
+                        }
+                    }
+                }
+                if let (Some(left_type), Some(right_type)) = (left_type, right_type) {
+                    let comparable = left_type == right_type
+                        || (!op.is_ordering() && (left_type == ConditionType::Null || right_type == ConditionType::Null));
+                    if !comparable {
+                        self.error(codes::TYPE_MISMATCH, message!(
+                            "El agente {} compara {} con {}: {}",
+                            agent_id,
+                            left_type.name(),
+                            right_type.name(),
+                            expr
+                        ));
+                    }
+                }
+                Some(ConditionType::Boolean)
+            }
+        }
+    }
+
+    /// Comprueba que el operando de `!`, `&&` o `||` sea booleano.
+    fn check_boolean_operand(&mut self, agent_id: &Message, op: &str, operand: &Expr, schema: Option<&Schema>) {
+        let operand_type = self.condition_type(agent_id, operand, schema);
+        if let Some(operand_type) = operand_type.filter(|operand_type| *operand_type != ConditionType::Boolean) {
+            self.error(codes::TYPE_MISMATCH, message!(
+                "El operador {} del agente {} espera booleanos y recibe {}: {}",
+                op,
+                agent_id,
+                operand_type.name(),
+                operand
+            ));
+        }
+    }
+
+    /// Valida un patrón glob de recursos como `file://prompts/*.md`.
+    fn validate_resource_glob(&mut self, name: &str, pattern: &str) {
+        let (scheme, path) = pattern.split_once("://").unwrap_or(("", pattern));
+
+        // HTTP no permite listar directorios, así que no hay forma de expandir el patrón
+        if matches!(scheme, "http" | "https") {
+            self.error(codes::INVALID_RESOURCE_GLOB, message!(
+                "'{}' usa un glob sobre {}, que no admite listados: {}",
+                name, scheme, pattern
+            ));
+            return;
+        }
+
+        let misplaced_recursive = path
+            .split('/')
+            .any(|segment| segment.contains("**") && segment != "**");
+        if misplaced_recursive {
+            self.error(codes::INVALID_RESOURCE_GLOB, message!(
+                "Glob inválido en '{}': '**' debe ocupar un segmento completo ({})",
+                name, pattern
+            ));
+        }
+    }
+
+    /// Valida un agente de modelo de ML.
+    fn validate_ml_agent(&mut self, agent: &Agent) {
+        // Verificar que tenga el campo 'model_path' o 'model_name' configurado
+        let has_model = agent.config.iter().any(|arg| match arg {
+            Argument::Named(name, ..) => name == "model_path" || name == "model_name",
+            _ => false,
+        });
+
+        if !has_model {
+            self.report(
+                Diagnostic::error(
+                    codes::INVALID_CONFIG,
+                    message!("Los agentes de ML deben tener 'model_path' o 'model_name' configurado"),
+                )
+                .with_help(message!("añade model_path: \"<uri del modelo>\" o model_name: \"<nombre>\"")),
+            );
+        }
+    }
+
+    /// Valida un monitor de calidad: métricas y umbrales conocidos y, para
+    /// medir la conformidad, un esquema de entrada con el que comparar.
+    fn validate_quality_monitor(&mut self, agent: &Agent, input_schema: Option<&Schema>) {
+        let agent_id = agent_name(agent);
+        let has_schema = input_schema.is_some() || matches!(agent.input_schema(), Ok(Some(_)));
+        match QualityMonitorConfig::from_agent(agent) {
+            Err(e) => {
+                let from = self.diagnostics.len();
+                self.error(codes::INVALID_CONFIG, message!(
+                    "Monitor de calidad inválido en el agente {}: {}",
+                    agent_id, e
+                ));
+                self.locate_cause(from, agent, &e.written());
+            }
+            Ok(config) if config.metrics.contains(&QualityMetric::Conformity) && !has_schema => {
+                self.report(
+                    Diagnostic::warning(
+                        codes::INVALID_CONFIG,
+                        message!(
+                            "El monitor de calidad {} mide la conformidad, pero los mensajes que consume no tienen esquema; todos contarán como conformes",
+                            agent_id
+                        ),
+                    )
+                    .with_help(message!("declara input_schema en el monitor o el esquema de su topic de entrada")),
+                );
+            }
+            Ok(_) => {}
+        }
+    }
+
+    /// Valida un agregador: su ventana, las claves por las que agrupa, que
+    /// deben estar en el esquema de los mensajes si lo hay, y los valores que
+    /// reduce: números para sum y avg, números o textos para min y max.
+    fn validate_aggregator(&mut self, agent: &Agent, input_schema: Option<&Schema>) {
+        let agent_id = agent_name(agent);
+        let config = match AggregatorConfig::from_agent(agent) {
+            Ok(config) => config,
+            Err(e) => {
+                let from = self.diagnostics.len();
+                self.error(codes::INVALID_CONFIG, message!(
+                    "Agregador inválido en el agente {}: {}",
+                    agent_id, e
+                ));
+                self.locate_cause(from, agent, &e.written());
+                return;
+            }
+        };
+
+        let from = self.diagnostics.len();
+        for path in &config.group_by {
+            self.condition_type(&agent_id, &Expr::Field(path.clone()), input_schema);
+        }
+        self.locate_option(from, agent, GROUP_BY_OPTION);
+        let from = self.diagnostics.len();
+        for (name, reduction) in &config.reductions {
+            let Some(expr) = &reduction.of else {
+                continue;
+            };
+            let value_type = self.condition_type(&agent_id, expr, input_schema);
+            let Some(value_type) = value_type else {
+                continue;
+            };
+            let expected = if reduction.reducer.is_numeric() {
+                value_type == ConditionType::Number
+            } else if reduction.reducer.is_ordering() {
+                matches!(value_type, ConditionType::Number | ConditionType::String)
+            } else {
+                true
+            };
+            if !expected {
+                self.error(codes::TYPE_MISMATCH, message!(
+                    "La reducción {} del agente {} no puede aplicar {} a {}: {}",
+                    name,
+                    agent_id,
+                    reduction.reducer.as_str(),
+                    value_type.name(),
+                    reduction
+                ));
+            }
+        }
+        self.locate_option(from, agent, REDUCE_OPTION);
+    }
+
+    /// Valida un motor de reglas: sus reglas, con nombres únicos y acciones
+    /// conocidas, y sus condiciones, que deben ser booleanas y leer campos
+    /// del esquema de los mensajes si lo hay.
+    fn validate_rule_engine(&mut self, agent: &Agent, input_schema: Option<&Schema>) {
+        let agent_id = agent_name(agent);
+        let config = match RuleEngineConfig::from_agent(agent) {
+            Ok(config) => config,
+            Err(e) => {
+                let from = self.diagnostics.len();
+                self.error(codes::INVALID_CONFIG, message!(
+                    "Motor de reglas inválido en el agente {}: {}",
+                    agent_id, e
+                ));
+                self.locate_cause(from, agent, &e.written());
+                return;
+            }
+        };
+
+        let from = self.diagnostics.len();
+        for rule in &config.rules {
+            let condition_type = self.condition_type(&agent_id, &rule.when, input_schema);
+            if condition_type.is_some_and(|condition_type| condition_type != ConditionType::Boolean) {
+                self.error(codes::INVALID_CONDITION, message!(
+                    "La regla {} del agente {} debe tener una condición booleana: {}",
+                    rule.name, agent_id, rule.when
+                ));
+            }
+        }
+        self.locate_option(from, agent, RULES_OPTION);
+    }
+
+    /// Valida una red bayesiana: un fichero de red en un formato que se sabe
+    /// leer y variables de consulta sin repetir.
+    fn validate_bayesian_network(&mut self, agent: &Agent) {
+        let agent_id = agent_name(agent);
+        if let Err(e) = BayesianNetworkConfig::from_agent(agent) {
+            let from = self.diagnostics.len();
+            self.error(codes::INVALID_CONFIG, message!(
+                "Red bayesiana inválida en el agente {}: {}",
+                agent_id, e
+            ));
+            self.locate_cause(from, agent, &e.written());
+            // Las causas que no nombran opción son las del fichero de la red
+            self.locate_option(from, agent, NETWORK_PATH_OPTION);
+        }
+    }
+
+    /// Valida el nombre de las plantillas de un agente Custom y su lenguaje;
+    /// que las plantillas existan se comprueba al generarlo.
+    fn validate_custom_agent(&mut self, agent: &Agent) {
+        let agent_id = agent_name(agent);
+        if let Err(e) = CustomAgentConfig::from_agent(agent) {
+            let from = self.diagnostics.len();
+            self.error(codes::INVALID_CONFIG, message!(
+                "Agente personalizado inválido {}: {}",
+                agent_id, e
+            ));
+            self.locate_cause(from, agent, &e.written());
+        }
+    }
+
+    /// Valida un normalizador: sus mapeos, que deben leer campos del esquema
+    /// de los mensajes si lo hay, y los campos que escala, que deben ser
+    /// números una vez mapeados.
+    fn validate_data_normalizer(&mut self, agent: &Agent, input_schema: Option<&Schema>) {
+        let agent_id = agent_name(agent);
+        let config = match DataNormalizerConfig::from_agent(agent) {
+            Ok(config) => config,
+            Err(e) => {
+                let from = self.diagnostics.len();
+                self.error(codes::INVALID_CONFIG, message!(
+                    "Normalizador inválido en el agente {}: {}",
+                    agent_id, e
+                ));
+                self.locate_cause(from, agent, &e.written());
+                return;
+            }
+        };
+
+        let from = self.diagnostics.len();
+        for mapping in &config.mappings {
+            if !config.scale.contains_key(&mapping.field) {
+                self.condition_type(&agent_id, &Expr::Field(mapping.from.clone()), input_schema);
+            }
+        }
+        self.locate_option(from, agent, MAPPINGS_OPTION);
+        let from = self.diagnostics.len();
+        for (field, scaler) in &config.scale {
+            let source = config.source_of(field);
+            let field_type = self.condition_type(&agent_id, &Expr::Field(source), input_schema);
+            if let Some(field_type) = field_type.filter(|field_type| *field_type != ConditionType::Number) {
+                self.error(codes::TYPE_MISMATCH, message!(
+                    "El agente {} no puede escalar {} con {}: es {}",
+                    agent_id,
+                    field,
+                    scaler.name(),
+                    field_type.name()
+                ));
+            }
+        }
+        self.locate_option(from, agent, SCALE_OPTION);
+    }
+
+    /// Valida un manejador de valores ausentes: los campos que rellena deben
+    /// estar en el esquema de los mensajes si lo hay, con el tipo de su valor
+    /// por defecto, y ser números si se imputan con mean o median.
+    fn validate_missing_value_handler(&mut self, agent: &Agent, input_schema: Option<&Schema>) {
+        let agent_id = agent_name(agent);
+        let config = match MissingValueHandlerConfig::from_agent(agent) {
+            Ok(config) => config,
+            Err(e) => {
+                let from = self.diagnostics.len();
+                self.error(codes::INVALID_CONFIG, message!(
+                    "Manejador de valores ausentes inválido en el agente {}: {}",
+                    agent_id, e
+                ));
+                self.locate_cause(from, agent, &e.written());
+                return;
+            }
+        };
+
+        let from = self.diagnostics.len();
+        for (field, value) in &config.defaults {
+            let path = MissingValueHandlerConfig::path(field);
+            let field_type = self.condition_type(&agent_id, &Expr::Field(path.clone()), input_schema);
+            let default_type = self.condition_type(&agent_id, &Expr::Literal(value.clone()), input_schema);
+            if let (Some(field_type), Some(default_type)) = (field_type, default_type) {
+                if field_type != default_type {
+                    let entry = format!("{}: {}", field, value);
+                    self.error(codes::TYPE_MISMATCH, message!(
+                        "El valor por defecto de {} en el agente {} es {} y el valor {}: {}",
+                        path.join("."),
+                        agent_id,
+                        default_type.name(),
+                        field_type.name(),
+                        entry
+                    ));
+                }
+            }
+        }
+        self.locate_option(from, agent, DEFAULTS_OPTION);
+        let from = self.diagnostics.len();
+        for (field, imputation) in &config.impute {
+            let path = MissingValueHandlerConfig::path(field);
+            let field_type = self.condition_type(&agent_id, &Expr::Field(path), input_schema);
+            if let Some(field_type) = field_type.filter(|field_type| imputation.is_numeric() && *field_type != ConditionType::Number) {
+                self.error(codes::TYPE_MISMATCH, message!(
+                    "El agente {} no puede imputar {} con {}(): es {}",
+                    agent_id,
+                    field,
+                    imputation.as_str(),
+                    field_type.name()
+                ));
+            }
+        }
+        self.locate_option(from, agent, IMPUTE_OPTION);
+    }
+
+    /// Valida el SLA de un agente HumanReview: escalaciones ordenadas y
+    /// anteriores al SLA, y cada revisor en un solo grupo de delegación; la
+    /// espera de sus mensajes: un timeout que no supera el SLA, un subject
+    /// on_timeout sin comodines y canales de notificación válidos; su
+    /// auditoría: emisor OIDC https y Secret de firma con nombre válido; y su
+    /// autenticación, que define el emisor y la audiencia en lugar de audit.
+    fn validate_human_review(&mut self, agent: &Agent) {
+        let agent_id = agent_name(agent);
+        match HumanReviewConfig::sla_of(agent) {
+            Ok(mut config) => {
+                if let Err(e) = config.read_wait(agent) {
+                    let from = self.diagnostics.len();
+                    self.error(codes::INVALID_CONFIG, message!(
+                        "Revisión inválida en el agente {}: {}",
+                        agent_id, e
+                    ));
+                    self.locate_cause(from, agent, &e.written());
+                }
+            }
+            Err(e) => {
+                let from = self.diagnostics.len();
+                self.error(codes::INVALID_CONFIG, message!(
+                    "SLA inválido en el agente {}: {}",
+                    agent_id, e
+                ));
+                self.locate_cause(from, agent, &e.written());
+            }
+        }
+        let audit = match ReviewAuditConfig::from_agent(agent) {
+            Ok(audit) => audit,
+            Err(e) => {
+                let from = self.diagnostics.len();
+                self.error(codes::INVALID_CONFIG, message!(
+                    "Auditoría inválida en el agente {}: {}",
+                    agent_id, e
+                ));
+                self.locate_cause(from, agent, &e.written());
+                ReviewAuditConfig::default()
+            }
+        };
+        match OidcAuthConfig::from_agent(agent) {
+            Ok(Some(_)) if audit.issuer.is_some() || audit.audience.is_some() => {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "El agente {} define el emisor y la audiencia en auth; quítalos de audit",
+                    agent_id
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                let from = self.diagnostics.len();
+                self.error(codes::INVALID_CONFIG, message!(
+                    "Autenticación inválida en el agente {}: {}",
+                    agent_id, e
+                ));
+                self.locate_cause(from, agent, &e.written());
+            }
+        }
+    }
+
+    /// Valida el proveedor de un agente LLM: el nombre de un proveedor, un
+    /// proveedor alojado como `OpenAI(gpt-4o)` o un servidor de inferencia
+    /// `VLLM(...)` / `TGI(...)`, con las opciones que su API exige o rechaza.
+    fn validate_provider(&mut self, agent_id: &Message, agent: &Agent, provider: &Value) {
+        // Las referencias sin resolver ya se informan como constantes no definidas
+        if matches!(provider, Value::Path(_) | Value::Variable(_)) {
+            return;
+        }
+        let checked = LLMProvider::from_value(provider).and_then(|provider| provider.kind.check_options(agent));
+        if let Err(e) = checked {
+            let from = self.diagnostics.len();
+            self.error(codes::INVALID_CONFIG, message!(
+                "Proveedor inválido en el agente {}: {}",
+                agent_id, e
+            ));
+            self.locate_cause(from, agent, &e.written());
+        }
+    }
+
+    /// Valida el feature store de un modelo ML: sus claves identifican la
+    /// entidad de cada mensaje, así que deben ser campos string o integer de
+    /// los mensajes que consume.
+    fn validate_feature_store(&mut self, agent_id: &Message, value: &Value, input_schema: Option<&Schema>) {
+        let config = match FeatureStoreConfig::from_value(value) {
+            Ok(config) => config,
+            Err(e) => {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "Feature store inválido en el agente {}: {}",
+                    agent_id, e
+                ));
+                return;
+            }
+        };
+        let Some(schema) = input_schema else {
+            self.report(
+                Diagnostic::warning(
+                    codes::INVALID_CONFIG,
+                    message!(
+                        "No se pueden comprobar las claves de la feature view '{}' del agente {}: los mensajes que consume no tienen esquema",
+                        config.feature_view, agent_id
+                    ),
+                )
+                .with_help(message!("declara input_schema en el agente o el esquema de su topic de entrada")),
+            );
+            return;
+        };
+
+        for key in &config.keys {
+            let Some(declared) = schema.fields.get(key) else {
+                let fields = schema.fields.keys().map(String::as_str);
+                self.report(
+                    Diagnostic::error(
+                        codes::INVALID_CONFIG,
+                        message!(
+                            "La clave '{}' de la feature view '{}' del agente {} no es un campo de los mensajes que consume",
+                            key, config.feature_view, agent_id
+                        ),
+                    )
+                    .with_suggestion(closest(key, fields)),
+                );
+                continue;
+            };
+            let field_type = declared.trim_end_matches('?');
+            if !matches!(field_type, "string" | "integer") {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "La clave '{}' de la feature view '{}' del agente {} es de tipo {}; las entidades se identifican con campos string o integer",
+                    key, config.feature_view, agent_id, field_type
+                ));
+            } else if declared.ends_with('?') {
+                self.warn(codes::INVALID_CONFIG, message!(
+                    "La clave '{}' de la feature view '{}' del agente {} es opcional; los mensajes sin ella se procesarán sin features",
+                    key, config.feature_view, agent_id
+                ));
+            }
+        }
+    }
+
+    /// Valida la memoria de sesiones de un agente LLM: su `scope` identifica
+    /// la sesión de cada mensaje, así que, si los mensajes que consume tienen
+    /// esquema, debe ser uno de sus campos.
+    fn validate_memory(&mut self, agent_id: &Message, value: &Value, input_schema: Option<&Schema>) {
+        let config = match MemoryConfig::from_value(value) {
+            Ok(config) => config,
+            Err(e) => {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "Memoria inválida en el agente {}: {}",
+                    agent_id, e
+                ));
+                return;
+            }
+        };
+        let Some(schema) = input_schema else {
+            return;
+        };
+
+        let (field, nested) = match config.scope.split_once('.') {
+            Some((field, _)) => (field, true),
+            None => (config.scope.as_str(), false),
+        };
+        let Some(declared) = schema.fields.get(field) else {
+            let fields = schema.fields.keys().map(String::as_str);
+            self.report(
+                Diagnostic::error(
+                    codes::INVALID_CONFIG,
+                    message!(
+                        "El scope '{}' de la memoria del agente {} no es un campo de los mensajes que consume",
+                        config.scope, agent_id
+                    ),
+                )
+                .with_suggestion(closest(field, fields)),
+            );
+            return;
+        };
+        let field_type = declared.trim_end_matches('?');
+        if !nested && !matches!(field_type, "string" | "integer") {
+            self.error(codes::INVALID_CONFIG, message!(
+                "El scope '{}' de la memoria del agente {} es de tipo {}; las sesiones se identifican con campos string o integer",
+                config.scope, agent_id, field_type
+            ));
+        } else if declared.ends_with('?') {
+            self.warn(codes::INVALID_CONFIG, message!(
+                "El scope '{}' de la memoria del agente {} es opcional; los mensajes sin él se procesarán sin historial",
+                config.scope, agent_id
+            ));
+        }
+    }
+
+    /// Valida el enrutamiento por clave de un Router: la clave decide la
+    /// partición de cada mensaje, así que debe ser un campo string o integer
+    /// de los mensajes que consume.
+    fn validate_routing(&mut self, agent_id: &Message, value: &Value, input_schema: Option<&Schema>) {
+        let config = match HashRoutingConfig::from_value(value) {
+            Ok(config) => config,
+            Err(e) => {
+                self.error(codes::INVALID_CONFIG, message!(
+                    "Estrategia de enrutamiento inválida en el agente {}: {}",
+                    agent_id, e
+                ));
+                return;
+            }
+        };
+        let key = config.key.join(".");
+        let Some(schema) = input_schema else {
+            self.report(
+                Diagnostic::warning(
+                    codes::INVALID_CONFIG,
+                    message!(
+                        "No se puede comprobar la clave {} del agente {}: los mensajes que consume no tienen esquema",
+                        key, agent_id
+                    ),
+                )
+                .with_help(message!("declara input_schema en el agente o el esquema de su topic de entrada")),
+            );
+            return;
+        };
+
+        let declared = match schema.field_type(&config.key, &[]) {
+            Ok(declared) => declared,
+            Err(e) => {
+                let fields = schema.fields.keys().map(String::as_str);
+                self.report(
+                    Diagnostic::error(
+                        codes::INVALID_CONFIG,
+                        message!(
+                            "La clave {} del agente {} no está en el esquema de sus mensajes: {}",
+                            key, agent_id, e
+                        ),
+                    )
+                    .with_suggestion(closest(&config.key[1], fields).map(|field| format!("data.{}", field))),
+                );
+                return;
+            }
+        };
+        // Los campos dentro de objetos y listas no se declaran
+        let Some(declared) = declared else {
+            return;
+        };
+        let field_type = declared.trim_end_matches('?');
+        if !matches!(field_type, "string" | "integer") {
+            self.error(codes::INVALID_CONFIG, message!(
+                "La clave {} del agente {} es de tipo {}; los mensajes se reparten por campos string o integer",
+                key, agent_id, field_type
+            ));
+        } else if declared.ends_with('?') {
+            self.warn(codes::INVALID_CONFIG, message!(
+                "La clave {} del agente {} es opcional; los mensajes sin ella no se podrán enrutar",
+                key, agent_id
+            ));
+        }
+    }
+
+    /// Valida un identificador (nombre de workflow, subworkflow, etc.).
+    fn validate_identifier(&mut self, id: &str, context: &str) {
+        if id.trim().is_empty() {
+            self.error(codes::INVALID_IDENTIFIER, message!("El {} no puede tener un nombre vacío", context));
+            return;
+        }
+
+        // Validar que solo contenga caracteres alfanuméricos y guiones bajos
+        if !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            let cleaned: String = id.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+            self.report(
+                Diagnostic::error(
+                    codes::INVALID_IDENTIFIER,
+                    message!("El {} '{}' solo puede contener caracteres alfanuméricos y guiones bajos", context, id),
+                )
+                .with_fix(id, cleaned.as_str())
+                .with_suggestion(Some(cleaned)),
+            );
+            return;
+        }
+
+        // Validar que no empiece con un número
+        if let Some(first_char) = id.chars().next() {
+            if first_char.is_ascii_digit() {
+                self.error(
+                    codes::INVALID_IDENTIFIER,
+                    message!("El {} '{}' no puede empezar con un número", context, id),
+                );
+            }
+        }
+    }
+
+    /// Registra un error o un aviso.
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Registra un error sin ayuda.
+    fn error(&mut self, code: &'static str, message: impl Into<Message>) {
+        self.report(Diagnostic::error(code, message));
+    }
+
+    /// Registra un aviso sin ayuda.
+    fn warn(&mut self, code: &'static str, message: impl Into<Message>) {
+        self.report(Diagnostic::warning(code, message));
+    }
+
+    /// Añade la posición de un nodo a los errores y avisos encontrados desde
+    /// `from` que aún no tienen una; los de sus nodos internos conservan la suya.
+    fn locate_errors(&mut self, from: usize, span: &Span) {
+        for diagnostic in &mut self.diagnostics[from..] {
+            *diagnostic = diagnostic.clone().at(span);
+        }
+    }
+
+    /// Señala la opción de un agente en los errores y avisos encontrados desde
+    /// `from` que aún no tienen posición; sin ella se quedan en el agente.
+    fn locate_option(&mut self, from: usize, agent: &Agent, option: &str) {
+        if let Some(span) = agent.option_span(option) {
+            self.locate_errors(from, span);
+        }
+    }
+
+    /// Señala, en los errores y avisos encontrados desde `from` que aún no
+    /// tienen posición, la opción del agente que nombra primero la causa.
+    fn locate_cause(&mut self, from: usize, agent: &Agent, cause: &str) {
+        let named = agent
+            .config
+            .iter()
+            .filter_map(|arg| match arg {
+                Argument::Named(name, _, span) => Some((mentions(cause, name)?, span)),
+                _ => None,
+            })
+            .min_by_key(|(at, _)| *at);
+        if let Some((_, span)) = named {
+            self.locate_errors(from, span);
+        }
+    }
+
+    /// Propone un ID para un agente sin él: su tipo en snake_case, numerado si ya existe.
+    fn propose_agent_id(&mut self, agent_type: AgentType) -> String {
+        let name: Vec<char> = format!("{:?}", agent_type).chars().collect();
+        let mut base = String::new();
+        for (i, c) in name.iter().enumerate() {
+            let word_start = i > 0
+                && c.is_ascii_uppercase()
+                && (name[i - 1].is_ascii_lowercase() || name.get(i + 1).is_some_and(char::is_ascii_lowercase));
+            if word_start {
+                base.push('_');
+            }
+            base.push(c.to_ascii_lowercase());
+        }
+
+        let mut id = base.clone();
+        let mut number = 1;
+        while self.declared_agent_ids.contains(&id) || self.agent_ids.contains(&id) {
+            number += 1;
+            id = format!("{}_{}", base, number);
+        }
+        self.declared_agent_ids.insert(id.clone());
+        id
+    }
+
+    /// Reinicia el estado del analizador.
+    fn reset(&mut self) {
+        self.agent_ids.clear();
+        self.declared_agent_ids.clear();
+        self.workflow_names.clear();
+        self.subworkflows.clear();
+        self.diagnostics.clear();
+        self.message_schemas.clear();
+        self.consumed_topics.clear();
+    }
+}
+
+/// Si un operando es un campo que el esquema declara opcional.
+fn is_nullable(expr: &Expr, schema: Option<&Schema>) -> bool {
+    let (path, optional) = match expr {
+        Expr::Field(path) => (path, &[][..]),
+        Expr::OptionalField(path, optional) => (path, optional.as_slice()),
+        _ => return false,
+    };
+    schema
+        .and_then(|schema| schema.field_type(path, optional).ok().flatten())
+        .is_some_and(|field_type| field_type.ends_with('?'))
+}
+
+/// Componentes fuertemente conexas con algún ciclo del subgrafo de los nodos incluidos, por el algoritmo de Tarjan.
+///
+/// Cada componente lleva sus nodos en orden, y las componentes van ordenadas por su primer nodo.
+fn cyclic_components(edges: &[Vec<usize>], included: &[bool]) -> Vec<Vec<usize>> {
+    struct Tarjan<'a> {
+        edges: &'a [Vec<usize>],
+        included: &'a [bool],
+        index: Vec<Option<usize>>,
+        lowlink: Vec<usize>,
+        on_stack: Vec<bool>,
+        stack: Vec<usize>,
+        next_index: usize,
+        components: Vec<Vec<usize>>,
+    }
+
+    impl Tarjan<'_> {
+        fn visit(&mut self, node: usize) {
+            self.index[node] = Some(self.next_index);
+            self.lowlink[node] = self.next_index;
+            self.next_index += 1;
+            self.stack.push(node);
+            self.on_stack[node] = true;
+
+            for &next in &self.edges[node] {
+                if !self.included[next] {
+                    continue;
+                }
+                match self.index[next] {
+                    None => {
+                        self.visit(next);
+                        self.lowlink[node] = self.lowlink[node].min(self.lowlink[next]);
+                    }
+                    Some(index) if self.on_stack[next] => self.lowlink[node] = self.lowlink[node].min(index),
+                    Some(_) => {}
+                }
+            }
+
+            if Some(self.lowlink[node]) == self.index[node] {
+                let mut component = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                // Un nodo suelto solo forma ciclo si se consume a sí mismo
+                if component.len() > 1 || self.edges[node].contains(&node) {
+                    component.sort_unstable();
+                    self.components.push(component);
+                }
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        edges,
+        included,
+        index: vec![None; edges.len()],
+        lowlink: vec![0; edges.len()],
+        on_stack: vec![false; edges.len()],
+        stack: Vec::new(),
+        next_index: 0,
+        components: Vec::new(),
+    };
+    for (node, _) in included.iter().enumerate().filter(|(_, included)| **included) {
+        if tarjan.index[node].is_none() {
+            tarjan.visit(node);
+        }
+    }
+    tarjan.components.sort_unstable();
+    tarjan.components
+}
+
+/// El ciclo más corto que pasa por el primer nodo de una componente fuertemente conexa, sin salir de ella.
+fn shortest_cycle(edges: &[Vec<usize>], component: &[usize]) -> Vec<usize> {
+    let start = component[0];
+    let mut previous: HashMap<usize, usize> = HashMap::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(node) = queue.pop_front() {
+        for &next in &edges[node] {
+            if next == start {
+                // Se reconstruye el camino hacia atrás hasta el inicio
+                let mut cycle = vec![node];
+                while let Some(&before) = cycle.last().and_then(|last| previous.get(last)) {
+                    cycle.push(before);
+                }
+                cycle.reverse();
+                return cycle;
+            }
+            if component.binary_search(&next).is_ok() && !previous.contains_key(&next) {
+                previous.insert(next, node);
+                queue.push_back(next);
+            }
+        }
+    }
+    vec![start]
+}
+
+/// Dónde nombra un texto una opción, como palabra completa.
+fn mentions(text: &str, option: &str) -> Option<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(option).map(|(at, _)| at).find(|&at| {
+        !text[..at].ends_with(is_word) && !text[at + option.len()..].starts_with(is_word)
+    })
+}
+
+/// El ID de un agente para los mensajes, o `<sin id>` si no lo tiene.
+fn agent_name(agent: &Agent) -> Message {
+    agent.id.clone().map_or_else(|| message!("<sin id>"), Message::Text)
+}
+
+/// Comprueba que un tamaño sea una cantidad de Kubernetes positiva (p. ej. `10Gi`).
+fn is_valid_quantity(size: &str) -> bool {
+    const SUFFIXES: [&str; 12] = ["Ki", "Mi", "Gi", "Ti", "Pi", "Ei", "k", "M", "G", "T", "P", "E"];
+
+    let number = SUFFIXES
+        .iter()
+        .find_map(|suffix| size.strip_suffix(suffix))
+        .unwrap_or(size);
+
+    !number.is_empty()
+        && number.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && number.parse::<f64>().is_ok_and(|n| n > 0.0)
+}
+
+/// Si una opción, en minúsculas, guarda una credencial por su nombre (`api_key`, `db_password`...).
+fn is_credential(name: &str) -> bool {
+    const CREDENTIALS: [&str; 5] = ["api_key", "apikey", "token", "password", "secret"];
+
+    CREDENTIALS
+        .iter()
+        .any(|credential| name == *credential || name.strip_suffix(credential).is_some_and(|prefix| prefix.ends_with('_')))
 }
@@ -0,0 +1,186 @@
+//! Catálogo de versiones de los esquemas de mensajes.
+//!
+//! Cada `kumeo generate` registra en `kumeo-schemas.json`, junto al programa,
+//! los esquemas `output_schema` de los agentes con el topic en el que
+//! publican, y los workflows que consumen cada topic. El análisis semántico
+//! compara los esquemas actuales con la última versión registrada: quitar o
+//! cambiar el tipo de un campo de un topic que otro workflow consume exige
+//! incrementar `schema_version`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{Program, Schema, Workflow};
+use crate::error::{KumeoError, Result};
+use crate::i18n::Message;
+use crate::message;
+
+/// Archivo del catálogo, junto al programa.
+pub const SCHEMA_CATALOG_FILE: &str = "kumeo-schemas.json";
+
+/// Campos de un esquema con su tipo.
+pub type SchemaFields = BTreeMap<String, String>;
+
+/// Esquemas y consumidores de un topic.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopicSchemas {
+    /// Workflow cuyo agente publica en el topic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub producer: Option<String>,
+    /// Workflows que consumen el topic
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub consumers: BTreeSet<String>,
+    /// Campos de cada versión registrada del esquema
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub versions: BTreeMap<u32, SchemaFields>,
+}
+
+/// Esquema que publica un agente.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishedSchema {
+    /// Topic en el que publica el agente
+    pub topic: String,
+    /// Workflow del agente
+    pub workflow: String,
+    /// ID del agente
+    pub agent: String,
+    /// Valor de `schema_version`
+    pub version: u32,
+    /// Campos del esquema
+    pub fields: SchemaFields,
+}
+
+/// Esquemas registrados, por topic.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaCatalog {
+    /// Esquemas y consumidores de cada topic
+    pub topics: BTreeMap<String, TopicSchemas>,
+}
+
+impl SchemaCatalog {
+    /// Lee el catálogo de un directorio; sin catálogo devuelve uno vacío.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(SCHEMA_CATALOG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        serde_json::from_str(&content).map_err(|e| {
+            KumeoError::IoError(message!("Catálogo de esquemas inválido {}: {}", path.display(), e))
+        })
+    }
+
+    /// Escribe el catálogo en un directorio.
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        std::fs::write(dir.join(SCHEMA_CATALOG_FILE), self.to_json()?)?;
+        Ok(())
+    }
+
+    /// Contenido del archivo del catálogo.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| KumeoError::IoError(e.to_string().into()))
+    }
+
+    /// Si algún topic tiene un esquema registrado.
+    pub fn has_schemas(&self) -> bool {
+        self.topics.values().any(|topic| !topic.versions.is_empty())
+    }
+
+    /// Última versión registrada del esquema de un topic.
+    pub fn latest(&self, topic: &str) -> Option<(u32, &SchemaFields)> {
+        let (version, fields) = self.topics.get(topic)?.versions.last_key_value()?;
+        Some((*version, fields))
+    }
+
+    /// Workflows registrados como consumidores de un topic.
+    pub fn consumers(&self, topic: &str) -> impl Iterator<Item = &str> {
+        self.topics.get(topic).into_iter().flat_map(|topic| topic.consumers.iter().map(String::as_str))
+    }
+
+    /// Registra los esquemas que publican los workflows de un programa y los
+    /// topics que consumen.
+    ///
+    /// Los consumos registrados antes por esos workflows se sustituyen, para
+    /// que un workflow que deja de consumir un topic deje de protegerlo.
+    pub fn record(&mut self, program: &Program) {
+        let workflows: BTreeSet<&str> = program.workflows.iter().map(|workflow| workflow.name.as_str()).collect();
+        for topic in self.topics.values_mut() {
+            topic.consumers.retain(|consumer| !workflows.contains(consumer.as_str()));
+        }
+
+        for workflow in &program.workflows {
+            for topic in consumed_topics(workflow) {
+                self.topics.entry(topic).or_default().consumers.insert(workflow.name.clone());
+            }
+            for schema in published_schemas(workflow) {
+                let topic = self.topics.entry(schema.topic).or_default();
+                topic.producer = Some(schema.workflow);
+                topic.versions.insert(schema.version, schema.fields);
+            }
+        }
+        self.topics
+            .retain(|_, topic| !topic.consumers.is_empty() || !topic.versions.is_empty());
+    }
+}
+
+/// Esquemas válidos que publican los agentes de un workflow.
+///
+/// Un agente publica en el topic de su `output` o en el destino del
+/// workflow. Los esquemas inválidos se omiten; el análisis los informa.
+pub fn published_schemas(workflow: &Workflow) -> Vec<PublishedSchema> {
+    let mut schemas = Vec::new();
+    for (agent, topics) in workflow.agents.iter().zip(workflow.deployed_topics()) {
+        let (Ok(Some((schema, version))), Some(topic)) = (agent.output_schema(), topics.output) else {
+            continue;
+        };
+        schemas.push(PublishedSchema {
+            topic,
+            workflow: workflow.name.clone(),
+            agent: agent.id.clone().unwrap_or_default(),
+            version,
+            fields: schema_fields(&schema),
+        });
+    }
+    schemas
+}
+
+/// Topics que consume un workflow: su fuente, las entradas de sus agentes y
+/// las de los subworkflows que invoca.
+pub fn consumed_topics(workflow: &Workflow) -> BTreeSet<String> {
+    let mut topics: BTreeSet<String> = workflow.source.iter().map(|source| source.topic().to_string()).collect();
+    topics.extend(workflow.deployed_topics().into_iter().filter_map(|topics| topics.input));
+    topics.extend(workflow.calls.iter().flat_map(|call| call.input.iter().cloned()));
+    topics
+}
+
+/// Campos de un esquema, en orden.
+pub fn schema_fields(schema: &Schema) -> SchemaFields {
+    schema.fields.iter().map(|(name, field_type)| (name.clone(), field_type.clone())).collect()
+}
+
+/// Esquema con los campos registrados en el catálogo.
+pub fn schema_from_fields(fields: &SchemaFields) -> Schema {
+    Schema {
+        fields: fields.iter().map(|(name, field_type)| (name.clone(), field_type.clone())).collect(),
+        strict: false,
+    }
+}
+
+/// Cambios incompatibles entre dos versiones de un esquema: campos
+/// eliminados o con otro tipo. Añadir campos es compatible.
+pub fn breaking_changes(previous: &SchemaFields, current: &SchemaFields) -> Vec<Message> {
+    previous
+        .iter()
+        .filter_map(|(name, previous_type)| match current.get(name) {
+            None => Some(message!("se elimina el campo '{}'", name)),
+            Some(current_type) if current_type != previous_type => Some(message!(
+                "el campo '{}' pasa de {} a {}",
+                name, previous_type, current_type
+            )),
+            Some(_) => None,
+        })
+        .collect()
+}
@@ -0,0 +1,204 @@
+//! Esquemas de configuración de los tipos de agente.
+//!
+//! Cada tipo de agente declara las opciones que entiende con su tipo y, si
+//! procede, su rango o si son obligatorias. Los esquemas de los agentes
+//! incluidos se distribuyen como datos en `agent_configs.json`; el esquema
+//! `"*"` se aplica a todos los tipos. Un tipo nuevo registra el suyo con
+//! [`ConfigSchemas::register`] o [`ConfigSchemas::extend_from_json`]. Las
+//! opciones que un esquema no declara no se comprueban.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{Agent, Argument, Value};
+use crate::error::{KumeoError, Result};
+use crate::i18n::Message;
+use crate::message;
+
+/// Esquemas de los agentes incluidos.
+const BUILTIN_SCHEMAS: &str = include_str!("agent_configs.json");
+
+/// Esquema que se aplica a todos los tipos de agente.
+pub const ANY_AGENT_TYPE: &str = "*";
+
+/// Tipo de una opción de configuración.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptionType {
+    /// Texto (o una ruta sin comillas)
+    String,
+    /// Cualquier número
+    Number,
+    /// Número sin decimales
+    Integer,
+    /// `true` o `false`
+    Boolean,
+    /// Objeto `{ ... }`
+    Object,
+    /// Lista `[ ... ]`
+    Array,
+    /// Segundos o un texto con sufijo `ms`, `s`, `m` o `h`
+    Duration,
+}
+
+impl std::fmt::Display for OptionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            OptionType::String => "string",
+            OptionType::Number => "number",
+            OptionType::Integer => "integer",
+            OptionType::Boolean => "boolean",
+            OptionType::Object => "object",
+            OptionType::Array => "array",
+            OptionType::Duration => "duration",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Esquema de una opción de configuración.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OptionSchema {
+    /// Tipo del valor
+    #[serde(rename = "type")]
+    pub option_type: OptionType,
+    /// Si el agente debe declarar la opción
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub required: bool,
+    /// Valor mínimo de una opción numérica
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    /// Valor máximo de una opción numérica
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    /// Esquemas de los campos de una opción de tipo objeto
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: ConfigSchema,
+}
+
+/// Opciones de un tipo de agente, por nombre.
+pub type ConfigSchema = BTreeMap<String, OptionSchema>;
+
+/// Esquemas de configuración por tipo de agente.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigSchemas {
+    types: BTreeMap<String, ConfigSchema>,
+}
+
+impl Default for ConfigSchemas {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl ConfigSchemas {
+    /// Esquemas de los agentes incluidos.
+    pub fn builtin() -> Self {
+        let types = serde_json::from_str(BUILTIN_SCHEMAS).expect("agent_configs.json debe ser un catálogo válido");
+        Self { types }
+    }
+
+    /// Registra el esquema de un tipo de agente, sustituyendo las opciones que ya declarara.
+    pub fn register(&mut self, agent_type: &str, schema: ConfigSchema) {
+        self.types.entry(agent_type.to_string()).or_default().extend(schema);
+    }
+
+    /// Registra los esquemas de un JSON con el formato de `agent_configs.json`.
+    pub fn extend_from_json(&mut self, json: &str) -> Result<()> {
+        let types: BTreeMap<String, ConfigSchema> = serde_json::from_str(json)
+            .map_err(|e| KumeoError::TypeError(message!("Esquemas de configuración inválidos: {}", e)))?;
+        for (agent_type, schema) in types {
+            self.register(&agent_type, schema);
+        }
+        Ok(())
+    }
+
+    /// Esquema de una opción para un tipo de agente.
+    pub fn option(&self, agent_type: &str, name: &str) -> Option<&OptionSchema> {
+        [agent_type, ANY_AGENT_TYPE]
+            .iter()
+            .find_map(|key| self.types.get(*key)?.get(name))
+    }
+
+    /// Problemas de la configuración de un agente, con la opción en la que
+    /// están: opciones obligatorias que faltan y valores con otro tipo o
+    /// fuera de rango.
+    pub fn check(&self, agent: &Agent) -> Vec<(String, Message)> {
+        let agent_type = agent.agent_type.to_string();
+        let mut problems = Vec::new();
+
+        let declared = [agent_type.as_str(), ANY_AGENT_TYPE]
+            .into_iter()
+            .filter_map(|key| self.types.get(key))
+            .flatten();
+        for (name, schema) in declared {
+            if schema.required && agent.config_value(name).is_none() {
+                problems.push((name.clone(), message!("falta la opción obligatoria '{}'", name)));
+            }
+        }
+
+        for argument in &agent.config {
+            if let Argument::Named(name, value, _) = argument {
+                if let Some(schema) = self.option(&agent_type, name) {
+                    let mut found = Vec::new();
+                    check_value(name, value, schema, &mut found);
+                    problems.extend(found.into_iter().map(|problem| (name.clone(), problem)));
+                }
+            }
+        }
+        problems
+    }
+}
+
+/// Comprueba el valor de una opción y, en los objetos, el de sus campos.
+fn check_value(name: &str, value: &Value, schema: &OptionSchema, problems: &mut Vec<Message>) {
+    // Las referencias sin resolver ya se informan como constantes no definidas
+    if matches!(value, Value::Variable(_)) {
+        return;
+    }
+
+    let number = match value {
+        Value::Number(n) => Some(*n),
+        _ => None,
+    };
+    let matches = match schema.option_type {
+        OptionType::String => matches!(value, Value::String(_) | Value::Path(_)),
+        OptionType::Number => number.is_some(),
+        OptionType::Integer => number.is_some_and(|n| n.fract() == 0.0),
+        OptionType::Boolean => matches!(value, Value::Boolean(_)),
+        OptionType::Object => matches!(value, Value::Object(_)),
+        OptionType::Array => matches!(value, Value::Array(_)),
+        OptionType::Duration => value.as_duration_secs().is_some(),
+    };
+    if !matches {
+        problems.push(message!("'{}' debe ser de tipo {}, no {}", name, schema.option_type, value));
+        return;
+    }
+
+    if let Some(n) = number {
+        let below = schema.min.is_some_and(|min| n < min);
+        let above = schema.max.is_some_and(|max| n > max);
+        if below || above {
+            let range = match (schema.min, schema.max) {
+                (Some(min), Some(max)) => message!("entre {} y {}", min, max),
+                (Some(min), None) => message!("al menos {}", min),
+                (None, Some(max)) => message!("como mucho {}", max),
+                (None, None) => unreachable!(),
+            };
+            problems.push(message!("'{}' debe estar {}, no {}", name, range, n));
+        }
+    }
+
+    if let Value::Object(fields) = value {
+        for (field, field_schema) in &schema.fields {
+            match fields.get(field) {
+                Some(field_value) => check_value(&format!("{}.{}", name, field), field_value, field_schema, problems),
+                None if field_schema.required => {
+                    problems.push(message!("falta el campo obligatorio '{}'", format!("{}.{}", name, field)));
+                }
+                None => {}
+            }
+        }
+    }
+}
@@ -1,6 +1,10 @@
 //! Módulo para el análisis semántico de programas Kumeo.
 
 mod analyzer;
+pub mod catalog;
+pub mod config_schema;
+pub mod resources;
+pub mod versioning;
 
 pub use analyzer::SemanticAnalyzer;
 
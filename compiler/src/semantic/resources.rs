@@ -0,0 +1,55 @@
+//! Comprobación de los recursos que los agentes cargan al arrancar.
+//!
+//! El análisis semántico solo ve las URIs de los recursos; con
+//! `kumeo check --check-resources` además se descargan y se valida su
+//! contenido tal como lo leerán los agentes. Por ahora se comprueban las
+//! tablas de rutas de los Router (`rules: resource(...)`): aunque el agente
+//! las recarga en caliente, la primera versión tiene que ser válida para que
+//! arranque.
+
+use std::path::Path;
+
+use crate::ast::{AgentType, Program, RouteTableConfig};
+use crate::diagnostics::{codes, Diagnostic};
+use crate::i18n::Message;
+use crate::message;
+use crate::vendor::{self, mirror::MirrorRule};
+
+/// Descarga y valida los recursos que los agentes de un programa cargan al
+/// arrancar. Las rutas locales se resuelven desde `base_dir` y las URIs
+/// pasan por las reglas de mirror.
+pub async fn check_resources(program: &Program, base_dir: &Path, mirrors: &[MirrorRule]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let agents = program.workflows.iter().flat_map(|workflow| &workflow.agents);
+    for agent in agents.filter(|agent| agent.agent_type == AgentType::Router) {
+        // Las tablas mal escritas ya las informa el análisis semántico
+        let Ok(Some(table)) = RouteTableConfig::from_agent(agent) else {
+            continue;
+        };
+        let agent_id = agent.id.clone().map_or_else(|| message!("<sin id>"), Message::Text);
+        let contents = match vendor::fetch(&table.source, base_dir, mirrors).await {
+            Ok(contents) => contents,
+            Err(e) => {
+                diagnostics.push(
+                    Diagnostic::error(
+                        codes::INVALID_RESOURCE,
+                        message!("No se pudo descargar la tabla de rutas del agente {}: {}", agent_id, format!("{:#}", e)),
+                    )
+                    .at(&agent.span)
+                    .with_help(message!("comprueba la URI o añade una regla de mirror con --mirror o KUMEO_RESOURCE_MIRRORS")),
+                );
+                continue;
+            }
+        };
+        if let Err(e) = table.check_table(&contents) {
+            diagnostics.push(
+                Diagnostic::error(
+                    codes::INVALID_RESOURCE,
+                    message!("La tabla de rutas {} del agente {} no es válida: {}", table.source, agent_id, e),
+                )
+                .at(&agent.span),
+            );
+        }
+    }
+    diagnostics
+}
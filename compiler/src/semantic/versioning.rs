@@ -0,0 +1,393 @@
+//! Versiones semánticas de los workflows.
+//!
+//! Un workflow puede declarar `version: "2.1.0"`. Al compararlo con una
+//! versión anterior del programa, cada cambio se clasifica como major, minor
+//! o patch:
+//!
+//! - major: lo que rompe a quien produce o consume sus mensajes: una fuente,
+//!   un destino o un topic de un agente que cambia, un agente o una opción
+//!   que se elimina, un agente que cambia de tipo, un campo del esquema de
+//!   salida que se elimina o cambia de tipo, el modo del workflow;
+//! - minor: lo que se añade sin romper nada: agentes, opciones, campos del
+//!   esquema de salida y subworkflows invocados;
+//! - patch: el resto, como los valores de las opciones o el despliegue.
+//!
+//! La versión declarada debe subir al menos en la clase del mayor de sus
+//! cambios. Los workflows sin versión en alguno de los dos programas no se
+//! comprueban, y los tests de un workflow no son parte de su versión.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::Serialize;
+
+use super::catalog::{breaking_changes, schema_fields};
+use crate::ast::{
+    Agent, Argument, Program, Workflow, INPUT_OPTION, INPUT_TOPIC_OPTION, OUTPUT_OPTION, OUTPUT_SCHEMA_OPTION,
+    OUTPUT_TOPIC_OPTION, SCHEMA_VERSION_OPTION,
+};
+use crate::i18n::Message;
+use crate::message;
+
+/// Opciones cuyos cambios se clasifican por los topics y el esquema de salida.
+const CONTRACT_OPTIONS: [&str; 6] = [
+    INPUT_OPTION,
+    OUTPUT_OPTION,
+    INPUT_TOPIC_OPTION,
+    OUTPUT_TOPIC_OPTION,
+    OUTPUT_SCHEMA_OPTION,
+    SCHEMA_VERSION_OPTION,
+];
+
+/// Versión semántica `MAJOR.MINOR.PATCH`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Version {
+    /// Cambios incompatibles
+    pub major: u64,
+    /// Cambios compatibles que añaden algo
+    pub minor: u64,
+    /// Correcciones
+    pub patch: u64,
+}
+
+impl Version {
+    /// Lee una versión `MAJOR.MINOR.PATCH`, sin ceros a la izquierda.
+    pub fn parse(version: &str) -> Option<Self> {
+        let number = |part: &str| {
+            let valid = !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()) && (part == "0" || !part.starts_with('0'));
+            valid.then(|| part.parse().ok()).flatten()
+        };
+        let mut parts = version.split('.');
+        let (Some(major), Some(minor), Some(patch), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+            return None;
+        };
+        Some(Self { major: number(major)?, minor: number(minor)?, patch: number(patch)? })
+    }
+
+    /// La siguiente versión con un cambio de la clase dada.
+    pub fn bump(self, class: ChangeClass) -> Self {
+        match class {
+            ChangeClass::Major => Self { major: self.major + 1, minor: 0, patch: 0 },
+            ChangeClass::Minor => Self { minor: self.minor + 1, patch: 0, ..self },
+            ChangeClass::Patch => Self { patch: self.patch + 1, ..self },
+        }
+    }
+
+    /// Clase del incremento de `self` a `next`; `None` si no cambia o retrocede.
+    pub fn bump_to(self, next: Self) -> Option<ChangeClass> {
+        match (next.major.cmp(&self.major), next.minor.cmp(&self.minor), next.patch.cmp(&self.patch)) {
+            (Ordering::Greater, _, _) => Some(ChangeClass::Major),
+            (Ordering::Equal, Ordering::Greater, _) => Some(ChangeClass::Minor),
+            (Ordering::Equal, Ordering::Equal, Ordering::Greater) => Some(ChangeClass::Patch),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Clase de un cambio, de menor a mayor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeClass {
+    /// No cambia lo que el workflow consume ni produce
+    Patch,
+    /// Añade algo sin romper a productores ni consumidores
+    Minor,
+    /// Rompe a productores o consumidores
+    Major,
+}
+
+impl fmt::Display for ChangeClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Patch => "patch",
+            Self::Minor => "minor",
+            Self::Major => "major",
+        })
+    }
+}
+
+/// Un cambio de un workflow.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Change {
+    /// Su clase
+    pub class: ChangeClass,
+    /// Qué cambia
+    pub description: Message,
+}
+
+/// Cambios de un workflow entre dos versiones de un programa.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WorkflowChanges {
+    /// Nombre del workflow
+    pub workflow: String,
+    /// Versión declarada en el programa anterior
+    pub previous_version: Option<String>,
+    /// Versión declarada ahora
+    pub version: Option<String>,
+    /// Sus cambios, en orden
+    pub changes: Vec<Change>,
+}
+
+impl WorkflowChanges {
+    /// Clase del mayor de los cambios; `None` sin cambios.
+    pub fn class(&self) -> Option<ChangeClass> {
+        self.changes.iter().map(|change| change.class).max()
+    }
+
+    /// Por qué la versión declarada no corresponde a los cambios, si no corresponde.
+    pub fn bump_error(&self) -> Option<Message> {
+        let previous = Version::parse(self.previous_version.as_deref()?)?;
+        let current = Version::parse(self.version.as_deref()?)?;
+        if current < previous {
+            return Some(message!(
+                "La versión del workflow {} retrocede de {} a {}",
+                self.workflow, previous, current
+            ));
+        }
+        let required = self.class()?;
+        if previous.bump_to(current) >= Some(required) {
+            return None;
+        }
+        let changes = self
+            .changes
+            .iter()
+            .filter(|change| change.class == required)
+            .map(|change| change.description.clone());
+        Some(message!(
+            "El workflow {} tiene cambios {} ({}), pero su versión pasa de {} a {}; debería ser al menos {}",
+            self.workflow,
+            required,
+            Message::join(changes, "; "),
+            previous,
+            current,
+            previous.bump(required)
+        ))
+    }
+}
+
+/// Cambios de los workflows de `current` que ya estaban en `previous`, en orden.
+pub fn diff_programs(previous: &Program, current: &Program) -> Vec<WorkflowChanges> {
+    current
+        .workflows
+        .iter()
+        .filter_map(|workflow| {
+            let before = previous.workflows.iter().find(|before| before.name == workflow.name)?;
+            Some(WorkflowChanges {
+                workflow: workflow.name.clone(),
+                previous_version: before.version.clone(),
+                version: workflow.version.clone(),
+                changes: diff_workflows(before, workflow),
+            })
+        })
+        .collect()
+}
+
+/// Cambios de un workflow respecto a su versión anterior, clasificados.
+pub fn diff_workflows(previous: &Workflow, current: &Workflow) -> Vec<Change> {
+    let mut changes = Changes::default();
+
+    // Fuente, destino y modo
+    let source_topic = |workflow: &Workflow| workflow.source.as_ref().map(|source| source.topic().to_string());
+    let target_topic = |workflow: &Workflow| workflow.target.as_ref().map(|target| target.topic().to_string());
+    if source_topic(previous) != source_topic(current) {
+        changes.major(message!("la fuente pasa de {} a {}", topic_name(source_topic(previous)), topic_name(source_topic(current))));
+    } else if differs(&previous.source, &current.source) {
+        changes.patch(message!("cambian las opciones de la fuente"));
+    }
+    if target_topic(previous) != target_topic(current) {
+        changes.major(message!("el destino pasa de {} a {}", topic_name(target_topic(previous)), topic_name(target_topic(current))));
+    } else if differs(&previous.target, &current.target) {
+        changes.patch(message!("cambian las opciones del destino"));
+    }
+    if previous.mode != current.mode {
+        let mode = |workflow: &Workflow| format!("{:?}", workflow.mode).to_lowercase();
+        changes.major(message!("el modo pasa de {} a {}", mode(previous), mode(current)));
+    }
+
+    // Agentes, por nombre
+    let agents = |workflow: &Workflow| -> BTreeMap<String, (usize, Agent, Option<String>, Option<String>)> {
+        workflow
+            .agents
+            .iter()
+            .zip(workflow.deployed_topics())
+            .enumerate()
+            .map(|(index, (agent, topics))| {
+                let name = agent.id.clone().unwrap_or_else(|| format!("agent{}", index + 1));
+                (name, (index, agent.clone(), topics.input, topics.output))
+            })
+            .collect()
+    };
+    let (before, after) = (agents(previous), agents(current));
+    for name in before.keys().filter(|name| !after.contains_key(*name)) {
+        changes.major(message!("se elimina el agente {}", name));
+    }
+    let mut added: Vec<(&usize, &String)> = after
+        .iter()
+        .filter(|(name, _)| !before.contains_key(*name))
+        .map(|(name, (index, ..))| (index, name))
+        .collect();
+    added.sort();
+    for (_, name) in added {
+        changes.minor(message!("se añade el agente {}", name));
+    }
+    let mut kept: Vec<&String> = after.keys().filter(|name| before.contains_key(*name)).collect();
+    kept.sort_by_key(|name| after[*name].0);
+    for name in kept {
+        let (_, previous_agent, previous_input, previous_output) = &before[name];
+        let (_, agent, input, output) = &after[name];
+        diff_agents(&mut changes, name, previous_agent, agent, (previous_input, previous_output), (input, output));
+    }
+
+    // Subworkflows invocados, por nombre
+    let calls = |workflow: &Workflow| -> BTreeMap<String, serde_json::Value> {
+        workflow.calls.iter().map(|call| (call.name.clone(), json(&(&call.input, &call.output)))).collect()
+    };
+    let (before, after) = (calls(previous), calls(current));
+    for (name, bindings) in &before {
+        match after.get(name) {
+            None => changes.major(message!("deja de invocarse el subworkflow {}", name)),
+            Some(current) if current != bindings => changes.major(message!("cambian los topics del subworkflow {}", name)),
+            Some(_) => {}
+        }
+    }
+    for name in after.keys().filter(|name| !before.contains_key(*name)) {
+        changes.minor(message!("se invoca el subworkflow {}", name));
+    }
+
+    // Lo demás no cambia lo que el workflow consume ni produce
+    if differs(&previous.context, &current.context) {
+        changes.patch(message!("cambia el contexto"));
+    }
+    if differs(&previous.preprocessors, &current.preprocessors) {
+        changes.patch(message!("cambian los preprocesadores"));
+    }
+    if differs(&previous.monitor, &current.monitor) {
+        changes.patch(message!("cambia la monitorización"));
+    }
+    if differs(&previous.deployment, &current.deployment) {
+        changes.patch(message!("cambia el despliegue"));
+    }
+    changes.0
+}
+
+/// Cambios de un agente que sigue en el workflow.
+fn diff_agents(
+    changes: &mut Changes,
+    name: &str,
+    previous: &Agent,
+    current: &Agent,
+    (previous_input, previous_output): (&Option<String>, &Option<String>),
+    (input, output): (&Option<String>, &Option<String>),
+) {
+    if previous.agent_type != current.agent_type {
+        changes.major(message!("el agente {} pasa de {} a {}", name, previous.agent_type, current.agent_type));
+        return;
+    }
+    if previous_input != input {
+        changes.major(message!(
+            "el agente {} pasa de consumir {} a {}",
+            name,
+            topic_name(previous_input.clone()),
+            topic_name(input.clone())
+        ));
+    }
+    if previous_output != output {
+        changes.major(message!(
+            "el agente {} pasa de publicar en {} a {}",
+            name,
+            topic_name(previous_output.clone()),
+            topic_name(output.clone())
+        ));
+    }
+
+    // Esquema de salida: quitar o cambiar campos es incompatible, añadirlos no
+    let fields = |agent: &Agent| agent.output_schema().ok().flatten().map(|(schema, _)| schema_fields(&schema));
+    match (fields(previous), fields(current)) {
+        (Some(before), Some(after)) => {
+            for change in breaking_changes(&before, &after) {
+                changes.major(message!("en el esquema de salida de {} {}", name, change));
+            }
+            for field in after.keys().filter(|field| !before.contains_key(*field)) {
+                changes.minor(message!("se añade el campo '{}' al esquema de salida de {}", field, name));
+            }
+        }
+        (None, Some(_)) => changes.minor(message!("el agente {} declara su esquema de salida", name)),
+        (Some(_), None) => changes.major(message!("el agente {} deja de declarar su esquema de salida", name)),
+        (None, None) => {}
+    }
+
+    // Opciones: las nuevas son compatibles, las eliminadas no
+    let options = |agent: &Agent| -> BTreeMap<String, serde_json::Value> {
+        agent
+            .config
+            .iter()
+            .filter_map(|argument| match argument {
+                Argument::Named(key, value, _) if !CONTRACT_OPTIONS.contains(&key.as_str()) => Some((key.clone(), json(value))),
+                _ => None,
+            })
+            .collect()
+    };
+    let positional = |agent: &Agent| -> Vec<serde_json::Value> {
+        agent
+            .config
+            .iter()
+            .filter_map(|argument| match argument {
+                Argument::Positional(value, _) => Some(json(value)),
+                _ => None,
+            })
+            .collect()
+    };
+    let (before, after) = (options(previous), options(current));
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    for key in keys {
+        match (before.get(key), after.get(key)) {
+            (Some(_), None) => changes.major(message!("se elimina la opción {} del agente {}", key, name)),
+            (None, Some(_)) => changes.minor(message!("se añade la opción {} al agente {}", key, name)),
+            (Some(old), Some(new)) if old != new => changes.patch(message!("cambia la opción {} del agente {}", key, name)),
+            _ => {}
+        }
+    }
+    if positional(previous) != positional(current) {
+        changes.patch(message!("cambian los argumentos del agente {}", name));
+    }
+}
+
+/// Cambios encontrados, en orden.
+#[derive(Default)]
+struct Changes(Vec<Change>);
+
+impl Changes {
+    fn major(&mut self, description: Message) {
+        self.0.push(Change { class: ChangeClass::Major, description });
+    }
+
+    fn minor(&mut self, description: Message) {
+        self.0.push(Change { class: ChangeClass::Minor, description });
+    }
+
+    fn patch(&mut self, description: Message) {
+        self.0.push(Change { class: ChangeClass::Patch, description });
+    }
+}
+
+/// Un topic entre comillas, o `ninguno`.
+fn topic_name(topic: Option<String>) -> Message {
+    topic.map_or_else(|| message!("ninguno"), |topic| Message::Text(format!("'{}'", topic)))
+}
+
+/// Valor comparable de una parte del AST; las posiciones no se serializan.
+fn json<T: Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+/// Si dos partes del AST difieren.
+fn differs<T: Serialize>(previous: &T, current: &T) -> bool {
+    json(previous) != json(current)
+}
@@ -0,0 +1,324 @@
+//! Simulation of workflow tests
+//!
+//! A `test` block of a workflow delivers a message to one of its agents and
+//! states what should become of it. The simulator doesn't run the agents: it
+//! follows the decisions the generated code makes around them. The message
+//! is checked against the agent's input schema, whose violations go to the
+//! agent's fallback, then the agent's `when` condition is evaluated and its
+//! `assert` invariants are checked. A message passing all of them is
+//! processed and published on the agent's output topic. Conditions are
+//! evaluated with the semantics of `kumeo_runtime::condition` and schemas
+//! with those of `kumeo_runtime::schema`.
+//!
+//! A quality monitor counts the schema violations of the messages it samples
+//! instead of rejecting them, and publishes a report per window rather than
+//! per message, so nothing is published for the message it processes. An
+//! aggregator doesn't publish for it either, unless its window closes on
+//! every message, nor does a rule engine when one of its rules drops it.
+//!
+//! Workflows are simulated as generated: with their subworkflows expanded,
+//! their topics wired and their input schemas attached.
+
+use anyhow::{anyhow, Result};
+use serde_json::Value as Json;
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::ast::{
+    AgentType, AggregationWindow, AggregatorConfig, CompareOp, Expr, FallbackConfig, RuleAction, RuleEngineConfig, Schema, Value, Workflow, WorkflowTest,
+    EXPECT_OUTCOME, EXPECT_TOPIC, FALLBACK_OPTION, WHEN_OPTION,
+};
+use crate::codegen::condition::AssertSettings;
+use crate::i18n::Message;
+use crate::message;
+
+/// What an agent does with a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The agent processes the message
+    Processed,
+    /// The agent's `when` condition discards the message
+    Skipped,
+    /// The message breaks the agent's input schema and goes to its fallback
+    Rejected,
+    /// The message breaks an `assert` invariant and is audited
+    Violated,
+}
+
+impl Outcome {
+    /// Every outcome
+    pub const ALL: [Outcome; 4] = [Outcome::Processed, Outcome::Skipped, Outcome::Rejected, Outcome::Violated];
+
+    /// The outcome as written in `expect: { outcome: ... }`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Processed => "processed",
+            Outcome::Skipped => "skipped",
+            Outcome::Rejected => "rejected",
+            Outcome::Violated => "violated",
+        }
+    }
+
+    /// Read an outcome as written in a test
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|outcome| outcome.as_str() == name)
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What became of a message delivered to an agent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    /// What the agent did with the message
+    pub outcome: Outcome,
+    /// The topic the result is published on, if any
+    pub topic: Option<String>,
+    /// Why the message wasn't processed
+    pub reason: Option<Message>,
+}
+
+/// Result of a workflow test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestReport {
+    /// The workflow the test belongs to
+    pub workflow: String,
+    /// The name of the test
+    pub name: String,
+    /// The expectations that didn't hold; empty when the test passed
+    pub failures: Vec<Message>,
+}
+
+impl TestReport {
+    /// Whether every expectation held
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Run every test of a workflow
+pub fn run(workflow: &Workflow) -> Result<Vec<TestReport>> {
+    workflow.tests.iter().map(|test| run_test(workflow, test)).collect()
+}
+
+/// Run a test of a workflow against the simulated delivery of its message
+pub fn run_test(workflow: &Workflow, test: &WorkflowTest) -> Result<TestReport> {
+    let delivery = deliver(workflow, &test.agent, &test.given.to_json())
+        .map_err(|e| anyhow!(message!("Test \"{}\" of workflow {}: {}", test.name, workflow.name, e)))?;
+
+    let mut failures = Vec::new();
+    let mut keys: Vec<&String> = test.expect.keys().collect();
+    keys.sort();
+    for key in keys {
+        let expected = &test.expect[key];
+        match (key.as_str(), expected) {
+            (EXPECT_TOPIC, Value::String(topic)) if delivery.topic.as_deref() != Some(topic.as_str()) => {
+                failures.push(match &delivery.topic {
+                    Some(actual) => message!("expected the message on '{}', it was published on '{}'", topic, actual),
+                    None => message!("expected the message on '{}', nothing was published", topic),
+                });
+            }
+            (EXPECT_TOPIC, Value::Null) => {
+                if let Some(actual) = &delivery.topic {
+                    failures.push(message!("expected nothing to be published, the message went to '{}'", actual));
+                }
+            }
+            (EXPECT_TOPIC, Value::String(_)) => {}
+            (EXPECT_OUTCOME, Value::String(outcome)) => match Outcome::parse(outcome) {
+                Some(outcome) if outcome == delivery.outcome => {}
+                Some(outcome) => failures.push(message!("expected the message to be {}, it was {}", outcome, delivery.outcome)),
+                None => return Err(anyhow!(message!("Test \"{}\": unknown outcome '{}'", test.name, outcome))),
+            },
+            _ => return Err(anyhow!(message!("Test \"{}\": invalid expectation {}: {}", test.name, key, expected))),
+        }
+    }
+    if let (false, Some(reason)) = (failures.is_empty(), &delivery.reason) {
+        failures.push(message!("the message was {}: {}", delivery.outcome, reason));
+    }
+
+    Ok(TestReport {
+        workflow: workflow.name.clone(),
+        name: test.name.clone(),
+        failures,
+    })
+}
+
+/// Simulate the delivery of a message to an agent of a workflow
+pub fn deliver(workflow: &Workflow, agent_id: &str, message: &Json) -> Result<Delivery> {
+    let (agent, topics) = workflow
+        .agents
+        .iter()
+        .zip(workflow.deployed_topics())
+        .find(|(agent, _)| agent.id.as_deref() == Some(agent_id))
+        .ok_or_else(|| anyhow!(message!("Unknown agent {}", agent_id)))?;
+
+    let monitor = agent.agent_type == AgentType::QualityMonitor;
+    let windowed = match agent.agent_type {
+        AgentType::QualityMonitor => true,
+        AgentType::Aggregator => AggregatorConfig::from_agent(agent).map_err(|e| anyhow!(message!("Invalid aggregator: {}", e)))?.window
+            != AggregationWindow::Count(1),
+        _ => false,
+    };
+    let schema = agent.input_schema().map_err(|e| anyhow!(message!("Invalid input schema: {}", e)))?;
+    if let (false, Some(Err(violations))) = (monitor, schema.map(|schema| check_schema(message, &schema))) {
+        let topic = match agent.config_value(FALLBACK_OPTION).map(FallbackConfig::from_value) {
+            Some(Ok(FallbackConfig::DeadLetter { subject })) => Some(subject),
+            Some(Ok(FallbackConfig::UseDefault { .. })) => topics.output,
+            Some(Ok(FallbackConfig::RetryLater { .. })) => topics.input,
+            Some(Err(e)) => return Err(anyhow!(message!("Invalid fallback: {}", e))),
+            _ => None,
+        };
+        return Ok(Delivery {
+            outcome: Outcome::Rejected,
+            topic,
+            reason: Some(violations),
+        });
+    }
+
+    if let Some(Value::Condition(expr)) = agent.config_value(WHEN_OPTION) {
+        if !truthy(&evaluate(expr, message)) {
+            return Ok(Delivery {
+                outcome: Outcome::Skipped,
+                topic: None,
+                reason: Some(message!("when condition `{}` is false", expr)),
+            });
+        }
+    }
+
+    if let Some(assertions) = AssertSettings::for_agent(workflow, agent)? {
+        let broken = agent.assertions().into_iter().find(|expr| !truthy(&evaluate(expr, message)));
+        if let Some(expr) = broken {
+            return Ok(Delivery {
+                outcome: Outcome::Violated,
+                topic: Some(assertions.subject),
+                reason: Some(message!("invariant `{}` doesn't hold", expr)),
+            });
+        }
+    }
+
+    let dropped = match agent.agent_type {
+        AgentType::RuleEngine => RuleEngineConfig::from_agent(agent)
+            .map_err(|e| anyhow!(message!("Invalid rules: {}", e)))?
+            .rules
+            .iter()
+            .any(|rule| rule.then == RuleAction::Drop && truthy(&evaluate(&rule.when, message))),
+        _ => false,
+    };
+
+    Ok(Delivery {
+        outcome: Outcome::Processed,
+        topic: if windowed || dropped { None } else { topics.output },
+        reason: None,
+    })
+}
+
+/// Check that a message has every field of a schema with its type
+fn check_schema(message: &Json, schema: &Schema) -> std::result::Result<(), Message> {
+    let Json::Object(fields) = message else {
+        return Err(message!("the message is not a JSON object"));
+    };
+
+    let mut declared: Vec<_> = schema.fields.iter().collect();
+    declared.sort();
+    let violations: Vec<Message> = declared
+        .into_iter()
+        .filter_map(|(name, declared)| {
+            let (field_type, optional) = match declared.strip_suffix('?') {
+                Some(field_type) => (field_type, true),
+                None => (declared.as_str(), false),
+            };
+            match fields.get(name) {
+                None | Some(Json::Null) if optional => None,
+                None | Some(Json::Null) => Some(message!("field '{}' is missing", name)),
+                Some(value) if has_type(value, field_type) => None,
+                Some(value) => Some(message!("field '{}' should be of type {}, found {}", name, field_type, value)),
+            }
+        })
+        .collect();
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(Message::join(violations, "; "))
+    }
+}
+
+/// Whether a JSON value has a schema type
+fn has_type(value: &Json, field_type: &str) -> bool {
+    match field_type {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.as_f64().is_some_and(|number| number.fract() == 0.0),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        _ => false,
+    }
+}
+
+/// Value of a condition or an operand for a message
+fn evaluate(expr: &Expr, data: &Json) -> Json {
+    match expr {
+        Expr::Field(path) | Expr::OptionalField(path, _) => field(data, path),
+        Expr::Default(value, default) => match evaluate(value, data) {
+            Json::Null => evaluate(default, data),
+            value => value,
+        },
+        Expr::Literal(value) => value.to_json(),
+        Expr::Not(inner) => Json::Bool(!truthy(&evaluate(inner, data))),
+        Expr::And(left, right) => Json::Bool(truthy(&evaluate(left, data)) && truthy(&evaluate(right, data))),
+        Expr::Or(left, right) => Json::Bool(truthy(&evaluate(left, data)) || truthy(&evaluate(right, data))),
+        Expr::Compare(left, op, right) => {
+            let (left, right) = (evaluate(left, data), evaluate(right, data));
+            let ordering = compare(&left, &right);
+            Json::Bool(match op {
+                CompareOp::Eq => ordering.map_or(left == right, Ordering::is_eq),
+                CompareOp::Ne => !ordering.map_or(left == right, Ordering::is_eq),
+                CompareOp::Lt => ordering.is_some_and(Ordering::is_lt),
+                CompareOp::Le => ordering.is_some_and(Ordering::is_le),
+                CompareOp::Gt => ordering.is_some_and(Ordering::is_gt),
+                CompareOp::Ge => ordering.is_some_and(Ordering::is_ge),
+            })
+        }
+    }
+}
+
+/// The value at the path of a field, null when any segment is missing; `data` is the message
+fn field(data: &Json, path: &[String]) -> Json {
+    let path = match path.split_first() {
+        Some((root, rest)) if root == "data" => rest,
+        _ => path,
+    };
+    path.iter()
+        .try_fold(data, |value, key| match value {
+            Json::Array(items) => key.parse::<usize>().ok().and_then(|index| items.get(index)),
+            _ => value.get(key),
+        })
+        .cloned()
+        .unwrap_or(Json::Null)
+}
+
+/// Whether a value counts as true on its own
+fn truthy(value: &Json) -> bool {
+    match value {
+        Json::Null => false,
+        Json::Bool(value) => *value,
+        Json::Number(number) => number.as_f64().is_some_and(|number| number != 0.0),
+        Json::String(text) => !text.is_empty(),
+        Json::Array(items) => !items.is_empty(),
+        Json::Object(map) => !map.is_empty(),
+    }
+}
+
+/// Order two numbers or two strings; any other pair is unordered
+fn compare(left: &Json, right: &Json) -> Option<Ordering> {
+    match (left, right) {
+        (Json::Number(left), Json::Number(right)) => left.as_f64()?.partial_cmp(&right.as_f64()?),
+        (Json::String(left), Json::String(right)) => Some(left.cmp(right)),
+        _ => None,
+    }
+}
@@ -0,0 +1,209 @@
+//! Program statistics
+//!
+//! Summarizes the size of a program for capacity planning and for reviewing
+//! large DSL codebases: its workflows, agents by type and by language, the
+//! topics they are wired through, the declared schemas, the resources and
+//! services it references by URI, and an estimate of what its agents request
+//! from a Kubernetes cluster.
+//!
+//! The estimate counts one pod per replica of each agent, and the requests of
+//! its container: those of the workflow's `deployment.resources` when given,
+//! otherwise the defaults its manifests request for the agent's language.
+//! Brokers, inference servers and other shared services are not counted.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::ast::{Agent, Program, Workflow};
+use crate::codegen::agent::agent_language;
+use crate::message;
+use crate::vendor::resource_uris;
+
+/// CPU in millicores and memory in MiB requested by a Rust agent container by default
+pub const RUST_REQUESTS: (u64, u64) = (100, 128);
+
+/// CPU in millicores and memory in MiB requested by a Python agent container by default
+pub const PYTHON_REQUESTS: (u64, u64) = (200, 256);
+
+/// Statistics of a program
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProgramStats {
+    /// Number of workflows
+    pub workflows: usize,
+    /// Number of subworkflows declared
+    pub subworkflows: usize,
+    /// Number of agents of all the workflows
+    pub agents: usize,
+    /// Number of agents of each type, named as in the DSL
+    pub agents_by_type: BTreeMap<String, usize>,
+    /// Number of agents of each language, `rust` or `python`
+    pub agents_by_language: BTreeMap<String, usize>,
+    /// Distinct topics the workflows consume and produce, sorted
+    pub topics: Vec<String>,
+    /// Number of topic schemas declared
+    pub schemas: usize,
+    /// URIs of the resources and services referenced, sorted
+    pub resources: Vec<String>,
+    /// Estimated footprint of all the workflows
+    pub footprint: Footprint,
+    /// Statistics of each workflow, in program order
+    pub by_workflow: Vec<WorkflowStats>,
+}
+
+/// Statistics of a workflow
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WorkflowStats {
+    /// Name of the workflow
+    pub name: String,
+    /// Number of agents
+    pub agents: usize,
+    /// Number of distinct topics it consumes and produces
+    pub topics: usize,
+    /// Estimated footprint of its agents
+    pub footprint: Footprint,
+}
+
+/// Estimated Kubernetes footprint of a set of agents
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Footprint {
+    /// Pods, one per replica of each agent
+    pub pods: u64,
+    /// CPU requested, in millicores
+    pub cpu_millis: u64,
+    /// Memory requested, in MiB
+    pub memory_mib: u64,
+}
+
+impl std::ops::AddAssign for Footprint {
+    fn add_assign(&mut self, other: Self) {
+        self.pods += other.pods;
+        self.cpu_millis += other.cpu_millis;
+        self.memory_mib += other.memory_mib;
+    }
+}
+
+impl ProgramStats {
+    /// Compute the statistics of a program, with its subworkflows already expanded
+    pub fn of(program: &Program) -> Result<Self> {
+        let mut agents_by_type = BTreeMap::new();
+        let mut agents_by_language = BTreeMap::new();
+        let mut topics = BTreeSet::new();
+        let mut footprint = Footprint::default();
+        let mut by_workflow = Vec::with_capacity(program.workflows.len());
+
+        for workflow in &program.workflows {
+            for agent in &workflow.agents {
+                *agents_by_type.entry(format!("{:?}", agent.agent_type)).or_insert(0) += 1;
+                *agents_by_language.entry(agent_language(agent).to_string()).or_insert(0) += 1;
+            }
+            let workflow_topics = workflow_topics(workflow);
+            let workflow_footprint = workflow_footprint(workflow)?;
+            footprint += workflow_footprint;
+            by_workflow.push(WorkflowStats {
+                name: workflow.name.clone(),
+                agents: workflow.agents.len(),
+                topics: workflow_topics.len(),
+                footprint: workflow_footprint,
+            });
+            topics.extend(workflow_topics);
+        }
+
+        Ok(Self {
+            workflows: program.workflows.len(),
+            subworkflows: program.subworkflows.len(),
+            agents: program.workflows.iter().map(|workflow| workflow.agents.len()).sum(),
+            agents_by_type,
+            agents_by_language,
+            topics: topics.into_iter().collect(),
+            schemas: program.schemas.len(),
+            resources: resource_uris(program).into_iter().collect(),
+            footprint,
+            by_workflow,
+        })
+    }
+}
+
+/// Topics a workflow consumes and produces, its source and target included
+fn workflow_topics(workflow: &Workflow) -> BTreeSet<String> {
+    let mut topics: BTreeSet<String> = workflow
+        .deployed_topics()
+        .into_iter()
+        .flat_map(|topics| [topics.input, topics.output])
+        .flatten()
+        .collect();
+    topics.extend(workflow.source.as_ref().map(|source| source.topic().to_string()));
+    topics.extend(workflow.target.as_ref().map(|target| target.topic().to_string()));
+    topics
+}
+
+/// Estimated footprint of the agents of a workflow
+fn workflow_footprint(workflow: &Workflow) -> Result<Footprint> {
+    let replicas = u64::from(workflow.deployment.as_ref().and_then(|deployment| deployment.replicas).unwrap_or(1));
+    let mut footprint = Footprint::default();
+    for agent in &workflow.agents {
+        let (cpu_millis, memory_mib) = agent_requests(workflow, agent)?;
+        footprint += Footprint {
+            pods: replicas,
+            cpu_millis: replicas * cpu_millis,
+            memory_mib: replicas * memory_mib,
+        };
+    }
+    Ok(footprint)
+}
+
+/// CPU in millicores and memory in MiB requested by each replica of an agent
+///
+/// Those of the workflow's `deployment.resources` when given, otherwise the
+/// defaults for the agent's language.
+pub fn agent_requests(workflow: &Workflow, agent: &Agent) -> Result<(u64, u64)> {
+    let resources = workflow.deployment.as_ref().and_then(|deployment| deployment.resources.as_ref());
+    let (default_cpu, default_memory) = match agent_language(agent) {
+        "python" => PYTHON_REQUESTS,
+        _ => RUST_REQUESTS,
+    };
+    let cpu = resources
+        .and_then(|resources| resources.cpu.as_deref())
+        .map(|cpu| parse_cpu_millis(cpu).ok_or_else(|| anyhow!(message!("Invalid CPU request '{}' of workflow {}", cpu, workflow.name))))
+        .transpose()?;
+    let memory = resources
+        .and_then(|resources| resources.memory.as_deref())
+        .map(|memory| {
+            parse_memory_mib(memory).ok_or_else(|| anyhow!(message!("Invalid memory request '{}' of workflow {}", memory, workflow.name)))
+        })
+        .transpose()?;
+    Ok((cpu.unwrap_or(default_cpu), memory.unwrap_or(default_memory)))
+}
+
+/// Read a Kubernetes CPU quantity, such as `500m` or `1.5`, in millicores
+pub fn parse_cpu_millis(quantity: &str) -> Option<u64> {
+    let quantity = quantity.trim();
+    let (amount, scale) = match quantity.strip_suffix('m') {
+        Some(millis) => (millis, 1.0),
+        None => (quantity, 1000.0),
+    };
+    let amount: f64 = amount.parse().ok()?;
+    (amount.is_finite() && amount >= 0.0).then(|| (amount * scale).ceil() as u64)
+}
+
+/// Read a Kubernetes memory quantity, such as `512Mi`, `1Gi` or `256M`, in MiB, rounding up
+pub fn parse_memory_mib(quantity: &str) -> Option<u64> {
+    const MIB: f64 = 1024.0 * 1024.0;
+    let quantity = quantity.trim();
+    let split = quantity.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(quantity.len());
+    let (amount, unit) = quantity.split_at(split);
+    let amount: f64 = amount.parse().ok()?;
+    let bytes = match unit {
+        "" => 1.0,
+        "Ki" => 1024.0,
+        "Mi" => MIB,
+        "Gi" => MIB * 1024.0,
+        "Ti" => MIB * 1024.0 * 1024.0,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        _ => return None,
+    };
+    Some((amount * bytes / MIB).ceil() as u64)
+}
@@ -0,0 +1,241 @@
+//! Watch mode of `kumeo generate`
+//!
+//! The dependency graph maps every watched file to the outputs generated
+//! from it. A `.kumeo` file belongs to the programs that are or import it;
+//! each of them is re-parsed and regenerated, since every agent carries the
+//! hash of its whole workflow. A template under `agents/` only affects the
+//! agents of its type, or of its language for the shared ones, and a
+//! template of a custom agent the agents generated from it. Their
+//! directories are regenerated alone; any other template affects every
+//! program. Changes arriving within the debounce window are handled
+//! together.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::ast::{Agent, AgentType, Program};
+use crate::codegen::agent::agent_language;
+use crate::codegen::custom::CustomSettings;
+use crate::message;
+
+/// Time without changes before a batch of changes is handled
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// What to regenerate of a program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Regeneration {
+    /// Re-parse the program and regenerate every output
+    Full,
+    /// Regenerate the directories of these agents
+    Agents(BTreeSet<String>),
+}
+
+/// What a program's outputs are generated from
+#[derive(Debug, Clone, Default)]
+struct ProgramSources {
+    /// Canonical paths of the entry file and everything it imports
+    files: HashSet<PathBuf>,
+    /// Each agent of the generated workflows
+    agents: Vec<WatchedAgent>,
+}
+
+/// What an agent is generated from, as far as templates go
+#[derive(Debug, Clone)]
+struct WatchedAgent {
+    /// ID of the agent
+    id: String,
+    /// Name of its templates: its type, or the templates of a custom agent
+    templates: String,
+    /// Language it is generated in
+    language: &'static str,
+}
+
+impl WatchedAgent {
+    fn of(id: String, agent: &Agent) -> Self {
+        let templates = match CustomSettings::for_agent(agent) {
+            Ok(Some(custom)) => custom.kind,
+            _ => agent.agent_type.to_string(),
+        };
+        Self { id, templates, language: agent_language(agent) }
+    }
+}
+
+/// Which outputs depend on which sources and templates
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    /// Sources of each program, by entry file
+    programs: BTreeMap<PathBuf, ProgramSources>,
+    /// Directories of the templates, canonical
+    templates: Vec<PathBuf>,
+}
+
+impl DependencyGraph {
+    /// Graph of the programs generated from template directories
+    pub fn new(templates: &[PathBuf]) -> Self {
+        Self {
+            programs: BTreeMap::new(),
+            templates: templates.iter().map(|dir| canonical(dir)).collect(),
+        }
+    }
+
+    /// Record the files a program was parsed from and the agents generated for it
+    pub fn record_program(&mut self, entry: &Path, files: HashSet<PathBuf>, program: &Program) {
+        let agents = program
+            .workflows
+            .iter()
+            .flat_map(|workflow| &workflow.agents)
+            .filter_map(|agent| agent.id.clone().map(|id| WatchedAgent::of(id, agent)))
+            .collect();
+        self.programs.insert(entry.to_path_buf(), ProgramSources { files, agents });
+    }
+
+    /// Record a program that failed to parse: it is regenerated when its entry changes
+    pub fn record_failed(&mut self, entry: &Path) {
+        let sources = self.programs.entry(entry.to_path_buf()).or_default();
+        sources.files.insert(canonical(entry));
+    }
+
+    /// Directories to watch: those of the sources, and the template directories recursively
+    pub fn watched_dirs(&self) -> BTreeMap<PathBuf, RecursiveMode> {
+        let mut dirs = BTreeMap::new();
+        for file in self.programs.values().flat_map(|sources| &sources.files) {
+            if let Some(dir) = file.parent() {
+                dirs.entry(dir.to_path_buf()).or_insert(RecursiveMode::NonRecursive);
+            }
+        }
+        for dir in &self.templates {
+            dirs.insert(dir.clone(), RecursiveMode::Recursive);
+        }
+        dirs
+    }
+
+    /// What to regenerate of each program after some files changed
+    ///
+    /// Programs left out of the result are unaffected.
+    pub fn affected(&self, changed: &[PathBuf]) -> BTreeMap<PathBuf, Regeneration> {
+        let mut affected: BTreeMap<PathBuf, Regeneration> = BTreeMap::new();
+        for path in changed {
+            let path = canonical(path);
+            for (entry, sources) in &self.programs {
+                let regeneration = if sources.files.contains(&path) {
+                    Some(Regeneration::Full)
+                } else {
+                    self.template_name(&path).and_then(|name| template_regeneration(&name, &sources.agents))
+                };
+                match (affected.get_mut(entry), regeneration) {
+                    (_, None) | (Some(Regeneration::Full), _) => {}
+                    (Some(Regeneration::Agents(agents)), Some(Regeneration::Agents(more))) => agents.extend(more),
+                    (_, Some(regeneration)) => {
+                        affected.insert(entry.clone(), regeneration);
+                    }
+                }
+            }
+        }
+        affected
+    }
+
+    /// Path of a template relative to its template directory, with `/` separators
+    fn template_name(&self, path: &Path) -> Option<String> {
+        let relative = self.templates.iter().find_map(|dir| path.strip_prefix(dir).ok())?;
+        let name = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        name.ends_with(".tera").then_some(name)
+    }
+}
+
+/// What a change of a template regenerates of a program with these agents
+///
+/// Templates of an agent type (`agents/rust/LLM/...`) regenerate the agents
+/// of that type, those of a custom agent (`agents/fraud-scorer/...`) the
+/// agents generated from them and those of a language
+/// (`agents/python/Dockerfile.tera`) the agents generated in it; the others
+/// regenerate the whole program.
+fn template_regeneration(name: &str, agents: &[WatchedAgent]) -> Option<Regeneration> {
+    let mut segments = name.split('/');
+    if segments.next() != Some("agents") {
+        return Some(Regeneration::Full);
+    }
+    let segments: Vec<&str> = segments.collect();
+    let matches_type = |templates: &str| segments.iter().any(|segment| segment.eq_ignore_ascii_case(templates));
+
+    let typed = AgentType::ALL.iter().any(|agent_type| matches_type(&agent_type.to_string()))
+        || agents.iter().any(|agent| matches_type(&agent.templates));
+    let selected: BTreeSet<String> = agents
+        .iter()
+        .filter(|agent| if typed { matches_type(&agent.templates) } else { segments.first() == Some(&agent.language) })
+        .map(|agent| agent.id.clone())
+        .collect();
+    (!selected.is_empty()).then_some(Regeneration::Agents(selected))
+}
+
+/// Canonical form of a path, or the path itself if it doesn't exist
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| {
+        // Deleted files can't be canonicalized, but their directory still can
+        match (path.parent(), path.file_name()) {
+            (Some(dir), Some(name)) if !dir.as_os_str().is_empty() => {
+                dir.canonicalize().map(|dir| dir.join(name)).unwrap_or_else(|_| path.to_path_buf())
+            }
+            _ => path.components().filter(|c| *c != Component::CurDir).collect(),
+        }
+    })
+}
+
+/// Watcher of the directories of a dependency graph
+pub struct SourceWatcher {
+    watcher: RecommendedWatcher,
+    watched: BTreeSet<PathBuf>,
+    events: mpsc::UnboundedReceiver<PathBuf>,
+}
+
+impl SourceWatcher {
+    /// Start a watcher without watched directories
+    pub fn new() -> Result<Self> {
+        let (sender, events) = mpsc::unbounded_channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if let Ok(event) = event {
+                if !event.kind.is_access() {
+                    for path in event.paths {
+                        let _ = sender.send(path);
+                    }
+                }
+            }
+        })
+        .context(message!("Failed to start the file watcher"))?;
+        Ok(Self { watcher, watched: BTreeSet::new(), events })
+    }
+
+    /// Watch the directories of a graph not watched yet
+    pub fn watch(&mut self, graph: &DependencyGraph) -> Result<()> {
+        for (dir, mode) in graph.watched_dirs() {
+            if self.watched.contains(&dir) || !dir.is_dir() {
+                continue;
+            }
+            self.watcher
+                .watch(&dir, mode)
+                .with_context(|| message!("Failed to watch {}", dir.display()))?;
+            self.watched.insert(dir);
+        }
+        Ok(())
+    }
+
+    /// The next batch of changed files: those changed until `debounce` passes without changes
+    ///
+    /// Returns `None` once the watcher stops.
+    pub async fn changes(&mut self, debounce: Duration) -> Option<Vec<PathBuf>> {
+        let mut changed = BTreeSet::new();
+        changed.insert(self.events.recv().await?);
+        while let Ok(Some(path)) = tokio::time::timeout(debounce, self.events.recv()).await {
+            changed.insert(path);
+        }
+        Some(changed.into_iter().collect())
+    }
+}
@@ -1,11 +1,39 @@
+use super::generate;
 use anyhow::Result;
 use kumeo_compiler::{
-    ast::{Agent, AgentType},
-    codegen::agent::generate_agent,
+    ast::{Agent, AgentType, Span, Workflow, WorkflowMode},
+    codegen::{
+        agent::{agent_language, generate_agent},
+        sink::{FsSink, PlanSink},
+        template_manager::TemplateManager,
+    },
+    parser::parse,
 };
-use std::path::Path;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use tempfile::tempdir;
-use tera::Tera;
+
+fn test_workflow() -> Workflow {
+    Workflow {
+        name: "test-workflow".to_string(),
+        source: None,
+        target: None,
+        context: None,
+        preprocessors: None,
+        agents: vec![],
+        monitor: None,
+        deployment: None,
+        version: None,
+        doc: None,
+        mode: WorkflowMode::Stream,
+        calls: vec![],
+        tests: vec![],
+        span: Span::default(),
+    }
+}
 
 #[test]
 fn test_generate_agent() -> Result<()> {
@@ -18,37 +46,16 @@ fn test_generate_agent() -> Result<()> {
         id: Some("test-agent".to_string()),
         agent_type: AgentType::LLM,
         config: vec![],
+        doc: None,
+        span: Span::default(),
     };
     
-    // Create templates directory for LLM agent
-    let templates_dir = std::env::current_dir()?.join("compiler/templates/agents/llm");
-    std::fs::create_dir_all(&templates_dir)?;
-    
-    // Create a simple Dockerfile template
-    std::fs::write(
-        templates_dir.join("Dockerfile.tera"),
-        "FROM python:3.9-slim\nWORKDIR /app\nCOPY . .\nRUN pip install -r requirements.txt"
-    )?;
-    
-    // Create a simple README template
-    std::fs::write(
-        templates_dir.join("README.md.tera"),
-        "# {{ agent_id }}\n\nThis is an LLM agent."
-    )?;
-    
-    // Create a simple k8s deployment template
-    std::fs::create_dir_all(templates_dir.join("kubernetes"))?;
-    std::fs::write(
-        templates_dir.join("kubernetes/deployment.yaml.tera"),
-        "apiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: {{ agent_id }}\nspec:\n  replicas: 1\n  selector:\n    matchLabels:\n      app: {{ agent_id }}\n  template:\n    metadata:\n      labels:\n        app: {{ agent_id }}\n    spec:\n      containers:\n      - name: {{ agent_id }}\n        image: {{ agent_id }}\n        ports:\n        - containerPort: 8080"
-    )?;
-    
-    // Initialize Tera
-    let tera = Tera::default();
+    // Initialize Tera with the built-in templates
+    let tera = TemplateManager::default().engine()?;
     
     // Generate agent files
     println!("Output directory: {}", output_dir.path().display());
-    generate_agent(&agent, output_dir.path(), &tera)?;
+    generate_agent(&test_workflow(), &agent, output_dir.path(), &tera, None, &mut FsSink)?;
     
     // Verify output directory structure
     let agent_dir = output_dir.path().join("agents/test-agent");
@@ -106,13 +113,15 @@ fn test_generate_agent_with_config() -> Result<()> {
         id: Some("config-agent".to_string()),
         agent_type: AgentType::LLM,
         config: vec![],
+        doc: None,
+        span: Span::default(),
     };
     
-    // Initialize Tera
-    let tera = Tera::default();
+    // Initialize Tera with the built-in templates
+    let tera = TemplateManager::default().engine()?;
     
     // Generate agent files
-    generate_agent(&agent, output_dir.path(), &tera)?;
+    generate_agent(&test_workflow(), &agent, output_dir.path(), &tera, None, &mut FsSink)?;
     
     // Verify output directory structure
     let agent_dir = output_dir.path().join("agents/config-agent");
@@ -123,22 +132,137 @@ fn test_generate_agent_with_config() -> Result<()> {
 }
 
 #[test]
-fn test_generate_agent_without_id() {
+fn test_generate_agent_without_id() -> Result<()> {
     // Create a test agent without an ID
     let agent = Agent {
         id: None,
         agent_type: AgentType::LLM,
         config: vec![],
+        doc: None,
+        span: Span::default(),
     };
     
-    // Initialize Tera
-    let tera = Tera::default();
+    // Initialize Tera with the built-in templates
+    let tera = TemplateManager::default().engine()?;
     
     // This should fail because the agent doesn't have an ID
-    let result = generate_agent(&agent, Path::new("."), &tera);
+    let result = generate_agent(&test_workflow(), &agent, Path::new("."), &tera, None, &mut FsSink);
     assert!(result.is_err());
     assert_eq!(
         result.unwrap_err().to_string(),
         "Agent must have an ID"
     );
+    Ok(())
+}
+
+const EVERY_AGENT_TYPE: &str = r#"
+workflow Everything {
+    source: NATS("orders");
+    target: NATS("orders.done");
+    agents: [
+        MissingValueHandler(id: "fill", defaults: { "customer.tier": "basic" }, impute: { amount: median() }),
+        DataNormalizer(id: "normalize", mappings: { amount: data.payment.amount }, scale: { amount: zscore(mean: 50, std: 10) }),
+        DataProcessor(id: "enrich"),
+        LLM(id: "classify", model: "gpt-4o"),
+        MLModel(id: "score", model_path: "models/score.onnx"),
+        BayesianNetwork(id: "infer", network_path: "models/fraud.xmlbif"),
+        QualityMonitor(id: "quality"),
+        DecisionMatrix(id: "policy"),
+        RuleEngine(id: "risk", rules: [high: data.score > 0.9 => set(risk: "high")]),
+        Aggregator(id: "totals", window: { count: 100 }, reduce: { total: sum(data.amount) }),
+        Router(id: "route"),
+        HumanReview(id: "approve")
+    ];
+}
+"#;
+
+#[test]
+fn test_every_agent_type_is_generated_from_its_templates() -> Result<()> {
+    let program = parse(EVERY_AGENT_TYPE)?;
+    let workflow = &program.workflows[0];
+    let tera = TemplateManager::default().engine()?;
+    for agent in &workflow.agents {
+        let agent_id = agent.id.as_deref().unwrap();
+        let mut sink = PlanSink::new();
+        generate_agent(workflow, agent, Path::new("out"), &tera, None, &mut sink)?;
+        let planned: Vec<PathBuf> = sink.plan()?.into_iter().map(|file| file.path).collect();
+
+        let agent_dir = Path::new("out/agents").join(agent_id);
+        let expected: Vec<String> = match agent_language(agent) {
+            "python" => vec![
+                "pyproject.toml".to_string(),
+                format!("src/kumeo_agent_{}/agent.py", agent_id),
+                "tests/test_agent.py".to_string(),
+            ],
+            _ => ["Cargo.toml", "src/agent.rs", "src/lib.rs", "src/tests.rs", "src/resilience.rs", "src/condition.rs", "src/schema.rs", "src/saga.rs"]
+                .map(String::from)
+                .to_vec(),
+        };
+        for file in expected.iter().map(String::as_str).chain(["Dockerfile", "README.md", "kubernetes/deployment.yaml"]) {
+            assert!(planned.contains(&agent_dir.join(file)), "{} agent {} lacks {}: {:?}", agent.agent_type, agent_id, file, planned);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_every_agent_type_renders_files_that_parse() -> Result<()> {
+    let generated = generate(EVERY_AGENT_TYPE)?;
+    let mut python = BTreeMap::new();
+    for (path, contents) in &generated.files {
+        // El runtime se copia tal cual, no se renderiza
+        if !path.starts_with("agents") || path.components().any(|part| part.as_os_str() == "kumeo-runtime") {
+            continue;
+        }
+        let name = path.to_string_lossy();
+        let source = String::from_utf8(contents.clone())?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("rs") => {
+                if let Err(error) = syn::parse_file(&source) {
+                    panic!("{} no es Rust válido: {}\n{}", name, error, source);
+                }
+            }
+            Some("toml") => {
+                toml::from_str::<toml::Value>(&source).unwrap_or_else(|error| panic!("{} no es TOML válido: {}", name, error));
+            }
+            Some("yaml") => {
+                for document in serde_yaml::Deserializer::from_str(&source) {
+                    serde_yaml::Value::deserialize(document).unwrap_or_else(|error| panic!("{} no es YAML válido: {}", name, error));
+                }
+            }
+            Some("py") => {
+                python.insert(name.into_owned(), source);
+            }
+            _ => {}
+        }
+    }
+    assert!(python.keys().any(|path| path.ends_with("kumeo_agent_infer/agent.py")), "{:?}", python.keys());
+
+    // Sin intérprete no hay con qué comprobar los agentes de Python
+    let Ok(mut interpreter) = Command::new("python3")
+        .args(["-c", "import ast, json, sys\nfor path, source in json.load(sys.stdin).items(): ast.parse(source, path)"])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    else {
+        return Ok(());
+    };
+    interpreter.stdin.take().expect("stdin").write_all(&serde_json::to_vec(&python)?)?;
+    let output = interpreter.wait_with_output()?;
+    assert!(output.status.success(), "Python inválido: {}", String::from_utf8_lossy(&output.stderr));
+    Ok(())
+}
+
+#[test]
+fn test_template_errors_fail_the_agent() -> Result<()> {
+    let program = parse(EVERY_AGENT_TYPE)?;
+    let workflow = &program.workflows[0];
+    let route = workflow.agents.iter().find(|agent| agent.id.as_deref() == Some("route")).expect("route");
+    let mut tera = TemplateManager::default().engine()?;
+    tera.add_raw_template("agents/rust/Router/src/agent.rs.tera", "{{ no_such_variable }}")?;
+
+    let error = generate_agent(workflow, route, Path::new("out"), &tera, None, &mut PlanSink::new()).unwrap_err();
+    let error = format!("{:#}", error);
+    assert!(error.contains("agents/rust/Router/src/agent.rs.tera") && error.contains("no_such_variable"), "{}", error);
+    Ok(())
 }
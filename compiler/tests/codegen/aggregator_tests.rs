@@ -0,0 +1,86 @@
+use super::{generate, rust_calls, rust_item, rust_tokens};
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::{AggregationWindow, AggregatorConfig, Reducer},
+    codegen::aggregator::AggregatorSettings,
+    parser::parse,
+};
+
+const TOTALS: &str = r#"
+workflow Totals {
+    source: NATS("orders");
+    target: NATS("totals");
+    agents: [
+        Aggregator(
+            id: "totals",
+            window: { time: "1m" },
+            group_by: [data.customer.id, data.region],
+            reduce: { total: sum(data.amount), orders: count(), biggest: max(data.amount ?? 0) }
+        ),
+        DataProcessor(id: "clean")
+    ];
+}
+"#;
+
+#[test]
+fn test_aggregator_config_is_read() -> Result<()> {
+    let program = parse(TOTALS)?;
+    let config = AggregatorConfig::from_agent(&program.workflows[0].agents[0]).map_err(anyhow::Error::msg)?;
+    assert_eq!(config.window, AggregationWindow::Time(60_000));
+    assert_eq!(config.group_names(), ["id", "region"]);
+    let reducers: Vec<(&str, Reducer)> = config.reductions.iter().map(|(name, reduction)| (name.as_str(), reduction.reducer)).collect();
+    assert_eq!(reducers, [("biggest", Reducer::Max), ("orders", Reducer::Count), ("total", Reducer::Sum)]);
+    assert_eq!(config.reductions["biggest"].to_string(), "max(data.amount ?? 0)");
+    assert_eq!(config.reductions["orders"].of, None);
+
+    let invalid = [
+        ("window: { count: 10, time: \"1m\" }, reduce: { n: count() }", "not both"),
+        ("window: { count: 0 }, reduce: { n: count() }", "count must be a whole number"),
+        ("window: { time: \"soon\" }, reduce: { n: count() }", "time must be a duration"),
+        ("window: {}, reduce: { n: count() }", "window needs a count or a time"),
+        ("reduce: { n: count() }", "missing window"),
+        ("window: { count: 10 }", "missing reduce"),
+        ("window: { count: 10 }, group_by: customer_id, reduce: { n: count() }", "group_by must be paths below data"),
+        ("window: { count: 10 }, group_by: [data.a.id, data.b.id], reduce: { n: count() }", "two group_by fields are named 'id'"),
+        ("window: { count: 10 }, group_by: data.region, reduce: { region: count() }", "reduction 'region' clashes"),
+        ("window: { count: 10 }, reduce: { window: count() }", "reduction 'window' clashes"),
+        ("window: { count: 10 }, reduce: { total: sum() }", "sum needs a value"),
+        ("window: { count: 10 }, reduce: { total: 3 }", "expected a reduction such as sum(data.amount)"),
+    ];
+    for (options, expected) in invalid {
+        let source = format!(r#"workflow A {{ agents: [Aggregator(id: "a", {})]; }}"#, options);
+        let program = parse(&source)?;
+        let error = AggregatorConfig::from_agent(&program.workflows[0].agents[0]).unwrap_err();
+        assert!(error.to_string().contains(expected), "{}: {}", options, error);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_aggregators_render_their_windows() -> Result<()> {
+    let program = parse(TOTALS)?;
+    let workflow = &program.workflows[0];
+    let aggregator = AggregatorSettings::for_agent(&workflow.agents[0])?.expect("Debería agregar mensajes");
+    assert_eq!(AggregatorSettings::for_agent(&workflow.agents[1])?, None);
+    assert_eq!((aggregator.window_count, aggregator.window_millis), (None, Some(60_000)));
+    assert_eq!(aggregator.groups[0].rust, r#"field(data, &["customer", "id"])"#);
+    assert_eq!(aggregator.reductions[0].rust.as_deref(), Some(r#"coalesce(field(data, &["amount"]), &json!(0))"#));
+
+    let generated = generate(TOTALS)?;
+    let window = generated.rust("agents/totals/src/window.rs");
+    assert_eq!(rust_item(&window, "COUNT"), rust_tokens("None"));
+    assert_eq!(rust_item(&window, "MILLIS"), rust_tokens("Some(60000)"));
+    assert_eq!(rust_item(&window, "GROUP_FIELDS"), rust_tokens(r#"&["id", "region"]"#));
+    assert_eq!(
+        rust_item(&window, "REDUCTIONS"),
+        rust_tokens(r#"&[("biggest", Reducer::Max), ("orders", Reducer::Count), ("total", Reducer::Sum)]"#)
+    );
+    assert_eq!(
+        rust_item(&window, "values"),
+        rust_tokens(r#"{ vec![Some(coalesce(field(data, &["amount"]), &json!(0)).clone()), None, Some(field(data, &["amount"]).clone())] }"#)
+    );
+    // Time windows close on the clock too
+    let calls = rust_calls(&generated.rust("agents/totals/src/agent.rs"));
+    assert!(calls.contains(".close_expired") && calls.contains("now_millis"), "{:?}", calls);
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use super::{generate, rust_item, rust_tokens};
+use anyhow::Result;
+use kumeo_compiler::{
+    codegen::{auth::AuthSettings, review::ReviewSettings},
+    parser::parse,
+};
+use serde::Deserialize;
+
+const PAYMENTS: &str = r#"workflow Payments {
+    agents: [HumanReview(id: "approval", auth: OIDC("https://sso.example.com", "review-ui", "roles", allow: ["finance", "audit"]))];
+}"#;
+
+const INGEST: &str = r#"workflow Ingest {
+    source: HTTP("/ingest", { auth: OIDC("https://sso.example.com", "ingest") });
+    agents: [LLM(id: "intake"), Router(id: "route")];
+}"#;
+
+#[test]
+fn test_review_api_requires_oidc_tokens() -> Result<()> {
+    let program = parse(PAYMENTS)?;
+    let workflow = &program.workflows[0];
+    let auth = AuthSettings::for_agent(workflow, &workflow.agents[0])?.expect("Debería tener autenticación");
+    assert_eq!(auth.client_id, "review-ui");
+    assert_eq!(auth.groups_claim, "roles");
+    assert_eq!(auth.allowed_groups, "finance,audit");
+    assert_eq!(auth.config_map, "approval-oidc");
+
+    // The reviewers are identified with the same issuer and client
+    let review = ReviewSettings::for_agent(workflow, &workflow.agents[0])?.expect("Debería tener ajustes de revisión");
+    assert_eq!(review.oidc_issuer.as_deref(), Some("https://sso.example.com"));
+    assert_eq!(review.oidc_audience, "review-ui");
+
+    let generated = generate(PAYMENTS)?;
+    let auth = generated.rust("agents/approval/src/auth.rs");
+    assert_eq!(rust_item(&auth, "GROUPS_CLAIM"), rust_tokens(r#""roles""#));
+    assert_eq!(rust_item(&auth, "ALLOWED_GROUPS"), rust_tokens(r#"&["finance", "audit"]"#));
+
+    // The settings reach the pod through a ConfigMap, and the review API through a Service
+    let rendered = generated.file("agents/approval/kubernetes/deployment.yaml");
+    let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(&rendered)
+        .map(serde_yaml::Value::deserialize)
+        .collect::<Result<_, _>>()?;
+    let document = |kind: &str| documents.iter().find(|document| document["kind"].as_str() == Some(kind)).cloned();
+
+    let config_map = document("ConfigMap").expect("Debería generar el ConfigMap de OIDC");
+    assert_eq!(config_map["metadata"]["name"].as_str(), Some("approval-oidc"));
+    assert_eq!(config_map["data"]["issuer"].as_str(), Some("https://sso.example.com"));
+    assert_eq!(config_map["data"]["allowed_groups"].as_str(), Some("finance,audit"));
+
+    let env = documents[0]["spec"]["template"]["spec"]["containers"][0]["env"].as_sequence().expect("env");
+    let var = |name: &str| env.iter().find(|var| var["name"].as_str() == Some(name)).cloned();
+    let issuer = var("KUMEO_OIDC_ISSUER").expect("emisor");
+    assert_eq!(issuer["valueFrom"]["configMapKeyRef"]["name"].as_str(), Some("approval-oidc"));
+    assert_eq!(env.iter().filter(|var| var["name"].as_str() == Some("KUMEO_OIDC_AUDIENCE")).count(), 1, "La audiencia solo debería definirse una vez");
+
+    let service = document("Service").expect("Debería exponer la API de revisión");
+    assert_eq!(service["metadata"]["name"].as_str(), Some("approval-reviews"));
+    assert_eq!(service["spec"]["ports"][0]["targetPort"].as_str(), Some("review-api"));
+    Ok(())
+}
+
+#[test]
+fn test_http_source_auth_protects_the_webhook() -> Result<()> {
+    let program = parse(INGEST)?;
+    let workflow = &program.workflows[0];
+
+    // Only the agent serving the webhook authenticates
+    assert_eq!(AuthSettings::for_agent(workflow, &workflow.agents[1])?, None);
+    let auth = AuthSettings::for_agent(workflow, &workflow.agents[0])?.expect("Debería tener autenticación");
+    assert_eq!((auth.groups_claim.as_str(), auth.allowed_groups.as_str()), ("groups", ""));
+    assert_eq!(auth.config_map, "intake-oidc");
+
+    let rendered = generate(INGEST)?.rust("agents/intake/src/auth.rs");
+    assert_eq!(rust_item(&rendered, "REQUIRED"), rust_tokens("true"));
+    assert_eq!(rust_item(&rendered, "ISSUER"), rust_tokens(r#"Some("https://sso.example.com")"#));
+    assert_eq!(rust_item(&rendered, "AUDIENCE"), rust_tokens(r#""ingest""#));
+    assert_eq!(rust_item(&rendered, "ALLOWED_GROUPS"), rust_tokens("&[]"));
+
+    // Without auth the webhook stays open
+    let open = r#"workflow Ingest { source: HTTP("/ingest"); agents: [LLM(id: "intake")]; }"#;
+    let program = parse(open)?;
+    assert_eq!(AuthSettings::for_agent(&program.workflows[0], &program.workflows[0].agents[0])?, None);
+    let rendered = generate(open)?.rust("agents/intake/src/auth.rs");
+    assert_eq!(rust_item(&rendered, "REQUIRED"), rust_tokens("false"));
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use super::generate;
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::NetworkFormat,
+    codegen::{bayesian_network::BayesianNetworkSettings, dependencies::DependencySettings},
+    parser::parse,
+};
+use serde_json::json;
+
+const FRAUD: &str = r#"
+workflow Fraud {
+    source: NATS("payments");
+    target: NATS("payments.scored");
+    agents: [
+        BayesianNetwork(id: "fraud", network_path: "models/fraud.xmlbif", query: ["fraud", "chargeback"]),
+        BayesianNetwork(id: "risk", network_path: "s3://models/risk.BIF"),
+        Router(id: "route")
+    ];
+}
+"#;
+
+#[test]
+fn test_bayesian_networks_are_compiled_from_their_options() -> Result<()> {
+    let program = parse(FRAUD)?;
+    let workflow = &program.workflows[0];
+
+    let fraud = BayesianNetworkSettings::for_agent(&workflow.agents[0])?.expect("Debería consultar una red");
+    assert_eq!(fraud.network_path, "models/fraud.xmlbif");
+    assert_eq!(fraud.format, NetworkFormat::XmlBif);
+    assert_eq!(fraud.python_query, r#"["fraud","chargeback"]"#);
+
+    // The extension is case-insensitive; without a query every variable not observed is published
+    let risk = BayesianNetworkSettings::for_agent(&workflow.agents[1])?.expect("Debería consultar una red");
+    assert_eq!((risk.format, risk.python_query.as_str()), (NetworkFormat::Bif, "[]"));
+    assert_eq!(BayesianNetworkSettings::for_agent(&workflow.agents[2])?, None);
+    Ok(())
+}
+
+#[test]
+fn test_bayesian_networks_render_their_reader_and_pin_pgmpy() -> Result<()> {
+    let program = parse(FRAUD)?;
+    let workflow = &program.workflows[0];
+
+    // Without an interpreter the agent can't be read
+    if let Some(constants) = generate(FRAUD)?.python_constants("agents/fraud/src/kumeo_agent_fraud/agent.py") {
+        assert_eq!(constants["_NETWORK_PATH"], json!("models/fraud.xmlbif"));
+        assert_eq!(constants["_NETWORK_FORMAT"], json!("xml_bif"));
+        assert_eq!(constants["_QUERY"], json!(["fraud", "chargeback"]));
+    }
+
+    let dependencies = DependencySettings::for_agent(workflow, &workflow.agents[0])?.expect("Se esperaban dependencias de Python");
+    assert!(dependencies.requirements.contains(&"pgmpy==0.1.26".to_string()), "{:?}", dependencies.requirements);
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::{Agent, AgentType, Deployment, Platform, Source, Span, Target, Workflow, WorkflowMode},
+    codegen::{
+        cluster::{self, ClusterProgram, NatsSettings},
+        sink::FsSink,
+        template_manager::TemplateManager,
+    },
+};
+use serde::Deserialize;
+use std::path::Path;
+use tempfile::tempdir;
+
+fn workflow(name: &str, agent_ids: &[&str], target: &str) -> Workflow {
+    Workflow {
+        name: name.to_string(),
+        source: Some(Source::NATS("events".to_string(), None)),
+        target: Some(Target::NATS(target.to_string(), None)),
+        context: None,
+        preprocessors: None,
+        agents: agent_ids
+            .iter()
+            .map(|id| Agent {
+                id: Some(id.to_string()),
+                agent_type: AgentType::LLM,
+                config: vec![],
+                doc: None,
+                span: Span::default(),
+            })
+            .collect(),
+        monitor: None,
+        deployment: None,
+        version: None,
+        doc: None,
+        mode: WorkflowMode::Stream,
+        calls: vec![],
+        tests: vec![],
+        span: Span::default(),
+    }
+}
+
+#[test]
+fn test_program_name_from_input() {
+    assert_eq!(cluster::program_name(Path::new("flows/Fraud_Detection.kumeo")), "fraud-detection");
+}
+
+#[test]
+fn test_workflows_of_a_program_share_its_namespace() {
+    let scoring = ClusterProgram::for_workflow("fraud", &workflow("Fraud Scoring", &["scorer"], "alerts.fraud"));
+    assert_eq!(scoring.name, "fraud-fraud-scoring");
+    assert_eq!(scoring.dir, "programs/fraud/fraud-scoring");
+    assert_eq!(scoring.namespace, "fraud", "Los workflows de un programa se despliegan en su namespace");
+
+    let alerts = ClusterProgram::for_workflow("fraud", &workflow("Alerts", &["scorer"], "alerts.sent"));
+    let conflicts = cluster::conflicts(&[scoring, alerts]);
+    assert!(
+        conflicts.iter().any(|conflict| conflict.contains("both deploy agent `scorer` into namespace `fraud`")),
+        "{:?}",
+        conflicts
+    );
+}
+
+#[test]
+fn test_subjects_overlap() {
+    assert!(cluster::subjects_overlap("orders.scored", "orders.scored"));
+    assert!(cluster::subjects_overlap("orders.*", "orders.scored"));
+    assert!(cluster::subjects_overlap("orders.>", "orders.eu.scored"));
+    assert!(!cluster::subjects_overlap("orders.>", "orders"));
+    assert!(!cluster::subjects_overlap("orders.*", "orders.eu.scored"));
+    assert!(!cluster::subjects_overlap("orders.scored", "payments.scored"));
+}
+
+#[test]
+fn test_cluster_conflicts() {
+    let fraud = ClusterProgram::new("fraud", &workflow("Fraud", &["scorer"], "alerts.fraud"));
+    let mut shared = workflow("Fraud", &["scorer"], "alerts.*");
+    shared.deployment = Some(Deployment {
+        name: "shared".to_string(),
+        namespace: Some("fraud".to_string()),
+        replicas: None,
+        resources: None,
+        env: None,
+        rollout: None,
+        storage: None,
+        require_signed: false,
+        trusted_keys: None,
+        env_secret: None,
+        infrastructure: None,
+        platform: Platform::default(),
+        scaling: None,
+        labels: None,
+        annotations: None,
+        registry: None,
+        tag: None,
+    });
+    let shared = ClusterProgram::new("shared", &shared);
+    let conflicts = cluster::conflicts(&[fraud.clone(), shared]);
+    assert_eq!(conflicts.len(), 3, "{:?}", conflicts);
+    assert!(conflicts[0].contains("workflow `Fraud`"));
+    assert!(conflicts[1].contains("agent `scorer` into namespace `fraud`"));
+    assert!(conflicts[2].contains("`alerts.fraud` and `alerts.*`"));
+
+    // Separate namespaces and subjects share the cluster fine
+    let clicks = ClusterProgram::new("clicks", &workflow("Clicks", &["scorer"], "clicks.scored"));
+    assert!(cluster::conflicts(&[fraud, clicks]).is_empty());
+}
+
+#[test]
+fn test_cluster_layout_shares_nats() -> Result<()> {
+    let output_dir = tempdir()?;
+    let programs = [
+        ClusterProgram::new("fraud", &workflow("Fraud", &["scorer", "alerter"], "alerts.fraud")),
+        ClusterProgram::new("clicks", &workflow("Clicks", &["counter"], "clicks.counted")),
+    ];
+    let tera = TemplateManager::default().engine()?;
+    cluster::generate_cluster_layout(&programs, output_dir.path(), &tera, &NatsSettings::default(), &mut FsSink)?;
+
+    let read = |path: &str| -> Result<serde_yaml::Value> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(output_dir.path().join(path))?)?)
+    };
+    let root = read("kustomization.yaml")?;
+    assert_eq!(root["resources"], serde_yaml::from_str::<serde_yaml::Value>("[namespaces.yaml, nats, programs/fraud, programs/clicks]")?);
+
+    let nats = std::fs::read_to_string(output_dir.path().join("nats/nats.yaml"))?;
+    let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(&nats)
+        .map(serde_yaml::Value::deserialize)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(documents[2]["kind"].as_str(), Some("StatefulSet"));
+    assert_eq!(documents[2]["metadata"]["namespace"].as_str(), Some(cluster::NATS_NAMESPACE));
+
+    let fraud = read("programs/fraud/kustomization.yaml")?;
+    assert_eq!(fraud["namespace"].as_str(), Some("fraud"));
+    assert_eq!(fraud["resources"][0].as_str(), Some("agents/scorer/kubernetes/deployment.yaml"));
+    let patch = fraud["patches"][0]["patch"].as_str().expect("patch");
+    assert!(patch.contains("nats://nats.kumeo-system.svc.cluster.local:4222"));
+
+    let namespaces = std::fs::read_to_string(output_dir.path().join("namespaces.yaml"))?;
+    let names: Vec<String> = serde_yaml::Deserializer::from_str(&namespaces)
+        .map(|document| serde_yaml::Value::deserialize(document).map(|ns| ns["metadata"]["name"].as_str().unwrap_or_default().to_string()))
+        .collect::<Result<_, _>>()?;
+    assert_eq!(names, ["clicks", "fraud"]);
+    Ok(())
+}
+
+#[test]
+fn test_program_kustomization_sets_deployment_labels() -> Result<()> {
+    let output_dir = tempdir()?;
+    let mut fraud = workflow("Fraud", &["scorer"], "alerts.fraud");
+    fraud.deployment = Some(Deployment {
+        name: "Fraud".to_string(),
+        labels: Some([("team".to_string(), "risk".to_string())].into()),
+        annotations: Some([("example.com/owner".to_string(), "risk@example.com".to_string())].into()),
+        ..Default::default()
+    });
+    let programs = [
+        ClusterProgram::new("fraud", &fraud),
+        ClusterProgram::new("clicks", &workflow("Clicks", &["counter"], "clicks.counted")),
+    ];
+    let tera = TemplateManager::default().engine()?;
+    cluster::generate_cluster_layout(&programs, output_dir.path(), &tera, &NatsSettings::default(), &mut FsSink)?;
+
+    let read = |path: &str| -> Result<serde_yaml::Value> {
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(output_dir.path().join(path))?)?)
+    };
+    let fraud = read("programs/fraud/kustomization.yaml")?;
+    assert_eq!(fraud["labels"][0]["pairs"]["team"].as_str(), Some("risk"));
+    assert!(fraud["labels"][0]["includeSelectors"].is_null(), "Las etiquetas no entran en los selectores");
+    assert_eq!(fraud["commonAnnotations"]["example.com/owner"].as_str(), Some("risk@example.com"));
+
+    let clicks = read("programs/clicks/kustomization.yaml")?;
+    assert!(clicks["labels"].is_null() && clicks["commonAnnotations"].is_null(), "Sin etiquetas no se añade nada");
+    Ok(())
+}
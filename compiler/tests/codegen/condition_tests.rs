@@ -0,0 +1,135 @@
+use super::{generate, rust_calls, rust_item, rust_strings, rust_tokens};
+use anyhow::Result;
+use kumeo_compiler::{
+    codegen::condition::{AssertSettings, WhenSettings},
+    parser::parse,
+};
+
+fn when_settings(condition: &str) -> Result<Option<WhenSettings>> {
+    let program = parse(&format!(
+        r#"workflow Filter {{
+            source: NATS("in");
+            agents: [DataProcessor(id: "filter", when: {})];
+        }}"#,
+        condition
+    ))?;
+    WhenSettings::for_agent(&program.workflows[0], &program.workflows[0].agents[0])
+}
+
+#[test]
+fn test_when_translates_to_rust_and_python() -> Result<()> {
+    let when = when_settings(r#"data.score >= 0.8 && !(data.lang != "es" || data.user.blocked)"#)?
+        .expect("Se esperaba una condición");
+
+    assert_eq!(when.source, r#"data.score >= 0.8 && !(data.lang != "es" || data.user.blocked)"#);
+    assert_eq!(
+        when.rust,
+        r#"compare(field(data, &["score"]), &json!(0.8)).is_some_and(std::cmp::Ordering::is_ge) && !(!equals(field(data, &["lang"]), &json!("es")) || truthy(field(data, &["user", "blocked"])))"#
+    );
+    assert_eq!(
+        when.python,
+        r#"_compare(_field(data, ("score",)), ">=", 0.8) and not (not _equals(_field(data, ("lang",)), "es") or _field(data, ("user", "blocked")))"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_agents_without_when_accept_everything() -> Result<()> {
+    let program = parse(
+        r#"workflow Filter {
+            source: NATS("in");
+            agents: [DataProcessor(id: "filter")];
+        }"#,
+    )?;
+    assert_eq!(WhenSettings::for_agent(&program.workflows[0], &program.workflows[0].agents[0])?, None);
+
+    let when = when_settings("(data.score > 1) == false")?.expect("Se esperaba una condición");
+    assert_eq!(
+        when.rust,
+        r#"equals(&Value::Bool(compare(field(data, &["score"]), &json!(1)).is_some_and(std::cmp::Ordering::is_gt)), &Value::Bool(false))"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_when_fields_resolve_against_the_input_schema() -> Result<()> {
+    let when_settings = |condition: &str| -> Result<Option<WhenSettings>> {
+        let program = parse(&format!(
+            r#"workflow Filter {{
+                source: NATS("in");
+                agents: [
+                    MLModel(id: "score", model_path: "models/score.onnx", output: "scores",
+                            output_schema: {{ score: "number", order: "object" }}),
+                    DataProcessor(id: "filter", input: "scores", when: {})
+                ];
+            }}"#,
+            condition
+        ))?;
+        let workflow = &program.workflows[0];
+        WhenSettings::for_agent(workflow, &workflow.agents[1])
+    };
+
+    let when = when_settings("data.score > 0.8 && data.order.total > 10")?.expect("Se esperaba una condición");
+    assert_eq!(when.fields.into_iter().collect::<Vec<_>>(), [("data.score".to_string(), "number".to_string())]);
+
+    let error = when_settings("data.total > 10").expect_err("Debería rechazar un campo que el esquema no declara");
+    assert!(error.to_string().contains("unknown field 'total'"));
+    assert!(when_settings("data.score.value > 10").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_defaults_fall_back_on_null_fields() -> Result<()> {
+    let when = when_settings(r#"data.customer?.tier ?? "free" == "gold""#)?.expect("Se esperaba una condición");
+    assert_eq!(
+        when.rust,
+        r#"equals(coalesce(field(data, &["customer", "tier"]), &json!("free")), &json!("gold"))"#
+    );
+    assert_eq!(when.python, r#"_equals(_coalesce(_field(data, ("customer", "tier")), "free"), "gold")"#);
+    Ok(())
+}
+
+/// A workflow whose only agent takes some options
+fn payments(options: &str) -> String {
+    format!(
+        r#"workflow Payments {{
+            source: NATS("in");
+            agents: [DataProcessor(id: "clean", {})];
+        }}"#,
+        options
+    )
+}
+
+fn assert_settings(options: &str) -> Result<Option<AssertSettings>> {
+    let program = parse(&payments(options))?;
+    AssertSettings::for_agent(&program.workflows[0], &program.workflows[0].agents[0])
+}
+
+#[test]
+fn test_assertions_are_compiled_and_audited() -> Result<()> {
+    let options = r#"assert: data.amount >= 0, assert: data.currency == "EUR""#;
+    let assertions = assert_settings(options)?.expect("Se esperaban invariantes");
+    assert_eq!(assertions.subject, "kumeo.audit.payments");
+    assert_eq!(assertions.checks.len(), 2);
+    assert_eq!(assertions.checks[1].source, r#"data.currency == "EUR""#);
+    assert_eq!(assertions.checks[1].rust, r#"equals(field(data, &["currency"]), &json!("EUR"))"#);
+    assert_eq!(assertions.checks[1].python, r#"_equals(_field(data, ("currency",)), "EUR")"#);
+    assert_eq!(assertions.checks[1].rust_source, r#""data.currency == \"EUR\"""#);
+
+    // Every invariant is checked in order, and the first one broken is returned
+    let condition = generate(&payments(options))?.rust("agents/clean/src/condition.rs");
+    let checks: String = assertions.checks.iter().map(|check| format!("if !({}) {{ return Some({}); }}", check.rust, check.rust_source)).collect();
+    let violation = format!(
+        "{{ let Ok(data) = serde_json::from_slice::<Value>(payload) else {{ return Some({}); }}; let data = &data; {} None }}",
+        assertions.checks[0].rust_source, checks
+    );
+    assert_eq!(rust_item(&condition, "violation"), rust_tokens(&violation));
+    assert!(rust_calls(&condition).contains(".publish"));
+    assert!(rust_strings(&condition).contains(&"kumeo.audit.payments".to_string()));
+
+    let assertions = assert_settings(r#"assert: data.amount >= 0, fallback: { action: "dead_letter", subject: "payments.invalid" }"#)?
+        .expect("Se esperaba un invariante");
+    assert_eq!(assertions.subject, "payments.invalid");
+    assert_eq!(assert_settings(r#"when: data.amount >= 0"#)?, None);
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use super::{generate_with, GenerateOptions};
+use anyhow::Result;
+use kumeo_compiler::{
+    codegen::{
+        console::{ConsoleSettings, CONSOLE_NAME, DEAD_LETTER_HISTORY},
+        drift::workflow_hash,
+        nats::ExternalNats,
+    },
+    parser::parse,
+};
+use serde::Deserialize;
+
+const FILES: [&str; 5] = [
+    "Cargo.toml",
+    "src/main.rs",
+    "static/index.html",
+    "Dockerfile",
+    "kubernetes/deployment.yaml",
+];
+
+const PROGRAM: &str = r#"
+workflow Payments {
+    source: NATS("payments.in");
+    target: NATS("payments.out");
+    agents: [
+        LLM(id: "score", engine: "ollama/llama3", prompt: "Score {{data}}",
+            fallback: { action: "dead_letter", subject: "score.failed" }),
+        Router(id: "route")
+    ];
+    deployment: { namespace: "payments", registry: "ghcr.io/acme", tag: "1.4.0" };
+}
+
+workflow Churn {
+    source: Kafka("events");
+    agents: [LLM(id: "predict", engine: "ollama/llama3", prompt: "Predict {{data}}")];
+}
+"#;
+
+#[test]
+fn test_console_shows_every_workflow() -> Result<()> {
+    let program = parse(PROGRAM)?;
+    let workflows: Vec<_> = program.workflows.iter().collect();
+    let console = ConsoleSettings::for_workflows(&workflows, "nats://nats:4222")?;
+
+    assert_eq!(console.image, format!("ghcr.io/acme/{}:1.4.0", CONSOLE_NAME), "La imagen usa el registro del primer workflow");
+    assert_eq!(console.dead_letter_history, DEAD_LETTER_HISTORY);
+    let names: Vec<&str> = console.workflows.iter().map(|workflow| workflow.name.as_str()).collect();
+    assert_eq!(names, ["Payments", "Churn"]);
+
+    let payments = &console.workflows[0];
+    assert_eq!(payments.hash, workflow_hash(&program.workflows[0])?, "Los agentes se comparan con el hash compilado");
+    assert_eq!(payments.registration_subject, "kumeo.control.Payments.registered");
+    assert_eq!(payments.cache_subject, "kumeo.control.Payments.cache");
+    let source = payments.source.as_ref().expect("Debería tener source");
+    assert_eq!((source.kind, source.topic.as_str()), ("NATS", "payments.in"));
+    assert_eq!(payments.agents[0].dead_letter.as_deref(), Some("score.failed"));
+    assert_eq!(payments.agents[1].dead_letter, None, "Sin fallback dead_letter no hay cola");
+    assert_eq!(payments.agents[0].input.as_deref(), Some("payments.in"));
+    assert_eq!(payments.agents[1].output.as_deref(), Some("payments.out"));
+
+    let churn = &console.workflows[1];
+    assert_eq!(churn.source.as_ref().map(|source| source.kind), Some("Kafka"));
+    assert_eq!(churn.target, None);
+
+    let topology: serde_json::Value = serde_json::from_str(&console.topology)?;
+    assert_eq!(topology[0]["agents"][0]["id"], "score");
+    Ok(())
+}
+
+#[test]
+fn test_console_templates_render() -> Result<()> {
+    let nats = ExternalNats::new("nats://nats.shared:4222", Some("nats-creds"))?;
+    let generated = generate_with(PROGRAM, &GenerateOptions { external_nats: Some(&nats), console: true })?;
+    let file = |file: &str| generated.file(&format!("console/{}", file));
+    for name in FILES {
+        file(name);
+    }
+
+    let main = file("src/main.rs");
+    assert!(main.contains(r#"const NATS_URL: &str = "nats://nats.shared:4222";"#), "{}", main);
+    assert!(main.contains(r#"\"dead_letter\": \"score.failed\""#), "La topología va embebida en el backend");
+    assert!(!main.contains("publish("), "La consola es de solo lectura");
+    assert!(file("static/index.html").contains("Kumeo console"));
+
+    let manifests = file("kubernetes/deployment.yaml");
+    let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(&manifests)
+        .map(serde_yaml::Value::deserialize)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(documents.len(), 2, "Deployment y Service");
+    assert_eq!(documents[0]["metadata"]["namespace"], "payments");
+    assert_eq!(documents[0]["spec"]["template"]["spec"]["containers"][0]["image"], "ghcr.io/acme/kumeo-console:1.4.0");
+    assert!(manifests.contains("name: nats-creds"), "Lee las credenciales del NATS externo");
+    assert!(file("Dockerfile").contains("EXPOSE 8080"));
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use anyhow::Result;
+use kumeo_compiler::{
+    codegen::{
+        contracts::{attach, ValidationSettings},
+        topics::wire,
+    },
+    parser::parse,
+};
+
+#[test]
+fn test_input_schemas_are_attached_to_consumers() -> Result<()> {
+    let program = parse(
+        r#"
+        schemas: { "orders.clean": { id: "string", total: "number" } };
+
+        workflow Orders {
+            source: NATS("orders");
+            target: NATS("alerts");
+            agents: [
+                DataProcessor(id: "clean", output: "orders.clean"),
+                MLModel(id: "score", model_path: "models/score.onnx", input: "orders.clean", output_schema: { id: "string", score: "number?" }),
+                Router(id: "route", input: "score.output"),
+                LLM(id: "explain", model: "gpt-4", input: "score.output", input_schema: { id: "string" })
+            ];
+        }
+        "#,
+    )?;
+    let workflow = attach(&wire(&program.workflows[0])?, &program.topic_schemas());
+    let agents = &workflow.agents;
+
+    // The source has no schema
+    assert!(agents[0].config_value("input_schema").is_none());
+    assert!(ValidationSettings::for_agent(&agents[0])?.is_none());
+
+    // A declared topic schema
+    let score = ValidationSettings::for_agent(&agents[1])?.expect("validation");
+    assert_eq!(score.rust, r#"&[("id", "string"), ("total", "number")]"#);
+    assert_eq!(score.python, r#"{"id": "string", "total": "number"}"#);
+
+    // The output schema of the producing step
+    let route = ValidationSettings::for_agent(&agents[2])?.expect("validation");
+    assert_eq!(route.rust, r#"&[("id", "string"), ("score", "number?")]"#);
+
+    // An agent's own input schema is kept
+    let explain = ValidationSettings::for_agent(&agents[3])?.expect("validation");
+    assert_eq!(explain.rust, r#"&[("id", "string")]"#);
+    Ok(())
+}
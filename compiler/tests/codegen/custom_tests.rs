@@ -0,0 +1,90 @@
+use anyhow::Result;
+use kumeo_compiler::{
+    codegen::{
+        agent::{agent_language, generate_agent},
+        custom::CustomSettings,
+        dependencies::REQUIREMENTS_FILE,
+        sink::FsSink,
+        template_manager::{TemplateManager, TemplateSource},
+    },
+    parser::parse,
+};
+use std::fs;
+use tempfile::tempdir;
+
+const FRAUD: &str = r#"
+workflow Fraud {
+    source: NATS("payments");
+    target: NATS("payments.scored");
+    agents: [
+        Custom("fraud-scorer", id: "scorer", language: "python", threshold: 0.8, labels: ["fraud", "ok"]),
+        Custom("enricher", id: "enrich")
+    ];
+}
+"#;
+
+#[test]
+fn test_custom_agents_pass_their_options_to_their_templates() -> Result<()> {
+    let program = parse(FRAUD)?;
+    let workflow = &program.workflows[0];
+
+    let scorer = CustomSettings::for_agent(&workflow.agents[0])?.expect("Debería ser un agente personalizado");
+    assert_eq!((scorer.kind.as_str(), scorer.language.as_str()), ("fraud-scorer", "python"));
+    assert_eq!(scorer.template_prefix(), "agents/fraud-scorer/");
+    // `language` picks the templates' language and isn't one of their options
+    let options: Vec<&str> = scorer.options.keys().map(String::as_str).collect();
+    assert_eq!(options, ["labels", "threshold"]);
+    assert_eq!(scorer.options["threshold"], serde_json::json!(0.8));
+    assert_eq!(agent_language(&workflow.agents[0]), "python");
+
+    // Rust by default
+    assert_eq!(agent_language(&workflow.agents[1]), "rust");
+    Ok(())
+}
+
+#[test]
+fn test_custom_agents_are_generated_from_the_project_templates() -> Result<()> {
+    let program = parse(FRAUD)?;
+    let workflow = &program.workflows[0];
+    let temp_dir = tempdir()?;
+    let project_dir = temp_dir.path().join("project");
+    fs::create_dir_all(project_dir.join("agents/fraud-scorer/src"))?;
+    fs::write(
+        project_dir.join("agents/fraud-scorer/src/agent.py.tera"),
+        "THRESHOLD = {{ custom.options.threshold }}  # {{ agent_name }} on {{ workflow_name }}",
+    )?;
+    fs::write(project_dir.join("agents/fraud-scorer/pyproject.toml.tera"), "name = \"{{ agent_name }}\"")?;
+
+    let tera = TemplateManager::default().with_layer(TemplateSource::Project, &project_dir).engine()?;
+    let output = temp_dir.path().join("output");
+    generate_agent(workflow, &workflow.agents[0], &output, &tera, None, &mut FsSink)?;
+
+    let scorer = output.join("agents/scorer");
+    assert_eq!(fs::read_to_string(scorer.join("src/agent.py"))?, "THRESHOLD = 0.8  # scorer on Fraud");
+    assert_eq!(fs::read_to_string(scorer.join("pyproject.toml"))?, "name = \"scorer\"");
+    // The rest is generated as for the built-in agents of its language
+    let dockerfile = fs::read_to_string(scorer.join("Dockerfile"))?;
+    assert!(dockerfile.contains("pip install --user -r requirements.txt"), "{}", dockerfile);
+    assert!(scorer.join(REQUIREMENTS_FILE).exists());
+    assert!(!scorer.join("tests/test_agent.py").exists(), "Los agentes personalizados traen sus propios tests");
+
+    // A Dockerfile among the templates replaces the generated one
+    fs::write(project_dir.join("agents/fraud-scorer/Dockerfile.tera"), "FROM python:3.12 # {{ agent_id }}")?;
+    let tera = TemplateManager::default().with_layer(TemplateSource::Project, &project_dir).engine()?;
+    generate_agent(workflow, &workflow.agents[0], &output, &tera, None, &mut FsSink)?;
+    assert_eq!(fs::read_to_string(scorer.join("Dockerfile"))?, "FROM python:3.12 # scorer");
+    Ok(())
+}
+
+#[test]
+fn test_custom_agents_without_templates_say_where_to_put_them() -> Result<()> {
+    let program = parse(FRAUD)?;
+    let workflow = &program.workflows[0];
+    let temp_dir = tempdir()?;
+
+    let error = generate_agent(workflow, &workflow.agents[1], temp_dir.path(), &tera::Tera::default(), None, &mut FsSink).unwrap_err();
+    let message = format!("{:#}", error);
+    assert!(message.contains("Custom agent enrich has no templates: create agents/enricher/"), "{}", message);
+    assert!(message.contains("agents/enricher/src/main.rs.tera"), "{}", message);
+    Ok(())
+}
@@ -0,0 +1,224 @@
+use super::generate;
+use anyhow::Result;
+use kumeo_compiler::{
+    codegen::{
+        agent::generate_agent,
+        dependencies::{CrateSettings, DependencySettings, REQUIREMENTS_FILE, RUNTIME_SOURCES},
+        sink::FsSink,
+        template_manager::TemplateManager,
+    },
+    parser::parse,
+};
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::tempdir;
+
+const RIDES: &str = r#"
+workflow Rides {
+    source: NATS("rides");
+    agents: [
+        MLModel(
+            id: "eta",
+            model_path: "models/eta.onnx",
+            features: Feast("driver_stats", keys: ["driver_id"], features: ["conv_rate"], online_store: "redis:6379")
+        ),
+        MLModel(id: "price", model: "models/price.pkl"),
+        QualityMonitor(id: "quality"),
+        Router(id: "dispatch")
+    ];
+}
+"#;
+
+const ORDERS: &str = r#"
+workflow Orders {
+    source: Kafka("orders");
+    target: Kafka("scored-orders");
+    agents: [
+        Router(id: "route"),
+        MLModel(id: "score", model_path: "models/score.onnx")
+    ];
+}
+"#;
+
+/// Names a Python module imports from its own package, as `module.name`, `None` without an interpreter
+///
+/// The module is read with the interpreter's own parser, failing the test if it isn't valid Python.
+fn python_local_imports(path: &str, source: &str) -> Result<Option<Vec<String>>> {
+    const SCRIPT: &str = "import ast, sys\n\
+        for node in ast.walk(ast.parse(sys.stdin.read())):\n\
+        \x20   if isinstance(node, ast.ImportFrom) and node.level:\n\
+        \x20       print(*(f'{node.module}.{alias.name}' for alias in node.names), sep='\\n')";
+    let Ok(mut python) = Command::new("python3")
+        .args(["-c", SCRIPT])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    else {
+        return Ok(None);
+    };
+    python.stdin.take().expect("stdin").write_all(source.as_bytes())?;
+    let output = python.wait_with_output()?;
+    assert!(output.status.success(), "{} no es Python válido: {}", path, String::from_utf8_lossy(&output.stderr));
+    Ok(Some(String::from_utf8(output.stdout)?.lines().map(str::to_string).collect()))
+}
+
+#[test]
+fn test_python_agents_pin_what_their_templates_need() -> Result<()> {
+    let program = parse(RIDES)?;
+    let workflow = &program.workflows[0];
+    let runtime = format!("kumeo-runtime=={}", env!("CARGO_PKG_VERSION"));
+
+    let eta = DependencySettings::for_agent(workflow, &workflow.agents[0])?.expect("Se esperaban dependencias de Python");
+    assert_eq!(
+        eta.requirements,
+        [
+            "aiohttp==3.9.5",
+            "feast[redis]==0.40.1",
+            runtime.as_str(),
+            "numpy==1.26.4",
+            "onnxruntime==1.18.1",
+            "pydantic==2.8.2",
+            "tensorflow==2.16.2",
+        ]
+    );
+
+    // El modelo está serializado con pickle
+    let price = DependencySettings::for_agent(workflow, &workflow.agents[1])?.expect("Se esperaban dependencias de Python");
+    assert!(price.requirements.contains(&"scikit-learn==1.5.1".to_string()));
+    assert!(price.requirements.contains(&"joblib==1.4.2".to_string()));
+    assert!(!price.requirements.iter().any(|requirement| requirement.starts_with("onnxruntime") || requirement.starts_with("feast")));
+
+    let quality = DependencySettings::for_agent(workflow, &workflow.agents[2])?.expect("Se esperaban dependencias de Python");
+    assert_eq!(quality.requirements, [runtime.as_str(), "pydantic==2.8.2"]);
+
+    assert_eq!(DependencySettings::for_agent(workflow, &workflow.agents[3])?, None, "Los agentes en Rust no usan requirements.txt");
+    Ok(())
+}
+
+#[test]
+fn test_rust_agents_list_their_crates_in_cargo_toml() -> Result<()> {
+    let program = parse(RIDES)?;
+    let workflow = &program.workflows[0];
+    assert_eq!(CrateSettings::for_agent(workflow, &workflow.agents[2])?, None, "Los agentes en Python no tienen Cargo.toml");
+
+    let manifest = generate(RIDES)?.file("agents/dispatch/Cargo.toml");
+
+    let dependencies = manifest
+        .split("[dependencies]\n")
+        .nth(1)
+        .and_then(|rest| rest.split("\n[dev-dependencies]\n").next())
+        .expect("Falta la sección [dependencies]");
+    let crates: Vec<&str> = dependencies.lines().map(|line| line.split(" = ").next().unwrap_or(line)).collect();
+    assert_eq!(
+        crates,
+        ["anyhow", "kumeo-runtime", "regex", "serde", "serde_json", "serde_yaml", "tokio", "tracing", "tracing-subscriber", "url"],
+        "{}",
+        manifest
+    );
+    assert!(manifest.contains(r#"kumeo-runtime = { path = "kumeo-runtime" }"#), "{}", manifest);
+    assert!(manifest.ends_with("[dev-dependencies]\ntempfile = \"3\"\n"), "Los tests de las rutas usan tempfile: {}", manifest);
+    Ok(())
+}
+
+#[test]
+fn test_every_agent_gets_a_dockerfile_of_its_language() -> Result<()> {
+    let generated = generate(RIDES)?;
+    let dockerfile = generated.file("agents/eta/Dockerfile");
+    assert!(dockerfile.contains("pip install --user -r requirements.txt"), "{}", dockerfile);
+    assert!(dockerfile.contains("kumeo_agent_eta.agent"), "{}", dockerfile);
+    let requirements = generated.file(&format!("agents/eta/{}", REQUIREMENTS_FILE));
+    assert!(requirements.lines().any(|line| line == "onnxruntime==1.18.1"), "{}", requirements);
+
+    let dockerfile = generated.file("agents/dispatch/Dockerfile");
+    assert!(dockerfile.contains("cargo build --release"), "{}", dockerfile);
+    assert!(!generated.has(&format!("agents/dispatch/{}", REQUIREMENTS_FILE)), "Los agentes en Rust no tienen requirements.txt");
+
+    // El Dockerfile de las plantillas del proyecto tiene prioridad
+    let program = parse(RIDES)?;
+    let workflow = &program.workflows[0];
+    let output = tempdir()?;
+    let dispatch = output.path().join("agents/dispatch");
+    let mut tera = TemplateManager::default().engine()?;
+    tera.add_raw_template("agents/rust/Dockerfile.tera", "FROM scratch # {{ agent_id }}")?;
+    generate_agent(workflow, &workflow.agents[3], output.path(), &tera, None, &mut FsSink)?;
+    assert_eq!(fs::read_to_string(dispatch.join("Dockerfile"))?, "FROM scratch # dispatch");
+    Ok(())
+}
+
+#[test]
+fn test_rust_agents_find_the_runtime_crate_they_depend_on() -> Result<()> {
+    let program = parse(RIDES)?;
+    let workflow = &program.workflows[0];
+    let output = tempdir()?;
+    let tera = TemplateManager::default().engine()?;
+    generate_agent(workflow, &workflow.agents[3], output.path(), &tera, None, &mut FsSink)?;
+
+    // La ruta de la dependencia lleva, desde el agente, al paquete kumeo-runtime
+    let dispatch = output.path().join("agents/dispatch");
+    let manifest: toml::Value = toml::from_str(&fs::read_to_string(dispatch.join("Cargo.toml"))?)?;
+    let path = manifest["dependencies"]["kumeo-runtime"]["path"].as_str().expect("kumeo-runtime sin path");
+    let runtime = dispatch.join(path);
+    let runtime_manifest: toml::Value = toml::from_str(&fs::read_to_string(runtime.join("Cargo.toml"))?)?;
+    assert_eq!(runtime_manifest["package"]["name"].as_str(), Some("kumeo-runtime"));
+    assert!(runtime.join("src/lib.rs").is_file() && runtime.join("proto/runtime.proto").is_file());
+
+    // Se copian todas sus fuentes tal cual, y nada para los agentes en Python
+    for (path, contents) in RUNTIME_SOURCES {
+        assert_eq!(&fs::read_to_string(runtime.join(path))?, contents, "{} no coincide con el runtime", path);
+    }
+    generate_agent(workflow, &workflow.agents[0], output.path(), &tera, None, &mut FsSink)?;
+    assert!(!output.path().join("agents/eta/kumeo-runtime").exists());
+
+    let dockerfile = fs::read_to_string(dispatch.join("Dockerfile"))?;
+    assert!(dockerfile.lines().any(|line| line == "COPY kumeo-runtime/ kumeo-runtime/"), "{}", dockerfile);
+    Ok(())
+}
+
+#[test]
+fn test_pyproject_lists_the_pinned_requirements() -> Result<()> {
+    let pyproject = generate(RIDES)?.file("agents/eta/pyproject.toml");
+    for requirement in ["\"onnxruntime==1.18.1\",", "\"feast[redis]==0.40.1\",", "\"tensorflow==2.16.2\","] {
+        assert!(pyproject.contains(requirement), "Falta {} en:\n{}", requirement, pyproject);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_kafka_workflows_give_agents_a_kafka_client() -> Result<()> {
+    let generated = generate(ORDERS)?;
+
+    // Los agentes en Rust activan el cliente de Kafka del runtime
+    let manifest: toml::Value = toml::from_str(&generated.file("agents/route/Cargo.toml"))?;
+    let runtime = &manifest["dependencies"]["kumeo-runtime"];
+    assert_eq!(runtime["features"].as_array().map(Vec::as_slice), Some(&[toml::Value::from("kafka")][..]), "{}", runtime);
+    let runtime_manifest: toml::Value = toml::from_str(&generated.file("agents/route/kumeo-runtime/Cargo.toml"))?;
+    assert_eq!(runtime_manifest["features"]["kafka"].as_array().map(Vec::len), Some(1));
+    assert!(runtime_manifest["dependencies"]["rdkafka"]["optional"].as_bool().unwrap_or_default());
+    let dockerfile = generated.file("agents/route/Dockerfile");
+    assert!(dockerfile.lines().any(|line| line.trim() == "build-essential \\"), "librdkafka se compila: {}", dockerfile);
+
+    // Los agentes en Python consumen y producen con aiokafka
+    let requirements = generated.file(&format!("agents/score/{}", REQUIREMENTS_FILE));
+    assert!(requirements.lines().any(|line| line == "aiokafka==0.11.0"), "{}", requirements);
+    let kafka = generated.file("agents/score/src/kumeo_agent_score/kafka.py");
+    let agent = generated.file("agents/score/src/kumeo_agent_score/agent.py");
+    if let Some(imports) = python_local_imports("kafka.py", &kafka)? {
+        assert!(imports.is_empty(), "{:?}", imports);
+        let imports = python_local_imports("agent.py", &agent)?.unwrap_or_default();
+        assert!(imports.contains(&"kafka.consume".to_string()) && imports.contains(&"kafka.start_producer".to_string()), "{:?}", imports);
+    }
+
+    // Sin Kafka no hay cliente ni módulo
+    let generated = generate(RIDES)?;
+    let manifest: toml::Value = toml::from_str(&generated.file("agents/dispatch/Cargo.toml"))?;
+    assert!(manifest["dependencies"]["kumeo-runtime"].get("features").is_none());
+    assert!(!generated.file("agents/dispatch/Dockerfile").contains("build-essential"));
+    assert!(!generated.file(&format!("agents/eta/{}", REQUIREMENTS_FILE)).contains("aiokafka"));
+    assert!(!generated.has("agents/eta/src/kumeo_agent_eta/kafka.py"));
+    if let Some(imports) = python_local_imports("agent.py", &generated.file("agents/eta/src/kumeo_agent_eta/agent.py"))? {
+        assert!(!imports.iter().any(|import| import.starts_with("kafka.")), "{:?}", imports);
+    }
+    Ok(())
+}
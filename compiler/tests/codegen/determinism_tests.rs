@@ -0,0 +1,148 @@
+use super::{generate_with, GenerateOptions};
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::Workflow,
+    codegen::{agent::generate_agent, sink::FsSink, taskfile::generate_taskfiles, template_manager::TemplateManager},
+    parser::parse,
+};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+use tera::Tera;
+
+const PROGRAM: &str = r#"
+workflow Orders {
+    source: HTTP("/orders", { method: "POST", host: "orders.example.com", port: "8088" });
+    target: NATS("orders.scored");
+    agents: [
+        LLM(id: "classifier", model: "llama3", options: { temperature: 0.2, top_p: 0.9, seed: 7, stop: "END" }),
+        MLModel(id: "scorer", model: "score.onnx", input_schema: { amount: "number", currency: "string", country: "string", user: "string" }),
+        DataProcessor(id: "enricher"),
+        Router(id: "router"),
+        DecisionMatrix(id: "policy"),
+        HumanReview(id: "approval", delegation: { finance: ["alice"], legal: ["bob"], ops: ["carol"] })
+    ];
+}
+"#;
+
+/// Templates dumping their whole context, so any unordered value shows up in the output
+fn context_dumps() -> Result<Tera> {
+    let mut tera = TemplateManager::default().engine()?;
+    for name in ["Taskfile.yml.tera", "tasks/tasks.yml.tera", "agents/README.md.tera", "agents/rust/Dockerfile.tera", "agents/python/Dockerfile.tera"] {
+        tera.add_raw_template(name, "{{ __tera_context }}")?;
+    }
+    Ok(tera)
+}
+
+/// Contents of every file generated for a workflow, by path relative to the output
+fn generate(workflow: &Workflow, tera: &Tera) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+    let output_dir = tempdir()?;
+    generate_taskfiles(workflow, output_dir.path(), tera, &mut FsSink)?;
+    for agent in &workflow.agents {
+        generate_agent(workflow, agent, output_dir.path(), tera, None, &mut FsSink)?;
+    }
+
+    let mut files = BTreeMap::new();
+    let mut pending = vec![output_dir.path().to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                let relative = path.strip_prefix(output_dir.path())?.to_path_buf();
+                files.insert(relative, fs::read(&path)?);
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[test]
+fn test_generation_is_byte_identical_across_runs() -> Result<()> {
+    let workflow = parse(PROGRAM)?.workflows.remove(0);
+    let tera = context_dumps()?;
+
+    let first = generate(&workflow, &tera)?;
+    assert!(first.contains_key(Path::new("Taskfile.yml")), "Debería generar el Taskfile: {:?}", first.keys());
+    assert!(first.contains_key(Path::new("agents/approval/README.md")), "Debería generar los agentes: {:?}", first.keys());
+
+    // Every map gets its own hash seed, so a few runs are enough to reorder an unordered one
+    for _ in 0..8 {
+        let again = generate(&workflow, &tera)?;
+        assert_eq!(again.keys().collect::<Vec<_>>(), first.keys().collect::<Vec<_>>(), "Deberían generarse los mismos archivos");
+        for (path, contents) in &first {
+            assert!(
+                again[path] == *contents,
+                "{} cambió entre generaciones:\n{}\n---\n{}",
+                path.display(),
+                String::from_utf8_lossy(contents),
+                String::from_utf8_lossy(&again[path])
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_whole_program_generation_is_byte_identical_across_runs() -> Result<()> {
+    // Two workflows, so the cluster layout and the console are generated too
+    let program = format!(
+        r#"{}
+        workflow Refunds {{
+            source: NATS("refunds");
+            agents: [ Router(id: "triage"), DataProcessor(id: "ledger") ];
+        }}"#,
+        PROGRAM
+    );
+    let options = GenerateOptions { console: true, ..Default::default() };
+
+    let first = generate_with(&program, &options)?;
+    assert!(first.has("programs/main/orders/agents/approval"), "Debería generar Orders: {:?}", first.files.keys());
+    assert!(first.has("console"), "Debería generar la consola: {:?}", first.files.keys());
+    for _ in 0..8 {
+        let again = generate_with(&program, &options)?;
+        assert_eq!(again.files.keys().collect::<Vec<_>>(), first.files.keys().collect::<Vec<_>>(), "Deberían generarse los mismos archivos");
+        for (path, contents) in &first.files {
+            assert!(
+                again.files[path] == *contents,
+                "{} cambió entre generaciones:\n{}\n---\n{}",
+                path.display(),
+                String::from_utf8_lossy(contents),
+                String::from_utf8_lossy(&again.files[path])
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_generation_keeps_no_state_between_workflows() -> Result<()> {
+    let orders = parse(PROGRAM)?.workflows.remove(0);
+    let refunds = parse(r#"workflow Refunds {
+        source: NATS("refunds");
+        agents: [ Router(id: "triage"), DataProcessor(id: "ledger") ];
+    }"#)?
+    .workflows
+    .remove(0);
+    let tera = context_dumps()?;
+    let alone = generate(&refunds, &context_dumps()?)?;
+
+    // One engine generates any number of workflows without carrying agents over
+    generate(&orders, &tera)?;
+    let after_orders = generate(&refunds, &tera)?;
+    assert!(after_orders == alone, "Generar otro workflow antes no debería cambiar la salida");
+    let taskfile = String::from_utf8_lossy(&after_orders[Path::new("Taskfile.yml")]).into_owned();
+    assert!(!taskfile.contains("classifier"), "Los agentes de Orders no deberían aparecer en Refunds: {}", taskfile);
+
+    // and can be shared by workflows generated in parallel
+    let (orders_output, refunds_output) = std::thread::scope(|scope| {
+        let orders_output = scope.spawn(|| generate(&orders, &tera));
+        let refunds_output = scope.spawn(|| generate(&refunds, &tera));
+        (orders_output.join().expect("hilo de Orders"), refunds_output.join().expect("hilo de Refunds"))
+    });
+    assert!(orders_output? == generate(&orders, &context_dumps()?)?, "La generación en paralelo debería dar la misma salida");
+    assert!(refunds_output? == alone, "La generación en paralelo debería dar la misma salida");
+    Ok(())
+}
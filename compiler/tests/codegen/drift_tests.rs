@@ -0,0 +1,60 @@
+use super::generate;
+use anyhow::Result;
+use kumeo_compiler::{ast::Workflow, codegen::drift::workflow_hash, parser::parse};
+use serde::Deserialize;
+
+fn workflow(source: &str) -> Result<Workflow> {
+    Ok(parse(source)?.workflows.remove(0))
+}
+
+#[test]
+fn test_workflow_hash_follows_the_definition() -> Result<()> {
+    let original = workflow(
+        r#"workflow Scoring {
+            source: NATS("in");
+            agents: [LLM(id: "scorer", model: "gpt-4", options: { temperature: 0.2, max_tokens: 100 })];
+        }"#,
+    )?;
+    let reformatted = workflow(
+        r#"workflow Scoring { source: NATS("in"); agents: [LLM(id: "scorer", model: "gpt-4", options: { max_tokens: 100, temperature: 0.2 })]; }"#,
+    )?;
+    let changed = workflow(
+        r#"workflow Scoring {
+            source: NATS("in");
+            agents: [LLM(id: "scorer", model: "gpt-4o", options: { temperature: 0.2, max_tokens: 100 })];
+        }"#,
+    )?;
+
+    let hash = workflow_hash(&original)?;
+    assert_eq!(hash.len(), 64);
+    assert_eq!(hash, workflow_hash(&reformatted)?);
+    assert_ne!(hash, workflow_hash(&changed)?);
+    Ok(())
+}
+
+#[test]
+fn test_deployment_carries_the_workflow_hash() -> Result<()> {
+    let source = r#"workflow Scoring {
+        source: NATS("in");
+        agents: [LLM(id: "scorer", model: "gpt-4")];
+    }"#;
+    let hash = workflow_hash(&workflow(source)?)?;
+
+    let manifests = generate(source)?.file("agents/scorer/kubernetes/deployment.yaml");
+    let deployment = serde_yaml::Deserializer::from_str(&manifests)
+        .map(serde_yaml::Value::deserialize)
+        .find(|document| matches!(document, Ok(document) if document["kind"].as_str() == Some("Deployment")))
+        .expect("Debería generar el Deployment")?;
+
+    let template = &deployment["spec"]["template"];
+    assert_eq!(template["metadata"]["annotations"]["kumeo.io/workflow-hash"].as_str(), Some(hash.as_str()));
+    let env = template["spec"]["containers"][0]["env"].as_sequence().expect("env");
+    let var = |name: &str| {
+        env.iter()
+            .find(|var| var["name"].as_str() == Some(name))
+            .and_then(|var| var["value"].as_str())
+    };
+    assert_eq!(var("KUMEO_WORKFLOW"), Some("Scoring"));
+    assert_eq!(var("KUMEO_WORKFLOW_HASH"), Some(hash.as_str()));
+    Ok(())
+}
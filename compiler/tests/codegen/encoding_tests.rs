@@ -0,0 +1,151 @@
+use super::generate;
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::Encoding,
+    codegen::{
+        contracts::attach,
+        encoding::{generate_schemas, message_name, EncodingSettings},
+        sink::FsSink,
+    },
+    parser::parse,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use tempfile::tempdir;
+
+const SCORING: &str = r#"
+schemas: { "orders": { id: "string", amount: "number", tags: "array?" } };
+
+workflow Scoring {
+    source: NATS("orders", { encoding: "avro" });
+    target: NATS("orders.scored", { encoding: "protobuf" });
+    agents: [
+        DataProcessor(id: "clean", output: "orders.clean", encoding: "msgpack"),
+        MLModel(id: "score", model_path: "models/score.onnx", input: "orders.clean",
+            output_schema: { id: "string", score: "number", risky: "boolean", rank: "integer?" })
+    ];
+}
+"#;
+
+#[test]
+fn test_agents_get_the_encodings_of_their_topics() -> Result<()> {
+    let program = parse(SCORING)?;
+    let workflow = attach(&program.workflows[0], &program.topic_schemas());
+
+    let clean = EncodingSettings::for_agent(&workflow, &workflow.agents[0])?.expect("Debería codificar");
+    let input = clean.input.expect("Debería decodificar la fuente");
+    assert_eq!((input.topic.as_str(), input.encoding, input.content_type), ("orders", Encoding::Avro, "application/avro"));
+    assert_eq!(input.schema.as_deref(), Some(r#"{"amount":"number","id":"string","tags":"array?"}"#));
+    let output = clean.output.expect("Debería codificar su salida");
+    assert_eq!((output.encoding, output.schema), (Encoding::Msgpack, None));
+
+    // The agent writing the target publishes in the target's encoding
+    let score = EncodingSettings::for_agent(&workflow, &workflow.agents[1])?.expect("Debería codificar");
+    assert_eq!(score.input.map(|input| input.encoding), Some(Encoding::Msgpack));
+    assert_eq!(score.output.map(|output| output.encoding), Some(Encoding::Protobuf));
+
+    let plain = parse(r#"workflow Plain { source: NATS("in"); target: NATS("out"); agents: [Router(id: "route")]; }"#)?;
+    assert_eq!(EncodingSettings::for_agent(&plain.workflows[0], &plain.workflows[0].agents[0])?, None);
+    assert_eq!(message_name("orders.scored"), "OrdersScored");
+    assert_eq!(message_name("2fa-codes"), "Topic2faCodes");
+    Ok(())
+}
+
+/// Field of a Protobuf message: its label, if any, type, name and number
+type ProtoField = (Option<String>, String, String, u32);
+
+/// The package and messages of a Protobuf definition, failing the test on what it can't read
+fn read_proto(proto: &str) -> (String, BTreeMap<String, Vec<ProtoField>>) {
+    let code: String = proto.lines().map(|line| line.split("//").next().unwrap_or_default()).collect::<Vec<_>>().join("\n");
+    let code = code.replace('{', " { ").replace('}', " } ").replace(';', " ; ").replace('=', " = ");
+    let mut words = code.split_whitespace();
+    let (mut package, mut messages) = (String::new(), BTreeMap::new());
+    let statement = |words: &mut std::str::SplitWhitespace| -> Vec<String> {
+        words.by_ref().take_while(|word| *word != ";").map(str::to_string).collect()
+    };
+    while let Some(word) = words.next() {
+        match word {
+            "syntax" => assert_eq!(statement(&mut words), ["=", "\"proto3\""]),
+            "package" => package = statement(&mut words).concat(),
+            "message" => {
+                let name = words.next().expect("nombre del mensaje").to_string();
+                assert_eq!(words.next(), Some("{"), "{}", proto);
+                let mut fields = Vec::new();
+                loop {
+                    let mut field = statement(&mut words);
+                    if field.first().map(String::as_str) == Some("}") {
+                        field.remove(0);
+                        assert!(field.is_empty(), "{}", proto);
+                        break;
+                    }
+                    let label = (field[0] == "optional" || field[0] == "repeated").then(|| field.remove(0));
+                    match field.as_slice() {
+                        [field_type, name, equals, number] if equals == "=" => {
+                            fields.push((label, field_type.clone(), name.clone(), number.parse().expect("número de campo")));
+                        }
+                        _ => panic!("Campo inválido {:?} en {}", field, proto),
+                    }
+                }
+                messages.insert(name, fields);
+            }
+            _ => panic!("Declaración inesperada {} en {}", word, proto),
+        }
+    }
+    (package, messages)
+}
+
+#[test]
+fn test_schemas_are_generated_for_protobuf_and_avro_topics() -> Result<()> {
+    let program = parse(SCORING)?;
+    let workflow = attach(&program.workflows[0], &program.topic_schemas());
+    let output = tempdir()?;
+    generate_schemas(&workflow, output.path(), &mut FsSink)?;
+
+    let proto = fs::read_to_string(output.path().join("schemas/orders.scored.proto"))?;
+    let (package, messages) = read_proto(&proto);
+    assert_eq!(package, "kumeo.scoring");
+    // Fields are numbered in name order
+    let field = |label: Option<&str>, field_type: &str, name: &str, number| (label.map(str::to_string), field_type.to_string(), name.to_string(), number);
+    let fields = vec![
+        field(None, "string", "id", 1),
+        field(Some("optional"), "sint64", "rank", 2),
+        field(None, "bool", "risky", 3),
+        field(None, "double", "score", 4),
+    ];
+    assert_eq!(messages, BTreeMap::from([("OrdersScored".to_string(), fields)]));
+
+    let avsc: serde_json::Value = serde_json::from_str(&fs::read_to_string(output.path().join("schemas/orders.avsc"))?)?;
+    assert_eq!(avsc["name"], "Orders");
+    assert_eq!(avsc["namespace"], "kumeo.scoring");
+    assert_eq!(avsc["fields"][0], serde_json::json!({ "name": "amount", "type": "double" }));
+    assert_eq!(avsc["fields"][2], serde_json::json!({ "name": "tags", "type": ["null", "string"], "default": null }));
+
+    // MessagePack needs no schema
+    assert!(!output.path().join("schemas/orders.clean.proto").exists());
+    assert!(!output.path().join("schemas/orders.clean.avsc").exists());
+    Ok(())
+}
+
+#[test]
+fn test_deployment_tells_the_runtime_the_encodings() -> Result<()> {
+    let manifests = generate(SCORING)?.file("agents/clean/kubernetes/deployment.yaml");
+    let deployment = serde_yaml::Deserializer::from_str(&manifests)
+        .map(serde_yaml::Value::deserialize)
+        .find(|document| matches!(document, Ok(document) if document["kind"].as_str() == Some("Deployment")))
+        .expect("Debería generar el Deployment")?;
+
+    let env = deployment["spec"]["template"]["spec"]["containers"][0]["env"].as_sequence().expect("env");
+    let var = |name: &str| {
+        env.iter()
+            .find(|var| var["name"].as_str() == Some(name))
+            .and_then(|var| var["value"].as_str())
+    };
+    assert_eq!(var("KUMEO_INPUT_ENCODING"), Some("avro"));
+    assert_eq!(var("KUMEO_INPUT_ENCODING_TOPIC"), Some("orders"));
+    assert_eq!(var("KUMEO_INPUT_SCHEMA"), Some(r#"{"amount":"number","id":"string","tags":"array?"}"#));
+    assert_eq!(var("KUMEO_OUTPUT_ENCODING"), Some("msgpack"));
+    assert_eq!(var("KUMEO_OUTPUT_ENCODING_TOPIC"), Some("orders.clean"));
+    assert_eq!(var("KUMEO_OUTPUT_SCHEMA"), None);
+    Ok(())
+}
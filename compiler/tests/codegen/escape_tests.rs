@@ -0,0 +1,142 @@
+use super::{generate, generate_workflows, rust_str, rust_strings, GenerateOptions};
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::{Argument, Value},
+    codegen::escape::{py_str_literal, register_filters, rust_str_literal},
+    parser::parse,
+};
+use serde::Deserialize;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tera::{Context, Tera};
+
+/// Texto con todo lo que un escapado HTML o una cita ingenua estropean
+const TRICKY: &str = "say \"hi\" & <bye>\\n\nnext: 'line' # {not a comment}";
+
+/// Texto con comillas, barras, saltos de línea y caracteres fuera de ASCII
+const UNICODE_TRICKY: &str = "dice \"hola\" y 'adiós' \\\n\\\" ''' \"\"\"\nsegunda línea: café 日本語 🚀 \u{7f}";
+
+/// Replace every `"@"` string of an option by [`UNICODE_TRICKY`], which the DSL can't write
+fn replace_placeholders(value: &mut Value) {
+    match value {
+        Value::String(text) if text == "@" => *text = UNICODE_TRICKY.to_string(),
+        Value::Array(items) => items.iter_mut().for_each(replace_placeholders),
+        Value::Object(fields) => fields.values_mut().for_each(replace_placeholders),
+        _ => {}
+    }
+}
+
+/// Check a generated Python file with the interpreter's own parser
+fn assert_python_parses(path: &str, source: &str) -> Result<()> {
+    let mut python = Command::new("python3")
+        .args(["-c", "import ast, sys; ast.parse(sys.stdin.read())"])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    python.stdin.take().expect("stdin").write_all(source.as_bytes())?;
+    let output = python.wait_with_output()?;
+    assert!(output.status.success(), "{} no es Python válido: {}", path, String::from_utf8_lossy(&output.stderr));
+    Ok(())
+}
+
+#[test]
+fn test_filters_quote_for_each_language() -> Result<()> {
+    let mut tera = Tera::default();
+    register_filters(&mut tera);
+    let mut context = Context::new();
+    context.insert("text", TRICKY);
+    context.insert("port", &8080);
+
+    let yaml = tera.render_str("value: {{ text | yaml_quote }}\nport: {{ port | yaml_quote }}\n", &context)?;
+    let document: serde_yaml::Value = serde_yaml::from_str(&yaml)?;
+    assert_eq!(document["value"].as_str(), Some(TRICKY), "El YAML debería devolver el texto tal cual: {}", yaml);
+    assert_eq!(document["port"].as_str(), Some("8080"), "Los números se citan como texto: {}", yaml);
+
+    let rust = tera.render_str("{{ text | rust_str }}", &context)?;
+    assert_eq!(rust, rust_str_literal(TRICKY));
+    assert_eq!(rust, format!("{:?}", TRICKY));
+
+    // Un literal de Python sin prefijo se lee como una cadena JSON
+    let python = tera.render_str("{{ text | py_str }}", &context)?;
+    assert_eq!(python, py_str_literal(TRICKY));
+    assert_eq!(serde_json::from_str::<String>(&python)?, TRICKY);
+
+    context.insert("list", &["a", "b"]);
+    assert!(tera.render_str("{{ list | yaml_quote }}", &context).is_err(), "Solo se citan valores escalares");
+    Ok(())
+}
+
+#[test]
+fn test_generated_files_keep_special_characters() -> Result<()> {
+    let generated = generate(
+        r#"workflow Support {
+            source: HTTP("/hooks/in?a=1&b=<2> #x");
+            agents: [ LLM(id: "writer", model: "gpt-4") ];
+            deployment: { env: { GREETING: "it's <bye> & see: you \\ # soon" } };
+        }"#,
+    )?;
+
+    // Sin autoescapado HTML los valores llegan enteros al manifiesto
+    let rendered = generated.file("agents/writer/kubernetes/deployment.yaml");
+    assert!(!rendered.contains("&quot;") && !rendered.contains("&lt;"), "{}", rendered);
+    let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(&rendered)
+        .map(serde_yaml::Value::deserialize)
+        .collect::<Result<_, _>>()?;
+    let env = documents[0]["spec"]["template"]["spec"]["containers"][0]["env"].as_sequence().expect("env");
+    let value = |name: &str| env.iter().find(|var| var["name"].as_str() == Some(name)).and_then(|var| var["value"].as_str());
+    assert_eq!(value("GREETING"), Some(r"it's <bye> & see: you \\ # soon"));
+    assert_eq!(value("KUMEO_WEBHOOK_PATH"), Some("/hooks/in?a=1&b=<2> #x"));
+
+    // Los ids del lenguaje no llevan comillas, pero el AST sí las admite
+    let mut program = parse(r#"workflow Support { agents: [ LLM(id: "writer", model: "gpt-4") ]; }"#)?;
+    program.workflows[0].agents[0].id = Some("writer \"v2\"".to_string());
+    let generated = generate_workflows(&program.workflows, &GenerateOptions::default())?;
+    let metrics = generated.rust(r#"agents/writer "v2"/src/metrics.rs"#);
+    assert_eq!(rust_str(&metrics, "AGENT"), "writer \"v2\"");
+    Ok(())
+}
+
+#[test]
+fn test_generated_code_parses_with_special_characters() -> Result<()> {
+    let mut program = parse(
+        r#"workflow Support {
+            source: NATS("in");
+            agents: [
+                LLM(id: "writer", model: "@", fallback: { action: "dead_letter", subject: "@" }),
+                MLModel(id: "score", model_path: "models/score.onnx", fallback: { action: "dead_letter", subject: "@" })
+            ];
+        }"#,
+    )?;
+    for argument in program.workflows[0].agents.iter_mut().flat_map(|agent| &mut agent.config) {
+        match argument {
+            Argument::Named(_, value, _) | Argument::Positional(value, _) => replace_placeholders(value),
+        }
+    }
+    let generated = generate_workflows(&program.workflows, &GenerateOptions::default())?;
+    let paths = |extension: &str| -> Vec<String> {
+        let paths = generated.files.keys().filter(|path| path.extension().is_some_and(|ext| ext == extension));
+        paths.map(|path| path.to_string_lossy().into_owned()).collect()
+    };
+
+    let rust = paths("rs");
+    assert!(rust.iter().any(|path| path.starts_with("agents/writer/")), "{:?}", rust);
+    for path in &rust {
+        generated.rust(path);
+    }
+    // The literals read back as the text of the options
+    assert!(rust_strings(&generated.rust("agents/writer/src/config.rs")).iter().any(|string| string == UNICODE_TRICKY));
+    assert!(rust_strings(&generated.rust("agents/writer/src/resilience.rs")).iter().any(|string| string == UNICODE_TRICKY));
+
+    // Sin intérprete no hay con qué comprobar los agentes de Python
+    if Command::new("python3").arg("--version").output().is_err() {
+        return Ok(());
+    }
+    let python = paths("py");
+    let agent = python.iter().find(|path| path.starts_with("agents/score/") && path.ends_with("agent.py")).expect("agent.py");
+    let constants = generated.python_constants(agent).expect("python3");
+    assert_eq!(constants["_DEAD_LETTER_SUBJECT"], serde_json::json!(UNICODE_TRICKY));
+    for path in &python {
+        assert_python_parses(path, &generated.file(path))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use super::{generate, rust_item, rust_str, rust_strings, rust_tokens};
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::{FailoverTrigger, ProviderChain},
+    codegen::failover::FailoverSettings,
+    codegen::inference::InferenceServer,
+    formatter::format,
+    parser::parse,
+};
+
+const SUPPORT: &str = r#"
+workflow Support {
+    source: NATS("tickets");
+    agents: [
+        LLM(
+            id: "reply",
+            model: "llama3",
+            providers: [
+                OpenAI(gpt-4o, timeout: "10s"),
+                Anthropic("claude-3-5-sonnet", on: [rate_limit, error]),
+                Ollama(llama3.1, endpoint: "http://ollama:11434/v1"),
+                VLLM(model: "meta-llama/Llama-3.1-8B-Instruct")
+            ]
+        ),
+        LLM(id: "triage", model: "meta-llama/Llama-3.1-8B-Instruct", provider: VLLM())
+    ];
+}
+"#;
+
+#[test]
+fn test_provider_chains_are_read_in_order() -> Result<()> {
+    let program = parse(SUPPORT)?;
+    let value = program.workflows[0].agents[0].config_value("providers").expect("Se esperaba una cadena de proveedores");
+    let chain = ProviderChain::from_value(value).map_err(anyhow::Error::msg)?;
+
+    let providers: Vec<&str> = chain.providers.iter().map(|provider| provider.provider.as_str()).collect();
+    assert_eq!(providers, vec!["openai", "anthropic", "ollama", "vllm"]);
+    assert_eq!(chain.providers[0].model.as_deref(), Some("gpt-4o"));
+    assert_eq!(chain.providers[0].timeout_secs, Some(10));
+    assert_eq!(chain.providers[0].on, FailoverTrigger::DEFAULT.to_vec());
+    assert_eq!(chain.providers[1].on, vec![FailoverTrigger::RateLimit, FailoverTrigger::Error]);
+    assert_eq!(chain.providers[2].model.as_deref(), Some("llama3.1"));
+    assert!(chain.providers[3].server.is_some(), "VLLM debería desplegar un servidor");
+
+    let program = parse(r#"workflow A { agents: [LLM(id: "a", model: "m", providers: [OpenAI(gpt-4o, on: [crash])])]; }"#)?;
+    let error = ProviderChain::from_value(program.workflows[0].agents[0].config_value("providers").unwrap()).unwrap_err();
+    assert!(error.to_string().contains("unknown failover trigger 'crash'"), "{}", error);
+    Ok(())
+}
+
+#[test]
+fn test_failover_settings_list_every_provider() -> Result<()> {
+    let program = parse(SUPPORT)?;
+    let workflow = &program.workflows[0];
+
+    let failover = FailoverSettings::for_agent(&workflow.agents[0])?.expect("Se esperaba una cadena de proveedores");
+    assert_eq!(failover.providers[0].base_url, None, "Los proveedores alojados usan su propia API");
+    assert_eq!(failover.providers[2].base_url.as_deref(), Some("http://ollama:11434/v1"));
+    assert_eq!(failover.providers[3].model, "meta-llama/Llama-3.1-8B-Instruct");
+    assert_eq!(failover.providers[3].base_url.as_deref(), Some("http://vllm-meta-llama-llama-3-1-8b-instruct:8000"));
+
+    let json: serde_json::Value = serde_json::from_str(&failover.json)?;
+    assert_eq!(json[0]["provider"], "openai");
+    assert_eq!(json[0]["timeout_secs"], 10);
+    assert_eq!(json[1]["on"], serde_json::json!(["rate_limit", "error"]));
+    assert_eq!(FailoverSettings::for_agent(&workflow.agents[1])?, None);
+
+    let servers = InferenceServer::for_workflow(workflow)?;
+    assert_eq!(servers.len(), 1, "La cadena debería compartir el servidor del mismo modelo");
+    assert_eq!(servers[0].agents, vec!["reply", "triage"]);
+    Ok(())
+}
+
+#[test]
+fn test_llm_agents_read_their_provider_chain() -> Result<()> {
+    let generated = generate(SUPPORT)?;
+    let config = generated.rust("agents/reply/src/config.rs");
+    let chain: serde_json::Value = serde_json::from_str(&rust_str(&config, "PROVIDER_CHAIN"))?;
+    let providers: Vec<_> = chain.as_array().expect("Se esperaba una lista").iter().map(|provider| provider["provider"].clone()).collect();
+    assert_eq!(providers, ["openai", "anthropic", "ollama", "vllm"]);
+    assert_eq!(chain[0]["model"], "gpt-4o");
+    let metrics = generated.rust("agents/reply/src/metrics.rs");
+    let strings = rust_strings(&metrics);
+    assert!(strings.iter().any(|string| string.starts_with(r#"kumeo_llm_requests_total{{agent="{}",provider"#)), "{:?}", strings);
+    assert_eq!(rust_item(&metrics, "DEFAULT_PORT"), rust_tokens("9090"));
+
+    let config = generated.rust("agents/triage/src/config.rs");
+    assert_eq!(rust_str(&config, "PROVIDER_CHAIN"), "[]", "Sin cadena no hay proveedores de respaldo");
+    Ok(())
+}
+
+#[test]
+fn test_provider_chains_keep_their_format() -> Result<()> {
+    let source = "workflow Support {\n    agents: [\n        LLM(id: \"reply\", model: \"llama3\", providers: [OpenAI(gpt-4o), Ollama(llama3)])\n    ];\n}\n";
+    assert_eq!(format(source)?, source);
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use super::generate;
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::{FeatureStoreConfig, Value},
+    codegen::feature_store::FeatureStoreSettings,
+    parser::parse,
+};
+use serde_json::json;
+
+const RIDES: &str = r#"
+workflow Rides {
+    source: NATS("rides");
+    agents: [
+        MLModel(
+            id: "eta",
+            model_path: "models/eta.onnx",
+            features: Feast("driver_stats", keys: ["driver_id"], features: ["conv_rate", "avg_daily_trips"], online_store: "redis:6379")
+        ),
+        MLModel(id: "price", model_path: "models/price.onnx")
+    ];
+}
+"#;
+
+#[test]
+fn test_feast_lookups_are_read_with_their_feature_view() -> Result<()> {
+    let program = parse(RIDES)?;
+    let value = program.workflows[0].agents[0].config_value("features").expect("Se esperaba la opción features");
+    let Value::Tagged(name, options) = value else {
+        panic!("Se esperaba un objeto Feast, no {}", value);
+    };
+    assert_eq!(name, "Feast");
+    assert_eq!(options.get("feature_view"), Some(&Value::String("driver_stats".to_string())));
+
+    let config = FeatureStoreConfig::from_value(value).map_err(anyhow::Error::msg)?;
+    assert_eq!(config.keys, vec!["driver_id"]);
+    assert_eq!(config.features, vec!["conv_rate", "avg_daily_trips"]);
+    assert_eq!(config.online_store.as_deref(), Some("redis:6379"));
+    assert_eq!(config.registry, None);
+    Ok(())
+}
+
+#[test]
+fn test_ml_agents_fetch_online_features() -> Result<()> {
+    let program = parse(RIDES)?;
+    let workflow = &program.workflows[0];
+
+    let features = FeatureStoreSettings::for_agent(workflow, &workflow.agents[0])?.expect("Se esperaba un feature store");
+    assert_eq!(features.feature_view, "driver_stats");
+    assert_eq!(features.project, "rides", "El proyecto por defecto es el workflow");
+    assert_eq!(features.python_keys, r#"["driver_id"]"#);
+    assert_eq!(features.python_registry, r#""data/registry.db""#);
+    assert_eq!(features.python_online_store, r#""redis:6379""#);
+    assert_eq!(FeatureStoreSettings::for_agent(workflow, &workflow.agents[1])?, None);
+
+    let generated = generate(RIDES)?;
+    // Without an interpreter the agents can't be read
+    if let Some(constants) = generated.python_constants("agents/eta/src/kumeo_agent_eta/agent.py") {
+        assert_eq!(constants["_FEATURE_VIEW"], json!("driver_stats"));
+        assert_eq!(constants["_FEATURE_KEYS"], json!(["driver_id"]));
+        assert_eq!(constants["_FEATURE_NAMES"], json!(["conv_rate", "avg_daily_trips"]));
+        assert_eq!(constants["_FEAST_REDIS_URL"], json!("os.environ.get('FEAST_REDIS_URL', 'redis:6379')"));
+    }
+    if let Some(constants) = generated.python_constants("agents/price/src/kumeo_agent_price/agent.py") {
+        assert_eq!(constants["_FEATURE_VIEW"], json!(null), "Sin feature store no debería conectarse a Feast");
+    }
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use super::{generate, rust_calls, rust_item, rust_str, rust_tokens};
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::{GuardrailAction, GuardrailCheck, GuardrailsConfig},
+    codegen::guardrails::GuardrailSettings,
+    parser::parse,
+};
+
+const SUPPORT: &str = r#"
+workflow Support {
+    source: NATS("tickets");
+    agents: [
+        LLM(
+            id: "reply",
+            model: "llama3",
+            guardrails: {
+                input: [pii_redact, prompt_injection],
+                output: [toxicity(threshold: 0.7, action: review), regex(name: "secrets", pattern: "sk-[A-Za-z0-9]{20,}")],
+                review: "moderator"
+            }
+        ),
+        HumanReview(id: "moderator", input: "reviews")
+    ];
+}
+"#;
+
+#[test]
+fn test_guardrails_are_read_per_stage() -> Result<()> {
+    let program = parse(SUPPORT)?;
+    let value = program.workflows[0].agents[0].config_value("guardrails").expect("Se esperaban guardrails");
+    let config = GuardrailsConfig::from_value(value).map_err(anyhow::Error::msg)?;
+
+    assert_eq!(config.input.len(), 2);
+    assert_eq!(config.input[0].name, "pii_redact");
+    assert_eq!(config.input[0].check, GuardrailCheck::Pii);
+    assert_eq!(config.input[0].action, GuardrailAction::Redact);
+    assert_eq!(config.input[1].action, GuardrailAction::Block, "Las reglas bloquean por defecto");
+    assert_eq!(config.output[0].check, GuardrailCheck::Toxicity { threshold: 0.7, endpoint: None });
+    assert_eq!(config.output[0].action, GuardrailAction::Review);
+    assert_eq!(config.output[1].name, "secrets");
+    assert_eq!(config.review.as_deref(), Some("moderator"));
+
+    let invalid = [
+        (r#"{ input: [profanity] }"#, "unknown guardrail 'profanity'"),
+        (r#"{ output: [toxicity(action: redact)] }"#, "toxicity can't redact"),
+        (r#"{ output: [toxicity(action: review)] }"#, "needs the HumanReview agent under review"),
+        (r#"{ input: [regex(pattern: "(unclosed")] }"#, "invalid pattern of regex"),
+        (r#"{ review: "moderator" }"#, "at least one input or output rule"),
+    ];
+    for (guardrails, expected) in invalid {
+        let source = format!(r#"workflow A {{ agents: [LLM(id: "a", model: "m", guardrails: {})]; }}"#, guardrails);
+        let program = parse(&source)?;
+        let error = GuardrailsConfig::from_value(program.workflows[0].agents[0].config_value("guardrails").unwrap()).unwrap_err();
+        assert!(error.to_string().contains(expected), "{}: {}", guardrails, error);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_guardrail_settings_send_reviews_to_the_reviewer() -> Result<()> {
+    let program = parse(SUPPORT)?;
+    let workflow = &program.workflows[0];
+
+    let guardrails = GuardrailSettings::for_agent(workflow, &workflow.agents[0])?.expect("Se esperaban guardrails");
+    assert_eq!(guardrails.review_subject.as_deref(), Some("reviews"));
+    assert_eq!(guardrails.output[0].action, "review");
+
+    // The literal is a Rust string whose contents are the rules as JSON
+    let json: String = serde_json::from_str(&guardrails.rules_literal)?;
+    let rules: serde_json::Value = serde_json::from_str(&json)?;
+    assert_eq!(rules["input"][0], serde_json::json!({ "name": "pii_redact", "check": "pii", "action": "redact" }));
+    assert_eq!(rules["output"][0]["threshold"], 0.7);
+    assert_eq!(rules["output"][1]["pattern"], "sk-[A-Za-z0-9]{20,}");
+    assert_eq!(GuardrailSettings::for_agent(workflow, &workflow.agents[1])?, None);
+    Ok(())
+}
+
+#[test]
+fn test_llm_agents_check_their_guardrails() -> Result<()> {
+    let generated = generate(SUPPORT)?;
+    let guardrails = generated.rust("agents/reply/src/guardrails.rs");
+    let rules: serde_json::Value = serde_json::from_str(&rust_str(&guardrails, "RULES"))?;
+    assert_eq!(rules["input"][0]["action"], "redact", "{}", rules);
+    assert_eq!(rust_item(&guardrails, "REVIEW_SUBJECT"), rust_tokens(r#"Some("reviews")"#));
+    let calls = rust_calls(&generated.rust("agents/reply/src/agent.rs"));
+    assert!(calls.contains(".check_input") && calls.contains(".check_output"), "{:?}", calls);
+
+    let unguarded = generate(r#"workflow Support { source: NATS("tickets"); agents: [LLM(id: "reply", model: "llama3")]; }"#)?;
+    let calls = rust_calls(&unguarded.rust("agents/reply/src/agent.rs"));
+    assert!(!calls.contains(".check_input") && !calls.contains(".check_output"), "Sin guardrails el agente no los comprueba");
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::{split_image, validate_image},
+    codegen::{
+        images::{generate_image_manifest, ImageManifest, IMAGES_FILE},
+        kubernetes::{DEFAULT_REGISTRY, DEFAULT_TAG},
+        sink::FsSink,
+    },
+    parser::parse,
+};
+use tempfile::tempdir;
+
+const WORKFLOW: &str = r#"workflow Payments {
+    source: NATS("payments");
+    agents: [
+        Router(id: "route"),
+        MLModel(id: "score", model_name: "fraud", image: "registry.internal:5000/risk/scorer"),
+        HumanReview(id: "approval", image: "ghcr.io/acme/approval:pinned", auth: OIDC("https://sso.example.com", "payments-reviews", "roles"))
+    ];
+    deployment: { registry: "ghcr.io/acme", tag: "1.4.0" };
+}"#;
+
+#[test]
+fn test_images_follow_the_deployment_block() -> Result<()> {
+    let program = parse(WORKFLOW)?;
+    let manifest = ImageManifest::for_workflow(&program.workflows[0])?;
+    let images: Vec<(&str, &str, &str)> = manifest
+        .images
+        .iter()
+        .map(|image| (image.name.as_str(), image.image.as_str(), image.context.as_str()))
+        .collect();
+    assert_eq!(
+        images,
+        [
+            ("route", "ghcr.io/acme/route:1.4.0", "agents/route"),
+            // An image without a tag gets the one of the deployment; the registry port isn't a tag
+            ("score", "registry.internal:5000/risk/scorer:1.4.0", "agents/score"),
+            ("approval", "ghcr.io/acme/approval:pinned", "agents/approval"),
+            ("approval-ui", "ghcr.io/acme/approval-ui:1.4.0", "agents/approval/ui"),
+        ]
+    );
+    assert_eq!(manifest.images[0].dockerfile, "agents/route/Dockerfile");
+
+    let program = parse(r#"workflow Clicks { source: NATS("clicks"); agents: [Router(id: "route")]; }"#)?;
+    let manifest = ImageManifest::for_workflow(&program.workflows[0])?;
+    assert_eq!(manifest.images[0].image, format!("{}route:{}", DEFAULT_REGISTRY, DEFAULT_TAG));
+    Ok(())
+}
+
+#[test]
+fn test_image_manifest_is_written_for_ci() -> Result<()> {
+    let program = parse(WORKFLOW)?;
+    let output = tempdir()?;
+    generate_image_manifest(&program.workflows[0], output.path(), &mut FsSink)?;
+    let manifest: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(output.path().join(IMAGES_FILE))?)?;
+    assert_eq!(manifest["workflow"], "Payments");
+    assert_eq!(manifest["images"].as_array().map(Vec::len), Some(4));
+    assert_eq!(manifest["images"][1]["dockerfile"], "agents/score/Dockerfile");
+    Ok(())
+}
+
+#[test]
+fn test_image_references() {
+    assert_eq!(split_image("ghcr.io/acme/scorer:1.2"), ("ghcr.io/acme/scorer", Some("1.2"), None));
+    assert_eq!(split_image("localhost:5000/scorer"), ("localhost:5000/scorer", None, None));
+    assert_eq!(split_image("scorer@sha256:ab12"), ("scorer", None, Some("sha256:ab12")));
+
+    for image in ["scorer", "ghcr.io/acme/scorer:1.2", "localhost:5000/risk/scorer", "acme/scorer@sha256:ab12"] {
+        assert!(validate_image(image).is_ok(), "{} debería ser una imagen válida", image);
+    }
+    for image in ["", "Acme/Scorer", "ghcr.io/acme/scorer:-1", "ghcr.io//scorer", "scorer@sha256:xyz"] {
+        assert!(validate_image(image).is_err(), "{} no debería ser una imagen válida", image);
+    }
+}
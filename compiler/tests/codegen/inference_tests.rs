@@ -0,0 +1,98 @@
+use super::{generate, rust_field_values, rust_item, rust_tokens};
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::{InferenceBackend, InferenceServerConfig},
+    codegen::inference::{server_name, InferenceServer, InferenceSettings},
+    parser::parse,
+};
+
+const SUPPORT: &str = r#"
+workflow Support {
+    source: NATS("tickets");
+    agents: [
+        LLM(id: "triage", model: "meta-llama/Llama-3.1-8B-Instruct", provider: VLLM(max_batch_size: 32)),
+        LLM(id: "summary", model: "meta-llama/Llama-3.1-8B-Instruct", provider: VLLM(gpus: 2)),
+        LLM(id: "reply", model: "llama3", provider: TGI(endpoint: "http://tgi.ml:8080/", model: "mistralai/Mistral-7B-Instruct-v0.3")),
+        LLM(id: "legal", model: "gpt-4", provider: "openai")
+    ];
+}
+"#;
+
+#[test]
+fn test_inference_servers_are_read_from_the_provider() -> Result<()> {
+    let program = parse(SUPPORT)?;
+    let agents = &program.workflows[0].agents;
+
+    let config = InferenceServerConfig::from_value(agents[2].config_value("provider").expect("Se esperaba un proveedor"))
+        .map_err(anyhow::Error::msg)?;
+    assert_eq!(config.backend, InferenceBackend::Tgi);
+    assert_eq!(config.endpoint.as_deref(), Some("http://tgi.ml:8080/"));
+    assert_eq!(config.model.as_deref(), Some("mistralai/Mistral-7B-Instruct-v0.3"));
+    assert_eq!(config.max_batch_size, InferenceServerConfig::DEFAULT_MAX_BATCH_SIZE);
+
+    let program = parse(r#"workflow A { agents: [LLM(id: "a", model: "m", provider: VLLM(port: 8000))]; }"#)?;
+    let error = InferenceServerConfig::from_value(program.workflows[0].agents[0].config_value("provider").unwrap()).unwrap_err();
+    assert!(error.to_string().contains("unknown VLLM setting 'port'"), "{}", error);
+    Ok(())
+}
+
+#[test]
+fn test_agents_share_a_server_per_model() -> Result<()> {
+    let program = parse(SUPPORT)?;
+    let workflow = &program.workflows[0];
+
+    let triage = InferenceSettings::for_agent(&workflow.agents[0])?.expect("Se esperaba un servidor de inferencia");
+    assert_eq!(triage.provider, "vllm");
+    assert_eq!(triage.model, "meta-llama/Llama-3.1-8B-Instruct", "El modelo por defecto es el del agente");
+    assert_eq!(triage.server.as_deref(), Some("vllm-meta-llama-llama-3-1-8b-instruct"));
+    assert_eq!(triage.endpoint, "http://vllm-meta-llama-llama-3-1-8b-instruct:8000");
+    assert_eq!(triage.max_batch_size, 32);
+
+    let reply = InferenceSettings::for_agent(&workflow.agents[2])?.expect("Se esperaba un servidor de inferencia");
+    assert_eq!(reply.endpoint, "http://tgi.ml:8080");
+    assert_eq!(reply.server, None, "Un endpoint no despliega servidor");
+    assert_eq!(InferenceSettings::for_agent(&workflow.agents[3])?, None);
+
+    let servers = InferenceServer::for_workflow(workflow)?;
+    assert_eq!(servers.len(), 1, "Los agentes con el mismo modelo deberían compartir servidor");
+    let server = &servers[0];
+    assert_eq!(server.agents, vec!["triage", "summary"]);
+    assert_eq!(server.gpus, 2);
+    assert!(server.args.windows(2).any(|pair| pair == ["--max-num-seqs", "32"]), "{:?}", server.args);
+    assert!(server.args.windows(2).any(|pair| pair == ["--tensor-parallel-size", "2"]), "{:?}", server.args);
+
+    let rendered = generate(SUPPORT)?.file(&format!("kubernetes/inference/{}.yaml", server.name));
+    let documents: Vec<serde_yaml::Value> = rendered
+        .split("\n---\n")
+        .map(serde_yaml::from_str)
+        .collect::<std::result::Result<_, _>>()?;
+    assert_eq!(documents.len(), 2);
+    let container = &documents[1]["spec"]["template"]["spec"]["containers"][0];
+    assert_eq!(container["resources"]["limits"]["nvidia.com/gpu"], serde_yaml::Value::from(2));
+    assert_eq!(container["args"][1], serde_yaml::Value::from("meta-llama/Llama-3.1-8B-Instruct"));
+    Ok(())
+}
+
+#[test]
+fn test_server_names_are_kubernetes_names() {
+    assert_eq!(server_name(InferenceBackend::Tgi, "mistralai/Mistral-7B-Instruct-v0.3"), "tgi-mistralai-mistral-7b-instruct-v0-3");
+    let name = server_name(InferenceBackend::Vllm, &"very-long-model-name/".repeat(5));
+    assert!(name.len() <= 63 && !name.ends_with('-'), "{}", name);
+}
+
+#[test]
+fn test_llm_agents_default_to_their_server() -> Result<()> {
+    let generated = generate(SUPPORT)?;
+    let config = generated.rust("agents/triage/src/config.rs");
+    assert_eq!(rust_field_values(&config, "provider"), [rust_tokens(r#""vllm".to_string()"#)]);
+    assert_eq!(
+        rust_field_values(&config, "base_url"),
+        [rust_tokens(r#"Some("http://vllm-meta-llama-llama-3-1-8b-instruct:8000".to_string())"#)]
+    );
+    assert_eq!(rust_item(&config, "default_max_batch_size"), rust_tokens("{ 32 }"));
+
+    let config = generated.rust("agents/legal/src/config.rs");
+    assert_eq!(rust_field_values(&config, "provider"), [rust_tokens(r#""openai".to_string()"#)], "Sin servidor el proveedor por defecto no cambia");
+    assert_eq!(rust_item(&config, "default_max_batch_size"), rust_tokens("{ 1 }"));
+    Ok(())
+}
@@ -1,61 +1,40 @@
 use anyhow::Result;
 use kumeo_compiler::{
-    ast::{Workflow, Agent, AgentType},
-    codegen::kubernetes::generate_kubernetes_config,
+    codegen::{
+        dependencies::RUNTIME_SOURCES,
+        escape::register_filters,
+        kubernetes::{generate_kubernetes_config, metric_query, FAILED_MESSAGES_METRIC, MESSAGES_METRIC},
+        sink::FsSink,
+    },
+    parser::parse,
 };
+use super::{generate, rust_calls};
+use serde::Deserialize;
 use std::path::Path;
 use tempfile::tempdir;
 use tera::Tera;
 
+/// The manifests generated for an agent of a workflow
+/// The manifests generated for an agent of a program
+fn agent_manifests(source: &str, agent_id: &str) -> Result<String> {
+    Ok(generate(source)?.file(&format!("agents/{}/kubernetes/deployment.yaml", agent_id)))
+}
+
 #[test]
 fn test_generate_kubernetes_config() -> Result<()> {
-    // Create a temporary directory for the test
-    let temp_dir = tempdir()?;
-    let output_dir = tempdir()?;
-    
-    // Create a test workflow with agents
-    let workflow = Workflow {
-        name: "test-workflow".to_string(),
-        source: None,
-        target: None,
-        context: None,
-        preprocessors: None,
-        agents: vec![
-            Agent {
-                id: Some("test-agent-1".to_string()),
-                agent_type: AgentType::LLM,
-                config: vec![],
-            },
-            Agent {
-                id: Some("test-agent-2".to_string()),
-                agent_type: AgentType::MLModel,
-                config: vec![],
-            },
-        ],
-        monitor: None,
-        deployment: None,
-    };
-    
-    // Initialize Tera
-    let tera = Tera::default();
-    
-    // Generate Kubernetes configuration
-    generate_kubernetes_config(&workflow, output_dir.path(), &tera)?;
-    
-    // Verify output directory structure
-    let kubernetes_dir = output_dir.path().join("kubernetes");
-    assert!(kubernetes_dir.exists());
-    assert!(kubernetes_dir.is_dir());
-    
-    // Verify Helm chart was generated
-    let helm_dir = kubernetes_dir.join("helm").join("test-workflow");
-    assert!(helm_dir.exists());
-    assert!(helm_dir.is_dir());
-    
-    // Verify values.yaml was generated
-    let values_file = helm_dir.join("values.yaml");
-    assert!(values_file.exists());
-    
+    let generated = generate(
+        r#"workflow TestWorkflow {
+            agents: [ LLM(id: "test-agent-1", model: "gpt-4"), MLModel(id: "test-agent-2", model_path: "models/test.onnx") ];
+        }"#,
+    )?;
+
+    // The Helm chart of the workflow enables each of its agents
+    let values: serde_yaml::Value = serde_yaml::from_str(&generated.file("kubernetes/helm/TestWorkflow/values.yaml"))?;
+    for agent in ["test-agent-1", "test-agent-2"] {
+        assert_eq!(values["config"]["agents"][agent]["enabled"].as_bool(), Some(true), "{:?}", values["config"]);
+        assert!(generated.has(&format!("agents/{}/kubernetes/deployment.yaml", agent)));
+    }
+
     Ok(())
 }
 
@@ -65,17 +44,8 @@ fn test_generate_kubernetes_with_custom_templates() -> Result<()> {
     let temp_dir = tempdir()?;
     let output_dir = tempdir()?;
     
-    // Create a test workflow
-    let workflow = Workflow {
-        name: "custom-templates".to_string(),
-        source: None,
-        target: None,
-        context: None,
-        preprocessors: None,
-        agents: vec![],
-        monitor: None,
-        deployment: None,
-    };
+    let program = parse("workflow CustomTemplates { }")?;
+    let workflow = &program.workflows[0];
     
     // Create custom templates
     let template_dir = temp_dir.path().join("templates/kubernetes");
@@ -88,59 +58,746 @@ fn test_generate_kubernetes_with_custom_templates() -> Result<()> {
     )?;
     
     // Initialize Tera with custom templates
-    let tera = Tera::new(&format!("{}/**/*.tera", template_dir.display()))?;
+    let mut tera = Tera::new(&format!("{}/**/*.tera", template_dir.display()))?;
+    register_filters(&mut tera);
     
     // Generate Kubernetes configuration
-    generate_kubernetes_config(&workflow, output_dir.path(), &tera)?;
+    generate_kubernetes_config(workflow, output_dir.path(), &tera, None, &mut FsSink)?;
     
     // Verify custom template was processed
     let config_map = output_dir
         .path()
-        .join("kubernetes/helm/custom-templates/templates/configmap.yaml");
+        .join("kubernetes/helm/CustomTemplates/templates/configmap.yaml");
     assert!(config_map.exists());
     
     // Verify the content was rendered correctly
-    let content = std::fs::read_to_string(config_map)?;
-    assert!(content.contains("name: custom-templates-config"));
+    let content: serde_yaml::Value = serde_yaml::from_str(&std::fs::read_to_string(config_map)?)?;
+    assert_eq!(content["metadata"]["name"].as_str(), Some("CustomTemplates-config"));
     
     Ok(())
 }
 
 #[test]
-fn test_count_agent_types() {
+fn test_count_agent_types() -> Result<()> {
     use kumeo_compiler::codegen::kubernetes::count_agent_types;
     
-    // Create a test workflow with agents
-    let workflow = Workflow {
-        name: "test-count".to_string(),
-        source: None,
-        target: None,
-        context: None,
-        preprocessors: None,
-        agents: vec![
-            Agent {
-                id: Some("agent1".to_string()),
-                agent_type: AgentType::LLM,
-                config: vec![],
-            },
-            Agent {
-                id: Some("agent2".to_string()),
-                agent_type: AgentType::MLModel,
-                config: vec![],
-            },
-            Agent {
-                id: Some("agent3".to_string()),
-                agent_type: AgentType::LLM,
-                config: vec![],
-            },
-        ],
-        monitor: None,
-        deployment: None,
-    };
-    
-    let counts = count_agent_types(&workflow);
+    let program = parse(
+        r#"workflow TestCount {
+            agents: [ LLM(id: "agent1", model: "gpt-4"), MLModel(id: "agent2", model_path: "models/agent2.onnx"), LLM(id: "agent3", model: "gpt-4") ];
+        }"#,
+    )?;
+    let counts = count_agent_types(&program.workflows[0]);
     
     assert_eq!(counts.get("llm"), Some(&2));
     assert_eq!(counts.get("mlmodel"), Some(&1));
     assert_eq!(counts.get("nonexistent"), None);
+    Ok(())
+}
+
+#[test]
+fn test_drain_settings_follow_agent_timeout() -> Result<()> {
+    use kumeo_compiler::codegen::kubernetes::{DrainSettings, DEFAULT_AGENT_TIMEOUT_SECS, PRE_STOP_SLEEP_SECS};
+
+    let program = parse(
+        r#"workflow Drain {
+            agents: [ LLM(id: "fast-agent", model: "gpt-4"), LLM(id: "slow-agent", model: "gpt-4", timeout: "2m") ];
+        }"#,
+    )?;
+    let agents = &program.workflows[0].agents;
+
+    let defaults = DrainSettings::for_agent(&agents[0]);
+    assert_eq!(defaults.drain_timeout_seconds, DEFAULT_AGENT_TIMEOUT_SECS);
+    assert_eq!(defaults.pre_stop_sleep_seconds, PRE_STOP_SLEEP_SECS);
+
+    let drain = DrainSettings::for_agent(&agents[1]);
+    assert_eq!(drain.drain_timeout_seconds, 120);
+    assert!(drain.termination_grace_period_seconds > drain.pre_stop_sleep_seconds + drain.drain_timeout_seconds);
+    Ok(())
+}
+
+#[test]
+fn test_agent_deployment_template_renders_drain_settings() -> Result<()> {
+    use kumeo_compiler::codegen::kubernetes::DrainSettings;
+
+    let source = r#"workflow DrainTest { agents: [ MLModel(id: "scorer") ]; }"#;
+    let program = parse(source)?;
+    let drain = DrainSettings::for_agent(&program.workflows[0].agents[0]);
+
+    let rendered = generate(source)?.file("agents/scorer/kubernetes/deployment.yaml");
+    let manifest: serde_yaml::Value = serde_yaml::from_str(&rendered)?;
+    let pod_spec = &manifest["spec"]["template"]["spec"];
+
+    assert_eq!(
+        pod_spec["terminationGracePeriodSeconds"].as_u64(),
+        Some(drain.termination_grace_period_seconds)
+    );
+    let pre_stop = &pod_spec["containers"][0]["lifecycle"]["preStop"]["exec"]["command"];
+    assert_eq!(pre_stop[1].as_str(), Some(drain.pre_stop_sleep_seconds.to_string().as_str()));
+
+    Ok(())
+}
+
+#[test]
+fn test_agent_deployment_template_renders_canary_rollout() -> Result<()> {
+    use kumeo_compiler::codegen::kubernetes::CanarySettings;
+
+    let source = r#"workflow CanaryTest {
+        agents: [ MLModel(id: "scorer", model_path: "models/scorer.onnx") ];
+        deployment: { rollout: canary { steps: [10%, 50%, 100%], analysis: "error_rate < 1%" } };
+    }"#;
+    let program = parse(source)?;
+    let canary = CanarySettings::for_agent(&program.workflows[0], "scorer")?;
+    let analysis = canary.as_ref().and_then(|c| c.analysis.as_ref()).expect("analysis");
+    assert_eq!(analysis.success_condition, "result[0] < 0.01");
+
+    let rendered = agent_manifests(source, "scorer")?;
+    let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(&rendered)
+        .map(serde_yaml::Value::deserialize)
+        .collect::<Result<_, _>>()?;
+
+    assert_eq!(documents.len(), 2);
+    assert_eq!(documents[0]["kind"].as_str(), Some("Rollout"));
+    let steps = documents[0]["spec"]["strategy"]["canary"]["steps"]
+        .as_sequence()
+        .expect("canary steps");
+    assert_eq!(steps[0]["setWeight"].as_u64(), Some(10));
+    assert_eq!(
+        steps[1]["analysis"]["templates"][0]["templateName"].as_str(),
+        Some("scorer-analysis")
+    );
+    assert_eq!(steps.last().unwrap()["setWeight"].as_u64(), Some(100));
+    assert_eq!(documents[1]["kind"].as_str(), Some("AnalysisTemplate"));
+
+    Ok(())
+}
+
+/// Value of a `&str` constant of the runtime, read from the sources vendored into the agents
+fn runtime_constant(path: &str, name: &str) -> String {
+    let (_, source) = RUNTIME_SOURCES.iter().find(|(source, _)| *source == path).expect(path);
+    let file = syn::parse_file(source).expect(path);
+    let value = file.items.iter().find_map(|item| match item {
+        syn::Item::Const(constant) if constant.ident == name => match &*constant.expr {
+            syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(value), .. }) => Some(value.value()),
+            _ => None,
+        },
+        _ => None,
+    });
+    value.unwrap_or_else(|| panic!("{} no define la constante {}", path, name))
+}
+
+#[test]
+fn test_canary_error_rate_reads_the_counters_the_runtime_exports() {
+    let received = runtime_constant("src/messaging/mod.rs", "MESSAGES_METRIC");
+    let failed = runtime_constant("src/messaging/mod.rs", "FAILED_MESSAGES_METRIC");
+    assert_eq!(received, MESSAGES_METRIC);
+    assert_eq!(failed, FAILED_MESSAGES_METRIC);
+
+    // Los mensajes fallidos entre los recibidos del mismo agente
+    let query = metric_query("error_rate", "scorer");
+    let (failures, messages) = query.split_once(" / ").expect("división");
+    assert!(failures.contains(&format!("{}{{app=\"scorer\"}}", failed)), "{}", query);
+    assert!(messages.contains(&format!("{}{{app=\"scorer\"}}", received)), "{}", query);
+}
+
+#[test]
+fn test_canary_agents_serve_their_metrics() -> Result<()> {
+    let workflow = |deployment: &str| {
+        format!(
+            r#"workflow Scoring {{
+                source: NATS("input");
+                agents: [ Router(id: "router"), MLModel(id: "scorer", model_path: "models/scorer.onnx") ];
+                {}
+            }}"#,
+            deployment
+        )
+    };
+    let generated = generate(&workflow(r#"deployment: { rollout: canary { steps: [10%, 100%], analysis: "error_rate < 1%" } };"#))?;
+
+    for agent in ["router", "scorer"] {
+        let rendered = generated.file(&format!("agents/{}/kubernetes/deployment.yaml", agent));
+        let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(&rendered)
+            .map(serde_yaml::Value::deserialize)
+            .collect::<Result<_, _>>()?;
+        let template = &documents[0]["spec"]["template"];
+        assert_eq!(template["metadata"]["annotations"]["prometheus.io/scrape"].as_str(), Some("true"), "{}", rendered);
+        assert_eq!(template["metadata"]["annotations"]["prometheus.io/port"].as_str(), Some("9090"), "{}", rendered);
+        let container = &template["spec"]["containers"][0];
+        let ports = container["ports"].as_sequence().expect("ports");
+        assert!(ports.iter().any(|port| port["name"].as_str() == Some("metrics") && port["containerPort"].as_u64() == Some(9090)));
+        let env = container["env"].as_sequence().expect("env");
+        assert!(env.iter().any(|var| var["name"].as_str() == Some("KUMEO_METRICS_PORT") && var["value"].as_str() == Some("9090")));
+    }
+
+    // El agente de Rust declara el módulo y lo arranca con el cliente del runtime
+    let lib = syn::parse_file(&generated.file("agents/router/src/lib.rs"))?;
+    assert!(lib.items.iter().any(|item| matches!(item, syn::Item::Mod(module) if module.ident == "metrics")));
+    syn::parse_file(&generated.file("agents/router/src/metrics.rs"))?;
+    assert!(rust_calls(&generated.rust("agents/router/src/agent.rs")).contains("crate::metrics::serve"));
+    let manifest: toml::Value = toml::from_str(&generated.file("agents/router/Cargo.toml"))?;
+    assert!(manifest["dependencies"].get("axum").is_some(), "{}", manifest);
+
+    // El de Python sirve lo mismo con aiohttp
+    if let Some(calls) = generated.python_calls("agents/scorer/src/kumeo_agent_scorer/agent.py") {
+        assert!(calls.contains("serve_metrics"), "{:?}", calls);
+    }
+    assert!(generated.has("agents/scorer/src/kumeo_agent_scorer/metrics.py"));
+    assert!(generated.file("agents/scorer/requirements.txt").lines().any(|line| line.starts_with("aiohttp==")));
+
+    // Sin canary no hay nada que exportar
+    let generated = generate(&workflow(""))?;
+    assert!(!generated.has("agents/router/src/metrics.rs"));
+    assert!(!generated.has("agents/scorer/src/kumeo_agent_scorer/metrics.py"));
+    let lib = syn::parse_file(&generated.file("agents/router/src/lib.rs"))?;
+    assert!(!lib.items.iter().any(|item| matches!(item, syn::Item::Mod(module) if module.ident == "metrics")));
+    Ok(())
+}
+
+/// The manifests of the blue/green agent `scorer`, given the annotations of its deployment
+fn blue_green_manifests(annotations: &str) -> Result<Vec<serde_yaml::Value>> {
+    let rendered = generate(&format!(
+        r#"workflow Scoring {{
+            source: NATS("input");
+            agents: [ Router(id: "scorer") ];
+            deployment: {{ rollout: blue_green, replicas: 2, tag: "v2", annotations: {{ {} }} }};
+        }}"#,
+        annotations
+    ))?
+    .file("agents/scorer/kubernetes/deployment.yaml");
+    Ok(serde_yaml::Deserializer::from_str(&rendered).map(serde_yaml::Value::deserialize).collect::<Result<_, _>>()?)
+}
+
+#[test]
+fn test_agent_deployment_template_renders_blue_green_slots() -> Result<()> {
+    // Only the idle slot gets the new version, without replicas until promoted
+    let documents = blue_green_manifests("")?;
+    let kinds: Vec<_> = documents.iter().map(|document| document["kind"].as_str().unwrap_or_default()).collect();
+    assert_eq!(kinds, ["Deployment", "Service"]);
+    let idle = &documents[0];
+    assert_eq!(idle["metadata"]["name"].as_str(), Some("scorer-green"));
+    assert_eq!(idle["spec"]["replicas"].as_u64(), Some(0));
+    assert_eq!(idle["spec"]["template"]["metadata"]["labels"]["kumeo.io/slot"].as_str(), Some("green"));
+    let image = idle["spec"]["template"]["spec"]["containers"][0]["image"].as_str().unwrap_or_default();
+    assert!(image.ends_with(":v2"), "{}", image);
+    assert_eq!(documents[1]["spec"]["selector"]["kumeo.io/slot"].as_str(), Some("blue"));
+
+    // Once green is promoted, the next version goes to blue and green keeps serving
+    let documents = blue_green_manifests(r#""kumeo.io/active-slot": "green""#)?;
+    assert_eq!(documents[0]["metadata"]["name"].as_str(), Some("scorer-blue"));
+    assert_eq!(documents[0]["spec"]["replicas"].as_u64(), Some(0));
+    assert_eq!(documents[1]["spec"]["selector"]["kumeo.io/slot"].as_str(), Some("green"));
+    Ok(())
+}
+
+#[test]
+fn test_rerendering_blue_green_agents_keeps_the_active_slot() -> Result<()> {
+    let first = blue_green_manifests(r#""kumeo.io/active-slot": "green""#)?;
+    let second = blue_green_manifests(r#""kumeo.io/active-slot": "green""#)?;
+    assert_eq!(first, second);
+    for documents in [&first, &second] {
+        assert_eq!(documents[1]["spec"]["selector"]["kumeo.io/slot"].as_str(), Some("green"));
+        assert!(
+            !documents.iter().any(|document| document["metadata"]["name"].as_str() == Some("scorer-green")),
+            "Aplicar de nuevo no debería tocar el slot activo"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_agent_deployment_template_renders_storage() -> Result<()> {
+    use kumeo_compiler::codegen::kubernetes::StorageSettings;
+
+    let source = r#"workflow StorageTest {
+        agents: [ MLModel(id: "scorer") ];
+        deployment: { storage: { scorer: { size: "10Gi", path: "/models", class: "fast-ssd" } } };
+    }"#;
+    let program = parse(source)?;
+    let storage = StorageSettings::for_agent(&program.workflows[0], "scorer").expect("storage settings");
+    assert_eq!(storage.claim_name, "scorer-data");
+
+    let rendered = generate(source)?.file("agents/scorer/kubernetes/deployment.yaml");
+    let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(&rendered)
+        .map(serde_yaml::Value::deserialize)
+        .collect::<Result<_, _>>()?;
+
+    let pod_spec = &documents[0]["spec"]["template"]["spec"];
+    assert_eq!(
+        pod_spec["containers"][0]["volumeMounts"][0]["mountPath"].as_str(),
+        Some("/models")
+    );
+    assert_eq!(
+        pod_spec["volumes"][0]["persistentVolumeClaim"]["claimName"].as_str(),
+        Some("scorer-data")
+    );
+    assert_eq!(documents[1]["kind"].as_str(), Some("PersistentVolumeClaim"));
+    assert_eq!(documents[1]["spec"]["storageClassName"].as_str(), Some("fast-ssd"));
+    assert_eq!(documents[1]["spec"]["resources"]["requests"]["storage"].as_str(), Some("10Gi"));
+
+    Ok(())
+}
+
+#[test]
+fn test_agent_deployment_template_renders_batch_job() -> Result<()> {
+    use kumeo_compiler::codegen::kubernetes::BatchSettings;
+
+    let source = r#"workflow Nightly {
+        mode: batch;
+        source: NATS("events", { until_sequence: 5000 });
+        agents: [ DataProcessor(id: "extract"), DataProcessor(id: "load") ];
+    }"#;
+    let program = parse(source)?;
+    let workflow = &program.workflows[0];
+    let first = BatchSettings::for_agent(workflow, "extract").expect("batch settings");
+    let second = BatchSettings::for_agent(workflow, "load").expect("batch settings");
+    assert_eq!(first.until_sequence, Some(5000));
+    assert_eq!(second.until_sequence, None);
+
+    let rendered = agent_manifests(source, "extract")?;
+    let manifest: serde_yaml::Value = serde_yaml::from_str(&rendered)?;
+
+    assert_eq!(manifest["kind"].as_str(), Some("Job"));
+    assert!(manifest["spec"]["selector"].is_null());
+    let pod_spec = &manifest["spec"]["template"]["spec"];
+    assert_eq!(pod_spec["restartPolicy"].as_str(), Some("Never"));
+    let env = pod_spec["containers"][0]["env"].as_sequence().expect("env");
+    assert!(env.iter().any(|var| {
+        var["name"].as_str() == Some("KUMEO_BATCH_UNTIL_SEQUENCE") && var["value"].as_str() == Some("5000")
+    }));
+
+    Ok(())
+}
+
+#[test]
+fn test_kafka_workflow_gets_in_cluster_broker() -> Result<()> {
+    use kumeo_compiler::codegen::kubernetes::{BrokerSettings, KafkaSettings};
+
+    let source = r#"workflow Clicks {
+        source: Kafka("clicks");
+        target: NATS("scored");
+        agents: [ MLModel(id: "scorer", model_path: "models/scorer.onnx") ];
+    }"#;
+    let program = parse(source)?;
+    let workflow = &program.workflows[0];
+    let kafka = KafkaSettings::for_workflow(workflow).expect("kafka settings");
+    assert!(kafka.in_cluster);
+    assert_eq!(kafka.bootstrap_servers, "clicks-kafka:9092");
+    assert_eq!(kafka.group_id, "Clicks");
+
+    let generated = generate(source)?;
+    let rendered = generated.file("kubernetes/kafka.yaml");
+    let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(&rendered)
+        .map(serde_yaml::Value::deserialize)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(documents[1]["kind"].as_str(), Some("StatefulSet"));
+    assert_eq!(documents[1]["metadata"]["name"].as_str(), Some("clicks-kafka"));
+
+    let broker = BrokerSettings::for_workflow(workflow);
+    assert_eq!(broker.kafka.as_ref().map(|kafka| kafka.bootstrap_servers.as_str()), Some("clicks-kafka:9092"));
+    let manifest: serde_yaml::Value = serde_yaml::from_str(&generated.file("agents/scorer/kubernetes/deployment.yaml"))?;
+    let env = manifest["spec"]["template"]["spec"]["containers"][0]["env"].as_sequence().expect("env");
+    let var = |name: &str| {
+        env.iter()
+            .find(|var| var["name"].as_str() == Some(name))
+            .and_then(|var| var["value"].as_str())
+            .map(str::to_string)
+    };
+    assert_eq!(var("KUMEO_SOURCE_BROKER").as_deref(), Some("kafka"));
+    assert_eq!(var("KUMEO_TARGET_BROKER").as_deref(), Some("nats"));
+    assert_eq!(var("KAFKA_BOOTSTRAP_SERVERS").as_deref(), Some("clicks-kafka:9092"));
+
+    // External brokers skip the in-cluster StatefulSet
+    let source = source.replace(r#"Kafka("clicks")"#, r#"Kafka("clicks", { brokers: "kafka.prod:9092" })"#);
+    let program = parse(&source)?;
+    let kafka = KafkaSettings::for_workflow(&program.workflows[0]).expect("kafka settings");
+    assert!(!kafka.in_cluster);
+    assert_eq!(kafka.bootstrap_servers, "kafka.prod:9092");
+    assert!(!generate(&source)?.has("kubernetes/kafka.yaml"));
+
+    Ok(())
+}
+
+#[test]
+fn test_mqtt_workflow_passes_broker_url() -> Result<()> {
+    use kumeo_compiler::codegen::kubernetes::BrokerSettings;
+
+    let program = parse(
+        r#"workflow Sensors {
+            source: MQTT("sensors/+/temp", { url: "mqtt://broker:8883" });
+            target: MQTT("alerts/temp");
+        }"#,
+    )?;
+
+    let broker = BrokerSettings::for_workflow(&program.workflows[0]);
+    assert_eq!(broker.source.as_ref().map(|s| s.broker), Some("mqtt"));
+    assert_eq!(broker.target.as_ref().map(|t| t.topic.as_str()), Some("alerts/temp"));
+    assert_eq!(broker.mqtt_url.as_deref(), Some("mqtt://broker:8883"));
+    assert!(broker.kafka.is_none());
+    Ok(())
+}
+
+#[test]
+fn test_http_source_exposes_webhook() -> Result<()> {
+    use kumeo_compiler::codegen::kubernetes::{BrokerSettings, WebhookSettings};
+
+    let source = r#"workflow Ingest {
+        source: HTTP("/ingest", { host: "hooks.example.com" });
+        agents: [ DataProcessor(id: "receiver"), DataProcessor(id: "enricher") ];
+    }"#;
+    let program = parse(source)?;
+    let workflow = &program.workflows[0];
+
+    // Only the agent reading the source serves the webhook
+    assert!(WebhookSettings::for_agent(workflow, "enricher").is_none());
+    let webhook = WebhookSettings::for_agent(workflow, "receiver").expect("webhook");
+    assert_eq!(webhook.method, "POST");
+
+    let broker = BrokerSettings::for_workflow(workflow);
+    let source_broker = broker.source.as_ref().expect("source");
+    assert_eq!((source_broker.broker, source_broker.topic.as_str()), ("http", "ingest.webhook"));
+
+    let rendered = agent_manifests(source, "receiver")?;
+    let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(&rendered)
+        .map(serde_yaml::Value::deserialize)
+        .collect::<Result<_, _>>()?;
+
+    let ports = &documents[0]["spec"]["template"]["spec"]["containers"][0]["ports"];
+    assert_eq!(ports[1]["containerPort"].as_u64(), Some(u64::from(webhook.port)));
+
+    let kinds: Vec<_> = documents.iter().filter_map(|doc| doc["kind"].as_str()).collect();
+    assert_eq!(kinds, ["Deployment", "Service", "Ingress"]);
+    let rule = &documents[2]["spec"]["rules"][0];
+    assert_eq!(rule["host"].as_str(), Some("hooks.example.com"));
+    assert_eq!(rule["http"]["paths"][0]["path"].as_str(), Some("/ingest"));
+    assert_eq!(
+        rule["http"]["paths"][0]["backend"]["service"]["name"].as_str(),
+        Some("receiver-webhook")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_file_source_and_target_mount_volumes() -> Result<()> {
+    use kumeo_compiler::codegen::kubernetes::FileSettings;
+
+    let source = r#"workflow Drop {
+        source: File("/data/in/*.json", { watch: true });
+        target: File("/data/out/", { claim: "results" });
+        agents: [ DataProcessor(id: "reader"), DataProcessor(id: "middle"), DataProcessor(id: "writer") ];
+    }"#;
+    let program = parse(source)?;
+    let workflow = &program.workflows[0];
+
+    assert!(FileSettings::for_agent(workflow, "middle").is_none());
+    let reader = FileSettings::for_agent(workflow, "reader").expect("source mount");
+    assert!(reader.watch);
+    assert_eq!(reader.mounts[0].path, "/data/in");
+    assert!(reader.mounts[0].read_only);
+    let writer = FileSettings::for_agent(workflow, "writer").expect("target mount");
+    assert!(!writer.watch);
+    assert_eq!(writer.mounts[0].path, "/data/out");
+
+    let generated = generate(source)?;
+    let render = |id: &str| -> Result<serde_yaml::Value> {
+        Ok(serde_yaml::from_str(&generated.file(&format!("agents/{}/kubernetes/deployment.yaml", id)))?)
+    };
+
+    let deployment = render("reader")?;
+    let pod = &deployment["spec"]["template"]["spec"];
+    assert_eq!(pod["containers"][0]["volumeMounts"][0]["mountPath"].as_str(), Some("/data/in"));
+    assert_eq!(pod["volumes"][0]["hostPath"]["path"].as_str(), Some("/data/in"));
+    let env = pod["containers"][0]["env"].as_sequence().expect("env");
+    assert!(env.iter().any(|var| var["name"].as_str() == Some("KUMEO_FILE_WATCH")));
+
+    let deployment = render("writer")?;
+    let pod = &deployment["spec"]["template"]["spec"];
+    assert_eq!(
+        pod["volumes"][0]["persistentVolumeClaim"]["claimName"].as_str(),
+        Some("results")
+    );
+    let env = pod["containers"][0]["env"].as_sequence().expect("env");
+    assert!(!env.iter().any(|var| var["name"].as_str() == Some("KUMEO_FILE_WATCH")));
+
+    Ok(())
+}
+
+#[test]
+fn test_require_signed_mounts_trusted_keys() -> Result<()> {
+    use kumeo_compiler::codegen::kubernetes::{SigningSettings, TRUSTED_KEYS_PATH};
+
+    let source = r#"workflow Scoring {
+        agents: [ MLModel(id: "scorer", model_path: "models/scorer.onnx") ];
+        deployment: { require_signed: true, trusted_keys: "release-keys" };
+    }"#;
+    let program = parse(source)?;
+    let signing = SigningSettings::for_workflow(&program.workflows[0]).expect("signing settings");
+    assert_eq!(signing.secret, "release-keys");
+
+    let deployment: serde_yaml::Value = serde_yaml::from_str(&agent_manifests(source, "scorer")?)?;
+
+    let pod = &deployment["spec"]["template"]["spec"];
+    let env = pod["containers"][0]["env"].as_sequence().expect("env");
+    let var = |name: &str| env.iter().find(|var| var["name"].as_str() == Some(name)).and_then(|var| var["value"].as_str());
+    assert_eq!(var("KUMEO_REQUIRE_SIGNED"), Some("true"));
+    assert_eq!(var("KUMEO_TRUSTED_KEYS"), Some(TRUSTED_KEYS_PATH));
+    assert_eq!(pod["containers"][0]["volumeMounts"][0]["mountPath"].as_str(), Some(TRUSTED_KEYS_PATH));
+    assert_eq!(pod["volumes"][0]["secret"]["secretName"].as_str(), Some("release-keys"));
+
+    let program = parse(&source.replace("require_signed: true", "require_signed: false"))?;
+    assert!(SigningSettings::for_workflow(&program.workflows[0]).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_preloaded_models_gate_readiness() -> Result<()> {
+    use kumeo_compiler::codegen::kubernetes::{PreloadSettings, READY_FILE_PATH};
+
+    let source = r#"workflow Scoring {
+        agents: [
+            MLModel(id: "scorer", model_path: "s3://models/scorer.onnx", preload: true),
+            MLModel(id: "idle", model_path: "s3://models/idle.onnx")
+        ];
+    }"#;
+    let program = parse(source)?;
+    let workflow = &program.workflows[0];
+    let preload = PreloadSettings::for_agent(workflow, &workflow.agents[0]).expect("preload settings");
+    assert_eq!(preload.uris, ["s3://models/scorer.onnx"]);
+    assert_eq!(preload.dir, None);
+    assert!(PreloadSettings::for_agent(workflow, &workflow.agents[1]).is_none());
+
+    let deployment: serde_yaml::Value = serde_yaml::from_str(&agent_manifests(source, "scorer")?)?;
+
+    let container = &deployment["spec"]["template"]["spec"]["containers"][0];
+    let env = container["env"].as_sequence().expect("env");
+    let var = |name: &str| env.iter().find(|var| var["name"].as_str() == Some(name)).and_then(|var| var["value"].as_str());
+    assert_eq!(var("KUMEO_PRELOAD"), Some("s3://models/scorer.onnx"));
+    assert_eq!(var("KUMEO_READY_FILE"), Some(READY_FILE_PATH));
+    assert_eq!(var("KUMEO_PRELOAD_DIR"), None);
+    assert_eq!(container["readinessProbe"]["exec"]["command"][1].as_str(), Some(READY_FILE_PATH));
+
+    // Agents with a volume pin their models on it
+    let source = source.replace("];\n    }", r#"];
+        deployment: { storage: { scorer: { size: "20Gi", path: "/data/" } } };
+    }"#);
+    let program = parse(&source)?;
+    let workflow = &program.workflows[0];
+    let preload = PreloadSettings::for_agent(workflow, &workflow.agents[0]).expect("preload settings");
+    assert_eq!(preload.dir.as_deref(), Some("/data/.kumeo-preload"));
+
+    Ok(())
+}
+
+#[test]
+fn test_env_references_come_from_secrets() -> Result<()> {
+    use kumeo_compiler::codegen::kubernetes::{SecretEnvSettings, DEFAULT_ENV_SECRET};
+
+    let source = r#"workflow Support {
+        agents: [
+            LLM(id: "writer", model: "gpt-4", options: { api_key: "${env.OPENAI_API_KEY}", org: "${env.OPENAI_ORG}/${env.OPENAI_API_KEY}" }),
+            LLM(id: "plain", model: "gpt-4")
+        ];
+    }"#;
+    let program = parse(source)?;
+    let workflow = &program.workflows[0];
+    let secret_env = SecretEnvSettings::for_agent(workflow, &workflow.agents[0]).expect("secret env");
+    assert_eq!(secret_env.secret, DEFAULT_ENV_SECRET);
+    assert_eq!(secret_env.vars, ["OPENAI_API_KEY", "OPENAI_ORG"]);
+    assert!(SecretEnvSettings::for_agent(workflow, &workflow.agents[1]).is_none());
+
+    let deployment: serde_yaml::Value = serde_yaml::from_str(&agent_manifests(source, "writer")?)?;
+
+    let env = deployment["spec"]["template"]["spec"]["containers"][0]["env"].as_sequence().expect("env");
+    let api_key = env
+        .iter()
+        .find(|var| var["name"].as_str() == Some("OPENAI_API_KEY"))
+        .expect("OPENAI_API_KEY");
+    assert_eq!(api_key["valueFrom"]["secretKeyRef"]["name"].as_str(), Some(DEFAULT_ENV_SECRET));
+    assert_eq!(api_key["valueFrom"]["secretKeyRef"]["key"].as_str(), Some("OPENAI_API_KEY"));
+
+    Ok(())
+}
+
+#[test]
+fn test_deployment_block_shapes_the_manifests() -> Result<()> {
+    use kumeo_compiler::codegen::kubernetes::{AutoscalingSettings, DeploymentSettings, DEFAULT_NAMESPACE};
+
+    let source = r#"workflow Scoring {
+        source: NATS("input");
+        agents: [ MLModel(id: "scorer", model_name: "fraud") ];
+        deployment: {
+            namespace: "risk",
+            replicas: 3,
+            resources: { cpu: "1.5", memory: "2Gi", gpu: "1" },
+            env: { LOG_LEVEL: "debug" },
+            scaling: { min_replicas: 2, max_replicas: 6, target_cpu: 70 }
+        };
+    }"#;
+    let program = parse(source)?;
+    let workflow = &program.workflows[0];
+    let settings = DeploymentSettings::for_agent(workflow, &workflow.agents[0])?;
+    assert_eq!(settings.namespace, "risk");
+    assert_eq!((settings.cpu.as_str(), settings.memory.as_str()), ("1500m", "2048Mi"));
+    assert_eq!(
+        settings.autoscaling,
+        Some(AutoscalingSettings { min_replicas: 2, max_replicas: 6, target_cpu: Some(70), target_memory: None })
+    );
+
+    let rendered = generate(source)?.file("agents/scorer/kubernetes/deployment.yaml");
+    let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(&rendered)
+        .map(serde_yaml::Value::deserialize)
+        .collect::<Result<_, _>>()?;
+    let deployment = &documents[0];
+    assert_eq!(deployment["metadata"]["namespace"].as_str(), Some("risk"));
+    assert!(deployment["spec"]["replicas"].is_null(), "Con escalado las réplicas las fija el HPA");
+    let container = &deployment["spec"]["template"]["spec"]["containers"][0];
+    assert_eq!(container["resources"]["requests"]["cpu"].as_str(), Some("1500m"));
+    assert_eq!(container["resources"]["requests"]["memory"].as_str(), Some("2048Mi"));
+    assert_eq!(container["resources"]["limits"]["nvidia.com/gpu"].as_str(), Some("1"));
+    let env = container["env"].as_sequence().expect("env");
+    let log_level = env.iter().find(|var| var["name"].as_str() == Some("LOG_LEVEL")).expect("LOG_LEVEL");
+    assert_eq!(log_level["value"].as_str(), Some("debug"));
+
+    let hpa = documents.last().unwrap();
+    assert_eq!(hpa["kind"].as_str(), Some("HorizontalPodAutoscaler"));
+    assert_eq!(hpa["metadata"]["namespace"].as_str(), Some("risk"));
+    assert_eq!(hpa["spec"]["scaleTargetRef"]["kind"].as_str(), Some("Deployment"));
+    assert_eq!(hpa["spec"]["scaleTargetRef"]["name"].as_str(), Some("scorer"));
+    assert_eq!(hpa["spec"]["minReplicas"].as_u64(), Some(2));
+    assert_eq!(hpa["spec"]["maxReplicas"].as_u64(), Some(6));
+    assert_eq!(hpa["spec"]["metrics"][0]["resource"]["target"]["averageUtilization"].as_u64(), Some(70));
+
+    // Without a deployment block the agent keeps one replica with the requests of its language
+    let source = r#"workflow Scoring { source: NATS("input"); agents: [ Router(id: "route") ]; }"#;
+    let program = parse(source)?;
+    let workflow = &program.workflows[0];
+    let settings = DeploymentSettings::for_agent(workflow, &workflow.agents[0])?;
+    assert_eq!(settings.namespace, DEFAULT_NAMESPACE);
+    assert_eq!((settings.replicas, settings.cpu.as_str(), settings.memory.as_str()), (1, "100m", "128Mi"));
+    let rendered = generate(source)?.file("agents/route/kubernetes/deployment.yaml");
+    let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(&rendered)
+        .map(serde_yaml::Value::deserialize)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(documents[0]["spec"]["replicas"].as_u64(), Some(1));
+    let kinds: Vec<_> = documents.iter().filter_map(|document| document["kind"].as_str()).collect();
+    assert_eq!(kinds, ["Deployment"], "Sin autoescalado no hay HorizontalPodAutoscaler");
+
+    Ok(())
+}
+
+#[test]
+fn test_deployment_labels_go_on_every_manifest() -> Result<()> {
+    use kumeo_compiler::codegen::kubernetes::{KafkaSettings, ManifestMetadata};
+
+    let source = r#"workflow Clicks {
+        source: Kafka("clicks");
+        agents: [ MLModel(id: "scorer", model_name: "ctr") ];
+        deployment: {
+            namespace: "ads",
+            labels: { team: "growth" },
+            annotations: { "example.com/owner": "growth@example.com" }
+        };
+    }"#;
+    let program = parse(source)?;
+    let workflow = &program.workflows[0];
+    let metadata = ManifestMetadata::for_workflow(workflow);
+    assert_eq!(metadata.namespace, "ads");
+
+    let documents = |rendered: &str| -> Result<Vec<serde_yaml::Value>> {
+        Ok(serde_yaml::Deserializer::from_str(rendered)
+            .map(serde_yaml::Value::deserialize)
+            .collect::<Result<_, _>>()?)
+    };
+    let check = |document: &serde_yaml::Value| {
+        let kind = document["kind"].as_str().unwrap_or_default();
+        assert_eq!(document["metadata"]["namespace"].as_str(), Some("ads"), "Namespace de {}", kind);
+        assert_eq!(document["metadata"]["labels"]["team"].as_str(), Some("growth"), "Etiquetas de {}", kind);
+        assert_eq!(
+            document["metadata"]["annotations"]["example.com/owner"].as_str(),
+            Some("growth@example.com"),
+            "Anotaciones de {}",
+            kind
+        );
+    };
+
+    let generated = generate(source)?;
+    let deployment = &documents(&generated.file("agents/scorer/kubernetes/deployment.yaml"))?[0];
+    check(deployment);
+    assert_eq!(deployment["metadata"]["labels"]["app"].as_str(), Some("scorer"), "Las etiquetas de Kumeo se mantienen");
+    assert_eq!(deployment["spec"]["template"]["metadata"]["labels"]["team"].as_str(), Some("growth"));
+    assert!(deployment["spec"]["selector"]["matchLabels"]["team"].is_null(), "Las etiquetas no entran en el selector");
+
+    assert!(KafkaSettings::for_workflow(workflow).is_some_and(|kafka| kafka.in_cluster));
+    for document in documents(&generated.file("kubernetes/kafka.yaml"))? {
+        check(&document);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_secrets_are_read_from_the_env_secret() -> Result<()> {
+    use kumeo_compiler::codegen::kubernetes::{SecretEnvSettings, DEFAULT_ENV_SECRET};
+    use std::collections::BTreeMap;
+
+    let source = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [
+            LLM(id: "writer", model: "gpt-4", api_key: secret("openai-api-key")),
+            Router(id: "route")
+        ];
+        deployment: { env: { DB_PASSWORD: secret("db-password"), LOG_LEVEL: "debug" } };
+    }"#;
+    let program = parse(source)?;
+    let workflow = &program.workflows[0];
+    let secret_env = SecretEnvSettings::for_agent(workflow, &workflow.agents[1]).expect("secret env");
+    assert!(secret_env.vars.is_empty());
+    assert_eq!(secret_env.deployment_vars, BTreeMap::from([("DB_PASSWORD".to_string(), "DB_PASSWORD".to_string())]));
+
+    let generated = generate(source)?;
+    let rendered = generated.file("agents/route/kubernetes/deployment.yaml");
+    let deployment: serde_yaml::Value = serde_yaml::from_str(&rendered)?;
+    let env = deployment["spec"]["template"]["spec"]["containers"][0]["env"].as_sequence().expect("env");
+    let password = env.iter().find(|var| var["name"].as_str() == Some("DB_PASSWORD")).expect("DB_PASSWORD");
+    assert!(password["value"].is_null(), "El secreto no debería quedar en claro: {}", rendered);
+    assert_eq!(password["valueFrom"]["secretKeyRef"]["name"].as_str(), Some(DEFAULT_ENV_SECRET));
+    assert_eq!(password["valueFrom"]["secretKeyRef"]["key"].as_str(), Some("DB_PASSWORD"));
+    assert!(env.iter().any(|var| var["name"].as_str() == Some("LOG_LEVEL") && var["value"].as_str() == Some("debug")));
+
+    let example = generated.file(".env.example");
+    assert!(example.contains("kubectl create secret generic kumeo-env --from-env-file=.env"), "{}", example);
+    assert!(example.contains("# Read by writer, route\nDB_PASSWORD=\n"), "{}", example);
+    assert!(example.contains("# Read by writer\nOPENAI_API_KEY=\n"), "{}", example);
+    assert!(!example.contains("LOG_LEVEL"), "{}", example);
+
+    // Without secrets there is nothing to fill in
+    let plain = generate(r#"workflow Plain { source: NATS("input"); agents: [ Router(id: "route") ]; }"#)?;
+    assert!(!plain.has(".env.example"));
+
+    Ok(())
+}
+
+#[test]
+fn test_blue_green_slots_autoscale_separately() -> Result<()> {
+
+    let rendered = generate(
+        r#"workflow Scoring {
+        source: NATS("input");
+        agents: [ Router(id: "route") ];
+        deployment: { rollout: blue_green, replicas: 2, scaling: { max_replicas: 5 } };
+    }"#,
+    )?
+    .file("agents/route/kubernetes/deployment.yaml");
+    let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(&rendered)
+        .map(serde_yaml::Value::deserialize)
+        .collect::<Result<_, _>>()?;
+    let targets: Vec<_> = documents
+        .iter()
+        .filter(|document| document["kind"].as_str() == Some("HorizontalPodAutoscaler"))
+        .map(|hpa| hpa["spec"]["scaleTargetRef"]["name"].as_str().unwrap_or_default())
+        .collect();
+    assert_eq!(targets, ["route-blue", "route-green"]);
+    assert_eq!(documents[0]["metadata"]["name"].as_str(), Some("route-green"));
+    assert_eq!(documents[0]["spec"]["replicas"].as_u64(), Some(0), "El slot inactivo no escala");
+
+    Ok(())
 }
@@ -0,0 +1,81 @@
+use anyhow::Result;
+use kumeo_compiler::{
+    codegen::{agent::generate_agent, llm_provider::ProviderSettings, sink::PlanSink, template_manager::TemplateManager},
+    parser::parse,
+};
+use std::path::Path;
+
+const ASSISTANTS: &str = r#"
+workflow Assistants {
+    source: NATS("questions");
+    agents: [
+        LLM(id: "default", model: "gpt-4o"),
+        LLM(id: "claude", provider: Anthropic(model: "claude-3-5-sonnet"), options: { max_tokens: 1024 }),
+        LLM(id: "local", model: "llama3", provider: { ollama: { endpoint: "http://ollama.ml:11434/v1" } }),
+        LLM(id: "served", model: "meta-llama/Llama-3.1-8B-Instruct", provider: VLLM()),
+        LLM(id: "chained", model: "gpt-4o", providers: [OpenAI(), Anthropic(model: "claude-3-5-sonnet")], max_tokens: 512),
+        LLM(id: "nameless", provider: "anthropic", max_tokens: 256)
+    ];
+}
+"#;
+
+#[test]
+fn test_provider_settings_follow_the_provider() -> Result<()> {
+    let program = parse(ASSISTANTS)?;
+    let agents = &program.workflows[0].agents;
+
+    let default = ProviderSettings::for_agent(&agents[0])?.expect("Se esperaba un proveedor");
+    assert_eq!((default.provider, default.model.as_str()), ("openai", "gpt-4o"), "Sin provider se usa OpenAI");
+    assert_eq!(default.base_url, None);
+    assert_eq!(default.max_tokens, None);
+
+    let claude = ProviderSettings::for_agent(&agents[1])?.expect("Se esperaba un proveedor");
+    assert_eq!((claude.provider, claude.model.as_str()), ("anthropic", "claude-3-5-sonnet"));
+    assert_eq!(claude.max_tokens, Some(1024), "max_tokens se lee también de options");
+
+    let local = ProviderSettings::for_agent(&agents[2])?.expect("Se esperaba un proveedor");
+    assert_eq!((local.provider, local.model.as_str()), ("ollama", "llama3"), "El modelo por defecto es el del agente");
+    assert_eq!(local.base_url.as_deref(), Some("http://ollama.ml:11434/v1"));
+
+    let served = ProviderSettings::for_agent(&agents[3])?.expect("Se esperaba un proveedor");
+    assert_eq!(served.provider, "vllm");
+    assert_eq!(served.base_url.as_deref(), Some("http://vllm-meta-llama-llama-3-1-8b-instruct:8000"));
+
+    assert_eq!(ProviderSettings::for_agent(&agents[4])?, None, "Una cadena de proveedores no tiene proveedor único");
+    let error = ProviderSettings::for_agent(&agents[5]).unwrap_err().to_string();
+    assert!(error.contains("The Anthropic provider of nameless needs a model"), "{}", error);
+
+    let program = parse(r#"workflow A { agents: [LLM(id: "a", model: "claude-3-5-sonnet", provider: "anthropic")]; }"#)?;
+    let error = ProviderSettings::for_agent(&program.workflows[0].agents[0]).unwrap_err().to_string();
+    assert!(error.contains("requires max_tokens"), "{}", error);
+    Ok(())
+}
+
+#[test]
+fn test_llm_agents_switch_providers_by_configuration() -> Result<()> {
+    let program = parse(ASSISTANTS)?;
+    let workflow = &program.workflows[0];
+    let tera = TemplateManager::default().engine()?;
+    let mut sink = PlanSink::new();
+    generate_agent(workflow, &workflow.agents[1], Path::new("out"), &tera, None, &mut sink)?;
+    let generated = |file: &str| -> String {
+        let path = Path::new("out/agents/claude/src").join(file);
+        String::from_utf8_lossy(sink.contents(&path).unwrap_or_else(|| panic!("Falta {}", path.display()))).into_owned()
+    };
+
+    let config = generated("config.rs");
+    assert!(config.contains(r#"provider: "anthropic".to_string(),"#), "{}", config);
+    assert!(config.contains(r#"model: "claude-3-5-sonnet".to_string(),"#));
+    assert!(config.contains("base_url: None,"));
+    assert!(config.contains("fn default_max_tokens() -> u32 {\n    1024\n}"));
+    assert!(config.contains(r#""anthropic" => Some("ANTHROPIC_API_KEY"),"#), "Cada API lee su propia clave");
+    assert!(config.contains(r#"env::var("KUMEO_LLM_PROVIDER")"#), "El proveedor se cambia sin regenerar");
+
+    let client = generated("llm_client.rs");
+    assert!(client.contains("pub type StreamCallback"), "{}", client);
+    assert!(!client.contains("generate_streaming<F>"), "El trait debería poder usarse como dyn LLMClient");
+    for provider in ["\"openai\" | \"ollama\"", "\"anthropic\"", "\"vllm\"", "\"tgi\""] {
+        assert!(client.contains(&format!("{} => Arc::new(", provider)), "Falta el cliente de {}", provider);
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use super::{generate, rust_calls, rust_item, rust_tokens};
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::{MemoryConfig, MemoryStore},
+    codegen::memory::MemorySettings,
+    parser::parse,
+};
+
+const CHAT: &str = r#"
+workflow Chat {
+    source: NATS("messages");
+    agents: [
+        LLM(id: "reply", model: "llama3", memory: { scope: "user.session", window: 5, store: state, ttl: "30m" }),
+        LLM(id: "summary", model: "llama3")
+    ];
+}
+"#;
+
+#[test]
+fn test_memory_is_read_with_defaults() -> Result<()> {
+    let program = parse(CHAT)?;
+    let value = program.workflows[0].agents[0].config_value("memory").expect("Se esperaba memoria");
+    let config = MemoryConfig::from_value(value).map_err(anyhow::Error::msg)?;
+    assert_eq!(config, MemoryConfig { scope: "user.session".to_string(), window: 5, store: MemoryStore::State, ttl_secs: 1800 });
+
+    let program = parse(r#"workflow A { agents: [LLM(id: "a", model: "m", memory: { scope: session_id })]; }"#)?;
+    let config = MemoryConfig::from_value(program.workflows[0].agents[0].config_value("memory").unwrap()).map_err(anyhow::Error::msg)?;
+    assert_eq!(config.window, MemoryConfig::DEFAULT_WINDOW);
+    assert_eq!(config.ttl_secs, MemoryConfig::DEFAULT_TTL_SECS);
+
+    let invalid = [
+        (r#"{ window: 10 }"#, "missing scope"),
+        (r#"{ scope: "session_id", window: 0 }"#, "window must be a whole number"),
+        (r#"{ scope: "session_id", store: redis }"#, "unknown store 'redis'"),
+        (r#"{ scope: "session_id", ttl: "soon" }"#, "ttl must be a duration"),
+        (r#"{ scope: "session_id", size: 3 }"#, "unknown memory setting 'size'"),
+    ];
+    for (memory, expected) in invalid {
+        let source = format!(r#"workflow A {{ agents: [LLM(id: "a", model: "m", memory: {})]; }}"#, memory);
+        let program = parse(&source)?;
+        let error = MemoryConfig::from_value(program.workflows[0].agents[0].config_value("memory").unwrap()).unwrap_err();
+        assert!(error.to_string().contains(expected), "{}: {}", memory, error);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_llm_agents_keep_session_history() -> Result<()> {
+    let program = parse(CHAT)?;
+    let workflow = &program.workflows[0];
+    assert!(MemorySettings::for_agent(workflow, &workflow.agents[0])?.is_some());
+    assert_eq!(MemorySettings::for_agent(workflow, &workflow.agents[1])?, None);
+
+    let generated = generate(CHAT)?;
+    let memory = generated.rust("agents/reply/src/memory.rs");
+    assert_eq!(rust_item(&memory, "SCOPE"), rust_tokens(r#"&["user", "session"]"#));
+    assert_eq!(rust_item(&memory, "WINDOW"), rust_tokens("5"));
+    assert_eq!(rust_item(&memory, "TTL"), rust_tokens("Duration::from_secs(1800)"));
+    assert_eq!(rust_item(&memory, "KEY_PREFIX"), rust_tokens(r#""memory.Chat.reply""#));
+    let calls = rust_calls(&generated.rust("agents/reply/src/agent.rs"));
+    assert!(calls.contains("Session::load") && calls.contains(".remember"), "{:?}", calls);
+
+    // Without memory the module still compiles, and the agent doesn't use it
+    assert_eq!(rust_item(&generated.rust("agents/summary/src/memory.rs"), "SCOPE"), rust_tokens("&[]"));
+    let calls = rust_calls(&generated.rust("agents/summary/src/agent.rs"));
+    assert!(!calls.contains("Session::load") && !calls.contains(".remember"), "Sin memoria el agente no guarda sesiones");
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use super::{generate, rust_item, rust_tokens};
+use anyhow::Result;
+use kumeo_compiler::{codegen::missing_values::MissingValueSettings, parser::parse};
+
+const PAYMENTS: &str = r#"
+workflow Payments {
+    source: NATS("payments.raw");
+    target: NATS("payments.completed");
+    agents: [
+        MissingValueHandler(
+            id: "fill",
+            defaults: { country: "US", "customer.tier": "basic", flagged: false },
+            impute: { amount: median(), "customer.age": mean() },
+            window: 200
+        ),
+        MissingValueHandler(id: "last", impute: { currency: last() }),
+        DataProcessor(id: "clean")
+    ];
+}
+"#;
+
+#[test]
+fn test_missing_value_handlers_compile_their_defaults_and_imputations() -> Result<()> {
+    let program = parse(PAYMENTS)?;
+    let workflow = &program.workflows[0];
+    let fill = MissingValueSettings::for_agent(&workflow.agents[0])?.expect("Debería rellenar valores");
+    assert_eq!(MissingValueSettings::for_agent(&workflow.agents[2])?, None);
+
+    let defaults: Vec<(&str, &str, &str)> = fill
+        .defaults
+        .iter()
+        .map(|default| (default.field.as_str(), default.rust_path.as_str(), default.json.as_str()))
+        .collect();
+    assert_eq!(
+        defaults,
+        [
+            ("country", r#"&["country"]"#, r#""US""#),
+            ("customer.tier", r#"&["customer", "tier"]"#, r#""basic""#),
+            ("flagged", r#"&["flagged"]"#, "false"),
+        ]
+    );
+    let impute: Vec<(&str, &str, &str)> = fill
+        .impute
+        .iter()
+        .map(|impute| (impute.field.as_str(), impute.rust_path.as_str(), impute.imputation.as_str()))
+        .collect();
+    assert_eq!(impute, [("amount", r#"&["amount"]"#, "median"), ("customer.age", r#"&["customer", "age"]"#, "mean")]);
+    assert_eq!(fill.window, 200);
+
+    // The window has a default
+    let last = MissingValueSettings::for_agent(&workflow.agents[1])?.expect("Debería rellenar valores");
+    assert_eq!(last.window, 1000);
+    assert!(last.defaults.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_missing_value_handlers_render_their_constants() -> Result<()> {
+    let generated = generate(PAYMENTS)?;
+    let impute = generated.rust("agents/fill/src/impute.rs");
+    assert_eq!(
+        rust_item(&impute, "DEFAULTS"),
+        rust_tokens(r#"&[(&["country"], "\"US\""), (&["customer", "tier"], "\"basic\""), (&["flagged"], "false")]"#)
+    );
+    assert_eq!(
+        rust_item(&impute, "IMPUTATIONS"),
+        rust_tokens(r#"&[(&["amount"], Imputation::Median), (&["customer", "age"], Imputation::Mean)]"#)
+    );
+    assert_eq!(rust_item(&impute, "WINDOW"), rust_tokens("200"));
+
+    let last = generated.rust("agents/last/src/impute.rs");
+    assert_eq!(rust_item(&last, "DEFAULTS"), rust_tokens("&[]"));
+    assert_eq!(rust_item(&last, "WINDOW"), rust_tokens("1000"));
+    Ok(())
+}
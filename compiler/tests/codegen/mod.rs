@@ -1,6 +1,327 @@
 //! Integration tests for the code generation module
 
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::Workflow,
+    codegen::{
+        cluster::ClusterProgram,
+        console::CONSOLE_DIR,
+        contracts::attach,
+        generate_cluster, generate_console, generate_workflow,
+        nats::ExternalNats,
+        sink::PlanSink,
+        subworkflow::{self, expand},
+        template_manager::TemplateManager,
+        topics::wire,
+    },
+    parser::parse,
+};
+use quote::ToTokens;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use syn::visit::{self, Visit};
+use tempfile::tempdir;
+
 mod template_processor_tests;
 mod agent_tests;
+mod aggregator_tests;
+mod encoding_tests;
 mod kubernetes_tests;
+mod rule_engine_tests;
+mod bayesian_network_tests;
+mod custom_tests;
+mod normalizer_tests;
+mod missing_values_tests;
 mod taskfile_tests;
+mod terraform_tests;
+mod nomad_tests;
+mod output_tests;
+mod cluster_tests;
+mod subworkflow_tests;
+mod condition_tests;
+mod nats_tests;
+mod resilience_tests;
+mod drift_tests;
+mod topics_tests;
+mod contracts_tests;
+mod quality_tests;
+mod model_drift_tests;
+mod feature_store_tests;
+mod inference_tests;
+mod llm_provider_tests;
+mod failover_tests;
+mod guardrails_tests;
+mod memory_tests;
+mod review_tests;
+mod review_ui_tests;
+mod console_tests;
+mod readme_tests;
+mod sink_tests;
+mod auth_tests;
+mod determinism_tests;
+mod routing_tests;
+mod saga_tests;
+mod monitoring_tests;
+mod escape_tests;
+mod images_tests;
+mod dependencies_tests;
+mod scaffold_tests;
+
+/// Files generated for a program, by path relative to the output directory
+#[derive(Debug, PartialEq, Eq)]
+struct Generated {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl Generated {
+    /// Contents of a generated file, failing the test if it wasn't generated
+    fn file(&self, path: &str) -> String {
+        match self.files.get(Path::new(path)) {
+            Some(contents) => String::from_utf8_lossy(contents).into_owned(),
+            None => panic!("Debería generarse {}; se generaron {:?}", path, self.files.keys().collect::<Vec<_>>()),
+        }
+    }
+
+    /// Whether a file, or any file below a directory, was generated
+    fn has(&self, path: &str) -> bool {
+        self.files.keys().any(|file| file.starts_with(path))
+    }
+
+    /// A generated Rust file, failing the test if it isn't valid Rust
+    fn rust(&self, path: &str) -> syn::File {
+        let source = self.file(path);
+        syn::parse_file(&source).unwrap_or_else(|error| panic!("{} no es Rust válido: {}\n{}", path, error, source))
+    }
+
+    /// The module-level constants of a generated Python file, `None` without an interpreter
+    ///
+    /// The file is read with the interpreter's own parser, failing the test
+    /// if it isn't valid Python; constants are the names assigned at module
+    /// level, to their literal, such as `_WINDOW = 200` or `_FEATURE_VIEW = None`,
+    /// or else to their normalized source, such as `os.environ.get('URL', 'redis:6379')`.
+    fn python_constants(&self, path: &str) -> Option<BTreeMap<String, serde_json::Value>> {
+        const SCRIPT: &str = "import ast, json, sys\n\
+            constants = {}\n\
+            for node in ast.parse(sys.stdin.read()).body:\n\
+            \x20   target = node.targets[0] if isinstance(node, ast.Assign) and len(node.targets) == 1 else getattr(node, 'target', None)\n\
+            \x20   if isinstance(node, (ast.Assign, ast.AnnAssign)) and isinstance(target, ast.Name) and node.value is not None:\n\
+            \x20       try:\n\
+            \x20           constants[target.id] = ast.literal_eval(node.value)\n\
+            \x20       except ValueError:\n\
+            \x20           constants[target.id] = ast.unparse(node.value)\n\
+            json.dump(constants, sys.stdout)";
+        self.read_python(path, SCRIPT)
+    }
+
+    /// What a generated Python file calls, such as `serve_metrics` or `self.runtime.publish`, `None` without an interpreter
+    fn python_calls(&self, path: &str) -> Option<BTreeSet<String>> {
+        const SCRIPT: &str = "import ast, json, sys\n\
+            calls = {ast.unparse(node.func) for node in ast.walk(ast.parse(sys.stdin.read())) if isinstance(node, ast.Call)}\n\
+            json.dump(sorted(calls), sys.stdout)";
+        self.read_python(path, SCRIPT)
+    }
+
+    /// What a script prints as JSON when given a generated Python file, `None` without an interpreter
+    fn read_python<T: serde::de::DeserializeOwned>(&self, path: &str, script: &str) -> Option<T> {
+        let source = self.file(path);
+        let mut python = Command::new("python3")
+            .args(["-c", script])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .ok()?;
+        python.stdin.take().expect("stdin").write_all(source.as_bytes()).expect("stdin");
+        let output = python.wait_with_output().expect("python3");
+        assert!(output.status.success(), "{} no es Python válido: {}", path, String::from_utf8_lossy(&output.stderr));
+        Some(serde_json::from_slice(&output.stdout).expect("JSON"))
+    }
+}
+
+/// The tokens of a Rust expression or block, to compare parsed code whatever its formatting
+fn rust_tokens(source: &str) -> String {
+    let expr: syn::Expr = syn::parse_str(source).unwrap_or_else(|error| panic!("{} no es Rust válido: {}", source, error));
+    tokens(&expr)
+}
+
+/// The tokens of some parsed Rust, without trailing commas
+fn tokens(code: &impl ToTokens) -> String {
+    let tokens = code.to_token_stream().to_string();
+    tokens.replace(" ,]", "]").replace(" ,)", ")").replace(" , }", " }")
+}
+
+/// Items of a Rust file, with those of its inline modules and impl blocks
+fn rust_items(file: &syn::File) -> Vec<(String, String)> {
+    #[derive(Default)]
+    struct Items(Vec<(String, String)>);
+    impl<'ast> Visit<'ast> for Items {
+        fn visit_item_const(&mut self, item: &'ast syn::ItemConst) {
+            self.0.push((item.ident.to_string(), tokens(&item.expr)));
+        }
+        fn visit_item_static(&mut self, item: &'ast syn::ItemStatic) {
+            self.0.push((item.ident.to_string(), tokens(&item.expr)));
+        }
+        fn visit_item_fn(&mut self, item: &'ast syn::ItemFn) {
+            self.0.push((item.sig.ident.to_string(), tokens(&item.block)));
+            visit::visit_item_fn(self, item);
+        }
+        fn visit_impl_item_fn(&mut self, item: &'ast syn::ImplItemFn) {
+            self.0.push((item.sig.ident.to_string(), tokens(&item.block)));
+            visit::visit_impl_item_fn(self, item);
+        }
+    }
+    let mut items = Items::default();
+    items.visit_file(file);
+    items.0
+}
+
+/// The value of a `const` or `static`, or the body of a function, of a Rust file, as its tokens
+fn rust_item(file: &syn::File, name: &str) -> String {
+    let item = rust_items(file).into_iter().find(|(ident, _)| ident == name);
+    item.map(|(_, tokens)| tokens).unwrap_or_else(|| panic!("No se define {}", name))
+}
+
+/// The value of a string `const` of a Rust file, raw strings too
+fn rust_str(file: &syn::File, name: &str) -> String {
+    let literal = rust_item(file, name);
+    syn::parse_str::<syn::LitStr>(&literal).unwrap_or_else(|_| panic!("{} no es una cadena: {}", name, literal)).value()
+}
+
+/// The values a Rust file gives a struct field in struct expressions, as their tokens
+fn rust_field_values(file: &syn::File, field: &str) -> Vec<String> {
+    struct Fields<'a>(&'a str, Vec<String>);
+    impl<'ast> Visit<'ast> for Fields<'_> {
+        fn visit_field_value(&mut self, value: &'ast syn::FieldValue) {
+            if matches!(&value.member, syn::Member::Named(ident) if ident == self.0) {
+                self.1.push(tokens(&value.expr));
+            }
+            visit::visit_field_value(self, value);
+        }
+    }
+    let mut fields = Fields(field, Vec::new());
+    fields.visit_file(file);
+    fields.1
+}
+
+/// Every expression of a Rust file, as its tokens, to check what it evaluates
+fn rust_exprs(file: &syn::File) -> BTreeSet<String> {
+    #[derive(Default)]
+    struct Exprs(BTreeSet<String>);
+    impl<'ast> Visit<'ast> for Exprs {
+        fn visit_expr(&mut self, expr: &'ast syn::Expr) {
+            self.0.insert(tokens(expr));
+            visit::visit_expr(self, expr);
+        }
+    }
+    let mut exprs = Exprs::default();
+    exprs.visit_file(file);
+    exprs.0
+}
+
+/// What a Rust file calls: paths such as `crate::saga::record`, and methods as `.remember`
+fn rust_calls(file: &syn::File) -> BTreeSet<String> {
+    #[derive(Default)]
+    struct Calls(BTreeSet<String>);
+    impl<'ast> Visit<'ast> for Calls {
+        fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+            if let syn::Expr::Path(path) = &*call.func {
+                self.0.insert(path.to_token_stream().to_string().replace(' ', ""));
+            }
+            visit::visit_expr_call(self, call);
+        }
+        fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+            self.0.insert(format!(".{}", call.method));
+            visit::visit_expr_method_call(self, call);
+        }
+    }
+    let mut calls = Calls::default();
+    calls.visit_file(file);
+    calls.0
+}
+
+/// The string literals of a Rust file, those inside macros such as `format!` too
+fn rust_strings(file: &syn::File) -> Vec<String> {
+    fn collect(tokens: proc_macro2::TokenStream, strings: &mut Vec<String>) {
+        for token in tokens {
+            match token {
+                proc_macro2::TokenTree::Group(group) => collect(group.stream(), strings),
+                proc_macro2::TokenTree::Literal(literal) => {
+                    if let Ok(string) = syn::parse_str::<syn::LitStr>(&literal.to_string()) {
+                        strings.push(string.value());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    let mut strings = Vec::new();
+    collect(file.to_token_stream(), &mut strings);
+    strings
+}
+
+/// Options of [`generate_with`], as those of `kumeo generate`
+#[derive(Default)]
+struct GenerateOptions<'a> {
+    /// NATS the agents connect to instead of deploying their own
+    external_nats: Option<&'a ExternalNats>,
+    /// Whether the web console is generated too
+    console: bool,
+}
+
+/// Generate a program with the built-in templates, as `kumeo generate` does, without writing anything
+fn generate(source: &str) -> Result<Generated> {
+    generate_with(source, &GenerateOptions::default())
+}
+
+/// Generate a program as `kumeo generate` does for a `main.kumeo`, without writing anything
+///
+/// Its workflows are expanded and wired first: subworkflows are inlined, and
+/// agents get the topics and schemas they consume.
+fn generate_with(source: &str, options: &GenerateOptions<'_>) -> Result<Generated> {
+    let mut program = parse(source)?;
+    let schemas = program.topic_schemas();
+    let standalone = subworkflow::standalone(&program);
+    program.workflows.extend(standalone);
+    let workflows = program
+        .workflows
+        .iter()
+        .map(|workflow| Ok(attach(&wire(&expand(workflow, &program.subworkflows)?)?, &schemas)))
+        .collect::<Result<Vec<_>>>()?;
+    generate_workflows(&workflows, options)
+}
+
+/// Generate workflows already expanded, as [`generate_with`] does
+///
+/// A single workflow is generated into the output directory, several into
+/// `programs/main/<workflow>/` of a shared cluster.
+fn generate_workflows(workflows: &[Workflow], options: &GenerateOptions<'_>) -> Result<Generated> {
+    let output_dir = tempdir()?;
+    let output = output_dir.path();
+    let templates = TemplateManager::default();
+    let mut sink = PlanSink::new();
+
+    let layout: Vec<ClusterProgram> = match workflows {
+        [_] => Vec::new(),
+        workflows => workflows.iter().map(|workflow| ClusterProgram::for_workflow("main", workflow)).collect(),
+    };
+    for (index, workflow) in workflows.iter().enumerate() {
+        let dir = layout.get(index).map_or_else(|| output.to_path_buf(), |member| output.join(&member.dir));
+        generate_workflow(workflow, &dir, options.external_nats, &templates, &mut sink)?;
+    }
+    if !layout.is_empty() {
+        generate_cluster(&layout, output, options.external_nats, &templates, &mut sink)?;
+    }
+    if options.console {
+        let workflows: Vec<&Workflow> = workflows.iter().collect();
+        generate_console(&workflows, &output.join(CONSOLE_DIR), !layout.is_empty(), options.external_nats, &templates, &mut sink)?;
+    }
+
+    let mut files = BTreeMap::new();
+    for planned in sink.plan()? {
+        let contents = sink.contents(&planned.path).unwrap_or_default().to_vec();
+        files.insert(planned.path.strip_prefix(output)?.to_path_buf(), contents);
+    }
+    Ok(Generated { files })
+}
@@ -0,0 +1,80 @@
+use super::generate;
+use anyhow::Result;
+use kumeo_compiler::{ast::Value, codegen::model_drift::ModelDriftSettings, parser::parse};
+use serde_json::json;
+
+const ORDERS: &str = r#"
+workflow Orders {
+    source: NATS("orders");
+    target: NATS("orders.scored");
+    agents: [
+        MLModel(
+            id: "score",
+            model_path: "models/score.onnx",
+            drift: {
+                reference: resource("s3://profiles/orders/score.json"),
+                method: "js",
+                window: 250,
+                features: ["total", "customer.country"]
+            }
+        ),
+        MLModel(id: "rank", model_path: "models/rank.onnx")
+    ];
+}
+"#;
+
+#[test]
+fn test_drift_references_are_read_as_their_uri() -> Result<()> {
+    let program = parse(ORDERS)?;
+    let Some(Value::Object(drift)) = program.workflows[0].agents[0].config_value("drift") else {
+        panic!("Se esperaba la opción drift");
+    };
+    assert_eq!(drift.get("reference"), Some(&Value::String("s3://profiles/orders/score.json".to_string())));
+    Ok(())
+}
+
+#[test]
+fn test_ml_agents_track_drift_against_their_reference() -> Result<()> {
+    let program = parse(ORDERS)?;
+    let workflow = &program.workflows[0];
+
+    let drift = ModelDriftSettings::for_agent(workflow, &workflow.agents[0])?.expect("Se esperaba drift");
+    assert_eq!(drift.reference, "s3://profiles/orders/score.json");
+    assert_eq!(drift.method, "js");
+    assert_eq!(drift.threshold, 0.1, "El umbral por defecto depende del método");
+    assert_eq!(drift.window, 250);
+    assert_eq!(drift.python_features, r#"["total","customer.country"]"#);
+    assert_eq!(drift.subject, "kumeo.monitor.orders");
+    assert_eq!(ModelDriftSettings::for_agent(workflow, &workflow.agents[1])?, None);
+
+    let generated = generate(ORDERS)?;
+    // Without an interpreter the agents can't be read
+    if let Some(constants) = generated.python_constants("agents/score/src/kumeo_agent_score/agent.py") {
+        assert_eq!(constants["_DRIFT_REFERENCE"], json!("s3://profiles/orders/score.json"));
+        assert_eq!(constants["_DRIFT_METHOD"], json!("js"));
+        assert_eq!(constants["_DRIFT_WINDOW"], json!(250));
+        assert_eq!(constants["_DRIFT_FEATURES"], json!(["total", "customer.country"]));
+        assert_eq!(constants["_DRIFT_SUBJECT"], json!("kumeo.monitor.orders"));
+    }
+    if let Some(constants) = generated.python_constants("agents/rank/src/kumeo_agent_rank/agent.py") {
+        assert_eq!(constants["_DRIFT_REFERENCE"], json!(null), "Sin drift no debería cargar un perfil");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_drift_defaults_to_psi() -> Result<()> {
+    let program = parse(
+        r#"workflow Orders {
+            source: NATS("orders");
+            agents: [MLModel(id: "score", drift: { reference: "file://profiles/score.json" })];
+        }"#,
+    )?;
+    let workflow = &program.workflows[0];
+    let drift = ModelDriftSettings::for_agent(workflow, &workflow.agents[0])?.expect("Se esperaba drift");
+    assert_eq!(drift.method, "psi");
+    assert_eq!(drift.threshold, 0.2);
+    assert_eq!(drift.window, 1000);
+    assert_eq!(drift.python_features, "[]");
+    Ok(())
+}
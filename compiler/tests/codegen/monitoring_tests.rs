@@ -0,0 +1,85 @@
+use super::generate;
+use anyhow::Result;
+use kumeo_compiler::{codegen::monitoring::MonitoringSettings, parser::parse};
+use serde::Deserialize;
+
+const WORKFLOW: &str = r#"workflow Review {
+    source: NATS("drafts");
+    agents: [
+        LLM(id: "write", model: "gpt-4", input: "drafts", output: "written"),
+        Router(id: "route", input: "written")
+    ];
+    monitor: { alert_threshold: 3, alert_error_rate: 0.1, alert_guardrail_blocks: 5, alert_unavailable: "10m", alert_severity: "critical" };
+    deployment: { namespace: "editorial", rollout: blue_green };
+}"#;
+
+#[test]
+fn test_monitoring_follows_the_monitor_block() -> Result<()> {
+    let program = parse(WORKFLOW)?;
+    let settings = MonitoringSettings::for_workflow(&program.workflows[0])?.expect("Debería monitorizarse");
+    assert_eq!(settings.namespace, "editorial");
+    assert!(settings.metrics_enabled && !settings.inference);
+
+    let alerts: Vec<&str> = settings.alerts.iter().map(|alert| alert.name).collect();
+    assert_eq!(alerts, ["KumeoAgentRestarting", "KumeoLLMErrorRate", "KumeoGuardrailBlocks", "KumeoAgentUnavailable"]);
+    assert_eq!(
+        settings.alerts[0].expr,
+        "increase(kube_pod_container_status_restarts_total{pod=~\"(write|route)-.*\"}[5m]) >= 3"
+    );
+    assert!(settings.alerts[1].expr.ends_with("> 0.1"), "{}", settings.alerts[1].expr);
+    // Los slots blue/green de un agente cuentan como uno
+    assert!(settings.alerts[3].expr.contains("deployment=~\"(write|route)(-(blue|green))?\""), "{}", settings.alerts[3].expr);
+    assert_eq!(settings.alerts[3].for_duration.as_deref(), Some("10m"));
+
+    // La latencia de extremo a extremo se alerta sobre el histograma del runtime
+    let program = parse(&WORKFLOW.replace("alert_severity", "alert_latency_p99: \"1500ms\", alert_severity"))?;
+    let settings = MonitoringSettings::for_workflow(&program.workflows[0])?.unwrap();
+    let latency = settings.alerts.last().unwrap();
+    assert_eq!(latency.name, "KumeoEndToEndLatency");
+    assert_eq!(
+        latency.expr,
+        "histogram_quantile(0.99, sum by (le) (rate(kumeo_e2e_latency_seconds_bucket{workflow=\"Review\"}[5m]))) > 1.5"
+    );
+
+    let program = parse(&WORKFLOW.replace("monitor: {", "monitor: { metrics_enabled: false,"))?;
+    assert!(!MonitoringSettings::for_workflow(&program.workflows[0])?.unwrap().metrics_enabled);
+    let program = parse(&WORKFLOW.replace(", alert_unavailable: \"10m\"", "").replace("rollout: blue_green", "platform: \"nomad\""))?;
+    assert_eq!(MonitoringSettings::for_workflow(&program.workflows[0])?, None, "En Nomad no hay Prometheus Operator");
+    Ok(())
+}
+
+#[test]
+fn test_monitoring_manifests_and_dashboard() -> Result<()> {
+    let generated = generate(WORKFLOW)?;
+    let manifests = generated.file("kubernetes/monitoring/monitoring.yaml");
+    let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(&manifests)
+        .map(serde_yaml::Value::deserialize)
+        .collect::<Result<_, _>>()?;
+    let kinds: Vec<&str> = documents.iter().filter_map(|document| document["kind"].as_str()).collect();
+    assert_eq!(kinds, ["PodMonitor", "PrometheusRule", "ConfigMap"], "Sin servidores de inferencia no hay ServiceMonitor");
+
+    let rule = &documents[1]["spec"]["groups"][0]["rules"][1];
+    assert_eq!(rule["alert"].as_str(), Some("KumeoLLMErrorRate"));
+    assert_eq!(rule["labels"]["severity"].as_str(), Some("critical"));
+    assert!(rule["annotations"]["summary"].as_str().unwrap().contains("{{ $labels.agent }}"), "{:?}", rule);
+
+    // El panel se importa desde el ConfigMap y también como JSON
+    let embedded = documents[2]["data"]["review.json"].as_str().expect("El ConfigMap debería llevar el panel");
+    let dashboard: serde_json::Value = serde_json::from_str(&generated.file("kubernetes/monitoring/dashboard.json"))?;
+    assert_eq!(serde_json::from_str::<serde_json::Value>(embedded)?, dashboard);
+    assert_eq!(dashboard["uid"], "kumeo-review");
+    assert_eq!(dashboard["panels"].as_array().map(Vec::len), Some(5));
+    let latency = &dashboard["panels"][4];
+    assert_eq!(latency["title"], "End-to-end latency");
+    let legends: Vec<&str> = latency["targets"].as_array().unwrap().iter().filter_map(|target| target["legendFormat"].as_str()).collect();
+    assert_eq!(legends, ["p50", "p90", "p99"]);
+    assert_eq!(latency["targets"][2]["refId"], "C");
+
+    // Sin bloque monitor no se genera nada
+    let generated = generate(&WORKFLOW.replace(
+        "monitor: { alert_threshold: 3, alert_error_rate: 0.1, alert_guardrail_blocks: 5, alert_unavailable: \"10m\", alert_severity: \"critical\" };",
+        "",
+    ))?;
+    assert!(!generated.has("kubernetes/monitoring"));
+    Ok(())
+}
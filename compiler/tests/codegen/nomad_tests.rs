@@ -0,0 +1,100 @@
+use super::{generate, generate_with, GenerateOptions};
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::Platform,
+    codegen::{
+        nats::ExternalNats,
+        nomad::{NomadSettings, NATS_SERVICE},
+    },
+    parser::parse,
+};
+
+const WORKFLOW: &str = r#"workflow Orders {
+    source: Kafka("orders");
+    target: NATS("scored");
+    agents: [
+        LLM(id: "classify", model: "gpt-4", api_key: "${env.OPENAI_API_KEY}", input: "orders", output: "classified"),
+        MLModel(id: "score", model: "s3://models/scorer.onnx", input: "classified")
+    ];
+    deployment: {
+        platform: "nomad",
+        namespace: "shop",
+        replicas: 3,
+        resources: { cpu: "500m", memory: "1Gi" },
+        require_signed: true,
+        storage: { score: { size: "10Gi", path: "/data" } }
+    };
+}"#;
+
+#[test]
+fn test_platform_is_parsed() -> Result<()> {
+    let program = parse(WORKFLOW)?;
+    assert_eq!(program.workflows[0].platform(), Platform::Nomad);
+
+    let program = parse(r#"workflow Orders { source: Kafka("orders"); agents: [Router(id: "route")]; }"#)?;
+    assert_eq!(program.workflows[0].platform(), Platform::Kubernetes, "Kubernetes sigue siendo la plataforma por defecto");
+
+    let message = parse(r#"workflow Orders { agents: [Router(id: "route")]; deployment: { platform: "swarm" }; }"#)
+        .unwrap_err()
+        .to_string();
+    assert!(message.contains("Unknown deployment platform swarm, expected kubernetes or nomad"), "{}", message);
+    Ok(())
+}
+
+#[test]
+fn test_settings_follow_the_deployment() -> Result<()> {
+    let program = parse(WORKFLOW)?;
+    let workflow = &program.workflows[0];
+    let settings = NomadSettings::for_agent(workflow, &workflow.agents[1], None)?;
+    assert_eq!(settings.job_type, "service");
+    assert_eq!(settings.namespace, "shop");
+    assert_eq!(settings.count, 3);
+    assert_eq!((settings.cpu_mhz, settings.memory_mb), (500, 1024));
+    assert_eq!(settings.nats_service, Some(NATS_SERVICE), "Sin NATS externo se busca el del workflow");
+
+    let nats = ExternalNats::new("nats://nats.shared:4222", None)?;
+    assert_eq!(NomadSettings::for_agent(workflow, &workflow.agents[1], Some(&nats))?.nats_service, None);
+    Ok(())
+}
+
+#[test]
+fn test_job_shares_the_agent_context() -> Result<()> {
+    let generated = generate(WORKFLOW)?;
+    let job = generated.file("agents/classify/nomad/job.nomad.hcl");
+    assert!(job.starts_with("job \"classify\" {\n  namespace   = \"shop\""), "{}", job);
+    assert!(job.contains("    count = 3\n"), "{}", job);
+    assert!(job.contains("KAFKA_GROUP_ID = \"Orders\""), "{}", job);
+    assert!(job.contains("KUMEO_REQUIRE_SIGNED = \"true\""), "{}", job);
+
+    // Secrets are read from Nomad Variables with Nomad's own template syntax
+    assert!(
+        job.contains("{{ with nomadVar \"kumeo/kumeo-env\" }}OPENAI_API_KEY={{ .OPENAI_API_KEY }}{{ end }}"),
+        "La clave debería leerse de las variables de Nomad: {}",
+        job
+    );
+    assert!(job.contains("{{ range nomadService \"nats\" }}NATS_URL=nats://{{ .Address }}:{{ .Port }}{{ end }}"), "{}", job);
+    assert!(job.contains("destination = \"secrets/trusted-keys/minisign.pub\""), "{}", job);
+    assert!(!job.contains("volume \"data\""), "Solo el agente con almacenamiento monta un volumen: {}", job);
+
+    let job = generated.file("agents/score/nomad/job.nomad.hcl");
+    assert!(job.contains("source          = \"score-data\""), "{}", job);
+    assert!(job.contains("destination = \"/data\""), "{}", job);
+    assert!(job.contains("cpu    = 500\n        memory = 1024"), "{}", job);
+    Ok(())
+}
+
+#[test]
+fn test_nats_job_is_only_generated_without_external_nats() -> Result<()> {
+    let nats = generate(WORKFLOW)?.file("nomad/nats.nomad.hcl");
+    assert!(nats.contains("namespace   = \"shop\""), "{}", nats);
+    assert!(nats.contains("name     = \"nats\""), "{}", nats);
+
+    let external = ExternalNats::new("nats://nats.shared:4222", None)?;
+    let generated = generate_with(WORKFLOW, &GenerateOptions { external_nats: Some(&external), ..Default::default() })?;
+    assert!(!generated.has("nomad"), "Con un NATS externo no se despliega otro");
+
+    let job = generated.file("agents/classify/nomad/job.nomad.hcl");
+    assert!(job.contains("NATS_URL = \"nats://nats.shared:4222\""), "{}", job);
+    assert!(!job.contains("nomadService"), "{}", job);
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use super::{generate, rust_item, rust_tokens};
+use anyhow::Result;
+use kumeo_compiler::{codegen::normalizer::NormalizerSettings, parser::parse};
+
+const PAYMENTS: &str = r#"
+workflow Payments {
+    source: NATS("payments.raw");
+    target: NATS("payments.normalized");
+    agents: [
+        DataNormalizer(
+            id: "normalize",
+            mappings: { amount: data.payment.amount, currency: data.payment.currency },
+            scale: { amount: minmax(min: 0, max: 10000), score: zscore(mean: 50, std: 12.5) }
+        ),
+        DataProcessor(id: "clean")
+    ];
+}
+"#;
+
+#[test]
+fn test_normalizers_compile_their_mappings_and_scalers() -> Result<()> {
+    let program = parse(PAYMENTS)?;
+    let workflow = &program.workflows[0];
+    let normalizer = NormalizerSettings::for_agent(&workflow.agents[0])?.expect("Debería normalizar");
+    assert_eq!(NormalizerSettings::for_agent(&workflow.agents[1])?, None);
+
+    let mappings: Vec<(&str, &str, &str)> = normalizer
+        .mappings
+        .iter()
+        .map(|mapping| (mapping.field.as_str(), mapping.source.as_str(), mapping.rust_path.as_str()))
+        .collect();
+    assert_eq!(
+        mappings,
+        [
+            ("amount", "data.payment.amount", r#"&["payment", "amount"]"#),
+            ("currency", "data.payment.currency", r#"&["payment", "currency"]"#),
+        ]
+    );
+    // Scalers are compiled to what they subtract and divide by
+    let scale: Vec<(&str, &str, &str, &str)> = normalizer
+        .scale
+        .iter()
+        .map(|scale| (scale.field.as_str(), scale.scaler.as_str(), scale.offset.as_str(), scale.divisor.as_str()))
+        .collect();
+    assert_eq!(scale, [("amount", "minmax", "0.0", "10000.0"), ("score", "zscore", "50.0", "12.5")]);
+    Ok(())
+}
+
+#[test]
+fn test_normalizers_render_their_constants() -> Result<()> {
+    let normalize = generate(PAYMENTS)?.rust("agents/normalize/src/normalize.rs");
+    assert_eq!(
+        rust_item(&normalize, "MAPPINGS"),
+        rust_tokens(r#"&[("amount", &["payment", "amount"]), ("currency", &["payment", "currency"])]"#)
+    );
+    assert_eq!(rust_item(&normalize, "SCALE"), rust_tokens(r#"&[("amount", 0.0, 10000.0), ("score", 50.0, 12.5)]"#));
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::{Agent, AgentType, Span, Workflow, WorkflowMode},
+    codegen::{output::OutputManifest, sink::FsSink},
+};
+use tempfile::tempdir;
+
+fn workflow_with(agent_ids: &[&str]) -> Workflow {
+    Workflow {
+        name: "test-workflow".to_string(),
+        source: None,
+        target: None,
+        context: None,
+        preprocessors: None,
+        agents: agent_ids
+            .iter()
+            .map(|id| Agent {
+                id: Some(id.to_string()),
+                agent_type: AgentType::LLM,
+                config: vec![],
+                doc: None,
+                span: Span::default(),
+            })
+            .collect(),
+        monitor: None,
+        deployment: None,
+        version: None,
+        doc: None,
+        mode: WorkflowMode::Stream,
+        calls: vec![],
+        tests: vec![],
+        span: Span::default(),
+    }
+}
+
+#[test]
+fn test_removed_agents_are_pruned() -> Result<()> {
+    let output_dir = tempdir()?;
+    let output = output_dir.path();
+    for agent in ["scorer", "router"] {
+        std::fs::create_dir_all(output.join("agents").join(agent).join("kubernetes"))?;
+        std::fs::write(output.join("agents").join(agent).join("kubernetes/deployment.yaml"), "kind: Deployment")?;
+    }
+
+    let mut manifest = OutputManifest::load(output)?;
+    manifest.record(&workflow_with(&["scorer", "router"]), output, &FsSink)?;
+    manifest.save(output, &mut FsSink)?;
+
+    // The router is removed from the program: it is flagged until pruned
+    let current = workflow_with(&["scorer"]);
+    let mut manifest = OutputManifest::load(output)?;
+    manifest.record(&current, output, &FsSink)?;
+    let orphans = manifest.orphans(&current);
+    assert_eq!(orphans.keys().copied().collect::<Vec<_>>(), ["router"]);
+    assert!(orphans["router"].contains("agents/router/kubernetes/deployment.yaml"));
+
+    // Files added by hand next to the generated ones are kept
+    std::fs::write(output.join("agents/router/NOTES.md"), "keep me")?;
+    let deleted = manifest.prune(&current, output)?;
+    assert_eq!(deleted, [output.join("agents/router/kubernetes/deployment.yaml")]);
+    assert!(!output.join("agents/router/kubernetes").exists());
+    assert!(output.join("agents/router/NOTES.md").exists());
+    assert!(output.join("agents/scorer/kubernetes/deployment.yaml").exists());
+    assert!(manifest.orphans(&current).is_empty());
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use super::generate;
+use anyhow::Result;
+use kumeo_compiler::{
+    codegen::{contracts::attach, quality::QualitySettings, topics::wire},
+    parser::parse,
+};
+use serde_json::json;
+
+const ORDERS: &str = r#"
+workflow Orders {
+    source: NATS("orders");
+    target: NATS("orders.scored");
+    agents: [
+        DataProcessor(id: "clean", output: "orders.clean", output_schema: { id: "string", total: "number", coupon: "string?" }),
+        MLModel(id: "score", model_path: "models/score.onnx", input: "orders.clean"),
+        QualityMonitor(
+            id: "orders_quality",
+            input: "orders.clean",
+            output: "orders.quality",
+            sample_rate: 0.25,
+            window: 200,
+            metrics: ["null_rate", "mean", "conformity"],
+            thresholds: { mean: 0.2, conformity: 0.05 },
+            fields: ["total", "coupon"]
+        )
+    ];
+}
+"#;
+
+#[test]
+fn test_quality_monitors_are_compiled_from_their_options() -> Result<()> {
+    let program = parse(ORDERS)?;
+    let workflow = attach(&wire(&program.workflows[0])?, &program.topic_schemas());
+    let monitor = &workflow.agents[2];
+
+    let quality = QualitySettings::for_agent(monitor)?.expect("Se esperaba un monitor de calidad");
+    assert_eq!(quality.sample_rate, 0.25);
+    assert_eq!(quality.window, 200);
+    assert_eq!(quality.metrics, vec!["null_rate", "mean", "conformity"]);
+    assert_eq!(quality.python_thresholds, r#"{"conformity":0.05,"mean":0.2}"#);
+    assert_eq!(quality.python_fields, r#"["total","coupon"]"#);
+    assert_eq!(QualitySettings::for_agent(&workflow.agents[1])?, None);
+
+    // Without an interpreter the agent can't be read
+    let generated = generate(ORDERS)?;
+    if let Some(constants) = generated.python_constants("agents/orders_quality/src/kumeo_agent_orders_quality/agent.py") {
+        assert_eq!(constants["_SAMPLE_RATE"], json!(0.25));
+        assert_eq!(constants["_WINDOW"], json!(200));
+        assert_eq!(constants["_METRICS"], json!(["null_rate", "mean", "conformity"]));
+        assert_eq!(constants["_THRESHOLDS"], json!({ "conformity": 0.05, "mean": 0.2 }));
+        assert_eq!(constants["_INPUT_SCHEMA"], json!({ "coupon": "string?", "id": "string", "total": "number" }));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_quality_monitors_default_to_every_metric() -> Result<()> {
+    let program = parse(
+        r#"workflow Orders {
+            source: NATS("orders");
+            agents: [QualityMonitor(id: "quality")];
+        }"#,
+    )?;
+    let quality = QualitySettings::for_agent(&program.workflows[0].agents[0])?.expect("Se esperaba un monitor de calidad");
+    assert_eq!(quality.sample_rate, 0.1);
+    assert_eq!(quality.window, 500);
+    assert_eq!(quality.metrics, vec!["null_rate", "mean", "stddev", "min", "max", "distinct", "conformity"]);
+    assert_eq!(quality.python_thresholds, "{}");
+    assert_eq!(quality.python_fields, "[]");
+    Ok(())
+}
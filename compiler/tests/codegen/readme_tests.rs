@@ -0,0 +1,38 @@
+use anyhow::Result;
+use kumeo_compiler::{
+    codegen::{agent::generate_agent, sink::FsSink, template_manager::TemplateManager},
+    parser::parse,
+};
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_agent_readme_has_its_documentation() -> Result<()> {
+    let program = parse(
+        r#"workflow Orders {
+            agents: [
+                /// Flags the orders that look fraudulent
+                ///
+                /// Trained on last year's chargebacks.
+                DataProcessor(id: "screen"),
+                Router(id: "route")
+            ];
+        }"#,
+    )?;
+    let tera = TemplateManager::default().engine()?;
+    let output = tempdir()?;
+    let workflow = &program.workflows[0];
+    for agent in &workflow.agents {
+        generate_agent(workflow, agent, output.path(), &tera, None, &mut FsSink)?;
+    }
+
+    let screen = fs::read_to_string(output.path().join("agents/screen/README.md"))?;
+    assert!(
+        screen.contains("DataProcessor agent of the `Orders` workflow, generated by Kumeo.\n\nFlags the orders that look fraudulent\n\nTrained on last year's chargebacks.\n\n## Image"),
+        "{}",
+        screen
+    );
+    let route = fs::read_to_string(output.path().join("agents/route/README.md"))?;
+    assert!(route.contains("generated by Kumeo.\n\n## Image"), "{}", route);
+    Ok(())
+}
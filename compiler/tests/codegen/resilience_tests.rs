@@ -0,0 +1,102 @@
+use super::{generate, rust_exprs, rust_item, rust_strings, rust_tokens};
+use anyhow::Result;
+use kumeo_compiler::{
+    codegen::resilience::{FallbackSettings, RetrySettings},
+    parser::parse,
+};
+
+/// A workflow whose only agent takes some options
+fn scoring(options: &str) -> String {
+    format!(
+        r#"workflow Scoring {{
+            source: NATS("in");
+            agents: [DataProcessor(id: "score", {})];
+        }}"#,
+        options
+    )
+}
+
+fn resilience_settings(options: &str) -> Result<(Option<RetrySettings>, FallbackSettings)> {
+    let program = parse(&scoring(options))?;
+    let agent = &program.workflows[0].agents[0];
+    Ok((RetrySettings::for_agent(agent)?, FallbackSettings::for_agent(agent)?))
+}
+
+/// The retry and fallback module generated for an agent with some options
+fn render_rust(options: &str) -> Result<syn::File> {
+    Ok(generate(&scoring(options))?.rust("agents/score/src/resilience.rs"))
+}
+
+#[test]
+fn test_retry_and_default_fallback_are_compiled() -> Result<()> {
+    let options = r#"retry: { max_attempts: 4, backoff: "500ms,2s" }, fallback: { action: "use_default", default: { label: "unknown", score: 0 } }"#;
+    let (retry, fallback) = resilience_settings(options)?;
+
+    let retry_settings = retry.clone().expect("Se esperaba una política retry");
+    assert_eq!(retry_settings.max_attempts, 4);
+    assert_eq!(retry_settings.backoff_ms, vec![500, 2000]);
+    assert_eq!(fallback.action, "use_default");
+    assert_eq!(
+        fallback.python_default.as_deref(),
+        Some(r#""{\"label\":\"unknown\",\"score\":0}""#)
+    );
+
+    let rendered = render_rust(options)?;
+    assert_eq!(
+        rust_item(&rendered, "policy"),
+        rust_tokens("{ RetryPolicy { max_attempts: 4, backoff: vec![Duration::from_millis(500), Duration::from_millis(2000)] } }")
+    );
+    let default = rust_tokens(r#""{\"label\":\"unknown\",\"score\":0}".as_bytes().to_vec()"#);
+    assert!(rust_exprs(&rendered).contains(&default), "Debería publicar el resultado por defecto");
+    Ok(())
+}
+
+#[test]
+fn test_agents_without_policies_fail_after_one_attempt() -> Result<()> {
+    let (retry, fallback) = resilience_settings(r#"schema: "in""#)?;
+    assert_eq!(retry, None);
+    assert_eq!(fallback.action, "fail");
+
+    let rendered = render_rust(r#"schema: "in""#)?;
+    assert_eq!(rust_item(&rendered, "policy"), rust_tokens("{ RetryPolicy::none() }"));
+    assert_eq!(rust_item(&rendered, "fallback"), rust_tokens("{ let _ = (runtime, msg); Err(error) }"));
+
+    let options = r#"fallback: { action: "dead_letter", subject: "score.failed" }"#;
+    let (_, fallback) = resilience_settings(options)?;
+    assert_eq!(fallback.subject.as_deref(), Some("score.failed"));
+    let rendered = render_rust(options)?;
+    let publish = rust_tokens(r#"runtime.publish("score.failed", msg.payload.to_vec()).await?"#);
+    assert!(rust_exprs(&rendered).contains(&publish), "Debería publicar el mensaje en score.failed");
+    Ok(())
+}
+
+#[test]
+fn test_failed_messages_are_retried_later() -> Result<()> {
+    let options = r#"fallback: { action: "retry_later", after: "10m" }"#;
+    let (_, fallback) = resilience_settings(options)?;
+    assert_eq!(fallback.action, "retry_later");
+    assert_eq!((fallback.delay_ms, fallback.max_delays), (Some(600_000), Some(3)));
+    // The count of delays is kept at least a day
+    assert_eq!(fallback.delays_ttl_secs, Some(86_400));
+
+    let rendered = render_rust(options)?;
+    let exprs = rust_exprs(&rendered);
+    assert!(exprs.contains(&rust_tokens("delays >= 3")), "Debería rendirse tras 3 retrasos");
+    assert!(exprs.contains(&rust_tokens("runtime.delay(&input_topic, msg.payload.to_vec(), Duration::from_millis(600000)).await?")));
+    assert!(rust_strings(&rendered).contains(&"delays.score.{:016x}".to_string()));
+    rust_item(&rendered, "fnv1a");
+
+    let (_, fallback) = resilience_settings(r#"fallback: { action: "retry_later", after: "12h", max_delays: 5 }"#)?;
+    assert_eq!(fallback.delays_ttl_secs, Some(12 * 3600 * 6), "La cuenta debería sobrevivir a todos los retrasos");
+
+    for (fallback, expected) in [
+        (r#"{ action: "retry_later" }"#, "requires an after"),
+        (r#"{ action: "retry_later", after: "soon" }"#, "invalid after 'soon'"),
+        (r#"{ action: "retry_later", after: "1m", max_delays: 0 }"#, "max_delays must be a whole number"),
+        (r#"{ action: "retry_later", after: "1m", subject: "x" }"#, "'subject' is not a setting of the retry_later action"),
+    ] {
+        let error = resilience_settings(&format!("fallback: {}", fallback)).unwrap_err();
+        assert!(error.to_string().contains(expected), "{}: {}", fallback, error);
+    }
+    Ok(())
+}
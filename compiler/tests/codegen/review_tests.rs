@@ -0,0 +1,164 @@
+use super::{generate, rust_item, rust_tokens};
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::{HumanReviewConfig, ReviewAuditConfig, SlaBreachAction},
+    codegen::review::{ReviewSettings, DEFAULT_AUDIT_SIGNING_SECRET},
+    parser::parse,
+};
+use serde::Deserialize;
+
+const APPROVALS: &str = r#"
+workflow Payments {
+    source: NATS("payments");
+    agents: [
+        HumanReview(
+            id: "approval",
+            sla: "4h",
+            on_sla_breach: reject,
+            escalation: [
+                { after: "2h", notify: ["finance"], via: "slack" },
+                { after: "3h", notify: ["cfo"], via: "pager" }
+            ],
+            delegation: { finance: ["alice", "bob"] }
+        ),
+        Router(id: "route")
+    ];
+}
+"#;
+
+#[test]
+fn test_review_sla_is_read() -> Result<()> {
+    let program = parse(APPROVALS)?;
+    let config = HumanReviewConfig::from_agent(&program.workflows[0].agents[0]).map_err(anyhow::Error::msg)?;
+    assert_eq!(config.sla_secs, Some(14_400));
+    assert_eq!(config.on_sla_breach, SlaBreachAction::Reject);
+    assert_eq!(config.escalation.len(), 2);
+    assert_eq!(config.escalation[1].after_secs, 10_800);
+    assert_eq!(config.group_of("bob"), Some("finance"));
+    assert_eq!(config.group_of("cfo"), None);
+
+    let invalid = [
+        (r#"sla: "soon""#, "sla must be a duration"),
+        (r#"on_sla_breach: approve"#, "on_sla_breach needs an sla"),
+        (r#"sla: "4h", on_sla_breach: escalate"#, "unknown on_sla_breach 'escalate'"),
+        (r#"sla: "4h", escalation: [{ after: "5h", notify: ["cfo"], via: "slack" }]"#, "once the sla has lapsed"),
+        (r#"escalation: [{ after: "2h", notify: ["a"], via: "slack" }, { after: "1h", notify: ["b"], via: "slack" }]"#, "increasing order"),
+        (r#"escalation: [{ after: "2h", notify: [], via: "slack" }]"#, "notify must be a non-empty list"),
+        (r#"escalation: [{ after: "2h", notify: ["a"], via: "Slack Ops" }]"#, "via must name a notification channel"),
+        (r#"escalation: [{ after: "2h", notify: ["a"], via: "slack", retry: 3 }]"#, "unknown escalation setting 'retry'"),
+        (r#"delegation: { finance: ["alice"], audit: ["alice"] }"#, "reviewer 'alice' belongs to delegation groups 'audit' and 'finance'"),
+    ];
+    for (options, expected) in invalid {
+        let source = format!(r#"workflow A {{ agents: [HumanReview(id: "a", {})]; }}"#, options);
+        let program = parse(&source)?;
+        let error = HumanReviewConfig::from_agent(&program.workflows[0].agents[0]).unwrap_err();
+        assert!(error.to_string().contains(expected), "{}: {}", options, error);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_reviewers_hand_their_timers_to_the_scheduler() -> Result<()> {
+    let program = parse(APPROVALS)?;
+    let workflow = &program.workflows[0];
+    let review = ReviewSettings::for_agent(workflow, &workflow.agents[0])?;
+    assert_eq!(ReviewSettings::for_agent(workflow, &workflow.agents[1])?, None);
+    assert_eq!(review.as_ref().map(|review| review.channels.clone()), Some(vec!["pager".to_string(), "slack".to_string()]));
+
+    let sla = generate(APPROVALS)?.rust("agents/approval/src/sla.rs");
+    assert_eq!(rust_item(&sla, "SLA"), rust_tokens("Duration::from_secs(14400)"));
+    assert_eq!(rust_item(&sla, "ON_BREACH"), rust_tokens("ReviewStatus::Rejected"));
+    assert_eq!(
+        rust_item(&sla, "ESCALATION"),
+        rust_tokens(
+            r#"&[
+                Escalation { after: Duration::from_secs(7200), via: "slack", notify: &["finance"] },
+                Escalation { after: Duration::from_secs(10800), via: "pager", notify: &["cfo"] }
+            ]"#
+        )
+    );
+    assert_eq!(rust_item(&sla, "DELEGATION"), rust_tokens(r#"&[("finance", &["alice", "bob"])]"#));
+
+    // Without an SLA the reviews expire after the configured timeout
+    let sla = generate(r#"workflow A { agents: [HumanReview(id: "a")]; }"#)?.rust("agents/a/src/sla.rs");
+    assert_eq!(rust_item(&sla, "SLA"), rust_tokens("Duration::from_secs(0)"));
+    assert_eq!(rust_item(&sla, "ON_BREACH"), rust_tokens("ReviewStatus::TimedOut"));
+    assert_eq!(rust_item(&sla, "ESCALATION"), rust_tokens("&[]"));
+    Ok(())
+}
+
+#[test]
+fn test_messages_wait_for_their_review() -> Result<()> {
+    let source = r#"workflow Payments {
+        agents: [HumanReview(id: "approval", sla: "4h", on_sla_breach: approve, timeout: "30m", on_timeout: NATS("payments.unreviewed"), notifications: ["slack", "email"], ui: false)];
+    }"#;
+    let program = parse(source)?;
+    let workflow = &program.workflows[0];
+    let config = HumanReviewConfig::from_agent(&workflow.agents[0]).map_err(anyhow::Error::msg)?;
+    assert_eq!((config.timeout_secs, config.wait_secs()), (Some(1_800), 1_800));
+    assert_eq!(config.on_timeout.as_deref(), Some("payments.unreviewed"));
+    assert_eq!(config.notifications, ["email", "slack"]);
+    assert!(!config.ui);
+
+    let review = ReviewSettings::for_agent(workflow, &workflow.agents[0])?.expect("Debería tener ajustes de revisión");
+    assert_eq!((review.timeout_secs, review.timeout_subject.as_str()), (1_800, "payments.unreviewed"));
+    assert_eq!(review.channels, ["email", "slack"]);
+
+    let generated = generate(source)?;
+    let timeout = generated.rust("agents/approval/src/timeout.rs");
+    assert_eq!(rust_item(&timeout, "TIMEOUT"), rust_tokens("Duration::from_secs(1800)"));
+    assert_eq!(rust_item(&timeout, "TIMEOUT_SUBJECT"), rust_tokens(r#""payments.unreviewed""#));
+    let sla = generated.rust("agents/approval/src/sla.rs");
+    assert_eq!(rust_item(&sla, "NOTIFICATIONS"), rust_tokens(r#"&["email", "slack"]"#));
+    Ok(())
+}
+
+#[test]
+fn test_review_decisions_are_signed_into_the_audit_stream() -> Result<()> {
+    let source = r#"workflow Payments {
+        agents: [HumanReview(id: "approval", audit: { issuer: "https://sso.example.com", signing_key: "payments-audit" })];
+    }"#;
+    let program = parse(source)?;
+    let workflow = &program.workflows[0];
+    let review = ReviewSettings::for_agent(workflow, &workflow.agents[0])?.expect("Debería tener ajustes de revisión");
+    assert_eq!(review.audit_subject, "kumeo.audit.payments");
+    assert_eq!(review.oidc_issuer.as_deref(), Some("https://sso.example.com"));
+    assert_eq!(review.oidc_audience, "approval", "La audiencia por defecto es el ID del agente");
+    assert_eq!(review.signing_secret, "payments-audit");
+
+    let generated = generate(source)?;
+    let audit = generated.rust("agents/approval/src/audit.rs");
+    assert_eq!(rust_item(&audit, "AUDIT_SUBJECT"), rust_tokens(r#""kumeo.audit.payments""#));
+
+    let auth = generated.rust("agents/approval/src/auth.rs");
+    assert_eq!(rust_item(&auth, "ISSUER"), rust_tokens(r#"Some("https://sso.example.com")"#));
+
+    // The pods get the signing key from the Secret and the issuer to verify tokens with
+    let rendered = generated.file("agents/approval/kubernetes/deployment.yaml");
+    let manifest = serde_yaml::Value::deserialize(serde_yaml::Deserializer::from_str(&rendered).next().expect("Deployment"))?;
+    let env = manifest["spec"]["template"]["spec"]["containers"][0]["env"].as_sequence().expect("env");
+    let var = |name: &str| env.iter().find(|var| var["name"].as_str() == Some(name)).cloned();
+    assert_eq!(var("KUMEO_AUDIT_SIGNING_KEY").expect("clave de firma")["valueFrom"]["secretKeyRef"]["name"].as_str(), Some("payments-audit"));
+    assert_eq!(var("KUMEO_OIDC_ISSUER").expect("emisor")["value"].as_str(), Some("https://sso.example.com"));
+
+    // Without `audit` the deployment provides the issuer and the Secret is the shared one
+    let program = parse(r#"workflow A { agents: [HumanReview(id: "a")]; }"#)?;
+    let review = ReviewSettings::for_agent(&program.workflows[0], &program.workflows[0].agents[0])?.expect("Debería tener ajustes de revisión");
+    assert_eq!((review.oidc_issuer, review.rust_oidc_issuer.as_str()), (None, "None"));
+    assert_eq!(review.signing_secret, DEFAULT_AUDIT_SIGNING_SECRET);
+
+    let invalid = [
+        (r#"audit: "sso""#, "audit must be an object"),
+        (r#"audit: { issuer: "http://sso.example.com" }"#, "issuer must be an https URL"),
+        (r#"audit: { audience: "" }"#, "audience must be a non-empty string"),
+        (r#"audit: { signing_key: "Audit_Key" }"#, "signing_key must name a Kubernetes Secret"),
+        (r#"audit: { issuer: "https://sso.example.com", key: "k" }"#, "unknown audit setting 'key'"),
+    ];
+    for (options, expected) in invalid {
+        let source = format!(r#"workflow A {{ agents: [HumanReview(id: "a", {})]; }}"#, options);
+        let program = parse(&source)?;
+        let error = ReviewAuditConfig::from_agent(&program.workflows[0].agents[0]).unwrap_err();
+        assert!(error.to_string().contains(expected), "{}: {}", options, error);
+    }
+    Ok(())
+}
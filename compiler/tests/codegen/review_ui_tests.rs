@@ -0,0 +1,63 @@
+use super::generate;
+use anyhow::Result;
+use kumeo_compiler::{
+    codegen::{
+        nats::ExternalNats,
+        review_ui::{ReviewUiSettings, DEFAULT_NATS_URL},
+    },
+    parser::parse,
+};
+
+const TEMPLATES: [&str; 9] = [
+    "package.json.tera",
+    "tsconfig.json.tera",
+    "src/config.ts.tera",
+    "src/server.ts.tera",
+    "src/web/app.ts.tera",
+    "src/web/tsconfig.json.tera",
+    "public/index.html.tera",
+    "Dockerfile.tera",
+    "kubernetes/deployment.yaml.tera",
+];
+
+#[test]
+fn test_reviewers_get_a_review_ui() -> Result<()> {
+    let source = r#"workflow Payments {
+        agents: [
+            HumanReview(id: "approval", auth: OIDC("https://sso.example.com", "payments-reviews", "roles")),
+            Router(id: "route")
+        ];
+    }"#;
+    let program = parse(source)?;
+    let workflow = &program.workflows[0];
+    let ui = ReviewUiSettings::for_agent(workflow, &workflow.agents[0], None)?.expect("Debería generar la UI de revisión");
+    assert_eq!(ReviewUiSettings::for_agent(workflow, &workflow.agents[1], None)?, None, "Solo los HumanReview tienen UI");
+    let program = parse(r#"workflow Payments { agents: [HumanReview(id: "approval", ui: false)]; }"#)?;
+    assert_eq!(ReviewUiSettings::for_agent(&program.workflows[0], &program.workflows[0].agents[0], None)?, None, "ui: false no genera la UI");
+    assert_eq!((ui.name.as_str(), ui.api_url.as_str()), ("approval-ui", "http://approval-reviews"));
+    assert_eq!(ui.nats_url, DEFAULT_NATS_URL);
+    assert_eq!(ui.oidc_issuer.as_deref(), Some("https://sso.example.com"));
+    assert_eq!(ui.oidc_audience, "payments-reviews");
+
+    // With an external NATS the UI follows the reviews there
+    let nats = ExternalNats::new("nats://nats.shared:4222", None)?;
+    let external = ReviewUiSettings::for_agent(workflow, &workflow.agents[0], Some(&nats))?.unwrap();
+    assert_eq!(external.nats_url, "nats://nats.shared:4222");
+
+    let generated = generate(source)?;
+    let ui_file = |file: &str| generated.file(&format!("agents/approval/ui/{}", file));
+    for file in TEMPLATES {
+        assert!(generated.has(&format!("agents/approval/ui/{}", file.trim_end_matches(".tera"))), "Falta {}", file);
+    }
+    let config = ui_file("src/config.ts");
+    assert!(config.contains(r#"export const PENDING_SUBJECT = "reviews.*.pending";"#), "{}", config);
+    assert!(config.contains(r#"process.env.KUMEO_REVIEW_API_URL ?? "http://approval-reviews""#));
+    let server = ui_file("src/server.ts");
+    assert!(server.contains("`/reviews/${encodeURIComponent(req.params.id)}/decision`"));
+    let manifests = ui_file("kubernetes/deployment.yaml");
+    assert!(manifests.contains("name: approval-ui"), "{}", manifests);
+    assert!(manifests.contains("value: \"http://approval-reviews\""));
+    assert!(manifests.contains("value: \"https://sso.example.com\""));
+    assert!(ui_file("Dockerfile").contains("EXPOSE 3000"));
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use super::{generate, rust_calls, rust_item, rust_tokens};
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::HashRoutingConfig,
+    codegen::routing::{RouteTableSettings, RoutingSettings},
+    parser::parse,
+};
+
+const ORDERS: &str = r#"
+workflow Orders {
+    source: NATS("orders");
+    target: NATS("orders.sharded");
+    agents: [
+        Router(id: "shard", strategy: hash(key: data.customer.id, partitions: 4)),
+        Router(id: "route")
+    ];
+}
+"#;
+
+#[test]
+fn test_hash_strategy_is_read() -> Result<()> {
+    let program = parse(r#"workflow A { agents: [Router(id: "a", strategy: hash(key: data.customer_id))]; }"#)?;
+    let config = HashRoutingConfig::from_agent(&program.workflows[0].agents[0]).map_err(anyhow::Error::msg)?;
+    assert_eq!(
+        config,
+        Some(HashRoutingConfig {
+            key: vec!["data".to_string(), "customer_id".to_string()],
+            partitions: HashRoutingConfig::DEFAULT_PARTITIONS,
+            targets: Vec::new(),
+        })
+    );
+
+    let program = parse(r#"workflow A { agents: [Router(id: "a", strategy: hash(key: data.region, targets: ["eu", "us"]))]; }"#)?;
+    let config = HashRoutingConfig::from_agent(&program.workflows[0].agents[0]).map_err(anyhow::Error::msg)?.unwrap();
+    assert_eq!((config.partitions, config.targets), (2, vec!["eu".to_string(), "us".to_string()]));
+
+    let invalid = [
+        ("random(key: data.id)", "unknown strategy 'random'"),
+        ("hash(partitions: 4)", "missing key"),
+        ("hash(key: customer_id)", "key must be a path below data"),
+        ("hash(key: data.id, partitions: 0)", "partitions must be a whole number"),
+        (r#"hash(key: data.id, targets: ["eu", "eu"])"#, "target 'eu' is listed twice"),
+        (r#"hash(key: data.id, partitions: 2, targets: ["eu", "us"])"#, "not both"),
+        ("hash(key: data.id, seed: 3)", "unknown hash setting 'seed'"),
+    ];
+    for (strategy, expected) in invalid {
+        let source = format!(r#"workflow A {{ agents: [Router(id: "a", strategy: {})]; }}"#, strategy);
+        let program = parse(&source)?;
+        let error = HashRoutingConfig::from_agent(&program.workflows[0].agents[0]).unwrap_err();
+        assert!(error.to_string().contains(expected), "{}: {}", strategy, error);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_routers_partition_by_key() -> Result<()> {
+    let program = parse(ORDERS)?;
+    let workflow = &program.workflows[0];
+    let routing = RoutingSettings::for_agent(workflow, &workflow.agents[0])?.expect("Debería enrutar por clave");
+    assert_eq!(RoutingSettings::for_agent(workflow, &workflow.agents[1])?, None);
+    assert_eq!(routing.key, "data.customer.id");
+    // Partitions are numbered subjects below the output topic
+    assert_eq!(routing.topics, ["orders.sharded.0", "orders.sharded.1", "orders.sharded.2", "orders.sharded.3"]);
+
+    let generated = generate(ORDERS)?;
+    let partition = generated.rust("agents/shard/src/partition.rs");
+    assert_eq!(rust_item(&partition, "KEY"), rust_tokens(r#"Some(&["customer", "id"])"#));
+    assert_eq!(rust_item(&partition, "TOPICS"), rust_tokens(r#"&["orders.sharded.0", "orders.sharded.1", "orders.sharded.2", "orders.sharded.3"]"#));
+    let calls = rust_calls(&generated.rust("agents/shard/src/agent.rs"));
+    assert!(calls.contains("crate::partition::topic"), "{:?}", calls);
+
+    // Without a strategy every message goes through the route rules
+    let partition = generated.rust("agents/route/src/partition.rs");
+    assert_eq!(rust_item(&partition, "KEY"), rust_tokens("None"));
+
+    // Targets are used as listed
+    let program = parse(r#"workflow A { agents: [Router(id: "a", strategy: hash(key: data.region, targets: ["orders.eu", "orders.us"]))]; }"#)?;
+    let routing = RoutingSettings::for_agent(&program.workflows[0], &program.workflows[0].agents[0])?.unwrap();
+    assert_eq!(routing.rust_topics, r#"&["orders.eu", "orders.us"]"#);
+    Ok(())
+}
+
+#[test]
+fn test_routers_watch_their_table_resource() -> Result<()> {
+    let source = r#"workflow Orders {
+        agents: [Router(id: "route", rules: resource("s3://config/routes.yaml")), Router(id: "fixed"), DataProcessor(id: "clean", rules: "clean.json")];
+    }"#;
+    let program = parse(source)?;
+    let agents = &program.workflows[0].agents;
+    let table = RouteTableSettings::for_agent(&agents[0])?.expect("Debería leer la tabla de un recurso");
+    assert_eq!((table.source.as_str(), table.yaml), ("s3://config/routes.yaml", true));
+    assert_eq!(RouteTableSettings::for_agent(&agents[1])?, None);
+    assert_eq!(RouteTableSettings::for_agent(&agents[2])?, None, "Solo los Router tienen tabla de rutas");
+
+    let generated = generate(source)?;
+    let routes = generated.rust("agents/route/src/routes.rs");
+    assert_eq!(rust_item(&routes, "TABLE_SOURCE"), rust_tokens(r#"Some("s3://config/routes.yaml")"#));
+    assert_eq!(rust_item(&routes, "TABLE_YAML"), rust_tokens("true"));
+    assert_eq!(rust_item(&routes, "RELOAD_INTERVAL"), rust_tokens("Duration::from_secs(30)"));
+
+    // Without a table resource the routes file is used
+    let routes = generated.rust("agents/fixed/src/routes.rs");
+    assert_eq!(rust_item(&routes, "TABLE_SOURCE"), rust_tokens("None"));
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use super::{generate, rust_item, rust_strings, rust_tokens};
+use anyhow::Result;
+use kumeo_compiler::{
+    codegen::{agent::agent_context, rule_engine::RuleEngineSettings},
+    parser::parse,
+};
+
+const RISK: &str = r#"
+workflow Risk {
+    source: NATS("orders");
+    target: NATS("orders.flagged");
+    agents: [
+        RuleEngine(
+            id: "risk",
+            rules: [
+                high_risk: data.score > 0.9 && data.country?.code != "ES" => set(risk: "high", review: true),
+                vip: data.tier ?? "basic" == "gold" => publish(topic: "orders.vip"),
+                tests: data.customer_id == "test" => drop()
+            ]
+        ),
+        DataProcessor(id: "clean")
+    ];
+}
+"#;
+
+#[test]
+fn test_rule_engines_compile_their_rules() -> Result<()> {
+    let program = parse(RISK)?;
+    let workflow = &program.workflows[0];
+    let engine = RuleEngineSettings::for_agent(&workflow.agents[0])?.expect("Debería evaluar reglas");
+    assert_eq!(RuleEngineSettings::for_agent(&workflow.agents[1])?, None);
+
+    let names: Vec<&str> = engine.rules.iter().map(|rule| rule.name.as_str()).collect();
+    assert_eq!(names, ["high_risk", "vip", "tests"]);
+    let high_risk = &engine.rules[0];
+    assert_eq!(high_risk.source, r#"data.score > 0.9 && data.country?.code != "ES""#);
+    assert_eq!(
+        high_risk.rust,
+        r#"compare(field(data, &["score"]), &json!(0.9)).is_some_and(std::cmp::Ordering::is_gt) && !equals(field(data, &["country", "code"]), &json!("ES"))"#
+    );
+    assert_eq!((high_risk.action.as_str(), high_risk.fields.as_deref()), ("set", Some(r#"{"review":true,"risk":"high"}"#)));
+    assert_eq!((engine.rules[1].action.as_str(), engine.rules[1].topic.as_deref()), ("publish", Some("orders.vip")));
+    assert_eq!((engine.rules[2].action.as_str(), engine.rules[2].fields.as_deref()), ("drop", None));
+    Ok(())
+}
+
+#[test]
+fn test_rule_engines_render_rules_and_metrics() -> Result<()> {
+    let program = parse(RISK)?;
+    let engine = RuleEngineSettings::for_agent(&program.workflows[0].agents[0])?.expect("Debería evaluar reglas");
+    let generated = generate(RISK)?;
+    let rules = generated.rust("agents/risk/src/rules.rs");
+    assert_eq!(
+        rust_item(&rules, "RULES"),
+        rust_tokens(r#"&[("high_risk", Action::Set("{\"review\":true,\"risk\":\"high\"}")), ("vip", Action::Publish("orders.vip")), ("tests", Action::Drop)]"#)
+    );
+    // Each rule is matched by the condition it compiles to, in rule order
+    let conditions: Vec<&str> = engine.rules.iter().map(|rule| rule.rust.as_str()).collect();
+    assert_eq!(rust_item(&rules, "matches"), rust_tokens(&format!("{{ [{}] }}", conditions.join(", "))));
+
+    let metrics = generated.rust("agents/risk/src/metrics.rs");
+    assert_eq!(rust_item(&metrics, "AGENT"), rust_tokens(r#""risk""#));
+    let strings = rust_strings(&metrics);
+    assert!(strings.iter().any(|string| string == "kumeo_rule_hits_total{{agent=\"{}\",rule=\"{}\"}} {}"), "{:?}", strings);
+    Ok(())
+}
+
+#[test]
+fn test_rule_engines_expose_their_metrics_port() -> Result<()> {
+    let program = parse(RISK)?;
+    let workflow = &program.workflows[0];
+    let engine = agent_context(workflow, &workflow.agents[0], None)?;
+    assert_eq!(engine.get("metrics_port"), Some(&serde_json::json!(9090)));
+    // Agents without counters to serve don't open the port
+    let clean = agent_context(workflow, &workflow.agents[1], None)?;
+    assert_eq!(clean.get("metrics_port"), Some(&serde_json::Value::Null));
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use super::{generate, rust_calls, rust_item, rust_tokens};
+use anyhow::Result;
+use kumeo_compiler::{ast::CompensationConfig, codegen::saga::SagaSettings, parser::parse};
+
+const ORDERS: &str = r#"
+workflow Orders {
+    source: NATS("orders");
+    agents: [
+        DataProcessor(id: "reserve", compensate: NATS("stock.release", correlation: data.order.id)),
+        LLM(id: "charge", model: "llama3", compensate: NATS("payments.refund", correlation: data.order.id)),
+        Router(id: "ship")
+    ];
+}
+"#;
+
+#[test]
+fn test_compensation_is_read() -> Result<()> {
+    let program = parse(r#"workflow A { agents: [DataProcessor(id: "a", compensate: NATS("orders.cancel"))]; }"#)?;
+    let config = CompensationConfig::from_agent(&program.workflows[0].agents[0]).map_err(anyhow::Error::msg)?;
+    assert_eq!(
+        config,
+        Some(CompensationConfig {
+            subject: "orders.cancel".to_string(),
+            correlation: vec!["data".to_string(), CompensationConfig::DEFAULT_CORRELATION.to_string()],
+        })
+    );
+
+    let invalid = [
+        (r#""orders.cancel""#, "must be a NATS(\"<subject>\") subject"),
+        (r#"NATS("")"#, "subject is empty"),
+        (r#"NATS("orders.*")"#, "must not have wildcards"),
+        (r#"NATS("orders.cancel", correlation: order_id)"#, "correlation must be a path below data"),
+        (r#"NATS("orders.cancel", ttl: "1h")"#, "unknown compensation setting 'ttl'"),
+    ];
+    for (compensate, expected) in invalid {
+        let source = format!(r#"workflow A {{ agents: [DataProcessor(id: "a", compensate: {})]; }}"#, compensate);
+        let program = parse(&source)?;
+        let error = CompensationConfig::from_agent(&program.workflows[0].agents[0]).unwrap_err();
+        assert!(error.to_string().contains(expected), "{}: {}", compensate, error);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_agents_record_and_roll_back_their_saga() -> Result<()> {
+    let program = parse(ORDERS)?;
+    let workflow = &program.workflows[0];
+    let saga = SagaSettings::for_agent(workflow, &workflow.agents[1])?.expect("Debería ser un paso de la saga");
+    assert_eq!(saga.correlation, "data.order.id");
+    assert_eq!(saga.subject.as_deref(), Some("payments.refund"));
+    assert_eq!(saga.key_prefix, "saga.Orders");
+    // Agents without a compensation still roll the saga back when they fail
+    let ship = SagaSettings::for_agent(workflow, &workflow.agents[2])?.expect("Debería tomar parte en la saga");
+    assert_eq!(ship.subject, None);
+
+    let generated = generate(ORDERS)?;
+    let saga = generated.rust("agents/charge/src/saga.rs");
+    assert_eq!(rust_item(&saga, "CORRELATION"), rust_tokens(r#"Some(&["order", "id"])"#));
+    assert_eq!(rust_item(&saga, "COMPENSATE"), rust_tokens(r#"Some("payments.refund")"#));
+    assert_eq!(rust_item(&saga, "KEY_PREFIX"), rust_tokens(r#""saga.Orders""#));
+    let calls = rust_calls(&generated.rust("agents/charge/src/agent.rs"));
+    assert!(calls.contains("crate::saga::record") && calls.contains("crate::saga::compensate"), "{:?}", calls);
+
+    // Without a saga the module does nothing
+    let saga = generate(r#"workflow A { agents: [LLM(id: "a", model: "llama3")]; }"#)?.rust("agents/a/src/saga.rs");
+    assert_eq!(rust_item(&saga, "CORRELATION"), rust_tokens("None"));
+    assert_eq!(rust_item(&saga, "COMPENSATE"), rust_tokens("None"));
+    Ok(())
+}
+
+#[test]
+fn test_saga_steps_must_agree() -> Result<()> {
+    let program = parse(
+        r#"workflow A { agents: [
+            DataProcessor(id: "a", compensate: NATS("a.undo")),
+            DataProcessor(id: "b", compensate: NATS("b.undo", correlation: data.order_id))
+        ]; }"#,
+    )?;
+    let workflow = &program.workflows[0];
+    let error = SagaSettings::for_agent(workflow, &workflow.agents[0]).unwrap_err();
+    assert!(error.to_string().contains("disagree on the correlation ID"), "{}", error);
+
+    let program = parse(r#"workflow A { agents: [DataProcessor(id: "a"), Router(id: "b")]; }"#)?;
+    assert_eq!(SagaSettings::for_agent(&program.workflows[0], &program.workflows[0].agents[0])?, None);
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use anyhow::Result;
+use kumeo_compiler::{
+    codegen::{agent::generate_agent, scaffold::TestScaffoldSettings, sink::FsSink, template_manager::TemplateManager},
+    parser::parse,
+};
+use std::fs;
+use tempfile::tempdir;
+
+const PAYMENTS: &str = r#"
+workflow Payments {
+    source: NATS("payments");
+    agents: [
+        DataProcessor(id: "parse", input_schema: { id: "string", amount: "number", country: "string?" }),
+        Router(
+            id: "fraud",
+            input_schema: { id: "string", amount: "number", country: "string?" },
+            when: data.amount > 500 && (data.country ?? "ES") != "ES"
+        ),
+        Router(id: "domestic", input_schema: { amount: "number" }, when: data.amount > 500),
+        MLModel(id: "score", model_path: "models/score.onnx", input_schema: { amount: "number", approved: "boolean" })
+    ];
+    test "routes fraud correctly" {
+        given: { id: "p1", amount: 900, country: "FR" },
+        when agent: "fraud",
+        expect: { outcome: "processed" }
+    }
+}
+"#;
+
+#[test]
+fn test_golden_path_message_is_one_the_agent_processes() -> Result<()> {
+    let program = parse(PAYMENTS)?;
+    let workflow = &program.workflows[0];
+
+    // Sin test del workflow, un mensaje con los campos obligatorios del esquema
+    let parse_agent = TestScaffoldSettings::for_agent(workflow, &workflow.agents[0])?;
+    assert_eq!(parse_agent.message, r#"{"amount":1,"id":"example"}"#);
+    assert_eq!(parse_agent.test, None);
+    assert!(parse_agent.processed);
+
+    // El mensaje de un test del workflow que el agente procesa tiene prioridad
+    let fraud = TestScaffoldSettings::for_agent(workflow, &workflow.agents[1])?;
+    assert_eq!(fraud.message, r#"{"amount":900,"country":"FR","id":"p1"}"#);
+    assert_eq!(fraud.test.as_deref(), Some("routes fraud correctly"));
+    assert!(fraud.processed);
+
+    // La condición `when` descarta el mensaje de ejemplo
+    let domestic = TestScaffoldSettings::for_agent(workflow, &workflow.agents[2])?;
+    assert!(!domestic.processed);
+    assert!(domestic.reason.as_deref().is_some_and(|reason| reason.contains("when condition")), "{:?}", domestic.reason);
+    Ok(())
+}
+
+#[test]
+fn test_every_agent_gets_a_test_scaffold() -> Result<()> {
+    let program = parse(PAYMENTS)?;
+    let workflow = &program.workflows[0];
+    let output = tempdir()?;
+    let tera = TemplateManager::default().engine()?;
+    for agent in &workflow.agents {
+        generate_agent(workflow, agent, output.path(), &tera, None, &mut FsSink)?;
+    }
+
+    let fraud = fs::read_to_string(output.path().join("agents/fraud/src/tests.rs"))?;
+    assert!(fraud.contains(r#"const MESSAGE: &str = "{\"amount\":900,\"country\":\"FR\",\"id\":\"p1\"}";"#), "{}", fraud);
+    assert!(fraud.contains(r#"RuntimeClient::new("test-fraud")"#), "{}", fraud);
+    assert!(fraud.contains(r#"assert_eq!(agent.id(), "fraud");"#), "{}", fraud);
+    assert!(!fraud.contains("#[ignore"), "El test del camino feliz debería ejecutarse:\n{}", fraud);
+
+    // Un mensaje que el agente no procesa deja el test ignorado, con el motivo
+    let domestic = fs::read_to_string(output.path().join("agents/domestic/src/tests.rs"))?;
+    assert!(domestic.contains("#[ignore = \"when condition"), "{}", domestic);
+
+    let score = fs::read_to_string(output.path().join("agents/score/tests/test_agent.py"))?;
+    assert!(score.contains("from kumeo_agent_score import agent as agent_module"), "{}", score);
+    assert!(score.contains("class MockRuntime:"), "{}", score);
+    assert!(score.contains(r#"MESSAGE = "{\"amount\":1,\"approved\":true}""#), "{}", score);
+    assert!(score.contains(r#"monkeypatch.setattr(agent_module.tf.keras.models, "load_model""#), "{}", score);
+    assert!(score.contains("assert agent_module._violation(data) is None"), "{}", score);
+    assert!(!score.contains("pytest.mark.skip"), "{}", score);
+    Ok(())
+}
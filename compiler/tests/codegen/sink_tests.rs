@@ -0,0 +1,96 @@
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::{Agent, AgentType, Span, Workflow, WorkflowMode},
+    codegen::{
+        output::OutputManifest,
+        sink::{Change, OutputSink, PlanSink},
+        template_processor::process_template_dir,
+    },
+};
+use std::fs;
+use tempfile::tempdir;
+use tera::Tera;
+
+#[test]
+fn test_plan_compares_generated_files_with_the_output() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let template_dir = temp_dir.path().join("templates");
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir_all(&template_dir)?;
+    fs::write(template_dir.join("new.txt.tera"), "Hello, {{ name }}!\n")?;
+    fs::write(template_dir.join("changed.txt.tera"), "name: {{ name }}\nversion: 1\n")?;
+    fs::write(template_dir.join("same.txt.tera"), "same\n")?;
+
+    // A previous generation left two of the files in the output
+    fs::create_dir_all(&output_dir)?;
+    fs::write(output_dir.join("changed.txt"), "name: Old\nversion: 1\n")?;
+    fs::write(output_dir.join("same.txt"), "same\n")?;
+
+    let mut context = tera::Context::new();
+    context.insert("name", "World");
+    let mut plan = PlanSink::new();
+    process_template_dir(&template_dir, &output_dir, &context, &Tera::default(), &[], &mut plan)?;
+
+    assert!(!output_dir.join("new.txt").exists(), "La simulación no debería escribir nada");
+    assert_eq!(fs::read_to_string(output_dir.join("changed.txt"))?, "name: Old\nversion: 1\n");
+    assert!(plan.exists(&output_dir.join("new.txt")), "Los archivos planificados deberían contar como existentes");
+
+    let files = plan.plan()?;
+    let change = |name: &str| {
+        files
+            .iter()
+            .find(|file| file.path == output_dir.join(name))
+            .map(|file| file.change.clone())
+            .unwrap_or_else(|| panic!("{} debería estar en el plan", name))
+    };
+    assert_eq!(change("new.txt"), Change::Create);
+    assert_eq!(change("same.txt"), Change::Unchanged);
+    match change("changed.txt") {
+        Change::Overwrite(diff) => {
+            assert!(diff.contains("-name: Old"), "El diff debería mostrar la línea anterior: {}", diff);
+            assert!(diff.contains("+name: World"), "El diff debería mostrar la línea nueva: {}", diff);
+            assert!(!diff.contains("-version"), "Las líneas iguales no deberían cambiar: {}", diff);
+        }
+        other => panic!("changed.txt debería sobrescribirse, no {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_planned_agent_files_are_recorded() -> Result<()> {
+    let output_dir = tempdir()?;
+    let output = output_dir.path();
+    let workflow = Workflow {
+        name: "test-workflow".to_string(),
+        source: None,
+        target: None,
+        context: None,
+        preprocessors: None,
+        agents: vec![Agent {
+            id: Some("scorer".to_string()),
+            agent_type: AgentType::LLM,
+            config: vec![],
+            doc: None,
+            span: Span::default(),
+        }],
+        monitor: None,
+        deployment: None,
+        version: None,
+        doc: None,
+        mode: WorkflowMode::Stream,
+        calls: vec![],
+        tests: vec![],
+        span: Span::default(),
+    };
+
+    let mut plan = PlanSink::new();
+    plan.write(&output.join("agents/scorer/Cargo.toml"), b"[package]")?;
+    let mut manifest = OutputManifest::load(output)?;
+    manifest.record(&workflow, output, &plan)?;
+    manifest.save(output, &mut plan)?;
+
+    assert!(manifest.agents["scorer"].contains("agents/scorer/Cargo.toml"));
+    assert!(!output.join("kumeo-output.json").exists(), "El manifiesto solo debería planificarse");
+    assert_eq!(plan.plan()?.len(), 2);
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::Value,
+    codegen::{kubernetes::BrokerSettings, subworkflow::{expand, standalone}},
+    parser::parse,
+};
+
+#[test]
+fn test_calls_expand_to_namespaced_agents() -> Result<()> {
+    let program = parse(
+        r#"
+        workflow Orders {
+            source: NATS("orders.raw");
+            target: NATS("orders.scored");
+            agents: [
+                use Enrich(input: "orders.raw", output: "orders.enriched"),
+                MLModel(id: "scorer", model_path: "models/scorer.onnx"),
+                use Enrich(input: "orders.scored", output: "orders.final")
+            ];
+        }
+
+        subworkflow Enrich {
+            input: ["raw"];
+            output: ["enriched"];
+            agents: [DataProcessor(id: "cleaner"), DataProcessor(id: "tagger")];
+        }
+        "#,
+    )?;
+
+    let workflow = expand(&program.workflows[0], &program.subworkflows)?;
+    assert!(workflow.calls.is_empty());
+    let ids: Vec<_> = workflow.agents.iter().filter_map(|agent| agent.id.as_deref()).collect();
+    assert_eq!(ids, ["enrich_cleaner", "enrich_tagger", "scorer", "enrich_2_cleaner", "enrich_2_tagger"]);
+
+    let topics = |index: usize| {
+        let broker = BrokerSettings::for_agent(&workflow, &workflow.agents[index]);
+        (broker.source.map(|e| e.topic), broker.target.map(|e| e.topic))
+    };
+    let subject = |topic: &str| Some(topic.to_string());
+    assert_eq!(topics(0), (subject("orders.raw"), subject("orders.enrich.cleaner")));
+    assert_eq!(topics(1), (subject("orders.enrich.cleaner"), subject("orders.enriched")));
+    // Agents of the workflow itself keep its source and target
+    assert_eq!(topics(2), (subject("orders.raw"), subject("orders.scored")));
+    assert_eq!(topics(4), (subject("orders.enrich_2.cleaner"), subject("orders.final")));
+    Ok(())
+}
+
+#[test]
+fn test_expanded_ids_must_not_clash() -> Result<()> {
+    let program = parse(
+        r#"
+        workflow Orders {
+            source: NATS("orders.raw");
+            agents: [use Enrich(input: "orders.raw", output: "orders.enriched"), DataProcessor(id: "enrich_cleaner")];
+        }
+
+        subworkflow Enrich {
+            input: ["raw"];
+            output: ["enriched"];
+            agents: [DataProcessor(id: "cleaner")];
+        }
+        "#,
+    )?;
+
+    let error = expand(&program.workflows[0], &program.subworkflows).expect_err("clashing IDs");
+    assert!(error.to_string().contains("enrich_cleaner"));
+    Ok(())
+}
+
+#[test]
+fn test_parameters_are_bound_to_the_invocation_subjects() -> Result<()> {
+    let program = parse(
+        r#"
+        workflow Orders {
+            source: NATS("orders.raw");
+            agents: [use Enrich(input: "orders.raw", output: "orders.enriched")];
+        }
+
+        subworkflow Enrich {
+            input: ["raw"];
+            output: ["enriched"];
+            agents: [DataProcessor(id: "cleaner", audit: "{raw} -> {enriched}", prompt: "{{data}}")];
+        }
+        "#,
+    )?;
+
+    let workflow = expand(&program.workflows[0], &program.subworkflows)?;
+    let cleaner = &workflow.agents[0];
+    assert_eq!(cleaner.config_value("audit"), Some(&Value::String("orders.raw -> orders.enriched".to_string())));
+    assert_eq!(cleaner.config_value("prompt"), Some(&Value::String("{{data}}".to_string())));
+
+    let unbound = parse(
+        r#"
+        workflow Orders {
+            source: NATS("orders.raw");
+            agents: [use Enrich(input: "orders.raw", output: "orders.enriched")];
+        }
+
+        subworkflow Enrich {
+            input: ["raw"];
+            output: ["enriched"];
+            agents: [DataProcessor(id: "cleaner", audit: "{region}")];
+        }
+        "#,
+    )?;
+    let error = expand(&unbound.workflows[0], &unbound.subworkflows).unwrap_err();
+    assert!(error.to_string().contains("{region}"), "{}", error);
+    Ok(())
+}
+
+#[test]
+fn test_uninvoked_subworkflows_stand_alone() -> Result<()> {
+    let program = parse(
+        r#"
+        workflow Orders {
+            source: NATS("orders.raw");
+            agents: [use Enrich(input: "orders.raw", output: "orders.enriched")];
+        }
+
+        subworkflow Enrich {
+            input: ["raw"];
+            output: ["enriched"];
+            agents: [DataProcessor(id: "cleaner")];
+        }
+
+        subworkflow Audit {
+            input: ["orders.enriched"];
+            output: ["orders.audited"];
+            agents: [DataProcessor(id: "checker"), DataProcessor(id: "signer")];
+        }
+        "#,
+    )?;
+
+    let workflows = standalone(&program);
+    let names: Vec<_> = workflows.iter().map(|workflow| workflow.name.as_str()).collect();
+    assert_eq!(names, ["Audit"], "Solo los subworkflows que ningún workflow invoca se generan solos");
+
+    let workflow = expand(&workflows[0], &program.subworkflows)?;
+    let ids: Vec<_> = workflow.agents.iter().filter_map(|agent| agent.id.as_deref()).collect();
+    assert_eq!(ids, ["audit_checker", "audit_signer"]);
+    let first = BrokerSettings::for_agent(&workflow, &workflow.agents[0]);
+    let last = BrokerSettings::for_agent(&workflow, &workflow.agents[1]);
+    assert_eq!(first.source.map(|e| e.topic).as_deref(), Some("orders.enriched"));
+    assert_eq!(last.target.map(|e| e.topic).as_deref(), Some("orders.audited"));
+    Ok(())
+}
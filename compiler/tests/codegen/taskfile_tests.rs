@@ -1,7 +1,7 @@
 use anyhow::Result;
 use kumeo_compiler::{
-    ast::{Workflow, Agent, AgentType},
-    codegen::taskfile::generate_taskfiles,
+    ast::{Workflow, WorkflowMode, Agent, AgentType, Span},
+    codegen::{sink::FsSink, taskfile::generate_taskfiles},
 };
 use std::path::Path;
 use tempfile::tempdir;
@@ -24,22 +24,32 @@ fn test_generate_taskfiles() -> Result<()> {
                 id: Some("rust-agent".to_string()),
                 agent_type: AgentType::LLM,
                 config: vec![],
+                doc: None,
+                span: Span::default(),
             },
             Agent {
                 id: Some("python-agent".to_string()),
                 agent_type: AgentType::MLModel,
                 config: vec![],
+                doc: None,
+                span: Span::default(),
             },
         ],
         monitor: None,
         deployment: None,
+        version: None,
+        doc: None,
+        mode: WorkflowMode::Stream,
+        calls: vec![],
+        tests: vec![],
+        span: Span::default(),
     };
     
     // Initialize Tera
     let tera = Tera::default();
     
     // Generate taskfiles
-    generate_taskfiles(&workflow, output_dir.path(), &tera)?;
+    generate_taskfiles(&workflow, output_dir.path(), &tera, &mut FsSink)?;
     
     // Verify Taskfile was generated
     let taskfile = output_dir.path().join("Taskfile.yaml");
@@ -72,9 +82,17 @@ fn test_generate_taskfiles_with_custom_templates() -> Result<()> {
             id: Some("test-agent".to_string()),
             agent_type: AgentType::LLM,
             config: vec![],
+            doc: None,
+            span: Span::default(),
         }],
         monitor: None,
         deployment: None,
+        version: None,
+        doc: None,
+        mode: WorkflowMode::Stream,
+        calls: vec![],
+        tests: vec![],
+        span: Span::default(),
     };
     
     // Create custom task templates
@@ -98,7 +116,7 @@ fn test_generate_taskfiles_with_custom_templates() -> Result<()> {
     let tera = Tera::new(&format!("{}/**/*.tera", template_dir.display()))?;
     
     // Generate taskfiles with custom templates
-    generate_taskfiles(&workflow, output_dir.path(), &tera)?;
+    generate_taskfiles(&workflow, output_dir.path(), &tera, &mut FsSink)?;
     
     // Verify custom task files were used
     let rust_tasks = output_dir.path().join("tasks/rust/Taskfile.yaml");
@@ -132,13 +150,19 @@ fn test_generate_taskfiles_without_agents() -> Result<()> {
         agents: vec![],
         monitor: None,
         deployment: None,
+        version: None,
+        doc: None,
+        mode: WorkflowMode::Stream,
+        calls: vec![],
+        tests: vec![],
+        span: Span::default(),
     };
     
     // Initialize Tera
     let tera = Tera::default();
     
     // Generate taskfiles
-    let result = generate_taskfiles(&workflow, output_dir.path(), &tera);
+    let result = generate_taskfiles(&workflow, output_dir.path(), &tera, &mut FsSink);
     
     // Should succeed even with no agents
     assert!(result.is_ok());
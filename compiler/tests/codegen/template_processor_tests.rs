@@ -5,7 +5,10 @@ use std::path::Path;
 use tempfile::tempdir;
 use tera::Tera;
 
+use kumeo_compiler::codegen::sink::FsSink;
+use kumeo_compiler::codegen::template_manager::{TemplateManager, TemplateSource};
 use kumeo_compiler::codegen::template_processor::{
+    add_overrides,
     create_base_context,
     ensure_dir,
     file_contains,
@@ -100,7 +103,7 @@ fn test_process_template_dir() -> Result<()> {
     
     // Process templates
     let tera = Tera::new(&format!("{}/**/*.tera", template_dir.display()))?;
-    process_template_dir(&template_dir, &output_dir, &context, &tera, &[])?;
+    process_template_dir(&template_dir, &output_dir, &context, &tera, &[], &mut FsSink)?;
     
     // Verify output files
     let output_file = output_dir.join("test.txt");
@@ -118,3 +121,106 @@ fn test_process_template_dir() -> Result<()> {
     
     Ok(())
 }
+
+#[test]
+fn test_overrides_replace_builtin_templates() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let template_dir = temp_dir.path().join("templates");
+    let overrides_dir = temp_dir.path().join("overrides");
+    let output_dir = temp_dir.path().join("output");
+    
+    // Built-in templates, one of them overridden by the project
+    fs::create_dir_all(template_dir.join("agents"))?;
+    fs::write(template_dir.join("agents/main.rs.tera"), "built-in {{ name }}")?;
+    fs::write(template_dir.join("agents/lib.rs.tera"), "built-in lib")?;
+    fs::create_dir_all(overrides_dir.join("agents"))?;
+    fs::write(overrides_dir.join("agents/main.rs.tera"), "custom {{ name }}")?;
+    
+    let mut context = tera::Context::new();
+    context.insert("name", "World");
+    
+    let mut tera = Tera::default();
+    assert_eq!(add_overrides(&mut tera, &overrides_dir)?, 1);
+    assert_eq!(tera.render("agents/main.rs.tera", &context)?, "custom World");
+    process_template_dir(&template_dir, &output_dir, &context, &tera, &[], &mut FsSink)?;
+    
+    assert_eq!(fs::read_to_string(output_dir.join("agents/main.rs"))?, "custom World", "La plantilla del proyecto debería sustituir a la incluida");
+    assert_eq!(fs::read_to_string(output_dir.join("agents/lib.rs"))?, "built-in lib");
+    assert!(add_overrides(&mut tera, &temp_dir.path().join("missing")).is_err());
+    
+    Ok(())
+}
+
+#[test]
+fn test_templates_resolve_through_layers() -> Result<()> {
+    let temp_dir = tempdir()?;
+    let builtin_dir = temp_dir.path().join("builtin");
+    let user_dir = temp_dir.path().join("user");
+    let project_dir = temp_dir.path().join("project");
+    for (dir, templates) in [
+        (&builtin_dir, &["main.rs.tera", "lib.rs.tera", "Dockerfile.tera"][..]),
+        (&user_dir, &["main.rs.tera", "lib.rs.tera"][..]),
+        (&project_dir, &["main.rs.tera"][..]),
+    ] {
+        fs::create_dir_all(dir.join("agents"))?;
+        for template in templates {
+            let layer = dir.file_name().unwrap().to_string_lossy();
+            fs::write(dir.join("agents").join(template), format!("{} {}", layer, template))?;
+        }
+    }
+
+    let templates = TemplateManager::new(&builtin_dir)
+        .with_layer(TemplateSource::User, &user_dir)
+        .with_layer(TemplateSource::Project, &project_dir);
+    let sources: Vec<TemplateSource> = templates.layers().iter().map(|layer| layer.source).collect();
+    assert_eq!(sources, [TemplateSource::Project, TemplateSource::User, TemplateSource::Builtin]);
+
+    // The first layer holding a template wins
+    let tera = templates.engine()?;
+    let context = tera::Context::new();
+    assert_eq!(tera.render("agents/main.rs.tera", &context)?, "project main.rs.tera");
+    assert_eq!(tera.render("agents/lib.rs.tera", &context)?, "user lib.rs.tera");
+    assert_eq!(tera.render("agents/Dockerfile.tera", &context)?, "builtin Dockerfile.tera");
+
+    let resolved = templates.resolve_all()?;
+    let summary: Vec<(&str, TemplateSource, &[TemplateSource])> = resolved
+        .iter()
+        .map(|template| (template.name.as_str(), template.source, template.shadows.as_slice()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("agents/Dockerfile.tera", TemplateSource::Builtin, &[][..]),
+            ("agents/lib.rs.tera", TemplateSource::User, &[TemplateSource::Builtin][..]),
+            ("agents/main.rs.tera", TemplateSource::Project, &[TemplateSource::User, TemplateSource::Builtin][..]),
+        ]
+    );
+    assert_eq!(resolved[2].path, Some(project_dir.join("agents/main.rs.tera")));
+
+    // A missing layer is reported
+    let missing = TemplateManager::new(&builtin_dir).with_layer(TemplateSource::Project, temp_dir.path().join("missing"));
+    assert!(missing.engine().is_err());
+    assert!(missing.resolve_all().is_err(), "Una capa inexistente debería ser un error");
+
+    Ok(())
+}
+
+#[test]
+fn test_builtin_templates_are_embedded() -> Result<()> {
+    // The compiler finds its templates wherever it runs
+    let temp_dir = tempdir()?;
+    let templates = TemplateManager::default();
+    assert_eq!(templates.layers()[0].dir, None);
+    assert!(templates.dirs().is_empty(), "Las plantillas incluidas no se vigilan");
+
+    let tera = templates.engine()?;
+    assert!(tera.get_template_names().any(|name| name == "agents/rust/LLM/src/agent.rs.tera"));
+    let resolved = templates.resolve_all()?;
+    let dockerfile = resolved.iter().find(|template| template.name == "agents/rust/Dockerfile.tera").expect("Debería estar el Dockerfile");
+    assert_eq!((dockerfile.source, &dockerfile.path), (TemplateSource::Builtin, &None));
+
+    // Built-in templates that are not there are an error, not an empty engine
+    let error = TemplateManager::new(temp_dir.path()).engine().unwrap_err();
+    assert!(error.to_string().contains("No built-in templates found"), "{}", error);
+    Ok(())
+}
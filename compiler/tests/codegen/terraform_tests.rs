@@ -0,0 +1,114 @@
+use super::{generate, generate_with, GenerateOptions};
+use anyhow::Result;
+use kumeo_compiler::{
+    ast::CloudProvider,
+    codegen::{
+        kubernetes::KafkaSettings,
+        nats::ExternalNats,
+        terraform::{ManagedKafka, TerraformSettings},
+    },
+    parser::parse,
+};
+use serde::Deserialize;
+
+fn workflow(infrastructure: &str) -> String {
+    format!(
+        r#"workflow Orders {{
+            source: Kafka("orders");
+            target: File("s3://orders-archive/out/");
+            agents: [
+                MLModel(id: "score", model: resource("s3://models-bucket/scorer.onnx")),
+                DataProcessor(id: "enrich", lookup: "gs://other-cloud/table.csv")
+            ];
+            deployment: {{ namespace: "shop", infrastructure: {} }};
+        }}"#,
+        infrastructure
+    )
+}
+
+#[test]
+fn test_infrastructure_is_opt_in() -> Result<()> {
+    let program = parse(r#"workflow Orders { source: Kafka("orders"); agents: [Router(id: "route")]; }"#)?;
+    assert_eq!(TerraformSettings::for_workflow(&program.workflows[0], None), None);
+    Ok(())
+}
+
+#[test]
+fn test_aws_infrastructure() -> Result<()> {
+    let source = workflow(r#"{ provider: "aws", region: "eu-west-1", kafka: "managed" }"#);
+    let program = parse(&source)?;
+    let workflow = &program.workflows[0];
+    let settings = TerraformSettings::for_workflow(workflow, None).expect("Debería generar Terraform");
+    assert_eq!(settings.provider, CloudProvider::Aws);
+    assert_eq!(settings.namespace, "shop");
+    assert_eq!(settings.nats_url.as_deref(), Some("nats://nats.shop:4222"));
+    assert_eq!(
+        settings.kafka,
+        Some(ManagedKafka { cluster_name: "orders-kafka".to_string(), bootstrap_secret: "orders-kafka".to_string() })
+    );
+    assert_eq!(settings.buckets, ["models-bucket", "orders-archive"], "Solo los buckets de la nube declarada");
+    assert_eq!(settings.provisioned_nats()?.map(|nats| nats.url), Some("nats://nats.shop:4222".to_string()));
+
+    // The agents read the managed brokers from the Secret instead of deploying their own
+    let kafka = KafkaSettings::for_workflow(workflow).unwrap();
+    assert!(!kafka.in_cluster);
+    assert_eq!(kafka.bootstrap_secret.as_deref(), Some("orders-kafka"));
+    let generated = generate(&source)?;
+    let manifests = generated.file("agents/score/kubernetes/deployment.yaml");
+    let deployment = serde_yaml::Deserializer::from_str(&manifests)
+        .map(serde_yaml::Value::deserialize)
+        .find(|document| matches!(document, Ok(document) if document["kind"].as_str() == Some("Deployment")))
+        .expect("Debería generar el Deployment")?;
+    let env = deployment["spec"]["template"]["spec"]["containers"][0]["env"].as_sequence().expect("env");
+    let bootstrap = env.iter().find(|var| var["name"].as_str() == Some("KAFKA_BOOTSTRAP_SERVERS")).expect("KAFKA_BOOTSTRAP_SERVERS");
+    assert_eq!(bootstrap["valueFrom"]["secretKeyRef"]["name"].as_str(), Some("orders-kafka"));
+
+    let versions = generated.file("terraform/versions.tf");
+    assert!(versions.contains("source  = \"hashicorp/aws\""), "{}", versions);
+    assert!(!versions.contains("google"), "{}", versions);
+    let main = generated.file("terraform/main.tf");
+    assert!(main.contains("resource \"helm_release\" \"nats\""), "{}", main);
+    assert!(main.contains("cluster_name           = \"orders-kafka\""), "{}", main);
+    assert!(main.contains("bootstrap_servers = aws_msk_cluster.kafka.bootstrap_brokers"), "{}", main);
+    assert!(main.contains("for_each = toset([\"models-bucket\", \"orders-archive\"])"), "{}", main);
+    let variables = generated.file("terraform/variables.tf");
+    assert!(variables.contains("default     = \"eu-west-1\""), "{}", variables);
+    assert!(variables.contains("variable \"kafka_subnet_ids\""), "{}", variables);
+    assert!(generated.file("terraform/outputs.tf").contains("output \"kafka_bootstrap_servers\""));
+    Ok(())
+}
+
+#[test]
+fn test_gcp_infrastructure_with_external_nats() -> Result<()> {
+    let source = workflow(r#"{ provider: "gcp", region: "europe-west1" }"#);
+    let program = parse(&source)?;
+    let workflow = &program.workflows[0];
+    let nats = ExternalNats::new("nats://nats.shared:4222", None)?;
+    let settings = TerraformSettings::for_workflow(workflow, Some(&nats)).unwrap();
+    assert_eq!(settings.nats_url, None, "Con un NATS externo no se despliega otro");
+    assert_eq!(settings.kafka, None, "Sin kafka: managed el broker sigue en el clúster");
+    assert_eq!(settings.buckets, ["other-cloud"]);
+    assert!(KafkaSettings::for_workflow(workflow).unwrap().in_cluster);
+
+    let generated = generate_with(&source, &GenerateOptions { external_nats: Some(&nats), ..Default::default() })?;
+    let main = generated.file("terraform/main.tf");
+    assert!(!main.contains("helm_release"), "{}", main);
+    assert!(!main.contains("kafka"), "{}", main);
+    assert!(main.contains("resource \"google_storage_bucket\" \"buckets\""), "{}", main);
+    assert!(generated.file("terraform/variables.tf").contains("variable \"project_id\""));
+    Ok(())
+}
+
+#[test]
+fn test_invalid_infrastructure_is_rejected() {
+    for (infrastructure, error) in [
+        (r#"{ region: "eu-west-1" }"#, "Infrastructure requires a provider"),
+        (r#"{ provider: "azure", region: "westeurope" }"#, "Unknown infrastructure provider azure"),
+        (r#"{ provider: "aws" }"#, "Infrastructure requires a region"),
+        (r#"{ provider: "aws", region: "eu-west-1", kafka: "serverless" }"#, "Unknown Kafka deployment serverless"),
+        (r#"{ provider: "aws", region: "eu-west-1", zone: "a" }"#, "Invalid infrastructure setting: zone"),
+    ] {
+        let message = parse(&workflow(infrastructure)).unwrap_err().to_string();
+        assert!(message.contains(error), "{}: {}", infrastructure, message);
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::Result;
+use kumeo_compiler::{
+    codegen::{kubernetes::BrokerSettings, topics::wire},
+    parser::parse,
+};
+
+/// Broker and topic of one end
+type End<'a> = Option<(&'a str, &'a str)>;
+
+fn endpoints(settings: &BrokerSettings) -> (End<'_>, End<'_>) {
+    (
+        settings.source.as_ref().map(|end| (end.broker, end.topic.as_str())),
+        settings.target.as_ref().map(|end| (end.broker, end.topic.as_str())),
+    )
+}
+
+#[test]
+fn test_agents_are_wired_into_a_pipeline() -> Result<()> {
+    let program = parse(
+        r#"
+        workflow Scoring {
+            source: Kafka("events");
+            target: NATS("alerts");
+            agents: [
+                DataProcessor(id: "clean", output: "scoring.clean"),
+                MLModel(id: "score", model_path: "models/score.onnx"),
+                Router(id: "route", input: "clean.output"),
+                LLM(id: "explain", model: "gpt-4", input: "score.output")
+            ];
+        }
+        "#,
+    )?;
+    let workflow = wire(&program.workflows[0])?;
+    let agents = &workflow.agents;
+
+    let clean = BrokerSettings::for_agent(&workflow, &agents[0]);
+    assert_eq!(endpoints(&clean), (Some(("kafka", "events")), Some(("nats", "scoring.clean"))));
+    let score = BrokerSettings::for_agent(&workflow, &agents[1]);
+    assert_eq!(endpoints(&score), (Some(("nats", "scoring.clean")), Some(("nats", "scoring.score"))));
+    let route = BrokerSettings::for_agent(&workflow, &agents[2]);
+    assert_eq!(endpoints(&route), (Some(("nats", "scoring.clean")), Some(("nats", "scoring.route"))));
+    // The last agent writes the workflow target
+    let explain = BrokerSettings::for_agent(&workflow, &agents[3]);
+    assert_eq!(endpoints(&explain), (Some(("nats", "scoring.score")), Some(("nats", "alerts"))));
+
+    assert!(agents.iter().all(|agent| agent.config_value("input").is_none() && agent.config_value("output").is_none()));
+    Ok(())
+}
+
+#[test]
+fn test_workflows_without_topics_are_unchanged() -> Result<()> {
+    let program = parse(
+        r#"
+        workflow Scoring {
+            source: NATS("events");
+            target: NATS("alerts");
+            agents: [DataProcessor(id: "clean"), LLM(id: "explain", model: "gpt-4")];
+        }
+        "#,
+    )?;
+    let workflow = wire(&program.workflows[0])?;
+    for agent in &workflow.agents {
+        let settings = BrokerSettings::for_agent(&workflow, agent);
+        assert_eq!(endpoints(&settings), (Some(("nats", "events")), Some(("nats", "alerts"))));
+    }
+    Ok(())
+}
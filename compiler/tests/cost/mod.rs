@@ -0,0 +1,79 @@
+//! Tests for the cost estimates
+
+use kumeo_compiler::{
+    cost::{CostEstimate, Pricing, DEFAULT_HOURS_PER_MONTH},
+    parse,
+};
+
+const SUPPORT: &str = r#"
+workflow Support {
+    source: NATS("tickets");
+    agents: [
+        LLM(id: "reply", model: "gpt-4o", budget: { input_tokens: 20000000, output_tokens: 4000000 }),
+        LLM(id: "triage", providers: [Ollama(llama3)], budget: { output_tokens: 1000000 }),
+        LLM(id: "summary", model: "gpt-4o")
+    ];
+    deployment: {
+        replicas: 2,
+        resources: { cpu: "500m", memory: "1Gi" }
+    };
+}
+
+workflow Archive {
+    source: NATS("tickets.closed");
+    agents: [DataProcessor(id: "store")];
+}
+"#;
+
+const PRICING: &str = r#"
+currency: EUR
+hours_per_month: 700
+cpu_core_hour: 0.04
+memory_gib_hour: 0.005
+llm:
+  default: { input: 0.5, output: 1.5 }
+  models:
+    gpt-4o: { input: 2.5, output: 10.0 }
+"#;
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+}
+
+#[test]
+fn test_deployments_are_priced_per_workflow() {
+    let program = parse(SUPPORT).expect("Debería parsear");
+    let pricing = Pricing::from_yaml(PRICING).expect("Debería leer los precios");
+    let estimate = CostEstimate::of(&program, &pricing).expect("Debería estimar el coste");
+    assert_eq!((estimate.currency.as_str(), estimate.by_workflow.len()), ("EUR", 2));
+
+    // Three agents of two replicas, each requesting half a core and 1 GiB
+    let support = &estimate.by_workflow[0];
+    assert_close(support.cost.cpu, 3.0 * 0.04 * 700.0);
+    assert_close(support.cost.memory, 6.0 * 0.005 * 700.0);
+    // Budgets are priced at their model's price, or the default one
+    assert_eq!(support.llm.len(), 2, "Los agentes sin presupuesto no cuentan");
+    assert_close(support.llm[0].cost, 20.0 * 2.5 + 4.0 * 10.0);
+    assert_eq!(support.llm[1].model.as_deref(), Some("llama3"));
+    assert_close(support.llm[1].cost, 1.5);
+    assert_close(support.cost.total, support.cost.cpu + support.cost.memory + 91.5);
+
+    let archive = &estimate.by_workflow[1];
+    assert_close(archive.cost.llm, 0.0);
+    assert_close(estimate.total.total, support.cost.total + archive.cost.total);
+}
+
+#[test]
+fn test_pricing_files_are_validated() {
+    let pricing = Pricing::from_yaml("cpu_core_hour: 0.04").expect("Los demás precios son opcionales");
+    assert_eq!((pricing.currency.as_str(), pricing.hours_per_month), ("USD", DEFAULT_HOURS_PER_MONTH));
+
+    // Budgets of models without a price are not silently free
+    let program = parse(SUPPORT).expect("Debería parsear");
+    let error = CostEstimate::of(&program, &pricing).unwrap_err().to_string();
+    assert!(error.contains("No token price for the model gpt-4o of reply"), "{}", error);
+
+    assert!(Pricing::from_yaml("cpu_core_hour: -1").unwrap_err().to_string().contains("cpu_core_hour must be a non-negative number"));
+    assert!(Pricing::from_yaml("llm: { models: { gpt-4o: { input: -2 } } }").unwrap_err().to_string().contains("input price of gpt-4o"));
+    assert!(Pricing::from_yaml("gpu_hour: 2").is_err(), "Debería rechazar los precios desconocidos");
+}
@@ -0,0 +1,164 @@
+//! Tests de la depuración paso a paso del historial de mensajes
+
+use anyhow::Result;
+use kumeo_compiler::debug::{reinject, reinjection, subject_file, Direction, Session, Trace, REINJECTED_HEADER};
+use kumeo_compiler::parser::parse;
+use serde_json::json;
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+const PAYMENTS: &str = r#"
+workflow Payments {
+    source: NATS("payments");
+    target: NATS("payments.scored");
+    agents: [
+        DataProcessor(id: "parse", output: "payments.parsed"),
+        MLModel(id: "score", model_path: "models/score.onnx")
+    ];
+}
+"#;
+
+/// Escribe los mensajes de un subject en el historial, como el runtime
+fn record(store: &Path, subject: &str, lines: &[serde_json::Value]) -> Result<()> {
+    std::fs::create_dir_all(store)?;
+    let contents: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    std::fs::write(subject_file(store, subject), contents.join("\n") + "\n")?;
+    Ok(())
+}
+
+fn store_of_payments(store: &Path) -> Result<()> {
+    record(store, "payments", &[
+        json!({"time": 1000, "agent": "parse", "direction": "in", "subject": "payments", "headers": {"Kumeo-Ingest-Time": "1000"}, "payload": {"amount": "12.5"}}),
+        json!({"time": 2000, "agent": "parse", "direction": "in", "subject": "payments", "payload": {"amount": "oops"}}),
+        // Otro agente que consume el mismo subject
+        json!({"time": 1500, "agent": "audit", "direction": "in", "subject": "payments", "payload": {"amount": "7"}}),
+    ])?;
+    record(store, "payments.parsed", &[
+        json!({"time": 1000, "agent": "parse", "direction": "out", "subject": "payments.parsed", "payload": {"amount": 12.5}}),
+        // Lo que recibe el agente siguiente no es de parse
+        json!({"time": 1001, "agent": "score", "direction": "in", "subject": "payments.parsed", "payload": {"amount": 12.5}}),
+    ])
+}
+
+#[test]
+fn test_traces_merge_the_inputs_and_outputs_of_an_agent() -> Result<()> {
+    let program = parse(PAYMENTS)?;
+    let store = tempfile::tempdir()?;
+    store_of_payments(store.path())?;
+
+    let trace = Trace::load(store.path(), &program.workflows[0], "parse")?;
+    assert_eq!((trace.input.as_deref(), trace.output.as_deref()), (Some("payments"), Some("payments.parsed")));
+    let steps: Vec<(u64, Direction)> = trace.records.iter().map(|record| (record.time, record.direction)).collect();
+    // A la misma hora, lo recibido va antes que lo publicado
+    assert_eq!(steps, [(1000, Direction::In), (1000, Direction::Out), (2000, Direction::In)]);
+
+    // El último agente publica en el target, que nadie ha grabado
+    let score = Trace::load(store.path(), &program.workflows[0], "score")?;
+    assert_eq!(score.records.len(), 1);
+    assert_eq!(score.output.as_deref(), Some("payments.scored"));
+
+    let error = Trace::load(store.path(), &program.workflows[0], "enrich").unwrap_err();
+    assert!(error.to_string().contains("Workflow Payments has no agent enrich"), "{}", error);
+
+    std::fs::write(subject_file(store.path(), "payments"), "{\"time\": 1}\n")?;
+    let error = format!("{:#}", Trace::load(store.path(), &program.workflows[0], "parse").unwrap_err());
+    assert!(error.contains("payments.jsonl:1"), "{}", error);
+    Ok(())
+}
+
+#[test]
+fn test_sessions_step_through_and_edit_the_trace() -> Result<()> {
+    let program = parse(PAYMENTS)?;
+    let store = tempfile::tempdir()?;
+    store_of_payments(store.path())?;
+    let mut session = Session::new(Trace::load(store.path(), &program.workflows[0], "parse")?);
+
+    assert!(!session.back(), "Debería empezar en el primer mensaje");
+    assert!(session.forward());
+    assert!(session.forward());
+    assert!(!session.forward(), "Debería parar en el último mensaje");
+    assert_eq!(session.position(), 2);
+    session.first();
+    assert_eq!(session.current().map(|record| record.time), Some(1000));
+
+    let screen = session.render(40);
+    assert!(screen.starts_with("parse (Payments) · message 1 of 3 · <- received on payments · 1970-01-01 00:00:01.000 UTC"), "{}", screen);
+    assert!(screen.contains("  Kumeo-Ingest-Time: 1000"), "{}", screen);
+    assert!(screen.contains("    \"amount\": \"12.5\""), "{}", screen);
+    assert!(screen.ends_with("[q] quit"), "{}", screen);
+    // Las pantallas pequeñas recortan el payload y conservan las teclas
+    assert_eq!(session.render(5).lines().count(), 5);
+
+    session.last();
+    assert!(session.edit("{ not json").is_err());
+    session.edit(r#"{"amount": "20"}"#)?;
+    assert!(session.is_edited());
+    assert_eq!(session.payload(), Some(&json!({"amount": "20"})));
+    assert!(session.render(40).contains("payload (edited):"));
+    // Volver al payload grabado deshace la edición
+    session.edit(r#"{"amount": "oops"}"#)?;
+    assert!(!session.is_edited());
+
+    let empty = Trace::load(tempfile::tempdir()?.path(), &program.workflows[0], "parse")?;
+    let screen = Session::new(empty).render(40);
+    assert!(screen.contains("Set KUMEO_DEBUG_SUBJECTS=payments,payments.parsed"), "{}", screen);
+    Ok(())
+}
+
+#[test]
+fn test_reinjected_messages_replace_the_ingest_time() -> Result<()> {
+    let program = parse(PAYMENTS)?;
+    let store = tempfile::tempdir()?;
+    store_of_payments(store.path())?;
+    let trace = Trace::load(store.path(), &program.workflows[0], "parse")?;
+
+    let (headers, payload) = reinjection(&trace.records[0], &json!({"amount": "13"}))?;
+    assert_eq!(headers, [(REINJECTED_HEADER.to_string(), "1000".to_string())]);
+    assert_eq!(payload, br#"{"amount":"13"}"#);
+    // Los payloads que no eran JSON se reinyectan como texto
+    let (_, payload) = reinjection(&trace.records[0], &json!("amount=13"))?;
+    assert_eq!(payload, b"amount=13");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_messages_are_reinjected_on_their_subject() -> Result<()> {
+    let program = parse(PAYMENTS)?;
+    let store = tempfile::tempdir()?;
+    store_of_payments(store.path())?;
+    let trace = Trace::load(store.path(), &program.workflows[0], "parse")?;
+
+    // Un servidor NATS falso que devuelve la primera publicación que recibe
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("nats://{}", listener.local_addr()?);
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        writer.write_all(b"INFO {\"server_name\":\"fake\",\"headers\":true}\r\n").await.unwrap();
+        let mut line = String::new();
+        while reader.read_line(&mut line).await.unwrap() > 0 {
+            let parts: Vec<String> = line.split_whitespace().map(str::to_string).collect();
+            line.clear();
+            match parts.first().map(String::as_str) {
+                Some("PING") => writer.write_all(b"PONG\r\n").await.unwrap(),
+                Some("HPUB") => {
+                    let size: usize = parts[3].parse().unwrap();
+                    let mut body = vec![0; size + 2];
+                    reader.read_exact(&mut body).await.unwrap();
+                    return (parts[1].clone(), String::from_utf8(body).unwrap());
+                }
+                _ => {}
+            }
+        }
+        panic!("No se publicó nada");
+    });
+
+    reinject(&url, &trace.records[2], &json!({"amount": "20"})).await?;
+    let (subject, body) = server.await?;
+    assert_eq!(subject, "payments");
+    assert!(body.contains("Kumeo-Reinjected: 2000\r\n"), "{}", body);
+    assert!(body.ends_with("{\"amount\":\"20\"}\r\n"), "{}", body);
+    Ok(())
+}
@@ -0,0 +1,56 @@
+//! Pruebas de la documentación de los programas
+
+use kumeo_compiler::{docs, parser::parse};
+
+const SOURCE: &str = r#"/// Classifies the orders of the shop
+/// and routes them.
+workflow Orders {
+    version: "1.2.0";
+    source: NATS("orders");
+    target: NATS("orders.done");
+    agents: [
+        /// Labels each order with its category
+        LLM(id: "classify", model: "llama3", output: "orders.classified"),
+        Router(id: "route", input: "orders.classified")
+    ];
+}
+
+workflow Audit {
+    agents: [DataProcessor(id: "log")];
+}
+"#;
+
+#[test]
+fn test_site_has_a_page_per_workflow() {
+    let program = parse(SOURCE).expect("Debería parsear");
+    let pages = docs::site_pages(&program);
+    let paths: Vec<_> = pages.iter().map(|page| page.path.to_string_lossy().to_string()).collect();
+    assert_eq!(paths, ["index.md", "orders.md", "audit.md"]);
+
+    let index = &pages[0].contents;
+    assert!(index.starts_with("---\nlayout: default\ntitle: Workflows\nhas_children: true\n"), "{}", index);
+    assert!(index.contains("- [Orders](orders.html) Classifies the orders of the shop\n"), "{}", index);
+
+    let orders = &pages[1].contents;
+    assert!(orders.contains("parent: Workflows\nnav_order: 1\n"), "{}", orders);
+    assert!(orders.contains("Version 1.2.0\n\nClassifies the orders of the shop\nand routes them.\n"), "{}", orders);
+    assert!(
+        orders.contains("### classify\n\nLLM agent.\n\nLabels each order with its category\n\n- Input: `orders`\n- Output: `orders.classified`\n"),
+        "{}",
+        orders
+    );
+    assert!(orders.contains("### route\n\nRouter agent.\n\n- Input: `orders.classified`\n"), "{}", orders);
+}
+
+#[test]
+fn test_hover_shows_the_documentation() {
+    let program = parse(SOURCE).expect("Debería parsear");
+    assert_eq!(
+        docs::hover(&program, 3, 10).as_deref(),
+        Some("**workflow** `Orders` 1.2.0\n\nClassifies the orders of the shop\nand routes them.")
+    );
+    assert_eq!(docs::hover(&program, 9, 12).as_deref(), Some("**LLM** `classify`\n\nLabels each order with its category"));
+    assert_eq!(docs::hover(&program, 10, 9).as_deref(), Some("**Router** `route`"), "Sin documentación se muestra el tipo");
+    assert_eq!(docs::hover(&program, 1, 5), None, "La documentación no es el nodo");
+    assert_eq!(docs::hover(&program, 9, 2), None, "Antes del agente no hay nada");
+}
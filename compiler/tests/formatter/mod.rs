@@ -0,0 +1,198 @@
+//! Tests for the source formatter
+
+use kumeo_compiler::{
+    formatter::{diff, format, format_with, source_files, FormatOptions, KeyOrder, TrailingCommas},
+    parse, Program,
+};
+
+/// Directory of the snapshots: `<name>.kumeo` formats to `<name>.formatted.kumeo`
+const SNAPSHOTS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/formatter/snapshots");
+
+fn snapshot(name: &str) -> (String, String) {
+    let read = |file: String| {
+        std::fs::read_to_string(format!("{}/{}", SNAPSHOTS, file)).expect("Debería existir el snapshot")
+    };
+    (read(format!("{}.kumeo", name)), read(format!("{}.formatted.kumeo", name)))
+}
+
+/// The program as parsed, without the positions of its nodes
+fn program(source: &str) -> serde_json::Value {
+    let program: Program = parse(source).expect("Debería parsear");
+    serde_json::to_value(program).expect("Debería serializarse")
+}
+
+fn assert_snapshot(name: &str) {
+    let (source, expected) = snapshot(name);
+    let formatted = format(&source).expect("Debería formatear");
+    assert_eq!(formatted, expected, "El formato de {} no coincide con su snapshot", name);
+    assert_eq!(program(&formatted), program(&source), "El formato no debería cambiar el programa");
+    assert_eq!(format(&formatted).expect("Debería formatear"), formatted, "Formatear dos veces debería dar lo mismo");
+}
+
+#[test]
+fn test_formats_every_item_of_a_program() {
+    assert_snapshot("orders");
+}
+
+#[test]
+fn test_formatting_keeps_comments() {
+    assert_snapshot("comments");
+}
+
+#[test]
+fn test_formatting_keeps_what_the_ast_drops() {
+    let source = "import 'shared.kumeo'\nworkflow A { agents: [MLModel(id: 'm', drift: { reference: resource(\"s3://p.json\") })]; }";
+    let formatted = format(source).expect("Debería formatear");
+    assert!(formatted.contains("import 'shared.kumeo'\n"), "{}", formatted);
+    assert!(formatted.contains("reference: resource(\"s3://p.json\")"), "{}", formatted);
+    assert_eq!(program(&formatted), program(source));
+}
+
+#[test]
+fn test_long_conditions_stay_on_their_line() {
+    let source = r#"workflow A { agents: [Router(id: "r", when: data.customer?.tier == "gold" && data.order.total >= 1000 && !(data.flags.review ?? false) && data.country != "XX")]; }"#;
+    let formatted = format(source).expect("Debería formatear");
+    assert!(
+        formatted.contains(r#"        when: data.customer?.tier == "gold" && data.order.total >= 1000 && !(data.flags.review ?? false) && data.country != "XX""#),
+        "{}",
+        formatted
+    );
+    assert_eq!(program(&formatted), program(source));
+}
+
+#[test]
+fn test_invalid_programs_are_not_formatted() {
+    assert!(format("workflow { agents: [] }").is_err());
+}
+
+#[test]
+fn test_separators_and_assignments_are_normalized() {
+    let source = r#"const LIMIT = 3
+workflow Orders {
+    source = Kafka("orders")
+    agents: [
+        Router(
+            id = "route" // el primero
+            input: "orders"
+            when: data.total >= 100
+        )
+    ]
+    deployment = { replicas: 2, }
+}
+"#;
+    let formatted = format(source).expect("Debería formatear");
+    let expected = r#"const LIMIT = 3
+
+workflow Orders {
+    source: Kafka("orders");
+    agents: [
+        Router(
+            id: "route", // el primero
+            input: "orders",
+            when: data.total >= 100
+        )
+    ];
+    deployment: { replicas: 2 };
+}
+"#;
+    assert_eq!(formatted, expected);
+    assert_eq!(program(&formatted), program(source), "El formato no debería cambiar el programa");
+    assert_eq!(format(&formatted).expect("Debería formatear"), formatted);
+}
+
+const RULES: &str = r#"workflow Rules { agents: [Router(id: "router", rules: { zeta: "z", alpha: "a", mid: ["first", "second", "third", "fourth"] })]; }"#;
+
+#[test]
+fn test_format_options_are_read_from_toml() {
+    let options = FormatOptions::from_toml("indent_width = 2\ntrailing_commas = \"always\"\nkey_order = \"alphabetical\"")
+        .expect("Debería leer las opciones");
+    assert_eq!(options.indent_width, 2);
+    assert_eq!(options.max_line_length, FormatOptions::default().max_line_length);
+    assert_eq!(options.trailing_commas, TrailingCommas::Always);
+    assert_eq!(options.key_order, KeyOrder::Alphabetical);
+
+    assert!(FormatOptions::from_toml("tabs = true").is_err(), "Debería rechazar opciones desconocidas");
+    assert!(FormatOptions::from_toml("key_order = \"random\"").is_err(), "Debería rechazar un orden desconocido");
+    assert!(FormatOptions::from_toml("indent_width = 0").is_err(), "Debería rechazar una indentación nula");
+}
+
+#[test]
+fn test_format_options_shape_the_output() {
+    let options = FormatOptions {
+        indent_width: 2,
+        max_line_length: 60,
+        trailing_commas: TrailingCommas::Always,
+        key_order: KeyOrder::Alphabetical,
+    };
+    let formatted = format_with(RULES, &options).expect("Debería formatear");
+    let expected = r#"workflow Rules {
+  agents: [
+    Router(
+      id: "router",
+      rules: {
+        alpha: "a",
+        mid: ["first", "second", "third", "fourth"],
+        zeta: "z",
+      },
+    ),
+  ];
+}
+"#;
+    assert_eq!(formatted, expected);
+    assert_eq!(program(&formatted), program(RULES), "El formato no debería cambiar el programa");
+    assert_eq!(format_with(&formatted, &options).expect("Debería formatear"), formatted);
+
+    // Sin comas finales, las que ya hay se quitan; el orden de las claves se conserva
+    let formatted = format(&formatted).expect("Debería formatear");
+    let expected = r#"workflow Rules {
+    agents: [
+        Router(
+            id: "router",
+            rules: { alpha: "a", mid: ["first", "second", "third", "fourth"], zeta: "z" }
+        )
+    ];
+}
+"#;
+    assert_eq!(formatted, expected);
+}
+
+#[test]
+fn test_key_order_keeps_objects_with_comments() {
+    let source = "workflow A { agents: [Router(id: \"r\", rules: {\n    b: 1, // segundo\n    a: 2\n})]; }";
+    let options = FormatOptions { key_order: KeyOrder::Alphabetical, ..FormatOptions::default() };
+    let formatted = format_with(source, &options).expect("Debería formatear");
+    assert!(formatted.find("b: 1").unwrap() < formatted.find("a: 2").unwrap(), "{}", formatted);
+}
+
+#[test]
+fn test_directories_and_globs_name_their_sources() {
+    let dir = tempfile::tempdir().expect("Debería crear un directorio temporal");
+    let nested = dir.path().join("billing");
+    std::fs::create_dir_all(&nested).unwrap();
+    for file in ["orders.kumeo", "billing/invoices.kumeo", "billing/notes.txt"] {
+        std::fs::write(dir.path().join(file), "workflow A {}\n").unwrap();
+    }
+
+    let files = source_files(dir.path()).expect("Debería listar el directorio");
+    assert_eq!(files, vec![nested.join("invoices.kumeo"), dir.path().join("orders.kumeo")]);
+
+    let files = source_files(&dir.path().join("billing/*.kumeo")).expect("Debería expandir el glob");
+    assert_eq!(files, vec![nested.join("invoices.kumeo")]);
+
+    assert!(source_files(&dir.path().join("missing.kumeo")).is_err(), "Un archivo inexistente es un error");
+}
+
+#[test]
+fn test_diff_shows_the_changed_lines() {
+    let original = "workflow A {\n  source: NATS(\"x\");\n    target: NATS(\"y\");\n}\n";
+    let formatted = format(original).expect("Debería formatear");
+    let expected = r#"@@ -1,4 +1,4 @@
+ workflow A {
+-  source: NATS("x");
++    source: NATS("x");
+     target: NATS("y");
+ }
+"#;
+    assert_eq!(diff(original, &formatted), expected);
+    assert_eq!(diff(&formatted, &formatted), "", "Sin cambios no hay diff");
+}
@@ -0,0 +1,203 @@
+//! Pruebas de la traducción de los diagnósticos y de los mensajes de los comandos
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use kumeo_compiler::{
+    diagnostics::{codes, Diagnostic, Severity},
+    i18n::{self, Locale, Message},
+    message,
+    parser::parse,
+    semantic::SemanticAnalyzer,
+};
+
+/// Diagnósticos del análisis de un programa
+fn diagnostics(source: &str) -> Vec<Diagnostic> {
+    let program = parse(source).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+    let _ = analyzer.analyze_program(&program);
+    analyzer.diagnostics().to_vec()
+}
+
+/// El diagnóstico de un código
+fn with_code(diagnostics: &[Diagnostic], code: &str) -> Diagnostic {
+    diagnostics
+        .iter()
+        .find(|diagnostic| diagnostic.code == code)
+        .cloned()
+        .unwrap_or_else(|| panic!("Debería informar de {}: {:?}", code, diagnostics))
+}
+
+#[test]
+fn test_locales_are_parsed_by_language() {
+    assert_eq!("en".parse::<Locale>(), Ok(Locale::En));
+    assert_eq!("es-ES".parse::<Locale>(), Ok(Locale::Es));
+    assert_eq!("en_US.UTF-8".parse::<Locale>(), Ok(Locale::En));
+    assert!("fr".parse::<Locale>().is_err(), "Solo hay diagnósticos en inglés y en español");
+    assert_eq!(Locale::default(), Locale::Es);
+}
+
+#[test]
+fn test_every_code_has_a_title_in_every_locale() {
+    for (code, description) in codes::ALL {
+        assert_eq!(i18n::title(code, Locale::En), Some(*description));
+        assert!(i18n::title(code, Locale::Es).is_some(), "Falta el título en español de {}", code);
+    }
+}
+
+#[test]
+fn test_analyzer_messages_are_translated_with_their_causes() {
+    let found = diagnostics(
+        r#"workflow Review {
+    source: NATS("drafts");
+    agents: [
+        LLM(id: "editor", model: "gpt-4", temperature: 3, output: "published"),
+        LLM(id: "checker", model: "gpt-4", retry: { max_attempts: 0 }, input: "published")
+    ];
+}"#,
+    );
+
+    // Mensaje del analizador con una causa de los esquemas de configuración, ambos en español
+    let config = with_code(&found, codes::INVALID_CONFIG);
+    assert_eq!(config.message, "Configuración inválida en el agente editor (llm): 'temperature' debe estar entre 0 y 2, no 3");
+    assert_eq!(
+        config.localized(Locale::En).message,
+        "Invalid configuration of agent editor (llm): 'temperature' must be between 0 and 2, not 3"
+    );
+    assert_eq!(config.localized(Locale::Es), config, "Traducir al idioma del mensaje no debería cambiarlo");
+
+    // Mensaje del analizador con una causa de la validación de la política, en inglés
+    let policy = with_code(&found, codes::INVALID_POLICY);
+    assert_eq!(
+        policy.localized(Locale::Es).message,
+        "Política retry inválida en el agente checker: max_attempts debe ser un número entero de al menos 1, no 0"
+    );
+    assert_eq!(
+        policy.localized(Locale::En).message,
+        "Invalid retry policy of agent checker: max_attempts must be a whole number of at least 1, found 0"
+    );
+}
+
+#[test]
+fn test_help_and_labels_are_translated() {
+    let found = diagnostics(
+        r#"workflow Loop {
+    source: NATS("in");
+    agents: [
+        DataProcessor(id: "a", input: "in", output: "b.out"),
+        DataProcessor(id: "b", input: "b.out", output: "c.out"),
+        DataProcessor(id: "c", input: "c.out", output: "b.out")
+    ];
+}"#,
+    );
+    let cycle = with_code(&found, codes::DATAFLOW_CYCLE).localized(Locale::En);
+    assert!(cycle.message.to_string().starts_with("Cycle in the dataflow of workflow Loop: "), "{}", cycle.message);
+    assert_eq!(
+        cycle.help.as_ref().map(ToString::to_string).as_deref(),
+        Some("filter the messages with `when` in some step of the cycle so that it ends")
+    );
+
+    assert_eq!(Severity::Warning.label(Locale::En), "warning");
+    assert_eq!(Severity::Warning.label(Locale::Es), "aviso");
+}
+
+#[test]
+fn test_parse_errors_are_translated() {
+    let error = parse("workflow Orders {\n    deployment: { colour: 2 };\n}\n").expect_err("Debería fallar").diagnostic();
+    assert_eq!(error.code, codes::MALFORMED);
+    assert_eq!(error.localized(Locale::Es).message, "Opción de despliegue inválida: colour");
+
+    let error = parse("workflow {\n}\n").expect_err("Debería fallar").diagnostic();
+    assert_eq!(error.code, codes::SYNTAX);
+    assert!(error.localized(Locale::Es).message.to_string().starts_with("se esperaba "), "{}", error.localized(Locale::Es).message);
+}
+
+#[test]
+fn test_messages_without_a_template_are_kept() {
+    let message = "Un mensaje que el catálogo no conoce";
+    assert_eq!(Message::from(message).localized(Locale::En), message);
+    assert_eq!(message!("{}: {}", "agente", 3).localized(Locale::En), "agente: 3", "Los argumentos se muestran tal cual");
+}
+
+#[test]
+fn test_command_messages_are_translated() {
+    assert_eq!(message!("Validación fallida").localized(Locale::En), "Validation failed");
+    assert_eq!(message!("Se encontraron errores de validación").localized(Locale::En), "Validation errors were found");
+    assert_eq!(
+        message!("Failed to write {}", "out/Taskfile.yml").localized(Locale::Es),
+        "No se pudo escribir out/Taskfile.yml"
+    );
+
+    // Los mensajes anidados se traducen al mismo idioma
+    let cause = message!("Invalid NATS URL: {}", "nats//broker");
+    let error = message!("Error en {}", message!("{}: {}", "main.kumeo", cause));
+    assert_eq!(error.localized(Locale::En), "Error in main.kumeo: Invalid NATS URL: nats//broker");
+    assert_eq!(error.localized(Locale::Es), "Error en main.kumeo: URL de NATS inválida: nats//broker");
+    assert_eq!(error.written(), "Error en main.kumeo: Invalid NATS URL: nats//broker");
+}
+
+/// Número de argumentos `{}` de una plantilla, sin contar las llaves escapadas
+fn placeholders(template: &str) -> usize {
+    template.replace("{{", "").replace("}}", "").matches("{}").count()
+}
+
+#[test]
+fn test_templates_match_in_both_languages() {
+    let mut translations = HashMap::new();
+    for (english, spanish) in i18n::templates() {
+        assert_eq!(placeholders(english), placeholders(spanish), "{} / {}", english, spanish);
+        for text in [english, spanish] {
+            let previous = translations.insert(text, (english, spanish));
+            assert!(
+                previous.is_none_or(|previous| previous == (english, spanish)),
+                "La plantilla {} tiene dos traducciones",
+                text
+            );
+        }
+    }
+}
+
+/// Las plantillas de las llamadas a `message!` de un archivo fuente
+fn message_templates(source: &str) -> Vec<String> {
+    source
+        .split("message!(")
+        .skip(1)
+        .filter_map(|call| {
+            let literal = call.trim_start().strip_prefix('"')?;
+            let mut template = String::new();
+            let mut chars = literal.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => return Some(template),
+                    '\\' => match chars.next()? {
+                        'n' => template.push('\n'),
+                        escaped => template.push(escaped),
+                    },
+                    c => template.push(c),
+                }
+            }
+            None
+        })
+        .collect()
+}
+
+#[test]
+fn test_every_message_has_a_template() {
+    let mut pending = vec![Path::new(env!("CARGO_MANIFEST_DIR")).join("src")];
+    let mut used = HashSet::new();
+    while let Some(path) = pending.pop() {
+        if path.is_dir() {
+            pending.extend(fs::read_dir(&path).unwrap().map(|entry| entry.unwrap().path()));
+        } else if path.extension().is_some_and(|extension| extension == "rs") {
+            for template in message_templates(&fs::read_to_string(&path).unwrap()) {
+                let (english, _) = i18n::template(&template)
+                    .unwrap_or_else(|| panic!("Falta en el catálogo la plantilla de {}: {}", path.display(), template));
+                used.insert(english);
+            }
+        }
+    }
+
+    let unused: Vec<_> = i18n::templates().filter(|(english, _)| !used.contains(english)).collect();
+    assert!(unused.is_empty(), "Plantillas del catálogo que no usa ningún mensaje: {:?}", unused);
+}
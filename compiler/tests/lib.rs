@@ -5,3 +5,16 @@ mod ast;
 mod parser;
 mod semantic;
 mod codegen;
+mod live;
+mod load;
+mod debug;
+mod simulator;
+mod vendor;
+mod formatter;
+mod project;
+mod watch;
+mod stats;
+mod cost;
+mod quota;
+mod docs;
+mod i18n;
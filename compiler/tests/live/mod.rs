@@ -0,0 +1,226 @@
+//! Tests for drift detection between the cluster and the DSL
+
+use kumeo_compiler::ast::{
+    Agent, AgentType, CanaryStrategy, Deployment, RolloutStrategy, Scaling, Span, Workflow, WorkflowMode,
+};
+use kumeo_compiler::live::{
+    diff, expected_agents, expected_kinds, parse_deployment_list, Drift, ExpectedReplicas, LiveDeployment,
+    WorkloadKind,
+};
+
+fn workflow_with_agents(ids: &[&str]) -> Workflow {
+    Workflow {
+        name: "drift".to_string(),
+        source: None,
+        target: None,
+        context: None,
+        preprocessors: None,
+        agents: ids
+            .iter()
+            .map(|id| Agent {
+                id: Some(id.to_string()),
+                agent_type: AgentType::LLM,
+                config: vec![],
+                doc: None,
+                span: Span::default(),
+            })
+            .collect(),
+        monitor: None,
+        deployment: None,
+        version: None,
+        doc: None,
+        mode: WorkflowMode::Stream,
+        calls: vec![],
+        tests: vec![],
+        span: Span::default(),
+    }
+}
+
+#[test]
+fn test_in_sync_cluster_reports_no_drift() {
+    let expected = expected_agents(&workflow_with_agents(&["classifier"]), "", "latest");
+    let live = vec![LiveDeployment {
+        name: "classifier".to_string(),
+        kind: WorkloadKind::Deployment,
+        replicas: 1,
+        images: vec!["classifier:latest".to_string()],
+    }];
+
+    assert!(diff(&expected, &live).is_empty());
+}
+
+#[test]
+fn test_detects_missing_agents_replica_drift_and_image_mismatch() {
+    let expected = expected_agents(&workflow_with_agents(&["classifier", "router"]), "ghcr.io/acme", "v2");
+    let live = vec![
+        LiveDeployment {
+            name: "classifier".to_string(),
+            kind: WorkloadKind::Deployment,
+            replicas: 3,
+            images: vec!["ghcr.io/acme/classifier:v1".to_string()],
+        },
+        LiveDeployment {
+            name: "legacy".to_string(),
+            kind: WorkloadKind::Deployment,
+            replicas: 1,
+            images: vec!["legacy:latest".to_string()],
+        },
+    ];
+
+    let drift = diff(&expected, &live);
+
+    assert!(drift.contains(&Drift::ReplicaDrift {
+        agent: "classifier".to_string(),
+        expected: 1,
+        actual: 3,
+    }));
+    assert!(drift.contains(&Drift::ImageMismatch {
+        agent: "classifier".to_string(),
+        expected: "ghcr.io/acme/classifier:v2".to_string(),
+        actual: vec!["ghcr.io/acme/classifier:v1".to_string()],
+    }));
+    assert!(drift.contains(&Drift::MissingAgent { agent: "router".to_string() }));
+    assert!(drift.contains(&Drift::UnexpectedDeployment { deployment: "legacy".to_string() }));
+}
+
+#[test]
+fn test_parse_deployment_list() {
+    let list = serde_json::json!({
+        "kind": "List",
+        "items": [{
+            "metadata": { "name": "classifier" },
+            "spec": {
+                "replicas": 2,
+                "template": { "spec": { "containers": [
+                    { "name": "classifier", "image": "classifier:latest" },
+                    { "name": "kumeo-runtime", "image": "kumeo/runtime:latest" }
+                ] } }
+            }
+        }]
+    });
+
+    let deployments = parse_deployment_list(&list, WorkloadKind::Deployment).unwrap();
+    assert_eq!(deployments.len(), 1);
+    assert_eq!(deployments[0].kind, WorkloadKind::Deployment);
+    assert_eq!(deployments[0].replicas, 2);
+    assert_eq!(deployments[0].images, vec!["classifier:latest", "kumeo/runtime:latest"]);
+
+    assert!(parse_deployment_list(&serde_json::json!({}), WorkloadKind::Deployment).is_err());
+}
+
+#[test]
+fn test_canary_agents_are_expected_as_rollouts() {
+    let mut workflow = workflow_with_agents(&["classifier"]);
+    workflow.deployment = Some(Deployment {
+        rollout: Some(RolloutStrategy::Canary(CanaryStrategy { steps: vec![20.0, 100.0], analysis: None })),
+        ..Default::default()
+    });
+
+    let expected = expected_agents(&workflow, "", "latest");
+    assert_eq!(expected[0].kind, WorkloadKind::Rollout);
+    assert_eq!(expected_kinds(&expected), vec![WorkloadKind::Deployment, WorkloadKind::Rollout]);
+
+    let rollout = LiveDeployment {
+        name: "classifier".to_string(),
+        kind: WorkloadKind::Rollout,
+        replicas: 1,
+        images: vec!["classifier:latest".to_string()],
+    };
+    assert!(diff(&expected, std::slice::from_ref(&rollout)).is_empty());
+
+    // A Deployment of the same name is not the Rollout the canary needs
+    let deployment = LiveDeployment { kind: WorkloadKind::Deployment, ..rollout };
+    let drift = diff(&expected, &[deployment]);
+    assert!(drift.contains(&Drift::MissingAgent { agent: "classifier".to_string() }));
+    assert!(drift.contains(&Drift::UnexpectedDeployment { deployment: "classifier".to_string() }));
+}
+
+#[test]
+fn test_blue_green_agents_are_expected_in_both_slots() {
+    let mut workflow = workflow_with_agents(&["enrich"]);
+    workflow.deployment = Some(Deployment {
+        replicas: Some(3),
+        rollout: Some(RolloutStrategy::BlueGreen),
+        ..Default::default()
+    });
+
+    let expected = expected_agents(&workflow, "", "v2");
+    let slots: Vec<_> = expected.iter().map(|agent| (agent.name.as_str(), agent.replicas, agent.image.as_deref())).collect();
+    assert_eq!(
+        slots,
+        vec![
+            ("enrich-blue", ExpectedReplicas::Fixed(3), None),
+            ("enrich-green", ExpectedReplicas::Fixed(0), Some("enrich:v2")),
+        ]
+    );
+
+    // The active slot still runs the version promoted before this one
+    let live: Vec<_> = expected
+        .iter()
+        .zip([(3, "enrich:v1"), (0, "enrich:v2")])
+        .map(|(agent, (replicas, image))| LiveDeployment {
+            name: agent.name.clone(),
+            kind: WorkloadKind::Deployment,
+            replicas,
+            images: vec![image.to_string()],
+        })
+        .collect();
+    assert!(diff(&expected, &live).is_empty());
+
+    // The annotation of the deployment says which slot is active
+    let deployment = workflow.deployment.as_mut().expect("deployment");
+    deployment.annotations = Some([("kumeo.io/active-slot".to_string(), "green".to_string())].into());
+    let expected = expected_agents(&workflow, "", "v2");
+    let idle: Vec<_> = expected.iter().filter(|agent| agent.image.is_some()).map(|agent| agent.name.as_str()).collect();
+    assert_eq!(idle, ["enrich-blue"]);
+}
+
+#[test]
+fn test_autoscaled_agents_may_run_any_replicas_within_their_bounds() {
+    let mut workflow = workflow_with_agents(&["classifier"]);
+    workflow.deployment = Some(Deployment {
+        replicas: Some(2),
+        scaling: Some(Scaling { min_replicas: 2, max_replicas: 5, target_cpu: Some(70), target_memory: None }),
+        ..Default::default()
+    });
+    let expected = expected_agents(&workflow, "", "latest");
+    assert_eq!(expected[0].replicas, ExpectedReplicas::Autoscaled { min: 2, max: 5 });
+
+    let live = |replicas| {
+        vec![LiveDeployment {
+            name: "classifier".to_string(),
+            kind: WorkloadKind::Deployment,
+            replicas,
+            images: vec!["classifier:latest".to_string()],
+        }]
+    };
+    assert!(diff(&expected, &live(4)).is_empty());
+    assert_eq!(
+        diff(&expected, &live(8)),
+        vec![Drift::ReplicasOutOfRange { agent: "classifier".to_string(), min: 2, max: 5, actual: 8 }]
+    );
+}
+
+#[test]
+fn test_batch_agents_are_expected_as_jobs() {
+    let mut workflow = workflow_with_agents(&["ingest"]);
+    workflow.mode = WorkflowMode::Batch;
+    workflow.deployment = Some(Deployment {
+        replicas: Some(3),
+        rollout: Some(RolloutStrategy::Canary(CanaryStrategy { steps: vec![100.0], analysis: None })),
+        ..Default::default()
+    });
+
+    let expected = expected_agents(&workflow, "", "latest");
+    assert_eq!(expected[0].kind, WorkloadKind::Job);
+    assert_eq!(expected[0].replicas, ExpectedReplicas::ToCompletion);
+    assert_eq!(expected_kinds(&expected), vec![WorkloadKind::Deployment, WorkloadKind::Job]);
+
+    let job = LiveDeployment {
+        name: "ingest".to_string(),
+        kind: WorkloadKind::Job,
+        replicas: 1,
+        images: vec!["ingest:latest".to_string()],
+    };
+    assert!(diff(&expected, &[job]).is_empty());
+}
@@ -0,0 +1,165 @@
+//! Tests de las pruebas de carga de los workflows
+
+use anyhow::Result;
+use kumeo_compiler::load::{percentile, run, LatencyStats, LoadPlan, LoadSettings, MessageSource};
+use kumeo_compiler::parser::parse;
+use serde_json::json;
+use std::io::Write;
+use std::time::Duration;
+use tempfile::NamedTempFile;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+const PAYMENTS: &str = r#"
+workflow Payments {
+    source: NATS("payments");
+    target: NATS("payments.scored");
+    agents: [
+        DataProcessor(id: "parse", input_schema: { id: "string", amount: "number", country: "string?" }),
+        MLModel(id: "score", model_path: "models/score.onnx")
+    ];
+}
+"#;
+
+#[test]
+fn test_plan_publishes_on_the_source_and_listens_on_the_outputs() -> Result<()> {
+    let program = parse(PAYMENTS)?;
+    let plan = LoadPlan::for_workflow(&program.workflows[0])?;
+    assert_eq!(plan.input_subject, "payments");
+    assert_eq!(plan.output_subjects, vec!["payments.scored".to_string()]);
+    assert!(plan.input_schema.is_some(), "Se esperaba el esquema de entrada del primer agente");
+
+    let program = parse(r#"workflow Clicks { source: Kafka("clicks"); agents: [ DataProcessor(id: "count") ]; }"#)?;
+    let error = LoadPlan::for_workflow(&program.workflows[0]).unwrap_err();
+    assert!(error.to_string().contains("does not read from NATS"), "{}", error);
+    Ok(())
+}
+
+#[test]
+fn test_generated_messages_follow_the_schema_and_vary() -> Result<()> {
+    let program = parse(PAYMENTS)?;
+    let plan = LoadPlan::for_workflow(&program.workflows[0])?;
+    let messages = MessageSource::Generated(plan.input_schema);
+
+    assert_eq!(messages.message(0), json!({ "id": "id-0", "amount": 0.5 }));
+    assert_eq!(messages.message(7), json!({ "id": "id-7", "amount": 7.5 }));
+    Ok(())
+}
+
+#[test]
+fn test_sample_files_are_used_in_turn() -> Result<()> {
+    // Un JSON por línea
+    let mut lines = NamedTempFile::new()?;
+    writeln!(lines, "{{\"id\": \"a\"}}\n\n{{\"id\": \"b\"}}")?;
+    let messages = MessageSource::from_file(lines.path())?;
+    assert_eq!(messages.message(0), json!({ "id": "a" }));
+    assert_eq!(messages.message(1), json!({ "id": "b" }));
+    assert_eq!(messages.message(2), json!({ "id": "a" }));
+
+    // Un array JSON
+    let mut array = NamedTempFile::new()?;
+    write!(array, "[{{\"id\": 1}}, {{\"id\": 2}}]")?;
+    assert_eq!(MessageSource::from_file(array.path())?.message(3), json!({ "id": 2 }));
+
+    let mut invalid = NamedTempFile::new()?;
+    writeln!(invalid, "{{\"id\": 1}}\nno es JSON")?;
+    let error = MessageSource::from_file(invalid.path()).unwrap_err();
+    assert!(error.to_string().contains("line 2"), "{}", error);
+    Ok(())
+}
+
+#[test]
+fn test_latency_percentiles_use_the_nearest_rank() {
+    let samples: Vec<Duration> = (1..=100).rev().map(Duration::from_millis).collect();
+    let mut sorted = samples.clone();
+    sorted.sort();
+    assert_eq!(percentile(&sorted, 50.0), Duration::from_millis(50));
+    assert_eq!(percentile(&sorted, 99.0), Duration::from_millis(99));
+    assert_eq!(percentile(&sorted, 0.0), Duration::from_millis(1));
+
+    let stats = LatencyStats::from_samples(&samples).expect("Se esperaban estadísticas");
+    assert_eq!((stats.min, stats.p50, stats.p90, stats.p99, stats.max), (1.0, 50.0, 90.0, 99.0, 100.0));
+    assert!((stats.mean - 50.5).abs() < 1e-9, "{}", stats.mean);
+    assert_eq!(LatencyStats::from_samples(&[]), None);
+}
+
+/// Un servidor NATS falso que hace de workflow: reenvía lo publicado en
+/// `input` a `output`, conservando o no las cabeceras
+async fn fake_workflow(input: &'static str, output: &'static str, keep_headers: bool) -> Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("nats://{}", listener.local_addr()?);
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        writer.write_all(b"INFO {\"server_name\":\"fake\",\"headers\":true}\r\n").await.unwrap();
+        let mut sid = None;
+        let mut line = String::new();
+        while reader.read_line(&mut line).await.unwrap() > 0 {
+            let parts: Vec<String> = line.split_whitespace().map(str::to_string).collect();
+            line.clear();
+            match parts.first().map(String::as_str) {
+                Some("PING") => writer.write_all(b"PONG\r\n").await.unwrap(),
+                Some("SUB") if parts[1] == output => sid = Some(parts[2].clone()),
+                Some("HPUB") => {
+                    let (header_size, size): (usize, usize) = (parts[2].parse().unwrap(), parts[3].parse().unwrap());
+                    let mut body = vec![0; size + 2];
+                    reader.read_exact(&mut body).await.unwrap();
+                    let (Some(sid), true) = (&sid, parts[1] == input) else { continue };
+                    let frame = if keep_headers {
+                        let mut frame = format!("HMSG {} {} {} {}\r\n", output, sid, header_size, size).into_bytes();
+                        frame.extend_from_slice(&body);
+                        frame
+                    } else {
+                        let payload = &body[header_size..];
+                        let mut frame = format!("MSG {} {} {}\r\n", output, sid, payload.len() - 2).into_bytes();
+                        frame.extend_from_slice(payload);
+                        frame
+                    };
+                    writer.write_all(&frame).await.unwrap();
+                }
+                _ => {}
+            }
+        }
+    });
+    Ok(url)
+}
+
+#[tokio::test]
+async fn test_run_matches_outputs_with_their_messages() -> Result<()> {
+    let program = parse(PAYMENTS)?;
+    let plan = LoadPlan::for_workflow(&program.workflows[0])?;
+    let messages = MessageSource::Generated(plan.input_schema.clone());
+    let settings = LoadSettings { rate: 50.0, duration: Duration::from_millis(200), drain: Duration::from_secs(5) };
+    assert_eq!(settings.messages(), 10);
+
+    // Con la cabecera de carga y sin ella, emparejando por orden de llegada
+    for keep_headers in [true, false] {
+        let url = fake_workflow("payments", "payments.scored", keep_headers).await?;
+        let report = run(&plan, &messages, &settings, &url).await?;
+        assert_eq!(report.sent, 10);
+        assert_eq!(report.received, 10, "Deberían llegar todos los resultados: {:?}", report);
+        assert_eq!(report.unanswered, 0);
+        assert!(report.latency.is_some());
+        assert!(report.duration_secs >= 0.2, "{}", report.duration_secs);
+        assert!(report.to_string().contains("sent 10 messages"), "{}", report);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_run_reports_messages_without_output() -> Result<()> {
+    let program = parse(PAYMENTS)?;
+    let plan = LoadPlan::for_workflow(&program.workflows[0])?;
+    let settings = LoadSettings { rate: 100.0, duration: Duration::from_millis(50), drain: Duration::from_millis(100) };
+
+    // El workflow falso responde en otro subject, así que no llega nada
+    let url = fake_workflow("otro", "payments.scored", true).await?;
+    let report = run(&plan, &MessageSource::Generated(None), &settings, &url).await?;
+    assert_eq!(report.sent, 5);
+    assert_eq!(report.received, 0);
+    assert_eq!(report.unanswered, 5);
+    assert_eq!(report.latency, None);
+    assert!(report.to_string().contains("no outputs received"), "{}", report);
+    Ok(())
+}
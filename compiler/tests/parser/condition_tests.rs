@@ -0,0 +1,178 @@
+use kumeo_compiler::ast::{AggregatorConfig, CompareOp, Expr, RuleAction, RuleEngineConfig, Value};
+use kumeo_compiler::parser::parse;
+
+#[test]
+fn test_parse_when_condition() {
+    let input = r#"
+    workflow Triage {
+        source: NATS("tickets");
+        agents: [
+            LLM(id: "escalate", model: "gpt-4", when: !data.spam && (data.score > 0.8 || data.tags.0 == "urgent"))
+        ];
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear la condición");
+    let Some(Value::Condition(expr)) = program.workflows[0].agents[0].config_value("when") else {
+        panic!("Se esperaba una condición");
+    };
+
+    let field = |path: &str| Box::new(Expr::Field(path.split('.').map(str::to_string).collect()));
+    let expected = Expr::And(
+        Box::new(Expr::Not(field("data.spam"))),
+        Box::new(Expr::Or(
+            Box::new(Expr::Compare(field("data.score"), CompareOp::Gt, Box::new(Expr::Literal(Value::Number(0.8))))),
+            Box::new(Expr::Compare(
+                field("data.tags.0"),
+                CompareOp::Eq,
+                Box::new(Expr::Literal(Value::String("urgent".to_string()))),
+            )),
+        )),
+    );
+    assert_eq!(**expr, expected);
+    assert_eq!(expr.to_string(), r#"!data.spam && (data.score > 0.8 || data.tags.0 == "urgent")"#);
+}
+
+#[test]
+fn test_when_requires_a_condition() {
+    let input = r#"
+    workflow Triage {
+        source: NATS("tickets");
+        agents: [LLM(id: "escalate", model: "gpt-4", when: data.score >)];
+    }
+    "#;
+
+    assert!(parse(input).is_err(), "Debería rechazar una comparación incompleta");
+}
+
+#[test]
+fn test_parse_optional_chaining_and_defaults() {
+    let input = r#"
+    workflow Notify {
+        source: NATS("orders");
+        agents: [
+            Router(id: "notify", when: data.customer?.email ?? data.contact ?? "unknown" != "unknown")
+        ];
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear ?. y ??");
+    let Some(Value::Condition(expr)) = program.workflows[0].agents[0].config_value("when") else {
+        panic!("Se esperaba una condición");
+    };
+
+    let email = Expr::OptionalField(
+        vec!["data".to_string(), "customer".to_string(), "email".to_string()],
+        vec![false, false, true],
+    );
+    let contact = Expr::Field(vec!["data".to_string(), "contact".to_string()]);
+    let unknown = || Box::new(Expr::Literal(Value::String("unknown".to_string())));
+    let expected = Expr::Compare(
+        Box::new(Expr::Default(Box::new(Expr::Default(Box::new(email), Box::new(contact))), unknown())),
+        CompareOp::Ne,
+        unknown(),
+    );
+    assert_eq!(**expr, expected);
+    assert_eq!(expr.to_string(), r#"data.customer?.email ?? data.contact ?? "unknown" != "unknown""#);
+}
+
+#[test]
+fn test_parse_assertions() {
+    let input = r#"
+    workflow Payments {
+        source: NATS("payments");
+        agents: [DataProcessor(id: "clean", assert: data.amount >= 0, when: data.kind == "card", assert: data.currency?.code != null)];
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear los invariantes assert");
+    let agent = &program.workflows[0].agents[0];
+    let assertions: Vec<String> = agent.assertions().iter().map(|expr| expr.to_string()).collect();
+    assert_eq!(assertions, vec!["data.amount >= 0", "data.currency?.code != null"]);
+    assert!(matches!(agent.config_value("when"), Some(Value::Condition(_))));
+}
+
+#[test]
+fn test_parse_reductions() {
+    let input = r#"
+    workflow Totals {
+        source: NATS("orders");
+        agents: [
+            Aggregator(
+                id: "totals",
+                window: { count: 100 },
+                group_by: data.customer_id,
+                reduce: { total: sum(data.amount), "orders": count(), biggest: max(data.amount ?? 0), }
+            )
+        ];
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear las reducciones");
+    let agent = &program.workflows[0].agents[0];
+    let Some(Value::Object(reductions)) = agent.config_value("reduce") else {
+        panic!("Se esperaban las reducciones");
+    };
+    let mut names: Vec<&str> = reductions.keys().map(String::as_str).collect();
+    names.sort();
+    assert_eq!(names, vec!["biggest", "orders", "total"]);
+    let Value::Tagged(reducer, arguments) = &reductions["biggest"] else {
+        panic!("Se esperaba una reducción");
+    };
+    assert_eq!(reducer, "max");
+    let Some(Value::Condition(expr)) = arguments.get("of") else {
+        panic!("Se esperaba el valor reducido");
+    };
+    assert_eq!(expr.to_string(), "data.amount ?? 0");
+    assert_eq!(reductions["orders"], Value::Tagged("count".to_string(), Default::default()));
+
+    // A reducer needs its parentheses, and a reduction folds one value
+    for reduce in ["{ total: sum }", "{ total: sum(data.a, data.b) }", "{ total: median(data.amount) }"] {
+        let input = format!(r#"workflow A {{ agents: [Aggregator(id: "a", window: {{ count: 1 }}, reduce: {})]; }}"#, reduce);
+        let rejected = parse(&input).map_or(true, |program| AggregatorConfig::from_agent(&program.workflows[0].agents[0]).is_err());
+        assert!(rejected, "Debería rechazar las reducciones {}", reduce);
+    }
+}
+
+#[test]
+fn test_parse_engine_rules() {
+    let input = r#"
+    workflow Risk {
+        source: NATS("orders");
+        agents: [
+            RuleEngine(
+                id: "risk",
+                rules: [
+                    high_risk: data.score > 0.9 && !data.vip => set(risk: "high", review: true),
+                    "vip": data.vip == true => publish(topic: "orders.vip")
+                    tests: data.customer_id == "test" => drop(),
+                ]
+            ),
+            Router(id: "route", rules: { "data.priority == 'high'": "target.high_priority" })
+        ];
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear las reglas");
+    let config = RuleEngineConfig::from_agent(&program.workflows[0].agents[0]).expect("Debería leer las reglas");
+    let names: Vec<&str> = config.rules.iter().map(|rule| rule.name.as_str()).collect();
+    assert_eq!(names, vec!["high_risk", "vip", "tests"]);
+    assert_eq!(config.rules[0].when.to_string(), "data.score > 0.9 && !data.vip");
+    let RuleAction::Set(fields) = &config.rules[0].then else {
+        panic!("Se esperaba una acción set");
+    };
+    assert_eq!(fields["risk"], Value::String("high".to_string()));
+    assert_eq!(config.rules[1].then, RuleAction::Publish("orders.vip".to_string()));
+    assert_eq!(config.rules[2].then, RuleAction::Drop);
+
+    // Las reglas de un Router siguen siendo un objeto
+    let router = &program.workflows[0].agents[1];
+    assert!(matches!(router.config_value("rules"), Some(Value::Object(_))));
+
+    // Una regla necesita su condición y una acción conocida con sus argumentos
+    for rules in ["[a: => drop()]", "[a: data.x > 1 => explode()]", "[a: data.x > 1 => publish()]", "[a: data.x > 1 => set()]"] {
+        let input = format!(r#"workflow A {{ agents: [RuleEngine(id: "a", rules: {})]; }}"#, rules);
+        let rejected = parse(&input).map_or(true, |program| RuleEngineConfig::from_agent(&program.workflows[0].agents[0]).is_err());
+        assert!(rejected, "Debería rechazar las reglas {}", rules);
+    }
+}
@@ -0,0 +1,161 @@
+use kumeo_compiler::ast::{Argument, Value};
+use kumeo_compiler::parser::{parse, parse_file};
+use std::fs;
+
+#[test]
+fn test_parse_and_substitute_constants() {
+    let input = r#"
+    const MODEL = "ollama/llama3";
+    const FALLBACKS = [$MODEL, "ollama/mistral"]
+
+    workflow Support {
+        source: NATS("tickets");
+        agents: [
+            LLM(id: "writer", model: $MODEL, options: { fallbacks: $FALLBACKS })
+        ];
+    }
+    "#;
+
+    let mut program = parse(input).expect("Debería parsear las constantes");
+    assert_eq!(program.constants.len(), 2);
+    assert_eq!(program.constants[0].name, "MODEL");
+    let writer = &program.workflows[0].agents[0];
+    assert_eq!(writer.config_value("model"), Some(&Value::Variable("MODEL".to_string())));
+
+    program.substitute_constants().expect("Debería sustituir las constantes");
+    let writer = &program.workflows[0].agents[0];
+    assert_eq!(writer.config_value("model"), Some(&Value::String("ollama/llama3".to_string())));
+    let Some(Value::Object(options)) = writer.config_value("options") else {
+        panic!("Se esperaba un objeto");
+    };
+    assert_eq!(
+        options["fallbacks"],
+        Value::Array(vec![
+            Value::String("ollama/llama3".to_string()),
+            Value::String("ollama/mistral".to_string()),
+        ])
+    );
+}
+
+#[test]
+fn test_undefined_constant_is_reported() {
+    let input = r#"
+    const FIRST = $SECOND;
+    const SECOND = "late";
+
+    subworkflow Enrich {
+        input: ["raw"];
+        output: ["enriched"];
+        agents: [DataProcessor(id: "enricher", steps: $STEPS)];
+    }
+    "#;
+
+    let mut program = parse(input).expect("Debería parsear");
+    // Constants can only reference the ones declared before them
+    assert_eq!(program.substitute_constants(), Err("SECOND".to_string()));
+
+    program.constants.remove(0);
+    assert_eq!(program.substitute_constants(), Err("STEPS".to_string()));
+}
+
+#[test]
+fn test_imported_constants_are_visible() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("models.kumeo"), r#"const SCORER = "s3://models/scorer.onnx";"#).unwrap();
+    fs::write(
+        dir.path().join("main.kumeo"),
+        r#"import "models.kumeo";
+        workflow Scoring {
+            source: NATS("events");
+            agents: [MLModel(id: "scorer", model_path: $SCORER)];
+        }"#,
+    )
+    .unwrap();
+
+    let mut program = parse_file(&dir.path().join("main.kumeo")).expect("Debería cargar los imports");
+    program.substitute_constants().unwrap();
+    assert!(matches!(
+        &program.workflows[0].agents[0].config[0],
+        Argument::Named(name, Value::String(path), _) if name == "model_path" && path == "s3://models/scorer.onnx"
+    ));
+}
+
+#[test]
+fn test_interpolate_constants_in_strings() {
+    let input = r#"
+    const REGION = "eu";
+    const SHARDS = 3;
+    const INCOMING = "orders.{REGION}.incoming";
+
+    workflow OrdersEu {
+        source: NATS("{INCOMING}");
+        target: NATS("orders.{REGION}.scored");
+        agents: [
+            LLM(id: "writer", model: "gpt-4", prompt: "Summarize {{data}} for {REGION} in {SHARDS} lines", api_key: "${env.KEY}"),
+            DataProcessor(id: "split", options: { topics: ["orders.{REGION}.{SHARDS}"], pattern: "{3} {not an identifier}" })
+        ];
+    }
+
+    subworkflow Enrich {
+        input: ["raw"];
+        output: ["enriched"];
+        agents: [DataProcessor(id: "enricher", reads: "{raw}@{REGION}")];
+    }
+    "#;
+
+    let mut program = parse(input).expect("Debería parsear");
+    program.substitute_constants().unwrap();
+    program.interpolate_constants().expect("Debería interpolar las constantes");
+
+    let workflow = &program.workflows[0];
+    assert_eq!(workflow.source.as_ref().unwrap().topic(), "orders.eu.incoming");
+    assert_eq!(workflow.target.as_ref().unwrap().topic(), "orders.eu.scored");
+    // Prompt placeholders and environment references are kept for the agent
+    assert_eq!(
+        workflow.agents[0].config_value("prompt"),
+        Some(&Value::String("Summarize {{data}} for eu in 3 lines".to_string()))
+    );
+    assert_eq!(workflow.agents[0].config_value("api_key"), Some(&Value::String("${env.KEY}".to_string())));
+    let Some(Value::Object(options)) = workflow.agents[1].config_value("options") else {
+        panic!("Se esperaba un objeto");
+    };
+    assert_eq!(options["topics"], Value::Array(vec![Value::String("orders.eu.3".to_string())]));
+    assert_eq!(options["pattern"], Value::String("{3} {not an identifier}".to_string()));
+    // Subworkflow parameters are left for the invocations to bind
+    assert_eq!(
+        program.subworkflows[0].agents[0].config_value("reads"),
+        Some(&Value::String("{raw}@eu".to_string()))
+    );
+}
+
+#[test]
+fn test_unresolved_interpolations_are_reported() {
+    let interpolate = |input: &str| {
+        let mut program = parse(input).expect("Debería parsear");
+        program.substitute_constants().unwrap();
+        program.interpolate_constants()
+    };
+
+    let error = interpolate(
+        r#"
+        workflow Orders {
+            source: NATS("orders.{REGION}.incoming");
+            agents: [DataProcessor(id: "clean")];
+        }
+        "#,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("{REGION}"), "{}", error);
+
+    let error = interpolate(
+        r#"
+        const REGIONS = ["eu", "us"];
+        workflow Orders {
+            source: NATS("orders.{REGIONS}.incoming");
+            agents: [DataProcessor(id: "clean")];
+        }
+        "#,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("REGIONS can't be interpolated"), "{}", error);
+}
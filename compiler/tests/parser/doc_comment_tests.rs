@@ -0,0 +1,48 @@
+use kumeo_compiler::parser::{parse, parse_recovering};
+
+const DOCUMENTED: &str = r#"
+/// Classifies the orders of the shop
+/// and routes them.
+workflow Orders {
+    agents: [
+        /// Labels each order with its category
+        ///
+        ///   Uses the small model to stay cheap.
+        LLM(id: "classify", model: "llama3", output: "orders.classified"),
+        //// Not documentation
+        Router(id: "route", input: "orders.classified")
+    ];
+}
+"#;
+
+#[test]
+fn test_doc_comments_document_workflows_and_agents() {
+    let program = parse(DOCUMENTED).expect("Debería parsear los comentarios de documentación");
+    let workflow = &program.workflows[0];
+    assert_eq!(workflow.doc.as_deref(), Some("Classifies the orders of the shop\nand routes them."));
+    assert_eq!(
+        workflow.agents[0].doc.as_deref(),
+        Some("Labels each order with its category\n\n  Uses the small model to stay cheap."),
+        "Solo se quita el espacio tras las barras"
+    );
+    assert_eq!(workflow.agents[1].doc, None, "Cuatro barras son un comentario normal");
+
+    // The documented nodes start after their documentation
+    assert_eq!((workflow.span.line, workflow.span.column), (4, 1));
+    assert_eq!(workflow.span.snippet, "workflow Orders {");
+    assert_eq!((workflow.agents[0].span.line, workflow.agents[0].span.column), (9, 9));
+    assert!(workflow.agents[0].span.snippet.starts_with("LLM(id: \"classify\""));
+}
+
+#[test]
+fn test_doc_comments_survive_error_recovery() {
+    let source = format!("workflow Broken {{\n    source: ;\n}}\n{}", DOCUMENTED);
+    let partial = parse_recovering(&source);
+    assert_eq!(partial.errors.len(), 1, "{:?}", partial.errors);
+    let workflow = &partial.program.workflows[0];
+    assert_eq!(workflow.name, "Orders");
+    assert_eq!(workflow.doc.as_deref(), Some("Classifies the orders of the shop\nand routes them."));
+
+    // A doc comment with nothing to document is an error
+    assert!(parse("workflow Orders {\n    agents: [Router(id: \"route\")];\n    /// Dangling\n}").is_err());
+}
@@ -49,3 +49,108 @@ fn test_duplicate_agent_id() {
     let program = parse(input).expect("El parsing debería tener éxito");
     assert_eq!(program.workflows[0].agents.len(), 2); // El parser permite IDs duplicados
 }
+
+#[test]
+fn test_errors_carry_their_location() {
+    let input = "workflow Test {\n    source: NATS(\"in\");\n    agents: [\n        LLM(id: \"writer\" model: \"gpt-4\")\n    ];\n}\n";
+    match KumeoError::from(parse(input).unwrap_err()) {
+        KumeoError::ParserError { line, column, .. } => {
+            assert_eq!(line, 4, "Debería señalar la línea del agente");
+            assert!(column > 0, "Debería señalar la columna");
+        }
+        other => panic!("Error inesperado: {}", other),
+    }
+
+    let input = "workflow Test {\n    source: NATS(\"in\");\n    agents: [\n        LLM(id: \"writer\", timeout: $MISSING)\n    ];\n}\n";
+    let program = parse(input).expect("Debería parsear");
+    let agent = &program.workflows[0].agents[0];
+    assert_eq!((agent.span.line, agent.span.column), (4, 9), "Debería guardar la posición del agente");
+    assert_eq!(agent.span.snippet, "LLM(id: \"writer\", timeout: $MISSING)");
+}
+
+#[test]
+fn test_syntax_errors_are_diagnostics() {
+    let input = "workflow Test {\n    source: NATS(\"in\");\n    agents: [\n        LLM(id: \"writer\" model: \"gpt-4\")\n    ];\n}\n";
+    let diagnostic = parse(input).unwrap_err().diagnostic();
+    assert_eq!(diagnostic.code, kumeo_compiler::diagnostics::codes::SYNTAX);
+    assert_eq!(diagnostic.span.line, 4, "Debería señalar la línea del agente");
+
+    // La línea se muestra sin sangría y el subrayado cae bajo la columna del error
+    let rendered = diagnostic.to_string();
+    let caret = " ".repeat(diagnostic.span.column - 1 - diagnostic.span.indent);
+    assert!(rendered.starts_with("error[KU0001]: expected"), "{}", rendered);
+    assert!(rendered.contains("4 | LLM(id: \"writer\" model: \"gpt-4\")"), "{}", rendered);
+    assert!(rendered.contains(&format!("  | {}^", caret)), "{}", rendered);
+}
+
+#[test]
+fn test_recovering_reports_every_error() {
+    use kumeo_compiler::parser::parse_recovering;
+
+    let input = r#"const MODEL = "gpt-4";
+
+workflow Review {
+    source: NATS("drafts");
+    agents: [
+        LLM(id: "write" model: "gpt-4", output: "review.draft"),
+        LLM(id: "edit", model: $MODEL, input: "review.draft", output: "review.edited"),
+        LLM(id: "publish", model: "gpt-4" input: "review.edited")
+    ];
+}
+
+workflow Broken {
+    source: NATS("x") target: NATS("y")
+    agents: [LLM(id: "a", model: "m")];
+}
+
+workflow Unclosed {
+    source: NATS("y");
+    agents: [LLM(id: "b", model: "m")];
+
+workflow Fine {
+    source: NATS("z");
+    agents: [Router(id: "r", rules: {})];
+}
+"#;
+    let partial = parse_recovering(input);
+    let lines: Vec<usize> = partial.errors.iter().map(|e| e.line_col().expect("Debería tener posición").0).collect();
+    assert_eq!(lines, vec![6, 8, 13, 19], "Debería informar de cada error: {:?}", partial.errors);
+
+    // Lo que parsea se conserva, sin los agentes con errores
+    let program = &partial.program;
+    assert_eq!(program.constants.len(), 1);
+    let names: Vec<&str> = program.workflows.iter().map(|w| w.name.as_str()).collect();
+    assert_eq!(names, vec!["Review", "Fine"]);
+    let agents: Vec<_> = program.workflows[0].agents.iter().map(|a| a.id.as_deref()).collect();
+    assert_eq!(agents, vec![Some("edit")]);
+    assert_eq!(program.workflows[0].agents[0].span.line, 7, "Debería conservar las posiciones");
+
+    // Sin errores, el resultado es el de parse
+    let partial = parse_recovering(r#"workflow Fine { source: NATS("z"); agents: [Router(id: "r", rules: {})]; }"#);
+    assert!(partial.is_complete());
+    assert_eq!(partial.program.workflows.len(), 1);
+}
+
+#[test]
+fn test_recovering_drops_items_without_steps() {
+    use kumeo_compiler::parser::parse_recovering;
+
+    // Un workflow puede quedarse sin agentes; un subworkflow no
+    let input = r#"
+workflow Single {
+    source: NATS("in");
+    agents: [LLM(id: "only" model: "m")];
+}
+
+subworkflow Enrich {
+    input: ["raw"];
+    output: ["enriched"];
+    agents: [LLM(id: "enrich", model: "m" input: "raw")];
+}
+"#;
+    let partial = parse_recovering(input);
+    assert_eq!(partial.errors.len(), 2, "{:?}", partial.errors);
+    assert_eq!(partial.program.workflows.len(), 1);
+    assert!(partial.program.workflows[0].agents.is_empty());
+    assert!(partial.program.subworkflows.is_empty());
+}
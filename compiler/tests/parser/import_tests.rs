@@ -0,0 +1,67 @@
+use kumeo_compiler::parser::{parse, parse_file};
+use std::fs;
+
+const SHARED: &str = r#"
+subworkflow Enrich {
+    input: ["raw"];
+    output: ["enriched"];
+    agents: [DataProcessor(id: "enricher")];
+}
+"#;
+
+#[test]
+fn test_parse_imports() {
+    let input = r#"
+    import "common/agents.kumeo";
+    import 'shared.kumeo'
+
+    workflow Main {
+        source: NATS("input");
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear los imports");
+    assert_eq!(program.imports, ["common/agents.kumeo", "shared.kumeo"]);
+    assert_eq!(program.workflows.len(), 1);
+}
+
+#[test]
+fn test_parse_file_merges_imports() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("common")).unwrap();
+    fs::write(dir.path().join("common/shared.kumeo"), SHARED).unwrap();
+    // Both files import the shared subworkflow; it must only be included once
+    fs::write(
+        dir.path().join("common/agents.kumeo"),
+        r#"import "shared.kumeo"; workflow Side { source: NATS("side"); }"#,
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("main.kumeo"),
+        r#"
+        import "common/agents.kumeo";
+        import "common/shared.kumeo";
+        workflow Main { source: NATS("input"); }
+        "#,
+    )
+    .unwrap();
+
+    let program = parse_file(&dir.path().join("main.kumeo")).expect("Debería resolver los imports");
+    assert!(program.imports.is_empty());
+    assert_eq!(program.subworkflows.len(), 1);
+    let names: Vec<_> = program.workflows.iter().map(|w| w.name.as_str()).collect();
+    assert_eq!(names, ["Side", "Main"]);
+}
+
+#[test]
+fn test_parse_file_detects_cycles() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.kumeo"), r#"import "b.kumeo";"#).unwrap();
+    fs::write(dir.path().join("b.kumeo"), r#"import "a.kumeo";"#).unwrap();
+
+    let error = parse_file(&dir.path().join("a.kumeo")).expect_err("Debería detectar el ciclo");
+    assert!(error.to_string().contains("Import cycle"), "{}", error);
+
+    fs::write(dir.path().join("c.kumeo"), r#"import "missing.kumeo";"#).unwrap();
+    assert!(parse_file(&dir.path().join("c.kumeo")).is_err());
+}
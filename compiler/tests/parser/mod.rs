@@ -3,6 +3,10 @@
 mod workflow_tests;
 mod subworkflow_tests;
 mod error_handling_tests;
+mod import_tests;
+mod constant_tests;
+mod condition_tests;
+mod doc_comment_tests;
 
 use kumeo_compiler::parser::parse;
 
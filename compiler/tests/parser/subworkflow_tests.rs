@@ -69,3 +69,51 @@ fn test_workflow_with_subworkflow() {
     assert_eq!(subworkflow.name, "DataProcessor");
     assert_eq!(subworkflow.agents.len(), 1);
 }
+
+#[test]
+fn test_parse_subworkflow_call() {
+    let input = r#"
+    workflow Orders {
+        source: NATS("orders.raw");
+        target: NATS("orders.scored");
+        agents: [
+            use Enrich(input: "orders.raw", output: "orders.enriched"),
+            MLModel(id: "scorer", model_path: "models/scorer.onnx"),
+            use Audit(input: ["orders.scored", "orders.enriched"], output: "orders.audit")
+        ];
+    }
+
+    subworkflow Enrich {
+        input: ["raw"];
+        output: ["enriched"];
+        agents: [DataProcessor(id: "cleaner"), LLM(id: "tagger", model: "llama3")];
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear la invocación del subworkflow");
+    let workflow = &program.workflows[0];
+    assert_eq!(workflow.agents.len(), 1);
+    assert_eq!(
+        workflow.calls,
+        vec![
+            SubworkflowCall {
+                name: "Enrich".to_string(),
+                input: vec!["orders.raw".to_string()],
+                output: vec!["orders.enriched".to_string()],
+                position: 0,
+            },
+            SubworkflowCall {
+                name: "Audit".to_string(),
+                input: vec!["orders.scored".to_string(), "orders.enriched".to_string()],
+                output: vec!["orders.audit".to_string()],
+                position: 1,
+            },
+        ]
+    );
+
+    let subworkflow = &program.subworkflows[0];
+    assert_eq!(subworkflow.input.as_deref(), Some(&["raw".to_string()][..]));
+    assert_eq!(subworkflow.output.as_deref(), Some(&["enriched".to_string()][..]));
+
+    assert!(parse(r#"workflow W { agents: [use Enrich(inputs: "a")]; }"#).is_err());
+}
@@ -82,3 +82,539 @@ fn test_workflow_with_preprocessors() {
     assert_eq!(preprocessors.len(), 1);
     assert_eq!(preprocessors[0].id.as_ref().unwrap(), "prep1");
 }
+
+#[test]
+fn test_parse_canary_rollout() {
+    let input = r#"
+    workflow Scoring {
+        source: NATS("input");
+        agents: [
+            MLModel(id: "scorer", model_name: "fraud")
+        ];
+        deployment: {
+            replicas: 3,
+            rollout: canary { steps: [10%, 50%, 100%], analysis: "error_rate < 1%" }
+        };
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear el bloque de despliegue");
+    let deployment = program.workflows[0]
+        .deployment
+        .as_ref()
+        .expect("Debería tener despliegue");
+
+    assert_eq!(deployment.replicas, Some(3));
+    assert_eq!(
+        deployment.rollout,
+        Some(RolloutStrategy::Canary(CanaryStrategy {
+            steps: vec![10.0, 50.0, 100.0],
+            analysis: Some("error_rate < 1%".to_string()),
+        }))
+    );
+}
+
+#[test]
+fn test_parse_blue_green_rollout() {
+    let input = r#"
+    workflow Scoring {
+        source: NATS("input");
+        deployment: { rollout: blue_green };
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear el despliegue blue/green");
+    let deployment = program.workflows[0].deployment.as_ref().unwrap();
+    assert_eq!(deployment.rollout, Some(RolloutStrategy::BlueGreen));
+}
+
+#[test]
+fn test_parse_agent_storage() {
+    let input = r#"
+    workflow Scoring {
+        source: NATS("input");
+        agents: [
+            MLModel(id: "scorer", model_name: "fraud")
+        ];
+        deployment: {
+            storage: {
+                scorer: { size: "10Gi", path: "/models", class: "fast-ssd" }
+            }
+        };
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear el almacenamiento");
+    let storage = program.workflows[0]
+        .deployment
+        .as_ref()
+        .and_then(|d| d.storage.as_ref())
+        .expect("Debería tener almacenamiento");
+
+    assert_eq!(
+        storage.get("scorer"),
+        Some(&Storage {
+            size: "10Gi".to_string(),
+            path: "/models".to_string(),
+            class: Some("fast-ssd".to_string()),
+        })
+    );
+}
+
+#[test]
+fn test_parse_batch_mode() {
+    let input = r#"
+    workflow Nightly {
+        mode: batch;
+        source: NATS("events", { until_sequence: 5000 });
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear el modo batch");
+    let workflow = &program.workflows[0];
+    assert_eq!(workflow.mode, WorkflowMode::Batch);
+    assert_eq!(
+        workflow.source.as_ref().and_then(|s| s.option("until_sequence")),
+        Some("5000")
+    );
+}
+
+#[test]
+fn test_parse_kafka_source_and_target() {
+    let input = r#"
+    workflow Clicks {
+        source: Kafka("clicks", { group: "scorers" });
+        target: Kafka("scored-clicks");
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear Kafka");
+    let workflow = &program.workflows[0];
+
+    match &workflow.source {
+        Some(source @ Source::Kafka(topic, _)) => {
+            assert_eq!(topic, "clicks");
+            assert_eq!(source.option("group"), Some("scorers"));
+        }
+        other => panic!("Tipo de source incorrecto: {:?}", other),
+    }
+    assert!(matches!(&workflow.target, Some(Target::Kafka(topic, None)) if topic == "scored-clicks"));
+}
+
+#[test]
+fn test_parse_mqtt_source_and_target() {
+    let input = r#"
+    workflow Sensors {
+        source: MQTT("sensors/+/temp", { url: "mqtt://broker:1883" });
+        target: MQTT("alerts/temp");
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear MQTT");
+    let workflow = &program.workflows[0];
+
+    match &workflow.source {
+        Some(source @ Source::MQTT(topic, _)) => {
+            assert_eq!(topic, "sensors/+/temp");
+            assert_eq!(source.option("url"), Some("mqtt://broker:1883"));
+        }
+        other => panic!("Tipo de source incorrecto: {:?}", other),
+    }
+    assert!(matches!(&workflow.target, Some(Target::MQTT(topic, None)) if topic == "alerts/temp"));
+}
+
+#[test]
+fn test_parse_http_webhook_source() {
+    let input = r#"
+    workflow Ingest {
+        source: HTTP("/ingest", { method: "POST" });
+        target: NATS("ingested");
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear HTTP");
+    match &program.workflows[0].source {
+        Some(source @ Source::HTTP(path, _)) => {
+            assert_eq!(path, "/ingest");
+            assert_eq!(source.option(WEBHOOK_METHOD_OPTION), Some("POST"));
+        }
+        other => panic!("Tipo de source incorrecto: {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_oidc_auth() {
+    let input = r#"
+    workflow Ingest {
+        source: HTTP("/ingest", { auth: OIDC("https://sso.example.com", "ingest", allow: ["ops", "oncall"]) });
+        agents: [HumanReview(id: "approval", auth: OIDC("https://sso.example.com", "reviews", "roles"))];
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear OIDC");
+    let workflow = &program.workflows[0];
+
+    // Las opciones de la fuente son planas: el objeto se aplana en `auth.*`
+    let source = workflow.source.as_ref().expect("source");
+    assert_eq!(source.option(AUTH_OPTION), Some("OIDC"));
+    assert_eq!(source.option("auth.issuer"), Some("https://sso.example.com"));
+    assert_eq!(source.option("auth.allow"), Some("ops,oncall"));
+    let auth = OidcAuthConfig::from_source(source).expect("auth válido").expect("auth");
+    assert_eq!(auth.client_id, "ingest");
+    assert_eq!(auth.groups_claim, OidcAuthConfig::DEFAULT_GROUPS_CLAIM);
+    assert_eq!(auth.allow, vec!["ops", "oncall"]);
+
+    let auth = OidcAuthConfig::from_agent(&workflow.agents[0]).expect("auth válido").expect("auth");
+    assert_eq!((auth.client_id.as_str(), auth.groups_claim.as_str()), ("reviews", "roles"));
+    assert!(auth.allow.is_empty());
+}
+
+#[test]
+fn test_parse_file_source_and_target() {
+    let input = r#"
+    workflow Drop {
+        source: File("/data/in/*.json", { watch: true });
+        target: File("/data/out/");
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear File");
+    let workflow = &program.workflows[0];
+
+    match &workflow.source {
+        Some(source @ Source::File(pattern, _)) => {
+            assert_eq!(pattern, "/data/in/*.json");
+            assert_eq!(source.option(FILE_WATCH_OPTION), Some("true"));
+        }
+        other => panic!("Tipo de source incorrecto: {:?}", other),
+    }
+    assert!(matches!(&workflow.target, Some(Target::File(directory, None)) if directory == "/data/out/"));
+}
+
+#[test]
+fn test_parse_require_signed() {
+    let input = r#"
+    workflow Scoring {
+        source: NATS("input");
+        deployment: { require_signed: true, trusted_keys: "release-keys" };
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear require_signed");
+    let deployment = program.workflows[0].deployment.as_ref().expect("deployment");
+    assert!(deployment.require_signed);
+    assert_eq!(deployment.trusted_keys.as_deref(), Some("release-keys"));
+}
+
+#[test]
+fn test_parse_env_secret() {
+    let input = r#"
+    workflow Support {
+        source: NATS("tickets");
+        agents: [ LLM(id: "writer", model: "gpt-4", api_key: "${env.OPENAI_API_KEY}") ];
+        deployment: { env_secret: "support-secrets" };
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear env_secret");
+    let workflow = &program.workflows[0];
+    assert_eq!(
+        workflow.deployment.as_ref().and_then(|d| d.env_secret.as_deref()),
+        Some("support-secrets")
+    );
+    // The reference is kept verbatim for the agent to resolve
+    assert!(matches!(
+        workflow.agents[0].config_value("api_key"),
+        Some(Value::String(key)) if key == "${env.OPENAI_API_KEY}"
+    ));
+}
+
+#[test]
+fn test_parse_schemas_block() {
+    let input = r#"
+    schemas: {
+        "orders.created": { id: "string", total: "number", note: "string?" },
+        "orders.scored": { id: "string", score: "number" }
+    };
+
+    workflow Orders {
+        source: NATS("orders.created");
+        agents: [ MLModel(id: "score", model_path: "models/score.onnx") ];
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear el bloque schemas");
+    assert_eq!(program.schemas.len(), 2);
+    assert_eq!(program.schemas[0].topic, "orders.created");
+    let schemas = program.topic_schemas();
+    assert_eq!(schemas["orders.created"].fields["note"], "string?");
+    assert_eq!(schemas["orders.scored"].fields["score"], "number");
+}
+
+#[test]
+fn test_parse_workflow_tests() {
+    let input = r#"
+    workflow Payments {
+        source: NATS("payments");
+        agents: [ Router(id: "router1", when: data.amount > 500) ];
+        test "routes fraud correctly" {
+            given: { amount: 900, country: "ES" },
+            when agent: "router1",
+            expect: { topic: "alerts.fraud" }
+        }
+        test "skips small payments" { given: { amount: 5 }, when agent: "router1", expect: { outcome: "skipped" } };
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear los bloques test");
+    let tests = &program.workflows[0].tests;
+    assert_eq!(tests.len(), 2);
+    assert_eq!(tests[0].name, "routes fraud correctly");
+    assert_eq!(tests[0].agent, "router1");
+    assert!(matches!(&tests[0].given, Value::Object(fields) if fields["amount"] == Value::Number(900.0)));
+    assert_eq!(tests[0].expect["topic"], Value::String("alerts.fraud".to_string()));
+    assert_eq!(tests[1].expect["outcome"], Value::String("skipped".to_string()));
+    assert_eq!(tests[1].span.line, 10);
+}
+
+#[test]
+fn test_separators_and_assignments_are_flexible() {
+    let canonical = r#"workflow Orders {
+        source: Kafka("orders");
+        agents: [
+            LLM(id: "classify", model: "gpt-4", input: "orders", output: "classified"),
+            Router(id: "route", input: "classified", rules: { tiers: ["gold", "silver"], mode: "first" })
+        ];
+        deployment: { replicas: 2 };
+    }"#;
+    let flexible = r#"workflow Orders {
+        source = Kafka("orders",)
+        agents: [
+            LLM(
+                id = "classify"
+                model: "gpt-4",
+                input: "orders"
+                output: "classified",
+            )
+            Router(id: "route", input: "classified", rules: {
+                tiers: [
+                    "gold"
+                    "silver",
+                ]
+                mode = "first",
+            })
+        ]
+        deployment = { replicas: 2, }
+    }"#;
+    let program = |input| serde_json::to_value(parse(input).expect("Debería parsear")).expect("Debería serializarse");
+    assert_eq!(program(flexible), program(canonical), "Las comas finales, los saltos de línea y `=` no deberían cambiar el programa");
+
+    // Sin salto de línea hace falta el separador
+    let message = parse(r#"workflow Orders { agents: [Router(id: "route" input: "orders")]; }"#)
+        .unwrap_err()
+        .to_string();
+    assert!(message.contains("expected a separator or a line break before input"), "{}", message);
+    assert!(parse(r#"workflow Orders { source: Kafka("orders") agents: [Router(id: "route")] }"#).is_err());
+}
+
+#[test]
+fn test_parse_scaling() {
+    let input = r#"
+    workflow Scoring {
+        source: NATS("input");
+        deployment: { scaling: { min_replicas: 2, max_replicas: 8, target_memory: 75 } };
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear el escalado");
+    let scaling = program.workflows[0].deployment.as_ref().unwrap().scaling.as_ref().unwrap();
+    assert_eq!(
+        scaling,
+        &Scaling { min_replicas: 2, max_replicas: 8, target_cpu: None, target_memory: Some(75) }
+    );
+
+    let scaling = |options: &str| parse(&input.replace("{ min_replicas: 2, max_replicas: 8, target_memory: 75 }", options));
+    let defaults = scaling("{ max_replicas: 4 }").expect("Debería parsear el escalado mínimo");
+    assert_eq!(
+        defaults.workflows[0].deployment.as_ref().unwrap().scaling,
+        Some(Scaling { min_replicas: 1, max_replicas: 4, target_cpu: Some(80), target_memory: None }),
+        "Sin objetivo se escala por CPU"
+    );
+    for (options, error) in [
+        ("{ min_replicas: 2 }", "Scaling requires max_replicas"),
+        ("{ min_replicas: 5, max_replicas: 3 }", "Scaling max_replicas 3 is below min_replicas 5"),
+        ("{ max_replicas: 0 }", "Scaling max_replicas must be a whole number of at least 1"),
+        ("{ max_replicas: 3, target_rps: 100 }", "Invalid scaling setting: target_rps"),
+    ] {
+        let message = scaling(options).unwrap_err().to_string();
+        assert!(message.contains(error), "{}: {}", options, message);
+    }
+}
+
+#[test]
+fn test_parse_monitor() {
+    let input = r#"
+    workflow Scoring {
+        source: NATS("input");
+        agents: [Router(id: "route")];
+        monitor: { metrics_enabled: true, alert_threshold: 3, alert_error_rate: 0.05, alert_unavailable: "10m" };
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear la monitorización");
+    let monitor = program.workflows[0].monitor.as_ref().unwrap();
+    assert!(monitor.metrics_enabled);
+    assert_eq!(monitor.scrape_interval, "30s");
+    assert_eq!(
+        monitor.alerts,
+        MonitorAlerts {
+            restarts: Some(3),
+            error_rate: Some(0.05),
+            guardrail_blocks: None,
+            unavailable_for: Some("10m".to_string()),
+            latency_p99: None,
+            window: "5m".to_string(),
+            severity: "warning".to_string(),
+        }
+    );
+
+    let monitor = |options: &str| {
+        parse(&input.replace("{ metrics_enabled: true, alert_threshold: 3, alert_error_rate: 0.05, alert_unavailable: \"10m\" }", options))
+    };
+    let defaults = monitor("{}").expect("Debería parsear una monitorización vacía");
+    let defaults = defaults.workflows[0].monitor.as_ref().unwrap();
+    assert!(defaults.metrics_enabled && !defaults.alerts.any(), "Sin opciones se recogen métricas y no hay alertas");
+    for (options, error) in [
+        ("{ metrics_enabled: 1 }", "Monitor metrics_enabled must be true or false"),
+        ("{ alert_threshold: 0 }", "Monitor alert_threshold must be a whole number of at least 1"),
+        ("{ alert_error_rate: 5 }", "Monitor alert_error_rate must be a fraction between 0 and 1"),
+        ("{ alert_window: \"1.5m\" }", "Monitor alert_window must be a duration such as \"5m\""),
+        ("{ alert_severity: \"page\" }", "Unknown alert severity \"page\", expected critical, warning or info"),
+        ("{ alert_latency: 3 }", "Invalid monitor setting: alert_latency"),
+        ("{ alert_latency_p99: \"fast\" }", "Monitor alert_latency_p99 must be a duration such as \"500ms\" or \"2s\""),
+    ] {
+        let message = monitor(options).unwrap_err().to_string();
+        assert!(message.contains(error), "{}: {}", options, message);
+    }
+}
+
+#[test]
+fn test_parse_secret() {
+    let input = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [ LLM(id: "writer", model: "gpt-4", api_key: secret("openai-api.key")) ];
+        deployment: { env: { DB_PASSWORD: secret("db_password"), LOG_LEVEL: "debug" } };
+    }"#;
+    let program = parse(input).expect("Debería parsear los secretos");
+    let workflow = &program.workflows[0];
+    assert_eq!(
+        workflow.agents[0].config_value("api_key"),
+        Some(&Value::String("${env.OPENAI_API_KEY}".to_string())),
+        "Un secreto se lee de la variable de su nombre en mayúsculas"
+    );
+    let deployment = workflow.deployment.as_ref().unwrap();
+    assert_eq!(deployment.secret_env().into_iter().collect::<Vec<_>>(), [("DB_PASSWORD", "DB_PASSWORD")]);
+    assert_eq!(deployment.plain_env().into_iter().collect::<Vec<_>>(), [("LOG_LEVEL", "debug")]);
+
+    for name in ["", "1password", "api key"] {
+        let message = parse(&input.replace("openai-api.key", name)).unwrap_err().to_string();
+        assert!(message.contains("Invalid secret name"), "{}: {}", name, message);
+    }
+}
+
+#[test]
+fn test_parse_deployment_metadata() {
+    let input = r#"workflow Scoring {
+        source: NATS("input");
+        agents: [ Router(id: "route") ];
+        deployment: {
+            namespace: "risk",
+            labels: { team: "fraud", "example.com/cost-center": "cc-42" },
+            annotations: { "example.com/owner": "fraud@example.com" }
+        };
+    }"#;
+    let program = parse(input).expect("Debería parsear las etiquetas y anotaciones");
+    let deployment = program.workflows[0].deployment.as_ref().unwrap();
+    let labels = deployment.labels.as_ref().expect("labels");
+    assert_eq!(labels.get("team").map(String::as_str), Some("fraud"));
+    assert_eq!(labels.get("example.com/cost-center").map(String::as_str), Some("cc-42"));
+    let annotations = deployment.annotations.as_ref().expect("annotations");
+    assert_eq!(annotations.get("example.com/owner").map(String::as_str), Some("fraud@example.com"));
+
+    for (from, to, expected) in [
+        ("\"risk\"", "\"Risk\"", "Invalid namespace 'Risk'"),
+        ("team:", "\"bad key\":", "Invalid label key 'bad key'"),
+        ("\"fraud\"", "\"-fraud\"", "Invalid value '-fraud' of label team"),
+        ("team:", "app:", "Label app is reserved for Kumeo"),
+        ("example.com/owner", "kumeo.io/owner", "Annotation kumeo.io/owner is reserved for Kumeo"),
+    ] {
+        let message = parse(&input.replacen(from, to, 1)).unwrap_err().to_string();
+        assert!(message.contains(expected), "{}: {}", to, message);
+    }
+}
+
+#[test]
+fn test_parse_image_settings() {
+    let input = r#"workflow Scoring {
+        source: NATS("input");
+        agents: [ Router(id: "route") ];
+        deployment: { registry: "ghcr.io/acme", tag: "1.4.0" };
+    }"#;
+    let program = parse(input).expect("Debería parsear el registro y el tag");
+    let deployment = program.workflows[0].deployment.as_ref().unwrap();
+    assert_eq!(deployment.registry.as_deref(), Some("ghcr.io/acme"));
+    assert_eq!(deployment.tag.as_deref(), Some("1.4.0"));
+
+    for (from, to, expected) in [
+        ("ghcr.io/acme", "ghcr.io/ACME", "Invalid registry 'ghcr.io/ACME'"),
+        ("1.4.0", ".1.4", "Invalid image tag '.1.4'"),
+    ] {
+        let message = parse(&input.replacen(from, to, 1)).unwrap_err().to_string();
+        assert!(message.contains(expected), "{}: {}", to, message);
+    }
+}
+
+#[test]
+fn test_parse_custom_agent() {
+    let input = r#"workflow Fraud {
+        source: NATS("payments");
+        agents: [ Custom("fraud-scorer", id: "scorer", language: "python", threshold: 0.8) ];
+    }"#;
+    let program = parse(input).expect("Debería parsear el agente personalizado");
+    let agent = &program.workflows[0].agents[0];
+    assert_eq!(agent.agent_type, AgentType::Custom);
+    assert_eq!(agent.id.as_deref(), Some("scorer"));
+    let config = CustomAgentConfig::from_agent(agent).expect("Debería leer sus plantillas");
+    assert_eq!(config.kind, "fraud-scorer");
+    assert_eq!(config.language, "python");
+    assert_eq!(agent.config_value("threshold"), Some(&Value::Number(0.8)));
+
+    // Solo los agentes Custom reciben el nombre de sus plantillas
+    let message = parse(&input.replacen("Custom(", "Router(", 1)).unwrap_err().to_string();
+    assert!(message.contains("Only Custom agents take the name of their templates"), "{}", message);
+}
+
+#[test]
+fn test_parse_preprocessor_agents() {
+    let input = r#"workflow Payments {
+        source: NATS("payments.raw");
+        agents: [
+            MissingValueHandler(id: "fill", defaults: { "customer.tier": "basic" }, impute: { amount: median() }),
+            DataNormalizer(id: "normalize", mappings: { amount: data.payment.amount }, scale: { amount: zscore(mean: 50, std: 10) })
+        ];
+    }"#;
+    let program = parse(input).expect("Debería parsear los preprocesadores");
+    let agents = &program.workflows[0].agents;
+    assert_eq!(agents[0].agent_type, AgentType::MissingValueHandler);
+    assert_eq!(agents[1].agent_type, AgentType::DataNormalizer);
+
+    let fill = MissingValueHandlerConfig::from_agent(&agents[0]).expect("Debería leer los valores por defecto");
+    assert_eq!(fill.defaults.get("customer.tier"), Some(&Value::String("basic".to_string())));
+    assert_eq!(fill.impute.get("amount"), Some(&Imputation::Median));
+    assert_eq!(fill.window, MissingValueHandlerConfig::DEFAULT_WINDOW);
+
+    let normalize = DataNormalizerConfig::from_agent(&agents[1]).expect("Debería leer los mapeos");
+    assert_eq!(normalize.mappings, [FieldMapping { field: "amount".to_string(), from: vec!["data".into(), "payment".into(), "amount".into()] }]);
+    assert_eq!(normalize.scale.get("amount"), Some(&Scaler::ZScore { mean: 50.0, std: 10.0 }));
+    assert_eq!(normalize.source_of("amount"), ["data", "payment", "amount"]);
+}
@@ -0,0 +1,72 @@
+//! Tests for project manifests
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use kumeo_compiler::project::{Project, ProjectManifest, MANIFEST_FILE};
+use tempfile::tempdir;
+
+const MANIFEST: &str = r#"
+entry = ["workflows/fraud.kumeo", "workflows/churn.kumeo"]
+output = "build"
+targets = ["rust"]
+templates = "templates"
+offline = true
+mirrors = ["https://models.example.com=https://mirror.internal/models"]
+quotas = "platform/quotas.yaml"
+console = true
+
+[deployment]
+namespace = "fraud"
+external_nats = "nats://nats.shared:4222"
+registry = "ghcr.io/acme"
+"#;
+
+#[test]
+fn test_manifest_is_parsed() {
+    let manifest = ProjectManifest::from_toml(MANIFEST).expect("Debería leer el manifiesto");
+
+    assert_eq!(manifest.entry, [PathBuf::from("workflows/fraud.kumeo"), PathBuf::from("workflows/churn.kumeo")]);
+    assert_eq!(manifest.output.as_deref(), Some(Path::new("build")));
+    assert!(manifest.offline);
+    assert!(manifest.console);
+    assert_eq!(manifest.mirror_rules().unwrap().len(), 1);
+    assert_eq!(manifest.deployment.namespace.as_deref(), Some("fraud"));
+    assert_eq!(manifest.deployment.nats_credentials, None);
+    assert_eq!(manifest.deployment.registry.as_deref(), Some("ghcr.io/acme"));
+    assert!(manifest.targets_language("rust"));
+    assert!(!manifest.targets_language("python"), "python no está entre los targets");
+    assert!(ProjectManifest::default().targets_language("python"), "Sin targets se admiten todos los lenguajes");
+}
+
+#[test]
+fn test_invalid_manifests_are_rejected() {
+    for (manifest, reason) in [
+        ("targets = [\"go\"]", "target desconocido"),
+        ("mirrors = [\"https://models.example.com\"]", "mirror sin destino"),
+        ("outputs = \"build\"", "clave desconocida"),
+        ("[deployment]\nnamespaces = \"fraud\"", "clave de despliegue desconocida"),
+        ("entry = \"fraud.kumeo\"", "entry no es una lista"),
+    ] {
+        assert!(ProjectManifest::from_toml(manifest).is_err(), "Debería rechazar un manifiesto con {}", reason);
+    }
+}
+
+#[test]
+fn test_project_is_discovered_from_subdirectories() {
+    let root = tempdir().unwrap();
+    let nested = root.path().join("workflows/fraud");
+    fs::create_dir_all(&nested).unwrap();
+    assert_eq!(Project::discover(&nested).unwrap(), None);
+
+    fs::write(root.path().join(MANIFEST_FILE), MANIFEST).unwrap();
+    let project = Project::discover(&nested).unwrap().expect("Debería encontrar el kumeo.toml del directorio padre");
+
+    let root = fs::canonicalize(root.path()).unwrap();
+    assert_eq!(project.root, root);
+    assert_eq!(project.entries()[0], root.join("workflows/fraud.kumeo"));
+    assert_eq!(project.output_dir(), Some(root.join("build")));
+    assert_eq!(project.templates_dir(), Some(root.join("templates")));
+    assert_eq!(project.vendor_dir(), None);
+    assert_eq!(project.quotas_file(), Some(root.join("platform/quotas.yaml")));
+}
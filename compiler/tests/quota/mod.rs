@@ -0,0 +1,99 @@
+//! Tests for the platform quotas
+
+use kumeo_compiler::{
+    diagnostics::codes,
+    parse,
+    quota::{check_quotas, namespace_usage, Quota, Quotas},
+};
+
+const FRAUD: &str = r#"
+workflow Scoring {
+    source: NATS("payments");
+    agents: [
+        DataProcessor(id: "enrich"),
+        MLModel(id: "score", model_path: "models/fraud.onnx")
+    ];
+    deployment: {
+        namespace: "fraud",
+        replicas: 2,
+        scaling: { min_replicas: 2, max_replicas: 5 },
+        resources: { cpu: "500m", memory: "1Gi" }
+    };
+}
+
+workflow Alerts {
+    source: NATS("payments.scored");
+    agents: [DataProcessor(id: "alert")];
+    deployment: {
+        namespace: "fraud",
+        replicas: 3
+    };
+}
+
+workflow Archive {
+    source: NATS("payments.closed");
+    agents: [DataProcessor(id: "store")];
+}
+"#;
+
+const QUOTAS: &str = r#"
+default: { replicas: 10 }
+namespaces:
+  fraud: { cpu: "4", memory: "16Gi", replicas: 12 }
+"#;
+
+#[test]
+fn test_namespaces_are_summed_at_their_peak() {
+    let program = parse(FRAUD).expect("Debería parsear");
+    let quotas = Quotas::from_yaml(QUOTAS).expect("Debería leer las cuotas");
+    let usage = namespace_usage(&program.workflows, &quotas).expect("Debería sumar los namespaces");
+
+    // Por orden de namespace: fraud y el namespace por defecto
+    assert_eq!(usage.len(), 2);
+    let fraud = &usage[0];
+    assert_eq!(fraud.namespace, "fraud");
+    assert_eq!(fraud.workflows, ["Scoring", "Alerts"]);
+    // Scoring escala hasta 5 réplicas de dos agentes; Alerts tiene 3 réplicas de un agente Rust
+    assert_eq!(fraud.peak.pods, 10 + 3);
+    assert_eq!(fraud.peak.cpu_millis, 10 * 500 + 3 * 100);
+    assert_eq!(fraud.peak.memory_mib, 10 * 1024 + 3 * 128);
+    assert_eq!(fraud.quota, Some(Quota { cpu_millis: Some(4000), memory_mib: Some(16384), pods: Some(12) }));
+
+    let violations = fraud.violations();
+    assert_eq!(violations.len(), 2, "{:?}", violations);
+    assert_eq!(
+        violations[0],
+        "Namespace fraud requests 5300m of CPU at its peak, above its quota of 4000m (workflows Scoring, Alerts)"
+    );
+    assert!(violations[1].to_string().contains("runs up to 13 pods, above its quota of 12 replicas"), "{}", violations[1]);
+
+    // El resto de namespaces toma la cuota por defecto
+    assert_eq!(usage[1].quota.and_then(|quota| quota.pods), Some(10));
+    assert!(usage[1].violations().is_empty());
+}
+
+#[test]
+fn test_quota_violations_are_reported_at_their_first_workflow() {
+    let program = parse(FRAUD).expect("Debería parsear");
+    let quotas = Quotas::from_yaml(QUOTAS).expect("Debería leer las cuotas");
+    let diagnostics = check_quotas(&program.workflows, &quotas).expect("Debería comprobar las cuotas");
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics.iter().all(|diagnostic| diagnostic.code == codes::QUOTA_EXCEEDED && diagnostic.is_error()));
+    assert_eq!(diagnostics[0].span, program.workflows[0].span);
+
+    // Sin cuotas no se limita nada
+    let unlimited = check_quotas(&program.workflows, &Quotas::default()).expect("Debería comprobar las cuotas");
+    assert!(unlimited.is_empty());
+}
+
+#[test]
+fn test_quotas_files_are_validated() {
+    let quotas = Quotas::from_yaml("namespaces: { fraud: { memory: 512Mi } }").expect("Los demás límites son opcionales");
+    assert_eq!(quotas.quota("fraud"), Some(Quota { memory_mib: Some(512), ..Quota::default() }));
+    assert_eq!(quotas.quota("churn"), None, "Sin cuota por defecto no se limitan los demás namespaces");
+
+    let error = format!("{:#}", Quotas::from_yaml("namespaces: { fraud: { cpu: lots } }").unwrap_err());
+    assert!(error.contains("Invalid CPU quota 'lots' of namespace fraud"), "{}", error);
+    assert!(Quotas::from_yaml("default: { memory: 2 cores }").is_err(), "Debería rechazar cantidades inválidas");
+    assert!(Quotas::from_yaml("namespaces: { fraud: { gpu: 1 } }").is_err(), "Debería rechazar los límites desconocidos");
+}
@@ -1,4 +1,7 @@
-use kumeo_compiler::{parse, semantic::SemanticAnalyzer};
+use kumeo_compiler::{
+    parse,
+    semantic::{config_schema::ConfigSchemas, SemanticAnalyzer},
+};
 
 #[test]
 fn test_llm_agent_requires_model() {
@@ -69,3 +72,1090 @@ fn test_valid_llm_agent() {
     let result = analyzer.analyze_program(&program);
     assert!(result.is_ok(), "Debería ser un agente LLM válido");
 }
+
+#[test]
+fn test_directory_contexts_must_be_listable_globs() {
+    let valid = r#"
+    workflow Support {
+        source: NATS("tickets");
+        agents: [
+            DataProcessor(id: "rules", context: "file://rules/**/*.yaml")
+        ];
+    }
+    "#;
+    let program = parse(valid).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok());
+
+    for context in ["https://example.com/prompts/*.md", "file://rules/**.yaml"] {
+        let input = format!(
+            r#"workflow Support {{
+                source: NATS("tickets");
+                agents: [ DataProcessor(id: "rules", context: "{}") ];
+            }}"#,
+            context
+        );
+        let program = parse(&input).expect("Debería parsear");
+        assert!(
+            SemanticAnalyzer::new().analyze_program(&program).is_err(),
+            "Debería rechazar {}",
+            context
+        );
+    }
+}
+
+#[test]
+fn test_preloaded_models_must_resolve() {
+    let valid = [
+        r#"MLModel(id: "scorer", model_path: "s3://models/scorer.onnx", preload: true)"#,
+        r#"LLM(id: "writer", model: "llama3", preload: ["s3://models/llama3.gguf", "s3://models/tokenizer.json"])"#,
+    ];
+    let invalid = [
+        // Nothing remote to warm up
+        r#"MLModel(id: "scorer", model_path: "/models/scorer.onnx", preload: true)"#,
+        r#"LLM(id: "writer", model: "llama3", preload: ["llama3"])"#,
+        r#"MLModel(id: "scorer", model_path: "s3://models/scorer.onnx", preload: "yes")"#,
+    ];
+
+    let analyze = |agent: &str| {
+        let input = format!(
+            r#"workflow Scoring {{
+                source: NATS("events");
+                agents: [ {} ];
+            }}"#,
+            agent
+        );
+        let program = parse(&input).expect("Debería parsear");
+        SemanticAnalyzer::new().analyze_program(&program)
+    };
+
+    for agent in valid {
+        assert!(analyze(agent).is_ok(), "Debería aceptar {}", agent);
+    }
+    for agent in invalid {
+        assert!(analyze(agent).is_err(), "Debería rechazar {}", agent);
+    }
+}
+
+#[test]
+fn test_unresolved_env_references_warn() {
+    let input = r#"
+    workflow Support {
+        source: NATS("tickets");
+        agents: [
+            LLM(id: "writer", model: "gpt-4", options: {
+                api_key: "${env.KUMEO_TEST_UNSET_API_KEY}",
+                path: "${env.PATH}",
+                header: "Bearer ${token}"
+            })
+        ];
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+    // Unresolved variables only warn; the Secret provides them in the cluster
+    assert!(analyzer.analyze_program(&program).is_ok());
+
+    let warnings = analyzer.warnings();
+    assert_eq!(warnings.len(), 2, "{:?}", warnings);
+    assert!(warnings.iter().any(|w| w.contains("${env.KUMEO_TEST_UNSET_API_KEY}")));
+    assert!(warnings.iter().any(|w| w.contains("${token}")));
+}
+
+#[test]
+fn test_inline_secrets_warn() {
+    let input = r#"
+    workflow Support {
+        source: NATS("tickets");
+        agents: [
+            LLM(id: "writer", model: "gpt-4", api_key: "sk-123", options: { db_password: "hunter2", max_tokens: 100 }),
+            LLM(id: "reviewer", model: "gpt-4", api_key: secret("openai-api-key"), tokenizer: "cl100k")
+        ];
+        deployment: { env: { SLACK_TOKEN: "xoxb-1", OPENAI_API_KEY: secret("openai-api-key"), LOG_LEVEL: "debug" } };
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+    // Las credenciales en claro solo avisan: el workflow sigue siendo válido
+    assert!(analyzer.analyze_program(&program).is_ok());
+
+    let warnings: Vec<_> = analyzer
+        .diagnostics()
+        .iter()
+        .filter(|diagnostic| diagnostic.code == "KU0307")
+        .map(|diagnostic| diagnostic.message.to_string())
+        .collect();
+    assert_eq!(warnings.len(), 3, "{:?}", warnings);
+    assert!(warnings.iter().any(|w| w.contains("writer escribe su api_key")), "{:?}", warnings);
+    assert!(warnings.iter().any(|w| w.contains("writer escribe su db_password")), "{:?}", warnings);
+    assert!(warnings.iter().any(|w| w.contains("escribe SLACK_TOKEN")), "{:?}", warnings);
+}
+
+#[test]
+fn test_agent_image_is_validated() {
+    let input = r#"
+    workflow Scoring {
+        source: NATS("input");
+        agents: [ Router(id: "route", image: "ghcr.io/acme/route:1.0"), Router(id: "fallback", image: "Acme/Route") ];
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+    assert!(analyzer.analyze_program(&program).is_err(), "Una imagen inválida debería ser un error");
+    let errors: Vec<_> = analyzer
+        .diagnostics()
+        .iter()
+        .filter(|diagnostic| diagnostic.code == "KU0302")
+        .map(|diagnostic| diagnostic.message.to_string())
+        .collect();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(errors[0].contains("Imagen inválida en el agente fallback"), "{:?}", errors);
+}
+
+#[test]
+fn test_when_conditions_are_type_checked() {
+    let valid = [
+        r#"data.score > 0.8 && data.lang == "es""#,
+        r#"!data.flagged || data.user.tier != null"#,
+        r#"data.name >= "m""#,
+        r#"data.archived"#,
+    ];
+    let invalid = [
+        r#"data.score > true"#,
+        r#""es" == 3"#,
+        r#""es" > 3"#,
+        r#"data.score + 1"#,
+        r#"score > 0.8"#,
+        r#"!"es""#,
+        r#"0.8"#,
+    ];
+
+    let analyze = |condition: &str| {
+        let input = format!(
+            r#"workflow Filter {{
+                source: NATS("in");
+                agents: [DataProcessor(id: "filter", when: {})];
+            }}"#,
+            condition
+        );
+        parse(&input).map_err(|e| e.to_string()).and_then(|program| {
+            SemanticAnalyzer::new().analyze_program(&program).map_err(|e| e.to_string())
+        })
+    };
+
+    for condition in valid {
+        assert!(analyze(condition).is_ok(), "Debería aceptar {}", condition);
+    }
+    for condition in invalid {
+        assert!(analyze(condition).is_err(), "Debería rechazar {}", condition);
+    }
+}
+
+#[test]
+fn test_when_fields_are_resolved_against_the_input_schema() {
+    let valid = [
+        r#"data.score > 0.8 && data.lang == "es""#,
+        r#"data.order.total > 100"#,
+        r#"data.tags.0 == "vip""#,
+    ];
+    let invalid = [
+        r#"data.total > 100"#,
+        r#"data.score == "high""#,
+        r#"data.lang.code == "es""#,
+        r#"data.flagged > 1"#,
+    ];
+
+    let analyze = |condition: &str| {
+        let input = format!(
+            r#"workflow Filter {{
+                source: NATS("in");
+                agents: [
+                    MLModel(id: "score", model_path: "models/score.onnx", output: "scores",
+                            output_schema: {{ score: "number", lang: "string", flagged: "boolean", order: "object", tags: "array" }}),
+                    DataProcessor(id: "filter", input: "scores", when: {})
+                ];
+            }}"#,
+            condition
+        );
+        parse(&input).map_err(|e| e.to_string()).and_then(|program| {
+            SemanticAnalyzer::new().analyze_program(&program).map_err(|e| e.to_string())
+        })
+    };
+
+    for condition in valid {
+        assert!(analyze(condition).is_ok(), "Debería aceptar {}", condition);
+    }
+    for condition in invalid {
+        assert!(analyze(condition).is_err(), "Debería rechazar {}", condition);
+    }
+    assert!(analyze("data.total > 100").unwrap_err().contains("unknown field 'total'"));
+}
+
+#[test]
+fn test_optional_fields_need_null_safe_access() {
+    let valid = [
+        r#"data.customer?.email == "a@b.c""#,
+        r#"data.discount ?? 0 > 10"#,
+        r#"data.discount == null"#,
+        r#"data.total > 10"#,
+    ];
+    let invalid = [
+        r#"data.customer.email == "a@b.c""#,
+        r#"data.discount > 10"#,
+        r#"data.discount ?? "none" > 10"#,
+    ];
+
+    let analyze = |condition: &str| {
+        let input = format!(
+            r#"workflow Orders {{
+                source: NATS("in");
+                agents: [
+                    DataProcessor(id: "parse", output: "orders",
+                                  output_schema: {{ total: "number", discount: "number?", customer: "object?" }}),
+                    Router(id: "route", input: "orders", when: {})
+                ];
+            }}"#,
+            condition
+        );
+        parse(&input).map_err(|e| e.to_string()).and_then(|program| {
+            SemanticAnalyzer::new().analyze_program(&program).map_err(|e| e.to_string())
+        })
+    };
+
+    for condition in valid {
+        assert!(analyze(condition).is_ok(), "Debería aceptar {}: {:?}", condition, analyze(condition));
+    }
+    for condition in invalid {
+        assert!(analyze(condition).is_err(), "Debería rechazar {}", condition);
+    }
+    assert!(analyze(r#"data.customer.email == "a@b.c""#).unwrap_err().contains("read 'email' with ?."));
+}
+
+#[test]
+fn test_retry_and_fallback_are_validated() {
+    let valid = [
+        r#"retry: { max_attempts: 5, backoff: "1s,2s,5s" }"#,
+        r#"retry: { backoff: ["500ms", 2] }, fallback: { action: "skip" }"#,
+        r#"fallback: { action: "use_default", default: { label: "unknown" } }"#,
+        r#"fallback: { action: "dead_letter", subject: "filter.failed" }"#,
+        r#"fallback: { action: "retry_later", after: "10m", max_delays: 2 }"#,
+    ];
+    let invalid = [
+        r#"retry: { max_attempts: 0 }"#,
+        r#"retry: { max_attempts: 2.5 }"#,
+        r#"retry: { backoff: "1s,soon" }"#,
+        r#"retry: { attempts: 3 }"#,
+        r#"retry: 3"#,
+        r#"fallback: { action: "retry" }"#,
+        r#"fallback: { action: "use_default" }"#,
+        r#"fallback: { action: "dead_letter", subject: "" }"#,
+        r#"fallback: { action: "skip", subject: "filter.failed" }"#,
+        r#"fallback: { action: "retry_later", after: "later" }"#,
+    ];
+
+    let analyze = |options: &str| {
+        let input = format!(
+            r#"workflow Filter {{
+                source: NATS("in");
+                agents: [DataProcessor(id: "filter", {})];
+            }}"#,
+            options
+        );
+        parse(&input).map_err(|e| e.to_string()).and_then(|program| {
+            SemanticAnalyzer::new().analyze_program(&program).map_err(|e| e.to_string())
+        })
+    };
+
+    for options in valid {
+        assert!(analyze(options).is_ok(), "Debería aceptar {}", options);
+    }
+    for options in invalid {
+        assert!(analyze(options).is_err(), "Debería rechazar {}", options);
+    }
+
+    // Python agents don't receive delayed messages
+    let program = parse(
+        r#"workflow Filter { source: NATS("in"); agents: [MLModel(id: "score", fallback: { action: "retry_later", after: "1m" })]; }"#,
+    )
+    .unwrap();
+    let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+    assert!(error.contains("los agentes Python no reciben mensajes diferidos"), "{}", error);
+}
+
+#[test]
+fn test_config_values_are_type_checked() {
+    let analyze = |agent: &str| {
+        let input = format!(
+            r#"workflow Writer {{
+                source: NATS("in");
+                agents: [{}];
+            }}"#,
+            agent
+        );
+        let program = parse(&input).expect("Debería parsear");
+        SemanticAnalyzer::new().analyze_program(&program).map_err(|e| e.to_string())
+    };
+
+    let valid = [
+        r#"LLM(id: "writer", model: "gpt-4", prompt: "Summarize {{data}}", temperature: 0.7, max_tokens: 500)"#,
+        r#"LLM(id: "writer", model: "gpt-4", options: { temperature: 2, fallbacks: ["gpt-3.5"] }, timeout: "30s")"#,
+        r#"Router(id: "route", routes_path: "config/routes.json", debug: false)"#,
+    ];
+    for agent in valid {
+        assert!(analyze(agent).is_ok(), "Debería aceptar {}: {:?}", agent, analyze(agent));
+    }
+
+    let error = analyze(r#"LLM(id: "writer", model: "gpt-4", prompt: 42)"#).unwrap_err();
+    assert!(error.contains("'prompt' debe ser de tipo string"), "{}", error);
+    let error = analyze(r#"LLM(id: "writer", model: "gpt-4", temperature: 2.5)"#).unwrap_err();
+    assert!(error.contains("'temperature' debe estar entre 0 y 2"), "{}", error);
+    let error = analyze(r#"LLM(id: "writer", model: "gpt-4", options: { max_tokens: 10.5 })"#).unwrap_err();
+    assert!(error.contains("'options.max_tokens' debe ser de tipo integer"), "{}", error);
+    let error = analyze(r#"LLM(id: "writer", prompt: "Summarize")"#).unwrap_err();
+    assert!(error.contains("falta la opción obligatoria 'model'"), "{}", error);
+    let error = analyze(r#"Router(id: "route", timeout: "soon")"#).unwrap_err();
+    assert!(error.contains("'timeout' debe ser de tipo duration"), "{}", error);
+}
+
+#[test]
+fn test_agent_types_can_register_config_schemas() {
+    let mut schemas = ConfigSchemas::builtin();
+    schemas
+        .extend_from_json(r#"{ "dataprocessor": { "window": { "type": "integer", "min": 1, "required": true } } }"#)
+        .expect("Debería leer los esquemas");
+    assert!(schemas.extend_from_json(r#"{ "router": { "debug": { "type": "flag" } } }"#).is_err());
+
+    let analyze = |agent: &str| {
+        let input = format!(r#"workflow Batch {{ source: NATS("in"); agents: [{}]; }}"#, agent);
+        let program = parse(&input).expect("Debería parsear");
+        SemanticAnalyzer::new()
+            .with_config_schemas(schemas.clone())
+            .analyze_program(&program)
+            .map_err(|e| e.to_string())
+    };
+
+    assert!(analyze(r#"DataProcessor(id: "batch", window: 10, validate: true)"#).is_ok());
+    let error = analyze(r#"DataProcessor(id: "batch", window: 0)"#).unwrap_err();
+    assert!(error.contains("'window' debe estar al menos 1"), "{}", error);
+    let error = analyze(r#"DataProcessor(id: "batch", validate: "yes")"#).unwrap_err();
+    assert!(error.contains("falta la opción obligatoria 'window'"), "{}", error);
+    assert!(error.contains("'validate' debe ser de tipo boolean"), "{}", error);
+}
+
+#[test]
+fn test_errors_point_to_the_agent() {
+    let input = "workflow Writer {\n    source: NATS(\"in\");\n    agents: [\n        LLM(id: \"writer\", model: \"gpt-4\"),\n        LLM(id: \"editor\", temperature: 3)\n    ];\n}\n";
+    let program = parse(input).expect("Debería parsear");
+    let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+    assert!(error.contains("'temperature' debe estar entre 0 y 2"), "{}", error);
+    assert!(error.contains("--> 5:9"), "Debería señalar la línea del agente: {}", error);
+    assert!(error.contains("| LLM(id: \"editor\", temperature: 3)"), "Debería mostrar el código: {}", error);
+    assert!(!error.contains("--> 4:"), "No debería señalar otros agentes: {}", error);
+}
+
+#[test]
+fn test_assertions_are_type_checked() {
+    let analyze = |options: &str| {
+        let input = format!(
+            r#"workflow Payments {{
+                source: NATS("in");
+                agents: [
+                    DataProcessor(id: "parse", output: "parsed", output_schema: {{ amount: "number", note: "string?" }}),
+                    DataProcessor(id: "clean", input: "parsed", {})
+                ];
+            }}"#,
+            options
+        );
+        let program = parse(&input).expect("Debería parsear");
+        SemanticAnalyzer::new().analyze_program(&program).map_err(|e| e.to_string())
+    };
+
+    assert!(analyze("assert: data.amount >= 0").is_ok(), "{:?}", analyze("assert: data.amount >= 0"));
+    let error = analyze("assert: data.amount").unwrap_err();
+    assert!(error.contains("La condición assert del agente clean debe ser booleana"), "{}", error);
+    let error = analyze("assert: data.total >= 0").unwrap_err();
+    assert!(error.contains("total"), "{}", error);
+}
+
+#[test]
+fn test_quality_monitors_are_validated() {
+    let analyze = |options: &str| {
+        let input = format!(
+            r#"workflow Orders {{
+                source: NATS("orders");
+                agents: [
+                    DataProcessor(id: "clean", output: "orders.clean", output_schema: {{ id: "string", total: "number" }}),
+                    QualityMonitor(id: "quality", input: "orders.clean", output: "orders.quality", {})
+                ];
+            }}"#,
+            options
+        );
+        let program = parse(&input).expect("Debería parsear");
+        let mut analyzer = SemanticAnalyzer::new();
+        let result = analyzer.analyze_program(&program).map_err(|e| e.to_string());
+        (result, analyzer.warnings())
+    };
+
+    let (result, warnings) = analyze(r#"sample_rate: 0.05, metrics: ["mean", "conformity"], thresholds: { mean: 0.1 }"#);
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(warnings.is_empty(), "Los informes no necesitan consumidor: {:?}", warnings);
+
+    let error = analyze(r#"metrics: ["mean", "median"]"#).0.unwrap_err();
+    assert!(error.contains("unknown metric 'median'"), "{}", error);
+    let error = analyze(r#"metrics: ["mean"], thresholds: { null_rate: 0.1 }"#).0.unwrap_err();
+    assert!(error.contains("threshold for 'null_rate', which is not in metrics"), "{}", error);
+    let error = analyze("sample_rate: 0").0.unwrap_err();
+    assert!(error.contains("sample_rate must be a number above 0"), "{}", error);
+    let error = analyze("sample_rate: 1.5").0.unwrap_err();
+    assert!(error.contains("'sample_rate' debe estar entre 0 y 1"), "{}", error);
+    assert!(!error.contains("Monitor de calidad inválido"), "No debería repetir el error: {}", error);
+}
+
+#[test]
+fn test_quality_monitors_warn_about_conformity_without_schema() {
+    let input = r#"workflow Orders {
+        source: NATS("orders");
+        agents: [QualityMonitor(id: "quality", output: "orders.quality")];
+    }"#;
+    let program = parse(input).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+    assert!(analyzer.analyze_program(&program).is_ok());
+    assert!(
+        analyzer.warnings().iter().any(|w| w.contains("mide la conformidad, pero los mensajes que consume no tienen esquema")),
+        "Debería avisar de la conformidad sin esquema: {:?}",
+        analyzer.warnings()
+    );
+}
+
+#[test]
+fn test_aggregators_are_validated() {
+    let analyze = |options: &str| {
+        let input = format!(
+            r#"workflow Orders {{
+                source: NATS("orders");
+                target: NATS("orders.totals");
+                agents: [
+                    Aggregator(id: "totals", input_schema: {{ customer_id: "string", amount: "number", note: "string?" }}, {})
+                ];
+            }}"#,
+            options
+        );
+        let program = parse(&input).expect("Debería parsear");
+        SemanticAnalyzer::new().analyze_program(&program).map_err(|e| e.to_string())
+    };
+
+    let result = analyze(r#"window: { time: "1m" }, group_by: data.customer_id, reduce: { total: sum(data.amount), last_note: last(data.note), n: count() }"#);
+    assert!(result.is_ok(), "{:?}", result);
+
+    let error = analyze("reduce: { n: count() }").unwrap_err();
+    assert!(error.contains("falta la opción obligatoria 'window'"), "{}", error);
+    let error = analyze("window: { count: 0 }, reduce: { n: count() }").unwrap_err();
+    assert!(error.contains("'window.count' debe estar al menos 1"), "{}", error);
+    let error = analyze("window: { count: 10, time: \"1m\" }, reduce: { n: count() }").unwrap_err();
+    assert!(error.contains("Agregador inválido en el agente totals: give a count or a time window, not both"), "{}", error);
+    let error = analyze("window: { count: 10 }, reduce: { total: avg() }").unwrap_err();
+    assert!(error.contains("avg needs a value"), "{}", error);
+    let error = analyze("window: { count: 10 }, group_by: data.region, reduce: { n: count() }").unwrap_err();
+    assert!(error.contains("El campo data.region del agente totals no está en el esquema"), "{}", error);
+    let error = analyze("window: { count: 10 }, reduce: { total: sum(data.customer_id) }").unwrap_err();
+    assert!(error.contains("La reducción total del agente totals no puede aplicar sum a un texto"), "{}", error);
+}
+
+#[test]
+fn test_rule_engines_are_validated() {
+    let analyze = |options: &str| {
+        let input = format!(
+            r#"workflow Orders {{
+                source: NATS("orders");
+                target: NATS("orders.flagged");
+                agents: [
+                    RuleEngine(id: "risk", input_schema: {{ customer_id: "string", score: "number", vip: "boolean" }}, {})
+                ];
+            }}"#,
+            options
+        );
+        let program = parse(&input).expect("Debería parsear");
+        SemanticAnalyzer::new().analyze_program(&program).map_err(|e| e.to_string())
+    };
+
+    let result = analyze(r#"rules: [high: data.score > 0.9 => set(risk: "high"), vip: data.vip => publish(topic: "orders.vip"), test: data.customer_id == "test" => drop()]"#);
+    assert!(result.is_ok(), "{:?}", result);
+
+    let error = analyze(r#"output: "orders.flagged""#).unwrap_err();
+    assert!(error.contains("falta la opción obligatoria 'rules'"), "{}", error);
+    let error = analyze("rules: [a: data.vip => drop(), a: data.score > 1 => drop()]").unwrap_err();
+    assert!(error.contains("Motor de reglas inválido en el agente risk: two rules are named 'a'"), "{}", error);
+    let error = analyze("rules: [a: data.vip => drop(now: true)]").unwrap_err();
+    assert!(error.contains("drop takes no arguments"), "{}", error);
+    let error = analyze("rules: [a: data.region == \"eu\" => drop()]").unwrap_err();
+    assert!(error.contains("El campo data.region del agente risk no está en el esquema"), "{}", error);
+    let error = analyze("rules: [a: data.score => drop()]").unwrap_err();
+    assert!(error.contains("La regla a del agente risk debe tener una condición booleana: data.score"), "{}", error);
+    let error = analyze("rules: [a: data.score > \"high\" => drop()]").unwrap_err();
+    assert!(error.contains("compara"), "{}", error);
+}
+
+#[test]
+fn test_bayesian_networks_are_validated() {
+    let analyze = |options: &str| {
+        let input = format!(
+            r#"workflow Fraud {{
+                source: NATS("payments");
+                target: NATS("payments.scored");
+                agents: [BayesianNetwork(id: "fraud", {})];
+            }}"#,
+            options
+        );
+        let program = parse(&input).expect("Debería parsear");
+        SemanticAnalyzer::new().analyze_program(&program).map_err(|e| e.to_string())
+    };
+
+    let result = analyze(r#"network_path: "models/fraud.bif", query: ["fraud"]"#);
+    assert!(result.is_ok(), "{:?}", result);
+    // La extensión se lee sin el fragmento ni la consulta de la URI
+    let result = analyze(r#"network_path: "s3://models/fraud.XMLBIF?version=3""#);
+    assert!(result.is_ok(), "{:?}", result);
+
+    let error = analyze(r#"query: ["fraud"]"#).unwrap_err();
+    assert!(error.contains("falta la opción obligatoria 'network_path'"), "{}", error);
+    let error = analyze(r#"network_path: "models/fraud.pkl""#).unwrap_err();
+    assert!(
+        error.contains("Red bayesiana inválida en el agente fraud: the network models/fraud.pkl must be a .bif, .xmlbif, .net or .uai file"),
+        "{}",
+        error
+    );
+    let error = analyze(r#"network_path: "models/fraud.bif", query: ["fraud", "fraud"]"#).unwrap_err();
+    assert!(error.contains("query lists 'fraud' twice"), "{}", error);
+}
+
+#[test]
+fn test_custom_agents_are_validated() {
+    let analyze = |agent: &str| {
+        let input = format!(
+            r#"workflow Fraud {{
+                source: NATS("payments");
+                agents: [{}];
+            }}"#,
+            agent
+        );
+        let program = parse(&input).expect("Debería parsear");
+        SemanticAnalyzer::new().analyze_program(&program).map_err(|e| e.to_string())
+    };
+
+    // Las demás opciones llegan a las plantillas sin comprobarse
+    let result = analyze(r#"Custom("fraud-scorer", id: "scorer", language: "python", threshold: 0.8)"#);
+    assert!(result.is_ok(), "{:?}", result);
+
+    let error = analyze(r#"Custom(id: "scorer")"#).unwrap_err();
+    assert!(error.contains("Agente personalizado inválido scorer: missing the name of the templates"), "{}", error);
+    let error = analyze(r#"Custom("fraud scorer", id: "scorer")"#).unwrap_err();
+    assert!(error.contains("must be letters, digits, '-' or '_'"), "{}", error);
+    let error = analyze(r#"Custom("MLModel", id: "scorer")"#).unwrap_err();
+    assert!(error.contains("is reserved for the built-in templates"), "{}", error);
+    let error = analyze(r#"Custom("fraud-scorer", id: "scorer", language: "go")"#).unwrap_err();
+    assert!(error.contains(r#"language must be "rust" or "python""#), "{}", error);
+    let error = analyze(r#"Custom("fraud-scorer", id: "scorer", language: 3)"#).unwrap_err();
+    assert!(error.contains("Configuración inválida en el agente scorer"), "{}", error);
+}
+
+#[test]
+fn test_data_normalizers_are_validated() {
+    let analyze = |options: &str| {
+        let input = format!(
+            r#"workflow Payments {{
+                source: NATS("payments.raw");
+                target: NATS("payments.normalized");
+                agents: [
+                    DataNormalizer(id: "normalize", input_schema: {{ payment: "object", currency: "string", score: "number" }}, {})
+                ];
+            }}"#,
+            options
+        );
+        let program = parse(&input).expect("Debería parsear");
+        SemanticAnalyzer::new().analyze_program(&program).map_err(|e| e.to_string())
+    };
+
+    let result = analyze("mappings: { amount: data.payment.amount, code: data.currency }, scale: { amount: minmax(min: 0, max: 10000), score: zscore(mean: 50, std: 10) }");
+    assert!(result.is_ok(), "{:?}", result);
+
+    let error = analyze("output: \"payments.normalized\"").unwrap_err();
+    assert!(error.contains("Normalizador inválido en el agente normalize: missing mappings or scale"), "{}", error);
+    let error = analyze("mappings: { amount: payment.amount }").unwrap_err();
+    assert!(error.contains("must read a path below data"), "{}", error);
+    let error = analyze("mappings: { amount: data.payment.amount, total: data.payment.amount }").unwrap_err();
+    assert!(error.contains("two mappings read data.payment.amount"), "{}", error);
+    let error = analyze("scale: { score: minmax(min: 10, max: 0) }").unwrap_err();
+    assert!(error.contains("the max of minmax must be above its min"), "{}", error);
+    let error = analyze("scale: { score: zscore(mean: 50) }").unwrap_err();
+    assert!(error.contains("zscore needs mean and std"), "{}", error);
+    let error = analyze("mappings: { amount: data.order.total }").unwrap_err();
+    assert!(error.contains("El campo data.order.total del agente normalize no está en el esquema"), "{}", error);
+    // Los campos mapeados se escalan con el tipo de su origen
+    let error = analyze("mappings: { amount: data.currency }, scale: { amount: minmax(min: 0, max: 1) }").unwrap_err();
+    assert!(error.contains("El agente normalize no puede escalar amount con minmax: es un texto"), "{}", error);
+    let error = analyze("mappings: 3").unwrap_err();
+    assert!(error.contains("Configuración inválida en el agente normalize"), "{}", error);
+}
+
+#[test]
+fn test_missing_value_handlers_are_validated() {
+    let analyze = |options: &str| {
+        let input = format!(
+            r#"workflow Payments {{
+                source: NATS("payments.raw");
+                target: NATS("payments.completed");
+                agents: [
+                    MissingValueHandler(id: "fill", input_schema: {{ amount: "number?", country: "string?", flagged: "boolean?" }}, {})
+                ];
+            }}"#,
+            options
+        );
+        let program = parse(&input).expect("Debería parsear");
+        SemanticAnalyzer::new().analyze_program(&program).map_err(|e| e.to_string())
+    };
+
+    let result = analyze(r#"defaults: { country: "US", flagged: false }, impute: { amount: median() }, window: 200"#);
+    assert!(result.is_ok(), "{:?}", result);
+
+    let error = analyze("window: 10").unwrap_err();
+    assert!(error.contains("Manejador de valores ausentes inválido en el agente fill: missing defaults or impute"), "{}", error);
+    let error = analyze("defaults: { country: null }").unwrap_err();
+    assert!(error.contains("the default of country can't be null"), "{}", error);
+    let error = analyze(r#"defaults: { amount: 0 }, impute: { amount: mean() }"#).unwrap_err();
+    assert!(error.contains("amount has both a default and an imputation"), "{}", error);
+    let error = analyze("impute: { amount: mode() }").unwrap_err();
+    assert!(error.contains("unknown imputation 'mode'"), "{}", error);
+    let error = analyze("impute: { amount: mean() }, window: 0").unwrap_err();
+    assert!(error.contains("'window' debe estar al menos 1"), "{}", error);
+    let error = analyze(r#"defaults: { region: "eu" }"#).unwrap_err();
+    assert!(error.contains("El campo data.region del agente fill no está en el esquema"), "{}", error);
+    let error = analyze("defaults: { country: 34 }").unwrap_err();
+    assert!(error.contains("El valor por defecto de data.country en el agente fill es un número y el valor un texto"), "{}", error);
+    let error = analyze("impute: { country: mean() }").unwrap_err();
+    assert!(error.contains("El agente fill no puede imputar country con mean(): es un texto"), "{}", error);
+    // last() rellena cualquier tipo
+    let result = analyze("impute: { country: last() }");
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_drift_is_validated_on_ml_agents_only() {
+    let analyze = |agent: &str| {
+        let input = format!(
+            r#"workflow Orders {{
+                source: NATS("orders");
+                agents: [{}];
+            }}"#,
+            agent
+        );
+        let program = parse(&input).expect("Debería parsear");
+        SemanticAnalyzer::new().analyze_program(&program).map_err(|e| e.to_string())
+    };
+
+    let result = analyze(r#"MLModel(id: "score", model_path: "models/score.onnx", drift: { reference: resource("s3://profiles/score.json"), method: "psi", threshold: 0.2 })"#);
+    assert!(result.is_ok(), "{:?}", result);
+
+    let error = analyze(r#"MLModel(id: "score", model_path: "models/score.onnx", drift: { reference: resource("s3://profiles/score.json"), method: "chi2" })"#).unwrap_err();
+    assert!(error.contains("Drift inválido en el agente score: unknown method 'chi2'"), "{}", error);
+    let error = analyze(r#"MLModel(id: "score", model_path: "models/score.onnx", drift: { reference: resource("s3://profiles/score.json"), bins: 10 })"#).unwrap_err();
+    assert!(error.contains("unknown drift setting 'bins'"), "{}", error);
+    let error = analyze(r#"MLModel(id: "score", model_path: "models/score.onnx", drift: { method: "psi" })"#).unwrap_err();
+    assert!(error.contains("falta el campo obligatorio 'drift.reference'"), "{}", error);
+    assert!(!error.contains("Drift inválido"), "No debería repetir el error: {}", error);
+    let error = analyze(r#"LLM(id: "summarize", model: "gpt-4", drift: { reference: "s3://profiles/score.json" })"#).unwrap_err();
+    assert!(error.contains("no admite drift"), "{}", error);
+}
+
+#[test]
+fn test_feature_store_keys_are_checked_against_the_input_schema() {
+    let analyze = |features: &str| {
+        let input = format!(
+            r#"workflow Rides {{
+                source: NATS("rides");
+                agents: [
+                    DataProcessor(id: "clean", output: "rides.clean", output_schema: {{ driver_id: "string", zone: "integer?", pickup: "object" }}),
+                    MLModel(id: "eta", model_path: "models/eta.onnx", input: "rides.clean", features: {})
+                ];
+            }}"#,
+            features
+        );
+        let program = parse(&input).expect("Debería parsear");
+        let mut analyzer = SemanticAnalyzer::new();
+        let result = analyzer.analyze_program(&program).map_err(|e| e.to_string());
+        (result, analyzer.warnings())
+    };
+
+    let (result, warnings) = analyze(r#"Feast("driver_stats", keys: ["driver_id"])"#);
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(warnings.is_empty(), "{:?}", warnings);
+
+    let (result, warnings) = analyze(r#"Feast("zone_stats", keys: ["zone"])"#);
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(warnings.iter().any(|w| w.contains("'zone'") && w.contains("es opcional")), "{:?}", warnings);
+
+    let error = analyze(r#"Feast("driver_stats", keys: ["driverid"])"#).0.unwrap_err();
+    assert!(error.contains("La clave 'driverid' de la feature view 'driver_stats'"), "{}", error);
+    assert!(error.contains("driver_id"), "Debería sugerir el campo parecido: {}", error);
+    let error = analyze(r#"Feast("pickup_stats", keys: ["pickup"])"#).0.unwrap_err();
+    assert!(error.contains("es de tipo object"), "{}", error);
+    let error = analyze(r#"Feast("driver_stats")"#).0.unwrap_err();
+    assert!(error.contains("needs the keys identifying its entities"), "{}", error);
+}
+
+#[test]
+fn test_feature_stores_are_for_ml_agents_only() {
+    let input = r#"workflow Rides {
+        source: NATS("rides");
+        agents: [LLM(id: "summary", model: "llama3", features: Feast("driver_stats", keys: ["driver_id"]))];
+    }"#;
+    let program = parse(input).expect("Debería parsear");
+    let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+    assert!(error.contains("no admite features"), "{}", error);
+}
+
+#[test]
+fn test_inference_servers_are_for_llm_agents_only() {
+    let input = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [MLModel(id: "scorer", model_path: "models/s.onnx", provider: VLLM(model: "llama3"))];
+    }"#;
+    let program = parse(input).expect("Debería parsear");
+    let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+    assert!(error.contains("no admite un servidor de inferencia"), "{}", error);
+}
+
+#[test]
+fn test_llm_providers_are_validated() {
+    let valid = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [
+            LLM(id: "triage", model: "llama3", provider: VLLM(endpoint: "http://vllm:8000", max_batch_size: 8)),
+            LLM(id: "reply", model: "gpt-4", provider: "openai")
+        ];
+    }"#;
+    let program = parse(valid).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok(), "Debería aceptar servidores y proveedores alojados");
+
+    let invalid = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [LLM(id: "triage", model: "llama3", provider: TGI(endpoint: "tgi:80", gpus: 0))];
+    }"#;
+    let program = parse(invalid).expect("Debería parsear");
+    let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+    assert!(error.contains("Proveedor inválido en el agente triage"), "{}", error);
+}
+
+#[test]
+fn test_provider_specific_options_are_validated() {
+    let valid = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [
+            LLM(id: "claude", model: "claude-3-5-sonnet", provider: Anthropic(), options: { max_tokens: 1024 }),
+            LLM(id: "local", model: "llama3", provider: { ollama: { endpoint: "http://ollama:11434/v1" } }),
+            LLM(id: "named", model: "llama3", provider: "Ollama")
+        ];
+    }"#;
+    let program = parse(valid).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok(), "Debería aceptar proveedores con sus opciones");
+
+    let cases = [
+        (r#"LLM(id: "reply", model: "claude-3-5-sonnet", provider: "anthropic")"#, "requires max_tokens"),
+        (r#"LLM(id: "reply", model: "gpt-4o", provider: "azure")"#, "unknown provider 'azure'"),
+        (r#"LLM(id: "reply", model: "llama3", provider: Ollama(), api_key: "${env.KEY}")"#, "doesn't accept api_key"),
+        (r#"LLM(id: "reply", model: "gpt-4o", provider: OpenAI(endpoint: "api.internal"))"#, "http(s) URL"),
+    ];
+    for (agent, expected) in cases {
+        let input = format!(r#"workflow Support {{ source: NATS("tickets"); agents: [{}]; }}"#, agent);
+        let program = parse(&input).expect("Debería parsear");
+        let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+        assert!(error.contains("Proveedor inválido en el agente reply"), "{}", error);
+        assert!(error.contains(expected), "{}: {}", expected, error);
+    }
+
+    // Todos los proveedores de una cadena reciben las opciones del agente
+    let chain = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [LLM(id: "reply", model: "gpt-4o", providers: [OpenAI(), Anthropic(claude-3-5-sonnet)])];
+    }"#;
+    let program = parse(chain).expect("Debería parsear");
+    let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+    assert!(error.contains("Cadena de proveedores inválida en el agente reply"), "{}", error);
+    assert!(error.contains("requires max_tokens"), "{}", error);
+}
+
+#[test]
+fn test_engine_is_ignored_with_a_warning() {
+    let input = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [LLM(id: "reply", model: "llama3", engine: "ollama/llama3")];
+    }"#;
+    let program = parse(input).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+    assert!(analyzer.analyze_program(&program).is_ok(), "engine solo avisa");
+    assert!(
+        analyzer.warnings().iter().any(|w| w.contains("El agente reply ignora engine: \"ollama/llama3\"")),
+        "{:?}",
+        analyzer.warnings()
+    );
+}
+
+#[test]
+fn test_provider_chains_are_validated() {
+    let valid = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [LLM(id: "reply", model: "llama3", providers: [OpenAI(gpt-4o, timeout: "10s"), Ollama(llama3)])];
+    }"#;
+    let program = parse(valid).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok(), "Debería aceptar cadenas de proveedores");
+
+    let both = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [LLM(id: "reply", model: "llama3", provider: "openai", providers: [Ollama(llama3)])];
+    }"#;
+    let program = parse(both).expect("Debería parsear");
+    let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+    assert!(error.contains("declara provider y providers"), "{}", error);
+
+    let invalid = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [LLM(id: "reply", model: "llama3", providers: [OpenAI(gpt-4o, retries: 3)])];
+    }"#;
+    let program = parse(invalid).expect("Debería parsear");
+    let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+    assert!(error.contains("Cadena de proveedores inválida en el agente reply"), "{}", error);
+}
+
+#[test]
+fn test_provider_chains_are_for_llm_agents_only() {
+    let input = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [MLModel(id: "scorer", model_path: "models/s.onnx", providers: [OpenAI(gpt-4o)])];
+    }"#;
+    let program = parse(input).expect("Debería parsear");
+    let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+    assert!(error.contains("no admite providers"), "{}", error);
+}
+
+#[test]
+fn test_guardrails_are_validated() {
+    let valid = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [
+            LLM(id: "reply", model: "llama3", guardrails: { input: [pii_block], output: [toxicity(action: review)], review: "moderator" }),
+            HumanReview(id: "moderator")
+        ];
+    }"#;
+    let program = parse(valid).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok(), "Debería aceptar los guardrails");
+
+    let invalid = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [LLM(id: "reply", model: "llama3", guardrails: { input: [pii_block(action: review)] })];
+    }"#;
+    let program = parse(invalid).expect("Debería parsear");
+    let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+    assert!(error.contains("Guardrails inválidos en el agente reply"), "{}", error);
+}
+
+#[test]
+fn test_guardrail_reviews_go_to_human_review_agents() {
+    let input = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [
+            LLM(id: "reply", model: "llama3", guardrails: { output: [toxicity(action: review)], review: "summary" }),
+            LLM(id: "summary", model: "llama3")
+        ];
+    }"#;
+    let program = parse(input).expect("Debería parsear");
+    let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+    assert!(error.contains("que no es un agente HumanReview del workflow"), "{}", error);
+
+    let input = r#"workflow Support {
+        source: NATS("tickets");
+        agents: [MLModel(id: "scorer", model_path: "models/s.onnx", guardrails: { input: [pii_block] })];
+    }"#;
+    let program = parse(input).expect("Debería parsear");
+    let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+    assert!(error.contains("no admite guardrails"), "{}", error);
+}
+
+#[test]
+fn test_session_memory_is_validated() {
+    let valid = r#"workflow Chat {
+        source: NATS("messages");
+        agents: [LLM(id: "reply", model: "llama3", input_schema: { session_id: "string", prompt: "string" }, memory: { scope: "session_id", window: 10, store: state, ttl: "1h" })];
+    }"#;
+    let program = parse(valid).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok(), "Debería aceptar la memoria");
+
+    let cases = [
+        (r#"LLM(id: "reply", model: "llama3", memory: { scope: "session_id", store: redis })"#, "Memoria inválida en el agente reply"),
+        (r#"LLM(id: "reply", model: "llama3", memory: { window: 10 })"#, "scope"),
+        (r#"LLM(id: "reply", model: "llama3", input_schema: { user: "string" }, memory: { scope: "session_id" })"#, "no es un campo de los mensajes que consume"),
+        (r#"LLM(id: "reply", model: "llama3", input_schema: { session_id: "object" }, memory: { scope: "session_id" })"#, "campos string o integer"),
+        (r#"MLModel(id: "scorer", model_path: "models/s.onnx", memory: { scope: "session_id" })"#, "no admite memory"),
+    ];
+    for (agent, expected) in cases {
+        let input = format!(r#"workflow Chat {{ source: NATS("messages"); agents: [{}]; }}"#, agent);
+        let program = parse(&input).expect("Debería parsear");
+        let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+        assert!(error.contains(expected), "{}: {}", agent, error);
+    }
+}
+
+#[test]
+fn test_token_budgets_are_validated() {
+    let valid = r#"workflow Chat {
+        source: NATS("messages");
+        agents: [LLM(id: "reply", model: "gpt-4o", budget: { input_tokens: 20000000, output_tokens: 4000000 })];
+    }"#;
+    let program = parse(valid).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok(), "Debería aceptar el presupuesto");
+
+    let cases = [
+        (r#"LLM(id: "reply", model: "gpt-4o", budget: { tokens: 1000 })"#, "unknown budget setting 'tokens'"),
+        (r#"LLM(id: "reply", model: "gpt-4o", budget: { input_tokens: 1.5 })"#, "input_tokens must be a whole number"),
+        (r#"LLM(id: "reply", model: "gpt-4o", budget: { input_tokens: 0 })"#, "needs input_tokens or output_tokens"),
+        (r#"Router(id: "route", budget: { input_tokens: 1000 })"#, "no admite budget"),
+    ];
+    for (agent, expected) in cases {
+        let input = format!(r#"workflow Chat {{ source: NATS("messages"); agents: [{}]; }}"#, agent);
+        let program = parse(&input).expect("Debería parsear");
+        let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+        assert!(error.contains(expected), "{}: {}", agent, error);
+    }
+}
+
+#[test]
+fn test_review_sla_is_validated() {
+    let valid = r#"workflow Payments {
+        source: NATS("payments");
+        agents: [HumanReview(id: "approval", sla: "4h", on_sla_breach: approve, escalation: [{ after: "1h", notify: ["finance"], via: "email" }], delegation: { finance: ["alice", "bob"] }, timeout: "30m", on_timeout: NATS("payments.unreviewed"), notifications: ["slack"], ui: false)];
+    }"#;
+    let program = parse(valid).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok(), "Debería aceptar el SLA");
+
+    let cases = [
+        (r#"HumanReview(id: "approval", sla: "4h", escalation: [{ after: "6h", notify: ["cfo"], via: "email" }])"#, "SLA inválido en el agente approval"),
+        (r#"HumanReview(id: "approval", on_sla_breach: reject)"#, "on_sla_breach needs an sla"),
+        (r#"HumanReview(id: "approval", sla: true)"#, "sla"),
+        (r#"Router(id: "route", sla: "4h")"#, "no admite sla"),
+        (r#"HumanReview(id: "approval", audit: { issuer: "http://sso.example.com" })"#, "Auditoría inválida en el agente approval"),
+        (r#"Router(id: "route", audit: { issuer: "https://sso.example.com" })"#, "no admite audit"),
+        (r#"HumanReview(id: "approval", sla: "1h", timeout: "2h")"#, "Revisión inválida en el agente approval"),
+        (r#"HumanReview(id: "approval", timeout: "pronto")"#, "'timeout' debe ser de tipo duration"),
+        (r#"HumanReview(id: "approval", on_timeout: NATS("payments.>"))"#, "Revisión inválida en el agente approval"),
+        (r#"HumanReview(id: "approval", notifications: "slack")"#, "'notifications' debe ser de tipo array"),
+    ];
+    for (agent, expected) in cases {
+        let input = format!(r#"workflow Payments {{ source: NATS("payments"); agents: [{}]; }}"#, agent);
+        let program = parse(&input).expect("Debería parsear");
+        let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+        assert!(error.contains(expected), "{}: {}", agent, error);
+    }
+}
+
+#[test]
+fn test_oidc_auth_is_validated() {
+    let valid = r#"workflow Payments {
+        source: HTTP("/payments", { auth: OIDC("https://sso.example.com", "payments", allow: ["ops"]) });
+        agents: [
+            Router(id: "intake"),
+            HumanReview(id: "approval", auth: OIDC("https://sso.example.com", "reviews", "roles", allow: ["finance"]))
+        ];
+    }"#;
+    let program = parse(valid).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok(), "Debería aceptar auth");
+
+    let cases = [
+        (r#"NATS("payments", { auth: OIDC("https://sso.example.com", "payments") })"#, r#"Router(id: "route")"#, "Solo las fuentes HTTP admiten auth"),
+        (r#"HTTP("/payments", { auth: OIDC("http://sso.example.com", "payments") })"#, r#"Router(id: "route")"#, "Autenticación inválida en la fuente HTTP"),
+        (r#"HTTP("/payments", { auth: OIDC("https://sso.example.com", "payments") })"#, r#"MLModel(id: "score", model: "score.onnx")"#, "que se genera en Python"),
+        (r#"NATS("payments")"#, r#"HumanReview(id: "approval", auth: OIDC("https://sso.example.com", ""))"#, "Autenticación inválida en el agente approval"),
+        (r#"NATS("payments")"#, r#"HumanReview(id: "approval", auth: OIDC("https://sso.example.com", "reviews"), audit: { issuer: "https://sso.example.com" })"#, "quítalos de audit"),
+        (r#"NATS("payments")"#, r#"Router(id: "route", auth: OIDC("https://sso.example.com", "reviews"))"#, "no admite auth"),
+    ];
+    for (source, agent, expected) in cases {
+        let input = format!(r#"workflow Payments {{ source: {}; agents: [{}]; }}"#, source, agent);
+        let program = parse(&input).expect("Debería parsear");
+        let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+        assert!(error.contains(expected), "{} / {}: {}", source, agent, error);
+    }
+}
+
+#[test]
+fn test_routing_key_is_validated() {
+    let valid = r#"workflow Orders {
+        source: NATS("orders");
+        agents: [Router(id: "shard", input_schema: { customer_id: "string", total: "number" }, strategy: hash(key: data.customer_id, partitions: 4))];
+    }"#;
+    let program = parse(valid).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok(), "Debería aceptar la estrategia");
+
+    let cases = [
+        (r#"Router(id: "shard", strategy: hash(key: customer_id))"#, "Estrategia de enrutamiento inválida en el agente shard"),
+        (r#"Router(id: "shard", input_schema: { customer_id: "string" }, strategy: hash(key: data.customer))"#, "no está en el esquema de sus mensajes"),
+        (r#"Router(id: "shard", input_schema: { total: "number" }, strategy: hash(key: data.total))"#, "campos string o integer"),
+        (r#"Router(id: "shard", input_schema: { customer_id: "string" }, strategy: hash(key: data.customer_id.region))"#, "has no field 'region'"),
+        (r#"LLM(id: "reply", model: "llama3", strategy: hash(key: data.customer_id))"#, "no admite strategy"),
+    ];
+    for (agent, expected) in cases {
+        let input = format!(r#"workflow Orders {{ source: NATS("orders"); agents: [{}]; }}"#, agent);
+        let program = parse(&input).expect("Debería parsear");
+        let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+        assert!(error.contains(expected), "{}: {}", agent, error);
+    }
+}
+
+#[test]
+fn test_route_table_resource_is_validated() {
+    let valid = r#"workflow Orders {
+        source: NATS("orders");
+        agents: [Router(id: "route", rules: resource("s3://config/routes.yaml")), DataProcessor(id: "clean", rules: "rules/clean.json")];
+    }"#;
+    let program = parse(valid).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok(), "Debería aceptar la tabla de rutas");
+
+    let cases = [
+        (r#"Router(id: "route", rules: resource("s3://config/routes.txt"))"#, "must be a .yaml, .yml or .json file"),
+        (r#"Router(id: "route", rules: resource(""))"#, "Tabla de rutas inválida en el agente route"),
+        (r#"Router(id: "route", rules: 3)"#, "rules must be a resource"),
+        (r#"Router(id: "route", rules: resource("https://config.example.com/routes.json"), routes_path: "config/routes.json")"#, "usa solo rules"),
+    ];
+    for (agent, expected) in cases {
+        let input = format!(r#"workflow Orders {{ source: NATS("orders"); agents: [{}]; }}"#, agent);
+        let program = parse(&input).expect("Debería parsear");
+        let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+        assert!(error.contains(expected), "{}: {}", agent, error);
+    }
+}
+
+#[test]
+fn test_compensations_are_validated() {
+    let valid = r#"workflow Orders {
+        source: NATS("orders");
+        agents: [
+            DataProcessor(id: "reserve", compensate: NATS("stock.release", correlation: data.order_id)),
+            Router(id: "charge", compensate: NATS("payments.refund", correlation: data.order_id))
+        ];
+    }"#;
+    let program = parse(valid).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok(), "Debería aceptar la saga");
+
+    let cases = [
+        (r#"MLModel(id: "score", compensate: NATS("scores.undo"))"#, "los agentes Python no toman parte en sagas"),
+        (r#"DataProcessor(id: "reserve", compensate: "stock.release")"#, "Compensación inválida en el agente reserve"),
+        (
+            r#"DataProcessor(id: "reserve", compensate: NATS("stock.release")), Router(id: "charge", compensate: NATS("payments.refund", correlation: data.order_id))"#,
+            "pero los pasos anteriores por data.correlation_id",
+        ),
+    ];
+    for (agents, expected) in cases {
+        let input = format!(r#"workflow Orders {{ source: NATS("orders"); agents: [{}]; }}"#, agents);
+        let program = parse(&input).expect("Debería parsear");
+        let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+        assert!(error.contains(expected), "{}: {}", agents, error);
+    }
+}
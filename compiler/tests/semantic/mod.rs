@@ -3,6 +3,9 @@
 mod workflow_validation;
 mod subworkflow_validation;
 mod agent_validation;
+mod schema_evolution;
+mod resource_checks;
+mod versioning;
 
 use kumeo_compiler::{parse, semantic::SemanticAnalyzer};
 
@@ -0,0 +1,40 @@
+use kumeo_compiler::{parse, semantic::resources::check_resources, vendor::mirror::MirrorRule};
+use tempfile::tempdir;
+
+const PROGRAM: &str = r#"workflow Orders {
+    source: NATS("orders");
+    agents: [Router(id: "route", rules: resource("s3://config/routes.yaml"))];
+}"#;
+
+#[tokio::test]
+async fn test_route_tables_are_fetched_and_checked() {
+    let dir = tempdir().expect("Debería crear el directorio");
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    let table = dir.path().join("config/routes.yaml");
+    std::fs::write(&table, "- id: eu\n  pattern: 'eu'\n  actions:\n    - type: publish\n      target: orders.eu\n    - type: delay\n      target: orders.eu.retry\n      after: 10m\n").unwrap();
+    let program = parse(PROGRAM).expect("Debería parsear");
+
+    // The compiler can't reach s3:// without a mirror
+    let diagnostics = check_resources(&program, dir.path(), &[]).await;
+    assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+    assert!(diagnostics[0].message.to_string().contains("No se pudo descargar la tabla de rutas del agente route"), "{}", diagnostics[0]);
+
+    let mirrors = MirrorRule::parse_list("s3://config/*=file://config/*").unwrap();
+    let diagnostics = check_resources(&program, dir.path(), &mirrors).await;
+    assert!(diagnostics.is_empty(), "La tabla debería ser válida: {:?}", diagnostics);
+
+    let invalid = [
+        ("- id: eu\n  pattern: '(eu'\n  actions: []\n", "invalid pattern of route 'eu'"),
+        ("- id: eu\n  pattern: 'eu'\n  actions:\n    - type: publish\n      target: ''\n", "an action without a target"),
+        ("- id: eu\n  pattern: 'eu'\n  actions: []\n- id: eu\n  pattern: 'us'\n  actions: []\n", "listed twice"),
+        ("- id: eu\n  pattern: 'eu'\n  actions:\n    - type: delay\n      target: orders.eu\n      after: soon\n", "invalid interval 'soon'"),
+        ("routes: {}", "invalid YAML routing table"),
+    ];
+    for (contents, expected) in invalid {
+        std::fs::write(&table, contents).unwrap();
+        let diagnostics = check_resources(&program, dir.path(), &mirrors).await;
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        assert!(diagnostics[0].message.to_string().contains(expected), "{}: {}", contents, diagnostics[0]);
+        assert_eq!(diagnostics[0].code, "KU0902");
+    }
+}
@@ -0,0 +1,83 @@
+use kumeo_compiler::{
+    parse,
+    semantic::{catalog::SchemaCatalog, SemanticAnalyzer},
+};
+
+/// Programa con un workflow que publica puntuaciones y otro que las consume
+fn program(schema: &str, consumed: bool) -> String {
+    let consumer = if consumed { "scores" } else { "other" };
+    format!(
+        r#"
+        workflow Scoring {{
+            source: NATS("events");
+            target: NATS("scores");
+            agents: [MLModel(id: "score", model_path: "models/score.onnx", {})];
+        }}
+        workflow Alerts {{
+            source: NATS("{}");
+            target: NATS("alerts");
+            agents: [Router(id: "route")];
+        }}
+        "#,
+        schema, consumer
+    )
+}
+
+#[test]
+fn test_breaking_schema_changes_require_a_version_bump() {
+    let recorded = parse(&program(r#"output_schema: { id: "string", score: "number" }"#, true))
+        .expect("Debería parsear");
+    let mut catalog = SchemaCatalog::default();
+    catalog.record(&recorded);
+
+    let analyze = |schema: &str, consumed: bool| {
+        let program = parse(&program(schema, consumed)).expect("Debería parsear");
+        SemanticAnalyzer::new()
+            .with_schema_catalog(catalog.clone())
+            .analyze_program(&program)
+    };
+
+    // Añadir campos es compatible
+    assert!(analyze(r#"output_schema: { id: "string", score: "number", label: "string" }"#, true).is_ok());
+
+    let error = analyze(r#"output_schema: { id: "string" }"#, true)
+        .expect_err("Debería rechazar que se elimine un campo sin cambiar de versión");
+    assert!(error.to_string().contains("se elimina el campo 'score'"));
+    assert!(error.to_string().contains("Alerts"));
+
+    let error = analyze(r#"output_schema: { id: "string", score: "string" }"#, true)
+        .expect_err("Debería rechazar que un campo cambie de tipo sin cambiar de versión");
+    assert!(error.to_string().contains("el campo 'score' pasa de number a string"));
+
+    // Incrementar la versión acepta el cambio
+    assert!(analyze(r#"output_schema: { id: "string" }, schema_version: 2"#, true).is_ok());
+
+    // Sin consumidores el esquema puede cambiar libremente
+    assert!(analyze(r#"output_schema: { id: "string" }"#, false).is_ok());
+
+    assert!(analyze(r#"output_schema: { id: "uuid" }"#, true).is_err());
+    assert!(analyze(r#"schema_version: 2"#, true).is_err());
+}
+
+#[test]
+fn test_schema_catalog_keeps_every_version() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut catalog = SchemaCatalog::load(dir.path()).unwrap();
+    assert!(!catalog.has_schemas());
+
+    catalog.record(&parse(&program(r#"output_schema: { id: "string", score: "number" }"#, true)).unwrap());
+    catalog.record(&parse(&program(r#"output_schema: { id: "string" }, schema_version: 2"#, true)).unwrap());
+    catalog.save(dir.path()).unwrap();
+
+    let catalog = SchemaCatalog::load(dir.path()).unwrap();
+    let scores = &catalog.topics["scores"];
+    assert_eq!(scores.producer.as_deref(), Some("Scoring"));
+    assert_eq!(scores.consumers.iter().collect::<Vec<_>>(), ["Alerts"]);
+    assert_eq!(scores.versions.keys().collect::<Vec<_>>(), [&1, &2]);
+    assert_eq!(catalog.latest("scores").unwrap().0, 2);
+
+    // Un workflow que deja de consumir el topic deja de protegerlo
+    let mut catalog = catalog;
+    catalog.record(&parse(&program(r#"output_schema: { id: "string" }, schema_version: 2"#, false)).unwrap());
+    assert!(catalog.consumers("scores").next().is_none());
+}
@@ -38,3 +38,35 @@ fn test_duplicate_subworkflow_names() {
     let result = analyzer.analyze_program(&program);
     assert!(result.is_err(), "Debería fallar por nombres duplicados");
 }
+
+#[test]
+fn test_subworkflow_calls_must_match_declaration() {
+    let analyze = |call: &str| {
+        let input = format!(
+            r#"
+            workflow Orders {{
+                source: NATS("orders.raw");
+                agents: [{}];
+            }}
+            subworkflow Enrich {{
+                input: ["raw"];
+                output: ["enriched", "rejected"];
+                agents: [DataProcessor(id: "cleaner")];
+            }}
+            "#,
+            call
+        );
+        let program = parse(&input).expect("Debería parsear");
+        SemanticAnalyzer::new().analyze_program(&program)
+    };
+
+    assert!(analyze(r#"use Enrich(input: "orders.raw", output: ["orders.clean", "orders.rejected"])"#).is_ok());
+
+    let error = analyze(r#"use Enrich(input: "orders.raw", output: "orders.clean")"#)
+        .expect_err("Debería rechazar las salidas que no coinciden");
+    assert!(error.to_string().contains("espera 2 salidas (enriched, rejected) y recibe 1"));
+
+    let error = analyze(r#"use Missing(input: "orders.raw", output: "orders.clean")"#)
+        .expect_err("Debería rechazar el subworkflow no definido");
+    assert!(error.to_string().contains("Subworkflow no definido: Missing"));
+}
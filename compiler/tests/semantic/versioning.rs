@@ -0,0 +1,93 @@
+use kumeo_compiler::{
+    parse,
+    semantic::{
+        versioning::{diff_programs, ChangeClass, Version},
+        SemanticAnalyzer,
+    },
+};
+
+/// Programa con un workflow de pedidos en la versión dada, con `extra` en el clasificador
+fn orders(version: &str, target: &str, extra: &str) -> String {
+    format!(
+        r#"
+        workflow Orders {{
+            version: "{}";
+            source: NATS("orders");
+            target: NATS("{}");
+            agents: [
+                LLM(id: "classify", model: "llama3", output: "orders.classified", output_schema: {{ label: "string" }}{}),
+                Router(id: "route", input: "orders.classified")
+            ];
+        }}
+        "#,
+        version, target, extra
+    )
+}
+
+#[test]
+fn test_versions_are_semantic() {
+    assert_eq!(Version::parse("2.1.0"), Some(Version { major: 2, minor: 1, patch: 0 }));
+    for invalid in ["2.1", "2.1.0.1", "v2.1.0", "2.01.0", "2.1.x", ""] {
+        assert_eq!(Version::parse(invalid), None, "{}", invalid);
+    }
+    let version = Version::parse("2.1.3").unwrap();
+    assert_eq!(version.bump(ChangeClass::Major).to_string(), "3.0.0");
+    assert_eq!(version.bump(ChangeClass::Minor).to_string(), "2.2.0");
+    assert_eq!(version.bump_to(Version::parse("2.1.4").unwrap()), Some(ChangeClass::Patch));
+    assert_eq!(version.bump_to(version), None);
+
+    let program = parse(&orders("2.1.0", "orders.done", "")).expect("Debería parsear");
+    assert_eq!(program.workflows[0].version.as_deref(), Some("2.1.0"));
+    let program = parse(&orders("latest", "orders.done", "")).expect("Debería parsear");
+    let error = SemanticAnalyzer::new().analyze_program(&program).unwrap_err().to_string();
+    assert!(error.contains("KU0107"), "{}", error);
+}
+
+#[test]
+fn test_changes_are_classified() {
+    let previous = parse(&orders("2.1.0", "orders.done", "")).expect("Debería parsear");
+
+    // Renaming a topic breaks its consumers
+    let current = parse(&orders("2.1.0", "orders.completed", "")).expect("Debería parsear");
+    let diff = &diff_programs(&previous, &current)[0];
+    assert_eq!(diff.class(), Some(ChangeClass::Major));
+    assert!(diff.changes.iter().any(|change| change.description.to_string().contains("el destino pasa de 'orders.done' a 'orders.completed'")));
+
+    // New options and schema fields are compatible
+    let current = parse(&orders("2.1.0", "orders.done", r#", timeout: "5s""#)).expect("Debería parsear");
+    assert_eq!(diff_programs(&previous, &current)[0].class(), Some(ChangeClass::Minor));
+    let extended = orders("2.1.0", "orders.done", "").replace(r#"{ label: "string" }"#, r#"{ label: "string", reason: "string" }"#);
+    let current = parse(&extended).expect("Debería parsear");
+    assert_eq!(diff_programs(&previous, &current)[0].class(), Some(ChangeClass::Minor));
+
+    // Changing a value is a fix, and removing an option breaks the agent's users
+    let with_timeout = parse(&orders("2.1.0", "orders.done", r#", timeout: "5s""#)).expect("Debería parsear");
+    let current = parse(&orders("2.1.0", "orders.done", r#", timeout: "10s""#)).expect("Debería parsear");
+    assert_eq!(diff_programs(&with_timeout, &current)[0].class(), Some(ChangeClass::Patch));
+    assert_eq!(diff_programs(&with_timeout, &previous)[0].class(), Some(ChangeClass::Major));
+
+    // The version and the tests are not changes
+    let current = parse(&orders("9.9.9", "orders.done", "")).expect("Debería parsear");
+    assert_eq!(diff_programs(&previous, &current)[0].class(), None);
+}
+
+#[test]
+fn test_version_bumps_must_match_the_changes() {
+    let previous = parse(&orders("2.1.0", "orders.done", "")).expect("Debería parsear");
+    let check = |version: &str, target: &str| {
+        let current = parse(&orders(version, target, "")).expect("Debería parsear");
+        SemanticAnalyzer::new().with_baseline(previous.clone()).analyze_program(&current).map_err(|e| e.to_string())
+    };
+
+    let error = check("2.2.0", "orders.completed").unwrap_err();
+    assert!(error.contains("KU0903"), "{}", error);
+    assert!(error.contains("debería ser al menos 3.0.0"), "{}", error);
+    assert!(check("3.0.0", "orders.completed").is_ok(), "Un cambio major con versión major es válido");
+    assert!(check("2.1.0", "orders.done").is_ok(), "Sin cambios la versión puede quedarse igual");
+    assert!(check("2.0.0", "orders.done").unwrap_err().contains("retrocede de 2.1.0 a 2.0.0"));
+
+    // Without a version on both sides nothing is gated
+    let unversioned = orders("2.1.0", "orders.completed", "").replace(r#"version: "2.1.0";"#, "");
+    let current = parse(&unversioned).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().with_baseline(previous.clone()).analyze_program(&current).is_ok());
+}
@@ -39,3 +39,746 @@ fn test_duplicate_workflow_names() {
     let result = analyzer.analyze_program(&program);
     assert!(result.is_err(), "Debería fallar por nombres duplicados");
 }
+
+#[test]
+fn test_canary_steps_must_increase() {
+    let input = r#"
+    workflow Scoring {
+        source: NATS("input");
+        deployment: {
+            rollout: canary { steps: [50%, 10%, 100%] }
+        };
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+
+    let result = analyzer.analyze_program(&program);
+    assert!(result.is_err(), "Debería fallar por pasos no crecientes");
+}
+
+#[test]
+fn test_canary_analysis_must_be_a_condition() {
+    let input = r#"
+    workflow Scoring {
+        source: NATS("input");
+        deployment: {
+            rollout: canary { steps: [10%, 100%], analysis: "error_rate" }
+        };
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+
+    let result = analyzer.analyze_program(&program);
+    assert!(result.is_err(), "Debería fallar por un análisis sin comparación");
+}
+
+#[test]
+fn test_blue_green_active_slot_must_be_a_slot() {
+    use kumeo_compiler::diagnostics::codes;
+
+    let workflow = |slot: &str| {
+        format!(
+            r#"workflow Scoring {{
+                source: NATS("input");
+                agents: [ Router(id: "route") ];
+                deployment: {{ rollout: blue_green, annotations: {{ "kumeo.io/active-slot": "{}" }} }};
+            }}"#,
+            slot
+        )
+    };
+
+    let program = parse(&workflow("green")).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok(), "green es un slot");
+
+    let program = parse(&workflow("gren")).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+    assert!(analyzer.analyze_program(&program).is_err(), "Debería fallar por un slot desconocido");
+    let diagnostic = analyzer
+        .diagnostics()
+        .iter()
+        .find(|diagnostic| diagnostic.code == codes::INVALID_DEPLOYMENT)
+        .expect("Falta el error del despliegue");
+    assert!(diagnostic.message.to_string().contains("gren"), "{}", diagnostic);
+}
+
+#[test]
+fn test_storage_size_must_be_a_quantity() {
+    let input = r#"
+    workflow Scoring {
+        source: NATS("input");
+        agents: [
+            DataProcessor(id: "cache")
+        ];
+        deployment: {
+            storage: { cache: { size: "ten gigs", path: "/data" } }
+        };
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+
+    let result = analyzer.analyze_program(&program);
+    assert!(result.is_err(), "Debería fallar por un tamaño inválido");
+}
+
+#[test]
+fn test_valid_storage_is_accepted() {
+    let input = r#"
+    workflow Scoring {
+        source: NATS("input");
+        agents: [
+            DataProcessor(id: "cache")
+        ];
+        deployment: {
+            storage: { cache: { size: "1.5Gi", path: "/data" } }
+        };
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+
+    assert!(analyzer.analyze_program(&program).is_ok());
+}
+
+#[test]
+fn test_batch_workflow_rejects_rollout() {
+    let input = r#"
+    workflow Nightly {
+        mode: batch;
+        source: NATS("events");
+        deployment: { rollout: blue_green };
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+
+    let result = analyzer.analyze_program(&program);
+    assert!(result.is_err(), "Debería fallar por un rollout en modo batch");
+}
+
+#[test]
+fn test_kafka_topic_names_are_validated() {
+    let input = r#"
+    workflow Clicks {
+        source: Kafka("clicks and views");
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+
+    let result = analyzer.analyze_program(&program);
+    assert!(result.is_err(), "Debería fallar por un tema de Kafka inválido");
+}
+
+#[test]
+fn test_kafka_sources_and_targets_are_accepted() {
+    let input = r#"
+    workflow Orders {
+        source: Kafka("orders", { group: "scorers" });
+        target: Kafka("scored-orders");
+        agents: [ Router(id: "route") ];
+    }
+    "#;
+
+    let program = parse(input).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+
+    let result = analyzer.analyze_program(&program);
+    assert!(result.is_ok(), "Los agentes generados leen y escriben Kafka: {:?}", result.err());
+}
+
+#[test]
+fn test_mqtt_wildcards_are_validated() {
+    let valid = r#"
+    workflow Sensors {
+        source: MQTT("sensors/+/temp/#");
+        target: MQTT("alerts/temp");
+    }
+    "#;
+    let program = parse(valid).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok());
+
+    for invalid in [
+        r#"workflow Sensors { source: MQTT("sensors/#/temp"); }"#,
+        r#"workflow Sensors { source: MQTT("sensors/kitchen+/temp"); }"#,
+        r#"workflow Sensors { target: MQTT("alerts/+"); }"#,
+    ] {
+        let program = parse(invalid).expect("Debería parsear");
+        assert!(
+            SemanticAnalyzer::new().analyze_program(&program).is_err(),
+            "Debería rechazar {}",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn test_http_sources_are_validated() {
+    let valid = r#"workflow Ingest { source: HTTP("/ingest", { method: "put" }); }"#;
+    let program = parse(valid).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok());
+
+    for invalid in [
+        r#"workflow Ingest { source: HTTP("ingest"); }"#,
+        r#"workflow Ingest { source: HTTP("/ingest", { method: "GET" }); }"#,
+        r#"workflow Ingest { mode: batch; source: HTTP("/ingest"); }"#,
+    ] {
+        let program = parse(invalid).expect("Debería parsear");
+        assert!(
+            SemanticAnalyzer::new().analyze_program(&program).is_err(),
+            "Debería rechazar {}",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn test_file_sources_and_targets_are_validated() {
+    let valid = r#"
+    workflow Drop {
+        source: File("/data/in/*.json", { watch: true });
+        target: File("/data/out/");
+    }
+    "#;
+    let program = parse(valid).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok());
+
+    for invalid in [
+        r#"workflow Drop { source: File("data/in/*.json"); }"#,
+        r#"workflow Drop { source: File("/data/*/in/*.json"); }"#,
+        r#"workflow Drop { source: File("/data/in/*.json", { watch: "yes" }); }"#,
+        r#"workflow Drop { target: File("/data/out/*"); }"#,
+    ] {
+        let program = parse(invalid).expect("Debería parsear");
+        assert!(
+            SemanticAnalyzer::new().analyze_program(&program).is_err(),
+            "Debería rechazar {}",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn test_require_signed_needs_model_signatures() {
+    let signed = r#"
+    workflow Scoring {
+        source: NATS("input");
+        agents: [
+            MLModel(id: "scorer", model_path: "https://models.example.com/scorer.onnx#minisign")
+        ];
+        deployment: { require_signed: true, trusted_keys: "release-keys" };
+    }
+    "#;
+    let program = parse(signed).expect("Debería parsear");
+    assert!(SemanticAnalyzer::new().analyze_program(&program).is_ok());
+
+    let unsigned = signed.replace("#minisign", "");
+    let program = parse(&unsigned).expect("Debería parsear");
+    assert!(
+        SemanticAnalyzer::new().analyze_program(&program).is_err(),
+        "Debería rechazar un modelo sin firma"
+    );
+}
+
+#[test]
+fn test_constants_are_resolved_before_validation() {
+    let analyze = |input: &str| {
+        let program = parse(input).expect("Debería parsear");
+        SemanticAnalyzer::new().analyze_program(&program)
+    };
+
+    // The preload check sees the substituted remote model path
+    let valid = r#"
+    const SCORER = "s3://models/scorer.onnx";
+    workflow Scoring {
+        source: NATS("events");
+        agents: [MLModel(id: "scorer", model_path: $SCORER, preload: true)];
+    }
+    "#;
+    assert!(analyze(valid).is_ok());
+
+    let undefined = r#"
+    workflow Scoring {
+        source: NATS("events");
+        agents: [MLModel(id: "scorer", model_path: $SCORER)];
+    }
+    "#;
+    let error = analyze(undefined).expect_err("Debería rechazar la constante no definida");
+    assert!(error.to_string().contains("$SCORER"));
+
+    let uninterpolated = r#"
+    workflow Scoring {
+        source: NATS("events.{REGION}");
+        agents: [MLModel(id: "scorer", model_path: "models/scorer.onnx")];
+    }
+    "#;
+    let error = analyze(uninterpolated).expect_err("Debería rechazar la interpolación sin resolver");
+    assert!(error.to_string().contains("{REGION}"));
+
+    let duplicate = r#"
+    const SCORER = "s3://models/a.onnx";
+    const SCORER = "s3://models/b.onnx";
+    workflow Scoring {
+        source: NATS("events");
+        agents: [MLModel(id: "scorer", model_path: $SCORER)];
+    }
+    "#;
+    assert!(analyze(duplicate).is_err());
+}
+
+#[test]
+fn test_consumed_topics_must_be_produced() {
+    let analyze = |agents: &str| {
+        let input = format!(
+            r#"
+            workflow Scoring {{
+                source: NATS("events");
+                target: NATS("alerts");
+                agents: [{}];
+            }}
+            "#,
+            agents
+        );
+        let program = parse(&input).expect("Debería parsear");
+        SemanticAnalyzer::new().analyze_program(&program)
+    };
+
+    // Explicit topics, references to another agent's output and the implicit chain
+    assert!(analyze(
+        r#"DataProcessor(id: "clean", input: "source", output: "clean.events"),
+           MLModel(id: "score", model_path: "models/score.onnx", input: "clean.events"),
+           Router(id: "route", input: "score.output"),
+           LLM(id: "explain", model: "gpt-4")"#
+    )
+    .is_ok());
+
+    let error = analyze(
+        r#"DataProcessor(id: "clean", output: "clean.events"),
+           MLModel(id: "score", model_path: "models/score.onnx", input: "cleaned.events")"#,
+    )
+    .expect_err("Debería rechazar un topic que nadie produce");
+    assert!(error.to_string().contains("cleaned.events"));
+
+    assert!(analyze(r#"DataProcessor(id: "clean", input: { topic: "events" })"#).is_err());
+}
+
+#[test]
+fn test_dataflow_cycles_are_reported_with_their_path() {
+    let analyze = |agents: &str| {
+        let input = format!(
+            r#"
+            workflow Review {{
+                source: NATS("drafts");
+                target: NATS("published");
+                agents: [{}];
+            }}
+            "#,
+            agents
+        );
+        let program = parse(&input).expect("Debería parsear");
+        let mut analyzer = SemanticAnalyzer::new();
+        let result = analyzer.analyze_program(&program).map_err(|e| e.to_string());
+        (result, analyzer.warnings().to_vec())
+    };
+
+    let (result, _) = analyze(
+        r#"LLM(id: "write", model: "gpt-4", input: "source", output: "review.draft"),
+           LLM(id: "critique", model: "gpt-4", input: "review.draft", output: "review.notes"),
+           LLM(id: "revise", model: "gpt-4", input: "review.notes", output: "review.draft")"#,
+    );
+    let error = result.expect_err("Debería rechazar el ciclo");
+    assert!(error.contains("critique -> revise -> critique"), "{}", error);
+
+    // A loop that stops once the condition fails is only a warning
+    let (result, warnings) = analyze(
+        r#"LLM(id: "write", model: "gpt-4", input: "source", output: "review.draft"),
+           LLM(id: "revise", model: "gpt-4", input: "review.draft", output: "review.draft", when: data.score < 0.8)"#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(warnings.iter().any(|warning| warning.contains("revise -> revise")), "{:?}", warnings);
+
+    // A guarded branch doesn't stop the unguarded loop that shares its steps
+    let (result, _) = analyze(
+        r#"LLM(id: "a", model: "gpt-4", input: "loop", output: "x"),
+           LLM(id: "b", model: "gpt-4", input: "x", output: "y", when: data.score < 0.8),
+           LLM(id: "c", model: "gpt-4", input: "x", output: "y"),
+           LLM(id: "d", model: "gpt-4", input: "y", output: "loop"),
+           LLM(id: "e", model: "gpt-4", input: "source", output: "loop")"#,
+    );
+    let error = result.expect_err("Debería rechazar el ciclo sin `when`");
+    assert!(error.contains("a -> c -> d -> a"), "{}", error);
+}
+
+#[test]
+fn test_unused_and_unreachable_agents_are_warned() {
+    let warnings = |workflows: &str| {
+        let program = parse(workflows).expect("Debería parsear");
+        let mut analyzer = SemanticAnalyzer::new();
+        let result = analyzer.analyze_program(&program);
+        assert!(result.is_ok(), "{:?}", result);
+        analyzer.warnings().to_vec()
+    };
+
+    let found = warnings(
+        r#"
+        workflow Review {
+            source: NATS("drafts");
+            target: NATS("published");
+            agents: [
+                LLM(id: "write", model: "gpt-4", input: "source", output: "review.draft"),
+                LLM(id: "audit", model: "gpt-4", input: "review.draft", output: "review.audit"),
+                LLM(id: "publish", model: "gpt-4", input: "review.draft", output: "published"),
+                LLM(id: "retry", model: "gpt-4", input: "review.retry", output: "review.again", when: data.score < 0.5),
+                LLM(id: "again", model: "gpt-4", input: "review.again", output: "review.retry", when: data.score < 0.5)
+            ];
+        }
+        "#,
+    );
+    assert!(found.iter().any(|warning| warning.contains("'review.audit'") && warning.contains("audit")), "{:?}", found);
+    assert!(found.iter().any(|warning| warning.contains("El paso retry") && warning.contains("nunca recibe")), "{:?}", found);
+    assert!(found.iter().any(|warning| warning.contains("El paso again")), "{:?}", found);
+    assert!(!found.iter().any(|warning| warning.contains("write") || warning.contains("publish")), "{:?}", found);
+
+    // A topic another workflow of the program consumes is in use
+    let found = warnings(
+        r#"
+        workflow Review {
+            source: NATS("drafts");
+            target: NATS("published");
+            agents: [
+                LLM(id: "write", model: "gpt-4", input: "source", output: "review.draft"),
+                LLM(id: "publish", model: "gpt-4", input: "review.draft", output: "published"),
+                LLM(id: "audit", model: "gpt-4", input: "review.draft", output: "review.audit")
+            ];
+        }
+        workflow Audit {
+            source: NATS("review.audit");
+            target: NATS("audit.log");
+            agents: [Router(id: "log")];
+        }
+        "#,
+    );
+    assert!(found.is_empty(), "{:?}", found);
+}
+
+#[test]
+fn test_message_contracts_between_agents() {
+    let errors = |source: &str| {
+        let program = parse(source).expect("Debería parsear");
+        match SemanticAnalyzer::new().analyze_program(&program) {
+            Ok(()) => String::new(),
+            Err(error) => error.to_string(),
+        }
+    };
+
+    let program = |produced: &str, expected: &str| {
+        format!(
+            r#"
+            schemas: {{ "orders.scored": {{ id: "string", score: "number" }} }};
+
+            workflow Orders {{
+                source: NATS("orders");
+                target: NATS("alerts");
+                agents: [
+                    DataProcessor(id: "score", output: "orders.scored", output_schema: {produced}),
+                    Router(id: "route", input: "orders.scored", input_schema: {expected})
+                ];
+            }}
+            "#
+        )
+    };
+
+    let found = errors(&program(
+        r#"{ id: "string", score: "integer", extra: "boolean" }"#,
+        r#"{ id: "string", reason: "string?" }"#,
+    ));
+    assert!(found.is_empty(), "{}", found);
+
+    let found = errors(&program(r#"{ id: "string", score: "string" }"#, r#"{ id: "string" }"#));
+    assert!(found.contains("El agente score publica en 'orders.scored'"), "{}", found);
+    assert!(found.contains("field 'score' is a string, not a number"), "{}", found);
+
+    let found = errors(&program(r#"{ id: "string", score: "number" }"#, r#"{ id: "string", reason: "string" }"#));
+    assert!(found.contains("El agente route espera en 'orders.scored'"), "{}", found);
+    assert!(found.contains("field 'reason' is missing"), "{}", found);
+}
+
+#[test]
+fn test_topic_encodings_must_match() {
+    let errors = |source: &str| {
+        let program = parse(source).expect("Debería parsear");
+        match SemanticAnalyzer::new().analyze_program(&program) {
+            Ok(()) => String::new(),
+            Err(error) => error.to_string(),
+        }
+    };
+
+    let program = |scoring: &str, encoding: &str, alerts: &str| {
+        format!(
+            r#"
+            schemas: {{ "scores": {{ id: "string", score: "number" }} }};
+
+            workflow Scoring {{
+                source: NATS("orders", {{ encoding: "msgpack" }});
+                target: NATS("scores", {{ encoding: "protobuf" }});
+                agents: [
+                    DataProcessor(id: "clean", output: "orders.clean", encoding: "{encoding}"),
+                    MLModel(id: "score", model_path: "models/score.onnx", input: "orders.clean", output: "scores"{scoring})
+                ];
+            }}
+            workflow Alerts {{
+                source: NATS("scores", {{ encoding: "{alerts}" }});
+                target: NATS("alerts");
+                agents: [Router(id: "route")];
+            }}
+            "#
+        )
+    };
+
+    // Los agentes publican en el destino en su codificación y los lectores la comparten
+    let schema = r#", output_schema: { id: "string", score: "number" }"#;
+    let found = errors(&program(schema, "avro", "protobuf"));
+    assert!(found.contains("El agente clean publica en 'orders.clean' en avro, que necesita su output_schema"), "{}", found);
+    let found = errors(&program(schema, "msgpack", "protobuf"));
+    assert!(found.is_empty(), "{}", found);
+
+    // Protobuf necesita el esquema del topic
+    let found = errors(&program("", "msgpack", "protobuf"));
+    assert!(found.contains("El agente score publica en 'scores' en protobuf, que necesita su output_schema"), "{}", found);
+
+    let found = errors(&program(&format!("{}, encoding: \"json\"", schema), "msgpack", "json"));
+    assert!(found.contains("El agente score publica en json en el destino del workflow Scoring, que usa protobuf"), "{}", found);
+
+    // Quien lee un topic de otro workflow lo lee en su codificación
+    let found = errors(&program(schema, "msgpack", "msgpack"));
+    assert!(found.contains("El workflow Alerts lee 'scores' en msgpack, pero el workflow Scoring lo publica en protobuf"), "{}", found);
+
+    let found = errors(&program(schema, "yaml", "protobuf"));
+    assert!(found.contains("Codificación inválida en el agente clean: unknown encoding \"yaml\""), "{}", found);
+
+    let found = errors(
+        r#"
+        workflow Files {
+            source: File("in/*.json", { encoding: "avro" });
+            target: NATS("out");
+            agents: [Router(id: "route")];
+        }
+        "#,
+    );
+    assert!(found.contains("La fuente del workflow Files usa la codificación avro, que solo admiten las fuentes NATS"), "{}", found);
+}
+
+#[test]
+fn test_workflow_tests_are_validated() {
+    let errors = |tests: &str| {
+        let source = format!(
+            r#"workflow Payments {{
+                source: NATS("payments");
+                agents: [ Router(id: "router1", when: data.amount > 500) ];
+                {}
+            }}"#,
+            tests
+        );
+        let program = parse(&source).expect("Debería parsear");
+        match SemanticAnalyzer::new().analyze_program(&program) {
+            Ok(()) => String::new(),
+            Err(error) => error.to_string(),
+        }
+    };
+
+    let found = errors(r#"test "fraud" { given: { amount: 900 }, when agent: "router1", expect: { topic: "alerts", outcome: "processed" } }"#);
+    assert!(found.is_empty(), "{}", found);
+
+    let found = errors(r#"test "fraud" { given: { amount: 900 }, when agent: "scorer", expect: { topic: "alerts" } }"#);
+    assert!(found.contains("Test \"fraud\" inválido en el workflow Payments: el agente scorer no existe"), "{}", found);
+    let found = errors(r#"test "fraud" { given: 900, when agent: "router1", expect: { outcome: "dropped", latency: 3 } }"#);
+    assert!(found.contains("el mensaje debe ser un objeto"), "{}", found);
+    assert!(found.contains("'outcome' debe ser \"processed\", \"skipped\", \"rejected\" o \"violated\""), "{}", found);
+    assert!(found.contains("expectativa desconocida 'latency'"), "{}", found);
+    let found = errors(
+        r#"test "fraud" { given: {}, when agent: "router1", expect: { topic: null } }
+           test "fraud" { given: {}, when agent: "router1", expect: { topic: null } }"#,
+    );
+    assert!(found.contains("Test duplicado: \"fraud\""), "{}", found);
+}
+
+#[test]
+fn test_diagnostics_have_codes_and_suggestions() {
+    use kumeo_compiler::diagnostics::codes;
+
+    let input = "workflow Review {\n    source: NATS(\"drafts\");\n    target: NATS(\"published\");\n    agents: [\n        LLM(id: \"write\", model: \"gpt-4\", input: \"source\", output: \"review.draft\"),\n        LLM(id: \"publish\", model: \"gpt-4\", input: \"reveiw.draft\", output: \"published\")\n    ];\n}\n";
+    let program = parse(input).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+    let error = analyzer.analyze_program(&program).unwrap_err().to_string();
+
+    let diagnostic = analyzer
+        .diagnostics()
+        .iter()
+        .find(|diagnostic| diagnostic.code == codes::UNPRODUCED_TOPIC)
+        .expect("Debería informar del topic sin productor");
+    assert_eq!(diagnostic.suggestion.as_deref(), Some("review.draft"));
+    assert_eq!((diagnostic.span.line, diagnostic.span.column), (6, 9), "Debería señalar al agente");
+
+    // El error renderizado lleva el código, la línea subrayada y la sugerencia
+    assert!(error.contains("error[KU0602]: El agente publish consume el topic 'reveiw.draft'"), "{}", error);
+    assert!(error.contains("6 | LLM(id: \"publish\""), "{}", error);
+    assert!(error.contains("  | ^^^^^"), "{}", error);
+    assert!(error.contains("= ayuda: ¿quisiste decir `review.draft`?"), "{}", error);
+
+    // Los avisos también son diagnósticos, con su propio código
+    assert!(analyzer
+        .diagnostics()
+        .iter()
+        .any(|diagnostic| !diagnostic.is_error() && diagnostic.code == codes::UNUSED_TOPIC));
+}
+
+#[test]
+fn test_diagnostics_export_to_sarif() {
+    use kumeo_compiler::diagnostics::{codes, sarif, SARIF_VERSION};
+    use std::path::Path;
+
+    let input = "workflow Review {\n    source: NATS(\"drafts\");\n    agents: [\n        LLM(id: \"write\", model: \"gpt-4\", output: \"review.draft\"),\n        LLM(id: \"publish\", model: \"gpt-4\", input: \"reveiw.draft\")\n    ];\n}\n";
+    let mut program = parse(input).expect("Debería parsear");
+    program.set_file("/repo/flows/review.kumeo");
+    let mut analyzer = SemanticAnalyzer::new();
+    assert!(analyzer.analyze_program(&program).is_err());
+
+    let log = sarif(analyzer.diagnostics(), Path::new("/repo"));
+    assert_eq!(log["version"], SARIF_VERSION);
+    let run = &log["runs"][0];
+    assert_eq!(run["originalUriBaseIds"]["%SRCROOT%"]["uri"], "file:///repo/");
+
+    // Cada código informado es una regla con la descripción de su documentación
+    let rules = run["tool"]["driver"]["rules"].as_array().expect("Debería tener reglas");
+    let rule = rules.iter().position(|rule| rule["id"] == codes::UNPRODUCED_TOPIC).expect("Debería describir KU0602");
+    assert_eq!(rules[rule]["shortDescription"]["text"], "A topic consumed in a workflow that nothing produces");
+
+    let result = run["results"]
+        .as_array()
+        .expect("Debería tener resultados")
+        .iter()
+        .find(|result| result["ruleId"] == codes::UNPRODUCED_TOPIC)
+        .expect("Debería informar del topic sin productor");
+    assert_eq!(result["ruleIndex"], rule);
+    assert_eq!(result["level"], "error");
+    assert_eq!(result["properties"]["suggestion"], "review.draft");
+    assert!(result["message"]["text"].as_str().unwrap().ends_with("¿quisiste decir `review.draft`?"));
+    let location = &result["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "flows/review.kumeo");
+    assert_eq!(location["artifactLocation"]["uriBaseId"], "%SRCROOT%");
+    assert_eq!((location["region"]["startLine"].as_u64(), location["region"]["startColumn"].as_u64()), (Some(5), Some(9)));
+
+    // Los avisos son resultados de nivel warning
+    assert!(run["results"].as_array().unwrap().iter().any(|result| result["level"] == "warning"));
+}
+
+#[test]
+fn test_diagnostics_export_to_versioned_json() {
+    use kumeo_compiler::diagnostics::{codes, JsonDiagnostic, Position, JSON_SCHEMA_VERSION};
+
+    let input = "workflow Review {\n    source: NATS(\"drafts\");\n    agents: [\n        LLM(id: \"write\", model: \"gpt-4\", output: \"review.draft\"),\n        LLM(id: \"publish\", model: \"gpt-4\", input: \"reveiw.draft\")\n    ];\n}\n";
+    let mut program = parse(input).expect("Debería parsear");
+    program.set_file("/repo/flows/review.kumeo");
+    let mut analyzer = SemanticAnalyzer::new();
+    assert!(analyzer.analyze_program(&program).is_err());
+
+    let diagnostic = analyzer
+        .diagnostics()
+        .iter()
+        .find(|diagnostic| diagnostic.code == codes::UNPRODUCED_TOPIC)
+        .expect("Debería informar del topic sin productor")
+        .to_json();
+    assert_eq!(diagnostic.file.as_deref(), Some("/repo/flows/review.kumeo"));
+    let range = diagnostic.range.expect("Debería tener rango");
+    assert_eq!((range.start, range.end.line), (Position { line: 5, column: 9 }, 5));
+
+    // La sugerencia es una corrección que reemplaza el topic mal escrito
+    assert_eq!(diagnostic.fixes.len(), 1, "{:?}", diagnostic.fixes);
+    let edit = &diagnostic.fixes[0].edits[0];
+    assert_eq!(edit.new_text, "\"review.draft\"");
+    assert_eq!((edit.range.start, edit.range.end), (Position { line: 5, column: 51 }, Position { line: 5, column: 65 }));
+
+    // El JSON se lee de vuelta con el mismo esquema, cuya versión publica la especificación
+    let json = serde_json::to_value(&diagnostic).unwrap();
+    assert_eq!(json["fixes"][0]["edits"][0]["range"]["start"]["column"], 51);
+    assert_eq!(serde_json::from_value::<JsonDiagnostic>(json).unwrap(), diagnostic);
+    let schema: serde_json::Value =
+        serde_json::from_str(include_str!("../../spec/diagnostics.schema.json")).expect("El esquema debería ser JSON");
+    assert_eq!(schema["properties"]["version"]["const"], JSON_SCHEMA_VERSION);
+}
+
+#[test]
+fn test_fixes_are_applied_as_edits() {
+    use kumeo_compiler::{diagnostics::{apply_edits, codes}, i18n::Locale};
+
+    let input = "workflow Review {\n    source: NATS(\"drafts\");\n    agents: [\n        LLM(model: \"gpt-4\", input: \"drafts\", output: \"review.draft\"),\n        LLM(id: \"llm\", model: \"gpt-4\", input: \"reveiw.draft\"),\n        Router()\n    ];\n}\n";
+    let program = parse(input).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+    assert!(analyzer.analyze_program(&program).is_err());
+
+    let fixes: Vec<_> = analyzer.diagnostics().iter().flat_map(|diagnostic| diagnostic.fixes(Locale::En)).collect();
+    let titles: Vec<&str> = fixes.iter().map(|fix| fix.title.as_str()).collect();
+    assert!(titles.contains(&"insert `id: \"llm_2\"`"), "Los IDs propuestos no repiten los existentes: {:?}", titles);
+    assert!(titles.contains(&"insert `id: \"router\"`"), "{:?}", titles);
+    assert!(titles.contains(&"replace `\"reveiw.draft\"` with `\"review.draft\"`"), "{:?}", titles);
+
+    let edits: Vec<_> = fixes.iter().flat_map(|fix| &fix.edits).collect();
+    let (fixed, applied) = apply_edits(input, &edits);
+    assert_eq!(applied, 3);
+    assert!(fixed.contains("LLM(id: \"llm_2\", model: \"gpt-4\", input: \"drafts\""), "{}", fixed);
+    assert!(fixed.contains("input: \"review.draft\")"), "{}", fixed);
+    assert!(fixed.contains("Router(id: \"router\")"), "{}", fixed);
+
+    // El programa corregido ya no tiene esos problemas
+    let mut analyzer = SemanticAnalyzer::new();
+    let _ = analyzer.analyze_program(&parse(&fixed).expect("Debería parsear el programa corregido"));
+    assert!(analyzer.diagnostics().iter().all(|diagnostic| diagnostic.fix.is_none()), "{:?}", analyzer.diagnostics());
+    assert!(!analyzer.diagnostics().iter().any(|diagnostic| diagnostic.code == codes::MISSING_AGENT_ID));
+
+    // Las ediciones que se solapan con una ya aplicada se descartan
+    let (_, applied) = apply_edits(input, &[edits[0], edits[0]]);
+    assert_eq!(applied, 1);
+}
+
+#[test]
+fn test_option_errors_point_at_the_option() {
+    use kumeo_compiler::diagnostics::codes;
+
+    let input = "workflow Review {\n    source: NATS(\"drafts\");\n    agents: [\n        LLM(id: \"write\", model: \"gpt-4\",\n            retry: \"often\")\n    ];\n}\n";
+    let program = parse(input).expect("Debería parsear");
+    let span = program.workflows[0].agents[0].option_span("retry").expect("Debería guardar la posición de la opción");
+    assert_eq!((span.line, span.column), (5, 13));
+
+    let mut analyzer = SemanticAnalyzer::new();
+    let error = analyzer.analyze_program(&program).unwrap_err().to_string();
+    let diagnostic = analyzer
+        .diagnostics()
+        .iter()
+        .find(|diagnostic| diagnostic.code == codes::INVALID_POLICY)
+        .expect("Debería rechazar la política retry");
+    assert_eq!((diagnostic.span.line, diagnostic.span.column), (5, 13), "Debería señalar la opción, no el agente");
+    assert!(error.contains("5 | retry: \"often\")"), "{}", error);
+    assert!(error.lines().any(|line| line == "  | ^^^^^^^^^^^^^^"), "Debería subrayar solo la opción: {}", error);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("review.kumeo");
+    std::fs::write(&path, input).unwrap();
+    let program = kumeo_compiler::parser::parse_file(&path).expect("Debería cargar el archivo");
+    let span = program.workflows[0].agents[0].option_span("retry").unwrap();
+    assert_eq!(span.file, Some(path.display().to_string()), "La opción debería saber de qué archivo viene");
+
+    // Las causas de los agentes de un tipo señalan la opción que nombran
+    let input = "workflow Risk {\n    source: NATS(\"claims\");\n    agents: [\n        BayesianNetwork(id: \"risk\", query: [\"fraud\"],\n            network_path: \"models/risk.csv\")\n    ];\n}\n";
+    let program = parse(input).expect("Debería parsear");
+    let mut analyzer = SemanticAnalyzer::new();
+    let error = analyzer.analyze_program(&program).unwrap_err().to_string();
+    assert!(error.contains("5 | network_path: \"models/risk.csv\")"), "{}", error);
+}
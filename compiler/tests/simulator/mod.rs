@@ -0,0 +1,183 @@
+//! Tests for the simulation of workflow tests
+
+use kumeo_compiler::{
+    ast::Workflow,
+    codegen::{contracts, topics},
+    parse,
+    simulator::{self, Outcome},
+};
+use serde_json::json;
+
+/// A workflow as generated, with its topics wired and its input schemas attached
+fn generated(source: &str) -> Workflow {
+    let program = parse(source).expect("Debería parsear");
+    let wired = topics::wire(&program.workflows[0]).expect("Debería cablear los topics");
+    contracts::attach(&wired, &program.topic_schemas())
+}
+
+const PAYMENTS: &str = r#"
+workflow Payments {
+    source: NATS("payments");
+    target: NATS("payments.reviewed");
+    agents: [
+        DataProcessor(id: "parse", output_schema: { id: "string", amount: "number", country: "string?" }),
+        Router(
+            id: "fraud",
+            output: "alerts.fraud",
+            when: data.amount > 500 && (data.country ?? "ES") != "ES",
+            fallback: { action: "dead_letter", subject: "payments.invalid" }
+        ),
+        LLM(id: "review", input: "parse.output", model: "gpt-4")
+    ];
+    test "routes fraud correctly" {
+        given: { id: "p1", amount: 900, country: "FR" },
+        when agent: "fraud",
+        expect: { topic: "alerts.fraud", outcome: "processed" }
+    }
+    test "keeps domestic payments" {
+        given: { id: "p2", amount: 900 },
+        when agent: "fraud",
+        expect: { topic: null, outcome: "skipped" }
+    }
+    test "rejects payments without amount" {
+        given: { id: "p3" },
+        when agent: "fraud",
+        expect: { topic: "payments.invalid", outcome: "rejected" }
+    }
+    test "reviews on the target" {
+        given: { id: "p4", amount: 10 },
+        when agent: "review",
+        expect: { topic: "alerts.fraud" }
+    }
+}
+"#;
+
+#[test]
+fn test_deliveries_follow_schemas_and_conditions() {
+    let workflow = generated(PAYMENTS);
+
+    let delivery = simulator::deliver(&workflow, "fraud", &json!({"id": "p1", "amount": 900, "country": "FR"})).unwrap();
+    assert_eq!(delivery.outcome, Outcome::Processed);
+    assert_eq!(delivery.topic.as_deref(), Some("alerts.fraud"));
+
+    let delivery = simulator::deliver(&workflow, "fraud", &json!({"id": "p2", "amount": 900})).unwrap();
+    assert_eq!(delivery.outcome, Outcome::Skipped);
+    assert_eq!(delivery.topic, None);
+
+    let delivery = simulator::deliver(&workflow, "fraud", &json!({"id": 3, "amount": 900})).unwrap();
+    assert_eq!(delivery.outcome, Outcome::Rejected);
+    assert_eq!(delivery.topic.as_deref(), Some("payments.invalid"));
+    assert!(delivery.reason.unwrap().to_string().contains("field 'id' should be of type string"));
+
+    assert!(simulator::deliver(&workflow, "missing", &json!({})).is_err());
+}
+
+#[test]
+fn test_workflow_tests_report_failed_expectations() {
+    let reports = simulator::run(&generated(PAYMENTS)).unwrap();
+    assert_eq!(reports.len(), 4);
+    for report in &reports[..3] {
+        assert!(report.passed(), "{}: {:?}", report.name, report.failures);
+    }
+
+    let failed = &reports[3];
+    assert_eq!(failed.name, "reviews on the target");
+    assert_eq!(
+        failed.failures.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        vec!["expected the message on 'alerts.fraud', it was published on 'payments.reviewed'".to_string()]
+    );
+}
+
+#[test]
+fn test_invariant_violations_are_audited() {
+    let workflow = generated(
+        r#"
+        workflow Payments {
+            source: NATS("payments");
+            agents: [ DataProcessor(id: "clean", assert: data.amount >= 0, assert: data.currency == "EUR") ];
+            test "audits negative amounts" {
+                given: { amount: -5, currency: "EUR" },
+                when agent: "clean",
+                expect: { topic: "kumeo.audit.payments", outcome: "violated" }
+            }
+        }
+        "#,
+    );
+
+    let delivery = simulator::deliver(&workflow, "clean", &json!({"amount": 5, "currency": "USD"})).unwrap();
+    assert_eq!(delivery.outcome, Outcome::Violated);
+    assert_eq!(delivery.reason.map(|reason| reason.to_string()).as_deref(), Some(r#"invariant `data.currency == "EUR"` doesn't hold"#));
+    let delivery = simulator::deliver(&workflow, "clean", &json!({"amount": 5, "currency": "EUR"})).unwrap();
+    assert_eq!(delivery.outcome, Outcome::Processed);
+
+    let reports = simulator::run(&workflow).unwrap();
+    assert!(reports[0].passed(), "{:?}", reports[0].failures);
+}
+
+#[test]
+fn test_quality_monitors_sample_without_rejecting() {
+    let workflow = generated(
+        r#"
+        workflow Orders {
+            source: NATS("orders");
+            agents: [
+                DataProcessor(id: "clean", output: "orders.clean", output_schema: { total: "number" }),
+                QualityMonitor(id: "quality", input: "orders.clean", output: "orders.quality")
+            ];
+        }
+        "#,
+    );
+
+    // Violations are measured as conformity, and reports are published per window
+    let delivery = simulator::deliver(&workflow, "quality", &json!({"total": "ten"})).unwrap();
+    assert_eq!(delivery.outcome, Outcome::Processed);
+    assert_eq!(delivery.topic, None);
+}
+
+#[test]
+fn test_aggregators_publish_per_window() {
+    let workflow = generated(
+        r#"
+        workflow Orders {
+            source: NATS("orders");
+            agents: [
+                Aggregator(id: "totals", output: "orders.totals", window: { count: 100 }, reduce: { total: sum(data.amount) }),
+                Aggregator(id: "each", input: "orders", output: "orders.each", window: { count: 1 }, reduce: { n: count() })
+            ];
+        }
+        "#,
+    );
+
+    // The message is folded into its window, which is published once it closes
+    let delivery = simulator::deliver(&workflow, "totals", &json!({"amount": 5})).unwrap();
+    assert_eq!(delivery.outcome, Outcome::Processed);
+    assert_eq!(delivery.topic, None);
+    // A window of one message closes on every message
+    let delivery = simulator::deliver(&workflow, "each", &json!({"amount": 5})).unwrap();
+    assert_eq!(delivery.topic.as_deref(), Some("orders.each"));
+}
+
+#[test]
+fn test_rule_engines_publish_unless_a_rule_drops() {
+    let workflow = generated(
+        r#"
+        workflow Orders {
+            source: NATS("orders");
+            agents: [
+                RuleEngine(id: "risk", output: "orders.flagged", rules: [
+                    high: data.score > 0.9 => set(risk: "high"),
+                    test: data.customer_id == "test" => drop()
+                ])
+            ];
+        }
+        "#,
+    );
+
+    let delivery = simulator::deliver(&workflow, "risk", &json!({"customer_id": "c1", "score": 0.95})).unwrap();
+    assert_eq!(delivery.outcome, Outcome::Processed);
+    assert_eq!(delivery.topic.as_deref(), Some("orders.flagged"));
+    // A dropped message is processed but not published
+    let delivery = simulator::deliver(&workflow, "risk", &json!({"customer_id": "test", "score": 0.95})).unwrap();
+    assert_eq!(delivery.outcome, Outcome::Processed);
+    assert_eq!(delivery.topic, None);
+}
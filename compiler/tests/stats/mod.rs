@@ -0,0 +1,71 @@
+//! Tests for the program statistics
+
+use kumeo_compiler::{
+    parse,
+    stats::{parse_cpu_millis, parse_memory_mib, Footprint, ProgramStats, PYTHON_REQUESTS, RUST_REQUESTS},
+};
+
+const SHOP: &str = r#"
+schemas: {
+    "orders.scored": { score: number }
+};
+
+workflow Orders {
+    source: NATS("orders");
+    target: NATS("orders.done");
+    agents: [
+        MLModel(id: "score", model: "s3://models/fraud.onnx", output: "orders.scored"),
+        LLM(id: "explain", model: "gpt-4"),
+        Router(id: "route")
+    ];
+    deployment: {
+        replicas: 2,
+        resources: { cpu: "250m", memory: "1Gi" }
+    };
+}
+
+workflow Audit {
+    source: NATS("orders.done");
+    agents: [DataProcessor(id: "log")];
+}
+"#;
+
+#[test]
+fn test_programs_are_summarized() {
+    let program = parse(SHOP).expect("Debería parsear");
+    let stats = ProgramStats::of(&program).expect("Debería calcular las estadísticas");
+
+    assert_eq!((stats.workflows, stats.subworkflows, stats.agents, stats.schemas), (2, 0, 4, 1));
+    assert_eq!(stats.agents_by_type["LLM"], 1);
+    assert_eq!(stats.agents_by_type.len(), 4);
+    assert_eq!((stats.agents_by_language["rust"], stats.agents_by_language["python"]), (3, 1));
+    assert!(stats.topics.contains(&"orders.scored".to_string()), "{:?}", stats.topics);
+    assert!(stats.topics.contains(&"orders.done".to_string()));
+    assert_eq!(stats.resources, ["s3://models/fraud.onnx"]);
+
+    // The requests of the deployment apply to every replica of every agent
+    let orders = &stats.by_workflow[0];
+    assert_eq!(orders.footprint, Footprint { pods: 6, cpu_millis: 6 * 250, memory_mib: 6 * 1024 });
+    // Without them, the defaults of the agent's language
+    let audit = &stats.by_workflow[1];
+    assert_eq!(audit.footprint, Footprint { pods: 1, cpu_millis: RUST_REQUESTS.0, memory_mib: RUST_REQUESTS.1 });
+    assert_eq!(stats.footprint.pods, 7);
+    assert_eq!(stats.footprint.cpu_millis, 1500 + RUST_REQUESTS.0);
+    assert!(PYTHON_REQUESTS.0 > RUST_REQUESTS.0);
+}
+
+#[test]
+fn test_quantities_are_read() {
+    assert_eq!(parse_cpu_millis("500m"), Some(500));
+    assert_eq!(parse_cpu_millis("1.5"), Some(1500));
+    assert_eq!(parse_cpu_millis("2"), Some(2000));
+    assert_eq!(parse_cpu_millis("lots"), None);
+    assert_eq!(parse_memory_mib("512Mi"), Some(512));
+    assert_eq!(parse_memory_mib("2Gi"), Some(2048));
+    assert_eq!(parse_memory_mib("256M"), Some(245));
+    assert_eq!(parse_memory_mib("1Pb"), None);
+
+    let program = parse(r#"workflow A { agents: [LLM(id: "a")]; deployment: { resources: { cpu: "a lot" } }; }"#).unwrap();
+    let error = ProgramStats::of(&program).unwrap_err();
+    assert!(error.to_string().contains("Invalid CPU request 'a lot' of workflow A"), "{}", error);
+}
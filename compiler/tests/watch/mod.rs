@@ -0,0 +1,92 @@
+//! Tests for the dependency graph of watch mode
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use kumeo_compiler::parser::parse_file_with_sources;
+use kumeo_compiler::watch::{DependencyGraph, Regeneration};
+use tempfile::tempdir;
+
+/// A project whose entry imports its agents, with a template directory
+fn project(dir: &Path) -> (PathBuf, DependencyGraph) {
+    fs::create_dir_all(dir.join("templates/agents/rust/LLM")).unwrap();
+    fs::write(
+        dir.join("agents.kumeo"),
+        r#"subworkflow Scoring { input: ["raw"]; output: ["scored"]; agents: [Router(id: "unused")]; }"#,
+    )
+    .unwrap();
+    fs::write(
+        dir.join("main.kumeo"),
+        r#"
+        import "agents.kumeo";
+        workflow Main {
+            source: NATS("input");
+            agents: [
+                LLM(id: "summarize", engine: "ollama/llama3"),
+                LLM(id: "classify", engine: "ollama/llama3"),
+                Router(id: "route")
+            ];
+        }
+        "#,
+    )
+    .unwrap();
+
+    let entry = dir.join("main.kumeo");
+    let (program, sources) = parse_file_with_sources(&entry).expect("Debería parsear el programa");
+    assert_eq!(sources.len(), 2, "El programa depende de la entrada y de su import");
+    let mut graph = DependencyGraph::new(&[dir.join("templates")]);
+    graph.record_program(&entry, sources, &program);
+    (entry, graph)
+}
+
+#[test]
+fn test_source_changes_regenerate_the_program() {
+    let dir = tempdir().unwrap();
+    let (entry, graph) = project(dir.path());
+
+    for changed in ["main.kumeo", "agents.kumeo"] {
+        let affected = graph.affected(&[dir.path().join(changed)]);
+        assert_eq!(affected.get(&entry), Some(&Regeneration::Full), "{} regenera todo el programa", changed);
+    }
+    assert!(graph.affected(&[dir.path().join("notes.txt")]).is_empty(), "Un archivo ajeno no afecta a nada");
+}
+
+#[test]
+fn test_template_changes_regenerate_their_agents() {
+    let dir = tempdir().unwrap();
+    let (entry, graph) = project(dir.path());
+    let templates = dir.path().join("templates");
+
+    let llm = graph.affected(&[templates.join("agents/rust/LLM/src/main.rs.tera")]);
+    let expected: BTreeSet<String> = ["summarize", "classify"].into_iter().map(String::from).collect();
+    assert_eq!(llm.get(&entry), Some(&Regeneration::Agents(expected)), "Solo se regeneran los agentes LLM");
+
+    let rust = graph.affected(&[templates.join("agents/rust/Dockerfile.tera")]);
+    match rust.get(&entry) {
+        Some(Regeneration::Agents(agents)) => assert_eq!(agents.len(), 3, "Todos los agentes se generan en Rust"),
+        other => panic!("Debería regenerar los agentes en Rust: {:?}", other),
+    }
+    assert!(
+        graph.affected(&[templates.join("agents/python/MLModel/main.py.tera")]).is_empty(),
+        "El programa no tiene agentes MLModel"
+    );
+
+    let both = graph.affected(&[templates.join("agents/rust/LLM/Cargo.toml.tera"), templates.join("kubernetes/nats.yaml.tera")]);
+    assert_eq!(both.get(&entry), Some(&Regeneration::Full), "Las plantillas de fuera de agents/ regeneran todo");
+}
+
+#[test]
+fn test_watched_dirs() {
+    let dir = tempdir().unwrap();
+    let (_, mut graph) = project(dir.path());
+    let failed = dir.path().join("broken/main.kumeo");
+    graph.record_failed(&failed);
+
+    let dirs = graph.watched_dirs();
+    let root = dir.path().canonicalize().unwrap();
+    assert_eq!(dirs.len(), 3, "{:?}", dirs);
+    assert!(dirs.contains_key(&root));
+    assert!(dirs.contains_key(&root.join("templates")));
+    assert!(graph.affected(&[failed]).contains_key(&dir.path().join("broken/main.kumeo")), "Un programa con errores se reintenta al cambiar");
+}
@@ -0,0 +1,593 @@
+//! Encodings of the messages on the wire
+//!
+//! Agents publish and receive JSON. A topic may travel in another encoding,
+//! declared with `encoding:` in the workflow: the runtime encodes what the
+//! agent publishes on its output topic and decodes what it receives, so the
+//! handlers keep seeing JSON. Every encoded message carries its encoding in
+//! the [`CONTENT_TYPE_HEADER`]; messages without one are read in the encoding
+//! declared for their topic, and JSON otherwise.
+//!
+//! Protobuf and Avro follow the schema of the topic, a map of field types
+//! such as `{"id": "integer", "note": "string?"}`. Its fields in name order
+//! are the fields of the message, numbered from 1 in Protobuf: `number` is a
+//! `double`, `integer` a `sint64` or `long`, `boolean` a `bool`, and `string`,
+//! `object` and `array` travel as strings, the last two as JSON text.
+//! Optional fields are `optional` in Protobuf and `["null", T]` unions in
+//! Avro. Fields outside the schema are dropped.
+
+use crate::error::{Result, RuntimeError};
+use crate::message;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// Header carrying the encoding of a message
+pub const CONTENT_TYPE_HEADER: &str = "Content-Type";
+
+/// Encoding of the messages the agent receives on its input topic
+pub const INPUT_ENCODING_ENV: &str = "KUMEO_INPUT_ENCODING";
+/// Topic the agent receives encoded messages on
+pub const INPUT_TOPIC_ENV: &str = "KUMEO_INPUT_ENCODING_TOPIC";
+/// Field types of the messages of the input topic, as a JSON object
+pub const INPUT_SCHEMA_ENV: &str = "KUMEO_INPUT_SCHEMA";
+/// Encoding of the messages the agent publishes on its output topic
+pub const OUTPUT_ENCODING_ENV: &str = "KUMEO_OUTPUT_ENCODING";
+/// Topic the agent publishes encoded messages on
+pub const OUTPUT_TOPIC_ENV: &str = "KUMEO_OUTPUT_ENCODING_TOPIC";
+/// Field types of the messages of the output topic, as a JSON object
+pub const OUTPUT_SCHEMA_ENV: &str = "KUMEO_OUTPUT_SCHEMA";
+
+/// An encoding of messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// JSON text
+    Json,
+    /// MessagePack
+    Msgpack,
+    /// Protocol Buffers
+    Protobuf,
+    /// Avro binary records
+    Avro,
+}
+
+impl Encoding {
+    const ALL: [Encoding; 4] = [Encoding::Json, Encoding::Msgpack, Encoding::Protobuf, Encoding::Avro];
+
+    /// Reads an encoding as written in `encoding: ...`
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|encoding| encoding.name() == name)
+    }
+
+    /// Reads the encoding of a content type, ignoring its parameters
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let media_type = content_type.split(';').next().unwrap_or_default().trim();
+        Self::ALL.into_iter().find(|encoding| encoding.content_type().eq_ignore_ascii_case(media_type))
+    }
+
+    /// The encoding as written in `encoding: ...`
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Json => "json",
+            Encoding::Msgpack => "msgpack",
+            Encoding::Protobuf => "protobuf",
+            Encoding::Avro => "avro",
+        }
+    }
+
+    /// The content type of the messages in the encoding
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Encoding::Json => "application/json",
+            Encoding::Msgpack => "application/msgpack",
+            Encoding::Protobuf => "application/x-protobuf",
+            Encoding::Avro => "application/avro",
+        }
+    }
+
+    fn needs_schema(self) -> bool {
+        matches!(self, Encoding::Protobuf | Encoding::Avro)
+    }
+}
+
+/// How a field of the schema is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    /// Objects and arrays, as JSON text
+    Json,
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+    name: String,
+    field_type: FieldType,
+    optional: bool,
+    /// Whether a missing value reads as an object rather than an array
+    object: bool,
+}
+
+/// Encodes and decodes the messages of a topic
+#[derive(Debug, Clone)]
+pub struct Codec {
+    encoding: Encoding,
+    /// Fields of the schema in name order, for Protobuf and Avro
+    fields: Vec<Field>,
+}
+
+impl Codec {
+    /// Creates the codec of an encoding, with the field types of the topic
+    pub fn new(encoding: Encoding, schema: Option<&BTreeMap<String, String>>) -> Result<Self> {
+        let fields = match schema {
+            Some(schema) => schema
+                .iter()
+                .map(|(name, declared)| {
+                    let base = declared.strip_suffix('?').unwrap_or(declared);
+                    let field_type = match base {
+                        "string" => FieldType::String,
+                        "number" => FieldType::Number,
+                        "integer" => FieldType::Integer,
+                        "boolean" => FieldType::Boolean,
+                        "object" | "array" => FieldType::Json,
+                        other => return Err(RuntimeError::Config(message!("Unknown type {} of field '{}'", other, name))),
+                    };
+                    Ok(Field {
+                        name: name.clone(),
+                        field_type,
+                        optional: declared.ends_with('?'),
+                        object: base == "object",
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?,
+            None if encoding.needs_schema() => {
+                return Err(RuntimeError::Config(message!("The {} encoding needs the schema of the topic", encoding.name())))
+            }
+            None => Vec::new(),
+        };
+        Ok(Self { encoding, fields })
+    }
+
+    /// The encoding of the codec
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Encodes a JSON message
+    pub fn encode(&self, json: &[u8]) -> Result<Vec<u8>> {
+        if self.encoding == Encoding::Json {
+            return Ok(json.to_vec());
+        }
+        let value: Value = serde_json::from_slice(json)?;
+        match self.encoding {
+            Encoding::Msgpack => rmp_serde::to_vec_named(&value).map_err(|e| RuntimeError::Serialization(e.to_string())),
+            Encoding::Protobuf => self.encode_protobuf(&value),
+            Encoding::Avro => self.encode_avro(&value),
+            Encoding::Json => unreachable!(),
+        }
+    }
+
+    /// Decodes a message into JSON
+    pub fn decode(&self, payload: &[u8]) -> Result<Vec<u8>> {
+        let value = match self.encoding {
+            Encoding::Json => return Ok(payload.to_vec()),
+            Encoding::Msgpack => rmp_serde::from_slice(payload).map_err(|e| RuntimeError::Serialization(e.to_string()))?,
+            Encoding::Protobuf => self.decode_protobuf(payload)?,
+            Encoding::Avro => self.decode_avro(payload)?,
+        };
+        Ok(serde_json::to_vec(&value)?)
+    }
+
+    /// The value of each field of the schema in a message, `None` when missing or null
+    fn values<'a>(&'a self, value: &'a Value) -> Result<impl Iterator<Item = (&'a Field, Option<&'a Value>)>> {
+        let object = value
+            .as_object()
+            .ok_or_else(|| RuntimeError::Serialization(message!("expected a JSON object, found {}", value)))?;
+        Ok(self.fields.iter().map(|field| (field, object.get(&field.name).filter(|value| !value.is_null()))))
+    }
+
+    fn encode_protobuf(&self, value: &Value) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        for (number, (field, value)) in self.values(value)?.enumerate() {
+            let Some(value) = value else {
+                continue;
+            };
+            let number = number as u64 + 1;
+            match field.field_type {
+                FieldType::Number => {
+                    write_varint(&mut out, number << 3 | 1);
+                    out.extend_from_slice(&number_of(field, value)?.to_le_bytes());
+                }
+                FieldType::Integer => {
+                    write_varint(&mut out, number << 3);
+                    write_varint(&mut out, zigzag(integer_of(field, value)?));
+                }
+                FieldType::Boolean => {
+                    write_varint(&mut out, number << 3);
+                    write_varint(&mut out, u64::from(boolean_of(field, value)?));
+                }
+                FieldType::String | FieldType::Json => {
+                    let text = text_of(field, value)?;
+                    write_varint(&mut out, number << 3 | 2);
+                    write_varint(&mut out, text.len() as u64);
+                    out.extend_from_slice(text.as_bytes());
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    fn decode_protobuf(&self, payload: &[u8]) -> Result<Value> {
+        let mut reader = Reader(payload);
+        let mut values: BTreeMap<usize, Value> = BTreeMap::new();
+        while !reader.0.is_empty() {
+            let key = reader.varint()?;
+            let (number, wire_type) = (key >> 3, key & 7);
+            let field = number.checked_sub(1).and_then(|index| self.fields.get(index as usize).map(|field| (index as usize, field)));
+            let value = match (wire_type, field.map(|(_, field)| field.field_type)) {
+                (0, Some(FieldType::Integer)) => Value::from(unzigzag(reader.varint()?)),
+                (0, Some(FieldType::Boolean)) => Value::Bool(reader.varint()? != 0),
+                (1, Some(FieldType::Number)) => number_value(f64::from_le_bytes(reader.fixed()?)),
+                (2, Some(field_type @ (FieldType::String | FieldType::Json))) => {
+                    let length = reader.varint()? as usize;
+                    text_value(field_type, reader.bytes(length)?)?
+                }
+                // Fields of a newer schema are skipped
+                (0, None) => {
+                    reader.varint()?;
+                    continue;
+                }
+                (1, None) => {
+                    reader.fixed()?;
+                    continue;
+                }
+                (2, None) => {
+                    let length = reader.varint()? as usize;
+                    reader.bytes(length)?;
+                    continue;
+                }
+                (5, None) => {
+                    reader.bytes(4)?;
+                    continue;
+                }
+                _ => return Err(RuntimeError::Serialization(message!("unexpected wire type {} for field {}", wire_type, number))),
+            };
+            if let Some((index, _)) = field {
+                values.insert(index, value);
+            }
+        }
+
+        // Fields left out hold their default value, unless they are optional
+        let mut object = Map::new();
+        for (index, field) in self.fields.iter().enumerate() {
+            match values.remove(&index) {
+                Some(value) => {
+                    object.insert(field.name.clone(), value);
+                }
+                None if !field.optional => {
+                    object.insert(field.name.clone(), default_value(field));
+                }
+                None => {}
+            }
+        }
+        Ok(Value::Object(object))
+    }
+
+    fn encode_avro(&self, value: &Value) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        for (field, value) in self.values(value)? {
+            match (value, field.optional) {
+                (None, true) => {
+                    write_varint(&mut out, zigzag(0));
+                    continue;
+                }
+                (None, false) => return Err(RuntimeError::Serialization(message!("missing required field '{}'", field.name))),
+                (Some(_), true) => write_varint(&mut out, zigzag(1)),
+                (Some(_), false) => {}
+            }
+            let value = value.unwrap_or(&Value::Null);
+            match field.field_type {
+                FieldType::Number => out.extend_from_slice(&number_of(field, value)?.to_le_bytes()),
+                FieldType::Integer => write_varint(&mut out, zigzag(integer_of(field, value)?)),
+                FieldType::Boolean => out.push(u8::from(boolean_of(field, value)?)),
+                FieldType::String | FieldType::Json => {
+                    let text = text_of(field, value)?;
+                    write_varint(&mut out, zigzag(text.len() as i64));
+                    out.extend_from_slice(text.as_bytes());
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    fn decode_avro(&self, payload: &[u8]) -> Result<Value> {
+        let mut reader = Reader(payload);
+        let mut object = Map::new();
+        for field in &self.fields {
+            if field.optional && unzigzag(reader.varint()?) == 0 {
+                continue;
+            }
+            let value = match field.field_type {
+                FieldType::Number => number_value(f64::from_le_bytes(reader.fixed()?)),
+                FieldType::Integer => Value::from(unzigzag(reader.varint()?)),
+                FieldType::Boolean => Value::Bool(reader.bytes(1)?[0] != 0),
+                FieldType::String | FieldType::Json => {
+                    let length = usize::try_from(unzigzag(reader.varint()?))
+                        .map_err(|_| RuntimeError::Serialization(message!("negative length of field '{}'", field.name)))?;
+                    text_value(field.field_type, reader.bytes(length)?)?
+                }
+            };
+            object.insert(field.name.clone(), value);
+        }
+        if !reader.0.is_empty() {
+            return Err(RuntimeError::Serialization(message!("{} bytes left after the record", reader.0.len())));
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+/// A topic whose messages are encoded
+#[derive(Debug, Clone)]
+struct EncodedTopic {
+    topic: String,
+    codec: Codec,
+}
+
+impl EncodedTopic {
+    /// Reads the encoded topic from the variables of one side of the agent
+    fn from_env(encoding_var: &str, topic_var: &str, schema_var: &str) -> Result<Option<Self>> {
+        let var = |name| std::env::var(name).ok().filter(|value: &String| !value.is_empty());
+        let Some(name) = var(encoding_var) else {
+            return Ok(None);
+        };
+        let encoding = Encoding::parse(&name)
+            .ok_or_else(|| RuntimeError::Config(message!("Unknown encoding in {}: {}", encoding_var, name)))?;
+        let topic = var(topic_var).ok_or_else(|| RuntimeError::Config(message!("{} is set without {}", encoding_var, topic_var)))?;
+        let schema: Option<BTreeMap<String, String>> = var(schema_var)
+            .map(|schema| serde_json::from_str(&schema))
+            .transpose()
+            .map_err(|e| RuntimeError::Config(message!("Invalid {}: {}", schema_var, e)))?;
+        Ok(Some(Self { topic, codec: Codec::new(encoding, schema.as_ref())? }))
+    }
+}
+
+/// Encodings of the topics of the agent
+#[derive(Debug, Clone, Default)]
+pub struct Codecs {
+    input: Option<EncodedTopic>,
+    output: Option<EncodedTopic>,
+}
+
+impl Codecs {
+    /// Creates the codecs of the input and output topics
+    pub fn new(input: Option<(String, Codec)>, output: Option<(String, Codec)>) -> Self {
+        let topic = |(topic, codec)| EncodedTopic { topic, codec };
+        Self {
+            input: input.map(topic),
+            output: output.map(topic),
+        }
+    }
+
+    /// Reads the encodings of the topics from the `KUMEO_INPUT_*` and `KUMEO_OUTPUT_*` variables
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            input: EncodedTopic::from_env(INPUT_ENCODING_ENV, INPUT_TOPIC_ENV, INPUT_SCHEMA_ENV)?,
+            output: EncodedTopic::from_env(OUTPUT_ENCODING_ENV, OUTPUT_TOPIC_ENV, OUTPUT_SCHEMA_ENV)?,
+        })
+    }
+
+    /// Encodes a message published on `subject`, telling its encoding in the headers
+    ///
+    /// Only the output topic is encoded; other subjects carry JSON as is.
+    pub fn encode(&self, subject: &str, payload: &[u8], headers: &mut HashMap<String, String>) -> Result<Vec<u8>> {
+        match &self.output {
+            Some(output) if output.topic == subject => {
+                headers.insert(CONTENT_TYPE_HEADER.to_string(), output.codec.encoding.content_type().to_string());
+                output.codec.encode(payload)
+            }
+            _ => Ok(payload.to_vec()),
+        }
+    }
+
+    /// Decodes a message received on `subject` into JSON, by its content type
+    ///
+    /// Messages without a content type are in the encoding of the input
+    /// topic when received on it, and JSON otherwise. The headers are left
+    /// telling the JSON the handler gets.
+    pub fn decode(&self, subject: &str, payload: &[u8], headers: &mut HashMap<String, String>) -> Result<Vec<u8>> {
+        let input = self.input.as_ref().filter(|input| input.topic == subject);
+        let encoding = match headers.get(CONTENT_TYPE_HEADER) {
+            Some(content_type) => match Encoding::from_content_type(content_type) {
+                Some(encoding) => encoding,
+                // Content types of other formats are left to the handler
+                None => return Ok(payload.to_vec()),
+            },
+            None => input.map_or(Encoding::Json, |input| input.codec.encoding),
+        };
+        let decoded = match encoding {
+            Encoding::Json => payload.to_vec(),
+            Encoding::Msgpack => Codec::new(Encoding::Msgpack, None)?.decode(payload)?,
+            _ => {
+                // Protobuf and Avro are read with the schema of the topic
+                let codec = [self.input.as_ref(), self.output.as_ref()]
+                    .into_iter()
+                    .flatten()
+                    .find(|topic| topic.topic == subject && topic.codec.encoding == encoding)
+                    .map(|topic| &topic.codec)
+                    .ok_or_else(|| {
+                        RuntimeError::Serialization(message!("No schema to read {} messages on {}", encoding.name(), subject))
+                    })?;
+                codec.decode(payload)?
+            }
+        };
+        if encoding != Encoding::Json {
+            headers.insert(CONTENT_TYPE_HEADER.to_string(), Encoding::Json.content_type().to_string());
+        }
+        Ok(decoded)
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// Reads an encoded message from the front
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8]> {
+        if self.0.len() < length {
+            return Err(RuntimeError::Serialization(message!("truncated message")));
+        }
+        let (bytes, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn fixed(&mut self) -> Result<[u8; 8]> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.bytes(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(RuntimeError::Serialization(message!("varint too long")))
+    }
+}
+
+fn mismatch(field: &Field, expected: &str, value: &Value) -> RuntimeError {
+    RuntimeError::Serialization(message!("field '{}' must be {}, found {}", field.name, expected, value))
+}
+
+fn number_of(field: &Field, value: &Value) -> Result<f64> {
+    value.as_f64().ok_or_else(|| mismatch(field, &message!("a number"), value))
+}
+
+fn integer_of(field: &Field, value: &Value) -> Result<i64> {
+    value
+        .as_i64()
+        .or_else(|| value.as_f64().filter(|n| n.fract() == 0.0).map(|n| n as i64))
+        .ok_or_else(|| mismatch(field, &message!("an integer"), value))
+}
+
+fn boolean_of(field: &Field, value: &Value) -> Result<bool> {
+    value.as_bool().ok_or_else(|| mismatch(field, &message!("a boolean"), value))
+}
+
+fn text_of(field: &Field, value: &Value) -> Result<String> {
+    match (field.field_type, value) {
+        (FieldType::Json, Value::Object(_) | Value::Array(_)) => Ok(value.to_string()),
+        (FieldType::Json, _) => Err(mismatch(field, &if field.object { message!("an object") } else { message!("an array") }, value)),
+        (_, Value::String(text)) => Ok(text.clone()),
+        _ => Err(mismatch(field, &message!("a string"), value)),
+    }
+}
+
+fn text_value(field_type: FieldType, bytes: &[u8]) -> Result<Value> {
+    let text = std::str::from_utf8(bytes).map_err(|e| RuntimeError::Serialization(e.to_string()))?;
+    match field_type {
+        FieldType::Json => Ok(serde_json::from_str(text)?),
+        _ => Ok(Value::String(text.to_string())),
+    }
+}
+
+fn number_value(number: f64) -> Value {
+    serde_json::Number::from_f64(number).map_or(Value::Null, Value::Number)
+}
+
+/// The value of a required field Protobuf leaves out, as it holds the default
+fn default_value(field: &Field) -> Value {
+    match field.field_type {
+        FieldType::String => Value::String(String::new()),
+        FieldType::Number => number_value(0.0),
+        FieldType::Integer => Value::from(0),
+        FieldType::Boolean => Value::Bool(false),
+        FieldType::Json if field.object => Value::Object(Map::new()),
+        FieldType::Json => Value::Array(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> BTreeMap<String, String> {
+        [("id", "integer"), ("score", "number"), ("note", "string?"), ("flagged", "boolean"), ("tags", "array?")]
+            .into_iter()
+            .map(|(name, field_type)| (name.to_string(), field_type.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_protobuf_follows_the_wire_format() {
+        let codec = Codec::new(Encoding::Protobuf, Some(&[("id".to_string(), "integer".to_string())].into())).unwrap();
+        // sint64 150 is zigzag 300, field 1 with wire type 0
+        assert_eq!(codec.encode(br#"{"id": 150}"#).unwrap(), [0x08, 0xac, 0x02]);
+        assert_eq!(codec.decode(&[0x08, 0xac, 0x02]).unwrap(), br#"{"id":150}"#);
+    }
+
+    #[test]
+    fn test_messages_round_trip_through_every_encoding() {
+        let message = json!({ "id": -7, "score": 0.5, "flagged": true, "tags": ["a", "b"], "extra": 1 });
+        let expected = json!({ "id": -7, "score": 0.5, "flagged": true, "tags": ["a", "b"] });
+        for encoding in [Encoding::Msgpack, Encoding::Protobuf, Encoding::Avro] {
+            let codec = Codec::new(encoding, Some(&schema())).unwrap();
+            let encoded = codec.encode(message.to_string().as_bytes()).unwrap();
+            let decoded: Value = serde_json::from_slice(&codec.decode(&encoded).unwrap()).unwrap();
+            // MessagePack keeps every field, the others those of the schema
+            let expected = if encoding == Encoding::Msgpack { &message } else { &expected };
+            assert_eq!(&decoded, expected, "{}", encoding.name());
+        }
+
+        let avro = Codec::new(Encoding::Avro, Some(&schema())).unwrap();
+        let error = avro.encode(br#"{"id": "7", "score": 1, "flagged": false}"#).unwrap_err();
+        assert!(error.to_string().contains("field 'id' must be an integer"), "{}", error);
+        assert!(Codec::new(Encoding::Avro, None).is_err());
+    }
+
+    #[test]
+    fn test_topics_are_encoded_and_decoded_by_content_type() {
+        let codec = || Codec::new(Encoding::Protobuf, Some(&schema())).unwrap();
+        let codecs = Codecs::new(Some(("orders".to_string(), codec())), Some(("scores".to_string(), codec())));
+        let message = br#"{"flagged":false,"id":1,"score":2.0}"#;
+
+        let mut headers = HashMap::new();
+        assert_eq!(codecs.encode("kumeo.audit", message, &mut headers).unwrap(), message);
+        assert!(headers.is_empty());
+        let encoded = codecs.encode("scores", message, &mut headers).unwrap();
+        assert_eq!(headers[CONTENT_TYPE_HEADER], "application/x-protobuf");
+
+        // The declared encoding of the input topic stands for a missing content type
+        let mut received = HashMap::new();
+        assert_eq!(codecs.decode("orders", &encoded, &mut received).unwrap(), message);
+        assert_eq!(received[CONTENT_TYPE_HEADER], "application/json");
+        assert_eq!(codecs.decode("scores", &encoded, &mut headers).unwrap(), message);
+
+        let mut msgpack = HashMap::from([(CONTENT_TYPE_HEADER.to_string(), "application/msgpack".to_string())]);
+        let packed = rmp_serde::to_vec_named(&json!({ "a": 1 })).unwrap();
+        assert_eq!(codecs.decode("other", &packed, &mut msgpack).unwrap(), br#"{"a":1}"#);
+        let mut unknown = HashMap::from([(CONTENT_TYPE_HEADER.to_string(), "application/avro".to_string())]);
+        assert!(codecs.decode("other", b"", &mut unknown).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+//! Evaluation helpers for agent `when` conditions
+//!
+//! The compiler translates an agent's `when:` expression into Rust code that
+//! calls these helpers on the JSON payload of each message. Missing fields
+//! read as null and comparing values of different types is false rather than
+//! an error, so a condition can reject a message but never fail it.
+
+use serde_json::Value;
+use std::cmp::Ordering;
+
+static NULL: Value = Value::Null;
+
+/// The value at a dotted path of the payload, null when any segment is missing
+///
+/// Numeric segments index into arrays.
+pub fn field<'a>(data: &'a Value, path: &[&str]) -> &'a Value {
+    path.iter()
+        .try_fold(data, |value, key| match value {
+            Value::Array(items) => key.parse::<usize>().ok().and_then(|index| items.get(index)),
+            _ => value.get(key),
+        })
+        .unwrap_or(&NULL)
+}
+
+/// The value, or the default when it is null (`??`)
+pub fn coalesce<'a>(value: &'a Value, default: &'a Value) -> &'a Value {
+    if value.is_null() {
+        default
+    } else {
+        value
+    }
+}
+
+/// Whether a value counts as true on its own
+pub fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(number) => number.as_f64().is_some_and(|number| number != 0.0),
+        Value::String(text) => !text.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+/// Order two numbers or two strings; any other pair is unordered
+pub fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => left.as_f64()?.partial_cmp(&right.as_f64()?),
+        (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+        _ => None,
+    }
+}
+
+/// JSON equality, with `1` and `1.0` being the same number
+pub fn equals(left: &Value, right: &Value) -> bool {
+    compare(left, right).map_or(left == right, Ordering::is_eq)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_field_paths() {
+        let data = json!({"order": {"items": [{"sku": "a1"}]}, "score": 0.9});
+        assert_eq!(field(&data, &["order", "items", "0", "sku"]), &json!("a1"));
+        assert_eq!(field(&data, &["score"]), &json!(0.9));
+        assert_eq!(field(&data, &["order", "missing", "sku"]), &Value::Null);
+        assert_eq!(coalesce(field(&data, &["customer", "email"]), &json!("unknown")), &json!("unknown"));
+        assert_eq!(coalesce(field(&data, &["score"]), &json!(0)), &json!(0.9));
+    }
+
+    #[test]
+    fn test_comparisons_across_types() {
+        assert!(equals(&json!(1), &json!(1.0)));
+        assert!(!equals(&json!(true), &json!(1)));
+        assert_eq!(compare(&json!(0.9), &json!(0.8)), Some(Ordering::Greater));
+        assert_eq!(compare(&json!("es"), &json!("en")), Some(Ordering::Greater));
+        assert_eq!(compare(&json!("0.9"), &json!(0.8)), None);
+        assert!(!truthy(&Value::Null) && !truthy(&json!("")) && truthy(&json!([0])));
+    }
+}
@@ -1,6 +1,7 @@
 //! Configuration for the Kumeo runtime
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 /// Configuración de recursos
@@ -10,6 +11,249 @@ pub struct ResourcesConfig {
     pub base_dir: PathBuf,
     /// Maximum cache time for resources (in seconds)
     pub cache_ttl: Option<u64>,
+    /// URI rewrite rules, tried in order before loading a resource
+    #[serde(default)]
+    pub mirrors: Vec<MirrorRule>,
+    /// Refuse resources without a valid `#minisign` or `#cosign` signature
+    #[serde(default)]
+    pub require_signed: bool,
+    /// Directory of trusted minisign and cosign public keys
+    #[serde(default)]
+    pub trusted_keys: Option<PathBuf>,
+    /// Concurrency and bandwidth limits for downloads
+    #[serde(default)]
+    pub limits: FetchLimits,
+    /// Models to load and pin before reporting ready
+    #[serde(default)]
+    pub preload: PreloadConfig,
+}
+
+/// Warm-up of the models an agent declares
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreloadConfig {
+    /// URIs loaded and pinned at startup
+    #[serde(default)]
+    pub uris: Vec<String>,
+    /// Directory pinned resources are kept in instead of memory
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    /// File created while no warm-up is in progress, for readiness probes
+    #[serde(default)]
+    pub ready_file: Option<PathBuf>,
+}
+
+impl PreloadConfig {
+    /// Reads `KUMEO_PRELOAD` (comma-separated URIs), `KUMEO_PRELOAD_DIR` and
+    /// `KUMEO_READY_FILE`
+    pub fn from_env() -> Self {
+        let uris = match std::env::var("KUMEO_PRELOAD") {
+            Ok(uris) => uris
+                .split(',')
+                .map(str::trim)
+                .filter(|uri| !uri.is_empty())
+                .map(str::to_string)
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+
+        Self {
+            uris,
+            dir: std::env::var_os("KUMEO_PRELOAD_DIR").map(PathBuf::from),
+            ready_file: std::env::var_os("KUMEO_READY_FILE").map(PathBuf::from),
+        }
+    }
+}
+
+/// Limits on remote resource downloads
+///
+/// Local `file://` reads are not limited.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FetchLimits {
+    /// Maximum downloads in flight across all schemes
+    #[serde(default)]
+    pub max_in_flight: Option<usize>,
+    /// Maximum downloads in flight per scheme (e.g. `s3` → 4)
+    #[serde(default)]
+    pub per_scheme: HashMap<String, usize>,
+    /// Bandwidth shared by all downloads (in bytes per second)
+    #[serde(default)]
+    pub bandwidth: Option<u64>,
+}
+
+impl FetchLimits {
+    /// Reads `KUMEO_FETCH_MAX_IN_FLIGHT`, `KUMEO_FETCH_BANDWIDTH` (bytes per
+    /// second) and `KUMEO_FETCH_SCHEME_LIMITS` (e.g. `s3=4,https=8`)
+    pub fn from_env() -> crate::error::Result<Self> {
+        let per_scheme = match std::env::var("KUMEO_FETCH_SCHEME_LIMITS") {
+            Ok(limits) => limits
+                .split(',')
+                .filter(|limit| !limit.trim().is_empty())
+                .map(|limit| {
+                    limit
+                        .split_once('=')
+                        .and_then(|(scheme, max)| Some((scheme.trim().to_ascii_lowercase(), max.trim().parse().ok()?)))
+                        .ok_or_else(|| {
+                            crate::error::RuntimeError::Config(crate::message!("Invalid KUMEO_FETCH_SCHEME_LIMITS: {}", limit))
+                        })
+                })
+                .collect::<crate::error::Result<_>>()?,
+            Err(_) => HashMap::new(),
+        };
+
+        Ok(Self {
+            max_in_flight: env_u64("KUMEO_FETCH_MAX_IN_FLIGHT")?.map(|max| max as usize),
+            per_scheme,
+            bandwidth: env_u64("KUMEO_FETCH_BANDWIDTH")?,
+        })
+    }
+}
+
+impl ResourcesConfig {
+    /// Creates a resource configuration rooted at `base_dir`.
+    ///
+    /// Mirror rules are read from `KUMEO_RESOURCE_MIRRORS`, a comma-separated
+    /// list of `FROM=TO` rules such as
+    /// `https://huggingface.co/*=https://mirror.internal/hf/*`. Signed resources
+    /// are required when `KUMEO_REQUIRE_SIGNED=true`, and `KUMEO_TRUSTED_KEYS`
+    /// names the directory of public keys to verify them with. Download
+    /// limits are read as described in [`FetchLimits::from_env`] and the
+    /// models to warm up as described in [`PreloadConfig::from_env`].
+    pub fn new(base_dir: PathBuf) -> crate::error::Result<Self> {
+        let mirrors = match std::env::var("KUMEO_RESOURCE_MIRRORS") {
+            Ok(rules) => MirrorRule::parse_list(&rules)?,
+            Err(_) => Vec::new(),
+        };
+
+        Ok(Self {
+            base_dir,
+            cache_ttl: Some(300),
+            mirrors,
+            require_signed: std::env::var("KUMEO_REQUIRE_SIGNED").is_ok_and(|value| value == "true"),
+            trusted_keys: std::env::var_os("KUMEO_TRUSTED_KEYS").map(PathBuf::from),
+            limits: FetchLimits::from_env()?,
+            preload: PreloadConfig::from_env(),
+        })
+    }
+
+    /// Applies the first matching mirror rule to a URI
+    pub fn mirror(&self, uri: &str) -> String {
+        self.mirrors
+            .iter()
+            .find_map(|rule| rule.apply(uri))
+            .unwrap_or_else(|| uri.to_string())
+    }
+}
+
+/// Redirects resource URIs, e.g. to an approved proxy
+///
+/// A `from` ending in `*` matches every URI with that prefix, and the rest of
+/// the URI replaces the `*` at the end of `to`. Otherwise the rule only
+/// matches `from` exactly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MirrorRule {
+    /// URI or `prefix*` to redirect
+    pub from: String,
+    /// Replacement URI, or `prefix*` when `from` is a prefix
+    pub to: String,
+}
+
+impl MirrorRule {
+    /// Parses a `FROM=TO` rule, checking that wildcards only end both sides
+    pub fn parse(rule: &str) -> crate::error::Result<Self> {
+        let invalid = |reason: &str| {
+            crate::error::RuntimeError::Config(crate::message!("Invalid mirror rule '{}': {}", rule, reason))
+        };
+
+        let (from, to) = rule.trim().split_once('=').ok_or_else(|| invalid(&crate::message!("expected FROM=TO")))?;
+        let (from, to) = (from.trim(), to.trim());
+        if from.is_empty() || to.is_empty() {
+            return Err(invalid(&crate::message!("expected FROM=TO")));
+        }
+        if from.trim_end_matches('*').contains('*') || to.trim_end_matches('*').contains('*') {
+            return Err(invalid(&crate::message!("'*' is only allowed at the end")));
+        }
+        if from.ends_with('*') != to.ends_with('*') {
+            return Err(invalid(&crate::message!("both sides must end in '*' or neither")));
+        }
+
+        Ok(Self {
+            from: from.to_string(),
+            to: to.to_string(),
+        })
+    }
+
+    /// Parses a comma-separated list of `FROM=TO` rules
+    pub fn parse_list(rules: &str) -> crate::error::Result<Vec<Self>> {
+        rules
+            .split(',')
+            .filter(|rule| !rule.trim().is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    /// The rewritten URI, if this rule matches
+    pub fn apply(&self, uri: &str) -> Option<String> {
+        match (self.from.strip_suffix('*'), self.to.strip_suffix('*')) {
+            (Some(from), Some(to)) => uri.strip_prefix(from).map(|rest| format!("{}{}", to, rest)),
+            _ => (uri == self.from).then(|| self.to.clone()),
+        }
+    }
+}
+
+/// Replaces `${env.NAME}` references with the values of environment variables
+///
+/// The compiler keeps these references in agent configurations, so secrets
+/// are only read inside the pod, where the manifests source them from a
+/// Secret. Other `${...}` placeholders are left as is; a referenced variable
+/// that is not set is an error.
+pub fn interpolate_env(text: &str) -> crate::error::Result<String> {
+    interpolate_with(text, |name| std::env::var(name).ok())
+}
+
+fn interpolate_with(text: &str, lookup: impl Fn(&str) -> Option<String>) -> crate::error::Result<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let placeholder = &rest[start..start + len + 1];
+        let name = placeholder[2..placeholder.len() - 1].strip_prefix("env.").filter(|name| {
+            name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+
+        result.push_str(&rest[..start]);
+        match name {
+            Some(name) => result.push_str(&lookup(name).ok_or_else(|| {
+                crate::error::RuntimeError::Config(crate::message!("Environment variable {} is not set", name))
+            })?),
+            None => result.push_str(placeholder),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Parses a JSON agent configuration, resolving `${env.NAME}` in its strings
+///
+/// References are resolved after parsing, so values containing quotes or
+/// backslashes cannot break the JSON.
+pub fn parse_agent_config<T: serde::de::DeserializeOwned>(text: &str) -> crate::error::Result<T> {
+    fn interpolate(value: &mut serde_json::Value) -> crate::error::Result<()> {
+        match value {
+            serde_json::Value::String(text) => *text = interpolate_env(text)?,
+            serde_json::Value::Array(items) => items.iter_mut().try_for_each(interpolate)?,
+            serde_json::Value::Object(map) => map.values_mut().try_for_each(interpolate)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    let mut value = serde_json::from_str(text)?;
+    interpolate(&mut value)?;
+    Ok(serde_json::from_value(value)?)
 }
 
 /// Configuración de mensajería
@@ -21,6 +265,121 @@ pub struct MessagingConfig {
     pub channel_prefix: Option<String>,
     /// Timeout for messaging operations (in seconds)
     pub timeout: Option<u64>,
+    /// Maximum time to wait for in-flight messages on shutdown (in seconds)
+    #[serde(default = "default_drain_timeout")]
+    pub drain_timeout: u64,
+    /// Bounded-input execution, for agents of batch workflows
+    #[serde(default)]
+    pub batch: Option<BatchConfig>,
+    /// MQTT broker URL (`mqtt://host:port`), used with the `mqtt` feature
+    #[serde(default)]
+    pub mqtt_url: Option<String>,
+    /// Keep watching file sources for new files, used with the `files` feature
+    #[serde(default)]
+    pub file_watch: bool,
+    /// Kafka cluster of the agent's input or output topic, used with the `kafka` feature
+    #[serde(default)]
+    pub kafka: Option<KafkaConfig>,
+}
+
+/// Kafka ends of an agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KafkaConfig {
+    /// Comma-separated bootstrap servers
+    pub bootstrap_servers: String,
+    /// Consumer group shared by the replicas of the agent
+    pub group_id: String,
+    /// Input topic, consumed from Kafka instead of NATS
+    pub input_topic: Option<String>,
+    /// Output topic, produced to Kafka instead of NATS
+    pub output_topic: Option<String>,
+}
+
+impl KafkaConfig {
+    /// Reads the Kafka ends the generated manifests set, if the agent has any
+    ///
+    /// `KAFKA_BOOTSTRAP_SERVERS` and `KAFKA_GROUP_ID` name the cluster; the
+    /// input topic `KUMEO_INPUT_TOPIC` is consumed from it when
+    /// `KUMEO_SOURCE_BROKER=kafka`, and the output topic `KUMEO_OUTPUT_TOPIC`
+    /// produced to it when `KUMEO_TARGET_BROKER=kafka`.
+    pub fn from_env() -> Option<Self> {
+        let bootstrap_servers = std::env::var("KAFKA_BOOTSTRAP_SERVERS").ok()?;
+        let topic = |broker: &str, topic: &str| {
+            std::env::var(broker)
+                .is_ok_and(|value| value == "kafka")
+                .then(|| std::env::var(topic).ok())
+                .flatten()
+        };
+        Some(Self {
+            bootstrap_servers,
+            group_id: std::env::var("KAFKA_GROUP_ID").unwrap_or_else(|_| "kumeo".to_string()),
+            input_topic: topic("KUMEO_SOURCE_BROKER", "KUMEO_INPUT_TOPIC"),
+            output_topic: topic("KUMEO_TARGET_BROKER", "KUMEO_OUTPUT_TOPIC"),
+        })
+    }
+}
+
+fn default_drain_timeout() -> u64 {
+    30
+}
+
+/// Configuración de ejecución batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchConfig {
+    /// Stop once a message with this stream sequence has been received
+    pub until_sequence: Option<u64>,
+    /// Consider the input consumed after this long without messages (in seconds)
+    #[serde(default = "default_batch_idle_timeout")]
+    pub idle_timeout: u64,
+}
+
+fn default_batch_idle_timeout() -> u64 {
+    30
+}
+
+/// Reads an optional numeric environment variable
+fn env_u64(name: &str) -> crate::error::Result<Option<u64>> {
+    match std::env::var(name) {
+        Ok(value) => value.trim().parse().map(Some).map_err(|_| {
+            crate::error::RuntimeError::Config(crate::message!("Invalid {}: {}", name, value))
+        }),
+        Err(_) => Ok(None),
+    }
+}
+
+impl MessagingConfig {
+    /// Creates a messaging configuration for the given NATS server.
+    ///
+    /// The drain timeout can be overridden with `KUMEO_DRAIN_TIMEOUT_SECS`,
+    /// which the generated Deployments set from the agent timeout. Batch Jobs
+    /// set `KUMEO_BATCH=true` and optionally `KUMEO_BATCH_UNTIL_SEQUENCE` and
+    /// `KUMEO_BATCH_IDLE_SECS`. MQTT sources and targets read the broker
+    /// from `KUMEO_MQTT_URL`, file sources watch for new files when
+    /// `KUMEO_FILE_WATCH=true`, and Kafka ones are read by
+    /// [`KafkaConfig::from_env`].
+    pub fn new(nats_url: String) -> crate::error::Result<Self> {
+        let drain_timeout = env_u64("KUMEO_DRAIN_TIMEOUT_SECS")?.unwrap_or_else(default_drain_timeout);
+
+        let batch = if std::env::var("KUMEO_BATCH").is_ok_and(|value| value == "true") {
+            Some(BatchConfig {
+                until_sequence: env_u64("KUMEO_BATCH_UNTIL_SEQUENCE")?,
+                idle_timeout: env_u64("KUMEO_BATCH_IDLE_SECS")?.unwrap_or_else(default_batch_idle_timeout),
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            nats_url,
+            channel_prefix: None,
+            timeout: None,
+            drain_timeout,
+            batch,
+            mqtt_url: std::env::var("KUMEO_MQTT_URL").ok(),
+            file_watch: std::env::var("KUMEO_FILE_WATCH").is_ok_and(|value| value == "true"),
+            kafka: KafkaConfig::from_env(),
+        })
+    }
 }
 
 /// Main runtime configuration
@@ -53,9 +412,40 @@ impl Default for RuntimeConfig {
             resources: ResourcesConfig {
                 base_dir: std::env::current_dir().unwrap_or_default(),
                 cache_ttl: Some(300), // 5 minutos por defecto
+                mirrors: Vec::new(),
+                require_signed: false,
+                trusted_keys: None,
+                limits: FetchLimits::default(),
+                preload: PreloadConfig::default(),
             },
             messaging: None,
             log_level: default_log_level(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolate_env_references() {
+        let lookup = |name: &str| (name == "OPENAI_API_KEY").then(|| "sk-\"quoted\"".to_string());
+
+        assert_eq!(
+            interpolate_with("Bearer ${env.OPENAI_API_KEY}", lookup).unwrap(),
+            "Bearer sk-\"quoted\""
+        );
+        // Other placeholders are not environment references
+        assert_eq!(interpolate_with("${user.name} ${env.bad-name}", lookup).unwrap(), "${user.name} ${env.bad-name}");
+        assert!(interpolate_with("${env.MISSING}", lookup).is_err());
+    }
+
+    #[test]
+    fn test_parse_agent_config_resolves_strings() {
+        std::env::set_var("KUMEO_TEST_API_KEY", "a\"b");
+        let config: HashMap<String, Vec<String>> =
+            parse_agent_config(r#"{"keys": ["${env.KUMEO_TEST_API_KEY}"]}"#).unwrap();
+        assert_eq!(config["keys"], ["a\"b"]);
+    }
+}
@@ -0,0 +1,200 @@
+//! Delayed delivery of messages
+//!
+//! An agent hands a message to the runtime with a subject and an interval,
+//! and the runtime publishes it on that subject once the interval is over:
+//! the way to retry a message later, after a rate limit or an outage cools
+//! down, without holding it in the agent. With NATS, delayed messages are
+//! kept in a JetStream stream with their subject and due time in headers, so
+//! they survive restarts of the agent and of the runtime; a durable consumer
+//! hands each one back until it is due, then publishes it. Without NATS they
+//! are timers of the runtime, lost when it restarts.
+
+use crate::error::{Result, RuntimeError};
+use crate::message;
+use crate::messaging::Manager as MessagingManager;
+use crate::scheduler::Scheduler;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Stream holding the delayed messages
+pub const STREAM: &str = "KUMEO_DELAYED";
+
+/// Subjects of the stream; a delayed message is stored under its own subject
+pub const SUBJECT_PREFIX: &str = "kumeo.delayed";
+
+/// Header with the subject a delayed message is published on
+pub const SUBJECT_HEADER: &str = "Kumeo-Delayed-Subject";
+
+/// Header with when a delayed message is due, in milliseconds since the epoch
+pub const DUE_HEADER: &str = "Kumeo-Delayed-Until";
+
+/// Consumer publishing the due messages, shared by the runtimes
+const CONSUMER: &str = "kumeo-delayed";
+
+/// Longest a delayed message is kept
+const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Milliseconds since the epoch
+pub fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_millis() as u64).unwrap_or_default()
+}
+
+/// How long a message due at `due_ms` still has to wait at `now_ms`, if it isn't due yet
+pub fn remaining(due_ms: u64, now_ms: u64) -> Option<Duration> {
+    (due_ms > now_ms).then(|| Duration::from_millis(due_ms - now_ms))
+}
+
+/// Where the delayed messages wait
+#[derive(Clone)]
+enum Backend {
+    /// Timers of the runtime
+    Memory { scheduler: Scheduler, next: Arc<AtomicU64> },
+    /// The JetStream stream, published through the runtime's messaging
+    #[cfg(feature = "nats")]
+    Nats { jetstream: async_nats::jetstream::Context, messaging: MessagingManager },
+}
+
+/// Delayed delivery of messages
+#[derive(Clone)]
+pub struct Delayer {
+    backend: Backend,
+}
+
+impl Delayer {
+    /// Delayed messages kept in JetStream, or in timers without a NATS connection
+    pub fn new(messaging: Option<&MessagingManager>, scheduler: Scheduler) -> Self {
+        #[cfg(feature = "nats")]
+        if let Some(messaging) = messaging {
+            if let Some(client) = messaging.nats_client() {
+                return Self {
+                    backend: Backend::Nats {
+                        jetstream: async_nats::jetstream::new(client.clone()),
+                        messaging: messaging.clone(),
+                    },
+                };
+            }
+        }
+        let _ = messaging;
+        Self { backend: Backend::Memory { scheduler, next: Arc::default() } }
+    }
+
+    /// Publish `payload` on `subject` once `delay` is over
+    pub async fn delay(&self, subject: &str, payload: Vec<u8>, delay: Duration) -> Result<()> {
+        if subject.is_empty() {
+            return Err(RuntimeError::Config(message!("A delayed message needs a subject")));
+        }
+        match &self.backend {
+            Backend::Memory { scheduler, next } => {
+                let key = format!("delayed.{}", next.fetch_add(1, Ordering::Relaxed));
+                scheduler.schedule(&key, delay, subject, payload)
+            }
+            #[cfg(feature = "nats")]
+            Backend::Nats { jetstream, .. } => {
+                self.stream().await?;
+                let mut headers = async_nats::HeaderMap::new();
+                headers.insert(SUBJECT_HEADER, subject);
+                headers.insert(DUE_HEADER, (now_ms() + delay.as_millis() as u64).to_string().as_str());
+                jetstream
+                    .publish_with_headers(format!("{}.{}", SUBJECT_PREFIX, subject), headers, payload.into())
+                    .await
+                    .map_err(|e| RuntimeError::Messaging(message!("Failed to delay a message for {}: {}", subject, e)))?
+                    .await
+                    .map_err(|e| RuntimeError::Messaging(message!("Delayed message for {} not stored: {}", subject, e)))?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Publish the delayed messages as they fall due; timers need no loop
+    pub async fn run(&self) -> Result<()> {
+        match &self.backend {
+            Backend::Memory { .. } => std::future::pending().await,
+            #[cfg(feature = "nats")]
+            Backend::Nats { messaging, .. } => {
+                use async_nats::jetstream::{consumer, AckKind};
+                use tokio_stream::StreamExt;
+
+                let stream = self.stream().await?;
+                let consumer = stream
+                    .get_or_create_consumer(
+                        CONSUMER,
+                        consumer::pull::Config { durable_name: Some(CONSUMER.to_string()), ..Default::default() },
+                    )
+                    .await
+                    .map_err(|e| RuntimeError::Messaging(message!("Failed to consume delayed messages: {}", e)))?;
+                let mut messages = consumer
+                    .messages()
+                    .await
+                    .map_err(|e| RuntimeError::Messaging(message!("Failed to consume delayed messages: {}", e)))?;
+
+                while let Some(message) = messages.next().await {
+                    let message = match message {
+                        Ok(message) => message,
+                        Err(e) => {
+                            tracing::warn!("Failed to read a delayed message: {}", e);
+                            continue;
+                        }
+                    };
+                    let header = |name| message.headers.as_ref().and_then(|headers| headers.get(name)).map(|value| value.to_string());
+                    let (Some(subject), Some(due)) = (header(SUBJECT_HEADER), header(DUE_HEADER).and_then(|due| due.parse().ok())) else {
+                        tracing::warn!("Dropping delayed message {} without a subject or due time", message.subject);
+                        let _ = message.ack_with(AckKind::Term).await;
+                        continue;
+                    };
+                    // Messages not due yet come back once they are
+                    let ack = match remaining(due, now_ms()) {
+                        Some(wait) => AckKind::Nak(Some(wait)),
+                        None => match messaging.publish(&subject, &message.payload, None).await {
+                            Ok(()) => AckKind::Ack,
+                            Err(e) => {
+                                tracing::warn!("Failed to publish a delayed message on {}, retrying: {}", subject, e);
+                                AckKind::Nak(Some(Duration::from_secs(1)))
+                            }
+                        },
+                    };
+                    if let Err(e) = message.ack_with(ack).await {
+                        tracing::warn!("Failed to acknowledge a delayed message for {}: {}", subject, e);
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The stream of the delayed messages, created if it doesn't exist yet
+    #[cfg(feature = "nats")]
+    async fn stream(&self) -> Result<async_nats::jetstream::stream::Stream> {
+        let Backend::Nats { jetstream, .. } = &self.backend else {
+            return Err(RuntimeError::Messaging(message!("Delayed messages are not kept in NATS")));
+        };
+        jetstream
+            .get_or_create_stream(async_nats::jetstream::stream::Config {
+                name: STREAM.to_string(),
+                subjects: vec![format!("{}.>", SUBJECT_PREFIX)],
+                max_age: MAX_AGE,
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| RuntimeError::Messaging(message!("Failed to open stream {}: {}", STREAM, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_wait_until_due() {
+        assert_eq!(remaining(10_000, 4_000), Some(Duration::from_secs(6)));
+        assert_eq!(remaining(10_000, 10_000), None);
+        assert_eq!(remaining(10_000, 12_000), None);
+    }
+
+    #[tokio::test]
+    async fn test_delays_need_messaging_without_nats() {
+        let delayer = Delayer::new(None, Scheduler::new(None));
+        assert!(delayer.delay("orders", b"{}".to_vec(), Duration::from_secs(600)).await.is_err());
+        assert!(delayer.delay("", b"{}".to_vec(), Duration::from_secs(600)).await.is_err());
+    }
+}
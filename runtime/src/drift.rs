@@ -0,0 +1,253 @@
+//! Detection of agents running a stale workflow definition
+//!
+//! The compiler embeds a hash of the workflow definition into every agent it
+//! generates. Agents announce it on the workflow's registration subject when
+//! they start, and the runtime compares the announcements: an agent left over
+//! from a previous deploy reports a different hash than the rest of the
+//! workflow. Drift is counted in [`DriftMonitor::metrics`] and published as a
+//! [`DriftEvent`] on the workflow's drift subject.
+
+use crate::error::Result;
+use crate::messaging::{Manager as MessagingManager, MessageHandler};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Workflow the runtime's agent belongs to
+pub const WORKFLOW_ENV: &str = "KUMEO_WORKFLOW";
+/// Hash of the workflow definition the runtime was deployed with
+pub const WORKFLOW_HASH_ENV: &str = "KUMEO_WORKFLOW_HASH";
+
+/// Subject agents of a workflow announce themselves on
+pub fn registration_subject(workflow: &str) -> String {
+    format!("kumeo.control.{}.registered", workflow)
+}
+
+/// Subject drift events of a workflow are published on
+pub fn drift_subject(workflow: &str) -> String {
+    format!("kumeo.control.{}.drift", workflow)
+}
+
+/// Announcement an agent publishes when it starts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Registration {
+    /// Workflow the agent was compiled from
+    pub workflow: String,
+    /// Identifier of the agent
+    pub agent_id: String,
+    /// Hash of the workflow definition the agent was compiled from
+    pub workflow_hash: String,
+}
+
+/// Agents of a workflow that disagree with the expected definition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DriftEvent {
+    /// Workflow the agents belong to
+    pub workflow: String,
+    /// Hash the agents are expected to report
+    pub expected_hash: String,
+    /// Agents reporting another hash, with the hash they report
+    pub stale_agents: BTreeMap<String, String>,
+}
+
+/// Latest registration of an agent
+struct Registered {
+    workflow_hash: String,
+    order: u64,
+}
+
+/// Registrations of a workflow and the drift last reported for it
+#[derive(Default)]
+struct WorkflowState {
+    agents: BTreeMap<String, Registered>,
+    reported: Option<DriftEvent>,
+}
+
+/// Compares the workflow hashes agents register with
+pub struct DriftMonitor {
+    expected_hash: Option<String>,
+    workflows: Mutex<HashMap<String, WorkflowState>>,
+    registrations: AtomicU64,
+    events: AtomicU64,
+}
+
+impl DriftMonitor {
+    /// Creates a monitor; without an expected hash the one most agents report is expected
+    pub fn new(expected_hash: Option<String>) -> Self {
+        Self {
+            expected_hash,
+            workflows: Mutex::new(HashMap::new()),
+            registrations: AtomicU64::new(0),
+            events: AtomicU64::new(0),
+        }
+    }
+
+    /// Creates a monitor expecting the hash in `KUMEO_WORKFLOW_HASH`, if set
+    pub fn from_env() -> Self {
+        Self::new(std::env::var(WORKFLOW_HASH_ENV).ok().filter(|hash| !hash.is_empty()))
+    }
+
+    /// Records a registration
+    ///
+    /// Returns an event when the stale agents of the workflow changed, so a
+    /// drift is reported once rather than on every registration. An empty
+    /// `stale_agents` means a previously reported drift is resolved.
+    pub fn register(&self, registration: Registration) -> Option<DriftEvent> {
+        let order = self.registrations.fetch_add(1, Ordering::Relaxed);
+        let mut workflows = self.workflows.lock().unwrap_or_else(|e| e.into_inner());
+        let state = workflows.entry(registration.workflow.clone()).or_default();
+        state.agents.insert(
+            registration.agent_id,
+            Registered { workflow_hash: registration.workflow_hash, order },
+        );
+
+        let expected_hash = self.expected_hash.clone().or_else(|| majority_hash(&state.agents))?;
+        let stale_agents: BTreeMap<String, String> = state
+            .agents
+            .iter()
+            .filter(|(_, agent)| agent.workflow_hash != expected_hash)
+            .map(|(id, agent)| (id.clone(), agent.workflow_hash.clone()))
+            .collect();
+
+        let unchanged = match &state.reported {
+            Some(reported) => reported.stale_agents == stale_agents && reported.expected_hash == expected_hash,
+            None => stale_agents.is_empty(),
+        };
+        if unchanged {
+            return None;
+        }
+
+        let event = DriftEvent {
+            workflow: registration.workflow,
+            expected_hash,
+            stale_agents,
+        };
+        self.events.fetch_add(1, Ordering::Relaxed);
+        state.reported = (!event.stale_agents.is_empty()).then(|| event.clone());
+        Some(event)
+    }
+
+    /// Metrics in the Prometheus text format
+    pub fn metrics(&self) -> String {
+        let workflows = self.workflows.lock().unwrap_or_else(|e| e.into_inner());
+        let mut names: Vec<_> = workflows.keys().collect();
+        names.sort();
+
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP kumeo_registrations_total Agent registrations received");
+        let _ = writeln!(out, "# TYPE kumeo_registrations_total counter");
+        let _ = writeln!(out, "kumeo_registrations_total {}", self.registrations.load(Ordering::Relaxed));
+        let _ = writeln!(out, "# HELP kumeo_drift_events_total Changes in the set of stale agents");
+        let _ = writeln!(out, "# TYPE kumeo_drift_events_total counter");
+        let _ = writeln!(out, "kumeo_drift_events_total {}", self.events.load(Ordering::Relaxed));
+        let _ = writeln!(out, "# HELP kumeo_stale_agents Agents reporting another workflow hash than expected");
+        let _ = writeln!(out, "# TYPE kumeo_stale_agents gauge");
+        for name in names {
+            let stale = workflows[name].reported.as_ref().map_or(0, |event| event.stale_agents.len());
+            let _ = writeln!(out, "kumeo_stale_agents{{workflow=\"{}\"}} {}", name, stale);
+        }
+        out
+    }
+}
+
+/// Hash most agents report; ties go to the most recent registration, as
+/// newer deploys register last
+fn majority_hash(agents: &BTreeMap<String, Registered>) -> Option<String> {
+    let mut counts: HashMap<&str, (usize, u64)> = HashMap::new();
+    for agent in agents.values() {
+        let entry = counts.entry(&agent.workflow_hash).or_default();
+        entry.0 += 1;
+        entry.1 = entry.1.max(agent.order);
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(_, count)| count)
+        .map(|(hash, _)| hash.to_string())
+}
+
+/// Feeds the registrations of a workflow to a monitor and publishes drift events
+pub struct RegistrationHandler {
+    monitor: std::sync::Arc<DriftMonitor>,
+    messaging: MessagingManager,
+}
+
+impl RegistrationHandler {
+    /// Creates a handler publishing drift events through `messaging`
+    pub fn new(monitor: std::sync::Arc<DriftMonitor>, messaging: MessagingManager) -> Self {
+        Self { monitor, messaging }
+    }
+}
+
+#[async_trait]
+impl MessageHandler for RegistrationHandler {
+    async fn handle_message(&self, _subject: &str, payload: &[u8], _headers: Option<&HashMap<String, String>>) -> Result<()> {
+        let registration: Registration = serde_json::from_slice(payload)?;
+        let Some(event) = self.monitor.register(registration) else {
+            return Ok(());
+        };
+
+        if event.stale_agents.is_empty() {
+            tracing::info!("Workflow {} no longer has stale agents", event.workflow);
+        } else {
+            tracing::warn!(
+                "Workflow {} has agents compiled from another definition than {}: {:?}",
+                event.workflow,
+                event.expected_hash,
+                event.stale_agents
+            );
+        }
+        self.messaging
+            .publish(&drift_subject(&event.workflow), &serde_json::to_vec(&event)?, None)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration(agent_id: &str, workflow_hash: &str) -> Registration {
+        Registration {
+            workflow: "scoring".to_string(),
+            agent_id: agent_id.to_string(),
+            workflow_hash: workflow_hash.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_stale_agent_is_reported_once() {
+        let monitor = DriftMonitor::new(Some("new".to_string()));
+        assert_eq!(monitor.register(registration("fetch", "new")), None);
+
+        let event = monitor.register(registration("score", "old")).unwrap();
+        assert_eq!(event.expected_hash, "new");
+        assert_eq!(event.stale_agents, BTreeMap::from([("score".to_string(), "old".to_string())]));
+        // The same drift is not reported again
+        assert_eq!(monitor.register(registration("score", "old")), None);
+        assert!(monitor.metrics().contains("kumeo_stale_agents{workflow=\"scoring\"} 1"));
+
+        // Redeploying the stale agent resolves the drift
+        let event = monitor.register(registration("score", "new")).unwrap();
+        assert!(event.stale_agents.is_empty());
+        assert!(monitor.metrics().contains("kumeo_stale_agents{workflow=\"scoring\"} 0"));
+        assert!(monitor.metrics().contains("kumeo_drift_events_total 2"));
+    }
+
+    #[test]
+    fn test_majority_hash_is_expected_without_a_configured_hash() {
+        let monitor = DriftMonitor::new(None);
+        assert_eq!(monitor.register(registration("fetch", "a")), None);
+
+        // On a tie the most recent registration wins
+        let event = monitor.register(registration("score", "b")).unwrap();
+        assert_eq!(event.expected_hash, "b");
+        assert_eq!(event.stale_agents.keys().collect::<Vec<_>>(), ["fetch"]);
+
+        let event = monitor.register(registration("notify", "a")).unwrap();
+        assert_eq!(event.expected_hash, "a");
+        assert_eq!(event.stale_agents.keys().collect::<Vec<_>>(), ["score"]);
+    }
+}
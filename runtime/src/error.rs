@@ -10,39 +10,39 @@ pub type Result<T> = std::result::Result<T, RuntimeError>;
 #[derive(Debug, Error)]
 pub enum RuntimeError {
     /// I/O error
-    #[error("I/O error: {0}")]
+    #[error("{}", crate::message!("I/O error: {}", .0))]
     Io(#[from] std::io::Error),
     
     /// Serialization/deserialization error
-    #[error("Serialization error: {0}")]
+    #[error("{}", crate::message!("Serialization error: {}", .0))]
     Serialization(String),
     
     /// Resource not found error
-    #[error("Resource not found: {0}")]
+    #[error("{}", crate::message!("Resource not found: {}", .0))]
     NotFound(String),
     
     /// Permission error
-    #[error("Permission denied: {0}")]
+    #[error("{}", crate::message!("Permission denied: {}", .0))]
     PermissionDenied(String),
     
     /// Configuration error
-    #[error("Configuration error: {0}")]
+    #[error("{}", crate::message!("Configuration error: {}", .0))]
     Config(String),
     
     /// Messaging error
-    #[error("Messaging error: {0}")]
+    #[error("{}", crate::message!("Messaging error: {}", .0))]
     Messaging(String),
     
     /// Error de recurso
-    #[error("Resource error: {0}")]
+    #[error("{}", crate::message!("Resource error: {}", .0))]
     Resource(String),
     
     /// Timeout error
-    #[error("Timeout: {0}")]
+    #[error("{}", crate::message!("Timeout: {}", .0))]
     Timeout(String),
     
     /// Generic error
-    #[error("Error: {0}")]
+    #[error("{}", crate::message!("Error: {}", .0))]
     Other(String),
 }
 
@@ -0,0 +1,173 @@
+//! Message history of the debug mode
+//!
+//! With `KUMEO_DEBUG_SUBJECTS` set to a comma-separated list of subjects,
+//! which may use the NATS wildcards `*` and `>`, the runtime appends every
+//! message it receives or publishes on one of them to the local store in
+//! `KUMEO_DEBUG_STORE`, `.kumeo/history` by default: a JSON Lines file per
+//! subject, with the time, direction, headers and decoded payload of each
+//! message. `kumeo debug` steps through the store. Without the variable
+//! nothing is recorded.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Subjects whose messages are recorded
+pub const SUBJECTS_ENV: &str = "KUMEO_DEBUG_SUBJECTS";
+
+/// Directory of the store
+pub const STORE_ENV: &str = "KUMEO_DEBUG_STORE";
+
+/// Agent the runtime serves, recorded with its messages
+pub const AGENT_ENV: &str = "KUMEO_AGENT_ID";
+
+/// Directory of the store unless `KUMEO_DEBUG_STORE` says otherwise
+pub const DEFAULT_STORE: &str = ".kumeo/history";
+
+/// Whether a message was received or published by the agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Received from the broker
+    In,
+    /// Published to the broker
+    Out,
+}
+
+/// A message of the store, as a line of its subject's file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// When it was received or published, in epoch milliseconds
+    pub time: u64,
+    /// Agent that received or published it, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    /// Whether it was received or published
+    pub direction: Direction,
+    /// Subject, without the channel prefix
+    pub subject: String,
+    /// Headers
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Payload: its JSON, or its text when it isn't JSON
+    pub payload: serde_json::Value,
+}
+
+/// Whether a subject matches a pattern with the NATS wildcards
+///
+/// `*` matches one token and a trailing `>` one or more.
+pub fn matches(pattern: &str, subject: &str) -> bool {
+    let mut tokens = subject.split('.');
+    for expected in pattern.split('.') {
+        match (expected, tokens.next()) {
+            (">", Some(_)) => return true,
+            ("*", Some(_)) => {}
+            (expected, Some(token)) if expected == token => {}
+            _ => return false,
+        }
+    }
+    tokens.next().is_none()
+}
+
+/// File of the store holding the messages of a subject
+pub fn subject_file(store: &Path, subject: &str) -> PathBuf {
+    store.join(format!("{}.jsonl", subject))
+}
+
+/// Records the messages of the selected subjects
+pub struct HistoryRecorder {
+    subjects: Vec<String>,
+    store: PathBuf,
+    agent: Option<String>,
+    lock: Mutex<()>,
+}
+
+impl HistoryRecorder {
+    /// Creates a recorder of the messages of `subjects` into `store`
+    pub fn new(subjects: Vec<String>, store: PathBuf, agent: Option<String>) -> Self {
+        Self { subjects, store, agent, lock: Mutex::new(()) }
+    }
+
+    /// Creates a recorder from `KUMEO_DEBUG_SUBJECTS`, `KUMEO_DEBUG_STORE` and `KUMEO_AGENT_ID`
+    pub fn from_env() -> Self {
+        let var = |name| std::env::var(name).ok().filter(|value: &String| !value.trim().is_empty());
+        let subjects = var(SUBJECTS_ENV)
+            .map(|subjects| subjects.split(',').map(|subject| subject.trim().to_string()).filter(|subject| !subject.is_empty()).collect())
+            .unwrap_or_default();
+        let store = var(STORE_ENV).unwrap_or_else(|| DEFAULT_STORE.to_string());
+        Self::new(subjects, PathBuf::from(store), var(AGENT_ENV))
+    }
+
+    /// Whether the messages of a subject are recorded
+    pub fn records(&self, subject: &str) -> bool {
+        self.subjects.iter().any(|pattern| matches(pattern, subject))
+    }
+
+    /// Appends a message to the store, if its subject is recorded
+    ///
+    /// Debugging never stops the agent: failures to write are logged.
+    pub fn record(&self, direction: Direction, subject: &str, headers: Option<&HashMap<String, String>>, payload: &[u8], time: u64) {
+        if !self.records(subject) {
+            return;
+        }
+        let payload = serde_json::from_slice(payload)
+            .unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(payload).into_owned()));
+        let record = Record {
+            time,
+            agent: self.agent.clone(),
+            direction,
+            subject: subject.to_string(),
+            headers: headers.map(|headers| headers.iter().map(|(k, v)| (k.clone(), v.clone())).collect()).unwrap_or_default(),
+            payload,
+        };
+        if let Err(e) = self.append(&record) {
+            tracing::warn!("Failed to record a message on {} in {}: {}", subject, self.store.display(), e);
+        }
+    }
+
+    fn append(&self, record: &Record) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        std::fs::create_dir_all(&self.store)?;
+        let mut file = OpenOptions::new().create(true).append(true).open(subject_file(&self.store, &record.subject))?;
+        file.write_all(&line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subjects_match_with_wildcards() {
+        assert!(matches("payments", "payments"));
+        assert!(!matches("payments", "payments.scored"));
+        assert!(matches("payments.*", "payments.scored"));
+        assert!(!matches("payments.*", "payments.scored.eu"));
+        assert!(matches("payments.>", "payments.scored.eu"));
+        assert!(!matches("payments.>", "payments"));
+    }
+
+    #[test]
+    fn test_messages_of_selected_subjects_are_appended() {
+        let store = tempfile::tempdir().unwrap();
+        let recorder = HistoryRecorder::new(vec!["payments.>".to_string()], store.path().to_path_buf(), Some("score".to_string()));
+        let headers = HashMap::from([("Kumeo-Ingest-Time".to_string(), "1000".to_string())]);
+
+        recorder.record(Direction::In, "payments.raw", Some(&headers), br#"{"amount": 10}"#, 1_000);
+        recorder.record(Direction::Out, "payments.raw", None, b"not json", 1_001);
+        recorder.record(Direction::Out, "orders", None, b"{}", 1_002);
+
+        let contents = std::fs::read_to_string(subject_file(store.path(), "payments.raw")).unwrap();
+        let records: Vec<Record> = contents.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].payload, serde_json::json!({"amount": 10}));
+        assert_eq!(records[0].agent.as_deref(), Some("score"));
+        assert_eq!(records[1].payload, serde_json::json!("not json"));
+        assert!(!subject_file(store.path(), "orders").exists());
+    }
+}
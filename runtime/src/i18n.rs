@@ -0,0 +1,278 @@
+//! Localization of the runtime errors
+//!
+//! The runtime writes its errors in English, with [`message!`](crate::message)
+//! where they are raised: the template is looked up in the catalog of this
+//! module and filled with the arguments, so an agent deployed with
+//! `KUMEO_LOCALE=es` reports them in Spanish. Templates missing from the
+//! catalog are kept as written.
+
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Variable with the locale of the errors, as `en` or `es`
+pub const LOCALE_ENV: &str = "KUMEO_LOCALE";
+
+/// A language the errors are rendered in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Locale {
+    /// English, the language the errors are written in
+    #[default]
+    En,
+    /// Spanish
+    Es,
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    /// Parses a locale such as `en`, `es-ES` or `en_US.UTF-8` by its language
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.split(['-', '_', '.']).next().unwrap_or_default().to_ascii_lowercase().as_str() {
+            "en" => Ok(Locale::En),
+            "es" => Ok(Locale::Es),
+            _ => Err(format!("Unsupported locale {}, expected en or es", s)),
+        }
+    }
+}
+
+/// The locale of the process, read once from [`LOCALE_ENV`]; English when unset or unknown
+pub fn locale() -> Locale {
+    static LOCALE: OnceLock<Locale> = OnceLock::new();
+    *LOCALE.get_or_init(|| {
+        std::env::var(LOCALE_ENV).ok().and_then(|locale| locale.parse().ok()).unwrap_or_default()
+    })
+}
+
+/// A template filled with its arguments in the locale of the process
+#[doc(hidden)]
+pub fn render(template: &'static str, arguments: &[String]) -> String {
+    localize(template, locale(), arguments)
+}
+
+/// A template filled with its arguments in a locale
+pub fn localize(template: &str, locale: Locale, arguments: &[String]) -> String {
+    let text = match locale {
+        Locale::En => template,
+        Locale::Es => MESSAGES.iter().find(|(en, _)| *en == template).map_or(template, |(_, es)| es),
+    };
+    let mut arguments = arguments.iter();
+    let mut filled = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('}')) => {
+                chars.next();
+                filled.push_str(arguments.next().map_or("", String::as_str));
+            }
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                filled.push(c);
+            }
+            _ => filled.push(c),
+        }
+    }
+    filled
+}
+
+/// Every template of the catalog, in English and Spanish
+pub fn templates() -> impl Iterator<Item = (&'static str, &'static str)> {
+    MESSAGES.iter().copied()
+}
+
+/// An error message built from a template of the catalog and its arguments
+///
+/// Takes the arguments of `format!`, with only `{}` in the template, one for
+/// each argument, which is checked at compile time. The arguments are
+/// rendered with their `Display`.
+#[macro_export]
+macro_rules! message {
+    ($template:literal $(, $argument:expr)* $(,)?) => {{
+        const _: () = assert!(
+            $crate::i18n::placeholders($template) == 0 $(+ $crate::i18n::one(stringify!($argument)))*,
+            "the template of a message needs one `{{}}` for each of its arguments"
+        );
+        $crate::i18n::render($template, &[$(::std::string::ToString::to_string(&$argument)),*])
+    }};
+}
+
+/// The `{}` of a template, which can have no other placeholders
+#[doc(hidden)]
+pub const fn placeholders(template: &str) -> usize {
+    let bytes = template.as_bytes();
+    let (mut count, mut position) = (0, 0);
+    while position < bytes.len() {
+        let next = if position + 1 < bytes.len() { bytes[position + 1] } else { 0 };
+        match (bytes[position], next) {
+            (b'{', b'}') => count += 1,
+            (b'{', b'{') | (b'}', b'}') => {}
+            (b'{', _) | (b'}', _) => panic!("the template of a message can only have `{{}}` placeholders"),
+            _ => {
+                position += 1;
+                continue;
+            }
+        }
+        position += 2;
+    }
+    count
+}
+
+/// One, for each argument of a message counted by [`message!`](crate::message)
+#[doc(hidden)]
+pub const fn one(_: &str) -> usize {
+    1
+}
+
+/// The templates of the runtime errors, in English and Spanish
+const MESSAGES: &[(&str, &str)] = &[
+    // Kinds of errors
+    ("I/O error: {}", "Error de E/S: {}"),
+    ("Serialization error: {}", "Error de serialización: {}"),
+    ("Resource not found: {}", "Recurso no encontrado: {}"),
+    ("Permission denied: {}", "Permiso denegado: {}"),
+    ("Configuration error: {}", "Error de configuración: {}"),
+    ("Messaging error: {}", "Error de mensajería: {}"),
+    ("Resource error: {}", "Error de recurso: {}"),
+    ("Timeout: {}", "Tiempo agotado: {}"),
+    ("Error: {}", "Error: {}"),
+    // Configuration
+    ("Invalid KUMEO_FETCH_SCHEME_LIMITS: {}", "KUMEO_FETCH_SCHEME_LIMITS inválido: {}"),
+    ("Invalid mirror rule '{}': {}", "Regla de réplica '{}' inválida: {}"),
+    ("expected FROM=TO", "se esperaba ORIGEN=DESTINO"),
+    ("'*' is only allowed at the end", "'*' solo se permite al final"),
+    ("both sides must end in '*' or neither", "ambos lados deben terminar en '*' o ninguno"),
+    ("Environment variable {} is not set", "La variable de entorno {} no está definida"),
+    ("Invalid {}: {}", "{} inválido: {}"),
+    // Messaging
+    ("Failed to connect to NATS: {}", "No se pudo conectar a NATS: {}"),
+    ("NATS support not compiled in", "El soporte de NATS no está compilado"),
+    ("NATS client not initialized", "El cliente de NATS no está inicializado"),
+    ("Failed to publish message: {}", "No se pudo publicar el mensaje: {}"),
+    ("Failed to subscribe: {}", "No se pudo suscribir: {}"),
+    ("MQTT broker not configured (set KUMEO_MQTT_URL)", "Broker MQTT no configurado (defina KUMEO_MQTT_URL)"),
+    ("Failed to flush nacks: {}", "No se pudieron enviar los nacks: {}"),
+    ("Failed to publish MQTT message: {}", "No se pudo publicar el mensaje MQTT: {}"),
+    ("Invalid MQTT topic filter: {}", "Filtro de tema MQTT inválido: {}"),
+    ("Unsupported MQTT URL: {}", "URL de MQTT no soportada: {}"),
+    ("Invalid MQTT port in {}", "Puerto MQTT inválido en {}"),
+    ("Failed to watch {}: {}", "No se pudo vigilar {}: {}"),
+    ("{} messages failed during the batch", "{} mensajes fallaron durante el lote"),
+    // State, timers and delayed messages
+    ("Failed to read state {}: {}", "No se pudo leer el estado {}: {}"),
+    ("State {} needs a TTL", "El estado {} necesita un TTL"),
+    ("Failed to write state {}: {}", "No se pudo escribir el estado {}: {}"),
+    ("State is not kept in NATS", "El estado no se guarda en NATS"),
+    ("Failed to create state bucket {}: {}", "No se pudo crear el bucket de estado {}: {}"),
+    ("Timer {} needs messaging to publish on {}", "El temporizador {} necesita mensajería para publicar en {}"),
+    ("A delayed message needs a subject", "Un mensaje diferido necesita un subject"),
+    ("Failed to delay a message for {}: {}", "No se pudo diferir un mensaje para {}: {}"),
+    ("Delayed message for {} not stored: {}", "El mensaje diferido para {} no se guardó: {}"),
+    ("Failed to consume delayed messages: {}", "No se pudieron consumir los mensajes diferidos: {}"),
+    ("Delayed messages are not kept in NATS", "Los mensajes diferidos no se guardan en NATS"),
+    ("Failed to open stream {}: {}", "No se pudo abrir el stream {}: {}"),
+    // Encodings and schemas
+    ("Unknown type {} of field '{}'", "Tipo {} desconocido del campo '{}'"),
+    ("The {} encoding needs the schema of the topic", "La codificación {} necesita el esquema del tema"),
+    ("expected a JSON object, found {}", "se esperaba un objeto JSON, se encontró {}"),
+    ("unexpected wire type {} for field {}", "tipo de cable {} inesperado para el campo {}"),
+    ("missing required field '{}'", "falta el campo obligatorio '{}'"),
+    ("negative length of field '{}'", "longitud negativa del campo '{}'"),
+    ("{} bytes left after the record", "{} bytes sobrantes tras el registro"),
+    ("Unknown encoding in {}: {}", "Codificación desconocida en {}: {}"),
+    ("{} is set without {}", "{} está definida sin {}"),
+    ("No schema to read {} messages on {}", "No hay esquema para leer mensajes {} en {}"),
+    ("truncated message", "mensaje truncado"),
+    ("varint too long", "varint demasiado largo"),
+    ("field '{}' must be {}, found {}", "el campo '{}' debe ser {}, se encontró {}"),
+    ("a number", "un número"),
+    ("an integer", "un entero"),
+    ("a boolean", "un booleano"),
+    ("an object", "un objeto"),
+    ("an array", "un array"),
+    ("a string", "una cadena"),
+    ("the message is not a JSON object", "el mensaje no es un objeto JSON"),
+    ("field '{}' is missing", "falta el campo '{}'"),
+    ("field '{}' should be of type {}, found {}", "el campo '{}' debería ser de tipo {}, se encontró {}"),
+    // Resources
+    ("Archive member '{}'", "Miembro '{}' del archivo"),
+    ("Invalid zip archive: {}", "Archivo zip inválido: {}"),
+    ("Invalid tar.gz archive: {}", "Archivo tar.gz inválido: {}"),
+    (
+        "Checksum mismatch for {}: expected sha256 {}, got {}",
+        "El checksum de {} no coincide: se esperaba sha256 {}, se obtuvo {}",
+    ),
+    ("Pinned copy of {} is unreadable: {}", "La copia fijada de {} no se puede leer: {}"),
+    ("Listing is not supported for {}", "No se puede listar {}"),
+    ("Invalid base directory: {}", "Directorio base inválido: {}"),
+    (
+        "Signed resources are required but no trusted keys are configured",
+        "Se exigen recursos firmados pero no hay claves de confianza configuradas",
+    ),
+    ("Scheme '{}' is built in", "El esquema '{}' ya viene incorporado"),
+    ("Invalid URI: {}", "URI inválida: {}"),
+    ("Preload task failed: {}", "La tarea de precarga falló: {}"),
+    ("Unsupported scheme for writing: {}", "Esquema no soportado para escribir: {}"),
+    ("Unsupported scheme: {}", "Esquema no soportado: {}"),
+    ("Refusing unsigned resource {}", "Se rechaza el recurso sin firma {}"),
+    ("Not an archive: {}", "No es un archivo comprimido: {}"),
+    ("Archive extraction panicked: {}", "La extracción del archivo entró en pánico: {}"),
+    ("HTTP request failed: {}", "La petición HTTP falló: {}"),
+    ("HTTP error: {}", "Error HTTP: {}"),
+    ("Failed to read response: {}", "No se pudo leer la respuesta: {}"),
+    ("Upload in progress", "Subida en curso"),
+    ("Watch interval of {} must not be zero", "El intervalo de vigilancia de {} no puede ser cero"),
+    ("Invalid cosign public key: {}", "Clave pública de cosign inválida: {}"),
+    ("Invalid minisign public key: {}", "Clave pública de minisign inválida: {}"),
+    ("Invalid signature for {}: {}", "Firma inválida de {}: {}"),
+    ("{} is not valid UTF-8: {}", "{} no es UTF-8 válido: {}"),
+    ("Invalid JSON in {}: {}", "JSON inválido en {}: {}"),
+    ("Invalid YAML in {}: {}", "YAML inválido en {}: {}"),
+    ("Invalid TOML in {}: {}", "TOML inválido en {}: {}"),
+    ("Invalid image in {}: {}", "Imagen inválida en {}: {}"),
+    ("Cannot decode {} resource {}", "No se puede decodificar el recurso {} {}"),
+    ("{} is a {} resource, not {}", "{} es un recurso {}, no {}"),
+    // Server
+    ("Server error: {}", "Error del servidor: {}"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates_are_localized() {
+        let arguments = ["orders".to_string(), "timeout".to_string()];
+        assert_eq!(localize("Failed to subscribe: {}", Locale::En, &arguments[1..]), "Failed to subscribe: timeout");
+        assert_eq!(localize("Failed to read state {}: {}", Locale::Es, &arguments), "No se pudo leer el estado orders: timeout");
+        assert_eq!(localize("Unknown template {}", Locale::Es, &arguments[..1]), "Unknown template orders");
+        assert_eq!("es_ES.UTF-8".parse(), Ok(Locale::Es));
+        assert!("fr".parse::<Locale>().is_err());
+    }
+
+    #[test]
+    fn test_every_message_has_a_template() {
+        let templates: Vec<_> = templates().collect();
+        for (en, es) in &templates {
+            assert_eq!(placeholders(en), placeholders(es), "{} / {}", en, es);
+        }
+
+        let mut used = Vec::new();
+        let mut pending = vec![std::path::PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/src"))];
+        while let Some(path) = pending.pop() {
+            if path.is_dir() {
+                pending.extend(std::fs::read_dir(&path).unwrap().map(|entry| entry.unwrap().path()));
+            } else if path.extension().is_some_and(|extension| extension == "rs") && !path.ends_with("i18n.rs") {
+                let source = std::fs::read_to_string(&path).unwrap();
+                for call in source.split("message!(").skip(1) {
+                    let template = call.trim_start().strip_prefix('"').and_then(|rest| rest.split('"').next());
+                    if let Some(template) = template {
+                        assert!(templates.iter().any(|(en, _)| *en == template), "{} has no template: {}", path.display(), template);
+                        used.push(template.to_string());
+                    }
+                }
+            }
+        }
+        for (en, _) in &templates {
+            assert!(used.iter().any(|template| template == en), "Unused template: {}", en);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! End-to-end latency of the messages of a workflow
+//!
+//! A message is stamped with its ingest time in the [`INGEST_HEADER`] when
+//! the workflow first receives it from its source: the time JetStream stored
+//! it when it comes from a stream, or its arrival otherwise. Agents keep the
+//! header on the messages they derive from it, so the time a message waited
+//! in queues behind slower agents counts as well as the time spent in them.
+//! When a message with the header is published on the workflow's output
+//! subject, the time since ingest is observed in a histogram per workflow,
+//! exported by [`LatencyTracker::metrics`] as `kumeo_e2e_latency_seconds`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Header carrying the time a message entered the workflow, in epoch milliseconds
+pub const INGEST_HEADER: &str = "Kumeo-Ingest-Time";
+
+/// Subject the workflow publishes its results on, whose messages end their trip
+pub const OUTPUT_TOPIC_ENV: &str = "KUMEO_OUTPUT_TOPIC";
+
+/// Upper bounds of the histogram buckets, in seconds
+pub const BUCKETS: [f64; 14] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0];
+
+/// Stamps the ingest time of a message, unless an earlier agent already did
+pub fn stamp(headers: &mut HashMap<String, String>, ingested_at: u64) {
+    headers.entry(INGEST_HEADER.to_string()).or_insert_with(|| ingested_at.to_string());
+}
+
+/// Reads the ingest time of a message, in epoch milliseconds
+pub fn ingest_time(headers: &HashMap<String, String>) -> Option<u64> {
+    headers.get(INGEST_HEADER).and_then(|value| value.trim().parse().ok())
+}
+
+/// Current time in epoch milliseconds
+pub fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// Latencies observed for a workflow
+struct Histogram {
+    /// Observations per bucket, not cumulative
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; BUCKETS.len()],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+/// Observes the end-to-end latency of the messages leaving a workflow
+pub struct LatencyTracker {
+    workflow: Option<String>,
+    output_topic: Option<String>,
+    workflows: Mutex<BTreeMap<String, Histogram>>,
+}
+
+impl LatencyTracker {
+    /// Creates a tracker observing the messages published on `output_topic`
+    ///
+    /// Without a workflow or an output subject nothing is observed, as the
+    /// agent does not end the trip of the workflow's messages.
+    pub fn new(workflow: Option<String>, output_topic: Option<String>) -> Self {
+        Self {
+            workflow,
+            output_topic,
+            workflows: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Creates a tracker for the workflow in `KUMEO_WORKFLOW` and the subject in `KUMEO_OUTPUT_TOPIC`
+    pub fn from_env() -> Self {
+        let var = |name| std::env::var(name).ok().filter(|value: &String| !value.is_empty());
+        Self::new(var(crate::drift::WORKFLOW_ENV), var(OUTPUT_TOPIC_ENV))
+    }
+
+    /// Observes a message published on `subject` at `now`, if it leaves the workflow
+    ///
+    /// Returns the latency observed. Ingest times in the future, from clocks
+    /// out of sync, count as no latency.
+    pub fn observe_publish(&self, subject: &str, headers: Option<&HashMap<String, String>>, now: u64) -> Option<Duration> {
+        let workflow = self.workflow.as_ref()?;
+        if self.output_topic.as_deref() != Some(subject) {
+            return None;
+        }
+        let latency = Duration::from_millis(now.saturating_sub(ingest_time(headers?)?));
+        self.workflows
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(workflow.clone())
+            .or_default()
+            .observe(latency.as_secs_f64());
+        Some(latency)
+    }
+
+    /// Metrics in the Prometheus text format
+    pub fn metrics(&self) -> String {
+        let workflows = self.workflows.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP kumeo_e2e_latency_seconds Time from ingest until a message leaves the workflow");
+        let _ = writeln!(out, "# TYPE kumeo_e2e_latency_seconds histogram");
+        for (name, histogram) in workflows.iter() {
+            let mut cumulative = 0;
+            for (bound, observed) in BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += observed;
+                let _ = writeln!(out, "kumeo_e2e_latency_seconds_bucket{{workflow=\"{}\",le=\"{}\"}} {}", name, bound, cumulative);
+            }
+            let _ = writeln!(out, "kumeo_e2e_latency_seconds_bucket{{workflow=\"{}\",le=\"+Inf\"}} {}", name, histogram.count);
+            let _ = writeln!(out, "kumeo_e2e_latency_seconds_sum{{workflow=\"{}\"}} {}", name, histogram.sum);
+            let _ = writeln!(out, "kumeo_e2e_latency_seconds_count{{workflow=\"{}\"}} {}", name, histogram.count);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ingest_time_is_stamped_once() {
+        let mut headers = HashMap::new();
+        stamp(&mut headers, 1_000);
+        // Later agents keep the time the workflow first received the message
+        stamp(&mut headers, 2_000);
+        assert_eq!(ingest_time(&headers), Some(1_000));
+    }
+
+    #[test]
+    fn test_latency_is_observed_on_the_output_subject() {
+        let tracker = LatencyTracker::new(Some("scoring".to_string()), Some("scores".to_string()));
+        let mut headers = HashMap::new();
+        stamp(&mut headers, 1_000);
+
+        // Intermediate subjects and messages without an ingest time are not observed
+        assert_eq!(tracker.observe_publish("features", Some(&headers), 1_300), None);
+        assert_eq!(tracker.observe_publish("scores", None, 1_300), None);
+        assert_eq!(tracker.observe_publish("scores", Some(&headers), 1_300), Some(Duration::from_millis(300)));
+        assert_eq!(tracker.observe_publish("scores", Some(&headers), 500), Some(Duration::ZERO));
+
+        let metrics = tracker.metrics();
+        assert!(metrics.contains("kumeo_e2e_latency_seconds_bucket{workflow=\"scoring\",le=\"0.25\"} 1"), "{}", metrics);
+        assert!(metrics.contains("kumeo_e2e_latency_seconds_bucket{workflow=\"scoring\",le=\"0.5\"} 2"), "{}", metrics);
+        assert!(metrics.contains("kumeo_e2e_latency_seconds_bucket{workflow=\"scoring\",le=\"+Inf\"} 2"), "{}", metrics);
+        assert!(metrics.contains("kumeo_e2e_latency_seconds_count{workflow=\"scoring\"} 2"), "{}", metrics);
+
+        let untracked = LatencyTracker::new(None, Some("scores".to_string()));
+        assert_eq!(untracked.observe_publish("scores", Some(&headers), 1_300), None);
+    }
+}
@@ -6,11 +6,23 @@
 #![warn(missing_docs)]
 #![warn(rustdoc::missing_crate_level_docs)]
 
+pub mod codec;
+pub mod condition;
 pub mod config;
+pub mod delay;
+pub mod drift;
 pub mod error;
+pub mod history;
+pub mod i18n;
+pub mod latency;
 pub mod resources;
+pub mod retry;
+pub mod schema;
 pub mod messaging;
 pub mod server;
+pub mod scheduler;
+pub mod state;
+pub mod status;
 
 // Re-export of the most common types
 pub use config::RuntimeConfig;
@@ -33,9 +45,94 @@ pub async fn init(config: RuntimeConfig) -> Result<()> {
         None
     };
     
+    // Preload the declared models; the ready file appears once they are pinned
+    let warm_up = resource_manager.warm_up();
+    
+    // Watch the registrations of the workflow for agents left over from an older deploy;
+    // batch agents skip it, their subscriptions end the Job once idle
+    let drift = std::sync::Arc::new(drift::DriftMonitor::from_env());
+    if let (Some(messaging), Ok(workflow)) = (&messaging, std::env::var(drift::WORKFLOW_ENV)) {
+        if config.messaging.as_ref().is_some_and(|messaging_config| messaging_config.batch.is_none()) {
+            let subscription = messaging::SubscriptionConfig {
+                subject: drift::registration_subject(&workflow),
+                queue_group: None,
+                timeout: None,
+            };
+            let handler = drift::RegistrationHandler::new(drift.clone(), messaging.clone());
+            messaging.subscribe(subscription, handler).await?;
+            
+            // Report the resource cache for the operator console
+            tokio::spawn(status::report_cache(resource_manager.clone(), messaging.clone(), workflow));
+        }
+    }
+    
+    // State of the agents, in JetStream when connected to NATS
+    let state = state::Manager::new(messaging.as_ref());
+    
+    // Timers of the agents, published through the same messaging
+    let scheduler = scheduler::Scheduler::new(messaging.clone());
+    
+    // Delayed messages, in JetStream when connected to NATS
+    let delayer = delay::Delayer::new(messaging.as_ref(), scheduler.clone());
+    
     // Start the server
-    let server = server::Server::new(config.socket_path, resource_manager, messaging);
-    server.run().await?;
+    let server = server::Server::new(config.socket_path, resource_manager, messaging.clone(), state, scheduler, delayer.clone(), drift);
+    
+    tokio::select! {
+        // A failed warm-up stops the runtime so the pod restarts instead of serving cold
+        result = async { tokio::try_join!(server.run(), warm_up, delayer.run()) } => { result?; }
+        _ = batch_input_exhausted(messaging.as_ref()) => {
+            // Batch Jobs finish their in-flight work and exit; failed
+            // messages make the Job fail so the chain stops
+            if let (Some(messaging), Some(messaging_config)) = (&messaging, &config.messaging) {
+                let deadline = std::time::Duration::from_secs(messaging_config.drain_timeout);
+                messaging.drain(deadline).await?;
+                let failed = messaging.failed_messages();
+                if failed > 0 {
+                    return Err(RuntimeError::Messaging(crate::message!("{} messages failed during the batch", failed)));
+                }
+                tracing::info!("Batch completed");
+            }
+        }
+        _ = shutdown_signal() => {
+            // Stop pulling, finish in-flight work and nack the rest
+            if let (Some(messaging), Some(messaging_config)) = (&messaging, &config.messaging) {
+                let deadline = std::time::Duration::from_secs(messaging_config.drain_timeout);
+                let nacked = messaging.drain(deadline).await?;
+                tracing::info!("Drain finished ({} messages nacked)", nacked);
+            }
+        }
+    }
     
     Ok(())
+}
+
+/// Completes once a batch agent has consumed its input
+async fn batch_input_exhausted(messaging: Option<&messaging::Manager>) {
+    match messaging {
+        Some(messaging) => messaging.input_exhausted().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Waits for SIGTERM (sent by Kubernetes on pod termination) or Ctrl+C
+async fn shutdown_signal() {
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to install SIGTERM handler: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    
+    tokio::select! {
+        _ = terminate => {},
+        _ = tokio::signal::ctrl_c() => {},
+    }
+    
+    tracing::info!("Shutdown signal received");
 }
\ No newline at end of file
@@ -0,0 +1,188 @@
+//! File sources and targets
+//!
+//! A file source reads every file matching a glob such as `/data/in/*.json`
+//! and, when watching, the ones that appear later. A file target writes each
+//! message as a new file, renaming it into place so readers never see a
+//! partial write.
+
+use super::MessageHandler;
+use crate::error::{Result, RuntimeError};
+use crate::message;
+use crate::resources::glob;
+use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+
+/// Directory a glob reads from: everything before the last `/`
+fn directory_of(pattern: &str) -> &Path {
+    match pattern.rsplit_once('/') {
+        Some((directory, _)) if !directory.is_empty() => Path::new(directory),
+        _ => Path::new("/"),
+    }
+}
+
+/// Reads the files matching `pattern` and passes each one to `handler`
+///
+/// Without `watch` the task ends once the existing files are processed.
+/// With it, new files are picked up when their writer closes them or renames
+/// them into the directory, until `shutdown` fires.
+pub async fn run_source(
+    pattern: String,
+    watch: bool,
+    handler: Arc<dyn MessageHandler>,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<()> {
+    let directory = directory_of(&pattern).to_path_buf();
+    let mut seen = HashSet::new();
+
+    // Subscribe before scanning so files created during the scan are not missed
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let _watcher = if watch {
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })
+        .map_err(|e| RuntimeError::Messaging(message!("Failed to watch {}: {}", directory.display(), e)))?;
+        watcher
+            .watch(&directory, RecursiveMode::NonRecursive)
+            .map_err(|e| RuntimeError::Messaging(message!("Failed to watch {}: {}", directory.display(), e)))?;
+        Some(watcher)
+    } else {
+        None
+    };
+
+    let mut existing = Vec::new();
+    let mut entries = tokio::fs::read_dir(&directory).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_file() {
+            existing.push(entry.path());
+        }
+    }
+    existing.sort();
+    for path in existing {
+        process(&pattern, path, &handler, &mut seen).await;
+    }
+
+    if !watch {
+        return Ok(());
+    }
+
+    loop {
+        let event = tokio::select! {
+            _ = shutdown.changed() => break,
+            event = events.recv() => match event {
+                Some(Ok(event)) => event,
+                Some(Err(e)) => {
+                    tracing::warn!("File watch error: {}", e);
+                    continue;
+                }
+                None => break,
+            },
+        };
+
+        // Creation fires before the content is written; wait for the close or rename
+        let complete = matches!(
+            event.kind,
+            EventKind::Access(AccessKind::Close(AccessMode::Write))
+                | EventKind::Modify(ModifyKind::Name(RenameMode::To | RenameMode::Both))
+        );
+        if complete {
+            for path in event.paths {
+                process(&pattern, path, &handler, &mut seen).await;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn process(pattern: &str, path: PathBuf, handler: &Arc<dyn MessageHandler>, seen: &mut HashSet<PathBuf>) {
+    let name = path.to_string_lossy().into_owned();
+    if !glob::matches(pattern, &name) || !seen.insert(path.clone()) {
+        return;
+    }
+
+    let result = match tokio::fs::read(&path).await {
+        Ok(payload) => handler.handle_message(&name, &payload, None).await,
+        Err(e) => Err(RuntimeError::Io(e)),
+    };
+    if let Err(e) = result {
+        tracing::error!("Error handling file {}: {}", name, e);
+    }
+}
+
+/// Writes a message as a new `.json` file in `directory` and returns its path
+pub async fn write_message(directory: &Path, payload: &[u8]) -> Result<PathBuf> {
+    tokio::fs::create_dir_all(directory).await?;
+
+    let name = format!(
+        "{}-{}.json",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis(),
+        uuid::Uuid::new_v4()
+    );
+    let path = directory.join(&name);
+    // Hidden temporary name, so globs like `*.json` only see complete files
+    let temporary = directory.join(format!(".{}.tmp", name));
+
+    tokio::fs::write(&temporary, payload).await?;
+    tokio::fs::rename(&temporary, &path).await?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use tokio::sync::Mutex;
+
+    #[derive(Default)]
+    struct Collect(Mutex<Vec<(String, Vec<u8>)>>);
+
+    #[async_trait]
+    impl MessageHandler for Collect {
+        async fn handle_message(&self, subject: &str, payload: &[u8], _headers: Option<&HashMap<String, String>>) -> Result<()> {
+            self.0.lock().await.push((subject.to_string(), payload.to_vec()));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reads_existing_and_new_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.json"), b"1").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), b"skip").unwrap();
+
+        let collected = Arc::new(Collect::default());
+        let pattern = format!("{}/*.json", dir.path().display());
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let task = tokio::spawn(run_source(pattern, true, collected.clone(), shutdown_rx));
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        write_message(dir.path(), b"2").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        shutdown.send(true).unwrap();
+        task.await.unwrap().unwrap();
+
+        let payloads: Vec<Vec<u8>> = collected.0.lock().await.iter().map(|(_, p)| p.clone()).collect();
+        assert_eq!(payloads, [b"1".to_vec(), b"2".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_one_shot_source_ends_after_existing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.json"), b"1").unwrap();
+
+        let collected = Arc::new(Collect::default());
+        let pattern = format!("{}/*.json", dir.path().display());
+        run_source(pattern, false, collected.clone(), watch::channel(false).1).await.unwrap();
+
+        assert_eq!(collected.0.lock().await.len(), 1);
+    }
+}
@@ -0,0 +1,169 @@
+//! Kafka transport for the sources and targets of a workflow
+
+use super::MessageHandler;
+use crate::codec::Codecs;
+use crate::error::{Result, RuntimeError};
+use crate::history::{Direction, HistoryRecorder};
+use crate::latency;
+use crate::message;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::message::{BorrowedHeaders, Header, Headers, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::Message as _;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// How long a produced message may wait in the producer's queue
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Counters a subscription adds its records to, shared with the NATS ones
+pub struct Counters {
+    /// Records received
+    pub received: Arc<AtomicU64>,
+    /// Records that failed to decode or whose handler returned an error
+    pub failures: Arc<AtomicU64>,
+}
+
+/// Connection to a Kafka cluster
+///
+/// Records are handed to the handler one at a time, keeping the order of
+/// each partition, and their offset is committed only after the handler
+/// succeeds: the records a replica had not handled when it stopped are
+/// consumed again by the group. A record whose handler fails is counted as
+/// failed and left uncommitted, but the commit of a later record of its
+/// partition moves past it.
+#[derive(Clone)]
+pub struct KafkaClient {
+    producer: FutureProducer,
+    bootstrap_servers: String,
+    group_id: String,
+    shutdown: watch::Receiver<bool>,
+}
+
+impl KafkaClient {
+    /// Connects to the cluster at `bootstrap_servers`, consuming in the group `group_id`
+    pub fn connect(bootstrap_servers: &str, group_id: &str, shutdown: watch::Receiver<bool>) -> Result<Self> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", bootstrap_servers)
+            .set("enable.idempotence", "true")
+            .create()
+            .map_err(|e| RuntimeError::Messaging(message!("Failed to connect to Kafka: {}", e)))?;
+
+        Ok(Self {
+            producer,
+            bootstrap_servers: bootstrap_servers.to_string(),
+            group_id: group_id.to_string(),
+            shutdown,
+        })
+    }
+
+    /// Publishes a message to a topic, once the cluster has stored it
+    pub async fn publish(&self, topic: &str, payload: &[u8], headers: &HashMap<String, String>) -> Result<()> {
+        let headers = headers.iter().fold(OwnedHeaders::new(), |all, (key, value)| {
+            all.insert(Header { key: key.as_str(), value: Some(value.as_str()) })
+        });
+        self.producer
+            .send(FutureRecord::<(), [u8]>::to(topic).payload(payload).headers(headers), SEND_TIMEOUT)
+            .await
+            .map(|_| ())
+            .map_err(|(e, _)| RuntimeError::Messaging(message!("Failed to publish Kafka message: {}", e)))
+    }
+
+    /// Subscribes to a topic
+    ///
+    /// Handlers get JSON whatever the encoding of the topic, and headers
+    /// stamped with the time Kafka stored the record as its ingest time.
+    pub async fn subscribe(
+        &self,
+        topic: &str,
+        handler: Arc<dyn MessageHandler>,
+        codecs: Arc<Codecs>,
+        history: Arc<HistoryRecorder>,
+        counters: Counters,
+    ) -> Result<()> {
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", &self.bootstrap_servers)
+            .set("group.id", &self.group_id)
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest")
+            .create()
+            .map_err(|e| RuntimeError::Messaging(message!("Failed to connect to Kafka: {}", e)))?;
+        consumer
+            .subscribe(&[topic])
+            .map_err(|e| RuntimeError::Messaging(message!("Failed to subscribe: {}", e)))?;
+
+        tokio::spawn(run_consumer(consumer, handler, codecs, history, counters, self.shutdown.clone()));
+        Ok(())
+    }
+}
+
+/// Polls the records of a subscription and hands them to its handler
+async fn run_consumer(
+    consumer: StreamConsumer,
+    handler: Arc<dyn MessageHandler>,
+    codecs: Arc<Codecs>,
+    history: Arc<HistoryRecorder>,
+    counters: Counters,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        // Stop pulling new records as soon as a drain starts; uncommitted
+        // ones go to the other replicas of the group
+        let record = tokio::select! {
+            _ = shutdown.changed() => break,
+            record = consumer.recv() => record,
+        };
+
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                // The client reconnects by itself
+                tracing::warn!("Kafka consumer error: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        counters.received.fetch_add(1, Ordering::Relaxed);
+
+        let topic = record.topic();
+        let mut headers = record.headers().map(headers_to_map).unwrap_or_default();
+        let stored_at = record.timestamp().to_millis().and_then(|millis| u64::try_from(millis).ok());
+        latency::stamp(&mut headers, stored_at.unwrap_or_else(latency::now_millis));
+        let payload = match codecs.decode(topic, record.payload().unwrap_or_default(), &mut headers) {
+            Ok(payload) => payload,
+            Err(e) => {
+                counters.failures.fetch_add(1, Ordering::Relaxed);
+                tracing::error!("Failed to decode a message on {}: {}", topic, e);
+                continue;
+            }
+        };
+        history.record(Direction::In, topic, Some(&headers), &payload, latency::now_millis());
+
+        match handler.handle_message(topic, &payload, Some(&headers)).await {
+            Ok(()) => {
+                if let Err(e) = consumer.commit_message(&record, CommitMode::Async) {
+                    tracing::error!("Failed to commit Kafka offset: {}", e);
+                }
+            }
+            Err(e) => {
+                counters.failures.fetch_add(1, Ordering::Relaxed);
+                tracing::error!("Error handling Kafka message: {}", e);
+            }
+        }
+    }
+}
+
+/// Converts the headers of a record into a map, skipping those without a UTF-8 value
+fn headers_to_map(headers: &BorrowedHeaders) -> HashMap<String, String> {
+    headers
+        .iter()
+        .filter_map(|header| {
+            let value = std::str::from_utf8(header.value?).ok()?;
+            Some((header.key.to_string(), value.to_string()))
+        })
+        .collect()
+}
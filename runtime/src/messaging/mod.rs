@@ -1,10 +1,34 @@
 //! Messaging handling in the runtime
 
+#[cfg(feature = "files")]
+pub mod file;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+
+use crate::codec::Codecs;
+use crate::history::{Direction, HistoryRecorder};
 use crate::error::{Result, RuntimeError};
+use crate::latency::{self, LatencyTracker};
+use crate::message;
 use async_trait::async_trait;
 use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::{watch, Mutex, Notify};
+use tokio::task::JoinHandle;
+
+/// Header carrying the priority of a message (higher values are more urgent)
+pub const PRIORITY_HEADER: &str = "Kumeo-Priority";
+
+/// Counter of the messages received from the subscriptions
+pub const MESSAGES_METRIC: &str = "kumeo_agent_messages_total";
+
+/// Counter of the received messages that failed to decode or whose handler returned an error
+pub const FAILED_MESSAGES_METRIC: &str = "kumeo_agent_messages_failed_total";
 
 /// Interface for message handling
 #[async_trait]
@@ -23,10 +47,42 @@ pub struct SubscriptionConfig {
     pub timeout: Option<Duration>,
 }
 
-/// Messaging handler with NATS support
+/// A message whose handler is still running
+struct InFlight {
+    priority: i64,
+    reply: Option<String>,
+    handle: JoinHandle<()>,
+}
+
+/// Messages currently being processed, keyed by delivery sequence
+#[derive(Default)]
+struct InFlightTracker {
+    messages: Mutex<HashMap<u64, InFlight>>,
+    next_id: AtomicU64,
+    finished: Notify,
+}
+
+/// Messaging handler with NATS support (and MQTT and Kafka with the `mqtt` and `kafka` features)
+///
+/// With the `kafka` feature, the agent's input and output topics are
+/// consumed from and produced to Kafka when its manifests say so, see
+/// [`crate::config::KafkaConfig`]; every other subject stays on NATS.
+#[derive(Clone)]
 pub struct Manager {
     client: Option<async_nats::Client>,
+    #[cfg(feature = "mqtt")]
+    mqtt: Option<mqtt::MqttClient>,
+    #[cfg(feature = "kafka")]
+    kafka: Option<kafka::KafkaClient>,
     config: crate::config::MessagingConfig,
+    shutdown: Arc<watch::Sender<bool>>,
+    in_flight: Arc<InFlightTracker>,
+    input_exhausted: Arc<watch::Sender<bool>>,
+    received: Arc<AtomicU64>,
+    failures: Arc<AtomicU64>,
+    latency: Arc<LatencyTracker>,
+    codecs: Arc<Codecs>,
+    history: Arc<HistoryRecorder>,
 }
 
 impl Manager {
@@ -36,57 +92,125 @@ impl Manager {
         {
             let client = async_nats::connect(&config.nats_url)
                 .await
-                .map_err(|e| RuntimeError::Messaging(format!("Failed to connect to NATS: {}", e)))?;
+                .map_err(|e| RuntimeError::Messaging(message!("Failed to connect to NATS: {}", e)))?;
+            
+            let shutdown = Arc::new(watch::channel(false).0);
+            
+            #[cfg(feature = "mqtt")]
+            let mqtt = match &config.mqtt_url {
+                Some(url) => {
+                    let client_id = format!("kumeo-{}", uuid::Uuid::new_v4());
+                    Some(mqtt::MqttClient::connect(url, &client_id, shutdown.subscribe())?)
+                }
+                None => None,
+            };
+            
+            #[cfg(feature = "kafka")]
+            let kafka = match &config.kafka {
+                Some(kafka) => Some(kafka::KafkaClient::connect(&kafka.bootstrap_servers, &kafka.group_id, shutdown.subscribe())?),
+                None => None,
+            };
                 
             Ok(Self {
                 client: Some(client),
+                #[cfg(feature = "mqtt")]
+                mqtt,
+                #[cfg(feature = "kafka")]
+                kafka,
                 config: config.clone(),
+                shutdown,
+                in_flight: Arc::new(InFlightTracker::default()),
+                input_exhausted: Arc::new(watch::channel(false).0),
+                received: Arc::new(AtomicU64::new(0)),
+                failures: Arc::new(AtomicU64::new(0)),
+                latency: Arc::new(LatencyTracker::from_env()),
+                codecs: Arc::new(Codecs::from_env()?),
+                history: Arc::new(HistoryRecorder::from_env()),
             })
         }
         
         #[cfg(not(feature = "nats"))]
-        Err(RuntimeError::Messaging("NATS support not compiled in".into()))
+        Err(RuntimeError::Messaging(message!("NATS support not compiled in")))
+    }
+    
+    /// The NATS connection, shared with the state store
+    #[cfg(feature = "nats")]
+    pub fn nats_client(&self) -> Option<&async_nats::Client> {
+        self.client.as_ref()
+    }
+    
+    /// End-to-end latency of the messages this agent publishes on the workflow's output
+    pub fn latency(&self) -> &Arc<LatencyTracker> {
+        &self.latency
     }
     
     /// Publishes a message
+    ///
+    /// Messages published on the workflow's output subject with an ingest
+    /// time end their trip, and their latency is observed. Messages on the
+    /// agent's output topic are encoded in its declared encoding, and
+    /// produced to Kafka when that is the agent's target.
     pub async fn publish(&self, subject: &str, payload: &[u8], headers: Option<HashMap<String, String>>) -> Result<()> {
+        #[cfg(feature = "kafka")]
+        if let Some(kafka) = self.kafka_topic(subject, |config| config.output_topic.as_deref()) {
+            let mut headers = headers.unwrap_or_default();
+            self.history.record(Direction::Out, subject, Some(&headers), payload, latency::now_millis());
+            let payload = self.codecs.encode(subject, payload, &mut headers)?;
+            kafka.publish(subject, &payload, &headers).await?;
+            self.latency.observe_publish(subject, Some(&headers), latency::now_millis());
+            return Ok(());
+        }
+        
         #[cfg(feature = "nats")]
         {
             if let Some(client) = &self.client {
+                let mut headers = headers.unwrap_or_default();
+                self.history.record(Direction::Out, subject, Some(&headers), payload, latency::now_millis());
+                let payload = self.codecs.encode(subject, payload, &mut headers)?;
+                let headers = (!headers.is_empty()).then_some(headers);
                 let mut msg = client.publish(
                     format!("{}{}", self.config.channel_prefix.as_deref().unwrap_or(""), subject),
-                    payload.to_vec().into()
+                    payload.into()
                 );
                 
-                if let Some(headers_map) = headers {
+                if let Some(headers_map) = &headers {
                     for (key, value) in headers_map {
-                        msg = msg.header(&key, &value);
+                        msg = msg.header(key.as_str(), value.as_str());
                     }
                 }
                 
                 msg.await
-                    .map_err(|e| RuntimeError::Messaging(format!("Failed to publish message: {}", e)))?;
+                    .map_err(|e| RuntimeError::Messaging(message!("Failed to publish message: {}", e)))?;
                 
+                self.latency.observe_publish(subject, headers.as_ref(), latency::now_millis());
                 Ok(())
             } else {
-                Err(RuntimeError::Messaging("NATS client not initialized".into()))
+                Err(RuntimeError::Messaging(message!("NATS client not initialized")))
             }
         }
         
         #[cfg(not(feature = "nats"))]
-        Err(RuntimeError::Messaging("NATS support not compiled in".into()))
+        Err(RuntimeError::Messaging(message!("NATS support not compiled in")))
     }
     
     /// Subscribes to a topic
+    ///
+    /// The agent's input topic is consumed from Kafka when that is its
+    /// source, in its consumer group rather than `queue_group`.
     pub async fn subscribe<H: MessageHandler>(
         &self,
         config: SubscriptionConfig,
         handler: H,
     ) -> Result<()> {
+        #[cfg(feature = "kafka")]
+        if self.kafka_topic(&config.subject, |kafka| kafka.input_topic.as_deref()).is_some() {
+            return self.subscribe_kafka(&config.subject, handler).await;
+        }
+        
         #[cfg(feature = "nats")]
         {
             let client = self.client.as_ref()
-                .ok_or_else(|| RuntimeError::Messaging("NATS client not initialized".to_string()))?;
+                .ok_or_else(|| RuntimeError::Messaging(message!("NATS client not initialized")))?;
                 
             let subject = format!(
                 "{}{}", 
@@ -98,23 +222,107 @@ impl Manager {
                 client.queue_subscribe(&subject, queue_group).await
             } else {
                 client.subscribe(&subject).await
-            }.map_err(|e| RuntimeError::Messaging(format!("Failed to subscribe: {}", e)))?;
+            }.map_err(|e| RuntimeError::Messaging(message!("Failed to subscribe: {}", e)))?;
             
             // Iniciar tarea para manejar mensajes
-            let handler = std::sync::Arc::new(handler);
+            let handler = Arc::new(handler);
+            let client = client.clone();
+            let in_flight = self.in_flight.clone();
+            let mut shutdown = self.shutdown.subscribe();
+            let batch = self.config.batch.clone();
+            let input_exhausted = self.input_exhausted.clone();
+            let received = self.received.clone();
+            let failures = self.failures.clone();
+            let codecs = self.codecs.clone();
+            let history = self.history.clone();
+            let prefix = self.config.channel_prefix.clone().unwrap_or_default();
             
             tokio::spawn(async move {
-                while let Some(message) = subscription.next().await {
+                let mut exhausted = false;
+                loop {
+                    let idle = async {
+                        match &batch {
+                            Some(batch) => tokio::time::sleep(Duration::from_secs(batch.idle_timeout)).await,
+                            None => std::future::pending().await,
+                        }
+                    };
+                    
+                    // Stop pulling new messages as soon as a drain starts
+                    let message = tokio::select! {
+                        _ = shutdown.changed() => break,
+                        _ = idle => {
+                            tracing::info!("No messages for a while, batch input consumed");
+                            exhausted = true;
+                            break;
+                        }
+                        message = subscription.next() => match message {
+                            Some(message) => message,
+                            None => break,
+                        },
+                    };
+                    received.fetch_add(1, Ordering::Relaxed);
+                    
                     let handler = handler.clone();
-                    let subject = message.subject.clone();
+                    let client = client.clone();
+                    let tracker = in_flight.clone();
+                    let subject = message.subject.to_string();
                     let payload = message.payload.to_vec();
-                    let headers = message.headers.clone();
+                    let reply = message.reply.as_ref().map(|r| r.to_string());
+                    // Messages entering the workflow are stamped with the time
+                    // JetStream stored them, so queueing before the first agent counts
+                    let mut headers = message.headers.as_ref().map(headers_to_map).unwrap_or_default();
+                    let ingested_at = reply.as_deref().and_then(jetstream_timestamp).unwrap_or_else(latency::now_millis);
+                    latency::stamp(&mut headers, ingested_at);
+                    // Handlers get JSON whatever the encoding of the topic
+                    let topic = subject.strip_prefix(prefix.as_str()).unwrap_or(&subject);
+                    let payload = match codecs.decode(topic, &payload, &mut headers) {
+                        Ok(payload) => payload,
+                        Err(e) => {
+                            failures.fetch_add(1, Ordering::Relaxed);
+                            tracing::error!("Failed to decode a message on {}: {}", subject, e);
+                            continue;
+                        }
+                    };
+                    history.record(Direction::In, topic, Some(&headers), &payload, latency::now_millis());
+                    let priority = message_priority(&headers);
+                    let id = in_flight.next_id.fetch_add(1, Ordering::Relaxed);
                     
-                    tokio::spawn(async move {
-                        if let Err(e) = handler.handle_message(&subject, &payload, headers.as_ref()).await {
-                            tracing::error!("Error handling message: {}", e);
+                    // Hold the lock while spawning so the task cannot
+                    // deregister itself before it has been registered
+                    let mut messages = in_flight.messages.lock().await;
+                    let task_reply = reply.clone();
+                    let task_failures = failures.clone();
+                    let handle = tokio::spawn(async move {
+                        match handler.handle_message(&subject, &payload, Some(&headers)).await {
+                            Ok(()) => acknowledge(&client, task_reply.as_deref(), ACK).await,
+                            Err(e) => {
+                                task_failures.fetch_add(1, Ordering::Relaxed);
+                                tracing::error!("Error handling message: {}", e);
+                            }
                         }
+                        tracker.messages.lock().await.remove(&id);
+                        tracker.finished.notify_waiters();
                     });
+                    let position = reply.as_deref().and_then(jetstream_position);
+                    messages.insert(id, InFlight { priority, reply, handle });
+                    drop(messages);
+                    
+                    // A batch ends with the last pending message or at the requested sequence
+                    if let (Some(batch), Some((sequence, pending))) = (&batch, position) {
+                        if pending == 0 || batch.until_sequence.is_some_and(|until| sequence >= until) {
+                            tracing::info!("Batch input consumed at stream sequence {}", sequence);
+                            exhausted = true;
+                            break;
+                        }
+                    }
+                }
+                
+                if let Err(e) = subscription.unsubscribe().await {
+                    tracing::warn!("Failed to unsubscribe while draining: {}", e);
+                }
+                
+                if exhausted {
+                    let _ = input_exhausted.send(true);
                 }
             });
             
@@ -122,64 +330,274 @@ impl Manager {
         }
         
         #[cfg(not(feature = "nats"))]
-        Err(RuntimeError::Messaging("NATS support not compiled in".into()))
+        Err(RuntimeError::Messaging(message!("NATS support not compiled in")))
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::sync::Arc;
-    use tokio::sync::Mutex;
     
-    struct TestHandler {
-        received: Arc<Mutex<Vec<(String, Vec<u8>)>>>,
+    /// Publishes a message to an MQTT topic
+    #[cfg(feature = "mqtt")]
+    pub async fn publish_mqtt(&self, topic: &str, payload: &[u8]) -> Result<()> {
+        self.mqtt_client()?.publish(topic, payload).await
     }
     
-    #[async_trait]
-    impl MessageHandler for TestHandler {
-        async fn handle_message(&self, subject: &str, payload: &[u8], _headers: Option<&HashMap<String, String>>) -> Result<()> {
-            let mut received = self.received.lock().await;
-            received.push((subject.to_string(), payload.to_vec()));
-            Ok(())
+    /// Subscribes to an MQTT topic filter such as `sensors/+/temp`
+    #[cfg(feature = "mqtt")]
+    pub async fn subscribe_mqtt<H: MessageHandler>(&self, filter: &str, handler: H) -> Result<()> {
+        self.mqtt_client()?.subscribe(filter, Arc::new(handler)).await
+    }
+    
+    #[cfg(feature = "mqtt")]
+    fn mqtt_client(&self) -> Result<&mqtt::MqttClient> {
+        self.mqtt.as_ref()
+            .ok_or_else(|| RuntimeError::Messaging(message!("MQTT broker not configured (set KUMEO_MQTT_URL)")))
+    }
+    
+    /// Publishes a message to a Kafka topic
+    #[cfg(feature = "kafka")]
+    pub async fn publish_kafka(&self, topic: &str, payload: &[u8]) -> Result<()> {
+        self.kafka_client()?.publish(topic, payload, &HashMap::new()).await
+    }
+    
+    /// Subscribes to a Kafka topic in the agent's consumer group
+    #[cfg(feature = "kafka")]
+    pub async fn subscribe_kafka<H: MessageHandler>(&self, topic: &str, handler: H) -> Result<()> {
+        let counters = kafka::Counters {
+            received: self.received.clone(),
+            failures: self.failures.clone(),
+        };
+        self.kafka_client()?.subscribe(topic, Arc::new(handler), self.codecs.clone(), self.history.clone(), counters).await
+    }
+    
+    #[cfg(feature = "kafka")]
+    fn kafka_client(&self) -> Result<&kafka::KafkaClient> {
+        self.kafka.as_ref()
+            .ok_or_else(|| RuntimeError::Messaging(message!("Kafka cluster not configured (set KAFKA_BOOTSTRAP_SERVERS)")))
+    }
+    
+    /// The Kafka client, if `subject` is the agent's topic `topic` picks on Kafka
+    #[cfg(feature = "kafka")]
+    fn kafka_topic(&self, subject: &str, topic: impl Fn(&crate::config::KafkaConfig) -> Option<&str>) -> Option<&kafka::KafkaClient> {
+        let config = self.config.kafka.as_ref()?;
+        (topic(config) == Some(subject)).then_some(self.kafka.as_ref()).flatten()
+    }
+    
+    /// Reads the files matching a glob such as `/data/in/*.json`
+    ///
+    /// Each file is one message whose subject is its path. The directory is
+    /// watched for new files when `KUMEO_FILE_WATCH=true`; otherwise the input
+    /// is exhausted once the existing files are processed.
+    #[cfg(feature = "files")]
+    pub async fn subscribe_files<H: MessageHandler>(&self, pattern: &str, handler: H) -> Result<()> {
+        let watch = self.config.file_watch;
+        let source = file::run_source(pattern.to_string(), watch, Arc::new(handler), self.shutdown.subscribe());
+        let input_exhausted = self.input_exhausted.clone();
+        
+        tokio::spawn(async move {
+            match source.await {
+                Ok(()) if !watch => {
+                    let _ = input_exhausted.send(true);
+                }
+                Ok(()) => {}
+                Err(e) => tracing::error!("File source stopped: {}", e),
+            }
+        });
+        Ok(())
+    }
+    
+    /// Writes a message as a new file in a target directory
+    #[cfg(feature = "files")]
+    pub async fn write_file(&self, directory: &str, payload: &[u8]) -> Result<std::path::PathBuf> {
+        file::write_message(std::path::Path::new(directory), payload).await
+    }
+    
+    /// Waits until a batch subscription has consumed its bounded input
+    ///
+    /// Never completes outside batch mode.
+    pub async fn input_exhausted(&self) {
+        if self.config.batch.is_none() {
+            return std::future::pending().await;
+        }
+        
+        let mut exhausted = self.input_exhausted.subscribe();
+        if exhausted.wait_for(|done| *done).await.is_err() {
+            std::future::pending::<()>().await;
         }
     }
     
-    #[tokio::test]
-    async fn test_messaging() {
-        // Este test requiere un servidor NATS en localhost:4222
-        // Se puede ejecutar con: docker run -p 4222:4222 nats:latest
+    /// Number of messages whose handler returned an error
+    pub fn failed_messages(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+    
+    /// Messages received and failed, in the Prometheus text format
+    ///
+    /// The canary analysis of an agent divides the rate of the second
+    /// counter by that of the first.
+    pub fn metrics(&self) -> String {
+        counter_metrics(self.received.load(Ordering::Relaxed), self.failed_messages())
+    }
+    
+    /// Stops pulling new messages and waits for in-flight ones to finish
+    ///
+    /// Handlers still running when `deadline` expires are aborted and their
+    /// messages nacked for redelivery, highest priority first. Returns the
+    /// number of messages that were nacked.
+    pub async fn drain(&self, deadline: Duration) -> Result<usize> {
+        let _ = self.shutdown.send(true);
         
-        if let Ok(config) = crate::config::MessagingConfig::new("nats://localhost:4222".to_string()) {
-            let manager = Manager::new(&config).await;
-            if let Ok(manager) = manager {
-                let received = Arc::new(Mutex::new(Vec::new()));
-                let handler = TestHandler {
-                    received: received.clone(),
-                };
-                
-                // Suscribirse a un tema
-                let sub_config = SubscriptionConfig {
-                    subject: "test.subject".to_string(),
-                    queue_group: None,
-                    timeout: Some(Duration::from_secs(5)),
-                };
-                
-                if manager.subscribe(sub_config, handler).await.is_ok() {
-                    // Publicar un mensaje
-                    let payload = b"test payload";
-                    if manager.publish("test.subject", payload, None).await.is_ok() {
-                        // Esperar un momento para que llegue el mensaje
-                        tokio::time::sleep(Duration::from_millis(100)).await;
-                        
-                        // Verificar que se recibió el mensaje
-                        let received = received.lock().await;
-                        assert!(!received.is_empty());
-                        assert_eq!(received[0].0, "test.subject");
-                        assert_eq!(received[0].1, payload);
-                    }
+        let wait_for_in_flight = async {
+            loop {
+                let finished = self.in_flight.finished.notified();
+                if self.in_flight.messages.lock().await.is_empty() {
+                    break;
                 }
+                finished.await;
             }
+        };
+        
+        if tokio::time::timeout(deadline, wait_for_in_flight).await.is_ok() {
+            tracing::info!("All in-flight messages completed");
+            return Ok(0);
         }
+        
+        let mut remaining: Vec<InFlight> = self.in_flight.messages.lock().await
+            .drain()
+            .map(|(_, message)| message)
+            .collect();
+        remaining.sort_by(|a, b| b.priority.cmp(&a.priority));
+        
+        tracing::warn!("Drain deadline reached, nacking {} in-flight messages", remaining.len());
+        
+        for message in &remaining {
+            message.handle.abort();
+            if let Some(client) = &self.client {
+                acknowledge(client, message.reply.as_deref(), NAK).await;
+            }
+        }
+        
+        if let Some(client) = &self.client {
+            client.flush()
+                .await
+                .map_err(|e| RuntimeError::Messaging(message!("Failed to flush nacks: {}", e)))?;
+        }
+        
+        Ok(remaining.len())
+    }
+}
+
+/// JetStream positive acknowledgement
+const ACK: &[u8] = b"+ACK";
+
+/// JetStream negative acknowledgement (requests redelivery)
+const NAK: &[u8] = b"-NAK";
+
+/// Sends a JetStream acknowledgement if the message came from a stream
+async fn acknowledge(client: &async_nats::Client, reply: Option<&str>, ack: &'static [u8]) {
+    // Plain request/reply subjects must not receive ack payloads
+    let Some(reply) = reply.filter(|r| r.starts_with("$JS.ACK.")) else {
+        return;
+    };
+    
+    if let Err(e) = client.publish(reply.to_string(), ack.into()).await {
+        tracing::error!("Failed to acknowledge message: {}", e);
+    }
+}
+
+/// Reads the stream sequence and pending count from a JetStream reply subject
+///
+/// Handles both `$JS.ACK.<stream>.<consumer>.<delivered>.<sseq>.<cseq>.<ts>.<pending>`
+/// and the newer form prefixed with the domain and account hash.
+fn jetstream_position(reply: &str) -> Option<(u64, u64)> {
+    let tokens: Vec<&str> = reply.strip_prefix("$JS.ACK.")?.split('.').collect();
+    let (sequence, pending) = match tokens.len() {
+        7 => (tokens[3], tokens[6]),
+        n if n >= 9 => (tokens[5], tokens[8]),
+        _ => return None,
+    };
+    Some((sequence.parse().ok()?, pending.parse().ok()?))
+}
+
+/// Reads the time JetStream stored a message from its reply subject, in epoch milliseconds
+fn jetstream_timestamp(reply: &str) -> Option<u64> {
+    let tokens: Vec<&str> = reply.strip_prefix("$JS.ACK.")?.split('.').collect();
+    let nanos: u64 = match tokens.len() {
+        7 => tokens[5],
+        n if n >= 9 => tokens[7],
+        _ => return None,
+    }
+    .parse()
+    .ok()?;
+    Some(nanos / 1_000_000)
+}
+
+/// Converts NATS headers into the map passed to handlers
+fn headers_to_map(headers: &async_nats::HeaderMap) -> HashMap<String, String> {
+    headers.iter()
+        .filter_map(|(name, values)| {
+            values.first().map(|value| (name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// The message counters in the Prometheus text format
+fn counter_metrics(received: u64, failed: u64) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# HELP {} Messages received from the subscriptions", MESSAGES_METRIC);
+    let _ = writeln!(out, "# TYPE {} counter", MESSAGES_METRIC);
+    let _ = writeln!(out, "{} {}", MESSAGES_METRIC, received);
+    let _ = writeln!(out, "# HELP {} Messages that failed to decode or to be handled", FAILED_MESSAGES_METRIC);
+    let _ = writeln!(out, "# TYPE {} counter", FAILED_MESSAGES_METRIC);
+    let _ = writeln!(out, "{} {}", FAILED_MESSAGES_METRIC, failed);
+    out
+}
+
+/// Reads the message priority header, defaulting to 0
+fn message_priority(headers: &HashMap<String, String>) -> i64 {
+    headers.get(PRIORITY_HEADER)
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_jetstream_position() {
+        assert_eq!(jetstream_position("$JS.ACK.ORDERS.scorer.1.42.40.1700000000.3"), Some((42, 3)));
+        assert_eq!(
+            jetstream_position("$JS.ACK.hub.ACCHASH.ORDERS.scorer.1.42.40.1700000000.0.xyz"),
+            Some((42, 0))
+        );
+        assert_eq!(jetstream_position("_INBOX.abc"), None);
+    }
+
+    #[test]
+    fn test_jetstream_timestamp() {
+        assert_eq!(jetstream_timestamp("$JS.ACK.ORDERS.scorer.1.42.40.1700000000123456789.3"), Some(1_700_000_000_123));
+        assert_eq!(
+            jetstream_timestamp("$JS.ACK.hub.ACCHASH.ORDERS.scorer.1.42.40.1700000000123456789.0.xyz"),
+            Some(1_700_000_000_123)
+        );
+        assert_eq!(jetstream_timestamp("_INBOX.abc"), None);
+    }
+
+    #[test]
+    fn test_message_priority() {
+        let mut headers = HashMap::new();
+        assert_eq!(message_priority(&headers), 0);
+        
+        headers.insert(PRIORITY_HEADER.to_string(), " 7 ".to_string());
+        assert_eq!(message_priority(&headers), 7);
+        
+        headers.insert(PRIORITY_HEADER.to_string(), "urgent".to_string());
+        assert_eq!(message_priority(&headers), 0);
+    }
+
+    #[test]
+    fn test_counter_metrics() {
+        let text = counter_metrics(12, 3);
+        assert!(text.lines().any(|line| line == "kumeo_agent_messages_total 12"), "{}", text);
+        assert!(text.lines().any(|line| line == "kumeo_agent_messages_failed_total 3"), "{}", text);
+        assert!(text.contains("# TYPE kumeo_agent_messages_failed_total counter"), "{}", text);
     }
 }
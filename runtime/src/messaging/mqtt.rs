@@ -0,0 +1,187 @@
+//! MQTT transport for IoT sources and targets
+
+use super::MessageHandler;
+use crate::error::{Result, RuntimeError};
+use crate::message;
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, RwLock};
+
+/// Port used when the broker URL does not name one
+const DEFAULT_PORT: u16 = 1883;
+
+/// Capacity of the request queue between the client and its event loop
+const REQUEST_CAPACITY: usize = 64;
+
+type Subscriptions = Arc<RwLock<Vec<(String, Arc<dyn MessageHandler>)>>>;
+
+/// Connection to an MQTT broker
+///
+/// Messages are delivered with QoS 1 and acknowledged only after the handler
+/// succeeds, so a failed message is redelivered by the broker.
+#[derive(Clone)]
+pub struct MqttClient {
+    client: AsyncClient,
+    subscriptions: Subscriptions,
+}
+
+impl MqttClient {
+    /// Connects to a broker given as `mqtt://host[:port]`
+    pub fn connect(url: &str, client_id: &str, shutdown: watch::Receiver<bool>) -> Result<Self> {
+        let (host, port) = parse_url(url)?;
+        let mut options = MqttOptions::new(client_id, host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        options.set_manual_acks(true);
+
+        let (client, event_loop) = AsyncClient::new(options, REQUEST_CAPACITY);
+        let subscriptions: Subscriptions = Arc::default();
+        tokio::spawn(run_event_loop(event_loop, client.clone(), subscriptions.clone(), shutdown));
+
+        Ok(Self { client, subscriptions })
+    }
+
+    /// Publishes a message to a topic
+    pub async fn publish(&self, topic: &str, payload: &[u8]) -> Result<()> {
+        self.client
+            .publish(topic, QoS::AtLeastOnce, false, payload.to_vec())
+            .await
+            .map_err(|e| RuntimeError::Messaging(message!("Failed to publish MQTT message: {}", e)))
+    }
+
+    /// Subscribes to a topic filter, which may contain `+` and `#` wildcards
+    pub async fn subscribe(&self, filter: &str, handler: Arc<dyn MessageHandler>) -> Result<()> {
+        if !is_valid_filter(filter) {
+            return Err(RuntimeError::Messaging(message!("Invalid MQTT topic filter: {}", filter)));
+        }
+
+        // Register first so retained messages delivered right after SUBACK are not lost
+        self.subscriptions.write().await.push((filter.to_string(), handler));
+        self.client
+            .subscribe(filter, QoS::AtLeastOnce)
+            .await
+            .map_err(|e| RuntimeError::Messaging(message!("Failed to subscribe: {}", e)))
+    }
+}
+
+/// Polls the connection and dispatches incoming messages to their handlers
+async fn run_event_loop(
+    mut event_loop: rumqttc::EventLoop,
+    client: AsyncClient,
+    subscriptions: Subscriptions,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        // Stop pulling new messages as soon as a drain starts; unacknowledged
+        // messages are redelivered to the next session
+        let event = tokio::select! {
+            _ = shutdown.changed() => break,
+            event = event_loop.poll() => event,
+        };
+
+        let publish = match event {
+            Ok(Event::Incoming(Packet::Publish(publish))) => publish,
+            Ok(_) => continue,
+            Err(e) => {
+                // The next poll reconnects
+                tracing::warn!("MQTT connection error: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+
+        let handlers: Vec<_> = subscriptions
+            .read()
+            .await
+            .iter()
+            .filter(|(filter, _)| topic_matches(filter, &publish.topic))
+            .map(|(_, handler)| handler.clone())
+            .collect();
+
+        let client = client.clone();
+        tokio::spawn(async move {
+            for handler in handlers {
+                if let Err(e) = handler.handle_message(&publish.topic, &publish.payload, None).await {
+                    tracing::error!("Error handling MQTT message: {}", e);
+                    return;
+                }
+            }
+            if let Err(e) = client.ack(&publish).await {
+                tracing::error!("Failed to acknowledge MQTT message: {}", e);
+            }
+        });
+    }
+}
+
+/// Splits `mqtt://host[:port]` (or `tcp://`) into host and port
+fn parse_url(url: &str) -> Result<(String, u16)> {
+    let address = url
+        .strip_prefix("mqtt://")
+        .or_else(|| url.strip_prefix("tcp://"))
+        .ok_or_else(|| RuntimeError::Config(message!("Unsupported MQTT URL: {}", url)))?
+        .trim_end_matches('/');
+
+    match address.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse()
+                .map_err(|_| RuntimeError::Config(message!("Invalid MQTT port in {}", url)))?;
+            Ok((host.to_string(), port))
+        }
+        None => Ok((address.to_string(), DEFAULT_PORT)),
+    }
+}
+
+/// Checks wildcard placement: `+` fills a whole level, `#` only the last one
+pub fn is_valid_filter(filter: &str) -> bool {
+    let levels: Vec<&str> = filter.split('/').collect();
+    !filter.is_empty()
+        && levels.iter().enumerate().all(|(i, level)| {
+            !level.contains(['+', '#']) || *level == "+" || (*level == "#" && i == levels.len() - 1)
+        })
+}
+
+/// Matches a topic against a subscription filter
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut topic_levels = topic.split('/');
+    for level in filter.split('/') {
+        match (level, topic_levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (level, Some(topic_level)) if level == topic_level => {}
+            _ => return false,
+        }
+    }
+    topic_levels.next().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_matches() {
+        assert!(topic_matches("sensors/+/temp", "sensors/kitchen/temp"));
+        assert!(!topic_matches("sensors/+/temp", "sensors/kitchen/humidity"));
+        assert!(!topic_matches("sensors/+/temp", "sensors/kitchen/temp/raw"));
+        assert!(topic_matches("sensors/#", "sensors"));
+        assert!(topic_matches("sensors/#", "sensors/kitchen/temp"));
+        assert!(!topic_matches("sensors", "sensors/kitchen"));
+    }
+
+    #[test]
+    fn test_filter_validation() {
+        assert!(is_valid_filter("sensors/+/temp"));
+        assert!(is_valid_filter("#"));
+        assert!(!is_valid_filter("sensors/#/temp"));
+        assert!(!is_valid_filter("sensors/kit+chen"));
+        assert!(!is_valid_filter(""));
+    }
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(parse_url("mqtt://broker:8883").unwrap(), ("broker".to_string(), 8883));
+        assert_eq!(parse_url("tcp://broker").unwrap(), ("broker".to_string(), DEFAULT_PORT));
+        assert!(parse_url("http://broker").is_err());
+    }
+}
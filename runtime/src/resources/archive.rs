@@ -0,0 +1,183 @@
+//! Archive bundles and member addressing
+//!
+//! A URI such as `s3://bucket/models.tar.gz!model/weights.onnx` addresses the
+//! member `model/weights.onnx` inside the archive `s3://bucket/models.tar.gz`.
+//! The archive itself is loaded (and cached) like any other resource, so
+//! several members of the same bundle only download it once.
+
+use crate::error::{Result, RuntimeError};
+use crate::message;
+use sha2::{Digest, Sha256};
+use std::io::{Cursor, Read};
+
+/// Separator between the archive URI and the member path
+pub const MEMBER_SEPARATOR: char = '!';
+
+/// Fragment parameter carrying the expected SHA-256 of a resource
+const SHA256_PARAM: &str = "sha256";
+
+/// Supported archive formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    /// ZIP archive
+    Zip,
+    /// Gzip-compressed tarball
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// Detects the archive format from the extension of a URI
+    pub fn from_uri(uri: &str) -> Option<Self> {
+        let path = uri.split(['?', '#']).next().unwrap_or("").to_ascii_lowercase();
+        if path.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Splits `archive!member` into the archive URI and the member path
+///
+/// Returns `None` when the URI does not address an archive member, so plain
+/// URIs that happen to contain `!` keep working.
+pub fn split_member(uri: &str) -> Option<(&str, &str)> {
+    let (archive, member) = uri.split_once(MEMBER_SEPARATOR)?;
+    ArchiveKind::from_uri(archive)?;
+    let member = member.trim_start_matches('/');
+    if member.is_empty() {
+        return None;
+    }
+    Some((archive, member))
+}
+
+/// Extracts a single member from an archive
+pub fn extract(kind: ArchiveKind, data: &[u8], member: &str) -> Result<Vec<u8>> {
+    let member = member.trim_start_matches('/');
+    let found = match kind {
+        ArchiveKind::Zip => extract_zip(data, member)?,
+        ArchiveKind::TarGz => extract_tar_gz(data, member)?,
+    };
+    found.ok_or_else(|| RuntimeError::NotFound(message!("Archive member '{}'", member)))
+}
+
+fn extract_zip(data: &[u8], member: &str) -> Result<Option<Vec<u8>>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))
+        .map_err(|e| RuntimeError::Resource(message!("Invalid zip archive: {}", e)))?;
+
+    let mut file = match archive.by_name(member) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(RuntimeError::Resource(message!("Invalid zip archive: {}", e))),
+    };
+
+    let mut content = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut content)?;
+    Ok(Some(content))
+}
+
+fn extract_tar_gz(data: &[u8], member: &str) -> Result<Option<Vec<u8>>> {
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(data));
+    let entries = archive
+        .entries()
+        .map_err(|e| RuntimeError::Resource(message!("Invalid tar.gz archive: {}", e)))?;
+
+    for entry in entries {
+        let mut entry = entry.map_err(|e| RuntimeError::Resource(message!("Invalid tar.gz archive: {}", e)))?;
+        let path = entry.path()?;
+        // Tarballs built with `tar -C dir .` prefix every member with `./`
+        if path.to_string_lossy().trim_start_matches("./") == member {
+            let mut content = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut content)?;
+            return Ok(Some(content));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Expected SHA-256 declared in the URI fragment (`...#sha256=<hex>`)
+pub fn expected_sha256(uri: &str) -> Option<&str> {
+    super::fragment_param(uri, SHA256_PARAM).filter(|expected| !expected.is_empty())
+}
+
+/// Checks content against the SHA-256 declared in its URI, if any
+pub fn verify_checksum(uri: &str, data: &[u8]) -> Result<()> {
+    let Some(expected) = expected_sha256(uri) else {
+        return Ok(());
+    };
+
+    let actual = hex::encode(Sha256::digest(data));
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(RuntimeError::Resource(message!(
+            "Checksum mismatch for {}: expected sha256 {}, got {}",
+            uri, expected, actual
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use std::io::Write;
+
+    pub(in crate::resources) fn tar_gz(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *content).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, content) in files {
+            writer.start_file(*path, zip::write::FileOptions::default()).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_split_member() {
+        assert_eq!(
+            split_member("s3://bucket/models.tar.gz!model/weights.onnx"),
+            Some(("s3://bucket/models.tar.gz", "model/weights.onnx"))
+        );
+        assert_eq!(
+            split_member("file:///bundle.zip#sha256=ab!/config.json"),
+            Some(("file:///bundle.zip#sha256=ab", "config.json"))
+        );
+        assert_eq!(split_member("https://host/page!important"), None);
+        assert_eq!(split_member("s3://bucket/models.tgz!"), None);
+    }
+
+    #[test]
+    fn test_extract_members() {
+        let files: &[(&str, &[u8])] = &[("model/weights.onnx", b"weights"), ("README", b"docs")];
+
+        assert_eq!(extract(ArchiveKind::TarGz, &tar_gz(files), "model/weights.onnx").unwrap(), b"weights");
+        assert_eq!(extract(ArchiveKind::Zip, &zip(files), "README").unwrap(), b"docs");
+        assert!(matches!(
+            extract(ArchiveKind::Zip, &zip(files), "missing"),
+            Err(RuntimeError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let digest = hex::encode(Sha256::digest(b"bundle"));
+
+        assert!(verify_checksum("s3://b/m.tgz", b"bundle").is_ok());
+        assert!(verify_checksum(&format!("s3://b/m.tgz#sha256={}", digest), b"bundle").is_ok());
+        assert!(verify_checksum(&format!("s3://b/m.tgz#sha256={}", digest), b"tampered").is_err());
+    }
+}
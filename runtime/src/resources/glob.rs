@@ -0,0 +1,82 @@
+//! Glob patterns over resource URIs
+//!
+//! Patterns use `*` and `?` within a path segment and `**` for any number of
+//! segments, e.g. `file://prompts/**/*.md`.
+
+/// Part of a pattern before its first wildcard segment, up to and including the last `/`
+///
+/// This is the prefix handed to loaders for listing.
+pub fn literal_prefix(pattern: &str) -> &str {
+    let wildcard = pattern.find(['*', '?']).unwrap_or(pattern.len());
+    match pattern[..wildcard].rfind('/') {
+        Some(slash) => &pattern[..=slash],
+        None => "",
+    }
+}
+
+/// Path of a URI for matching: everything after the scheme, without leading slashes
+///
+/// `file:///prompts/a.md` and `file://prompts/a.md` both become `prompts/a.md`.
+pub fn uri_path(uri: &str) -> &str {
+    uri.split_once("://")
+        .map(|(_, rest)| rest)
+        .unwrap_or(uri)
+        .trim_start_matches('/')
+}
+
+/// Whether the scheme and path of `uri` match `pattern`
+pub fn matches(pattern: &str, uri: &str) -> bool {
+    let scheme = |s: &str| s.split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase());
+    if scheme(pattern) != scheme(uri) {
+        return false;
+    }
+
+    let pattern: Vec<&str> = uri_path(pattern).split('/').collect();
+    let path: Vec<&str> = uri_path(uri).split('/').collect();
+    match_segments(&pattern, &path)
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((name, path_rest)) => {
+                match_segment(segment.as_bytes(), name.as_bytes()) && match_segments(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn match_segment(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| match_segment(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && match_segment(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_segment(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal_prefix() {
+        assert_eq!(literal_prefix("file://prompts/*.md"), "file://prompts/");
+        assert_eq!(literal_prefix("s3://bucket/rules/**/v?.yaml"), "s3://bucket/rules/");
+        assert_eq!(literal_prefix("file://prompts/intro.md"), "file://prompts/");
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("file://prompts/*.md", "file:///prompts/intro.md"));
+        assert!(!matches("file://prompts/*.md", "file:///prompts/nested/intro.md"));
+        assert!(!matches("file://prompts/*.md", "file:///prompts/intro.txt"));
+        assert!(matches("file://prompts/**/*.md", "file:///prompts/intro.md"));
+        assert!(matches("file://prompts/**/*.md", "file:///prompts/a/b/intro.md"));
+        assert!(matches("s3://bucket/v?.yaml", "s3://bucket/v1.yaml"));
+        assert!(!matches("s3://bucket/*.yaml", "file:///bucket/v1.yaml"));
+    }
+}
@@ -0,0 +1,131 @@
+//! Concurrency and bandwidth limits for resource downloads
+//!
+//! When many agents cold-start at once, each one fetching its models at full
+//! speed saturates the egress of the object store or trips its rate limits.
+//! Downloads take a permit for their scheme and one from the global in-flight
+//! cap, and the bytes they receive are paced by a shared bandwidth budget.
+
+use crate::config::FetchLimits;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+/// Permits held for the duration of a download
+pub(super) struct Permit {
+    _scheme: Option<OwnedSemaphorePermit>,
+    _global: Option<OwnedSemaphorePermit>,
+}
+
+/// Shared download limits of a resource manager
+#[derive(Debug)]
+pub(super) struct Limiter {
+    global: Option<Arc<Semaphore>>,
+    schemes: HashMap<String, Arc<Semaphore>>,
+    bandwidth: Option<Throttle>,
+}
+
+impl Limiter {
+    pub(super) fn new(limits: &FetchLimits) -> Self {
+        Self {
+            global: limits.max_in_flight.map(|max| Arc::new(Semaphore::new(max.max(1)))),
+            schemes: limits
+                .per_scheme
+                .iter()
+                .map(|(scheme, max)| (scheme.to_ascii_lowercase(), Arc::new(Semaphore::new((*max).max(1)))))
+                .collect(),
+            bandwidth: limits.bandwidth.filter(|rate| *rate > 0).map(Throttle::new),
+        }
+    }
+
+    /// Waits for a download slot for `scheme`
+    ///
+    /// The scheme permit is taken first, so downloads queued behind a busy
+    /// scheme do not hold global slots other schemes could use.
+    pub(super) async fn acquire(&self, scheme: &str) -> Permit {
+        let scheme = match self.schemes.get(scheme) {
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        let global = match &self.global {
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        Permit { _scheme: scheme, _global: global }
+    }
+
+    /// Waits until `bytes` fit in the bandwidth budget
+    pub(super) async fn throttle(&self, bytes: usize) {
+        if let Some(throttle) = &self.bandwidth {
+            throttle.consume(bytes).await;
+        }
+    }
+}
+
+/// Paces downloads to a rate shared by all of them
+///
+/// Every chunk reserves its transfer time on a shared clock, so concurrent
+/// downloads split the bandwidth instead of each getting the full rate.
+#[derive(Debug)]
+struct Throttle {
+    bytes_per_second: u64,
+    next_free: Mutex<Instant>,
+}
+
+impl Throttle {
+    fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second,
+            next_free: Mutex::new(Instant::now()),
+        }
+    }
+
+    async fn consume(&self, bytes: usize) {
+        let ready_at = {
+            let mut next_free = self.next_free.lock().await;
+            let start = (*next_free).max(Instant::now());
+            *next_free = start + Duration::from_secs_f64(bytes as f64 / self.bytes_per_second as f64);
+            *next_free
+        };
+        tokio::time::sleep_until(ready_at).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scheme_and_global_limits() {
+        let limiter = Arc::new(Limiter::new(&FetchLimits {
+            max_in_flight: Some(3),
+            per_scheme: HashMap::from([("s3".to_string(), 1)]),
+            bandwidth: None,
+        }));
+
+        let first = limiter.acquire("s3").await;
+        // A second s3 download waits, other schemes still get global slots
+        assert!(tokio::time::timeout(Duration::from_millis(10), limiter.acquire("s3")).await.is_err());
+        let _https = limiter.acquire("https").await;
+        let _gs = limiter.acquire("gs").await;
+        assert!(tokio::time::timeout(Duration::from_millis(10), limiter.acquire("https")).await.is_err());
+
+        drop(first);
+        assert!(tokio::time::timeout(Duration::from_millis(10), limiter.acquire("s3")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_bandwidth_is_shared() {
+        let limiter = Limiter::new(&FetchLimits {
+            max_in_flight: None,
+            per_scheme: HashMap::new(),
+            bandwidth: Some(10_000),
+        });
+
+        // Two concurrent 1 KB chunks at 10 KB/s take 200ms together, not 100ms
+        let start = Instant::now();
+        tokio::join!(limiter.throttle(1_000), limiter.throttle(1_000));
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
@@ -1,72 +1,454 @@
 //! Resource management in the runtime
 
+mod archive;
+pub(crate) mod glob;
+mod limits;
+mod preload;
+mod resource;
+mod signature;
+mod watch;
+
+pub use archive::{ArchiveKind, MEMBER_SEPARATOR};
+pub use resource::{Format, Resource};
+pub use signature::{SignatureKind, TrustedKey};
+
+use crate::config::ResourcesConfig;
 use crate::error::{Result, RuntimeError};
+use crate::message;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use std::time::{SystemTime, Duration};
 use url::Url;
 
+/// Value of a `key=value` (or bare `key`) parameter of a URI fragment
+///
+/// Parameters are separated by `&`, e.g. `#sha256=<hex>&minisign`. An archive
+/// member suffix (`!member`) is not part of the parameter.
+pub(crate) fn fragment_param<'a>(uri: &'a str, name: &str) -> Option<&'a str> {
+    let (_, fragment) = uri.split_once('#')?;
+    let fragment = archive::split_member(uri).map_or(fragment, |(archive_uri, _)| {
+        archive_uri.split_once('#').map_or("", |(_, fragment)| fragment)
+    });
+    fragment.split('&').find_map(|param| match param.split_once('=') {
+        Some((key, value)) if key == name => Some(value),
+        None if param == name => Some(""),
+        _ => None,
+    })
+}
+
+/// Loader for a custom URI scheme (e.g. `s3://` or `models://`)
+#[async_trait]
+pub trait ResourceLoader: Send + Sync + 'static {
+    /// Loads the resource identified by `url`
+    async fn load(&self, url: &Url) -> Result<Vec<u8>>;
+    
+    /// Lists the resources under `prefix`, one page at a time
+    ///
+    /// `cursor` is `None` for the first page and the previous page's
+    /// `next_cursor` afterwards, which maps directly onto the continuation
+    /// tokens of object stores. Loaders that cannot list keep this default.
+    async fn list(&self, prefix: &Url, cursor: Option<&str>) -> Result<Listing> {
+        let _ = cursor;
+        Err(RuntimeError::Resource(message!("Listing is not supported for {}", prefix)))
+    }
+}
+
+/// One page of a resource listing
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Listing {
+    /// Full URIs of the resources on this page
+    pub uris: Vec<String>,
+    /// Cursor of the next page, if any
+    pub next_cursor: Option<String>,
+}
+
+/// A resource held by the cache of a manager
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedResource {
+    /// Cache key: the URI after the mirror rules
+    pub uri: String,
+    /// Size of the content in bytes, if known
+    pub size: Option<u64>,
+    /// Seconds since the resource was cached; none for pinned resources
+    pub age_secs: Option<u64>,
+    /// Whether the resource is pinned, so the TTL never evicts it
+    pub pinned: bool,
+    /// Whether the TTL has lapsed, so the next load fetches it again
+    pub expired: bool,
+}
+
+/// What the cache of a manager holds
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheReport {
+    /// Whether no warm-up is in progress
+    pub ready: bool,
+    /// Seconds cached resources are kept, if they expire
+    pub ttl_secs: Option<u64>,
+    /// Cached and pinned resources, by URI
+    pub resources: Vec<CachedResource>,
+}
+
 /// Resource manager
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Manager {
     base_dir: PathBuf,
-    cache: Arc<RwLock<HashMap<String, (Vec<u8>, SystemTime)>>>,
+    cache: Arc<RwLock<HashMap<String, (Resource, SystemTime)>>>,
     cache_ttl: Option<Duration>,
+    loaders: Arc<RwLock<HashMap<String, Arc<dyn ResourceLoader>>>>,
+    config: ResourcesConfig,
+    trusted_keys: Arc<Vec<TrustedKey>>,
+    limiter: Arc<limits::Limiter>,
+    pinned: Arc<RwLock<HashMap<String, preload::Pinned>>>,
+    warmup: Arc<preload::Warmup>,
+}
+
+impl fmt::Debug for Manager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Manager")
+            .field("base_dir", &self.base_dir)
+            .field("cache_ttl", &self.cache_ttl)
+            .field("mirrors", &self.config.mirrors)
+            .field("require_signed", &self.config.require_signed)
+            .field("trusted_keys", &self.trusted_keys.len())
+            .field("limits", &self.config.limits)
+            .field("preload", &self.config.preload)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Manager {
     /// Creates a new resource manager
-    pub fn new(config: &super::super::config::ResourcesConfig) -> Result<Self> {
+    pub fn new(config: &ResourcesConfig) -> Result<Self> {
         let base_dir = config.base_dir.canonicalize()
-            .map_err(|_| RuntimeError::Config(format!("Invalid base directory: {:?}", config.base_dir)))?;
+            .map_err(|_| RuntimeError::Config(message!("Invalid base directory: {}", format!("{:?}", config.base_dir))))?;
             
         let cache_ttl = config.cache_ttl.map(Duration::from_secs);
+        
+        let trusted_keys = match &config.trusted_keys {
+            Some(directory) => signature::load_keys(directory)?,
+            None => Vec::new(),
+        };
+        if config.require_signed && trusted_keys.is_empty() {
+            return Err(RuntimeError::Config(message!("Signed resources are required but no trusted keys are configured")));
+        }
             
         Ok(Self {
             base_dir,
             cache: Arc::new(RwLock::new(HashMap::new())),
             cache_ttl,
+            loaders: Arc::new(RwLock::new(HashMap::new())),
+            config: config.clone(),
+            trusted_keys: Arc::new(trusted_keys),
+            limiter: Arc::new(limits::Limiter::new(&config.limits)),
+            pinned: Arc::new(RwLock::new(HashMap::new())),
+            warmup: Arc::new(preload::Warmup::new(config.preload.ready_file.clone())),
         })
     }
     
-    /// Gets a resource
+    /// Registers a loader for a custom URI scheme
+    ///
+    /// Registration takes the async lock, so it is safe to call from any task
+    /// while other tasks are loading resources. A loader registered for an
+    /// already known scheme replaces the previous one; `file`, `http` and
+    /// `https` are built in and cannot be overridden.
+    pub async fn register_loader<L: ResourceLoader>(&self, scheme: &str, loader: L) -> Result<()> {
+        let scheme = scheme.to_ascii_lowercase();
+        if matches!(scheme.as_str(), "file" | "http" | "https") {
+            return Err(RuntimeError::Resource(message!("Scheme '{}' is built in", scheme)));
+        }
+        
+        self.loaders.write().await.insert(scheme, Arc::new(loader));
+        Ok(())
+    }
+    
+    /// Gets the content of a resource
     pub async fn get(&self, uri: &str) -> Result<Vec<u8>> {
-        // Check cache first
-        if let Some((data, timestamp)) = self.check_cache(uri).await? {
-            return Ok(data);
+        Ok(self.load(uri).await?.data)
+    }
+    
+    /// Gets a resource with its content type, for typed decoding
+    ///
+    /// `archive!member` URIs (e.g. `s3://bucket/models.tar.gz!model/weights.onnx`)
+    /// extract a single member from a zip or tar.gz bundle. A `#sha256=<hex>`
+    /// fragment on a URI is checked against the downloaded content, so for
+    /// archives it verifies the whole bundle before anything is extracted.
+    /// Signatures declared with `#minisign` or `#cosign` are verified the
+    /// same way, and with `require_signed` unsigned resources are refused.
+    /// Mirror rules are applied first, so the cache is keyed by the mirror.
+    pub async fn load(&self, uri: &str) -> Result<Resource> {
+        self.load_mirrored(&self.config.mirror(uri)).await
+    }
+    
+    async fn load_mirrored(&self, uri: &str) -> Result<Resource> {
+        match archive::split_member(uri) {
+            Some((archive_uri, member)) => self.load_member(uri, archive_uri, member).await,
+            None => self.fetch(uri).await,
+        }
+    }
+    
+    /// Lists the URIs of all resources under a prefix such as `file://prompts/`
+    pub async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        self.list_mirrored(&self.config.mirror(prefix)).await
+    }
+    
+    async fn list_mirrored(&self, prefix: &str) -> Result<Vec<String>> {
+        if let Some(path) = prefix.strip_prefix("file://") {
+            return self.list_files(path).await;
         }
         
-        // Parse the URI
-        let url = Url::parse(uri)
-            .map_err(|e| RuntimeError::Resource(format!("Invalid URI: {}", e)))?;
+        let url = Url::parse(prefix)
+            .map_err(|e| RuntimeError::Resource(message!("Invalid URI: {}", e)))?;
+        let loader = match url.scheme() {
+            "http" | "https" => None,
+            scheme => self.loaders.read().await.get(scheme).cloned(),
+        }
+        .ok_or_else(|| RuntimeError::Resource(message!("Listing is not supported for {}", url.scheme())))?;
         
-        // Handle different schemes
-        let data = match url.scheme() {
-            "file" => self.load_file(url.path()).await?,
-            "http" | "https" => self.load_http(uri).await?,
-            _ => return Err(RuntimeError::Resource(format!("Unsupported scheme: {}", url.scheme()))),
-        };
+        let mut uris = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = loader.list(&url, cursor.as_deref()).await?;
+            uris.extend(page.uris);
+            match page.next_cursor {
+                Some(next) if cursor.as_ref() != Some(&next) => cursor = Some(next),
+                _ => break,
+            }
+        }
         
-        // Almacenar en caché
-        self.update_cache(uri, data.clone()).await;
+        Ok(uris)
+    }
+    
+    /// Loads every resource matching a glob such as `file://prompts/*.md`
+    ///
+    /// `*` and `?` match within a path segment and `**` across segments.
+    /// Resources are returned sorted by URI so prompt libraries and rule
+    /// sets load in a stable order.
+    pub async fn load_glob(&self, pattern: &str) -> Result<Vec<Resource>> {
+        let pattern = self.config.mirror(pattern);
+        let mut uris: Vec<String> = self.list_mirrored(glob::literal_prefix(&pattern)).await?
+            .into_iter()
+            .filter(|uri| glob::matches(&pattern, uri))
+            .collect();
+        uris.sort();
         
-        Ok(data)
+        // The listed URIs are already mirrored; don't rewrite them twice
+        let mut resources = Vec::with_capacity(uris.len());
+        for uri in &uris {
+            resources.push(self.load_mirrored(uri).await?);
+        }
+        Ok(resources)
+    }
+    
+    /// Loads the models declared in the configuration and pins them
+    ///
+    /// Runs once at startup; the ready file is written when it finishes.
+    pub fn warm_up(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        self.preload(self.config.preload.uris.clone())
+    }
+    
+    /// Loads resources ahead of traffic and pins them in the cache
+    ///
+    /// Pinned resources never expire, and with a preload directory they are
+    /// kept on disk instead of in memory. The manager stops being ready as
+    /// soon as this is called, not when the future is first polled, and is
+    /// ready again once the warm-up finishes, whether it succeeds or not.
+    pub fn preload(&self, uris: Vec<String>) -> impl Future<Output = Result<()>> + Send + 'static {
+        let guard = self.warmup.begin();
+        let manager = self.clone();
+        async move {
+            let _guard = guard;
+            let mut tasks = tokio::task::JoinSet::new();
+            for uri in uris {
+                let manager = manager.clone();
+                tasks.spawn(async move { manager.pin(&uri).await });
+            }
+            // Dropping the set on the first error aborts the other downloads
+            while let Some(result) = tasks.join_next().await {
+                result.map_err(|e| RuntimeError::Other(message!("Preload task failed: {}", e)))??;
+            }
+            Ok(())
+        }
+    }
+    
+    /// Whether no warm-up is in progress
+    pub fn is_ready(&self) -> bool {
+        self.warmup.is_ready()
+    }
+    
+    /// Reports the resources the cache holds, pinned or not
+    pub async fn cache_report(&self) -> CacheReport {
+        let mut resources: Vec<CachedResource> = Vec::new();
+        let pinned: Vec<(String, preload::Pinned)> = self
+            .pinned
+            .read()
+            .await
+            .iter()
+            .map(|(key, pinned)| (key.clone(), pinned.clone()))
+            .collect();
+        for (uri, pinned) in pinned {
+            resources.push(CachedResource {
+                uri,
+                size: pinned.size().await,
+                age_secs: None,
+                pinned: true,
+                expired: false,
+            });
+        }
+        for (uri, (resource, timestamp)) in self.cache.read().await.iter() {
+            let age = timestamp.elapsed().unwrap_or_default();
+            resources.push(CachedResource {
+                uri: uri.clone(),
+                size: Some(resource.data.len() as u64),
+                age_secs: Some(age.as_secs()),
+                pinned: false,
+                expired: self.cache_ttl.is_some_and(|ttl| age > ttl),
+            });
+        }
+        resources.sort_by(|a, b| a.uri.cmp(&b.uri));
+        CacheReport {
+            ready: self.is_ready(),
+            ttl_secs: self.cache_ttl.map(|ttl| ttl.as_secs()),
+            resources,
+        }
+    }
+    
+    /// Waits until every warm-up in progress has finished
+    pub async fn wait_ready(&self) {
+        self.warmup.wait_ready().await
+    }
+    
+    /// Watches a resource for changes
+    ///
+    /// The resource is loaded before this returns, so a missing or invalid
+    /// one fails right away. It is then loaded again every `interval`,
+    /// bypassing the cache but not the checksum and signature checks, and the
+    /// receiver is updated whenever its content changes. Watching stops once
+    /// every receiver is dropped. Pinned resources never change.
+    pub async fn watch(&self, uri: &str, interval: Duration) -> Result<tokio::sync::watch::Receiver<Resource>> {
+        watch::spawn(self.clone(), uri.to_string(), interval).await
     }
     
     /// Saves a resource
     pub async fn put(&self, uri: &str, data: &[u8]) -> Result<()> {
         let url = Url::parse(uri)
-            .map_err(|e| RuntimeError::Resource(format!("Invalid URI: {}", e)))?;
+            .map_err(|e| RuntimeError::Resource(message!("Invalid URI: {}", e)))?;
             
         match url.scheme() {
             "file" => self.save_file(url.path(), data).await,
-            _ => Err(RuntimeError::Resource(format!("Unsupported scheme for writing: {}", url.scheme()))),
+            _ => Err(RuntimeError::Resource(message!("Unsupported scheme for writing: {}", url.scheme()))),
         }
     }
     
     // Helper methods
+    async fn fetch(&self, uri: &str) -> Result<Resource> {
+        // Check cache first
+        if let Some(resource) = self.check_cache(uri).await? {
+            return Ok(resource);
+        }
+        
+        let resource = self.download(uri).await?;
+        
+        // Only verified content reaches the cache
+        archive::verify_checksum(uri, &resource.data)?;
+        self.verify_signature(uri, &resource.data).await?;
+        
+        // Almacenar en caché
+        self.update_cache(uri, resource.clone()).await;
+        
+        Ok(resource)
+    }
+    
+    async fn download(&self, uri: &str) -> Result<Resource> {
+        // Parse the URI
+        let url = Url::parse(uri)
+            .map_err(|e| RuntimeError::Resource(message!("Invalid URI: {}", e)))?;
+        
+        if url.scheme() == "file" {
+            return Ok(Resource::new(uri, None, self.load_file(url.path()).await?));
+        }
+        
+        // Remote downloads share the concurrency and bandwidth limits
+        let _permit = self.limiter.acquire(url.scheme()).await;
+        match url.scheme() {
+            "http" | "https" => self.load_http(uri).await,
+            scheme => {
+                // Release the lock before loading so slow loaders don't block registration
+                let loader = self.loaders.read().await.get(scheme).cloned()
+                    .ok_or_else(|| RuntimeError::Resource(message!("Unsupported scheme: {}", scheme)))?;
+                let data = loader.load(&url).await?;
+                // Loaders return whole objects; pace them before the next download starts
+                self.limiter.throttle(data.len()).await;
+                Ok(Resource::new(uri, None, data))
+            }
+        }
+    }
+    
+    async fn verify_signature(&self, uri: &str, data: &[u8]) -> Result<()> {
+        match signature::signature_ref(uri) {
+            Some(reference) => {
+                // Signatures are small and not cached, so a rotated signature is picked up
+                let signature = self.download(&self.config.mirror(&reference.uri)).await?;
+                signature::verify(uri, reference.kind, data, &signature.data, &self.trusted_keys)
+            }
+            None if self.config.require_signed => {
+                Err(RuntimeError::Resource(message!("Refusing unsigned resource {}", uri)))
+            }
+            None => Ok(()),
+        }
+    }
+    
+    /// Loads a resource again, ignoring the cached copy
+    async fn reload(&self, uri: &str) -> Result<Resource> {
+        let key = self.config.mirror(uri);
+        {
+            let mut cache = self.cache.write().await;
+            cache.remove(&key);
+            // The bundle of an archive member may have changed too
+            if let Some((archive_uri, _)) = archive::split_member(&key) {
+                cache.remove(archive_uri);
+            }
+        }
+        self.load_mirrored(&key).await
+    }
+    
+    async fn pin(&self, uri: &str) -> Result<()> {
+        let key = self.config.mirror(uri);
+        let resource = self.load_mirrored(&key).await?;
+        let pinned = preload::Pinned::new(&key, resource, self.config.preload.dir.as_deref()).await?;
+        self.pinned.write().await.insert(key.clone(), pinned);
+        // The pinned copy replaces the cached one
+        self.cache.write().await.remove(&key);
+        Ok(())
+    }
+    
+    async fn load_member(&self, uri: &str, archive_uri: &str, member: &str) -> Result<Resource> {
+        if let Some(resource) = self.check_cache(uri).await? {
+            return Ok(resource);
+        }
+        
+        // The bundle is cached under its own URI, so sibling members reuse the download
+        let bundle = self.fetch(archive_uri).await?;
+        let kind = ArchiveKind::from_uri(archive_uri)
+            .ok_or_else(|| RuntimeError::Resource(message!("Not an archive: {}", archive_uri)))?;
+        
+        // Decompression is CPU bound; keep it off the async workers
+        let member_path = member.to_string();
+        let data = tokio::task::spawn_blocking(move || archive::extract(kind, &bundle.data, &member_path))
+            .await
+            .map_err(|e| RuntimeError::Other(message!("Archive extraction panicked: {}", e)))??;
+        
+        let resource = Resource::new(uri, None, data);
+        self.update_cache(uri, resource.clone()).await;
+        
+        Ok(resource)
+    }
+    
     async fn load_file(&self, path: &str) -> Result<Vec<u8>> {
         let full_path = self.base_dir.join(path.trim_start_matches('/'));
         tokio::fs::read(&full_path)
@@ -74,6 +456,31 @@ impl Manager {
             .map_err(|e| RuntimeError::Io(e).into())
     }
     
+    async fn list_files(&self, path: &str) -> Result<Vec<String>> {
+        let mut uris = Vec::new();
+        let mut pending = vec![self.base_dir.join(path.trim_start_matches('/'))];
+        
+        while let Some(dir) = pending.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                // A prefix that does not exist simply has no resources
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(RuntimeError::Io(e)),
+            };
+            
+            while let Some(entry) = entries.next_entry().await? {
+                let entry_path = entry.path();
+                if entry.file_type().await?.is_dir() {
+                    pending.push(entry_path);
+                } else if let Ok(relative) = entry_path.strip_prefix(&self.base_dir) {
+                    uris.push(format!("file:///{}", relative.to_string_lossy().replace('\\', "/")));
+                }
+            }
+        }
+        
+        Ok(uris)
+    }
+    
     async fn save_file(&self, path: &str, data: &[u8]) -> Result<()> {
         let full_path = self.base_dir.join(path.trim_start_matches('/'));
         if let Some(parent) = full_path.parent() {
@@ -85,39 +492,394 @@ impl Manager {
             .map_err(|e| RuntimeError::Io(e).into())
     }
     
-    async fn load_http(&self, url: &str) -> Result<Vec<u8>> {
-        let response = reqwest::get(url)
+    async fn load_http(&self, url: &str) -> Result<Resource> {
+        let mut response = reqwest::get(url)
             .await
-            .map_err(|e| RuntimeError::Resource(format!("HTTP request failed: {}", e)))?;
+            .map_err(|e| RuntimeError::Resource(message!("HTTP request failed: {}", e)))?;
             
         if !response.status().is_success() {
-            return Err(RuntimeError::Resource(format!("HTTP error: {}", response.status())));
+            return Err(RuntimeError::Resource(message!("HTTP error: {}", response.status())));
         }
         
-        response.bytes()
+        let content_type = response.headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        
+        // Read chunk by chunk so the bandwidth limit paces the transfer itself
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk()
             .await
-            .map(|b| b.to_vec())
-            .map_err(|e| RuntimeError::Resource(format!("Failed to read response: {}", e)).into())
+            .map_err(|e| RuntimeError::Resource(message!("Failed to read response: {}", e)))?
+        {
+            self.limiter.throttle(chunk.len()).await;
+            data.extend_from_slice(&chunk);
+        }
+        
+        Ok(Resource::new(url, content_type, data))
     }
     
-    async fn check_cache(&self, key: &str) -> Result<Option<Vec<u8>>> {
+    async fn check_cache(&self, key: &str) -> Result<Option<Resource>> {
+        // Clone the entry so a disk read doesn't hold the lock
+        let pinned = self.pinned.read().await.get(key).cloned();
+        if let Some(pinned) = pinned {
+            return pinned.resource().await.map(Some);
+        }
+        
         let cache = self.cache.read().await;
-        if let Some((data, timestamp)) = cache.get(key) {
+        if let Some((resource, timestamp)) = cache.get(key) {
             if let Some(ttl) = self.cache_ttl {
                 if let Ok(elapsed) = timestamp.elapsed() {
                     if elapsed <= ttl {
-                        return Ok(Some(data.clone()));
+                        return Ok(Some(resource.clone()));
                     }
                 }
             } else {
-                return Ok(Some(data.clone()));
+                return Ok(Some(resource.clone()));
             }
         }
         Ok(None)
     }
     
-    async fn update_cache(&self, key: &str, data: Vec<u8>) {
+    async fn update_cache(&self, key: &str, resource: Resource) {
         let mut cache = self.cache.write().await;
-        cache.insert(key.to_string(), (data, SystemTime::now()));
+        cache.insert(key.to_string(), (resource, SystemTime::now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{FetchLimits, MirrorRule, PreloadConfig};
+    
+    struct StaticLoader(&'static [u8]);
+    
+    #[async_trait]
+    impl ResourceLoader for StaticLoader {
+        async fn load(&self, _url: &Url) -> Result<Vec<u8>> {
+            Ok(self.0.to_vec())
+        }
+    }
+    
+    fn manager() -> Manager {
+        Manager::new(&ResourcesConfig {
+            base_dir: std::env::temp_dir(),
+            cache_ttl: None,
+            mirrors: Vec::new(),
+            require_signed: false,
+            trusted_keys: None,
+            limits: FetchLimits::default(),
+            preload: PreloadConfig::default(),
+        })
+        .unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_register_loader_from_async_context() {
+        let manager = manager();
+        manager.register_loader("mem", StaticLoader(b"hello")).await.unwrap();
+        
+        assert_eq!(manager.get("mem://greeting").await.unwrap(), b"hello");
+        assert!(manager.get("unknown://greeting").await.is_err());
+    }
+    
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_register_loaders_concurrently() {
+        let manager = manager();
+        
+        let tasks: Vec<_> = (0..8)
+            .map(|i| {
+                let manager = manager.clone();
+                tokio::spawn(async move {
+                    let scheme = format!("mem{}", i);
+                    manager.register_loader(&scheme, StaticLoader(b"data")).await.unwrap();
+                    manager.get(&format!("{}://item", scheme)).await.unwrap()
+                })
+            })
+            .collect();
+        
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), b"data");
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_cache_report_lists_cached_resources() {
+        let manager = manager();
+        manager.register_loader("mem", StaticLoader(b"hello")).await.unwrap();
+        assert!(manager.cache_report().await.resources.is_empty());
+        
+        manager.get("mem://greeting").await.unwrap();
+        let report = manager.cache_report().await;
+        assert!(report.ready);
+        assert_eq!(report.ttl_secs, None);
+        assert_eq!(report.resources.len(), 1);
+        assert_eq!(report.resources[0].uri, "mem://greeting");
+        assert_eq!(report.resources[0].size, Some(5));
+        assert!(!report.resources[0].pinned && !report.resources[0].expired);
+    }
+    
+    #[tokio::test]
+    async fn test_loaded_resources_decode_by_extension() {
+        let manager = manager();
+        manager.register_loader("mem", StaticLoader(br#"{"replicas": 3}"#)).await.unwrap();
+        
+        let resource = manager.load("mem://config.json").await.unwrap();
+        assert_eq!(resource.format(), Format::Json);
+        let config: HashMap<String, u32> = resource.decode().unwrap();
+        assert_eq!(config["replicas"], 3);
+    }
+    
+    struct CountingLoader {
+        data: Vec<u8>,
+        calls: Arc<std::sync::atomic::AtomicUsize>,
+    }
+    
+    #[async_trait]
+    impl ResourceLoader for CountingLoader {
+        async fn load(&self, _url: &Url) -> Result<Vec<u8>> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(self.data.clone())
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_archive_members_share_one_download() {
+        let manager = manager();
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let bundle = archive::tests::tar_gz(&[
+            ("model/weights.onnx", b"weights"),
+            ("model/config.json", br#"{"layers": 4}"#),
+        ]);
+        let digest = hex::encode(<sha2::Sha256 as sha2::Digest>::digest(&bundle));
+        manager
+            .register_loader("s3", CountingLoader { data: bundle, calls: calls.clone() })
+            .await
+            .unwrap();
+        
+        let archive_uri = format!("s3://bucket/models.tar.gz#sha256={}", digest);
+        let weights = manager.get(&format!("{}!model/weights.onnx", archive_uri)).await.unwrap();
+        assert_eq!(weights, b"weights");
+        
+        let config = manager.load(&format!("{}!model/config.json", archive_uri)).await.unwrap();
+        let config: HashMap<String, u32> = config.decode().unwrap();
+        assert_eq!(config["layers"], 4);
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        
+        // A bundle that does not match its checksum is rejected before extraction
+        let tampered = "s3://bucket/other.tar.gz#sha256=00!model/weights.onnx";
+        assert!(manager.get(tampered).await.is_err());
+    }
+    
+    /// Object store double that serves one key per page
+    struct PagedLoader(Vec<&'static str>);
+    
+    #[async_trait]
+    impl ResourceLoader for PagedLoader {
+        async fn load(&self, url: &Url) -> Result<Vec<u8>> {
+            Ok(url.path().as_bytes().to_vec())
+        }
+        
+        async fn list(&self, prefix: &Url, cursor: Option<&str>) -> Result<Listing> {
+            let page: usize = cursor.map_or(0, |c| c.parse().unwrap());
+            Ok(Listing {
+                uris: vec![format!("{}{}", prefix, self.0[page])],
+                next_cursor: (page + 1 < self.0.len()).then(|| (page + 1).to_string()),
+            })
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_load_glob_follows_pages() {
+        let manager = manager();
+        manager
+            .register_loader("s3", PagedLoader(vec!["b.md", "notes.txt", "a.md", "sub/c.md"]))
+            .await
+            .unwrap();
+        
+        let prompts = manager.load_glob("s3://bucket/prompts/*.md").await.unwrap();
+        let uris: Vec<&str> = prompts.iter().map(|r| r.uri.as_str()).collect();
+        assert_eq!(uris, ["s3://bucket/prompts/a.md", "s3://bucket/prompts/b.md"]);
+        
+        let all = manager.load_glob("s3://bucket/prompts/**/*.md").await.unwrap();
+        assert_eq!(all.len(), 3);
+    }
+    
+    #[tokio::test]
+    async fn test_load_glob_from_base_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("prompts/nested")).unwrap();
+        std::fs::write(dir.path().join("prompts/intro.md"), "intro").unwrap();
+        std::fs::write(dir.path().join("prompts/nested/deep.md"), "deep").unwrap();
+        std::fs::write(dir.path().join("prompts/notes.txt"), "notes").unwrap();
+        
+        let manager = Manager::new(&ResourcesConfig {
+            base_dir: dir.path().to_path_buf(),
+            cache_ttl: None,
+            mirrors: Vec::new(),
+            require_signed: false,
+            trusted_keys: None,
+            limits: FetchLimits::default(),
+            preload: PreloadConfig::default(),
+        })
+        .unwrap();
+        
+        let prompts = manager.load_glob("file://prompts/*.md").await.unwrap();
+        assert_eq!(prompts.len(), 1);
+        assert_eq!(prompts[0].data, b"intro");
+        assert_eq!(manager.load_glob("file://prompts/**/*.md").await.unwrap().len(), 2);
+        assert!(manager.load_glob("file://missing/*.md").await.unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_builtin_schemes_cannot_be_overridden() {
+        let manager = manager();
+        assert!(manager.register_loader("file", StaticLoader(b"")).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_mirror_rules_redirect_loads() {
+        struct EchoLoader;
+        
+        #[async_trait]
+        impl ResourceLoader for EchoLoader {
+            async fn load(&self, url: &Url) -> Result<Vec<u8>> {
+                Ok(url.as_str().as_bytes().to_vec())
+            }
+        }
+        
+        let manager = Manager::new(&ResourcesConfig {
+            base_dir: std::env::temp_dir(),
+            cache_ttl: None,
+            mirrors: MirrorRule::parse_list(
+                "https://huggingface.co/*=mirror://hf/*, https://example.com/rules.json=mirror://rules.json",
+            )
+            .unwrap(),
+            require_signed: false,
+            trusted_keys: None,
+            limits: FetchLimits::default(),
+            preload: PreloadConfig::default(),
+        })
+        .unwrap();
+        manager.register_loader("mirror", EchoLoader).await.unwrap();
+        
+        let weights = manager.get("https://huggingface.co/org/model/weights.bin").await.unwrap();
+        assert_eq!(weights, b"mirror://hf/org/model/weights.bin");
+        assert_eq!(manager.get("https://example.com/rules.json").await.unwrap(), b"mirror://rules.json");
+        
+        assert!(MirrorRule::parse("https://huggingface.co/*=https://mirror.internal/hf").is_err());
+        assert!(MirrorRule::parse("https://*.hf.co/*=https://mirror.internal/*").is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_signed_resources_are_verified() {
+        struct SignedLoader;
+        
+        #[async_trait]
+        impl ResourceLoader for SignedLoader {
+            async fn load(&self, url: &Url) -> Result<Vec<u8>> {
+                Ok(match url.path() {
+                    "/model.bin.minisig" => signature::tests::MINISIGN_SIGNATURE.as_bytes().to_vec(),
+                    "/tampered.bin.minisig" => signature::tests::MINISIGN_SIGNATURE.as_bytes().to_vec(),
+                    "/tampered.bin" => b"tampered".to_vec(),
+                    _ => b"test".to_vec(),
+                })
+            }
+        }
+        
+        let keys = tempfile::tempdir().unwrap();
+        std::fs::write(keys.path().join("release.pub"), signature::tests::MINISIGN_KEY).unwrap();
+        let manager = Manager::new(&ResourcesConfig {
+            base_dir: std::env::temp_dir(),
+            cache_ttl: None,
+            mirrors: Vec::new(),
+            require_signed: true,
+            trusted_keys: Some(keys.path().to_path_buf()),
+            limits: FetchLimits::default(),
+            preload: PreloadConfig::default(),
+        })
+        .unwrap();
+        manager.register_loader("models", SignedLoader).await.unwrap();
+        
+        assert_eq!(manager.get("models://bucket/model.bin#minisign").await.unwrap(), b"test");
+        assert!(manager.get("models://bucket/tampered.bin#minisign").await.is_err());
+        assert!(manager.get("models://bucket/model.bin").await.is_err());
+    }
+    
+    /// Loader serving whatever the test last stored
+    struct MutableLoader(Arc<std::sync::Mutex<Vec<u8>>>);
+    
+    #[async_trait]
+    impl ResourceLoader for MutableLoader {
+        async fn load(&self, _url: &Url) -> Result<Vec<u8>> {
+            let data = self.0.lock().unwrap().clone();
+            if data.is_empty() {
+                return Err(RuntimeError::Resource(message!("Upload in progress")));
+            }
+            Ok(data)
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_watched_resources_are_reloaded() {
+        let manager = manager();
+        let content = Arc::new(std::sync::Mutex::new(b"v1".to_vec()));
+        manager.register_loader("s3", MutableLoader(content.clone())).await.unwrap();
+        
+        let mut routes = manager.watch("s3://config/routes.yaml", Duration::from_millis(10)).await.unwrap();
+        assert_eq!(routes.borrow_and_update().data, b"v1");
+        
+        *content.lock().unwrap() = b"v2".to_vec();
+        tokio::time::timeout(Duration::from_secs(5), routes.changed()).await.unwrap().unwrap();
+        assert_eq!(routes.borrow_and_update().data, b"v2");
+        // Plain loads see the new version too
+        assert_eq!(manager.get("s3://config/routes.yaml").await.unwrap(), b"v2");
+        
+        // A failed reload keeps the last version
+        content.lock().unwrap().clear();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!routes.has_changed().unwrap());
+        assert_eq!(routes.borrow().data, b"v2");
+        
+        assert!(manager.watch("s3://config/routes.yaml", Duration::ZERO).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_preloaded_resources_are_pinned() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = Manager::new(&ResourcesConfig {
+            base_dir: std::env::temp_dir(),
+            // Everything else expires immediately
+            cache_ttl: Some(0),
+            mirrors: Vec::new(),
+            require_signed: false,
+            trusted_keys: None,
+            limits: FetchLimits::default(),
+            preload: PreloadConfig {
+                uris: vec!["s3://models/scorer.onnx".to_string()],
+                dir: Some(dir.path().join("pinned")),
+                ready_file: Some(dir.path().join("ready")),
+            },
+        })
+        .unwrap();
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        manager
+            .register_loader("s3", CountingLoader { data: b"weights".to_vec(), calls: calls.clone() })
+            .await
+            .unwrap();
+        
+        let warm_up = manager.warm_up();
+        assert!(!manager.is_ready());
+        warm_up.await.unwrap();
+        assert!(manager.is_ready());
+        assert!(dir.path().join("ready").exists());
+        
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(manager.get("s3://models/scorer.onnx").await.unwrap(), b"weights");
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        
+        // A failed warm-up reports the error and restores readiness
+        assert!(manager.preload(vec!["missing://model".to_string()]).await.is_err());
+        assert!(manager.is_ready());
     }
 }
@@ -0,0 +1,187 @@
+//! Model warm-up: preloading and pinning resources
+//!
+//! Large models make the first request of a fresh replica slow, since it has
+//! to download and verify them first. Agents declare the models they need and
+//! the runtime loads them ahead of traffic, pinning them so the cache TTL
+//! never evicts them. The manager only reports ready once every warm-up in
+//! progress has finished, and mirrors that into a ready file the Kubernetes
+//! readiness probe checks.
+
+use super::Resource;
+use crate::error::{Result, RuntimeError};
+use crate::message;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::sync::watch;
+
+/// A resource kept in the cache regardless of its TTL
+#[derive(Debug, Clone)]
+pub(super) enum Pinned {
+    /// Content held in memory
+    Memory(Resource),
+    /// Content written to the preload directory, read back on every load
+    Disk {
+        uri: String,
+        content_type: Option<String>,
+        path: PathBuf,
+    },
+}
+
+impl Pinned {
+    /// Pins a resource, on disk when a preload directory is configured
+    ///
+    /// Files are named after the hash of the cache key, so pinning a
+    /// resource again overwrites its previous copy.
+    pub(super) async fn new(key: &str, resource: Resource, directory: Option<&Path>) -> Result<Self> {
+        let Some(directory) = directory else {
+            return Ok(Pinned::Memory(resource));
+        };
+
+        tokio::fs::create_dir_all(directory).await?;
+        let path = directory.join(hex::encode(Sha256::digest(key.as_bytes())));
+        tokio::fs::write(&path, &resource.data).await?;
+        Ok(Pinned::Disk {
+            uri: resource.uri,
+            content_type: resource.content_type,
+            path,
+        })
+    }
+
+    /// Size of the pinned content, unless its copy on disk is unreadable
+    pub(super) async fn size(&self) -> Option<u64> {
+        match self {
+            Pinned::Memory(resource) => Some(resource.data.len() as u64),
+            Pinned::Disk { path, .. } => tokio::fs::metadata(path).await.ok().map(|metadata| metadata.len()),
+        }
+    }
+
+    /// The pinned resource
+    pub(super) async fn resource(&self) -> Result<Resource> {
+        match self {
+            Pinned::Memory(resource) => Ok(resource.clone()),
+            Pinned::Disk { uri, content_type, path } => {
+                let data = tokio::fs::read(path).await.map_err(|e| {
+                    RuntimeError::Resource(message!("Pinned copy of {} is unreadable: {}", uri, e))
+                })?;
+                Ok(Resource::new(uri.clone(), content_type.clone(), data))
+            }
+        }
+    }
+}
+
+/// Readiness of a resource manager: the number of warm-ups in progress
+#[derive(Debug)]
+pub(super) struct Warmup {
+    pending: watch::Sender<usize>,
+    ready_file: Option<PathBuf>,
+}
+
+impl Warmup {
+    /// The ready file is only written when the first warm-up finishes, so
+    /// a replica is never reported ready before its declared models load
+    pub(super) fn new(ready_file: Option<PathBuf>) -> Self {
+        Self {
+            pending: watch::channel(0).0,
+            ready_file,
+        }
+    }
+
+    /// Starts a warm-up; readiness returns once the guard is dropped
+    pub(super) fn begin(self: &std::sync::Arc<Self>) -> WarmupGuard {
+        // The ready file is updated under the lock so transitions can't interleave
+        self.pending.send_modify(|pending| {
+            *pending += 1;
+            if *pending == 1 {
+                self.mark_logged(false);
+            }
+        });
+        WarmupGuard(self.clone())
+    }
+
+    pub(super) fn is_ready(&self) -> bool {
+        *self.pending.borrow() == 0
+    }
+
+    pub(super) async fn wait_ready(&self) {
+        let mut pending = self.pending.subscribe();
+        // The sender lives as long as `self`, so this cannot fail
+        let _ = pending.wait_for(|pending| *pending == 0).await;
+    }
+
+    fn mark(&self, ready: bool) -> Result<()> {
+        let Some(path) = &self.ready_file else {
+            return Ok(());
+        };
+        if ready {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, b"ready\n")?;
+        } else {
+            match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn mark_logged(&self, ready: bool) {
+        if let Err(e) = self.mark(ready) {
+            tracing::warn!("Failed to update the ready file: {}", e);
+        }
+    }
+}
+
+/// Keeps the manager not ready while a warm-up runs
+#[derive(Debug)]
+pub(super) struct WarmupGuard(std::sync::Arc<Warmup>);
+
+impl Drop for WarmupGuard {
+    fn drop(&mut self) {
+        self.0.pending.send_modify(|pending| {
+            *pending -= 1;
+            if *pending == 0 {
+                self.0.mark_logged(true);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_ready_file_follows_warmups() {
+        let dir = tempfile::tempdir().unwrap();
+        let ready_file = dir.path().join("ready");
+        let warmup = Arc::new(Warmup::new(Some(ready_file.clone())));
+        assert!(!ready_file.exists());
+
+        let first = warmup.begin();
+        let second = warmup.begin();
+        assert!(!warmup.is_ready() && !ready_file.exists());
+
+        drop(first);
+        assert!(!warmup.is_ready() && !ready_file.exists());
+        drop(second);
+        assert!(ready_file.exists());
+        tokio::time::timeout(std::time::Duration::from_millis(100), warmup.wait_ready())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_disk_pins_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let resource = Resource::new("s3://models/scorer.onnx", Some("application/octet-stream".to_string()), b"weights".to_vec());
+
+        let pinned = Pinned::new("s3://models/scorer.onnx", resource, Some(dir.path())).await.unwrap();
+        assert!(matches!(pinned, Pinned::Disk { .. }));
+        let loaded = pinned.resource().await.unwrap();
+        assert_eq!(loaded.data, b"weights");
+        assert_eq!(loaded.content_type.as_deref(), Some("application/octet-stream"));
+    }
+}
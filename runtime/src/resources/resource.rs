@@ -0,0 +1,197 @@
+//! Loaded resources and their typed decoding
+
+use crate::error::{Result, RuntimeError};
+use crate::message;
+use serde::de::DeserializeOwned;
+use std::fmt;
+
+/// Encoding of a resource, negotiated from its content type or extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// JSON document
+    Json,
+    /// YAML document
+    Yaml,
+    /// TOML document
+    Toml,
+    /// Raster image (PNG, JPEG, GIF, WebP, BMP)
+    Image,
+    /// Anything else
+    Unknown,
+}
+
+impl Format {
+    /// Guesses the format from a MIME type such as `application/json; charset=utf-8`
+    pub fn from_content_type(content_type: &str) -> Self {
+        let mime = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        match mime.as_str() {
+            "application/json" | "text/json" => Format::Json,
+            "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => Format::Yaml,
+            "application/toml" | "text/toml" | "text/x-toml" => Format::Toml,
+            m if m.ends_with("+json") => Format::Json,
+            m if m.ends_with("+yaml") => Format::Yaml,
+            m if m.starts_with("image/") => Format::Image,
+            _ => Format::Unknown,
+        }
+    }
+
+    /// Guesses the format from the extension of a path or URI
+    ///
+    /// For `archive!member` URIs the member's extension is used.
+    pub fn from_extension(path: &str) -> Self {
+        let path = super::archive::split_member(path).map_or(path, |(_, member)| member);
+        let path = path.split(['?', '#']).next().unwrap_or("");
+        let extension = match path.rsplit_once('.') {
+            Some((_, extension)) if !extension.contains('/') => extension.to_ascii_lowercase(),
+            _ => return Format::Unknown,
+        };
+
+        match extension.as_str() {
+            "json" => Format::Json,
+            "yaml" | "yml" => Format::Yaml,
+            "toml" => Format::Toml,
+            "png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp" => Format::Image,
+            _ => Format::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Json => "JSON",
+            Format::Yaml => "YAML",
+            Format::Toml => "TOML",
+            Format::Image => "image",
+            Format::Unknown => "unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A loaded resource with its metadata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resource {
+    /// URI the resource was loaded from
+    pub uri: String,
+    /// Content type reported by the source, if any
+    pub content_type: Option<String>,
+    /// Raw content
+    pub data: Vec<u8>,
+}
+
+impl Resource {
+    /// Creates a resource from its URI, content type and content
+    pub fn new(uri: impl Into<String>, content_type: Option<String>, data: Vec<u8>) -> Self {
+        Self {
+            uri: uri.into(),
+            content_type,
+            data,
+        }
+    }
+
+    /// Format of the resource: the content type wins over the extension
+    pub fn format(&self) -> Format {
+        self.content_type
+            .as_deref()
+            .map(Format::from_content_type)
+            .filter(|format| *format != Format::Unknown)
+            .unwrap_or_else(|| Format::from_extension(&self.uri))
+    }
+
+    /// Content as UTF-8 text
+    pub fn as_text(&self) -> Result<&str> {
+        std::str::from_utf8(&self.data)
+            .map_err(|e| RuntimeError::Serialization(message!("{} is not valid UTF-8: {}", self.uri, e)))
+    }
+
+    /// Decodes a JSON resource
+    pub fn as_json<T: DeserializeOwned>(&self) -> Result<T> {
+        self.expect_format(Format::Json)?;
+        serde_json::from_slice(&self.data)
+            .map_err(|e| RuntimeError::Serialization(message!("Invalid JSON in {}: {}", self.uri, e)))
+    }
+
+    /// Decodes a YAML resource
+    pub fn as_yaml<T: DeserializeOwned>(&self) -> Result<T> {
+        self.expect_format(Format::Yaml)?;
+        serde_yaml::from_slice(&self.data)
+            .map_err(|e| RuntimeError::Serialization(message!("Invalid YAML in {}: {}", self.uri, e)))
+    }
+
+    /// Decodes a TOML resource
+    pub fn as_toml<T: DeserializeOwned>(&self) -> Result<T> {
+        self.expect_format(Format::Toml)?;
+        toml::from_str(self.as_text()?)
+            .map_err(|e| RuntimeError::Serialization(message!("Invalid TOML in {}: {}", self.uri, e)))
+    }
+
+    /// Decodes an image resource
+    #[cfg(feature = "images")]
+    pub fn as_image(&self) -> Result<image::DynamicImage> {
+        self.expect_format(Format::Image)?;
+        image::load_from_memory(&self.data)
+            .map_err(|e| RuntimeError::Serialization(message!("Invalid image in {}: {}", self.uri, e)))
+    }
+
+    /// Decodes a structured resource according to its format
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T> {
+        match self.format() {
+            Format::Json => self.as_json(),
+            Format::Yaml => self.as_yaml(),
+            Format::Toml => self.as_toml(),
+            format => Err(RuntimeError::Serialization(message!(
+                "Cannot decode {} resource {}", format, self.uri
+            ))),
+        }
+    }
+
+    /// Rejects decoding as `expected` when the resource is known to be something else
+    fn expect_format(&self, expected: Format) -> Result<()> {
+        match self.format() {
+            Format::Unknown => Ok(()),
+            format if format == expected => Ok(()),
+            format => Err(RuntimeError::Serialization(message!(
+                "{} is a {} resource, not {}", self.uri, format, expected
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_format_negotiation() {
+        assert_eq!(Format::from_content_type("application/json; charset=utf-8"), Format::Json);
+        assert_eq!(Format::from_content_type("application/vnd.api+json"), Format::Json);
+        assert_eq!(Format::from_content_type("image/png"), Format::Image);
+        assert_eq!(Format::from_extension("file:///config/app.yml"), Format::Yaml);
+        assert_eq!(Format::from_extension("https://host/model.toml?rev=2"), Format::Toml);
+        assert_eq!(Format::from_extension("https://host.example/data"), Format::Unknown);
+
+        // The content type wins over the extension
+        let resource = Resource::new("https://host/data.txt", Some("application/json".into()), b"{}".to_vec());
+        assert_eq!(resource.format(), Format::Json);
+    }
+
+    #[test]
+    fn test_typed_decoding() {
+        let json = Resource::new("file:///a.json", None, br#"{"a": 1}"#.to_vec());
+        let yaml = Resource::new("file:///a.yaml", None, b"a: 1".to_vec());
+        let toml = Resource::new("file:///a.toml", None, b"a = 1".to_vec());
+
+        for resource in [&json, &yaml, &toml] {
+            let decoded: HashMap<String, i64> = resource.decode().unwrap();
+            assert_eq!(decoded["a"], 1);
+        }
+
+        assert!(json.as_yaml::<HashMap<String, i64>>().is_err());
+
+        let untyped = Resource::new("mem://blob", None, br#"{"a": 1}"#.to_vec());
+        assert!(untyped.as_json::<HashMap<String, i64>>().is_ok());
+        assert!(untyped.decode::<HashMap<String, i64>>().is_err());
+    }
+}
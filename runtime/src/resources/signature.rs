@@ -0,0 +1,203 @@
+//! Signature verification for resources
+//!
+//! A resource opts into verification through its URI fragment:
+//! `https://models.example.com/scorer.onnx#minisign` expects a minisign
+//! signature next to it (`scorer.onnx.minisig`) and `#cosign` a cosign blob
+//! signature (`scorer.onnx.sig`). `#minisign=<uri>` points at a signature
+//! stored elsewhere. Signatures are checked against the trusted public keys.
+
+use super::fragment_param;
+use crate::error::{Result, RuntimeError};
+use crate::message;
+use base64::Engine as _;
+use p256::ecdsa::signature::Verifier as _;
+use p256::pkcs8::DecodePublicKey as _;
+use std::path::Path;
+
+/// Signature formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureKind {
+    /// Minisign (Ed25519) signature file
+    Minisign,
+    /// Cosign blob signature: base64 DER ECDSA P-256 over SHA-256
+    Cosign,
+}
+
+impl SignatureKind {
+    const ALL: [SignatureKind; 2] = [SignatureKind::Minisign, SignatureKind::Cosign];
+
+    /// Fragment parameter declaring the signature
+    pub fn param(self) -> &'static str {
+        match self {
+            SignatureKind::Minisign => "minisign",
+            SignatureKind::Cosign => "cosign",
+        }
+    }
+
+    /// Extension of the signature stored next to the resource
+    pub fn extension(self) -> &'static str {
+        match self {
+            SignatureKind::Minisign => ".minisig",
+            SignatureKind::Cosign => ".sig",
+        }
+    }
+}
+
+/// Where the signature of a resource is and how to check it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureRef {
+    /// Signature format
+    pub kind: SignatureKind,
+    /// URI of the signature
+    pub uri: String,
+}
+
+/// Signature declared in the URI fragment, if any
+pub fn signature_ref(uri: &str) -> Option<SignatureRef> {
+    SignatureKind::ALL.into_iter().find_map(|kind| {
+        let location = fragment_param(uri, kind.param())?;
+        let uri = if location.is_empty() {
+            let resource = uri.split_once('#').map_or(uri, |(resource, _)| resource);
+            format!("{}{}", resource, kind.extension())
+        } else {
+            location.to_string()
+        };
+        Some(SignatureRef { kind, uri })
+    })
+}
+
+/// Public key signatures are checked against
+#[derive(Debug, Clone)]
+pub enum TrustedKey {
+    /// Minisign public key
+    Minisign(minisign_verify::PublicKey),
+    /// Cosign (ECDSA P-256) public key
+    Cosign(p256::ecdsa::VerifyingKey),
+}
+
+impl TrustedKey {
+    /// Parses a cosign PEM key, a `minisign.pub` file or a bare minisign key
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        if text.starts_with("-----BEGIN PUBLIC KEY-----") {
+            return p256::ecdsa::VerifyingKey::from_public_key_pem(text)
+                .map(TrustedKey::Cosign)
+                .map_err(|e| RuntimeError::Config(message!("Invalid cosign public key: {}", e)));
+        }
+
+        let key = if text.lines().count() == 1 {
+            minisign_verify::PublicKey::from_base64(text)
+        } else {
+            minisign_verify::PublicKey::decode(text)
+        };
+        key.map(TrustedKey::Minisign)
+            .map_err(|e| RuntimeError::Config(message!("Invalid minisign public key: {}", e)))
+    }
+}
+
+/// Loads every key file in a directory, such as a mounted Secret
+pub fn load_keys(directory: &Path) -> Result<Vec<TrustedKey>> {
+    let mut keys = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        // Secret volumes also contain hidden `..data` bookkeeping entries
+        let hidden = path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if hidden || !path.is_file() {
+            continue;
+        }
+
+        let text = std::fs::read_to_string(&path)?;
+        let key = TrustedKey::parse(&text)
+            .map_err(|e| RuntimeError::Config(format!("{}: {}", path.display(), e)))?;
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+/// Checks `data` against its signature with the trusted keys of that kind
+pub fn verify(uri: &str, kind: SignatureKind, data: &[u8], signature: &[u8], keys: &[TrustedKey]) -> Result<()> {
+    let invalid = |reason: &dyn std::fmt::Display| {
+        RuntimeError::Resource(message!("Invalid signature for {}: {}", uri, reason))
+    };
+    let signature = std::str::from_utf8(signature).map_err(|e| invalid(&e))?.trim();
+
+    let verified = match kind {
+        SignatureKind::Minisign => {
+            let signature = minisign_verify::Signature::decode(signature).map_err(|e| invalid(&e))?;
+            keys.iter().any(|key| {
+                matches!(key, TrustedKey::Minisign(key) if key.verify(data, &signature, false).is_ok())
+            })
+        }
+        SignatureKind::Cosign => {
+            let der = base64::engine::general_purpose::STANDARD
+                .decode(signature)
+                .map_err(|e| invalid(&e))?;
+            let signature = p256::ecdsa::Signature::from_der(&der).map_err(|e| invalid(&e))?;
+            keys.iter().any(|key| {
+                matches!(key, TrustedKey::Cosign(key) if key.verify(data, &signature).is_ok())
+            })
+        }
+    };
+
+    if !verified {
+        return Err(invalid(&"no trusted key matches"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use p256::ecdsa::signature::Signer as _;
+    use p256::pkcs8::EncodePublicKey as _;
+
+    // Test vector from the minisign-verify documentation
+    pub(in crate::resources) const MINISIGN_KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    pub(in crate::resources) const MINISIGN_SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1633700835\tfile:test\tprehashed
+wLMDjy9FLAuxZ3q4NlEvkgtyhrr0gtTu6KC4KBJdITbbOeAi1zBIYo0v4iTgt8jJpIidRJnp94ABQkJAgAooBQ==
+";
+
+    #[test]
+    fn test_signature_ref_from_fragment() {
+        assert_eq!(
+            signature_ref("https://models.example.com/scorer.onnx#sha256=ab&minisign"),
+            Some(SignatureRef {
+                kind: SignatureKind::Minisign,
+                uri: "https://models.example.com/scorer.onnx.minisig".to_string(),
+            })
+        );
+        assert_eq!(
+            signature_ref("s3://models/scorer.onnx#cosign=s3://signatures/scorer.sig").map(|s| s.uri),
+            Some("s3://signatures/scorer.sig".to_string())
+        );
+        assert_eq!(signature_ref("https://models.example.com/scorer.onnx"), None);
+    }
+
+    #[test]
+    fn test_verify_minisign() {
+        let keys = [TrustedKey::parse(MINISIGN_KEY).unwrap()];
+        verify("test", SignatureKind::Minisign, b"test", MINISIGN_SIGNATURE.as_bytes(), &keys).unwrap();
+        assert!(verify("test", SignatureKind::Minisign, b"tampered", MINISIGN_SIGNATURE.as_bytes(), &keys).is_err());
+    }
+
+    #[test]
+    fn test_verify_cosign() {
+        let signing_key = p256::ecdsa::SigningKey::from_bytes(&[7u8; 32].into()).unwrap();
+        let pem = signing_key
+            .verifying_key()
+            .to_public_key_pem(p256::pkcs8::LineEnding::LF)
+            .unwrap();
+        let signature: p256::ecdsa::Signature = signing_key.sign(b"weights");
+        let encoded = base64::engine::general_purpose::STANDARD.encode(signature.to_der());
+
+        let keys = [TrustedKey::parse(&pem).unwrap()];
+        verify("model", SignatureKind::Cosign, b"weights", encoded.as_bytes(), &keys).unwrap();
+        assert!(verify("model", SignatureKind::Cosign, b"tampered", encoded.as_bytes(), &keys).is_err());
+
+        // A signature is only checked against keys of its own kind
+        let minisign = [TrustedKey::parse(MINISIGN_KEY).unwrap()];
+        assert!(verify("model", SignatureKind::Cosign, b"weights", encoded.as_bytes(), &minisign).is_err());
+    }
+}
@@ -0,0 +1,52 @@
+//! Resource watching: reloading resources that change at runtime
+//!
+//! Some resources are configuration rather than artifacts, such as the
+//! routing table of a Router, and are edited while the agents run. A watched
+//! resource is loaded again every interval, bypassing the cache, and its
+//! watchers see each new version as soon as its content changes. A failed
+//! reload keeps the last version, so a broken upload never leaves a watcher
+//! without one.
+
+use super::{Manager, Resource};
+use crate::error::{Result, RuntimeError};
+use crate::message;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::MissedTickBehavior;
+use tracing::warn;
+
+/// Loads a resource and starts reloading it every `interval`
+///
+/// The reloads stop once every receiver is dropped.
+pub(super) async fn spawn(manager: Manager, uri: String, interval: Duration) -> Result<watch::Receiver<Resource>> {
+    if interval.is_zero() {
+        return Err(RuntimeError::Resource(message!("Watch interval of {} must not be zero", uri)));
+    }
+    let (sender, receiver) = watch::channel(manager.reload(&uri).await?);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick is immediate, and the resource was just loaded
+        ticker.tick().await;
+        loop {
+            tokio::select! {
+                _ = sender.closed() => break,
+                _ = ticker.tick() => {}
+            }
+            match manager.reload(&uri).await {
+                Ok(resource) => {
+                    sender.send_if_modified(|current| {
+                        let changed = current.data != resource.data;
+                        if changed {
+                            *current = resource;
+                        }
+                        changed
+                    });
+                }
+                Err(e) => warn!("Failed to reload {}, keeping the last version: {}", uri, e),
+            }
+        }
+    });
+    Ok(receiver)
+}
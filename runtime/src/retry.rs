@@ -0,0 +1,113 @@
+//! Retry policies of agents
+//!
+//! The compiler turns an agent's `retry:` option into a [`RetryPolicy`] that
+//! the generated code runs every message through. What happens once the
+//! attempts are exhausted is decided by the agent's `fallback:` option.
+
+use std::future::Future;
+use std::time::Duration;
+
+/// How often a failed message is attempted, and how long to wait in between
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts in total, the first one included
+    pub max_attempts: u32,
+    /// Delays before the successive retries; the last one repeats
+    pub backoff: Vec<Duration>,
+}
+
+impl RetryPolicy {
+    /// A single attempt, for agents without a `retry` option
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            backoff: Vec::new(),
+        }
+    }
+
+    /// Delay before a retry, `1` being the first one
+    pub fn delay(&self, retry: u32) -> Duration {
+        let index = (retry.max(1) - 1) as usize;
+        self.backoff
+            .get(index)
+            .or(self.backoff.last())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Run `operation` until it succeeds or the attempts are exhausted
+    ///
+    /// `operation` gets the attempt number, starting at 1. The error of the
+    /// last attempt is returned.
+    pub async fn run<T, E, F, Fut>(&self, mut operation: F) -> Result<T, E>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: std::fmt::Display,
+    {
+        let mut attempt = 1;
+        loop {
+            match operation(attempt).await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.max_attempts => {
+                    let delay = self.delay(attempt);
+                    tracing::warn!(
+                        "Attempt {}/{} failed, retrying in {:?}: {}",
+                        attempt,
+                        self.max_attempts,
+                        delay,
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_backoff_repeats() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            backoff: vec![Duration::from_secs(1), Duration::from_secs(2)],
+        };
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::from_secs(2));
+        assert_eq!(policy.delay(4), Duration::from_secs(2));
+        assert_eq!(RetryPolicy::none().delay(1), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_run_stops_after_max_attempts() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            backoff: vec![Duration::from_millis(1)],
+        };
+
+        let mut attempts = Vec::new();
+        let result: Result<(), String> = policy
+            .run(|attempt| {
+                attempts.push(attempt);
+                async move { Err(format!("attempt {} failed", attempt)) }
+            })
+            .await;
+        assert_eq!(result, Err("attempt 3 failed".to_string()));
+        assert_eq!(attempts, [1, 2, 3]);
+
+        let result = policy
+            .run(|attempt| async move {
+                match attempt {
+                    1 => Err("transient"),
+                    _ => Ok(attempt),
+                }
+            })
+            .await;
+        assert_eq!(result, Ok(2));
+    }
+}
@@ -0,0 +1,84 @@
+//! Timers of the agents
+//!
+//! An agent schedules a message to be published after a delay, under a key;
+//! scheduling a key again replaces its timer, and cancelling a key prefix
+//! drops every timer under it before it fires. HumanReview agents schedule
+//! the escalations and the SLA breach of each review this way and cancel
+//! them once the review is decided. Timers live in the runtime, so they keep
+//! running while the agent restarts but are lost when the runtime does.
+
+use crate::error::{Result, RuntimeError};
+use crate::message;
+use crate::messaging::Manager as MessagingManager;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Timers of the agents
+#[derive(Clone)]
+pub struct Scheduler {
+    messaging: Option<MessagingManager>,
+    timers: Arc<Mutex<HashMap<String, JoinHandle<()>>>>,
+}
+
+impl Scheduler {
+    /// Scheduler publishing through the runtime's messaging, if it has any
+    pub fn new(messaging: Option<MessagingManager>) -> Self {
+        Self { messaging, timers: Arc::default() }
+    }
+
+    /// Publish `payload` on `subject` after `delay`, replacing the timer of `key`
+    pub fn schedule(&self, key: &str, delay: Duration, subject: &str, payload: Vec<u8>) -> Result<()> {
+        let Some(messaging) = self.messaging.clone() else {
+            return Err(RuntimeError::Config(message!("Timer {} needs messaging to publish on {}", key, subject)));
+        };
+        let timers = self.timers.clone();
+        let (owned_key, subject) = (key.to_string(), subject.to_string());
+        let mut guard = self.timers.lock().unwrap_or_else(|e| e.into_inner());
+        let timer = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            // Forget the timer first, so cancelling it now is a no-op
+            timers.lock().unwrap_or_else(|e| e.into_inner()).remove(&owned_key);
+            match messaging.publish(&subject, &payload, None).await {
+                Ok(()) => debug!("Timer {} fired on {}", owned_key, subject),
+                Err(e) => warn!("Timer {} failed to publish on {}: {}", owned_key, subject, e),
+            }
+        });
+        if let Some(previous) = guard.insert(key.to_string(), timer) {
+            previous.abort();
+        }
+        Ok(())
+    }
+
+    /// Drop the timers whose key starts with `prefix`; returns how many there were
+    pub fn cancel(&self, prefix: &str) -> usize {
+        let mut timers = self.timers.lock().unwrap_or_else(|e| e.into_inner());
+        let keys: Vec<String> = timers.keys().filter(|key| key.starts_with(prefix)).cloned().collect();
+        for key in &keys {
+            if let Some(timer) = timers.remove(key) {
+                timer.abort();
+            }
+        }
+        keys.len()
+    }
+
+    /// Timers waiting to fire
+    pub fn pending(&self) -> usize {
+        self.timers.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timers_need_messaging() {
+        let scheduler = Scheduler::new(None);
+        assert!(scheduler.schedule("review.42.sla", Duration::from_secs(1), "reviews.42.completed", Vec::new()).is_err());
+        assert_eq!(scheduler.pending(), 0);
+        assert_eq!(scheduler.cancel("review.42."), 0);
+    }
+}
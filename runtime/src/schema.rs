@@ -0,0 +1,80 @@
+//! Validation of messages against an agent's input schema
+//!
+//! The compiler embeds the schema of the messages an agent consumes as a list
+//! of field names and types (`string`, `number`, `integer`, `boolean`,
+//! `object`, `array`, with a trailing `?` for optional fields). Generated
+//! agents check every payload with [`check`] before processing it, so a
+//! producer breaking the contract fails fast instead of deep in the agent.
+
+use crate::message;
+use serde_json::Value;
+
+/// Check that a message has every declared field with the declared type
+///
+/// Optional fields may be missing or null. An `integer` must be a number
+/// without a fraction. Undeclared fields are ignored. The error lists every
+/// violation.
+pub fn check(data: &Value, fields: &[(&str, &str)]) -> Result<(), String> {
+    let Value::Object(message) = data else {
+        return Err(message!("the message is not a JSON object"));
+    };
+
+    let violations: Vec<String> = fields
+        .iter()
+        .filter_map(|&(name, declared)| {
+            let (field_type, optional) = match declared.strip_suffix('?') {
+                Some(field_type) => (field_type, true),
+                None => (declared, false),
+            };
+            match message.get(name) {
+                None | Some(Value::Null) if optional => None,
+                None | Some(Value::Null) => Some(message!("field '{}' is missing", name)),
+                Some(value) if has_type(value, field_type) => None,
+                Some(value) => Some(message!("field '{}' should be of type {}, found {}", name, field_type, value)),
+            }
+        })
+        .collect();
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations.join("; "))
+    }
+}
+
+/// Whether a JSON value has a schema type
+fn has_type(value: &Value, field_type: &str) -> bool {
+    match field_type {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|number| number.fract() == 0.0),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const FIELDS: &[(&str, &str)] = &[("id", "string"), ("quantity", "integer"), ("total", "number"), ("note", "string?")];
+
+    #[test]
+    fn test_valid_messages() {
+        assert_eq!(check(&json!({"id": "a1", "quantity": 2, "total": 10}), FIELDS), Ok(()));
+        assert_eq!(check(&json!({"id": "a1", "quantity": 2.0, "total": 9.5, "note": null, "extra": true}), FIELDS), Ok(()));
+    }
+
+    #[test]
+    fn test_violations_are_listed() {
+        let error = check(&json!({"quantity": 1.5, "total": "10", "note": 3}), FIELDS).unwrap_err();
+        assert!(error.contains("field 'id' is missing"));
+        assert!(error.contains("field 'quantity' should be of type integer, found 1.5"));
+        assert!(error.contains("field 'total' should be of type number"));
+        assert!(error.contains("field 'note' should be of type string"));
+        assert!(check(&json!([1, 2]), FIELDS).is_err());
+    }
+}
@@ -1,12 +1,18 @@
 //! gRPC server for the runtime
 
+use crate::delay::Delayer;
+use crate::drift::DriftMonitor;
 use crate::error::{Result, RuntimeError};
+use crate::message;
 use crate::messaging::Manager as MessagingManager;
 use crate::resources::Manager as ResourceManager;
+use crate::scheduler::Scheduler;
+use crate::state::Manager as StateManager;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::net::{UnixListener, UnixStream};
 use tokio::sync::mpsc;
-use tokio_stream::wrappers::UnixListenerStream;
+use tokio_stream::wrappers::{ReceiverStream, UnixListenerStream};
 use tonic::transport::Server;
 use tracing::{info, error};
 
@@ -15,6 +21,10 @@ pub struct Server {
     socket_path: PathBuf,
     resource_manager: ResourceManager,
     messaging: Option<MessagingManager>,
+    state: StateManager,
+    scheduler: Scheduler,
+    delayer: Delayer,
+    drift: Arc<DriftMonitor>,
 }
 
 impl Server {
@@ -23,11 +33,19 @@ impl Server {
         socket_path: PathBuf,
         resource_manager: ResourceManager,
         messaging: Option<MessagingManager>,
+        state: StateManager,
+        scheduler: Scheduler,
+        delayer: Delayer,
+        drift: Arc<DriftMonitor>,
     ) -> Self {
         Self {
             socket_path,
             resource_manager,
             messaging,
+            state,
+            scheduler,
+            delayer,
+            drift,
         }
     }
 
@@ -73,6 +91,10 @@ impl Server {
         let service = RuntimeServiceServer::new(RuntimeServiceImpl {
             resource_manager: self.resource_manager,
             messaging: self.messaging,
+            state: self.state,
+            scheduler: self.scheduler,
+            delayer: self.delayer,
+            drift: self.drift,
         });
 
         // Iniciar el servidor
@@ -80,7 +102,7 @@ impl Server {
             .add_service(service)
             .serve_with_incoming(incoming)
             .await
-            .map_err(|e| RuntimeError::Other(format!("Server error: {}", e)))?;
+            .map_err(|e| RuntimeError::Other(message!("Server error: {}", e)))?;
 
         Ok(())
     }
@@ -90,6 +112,10 @@ impl Server {
 struct RuntimeServiceImpl {
     resource_manager: ResourceManager,
     messaging: Option<MessagingManager>,
+    state: StateManager,
+    scheduler: Scheduler,
+    delayer: Delayer,
+    drift: Arc<DriftMonitor>,
 }
 
 #[tonic::async_trait]
@@ -115,13 +141,149 @@ impl RuntimeService for RuntimeServiceImpl {
         let req = request.into_inner();
         match self.resource_manager.put(&req.uri, &req.data).await {
             Ok(_) => Ok(tonic::Response::new(ResourceResponse {
-                result: Some(resource_response::Result::Data(Vec::new()))),
+                result: Some(resource_response::Result::Data(Vec::new())),
                 metadata: Default::default(),
             })),
             Err(e) => Err(tonic::Status::internal(e.to_string())),
         }
     }
 
+    type WatchResourceStream = ReceiverStream<std::result::Result<ResourceResponse, tonic::Status>>;
+
+    async fn watch_resource(
+        &self,
+        request: tonic::Request<WatchResourceRequest>,
+    ) -> std::result::Result<tonic::Response<Self::WatchResourceStream>, tonic::Status> {
+        // The first version is sent right away, so a missing resource fails the call
+        let req = request.into_inner();
+        let interval = std::time::Duration::from_secs(req.interval_secs);
+        let mut versions = self
+            .resource_manager
+            .watch(&req.uri, interval)
+            .await
+            .map_err(|e| tonic::Status::failed_precondition(e.to_string()))?;
+
+        let (sender, receiver) = mpsc::channel(1);
+        tokio::spawn(async move {
+            loop {
+                let data = versions.borrow_and_update().data.clone();
+                let response = ResourceResponse {
+                    result: Some(resource_response::Result::Data(data)),
+                    metadata: Default::default(),
+                };
+                if sender.send(Ok(response)).await.is_err() {
+                    break;
+                }
+                // Dropping the receiver once the client is gone stops the reloads
+                tokio::select! {
+                    _ = sender.closed() => break,
+                    changed = versions.changed() => if changed.is_err() { break },
+                }
+            }
+        });
+        Ok(tonic::Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn preload(
+        &self,
+        request: tonic::Request<PreloadRequest>,
+    ) -> std::result::Result<tonic::Response<PreloadResponse>, tonic::Status> {
+        // Agents declare models ahead of scale-up; the call returns once they are pinned
+        let req = request.into_inner();
+        let response = match self.resource_manager.preload(req.uris).await {
+            Ok(()) => PreloadResponse { success: true, error: String::new() },
+            Err(e) => PreloadResponse { success: false, error: e.to_string() },
+        };
+        Ok(tonic::Response::new(response))
+    }
+
+    async fn get_state(
+        &self,
+        request: tonic::Request<GetStateRequest>,
+    ) -> std::result::Result<tonic::Response<StateResponse>, tonic::Status> {
+        let req = request.into_inner();
+        let ttl = std::time::Duration::from_secs(req.ttl_secs);
+        match self.state.get(&req.key, ttl).await {
+            Ok(value) => Ok(tonic::Response::new(StateResponse {
+                found: value.is_some(),
+                value: value.unwrap_or_default(),
+            })),
+            Err(e) => Err(tonic::Status::internal(e.to_string())),
+        }
+    }
+
+    async fn put_state(
+        &self,
+        request: tonic::Request<PutStateRequest>,
+    ) -> std::result::Result<tonic::Response<StateResponse>, tonic::Status> {
+        let req = request.into_inner();
+        let ttl = std::time::Duration::from_secs(req.ttl_secs);
+        match self.state.put(&req.key, &req.value, ttl).await {
+            Ok(()) => Ok(tonic::Response::new(StateResponse { found: true, value: Vec::new() })),
+            Err(e) => Err(tonic::Status::internal(e.to_string())),
+        }
+    }
+
+    async fn schedule(
+        &self,
+        request: tonic::Request<ScheduleRequest>,
+    ) -> std::result::Result<tonic::Response<ScheduleResponse>, tonic::Status> {
+        let req = request.into_inner();
+        let delay = std::time::Duration::from_millis(req.delay_ms);
+        match self.scheduler.schedule(&req.key, delay, &req.subject, req.payload) {
+            Ok(()) => Ok(tonic::Response::new(ScheduleResponse { timers: 1 })),
+            Err(e) => Err(tonic::Status::failed_precondition(e.to_string())),
+        }
+    }
+
+    async fn cancel_schedule(
+        &self,
+        request: tonic::Request<CancelScheduleRequest>,
+    ) -> std::result::Result<tonic::Response<ScheduleResponse>, tonic::Status> {
+        let req = request.into_inner();
+        let cancelled = self.scheduler.cancel(&req.prefix);
+        Ok(tonic::Response::new(ScheduleResponse { timers: cancelled as u32 }))
+    }
+
+    async fn delay(
+        &self,
+        request: tonic::Request<DelayRequest>,
+    ) -> std::result::Result<tonic::Response<DelayResponse>, tonic::Status> {
+        let req = request.into_inner();
+        let delay = std::time::Duration::from_millis(req.delay_ms);
+        match self.delayer.delay(&req.subject, req.payload, delay).await {
+            Ok(()) => Ok(tonic::Response::new(DelayResponse {})),
+            Err(e) => Err(tonic::Status::failed_precondition(e.to_string())),
+        }
+    }
+
+    async fn health(
+        &self,
+        _request: tonic::Request<HealthCheckRequest>,
+    ) -> std::result::Result<tonic::Response<HealthCheckResponse>, tonic::Status> {
+        let (status, message) = if self.resource_manager.is_ready() {
+            (health_check_response::ServingStatus::Serving, String::new())
+        } else {
+            (health_check_response::ServingStatus::NotServing, "Warming up models".to_string())
+        };
+        Ok(tonic::Response::new(HealthCheckResponse {
+            status: status as i32,
+            message,
+        }))
+    }
+
+    async fn metrics(
+        &self,
+        _request: tonic::Request<MetricsRequest>,
+    ) -> std::result::Result<tonic::Response<MetricsResponse>, tonic::Status> {
+        let mut text = self.drift.metrics();
+        if let Some(messaging) = &self.messaging {
+            text.push_str(&messaging.metrics());
+            text.push_str(&messaging.latency().metrics());
+        }
+        Ok(tonic::Response::new(MetricsResponse { text }))
+    }
+
     // Implementar otros métodos del servicio...
 }
 
@@ -0,0 +1,177 @@
+//! State store of the agents
+//!
+//! Agents keep small values that outlive a message, such as the chat history
+//! of a session, under a key with a TTL: a value expires once it goes
+//! unwritten for that long. With NATS, values live in JetStream key-value
+//! buckets, one per TTL, shared by the replicas of an agent; without it they
+//! live in the memory of the runtime.
+
+use crate::error::{Result, RuntimeError};
+use crate::message;
+use base64::Engine as _;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Prefix of the JetStream buckets holding the state
+pub const BUCKET_PREFIX: &str = "KUMEO_STATE";
+
+/// Name of the bucket holding values with a TTL
+pub fn bucket_name(ttl: Duration) -> String {
+    format!("{}_{}S", BUCKET_PREFIX, ttl.as_secs())
+}
+
+/// Key of a value in a bucket
+///
+/// Bucket keys only allow a few characters, so agent keys are encoded as URL-safe base64.
+pub fn bucket_key(key: &str) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(key)
+}
+
+/// Where the values are kept
+#[derive(Clone)]
+enum Backend {
+    /// Values and when they expire
+    Memory(Arc<Mutex<HashMap<String, (Vec<u8>, Instant)>>>),
+    /// JetStream buckets, opened on first use
+    #[cfg(feature = "nats")]
+    Nats {
+        jetstream: async_nats::jetstream::Context,
+        buckets: Arc<tokio::sync::Mutex<HashMap<u64, async_nats::jetstream::kv::Store>>>,
+    },
+}
+
+/// State store of the agents
+#[derive(Clone)]
+pub struct Manager {
+    backend: Backend,
+}
+
+impl Manager {
+    /// Store kept in the memory of the runtime
+    pub fn in_memory() -> Self {
+        Self { backend: Backend::Memory(Arc::default()) }
+    }
+
+    /// Store kept in JetStream, or in memory without a NATS connection
+    pub fn new(messaging: Option<&crate::messaging::Manager>) -> Self {
+        #[cfg(feature = "nats")]
+        if let Some(client) = messaging.and_then(|messaging| messaging.nats_client()) {
+            return Self {
+                backend: Backend::Nats {
+                    jetstream: async_nats::jetstream::new(client.clone()),
+                    buckets: Arc::default(),
+                },
+            };
+        }
+        let _ = messaging;
+        Self::in_memory()
+    }
+
+    /// The value of a key, if it has one that hasn't expired
+    ///
+    /// `ttl` is the one the key is written with, which picks its bucket.
+    #[cfg_attr(not(feature = "nats"), allow(unused_variables))]
+    pub async fn get(&self, key: &str, ttl: Duration) -> Result<Option<Vec<u8>>> {
+        match &self.backend {
+            Backend::Memory(values) => {
+                let mut values = values.lock().unwrap_or_else(|e| e.into_inner());
+                match values.get(key) {
+                    Some((_, expires)) if *expires <= Instant::now() => {
+                        values.remove(key);
+                        Ok(None)
+                    }
+                    Some((value, _)) => Ok(Some(value.clone())),
+                    None => Ok(None),
+                }
+            }
+            #[cfg(feature = "nats")]
+            Backend::Nats { .. } => {
+                let bucket = self.bucket(ttl).await?;
+                let value = bucket
+                    .get(bucket_key(key))
+                    .await
+                    .map_err(|e| RuntimeError::Messaging(message!("Failed to read state {}: {}", key, e)))?;
+                Ok(value.map(|value| value.to_vec()))
+            }
+        }
+    }
+
+    /// Set the value of a key, which expires `ttl` from now
+    pub async fn put(&self, key: &str, value: &[u8], ttl: Duration) -> Result<()> {
+        if ttl.is_zero() {
+            return Err(RuntimeError::Config(message!("State {} needs a TTL", key)));
+        }
+        match &self.backend {
+            Backend::Memory(values) => {
+                let mut values = values.lock().unwrap_or_else(|e| e.into_inner());
+                // Expired values are dropped as new ones arrive
+                let now = Instant::now();
+                values.retain(|_, (_, expires)| *expires > now);
+                values.insert(key.to_string(), (value.to_vec(), now + ttl));
+                Ok(())
+            }
+            #[cfg(feature = "nats")]
+            Backend::Nats { .. } => {
+                let bucket = self.bucket(ttl).await?;
+                bucket
+                    .put(bucket_key(key), value.to_vec().into())
+                    .await
+                    .map_err(|e| RuntimeError::Messaging(message!("Failed to write state {}: {}", key, e)))?;
+                Ok(())
+            }
+        }
+    }
+
+    /// The bucket of a TTL, created if it doesn't exist yet
+    #[cfg(feature = "nats")]
+    async fn bucket(&self, ttl: Duration) -> Result<async_nats::jetstream::kv::Store> {
+        let Backend::Nats { jetstream, buckets } = &self.backend else {
+            return Err(RuntimeError::Messaging(message!("State is not kept in NATS")));
+        };
+        let mut buckets = buckets.lock().await;
+        if let Some(bucket) = buckets.get(&ttl.as_secs()) {
+            return Ok(bucket.clone());
+        }
+        let name = bucket_name(ttl);
+        let bucket = match jetstream.get_key_value(&name).await {
+            Ok(bucket) => bucket,
+            Err(_) => jetstream
+                .create_key_value(async_nats::jetstream::kv::Config {
+                    bucket: name.clone(),
+                    max_age: ttl,
+                    history: 1,
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| RuntimeError::Messaging(message!("Failed to create state bucket {}: {}", name, e)))?,
+        };
+        buckets.insert(ttl.as_secs(), bucket.clone());
+        Ok(bucket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_values_expire_after_their_ttl() {
+        let state = Manager::in_memory();
+        let ttl = Duration::from_millis(50);
+        state.put("memory.chat.reply.42", b"[]", ttl).await.unwrap();
+        assert_eq!(state.get("memory.chat.reply.42", ttl).await.unwrap(), Some(b"[]".to_vec()));
+        assert_eq!(state.get("memory.chat.reply.7", ttl).await.unwrap(), None);
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert_eq!(state.get("memory.chat.reply.42", ttl).await.unwrap(), None);
+        assert!(state.put("memory.chat.reply.42", b"[]", Duration::ZERO).await.is_err());
+    }
+
+    #[test]
+    fn test_bucket_keys_are_valid() {
+        assert_eq!(bucket_name(Duration::from_secs(86_400)), "KUMEO_STATE_86400S");
+        let key = bucket_key("memory.chat.reply.user@example.com/1");
+        assert!(key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    }
+}
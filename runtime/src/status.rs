@@ -0,0 +1,63 @@
+//! Status reports of the runtime
+//!
+//! Every [`REPORT_INTERVAL`] the runtime of an agent publishes what its
+//! resource cache holds on the workflow's cache subject, next to the
+//! registration and drift subjects, so operator consoles can show the state
+//! of every replica without reaching into the pods.
+
+use crate::history::AGENT_ENV;
+use crate::messaging::Manager as MessagingManager;
+use crate::resources::{CacheReport, Manager as ResourceManager};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How often the cache is reported
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Variable naming the replica, set by Kubernetes to the pod name
+const INSTANCE_ENV: &str = "HOSTNAME";
+
+/// Subject the cache reports of a workflow are published on
+pub fn cache_subject(workflow: &str) -> String {
+    format!("kumeo.control.{}.cache", workflow)
+}
+
+/// Cache report of one replica of an agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStatus {
+    /// Workflow the agent belongs to
+    pub workflow: String,
+    /// Identifier of the agent, if the deployment names it
+    pub agent_id: Option<String>,
+    /// Replica the report comes from, if known
+    pub instance: Option<String>,
+    /// Milliseconds since the Unix epoch when the report was taken
+    pub time: u64,
+    /// What the cache holds
+    pub cache: CacheReport,
+}
+
+/// Publish the cache of `resources` every [`REPORT_INTERVAL`], forever
+///
+/// Failed publications are logged and retried on the next report.
+pub async fn report_cache(resources: ResourceManager, messaging: MessagingManager, workflow: String) {
+    let subject = cache_subject(&workflow);
+    let mut interval = tokio::time::interval(REPORT_INTERVAL);
+    loop {
+        interval.tick().await;
+        let status = CacheStatus {
+            workflow: workflow.clone(),
+            agent_id: std::env::var(AGENT_ENV).ok(),
+            instance: std::env::var(INSTANCE_ENV).ok(),
+            time: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis() as u64),
+            cache: resources.cache_report().await,
+        };
+        let published = match serde_json::to_vec(&status) {
+            Ok(payload) => messaging.publish(&subject, &payload, None).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = published {
+            tracing::warn!("Failed to report the resource cache on {}: {}", subject, e);
+        }
+    }
+}
@@ -0,0 +1,7 @@
+//! Integration tests for the Kumeo runtime
+//!
+//! Se ejecutan con `cargo test --features integration`, que arranca un
+//! servidor NATS en Docker; sin Docker fallan en lugar de omitirse.
+
+#[cfg(feature = "integration")]
+mod nats;
@@ -0,0 +1,161 @@
+use futures::StreamExt;
+use kumeo_runtime::messaging::{Manager, SubscriptionConfig, PRIORITY_HEADER};
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+use super::{eventually, Collector, NatsServer};
+
+fn subscription(subject: &str, queue_group: Option<&str>) -> SubscriptionConfig {
+    SubscriptionConfig {
+        subject: subject.to_string(),
+        queue_group: queue_group.map(str::to_string),
+        timeout: None,
+    }
+}
+
+/// Espera a que el servidor haya procesado las suscripciones del gestor
+async fn flush(manager: &Manager) {
+    let client = manager.nats_client().expect("Se esperaba un cliente de NATS");
+    client.flush().await.expect("No se pudo vaciar la conexión");
+}
+
+#[tokio::test]
+async fn test_publish_and_subscribe() {
+    let server = NatsServer::start().await;
+    let manager = server.manager().await;
+    let collector = Collector::default();
+    manager.subscribe(subscription("orders.created", None), collector.clone()).await.unwrap();
+    flush(&manager).await;
+
+    let headers = HashMap::from([(PRIORITY_HEADER.to_string(), "5".to_string())]);
+    manager.publish("orders.created", b"{\"id\": 1}", Some(headers)).await.unwrap();
+    manager.publish("orders.cancelled", b"{\"id\": 2}", None).await.unwrap();
+
+    let received = collector.wait_for(1).await;
+    assert_eq!(received[0].subject, "orders.created");
+    assert_eq!(received[0].payload, b"{\"id\": 1}");
+    assert_eq!(received[0].headers.get(PRIORITY_HEADER).map(String::as_str), Some("5"));
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(collector.received().await.len(), 1, "Solo debería llegar el mensaje del subject suscrito");
+}
+
+#[tokio::test]
+async fn test_channel_prefix_applies_to_both_ends() {
+    let server = NatsServer::start().await;
+    let manager = server.manager_with(|config| config.channel_prefix = Some("staging.".to_string())).await;
+    let collector = Collector::default();
+    manager.subscribe(subscription("orders", None), collector.clone()).await.unwrap();
+
+    // Un cliente sin prefijo ve el subject completo
+    let observer = server.manager().await;
+    let raw = Collector::default();
+    observer.subscribe(subscription("staging.orders", None), raw.clone()).await.unwrap();
+    flush(&manager).await;
+    flush(&observer).await;
+
+    manager.publish("orders", b"hola", None).await.unwrap();
+    assert_eq!(collector.wait_for(1).await[0].subject, "staging.orders");
+    assert_eq!(raw.wait_for(1).await[0].payload, b"hola");
+}
+
+#[tokio::test]
+async fn test_queue_groups_deliver_each_message_once() {
+    let server = NatsServer::start().await;
+    let workers = [Collector::default(), Collector::default()];
+    let mut managers = Vec::new();
+    for worker in &workers {
+        let manager = server.manager().await;
+        manager.subscribe(subscription("jobs", Some("workers")), worker.clone()).await.unwrap();
+        flush(&manager).await;
+        managers.push(manager);
+    }
+    // Fuera del grupo se reciben todos los mensajes
+    let auditor = Collector::default();
+    managers[0].subscribe(subscription("jobs", None), auditor.clone()).await.unwrap();
+    flush(&managers[0]).await;
+
+    let publisher = server.manager().await;
+    for index in 0..20 {
+        publisher.publish("jobs", index.to_string().as_bytes(), None).await.unwrap();
+    }
+
+    assert_eq!(auditor.wait_for(20).await.len(), 20);
+    let delivered = eventually(|| async {
+        let mut delivered = workers[0].received().await;
+        delivered.extend(workers[1].received().await);
+        (delivered.len() >= 20).then_some(delivered)
+    })
+    .await
+    .expect("El grupo no recibió todos los mensajes");
+    let payloads: BTreeSet<Vec<u8>> = delivered.iter().map(|message| message.payload.clone()).collect();
+    assert_eq!(delivered.len(), 20, "Cada mensaje debería llegar a un solo miembro del grupo");
+    assert_eq!(payloads.len(), 20);
+}
+
+#[tokio::test]
+async fn test_request_reply() {
+    let server = NatsServer::start().await;
+    let manager = server.manager().await;
+    let client = manager.nats_client().expect("Se esperaba un cliente de NATS").clone();
+
+    let mut requests = client.subscribe("scores.lookup").await.unwrap();
+    let responder = client.clone();
+    tokio::spawn(async move {
+        while let Some(request) = requests.next().await {
+            if let Some(reply) = request.reply {
+                let mut answer = b"score:".to_vec();
+                answer.extend_from_slice(&request.payload);
+                responder.publish(reply, answer.into()).await.unwrap();
+            }
+        }
+    });
+    client.flush().await.unwrap();
+
+    let response = client.request("scores.lookup", "42".into()).await.unwrap();
+    assert_eq!(response.payload.as_ref(), b"score:42");
+}
+
+#[tokio::test]
+async fn test_handlers_do_not_ack_plain_requests() {
+    let server = NatsServer::start().await;
+    let manager = server.manager().await;
+    let collector = Collector::default();
+    manager.subscribe(subscription("audit", None), collector.clone()).await.unwrap();
+    flush(&manager).await;
+
+    // Las respuestas de JetStream no deben llegar a un inbox de request/reply
+    let requester = server.manager().await;
+    let client = requester.nats_client().expect("Se esperaba un cliente de NATS");
+    let inbox = client.new_inbox();
+    let mut replies = client.subscribe(inbox.clone()).await.unwrap();
+    client.publish_with_reply("audit", inbox, "entrada".into()).await.unwrap();
+
+    collector.wait_for(1).await;
+    let reply = tokio::time::timeout(Duration::from_millis(500), replies.next()).await;
+    assert!(reply.is_err(), "No se esperaba respuesta, llegó {:?}", reply);
+}
+
+#[tokio::test]
+async fn test_subscriptions_survive_a_server_restart() {
+    let server = NatsServer::start().await;
+    let manager = server.manager().await;
+    let collector = Collector::default();
+    manager.subscribe(subscription("events", None), collector.clone()).await.unwrap();
+    flush(&manager).await;
+
+    manager.publish("events", b"antes", None).await.unwrap();
+    collector.wait_for(1).await;
+
+    server.restart().await;
+
+    // El cliente se reconecta solo y vuelve a suscribirse; hasta entonces se reintenta
+    let received = eventually(|| async {
+        let _ = manager.publish("events", b"despues", None).await;
+        let received = collector.received().await;
+        received.iter().any(|message| message.payload == b"despues").then_some(received)
+    })
+    .await
+    .expect("No se recibieron mensajes tras reiniciar NATS");
+    assert_eq!(received[0].payload, b"antes");
+}
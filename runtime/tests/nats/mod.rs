@@ -0,0 +1,135 @@
+//! Harness de los tests contra un servidor NATS real
+//!
+//! Cada test arranca su propio contenedor de NATS con testcontainers, en un
+//! puerto fijo del host para que los clientes puedan reconectarse cuando se
+//! reinicia.
+
+mod messaging_tests;
+
+use async_trait::async_trait;
+use kumeo_runtime::config::MessagingConfig;
+use kumeo_runtime::messaging::{Manager, MessageHandler};
+use kumeo_runtime::Result;
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
+use testcontainers::core::IntoContainerPort;
+use testcontainers::runners::AsyncRunner;
+use testcontainers::{ContainerAsync, ImageExt};
+use testcontainers_modules::nats::Nats;
+use tokio::sync::Mutex;
+
+/// Puerto de clientes de NATS dentro del contenedor
+const CLIENT_PORT: u16 = 4222;
+
+/// Tiempo máximo que se espera a que algo ocurra
+pub const PATIENCE: Duration = Duration::from_secs(30);
+
+/// Un servidor NATS en Docker, parado al salir del test
+pub struct NatsServer {
+    container: ContainerAsync<Nats>,
+    port: u16,
+}
+
+impl NatsServer {
+    /// Arranca un servidor en un puerto libre del host
+    pub async fn start() -> Self {
+        let port = free_port();
+        let container = Nats::default()
+            .with_mapped_port(port, CLIENT_PORT.tcp())
+            .start()
+            .await
+            .expect("No se pudo arrancar NATS en Docker");
+        Self { container, port }
+    }
+
+    /// URL de clientes del servidor
+    pub fn url(&self) -> String {
+        format!("nats://127.0.0.1:{}", self.port)
+    }
+
+    /// Un gestor de mensajería conectado al servidor
+    pub async fn manager(&self) -> Manager {
+        self.manager_with(|_| {}).await
+    }
+
+    /// Un gestor de mensajería conectado al servidor, con su configuración ajustada
+    pub async fn manager_with(&self, configure: impl FnOnce(&mut MessagingConfig)) -> Manager {
+        let mut config = MessagingConfig::new(self.url()).expect("Configuración de mensajería inválida");
+        configure(&mut config);
+        Manager::new(&config).await.expect("No se pudo conectar a NATS")
+    }
+
+    /// Para el servidor y lo vuelve a arrancar en el mismo puerto
+    pub async fn restart(&self) {
+        self.container.stop().await.expect("No se pudo parar NATS");
+        self.container.start().await.expect("No se pudo volver a arrancar NATS");
+    }
+}
+
+/// Un puerto del host libre en este momento
+fn free_port() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("No hay puertos libres");
+    listener.local_addr().expect("Puerto sin dirección").port()
+}
+
+/// Un mensaje recibido por un handler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Received {
+    pub subject: String,
+    pub payload: Vec<u8>,
+    pub headers: HashMap<String, String>,
+}
+
+/// Handler que guarda los mensajes que recibe
+#[derive(Clone, Default)]
+pub struct Collector {
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl Collector {
+    /// Los mensajes recibidos hasta ahora
+    pub async fn received(&self) -> Vec<Received> {
+        self.received.lock().await.clone()
+    }
+
+    /// Espera a haber recibido al menos `count` mensajes
+    pub async fn wait_for(&self, count: usize) -> Vec<Received> {
+        eventually(|| async {
+            let received = self.received().await;
+            (received.len() >= count).then_some(received)
+        })
+        .await
+        .unwrap_or_else(|| panic!("No llegaron {} mensajes en {:?}", count, PATIENCE))
+    }
+}
+
+#[async_trait]
+impl MessageHandler for Collector {
+    async fn handle_message(&self, subject: &str, payload: &[u8], headers: Option<&HashMap<String, String>>) -> Result<()> {
+        self.received.lock().await.push(Received {
+            subject: subject.to_string(),
+            payload: payload.to_vec(),
+            headers: headers.cloned().unwrap_or_default(),
+        });
+        Ok(())
+    }
+}
+
+/// Repite una comprobación hasta que da un valor o se acaba la paciencia
+pub async fn eventually<T, F, Fut>(mut check: F) -> Option<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Option<T>>,
+{
+    let deadline = tokio::time::Instant::now() + PATIENCE;
+    while tokio::time::Instant::now() < deadline {
+        if let Some(value) = check().await {
+            return Some(value);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    None
+}